@@ -1,7 +1,46 @@
-use std::{env, net::IpAddr, path::PathBuf};
+use std::{
+    collections::HashMap,
+    env,
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
+use amf_formats::limits::{
+    DEFAULT_MAX_DECODED_BYTES, DEFAULT_MAX_DEPTH, DEFAULT_MAX_ELEMENTS, DEFAULT_MAX_STRING_LENGTH,
+};
 use config::{Config, ConfigError, Environment, File};
+use rtmp_server::config::{
+    DEFAULT_FORWARD_AUDIO_FOUR_CC, DEFAULT_FORWARD_VIDEO_FOUR_CC, DEFAULT_PEER_BANDWIDTH,
+    DEFAULT_WINDOW_ACK_SIZE, RtmpAppConfig, RtmpServerConfig,
+};
+use rtp_session::fec::FecConfig;
+use rtsp_server::{
+    middleware::load_shedding::{LoadShedder, LoadThreshold, RoundRobinRedirect},
+    multicast::MulticastGroup,
+};
 use serde::Deserialize;
+use server_utils::{egress_shaping::EgressShapingConfig, ingest_limit::IngestLimitConfig};
+use stream_center::{
+    audio_continuity::AudioGapConcealment,
+    backpressure::BackpressurePolicy,
+    dvr::DvrWindow,
+    gop_budget::{DEFAULT_GOP_CACHE_BUDGET_BYTES, GopCacheBudgetConfig},
+    identity::StreamIdentity,
+    latency::{DEFAULT_LATENCY_WINDOW, LatencyConfig},
+    recovery_point::RecoveryPointJoin,
+    rtmp_control::PeerBandwidthLimitType,
+    slice_integrity::SliceIntegrity,
+    takeover::TakeoverPolicy,
+    timestamp_normalization::TimestampNormalization,
+    trace::DEFAULT_TRACE_CAPACITY,
+    watchdog::IdleWatchdog,
+};
+use unified_io::{
+    proxy_protocol::{IpCidr, TrustedProxies},
+    tls::{TlsListenerConfig, TlsServerConfig},
+};
 
 use crate::{
     AppCli,
@@ -14,6 +53,34 @@ use crate::{
 pub(crate) struct Logger {
     pub(crate) level: String,
     pub(crate) dir: PathBuf,
+    /// one json object per line, for log collectors parsing structured logs
+    #[serde(default)]
+    pub(crate) json: bool,
+}
+
+/// a tls listener besides the plain one, e.g., rtmps or rtsps
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub(crate) struct TlsListener {
+    pub(crate) enable: bool,
+    pub(crate) port: u16,
+    pub(crate) cert_chain_path: PathBuf,
+    pub(crate) private_key_path: PathBuf,
+}
+
+impl TlsListener {
+    pub(crate) fn to_listener_config(&self) -> Option<TlsListenerConfig> {
+        if !self.enable {
+            return None;
+        }
+        Some(TlsListenerConfig {
+            port: self.port,
+            tls: TlsServerConfig {
+                cert_chain_path: self.cert_chain_path.clone(),
+                private_key_path: self.private_key_path.clone(),
+            },
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,8 +90,32 @@ pub(crate) struct RtmpServer {
     pub(crate) address: IpAddr,
     pub(crate) port: u16,
     pub(crate) chunk_size: u32,
+    /// sent in WindowAckSize and SetPeerBandwidth once a client connects, 4096 and dynamic if absent
+    #[serde(default)]
+    pub(crate) window_ack_size: Option<u32>,
+    #[serde(default)]
+    pub(crate) peer_bandwidth: Option<u32>,
+    #[serde(default)]
+    pub(crate) peer_bandwidth_limit_type: Option<PeerBandwidthLimitType>,
     pub(crate) write_timeout_ms: u64,
     pub(crate) read_timeout_ms: u64,
+    /// bound the amf values of the commands clients send, the defaults of amf_formats if absent
+    #[serde(default)]
+    pub(crate) amf_max_depth: Option<usize>,
+    #[serde(default)]
+    pub(crate) amf_max_decoded_bytes: Option<usize>,
+    #[serde(default)]
+    pub(crate) amf_max_string_length: Option<usize>,
+    #[serde(default)]
+    pub(crate) amf_max_elements: Option<usize>,
+    /// comma separated FourCCs, like avc1,hvc1
+    #[serde(default)]
+    pub(crate) forward_video_four_cc: Option<String>,
+    #[serde(default)]
+    pub(crate) forward_audio_four_cc: Option<String>,
+    /// call onBWDone on the clients after the connect response, for legacy encoders, true if absent
+    #[serde(default)]
+    pub(crate) send_on_bw_done: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -34,6 +125,12 @@ pub(crate) struct HttpServer {
     pub(crate) address: IpAddr,
     pub(crate) port: u16,
     pub(crate) workers: u64,
+    /// files under this directory can be played by the vod api
+    #[serde(default)]
+    pub(crate) vod_dir: Option<PathBuf>,
+    /// the packet captures of rtp sessions started by the admin api are written here
+    #[serde(default)]
+    pub(crate) capture_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -42,6 +139,261 @@ pub(crate) struct RtspServer {
     pub(crate) enable: bool,
     pub(crate) address: IpAddr,
     pub(crate) port: u16,
+    /// sent packets kept per play session to answer nacks
+    #[serde(default)]
+    pub(crate) nack_history_packets: Option<usize>,
+    #[serde(default)]
+    pub(crate) nack_history_bytes: Option<usize>,
+    /// offer rtx streams in DESCRIBE
+    #[serde(default)]
+    pub(crate) offer_rtx: bool,
+    /// spread the packets of play sessions by the timestamps of their frames, on if absent
+    #[serde(default)]
+    pub(crate) pacing: Option<bool>,
+    /// bytes sent at once when a play starts, before the pacing
+    #[serde(default)]
+    pub(crate) pacing_burst_bytes: Option<usize>,
+    /// how much faster than real time a play catches up with live
+    #[serde(default)]
+    pub(crate) pacing_catch_up_speed: Option<f64>,
+    /// byte budgets of the buffers reassembling published h264 streams
+    #[serde(default)]
+    pub(crate) h264_fragments_buffer_bytes: Option<usize>,
+    #[serde(default)]
+    pub(crate) h264_de_interleaving_buffer_bytes: Option<usize>,
+    #[serde(default)]
+    pub(crate) h264_decoder_buffer_bytes: Option<usize>,
+    /// evict non-idr nal units first when a budget is exceeded
+    #[serde(default)]
+    pub(crate) h264_buffer_retain_idr: Option<bool>,
+    /// pass the access unit delimiters of published h264 on
+    #[serde(default)]
+    pub(crate) h264_access_unit_delimiters: Option<bool>,
+    /// frames held back to derive the dts of published h264 with b-frames, when the sps does not tell how many
+    #[serde(default)]
+    pub(crate) h264_reorder_frames: Option<usize>,
+    /// accept clients requiring the onvif backchannel
+    #[serde(default)]
+    pub(crate) onvif_backchannel: bool,
+    /// describe the medias as RTP/SAVP with a=crypto keys to rtsps clients, built with the srtp feature
+    #[serde(default)]
+    pub(crate) srtp: bool,
+    /// log each request with its status and latency
+    #[serde(default)]
+    pub(crate) log_requests: bool,
+    /// connections of an ip beyond this many are answered with 503
+    #[serde(default)]
+    pub(crate) max_sessions_per_ip: Option<usize>,
+    /// players are redirected to the redirect targets once the server has this many connections
+    #[serde(default)]
+    pub(crate) redirect_max_sessions: Option<usize>,
+    /// comma separated `host[:port]` of the sibling servers, taken in turn
+    #[serde(default)]
+    pub(crate) redirect_targets: Option<String>,
+    /// the interface streams are multicast on, the routing table decides if absent
+    #[serde(default)]
+    pub(crate) multicast_interface: Option<Ipv4Addr>,
+    /// whether the receivers on this host get the multicast packets
+    #[serde(default)]
+    pub(crate) multicast_loopback: bool,
+    /// the sdes items sent in rtcp besides the cname, the tool is the server agent if absent
+    #[serde(default)]
+    pub(crate) sdes_tool: Option<String>,
+    #[serde(default)]
+    pub(crate) sdes_name: Option<String>,
+    #[serde(default)]
+    pub(crate) sdes_email: Option<String>,
+    /// keeps the secret the rtcp cnames are derived from, they change on restart if absent
+    #[serde(default)]
+    pub(crate) data_dir: Option<PathBuf>,
+    /// how long DESCRIBE waits for the sequence headers of a stream, 3 seconds if absent
+    #[serde(default)]
+    pub(crate) describe_wait_ms: Option<u64>,
+    /// answer the medias known so far once DESCRIBE waited, 453 otherwise
+    #[serde(default)]
+    pub(crate) describe_minimal_sdp: bool,
+    /// requests with a longer body are answered with 413, 64 KiB if absent
+    #[serde(default)]
+    pub(crate) max_body_bytes: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub(crate) struct SrtServer {
+    pub(crate) enable: bool,
+    pub(crate) address: IpAddr,
+    pub(crate) port: u16,
+    pub(crate) latency_ms: u64,
+    /// callers must present the same passphrase, no encryption if not set
+    #[serde(default)]
+    pub(crate) passphrase: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+#[allow(unused)]
+pub(crate) struct IngestLimit {
+    pub(crate) max_rtmp_message_size: usize,
+    pub(crate) max_rtp_access_unit_size: usize,
+    /// 0 means unlimited
+    pub(crate) per_stream_bitrate_kbps: u64,
+    /// 0 means unlimited
+    pub(crate) global_bitrate_kbps: u64,
+    pub(crate) burst_ms: u64,
+}
+
+impl Default for IngestLimit {
+    fn default() -> Self {
+        let config = IngestLimitConfig::default();
+        Self {
+            max_rtmp_message_size: config.max_rtmp_message_size,
+            max_rtp_access_unit_size: config.max_rtp_access_unit_size,
+            per_stream_bitrate_kbps: config.per_stream_bitrate_kbps.unwrap_or_default(),
+            global_bitrate_kbps: config.global_bitrate_kbps.unwrap_or_default(),
+            burst_ms: config.burst_ms,
+        }
+    }
+}
+
+impl From<&IngestLimit> for IngestLimitConfig {
+    fn from(value: &IngestLimit) -> Self {
+        Self {
+            max_rtmp_message_size: value.max_rtmp_message_size,
+            max_rtp_access_unit_size: value.max_rtp_access_unit_size,
+            per_stream_bitrate_kbps: Some(value.per_stream_bitrate_kbps).filter(|v| *v > 0),
+            global_bitrate_kbps: Some(value.global_bitrate_kbps).filter(|v| *v > 0),
+            burst_ms: value.burst_ms,
+        }
+    }
+}
+
+/// bitrate caps of the players
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+#[allow(unused)]
+pub(crate) struct EgressShaping {
+    /// 0 means unlimited, players may ask for less with the max_bitrate_kbps param
+    pub(crate) per_subscriber_kbps: u64,
+    /// 0 means unlimited
+    pub(crate) per_stream_kbps: u64,
+    /// 0 means unlimited
+    pub(crate) global_kbps: u64,
+    pub(crate) burst_ms: u64,
+}
+
+impl Default for EgressShaping {
+    fn default() -> Self {
+        let config = EgressShapingConfig::default();
+        Self {
+            per_subscriber_kbps: config.per_subscriber_kbps.unwrap_or_default(),
+            per_stream_kbps: config.per_stream_kbps.unwrap_or_default(),
+            global_kbps: config.global_kbps.unwrap_or_default(),
+            burst_ms: config.burst_ms,
+        }
+    }
+}
+
+impl From<&EgressShaping> for EgressShapingConfig {
+    fn from(value: &EgressShaping) -> Self {
+        Self {
+            per_subscriber_kbps: Some(value.per_subscriber_kbps).filter(|v| *v > 0),
+            per_stream_kbps: Some(value.per_stream_kbps).filter(|v| *v > 0),
+            global_kbps: Some(value.global_kbps).filter(|v| *v > 0),
+            burst_ms: value.burst_ms,
+        }
+    }
+}
+
+/// per stream ring buffers of pipeline events, served by the http api
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+#[allow(unused)]
+pub(crate) struct PipelineTrace {
+    pub(crate) enable: bool,
+    /// events kept per stream
+    pub(crate) capacity: usize,
+}
+
+impl Default for PipelineTrace {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            capacity: DEFAULT_TRACE_CAPACITY,
+        }
+    }
+}
+
+/// the stream center state kept across graceful restarts
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+#[allow(unused)]
+pub(crate) struct Snapshot {
+    /// saved to on shutdown and restored from at startup, nothing is kept if absent
+    pub(crate) data_dir: Option<PathBuf>,
+}
+
+/// ingest to sink latency of each stream, served by the http api
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+#[allow(unused)]
+pub(crate) struct LatencyMeasurement {
+    pub(crate) enable: bool,
+    /// the percentiles are computed over this window
+    pub(crate) window_ms: u64,
+}
+
+impl Default for LatencyMeasurement {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            window_ms: DEFAULT_LATENCY_WINDOW.as_millis() as u64,
+        }
+    }
+}
+
+impl From<&LatencyMeasurement> for LatencyConfig {
+    fn from(value: &LatencyMeasurement) -> Self {
+        Self {
+            window: Duration::from_millis(value.window_ms),
+        }
+    }
+}
+
+/// the memory the gop caches of all the streams share
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+#[allow(unused)]
+pub(crate) struct GopCache {
+    /// 0 means unlimited, the largest caches give up their oldest gops first once over it
+    pub(crate) max_bytes: u64,
+    /// the latest gops each stream keeps whatever the budget, one at least
+    pub(crate) min_gops: usize,
+}
+
+impl Default for GopCache {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_GOP_CACHE_BUDGET_BYTES,
+            min_gops: 1,
+        }
+    }
+}
+
+impl From<&GopCache> for GopCacheBudgetConfig {
+    fn from(value: &GopCache) -> Self {
+        Self {
+            max_bytes: value.max_bytes,
+            min_gops: value.min_gops.max(1),
+        }
+    }
+}
+
+/// payload crc of the frames checked between the stages, takes a build with the frame-crc feature
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+#[allow(unused)]
+pub(crate) struct FrameCrcValidation {
+    pub(crate) enable: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -51,6 +403,75 @@ pub(crate) struct AppConfig {
     pub(crate) rtmp_server: RtmpServer,
     pub(crate) http_server: HttpServer,
     pub(crate) rtsp_server: RtspServer,
+    /// tls listener of the rtmp server
+    #[serde(default)]
+    pub(crate) rtmps: Option<TlsListener>,
+    /// tls listener of the rtsp server
+    #[serde(default)]
+    pub(crate) rtsps: Option<TlsListener>,
+    #[serde(default)]
+    pub(crate) srt_server: Option<SrtServer>,
+    #[serde(default)]
+    pub(crate) ingest_limit: IngestLimit,
+    #[serde(default)]
+    pub(crate) egress_shaping: EgressShaping,
+    #[serde(default)]
+    pub(crate) pipeline_trace: PipelineTrace,
+    #[serde(default)]
+    pub(crate) latency_measurement: LatencyMeasurement,
+    #[serde(default)]
+    pub(crate) frame_crc_validation: FrameCrcValidation,
+    #[serde(default)]
+    pub(crate) gop_cache: GopCache,
+    #[serde(default)]
+    pub(crate) snapshot: Snapshot,
+    /// app name to takeover policy, the `default` key applies to the other apps
+    #[serde(default)]
+    pub(crate) publish_takeover: HashMap<String, String>,
+    /// app name to idle watchdog, the `default` key applies to the other apps
+    #[serde(default)]
+    pub(crate) publish_idle_watchdog: HashMap<String, String>,
+    /// app name to recovery point join, the `default` key applies to the other apps
+    #[serde(default)]
+    pub(crate) recovery_point_join: HashMap<String, String>,
+    /// app name to how the holes in the audio of its streams are concealed, `off`, `silence` or `stretch`,
+    /// the `default` key applies to the other apps
+    #[serde(default)]
+    pub(crate) audio_gap_concealment: HashMap<String, String>,
+    /// app name to how the timestamps of its publishers are mapped, `off`, `wallclock`
+    /// or `wallclock:<max slew ms per second>:<discontinuity threshold ms>`,
+    /// the `default` key applies to the other apps
+    #[serde(default)]
+    pub(crate) timestamp_normalization: HashMap<String, String>,
+    /// app name to what is done with the h264 access units of its streams missing slices,
+    /// `off`, `tag` or `drop`, the `default` key applies to the other apps
+    #[serde(default)]
+    pub(crate) slice_integrity: HashMap<String, String>,
+    /// app name to the dvr window its streams keep for timeshift playback, `off`, `<seconds>`
+    /// or `<seconds>:<max bytes>`, the `default` key applies to the other apps
+    #[serde(default)]
+    pub(crate) dvr_window: HashMap<String, String>,
+    /// app name to when its publishers are advised to lower their bitrate, `off` or
+    /// `<raise score>:<clear score>:<min interval seconds>`, the `default` key applies to the other apps
+    #[serde(default)]
+    pub(crate) backpressure: HashMap<String, String>,
+    /// app name to the rtmp protocol control settings overriding the ones of the rtmp server
+    #[serde(default)]
+    pub(crate) rtmp_apps: HashMap<String, String>,
+    /// `app/stream` to the multicast group rtsp clients may play it from
+    #[serde(default)]
+    pub(crate) rtsp_multicast: HashMap<String, String>,
+    /// `app/stream` to the protection of the packets its rtsp players get
+    #[serde(default)]
+    pub(crate) rtsp_fec: HashMap<String, String>,
+    /// `app/stream` of an alias to the one of the stream it plays
+    #[serde(default)]
+    pub(crate) stream_alias: HashMap<String, String>,
+    /// networks of the load balancers in front of the servers, e.g. `10.0.0.0/8`.
+    /// they send a proxy protocol header ahead of rtmp and rtsp connections,
+    /// and X-Real-IP or X-Forwarded-For along with http requests
+    #[serde(default)]
+    pub(crate) trusted_proxies: Vec<String>,
 }
 
 impl AppConfig {
@@ -90,6 +511,305 @@ impl AppConfig {
         Ok(())
     }
 
+    pub(crate) fn rtmp_server_config(&self) -> AppResult<RtmpServerConfig> {
+        let apps = self
+            .rtmp_apps
+            .iter()
+            .map(|(app, config)| {
+                let config = config.parse::<RtmpAppConfig>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the rtmp config of app {} is invalid: {}",
+                        app, err
+                    )))
+                })?;
+                Ok((app.clone(), config))
+            })
+            .collect::<AppResult<_>>()?;
+        Ok(RtmpServerConfig {
+            address: self.rtmp_server.address,
+            port: self.rtmp_server.port,
+            chunk_size: self.rtmp_server.chunk_size,
+            window_ack_size: self
+                .rtmp_server
+                .window_ack_size
+                .unwrap_or(DEFAULT_WINDOW_ACK_SIZE),
+            peer_bandwidth: self
+                .rtmp_server
+                .peer_bandwidth
+                .unwrap_or(DEFAULT_PEER_BANDWIDTH),
+            peer_bandwidth_limit_type: self
+                .rtmp_server
+                .peer_bandwidth_limit_type
+                .unwrap_or(PeerBandwidthLimitType::Dynamic),
+            apps,
+            write_timeout_ms: self.rtmp_server.write_timeout_ms,
+            read_timeout_ms: self.rtmp_server.read_timeout_ms,
+            amf_max_depth: self.rtmp_server.amf_max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+            amf_max_decoded_bytes: self
+                .rtmp_server
+                .amf_max_decoded_bytes
+                .unwrap_or(DEFAULT_MAX_DECODED_BYTES),
+            amf_max_string_length: self
+                .rtmp_server
+                .amf_max_string_length
+                .unwrap_or(DEFAULT_MAX_STRING_LENGTH),
+            amf_max_elements: self
+                .rtmp_server
+                .amf_max_elements
+                .unwrap_or(DEFAULT_MAX_ELEMENTS),
+            rtmps: self
+                .rtmps
+                .as_ref()
+                .and_then(|rtmps| rtmps.to_listener_config()),
+            forward_video_four_cc: four_cc_list(
+                self.rtmp_server.forward_video_four_cc.as_deref(),
+                &DEFAULT_FORWARD_VIDEO_FOUR_CC,
+            ),
+            forward_audio_four_cc: four_cc_list(
+                self.rtmp_server.forward_audio_four_cc.as_deref(),
+                &DEFAULT_FORWARD_AUDIO_FOUR_CC,
+            ),
+            trusted_proxies: self.trusted_proxies()?,
+            send_on_bw_done: self.rtmp_server.send_on_bw_done.unwrap_or(true),
+        })
+    }
+
+    pub(crate) fn trusted_proxies(&self) -> AppResult<TrustedProxies> {
+        self.trusted_proxies
+            .iter()
+            .map(|network| {
+                network.parse::<IpCidr>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the trusted proxy {} is invalid: {}",
+                        network, err
+                    )))
+                })
+            })
+            .collect::<AppResult<_>>()
+            .map(TrustedProxies::new)
+    }
+
+    pub(crate) fn takeover_policies(&self) -> AppResult<Vec<(String, TakeoverPolicy)>> {
+        self.publish_takeover
+            .iter()
+            .map(|(app, policy)| {
+                let policy = policy.parse::<TakeoverPolicy>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the publish takeover policy of app {} is invalid: {}",
+                        app, err
+                    )))
+                })?;
+                Ok((app.clone(), policy))
+            })
+            .collect()
+    }
+
+    pub(crate) fn idle_watchdogs(&self) -> AppResult<Vec<(String, IdleWatchdog)>> {
+        self.publish_idle_watchdog
+            .iter()
+            .map(|(app, watchdog)| {
+                let watchdog = watchdog.parse::<IdleWatchdog>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the publish idle watchdog of app {} is invalid: {}",
+                        app, err
+                    )))
+                })?;
+                Ok((app.clone(), watchdog))
+            })
+            .collect()
+    }
+
+    pub(crate) fn recovery_point_joins(&self) -> AppResult<Vec<(String, RecoveryPointJoin)>> {
+        self.recovery_point_join
+            .iter()
+            .map(|(app, join)| {
+                let join = join.parse::<RecoveryPointJoin>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the recovery point join of app {} is invalid: {}",
+                        app, err
+                    )))
+                })?;
+                Ok((app.clone(), join))
+            })
+            .collect()
+    }
+
+    pub(crate) fn audio_gap_concealments(&self) -> AppResult<Vec<(String, AudioGapConcealment)>> {
+        self.audio_gap_concealment
+            .iter()
+            .map(|(app, concealment)| {
+                let concealment = concealment.parse::<AudioGapConcealment>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the audio gap concealment of app {} is invalid: {}",
+                        app, err
+                    )))
+                })?;
+                Ok((app.clone(), concealment))
+            })
+            .collect()
+    }
+
+    pub(crate) fn timestamp_normalizations(
+        &self,
+    ) -> AppResult<Vec<(String, TimestampNormalization)>> {
+        self.timestamp_normalization
+            .iter()
+            .map(|(app, normalization)| {
+                let normalization =
+                    normalization
+                        .parse::<TimestampNormalization>()
+                        .map_err(|err| {
+                            AppError::ConfigError(ConfigError::Message(format!(
+                                "the timestamp normalization of app {} is invalid: {}",
+                                app, err
+                            )))
+                        })?;
+                Ok((app.clone(), normalization))
+            })
+            .collect()
+    }
+
+    pub(crate) fn slice_integrities(&self) -> AppResult<Vec<(String, SliceIntegrity)>> {
+        self.slice_integrity
+            .iter()
+            .map(|(app, integrity)| {
+                let integrity = integrity.parse::<SliceIntegrity>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the slice integrity of app {} is invalid: {}",
+                        app, err
+                    )))
+                })?;
+                Ok((app.clone(), integrity))
+            })
+            .collect()
+    }
+
+    pub(crate) fn dvr_windows(&self) -> AppResult<Vec<(String, DvrWindow)>> {
+        self.dvr_window
+            .iter()
+            .map(|(app, window)| {
+                let window = window.parse::<DvrWindow>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the dvr window of app {} is invalid: {}",
+                        app, err
+                    )))
+                })?;
+                Ok((app.clone(), window))
+            })
+            .collect()
+    }
+
+    pub(crate) fn backpressure_policies(&self) -> AppResult<Vec<(String, BackpressurePolicy)>> {
+        self.backpressure
+            .iter()
+            .map(|(app, policy)| {
+                let policy = policy.parse::<BackpressurePolicy>().map_err(|err| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the backpressure policy of app {} is invalid: {}",
+                        app, err
+                    )))
+                })?;
+                Ok((app.clone(), policy))
+            })
+            .collect()
+    }
+
+    pub(crate) fn rtsp_fec_configs(&self) -> AppResult<HashMap<StreamIdentity, FecConfig>> {
+        self.rtsp_fec
+            .iter()
+            .map(|(stream, fec)| {
+                let invalid = |err: String| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the rtsp fec of stream {} is invalid: {}",
+                        stream, err
+                    )))
+                };
+                let stream_id = stream
+                    .parse::<StreamIdentity>()
+                    .map_err(|err| invalid(err.to_string()))?;
+                let fec = fec
+                    .parse::<FecConfig>()
+                    .map_err(|err| invalid(err.to_string()))?;
+                Ok((stream_id, fec))
+            })
+            .collect()
+    }
+
+    pub(crate) fn rtsp_multicast_groups(
+        &self,
+    ) -> AppResult<HashMap<StreamIdentity, MulticastGroup>> {
+        self.rtsp_multicast
+            .iter()
+            .map(|(stream, group)| {
+                let invalid = |err: String| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the rtsp multicast group of stream {} is invalid: {}",
+                        stream, err
+                    )))
+                };
+                let stream_id = stream
+                    .parse::<StreamIdentity>()
+                    .map_err(|err| invalid(err.to_string()))?;
+                let mut group = group
+                    .parse::<MulticastGroup>()
+                    .map_err(|err| invalid(err.to_string()))?;
+                group.options.interface = self
+                    .rtsp_server
+                    .multicast_interface
+                    .unwrap_or(Ipv4Addr::UNSPECIFIED);
+                group.options.loopback = self.rtsp_server.multicast_loopback;
+                Ok((stream_id, group))
+            })
+            .collect()
+    }
+
+    /// None unless both the threshold and the targets are configured
+    pub(crate) fn rtsp_load_shedder(&self) -> AppResult<Option<LoadShedder>> {
+        let (Some(max_sessions), Some(targets)) = (
+            self.rtsp_server.redirect_max_sessions,
+            self.rtsp_server.redirect_targets.as_ref(),
+        ) else {
+            return Ok(None);
+        };
+        let targets: Vec<_> = targets
+            .split(',')
+            .map(|target| target.trim())
+            .filter(|target| !target.is_empty())
+            .map(|target| target.to_owned())
+            .collect();
+        if let Some(target) = targets.iter().find(|target| target.contains('/')) {
+            return Err(AppError::ConfigError(ConfigError::Message(format!(
+                "the rtsp redirect target {} is invalid, expect host[:port]",
+                target
+            ))));
+        }
+        Ok(Some(LoadShedder::new(
+            LoadThreshold::Sessions(max_sessions),
+            Arc::new(RoundRobinRedirect::new(targets)),
+        )))
+    }
+
+    pub(crate) fn stream_aliases(&self) -> AppResult<HashMap<StreamIdentity, StreamIdentity>> {
+        self.stream_alias
+            .iter()
+            .map(|(alias, stream)| {
+                let invalid = |err: String| {
+                    AppError::ConfigError(ConfigError::Message(format!(
+                        "the stream alias {} is invalid: {}",
+                        alias, err
+                    )))
+                };
+                let alias_id = alias
+                    .parse::<StreamIdentity>()
+                    .map_err(|err| invalid(err.to_string()))?;
+                let stream_id = stream
+                    .parse::<StreamIdentity>()
+                    .map_err(|err| invalid(err.to_string()))?;
+                Ok((alias_id, stream_id))
+            })
+            .collect()
+    }
+
     pub(crate) fn validate(&self) -> AppResult<()> {
         let _ = parse_log_level(&self.logger.level)?;
 
@@ -100,6 +820,64 @@ impl AppConfig {
             ))));
         }
 
+        if self.ingest_limit.max_rtmp_message_size == 0
+            || self.ingest_limit.max_rtp_access_unit_size == 0
+        {
+            return Err(AppError::ConfigError(ConfigError::Message(format!(
+                "the ingest limit of message size must not be zero: {:?}",
+                self.ingest_limit
+            ))));
+        }
+
+        if self.egress_shaping.burst_ms == 0 {
+            return Err(AppError::ConfigError(ConfigError::Message(
+                "the egress shaping burst must not be zero".to_owned(),
+            )));
+        }
+
+        if self.pipeline_trace.enable && self.pipeline_trace.capacity == 0 {
+            return Err(AppError::ConfigError(ConfigError::Message(
+                "the pipeline trace capacity must not be zero".to_owned(),
+            )));
+        }
+
+        if self.latency_measurement.enable && self.latency_measurement.window_ms == 0 {
+            return Err(AppError::ConfigError(ConfigError::Message(
+                "the latency measurement window must not be zero".to_owned(),
+            )));
+        }
+
+        let rtmp_server_config = self.rtmp_server_config()?;
+        let _ = rtmp_server_config
+            .controls()
+            .and_then(|_| rtmp_server_config.amf_limits())
+            .map_err(|err| AppError::ConfigError(ConfigError::Message(err.to_string())))?;
+        let _ = self.takeover_policies()?;
+        let _ = self.idle_watchdogs()?;
+        let _ = self.recovery_point_joins()?;
+        let _ = self.audio_gap_concealments()?;
+        let _ = self.timestamp_normalizations()?;
+        let _ = self.slice_integrities()?;
+        let _ = self.dvr_windows()?;
+        let _ = self.backpressure_policies()?;
+        let _ = self.rtsp_multicast_groups()?;
+        let _ = self.rtsp_fec_configs()?;
+        let _ = self.rtsp_load_shedder()?;
+        let _ = self.stream_aliases()?;
+        let _ = self.trusted_proxies()?;
+
         Ok(())
     }
 }
+
+fn four_cc_list(configured: Option<&str>, default: &[&str]) -> Vec<String> {
+    match configured {
+        Some(list) => list
+            .split(',')
+            .map(|four_cc| four_cc.trim())
+            .filter(|four_cc| !four_cc.is_empty())
+            .map(|four_cc| four_cc.to_owned())
+            .collect(),
+        None => default.iter().map(|four_cc| four_cc.to_string()).collect(),
+    }
+}
@@ -1,9 +1,27 @@
-use std::env;
+use std::{env, sync::Arc};
 
 use clap::Parser;
-use http_server::{config::HttpServerConfig, server::HttpServer};
-use rtsp_server::server::RtspServer;
-use stream_center::stream_center;
+use http_server::config::HttpServerConfig;
+use media_server::builder::{MediaServerBuilder, StreamCenterOptions};
+use rtp_formats::codec::h264::{
+    dts::DEFAULT_REORDER_FRAMES, packet::sequencer::budget::RtpH264BufferConfig,
+};
+use rtp_session::{
+    pacing::PacingConfig,
+    retransmission::{
+        DEFAULT_HISTORY_MAX_BYTES, DEFAULT_HISTORY_MAX_PACKETS, RetransmissionConfig,
+    },
+    sdes::SdesConfig,
+};
+use rtsp_formats::consts::common::DEFAULT_MAX_BODY_SIZE;
+use rtsp_server::{
+    SERVER_AGENT,
+    config::RtspServerConfig,
+    describe::{DEFAULT_DESCRIBE_WAIT, DescribeConfig},
+    middleware::{request_logger::RequestLogger, session_limiter::SessionLimiter},
+};
+use srt_server::config::SrtServerConfig;
+use stream_center::trace::RingBufferTracer;
 use time::macros::format_description;
 use tokio::signal;
 use tracing::{self, Dispatch};
@@ -61,8 +79,6 @@ async fn app_run(config: AppConfig) {
         .with_timer(LocalTime::new(format_description!(
             "[year]-[month]-[day] [hour]:[minute]:[second] [unix_timestamp precision:nanosecond]"
         )))
-        // Use a more compact, abbreviated log format
-        .compact()
         .with_ansi(false)
         // Display source code file paths
         .with_file(true)
@@ -73,10 +89,21 @@ async fn app_run(config: AppConfig) {
         // display the event's target (module path)
         .with_target(false)
         .with_env_filter(EnvFilter::from_env("LOG_LEVEL"))
-        .with_writer(log_writer)
-        // Build the subscriber
-        .finish();
-    tracing::dispatcher::set_global_default(Dispatch::new(subscriber)).unwrap();
+        .with_writer(log_writer);
+    let dispatch = if config.logger.json {
+        // the fields of the session and stream spans go along with every event
+        Dispatch::new(
+            subscriber
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .finish(),
+        )
+    } else {
+        // Use a more compact, abbreviated log format
+        Dispatch::new(subscriber.compact().finish())
+    };
+    tracing::dispatcher::set_global_default(dispatch).unwrap();
 
     {
         let msg = format!("yam_server is starting with config: {:?}", config);
@@ -84,93 +111,235 @@ async fn app_run(config: AppConfig) {
         println!("{}", msg);
     }
 
-    let mut stream_center = stream_center::StreamCenter::new();
-
-    if config.rtmp_server.enable {
-        let mut rtmp_server = rtmp_server::server::RtmpServer::new(
-            rtmp_server::config::RtmpServerConfig {
-                address: config.rtmp_server.address,
-                port: config.rtmp_server.port,
-                chunk_size: config.rtmp_server.chunk_size,
-                write_timeout_ms: config.rtmp_server.write_timeout_ms,
-                read_timeout_ms: config.rtmp_server.read_timeout_ms,
-            },
-            stream_center.get_event_sender(),
+    let mut stream_center_options = StreamCenterOptions::default();
+    if config.pipeline_trace.enable {
+        stream_center_options.tracer = Some(Arc::new(RingBufferTracer::new(
+            config.pipeline_trace.capacity,
+        )));
+    }
+    for (app, policy) in config.takeover_policies().unwrap() {
+        if app == "default" {
+            stream_center_options.default_takeover_policy = Some(policy);
+        } else {
+            stream_center_options.takeover_policies.insert(app, policy);
+        }
+    }
+    for (app, watchdog) in config.idle_watchdogs().unwrap() {
+        if app == "default" {
+            stream_center_options.default_idle_watchdog = Some(watchdog);
+        } else {
+            stream_center_options.idle_watchdogs.insert(app, watchdog);
+        }
+    }
+    for (app, join) in config.recovery_point_joins().unwrap() {
+        if app == "default" {
+            stream_center_options.default_recovery_point_join = Some(join);
+        } else {
+            stream_center_options.recovery_point_joins.insert(app, join);
+        }
+    }
+    for (app, concealment) in config.audio_gap_concealments().unwrap() {
+        if app == "default" {
+            stream_center_options.default_audio_gap_concealment = Some(concealment);
+        } else {
+            stream_center_options
+                .audio_gap_concealments
+                .insert(app, concealment);
+        }
+    }
+    for (app, normalization) in config.timestamp_normalizations().unwrap() {
+        if app == "default" {
+            stream_center_options.default_timestamp_normalization = Some(normalization);
+        } else {
+            stream_center_options
+                .timestamp_normalizations
+                .insert(app, normalization);
+        }
+    }
+    for (app, integrity) in config.slice_integrities().unwrap() {
+        if app == "default" {
+            stream_center_options.default_slice_integrity = Some(integrity);
+        } else {
+            stream_center_options
+                .slice_integrities
+                .insert(app, integrity);
+        }
+    }
+    for (app, window) in config.dvr_windows().unwrap() {
+        if app == "default" {
+            stream_center_options.default_dvr_window = Some(window);
+        } else {
+            stream_center_options.dvr_windows.insert(app, window);
+        }
+    }
+    for (app, policy) in config.backpressure_policies().unwrap() {
+        if app == "default" {
+            stream_center_options.default_backpressure_policy = Some(policy);
+        } else {
+            stream_center_options
+                .backpressure_policies
+                .insert(app, policy);
+        }
+    }
+    if config.latency_measurement.enable {
+        stream_center_options.latency = Some((&config.latency_measurement).into());
+    }
+    stream_center_options.aliases = config.stream_aliases().unwrap();
+    stream_center_options.gop_cache_budget = Some((&config.gop_cache).into());
+    #[cfg(feature = "frame-crc")]
+    {
+        stream_center_options.frame_crc_validation = config.frame_crc_validation.enable;
+    }
+    #[cfg(not(feature = "frame-crc"))]
+    if config.frame_crc_validation.enable {
+        tracing::warn!(
+            "frame crc validation is enabled, but the server is built without the frame-crc feature"
         );
-        tokio::spawn(async move {
-            if let Err(err) = rtmp_server.run().await {
-                tracing::error!("rtmp server thread exit with err: {:?}", err);
-            }
-        });
+    }
 
-        {
-            let msg = format!(
-                "rtmp server is started with config: {:?}",
-                config.rtmp_server
-            );
-            tracing::info!(msg);
-            println!("{}", msg);
-        }
+    let mut builder = MediaServerBuilder::new()
+        .with_stream_center_options(stream_center_options)
+        .with_ingest_limit((&config.ingest_limit).into())
+        .with_egress_shaping((&config.egress_shaping).into());
+    if let Some(data_dir) = config.snapshot.data_dir.clone() {
+        builder = builder.with_data_dir(data_dir);
+    }
+
+    if config.rtmp_server.enable {
+        builder = builder.with_rtmp(config.rtmp_server_config().unwrap());
     }
 
     if config.http_server.enable {
-        let mut http_server = HttpServer::new(
-            HttpServerConfig {
-                address: config.http_server.address,
-                port: config.http_server.port,
-                workers: config.http_server.workers,
-            },
-            stream_center.get_event_sender(),
-        );
-        tokio::spawn(async move {
-            if let Err(err) = http_server.run().await {
-                tracing::error!("http server thread exit with err: {:?}", err);
-            }
+        builder = builder.with_http(HttpServerConfig {
+            address: config.http_server.address,
+            port: config.http_server.port,
+            workers: config.http_server.workers,
+            vod_dir: config.http_server.vod_dir.clone(),
+            capture_dir: config.http_server.capture_dir.clone(),
+            trusted_proxies: config.trusted_proxies().unwrap(),
         });
-
-        {
-            let msg = format!(
-                "http server is started with config: {:?}",
-                config.http_server
-            );
-            tracing::info!(msg);
-            println!("{}", msg);
-        }
     }
 
     if config.rtsp_server.enable {
-        let rtsp_server = RtspServer::new(
-            stream_center.get_event_sender(),
-            rtsp_server::config::RtspServerConfig {
-                address: config.rtsp_server.address,
-                port: config.rtsp_server.port,
+        builder = builder.with_rtsp(RtspServerConfig {
+            address: config.rtsp_server.address,
+            port: config.rtsp_server.port,
+            rtsps: config
+                .rtsps
+                .as_ref()
+                .and_then(|rtsps| rtsps.to_listener_config()),
+            retransmission: RetransmissionConfig {
+                max_packets: config
+                    .rtsp_server
+                    .nack_history_packets
+                    .unwrap_or(DEFAULT_HISTORY_MAX_PACKETS),
+                max_bytes: config
+                    .rtsp_server
+                    .nack_history_bytes
+                    .unwrap_or(DEFAULT_HISTORY_MAX_BYTES),
             },
-        );
-        tokio::spawn(async move {
-            if let Err(err) = rtsp_server.run().await {
-                tracing::error!("rtsp server thread exit with err: {:?}", err);
-            }
+            offer_rtx: config.rtsp_server.offer_rtx,
+            pacing: pacing_config(&config.rtsp_server),
+            h264_buffer: h264_buffer_config(&config.rtsp_server),
+            h264_access_unit_delimiters: config
+                .rtsp_server
+                .h264_access_unit_delimiters
+                .unwrap_or(true),
+            h264_reorder_frames: config
+                .rtsp_server
+                .h264_reorder_frames
+                .unwrap_or(DEFAULT_REORDER_FRAMES),
+            onvif_backchannel: config.rtsp_server.onvif_backchannel,
+            srtp: config.rtsp_server.srtp,
+            multicast: config.rtsp_multicast_groups().unwrap(),
+            fec: config.rtsp_fec_configs().unwrap(),
+            sdes: SdesConfig {
+                tool: config
+                    .rtsp_server
+                    .sdes_tool
+                    .clone()
+                    .unwrap_or_else(|| SERVER_AGENT.to_owned()),
+                name: config.rtsp_server.sdes_name.clone(),
+                email: config.rtsp_server.sdes_email.clone(),
+            },
+            data_dir: config.rtsp_server.data_dir.clone(),
+            describe: DescribeConfig {
+                wait: config
+                    .rtsp_server
+                    .describe_wait_ms
+                    .map_or(DEFAULT_DESCRIBE_WAIT, std::time::Duration::from_millis),
+                minimal_sdp_on_timeout: config.rtsp_server.describe_minimal_sdp,
+            },
+            trusted_proxies: config.trusted_proxies().unwrap(),
+            max_body_size: config
+                .rtsp_server
+                .max_body_bytes
+                .unwrap_or(DEFAULT_MAX_BODY_SIZE),
         });
-
-        {
-            let msg = format!(
-                "rtsp server is started with config: {:?}",
-                config.rtsp_server
-            );
-            tracing::info!(msg);
-            println!("{}", msg);
+        if config.rtsp_server.log_requests {
+            builder = builder.with_rtsp_middleware(Arc::new(RequestLogger));
+        }
+        if let Some(max_sessions_per_ip) = config.rtsp_server.max_sessions_per_ip {
+            builder =
+                builder.with_rtsp_middleware(Arc::new(SessionLimiter::new(max_sessions_per_ip)));
+        }
+        if let Some(load_shedder) = config.rtsp_load_shedder().unwrap() {
+            builder = builder.with_rtsp_middleware(Arc::new(load_shedder));
         }
     }
 
-    tokio::spawn(async move {
-        if let Err(err) = stream_center.run().await {
-            tracing::error!("stream center thread exit with err: {:?}", err);
-        }
-    });
+    if let Some(srt_config) = config.srt_server.as_ref()
+        && srt_config.enable
+    {
+        builder = builder.with_srt(SrtServerConfig {
+            address: srt_config.address,
+            port: srt_config.port,
+            latency: std::time::Duration::from_millis(srt_config.latency_ms),
+            passphrase: srt_config.passphrase.clone(),
+        });
+    }
+
+    let mut media_server = builder.build();
+    media_server.start().unwrap();
     {
         let msg = "stream center is started\nall servers are started".to_string();
         tracing::info!(msg);
         println!("{}", msg);
     }
     let _ = signal::ctrl_c().await;
+    media_server.shutdown().await;
+}
+
+fn pacing_config(rtsp_server: &config::RtspServer) -> Option<PacingConfig> {
+    if !rtsp_server.pacing.unwrap_or(true) {
+        return None;
+    }
+    let default = PacingConfig::default();
+    Some(PacingConfig {
+        burst_bytes: rtsp_server
+            .pacing_burst_bytes
+            .unwrap_or(default.burst_bytes),
+        catch_up_speed: rtsp_server
+            .pacing_catch_up_speed
+            .unwrap_or(default.catch_up_speed),
+        ..default
+    })
+}
+
+fn h264_buffer_config(rtsp_server: &config::RtspServer) -> RtpH264BufferConfig {
+    let default = RtpH264BufferConfig::default();
+    RtpH264BufferConfig {
+        fragments_bytes: rtsp_server
+            .h264_fragments_buffer_bytes
+            .unwrap_or(default.fragments_bytes),
+        de_interleaving_bytes: rtsp_server
+            .h264_de_interleaving_buffer_bytes
+            .unwrap_or(default.de_interleaving_bytes),
+        decoder_bytes: rtsp_server
+            .h264_decoder_buffer_bytes
+            .unwrap_or(default.decoder_bytes),
+        retain_idr: rtsp_server
+            .h264_buffer_retain_idr
+            .unwrap_or(default.retain_idr),
+    }
 }
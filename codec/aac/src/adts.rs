@@ -0,0 +1,103 @@
+use codec_bitstream::reader::BitstreamReader;
+use utils::traits::reader::BitwiseReadFrom;
+
+use crate::{
+    errors::{AACCodecError, AACCodecResult},
+    mpeg4_configuration::audio_specific_config::AudioSpecificConfig,
+};
+
+pub const ADTS_SYNC_WORD: u16 = 0xFFF;
+
+/// fixed and variable header of adts, ISO/IEC 13818-7, 6.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdtsHeader {
+    /// 0 for MPEG-4, 1 for MPEG-2
+    pub id: u8,
+    pub protection_absent: bool,
+    /// audio object type minus 1
+    pub profile: u8,
+    pub sampling_frequency_index: u8,
+    pub channel_configuration: u8,
+    /// length of the frame including the header
+    pub frame_length: u16,
+    pub number_of_raw_data_blocks_in_frame: u8,
+}
+
+impl AdtsHeader {
+    pub fn parse(bytes: &[u8]) -> AACCodecResult<Self> {
+        if bytes.len() < 7 {
+            return Err(AACCodecError::InvalidAdtsHeader(format!(
+                "need 7 bytes, got {}",
+                bytes.len()
+            )));
+        }
+        let sync_word = ((bytes[0] as u16) << 4) | (bytes[1] >> 4) as u16;
+        if sync_word != ADTS_SYNC_WORD {
+            return Err(AACCodecError::InvalidAdtsHeader(format!(
+                "invalid sync word: {:#05x}",
+                sync_word
+            )));
+        }
+        let header = Self {
+            id: (bytes[1] >> 3) & 0b1,
+            protection_absent: bytes[1] & 0b1 == 1,
+            profile: (bytes[2] >> 6) & 0b11,
+            sampling_frequency_index: (bytes[2] >> 2) & 0b1111,
+            channel_configuration: ((bytes[2] & 0b1) << 2) | (bytes[3] >> 6),
+            frame_length: (((bytes[3] & 0b11) as u16) << 11)
+                | ((bytes[4] as u16) << 3)
+                | (bytes[5] >> 5) as u16,
+            number_of_raw_data_blocks_in_frame: bytes[6] & 0b11,
+        };
+        if (header.frame_length as usize) < header.header_length() {
+            return Err(AACCodecError::InvalidAdtsHeader(format!(
+                "frame length {} is shorter than the header",
+                header.frame_length
+            )));
+        }
+        Ok(header)
+    }
+
+    pub fn header_length(&self) -> usize {
+        if self.protection_absent { 7 } else { 9 }
+    }
+
+    pub fn audio_object_type(&self) -> u8 {
+        self.profile + 1
+    }
+
+    /// the 2 bytes AudioSpecificConfig carrying the same audio object type, sampling rate and channels
+    pub fn audio_specific_config_bytes(&self) -> [u8; 2] {
+        let object_type = self.audio_object_type();
+        [
+            (object_type << 3) | (self.sampling_frequency_index >> 1),
+            ((self.sampling_frequency_index & 0b1) << 7) | (self.channel_configuration << 3),
+        ]
+    }
+
+    pub fn audio_specific_config(&self) -> AACCodecResult<AudioSpecificConfig> {
+        let bytes = self.audio_specific_config_bytes();
+        let mut reader = BitstreamReader::new(&bytes);
+        AudioSpecificConfig::read_from(&mut reader)
+    }
+}
+
+/// splits a buffer of back to back adts frames into headers and raw access units
+pub fn split_adts_frames(bytes: &[u8]) -> AACCodecResult<Vec<(AdtsHeader, &[u8])>> {
+    let mut frames = vec![];
+    let mut remaining = bytes;
+    while !remaining.is_empty() {
+        let header = AdtsHeader::parse(remaining)?;
+        let frame_length = header.frame_length as usize;
+        if frame_length > remaining.len() {
+            return Err(AACCodecError::InvalidAdtsHeader(format!(
+                "frame length {} exceeds the {} bytes left",
+                frame_length,
+                remaining.len()
+            )));
+        }
+        frames.push((header, &remaining[header.header_length()..frame_length]));
+        remaining = &remaining[frame_length..];
+    }
+    Ok(frames)
+}
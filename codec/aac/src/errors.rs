@@ -18,6 +18,12 @@ pub enum AACCodecError {
     UnknownOrchToken(u8),
     #[error("unknwn event type for score_line: {0}")]
     UnknownScoreLineType(u8),
+    #[error("invalid adts header: {0}")]
+    InvalidAdtsHeader(String),
+    #[error("no silence for {0}")]
+    SilenceNotSupported(String),
+    #[error("invalid program config element: {0}")]
+    InvalidProgramConfigElement(String),
 }
 
 pub type AACCodecResult<T> = Result<T, AACCodecError>;
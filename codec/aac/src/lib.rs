@@ -1,2 +1,4 @@
+pub mod adts;
 pub mod errors;
 pub mod mpeg4_configuration;
+pub mod silence;
@@ -6,14 +6,21 @@ use utils::traits::{
     dynamic_sized_packet::DynamicSizedBitsPacket, fixed_packet::FixedBitwisePacket,
 };
 
+use crate::errors::AACCodecResult;
+
 use super::{
-    als_specific_config::ALSSpecificConfig, celp_specific_config::CelpSpecificConfig,
-    dst_specific_config::DSTSpecificConfig, eld_specific_config::ELDSpecificConfig,
+    als_specific_config::ALSSpecificConfig,
+    celp_specific_config::CelpSpecificConfig,
+    dst_specific_config::DSTSpecificConfig,
+    eld_specific_config::ELDSpecificConfig,
     error_protection_specific_config::ErrorProtectionSpecificConfig,
     error_resilient_celp_specific_config::ErrorResilientCelpSpecificConfig,
     error_resilient_hvxc_specific_config::ErrorResilientHvxcSpecificConfig,
-    ga_specific_config::GASpecificConfig, hvxc_specific_config::HvxcSpecificConfig,
-    sls_specific_config::SLSSpecificConfig, ssc_specific_config::SSCSpecificConfig,
+    ga_specific_config::GASpecificConfig,
+    hvxc_specific_config::HvxcSpecificConfig,
+    program_config_element::{ChannelPositions, ProgramConfigElement},
+    sls_specific_config::SLSSpecificConfig,
+    ssc_specific_config::SSCSpecificConfig,
     structured_audio_specific_config::StructuredAudioSpecificConfig,
     tts_specific_config::TTSSpecificConfig,
 };
@@ -62,6 +69,29 @@ impl DynamicSizedBitsPacket for AudioSpecificConfig {
     }
 }
 
+impl AudioSpecificConfig {
+    /// the channel layout of channel configuration 0, carried by the GA config
+    pub fn program_config_element(&self) -> Option<&ProgramConfigElement> {
+        match &self.specific_config {
+            SpecificConfig::Ga(ga) => ga.program_config_element.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// the channels of the channel configuration, or of the program config element for configuration 0.
+    /// None for the configurations out of 1 to 7 and a configuration 0 without the element
+    pub fn channel_positions(&self) -> AACCodecResult<Option<ChannelPositions>> {
+        if self.channel_configuration != 0 {
+            return Ok(ChannelPositions::of_channel_configuration(
+                self.channel_configuration,
+            ));
+        }
+        self.program_config_element()
+            .map(ProgramConfigElement::channel_positions)
+            .transpose()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum SpecificConfig {
     Ga(GASpecificConfig),
@@ -25,7 +25,17 @@ impl<W: BitWrite> BitwiseWriteTo<W> for AudioObjectType {
 impl<W: BitWrite> BitwiseWriteTo<W> for AudioSpecificConfig {
     type Error = AACCodecError;
     fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
-        self.audio_object_type.write_to(writer)?;
+        // explicit sbr signaling leads with the object type of he-aac, the one of the core follows the extension
+        let explicit_sbr = self.sbr_present_flag == 1;
+        if explicit_sbr {
+            if self.ps_present_flag == 1 {
+                AudioObjectType::PS.write_to(writer)?;
+            } else {
+                AudioObjectType::SBR.write_to(writer)?;
+            }
+        } else {
+            self.audio_object_type.write_to(writer)?;
+        }
         writer.write::<4, u8>(self.sampling_frequency_index.into())?;
         if let Some(frequency) = self.sampling_frequency {
             writer.write::<24, u32>(frequency)?;
@@ -37,6 +47,9 @@ impl<W: BitWrite> BitwiseWriteTo<W> for AudioSpecificConfig {
         if let Some(frequency) = self.extension_sampling_frequency {
             writer.write::<24, u32>(frequency)?;
         }
+        if explicit_sbr {
+            self.audio_object_type.write_to(writer)?;
+        }
         if let Some(configuration) = self.extension_channel_configuration {
             writer.write::<4, u8>(configuration)?;
         }
@@ -1,6 +1,6 @@
 use utils::traits::{dynamic_sized_packet::DynamicSizedBitsPacket, fixed_packet::FixedBitwisePacket};
 
-use crate::errors::AACCodecError;
+use crate::errors::{AACCodecError, AACCodecResult};
 
 use super::audio_specific_config::sampling_frequency_index::SamplingFrequencyIndex;
 pub mod reader;
@@ -81,17 +81,11 @@ pub struct ProgramConfigElement {
     pub element_instance_tag: u8,                         // 4 bits
     pub object_type: ObjectType,                          // 2 bits
     pub sampling_frequency_index: SamplingFrequencyIndex, // 4 bits
-    #[allow(unused)]
     num_front_channel_elements: u8,                       // 4 bits
-    #[allow(unused)]
     num_side_channel_elements: u8,                        // 4 bits
-    #[allow(unused)]
     num_back_channel_elements: u8,                        // 4 bits
-    #[allow(unused)]
     num_lfe_channel_elements: u8,                         // 2 bits
-    #[allow(unused)]
     num_assoc_data_elements: u8,                          // 3 bits
-    #[allow(unused)]
     num_valid_cc_elements: u8,                            // 4 bits
     #[allow(unused)]
     mono_mixdown_present: bool,                           // 1 bit
@@ -115,7 +109,6 @@ pub struct ProgramConfigElement {
     pub assoc_data_element_tag_select: Vec<u8>, // 4 bits
     pub valid_cc_elements: Vec<ValidCCElement>,
     // byte_alignment
-    #[allow(unused)]
     comment_field_bytes: u8,         // 8 bits
     pub comment_field_data: Vec<u8>, // 8 bits
 }
@@ -147,4 +140,97 @@ impl DynamicSizedBitsPacket for ProgramConfigElement {
         8 + // comment_field_bytes
         self.comment_field_data.len() * 8
     }
-}
\ No newline at end of file
+}
+
+/// the channels a program config element lays out, a cpe carries 2 and a sce 1
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelPositions {
+    pub front: u8,
+    pub side: u8,
+    pub back: u8,
+    pub lfe: u8,
+}
+
+impl ChannelPositions {
+    /// the positions of the channel configurations 1 to 7, Table 1.19
+    pub fn of_channel_configuration(channel_configuration: u8) -> Option<Self> {
+        let (front, side, back, lfe) = match channel_configuration {
+            1 => (1, 0, 0, 0),
+            2 => (2, 0, 0, 0),
+            3 => (3, 0, 0, 0),
+            4 => (3, 0, 1, 0),
+            5 => (3, 0, 2, 0),
+            6 => (3, 0, 2, 1),
+            // the outside front pair counts to the front
+            7 => (5, 0, 2, 1),
+            _ => return None,
+        };
+        Some(Self {
+            front,
+            side,
+            back,
+            lfe,
+        })
+    }
+
+    /// the channels besides the lfe ones
+    pub fn main_channels(&self) -> u8 {
+        self.front + self.side + self.back
+    }
+
+    pub fn total(&self) -> u8 {
+        self.main_channels() + self.lfe
+    }
+}
+
+impl ProgramConfigElement {
+    /// the element counts as read have to match the elements kept, and fit their bits
+    pub fn validate(&self) -> AACCodecResult<()> {
+        let counts = [
+            ("front", self.num_front_channel_elements, self.front_channel_elements.len(), 4),
+            ("side", self.num_side_channel_elements, self.side_channel_elements.len(), 4),
+            ("back", self.num_back_channel_elements, self.back_channel_elements.len(), 4),
+            ("lfe", self.num_lfe_channel_elements, self.lfe_element_tag_select.len(), 2),
+            (
+                "assoc data",
+                self.num_assoc_data_elements,
+                self.assoc_data_element_tag_select.len(),
+                3,
+            ),
+            ("valid cc", self.num_valid_cc_elements, self.valid_cc_elements.len(), 4),
+            ("comment field", self.comment_field_bytes, self.comment_field_data.len(), 8),
+        ];
+        for (name, count, len, bits) in counts {
+            if count as usize != len {
+                return Err(AACCodecError::InvalidProgramConfigElement(format!(
+                    "{} {} elements counted, {} present",
+                    count, name, len
+                )));
+            }
+            if len >= 1 << bits {
+                return Err(AACCodecError::InvalidProgramConfigElement(format!(
+                    "{} {} elements do not fit {} bits",
+                    len, name, bits
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// the channels of the front, side, back and lfe elements
+    pub fn channel_positions(&self) -> AACCodecResult<ChannelPositions> {
+        self.validate()?;
+        let channels = |elements: &[ChannelElement]| {
+            elements
+                .iter()
+                .map(|element| if element.is_cpe { 2 } else { 1 })
+                .sum::<u8>()
+        };
+        Ok(ChannelPositions {
+            front: channels(&self.front_channel_elements),
+            side: channels(&self.side_channel_elements),
+            back: channels(&self.back_channel_elements),
+            lfe: self.lfe_element_tag_select.len() as u8,
+        })
+    }
+}
@@ -36,6 +36,7 @@ impl<W: BitWrite> BitwiseWriteTo<W> for ValidCCElement {
 impl<W: BitWrite> BitwiseWriteTo<W> for ProgramConfigElement {
     type Error = AACCodecError;
     fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        self.validate()?;
         writer.write::<4, u8>(self.element_instance_tag)?;
         writer.write::<2, u8>(self.object_type.into())?;
         writer.write::<4, u8>(self.sampling_frequency_index.into())?;
@@ -0,0 +1,185 @@
+//! silent access units, for filling the holes publishers leave in the audio
+//! @see: ISO/IEC 14496-3, 4.4.2 GA bitstream payloads
+
+use bitstream_io::{BigEndian, BitWrite, BitWriter};
+
+use crate::{
+    errors::{AACCodecError, AACCodecResult},
+    mpeg4_configuration::audio_specific_config::{
+        AudioSpecificConfig, SpecificConfig, audio_object_type::AudioObjectType,
+    },
+};
+
+/// id_syn_ele, Table 4.85
+const ID_SCE: u8 = 0;
+const ID_CPE: u8 = 1;
+const ID_LFE: u8 = 3;
+const ID_END: u8 = 7;
+
+/// the gain of an ics with no spectral data, anything decodes to zeros then
+const SILENT_GLOBAL_GAIN: u8 = 160;
+
+impl AudioSpecificConfig {
+    /// the samples of a raw data block of the core coder, 1024, or 960 if the frame length flag of the GA config is set.
+    /// None for the object types out of the GA family
+    pub fn core_samples_per_frame(&self) -> Option<u32> {
+        match &self.specific_config {
+            SpecificConfig::Ga(ga) if ga.frame_length_flag => Some(960),
+            SpecificConfig::Ga(_) => Some(1024),
+            _ => None,
+        }
+    }
+
+    /// the sampling rate of the core coder, from the index or the explicit frequency
+    pub fn core_sample_rate(&self) -> Option<u32> {
+        self.sampling_frequency_index
+            .get_sampling_frequency()
+            .or(self.sampling_frequency)
+    }
+
+    /// sbr is signaled, explicitly by the object type of he-aac, or implicitly by the sync extension
+    pub fn has_sbr(&self) -> bool {
+        self.sbr_present_flag == 1
+            || self
+                .sync_extension_audio_object_type5
+                .as_ref()
+                .is_some_and(|extension| extension.sbr_present_flag)
+    }
+
+    /// parametric stereo is signaled, he-aac v2
+    pub fn has_ps(&self) -> bool {
+        self.ps_present_flag == 1
+            || self
+                .sync_extension_audio_object_type5
+                .as_ref()
+                .is_some_and(|extension| extension.ps_present_flag == Some(true))
+    }
+
+    /// the rate the sbr outputs at, of the signaling in use
+    fn extension_sample_rate(&self) -> Option<u32> {
+        if self.sbr_present_flag == 1 {
+            return self
+                .extension_sampling_frequency_index
+                .and_then(|index| index.get_sampling_frequency())
+                .or(self.extension_sampling_frequency);
+        }
+        self.sync_extension_audio_object_type5
+            .as_ref()
+            .and_then(|extension| {
+                extension
+                    .extension_sampling_frequency_index
+                    .and_then(|index| index.get_sampling_frequency())
+                    .or(extension.extension_sampling_frequency)
+            })
+    }
+
+    /// the rate the decoder outputs at, the extension rate if sbr is present, the core rate otherwise.
+    /// an sbr telling no rate doubles the core rate
+    pub fn effective_sample_rate(&self) -> Option<u32> {
+        let core = self.core_sample_rate()?;
+        if !self.has_sbr() {
+            return Some(core);
+        }
+        Some(
+            self.extension_sample_rate()
+                .filter(|rate| *rate > 0)
+                .unwrap_or(core * 2),
+        )
+    }
+
+    /// the samples a raw data block decodes to at the effective rate,
+    /// 2048 for he-aac with the sbr at twice the core rate
+    pub fn samples_per_frame(&self) -> Option<u32> {
+        let samples = self.core_samples_per_frame()?;
+        match (self.core_sample_rate(), self.effective_sample_rate()) {
+            (Some(core), Some(effective)) if core > 0 => {
+                Some((samples as u64 * effective as u64 / core as u64) as u32)
+            }
+            _ => Some(samples),
+        }
+    }
+
+    /// the nanoseconds a raw data block lasts
+    pub fn frame_duration_nano(&self) -> Option<u64> {
+        let samples = self.samples_per_frame()? as u64;
+        let rate = self.effective_sample_rate().filter(|rate| *rate > 0)? as u64;
+        Some(samples * 1_000_000_000 / rate)
+    }
+}
+
+/// the elements of the channel configurations 1 to 7, Table 1.19
+fn channel_elements(channel_configuration: u8) -> Option<&'static [u8]> {
+    Some(match channel_configuration {
+        1 => &[ID_SCE],
+        2 => &[ID_CPE],
+        3 => &[ID_SCE, ID_CPE],
+        4 => &[ID_SCE, ID_CPE, ID_SCE],
+        5 => &[ID_SCE, ID_CPE, ID_CPE],
+        6 => &[ID_SCE, ID_CPE, ID_CPE, ID_LFE],
+        7 => &[ID_SCE, ID_CPE, ID_CPE, ID_CPE, ID_LFE],
+        _ => return None,
+    })
+}
+
+/// an individual_channel_stream of a long window with no scale factor band,
+/// so no section, scale factor or spectral data follows
+fn write_silent_ics<W: BitWrite>(writer: &mut W) -> AACCodecResult<()> {
+    writer.write::<8, u8>(SILENT_GLOBAL_GAIN)?;
+    // ics_info: ics_reserved_bit, window_sequence ONLY_LONG_SEQUENCE, window_shape KBD
+    writer.write_bit(false)?;
+    writer.write::<2, u8>(0)?;
+    writer.write_bit(true)?;
+    // max_sfb
+    writer.write::<6, u8>(0)?;
+    // predictor_data_present of main, ltp_data_present of ltp, unused of lc and ssr
+    writer.write_bit(false)?;
+    // pulse_data_present, tns_data_present, gain_control_data_present
+    writer.write_bit(false)?;
+    writer.write_bit(false)?;
+    writer.write_bit(false)?;
+    Ok(())
+}
+
+/// a raw_data_block decoding to silence, with the elements the channel configuration implies.
+/// only plain AAC main, lc, ssr and ltp are supported, an sbr or ps payload is not written
+pub fn silent_raw_data_block(config: &AudioSpecificConfig) -> AACCodecResult<Vec<u8>> {
+    let unsupported = |reason: String| Err(AACCodecError::SilenceNotSupported(reason));
+    match config.audio_object_type {
+        AudioObjectType::AACMain
+        | AudioObjectType::AACLC
+        | AudioObjectType::AACSSR
+        | AudioObjectType::AACLTP => {}
+        object_type => return unsupported(format!("audio object type {:?}", object_type)),
+    }
+    if config.sbr_present_flag == 1 || config.ps_present_flag == 1 {
+        return unsupported("sbr or ps is present".to_owned());
+    }
+    let Some(elements) = channel_elements(config.channel_configuration) else {
+        return unsupported(format!(
+            "channel configuration {}",
+            config.channel_configuration
+        ));
+    };
+
+    let mut bytes = Vec::new();
+    let mut writer = BitWriter::endian(&mut bytes, BigEndian);
+    // element_instance_tag counts the elements of each type
+    let mut instance_tags = [0u8; 8];
+    for id in elements {
+        writer.write::<3, u8>(*id)?;
+        writer.write::<4, u8>(instance_tags[*id as usize])?;
+        instance_tags[*id as usize] += 1;
+        match *id {
+            ID_CPE => {
+                // common_window off, each channel has an ics of its own
+                writer.write_bit(false)?;
+                write_silent_ics(&mut writer)?;
+                write_silent_ics(&mut writer)?;
+            }
+            _ => write_silent_ics(&mut writer)?,
+        }
+    }
+    writer.write::<3, u8>(ID_END)?;
+    writer.byte_align()?;
+    Ok(bytes)
+}
@@ -0,0 +1,64 @@
+use tokio_util::bytes::Bytes;
+use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
+
+use crate::{
+    errors::Av1CodecResult,
+    obu::reader::split_obus,
+    sequence_header::{SequenceHeaderObu, reader::find_sequence_header},
+};
+
+pub mod reader;
+#[cfg(test)]
+mod test;
+pub mod writer;
+
+/// @see: AV1 Codec ISO Media File Format Binding, Section 2.3.3 AV1 Codec Configuration Box Syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Av1CodecConfigurationRecord {
+    // marker: f(1), equal to 1
+    // version: f(7), equal to 1
+    pub seq_profile: u8,            // f(3)
+    pub seq_level_idx_0: u8,        // f(5)
+    pub seq_tier_0: u8,             // f(1)
+    pub high_bitdepth: bool,        // f(1)
+    pub twelve_bit: bool,           // f(1)
+    pub monochrome: bool,           // f(1)
+    pub chroma_subsampling_x: bool, // f(1)
+    pub chroma_subsampling_y: bool, // f(1)
+    pub chroma_sample_position: u8, // f(2)
+    // reserved: f(3), equal to 0
+    pub initial_presentation_delay_minus_one: Option<u8>, // f(1) present flag and f(4)
+    /// the sequence header obu, maybe followed by metadata obus, in the low overhead bitstream format
+    pub config_obus: Bytes,
+}
+
+impl Av1CodecConfigurationRecord {
+    /// the record describing `sequence_header`, which must be among `config_obus`
+    pub fn new(sequence_header: &SequenceHeaderObu, config_obus: Bytes) -> Self {
+        let color_config = &sequence_header.color_config;
+        Self {
+            seq_profile: sequence_header.seq_profile,
+            seq_level_idx_0: sequence_header.seq_level_idx_0(),
+            seq_tier_0: sequence_header.seq_tier_0(),
+            high_bitdepth: color_config.high_bitdepth,
+            twelve_bit: color_config.twelve_bit,
+            monochrome: color_config.mono_chrome,
+            chroma_subsampling_x: color_config.subsampling_x,
+            chroma_subsampling_y: color_config.subsampling_y,
+            chroma_sample_position: color_config.chroma_sample_position,
+            initial_presentation_delay_minus_one: None,
+            config_obus,
+        }
+    }
+
+    /// None if the config obus carry no sequence header, which is allowed but unusual
+    pub fn sequence_header(&self) -> Av1CodecResult<Option<SequenceHeaderObu>> {
+        find_sequence_header(&split_obus(&self.config_obus)?)
+    }
+}
+
+impl DynamicSizedPacket for Av1CodecConfigurationRecord {
+    fn get_packet_bytes_count(&self) -> usize {
+        4 + self.config_obus.len()
+    }
+}
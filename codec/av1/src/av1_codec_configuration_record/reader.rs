@@ -0,0 +1,55 @@
+use std::io;
+
+use byteorder::ReadBytesExt;
+use tokio_util::bytes::Bytes;
+use utils::traits::reader::ReadFrom;
+
+use crate::errors::Av1CodecError;
+
+use super::Av1CodecConfigurationRecord;
+
+impl<R: io::Read> ReadFrom<R> for Av1CodecConfigurationRecord {
+    type Error = Av1CodecError;
+    fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
+        let byte = reader.read_u8()?;
+        if byte >> 7 != 1 {
+            return Err(Av1CodecError::InvalidAv1CodecConfigurationMarker);
+        }
+        let version = byte & 0b111_1111;
+        if version != 1 {
+            return Err(Av1CodecError::UnknownAv1CodecConfigurationVersion(version));
+        }
+        let byte = reader.read_u8()?;
+        let seq_profile = byte >> 5;
+        let seq_level_idx_0 = byte & 0b1_1111;
+        let byte = reader.read_u8()?;
+        let seq_tier_0 = byte >> 7;
+        let high_bitdepth = (byte >> 6) & 0b1 == 1;
+        let twelve_bit = (byte >> 5) & 0b1 == 1;
+        let monochrome = (byte >> 4) & 0b1 == 1;
+        let chroma_subsampling_x = (byte >> 3) & 0b1 == 1;
+        let chroma_subsampling_y = (byte >> 2) & 0b1 == 1;
+        let chroma_sample_position = byte & 0b11;
+        let byte = reader.read_u8()?;
+        let initial_presentation_delay_minus_one = if (byte >> 4) & 0b1 == 1 {
+            Some(byte & 0b1111)
+        } else {
+            None
+        };
+        let mut config_obus = Vec::new();
+        reader.read_to_end(&mut config_obus)?;
+        Ok(Self {
+            seq_profile,
+            seq_level_idx_0,
+            seq_tier_0,
+            high_bitdepth,
+            twelve_bit,
+            monochrome,
+            chroma_subsampling_x,
+            chroma_subsampling_y,
+            chroma_sample_position,
+            initial_presentation_delay_minus_one,
+            config_obus: Bytes::from(config_obus),
+        })
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests {
+    use tokio_util::bytes::Bytes;
+    use utils::traits::{
+        dynamic_sized_packet::DynamicSizedPacket, reader::ReadFrom, writer::WriteTo,
+    };
+
+    use crate::{
+        av1_codec_configuration_record::Av1CodecConfigurationRecord,
+        obu::{ObuType, reader::split_obus},
+    };
+
+    /// main profile, level 4.0, 1920x1080, 8 bits 4:2:0
+    const SEQUENCE_HEADER_1080P: [u8; 13] = [
+        0x0a, 0x0b, 0x00, 0x00, 0x00, 0x42, 0xab, 0xbf, 0xc3, 0x73, 0xff, 0xe6, 0x01,
+    ];
+
+    #[test]
+    fn test_record_round_trip() {
+        let mut bytes = vec![0x81, 0x08, 0x0c, 0x00];
+        bytes.extend_from_slice(&SEQUENCE_HEADER_1080P);
+        let record = Av1CodecConfigurationRecord::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(record.seq_profile, 0);
+        assert_eq!(record.seq_level_idx_0, 8);
+        assert!(!record.high_bitdepth);
+        assert!(record.chroma_subsampling_x && record.chroma_subsampling_y);
+        assert_eq!(record.initial_presentation_delay_minus_one, None);
+        assert_eq!(record.config_obus.as_ref(), SEQUENCE_HEADER_1080P);
+        assert_eq!(record.get_packet_bytes_count(), bytes.len());
+
+        let sequence_header = record.sequence_header().unwrap().unwrap();
+        assert_eq!(sequence_header.get_video_width(), 1920);
+        assert_eq!(sequence_header.get_video_height(), 1080);
+
+        let mut written = Vec::new();
+        record.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+
+        // the fields in the first 4 bytes agree with the sequence header
+        let rebuilt = Av1CodecConfigurationRecord::new(
+            &sequence_header,
+            Bytes::from_static(&SEQUENCE_HEADER_1080P),
+        );
+        assert_eq!(rebuilt, record);
+    }
+
+    #[test]
+    fn test_invalid_marker_and_version() {
+        let mut bytes = vec![0x01, 0x08, 0x0c, 0x00];
+        assert!(Av1CodecConfigurationRecord::read_from(&mut bytes.as_slice()).is_err());
+        bytes[0] = 0x82;
+        assert!(Av1CodecConfigurationRecord::read_from(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_split_temporal_unit() {
+        // temporal delimiter, sequence header, then a frame obu without a size field
+        let mut bytes = vec![0x12, 0x00];
+        bytes.extend_from_slice(&SEQUENCE_HEADER_1080P);
+        bytes.extend_from_slice(&[0x30, 0xaa, 0xbb, 0xcc]);
+        let obus = split_obus(&bytes).unwrap();
+        let types: Vec<_> = obus.iter().map(|obu| obu.obu_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                ObuType::TemporalDelimiter,
+                ObuType::SequenceHeader,
+                ObuType::Frame
+            ]
+        );
+        assert!(obus[0].payload.is_empty());
+        assert_eq!(obus[2].payload.as_ref(), [0xaa, 0xbb, 0xcc]);
+
+        let mut written = Vec::new();
+        for obu in &obus {
+            obu.write_to(&mut written).unwrap();
+            assert!(obu.get_packet_bytes_count() > 0);
+        }
+        assert_eq!(written, bytes);
+    }
+}
@@ -0,0 +1,32 @@
+use std::io;
+
+use byteorder::WriteBytesExt;
+use utils::traits::writer::WriteTo;
+
+use crate::errors::Av1CodecError;
+
+use super::Av1CodecConfigurationRecord;
+
+impl<W: io::Write> WriteTo<W> for Av1CodecConfigurationRecord {
+    type Error = Av1CodecError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        // marker and version
+        writer.write_u8(0b1000_0001)?;
+        writer.write_u8(((self.seq_profile & 0b111) << 5) | (self.seq_level_idx_0 & 0b1_1111))?;
+        writer.write_u8(
+            ((self.seq_tier_0 & 0b1) << 7)
+                | ((self.high_bitdepth as u8) << 6)
+                | ((self.twelve_bit as u8) << 5)
+                | ((self.monochrome as u8) << 4)
+                | ((self.chroma_subsampling_x as u8) << 3)
+                | ((self.chroma_subsampling_y as u8) << 2)
+                | (self.chroma_sample_position & 0b11),
+        )?;
+        writer.write_u8(match self.initial_presentation_delay_minus_one {
+            Some(delay) => 0b1_0000 | (delay & 0b1111),
+            None => 0,
+        })?;
+        writer.write_all(&self.config_obus)?;
+        Ok(())
+    }
+}
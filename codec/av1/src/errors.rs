@@ -0,0 +1,21 @@
+use std::io;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Av1CodecError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("obu forbidden bit is set")]
+    ForbiddenBitSet,
+    #[error("invalid leb128 value: {0}")]
+    InvalidLeb128(String),
+    #[error("syntax error: {0}")]
+    SyntaxError(String),
+    #[error("unknown av1 codec configuration version: {0}")]
+    UnknownAv1CodecConfigurationVersion(u8),
+    #[error("invalid av1 codec configuration marker")]
+    InvalidAv1CodecConfigurationMarker,
+}
+
+pub type Av1CodecResult<T> = Result<T, Av1CodecError>;
@@ -0,0 +1,51 @@
+use std::io;
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use crate::errors::{Av1CodecError, Av1CodecResult};
+
+/// a leb128 value takes at most 8 bytes in av1
+pub const MAX_LEB128_BYTES: usize = 8;
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 4.10.5 leb128()
+pub fn read_leb128<R: io::Read>(reader: &mut R) -> Av1CodecResult<u64> {
+    let mut value: u64 = 0;
+    for i in 0..MAX_LEB128_BYTES {
+        let byte = reader.read_u8()?;
+        value |= ((byte & 0x7F) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            if value > u32::MAX as u64 {
+                return Err(Av1CodecError::InvalidLeb128(format!(
+                    "value {} exceeds (1 << 32) - 1",
+                    value
+                )));
+            }
+            return Ok(value);
+        }
+    }
+    Err(Av1CodecError::InvalidLeb128(format!(
+        "no terminating byte in {} bytes",
+        MAX_LEB128_BYTES
+    )))
+}
+
+pub fn write_leb128<W: io::Write>(writer: &mut W, mut value: u64) -> Av1CodecResult<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            writer.write_u8(byte)?;
+            return Ok(());
+        }
+        writer.write_u8(byte | 0x80)?;
+    }
+}
+
+pub fn leb128_bytes_count(mut value: u64) -> usize {
+    let mut count = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        count += 1;
+    }
+    count
+}
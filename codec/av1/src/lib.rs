@@ -0,0 +1,5 @@
+pub mod av1_codec_configuration_record;
+pub mod errors;
+pub mod leb128;
+pub mod obu;
+pub mod sequence_header;
@@ -0,0 +1,89 @@
+use tokio_util::bytes::Bytes;
+
+pub mod reader;
+pub mod writer;
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 6.2.2 OBU header semantics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObuType {
+    SequenceHeader,
+    TemporalDelimiter,
+    FrameHeader,
+    TileGroup,
+    Metadata,
+    Frame,
+    RedundantFrameHeader,
+    TileList,
+    Padding,
+    Reserved(u8),
+}
+
+impl From<ObuType> for u8 {
+    fn from(value: ObuType) -> Self {
+        match value {
+            ObuType::SequenceHeader => 1,
+            ObuType::TemporalDelimiter => 2,
+            ObuType::FrameHeader => 3,
+            ObuType::TileGroup => 4,
+            ObuType::Metadata => 5,
+            ObuType::Frame => 6,
+            ObuType::RedundantFrameHeader => 7,
+            ObuType::TileList => 8,
+            ObuType::Padding => 15,
+            ObuType::Reserved(v) => v,
+        }
+    }
+}
+
+impl From<u8> for ObuType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::SequenceHeader,
+            2 => Self::TemporalDelimiter,
+            3 => Self::FrameHeader,
+            4 => Self::TileGroup,
+            5 => Self::Metadata,
+            6 => Self::Frame,
+            7 => Self::RedundantFrameHeader,
+            8 => Self::TileList,
+            15 => Self::Padding,
+            v => Self::Reserved(v),
+        }
+    }
+}
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 5.3.3 OBU extension syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObuExtensionHeader {
+    pub temporal_id: u8, // f(3)
+    pub spatial_id: u8,  // f(2)
+}
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 5.3.2 OBU header syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObuHeader {
+    pub obu_type: ObuType, // f(4)
+    pub extension: Option<ObuExtensionHeader>,
+    pub has_size_field: bool, // f(1)
+}
+
+impl ObuHeader {
+    pub fn bytes_count(&self) -> usize {
+        if self.extension.is_some() { 2 } else { 1 }
+    }
+}
+
+/// an obu as found in the low overhead bitstream format,
+/// the payload excludes the header and the size field
+#[derive(Debug, Clone)]
+pub struct Obu {
+    pub header: ObuHeader,
+    pub payload: Bytes,
+}
+
+impl Obu {
+    #[inline]
+    pub fn obu_type(&self) -> ObuType {
+        self.header.obu_type
+    }
+}
@@ -0,0 +1,70 @@
+use std::io;
+
+use byteorder::ReadBytesExt;
+use num::ToPrimitive;
+use tokio_util::bytes::Bytes;
+use utils::traits::reader::ReadFrom;
+
+use crate::{
+    errors::{Av1CodecError, Av1CodecResult},
+    leb128::read_leb128,
+};
+
+use super::{Obu, ObuExtensionHeader, ObuHeader};
+
+impl<R: io::Read> ReadFrom<R> for ObuHeader {
+    type Error = Av1CodecError;
+    fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
+        let byte = reader.read_u8()?;
+        if byte & 0b1000_0000 != 0 {
+            return Err(Av1CodecError::ForbiddenBitSet);
+        }
+        let obu_type = ((byte >> 3) & 0b1111).into();
+        let extension_flag = (byte >> 2) & 0b1 == 1;
+        let has_size_field = (byte >> 1) & 0b1 == 1;
+        let extension = if extension_flag {
+            let byte = reader.read_u8()?;
+            Some(ObuExtensionHeader {
+                temporal_id: (byte >> 5) & 0b111,
+                spatial_id: (byte >> 3) & 0b11,
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            obu_type,
+            extension,
+            has_size_field,
+        })
+    }
+}
+
+/// splits a temporal unit or the config obus in the low overhead bitstream format,
+/// only the last obu may go without a size field, it takes the remaining bytes then
+pub fn split_obus(bytes: &[u8]) -> Av1CodecResult<Vec<Obu>> {
+    let mut obus = Vec::new();
+    let mut cursor = io::Cursor::new(bytes);
+    while (cursor.position() as usize) < bytes.len() {
+        let header = ObuHeader::read_from(&mut cursor)?;
+        let size = if header.has_size_field {
+            read_leb128(&mut cursor)?.to_usize().unwrap()
+        } else {
+            bytes.len() - cursor.position().to_usize().unwrap()
+        };
+        let start = cursor.position().to_usize().unwrap();
+        let end = start.checked_add(size).filter(|end| *end <= bytes.len());
+        let Some(end) = end else {
+            return Err(Av1CodecError::SyntaxError(format!(
+                "obu of {} bytes overflows the remaining {} bytes",
+                size,
+                bytes.len() - start
+            )));
+        };
+        obus.push(Obu {
+            header,
+            payload: Bytes::copy_from_slice(&bytes[start..end]),
+        });
+        cursor.set_position(end as u64);
+    }
+    Ok(obus)
+}
@@ -0,0 +1,52 @@
+use std::io;
+
+use byteorder::WriteBytesExt;
+use utils::traits::{dynamic_sized_packet::DynamicSizedPacket, writer::WriteTo};
+
+use crate::{
+    errors::Av1CodecError,
+    leb128::{leb128_bytes_count, write_leb128},
+};
+
+use super::{Obu, ObuHeader};
+
+impl<W: io::Write> WriteTo<W> for ObuHeader {
+    type Error = Av1CodecError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        let obu_type: u8 = self.obu_type.into();
+        writer.write_u8(
+            ((obu_type & 0b1111) << 3)
+                | ((self.extension.is_some() as u8) << 2)
+                | ((self.has_size_field as u8) << 1),
+        )?;
+        if let Some(extension) = &self.extension {
+            writer.write_u8(
+                ((extension.temporal_id & 0b111) << 5) | ((extension.spatial_id & 0b11) << 3),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl DynamicSizedPacket for Obu {
+    fn get_packet_bytes_count(&self) -> usize {
+        let mut result = self.header.bytes_count() + self.payload.len();
+        if self.header.has_size_field {
+            result += leb128_bytes_count(self.payload.len() as u64);
+        }
+        result
+    }
+}
+
+/// the obu as it was read, the size field is written only if the header has it
+impl<W: io::Write> WriteTo<W> for Obu {
+    type Error = Av1CodecError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        self.header.write_to(writer)?;
+        if self.header.has_size_field {
+            write_leb128(writer, self.payload.len() as u64)?;
+        }
+        writer.write_all(&self.payload)?;
+        Ok(())
+    }
+}
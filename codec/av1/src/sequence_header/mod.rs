@@ -0,0 +1,135 @@
+pub mod reader;
+#[cfg(test)]
+mod test;
+
+/// seq_force_screen_content_tools, the frame headers choose whether to use the screen content tools
+pub const SELECT_SCREEN_CONTENT_TOOLS: u8 = 2;
+/// seq_force_integer_mv, the frame headers choose whether to use integer motion vectors
+pub const SELECT_INTEGER_MV: u8 = 2;
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 5.5.3 Timing info syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingInfo {
+    pub num_units_in_display_tick: u32, // f(32)
+    pub time_scale: u32,                // f(32)
+    /// present if equal_picture_interval
+    pub num_ticks_per_picture_minus_1: Option<u32>, // uvlc()
+}
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 5.5.4 Decoder model info syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderModelInfo {
+    pub buffer_delay_length_minus_1: u8,            // f(5)
+    pub num_units_in_decoding_tick: u32,            // f(32)
+    pub buffer_removal_time_length_minus_1: u8,     // f(5)
+    pub frame_presentation_time_length_minus_1: u8, // f(5)
+}
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 5.5.5 Operating parameters info syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperatingParametersInfo {
+    pub decoder_buffer_delay: u32, // f(buffer_delay_length_minus_1 + 1)
+    pub encoder_buffer_delay: u32, // f(buffer_delay_length_minus_1 + 1)
+    pub low_delay_mode_flag: bool, // f(1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperatingPoint {
+    pub operating_point_idc: u16, // f(12)
+    pub seq_level_idx: u8,        // f(5)
+    /// only signaled for levels above 3.3
+    pub seq_tier: u8, // f(1)
+    pub operating_parameters_info: Option<OperatingParametersInfo>,
+    pub initial_display_delay_minus_1: Option<u8>, // f(4)
+}
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 5.5.2 Color config syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorConfig {
+    pub high_bitdepth: bool, // f(1)
+    pub twelve_bit: bool,    // f(1)
+    pub mono_chrome: bool,   // f(1)
+    /// 2 (unspecified) unless the color description is present
+    pub color_primaries: u8, // f(8)
+    pub transfer_characteristics: u8, // f(8)
+    pub matrix_coefficients: u8, // f(8)
+    pub color_range: bool,   // f(1)
+    pub subsampling_x: bool, // f(1)
+    pub subsampling_y: bool, // f(1)
+    pub chroma_sample_position: u8, // f(2)
+    pub separate_uv_delta_q: bool, // f(1)
+}
+
+impl ColorConfig {
+    pub fn bit_depth(&self) -> u8 {
+        match (self.high_bitdepth, self.twelve_bit) {
+            (false, _) => 8,
+            (true, false) => 10,
+            (true, true) => 12,
+        }
+    }
+}
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 5.5.1 General sequence header OBU syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceHeaderObu {
+    pub seq_profile: u8,                    // f(3)
+    pub still_picture: bool,                // f(1)
+    pub reduced_still_picture_header: bool, // f(1)
+    pub timing_info: Option<TimingInfo>,
+    pub decoder_model_info: Option<DecoderModelInfo>,
+    pub initial_display_delay_present_flag: bool, // f(1)
+    /// operating_points_cnt_minus_1 + 1 of them, f(5)
+    pub operating_points: Vec<OperatingPoint>,
+    pub frame_width_bits_minus_1: u8,                   // f(4)
+    pub frame_height_bits_minus_1: u8,                  // f(4)
+    pub max_frame_width_minus_1: u32,                   // f(frame_width_bits_minus_1 + 1)
+    pub max_frame_height_minus_1: u32,                  // f(frame_height_bits_minus_1 + 1)
+    pub frame_id_numbers_present_flag: bool,            // f(1)
+    pub delta_frame_id_length_minus_2: Option<u8>,      // f(4)
+    pub additional_frame_id_length_minus_1: Option<u8>, // f(3)
+    pub use_128x128_superblock: bool,                   // f(1)
+    pub enable_filter_intra: bool,                      // f(1)
+    pub enable_intra_edge_filter: bool,                 // f(1)
+    pub enable_interintra_compound: bool,               // f(1)
+    pub enable_masked_compound: bool,                   // f(1)
+    pub enable_warped_motion: bool,                     // f(1)
+    pub enable_dual_filter: bool,                       // f(1)
+    pub enable_order_hint: bool,                        // f(1)
+    pub enable_jnt_comp: bool,                          // f(1)
+    pub enable_ref_frame_mvs: bool,                     // f(1)
+    /// 0, 1 or SELECT_SCREEN_CONTENT_TOOLS
+    pub seq_force_screen_content_tools: u8,
+    /// 0, 1 or SELECT_INTEGER_MV
+    pub seq_force_integer_mv: u8,
+    /// order_hint_bits_minus_1 + 1, 0 if the order hint is disabled
+    pub order_hint_bits: u8,
+    pub enable_superres: bool,    // f(1)
+    pub enable_cdef: bool,        // f(1)
+    pub enable_restoration: bool, // f(1)
+    pub color_config: ColorConfig,
+    pub film_grain_params_present: bool, // f(1)
+}
+
+impl SequenceHeaderObu {
+    pub fn get_video_width(&self) -> u64 {
+        self.max_frame_width_minus_1 as u64 + 1
+    }
+
+    pub fn get_video_height(&self) -> u64 {
+        self.max_frame_height_minus_1 as u64 + 1
+    }
+
+    /// the level and tier of the first operating point, which the codec configuration record carries
+    pub fn seq_level_idx_0(&self) -> u8 {
+        self.operating_points
+            .first()
+            .map_or(0, |point| point.seq_level_idx)
+    }
+
+    pub fn seq_tier_0(&self) -> u8 {
+        self.operating_points
+            .first()
+            .map_or(0, |point| point.seq_tier)
+    }
+}
@@ -0,0 +1,339 @@
+use bitstream_io::BitRead;
+use utils::traits::reader::{BitwiseReadFrom, BitwiseReadReaminingFrom};
+
+use crate::{
+    errors::{Av1CodecError, Av1CodecResult},
+    obu::{Obu, ObuType},
+};
+
+use super::{
+    ColorConfig, DecoderModelInfo, OperatingParametersInfo, OperatingPoint, SELECT_INTEGER_MV,
+    SELECT_SCREEN_CONTENT_TOOLS, SequenceHeaderObu, TimingInfo,
+};
+
+/// @see: AV1 Bitstream & Decoding Process Specification, Section 4.10.3 uvlc()
+fn read_uvlc<R: BitRead>(reader: &mut R) -> Av1CodecResult<u32> {
+    let mut leading_zeros = 0;
+    while !reader.read_bit()? {
+        leading_zeros += 1;
+        if leading_zeros >= 32 {
+            return Ok(u32::MAX);
+        }
+    }
+    if leading_zeros == 0 {
+        return Ok(0);
+    }
+    let value: u32 = reader.read_var(leading_zeros)?;
+    Ok(value + ((1u64 << leading_zeros) - 1) as u32)
+}
+
+impl<R: BitRead> BitwiseReadFrom<R> for TimingInfo {
+    type Error = Av1CodecError;
+    fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
+        let num_units_in_display_tick = reader.read::<32, u32>()?;
+        let time_scale = reader.read::<32, u32>()?;
+        let equal_picture_interval = reader.read_bit()?;
+        let num_ticks_per_picture_minus_1 = if equal_picture_interval {
+            Some(read_uvlc(reader)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            num_units_in_display_tick,
+            time_scale,
+            num_ticks_per_picture_minus_1,
+        })
+    }
+}
+
+impl<R: BitRead> BitwiseReadFrom<R> for DecoderModelInfo {
+    type Error = Av1CodecError;
+    fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
+        Ok(Self {
+            buffer_delay_length_minus_1: reader.read::<5, u8>()?,
+            num_units_in_decoding_tick: reader.read::<32, u32>()?,
+            buffer_removal_time_length_minus_1: reader.read::<5, u8>()?,
+            frame_presentation_time_length_minus_1: reader.read::<5, u8>()?,
+        })
+    }
+}
+
+/// the buffer delays are as long as the decoder model info says
+impl<R: BitRead> BitwiseReadReaminingFrom<&DecoderModelInfo, R> for OperatingParametersInfo {
+    type Error = Av1CodecError;
+    fn read_remaining_from(header: &DecoderModelInfo, reader: &mut R) -> Result<Self, Self::Error> {
+        let bits = header.buffer_delay_length_minus_1 as u32 + 1;
+        Ok(Self {
+            decoder_buffer_delay: reader.read_var(bits)?,
+            encoder_buffer_delay: reader.read_var(bits)?,
+            low_delay_mode_flag: reader.read_bit()?,
+        })
+    }
+}
+
+/// color_config() depends on the seq_profile
+impl<R: BitRead> BitwiseReadReaminingFrom<u8, R> for ColorConfig {
+    type Error = Av1CodecError;
+    fn read_remaining_from(seq_profile: u8, reader: &mut R) -> Result<Self, Self::Error> {
+        let high_bitdepth = reader.read_bit()?;
+        let twelve_bit = if seq_profile == 2 && high_bitdepth {
+            reader.read_bit()?
+        } else {
+            false
+        };
+        let mono_chrome = if seq_profile == 1 {
+            false
+        } else {
+            reader.read_bit()?
+        };
+        let (color_primaries, transfer_characteristics, matrix_coefficients) =
+            if reader.read_bit()? {
+                (
+                    reader.read::<8, u8>()?,
+                    reader.read::<8, u8>()?,
+                    reader.read::<8, u8>()?,
+                )
+            } else {
+                // CP_UNSPECIFIED, TC_UNSPECIFIED, MC_UNSPECIFIED
+                (2, 2, 2)
+            };
+        let mut result = Self {
+            high_bitdepth,
+            twelve_bit,
+            mono_chrome,
+            color_primaries,
+            transfer_characteristics,
+            matrix_coefficients,
+            color_range: false,
+            subsampling_x: true,
+            subsampling_y: true,
+            // CSP_UNKNOWN
+            chroma_sample_position: 0,
+            separate_uv_delta_q: false,
+        };
+        if mono_chrome {
+            result.color_range = reader.read_bit()?;
+            return Ok(result);
+        }
+        // CP_BT_709, TC_SRGB and MC_IDENTITY
+        if color_primaries == 1 && transfer_characteristics == 13 && matrix_coefficients == 0 {
+            result.color_range = true;
+            result.subsampling_x = false;
+            result.subsampling_y = false;
+        } else {
+            result.color_range = reader.read_bit()?;
+            match seq_profile {
+                0 => {}
+                1 => {
+                    result.subsampling_x = false;
+                    result.subsampling_y = false;
+                }
+                _ => {
+                    if result.bit_depth() == 12 {
+                        result.subsampling_x = reader.read_bit()?;
+                        result.subsampling_y = if result.subsampling_x {
+                            reader.read_bit()?
+                        } else {
+                            false
+                        };
+                    } else {
+                        result.subsampling_y = false;
+                    }
+                }
+            }
+            if result.subsampling_x && result.subsampling_y {
+                result.chroma_sample_position = reader.read::<2, u8>()?;
+            }
+        }
+        result.separate_uv_delta_q = reader.read_bit()?;
+        Ok(result)
+    }
+}
+
+impl<R: BitRead> BitwiseReadFrom<R> for SequenceHeaderObu {
+    type Error = Av1CodecError;
+    fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
+        let seq_profile = reader.read::<3, u8>()?;
+        if seq_profile > 2 {
+            return Err(Av1CodecError::SyntaxError(format!(
+                "unknown seq_profile: {}",
+                seq_profile
+            )));
+        }
+        let still_picture = reader.read_bit()?;
+        let reduced_still_picture_header = reader.read_bit()?;
+
+        let mut timing_info = None;
+        let mut decoder_model_info = None;
+        let mut initial_display_delay_present_flag = false;
+        let mut operating_points = Vec::new();
+        if reduced_still_picture_header {
+            operating_points.push(OperatingPoint {
+                operating_point_idc: 0,
+                seq_level_idx: reader.read::<5, u8>()?,
+                seq_tier: 0,
+                operating_parameters_info: None,
+                initial_display_delay_minus_1: None,
+            });
+        } else {
+            if reader.read_bit()? {
+                timing_info = Some(TimingInfo::read_from(reader)?);
+                if reader.read_bit()? {
+                    decoder_model_info = Some(DecoderModelInfo::read_from(reader)?);
+                }
+            }
+            initial_display_delay_present_flag = reader.read_bit()?;
+            let operating_points_cnt_minus_1 = reader.read::<5, u8>()?;
+            for _ in 0..=operating_points_cnt_minus_1 {
+                let operating_point_idc = reader.read::<12, u16>()?;
+                let seq_level_idx = reader.read::<5, u8>()?;
+                let seq_tier = if seq_level_idx > 7 {
+                    reader.read::<1, u8>()?
+                } else {
+                    0
+                };
+                let mut operating_parameters_info = None;
+                if let Some(decoder_model_info) = &decoder_model_info
+                    && reader.read_bit()?
+                {
+                    operating_parameters_info = Some(OperatingParametersInfo::read_remaining_from(
+                        decoder_model_info,
+                        reader,
+                    )?);
+                }
+                let mut initial_display_delay_minus_1 = None;
+                if initial_display_delay_present_flag && reader.read_bit()? {
+                    initial_display_delay_minus_1 = Some(reader.read::<4, u8>()?);
+                }
+                operating_points.push(OperatingPoint {
+                    operating_point_idc,
+                    seq_level_idx,
+                    seq_tier,
+                    operating_parameters_info,
+                    initial_display_delay_minus_1,
+                });
+            }
+        }
+
+        let frame_width_bits_minus_1 = reader.read::<4, u8>()?;
+        let frame_height_bits_minus_1 = reader.read::<4, u8>()?;
+        let max_frame_width_minus_1 = reader.read_var(frame_width_bits_minus_1 as u32 + 1)?;
+        let max_frame_height_minus_1 = reader.read_var(frame_height_bits_minus_1 as u32 + 1)?;
+        let frame_id_numbers_present_flag = if reduced_still_picture_header {
+            false
+        } else {
+            reader.read_bit()?
+        };
+        let (delta_frame_id_length_minus_2, additional_frame_id_length_minus_1) =
+            if frame_id_numbers_present_flag {
+                (Some(reader.read::<4, u8>()?), Some(reader.read::<3, u8>()?))
+            } else {
+                (None, None)
+            };
+        let use_128x128_superblock = reader.read_bit()?;
+        let enable_filter_intra = reader.read_bit()?;
+        let enable_intra_edge_filter = reader.read_bit()?;
+
+        let mut result = Self {
+            seq_profile,
+            still_picture,
+            reduced_still_picture_header,
+            timing_info,
+            decoder_model_info,
+            initial_display_delay_present_flag,
+            operating_points,
+            frame_width_bits_minus_1,
+            frame_height_bits_minus_1,
+            max_frame_width_minus_1,
+            max_frame_height_minus_1,
+            frame_id_numbers_present_flag,
+            delta_frame_id_length_minus_2,
+            additional_frame_id_length_minus_1,
+            use_128x128_superblock,
+            enable_filter_intra,
+            enable_intra_edge_filter,
+            enable_interintra_compound: false,
+            enable_masked_compound: false,
+            enable_warped_motion: false,
+            enable_dual_filter: false,
+            enable_order_hint: false,
+            enable_jnt_comp: false,
+            enable_ref_frame_mvs: false,
+            seq_force_screen_content_tools: SELECT_SCREEN_CONTENT_TOOLS,
+            seq_force_integer_mv: SELECT_INTEGER_MV,
+            order_hint_bits: 0,
+            enable_superres: false,
+            enable_cdef: false,
+            enable_restoration: false,
+            color_config: ColorConfig {
+                high_bitdepth: false,
+                twelve_bit: false,
+                mono_chrome: false,
+                color_primaries: 2,
+                transfer_characteristics: 2,
+                matrix_coefficients: 2,
+                color_range: false,
+                subsampling_x: true,
+                subsampling_y: true,
+                chroma_sample_position: 0,
+                separate_uv_delta_q: false,
+            },
+            film_grain_params_present: false,
+        };
+        if !reduced_still_picture_header {
+            result.enable_interintra_compound = reader.read_bit()?;
+            result.enable_masked_compound = reader.read_bit()?;
+            result.enable_warped_motion = reader.read_bit()?;
+            result.enable_dual_filter = reader.read_bit()?;
+            result.enable_order_hint = reader.read_bit()?;
+            if result.enable_order_hint {
+                result.enable_jnt_comp = reader.read_bit()?;
+                result.enable_ref_frame_mvs = reader.read_bit()?;
+            }
+            let seq_choose_screen_content_tools = reader.read_bit()?;
+            if !seq_choose_screen_content_tools {
+                result.seq_force_screen_content_tools = reader.read::<1, u8>()?;
+            }
+            if result.seq_force_screen_content_tools > 0 {
+                let seq_choose_integer_mv = reader.read_bit()?;
+                if !seq_choose_integer_mv {
+                    result.seq_force_integer_mv = reader.read::<1, u8>()?;
+                }
+            } else {
+                result.seq_force_integer_mv = SELECT_INTEGER_MV;
+            }
+            if result.enable_order_hint {
+                result.order_hint_bits = reader.read::<3, u8>()? + 1;
+            }
+        }
+        result.enable_superres = reader.read_bit()?;
+        result.enable_cdef = reader.read_bit()?;
+        result.enable_restoration = reader.read_bit()?;
+        result.color_config = ColorConfig::read_remaining_from(seq_profile, reader)?;
+        result.film_grain_params_present = reader.read_bit()?;
+        Ok(result)
+    }
+}
+
+impl TryFrom<&Obu> for SequenceHeaderObu {
+    type Error = Av1CodecError;
+    fn try_from(value: &Obu) -> Result<Self, Self::Error> {
+        if value.obu_type() != ObuType::SequenceHeader {
+            return Err(Av1CodecError::SyntaxError(format!(
+                "expect a sequence header obu, got {:?} instead",
+                value.obu_type()
+            )));
+        }
+        let mut reader =
+            bitstream_io::BitReader::endian(&value.payload[..], bitstream_io::BigEndian);
+        Self::read_from(&mut reader)
+    }
+}
+
+/// the first sequence header among `obus`, None if there is none
+pub fn find_sequence_header(obus: &[Obu]) -> Av1CodecResult<Option<SequenceHeaderObu>> {
+    obus.iter()
+        .find(|obu| obu.obu_type() == ObuType::SequenceHeader)
+        .map(SequenceHeaderObu::try_from)
+        .transpose()
+}
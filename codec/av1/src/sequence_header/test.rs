@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        obu::{ObuType, reader::split_obus},
+        sequence_header::{
+            SELECT_INTEGER_MV, SELECT_SCREEN_CONTENT_TOOLS, SequenceHeaderObu, TimingInfo,
+            reader::find_sequence_header,
+        },
+    };
+
+    /// main profile, level 4.0, 1920x1080, 8 bits 4:2:0, no timing info
+    const SEQUENCE_HEADER_1080P: &str = "0a0b00000042abbfc373ffe601";
+    /// main profile, level 3.1, 1280x720 at 60000/1001 fps, 10 bits 4:2:0 bt.709,
+    /// chroma samples vertically co-located with luma samples
+    const SEQUENCE_HEADER_720P_10BIT: &str = "0a180400000fa40003a983000005ff04ff02cf3fe67404040490";
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn parse(hex: &str) -> SequenceHeaderObu {
+        let obus = split_obus(&from_hex(hex)).unwrap();
+        assert_eq!(obus.len(), 1);
+        assert_eq!(obus[0].obu_type(), ObuType::SequenceHeader);
+        assert!(obus[0].header.has_size_field);
+        find_sequence_header(&obus).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_parse_1080p_sequence_header() {
+        let sequence_header = parse(SEQUENCE_HEADER_1080P);
+        assert_eq!(sequence_header.get_video_width(), 1920);
+        assert_eq!(sequence_header.get_video_height(), 1080);
+        assert_eq!(sequence_header.seq_profile, 0);
+        assert_eq!(sequence_header.seq_level_idx_0(), 8);
+        assert_eq!(sequence_header.seq_tier_0(), 0);
+        assert!(sequence_header.timing_info.is_none());
+        assert!(!sequence_header.still_picture);
+        assert!(sequence_header.enable_order_hint);
+        assert_eq!(sequence_header.order_hint_bits, 7);
+        assert_eq!(
+            sequence_header.seq_force_screen_content_tools,
+            SELECT_SCREEN_CONTENT_TOOLS
+        );
+        assert_eq!(sequence_header.seq_force_integer_mv, SELECT_INTEGER_MV);
+        assert!(sequence_header.enable_cdef && sequence_header.enable_restoration);
+        assert!(!sequence_header.enable_superres);
+
+        let color_config = &sequence_header.color_config;
+        assert_eq!(color_config.bit_depth(), 8);
+        assert!(!color_config.mono_chrome);
+        assert!(color_config.subsampling_x && color_config.subsampling_y);
+        assert_eq!(color_config.color_primaries, 2);
+        assert!(!sequence_header.film_grain_params_present);
+    }
+
+    #[test]
+    fn test_parse_720p_10bit_sequence_header() {
+        let sequence_header = parse(SEQUENCE_HEADER_720P_10BIT);
+        assert_eq!(sequence_header.get_video_width(), 1280);
+        assert_eq!(sequence_header.get_video_height(), 720);
+        assert_eq!(sequence_header.seq_level_idx_0(), 5);
+        assert_eq!(
+            sequence_header.timing_info,
+            Some(TimingInfo {
+                num_units_in_display_tick: 1001,
+                time_scale: 60000,
+                num_ticks_per_picture_minus_1: Some(0),
+            })
+        );
+        assert!(sequence_header.decoder_model_info.is_none());
+        assert_eq!(sequence_header.seq_force_screen_content_tools, 0);
+        assert_eq!(sequence_header.seq_force_integer_mv, SELECT_INTEGER_MV);
+
+        let color_config = &sequence_header.color_config;
+        assert_eq!(color_config.bit_depth(), 10);
+        assert_eq!(
+            (
+                color_config.color_primaries,
+                color_config.transfer_characteristics,
+                color_config.matrix_coefficients
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(color_config.chroma_sample_position, 1);
+    }
+
+    #[test]
+    fn test_truncated_sequence_header() {
+        let mut bytes = from_hex(SEQUENCE_HEADER_1080P);
+        // the obu claims more bytes than there are
+        bytes.truncate(8);
+        assert!(split_obus(&bytes).is_err());
+    }
+}
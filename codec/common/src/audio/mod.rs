@@ -1,4 +1,8 @@
-use crate::{FrameType, errors::CodecCommonError};
+use crate::{
+    FrameType,
+    errors::{CodecCommonError, CodecCommonResult},
+};
+use std::{str::FromStr, time::Instant};
 pub mod reader;
 pub mod writer;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -23,6 +27,27 @@ pub enum AudioCodecCommon {
 }
 
 impl AudioCodecCommon {
+    pub(crate) const ALL: [Self; 17] = [
+        Self::LinearPCM,
+        Self::ADPCM,
+        Self::MP3,
+        Self::LinearPCMLittleEndian,
+        Self::NellyMoser16KHZ,
+        Self::NellyMoser8KHZ,
+        Self::NellyMoser,
+        Self::G711ALawLogarithmicPCM,
+        Self::G711MULawLogarithmicPCM,
+        Self::AAC,
+        Self::Speex,
+        Self::MP38KHZ,
+        Self::DeviceSpecific,
+        Self::AC3,
+        Self::EAC3,
+        Self::OPUS,
+        Self::FLAC,
+    ];
+
+    /// the canonical name, also the serialized form with the serde feature
     pub fn get_codec_name(&self) -> &'static str {
         match self {
             Self::LinearPCM => "LinearPCM",
@@ -32,7 +57,7 @@ impl AudioCodecCommon {
             Self::NellyMoser16KHZ => "NellyMoser16KHZ",
             Self::NellyMoser8KHZ => "NellyMoser8KHZ",
             Self::NellyMoser => "NellyMoser",
-            Self::G711ALawLogarithmicPCM => "G711ALowLogarithmicPCM",
+            Self::G711ALawLogarithmicPCM => "G711ALawLogarithmicPCM",
             Self::G711MULawLogarithmicPCM => "G711MULawLogarithmicPCM",
             Self::AAC => "AAC",
             Self::Speex => "Speex",
@@ -46,6 +71,31 @@ impl AudioCodecCommon {
     }
 }
 
+impl FromStr for AudioCodecCommon {
+    type Err = CodecCommonError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|codec| codec.get_codec_name() == s)
+            .ok_or_else(|| CodecCommonError::UnknownCodecName(s.to_owned()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for AudioCodecCommon {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.get_codec_name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for AudioCodecCommon {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum SoundRateCommon {
     KHZ5D5,
@@ -107,14 +157,22 @@ impl TryFrom<&codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecif
     fn try_from(
         value: &codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecificConfig,
     ) -> Result<Self, Self::Error> {
-        let sound_rate: SoundRateCommon = value.sampling_frequency_index.try_into()?;
-        let sound_type = if value.channel_configuration == 1 {
-            SoundTypeCommon::Mono
-        } else if value.channel_configuration == 0 {
-            tracing::warn!("channel configuration is 0, treat as stereo");
-            SoundTypeCommon::Stereo
-        } else {
-            SoundTypeCommon::Stereo
+        // the rate the decoder outputs, twice the core rate of he-aac
+        let sound_rate: SoundRateCommon = value
+            .effective_sample_rate()
+            .and_then(AACSamplingFrequencyIndex::get_sampling_frequency_index)
+            .unwrap_or(value.sampling_frequency_index)
+            .try_into()?;
+        let sound_type = match value.channel_positions()? {
+            Some(positions) if positions.total() == 1 => SoundTypeCommon::Mono,
+            Some(_) => SoundTypeCommon::Stereo,
+            None => {
+                tracing::warn!(
+                    "no channel layout of channel configuration {}, treat as stereo",
+                    value.channel_configuration
+                );
+                SoundTypeCommon::Stereo
+            }
         };
         Ok(Self {
             sound_rate,
@@ -124,12 +182,85 @@ impl TryFrom<&codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecif
     }
 }
 
+type AACChannelPositions = codec_aac::mpeg4_configuration::program_config_element::ChannelPositions;
+
+/// the speaker layout of an audio stream, the common ones by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    /// front left, right and center
+    Surround3_0,
+    /// 3.0 and a back center
+    Surround4_0,
+    /// 3.0 and a surround pair
+    Surround5_0,
+    /// 5.0 and an lfe
+    Surround5_1,
+    /// 5.1 and another surround pair
+    Surround7_1,
+    /// any other, by its channels
+    Other {
+        channels: u8,
+    },
+}
+
+impl ChannelLayout {
+    pub fn channel_count(&self) -> u8 {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => 2,
+            Self::Surround3_0 => 3,
+            Self::Surround4_0 => 4,
+            Self::Surround5_0 => 5,
+            Self::Surround5_1 => 6,
+            Self::Surround7_1 => 8,
+            Self::Other { channels } => *channels,
+        }
+    }
+
+    /// more than the 2 channels the legacy sound type tells
+    pub fn is_multichannel(&self) -> bool {
+        self.channel_count() > 2
+    }
+}
+
+impl From<AACChannelPositions> for ChannelLayout {
+    fn from(value: AACChannelPositions) -> Self {
+        match (value.main_channels(), value.lfe) {
+            (1, 0) => Self::Mono,
+            (2, 0) => Self::Stereo,
+            (3, 0) => Self::Surround3_0,
+            (4, 0) => Self::Surround4_0,
+            (5, 0) => Self::Surround5_0,
+            (5, 1) => Self::Surround5_1,
+            (7, 1) => Self::Surround7_1,
+            _ => Self::Other {
+                channels: value.total(),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioFrameInfo {
     pub codec_id: AudioCodecCommon,
     pub frame_type: FrameType,
     pub sound_info: SoundInfoCommon,
     pub timestamp_nano: u64,
+    /// wallclock the frame entered the server from its publisher,
+    /// only set if latency measurement is enabled
+    pub ingest_time: Option<Instant>,
+    /// the audio track of a multitrack stream, 0 is the default track
+    pub track_id: u8,
+    /// the timestamp the publisher sent the frame with, set if the stream center normalized it
+    pub publisher_timestamp_nano: Option<u64>,
+    /// set on the first frame after the timeline of the stream was anchored again,
+    /// sinks may start over, e.g., with a new segment
+    pub discontinuity: bool,
+    /// crc of the payload as it entered the stream center, see [`crate::frame_crc`]
+    #[cfg(feature = "frame-crc")]
+    pub payload_crc: Option<u32>,
 }
 
 impl AudioFrameInfo {
@@ -150,6 +281,12 @@ impl AudioFrameInfo {
                 sound_type,
             },
             timestamp_nano,
+            ingest_time: None,
+            track_id: 0,
+            publisher_timestamp_nano: None,
+            discontinuity: false,
+            #[cfg(feature = "frame-crc")]
+            payload_crc: None,
         }
     }
 }
@@ -169,6 +306,16 @@ impl From<codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecificCo
     }
 }
 
+impl AudioConfig {
+    /// None if the config tells no layout, e.g., aac of a channel configuration out of 0 to 7.
+    /// the layout of aac channel configuration 0 is of its program config element
+    pub fn channel_layout(&self) -> CodecCommonResult<Option<ChannelLayout>> {
+        match self {
+            Self::AAC(config) => Ok(config.channel_positions()?.map(ChannelLayout::from)),
+        }
+    }
+}
+
 impl From<&AudioConfig> for AudioCodecCommon {
     fn from(value: &AudioConfig) -> Self {
         match value {
@@ -15,10 +15,7 @@ impl<W: io::Write> WriteTo<W> for AudioConfig {
             Self::AAC(config) => {
                 let mut writer = bitstream_io::BitWriter::endian(writer, BigEndian);
                 config.write_to(writer.by_ref()).map_err(|err| {
-                    CodecCommonError::WriteAudioConfigFailed(
-                        Box::new(self.clone()),
-                        format!("{}", err),
-                    )
+                    CodecCommonError::WriteAudioConfigFailed(Box::new(self.clone()), err)
                 })?;
                 writer.byte_align()?;
                 Ok(())
@@ -11,9 +11,15 @@ pub enum CodecCommonError {
     InvalidNaluSizeLengthMinueOne(u8),
     #[error("parse h264 nalu failed: {0}")]
     ParseH264NaluFailed(#[from] codec_h264::errors::H264CodecError),
+    #[error("parse av1 obus failed: {0}")]
+    ParseAv1ObusFailed(#[from] codec_av1::errors::Av1CodecError),
     #[error("write audio config failed: {0:?}, {1}")]
-    WriteAudioConfigFailed(Box<AudioConfig>, String),
+    WriteAudioConfigFailed(Box<AudioConfig>, #[source] codec_aac::errors::AACCodecError),
     #[error("invalid sampling frequency index: {0}")]
     InvalidSamplingFrequencyIndex(u8),
+    #[error("unknown codec name: {0}")]
+    UnknownCodecName(String),
+    #[error("invalid aac config: {0}")]
+    InvalidAACConfig(#[from] codec_aac::errors::AACCodecError),
 }
 pub type CodecCommonResult<T> = Result<T, CodecCommonError>;
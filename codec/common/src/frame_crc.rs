@@ -0,0 +1,23 @@
+//! crc32 of the frame payloads, independent of how the payload is framed, e.g., avcc or annexb,
+//! so that a conversion between framings keeps it
+
+use crate::video::VideoFrameUnit;
+
+/// over the header and body of each nal unit, or over the obus
+pub fn video_payload_crc(payload: &VideoFrameUnit) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    match payload {
+        VideoFrameUnit::H264 { nal_units } => {
+            for nalu in nal_units {
+                hasher.update(&[u8::from(nalu.header)]);
+                hasher.update(&nalu.body);
+            }
+        }
+        VideoFrameUnit::AV1 { obus } => hasher.update(obus),
+    }
+    hasher.finalize()
+}
+
+pub fn audio_payload_crc(payload: &[u8]) -> u32 {
+    crc32fast::hash(payload)
+}
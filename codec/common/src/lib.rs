@@ -2,6 +2,10 @@ use std::fmt::Debug;
 
 pub mod audio;
 pub mod errors;
+#[cfg(feature = "frame-crc")]
+pub mod frame_crc;
+#[cfg(test)]
+mod test;
 pub mod video;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -60,6 +64,28 @@ impl MediaFrameTimestamp {
         self.apply_offset_nano(ts)
     }
 
+    /// negative composition time offsets are legal in enhanced rtmp,
+    /// the pts is clamped at 0 as timestamps are unsigned
+    pub fn apply_signed_offset_ms(&mut self, cts_ms: i64) -> &mut Self {
+        let cts_nano = cts_ms.checked_mul(1_000_000).unwrap();
+        self.presentation_timestamp_nano = self
+            .presentation_timestamp_nano
+            .saturating_add_signed(cts_nano);
+        self
+    }
+
+    /// moves pts and dts together, e.g., onto the timeline of another source,
+    /// the composition time offset is kept, timestamps are clamped at 0
+    pub fn shift_nano(&mut self, offset_nano: i64) -> &mut Self {
+        self.presentation_timestamp_nano = self
+            .presentation_timestamp_nano
+            .saturating_add_signed(offset_nano);
+        self.decode_timestamp_nano = self
+            .decode_timestamp_nano
+            .saturating_add_signed(offset_nano);
+        self
+    }
+
     pub fn pts(&self) -> u64 {
         self.presentation_timestamp_nano
     }
@@ -76,6 +102,11 @@ impl MediaFrameTimestamp {
         self.dts().checked_div(1_000_000).unwrap()
     }
 
+    /// the composition time offset, pts - dts, in milliseconds
+    pub fn cts_ms(&self) -> i64 {
+        self.pts_ms() as i64 - self.dts_ms() as i64
+    }
+
     pub fn to_debug_str(&self) -> String {
         format!(
             "pts nano: {}, dts nano: {}",
@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecificConfig;
+    use codec_bitstream::reader::BitstreamReader;
+    use utils::traits::{reader::BitwiseReadFrom, writer::WriteTo};
+
+    use crate::{
+        audio::{AudioCodecCommon, AudioConfig, ChannelLayout, SoundInfoCommon, SoundTypeCommon},
+        errors::CodecCommonError,
+        video::VideoCodecCommon,
+    };
+
+    #[test]
+    fn codec_names_parse_back() {
+        for codec in AudioCodecCommon::ALL {
+            assert_eq!(
+                codec.get_codec_name().parse::<AudioCodecCommon>().unwrap(),
+                codec
+            );
+        }
+        for codec in VideoCodecCommon::ALL {
+            assert_eq!(
+                codec.get_codec_name().parse::<VideoCodecCommon>().unwrap(),
+                codec
+            );
+        }
+        assert!("avc".parse::<VideoCodecCommon>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn codecs_serialize_to_their_names() {
+        for codec in AudioCodecCommon::ALL {
+            let json = serde_json::to_string(&codec).unwrap();
+            assert_eq!(json, format!("\"{}\"", codec.get_codec_name()));
+            assert_eq!(
+                serde_json::from_str::<AudioCodecCommon>(&json).unwrap(),
+                codec
+            );
+        }
+        for codec in VideoCodecCommon::ALL {
+            let json = serde_json::to_string(&codec).unwrap();
+            assert_eq!(json, format!("\"{}\"", codec.get_codec_name()));
+            assert_eq!(
+                serde_json::from_str::<VideoCodecCommon>(&json).unwrap(),
+                codec
+            );
+        }
+        assert!(serde_json::from_str::<VideoCodecCommon>("\"H264\"").is_err());
+    }
+
+    fn aac_config_from_hex(hex: &str) -> AudioSpecificConfig {
+        let bytes = utils::bytes::hex_to_bytes(hex).unwrap();
+        AudioSpecificConfig::read_from(&mut BitstreamReader::new(&bytes)).unwrap()
+    }
+
+    /// aac lc 48khz of channel configuration 0, the program config element lays out
+    /// a sce and a cpe in the front, a cpe in the back and an lfe
+    const AAC_PCE_5_1: &str = "118004C8050001088000";
+    /// as 5.1, with another cpe on the sides
+    const AAC_PCE_7_1: &str = "118004C845000108C80000";
+
+    #[test]
+    fn program_config_elements_tell_the_channel_layout() {
+        for (hex, layout, channels) in [
+            (AAC_PCE_5_1, ChannelLayout::Surround5_1, 6),
+            (AAC_PCE_7_1, ChannelLayout::Surround7_1, 8),
+        ] {
+            let config = aac_config_from_hex(hex);
+            assert_eq!(config.channel_configuration, 0);
+            let positions = config.channel_positions().unwrap().unwrap();
+            assert_eq!(positions.front, 3, "{}", hex);
+            assert_eq!(positions.back, 2, "{}", hex);
+            assert_eq!(positions.lfe, 1, "{}", hex);
+            assert_eq!(positions.total(), channels, "{}", hex);
+
+            let config = AudioConfig::AAC(config);
+            let channel_layout = config.channel_layout().unwrap().unwrap();
+            assert_eq!(channel_layout, layout);
+            assert_eq!(channel_layout.channel_count(), channels);
+            assert!(channel_layout.is_multichannel());
+            // the element goes on as it came in
+            let mut written = vec![];
+            config.write_to(&mut written).unwrap();
+            assert_eq!(written, utils::bytes::hex_to_bytes(hex).unwrap(), "{}", hex);
+        }
+
+        // the shortcuts of the channel configurations agree with the elements
+        let stereo = aac_config_from_hex("1190");
+        let sound_info = SoundInfoCommon::try_from(&stereo).unwrap();
+        assert_eq!(sound_info.sound_type, SoundTypeCommon::Stereo);
+        assert_eq!(
+            AudioConfig::AAC(stereo).channel_layout().unwrap(),
+            Some(ChannelLayout::Stereo)
+        );
+        // channel configuration 7 is 7.1, its outside front pair counted in the front
+        let mut surround = aac_config_from_hex("11B8");
+        assert_eq!(surround.channel_configuration, 7);
+        assert_eq!(
+            AudioConfig::AAC(surround.clone()).channel_layout().unwrap(),
+            Some(ChannelLayout::Surround7_1)
+        );
+        surround.channel_configuration = 15;
+        assert_eq!(AudioConfig::AAC(surround).channel_layout().unwrap(), None);
+    }
+
+    #[test]
+    fn inconsistent_program_config_elements_are_refused() {
+        let mut config = aac_config_from_hex(AAC_PCE_5_1);
+        let codec_aac::mpeg4_configuration::audio_specific_config::SpecificConfig::Ga(ga) =
+            &mut config.specific_config
+        else {
+            panic!("expect a GA config");
+        };
+        let element = ga.program_config_element.as_mut().unwrap();
+        // one more cpe than the element count tells
+        let cpe = element.back_channel_elements[0];
+        element.back_channel_elements.push(cpe);
+
+        assert!(config.channel_positions().is_err());
+        let config = AudioConfig::AAC(config);
+        assert!(matches!(
+            config.channel_layout(),
+            Err(CodecCommonError::InvalidAACConfig(_))
+        ));
+        assert!(config.write_to(&mut vec![]).is_err());
+    }
+}
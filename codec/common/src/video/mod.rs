@@ -1,9 +1,18 @@
 pub mod reader;
 pub mod writer;
-use crate::{FrameType, MediaFrameTimestamp};
+use crate::{FrameType, MediaFrameTimestamp, errors::CodecCommonError};
+use codec_av1::{
+    av1_codec_configuration_record::Av1CodecConfigurationRecord,
+    obu::{ObuType, reader::split_obus},
+    sequence_header::SequenceHeaderObu,
+};
 use codec_h264::{
-    avc_decoder_configuration_record::AvcDecoderConfigurationRecord, nalu_type::NALUType,
+    avc_decoder_configuration_record::AvcDecoderConfigurationRecord,
+    nalu_type::NALUType,
+    sei::{RecoveryPoint, Sei},
 };
+use std::{str::FromStr, time::Instant};
+use tokio_util::bytes::Bytes;
 use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -21,6 +30,20 @@ pub enum VideoCodecCommon {
 }
 
 impl VideoCodecCommon {
+    pub(crate) const ALL: [Self; 10] = [
+        Self::SorensonH263,
+        Self::ScreenVideo,
+        Self::On2VP6,
+        Self::On2VP6WithAlpha,
+        Self::ScreenVideoV2,
+        Self::AVC,
+        Self::HEVC,
+        Self::VP8,
+        Self::VP9,
+        Self::AV1,
+    ];
+
+    /// the canonical name, also the serialized form with the serde feature
     pub fn get_codec_name(&self) -> &'static str {
         match self {
             Self::SorensonH263 => "SorensonH263",
@@ -37,11 +60,53 @@ impl VideoCodecCommon {
     }
 }
 
+impl FromStr for VideoCodecCommon {
+    type Err = CodecCommonError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|codec| codec.get_codec_name() == s)
+            .ok_or_else(|| CodecCommonError::UnknownCodecName(s.to_owned()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for VideoCodecCommon {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.get_codec_name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VideoCodecCommon {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VideoFrameInfo {
     pub codec_id: VideoCodecCommon,
     pub frame_type: FrameType,
     pub timestamp: MediaFrameTimestamp,
+    /// wallclock the frame entered the server from its publisher,
+    /// only set if latency measurement is enabled
+    pub ingest_time: Option<Instant>,
+    /// set on a frame carrying a recovery point, decoding may start at it
+    /// and the output is exact this many frames later
+    pub recovery_frame_cnt: Option<u64>,
+    /// set on an access unit whose slices do not cover its picture,
+    /// sinks may drop it or conceal the picture
+    pub corrupt: bool,
+    /// the timestamps the publisher sent the frame with, set if the stream center normalized them
+    pub publisher_timestamp: Option<MediaFrameTimestamp>,
+    /// set on the first frame after the timeline of the stream was anchored again,
+    /// sinks may start over, e.g., with a new segment
+    pub discontinuity: bool,
+    /// crc of the payload as it entered the stream center, see [`crate::frame_crc`]
+    #[cfg(feature = "frame-crc")]
+    pub payload_crc: Option<u32>,
 }
 
 impl VideoFrameInfo {
@@ -54,6 +119,13 @@ impl VideoFrameInfo {
             codec_id,
             frame_type,
             timestamp,
+            ingest_time: None,
+            recovery_frame_cnt: None,
+            corrupt: false,
+            publisher_timestamp: None,
+            discontinuity: false,
+            #[cfg(feature = "frame-crc")]
+            payload_crc: None,
         }
     }
 }
@@ -67,12 +139,53 @@ pub struct H264VideoConfig {
         Option<codec_h264::avc_decoder_configuration_record::AvcDecoderConfigurationRecord>,
 }
 
+impl H264VideoConfig {
+    /// the fixed frame rate the sps declares, None if there is no sps or it declares none
+    pub fn nominal_frame_rate(&self) -> Option<f64> {
+        self.sps.as_ref().and_then(|sps| sps.nominal_frame_rate())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Av1VideoConfig {
+    pub sequence_header: Option<SequenceHeaderObu>,
+    pub av1_codec_configuration_record: Option<Av1CodecConfigurationRecord>,
+}
+
+// media frames box the config already
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum VideoConfig {
     H264(H264VideoConfig),
+    AV1(Av1VideoConfig),
     // TODO
 }
 
+impl VideoConfig {
+    /// (width, height) in pixels, None if the config carries no sequence level header
+    pub fn dimensions(&self) -> Option<(u64, u64)> {
+        match self {
+            Self::H264(H264VideoConfig { sps, .. }) => sps
+                .as_ref()
+                .map(|sps| (sps.get_video_width(), sps.get_video_height())),
+            Self::AV1(Av1VideoConfig {
+                sequence_header, ..
+            }) => sequence_header
+                .as_ref()
+                .map(|header| (header.get_video_width(), header.get_video_height())),
+        }
+    }
+
+    /// frames per second the sequence level header declares, to be trusted over the
+    /// frame rate measured from the timestamps
+    pub fn nominal_frame_rate(&self) -> Option<f64> {
+        match self {
+            Self::H264(config) => config.nominal_frame_rate(),
+            Self::AV1(_) => None,
+        }
+    }
+}
+
 impl From<AvcDecoderConfigurationRecord> for VideoConfig {
     fn from(value: AvcDecoderConfigurationRecord) -> Self {
         let sps = value
@@ -98,10 +211,24 @@ impl From<AvcDecoderConfigurationRecord> for VideoConfig {
     }
 }
 
+impl From<Av1CodecConfigurationRecord> for VideoConfig {
+    fn from(value: Av1CodecConfigurationRecord) -> Self {
+        let sequence_header = value.sequence_header().unwrap_or_else(|err| {
+            tracing::warn!("parse av1 sequence header from config obus failed: {}", err);
+            None
+        });
+        VideoConfig::AV1(Av1VideoConfig {
+            sequence_header,
+            av1_codec_configuration_record: Some(value),
+        })
+    }
+}
+
 impl From<&VideoConfig> for VideoCodecCommon {
     fn from(value: &VideoConfig) -> Self {
         match value {
             VideoConfig::H264 { .. } => VideoCodecCommon::AVC,
+            VideoConfig::AV1 { .. } => VideoCodecCommon::AV1,
             // TODO
         }
     }
@@ -112,6 +239,8 @@ pub enum VideoFrameUnit {
     H264 {
         nal_units: Vec<codec_h264::nalu::NalUnit>,
     },
+    /// a temporal unit, the obus in the low overhead bitstream format
+    AV1 { obus: Bytes },
     // TODO
 }
 
@@ -119,11 +248,14 @@ impl VideoFrameUnit {
     pub fn units_cnt(&self) -> usize {
         match self {
             Self::H264 { nal_units } => nal_units.len(),
+            Self::AV1 { .. } => 1,
         }
     }
 
     pub fn bytes_cnt(&self, delimiter_size: usize) -> usize {
         match self {
+            // obus carry their own sizes, nothing delimits them
+            Self::AV1 { obus } => return obus.len(),
             Self::H264 { nal_units } => nal_units
                 .iter()
                 .fold(0, |prev, item| prev + item.get_packet_bytes_count()),
@@ -137,6 +269,22 @@ impl VideoFrameUnit {
             Self::H264 { nal_units } => nal_units
                 .iter()
                 .any(|v| matches!(v.header.nal_unit_type, NALUType::IDRSlice)),
+            // encoders repeat the sequence header on every key frame
+            Self::AV1 { obus } => split_obus(obus).is_ok_and(|obus| {
+                obus.iter()
+                    .any(|obu| obu.obu_type() == ObuType::SequenceHeader)
+            }),
+        }
+    }
+
+    /// the recovery point sei sent along with the frame, intra refresh encoders send it instead of IDRs
+    pub fn recovery_point(&self) -> Option<RecoveryPoint> {
+        match self {
+            Self::H264 { nal_units } => nal_units
+                .iter()
+                .filter(|v| matches!(v.header.nal_unit_type, NALUType::SEI))
+                .find_map(|v| Sei::try_from(v).ok()?.recovery_point()),
+            Self::AV1 { .. } => None,
         }
     }
 }
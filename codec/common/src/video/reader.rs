@@ -98,6 +98,13 @@ pub fn parse_to_nal_units(
             let nal_units = parse_to_avc_nal_units(nalu_bytes)?;
             Ok(VideoFrameUnit::H264 { nal_units })
         }
+        VideoCodecCommon::AV1 => {
+            // the obus are kept as is, only make sure they are well formed
+            codec_av1::obu::reader::split_obus(bytes)?;
+            Ok(VideoFrameUnit::AV1 {
+                obus: Bytes::copy_from_slice(bytes),
+            })
+        }
         _ => unimplemented!(),
     }
 }
@@ -17,6 +17,7 @@ impl<W: io::Write> WriteTo<W> for VideoFrameUnit {
                 unit.write_to(writer)?;
                 Ok::<(), Self::Error>(())
             })?,
+            Self::AV1 { obus } => writer.write_all(obus)?,
         }
         Ok(())
     }
@@ -56,6 +57,7 @@ impl<'a, W: io::Write> WriteTo<W> for VideoFrameUnitAvccWriter<'a> {
                 unit.write_to(writer)?;
                 Ok::<(), Self::Error>(())
             })?,
+            VideoFrameUnit::AV1 { obus } => writer.write_all(obus)?,
         }
         Ok(())
     }
@@ -0,0 +1,126 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use num::ToPrimitive;
+use std::io;
+use tokio_util::bytes::Buf;
+
+use crate::errors::{H264CodecError, H264CodecResult};
+
+#[cfg(test)]
+mod test;
+
+/// the 4 bytes start code written ahead of every nalu, Annex B.1
+pub const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// the byte count of a nalu length prefix,
+/// lengthSizeMinusOne of the avc decoder configuration record can only be 0, 1 or 3
+pub fn nalu_length_size(length_size_minus_one: u8) -> H264CodecResult<usize> {
+    match length_size_minus_one {
+        0 | 1 | 3 => Ok(length_size_minus_one as usize + 1),
+        _ => Err(H264CodecError::InvalidLengthSizeMinusOne(
+            length_size_minus_one,
+        )),
+    }
+}
+
+/// splits an AnnexB byte stream by 3 or 4 bytes start codes, the start codes are not included
+pub fn split_annexb(bytes: &[u8]) -> Vec<&[u8]> {
+    let mut nalus = vec![];
+    let mut start = None;
+    let mut i = 0;
+    while i + 3 <= bytes.len() {
+        if bytes[i..i + 3] == [0, 0, 1] {
+            if let Some(start) = start {
+                let mut end = i;
+                // the zero byte of a 4 bytes start code belongs to the start code
+                while end > start && bytes[end - 1] == 0 {
+                    end -= 1;
+                }
+                nalus.push(&bytes[start..end]);
+            }
+            i += 3;
+            start = Some(i);
+            continue;
+        }
+        i += 1;
+    }
+    if let Some(start) = start
+        && start < bytes.len()
+    {
+        nalus.push(&bytes[start..]);
+    }
+    nalus.retain(|nalu| !nalu.is_empty());
+    nalus
+}
+
+/// splits length prefixed nalus, the prefixes are not included
+pub fn split_avcc(bytes: &[u8], length_size_minus_one: u8) -> H264CodecResult<Vec<&[u8]>> {
+    let length_size = nalu_length_size(length_size_minus_one)?;
+    let mut nalus = vec![];
+    let mut cursor = io::Cursor::new(bytes);
+    while cursor.has_remaining() {
+        let nalu_size = cursor
+            .read_uint::<BigEndian>(length_size)?
+            .to_usize()
+            .unwrap();
+        let start = cursor.position() as usize;
+        if nalu_size > cursor.remaining() {
+            return Err(H264CodecError::SyntaxError(format!(
+                "nalu of {} bytes overruns the remaining {} bytes",
+                nalu_size,
+                cursor.remaining()
+            )));
+        }
+        cursor.advance(nalu_size);
+        nalus.push(&bytes[start..start + nalu_size]);
+    }
+    Ok(nalus)
+}
+
+/// writes each nalu behind a 4 bytes start code
+pub fn write_annexb<'a, W: io::Write>(
+    nalus: impl IntoIterator<Item = &'a [u8]>,
+    writer: &mut W,
+) -> H264CodecResult<()> {
+    for nalu in nalus {
+        writer.write_all(&START_CODE)?;
+        writer.write_all(nalu)?;
+    }
+    Ok(())
+}
+
+/// writes each nalu behind its length, in 1, 2 or 4 bytes by length_size_minus_one
+pub fn write_avcc<'a, W: io::Write>(
+    nalus: impl IntoIterator<Item = &'a [u8]>,
+    length_size_minus_one: u8,
+    writer: &mut W,
+) -> H264CodecResult<()> {
+    let length_size = nalu_length_size(length_size_minus_one)?;
+    for nalu in nalus {
+        if nalu.len() as u64 >= 1_u64 << (length_size * 8) {
+            return Err(H264CodecError::SyntaxError(format!(
+                "nalu of {} bytes does not fit in a {} bytes length prefix",
+                nalu.len(),
+                length_size
+            )));
+        }
+        writer.write_uint::<BigEndian>(nalu.len() as u64, length_size)?;
+        writer.write_all(nalu)?;
+    }
+    Ok(())
+}
+
+/// length prefixed nalus, as in flv and mp4, to an AnnexB byte stream, as in mpeg-ts
+pub fn avcc_to_annexb(bytes: &[u8], length_size_minus_one: u8) -> H264CodecResult<Vec<u8>> {
+    let nalus = split_avcc(bytes, length_size_minus_one)?;
+    let mut result = Vec::with_capacity(bytes.len() + nalus.len() * START_CODE.len());
+    write_annexb(nalus, &mut result)?;
+    Ok(result)
+}
+
+/// an AnnexB byte stream to length prefixed nalus
+pub fn annexb_to_avcc(bytes: &[u8], length_size_minus_one: u8) -> H264CodecResult<Vec<u8>> {
+    let nalus = split_annexb(bytes);
+    let mut result = Vec::with_capacity(bytes.len());
+    write_avcc(nalus, length_size_minus_one, &mut result)?;
+    Ok(result)
+}
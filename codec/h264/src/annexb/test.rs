@@ -0,0 +1,89 @@
+use crate::{
+    annexb::{annexb_to_avcc, avcc_to_annexb, split_annexb, split_avcc},
+    errors::H264CodecError,
+};
+
+const SPS: [u8; 4] = [0x67, 0x42, 0xC0, 0x1E];
+const PPS: [u8; 3] = [0x68, 0xCE, 0x3C];
+// an emulation prevention byte inside, it must survive the conversions
+const IDR_SLICE: [u8; 7] = [0x65, 0x88, 0x00, 0x00, 0x03, 0x01, 0xFF];
+
+fn annexb() -> Vec<u8> {
+    [
+        &[0, 0, 0, 1][..],
+        &SPS,
+        &[0, 0, 0, 1],
+        &PPS,
+        &[0, 0, 0, 1],
+        &IDR_SLICE,
+    ]
+    .concat()
+}
+
+fn avcc_4() -> Vec<u8> {
+    [
+        &[0, 0, 0, 4][..],
+        &SPS,
+        &[0, 0, 0, 3],
+        &PPS,
+        &[0, 0, 0, 7],
+        &IDR_SLICE,
+    ]
+    .concat()
+}
+
+#[test]
+fn test_split_annexb_with_mixed_start_codes() {
+    let bytes = [
+        &[0, 0, 1][..],
+        &SPS,
+        &[0, 0, 0, 1],
+        &PPS,
+        &[0, 0, 1],
+        &IDR_SLICE,
+    ]
+    .concat();
+    assert_eq!(split_annexb(&bytes), vec![&SPS[..], &PPS, &IDR_SLICE]);
+}
+
+#[test]
+fn test_avcc_to_annexb() {
+    assert_eq!(avcc_to_annexb(&avcc_4(), 3).unwrap(), annexb());
+
+    let avcc_2 = [&[0, 4][..], &SPS, &[0, 3], &PPS, &[0, 7], &IDR_SLICE].concat();
+    assert_eq!(avcc_to_annexb(&avcc_2, 1).unwrap(), annexb());
+
+    let avcc_1 = [&[4][..], &SPS, &[3], &PPS, &[7], &IDR_SLICE].concat();
+    assert_eq!(avcc_to_annexb(&avcc_1, 0).unwrap(), annexb());
+}
+
+#[test]
+fn test_annexb_to_avcc() {
+    assert_eq!(annexb_to_avcc(&annexb(), 3).unwrap(), avcc_4());
+    assert_eq!(
+        split_avcc(&annexb_to_avcc(&annexb(), 1).unwrap(), 1).unwrap(),
+        vec![&SPS[..], &PPS, &IDR_SLICE]
+    );
+    assert_eq!(
+        avcc_to_annexb(&annexb_to_avcc(&annexb(), 0).unwrap(), 0).unwrap(),
+        annexb()
+    );
+}
+
+#[test]
+fn test_invalid_avcc() {
+    assert!(matches!(
+        avcc_to_annexb(&avcc_4(), 2),
+        Err(H264CodecError::InvalidLengthSizeMinusOne(2))
+    ));
+    let truncated = &avcc_4()[..avcc_4().len() - 1];
+    assert!(matches!(
+        avcc_to_annexb(truncated, 3),
+        Err(H264CodecError::SyntaxError(_))
+    ));
+    let oversized = [&[0, 0, 0, 1][..], &[0x65; 256]].concat();
+    assert!(matches!(
+        annexb_to_avcc(&oversized, 0),
+        Err(H264CodecError::SyntaxError(_))
+    ));
+}
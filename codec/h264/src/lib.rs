@@ -1,13 +1,18 @@
+pub mod annexb;
 pub mod avc_decoder_configuration_record;
 pub mod errors;
 mod exp_golomb;
 pub mod nalu;
 pub mod nalu_header;
 pub mod nalu_type;
+pub mod poc;
 pub mod pps;
 pub mod rbsp;
 pub mod reader;
 pub mod scaling_list;
+pub mod sei;
+pub mod slice_coverage;
+pub mod slice_header;
 pub mod sps;
 pub mod sps_ext;
 pub mod vui;
@@ -55,6 +55,21 @@ impl From<NALUType> for u8 {
     }
 }
 
+impl NALUType {
+    /// @see: Recommendation  ITU-T H.264 (V15) (08/2024)   – Coding of moving video
+    /// Table 7-1, nal_unit_type 1 to 5 are VCL NAL units
+    pub fn is_vcl(&self) -> bool {
+        matches!(
+            self,
+            Self::NonIDRSlice
+                | Self::DataPartitionASlice
+                | Self::DataPartitionBSlice
+                | Self::DataPartitionCSlice
+                | Self::IDRSlice
+        )
+    }
+}
+
 pub const H264_NALU_TYPE_U8_MASK: u8 = 0b11111;
 
 impl TryFrom<u8> for NALUType {
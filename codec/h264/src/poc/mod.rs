@@ -0,0 +1,165 @@
+use crate::{
+    errors::{H264CodecError, H264CodecResult},
+    slice_header::SliceHeaderPicOrder,
+    sps::Sps,
+};
+
+#[cfg(test)]
+mod test;
+
+/// Derives the picture order count of the pictures fed in decoding order.
+/// memory_management_control_operation 5 is not looked for,
+/// the pictures after one are counted as if it was not there
+/// @see: Recommendation  ITU-T H.264 (V15) (08/2024)   – Coding of moving video
+/// Section 8.2.1 Decoding process for picture order count
+#[derive(Debug, Default, Clone)]
+pub struct PicOrderCounter {
+    /// of the previous reference picture, pic_order_cnt_type 0
+    prev_pic_order_cnt_msb: i64,
+    prev_pic_order_cnt_lsb: i64,
+    /// of the previous picture, pic_order_cnt_type 1 and 2
+    prev_frame_num: u64,
+    prev_frame_num_offset: i64,
+}
+
+impl PicOrderCounter {
+    /// PicOrderCnt of the picture the first slice of which is `header`,
+    /// the smaller one of TopFieldOrderCnt and BottomFieldOrderCnt for a frame
+    pub fn next(
+        &mut self,
+        nal_ref_idc: u8,
+        idr_pic_flag: bool,
+        sps: &Sps,
+        header: &SliceHeaderPicOrder,
+    ) -> H264CodecResult<i64> {
+        let result = match sps.pic_order_cnt_type {
+            0 => self.pic_order_cnt_type_0(nal_ref_idc, idr_pic_flag, sps, header),
+            1 => Ok(self.pic_order_cnt_type_1(nal_ref_idc, idr_pic_flag, sps, header)),
+            2 => Ok(self.pic_order_cnt_type_2(nal_ref_idc, idr_pic_flag, sps, header)),
+            v => Err(H264CodecError::SyntaxError(format!(
+                "pic_order_cnt_type out of range: {}",
+                v
+            ))),
+        };
+        self.prev_frame_num = header.frame_num;
+        result
+    }
+
+    /// Section 8.2.1.1
+    fn pic_order_cnt_type_0(
+        &mut self,
+        nal_ref_idc: u8,
+        idr_pic_flag: bool,
+        sps: &Sps,
+        header: &SliceHeaderPicOrder,
+    ) -> H264CodecResult<i64> {
+        let (Some(log2_max_lsb_minus4), Some(lsb)) = (
+            sps.log2_max_pic_order_cnt_lsb_minus4,
+            header.pic_order_cnt_lsb,
+        ) else {
+            return Err(H264CodecError::SyntaxError(
+                "pic_order_cnt_type 0 without pic_order_cnt_lsb".to_owned(),
+            ));
+        };
+        if idr_pic_flag {
+            self.prev_pic_order_cnt_msb = 0;
+            self.prev_pic_order_cnt_lsb = 0;
+        }
+        let max_lsb = 1_i64 << (log2_max_lsb_minus4 + 4);
+        let lsb = lsb as i64;
+        let (prev_msb, prev_lsb) = (self.prev_pic_order_cnt_msb, self.prev_pic_order_cnt_lsb);
+        let msb = if lsb < prev_lsb && prev_lsb - lsb >= max_lsb / 2 {
+            prev_msb + max_lsb
+        } else if lsb > prev_lsb && lsb - prev_lsb > max_lsb / 2 {
+            prev_msb - max_lsb
+        } else {
+            prev_msb
+        };
+        if nal_ref_idc != 0 {
+            self.prev_pic_order_cnt_msb = msb;
+            self.prev_pic_order_cnt_lsb = lsb;
+        }
+        let top = msb + lsb;
+        if header.field_pic_flag {
+            // a field has only the order count of its own parity
+            return Ok(top);
+        }
+        Ok(top.min(top + header.delta_pic_order_cnt_bottom.unwrap_or(0)))
+    }
+
+    /// FrameNumOffset, Section 8.2.1.2 and 8.2.1.3
+    fn frame_num_offset(&mut self, idr_pic_flag: bool, sps: &Sps, frame_num: u64) -> i64 {
+        let offset = if idr_pic_flag {
+            0
+        } else if self.prev_frame_num > frame_num {
+            self.prev_frame_num_offset + (1_i64 << (sps.log2_max_frame_num_minus4 + 4))
+        } else {
+            self.prev_frame_num_offset
+        };
+        self.prev_frame_num_offset = offset;
+        offset
+    }
+
+    /// Section 8.2.1.2
+    fn pic_order_cnt_type_1(
+        &mut self,
+        nal_ref_idc: u8,
+        idr_pic_flag: bool,
+        sps: &Sps,
+        header: &SliceHeaderPicOrder,
+    ) -> i64 {
+        let frame_num_offset = self.frame_num_offset(idr_pic_flag, sps, header.frame_num);
+        let Some(type_1) = sps.pic_order_cnt_type_1.as_ref() else {
+            return 0;
+        };
+        let cycle_length = type_1.offset_for_ref_frame.len() as i64;
+        let mut abs_frame_num = if cycle_length != 0 {
+            frame_num_offset + header.frame_num as i64
+        } else {
+            0
+        };
+        if nal_ref_idc == 0 && abs_frame_num > 0 {
+            abs_frame_num -= 1;
+        }
+        let mut expected = 0;
+        if abs_frame_num > 0 {
+            let cycle_count = (abs_frame_num - 1) / cycle_length;
+            let frame_num_in_cycle = (abs_frame_num - 1) % cycle_length;
+            let expected_delta_per_cycle: i64 = type_1.offset_for_ref_frame.iter().sum();
+            expected = cycle_count * expected_delta_per_cycle
+                + type_1.offset_for_ref_frame[..=frame_num_in_cycle as usize]
+                    .iter()
+                    .sum::<i64>();
+        }
+        if nal_ref_idc == 0 {
+            expected += type_1.offset_for_non_ref_pic;
+        }
+        let [delta_0, delta_1] = header.delta_pic_order_cnt;
+        if !header.field_pic_flag {
+            let top = expected + delta_0;
+            top.min(top + type_1.offset_for_top_to_bottom_field + delta_1)
+        } else if !header.bottom_field_flag {
+            expected + delta_0
+        } else {
+            expected + type_1.offset_for_top_to_bottom_field + delta_0
+        }
+    }
+
+    /// Section 8.2.1.3, the output order is the decoding order
+    fn pic_order_cnt_type_2(
+        &mut self,
+        nal_ref_idc: u8,
+        idr_pic_flag: bool,
+        sps: &Sps,
+        header: &SliceHeaderPicOrder,
+    ) -> i64 {
+        let frame_num_offset = self.frame_num_offset(idr_pic_flag, sps, header.frame_num);
+        if idr_pic_flag {
+            0
+        } else if nal_ref_idc == 0 {
+            2 * (frame_num_offset + header.frame_num as i64) - 1
+        } else {
+            2 * (frame_num_offset + header.frame_num as i64)
+        }
+    }
+}
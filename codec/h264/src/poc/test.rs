@@ -0,0 +1,202 @@
+use bitstream_io::{BigEndian, BitWrite, BitWriter};
+use tokio_util::bytes::Bytes;
+
+use crate::{
+    exp_golomb::{write_se, write_ue},
+    nalu::NalUnit,
+    nalu_header::NaluHeader,
+    poc::PicOrderCounter,
+    pps::Pps,
+    slice_header::SliceHeaderPicOrder,
+    sps::{Sps, chroma_format_idc::ChromaFormatIdc},
+};
+
+/// log2_max_frame_num = 4, log2_max_pic_order_cnt_lsb = 6
+const FRAME_NUM_BITS: u32 = 4;
+const POC_LSB_BITS: u32 = 6;
+
+fn nalu(nal_header: u8, write: impl FnOnce(&mut BitWriter<&mut Vec<u8>, BigEndian>)) -> NalUnit {
+    let mut bytes = Vec::new();
+    let mut writer = BitWriter::endian(&mut bytes, BigEndian);
+    write(&mut writer);
+    writer.write_bit(true).unwrap();
+    writer.byte_align().unwrap();
+    NalUnit {
+        header: NaluHeader::try_from(nal_header).unwrap(),
+        body: Bytes::from(bytes),
+    }
+}
+
+/// main profile, progressive
+fn sps(pic_order_cnt_type: u64) -> Sps {
+    let nalu = nalu(0x67, |writer| {
+        writer.write::<8, u8>(77).unwrap();
+        writer.write::<8, u8>(0).unwrap();
+        writer.write::<8, u8>(30).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, FRAME_NUM_BITS as u64 - 4).unwrap();
+        write_ue(writer, pic_order_cnt_type).unwrap();
+        match pic_order_cnt_type {
+            0 => write_ue(writer, POC_LSB_BITS as u64 - 4).unwrap(),
+            1 => {
+                // delta_pic_order_always_zero_flag
+                writer.write_bit(true).unwrap();
+                // offset_for_non_ref_pic, offset_for_top_to_bottom_field
+                write_se(writer, -2_i64).unwrap();
+                write_se(writer, 0_i64).unwrap();
+                // one reference frame per cycle, 4 apart
+                write_ue(writer, 1_u8).unwrap();
+                write_se(writer, 4_i64).unwrap();
+            }
+            _ => {}
+        }
+        // max_num_ref_frames, gaps_in_frame_num_value_allowed_flag
+        write_ue(writer, 2_u8).unwrap();
+        writer.write_bit(false).unwrap();
+        // 320x240
+        write_ue(writer, 19_u8).unwrap();
+        write_ue(writer, 14_u8).unwrap();
+        // frame_mbs_only_flag, direct_8x8_inference_flag, frame_cropping_flag, vui_parameters_present_flag
+        writer.write_bit(true).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+    });
+    Sps::try_from(&nalu).unwrap()
+}
+
+fn pps() -> Pps {
+    let nalu = nalu(0x68, |writer| {
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        // entropy_coding_mode_flag, bottom_field_pic_order_in_frame_present_flag
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+        // num_slice_groups_minus1, num_ref_idx_l0/l1_default_active_minus1
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        // weighted_pred_flag, weighted_bipred_idc
+        writer.write_bit(false).unwrap();
+        writer.write::<2, u8>(0).unwrap();
+        // pic_init_qp_minus26, pic_init_qs_minus26, chroma_qp_index_offset
+        write_se(writer, 0_i64).unwrap();
+        write_se(writer, 0_i64).unwrap();
+        write_se(writer, 0_i64).unwrap();
+        // deblocking_filter_control_present_flag, constrained_intra_pred_flag, redundant_pic_cnt_present_flag
+        writer.write_bit(true).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+    });
+    Pps::try_from((ChromaFormatIdc::Chroma420, &nalu)).unwrap()
+}
+
+/// the first slice of a picture, `kind` is 'I' for an idr, 'P' or 'B' otherwise, B slices are not referenced
+fn slice(kind: char, frame_num: u64, pic_order_cnt_lsb: u64) -> NalUnit {
+    let (nal_header, slice_type) = match kind {
+        'I' => (0x65, 7_u8),
+        'P' => (0x61, 5),
+        _ => (0x01, 6),
+    };
+    nalu(nal_header, |writer| {
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, slice_type).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        writer.write_var(FRAME_NUM_BITS, frame_num).unwrap();
+        if kind == 'I' {
+            write_ue(writer, 0_u8).unwrap();
+        }
+        writer.write_var(POC_LSB_BITS, pic_order_cnt_lsb).unwrap();
+        // the rest of the slice
+        writer.write::<16, u16>(0xABCD).unwrap();
+    })
+}
+
+fn pic_order_cnts(sps: &Sps, slices: &[NalUnit]) -> Vec<i64> {
+    let pps = pps();
+    let mut counter = PicOrderCounter::default();
+    slices
+        .iter()
+        .map(|slice| {
+            let header = SliceHeaderPicOrder::try_from((slice, sps, &pps)).unwrap();
+            counter
+                .next(
+                    slice.header.nal_ref_idc,
+                    header.idr_pic_id.is_some(),
+                    sps,
+                    &header,
+                )
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn test_slice_header_pic_order_fields() {
+    let sps = sps(0);
+    let header = SliceHeaderPicOrder::try_from((&slice('I', 0, 0), &sps, &pps())).unwrap();
+    assert_eq!(header.prefix.slice_type, 7);
+    assert_eq!(header.idr_pic_id, Some(0));
+    assert_eq!(header.pic_order_cnt_lsb, Some(0));
+    assert!(!header.field_pic_flag);
+
+    let header = SliceHeaderPicOrder::try_from((&slice('B', 3, 42), &sps, &pps())).unwrap();
+    assert_eq!(header.frame_num, 3);
+    assert_eq!(header.idr_pic_id, None);
+    assert_eq!(header.pic_order_cnt_lsb, Some(42));
+    assert_eq!(header.delta_pic_order_cnt_bottom, None);
+}
+
+#[test]
+fn test_pic_order_cnt_type_0_with_b_frames() {
+    // I P B B P B B in decoding order, I B B P B B P in output order
+    let slices = [
+        slice('I', 0, 0),
+        slice('P', 1, 6),
+        slice('B', 2, 2),
+        slice('B', 2, 4),
+        slice('P', 2, 12),
+        slice('B', 3, 8),
+        slice('B', 3, 10),
+    ];
+    assert_eq!(pic_order_cnts(&sps(0), &slices), [0, 6, 2, 4, 12, 8, 10]);
+}
+
+#[test]
+fn test_pic_order_cnt_type_0_lsb_wraps() {
+    let mut slices = vec![slice('I', 0, 0)];
+    slices.extend((1..=10).map(|i| slice('P', i % 16, (i * 8) % 64)));
+    // a new idr starts over
+    slices.push(slice('I', 0, 0));
+    assert_eq!(
+        pic_order_cnts(&sps(0), &slices),
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 72, 80, 0]
+    );
+}
+
+#[test]
+fn test_pic_order_cnt_type_1() {
+    // I P B P B, the b frames are 2 before the next reference frame
+    let slices = [
+        slice('I', 0, 0),
+        slice('P', 1, 0),
+        slice('B', 2, 0),
+        slice('P', 2, 0),
+        slice('B', 3, 0),
+    ];
+    let sps = sps(1);
+    // no pic_order_cnt_lsb is written for type 1, the lsb bits are the rest of the slice then
+    assert_eq!(pic_order_cnts(&sps, &slices), [0, 4, 2, 8, 6]);
+}
+
+#[test]
+fn test_pic_order_cnt_type_2_follows_decoding_order() {
+    let mut slices = vec![slice('I', 0, 0)];
+    slices.extend((1..=17).map(|i| slice('P', i % 16, 0)));
+    slices.push(slice('B', 2, 0));
+    let pocs = pic_order_cnts(&sps(2), &slices);
+    assert_eq!(pocs[..4], [0, 2, 4, 6]);
+    // frame_num wrapped at 16
+    assert_eq!(pocs[16..], [32, 34, 35]);
+    assert!(pocs.windows(2).all(|pair| pair[0] < pair[1]));
+}
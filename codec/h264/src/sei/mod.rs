@@ -0,0 +1,65 @@
+use codec_bitstream::reader::BitstreamReader;
+use tokio_util::bytes::Bytes;
+use utils::traits::reader::BitwiseReadFrom;
+
+use crate::errors::H264CodecError;
+
+pub mod reader;
+#[cfg(test)]
+mod test;
+
+/// @see: Recommendation  ITU-T H.264 (V15) (08/2024)   – Coding of moving video
+/// Annex D.1 SEI payload syntax
+pub const SEI_PAYLOAD_TYPE_RECOVERY_POINT: u32 = 6;
+
+/// @see: Recommendation  ITU-T H.264 (V15) (08/2024)   – Coding of moving video
+/// Section 7.3.2.3.1 Supplemental enhancement information message syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeiMessage {
+    pub payload_type: u32,
+    pub payload: Bytes,
+}
+
+/// the messages of a sei nal unit, in the order they are sent
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sei {
+    pub messages: Vec<SeiMessage>,
+}
+
+impl Sei {
+    /// the first recovery point message that parses, if any
+    pub fn recovery_point(&self) -> Option<RecoveryPoint> {
+        self.messages
+            .iter()
+            .filter(|message| message.payload_type == SEI_PAYLOAD_TYPE_RECOVERY_POINT)
+            .find_map(|message| RecoveryPoint::try_from(message).ok())
+    }
+}
+
+/// decoding may start at the picture carrying it,
+/// the output is correct from recovery_frame_cnt pictures on,
+/// it is what intra refresh encoders send instead of IDR pictures
+/// @see: Recommendation  ITU-T H.264 (V15) (08/2024)   – Coding of moving video
+/// Section D.1.8 Recovery point SEI message syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryPoint {
+    pub recovery_frame_cnt: u64,      // ue(v)
+    pub exact_match_flag: bool,       // u(1)
+    pub broken_link_flag: bool,       // u(1)
+    pub changing_slice_group_idc: u8, // u(2)
+}
+
+impl TryFrom<&SeiMessage> for RecoveryPoint {
+    type Error = H264CodecError;
+
+    fn try_from(message: &SeiMessage) -> Result<Self, Self::Error> {
+        if message.payload_type != SEI_PAYLOAD_TYPE_RECOVERY_POINT {
+            return Err(H264CodecError::SyntaxError(format!(
+                "not a recovery point sei, payload type: {}",
+                message.payload_type
+            )));
+        }
+        let mut reader = BitstreamReader::new(&message.payload);
+        Self::read_from(&mut reader)
+    }
+}
@@ -0,0 +1,75 @@
+use bitstream_io::BitRead;
+use tokio_util::bytes::{Buf, Bytes};
+use utils::traits::reader::BitwiseReadFrom;
+
+use crate::{errors::H264CodecError, exp_golomb::read_ue, nalu::NalUnit, nalu_type::NALUType};
+
+use super::{RecoveryPoint, Sei, SeiMessage};
+
+/// the rbsp_trailing_bits() of a sei rbsp are a whole byte, sei messages are byte aligned
+const RBSP_TRAILING_BYTE: u8 = 0x80;
+
+/// payloadType and payloadSize are sent as a run of 0xFF bytes and a last byte, all summed up
+fn read_ff_coded(body: &mut Bytes) -> Result<u32, H264CodecError> {
+    let mut value: u32 = 0;
+    loop {
+        if !body.has_remaining() {
+            return Err(H264CodecError::SyntaxError(
+                "sei message ends in its header".to_owned(),
+            ));
+        }
+        let byte = body.get_u8();
+        value = value.checked_add(byte as u32).ok_or_else(|| {
+            H264CodecError::SyntaxError("sei payload type or size overflows".to_owned())
+        })?;
+        if byte != 0xFF {
+            return Ok(value);
+        }
+    }
+}
+
+impl TryFrom<&NalUnit> for Sei {
+    type Error = H264CodecError;
+
+    fn try_from(nalu: &NalUnit) -> Result<Self, Self::Error> {
+        if nalu.header.nal_unit_type != NALUType::SEI {
+            return Err(H264CodecError::UnknownNaluType(
+                nalu.header.nal_unit_type.into(),
+            ));
+        }
+        let mut body = nalu.body.clone();
+        let mut messages = vec![];
+        while body.has_remaining() && body[..] != [RBSP_TRAILING_BYTE] {
+            let payload_type = read_ff_coded(&mut body)?;
+            let payload_size = read_ff_coded(&mut body)? as usize;
+            if body.remaining() < payload_size {
+                return Err(H264CodecError::SyntaxError(format!(
+                    "sei payload of {} bytes, only {} left",
+                    payload_size,
+                    body.remaining()
+                )));
+            }
+            messages.push(SeiMessage {
+                payload_type,
+                payload: body.split_to(payload_size),
+            });
+        }
+        Ok(Self { messages })
+    }
+}
+
+impl<R: BitRead> BitwiseReadFrom<R> for RecoveryPoint {
+    type Error = H264CodecError;
+    fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
+        let recovery_frame_cnt = read_ue(reader)?;
+        let exact_match_flag = reader.read_bit()?;
+        let broken_link_flag = reader.read_bit()?;
+        let changing_slice_group_idc = reader.read::<2, u8>()?;
+        Ok(Self {
+            recovery_frame_cnt,
+            exact_match_flag,
+            broken_link_flag,
+            changing_slice_group_idc,
+        })
+    }
+}
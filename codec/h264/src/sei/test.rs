@@ -0,0 +1,64 @@
+use tokio_util::bytes::Bytes;
+
+use crate::{
+    nalu::NalUnit,
+    nalu_header::NaluHeader,
+    sei::{RecoveryPoint, SEI_PAYLOAD_TYPE_RECOVERY_POINT, Sei},
+};
+
+fn nal_unit(nal_header: u8, body: &[u8]) -> NalUnit {
+    NalUnit {
+        header: NaluHeader::try_from(nal_header).unwrap(),
+        body: Bytes::copy_from_slice(body),
+    }
+}
+
+#[test]
+fn test_recovery_point_after_user_data() {
+    let mut body = vec![5, 16];
+    body.extend_from_slice(&[0xAB; 16]);
+    // recovery_frame_cnt = 29 (000011110), exact_match_flag = 1, broken_link_flag = 0,
+    // changing_slice_group_idc = 0 (00), then the payload is aligned with 1 0 0
+    body.extend_from_slice(&[6, 2, 0b0000_1111, 0b0100_0100]);
+    body.push(0x80);
+
+    let sei = Sei::try_from(&nal_unit(0x06, &body)).unwrap();
+    assert_eq!(sei.messages.len(), 2);
+    assert_eq!(sei.messages[0].payload_type, 5);
+    assert_eq!(sei.messages[0].payload.len(), 16);
+    assert_eq!(
+        sei.messages[1].payload_type,
+        SEI_PAYLOAD_TYPE_RECOVERY_POINT
+    );
+    assert_eq!(
+        sei.recovery_point(),
+        Some(RecoveryPoint {
+            recovery_frame_cnt: 29,
+            exact_match_flag: true,
+            broken_link_flag: false,
+            changing_slice_group_idc: 0,
+        })
+    );
+}
+
+#[test]
+fn test_ff_coded_payload_type_and_size() {
+    // payload type 255 + 45 = 300, payload size 255 + 1 = 256
+    let mut body = vec![0xFF, 45, 0xFF, 1];
+    body.extend_from_slice(&[0; 256]);
+    body.push(0x80);
+
+    let sei = Sei::try_from(&nal_unit(0x06, &body)).unwrap();
+    assert_eq!(sei.messages.len(), 1);
+    assert_eq!(sei.messages[0].payload_type, 300);
+    assert_eq!(sei.messages[0].payload.len(), 256);
+    assert_eq!(sei.recovery_point(), None);
+}
+
+#[test]
+fn test_truncated_sei() {
+    assert!(Sei::try_from(&nal_unit(0x06, &[6, 4, 0x84])).is_err());
+    assert!(Sei::try_from(&nal_unit(0x06, &[0xFF])).is_err());
+    // not a sei nal unit
+    assert!(Sei::try_from(&nal_unit(0x41, &[6, 1, 0x84, 0x80])).is_err());
+}
@@ -0,0 +1,150 @@
+use codec_bitstream::reader::BitstreamReader;
+use utils::traits::reader::BitwiseReadFrom;
+
+use crate::{
+    errors::{H264CodecError, H264CodecResult},
+    nalu::NalUnit,
+    nalu_type::NALUType,
+    pps::Pps,
+    slice_header::{SliceHeaderPicOrder, SliceHeaderPrefix},
+    sps::Sps,
+};
+
+#[cfg(test)]
+mod test;
+
+/// the colour plane and the field of a picture, None for a frame
+type PictureKey = (Option<u8>, Option<bool>);
+
+/// The macroblocks the slices of one picture of an access unit start at,
+/// a picture is a frame or a field, of one colour plane
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureSlices {
+    /// PicSizeInMbs
+    pub pic_size_in_mbs: u64,
+    /// the macroblock addresses, in increasing order without the redundant ones
+    pub starts: Vec<u64>,
+}
+
+impl PictureSlices {
+    /// the pictures of the slices of an access unit, in the order they come.
+    /// `parameter_sets` gives the pps of an id and the sps it refers to
+    /// @see: Recommendation  ITU-T H.264 (V15) (08/2024)   – Coding of moving video
+    /// Section 7.4.3 Slice header semantics, first_mb_in_slice
+    pub fn of_access_unit<'a, 'p>(
+        nal_units: impl IntoIterator<Item = &'a NalUnit>,
+        parameter_sets: impl Fn(u64) -> Option<(&'p Sps, &'p Pps)>,
+    ) -> H264CodecResult<Vec<Self>> {
+        let mut pictures: Vec<(PictureKey, Self)> = Vec::new();
+        for nalu in nal_units {
+            // the other partitions of a slice carry no slice header
+            if !matches!(
+                nalu.header.nal_unit_type,
+                NALUType::NonIDRSlice | NALUType::DataPartitionASlice | NALUType::IDRSlice
+            ) {
+                continue;
+            }
+            let prefix = SliceHeaderPrefix::read_from(&mut BitstreamReader::new(&nalu.body))?;
+            let (sps, pps) = parameter_sets(prefix.pic_parameter_set_id).ok_or_else(|| {
+                H264CodecError::SyntaxError(format!(
+                    "slice refers to unknown pps {}",
+                    prefix.pic_parameter_set_id
+                ))
+            })?;
+            let header = SliceHeaderPicOrder::try_from((nalu, sps, pps))?;
+            let field = header.field_pic_flag.then_some(header.bottom_field_flag);
+            let mbaff = !header.field_pic_flag && sps.mb_adaptive_frame_field_flag == Some(true);
+            let start = prefix.first_mb_in_slice * (1 + mbaff as u64);
+            let key = (header.colour_plane_id, field);
+            match pictures.iter_mut().find(|(picture, _)| *picture == key) {
+                Some((_, picture)) => picture.starts.push(start),
+                None => pictures.push((
+                    key,
+                    Self {
+                        pic_size_in_mbs: sps.pic_size_in_mbs(header.field_pic_flag),
+                        starts: vec![start],
+                    },
+                )),
+            }
+        }
+        Ok(pictures
+            .into_iter()
+            .map(|(_, mut picture)| {
+                picture.starts.sort_unstable();
+                picture.starts.dedup();
+                picture
+            })
+            .collect())
+    }
+}
+
+/// how the slices of a picture are found not to cover it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoverageGap {
+    /// no slice starts at the first macroblock
+    FirstSliceMissing { first_mb: u64 },
+    /// a slice starts beyond the last macroblock
+    OutOfPicture { mb: u64, pic_size_in_mbs: u64 },
+    /// slices the stream puts in each of its pictures are not there
+    SlicesMissing { missing: Vec<u64> },
+}
+
+/// Tells the pictures of a stream whose slices do not cover them.
+/// where a slice ends is only known from its slice data, so a slice missing after the first one
+/// is told from the layout of the pictures before: once two pictures in a row start their slices
+/// at the same macroblocks, as encoders with a fixed slice count do, a picture with only some of
+/// them misses the others. streams whose slices are cut by size are only checked for the first
+/// slice and the picture size
+#[derive(Debug, Default, Clone)]
+pub struct SliceCoverage {
+    /// the layout of the stream
+    layout: Option<PictureSlices>,
+    /// the layout of the picture before, it becomes the one of the stream if it repeats
+    candidate: Option<PictureSlices>,
+}
+
+impl SliceCoverage {
+    pub fn check(&mut self, picture: &PictureSlices) -> Result<(), CoverageGap> {
+        match picture.starts.first() {
+            Some(0) => {}
+            first_mb => {
+                return Err(CoverageGap::FirstSliceMissing {
+                    first_mb: first_mb.copied().unwrap_or_default(),
+                });
+            }
+        }
+        if let Some(&mb) = picture.starts.last()
+            && mb >= picture.pic_size_in_mbs
+        {
+            return Err(CoverageGap::OutOfPicture {
+                mb,
+                pic_size_in_mbs: picture.pic_size_in_mbs,
+            });
+        }
+        if let Some(layout) = &self.layout
+            && layout.pic_size_in_mbs == picture.pic_size_in_mbs
+            && picture.starts.len() < layout.starts.len()
+            && picture
+                .starts
+                .iter()
+                .all(|start| layout.starts.binary_search(start).is_ok())
+        {
+            return Err(CoverageGap::SlicesMissing {
+                missing: layout
+                    .starts
+                    .iter()
+                    .filter(|start| picture.starts.binary_search(start).is_err())
+                    .copied()
+                    .collect(),
+            });
+        }
+        if self.layout.as_ref() != Some(picture) {
+            if self.candidate.as_ref() == Some(picture) {
+                self.layout = self.candidate.take();
+            } else {
+                self.candidate = Some(picture.clone());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use bitstream_io::{BigEndian, BitWrite, BitWriter};
+use tokio_util::bytes::Bytes;
+
+use crate::{
+    exp_golomb::{write_se, write_ue},
+    nalu::NalUnit,
+    nalu_header::NaluHeader,
+    pps::Pps,
+    slice_coverage::{CoverageGap, PictureSlices, SliceCoverage},
+    sps::{Sps, chroma_format_idc::ChromaFormatIdc},
+};
+
+/// 320x240 is 20x15 macroblocks
+const PIC_SIZE_IN_MBS: u64 = 300;
+
+fn nalu(nal_header: u8, write: impl FnOnce(&mut BitWriter<&mut Vec<u8>, BigEndian>)) -> NalUnit {
+    let mut bytes = Vec::new();
+    let mut writer = BitWriter::endian(&mut bytes, BigEndian);
+    write(&mut writer);
+    writer.write_bit(true).unwrap();
+    writer.byte_align().unwrap();
+    NalUnit {
+        header: NaluHeader::try_from(nal_header).unwrap(),
+        body: Bytes::from(bytes),
+    }
+}
+
+/// main profile, progressive 320x240, pic_order_cnt_type 2
+fn sps() -> Sps {
+    let nalu = nalu(0x67, |writer| {
+        writer.write::<8, u8>(77).unwrap();
+        writer.write::<8, u8>(0).unwrap();
+        writer.write::<8, u8>(30).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        // log2_max_frame_num_minus4, pic_order_cnt_type
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, 2_u8).unwrap();
+        // max_num_ref_frames, gaps_in_frame_num_value_allowed_flag
+        write_ue(writer, 1_u8).unwrap();
+        writer.write_bit(false).unwrap();
+        write_ue(writer, 19_u8).unwrap();
+        write_ue(writer, 14_u8).unwrap();
+        // frame_mbs_only_flag, direct_8x8_inference_flag, frame_cropping_flag, vui_parameters_present_flag
+        writer.write_bit(true).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+    });
+    Sps::try_from(&nalu).unwrap()
+}
+
+fn pps() -> Pps {
+    let nalu = nalu(0x68, |writer| {
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write::<2, u8>(0).unwrap();
+        write_se(writer, 0_i64).unwrap();
+        write_se(writer, 0_i64).unwrap();
+        write_se(writer, 0_i64).unwrap();
+        writer.write_bit(true).unwrap();
+        writer.write_bit(false).unwrap();
+        writer.write_bit(false).unwrap();
+    });
+    Pps::try_from((ChromaFormatIdc::Chroma420, &nalu)).unwrap()
+}
+
+/// a P slice of frame 1 starting at `first_mb`, or an I slice of an idr
+fn slice(idr: bool, first_mb: u64) -> NalUnit {
+    let (nal_header, slice_type) = if idr { (0x65, 7_u8) } else { (0x61, 5) };
+    nalu(nal_header, |writer| {
+        write_ue(writer, first_mb).unwrap();
+        write_ue(writer, slice_type).unwrap();
+        write_ue(writer, 0_u8).unwrap();
+        writer.write_var(4, !idr as u8).unwrap();
+        if idr {
+            write_ue(writer, 0_u8).unwrap();
+        }
+        // the rest of the slice
+        writer.write::<16, u16>(0xABCD).unwrap();
+    })
+}
+
+fn pictures(idr: bool, starts: &[u64]) -> Vec<PictureSlices> {
+    let (sps, pps) = (sps(), pps());
+    let slices: Vec<_> = starts.iter().map(|start| slice(idr, *start)).collect();
+    PictureSlices::of_access_unit(&slices, |id| (id == 0).then_some((&sps, &pps))).unwrap()
+}
+
+#[test]
+fn test_slices_of_an_access_unit() {
+    assert_eq!(sps().pic_size_in_mbs(false), PIC_SIZE_IN_MBS);
+    // the slices come in any order, a redundant one is left out
+    assert_eq!(
+        pictures(true, &[100, 0, 200, 100]),
+        vec![PictureSlices {
+            pic_size_in_mbs: PIC_SIZE_IN_MBS,
+            starts: vec![0, 100, 200],
+        }]
+    );
+    let (sps, pps) = (sps(), pps());
+    assert!(PictureSlices::of_access_unit(&[slice(false, 0)], |_| None::<(&Sps, &Pps)>).is_err());
+    // parameter sets are no slices
+    let sps_nalu: NalUnit = (&sps).into();
+    assert!(
+        PictureSlices::of_access_unit(&[sps_nalu], |_| Some((&sps, &pps)))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_complete_pictures_pass() {
+    let mut coverage = SliceCoverage::default();
+    for idr in [true, false, false, false] {
+        for picture in pictures(idr, &[0, 100, 200]) {
+            assert_eq!(coverage.check(&picture), Ok(()));
+        }
+    }
+    // a picture of a single slice after the layout of one
+    let mut coverage = SliceCoverage::default();
+    for _ in 0..3 {
+        assert_eq!(coverage.check(&pictures(false, &[0])[0]), Ok(()));
+    }
+}
+
+#[test]
+fn test_missing_middle_slice_is_detected() {
+    let mut coverage = SliceCoverage::default();
+    for idr in [true, false] {
+        assert_eq!(coverage.check(&pictures(idr, &[0, 100, 200])[0]), Ok(()));
+    }
+    assert_eq!(
+        coverage.check(&pictures(false, &[0, 200])[0]),
+        Err(CoverageGap::SlicesMissing { missing: vec![100] })
+    );
+    assert_eq!(coverage.check(&pictures(false, &[0, 100, 200])[0]), Ok(()));
+}
+
+#[test]
+fn test_first_slice_missing_and_out_of_picture() {
+    let mut coverage = SliceCoverage::default();
+    assert_eq!(
+        coverage.check(&pictures(true, &[100, 200])[0]),
+        Err(CoverageGap::FirstSliceMissing { first_mb: 100 })
+    );
+    assert_eq!(
+        coverage.check(&pictures(false, &[0, 300])[0]),
+        Err(CoverageGap::OutOfPicture {
+            mb: 300,
+            pic_size_in_mbs: PIC_SIZE_IN_MBS
+        })
+    );
+}
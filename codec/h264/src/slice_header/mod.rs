@@ -0,0 +1,95 @@
+use codec_bitstream::reader::BitstreamReader;
+use utils::traits::reader::BitwiseReadFrom;
+
+use crate::{errors::H264CodecError, nalu::NalUnit, nalu_type::NALUType, pps::Pps, sps::Sps};
+
+pub mod reader;
+#[cfg(test)]
+mod test;
+
+/// The leading fields of slice_header(), enough to find access unit boundaries
+/// without knowing the active sps and pps
+/// @see: Recommendation  ITU-T H.264 (V15) (08/2024)   – Coding of moving video
+/// Section 7.3.3 Slice header syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SliceHeaderPrefix {
+    pub first_mb_in_slice: u64,    // ue(v)
+    pub slice_type: u64,           // ue(v)
+    pub pic_parameter_set_id: u64, // ue(v)
+}
+
+impl SliceHeaderPrefix {
+    /// slice_type values 5..=9 mean every slice of the picture has the same type
+    pub fn slice_type_normalized(&self) -> u64 {
+        self.slice_type % 5
+    }
+
+    /// the first slice of a picture always starts at macroblock 0
+    pub fn is_first_slice(&self) -> bool {
+        self.first_mb_in_slice == 0
+    }
+}
+
+impl TryFrom<&NalUnit> for SliceHeaderPrefix {
+    type Error = H264CodecError;
+
+    fn try_from(nalu: &NalUnit) -> Result<Self, Self::Error> {
+        if !nalu.header.nal_unit_type.is_vcl() {
+            return Err(H264CodecError::UnknownNaluType(
+                nalu.header.nal_unit_type.into(),
+            ));
+        }
+        let mut reader = BitstreamReader::new(&nalu.body);
+        Self::read_from(&mut reader)
+    }
+}
+
+/// The slice_header() fields up to the picture order count, parsed with the active sps and pps
+/// @see: Recommendation  ITU-T H.264 (V15) (08/2024)   – Coding of moving video
+/// Section 7.3.3 Slice header syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SliceHeaderPicOrder {
+    pub prefix: SliceHeaderPrefix,
+    /// if separate_colour_plane_flag {
+    pub colour_plane_id: Option<u8>, // u(2)
+    /// }
+    pub frame_num: u64, // u(v), log2_max_frame_num_minus4 + 4 bits
+    /// if !frame_mbs_only_flag {
+    pub field_pic_flag: bool, // u(1)
+    /// if field_pic_flag {
+    pub bottom_field_flag: bool, // u(1)
+    /// }}
+    /// if IdrPicFlag {
+    pub idr_pic_id: Option<u64>, // ue(v)
+    /// }
+    /// if pic_order_cnt_type == 0 {
+    pub pic_order_cnt_lsb: Option<u64>, // u(v), log2_max_pic_order_cnt_lsb_minus4 + 4 bits
+    /// if bottom_field_pic_order_in_frame_present_flag && !field_pic_flag {
+    pub delta_pic_order_cnt_bottom: Option<i64>, // se(v)
+    /// }}
+    /// if pic_order_cnt_type == 1 && !delta_pic_order_always_zero_flag {
+    ///   delta_pic_order_cnt[0]
+    ///   if bottom_field_pic_order_in_frame_present_flag && !field_pic_flag {
+    ///     delta_pic_order_cnt[1]
+    /// }}, 0 if not present
+    pub delta_pic_order_cnt: [i64; 2], // se(v)
+}
+
+impl TryFrom<(&NalUnit, &Sps, &Pps)> for SliceHeaderPicOrder {
+    type Error = H264CodecError;
+
+    fn try_from((nalu, sps, pps): (&NalUnit, &Sps, &Pps)) -> Result<Self, Self::Error> {
+        if !nalu.header.nal_unit_type.is_vcl() {
+            return Err(H264CodecError::UnknownNaluType(
+                nalu.header.nal_unit_type.into(),
+            ));
+        }
+        let mut reader = BitstreamReader::new(&nalu.body);
+        Self::read_with(
+            &mut reader,
+            nalu.header.nal_unit_type == NALUType::IDRSlice,
+            sps,
+            pps,
+        )
+    }
+}
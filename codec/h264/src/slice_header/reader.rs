@@ -0,0 +1,103 @@
+use bitstream_io::BitRead;
+use utils::traits::reader::BitwiseReadFrom;
+
+use crate::{
+    errors::H264CodecError,
+    exp_golomb::{read_se, read_ue},
+    pps::Pps,
+    sps::Sps,
+};
+
+use super::{SliceHeaderPicOrder, SliceHeaderPrefix};
+
+impl<R: BitRead> BitwiseReadFrom<R> for SliceHeaderPrefix {
+    type Error = H264CodecError;
+    fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
+        let first_mb_in_slice = read_ue(reader)?;
+        let slice_type = read_ue(reader)?;
+        if slice_type > 9 {
+            return Err(H264CodecError::SyntaxError(format!(
+                "slice_type out of range: {}",
+                slice_type
+            )));
+        }
+        let pic_parameter_set_id = read_ue(reader)?;
+        Ok(Self {
+            first_mb_in_slice,
+            slice_type,
+            pic_parameter_set_id,
+        })
+    }
+}
+
+impl SliceHeaderPicOrder {
+    pub fn read_with<R: BitRead>(
+        reader: &mut R,
+        idr_pic_flag: bool,
+        sps: &Sps,
+        pps: &Pps,
+    ) -> Result<Self, H264CodecError> {
+        let prefix = SliceHeaderPrefix::read_from(reader)?;
+        if prefix.pic_parameter_set_id != pps.pic_parameter_set_id as u64 {
+            return Err(H264CodecError::SyntaxError(format!(
+                "slice refers to pps {} while pps {} is given",
+                prefix.pic_parameter_set_id, pps.pic_parameter_set_id
+            )));
+        }
+        let colour_plane_id = if sps.separate_colour_plane() {
+            Some(reader.read::<2, u8>()?)
+        } else {
+            None
+        };
+        let frame_num = reader.read_var::<u64>(sps.log2_max_frame_num_minus4 as u32 + 4)?;
+        let field_pic_flag = !sps.frame_mbs_only() && reader.read_bit()?;
+        let bottom_field_flag = field_pic_flag && reader.read_bit()?;
+        let idr_pic_id = if idr_pic_flag {
+            Some(read_ue(reader)?)
+        } else {
+            None
+        };
+        let with_bottom_delta = pps.bottom_field_pic_order_in_frame_present_flag && !field_pic_flag;
+        let mut pic_order_cnt_lsb = None;
+        let mut delta_pic_order_cnt_bottom = None;
+        let mut delta_pic_order_cnt = [0; 2];
+        match sps.pic_order_cnt_type {
+            0 => {
+                let bits = sps.log2_max_pic_order_cnt_lsb_minus4.ok_or_else(|| {
+                    H264CodecError::SyntaxError(
+                        "pic_order_cnt_type 0 without log2_max_pic_order_cnt_lsb_minus4".to_owned(),
+                    )
+                })? as u32
+                    + 4;
+                pic_order_cnt_lsb = Some(reader.read_var::<u64>(bits)?);
+                if with_bottom_delta {
+                    delta_pic_order_cnt_bottom = Some(read_se(reader)?);
+                }
+            }
+            1 => {
+                let always_zero = sps
+                    .pic_order_cnt_type_1
+                    .as_ref()
+                    .is_none_or(|v| v.delta_pic_order_always_zero_flag);
+                if !always_zero {
+                    delta_pic_order_cnt[0] = read_se(reader)?;
+                    if with_bottom_delta {
+                        delta_pic_order_cnt[1] = read_se(reader)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(Self {
+            prefix,
+            colour_plane_id,
+            frame_num,
+            field_pic_flag,
+            bottom_field_flag,
+            idr_pic_id,
+            pic_order_cnt_lsb,
+            delta_pic_order_cnt_bottom,
+            delta_pic_order_cnt,
+        })
+    }
+}
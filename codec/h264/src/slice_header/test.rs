@@ -0,0 +1,45 @@
+use tokio_util::bytes::Bytes;
+
+use crate::{nalu::NalUnit, nalu_header::NaluHeader, slice_header::SliceHeaderPrefix};
+
+fn slice(nal_header: u8, body: &[u8]) -> NalUnit {
+    NalUnit {
+        header: NaluHeader::try_from(nal_header).unwrap(),
+        body: Bytes::copy_from_slice(body),
+    }
+}
+
+#[test]
+fn test_first_slice_of_idr() {
+    // first_mb_in_slice = 0 (1), slice_type = 7 (0001000), pps_id = 0 (1)
+    let prefix = SliceHeaderPrefix::try_from(&slice(0x65, &[0b1000_1000, 0b1000_0000])).unwrap();
+    assert_eq!(
+        prefix,
+        SliceHeaderPrefix {
+            first_mb_in_slice: 0,
+            slice_type: 7,
+            pic_parameter_set_id: 0,
+        }
+    );
+    assert!(prefix.is_first_slice());
+    assert_eq!(prefix.slice_type_normalized(), 2);
+}
+
+#[test]
+fn test_following_slice() {
+    // first_mb_in_slice = 120 (0000001111001), slice_type = 0 (1), pps_id = 1 (010)
+    let prefix =
+        SliceHeaderPrefix::try_from(&slice(0x41, &[0b0000_0011, 0b1100_1101, 0b0000_0000]))
+            .unwrap();
+    assert_eq!(prefix.first_mb_in_slice, 120);
+    assert_eq!(prefix.slice_type, 0);
+    assert_eq!(prefix.pic_parameter_set_id, 1);
+    assert!(!prefix.is_first_slice());
+}
+
+#[test]
+fn test_non_vcl_is_rejected() {
+    assert!(SliceHeaderPrefix::try_from(&slice(0x67, &[0x80])).is_err());
+    // slice_type 10 (0001011) is out of range
+    assert!(SliceHeaderPrefix::try_from(&slice(0x41, &[0b1000_1011, 0b1000_0000])).is_err());
+}
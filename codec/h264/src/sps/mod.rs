@@ -209,6 +209,49 @@ impl Sps {
             .unwrap()
     }
 
+    /// false if pictures may be coded as fields
+    pub fn frame_mbs_only(&self) -> bool {
+        self.frame_mbs_only_flag
+    }
+
+    /// PicSizeInMbs of a frame, or of a field which has half of its macroblock rows
+    /// @see: Recommendation  ITU-T H.264 (V15) 7.4.3 Slice header semantics
+    pub fn pic_size_in_mbs(&self, field_pic: bool) -> u64 {
+        let frame_height_in_mbs =
+            (2 - self.frame_mbs_only_flag as u64) * (self.pic_height_in_map_units_minus1 + 1);
+        (self.pic_width_in_mbs_minus1 + 1) * (frame_height_in_mbs / (1 + field_pic as u64))
+    }
+
+    pub fn separate_colour_plane(&self) -> bool {
+        self.profile_idc_related
+            .as_ref()
+            .and_then(|v| v.separate_colour_plane_flag)
+            .unwrap_or(false)
+    }
+
+    /// from the bitstream restriction of the vui, None if it is not there
+    pub fn max_num_reorder_frames(&self) -> Option<u64> {
+        self.vui_parameters
+            .as_ref()
+            .and_then(|v| v.bitstream_restriction.as_ref())
+            .map(|v| v.max_num_reorder_frames)
+    }
+
+    /// frames per second from the timing info of the vui, None if it is not there
+    /// or the frame rate is not fixed. a frame is two ticks, one per field, even when
+    /// the stream is progressive.
+    /// @see: Recommendation  ITU-T H.264 (V15) E.2.1 VUI parameters semantics
+    pub fn nominal_frame_rate(&self) -> Option<f64> {
+        let timing_info = self.vui_parameters.as_ref()?.timing_info.as_ref()?;
+        if !timing_info.fixed_frame_rate_flag
+            || timing_info.num_units_in_tick == 0
+            || timing_info.time_scale == 0
+        {
+            return None;
+        }
+        Some(timing_info.time_scale as f64 / (2.0 * timing_info.num_units_in_tick as f64))
+    }
+
     pub fn get_chroma_format_idc(&self) -> Option<ChromaFormatIdc> {
         self.profile_idc_related
             .as_ref()
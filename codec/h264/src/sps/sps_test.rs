@@ -1,7 +1,10 @@
 #[cfg(test)]
 mod test {
     use bitstream_io::BitRead;
-    use utils::traits::reader::BitwiseReadFrom;
+    use utils::traits::{
+        reader::{BitwiseReadFrom, ReadFrom},
+        writer::WriteTo,
+    };
 
     use crate::{
         nalu::NalUnit,
@@ -106,4 +109,128 @@ mod test {
         assert!(reader.read_bit().unwrap());
         assert_eq!(sps_parsed.seq_parameter_set_id, 0);
     }
+
+    /// sps nal units with the header byte and emulation prevention, as they are carried in sdp
+    const SPS_CORPUS: &[(&str, &str)] = &[
+        (
+            "high 3.0 from the sprop-parameter-sets of an rtp h264 fmtp",
+            "6764001eacd940d83de6f011000003000100000300300f162d96",
+        ),
+        (
+            "main 4.0 from the sdp of an onvif camera",
+            "674d40288d8d403c0113f2c200000e100002bf2008",
+        ),
+        (
+            "high 4.4 1080p by x264, vui with timing info and bitstream restriction",
+            "6764002cacd940780227e5c044000003000400000300c83c60c658",
+        ),
+        (
+            "main 3.1 720p, cbr nal hrd with one sched sel and pic struct",
+            "674d401fed00a00b7602d404040500000300010000030032e86002710002711bdef828",
+        ),
+        (
+            "high 4.0 1080p, nal and vcl hrd with three sched sels each",
+            "67640028acda01e0089f97016a020202800001f4800075306c9001f4001f40007d0001f40001f40001f40dee5c5920038400384000e10003840003840003841bdcb83c22116580",
+        ),
+        (
+            "constrained baseline 3.0 cif, vcl hrd only with low delay",
+            "6742c01eda05825b016a0202023000400405ef705e2c5d40",
+        ),
+    ];
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sps_corpus_round_trip() {
+        for (name, hex) in SPS_CORPUS {
+            let bytes = from_hex(hex);
+            let nalu = NalUnit::read_from(&mut &bytes[..]).unwrap();
+            let sps = Sps::try_from(&nalu).unwrap_or_else(|err| panic!("{}: {}", name, err));
+            let nalu: NalUnit = (&sps).into();
+            let mut written = Vec::new();
+            nalu.write_to(&mut written).unwrap();
+            assert_eq!(written, bytes, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_sps_corpus_hrd_parameters() {
+        let parse = |index: usize| {
+            let bytes = from_hex(SPS_CORPUS[index].1);
+            let nalu = NalUnit::read_from(&mut &bytes[..]).unwrap();
+            Sps::try_from(&nalu).unwrap().vui_parameters.unwrap()
+        };
+        let vui = parse(3);
+        let nal_hrd = vui.nal_hrd_parameters.as_ref().unwrap();
+        assert_eq!(nal_hrd.sched_sels.len(), 1);
+        assert!(nal_hrd.sched_sels[0].cbr_flag);
+        assert!(vui.vcl_hrd_parameters.is_none());
+        assert_eq!(vui.low_delay_hrd_flag, Some(false));
+        assert!(vui.pic_struct_present_flag);
+
+        let vui = parse(4);
+        for hrd in [&vui.nal_hrd_parameters, &vui.vcl_hrd_parameters] {
+            let hrd = hrd.as_ref().unwrap();
+            assert_eq!(hrd.cpb_cnt_minus1, 2);
+            assert_eq!(hrd.sched_sels.len(), 3);
+            assert_eq!(hrd.dpb_output_delay_length_minus1, 5);
+        }
+        assert_eq!(
+            vui.bitstream_restriction
+                .as_ref()
+                .unwrap()
+                .max_dec_frame_buffering,
+            4
+        );
+
+        let vui = parse(5);
+        assert!(vui.nal_hrd_parameters.is_none());
+        assert_eq!(
+            vui.vcl_hrd_parameters.as_ref().unwrap().time_offset_length,
+            0
+        );
+        assert_eq!(vui.low_delay_hrd_flag, Some(true));
+    }
+
+    #[test]
+    fn test_sps_nominal_frame_rate() {
+        let bytes = from_hex(SPS_CORPUS[2].1);
+        let nalu = NalUnit::read_from(&mut &bytes[..]).unwrap();
+        let sps = Sps::try_from(&nalu).unwrap();
+        let with_timing_info = |num_units_in_tick, time_scale, fixed_frame_rate_flag| {
+            let mut sps = sps.clone();
+            sps.vui_parameters.as_mut().unwrap().timing_info = Some(TimingInfo {
+                num_units_in_tick,
+                time_scale,
+                fixed_frame_rate_flag,
+            });
+            // through the bitstream, so the timing info is what a decoder would see
+            let nalu: NalUnit = (&sps).into();
+            Sps::try_from(&nalu).unwrap()
+        };
+
+        assert_eq!(
+            with_timing_info(1, 50, true).nominal_frame_rate(),
+            Some(25.0)
+        );
+        let rate = with_timing_info(1001, 60000, true)
+            .nominal_frame_rate()
+            .unwrap();
+        assert!((rate - 29.97).abs() < 0.001, "{}", rate);
+        let rate = with_timing_info(1001, 120000, true)
+            .nominal_frame_rate()
+            .unwrap();
+        assert!((rate - 59.94).abs() < 0.001, "{}", rate);
+
+        assert_eq!(with_timing_info(1, 50, false).nominal_frame_rate(), None);
+        assert_eq!(with_timing_info(0, 50, true).nominal_frame_rate(), None);
+        let mut sps = sps.clone();
+        sps.vui_parameters.as_mut().unwrap().timing_info = None;
+        assert_eq!(sps.nominal_frame_rate(), None);
+    }
 }
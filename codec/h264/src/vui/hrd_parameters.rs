@@ -34,7 +34,7 @@ pub struct HrdParameters {
 
 impl DynamicSizedBitsPacket for HrdParameters {
     fn get_packet_bits_count(&self) -> usize {
-        find_ue_bits_count(self.cpb_cnt_minus1).unwrap() + 
+        find_ue_bits_count(self.sched_sels.len().saturating_sub(1) as u64).unwrap() +
         4 + // bit_rate_scale
         4 + // cpb_size_scale
         self.sched_sels.iter().fold(0, |prev, item| prev + item.get_packet_bits_count()) +
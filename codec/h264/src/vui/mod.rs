@@ -308,7 +308,7 @@ impl DynamicSizedBitsPacket for VuiParameters {
         self.nal_hrd_parameters.as_ref().map_or(0, |v|v.get_packet_bits_count()) +
         1 + // vcl_hrd_parameters_present_flag
         self.vcl_hrd_parameters.as_ref().map_or(0, |v| v.get_packet_bits_count()) +
-        self.low_delay_hrd_flag.map_or(0, |_|1) +
+        usize::from(self.nal_hrd_parameters.is_some() || self.vcl_hrd_parameters.is_some()) + // low_delay_hrd_flag
         1 + // pic_struct_present_flag
         1 + // bitstream_restriction_flag
         self.bitstream_restriction.as_ref().map_or(0, |v| v.get_packet_bits_count())
@@ -28,6 +28,12 @@ impl<R: BitRead> BitwiseReadFrom<R> for HrdParameters {
     type Error = H264CodecError;
     fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
         let cpb_cnt_minus1 = read_ue(reader)?;
+        if cpb_cnt_minus1 > 31 {
+            return Err(H264CodecError::SyntaxError(format!(
+                "cpb_cnt_minus1 in hrd parameters should be in [0, 31]: {}",
+                cpb_cnt_minus1
+            )));
+        }
         let cpb_cnt_minus1 = cpb_cnt_minus1.to_u8().unwrap();
 
         let bit_rate_scale = reader.read::<4, u8>()?;
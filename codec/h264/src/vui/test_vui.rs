@@ -1,12 +1,17 @@
 #[cfg(test)]
 mod test {
-    use utils::traits::{reader::BitwiseReadFrom, writer::BitwiseWriteTo};
+    use bitstream_io::BitWrite;
+    use utils::traits::{
+        dynamic_sized_packet::DynamicSizedBitsPacket, reader::BitwiseReadFrom,
+        writer::BitwiseWriteTo,
+    };
 
     use crate::{
         rbsp::{rbsp_extract, rbsp_to_sodb},
         vui::{
-            AspectRatioInfo, BitstreamRestriction, ColourDescription, TimingInfo, VideoSignalType,
-            VuiParameters,
+            AspectRatioIdc, AspectRatioInfo, AspectRatioInfoExtendedSAR, BitstreamRestriction,
+            ChromaLocInfo, ColourDescription, TimingInfo, VideoSignalType, VuiParameters,
+            hrd_parameters::{HrdParameters, SchedSel},
         },
     };
 
@@ -69,4 +74,192 @@ mod test {
         );
         assert_eq!(vui_parsed.timing_info.as_ref().unwrap().time_scale, 60);
     }
+
+    /// xorshift, the cases are the same on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+
+        fn flag(&mut self) -> bool {
+            self.next() & 1 == 1
+        }
+
+        /// mostly small values as encoders write them, sometimes up to the bound
+        fn ue(&mut self, bound: u64) -> u64 {
+            if self.flag() {
+                self.below(16.min(bound))
+            } else {
+                self.below(bound)
+            }
+        }
+    }
+
+    fn random_hrd(rng: &mut Rng) -> HrdParameters {
+        let sched_sels: Vec<_> = (0..=rng.below(32))
+            .map(|_| SchedSel {
+                bit_rate_value_minus1: rng.ue(u32::MAX as u64),
+                cpb_size_value_minus1: rng.ue(u32::MAX as u64),
+                cbr_flag: rng.flag(),
+            })
+            .collect();
+        HrdParameters {
+            cpb_cnt_minus1: (sched_sels.len() - 1) as u8,
+            bit_rate_scale: rng.below(16) as u8,
+            cpb_size_scale: rng.below(16) as u8,
+            sched_sels,
+            initial_cpb_removal_delay_length_minus1: rng.below(32) as u8,
+            cpb_removal_delay_length_minus1: rng.below(32) as u8,
+            dpb_output_delay_length_minus1: rng.below(32) as u8,
+            time_offset_length: rng.below(32) as u8,
+        }
+    }
+
+    fn random_vui(rng: &mut Rng) -> VuiParameters {
+        let aspect_ratio_info = rng.flag().then(|| {
+            let aspect_ratio_idc = AspectRatioIdc::from(rng.below(256) as u8);
+            AspectRatioInfo {
+                aspect_ratio_idc,
+                aspect_ratio_info_extended_sar: (aspect_ratio_idc == AspectRatioIdc::ExtendedSAR)
+                    .then(|| AspectRatioInfoExtendedSAR {
+                        sar_width: rng.next() as u16,
+                        sar_height: rng.next() as u16,
+                    }),
+            }
+        });
+        let overscan_appropriate_flag = rng.flag().then(|| rng.flag());
+        let video_signal_type = rng.flag().then(|| {
+            let colour_description = rng.flag().then(|| ColourDescription {
+                colour_primaries: rng.next() as u8,
+                transfer_characteristics: rng.next() as u8,
+                matrix_coefficients: rng.next() as u8,
+            });
+            VideoSignalType {
+                video_format: (rng.below(8) as u8).try_into().unwrap(),
+                video_full_range_flag: rng.flag(),
+                colour_description_present_flag: colour_description.is_some(),
+                colour_description,
+            }
+        });
+        let chroma_loc_info = rng.flag().then(|| ChromaLocInfo {
+            chroma_sample_loc_type_top_field: rng.below(6) as u8,
+            chroma_sample_loc_type_bottom_field: rng.below(6) as u8,
+        });
+        let timing_info = rng.flag().then(|| TimingInfo {
+            num_units_in_tick: rng.next() as u32 | 1,
+            time_scale: rng.next() as u32 | 1,
+            fixed_frame_rate_flag: rng.flag(),
+        });
+        let nal_hrd_parameters = rng.flag().then(|| random_hrd(rng));
+        let vcl_hrd_parameters = rng.flag().then(|| random_hrd(rng));
+        // the flag left out while some hrd parameters are present is written as 0
+        let low_delay_hrd_flag = rng.flag().then(|| rng.flag());
+        let bitstream_restriction = rng.flag().then(|| {
+            let max_dec_frame_buffering = rng.ue(17);
+            BitstreamRestriction {
+                motion_vectors_over_pic_boundaries_flag: rng.flag(),
+                max_bytes_per_pic_denom: rng.below(17) as u8,
+                max_bits_per_mb_denom: rng.below(17) as u8,
+                log2_max_mv_length_horizontal: rng.below(16) as u8,
+                log2_max_mv_length_vertical: rng.below(16) as u8,
+                max_num_reorder_frames: rng.below(max_dec_frame_buffering + 1),
+                max_dec_frame_buffering,
+            }
+        });
+        VuiParameters {
+            aspect_ratio_info_present_flag: aspect_ratio_info.is_some(),
+            aspect_ratio_info,
+            overscan_info_present_flag: overscan_appropriate_flag.is_some(),
+            overscan_appropriate_flag,
+            video_signal_type_present_flag: video_signal_type.is_some(),
+            video_signal_type,
+            chroma_loc_info_present_flag: chroma_loc_info.is_some(),
+            chroma_loc_info,
+            timing_info_present_flag: timing_info.is_some(),
+            timing_info,
+            nal_hrd_parameters_present_flag: nal_hrd_parameters.is_some(),
+            nal_hrd_parameters,
+            vcl_hrd_parameters_present_flag: vcl_hrd_parameters.is_some(),
+            vcl_hrd_parameters,
+            low_delay_hrd_flag,
+            pic_struct_present_flag: rng.flag(),
+            bitstream_restriction_flag: bitstream_restriction.is_some(),
+            bitstream_restriction,
+        }
+    }
+
+    fn write_vui(vui: &VuiParameters) -> (Vec<u8>, u32) {
+        let mut bytes = Vec::new();
+        let mut writer = bitstream_io::BitWriter::endian(&mut bytes, bitstream_io::BigEndian);
+        vui.write_to(&mut writer).unwrap();
+        let mut padding = 0;
+        while !writer.byte_aligned() {
+            writer.write_bit(false).unwrap();
+            padding += 1;
+        }
+        (bytes, padding)
+    }
+
+    #[test]
+    fn test_random_vui_round_trip() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for case in 0..2000 {
+            let vui = random_vui(&mut rng);
+            let (written, padding) = write_vui(&vui);
+            assert_eq!(
+                written.len() * 8 - padding as usize,
+                vui.get_packet_bits_count(),
+                "case {}: {:?}",
+                case,
+                vui
+            );
+            let mut reader = bitstream_io::BitReader::endian(&written[..], bitstream_io::BigEndian);
+            let parsed = VuiParameters::read_from(&mut reader).unwrap();
+            let hrd_present = vui.nal_hrd_parameters.is_some() || vui.vcl_hrd_parameters.is_some();
+            assert_eq!(
+                parsed.low_delay_hrd_flag,
+                hrd_present.then(|| vui.low_delay_hrd_flag.unwrap_or(false)),
+                "case {}",
+                case
+            );
+            for (hrd, parsed) in [
+                (&vui.nal_hrd_parameters, &parsed.nal_hrd_parameters),
+                (&vui.vcl_hrd_parameters, &parsed.vcl_hrd_parameters),
+            ] {
+                assert_eq!(
+                    hrd.as_ref().map(|hrd| hrd.sched_sels.len()),
+                    parsed.as_ref().map(|hrd| hrd.sched_sels.len()),
+                    "case {}",
+                    case
+                );
+            }
+            assert_eq!(write_vui(&parsed).0, written, "case {}: {:?}", case, vui);
+        }
+    }
+
+    #[test]
+    fn test_hrd_without_sched_sels_is_rejected() {
+        let mut hrd = random_hrd(&mut Rng(1));
+        hrd.sched_sels.clear();
+        let mut bytes = Vec::new();
+        let mut writer = bitstream_io::BitWriter::endian(&mut bytes, bitstream_io::BigEndian);
+        assert!(hrd.write_to(&mut writer).is_err());
+    }
+
+    #[test]
+    fn test_hrd_cpb_cnt_out_of_range_is_rejected() {
+        // cpb_cnt_minus1 ue(v) of 32: 00000 100001, followed by zeros
+        let bytes = [0b0000_0100, 0b0010_0000, 0, 0, 0, 0, 0, 0];
+        let mut reader = bitstream_io::BitReader::endian(&bytes[..], bitstream_io::BigEndian);
+        assert!(HrdParameters::read_from(&mut reader).is_err());
+    }
 }
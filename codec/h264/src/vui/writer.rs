@@ -23,8 +23,14 @@ impl<W: BitWrite> BitwiseWriteTo<W> for SchedSel {
 impl<W: BitWrite> BitwiseWriteTo<W> for HrdParameters {
     type Error = H264CodecError;
     fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
-        let cpb_cnt_minus1 = self.sched_sels.len().to_u64().unwrap();
-        write_ue(writer, cpb_cnt_minus1)?;
+        let cpb_cnt_minus1 = self.sched_sels.len().checked_sub(1).filter(|v| *v <= 31);
+        let Some(cpb_cnt_minus1) = cpb_cnt_minus1 else {
+            return Err(H264CodecError::SyntaxError(format!(
+                "hrd parameters should have 1 to 32 sched sels: {}",
+                self.sched_sels.len()
+            )));
+        };
+        write_ue(writer, cpb_cnt_minus1.to_u64().unwrap())?;
         writer.write::<4, _>(self.bit_rate_scale)?;
         writer.write::<4, _>(self.cpb_size_scale)?;
         self.sched_sels
@@ -164,8 +170,8 @@ impl<W: BitWrite> BitwiseWriteTo<W> for VuiParameters {
         } else {
             writer.write_bit(false)?;
         }
-        if let Some(low_delay_hrd) = self.low_delay_hrd_flag {
-            writer.write_bit(low_delay_hrd)?;
+        if self.nal_hrd_parameters.is_some() || self.vcl_hrd_parameters.is_some() {
+            writer.write_bit(self.low_delay_hrd_flag.unwrap_or(false))?;
         }
         writer.write_bit(self.pic_struct_present_flag)?;
         if let Some(bitstream_restriction) = &self.bitstream_restriction {
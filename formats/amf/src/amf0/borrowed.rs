@@ -0,0 +1,438 @@
+//! amf0 values borrowing their keys and strings from the input,
+//! for the commands parsed on every connection, which look up a few fields and drop the rest
+
+use core::time;
+use std::io;
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+    AmfComplexObject,
+    errors::{AmfError, AmfResult},
+    limits::{ReadBudget, ReadLimits},
+};
+
+use super::{Value, amf0_marker, amf3};
+
+/// like `Value`, with the strings borrowed. references are resolved to copies of the values they refer to
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueRef<'a> {
+    Number(f64),
+    Boolean(bool),
+    String(&'a str),
+    Object {
+        name: Option<&'a str>,
+        entries: Vec<(&'a str, ValueRef<'a>)>,
+    },
+    Null,
+    Undefined,
+    ECMAArray(Vec<(&'a str, ValueRef<'a>)>),
+    ObjectEnd,
+    StrictArray(Vec<ValueRef<'a>>),
+    Date {
+        time_zone: i16,
+        millis_timestamp: time::Duration,
+    },
+    XMLDocument(&'a str),
+    /// rare in commands, read owned
+    AVMPlus(amf3::Value),
+}
+
+impl<'a> ValueRef<'a> {
+    pub fn into_owned(self) -> Value {
+        let owned_pairs = |entries: Vec<(&str, ValueRef)>| {
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value.into_owned()))
+                .collect()
+        };
+        match self {
+            ValueRef::Number(v) => Value::Number(v),
+            ValueRef::Boolean(v) => Value::Boolean(v),
+            ValueRef::String(v) => Value::String(v.to_owned()),
+            ValueRef::Object { name, entries } => Value::Object {
+                name: name.map(str::to_owned),
+                entries: owned_pairs(entries),
+            },
+            ValueRef::Null => Value::Null,
+            ValueRef::Undefined => Value::Undefined,
+            ValueRef::ECMAArray(entries) => Value::ECMAArray(owned_pairs(entries)),
+            ValueRef::ObjectEnd => Value::ObjectEnd,
+            ValueRef::StrictArray(values) => {
+                Value::StrictArray(values.into_iter().map(ValueRef::into_owned).collect())
+            }
+            ValueRef::Date {
+                time_zone,
+                millis_timestamp,
+            } => Value::Date {
+                time_zone,
+                millis_timestamp,
+            },
+            ValueRef::XMLDocument(v) => Value::XMLDocument(v.to_owned()),
+            ValueRef::AVMPlus(v) => Value::AVMPlus(v),
+        }
+    }
+
+    pub fn try_as_str(&self) -> Option<&str> {
+        match *self {
+            ValueRef::String(v) | ValueRef::XMLDocument(v) => Some(v),
+            ValueRef::AVMPlus(ref v) => v.try_as_str(),
+            _ => None,
+        }
+    }
+
+    pub fn try_as_f64(&self) -> Option<f64> {
+        match *self {
+            ValueRef::Number(v) => Some(v),
+            ValueRef::AVMPlus(ref v) => v.try_as_f64(),
+            _ => None,
+        }
+    }
+
+    pub fn try_as_bool(&self) -> Option<bool> {
+        match *self {
+            ValueRef::Boolean(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// the entries of an object or an ecma array
+    pub fn entries(&self) -> Option<&[(&'a str, ValueRef<'a>)]> {
+        match self {
+            ValueRef::Object { entries, .. } | ValueRef::ECMAArray(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// the first entry of an object or an ecma array with the key
+    pub fn get(&self, key: &str) -> Option<&ValueRef<'a>> {
+        self.entries()?
+            .iter()
+            .find(|(entry_key, _)| *entry_key == key)
+            .map(|(_, value)| value)
+    }
+}
+
+impl AmfComplexObject for ValueRef<'_> {
+    fn extract_bool_field(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(ValueRef::try_as_bool)
+    }
+
+    fn extract_number_field(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(ValueRef::try_as_f64)
+    }
+
+    fn extract_string_field(&self, key: &str) -> Option<String> {
+        self.get(key)
+            .and_then(ValueRef::try_as_str)
+            .map(str::to_owned)
+    }
+
+    fn extract_array_field(&self, key: &str) -> Option<Box<dyn Iterator<Item = crate::Value>>> {
+        self.get(key)
+            .cloned()
+            .and_then(|value| value.into_owned().try_into_values().ok())
+    }
+
+    fn extract_object_field(
+        &self,
+        key: &str,
+    ) -> Option<Box<dyn Iterator<Item = (String, crate::Value)>>> {
+        self.get(key)
+            .cloned()
+            .and_then(|value| value.into_owned().try_into_pairs().ok())
+    }
+}
+
+/// reads the values of a buffer without copying their strings, with the limits of `Reader`
+#[derive(Debug)]
+pub struct RefReader<'a> {
+    input: &'a [u8],
+    /// the objects and arrays so far for the references, with what each was charged
+    referenceable: Vec<(ValueRef<'a>, usize)>,
+    budget: ReadBudget,
+}
+
+impl<'a> RefReader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Self::with_limits(input, ReadLimits::default())
+    }
+
+    /// the limits span all the values read
+    pub fn with_limits(input: &'a [u8], limits: ReadLimits) -> Self {
+        Self {
+            input,
+            referenceable: Vec::new(),
+            budget: ReadBudget::new(limits),
+        }
+    }
+
+    /// what is left of the input
+    pub fn remaining(&self) -> &'a [u8] {
+        self.input
+    }
+
+    pub fn read(&mut self) -> AmfResult<ValueRef<'a>> {
+        let marker = self.input.read_u8()?;
+        self.budget.charge_values(1)?;
+
+        match marker {
+            amf0_marker::NUMBER => Ok(ValueRef::Number(self.input.read_f64::<BigEndian>()?)),
+            amf0_marker::BOOLEAN => Ok(ValueRef::Boolean(self.input.read_u8()? != 0)),
+            amf0_marker::STRING => {
+                let len = self.input.read_u16::<BigEndian>()?;
+                self.read_str(len as usize).map(ValueRef::String)
+            }
+            amf0_marker::OBJECT => self.read_referenceable(|this| {
+                Ok(ValueRef::Object {
+                    name: None,
+                    entries: this.read_key_value_pairs()?,
+                })
+            }),
+            amf0_marker::MOVIECLIP => Err(AmfError::Unsupported { marker }),
+            amf0_marker::NULL => Ok(ValueRef::Null),
+            amf0_marker::UNDEFINED => Ok(ValueRef::Undefined),
+            amf0_marker::REFERENCE => self.read_reference(),
+            amf0_marker::ECMA_ARRAY => self.read_referenceable(|this| {
+                // only a hint of the count, a hint beyond the limit is refused anyway
+                let len = this.input.read_u32::<BigEndian>()? as usize;
+                this.budget.check_elements(len)?;
+                Ok(ValueRef::ECMAArray(this.read_key_value_pairs()?))
+            }),
+            amf0_marker::OBJECT_END => Ok(ValueRef::ObjectEnd),
+            amf0_marker::STRICT_ARRAY => self.read_referenceable(|this| {
+                let len = this.input.read_u32::<BigEndian>()? as usize;
+                this.budget.check_elements(len)?;
+                let values = (0..len).map(|_| this.read()).collect::<AmfResult<_>>()?;
+                Ok(ValueRef::StrictArray(values))
+            }),
+            amf0_marker::DATE => self.read_date(),
+            amf0_marker::LONG_STRING => {
+                let len = self.input.read_u32::<BigEndian>()?;
+                self.read_str(len as usize).map(ValueRef::String)
+            }
+            amf0_marker::UNSUPPORTED => Err(AmfError::Unsupported { marker }),
+            amf0_marker::RECORDSET => Err(AmfError::Unsupported { marker }),
+            amf0_marker::XML_DOCUMENT => {
+                let len = self.input.read_u32::<BigEndian>()?;
+                self.read_str(len as usize).map(ValueRef::XMLDocument)
+            }
+            amf0_marker::TYPED_OBJECT => self.read_referenceable(|this| {
+                let name_len = this.input.read_u16::<BigEndian>()?;
+                let name = this.read_str(name_len as usize)?;
+                Ok(ValueRef::Object {
+                    name: Some(name),
+                    entries: this.read_key_value_pairs()?,
+                })
+            }),
+            amf0_marker::AVMPLUS_OBJECT => {
+                let mut reader = amf3::Reader::with_budget(&mut self.input, self.budget.clone());
+                let result = reader.read();
+                self.budget = reader.into_budget();
+                Ok(ValueRef::AVMPlus(result?))
+            }
+            _ => Err(AmfError::Unknown { marker }),
+        }
+    }
+
+    pub fn read_all(&mut self) -> AmfResult<Vec<ValueRef<'a>>> {
+        let mut result = Vec::new();
+        while let Ok(value) = self.read() {
+            result.push(value);
+        }
+        Ok(result)
+    }
+
+    fn read_str(&mut self, len: usize) -> AmfResult<&'a str> {
+        self.budget.charge_string(len)?;
+        if self.input.len() < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(std::str::from_utf8(bytes)?)
+    }
+
+    fn read_key_value_pairs(&mut self) -> AmfResult<Vec<(&'a str, ValueRef<'a>)>> {
+        let mut result = Vec::new();
+        loop {
+            let len = self.input.read_u16::<BigEndian>()?;
+            let key = self.read_str(len as usize)?;
+            let value = self.read()?;
+            if matches!(value, ValueRef::ObjectEnd) {
+                break;
+            }
+            self.budget.check_elements(result.len() + 1)?;
+            result.push((key, value));
+        }
+        Ok(result)
+    }
+
+    fn read_reference(&mut self) -> AmfResult<ValueRef<'a>> {
+        let index = self.input.read_u16::<BigEndian>()? as usize;
+        let (value, decoded_bytes) = self
+            .referenceable
+            .get(index)
+            .ok_or(AmfError::OutOfRangeReference { index })?;
+        if matches!(value, ValueRef::Null) {
+            return Err(AmfError::CircularReference { index });
+        }
+        self.budget.charge(*decoded_bytes)?;
+        Ok(value.clone())
+    }
+
+    fn read_date(&mut self) -> AmfResult<ValueRef<'a>> {
+        let timestamp = self.input.read_f64::<BigEndian>()?;
+        if !(timestamp.is_finite() && timestamp.is_sign_positive()) {
+            return Err(AmfError::InvalidDate {
+                milliseconds: timestamp,
+            });
+        }
+        let time_zone = self.input.read_i16::<BigEndian>()?;
+        if time_zone != 0x0000 {
+            return Err(AmfError::UnexpectedTimeZone { offset: time_zone });
+        }
+        Ok(ValueRef::Date {
+            time_zone,
+            millis_timestamp: time::Duration::from_millis(timestamp as u64),
+        })
+    }
+
+    /// see `Reader::read_and_record_referenceable_inner`
+    fn read_referenceable<F>(&mut self, f: F) -> AmfResult<ValueRef<'a>>
+    where
+        F: FnOnce(&mut Self) -> AmfResult<ValueRef<'a>>,
+    {
+        self.budget.enter()?;
+        let decoded_bytes = self.budget.decoded_bytes();
+        let index = self.referenceable.len();
+        self.referenceable.push((ValueRef::Null, 0));
+        let result = f(self);
+        self.budget.leave();
+        let result = result?;
+        self.referenceable[index] = (result.clone(), self.budget.decoded_bytes() - decoded_bytes);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use crate::{
+        amf0::{Reader, Value},
+        limits::ReadLimits,
+    };
+
+    use super::{RefReader, ValueRef};
+
+    /// the values of `Reader` and the errors of the same kind
+    fn assert_same_as_owned(file: &str, data: &[u8], limits: ReadLimits) {
+        let mut reader = Reader::with_limits(data, limits);
+        let mut owned = vec![];
+        let owned_err = loop {
+            match reader.read() {
+                Ok(value) => owned.push(value),
+                Err(err) => break err,
+            }
+        };
+        let mut reader = RefReader::with_limits(data, limits);
+        let mut borrowed: Vec<Value> = vec![];
+        let borrowed_err = loop {
+            match reader.read() {
+                Ok(value) => borrowed.push(value.into_owned()),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(borrowed, owned, "{}", file);
+        assert_eq!(
+            mem::discriminant(&borrowed_err),
+            mem::discriminant(&owned_err),
+            "{}: {:?} vs {:?}",
+            file,
+            borrowed_err,
+            owned_err
+        );
+    }
+
+    macro_rules! test_data {
+        ($($file:literal),* $(,)?) => {
+            [$(($file, &include_bytes!(concat!("../../test_data/", $file))[..])),*]
+        };
+    }
+
+    #[test]
+    fn borrowed_values_are_the_owned_ones() {
+        let files = test_data!(
+            "amf0-avmplus-object.bin",
+            "amf0-bad-object-end.bin",
+            "amf0-bad-reference.bin",
+            "amf0-boolean-false.bin",
+            "amf0-boolean-partial.bin",
+            "amf0-boolean-true.bin",
+            "amf0-circular-reference.bin",
+            "amf0-complex-encoded-string.bin",
+            "amf0-date-invalid.bin",
+            "amf0-date-minus.bin",
+            "amf0-date-partial.bin",
+            "amf0-date.bin",
+            "amf0-ecma-array-partial.bin",
+            "amf0-ecma-ordinal-array.bin",
+            "amf0-empty.bin",
+            "amf0-hash.bin",
+            "amf0-long-string-partial.bin",
+            "amf0-long-string.bin",
+            "amf0-movieclip.bin",
+            "amf0-null.bin",
+            "amf0-number-negative-infinity.bin",
+            "amf0-number-partial.bin",
+            "amf0-number-positive-infinity.bin",
+            "amf0-number.bin",
+            "amf0-object-partial.bin",
+            "amf0-object.bin",
+            "amf0-recordset.bin",
+            "amf0-ref-test.bin",
+            "amf0-reference-partial.bin",
+            "amf0-strict-array-partial.bin",
+            "amf0-strict-array.bin",
+            "amf0-string-partial.bin",
+            "amf0-string.bin",
+            "amf0-time.bin",
+            "amf0-typed-object-partial.bin",
+            "amf0-typed-object.bin",
+            "amf0-undefined.bin",
+            "amf0-unknown-marker.bin",
+            "amf0-unsupported.bin",
+            "amf0-untyped-object.bin",
+            "amf0-writer-references.bin",
+            "amf0-xml-doc.bin",
+            "amf0-xml-document-partial.bin",
+        );
+        let tight = ReadLimits {
+            max_depth: 1,
+            max_decoded_bytes: 64,
+            max_string_length: 8,
+            max_elements: 2,
+        };
+        for (file, data) in files {
+            assert_same_as_owned(file, data, ReadLimits::default());
+            assert_same_as_owned(file, data, tight);
+        }
+    }
+
+    #[test]
+    fn strings_are_borrowed_from_the_input() {
+        let mut data = vec![0x03, 0x00, 0x03];
+        data.extend_from_slice(b"app");
+        data.extend_from_slice(&[0x02, 0x00, 0x04]);
+        data.extend_from_slice(b"live");
+        data.extend_from_slice(&[0x00, 0x00, 0x09]);
+        let value = RefReader::new(&data).read().unwrap();
+        let app = value.get("app").and_then(ValueRef::try_as_str).unwrap();
+        assert_eq!(app, "live");
+        assert!(data.as_ptr_range().contains(&app.as_ptr()));
+        let (key, _) = &value.entries().unwrap()[0];
+        assert!(data.as_ptr_range().contains(&key.as_ptr()));
+        assert!(value.get("tcUrl").is_none());
+    }
+}
@@ -3,10 +3,13 @@
 use core::time;
 use std::io;
 
+pub use self::borrowed::{RefReader, ValueRef};
 pub use self::reader::Reader;
+pub use self::writer::Writer;
 use crate::amf3;
 use crate::errors::AmfResult;
 
+mod borrowed;
 mod reader;
 mod writer;
 
@@ -78,6 +81,16 @@ impl Value {
         Reader::new(reader).read_all()
     }
 
+    /// with use_references, a complex value repeated in the values is written as a reference
+    pub fn write_all<W>(writer: W, values: &[Self], use_references: bool) -> AmfResult<()>
+    where
+        W: io::Write,
+    {
+        Writer::new(writer)
+            .with_references(use_references)
+            .write_all(values)
+    }
+
     pub fn try_as_str(&self) -> Option<&str> {
         match *self {
             Value::String(ref str) => Some(str),
@@ -1,22 +1,28 @@
 use core::time;
-use std::{io, vec};
+use std::io::{self, Read};
 
 use byteorder::{BigEndian, ReadBytesExt};
 use utils::traits::reader::ReadFrom;
 
-use crate::errors::{AmfError, AmfResult};
+use crate::{
+    errors::{AmfError, AmfResult},
+    limits::{ReadBudget, ReadLimits},
+};
 
 use super::{Value, amf0_marker, amf3};
 
 #[derive(Debug)]
 struct Amf0Referenceable {
     objects: Vec<Value>,
+    /// what each object was charged, charged again for each reference to it
+    decoded_bytes: Vec<usize>,
 }
 
 #[derive(Debug)]
 pub struct Reader<R> {
     inner: R,
     referenceable: Amf0Referenceable,
+    budget: ReadBudget,
 }
 impl<R> Reader<R> {
     /// Unwraps this `Decoder`, returning the underlying reader.
@@ -39,15 +45,24 @@ where
     R: io::Read,
 {
     pub fn new(inner: R) -> Self {
+        Self::with_limits(inner, ReadLimits::default())
+    }
+
+    /// the limits span all the values read
+    pub fn with_limits(inner: R, limits: ReadLimits) -> Self {
         Self {
             inner,
             referenceable: Amf0Referenceable {
                 objects: Vec::new(),
+                decoded_bytes: Vec::new(),
             },
+            budget: ReadBudget::new(limits),
         }
     }
+
     pub fn read(&mut self) -> AmfResult<Value> {
         let marker = self.inner.read_u8()?;
+        self.budget.charge_values(1)?;
 
         match marker {
             amf0_marker::NUMBER => self.read_number(),
@@ -88,9 +103,17 @@ where
         let bool = self.inner.read_u8()?;
         Ok(Value::Boolean(bool != 0))
     }
+    /// the buffer grows with the data actually read, a declared length beyond the data allocates nothing more
     fn read_utf8_inner(&mut self, len: usize) -> AmfResult<String> {
-        let mut buffer = vec![0; len];
-        self.inner.read_exact(&mut buffer)?;
+        self.budget.charge_string(len)?;
+        let mut buffer = Vec::new();
+        self.inner
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut buffer)?;
+        if buffer.len() < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
         let result = String::from_utf8(buffer)?;
         Ok(result)
     }
@@ -111,6 +134,7 @@ where
             if matches!(value, Value::ObjectEnd) {
                 break;
             }
+            self.budget.check_elements(result.len() + 1)?;
             result.push((key, value));
         }
         Ok(result)
@@ -126,19 +150,24 @@ where
     }
     pub fn read_reference(&mut self) -> AmfResult<Value> {
         let index = self.inner.read_u16::<BigEndian>()? as usize;
-        self.referenceable
+        let value = self
+            .referenceable
             .objects
             .get(index)
             .ok_or(AmfError::OutOfRangeReference { index })
             .and_then(|v| match *v {
                 Value::Null => Err(AmfError::CircularReference { index }),
-                _ => Ok(v.clone()),
-            })
+                _ => Ok(v),
+            })?;
+        self.budget
+            .charge(self.referenceable.decoded_bytes[index])?;
+        Ok(value.clone())
     }
     pub fn read_ecma_array(&mut self) -> AmfResult<Value> {
         self.read_and_record_referenceable_inner(|this| {
-            // TODO - is this completely useless?
-            let _len = this.inner.read_u32::<BigEndian>()? as usize;
+            // only a hint of the count, a hint beyond the limit is refused anyway
+            let len = this.inner.read_u32::<BigEndian>()? as usize;
+            this.budget.check_elements(len)?;
             let pairs = this.read_key_value_pairs_inner()?;
             Ok(Value::ECMAArray(pairs))
         })
@@ -146,6 +175,7 @@ where
     pub fn read_strict_array(&mut self) -> AmfResult<Value> {
         self.read_and_record_referenceable_inner(|this| {
             let len = this.inner.read_u32::<BigEndian>()? as usize;
+            this.budget.check_elements(len)?;
             let values = (0..len).map(|_| this.read()).collect::<AmfResult<_>>()?;
             Ok(Value::StrictArray(values))
         })
@@ -182,17 +212,26 @@ where
         })
     }
     pub fn read_avm_plus(&mut self) -> AmfResult<Value> {
-        let result = amf3::Reader::new(&mut self.inner).read()?;
-        Ok(Value::AVMPlus(result))
+        let mut reader = amf3::Reader::with_budget(&mut self.inner, self.budget.clone());
+        let result = reader.read();
+        self.budget = reader.into_budget();
+        Ok(Value::AVMPlus(result?))
     }
+    /// the referenceable values are the objects and arrays, so they are the ones nested
     fn read_and_record_referenceable_inner<F>(&mut self, f: F) -> AmfResult<Value>
     where
         F: FnOnce(&mut Self) -> AmfResult<Value>,
     {
+        self.budget.enter()?;
+        let decoded_bytes = self.budget.decoded_bytes();
         let len = self.referenceable.objects.len();
         self.referenceable.objects.push(Value::Null);
-        let result = f(self)?;
+        self.referenceable.decoded_bytes.push(0);
+        let result = f(self);
+        self.budget.leave();
+        let result = result?;
         self.referenceable.objects[len] = result.clone();
+        self.referenceable.decoded_bytes[len] = self.budget.decoded_bytes() - decoded_bytes;
         Ok(result)
     }
 }
@@ -213,6 +252,7 @@ mod tests {
         amf0::{Value, amf0_marker},
         amf3,
         errors::AmfError,
+        limits::{ReadLimit, ReadLimits},
     };
 
     use super::Reader;
@@ -232,6 +272,32 @@ mod tests {
             }
         };
     }
+
+    /// the partial data declaring a string longer than the default limit
+    macro_rules! assert_long_partial {
+        ($file:expr) => {
+            assert!(matches!(
+                decode!($file),
+                Err(AmfError::LimitExceeded {
+                    limit: ReadLimit::StringLength,
+                    ..
+                })
+            ));
+            let data = include_bytes!($file);
+            match Reader::with_limits(&data[..], unlimited_strings()).read() {
+                Err(AmfError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        };
+    }
+
+    fn unlimited_strings() -> ReadLimits {
+        ReadLimits {
+            max_string_length: usize::MAX,
+            max_decoded_bytes: usize::MAX,
+            ..Default::default()
+        }
+    }
     #[test]
     fn number() {
         assert_eq!(
@@ -303,7 +369,7 @@ mod tests {
             Value::String("a".repeat(0x10013))
         );
 
-        assert_eof!("../../test_data/amf0-long-string-partial.bin");
+        assert_long_partial!("../../test_data/amf0-long-string-partial.bin");
     }
 
     #[test]
@@ -313,7 +379,7 @@ mod tests {
             Value::XMLDocument("<parent><child prop=\"test\" /></parent>".to_string())
         );
 
-        assert_eof!("../../test_data/amf0-xml-document-partial.bin");
+        assert_long_partial!("../../test_data/amf0-xml-document-partial.bin");
     }
 
     #[test]
@@ -388,6 +454,32 @@ mod tests {
         assert_eof!("../../test_data/amf0-object-partial.bin");
     }
 
+    #[test]
+    fn reference_errors() {
+        // a strict array holding a reference to itself
+        assert!(matches!(
+            decode!("../../test_data/amf0-circular-reference.bin"),
+            Err(AmfError::CircularReference { index: 0 })
+        ));
+        assert!(matches!(
+            decode!("../../test_data/amf0-bad-reference.bin"),
+            Err(AmfError::OutOfRangeReference { index: 0 })
+        ));
+        assert_eof!("../../test_data/amf0-reference-partial.bin");
+    }
+
+    #[test]
+    fn references_span_the_values_of_a_message() {
+        let data = include_bytes!("../../test_data/amf0-writer-references.bin");
+        let values = Reader::new(&mut &data[..]).read_all().unwrap();
+        assert_eq!(values.len(), 3);
+        let Value::ECMAArray(entries) = &values[1] else {
+            panic!("unexpected value: {:?}", values[1]);
+        };
+        assert_eq!(entries[0].1, entries[1].1);
+        assert_eq!(values[2], entries[0].1);
+    }
+
     #[test]
     fn ecma_array() {
         {
@@ -520,4 +612,98 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn deeply_nested_arrays_exceed_the_depth_limit() {
+        let mut data = Vec::new();
+        for _ in 0..10_000 {
+            data.push(amf0_marker::STRICT_ARRAY);
+            data.extend_from_slice(&1u32.to_be_bytes());
+        }
+        data.push(amf0_marker::NULL);
+        assert!(matches!(
+            Reader::new(&mut &data[..]).read(),
+            Err(AmfError::LimitExceeded {
+                limit: ReadLimit::Depth,
+                value: 33,
+                max: 32
+            })
+        ));
+    }
+
+    #[test]
+    fn declared_lengths_are_checked_before_allocating() {
+        let mut data = vec![amf0_marker::LONG_STRING];
+        data.extend_from_slice(&u32::MAX.to_be_bytes());
+        data.extend_from_slice(b"short");
+        assert!(matches!(
+            Reader::new(&mut &data[..]).read(),
+            Err(AmfError::LimitExceeded {
+                limit: ReadLimit::StringLength,
+                value: 0xFFFF_FFFF,
+                ..
+            })
+        ));
+
+        match Reader::with_limits(&data[..], unlimited_strings()).read() {
+            Err(AmfError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut data = vec![amf0_marker::STRICT_ARRAY];
+        data.extend_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            Reader::new(&mut &data[..]).read(),
+            Err(AmfError::LimitExceeded {
+                limit: ReadLimit::Elements,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn objects_with_too_many_keys_exceed_the_elements_limit() {
+        let mut data = vec![amf0_marker::OBJECT];
+        for _ in 0..1_000_000 {
+            data.extend_from_slice(&[0x00, 0x01, b'k', amf0_marker::NULL]);
+        }
+        data.extend_from_slice(&[0x00, 0x00, amf0_marker::OBJECT_END]);
+        assert!(matches!(
+            Reader::new(&mut &data[..]).read(),
+            Err(AmfError::LimitExceeded {
+                limit: ReadLimit::Elements,
+                value: 65537,
+                max: 65536
+            })
+        ));
+    }
+
+    #[test]
+    fn references_are_charged_for_what_they_copy() {
+        let mut data = vec![amf0_marker::STRICT_ARRAY];
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.push(amf0_marker::LONG_STRING);
+        data.extend_from_slice(&1000u32.to_be_bytes());
+        data.extend_from_slice(&[b'a'; 1000]);
+        for _ in 0..3 {
+            data.extend_from_slice(&[amf0_marker::REFERENCE, 0x00, 0x00]);
+        }
+
+        let limits = ReadLimits {
+            max_decoded_bytes: 4000,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_limits(&data[..], limits);
+        for _ in 0..3 {
+            reader.read().unwrap();
+        }
+        assert!(matches!(
+            reader.read(),
+            Err(AmfError::LimitExceeded {
+                limit: ReadLimit::DecodedBytes,
+                value: 4064,
+                max: 4000
+            })
+        ));
+    }
 }
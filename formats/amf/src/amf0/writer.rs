@@ -11,30 +11,166 @@ use utils::traits::writer::WriteTo;
 
 use super::{Value, amf0_marker};
 
-impl<W: io::Write> WriteTo<W> for Value {
-    type Error = AmfError;
-    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
-        match self {
-            Value::Number(n) => Self::write_number(writer, *n),
-            Value::Boolean(b) => Self::write_boolean(writer, *b),
-            Value::String(ss) => Self::write_string(writer, ss),
-            Value::Object { name, entries } => match name {
-                Some(name) => Self::write_typed_object_arr_inner(writer, name, entries),
-                None => Self::write_anonymous_object_arr(writer, entries),
-            },
-            Value::Null => Self::write_null(writer),
-            Value::Undefined => Self::write_undefined(writer),
-            Value::Reference { index } => Self::write_reference(writer, *index),
-            Value::ECMAArray(arr) => Self::write_ecma_array(writer, arr),
-            Value::ObjectEnd => Self::write_object_end(writer),
-            Value::StrictArray(arr) => Self::write_strict_array(writer, arr),
+#[derive(Debug, Default)]
+struct Amf0Referenceable {
+    /// whether the complex value of each reference index is completely written,
+    /// a reference to an incomplete one points at one of its own ancestors
+    completed: Vec<bool>,
+    /// the complex values by reference index, only kept to find repeated ones
+    objects: Vec<Value>,
+}
+
+/// objects, typed objects, ecma arrays and strict arrays get reference indexes in the order they are written,
+/// as the reader counts them
+#[derive(Debug)]
+pub struct Writer<W> {
+    inner: W,
+    use_references: bool,
+    referenceable: Amf0Referenceable,
+}
+
+impl<W> Writer<W> {
+    /// Unwraps this `Writer`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Get the reference to the underlying writer.
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get the mutable reference to the underlying writer.
+    pub fn inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W> Writer<W>
+where
+    W: io::Write,
+{
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            use_references: false,
+            referenceable: Amf0Referenceable::default(),
+        }
+    }
+
+    /// a complex value identical to one written before is written as a reference to it
+    pub fn with_references(mut self, use_references: bool) -> Self {
+        self.use_references = use_references;
+        self
+    }
+
+    pub fn write(&mut self, value: &Value) -> AmfResult<()> {
+        match value {
+            Value::Number(n) => Value::write_number(&mut self.inner, *n),
+            Value::Boolean(b) => Value::write_boolean(&mut self.inner, *b),
+            Value::String(ss) => Value::write_string(&mut self.inner, ss),
+            Value::Object { .. } | Value::ECMAArray(_) | Value::StrictArray(_) => {
+                self.write_referenceable(value)
+            }
+            Value::Null => Value::write_null(&mut self.inner),
+            Value::Undefined => Value::write_undefined(&mut self.inner),
+            Value::Reference { index } => self.write_reference(*index),
+            Value::ObjectEnd => Value::write_object_end(&mut self.inner),
             Value::Date {
                 time_zone,
                 millis_timestamp: unix_timestamp,
-            } => Self::write_date(writer, unix_timestamp, *time_zone),
-            Value::XMLDocument(xml) => Self::write_xml(writer, xml),
-            Value::AVMPlus(value) => Self::write_avm_plus(writer, value),
+            } => Value::write_date(&mut self.inner, unix_timestamp, *time_zone),
+            Value::XMLDocument(xml) => Value::write_xml(&mut self.inner, xml),
+            Value::AVMPlus(value) => Value::write_avm_plus(&mut self.inner, value),
+        }
+    }
+
+    /// the values share one reference table, like a message read by `Reader::read_all`
+    pub fn write_all(&mut self, values: &[Value]) -> AmfResult<()> {
+        values.iter().try_for_each(|value| self.write(value))
+    }
+
+    /// a reference given by the caller must point to a complex value written completely,
+    /// otherwise the reader would never finish resolving it
+    fn write_reference(&mut self, index: u16) -> AmfResult<()> {
+        match self.referenceable.completed.get(index as usize) {
+            None => Err(AmfError::OutOfRangeReference {
+                index: index as usize,
+            }),
+            Some(false) => Err(AmfError::CircularReference {
+                index: index as usize,
+            }),
+            Some(true) => Value::write_reference(&mut self.inner, index),
+        }
+    }
+
+    fn write_referenceable(&mut self, value: &Value) -> AmfResult<()> {
+        if self.use_references
+            && let Some(index) = self
+                .referenceable
+                .objects
+                .iter()
+                .zip(&self.referenceable.completed)
+                .position(|(object, completed)| *completed && object == value)
+            && let Ok(index) = u16::try_from(index)
+        {
+            return Value::write_reference(&mut self.inner, index);
+        }
+
+        let index = self.referenceable.completed.len();
+        self.referenceable.completed.push(false);
+        if self.use_references {
+            self.referenceable.objects.push(value.clone());
         }
+        match value {
+            Value::Object {
+                name: Some(name),
+                entries,
+            } => {
+                self.inner.write_u8(amf0_marker::TYPED_OBJECT)?;
+                Value::write_short_string_inner(&mut self.inner, name)?;
+                self.write_pairs_inner(entries)?;
+            }
+            Value::Object {
+                name: None,
+                entries,
+            } => {
+                self.inner.write_u8(amf0_marker::OBJECT)?;
+                self.write_pairs_inner(entries)?;
+            }
+            Value::ECMAArray(arr) => {
+                assert!(arr.len() <= 0xFFFF_FFFF);
+                self.inner.write_u8(amf0_marker::ECMA_ARRAY)?;
+                self.inner.write_u32::<BigEndian>(arr.len() as u32)?;
+                self.write_pairs_inner(arr)?;
+            }
+            Value::StrictArray(arr) => {
+                assert!(arr.len() <= 0xFFFF_FFFF);
+                self.inner.write_u8(amf0_marker::STRICT_ARRAY)?;
+                self.inner.write_u32::<BigEndian>(arr.len() as u32)?;
+                self.write_all(arr)?;
+            }
+            _ => unreachable!("not a complex value: {:?}", value),
+        }
+        self.referenceable.completed[index] = true;
+        Ok(())
+    }
+
+    fn write_pairs_inner(&mut self, entries: &[(String, Value)]) -> AmfResult<()> {
+        for (key, value) in entries {
+            Value::write_short_string_inner(&mut self.inner, key)?;
+            self.write(value)?;
+        }
+        self.inner.write_u16::<BigEndian>(0)?;
+        self.inner.write_u8(amf0_marker::OBJECT_END)?;
+        Ok(())
+    }
+}
+
+impl<W: io::Write> WriteTo<W> for Value {
+    type Error = AmfError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        Writer::new(writer).write(self)
     }
 }
 
@@ -71,26 +207,14 @@ impl Value {
         }
         Ok(())
     }
-    fn write_pairs_inner<W: io::Write>(
-        writer: &mut W,
-        entries: &[(String, Value)],
-    ) -> AmfResult<()> {
-        for (key, value) in entries {
-            Self::write_short_string_inner(writer, key)?;
-            value.write_to(writer)?;
-        }
-        writer.write_u16::<BigEndian>(0)?;
-        writer.write_u8(amf0_marker::OBJECT_END)?;
-        Ok(())
-    }
     fn write_anonymous_object_arr<W: io::Write>(
         writer: &mut W,
         entries: &[(String, Value)],
     ) -> AmfResult<()> {
-        assert!(entries.len() <= 0xFFFF_FFFF);
-        writer.write_u8(amf0_marker::OBJECT)?;
-        Self::write_pairs_inner(writer, entries)?;
-        Ok(())
+        Writer::new(writer).write(&Value::Object {
+            name: None,
+            entries: entries.to_vec(),
+        })
     }
     pub fn write_anonymous_object<W: io::Write>(
         writer: &mut W,
@@ -120,24 +244,14 @@ impl Value {
         writer: &mut W,
         arr: &[(String, Value)],
     ) -> AmfResult<()> {
-        assert!(arr.len() <= 0xFFFF_FFFF);
-        writer.write_u8(amf0_marker::ECMA_ARRAY)?;
-        writer.write_u32::<BigEndian>(arr.len() as u32)?;
-        Self::write_pairs_inner(writer, arr)?;
-        Ok(())
+        Writer::new(writer).write(&Value::ECMAArray(arr.to_vec()))
     }
     fn write_object_end<W: io::Write>(writer: &mut W) -> AmfResult<()> {
         writer.write_u8(amf0_marker::OBJECT_END)?;
         Ok(())
     }
     pub fn write_strict_array<W: io::Write>(writer: &mut W, arr: &[Value]) -> AmfResult<()> {
-        assert!(arr.len() <= 0xFFFF_FFFF);
-        writer.write_u8(amf0_marker::STRICT_ARRAY)?;
-        writer.write_u32::<BigEndian>(arr.len() as u32)?;
-        for v in arr {
-            v.write_to(writer)?;
-        }
-        Ok(())
+        Writer::new(writer).write(&Value::StrictArray(arr.to_vec()))
     }
     pub fn write_date<W: io::Write>(
         writer: &mut W,
@@ -160,11 +274,10 @@ impl Value {
         name: &str,
         entries: &[(String, Value)],
     ) -> AmfResult<()> {
-        assert!(entries.len() <= 0xFFFF_FFFF);
-        writer.write_u8(amf0_marker::TYPED_OBJECT)?;
-        Self::write_short_string_inner(writer, name)?;
-        Self::write_pairs_inner(writer, entries)?;
-        Ok(())
+        Writer::new(writer).write(&Value::Object {
+            name: Some(name.to_owned()),
+            entries: entries.to_vec(),
+        })
     }
     pub fn write_typed_object<W: io::Write>(
         writer: &mut W,
@@ -189,7 +302,11 @@ impl Value {
 mod tests {
     use core::time;
 
-    use crate::{amf0::Value, amf3};
+    use crate::{
+        amf0::{Value, Writer},
+        amf3,
+        errors::AmfError,
+    };
     use utils::traits::writer::WriteTo;
 
     macro_rules! encode {
@@ -321,6 +438,75 @@ mod tests {
         )
     }
 
+    fn stream_object() -> Value {
+        Value::Object {
+            name: None,
+            entries: vec![
+                ("codec".to_string(), Value::String("avc1".to_string())),
+                ("width".to_string(), Value::Number(1280.0)),
+            ],
+        }
+    }
+
+    /// the stream object appears 4 times, every complex value takes a reference index
+    fn repeated_values() -> Vec<Value> {
+        vec![
+            Value::String("onMetaData".to_string()),
+            Value::ECMAArray(vec![
+                ("main".to_string(), stream_object()),
+                ("backup".to_string(), stream_object()),
+                (
+                    "renditions".to_string(),
+                    Value::StrictArray(vec![
+                        stream_object(),
+                        Value::Object {
+                            name: Some("org.amf.Rendition".to_string()),
+                            entries: vec![("width".to_string(), Value::Number(640.0))],
+                        },
+                    ]),
+                ),
+            ]),
+            stream_object(),
+        ]
+    }
+
+    #[test]
+    fn repeated_objects_are_written_as_references() {
+        let mut buf = Vec::new();
+        Value::write_all(&mut buf, &repeated_values(), true).unwrap();
+        assert_eq!(
+            buf,
+            include_bytes!("../../test_data/amf0-writer-references.bin")
+        );
+
+        let mut expanded = Vec::new();
+        Value::write_all(&mut expanded, &repeated_values(), false).unwrap();
+        assert!(expanded.len() > buf.len());
+        assert_eq!(Value::read_all(&buf[..]).unwrap(), repeated_values());
+        assert_eq!(Value::read_all(&expanded[..]).unwrap(), repeated_values());
+    }
+
+    #[test]
+    fn reference_to_an_ancestor_is_circular() {
+        // the object gets index 0 and refers to itself while still being written
+        let cyclic = Value::Object {
+            name: None,
+            entries: vec![(
+                "children".to_string(),
+                Value::StrictArray(vec![Value::Reference { index: 0 }]),
+            )],
+        };
+        let mut buf = Vec::new();
+        assert!(matches!(
+            cyclic.write_to(&mut buf),
+            Err(AmfError::CircularReference { index: 0 })
+        ));
+        assert!(matches!(
+            Writer::new(&mut buf).write(&Value::Reference { index: 3 }),
+            Err(AmfError::OutOfRangeReference { index: 3 })
+        ));
+    }
+
     #[test]
     fn ecma_array() {
         let arr = vec![
@@ -1,7 +1,10 @@
 use core::time;
-use std::io;
+use std::io::{self, Read};
 
-use crate::errors::{AmfError, AmfResult};
+use crate::{
+    errors::{AmfError, AmfResult},
+    limits::{ReadBudget, ReadLimits},
+};
 use byteorder::{BigEndian, ReadBytesExt};
 use utils::traits::reader::ReadFrom;
 
@@ -17,12 +20,15 @@ struct Amf3Referenceable {
     traits: Vec<Amf3Trait>,
     strings: Vec<String>,
     objects: Vec<Value>,
+    /// what each object was charged, charged again for each reference to it
+    decoded_bytes: Vec<usize>,
 }
 
 #[derive(Debug)]
 pub struct Reader<R> {
     inner: R,
     referenceable: Amf3Referenceable,
+    budget: ReadBudget,
 }
 
 impl<R> Reader<R> {
@@ -42,17 +48,34 @@ where
     R: io::Read,
 {
     pub fn new(inner: R) -> Self {
+        Self::with_limits(inner, ReadLimits::default())
+    }
+
+    /// the limits span all the values read
+    pub fn with_limits(inner: R, limits: ReadLimits) -> Self {
+        Self::with_budget(inner, ReadBudget::new(limits))
+    }
+
+    pub(crate) fn with_budget(inner: R, budget: ReadBudget) -> Self {
         Self {
             inner,
             referenceable: Amf3Referenceable {
                 traits: Vec::new(),
                 strings: Vec::new(),
                 objects: Vec::new(),
+                decoded_bytes: Vec::new(),
             },
+            budget,
         }
     }
+
+    pub(crate) fn into_budget(self) -> ReadBudget {
+        self.budget
+    }
+
     pub fn read(&mut self) -> AmfResult<Value> {
         let marker = self.inner.read_u8()?;
+        self.budget.charge_values(1)?;
 
         match marker {
             amf3_marker::UNDEFINED => Ok(Value::Undefined),
@@ -107,9 +130,14 @@ where
             Ok(SizeOrIndex::Size(value))
         }
     }
+    /// the buffer grows with the data actually read, a declared length beyond the data allocates nothing more
     fn read_bytes(&mut self, len: usize) -> AmfResult<Vec<u8>> {
-        let mut buf = vec![0; len];
-        self.inner.read_exact(&mut buf)?;
+        self.budget.charge_string(len)?;
+        let mut buf = Vec::new();
+        self.inner.by_ref().take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
         Ok(buf)
     }
     fn read_utf8(&mut self, len: usize) -> AmfResult<String> {
@@ -125,6 +153,7 @@ where
                     .strings
                     .get(index)
                     .ok_or(AmfError::OutOfRangeReference { index })?;
+                self.budget.charge(result.len())?;
                 Ok(result.clone())
             }
             SizeOrIndex::Size(size) => {
@@ -141,28 +170,48 @@ where
         F: FnOnce(&mut Self, usize) -> AmfResult<Value>,
     {
         match self.read_size_or_index()? {
-            SizeOrIndex::Index(index) => self
-                .referenceable
-                .objects
-                .get(index)
-                .ok_or(AmfError::OutOfRangeReference { index })
-                .and_then(|v| {
-                    if *v == Value::Null {
-                        Err(AmfError::CircularReference { index })
-                    } else {
-                        Ok(v.clone())
-                    }
-                }),
+            SizeOrIndex::Index(index) => {
+                let value = self
+                    .referenceable
+                    .objects
+                    .get(index)
+                    .ok_or(AmfError::OutOfRangeReference { index })
+                    .and_then(|v| {
+                        if *v == Value::Null {
+                            Err(AmfError::CircularReference { index })
+                        } else {
+                            Ok(v)
+                        }
+                    })?;
+                self.budget
+                    .charge(self.referenceable.decoded_bytes[index])?;
+                Ok(value.clone())
+            }
             SizeOrIndex::Size(size) => {
+                let decoded_bytes = self.budget.decoded_bytes();
                 let index = self.referenceable.objects.len();
                 self.referenceable.objects.push(Value::Null);
+                self.referenceable.decoded_bytes.push(0);
                 let result = f(self, size)?;
                 self.referenceable.objects[index] = result.clone();
+                self.referenceable.decoded_bytes[index] =
+                    self.budget.decoded_bytes() - decoded_bytes;
                 Ok(result)
             }
         }
     }
 
+    /// reads the entries of an array, an object, an object vector or a dictionary
+    fn nested<F>(&mut self, f: F) -> AmfResult<Value>
+    where
+        F: FnOnce(&mut Self) -> AmfResult<Value>,
+    {
+        self.budget.enter()?;
+        let result = f(self);
+        self.budget.leave();
+        result
+    }
+
     fn read_trait(&mut self, size: usize) -> AmfResult<Amf3Trait> {
         if (size & 0b1) == 0 {
             let index = size >> 1;
@@ -181,6 +230,7 @@ where
 
         let is_dynamic = (size & 0b100) != 0;
         let field_num = size >> 3;
+        self.budget.check_elements(field_num)?;
         let class_name = self.read_and_record_utf8()?;
         let fields = (0..field_num)
             .map(|_| self.read_and_record_utf8())
@@ -245,38 +295,44 @@ where
             if key.is_empty() {
                 return Ok(result);
             }
+            self.budget.check_elements(result.len() + 1)?;
             let value = self.read()?;
             result.push((key, value));
         }
     }
     pub fn read_array(&mut self) -> AmfResult<Value> {
         self.read_and_record_object(|this, size| {
-            let assoc_entries = this.read_pairs()?;
-            let dense_entries = (0..size).map(|_| this.read()).collect::<AmfResult<_>>()?;
-            Ok(Value::Array {
-                assoc_entries,
-                dense_entries,
+            this.nested(|this| {
+                this.budget.check_elements(size)?;
+                let assoc_entries = this.read_pairs()?;
+                let dense_entries = (0..size).map(|_| this.read()).collect::<AmfResult<_>>()?;
+                Ok(Value::Array {
+                    assoc_entries,
+                    dense_entries,
+                })
             })
         })
     }
     pub fn read_object(&mut self) -> AmfResult<Value> {
         self.read_and_record_object(|this, size| {
-            let amf3_trait = this.read_and_record_trait(size)?;
-            let mut entries = amf3_trait
-                .fields
-                .iter()
-                .map(|key| {
-                    let value = this.read()?;
-                    Ok((key.clone(), value))
+            this.nested(|this| {
+                let amf3_trait = this.read_and_record_trait(size)?;
+                let mut entries = amf3_trait
+                    .fields
+                    .iter()
+                    .map(|key| {
+                        let value = this.read()?;
+                        Ok((key.clone(), value))
+                    })
+                    .collect::<AmfResult<Vec<_>>>()?;
+                if amf3_trait.is_dynamic {
+                    entries.extend(this.read_pairs()?);
+                }
+                Ok(Value::Object {
+                    name: amf3_trait.class_name,
+                    sealed_fields_count: amf3_trait.fields.len(),
+                    entries,
                 })
-                .collect::<AmfResult<Vec<_>>>()?;
-            if amf3_trait.is_dynamic {
-                entries.extend(this.read_pairs()?);
-            }
-            Ok(Value::Object {
-                name: amf3_trait.class_name,
-                sealed_fields_count: amf3_trait.fields.len(),
-                entries,
             })
         })
     }
@@ -286,8 +342,14 @@ where
     pub fn read_byte_array(&mut self) -> AmfResult<Value> {
         self.read_and_record_object(|this, len| this.read_bytes(len).map(Value::ByteArray))
     }
+    /// the entries of the number vectors are not values read, they are charged as such here
+    fn check_vector(&mut self, count: usize) -> AmfResult<()> {
+        self.budget.check_elements(count)?;
+        self.budget.charge_values(count)
+    }
     pub fn read_i32_vector(&mut self) -> AmfResult<Value> {
         self.read_and_record_object(|this, count| {
+            this.check_vector(count)?;
             let is_fixed = this.inner.read_u8()? != 0;
             let entries = (0..count)
                 .map(|_| this.inner.read_i32::<BigEndian>())
@@ -297,6 +359,7 @@ where
     }
     pub fn read_u32_vector(&mut self) -> AmfResult<Value> {
         self.read_and_record_object(|this, count| {
+            this.check_vector(count)?;
             let is_fixed = this.inner.read_u8()? != 0;
             let entries = (0..count)
                 .map(|_| this.inner.read_u32::<BigEndian>())
@@ -306,6 +369,7 @@ where
     }
     pub fn read_double_vector(&mut self) -> AmfResult<Value> {
         self.read_and_record_object(|this, count| {
+            this.check_vector(count)?;
             let is_fixed = this.inner.read_u8()? != 0;
             let entries = (0..count)
                 .map(|_| this.inner.read_f64::<BigEndian>())
@@ -315,31 +379,37 @@ where
     }
     pub fn read_object_vector(&mut self) -> AmfResult<Value> {
         self.read_and_record_object(|this, count| {
-            let is_fixed = this.inner.read_u8()? != 0;
-            let class_name = this.read_and_record_utf8()?;
-            let entries = (0..count).map(|_| this.read()).collect::<AmfResult<_>>()?;
-            Ok(Value::ObjectVector {
-                is_fixed,
-                entries,
-                class_name: if class_name.is_empty() {
-                    None
-                } else {
-                    Some(class_name)
-                },
+            this.nested(|this| {
+                this.budget.check_elements(count)?;
+                let is_fixed = this.inner.read_u8()? != 0;
+                let class_name = this.read_and_record_utf8()?;
+                let entries = (0..count).map(|_| this.read()).collect::<AmfResult<_>>()?;
+                Ok(Value::ObjectVector {
+                    is_fixed,
+                    entries,
+                    class_name: if class_name.is_empty() {
+                        None
+                    } else {
+                        Some(class_name)
+                    },
+                })
             })
         })
     }
     pub fn read_dictionary(&mut self) -> AmfResult<Value> {
         self.read_and_record_object(|this, count| {
-            let is_weak = this.inner.read_u8()? == 1;
-            let entries = (0..count)
-                .map(|_| {
-                    let key = this.read()?;
-                    let value = this.read()?;
-                    Ok((key, value))
-                })
-                .collect::<AmfResult<_>>()?;
-            Ok(Value::Dictionary { is_weak, entries })
+            this.nested(|this| {
+                this.budget.check_elements(count)?;
+                let is_weak = this.inner.read_u8()? == 1;
+                let entries = (0..count)
+                    .map(|_| {
+                        let key = this.read()?;
+                        let value = this.read()?;
+                        Ok((key, value))
+                    })
+                    .collect::<AmfResult<_>>()?;
+                Ok(Value::Dictionary { is_weak, entries })
+            })
         })
     }
 }
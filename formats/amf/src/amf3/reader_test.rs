@@ -6,7 +6,11 @@ mod tests {
         vec,
     };
 
-    use crate::{amf3::Reader, amf3::Value, errors::AmfError};
+    use crate::{
+        amf3::{Reader, Value, amf3_marker},
+        errors::AmfError,
+        limits::ReadLimit,
+    };
 
     macro_rules! decode {
         ($file:expr) => {{
@@ -629,4 +633,77 @@ mod tests {
     fn u29() {
         assert_eof!("../../test_data/amf3-u29-partial.bin");
     }
+
+    #[test]
+    fn deeply_nested_arrays_exceed_the_depth_limit() {
+        let mut data = Vec::new();
+        for _ in 0..10_000 {
+            // one dense entry, no assoc entry
+            data.extend_from_slice(&[amf3_marker::ARRAY, 0x03, 0x01]);
+        }
+        data.push(amf3_marker::NULL);
+        assert!(matches!(
+            Reader::new(&mut &data[..]).read(),
+            Err(AmfError::LimitExceeded {
+                limit: ReadLimit::Depth,
+                value: 33,
+                max: 32
+            })
+        ));
+    }
+
+    #[test]
+    fn declared_lengths_are_checked_before_allocating() {
+        let mut data = vec![amf3_marker::STRING, 0xFF, 0xFF, 0xFF, 0xFF];
+        data.extend_from_slice(b"short");
+        assert!(matches!(
+            Reader::new(&mut &data[..]).read(),
+            Err(AmfError::LimitExceeded {
+                limit: ReadLimit::StringLength,
+                value: 0x0FFF_FFFF,
+                ..
+            })
+        ));
+
+        for marker in [
+            amf3_marker::ARRAY,
+            amf3_marker::VECTOR_INT,
+            amf3_marker::VECTOR_OBJECT,
+        ] {
+            let data = [marker, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+            assert!(matches!(
+                Reader::new(&mut &data[..]).read(),
+                Err(AmfError::LimitExceeded {
+                    limit: ReadLimit::Elements,
+                    value: 0x0FFF_FFFF,
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn objects_with_too_many_keys_exceed_the_elements_limit() {
+        // a dynamic anonymous object, its keys after the first one are references to it
+        let mut data = vec![
+            amf3_marker::OBJECT,
+            0x0B,
+            0x01,
+            0x03,
+            b'k',
+            amf3_marker::NULL,
+        ];
+        for _ in 1..1_000_000 {
+            data.extend_from_slice(&[0x00, amf3_marker::NULL]);
+        }
+        data.push(0x01);
+        assert!(matches!(
+            Reader::new(&mut &data[..]).read(),
+            Err(AmfError::LimitExceeded {
+                limit: ReadLimit::Elements,
+                value: 65537,
+                max: 65536
+            })
+        ));
+    }
 }
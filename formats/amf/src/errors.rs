@@ -1,8 +1,11 @@
-use std::{io, string};
+use std::{io, str, string};
 
 use thiserror::Error;
 
-use crate::amf3::{self};
+use crate::{
+    amf3::{self},
+    limits::ReadLimit,
+};
 
 #[derive(Error, Debug)]
 pub enum AmfError {
@@ -10,6 +13,9 @@ pub enum AmfError {
     Io(#[from] io::Error),
     #[error("invalid utf8 data: {0}")]
     InvalidUtf8(#[from] string::FromUtf8Error),
+    /// of the strings borrowed from the input
+    #[error("invalid utf8 data: {0}")]
+    InvalidUtf8Str(#[from] str::Utf8Error),
     #[error("unsupported amf value marker: {marker}")]
     Unsupported { marker: u8 },
     #[error("unknown marker: {marker}")]
@@ -30,6 +36,12 @@ pub enum AmfError {
     U29OutOfRange { value: u32 },
     #[error("size value out of range, value: {value}")]
     SizeOutOfRange { value: usize },
+    #[error("{limit} limit exceeded: {value} > {max}")]
+    LimitExceeded {
+        limit: ReadLimit,
+        value: usize,
+        max: usize,
+    },
     #[error("trait: {entries:?}, sealed_count: {sealed_count}")]
     Amf3TraitInvalid {
         entries: Vec<(String, amf3::Value)>,
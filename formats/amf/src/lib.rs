@@ -1,14 +1,13 @@
 use std::{collections::HashMap, io};
 
 use errors::{AmfError, AmfResult};
-use utils::traits::{
-    reader::{ReadFrom, ReadRemainingFrom},
-    writer::WriteTo,
-};
+use limits::ReadLimits;
+use utils::traits::{reader::ReadRemainingFrom, writer::WriteTo};
 
 pub mod amf0;
 pub mod amf3;
 pub mod errors;
+pub mod limits;
 
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -36,24 +35,57 @@ pub enum Version {
     Amf3 = 3,
 }
 
+/// how a value is read, the limits bound each value read
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReadOptions {
+    pub version: Version,
+    pub limits: ReadLimits,
+}
+
+impl From<Version> for ReadOptions {
+    fn from(version: Version) -> Self {
+        Self {
+            version,
+            limits: ReadLimits::default(),
+        }
+    }
+}
+
 impl<R: io::Read> ReadRemainingFrom<Version, R> for Value {
     type Error = AmfError;
     fn read_remaining_from(header: Version, reader: &mut R) -> Result<Self, Self::Error> {
-        match header {
-            Version::Amf0 => amf0::Value::read_from(reader).map(Value::AMF0Value),
-            Version::Amf3 => amf3::Value::read_from(reader).map(Value::AMF3Value),
+        Value::read_remaining_from(ReadOptions::from(header), reader)
+    }
+}
+
+impl<R: io::Read> ReadRemainingFrom<ReadOptions, R> for Value {
+    type Error = AmfError;
+    fn read_remaining_from(header: ReadOptions, reader: &mut R) -> Result<Self, Self::Error> {
+        match header.version {
+            Version::Amf0 => amf0::Reader::with_limits(reader, header.limits)
+                .read()
+                .map(Value::AMF0Value),
+            Version::Amf3 => amf3::Reader::with_limits(reader, header.limits)
+                .read()
+                .map(Value::AMF3Value),
         }
     }
 }
 
 impl Value {
-    pub fn read_string<R: io::Read>(reader: &mut R, version: Version) -> AmfResult<Option<String>> {
-        let value = Value::read_remaining_from(version, reader)?;
+    pub fn read_string<R: io::Read>(
+        reader: &mut R,
+        options: impl Into<ReadOptions>,
+    ) -> AmfResult<Option<String>> {
+        let value = Value::read_remaining_from(options.into(), reader)?;
         Ok(value.try_as_str().map(|v| v.to_owned()))
     }
 
-    pub fn read_null<R: io::Read>(reader: &mut R, version: Version) -> AmfResult<Option<()>> {
-        let value = Value::read_remaining_from(version, reader)?;
+    pub fn read_null<R: io::Read>(
+        reader: &mut R,
+        options: impl Into<ReadOptions>,
+    ) -> AmfResult<Option<()>> {
+        let value = Value::read_remaining_from(options.into(), reader)?;
         match value {
             Value::AMF0Value(value) => {
                 if matches!(value, amf0::Value::Null) {
@@ -72,24 +104,30 @@ impl Value {
         }
     }
 
-    pub fn read_number<R: io::Read>(reader: &mut R, version: Version) -> AmfResult<Option<f64>> {
-        let value = Value::read_remaining_from(version, reader)?;
+    pub fn read_number<R: io::Read>(
+        reader: &mut R,
+        options: impl Into<ReadOptions>,
+    ) -> AmfResult<Option<f64>> {
+        let value = Value::read_remaining_from(options.into(), reader)?;
         Ok(value.try_as_f64())
     }
 
     pub fn read_object<R: io::Read>(
         reader: &mut R,
-        version: Version,
+        options: impl Into<ReadOptions>,
     ) -> AmfResult<Option<HashMap<String, Value>>> {
-        let value = Value::read_remaining_from(version, reader)?;
+        let value = Value::read_remaining_from(options.into(), reader)?;
         match value.try_into_pairs() {
             Ok(iter) => Ok(Some(iter.collect::<HashMap<String, Value>>())),
             _ => Ok(None),
         }
     }
 
-    pub fn read_bool<R: io::Read>(reader: &mut R, version: Version) -> AmfResult<Option<bool>> {
-        let value = Value::read_remaining_from(version, reader)?;
+    pub fn read_bool<R: io::Read>(
+        reader: &mut R,
+        options: impl Into<ReadOptions>,
+    ) -> AmfResult<Option<bool>> {
+        let value = Value::read_remaining_from(options.into(), reader)?;
         Ok(value.try_as_bool())
     }
 
@@ -99,12 +137,12 @@ impl Value {
     {
         match version {
             Version::Amf0 => Ok(amf0::Value::read_all(reader)?
-                .iter()
-                .map(|v| Value::from(v.clone()))
+                .into_iter()
+                .map(Value::from)
                 .collect()),
             Version::Amf3 => Ok(amf3::Value::read_all(reader)?
-                .iter()
-                .map(|v| Value::from(v.clone()))
+                .into_iter()
+                .map(Value::from)
                 .collect()),
         }
     }
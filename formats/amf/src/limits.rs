@@ -0,0 +1,134 @@
+use std::fmt;
+
+use crate::errors::{AmfError, AmfResult};
+
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+pub const DEFAULT_MAX_DECODED_BYTES: usize = 16 * 1024 * 1024;
+pub const DEFAULT_MAX_STRING_LENGTH: usize = 4 * 1024 * 1024;
+pub const DEFAULT_MAX_ELEMENTS: usize = 64 * 1024;
+
+/// what a value is charged in the decoded bytes besides the bytes of its strings
+const VALUE_DECODED_BYTES: usize = 8;
+
+/// bounds what a reader decodes, so that values from untrusted peers can not exhaust the stack or the memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadLimits {
+    /// of the arrays and objects nested in each other
+    pub max_depth: usize,
+    /// of all the values read by a reader, the bytes of strings and byte arrays plus 8 bytes for each value.
+    /// the values copied by references are counted again
+    pub max_decoded_bytes: usize,
+    /// of a string, a key, an xml document or a byte array
+    pub max_string_length: usize,
+    /// of an array, an object, a vector or a dictionary
+    pub max_elements: usize,
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_decoded_bytes: DEFAULT_MAX_DECODED_BYTES,
+            max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            max_elements: DEFAULT_MAX_ELEMENTS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadLimit {
+    Depth,
+    DecodedBytes,
+    StringLength,
+    Elements,
+}
+
+impl fmt::Display for ReadLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadLimit::Depth => write!(f, "depth"),
+            ReadLimit::DecodedBytes => write!(f, "decoded bytes"),
+            ReadLimit::StringLength => write!(f, "string length"),
+            ReadLimit::Elements => write!(f, "elements"),
+        }
+    }
+}
+
+/// what a reader has used up of its limits, handed over to the amf3 reader of an avmplus value
+#[derive(Debug, Clone)]
+pub(crate) struct ReadBudget {
+    limits: ReadLimits,
+    depth: usize,
+    decoded_bytes: usize,
+}
+
+impl ReadBudget {
+    pub(crate) fn new(limits: ReadLimits) -> Self {
+        Self {
+            limits,
+            depth: 0,
+            decoded_bytes: 0,
+        }
+    }
+
+    pub(crate) fn decoded_bytes(&self) -> usize {
+        self.decoded_bytes
+    }
+
+    pub(crate) fn enter(&mut self) -> AmfResult<()> {
+        if self.depth >= self.limits.max_depth {
+            return Err(AmfError::LimitExceeded {
+                limit: ReadLimit::Depth,
+                value: self.depth + 1,
+                max: self.limits.max_depth,
+            });
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    pub(crate) fn charge(&mut self, bytes: usize) -> AmfResult<()> {
+        let decoded_bytes = self.decoded_bytes.saturating_add(bytes);
+        if decoded_bytes > self.limits.max_decoded_bytes {
+            return Err(AmfError::LimitExceeded {
+                limit: ReadLimit::DecodedBytes,
+                value: decoded_bytes,
+                max: self.limits.max_decoded_bytes,
+            });
+        }
+        self.decoded_bytes = decoded_bytes;
+        Ok(())
+    }
+
+    /// charges a value, `count` of them for the vectors
+    pub(crate) fn charge_values(&mut self, count: usize) -> AmfResult<()> {
+        self.charge(count.saturating_mul(VALUE_DECODED_BYTES))
+    }
+
+    /// checks a declared length before anything is allocated for it
+    pub(crate) fn charge_string(&mut self, length: usize) -> AmfResult<()> {
+        if length > self.limits.max_string_length {
+            return Err(AmfError::LimitExceeded {
+                limit: ReadLimit::StringLength,
+                value: length,
+                max: self.limits.max_string_length,
+            });
+        }
+        self.charge(length)
+    }
+
+    pub(crate) fn check_elements(&self, count: usize) -> AmfResult<()> {
+        if count > self.limits.max_elements {
+            return Err(AmfError::LimitExceeded {
+                limit: ReadLimit::Elements,
+                value: count,
+                max: self.limits.max_elements,
+            });
+        }
+        Ok(())
+    }
+}
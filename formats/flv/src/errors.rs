@@ -22,7 +22,7 @@ pub enum FLVError {
     UnknownCodecID(u8),
     #[error("unknown avc packet type: {0}")]
     UnknownAVCPacketType(u8),
-    #[error("amf meta error: {0:?}")]
+    #[error("amf meta error: {0}")]
     AMFError(#[from] amf_formats::errors::AmfError),
     #[error("unexpected value: {0}")]
     UnexpectedValue(String),
@@ -49,3 +49,27 @@ pub enum FLVError {
 }
 
 pub type FLVResult<T> = Result<T, FLVError>;
+
+/// the errors of the async tag stream, with the byte offset they are found at
+#[derive(Debug, Error)]
+pub enum FlvStreamError {
+    #[error("Io error: {0}")]
+    Io(#[from] io::Error),
+    /// the data ends before the flv header does, a truncated tag ends the stream instead
+    #[error("flv data truncated at byte {offset}")]
+    Truncated { offset: u64 },
+    #[error("corrupted flv tag at byte {offset}: {source}")]
+    Corrupted { offset: u64, source: FLVError },
+    #[error(
+        "previous tag size of the tag at byte {offset} is {previous_tag_size}, expect {expected}"
+    )]
+    PreviousTagSizeMismatch {
+        offset: u64,
+        previous_tag_size: u32,
+        expected: u32,
+    },
+    #[error("no flv tag found in {window} bytes from byte {offset}")]
+    ResyncFailed { offset: u64, window: u64 },
+}
+
+pub type FlvStreamResult<T> = Result<T, FlvStreamError>;
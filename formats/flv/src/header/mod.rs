@@ -32,4 +32,16 @@ impl FLVHeader {
             data_offset: 9,
         }
     }
+
+    pub fn has_audio(&self) -> bool {
+        self.has_audio
+    }
+
+    pub fn has_video(&self) -> bool {
+        self.has_video
+    }
+
+    pub fn data_offset(&self) -> u32 {
+        self.data_offset
+    }
 }
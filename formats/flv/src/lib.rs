@@ -1,3 +1,4 @@
 pub mod errors;
 pub mod header;
+pub mod stream;
 pub mod tag;
@@ -0,0 +1,427 @@
+use std::io::{self, Cursor, SeekFrom};
+
+use futures::{Stream, stream};
+use num::ToPrimitive;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use utils::traits::{fixed_packet::FixedPacket, reader::ReadFrom};
+
+use crate::{
+    errors::{FLVError, FlvStreamError, FlvStreamResult},
+    header::FLVHeader,
+    tag::{FLVTag, flv_tag_header::FLVTagHeader},
+};
+
+#[cfg(test)]
+mod test;
+
+const FLV_HEADER_BYTES: usize = 9;
+const PREVIOUS_TAG_SIZE_BYTES: usize = 4;
+/// how far from the position of a resyncing seek the next tag is looked for,
+/// and how far a recovering stream skips garbage at most
+pub const DEFAULT_RESYNC_WINDOW: u64 = 256 * 1024;
+/// a recovering stream takes a tag with more data than this for garbage
+pub const MAX_RECOVERED_TAG_DATA_BYTES: u32 = 8 << 20;
+/// the tag a recovering stream skips garbage to is at most this far behind the tag before it,
+/// the audio and the video of a file are interleaved closer
+const MAX_RECOVERED_TIMESTAMP_BACKSTEP_MS: u32 = 1000;
+/// and at most this far ahead of it
+const MAX_RECOVERED_TIMESTAMP_STEP_MS: u32 = 5 * 60 * 1000;
+
+/// what the stream does with a damaged tag
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlvReadMode {
+    /// the tag fails the read, for checking the files written by the server itself
+    #[default]
+    Strict,
+    /// a wrong PreviousTagSize is logged and the tags are still framed by their DataSize,
+    /// an implausible tag header or a tag failing to parse is skipped over to the next plausible tag
+    /// within the resync window, for playing the files of other tools
+    Recover,
+}
+
+/// the damage a recovering stream got past so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlvRecoveryStats {
+    /// the tags whose PreviousTagSize field did not match their size
+    pub previous_tag_size_mismatches: u64,
+    /// the times garbage was skipped to the next plausible tag
+    pub resyncs: u64,
+    pub skipped_bytes: u64,
+}
+
+/// reads the tags of a flv file one at a time, the file is never loaded as a whole,
+/// each tag comes along with its byte offset in the file
+#[derive(Debug)]
+pub struct FlvTagStream<R> {
+    inner: R,
+    header: FLVHeader,
+    /// offset of the first tag
+    data_start: u64,
+    position: u64,
+    resync_window: u64,
+    /// where the data ends in the middle of a tag, typical of files from a crashed recorder
+    truncated_at: Option<u64>,
+    mode: FlvReadMode,
+    recovery: FlvRecoveryStats,
+    /// of the latest tag read, garbage is skipped to a tag not far from it
+    last_timestamp: Option<u32>,
+}
+
+enum TagSearch {
+    Found(u64),
+    /// no tag up to the end of the data, where the search stopped
+    EndOfData(u64),
+    /// no tag in the window
+    NotFound,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> FlvTagStream<R> {
+    /// reads and checks the flv header, the stream is at the first tag then
+    pub async fn new(mut inner: R) -> FlvStreamResult<Self> {
+        let mut header_bytes = [0; FLV_HEADER_BYTES];
+        let read = read_full(&mut inner, &mut header_bytes).await?;
+        if read < FLV_HEADER_BYTES {
+            return Err(FlvStreamError::Truncated {
+                offset: read as u64,
+            });
+        }
+        let header = FLVHeader::read_from(&mut Cursor::new(&header_bytes))
+            .map_err(|source| FlvStreamError::Corrupted { offset: 0, source })?;
+        if header.data_offset().to_usize().unwrap() < FLV_HEADER_BYTES {
+            return Err(FlvStreamError::Corrupted {
+                offset: 0,
+                source: FLVError::InconsistentHeader(format!(
+                    "data offset {} is within the flv header",
+                    header.data_offset()
+                )),
+            });
+        }
+        // skip the PreviousTagSize0 field
+        let data_start = u64::from(header.data_offset()) + PREVIOUS_TAG_SIZE_BYTES as u64;
+        inner.seek(SeekFrom::Start(data_start)).await?;
+        Ok(Self {
+            inner,
+            header,
+            data_start,
+            position: data_start,
+            resync_window: DEFAULT_RESYNC_WINDOW,
+            truncated_at: None,
+            mode: FlvReadMode::default(),
+            recovery: FlvRecoveryStats::default(),
+            last_timestamp: None,
+        })
+    }
+
+    pub fn with_resync_window(mut self, resync_window: u64) -> Self {
+        self.resync_window = resync_window;
+        self
+    }
+
+    pub fn with_read_mode(mut self, mode: FlvReadMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// all zero for a strict stream
+    pub fn recovery_stats(&self) -> FlvRecoveryStats {
+        self.recovery
+    }
+
+    pub fn header(&self) -> &FLVHeader {
+        &self.header
+    }
+
+    pub fn data_start(&self) -> u64 {
+        self.data_start
+    }
+
+    /// where the next tag is read from
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// set once the stream ended at a truncated tag
+    pub fn truncated_at(&self) -> Option<u64> {
+        self.truncated_at
+    }
+
+    /// goes on from a tag known to start at the position
+    pub async fn seek(&mut self, position: u64) -> FlvStreamResult<()> {
+        self.inner.seek(SeekFrom::Start(position)).await?;
+        self.position = position;
+        self.truncated_at = None;
+        self.last_timestamp = None;
+        Ok(())
+    }
+
+    /// goes on from the first tag at or after the position, which may be a few bytes off,
+    /// and returns where that tag starts. a tag is told apart from payload bytes by the
+    /// PreviousTagSize field following it, or by the one before it for the last tag of the file
+    pub async fn seek_and_resync(&mut self, position: u64) -> FlvStreamResult<u64> {
+        let start = position.max(self.data_start);
+        match self.find_tag(start, |_| true).await? {
+            TagSearch::Found(candidate) => {
+                self.seek(candidate).await?;
+                Ok(candidate)
+            }
+            _ => Err(FlvStreamError::ResyncFailed {
+                offset: start,
+                window: self.resync_window,
+            }),
+        }
+    }
+
+    /// the first tag at or after the start within the resync window, whose header passes the filter
+    async fn find_tag(
+        &mut self,
+        start: u64,
+        filter: impl Fn(&FLVTagHeader) -> bool,
+    ) -> FlvStreamResult<TagSearch> {
+        let mut window =
+            vec![0; self.resync_window.to_usize().unwrap() + FLVTagHeader::bytes_count()];
+        self.inner.seek(SeekFrom::Start(start)).await?;
+        let read = read_full(&mut self.inner, &mut window).await?;
+        let candidates = (read + 1)
+            .saturating_sub(FLVTagHeader::bytes_count())
+            .min(self.resync_window.to_usize().unwrap());
+        for offset in 0..candidates {
+            let Some(tag_header) = FLVTagHeader::read_plausible(&window[offset..read])
+                .filter(|tag_header| filter(tag_header))
+            else {
+                continue;
+            };
+            let candidate = start + offset as u64;
+            if self.is_tag_start(candidate, &tag_header).await? {
+                return Ok(TagSearch::Found(candidate));
+            }
+        }
+        if read < window.len() {
+            return Ok(TagSearch::EndOfData(start + read as u64));
+        }
+        Ok(TagSearch::NotFound)
+    }
+
+    /// skips the garbage at the position to the next tag of a plausible size and timestamp,
+    /// the stream ends if there is none up to the end of the data
+    async fn recover(&mut self, position: u64) -> FlvStreamResult<()> {
+        let last_timestamp = self.last_timestamp;
+        let search = self
+            .find_tag(position + 1, |tag_header| {
+                is_recoverable(tag_header, last_timestamp)
+            })
+            .await?;
+        match search {
+            TagSearch::Found(next) => {
+                tracing::warn!(
+                    "garbage at byte {} of the flv data, {} bytes skipped to the next tag",
+                    position,
+                    next - position
+                );
+                self.recovery.resyncs += 1;
+                self.recovery.skipped_bytes += next - position;
+                self.seek(next).await?;
+                self.last_timestamp = last_timestamp;
+                Ok(())
+            }
+            TagSearch::EndOfData(end) => {
+                tracing::warn!(
+                    "garbage at byte {} of the flv data up to its end, {} bytes skipped",
+                    position,
+                    end - position
+                );
+                self.recovery.skipped_bytes += end - position;
+                self.position = end;
+                self.truncated_at = Some(position);
+                Ok(())
+            }
+            TagSearch::NotFound => Err(FlvStreamError::ResyncFailed {
+                offset: position + 1,
+                window: self.resync_window,
+            }),
+        }
+    }
+
+    async fn is_tag_start(
+        &mut self,
+        position: u64,
+        tag_header: &FLVTagHeader,
+    ) -> FlvStreamResult<bool> {
+        let mut previous_tag_size = [0; PREVIOUS_TAG_SIZE_BYTES];
+        if self
+            .read_at(position + tag_size(tag_header), &mut previous_tag_size)
+            .await?
+            == PREVIOUS_TAG_SIZE_BYTES
+        {
+            return Ok(u64::from(u32::from_be_bytes(previous_tag_size)) == tag_size(tag_header));
+        }
+
+        // nothing follows the last tag, the field before it has to point back at a tag
+        if position == self.data_start {
+            return Ok(true);
+        }
+        if self
+            .read_at(
+                position - PREVIOUS_TAG_SIZE_BYTES as u64,
+                &mut previous_tag_size,
+            )
+            .await?
+            < PREVIOUS_TAG_SIZE_BYTES
+        {
+            return Ok(false);
+        }
+        let previous_size = u64::from(u32::from_be_bytes(previous_tag_size));
+        let Some(previous_start) = (position - PREVIOUS_TAG_SIZE_BYTES as u64)
+            .checked_sub(previous_size)
+            .filter(|start| *start >= self.data_start)
+        else {
+            return Ok(false);
+        };
+        let mut header_bytes = [0; 11];
+        let read = self.read_at(previous_start, &mut header_bytes).await?;
+        Ok(read == header_bytes.len()
+            && FLVTagHeader::read_plausible(&header_bytes)
+                .is_some_and(|header| tag_size(&header) == previous_size))
+    }
+
+    /// None at the end of the data, a truncated last tag ends it as well, see truncated_at.
+    /// after an error the stream is past the failed tag, a resyncing seek gets it back on track.
+    /// a recovering stream skips the damage it can get past, see recovery_stats
+    pub async fn next_tag(&mut self) -> FlvStreamResult<Option<(u64, FLVTag)>> {
+        loop {
+            if self.truncated_at.is_some() {
+                return Ok(None);
+            }
+            let tag_position = self.position;
+            let mut header_bytes = [0; 11];
+            match self.read(&mut header_bytes).await? {
+                0 => return Ok(None),
+                read if read < header_bytes.len() => {
+                    self.truncated_at = Some(tag_position);
+                    return Ok(None);
+                }
+                _ => {}
+            }
+            let tag_header = match self.mode {
+                FlvReadMode::Strict => FLVTagHeader::read_from(&mut Cursor::new(&header_bytes))
+                    .map_err(|source| FlvStreamError::Corrupted {
+                        offset: tag_position,
+                        source,
+                    })?,
+                FlvReadMode::Recover => match FLVTagHeader::read_plausible(&header_bytes)
+                    .filter(|tag_header| tag_header.data_size <= MAX_RECOVERED_TAG_DATA_BYTES)
+                {
+                    Some(tag_header) => tag_header,
+                    None => {
+                        self.recover(tag_position).await?;
+                        continue;
+                    }
+                },
+            };
+            let expected = tag_size(&tag_header);
+
+            let mut body = vec![0; tag_header.data_size.to_usize().unwrap()];
+            if self.read(&mut body).await? < body.len() {
+                self.truncated_at = Some(tag_position);
+                return Ok(None);
+            }
+
+            let mut previous_tag_size = [0; PREVIOUS_TAG_SIZE_BYTES];
+            match self.read(&mut previous_tag_size).await? {
+                // the last tag may go without it
+                0 => {}
+                PREVIOUS_TAG_SIZE_BYTES => {
+                    let previous_tag_size = u32::from_be_bytes(previous_tag_size);
+                    if u64::from(previous_tag_size) != expected {
+                        let mismatch = FlvStreamError::PreviousTagSizeMismatch {
+                            offset: tag_position,
+                            previous_tag_size,
+                            expected: expected.to_u32().unwrap(),
+                        };
+                        if self.mode == FlvReadMode::Strict {
+                            return Err(mismatch);
+                        }
+                        tracing::warn!("{}, the tag is framed by its data size", mismatch);
+                        self.recovery.previous_tag_size_mismatches += 1;
+                    }
+                }
+                // the tag is whole, the stream ends after it
+                _ => self.truncated_at = Some(tag_position + expected),
+            }
+
+            let timestamp = tag_header.timestamp;
+            match FLVTag::from_parts(tag_header, &body) {
+                Ok(tag) => {
+                    self.last_timestamp = Some(timestamp);
+                    return Ok(Some((tag_position, tag)));
+                }
+                Err(source) if self.mode == FlvReadMode::Strict => {
+                    return Err(FlvStreamError::Corrupted {
+                        offset: tag_position,
+                        source,
+                    });
+                }
+                Err(source) => {
+                    tracing::warn!(
+                        "flv tag at byte {} does not parse: {}, taken for garbage",
+                        tag_position,
+                        source
+                    );
+                    self.recover(tag_position).await?;
+                }
+            }
+        }
+    }
+
+    /// the tags from the current position on, the stream ends after an error
+    pub fn tags(&mut self) -> impl Stream<Item = FlvStreamResult<(u64, FLVTag)>> + '_ {
+        stream::unfold(Some(self), |this| async move {
+            let this = this?;
+            match this.next_tag().await {
+                Ok(Some(tag)) => Some((Ok(tag), Some(this))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = read_full(&mut self.inner, buf).await?;
+        self.position += read as u64;
+        Ok(read)
+    }
+
+    /// leaves the inner reader wherever it stops, the caller seeks back
+    async fn read_at(&mut self, position: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.seek(SeekFrom::Start(position)).await?;
+        read_full(&mut self.inner, buf).await
+    }
+}
+
+/// a tag a recovering stream may skip garbage to, not too large and not too far from the tag before
+fn is_recoverable(tag_header: &FLVTagHeader, last_timestamp: Option<u32>) -> bool {
+    tag_header.data_size <= MAX_RECOVERED_TAG_DATA_BYTES
+        && last_timestamp.is_none_or(|last| {
+            tag_header
+                .timestamp
+                .saturating_add(MAX_RECOVERED_TIMESTAMP_BACKSTEP_MS)
+                >= last
+                && tag_header.timestamp <= last.saturating_add(MAX_RECOVERED_TIMESTAMP_STEP_MS)
+        })
+}
+
+/// the tag header and the data, what the PreviousTagSize field after the tag says
+fn tag_size(tag_header: &FLVTagHeader) -> u64 {
+    FLVTagHeader::bytes_count() as u64 + u64::from(tag_header.data_size)
+}
+
+/// reads until the buffer is full or the data ends, returns the bytes read
+async fn read_full<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
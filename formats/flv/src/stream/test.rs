@@ -0,0 +1,216 @@
+use std::io::Cursor;
+
+use futures::StreamExt;
+
+use super::{FlvReadMode, FlvRecoveryStats, FlvTagStream};
+use crate::{
+    errors::FlvStreamError,
+    tag::{flv_tag_body::FLVTagBody, flv_tag_header::FLVTagType},
+};
+
+const TAG_COUNT: usize = 10;
+
+/// an aac raw frame tag, the payload bytes look like audio tag types to mislead a resync
+fn audio_tag(index: usize) -> Vec<u8> {
+    let data_size = 2 + 20 + index * 3;
+    let timestamp = (index * 23) as u32;
+    let mut tag = vec![8];
+    tag.extend_from_slice(&(data_size as u32).to_be_bytes()[1..]);
+    tag.extend_from_slice(&timestamp.to_be_bytes()[1..]);
+    tag.push((timestamp >> 24) as u8);
+    tag.extend_from_slice(&[0; 3]);
+    tag.extend_from_slice(&[0xAF, 0x01]);
+    tag.extend(std::iter::repeat_n(8, data_size - 2));
+    tag
+}
+
+/// the file and the offset of every tag
+fn flv_file() -> (Vec<u8>, Vec<u64>) {
+    let mut bytes = vec![b'F', b'L', b'V', 1, 0b0000_0100, 0, 0, 0, 9];
+    bytes.extend_from_slice(&0_u32.to_be_bytes());
+    let mut offsets = Vec::new();
+    for index in 0..TAG_COUNT {
+        offsets.push(bytes.len() as u64);
+        let tag = audio_tag(index);
+        bytes.extend_from_slice(&tag);
+        bytes.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+    }
+    (bytes, offsets)
+}
+
+async fn read_all(stream: &mut FlvTagStream<Cursor<Vec<u8>>>) -> Vec<(u64, u32)> {
+    stream
+        .tags()
+        .map(|tag| {
+            let (offset, tag) = tag.unwrap();
+            assert_eq!(tag.tag_header.tag_type, FLVTagType::Audio);
+            assert!(matches!(
+                tag.body_with_filter.body,
+                FLVTagBody::Audio { .. }
+            ));
+            (offset, tag.tag_header.timestamp)
+        })
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn reads_every_tag_with_its_offset() {
+    let (bytes, offsets) = flv_file();
+    let mut stream = FlvTagStream::new(Cursor::new(bytes)).await.unwrap();
+    assert_eq!(stream.data_start(), offsets[0]);
+    let tags = read_all(&mut stream).await;
+    let expected: Vec<_> = offsets
+        .iter()
+        .enumerate()
+        .map(|(index, offset)| (*offset, (index * 23) as u32))
+        .collect();
+    assert_eq!(tags, expected);
+    assert_eq!(stream.truncated_at(), None);
+}
+
+#[tokio::test]
+async fn truncated_last_tag_ends_the_stream() {
+    let (mut bytes, offsets) = flv_file();
+    for cut_in_last_tag in [5, 15] {
+        bytes.truncate(offsets[TAG_COUNT - 1] as usize + cut_in_last_tag);
+        let mut stream = FlvTagStream::new(Cursor::new(bytes.clone())).await.unwrap();
+        let tags = read_all(&mut stream).await;
+        assert_eq!(tags.len(), TAG_COUNT - 1);
+        assert_eq!(stream.truncated_at(), Some(offsets[TAG_COUNT - 1]));
+    }
+
+    let stream = FlvTagStream::new(Cursor::new(bytes[..5].to_vec())).await;
+    assert!(matches!(
+        stream,
+        Err(FlvStreamError::Truncated { offset: 5 })
+    ));
+}
+
+#[tokio::test]
+async fn seek_resyncs_to_the_next_tag() {
+    let (bytes, offsets) = flv_file();
+    let mut stream = FlvTagStream::new(Cursor::new(bytes)).await.unwrap();
+    for index in 1..TAG_COUNT {
+        for off_by in [0, 1, 7, 12] {
+            let position = stream
+                .seek_and_resync(offsets[index - 1] + off_by)
+                .await
+                .unwrap();
+            let expected = if off_by == 0 { index - 1 } else { index };
+            assert_eq!(position, offsets[expected]);
+            let (offset, tag) = stream.next_tag().await.unwrap().unwrap();
+            assert_eq!(offset, offsets[expected]);
+            assert_eq!(tag.tag_header.timestamp, (expected * 23) as u32);
+        }
+    }
+
+    // nothing but the PreviousTagSize field of the last tag is left
+    let end = stream.seek_and_resync(offsets[TAG_COUNT - 1] + 1).await;
+    assert!(matches!(end, Err(FlvStreamError::ResyncFailed { .. })));
+}
+
+#[tokio::test]
+async fn seek_resyncs_to_the_last_tag_without_previous_tag_size() {
+    let (mut bytes, offsets) = flv_file();
+    bytes.truncate(bytes.len() - 4);
+    let mut stream = FlvTagStream::new(Cursor::new(bytes)).await.unwrap();
+    let position = stream
+        .seek_and_resync(offsets[TAG_COUNT - 2] + 3)
+        .await
+        .unwrap();
+    assert_eq!(position, offsets[TAG_COUNT - 1]);
+    assert_eq!(read_all(&mut stream).await.len(), 1);
+    assert_eq!(stream.truncated_at(), None);
+}
+
+#[tokio::test]
+async fn corrupted_previous_tag_size_is_reported() {
+    let (mut bytes, offsets) = flv_file();
+    let field = offsets[3] as usize - 4;
+    bytes[field + 3] ^= 0xFF;
+    let mut stream = FlvTagStream::new(Cursor::new(bytes)).await.unwrap();
+    let results: Vec<_> = stream.tags().collect().await;
+    assert_eq!(results.len(), 3);
+    assert!(matches!(
+        results[2],
+        Err(FlvStreamError::PreviousTagSizeMismatch { offset, .. }) if offset == offsets[2]
+    ));
+
+    // the tags after it are still there
+    stream.seek_and_resync(stream.position()).await.unwrap();
+    assert_eq!(read_all(&mut stream).await.len(), TAG_COUNT - 3);
+}
+
+/// garbage no tag header starts in
+fn junk(len: usize) -> Vec<u8> {
+    (0..len).map(|index| 0xC0 | (index as u8 & 0x3F)).collect()
+}
+
+/// the file with the PreviousTagSize field after the third tag corrupted, and junk put before
+/// the seventh tag. the offsets of the tags are the ones in the damaged file
+fn damaged_flv_file(junk_len: usize) -> (Vec<u8>, Vec<u64>) {
+    let (mut bytes, mut offsets) = flv_file();
+    let field = offsets[3] as usize - 4;
+    bytes[field + 3] ^= 0xFF;
+    bytes.splice(offsets[6] as usize..offsets[6] as usize, junk(junk_len));
+    for offset in &mut offsets[6..] {
+        *offset += junk_len as u64;
+    }
+    (bytes, offsets)
+}
+
+#[tokio::test]
+async fn recovering_stream_reads_every_tag_past_the_damage() {
+    const JUNK: usize = 100;
+    let (bytes, offsets) = damaged_flv_file(JUNK);
+    let mut stream = FlvTagStream::new(Cursor::new(bytes))
+        .await
+        .unwrap()
+        .with_read_mode(FlvReadMode::Recover);
+    let tags = read_all(&mut stream).await;
+    let expected: Vec<_> = offsets
+        .iter()
+        .enumerate()
+        .map(|(index, offset)| (*offset, (index * 23) as u32))
+        .collect();
+    assert_eq!(tags, expected);
+    assert_eq!(stream.truncated_at(), None);
+    assert_eq!(
+        stream.recovery_stats(),
+        FlvRecoveryStats {
+            previous_tag_size_mismatches: 1,
+            resyncs: 1,
+            skipped_bytes: JUNK as u64,
+        }
+    );
+}
+
+#[tokio::test]
+async fn strict_stream_fails_at_the_junk() {
+    let (mut bytes, offsets) = flv_file();
+    bytes.splice(offsets[6] as usize..offsets[6] as usize, junk(100));
+    let mut stream = FlvTagStream::new(Cursor::new(bytes)).await.unwrap();
+    let results: Vec<_> = stream.tags().collect().await;
+    assert_eq!(results.len(), 7);
+    assert!(matches!(
+        results[6],
+        Err(FlvStreamError::Corrupted { offset, .. }) if offset == offsets[6]
+    ));
+    assert_eq!(stream.recovery_stats(), FlvRecoveryStats::default());
+}
+
+#[tokio::test]
+async fn recovering_stream_ends_at_trailing_garbage() {
+    let (mut bytes, _) = flv_file();
+    let end = bytes.len() as u64;
+    bytes.extend(junk(40));
+    let mut stream = FlvTagStream::new(Cursor::new(bytes))
+        .await
+        .unwrap()
+        .with_read_mode(FlvReadMode::Recover);
+    assert_eq!(read_all(&mut stream).await.len(), TAG_COUNT);
+    assert_eq!(stream.truncated_at(), Some(end));
+    assert_eq!(stream.recovery_stats().skipped_bytes, 40);
+    assert_eq!(stream.recovery_stats().resyncs, 0);
+}
@@ -106,6 +106,8 @@ pub struct AudioTagHeaderWithoutMultiTrack {
     pub legacy_info: Option<LegacyAudioHeaderInfo>,
     pub timestamp_nano: Option<u32>,
     pub track_type: Option<AvMultiTrackType>,
+    /// the track of a multitrack tag, 0 is the default track
+    pub track_id: u8,
     // for debug
     pub is_enhanced_rtmp: bool,
 }
@@ -138,7 +140,7 @@ impl TryInto<ExAudioTagHeader> for &AudioTagHeaderWithoutMultiTrack {
     fn try_into(self) -> Result<ExAudioTagHeader, Self::Error> {
         let mut tracks: HashMap<u8, AudioTrackInfo> = HashMap::new();
         tracks.insert(
-            0,
+            self.track_id,
             AudioTrackInfo {
                 codec: self.codec_id.try_into()?,
             },
@@ -172,6 +174,7 @@ impl From<&audio_tag_header::LegacyAudioTagHeader> for AudioTagHeaderWithoutMult
             }),
             timestamp_nano: None,
             track_type: None,
+            track_id: 0,
             is_enhanced_rtmp: false,
         }
     }
@@ -180,14 +183,17 @@ impl From<&audio_tag_header::LegacyAudioTagHeader> for AudioTagHeaderWithoutMult
 impl TryFrom<&ExAudioTagHeader> for AudioTagHeaderWithoutMultiTrack {
     type Error = FLVError;
     fn try_from(value: &ExAudioTagHeader) -> Result<Self, Self::Error> {
-        let track_info = value.tracks.get(&0);
-        if track_info.is_none() {
+        // a tag of one track might carry any track id, the default track is taken otherwise
+        let track = match value.tracks.len() {
+            1 => value.tracks.iter().next(),
+            _ => value.tracks.get_key_value(&0),
+        };
+        let Some((track_id, track_info)) = track else {
             return Err(FLVError::InconsistentHeader(format!(
                 "expect a valid ExAudioHeader, got {:?} instead",
                 value
             )));
-        }
-        let track_info = track_info.unwrap();
+        };
 
         Ok(Self {
             packet_type: value.packet_type,
@@ -195,6 +201,7 @@ impl TryFrom<&ExAudioTagHeader> for AudioTagHeaderWithoutMultiTrack {
             legacy_info: None,
             timestamp_nano: value.packet_mod_ex.timestamp_nano,
             track_type: value.track_type,
+            track_id: *track_id,
             is_enhanced_rtmp: true,
         })
     }
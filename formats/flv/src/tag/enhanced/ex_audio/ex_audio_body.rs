@@ -1,31 +1,38 @@
+use codec_common::audio::ChannelLayout;
+
 use crate::errors::FLVError;
-#[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AudioChannelOrder {
     // Only the channel count is specified, without any further information about the channel order
-    Unspecified = 0,
+    Unspecified,
     // The native channel order (i.e., the channels are in the same order in
     // which as defined in the AudioChannel enum).
-    Native = 1,
+    Native,
     // The channel order does not correspond to any predefined
     // order and is stored as an explicit map.
-    Custom = 2,
+    Custom,
+    // An order defined after this was written, what follows the channel count is kept as is
+    Unknown(u8),
 }
 
 impl From<AudioChannelOrder> for u8 {
     fn from(value: AudioChannelOrder) -> Self {
-        value as u8
+        match value {
+            AudioChannelOrder::Unspecified => 0,
+            AudioChannelOrder::Native => 1,
+            AudioChannelOrder::Custom => 2,
+            AudioChannelOrder::Unknown(value) => value,
+        }
     }
 }
 
-impl TryFrom<u8> for AudioChannelOrder {
-    type Error = FLVError;
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl From<u8> for AudioChannelOrder {
+    fn from(value: u8) -> Self {
         match value {
-            0 => Ok(Self::Unspecified),
-            1 => Ok(Self::Native),
-            2 => Ok(Self::Custom),
-            _ => Err(FLVError::UnknownChannelOrder(value)),
+            0 => Self::Unspecified,
+            1 => Self::Native,
+            2 => Self::Custom,
+            _ => Self::Unknown(value),
         }
     }
 }
@@ -144,11 +151,11 @@ impl TryFrom<u8> for AudioChannel {
             return Ok(AUDIO_CHANNEL_INDEXES[value as usize]);
         }
 
-        if value == AudioChannel::Unused.into() {
+        if value == u8::from(AudioChannel::Unused) {
             return Ok(AudioChannel::Unused);
         }
 
-        if value == AudioChannel::Unknown.into() {
+        if value == u8::from(AudioChannel::Unknown) {
             return Ok(AudioChannel::Unknown);
         }
 
@@ -183,8 +190,70 @@ pub fn make_channel_masks(channels: &[AudioChannel]) -> u32 {
     mask
 }
 
-#[derive(Debug)]
+/// the body of a MultichannelConfig audio packet
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AudioMultichannelConfig {
     pub channel_order: AudioChannelOrder,
+    pub channel_count: u8,
+    /// the speaker of each channel, Unknown ones for the unspecified order, empty for unknown orders
     pub channel_mapping: Vec<AudioChannel>,
+    /// the mask of the native order, bits of reserved channels included
+    pub channel_flags: u32,
+    /// what follows the channel count of an unknown order
+    pub unknown_data: Vec<u8>,
+}
+
+impl AudioMultichannelConfig {
+    pub fn unspecified(channel_count: u8) -> Self {
+        Self {
+            channel_order: AudioChannelOrder::Unspecified,
+            channel_count,
+            channel_mapping: vec![AudioChannel::Unknown; channel_count as usize],
+            channel_flags: 0,
+            unknown_data: Vec::new(),
+        }
+    }
+
+    pub fn native(channel_flags: u32) -> Self {
+        let channel_mapping = read_channels_from_mask(channel_flags);
+        Self {
+            channel_order: AudioChannelOrder::Native,
+            channel_count: channel_flags.count_ones() as u8,
+            channel_mapping,
+            channel_flags,
+            unknown_data: Vec::new(),
+        }
+    }
+
+    pub fn custom(channel_mapping: Vec<AudioChannel>) -> Self {
+        Self {
+            channel_order: AudioChannelOrder::Custom,
+            channel_count: channel_mapping.len() as u8,
+            channel_mapping,
+            channel_flags: 0,
+            unknown_data: Vec::new(),
+        }
+    }
+}
+
+/// the named layouts in the native order, the speakers of the others are unknown
+impl From<ChannelLayout> for AudioMultichannelConfig {
+    fn from(value: ChannelLayout) -> Self {
+        use audio_channel_mask::*;
+        let front = FRONT_LEFT | FRONT_RIGHT | FRONT_CENTER;
+        match value {
+            ChannelLayout::Mono => Self::native(FRONT_CENTER),
+            ChannelLayout::Stereo => Self::native(FRONT_LEFT | FRONT_RIGHT),
+            ChannelLayout::Surround3_0 => Self::native(front),
+            ChannelLayout::Surround4_0 => Self::native(front | BACK_CENTER),
+            ChannelLayout::Surround5_0 => Self::native(front | BACK_LEFT | BACK_RIGHT),
+            ChannelLayout::Surround5_1 => {
+                Self::native(front | LOW_FREQUENCY1 | BACK_LEFT | BACK_RIGHT)
+            }
+            ChannelLayout::Surround7_1 => Self::native(
+                front | LOW_FREQUENCY1 | BACK_LEFT | BACK_RIGHT | SIDE_LEFT | SIDE_RIGHT,
+            ),
+            ChannelLayout::Other { channels } => Self::unspecified(channels),
+        }
+    }
 }
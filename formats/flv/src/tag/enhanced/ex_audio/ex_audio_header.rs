@@ -1,12 +1,13 @@
 use std::collections::HashMap;
 
 use codec_common::FrameType;
+use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
 
 use crate::{
     errors::FLVError,
     tag::{
         audio_tag_header::{self, AACPacketType},
-        enhanced::AvMultiTrackType,
+        enhanced::{AvMultiTrackType, ModExSection},
     },
 };
 
@@ -192,3 +193,20 @@ pub struct ExAudioTagHeader {
 
     pub tracks: HashMap<u8, AudioTrackInfo>,
 }
+
+impl DynamicSizedPacket for ExAudioTagHeader {
+    fn get_packet_bytes_count(&self) -> usize {
+        let mut result = 1;
+        if let Some(timestamp_nano) = self.packet_mod_ex.timestamp_nano {
+            result +=
+                ModExSection::timestamp_offset_nano(timestamp_nano, 0).get_packet_bytes_count();
+        }
+        // the codec
+        result += 4;
+        if self.track_type.is_some() {
+            // the multi track type and the track id
+            result += 2;
+        }
+        result
+    }
+}
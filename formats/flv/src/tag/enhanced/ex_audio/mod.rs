@@ -1,4 +1,6 @@
-use ex_audio_header::{AudioPacketType, ExAudioTagHeader};
+use ex_audio_header::{AudioPacketType, AudioTrackInfo, ExAudioTagHeader};
+
+use crate::errors::FLVError;
 
 pub mod ex_audio_body;
 pub mod ex_audio_header;
@@ -10,4 +12,14 @@ impl ExAudioTagHeader {
     pub fn is_sequence_header(&self) -> bool {
         matches!(self.packet_type, AudioPacketType::SequenceStart)
     }
+
+    fn single_track(&self) -> Result<(u8, &AudioTrackInfo), FLVError> {
+        match self.tracks.iter().next() {
+            Some((track_id, track)) if self.tracks.len() == 1 => Ok((*track_id, track)),
+            _ => Err(FLVError::InconsistentHeader(format!(
+                "expect an audio header with exactly one track, got {:?} instead",
+                self
+            ))),
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use crate::{
     errors::FLVError,
     tag::enhanced::{
-        AvMultiTrackType,
+        AvMultiTrackType, ModExSection,
         ex_audio::ex_audio_header::{
             AudioFourCC, AudioPacketModExType, AudioPacketType, AudioTrackInfo,
         },
@@ -9,10 +9,7 @@ use crate::{
 };
 use byteorder::{BigEndian, ReadBytesExt};
 use num::ToPrimitive;
-use std::{
-    collections::HashMap,
-    io::{self, Cursor},
-};
+use std::{collections::HashMap, io};
 use utils::traits::reader::{ReadFrom, ReadRemainingFrom};
 
 use super::{
@@ -31,25 +28,13 @@ impl<R: io::Read> ReadRemainingFrom<u8, R> for ExAudioTagHeader {
         let mut audio_multi_track_type: Option<AvMultiTrackType> = None;
 
         while audio_packet_type == AudioPacketType::ModEx {
-            let mut mod_ex_data_size = reader.read_u8()? as u32 + 1;
-            if mod_ex_data_size == 256 {
-                mod_ex_data_size = reader.read_u16::<BigEndian>()? as u32 + 1;
-            }
-
-            let mut mod_ex_data = vec![0_u8; mod_ex_data_size as usize];
-            reader.read_exact(&mut mod_ex_data)?;
-            let mut mod_ex_cursor = Cursor::new(&mod_ex_data);
-
-            let byte = reader.read_u8()?;
-            let audio_packet_mod_ex_type: AudioPacketModExType =
-                ((byte >> 4) & 0b1111).try_into()?;
-
-            audio_packet_type = (byte & 0b1111).try_into()?;
-
-            match audio_packet_mod_ex_type {
-                AudioPacketModExType::TimestampOffsetNano => {
-                    timestamp_nano = Some(mod_ex_cursor.read_u24::<BigEndian>()?);
-                } // will there be other extensions in the future?
+            let mod_ex = ModExSection::read_from(reader)?;
+            audio_packet_type = mod_ex.next_packet_type.try_into()?;
+            // unknown ModEx types are skipped, their data is never part of the payload
+            if let Ok(AudioPacketModExType::TimestampOffsetNano) =
+                AudioPacketModExType::try_from(mod_ex.mod_ex_type)
+            {
+                timestamp_nano = Some(mod_ex.read_timestamp_offset_nano()?);
             }
         }
 
@@ -116,16 +101,18 @@ impl<R: io::Read> ReadRemainingFrom<u8, R> for ExAudioTagHeader {
 impl<R: io::Read> ReadFrom<R> for AudioMultichannelConfig {
     type Error = FLVError;
     fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
-        let audio_channel_order: AudioChannelOrder = reader.read_u8()?.try_into()?;
-        let channel_cnt = reader.read_u8()? as usize;
-        let mapping = match audio_channel_order {
-            AudioChannelOrder::Unspecified => vec![AudioChannel::Unknown; channel_cnt],
+        let channel_order: AudioChannelOrder = reader.read_u8()?.into();
+        let channel_count = reader.read_u8()?;
+        let mut channel_flags = 0;
+        let mut unknown_data = Vec::new();
+        let channel_mapping = match channel_order {
+            AudioChannelOrder::Unspecified => vec![AudioChannel::Unknown; channel_count as usize],
             AudioChannelOrder::Native => {
-                let channel_mask = reader.read_u32::<BigEndian>()?;
-                read_channels_from_mask(channel_mask)
+                channel_flags = reader.read_u32::<BigEndian>()?;
+                read_channels_from_mask(channel_flags)
             }
             AudioChannelOrder::Custom => {
-                let mut channels = vec![0_u8; channel_cnt];
+                let mut channels = vec![0_u8; channel_count as usize];
                 reader.read_exact(&mut channels)?;
                 let mut channel_mapping = Vec::new();
                 for v in channels {
@@ -133,10 +120,17 @@ impl<R: io::Read> ReadFrom<R> for AudioMultichannelConfig {
                 }
                 channel_mapping
             }
+            AudioChannelOrder::Unknown(_) => {
+                reader.read_to_end(&mut unknown_data)?;
+                Vec::new()
+            }
         };
         Ok(Self {
-            channel_order: audio_channel_order,
-            channel_mapping: mapping,
+            channel_order,
+            channel_count,
+            channel_mapping,
+            channel_flags,
+            unknown_data,
         })
     }
 }
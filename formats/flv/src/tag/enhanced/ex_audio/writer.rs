@@ -1,22 +1,68 @@
-// todo!()
-
 use std::io;
 
 use utils::traits::writer::WriteTo;
 
-use super::ex_audio_body::{
-    AudioChannel, AudioChannelOrder, AudioMultichannelConfig, make_channel_masks,
+use super::{
+    ex_audio_body::{AudioChannel, AudioChannelOrder, AudioMultichannelConfig},
+    ex_audio_header::{AudioPacketType, ExAudioTagHeader},
+};
+use crate::{
+    errors::FLVError,
+    tag::enhanced::{AvMultiTrackType, ModExSection},
 };
-use crate::errors::FLVError;
 use byteorder::{BigEndian, WriteBytesExt};
 
+impl<W: io::Write> WriteTo<W> for ExAudioTagHeader {
+    type Error = FLVError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        let packet_type = if self.track_type.is_some() {
+            AudioPacketType::MultiTrack
+        } else {
+            self.packet_type
+        };
+        let first_packet_type = if self.packet_mod_ex.timestamp_nano.is_some() {
+            AudioPacketType::ModEx
+        } else {
+            packet_type
+        };
+        // SoundFormat 9 marks the enhanced audio header
+        writer.write_u8((9 << 4) | <AudioPacketType as Into<u8>>::into(first_packet_type))?;
+
+        if let Some(timestamp_nano) = self.packet_mod_ex.timestamp_nano {
+            ModExSection::timestamp_offset_nano(timestamp_nano, packet_type.into())
+                .write_to(writer)?;
+        }
+
+        let (track_id, track) = self.single_track()?;
+        match self.track_type {
+            None => writer.write_u32::<BigEndian>(track.codec.into())?,
+            Some(AvMultiTrackType::OneTrack) => {
+                let mut byte = <AvMultiTrackType as Into<u8>>::into(AvMultiTrackType::OneTrack);
+                byte <<= 4;
+                byte |= <AudioPacketType as Into<u8>>::into(self.packet_type);
+                writer.write_u8(byte)?;
+                writer.write_u32::<BigEndian>(track.codec.into())?;
+                writer.write_u8(track_id)?;
+            }
+            Some(track_type) => {
+                return Err(FLVError::InconsistentHeader(format!(
+                    "audio header with multi track type: {:?} can not be written without the track data",
+                    track_type
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
 impl<W: io::Write> WriteTo<W> for AudioMultichannelConfig {
     type Error = FLVError;
     fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
         writer.write_u8(self.channel_order.into())?;
-        writer.write_u8(self.channel_mapping.len() as u8)?;
+        writer.write_u8(self.channel_count)?;
         match self.channel_order {
             AudioChannelOrder::Unspecified => {}
+            AudioChannelOrder::Unknown(_) => writer.write_all(&self.unknown_data)?,
             AudioChannelOrder::Custom => {
                 let buffer: Vec<u8> = self
                     .channel_mapping
@@ -25,10 +71,7 @@ impl<W: io::Write> WriteTo<W> for AudioMultichannelConfig {
                     .collect();
                 writer.write_all(&buffer)?;
             }
-            AudioChannelOrder::Native => {
-                let mask = make_channel_masks(&self.channel_mapping);
-                writer.write_u32::<BigEndian>(mask)?;
-            }
+            AudioChannelOrder::Native => writer.write_u32::<BigEndian>(self.channel_flags)?,
         }
         Ok(())
     }
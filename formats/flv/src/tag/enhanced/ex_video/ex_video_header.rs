@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
+use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
+
 use crate::{
     errors::FLVError,
     tag::{
-        enhanced::AvMultiTrackType,
+        enhanced::{AvMultiTrackType, ModExSection},
         video_tag_header::{AVCPacketType, FrameTypeFLV, VideoCommand},
     },
 };
@@ -180,7 +182,8 @@ pub struct VideoModEx {
 #[derive(Debug, Clone)]
 pub struct VideoTrackInfo {
     pub codec: VideoFourCC,
-    pub composition_time: Option<u32>,
+    /// SI24, in milliseconds
+    pub composition_time: Option<i32>,
 }
 
 #[derive(Debug, Clone)]
@@ -192,3 +195,26 @@ pub struct ExVideoTagHeader {
     pub video_command: Option<VideoCommand>,
     pub tracks: HashMap<u8, VideoTrackInfo>,
 }
+
+impl DynamicSizedPacket for ExVideoTagHeader {
+    fn get_packet_bytes_count(&self) -> usize {
+        let mut result = 1;
+        if let Some(timestamp_nano) = self.packet_mod_ex.timestamp_nano {
+            result +=
+                ModExSection::timestamp_offset_nano(timestamp_nano, 0).get_packet_bytes_count();
+        }
+        if self.is_command() {
+            return result + 1;
+        }
+        // the codec
+        result += 4;
+        if self.track_type.is_some() {
+            // the multi track type and the track id
+            result += 2;
+        }
+        if self.has_composition_time() {
+            result += 3;
+        }
+        result
+    }
+}
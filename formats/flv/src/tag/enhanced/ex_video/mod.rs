@@ -1,6 +1,6 @@
-use ex_video_header::{ExVideoTagHeader, VideoPacketType};
+use ex_video_header::{ExVideoTagHeader, VideoFourCC, VideoPacketType, VideoTrackInfo};
 
-use crate::tag::video_tag_header::FrameTypeFLV;
+use crate::{errors::FLVError, tag::video_tag_header::FrameTypeFLV};
 
 pub mod ex_video_header;
 pub mod reader;
@@ -16,4 +16,30 @@ impl ExVideoTagHeader {
     pub fn is_key_frame(&self) -> bool {
         matches!(self.frame_type, FrameTypeFLV::KeyFrame)
     }
+
+    /// command frames carry a video command instead of the codec and tracks
+    #[inline]
+    pub fn is_command(&self) -> bool {
+        self.packet_type != VideoPacketType::Metadata
+            && self.frame_type == FrameTypeFLV::CommandFrame
+    }
+
+    fn has_composition_time(&self) -> bool {
+        self.packet_type == VideoPacketType::CodedFrames
+            && self
+                .tracks
+                .values()
+                .next()
+                .is_some_and(|track| matches!(track.codec, VideoFourCC::AVC | VideoFourCC::HEVC))
+    }
+
+    fn single_track(&self) -> Result<(u8, &VideoTrackInfo), FLVError> {
+        match self.tracks.iter().next() {
+            Some((track_id, track)) if self.tracks.len() == 1 => Ok((*track_id, track)),
+            _ => Err(FLVError::InconsistentHeader(format!(
+                "expect a video header with exactly one track, got {:?} instead",
+                self
+            ))),
+        }
+    }
 }
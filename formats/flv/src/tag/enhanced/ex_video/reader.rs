@@ -1,7 +1,4 @@
-use std::{
-    collections::HashMap,
-    io::{self, Cursor},
-};
+use std::{collections::HashMap, io};
 
 use byteorder::{BigEndian, ReadBytesExt};
 use num::ToPrimitive;
@@ -11,7 +8,7 @@ use crate::{
     errors::FLVError,
     tag::{
         enhanced::{
-            AvMultiTrackType,
+            AvMultiTrackType, ModExSection,
             ex_video::ex_video_header::{
                 VideoFourCC, VideoModEx, VideoPacketModExType, VideoPacketType, VideoTrackInfo,
             },
@@ -30,21 +27,13 @@ impl<R: io::Read> ReadRemainingFrom<u8, R> for ExVideoTagHeader {
         let mut video_packet_type: VideoPacketType = (header & 0b1111).try_into()?;
         let mut timestamp_nano = None;
         while video_packet_type == VideoPacketType::ModEx {
-            let mut mod_ex_data_size: u32 = reader.read_u8()? as u32 + 1;
-            if mod_ex_data_size == 256 {
-                mod_ex_data_size = reader.read_u16::<BigEndian>()? as u32 + 1;
-            }
-            let mut mod_ex_data = vec![0_u8; mod_ex_data_size as usize];
-            reader.read_exact(&mut mod_ex_data)?;
-            let mut cursor = Cursor::new(&mut mod_ex_data);
-
-            let byte = reader.read_u8()?;
-            let video_packet_mod_ex_type: VideoPacketModExType =
-                ((byte >> 4) & 0b1111).try_into()?;
-            video_packet_type = (byte & 0b11111).try_into()?;
-
-            if video_packet_mod_ex_type == VideoPacketModExType::TimestampOffsetNano {
-                timestamp_nano = Some(cursor.read_u24::<BigEndian>()?);
+            let mod_ex = ModExSection::read_from(reader)?;
+            video_packet_type = mod_ex.next_packet_type.try_into()?;
+            // unknown ModEx types are skipped, their data is never part of the payload
+            if let Ok(VideoPacketModExType::TimestampOffsetNano) =
+                VideoPacketModExType::try_from(mod_ex.mod_ex_type)
+            {
+                timestamp_nano = Some(mod_ex.read_timestamp_offset_nano()?);
             }
         }
 
@@ -90,11 +79,11 @@ impl<R: io::Read> ReadRemainingFrom<u8, R> for ExVideoTagHeader {
             };
 
             let mut composition_time = None;
-            if video_packet_type == VideoPacketType::CodedFrames
-                && video_four_cc == VideoFourCC::AVC
-                || video_four_cc == VideoFourCC::HEVC
+            if video_command.is_none()
+                && video_packet_type == VideoPacketType::CodedFrames
+                && (video_four_cc == VideoFourCC::AVC || video_four_cc == VideoFourCC::HEVC)
             {
-                composition_time = Some(reader.read_u24::<BigEndian>()?);
+                composition_time = Some(reader.read_i24::<BigEndian>()?);
             }
 
             tracks.insert(
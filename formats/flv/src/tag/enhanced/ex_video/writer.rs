@@ -1 +1,75 @@
-// todo!()
+use std::io;
+
+use byteorder::{BigEndian, WriteBytesExt};
+use utils::traits::writer::WriteTo;
+
+use crate::{
+    errors::FLVError,
+    tag::{
+        enhanced::{AvMultiTrackType, ModExSection},
+        video_tag_header::FrameTypeFLV,
+    },
+};
+
+use super::ex_video_header::{ExVideoTagHeader, VideoPacketType};
+
+impl<W: io::Write> WriteTo<W> for ExVideoTagHeader {
+    type Error = FLVError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        let packet_type = if self.track_type.is_some() {
+            VideoPacketType::Multitrack
+        } else {
+            self.packet_type
+        };
+        let first_packet_type = if self.packet_mod_ex.timestamp_nano.is_some() {
+            VideoPacketType::ModEx
+        } else {
+            packet_type
+        };
+        let mut byte = 0b1000;
+        byte |= <FrameTypeFLV as Into<u8>>::into(self.frame_type) & 0b111;
+        byte <<= 4;
+        byte |= <VideoPacketType as Into<u8>>::into(first_packet_type);
+        writer.write_u8(byte)?;
+
+        if let Some(timestamp_nano) = self.packet_mod_ex.timestamp_nano {
+            ModExSection::timestamp_offset_nano(timestamp_nano, packet_type.into())
+                .write_to(writer)?;
+        }
+
+        if self.is_command() {
+            let command = self.video_command.ok_or_else(|| {
+                FLVError::InconsistentHeader(
+                    "video header with frame type: 5 (VideoCommand) should also has video_command"
+                        .to_owned(),
+                )
+            })?;
+            writer.write_u8(command.into())?;
+            return Ok(());
+        }
+
+        let (track_id, track) = self.single_track()?;
+        match self.track_type {
+            None => writer.write_u32::<BigEndian>(track.codec.into())?,
+            Some(AvMultiTrackType::OneTrack) => {
+                let mut byte = <AvMultiTrackType as Into<u8>>::into(AvMultiTrackType::OneTrack);
+                byte <<= 4;
+                byte |= <VideoPacketType as Into<u8>>::into(self.packet_type);
+                writer.write_u8(byte)?;
+                writer.write_u32::<BigEndian>(track.codec.into())?;
+                writer.write_u8(track_id)?;
+            }
+            Some(track_type) => {
+                return Err(FLVError::InconsistentHeader(format!(
+                    "video header with multi track type: {:?} can not be written without the track data",
+                    track_type
+                )));
+            }
+        }
+
+        if self.has_composition_time() {
+            writer.write_i24::<BigEndian>(track.composition_time.unwrap_or(0))?;
+        }
+        Ok(())
+    }
+}
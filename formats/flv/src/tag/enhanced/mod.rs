@@ -1,8 +1,15 @@
+use std::io;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
 use crate::errors::FLVError;
 
 pub mod ex_audio;
 pub mod ex_video;
 
+#[cfg(test)]
+mod test;
+
 pub const fn make_four_cc(cc: &str) -> u32 {
     assert!(cc.len() == 4);
     let bytes = cc.as_bytes();
@@ -36,3 +43,71 @@ impl TryFrom<u8> for AvMultiTrackType {
         }
     }
 }
+
+/// A ModEx section, prefixed to the rest of an enhanced audio or video tag header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModExSection {
+    /// VideoPacketModExType or AudioPacketModExType, kept raw so unknown types can be skipped
+    pub mod_ex_type: u8,
+    pub data: Vec<u8>,
+    /// the packet type following this section, might be ModEx again
+    pub next_packet_type: u8,
+}
+
+impl ModExSection {
+    /// TimestampOffsetNano data is a UI24
+    pub fn timestamp_offset_nano(timestamp_nano: u32, next_packet_type: u8) -> Self {
+        Self {
+            mod_ex_type: 0,
+            data: timestamp_nano.to_be_bytes()[1..].to_vec(),
+            next_packet_type,
+        }
+    }
+
+    pub fn read_timestamp_offset_nano(&self) -> Result<u32, FLVError> {
+        (&self.data[..]).read_u24::<BigEndian>().map_err(|_| {
+            FLVError::InconsistentHeader(format!(
+                "TimestampOffsetNano ModEx expects 3 bytes of data, got {}",
+                self.data.len()
+            ))
+        })
+    }
+
+    pub fn read_from<R: io::Read>(reader: &mut R) -> Result<Self, FLVError> {
+        let mut data_size = reader.read_u8()? as usize + 1;
+        if data_size == 256 {
+            data_size = reader.read_u16::<BigEndian>()? as usize + 1;
+        }
+        let mut data = vec![0_u8; data_size];
+        reader.read_exact(&mut data)?;
+        let byte = reader.read_u8()?;
+        Ok(Self {
+            mod_ex_type: (byte >> 4) & 0b1111,
+            data,
+            next_packet_type: byte & 0b1111,
+        })
+    }
+
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> Result<(), FLVError> {
+        if self.data.is_empty() || self.data.len() > u16::MAX as usize + 1 {
+            return Err(FLVError::InconsistentHeader(format!(
+                "ModEx data size should be in [1, 65536], got {}",
+                self.data.len()
+            )));
+        }
+        if self.data.len() < 256 {
+            writer.write_u8((self.data.len() - 1) as u8)?;
+        } else {
+            writer.write_u8(255)?;
+            writer.write_u16::<BigEndian>((self.data.len() - 1) as u16)?;
+        }
+        writer.write_all(&self.data)?;
+        writer.write_u8((self.mod_ex_type << 4) | (self.next_packet_type & 0b1111))?;
+        Ok(())
+    }
+
+    pub fn get_packet_bytes_count(&self) -> usize {
+        let size_bytes = if self.data.len() < 256 { 1 } else { 3 };
+        size_bytes + self.data.len() + 1
+    }
+}
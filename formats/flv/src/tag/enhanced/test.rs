@@ -0,0 +1,276 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use codec_common::{
+        FrameType, MediaFrameTimestamp,
+        video::{VideoCodecCommon, VideoFrameInfo},
+    };
+    use utils::traits::{
+        dynamic_sized_packet::DynamicSizedPacket, reader::ReadFrom, writer::WriteTo,
+    };
+
+    use crate::tag::{
+        audio_tag_header::AudioTagHeader,
+        audio_tag_header_info::AudioTagHeaderWithoutMultiTrack,
+        enhanced::{
+            AvMultiTrackType, ModExSection,
+            ex_audio::{
+                ex_audio_body::{
+                    AudioChannel, AudioChannelOrder, AudioMultichannelConfig, audio_channel_mask,
+                },
+                ex_audio_header::{AudioFourCC, AudioPacketType, ExAudioTagHeader},
+            },
+            ex_video::ex_video_header::{ExVideoTagHeader, VideoFourCC, VideoPacketType},
+        },
+        video_tag_header::{FrameTypeFLV, VideoTagHeader},
+        video_tag_header_info::VideoTagHeaderWithoutMultiTrack,
+    };
+
+    const TIMESTAMP_NANO: u32 = 500_000;
+    /// an avcc nal unit with a 4 bytes length prefix
+    const NAL_UNIT: [u8; 6] = [0, 0, 0, 2, 0x65, 0x88];
+
+    fn read_video(bytes: &[u8]) -> (ExVideoTagHeader, Vec<u8>) {
+        let mut reader = Cursor::new(bytes);
+        let header = match VideoTagHeader::read_from(&mut reader).unwrap() {
+            VideoTagHeader::Enhanced(header) => header,
+            header => panic!("expect an enhanced header, got {:?}", header),
+        };
+        let mut payload = vec![];
+        reader.read_to_end(&mut payload).unwrap();
+        (header, payload)
+    }
+
+    fn avc_coded_frame(mod_ex_sections: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        // IsExHeader | KeyFrame | ModEx
+        bytes.push(0x80 | (1 << 4) | 7);
+        bytes.extend_from_slice(mod_ex_sections);
+        bytes.extend_from_slice(b"avc1");
+        // composition time
+        bytes.extend_from_slice(&[0, 0, 40]);
+        bytes.extend_from_slice(&NAL_UNIT);
+        bytes
+    }
+
+    fn assert_avc_coded_frame(header: &ExVideoTagHeader, payload: &[u8]) {
+        assert_eq!(header.frame_type, FrameTypeFLV::KeyFrame);
+        assert_eq!(header.packet_type, VideoPacketType::CodedFrames);
+        assert_eq!(header.packet_mod_ex.timestamp_nano, Some(TIMESTAMP_NANO));
+        let track = header.tracks.get(&0).unwrap();
+        assert_eq!(track.codec, VideoFourCC::AVC);
+        assert_eq!(track.composition_time, Some(40));
+        assert_eq!(payload, NAL_UNIT);
+    }
+
+    #[test]
+    fn video_with_one_mod_ex_section() {
+        let mut mod_ex = vec![];
+        ModExSection::timestamp_offset_nano(TIMESTAMP_NANO, VideoPacketType::CodedFrames.into())
+            .write_to(&mut mod_ex)
+            .unwrap();
+        // size - 1, UI24 nano offset, TimestampOffsetNano | CodedFrames
+        assert_eq!(mod_ex, [0x02, 0x07, 0xA1, 0x20, 0x01]);
+
+        let bytes = avc_coded_frame(&mod_ex);
+        let (header, payload) = read_video(&bytes);
+        assert_avc_coded_frame(&header, &payload);
+    }
+
+    #[test]
+    fn video_with_an_unknown_mod_ex_section() {
+        let mut mod_ex = vec![];
+        // an unknown ModEx type 0xF with 2 bytes of data, followed by another ModEx
+        mod_ex.extend_from_slice(&[0x01, 0xAA, 0xBB, 0xF7]);
+        ModExSection::timestamp_offset_nano(TIMESTAMP_NANO, VideoPacketType::CodedFrames.into())
+            .write_to(&mut mod_ex)
+            .unwrap();
+
+        let bytes = avc_coded_frame(&mod_ex);
+        let (header, payload) = read_video(&bytes);
+        assert_avc_coded_frame(&header, &payload);
+    }
+
+    #[test]
+    fn video_header_round_trip() {
+        let mut mod_ex = vec![];
+        ModExSection::timestamp_offset_nano(TIMESTAMP_NANO, VideoPacketType::CodedFrames.into())
+            .write_to(&mut mod_ex)
+            .unwrap();
+        let bytes = avc_coded_frame(&mod_ex);
+        let (header, _) = read_video(&bytes);
+
+        let mut written = vec![];
+        header.write_to(&mut written).unwrap();
+        assert_eq!(written.len(), header.get_packet_bytes_count());
+        assert_eq!(written, bytes[..bytes.len() - NAL_UNIT.len()]);
+    }
+
+    #[test]
+    fn coded_frames_without_composition_time_go_as_coded_frames_x() {
+        let frame_info = |cts_ms: i64| {
+            VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                FrameType::CodedFrames,
+                *MediaFrameTimestamp::with_timestamp_ms(40).apply_signed_offset_ms(cts_ms),
+            )
+        };
+        for (cts_ms, packet_type, bytes_count) in [
+            (0, VideoPacketType::CodedFramesX, 5),
+            (80, VideoPacketType::CodedFrames, 8),
+        ] {
+            let header = ExVideoTagHeader::try_from(&frame_info(cts_ms)).unwrap();
+            assert_eq!(header.packet_type, packet_type);
+            let mut bytes = vec![];
+            header.write_to(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), bytes_count);
+            assert_eq!(bytes.len(), header.get_packet_bytes_count());
+
+            let (read, payload) = read_video(&bytes);
+            assert!(payload.is_empty());
+            assert_eq!(read.packet_type, packet_type);
+            let info = VideoTagHeaderWithoutMultiTrack::try_from(&read).unwrap();
+            assert_eq!(info.composition_time.unwrap_or(0), cts_ms as i32);
+        }
+    }
+
+    #[test]
+    fn mod_ex_section_with_large_data() {
+        let section = ModExSection {
+            mod_ex_type: 0xF,
+            data: vec![0x5A; 300],
+            next_packet_type: 1,
+        };
+        let mut bytes = vec![];
+        section.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), section.get_packet_bytes_count());
+        assert_eq!(
+            ModExSection::read_from(&mut Cursor::new(&bytes)).unwrap(),
+            section
+        );
+    }
+
+    #[test]
+    fn audio_with_mod_ex_round_trip() {
+        let mut bytes = vec![];
+        // SoundFormat 9 | ModEx
+        bytes.push((9 << 4) | 7);
+        ModExSection::timestamp_offset_nano(TIMESTAMP_NANO, AudioPacketType::CodedFrames.into())
+            .write_to(&mut bytes)
+            .unwrap();
+        bytes.extend_from_slice(b"mp4a");
+        let header_size = bytes.len();
+        bytes.extend_from_slice(&[0x21, 0x10]);
+
+        let mut reader = Cursor::new(&bytes);
+        let header: ExAudioTagHeader = match AudioTagHeader::read_from(&mut reader).unwrap() {
+            AudioTagHeader::Enhanced(header) => header,
+            header => panic!("expect an enhanced header, got {:?}", header),
+        };
+        assert_eq!(reader.position() as usize, header_size);
+        assert_eq!(header.packet_type, AudioPacketType::CodedFrames);
+        assert_eq!(header.packet_mod_ex.timestamp_nano, Some(TIMESTAMP_NANO));
+        assert_eq!(header.tracks.get(&0).unwrap().codec, AudioFourCC::AAC);
+
+        let mut written = vec![];
+        header.write_to(&mut written).unwrap();
+        assert_eq!(written.len(), header.get_packet_bytes_count());
+        assert_eq!(written, bytes[..header_size]);
+    }
+
+    #[test]
+    fn audio_of_one_track_keeps_its_track_id() {
+        let mut bytes = vec![];
+        // SoundFormat 9 | MultiTrack
+        bytes.push((9 << 4) | 5);
+        bytes.push((u8::from(AvMultiTrackType::OneTrack) << 4) | 1);
+        bytes.extend_from_slice(b"mp4a");
+        // the track id
+        bytes.push(2);
+        let header_size = bytes.len();
+        bytes.extend_from_slice(&[0x21, 0x10]);
+
+        let mut reader = Cursor::new(&bytes);
+        let header = AudioTagHeader::read_from(&mut reader).unwrap();
+        assert_eq!(reader.position() as usize, header_size);
+        let info = AudioTagHeaderWithoutMultiTrack::try_from(&header).unwrap();
+        assert_eq!(info.packet_type, AudioPacketType::CodedFrames);
+        assert_eq!(info.track_type, Some(AvMultiTrackType::OneTrack));
+        assert_eq!(info.track_id, 2);
+    }
+
+    fn multichannel_config_round_trip(bytes: &[u8]) -> AudioMultichannelConfig {
+        let config = AudioMultichannelConfig::read_from(&mut Cursor::new(bytes)).unwrap();
+        let mut written = vec![];
+        config.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+        config
+    }
+
+    #[test]
+    fn stereo_multichannel_config() {
+        // Native, 2 channels, FrontLeft | FrontRight
+        let config = multichannel_config_round_trip(&[1, 2, 0, 0, 0, 0x03]);
+        assert_eq!(config, AudioMultichannelConfig::native(0x03));
+        assert_eq!(config.channel_count, 2);
+        assert_eq!(
+            config.channel_mapping,
+            [AudioChannel::FrontLeft, AudioChannel::FrontRight]
+        );
+    }
+
+    #[test]
+    fn surround_5_1_multichannel_config() {
+        let flags = audio_channel_mask::FRONT_LEFT
+            | audio_channel_mask::FRONT_RIGHT
+            | audio_channel_mask::FRONT_CENTER
+            | audio_channel_mask::LOW_FREQUENCY1
+            | audio_channel_mask::BACK_LEFT
+            | audio_channel_mask::BACK_RIGHT;
+        let config = multichannel_config_round_trip(&[1, 6, 0, 0, 0, 0x3F]);
+        assert_eq!(config.channel_order, AudioChannelOrder::Native);
+        assert_eq!(config.channel_flags, flags);
+        assert_eq!(config.channel_count, 6);
+        assert_eq!(config.channel_mapping[3], AudioChannel::LowFrequency1);
+
+        // the same layout mapped explicitly, with the surround channels on the sides
+        let config = multichannel_config_round_trip(&[2, 6, 0, 1, 2, 3, 9, 10]);
+        assert_eq!(
+            config,
+            AudioMultichannelConfig::custom(vec![
+                AudioChannel::FrontLeft,
+                AudioChannel::FrontRight,
+                AudioChannel::FrontCenter,
+                AudioChannel::LowFrequency1,
+                AudioChannel::SideLeft,
+                AudioChannel::SideRight,
+            ])
+        );
+    }
+
+    #[test]
+    fn custom_multichannel_config() {
+        // the right channel first, then one of an unknown speaker and an unused one
+        let config = multichannel_config_round_trip(&[2, 3, 1, 0xFF, 0xFE]);
+        assert_eq!(
+            config.channel_mapping,
+            [
+                AudioChannel::FrontRight,
+                AudioChannel::Unknown,
+                AudioChannel::Unused
+            ]
+        );
+
+        let config = multichannel_config_round_trip(&[0, 4]);
+        assert_eq!(config, AudioMultichannelConfig::unspecified(4));
+    }
+
+    #[test]
+    fn unknown_channel_order_passes_through() {
+        let config = multichannel_config_round_trip(&[7, 2, 0xAA, 0xBB, 0xCC]);
+        assert_eq!(config.channel_order, AudioChannelOrder::Unknown(7));
+        assert_eq!(config.channel_count, 2);
+        assert_eq!(config.unknown_data, [0xAA, 0xBB, 0xCC]);
+    }
+}
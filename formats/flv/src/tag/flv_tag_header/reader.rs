@@ -31,6 +31,22 @@ impl<R: io::Read> ReadFrom<R> for FLVTagHeader {
     }
 }
 
+impl FLVTagHeader {
+    /// the header if the bytes look like the start of a tag, the reserved bits and the stream id
+    /// are zero and the tag type is known, for finding the next tag in a damaged file
+    pub fn read_plausible(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::bytes_count()
+            || bytes[0] & 0b1100_0000 != 0
+            || bytes[8..11] != [0; 3]
+        {
+            return None;
+        }
+        Self::read_from(&mut io::Cursor::new(bytes))
+            .ok()
+            .filter(|header| header.data_size > 0)
+    }
+}
+
 impl<R: AsRef<[u8]>> TryReadFrom<R> for FLVTagHeader {
     type Error = FLVError;
     fn try_read_from(reader: &mut io::Cursor<R>) -> Result<Option<Self>, Self::Error> {
@@ -40,18 +40,14 @@ impl<W: io::Write> WriteTo<W> for FLVTagBodyWithFilter {
             FLVTagBody::Audio { header, body } => {
                 match header {
                     AudioTagHeader::Legacy(header) => header.write_to(writer)?,
-                    AudioTagHeader::Enhanced(_ex_header) => {
-                        todo!()
-                    }
+                    AudioTagHeader::Enhanced(ex_header) => ex_header.write_to(writer)?,
                 }
                 writer.write_all(body)?;
             }
             FLVTagBody::Video { header, body } => {
                 match header {
                     VideoTagHeader::Legacy(header) => header.write_to(writer)?,
-                    VideoTagHeader::Enhanced(_ex_header) => {
-                        todo!()
-                    }
+                    VideoTagHeader::Enhanced(ex_header) => ex_header.write_to(writer)?,
                 }
                 writer.write_all(body)?;
             }
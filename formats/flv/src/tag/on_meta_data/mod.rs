@@ -10,15 +10,49 @@ use super::{
 };
 
 pub mod reader;
+#[cfg(test)]
+mod test;
 pub mod writer;
+
+/// the fields of onMetaData that have a typed field in [`OnMetaData`]
+const KNOWN_FIELDS: [&str; 20] = [
+    "audiochannels",
+    "audiocodecid",
+    "audiodatarate",
+    "audiodelay",
+    "audiosamplerate",
+    "audiosamplesize",
+    "canSeekToEnd",
+    "creationdate",
+    "duration",
+    "filesize",
+    "framerate",
+    "height",
+    "stereo",
+    "videocodecid",
+    "videodatarate",
+    "width",
+    "audioTrackIdInfoMap",
+    "videoTrackIdInfoMap",
+    "keyframes",
+    "@setDataFrame",
+];
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ScriptKeyframeInfo {
-    _file_position: f64,
-    _time: f64,
+    /// byte offset of the keyframe tag in the file
+    pub file_position: f64,
+    /// in seconds
+    pub time: f64,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OnMetaData {
+    /// "audiochannels"
+    /// Number of audio channels, not part of the spec but written by most encoders
+    pub audio_channels: Option<f64>,
     /// "audiocodecid", from enhanced rtmp
     /// Audio codec ID used in the file: See AudioTagHeader of the legacy [FLV] specification for available CodecID values.
     /// When [FourCC] is used to signal the codec, this property is set to a FOURCC value.
@@ -72,7 +106,10 @@ pub struct OnMetaData {
     pub width: Option<f64>,
     /// "audioTrackIdInfoMap" and "videoTrackIdInfoMap" are way too complicated, sucks
     /// @see Enhanced RTMP v2-2024-10-22-b1 p15
+    /// they are amf values, not serialized with the serde feature
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub audio_track_id_info_map: Option<HashMap<String, amf_formats::Value>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub video_track_id_info_map: Option<HashMap<String, amf_formats::Value>>,
 
     /// @see: http://www.cnblogs.com/musicfans/archive/2012/11/07/2819291.html
@@ -81,6 +118,48 @@ pub struct OnMetaData {
     ///   "times": [number]
     /// }
     pub keyframes: Option<Vec<ScriptKeyframeInfo>>,
+
+    /// the nonstandard fields the publisher set, e.g., "encoder", kept as they are
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub extra_fields: HashMap<String, amf_formats::Value>,
+}
+
+impl OnMetaData {
+    /// applies a metadata update of the publisher, the fields it carries override,
+    /// the ones it misses are kept
+    pub fn merge(&mut self, update: OnMetaData) {
+        macro_rules! merge_fields {
+            ($($field:ident),*) => {
+                $(
+                    if update.$field.is_some() {
+                        self.$field = update.$field;
+                    }
+                )*
+            };
+        }
+        merge_fields!(
+            audio_channels,
+            audio_codec_id,
+            audio_data_rate,
+            audio_delay,
+            audio_sample_rate,
+            audio_sample_size,
+            can_seek_to_end,
+            creation_date,
+            duration,
+            file_size,
+            frame_rate,
+            height,
+            stereo,
+            video_codec_id,
+            video_data_rate,
+            width,
+            audio_track_id_info_map,
+            video_track_id_info_map,
+            keyframes
+        );
+        self.extra_fields.extend(update.extra_fields);
+    }
 }
 
 impl From<HashMap<String, amf_formats::Value>> for OnMetaData {
@@ -115,8 +194,8 @@ impl From<HashMap<String, amf_formats::Value>> for OnMetaData {
                         return None;
                     }
                     keyframe_infos.push(ScriptKeyframeInfo {
-                        _file_position: pos_num.unwrap(),
-                        _time: time_num.unwrap(),
+                        file_position: pos_num.unwrap(),
+                        time: time_num.unwrap(),
                     });
                 }
                 Some(keyframe_infos)
@@ -124,6 +203,7 @@ impl From<HashMap<String, amf_formats::Value>> for OnMetaData {
         };
 
         Self {
+            audio_channels: value.extract_number_field("audiochannels"),
             audio_codec_id: value.extract_number_field("audiocodecid").map(|v| {
                 let four_cc_codec: Result<AudioFourCC, _> = (v as u32).try_into();
                 if let Ok(codec) = four_cc_codec {
@@ -172,6 +252,11 @@ impl From<HashMap<String, amf_formats::Value>> for OnMetaData {
                 },
             ),
             keyframes: extract_keyframe_info(),
+            extra_fields: value
+                .iter()
+                .filter(|(key, _)| !KNOWN_FIELDS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
         }
     }
 }
@@ -179,6 +264,12 @@ impl From<HashMap<String, amf_formats::Value>> for OnMetaData {
 impl From<&OnMetaData> for Vec<(String, amf_formats::amf0::Value)> {
     fn from(value: &OnMetaData) -> Self {
         let mut result = vec![];
+        if let Some(audio_channels) = value.audio_channels {
+            result.push((
+                "audiochannels".to_string(),
+                amf_formats::amf0::number(audio_channels),
+            ));
+        }
         if let Some(audio_codec) = value.audio_codec_id {
             let audio_codec_four_cc: AudioFourCC =
                 audio_codec.try_into().unwrap_or(AudioFourCC::AAC);
@@ -302,7 +393,7 @@ impl From<&OnMetaData> for Vec<(String, amf_formats::amf0::Value)> {
                             amf_formats::amf0::Value::StrictArray(
                                 key_frames
                                     .iter()
-                                    .map(|item| amf_formats::amf0::number(item._file_position))
+                                    .map(|item| amf_formats::amf0::number(item.file_position))
                                     .collect(),
                             ),
                         ),
@@ -311,7 +402,7 @@ impl From<&OnMetaData> for Vec<(String, amf_formats::amf0::Value)> {
                             amf_formats::amf0::Value::StrictArray(
                                 key_frames
                                     .iter()
-                                    .map(|item| amf_formats::amf0::number(item._time))
+                                    .map(|item| amf_formats::amf0::number(item.time))
                                     .collect(),
                             ),
                         ),
@@ -319,6 +410,18 @@ impl From<&OnMetaData> for Vec<(String, amf_formats::amf0::Value)> {
                 },
             ));
         }
+        // sorted, so the same metadata is always written the same
+        let mut extra_fields: Vec<_> = value.extra_fields.iter().collect();
+        extra_fields.sort_by_key(|(key, _)| *key);
+        for (key, value) in extra_fields {
+            result.push((
+                key.clone(),
+                match value.clone() {
+                    Value::AMF0Value(value) => value,
+                    Value::AMF3Value(value) => amf_formats::amf0::Value::AVMPlus(value),
+                },
+            ));
+        }
 
         result
     }
@@ -12,18 +12,11 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for OnMetaData {
         header: amf_formats::Version,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
-        let name = amf_formats::Value::read_remaining_from(header, reader)?;
-        let name_valid = match name.try_as_str() {
-            None => false,
-            Some(name) => name == "@setDataFrame",
-        };
-        if !name_valid {
-            return Err(FLVError::InvalidOnMetaData(format!(
-                "expect @setDataFrame, got: {:?}",
-                name
-            )));
+        // the wrapper of data sent over rtmp, the flv files go without it
+        let mut name = amf_formats::Value::read_remaining_from(header, reader)?;
+        if name.try_as_str() == Some("@setDataFrame") {
+            name = amf_formats::Value::read_remaining_from(header, reader)?;
         }
-        let name = amf_formats::Value::read_remaining_from(header, reader)?;
         let name_valid = match name.try_as_str() {
             None => false,
             Some(name) => name == "onMetaData",
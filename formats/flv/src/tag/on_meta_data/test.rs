@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::tag::on_meta_data::OnMetaData;
+
+    fn on_meta_data(fields: Vec<(&str, amf_formats::amf0::Value)>) -> OnMetaData {
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), amf_formats::Value::AMF0Value(value)))
+            .collect::<HashMap<_, _>>()
+            .into()
+    }
+
+    #[test]
+    fn update_overrides_the_fields_it_carries_and_keeps_the_rest() {
+        let mut merged = on_meta_data(vec![
+            ("width", amf_formats::amf0::number(1280)),
+            ("height", amf_formats::amf0::number(720)),
+            ("encoder", amf_formats::amf0::string("obs-output module")),
+            ("fileName", amf_formats::amf0::string("a.flv")),
+        ]);
+        assert_eq!(merged.extra_fields.len(), 2);
+
+        merged.merge(on_meta_data(vec![
+            ("height", amf_formats::amf0::number(1080)),
+            ("videodatarate", amf_formats::amf0::number(6000)),
+            ("encoder", amf_formats::amf0::string("obs 30.0")),
+        ]));
+        assert_eq!(merged.width, Some(1280.0));
+        assert_eq!(merged.height, Some(1080.0));
+        assert_eq!(merged.video_data_rate, Some(6000.0));
+        assert_eq!(
+            merged.extra_fields["encoder"].try_as_str(),
+            Some("obs 30.0")
+        );
+        assert_eq!(merged.extra_fields["fileName"].try_as_str(), Some("a.flv"));
+
+        let pairs: Vec<(String, amf_formats::amf0::Value)> = (&merged).into();
+        let keys: Vec<_> = pairs.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(
+            keys,
+            ["height", "videodatarate", "width", "encoder", "fileName"]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn on_meta_data_round_trips_through_json() {
+        use codec_common::{audio::AudioCodecCommon, video::VideoCodecCommon};
+
+        use crate::tag::on_meta_data::ScriptKeyframeInfo;
+
+        let on_meta_data = OnMetaData {
+            audio_channels: Some(2.0),
+            audio_codec_id: Some(AudioCodecCommon::AAC),
+            audio_data_rate: Some(128.0),
+            audio_delay: None,
+            audio_sample_rate: Some(44100.0),
+            audio_sample_size: Some(16.0),
+            can_seek_to_end: Some(false),
+            creation_date: Some("2024-01-01".to_owned()),
+            duration: Some(0.0),
+            file_size: None,
+            frame_rate: Some(30.0),
+            height: Some(720.0),
+            stereo: Some(true),
+            video_codec_id: Some(VideoCodecCommon::AVC),
+            video_data_rate: Some(2500.0),
+            width: Some(1280.0),
+            audio_track_id_info_map: Some(HashMap::new()),
+            video_track_id_info_map: None,
+            keyframes: Some(vec![ScriptKeyframeInfo {
+                file_position: 13.0,
+                time: 0.0,
+            }]),
+            extra_fields: HashMap::new(),
+        };
+        let json = serde_json::to_value(&on_meta_data).unwrap();
+        assert_eq!(json["audio_codec_id"], "AAC");
+        assert_eq!(json["video_codec_id"], "AVC");
+        assert_eq!(json["width"], 1280.0);
+        assert_eq!(json["keyframes"][0]["file_position"], 13.0);
+        assert!(json.get("audio_track_id_info_map").is_none());
+
+        let deserialized: OnMetaData = serde_json::from_value(json).unwrap();
+        assert!(deserialized.audio_track_id_info_map.is_none());
+        assert_eq!(
+            format!("{:?}", deserialized),
+            format!(
+                "{:?}",
+                OnMetaData {
+                    audio_track_id_info_map: None,
+                    ..on_meta_data
+                }
+            )
+        );
+    }
+}
@@ -1,40 +1,66 @@
 use num::ToPrimitive;
 use tokio_util::bytes::Buf;
-use utils::traits::reader::{ReadFrom, ReadRemainingFrom, TryReadFrom};
+use utils::traits::{
+    fixed_packet::FixedPacket,
+    reader::{ReadFrom, ReadRemainingFrom, TryReadFrom},
+};
 
 use std::io::{self, Cursor, Read};
 
-use crate::errors::FLVError;
+use crate::errors::{FLVError, FLVResult};
 
 use super::{FLVTag, FLVTagBodyWithFilter, FLVTagHeader};
 
+impl FLVTag {
+    /// the tag out of its header and exactly data_size bytes of body,
+    /// the core shared by the sync readers and the async tag stream
+    pub fn from_parts(tag_header: FLVTagHeader, body: &[u8]) -> FLVResult<Self> {
+        if body.len() != tag_header.data_size.to_usize().unwrap() {
+            return Err(FLVError::InconsistentHeader(format!(
+                "tag data size is {}, got {} bytes of body",
+                tag_header.data_size,
+                body.len()
+            )));
+        }
+        Ok(Self {
+            body_with_filter: FLVTagBodyWithFilter::read_remaining_from(
+                &tag_header,
+                &mut Cursor::new(body),
+            )?,
+            tag_header,
+        })
+    }
+}
+
 impl<R: io::Read> ReadFrom<R> for FLVTag {
     type Error = FLVError;
     fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
         let tag_header = FLVTagHeader::read_from(reader)?;
-
-        let tag_body = FLVTagBodyWithFilter::read_remaining_from(&tag_header, reader)?;
-        Ok(FLVTag {
-            tag_header,
-            body_with_filter: tag_body,
-        })
+        let mut body = vec![0; tag_header.data_size.to_usize().unwrap()];
+        reader.read_exact(&mut body)?;
+        Self::from_parts(tag_header, &body)
     }
 }
 
 impl<R: AsRef<[u8]>> TryReadFrom<R> for FLVTag {
     type Error = FLVError;
     fn try_read_from(reader: &mut Cursor<R>) -> Result<Option<Self>, Self::Error> {
-        let tag_header = FLVTagHeader::try_read_from(reader.by_ref())?;
-        if tag_header.is_none() {
+        if reader.remaining() < FLVTagHeader::bytes_count() {
             return Ok(None);
         }
-        let tag_header = tag_header.unwrap();
-        if reader.remaining() < tag_header.data_size.to_usize().unwrap() {
+        let tag_start = reader.position();
+        let tag_header = FLVTagHeader::read_from(reader.by_ref())?;
+        let data_size = tag_header.data_size.to_usize().unwrap();
+        if reader.remaining() < data_size {
+            reader.set_position(tag_start);
             return Ok(None);
         }
-        Ok(Some(Self {
-            body_with_filter: FLVTagBodyWithFilter::read_remaining_from(&tag_header, reader)?,
+        let body_start = reader.position().to_usize().unwrap();
+        reader.advance(data_size);
+        Self::from_parts(
             tag_header,
-        }))
+            &reader.get_ref().as_ref()[body_start..body_start + data_size],
+        )
+        .map(Some)
     }
 }
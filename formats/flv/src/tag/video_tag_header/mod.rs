@@ -1,14 +1,22 @@
-use super::enhanced::ex_video::ex_video_header::ExVideoTagHeader;
+use super::enhanced::ex_video::ex_video_header::{
+    ExVideoTagHeader, VideoFourCC, VideoModEx, VideoPacketType, VideoTrackInfo,
+};
 use crate::errors::FLVError;
 use codec_common::{
     FrameType,
     video::{VideoCodecCommon, VideoFrameInfo},
 };
 use num::ToPrimitive;
+use std::collections::HashMap;
 use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
 
 pub mod reader;
 pub mod writer;
+
+/// the range of a SI24 composition time offset
+pub const MIN_COMPOSITION_TIME: i32 = -(1 << 23);
+pub const MAX_COMPOSITION_TIME: i32 = (1 << 23) - 1;
+
 ///
 /// Type of video frame.
 /// The following values are defined:
@@ -178,7 +186,8 @@ pub struct LegacyVideoTagHeader {
     /// ELSE 0
     /// See ISO 14496-12, 8.15.3 for an explanation of composition times.
     /// The offset in an FLV file is always in milliseconds.
-    pub composition_time: Option<u32>,
+    /// SI24, B-frames make it positive, enhanced rtmp allows it to be negative.
+    pub composition_time: Option<i32>,
 }
 
 impl DynamicSizedPacket for LegacyVideoTagHeader {
@@ -213,23 +222,92 @@ impl TryFrom<&VideoFrameInfo> for LegacyVideoTagHeader {
         } else {
             None
         };
-        let cts = value
-            .timestamp
-            .pts_ms()
-            .checked_sub(value.timestamp.dts_ms())
-            .unwrap()
-            .to_u32()
-            .unwrap();
         Ok(Self {
             frame_type: value.frame_type.into(),
             codec_id: value.codec_id.try_into()?,
             avc_packet_type: packet_type,
             video_command: None,
-            composition_time: Some(cts),
+            composition_time: Some(composition_time(value)?),
+        })
+    }
+}
+
+/// the coded frames of avc and hevc without a composition time offset go as CodedFramesX,
+/// which leaves the zero offset out
+impl TryFrom<&VideoFrameInfo> for ExVideoTagHeader {
+    type Error = FLVError;
+    fn try_from(value: &VideoFrameInfo) -> Result<Self, Self::Error> {
+        let codec: VideoFourCC = value.codec_id.try_into()?;
+        let composition_time = composition_time(value)?;
+        let packet_type = match value.frame_type {
+            FrameType::CodedFrames | FrameType::KeyFrame
+                if composition_time == 0
+                    && matches!(codec, VideoFourCC::AVC | VideoFourCC::HEVC) =>
+            {
+                VideoPacketType::CodedFramesX
+            }
+            FrameType::CodedFrames | FrameType::KeyFrame => VideoPacketType::CodedFrames,
+            FrameType::SequenceEnd => VideoPacketType::SequenceEnd,
+            FrameType::SequenceStart => VideoPacketType::SequenceStart,
+        };
+        Ok(Self {
+            packet_type,
+            frame_type: value.frame_type.into(),
+            packet_mod_ex: VideoModEx {
+                timestamp_nano: None,
+            },
+            track_type: None,
+            video_command: None,
+            tracks: HashMap::from([(
+                0,
+                VideoTrackInfo {
+                    codec,
+                    composition_time: (packet_type == VideoPacketType::CodedFrames)
+                        .then_some(composition_time),
+                },
+            )]),
         })
     }
 }
 
+/// the legacy header if the codec has a legacy CodecID, the enhanced one otherwise
+impl TryFrom<&VideoFrameInfo> for VideoTagHeader {
+    type Error = FLVError;
+    fn try_from(value: &VideoFrameInfo) -> Result<Self, Self::Error> {
+        let codec_id: Result<CodecID, _> = value.codec_id.try_into();
+        match codec_id {
+            Ok(_) => Ok(Self::Legacy(value.try_into()?)),
+            Err(_) => Ok(Self::Enhanced(value.try_into()?)),
+        }
+    }
+}
+
+impl VideoTagHeader {
+    /// the enhanced header if the codec has a FourCC, the legacy one otherwise,
+    /// for players that listed the codec in the fourCcList of their connect
+    pub fn enhanced(value: &VideoFrameInfo) -> Result<Self, FLVError> {
+        let four_cc: Result<VideoFourCC, _> = value.codec_id.try_into();
+        match four_cc {
+            Ok(_) => Ok(Self::Enhanced(value.try_into()?)),
+            Err(_) => Ok(Self::Legacy(value.try_into()?)),
+        }
+    }
+}
+
+fn composition_time(value: &VideoFrameInfo) -> Result<i32, FLVError> {
+    value
+        .timestamp
+        .cts_ms()
+        .to_i32()
+        .filter(|cts| (MIN_COMPOSITION_TIME..=MAX_COMPOSITION_TIME).contains(cts))
+        .ok_or_else(|| {
+            FLVError::InconsistentHeader(format!(
+                "composition time offset does not fit in SI24, {:?}",
+                value.timestamp
+            ))
+        })
+}
+
 impl LegacyVideoTagHeader {
     #[inline]
     pub fn get_frame_type(&self) -> FrameTypeFLV {
@@ -273,3 +351,12 @@ pub enum VideoTagHeader {
     Legacy(LegacyVideoTagHeader),
     Enhanced(ExVideoTagHeader),
 }
+
+impl DynamicSizedPacket for VideoTagHeader {
+    fn get_packet_bytes_count(&self) -> usize {
+        match self {
+            Self::Legacy(header) => header.get_packet_bytes_count(),
+            Self::Enhanced(header) => header.get_packet_bytes_count(),
+        }
+    }
+}
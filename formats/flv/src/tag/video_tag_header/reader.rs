@@ -31,7 +31,7 @@ impl<R: io::Read> ReadFrom<R> for VideoTagHeader {
             let packet_type = reader.read_u8()?;
             avc_packet_type = Some(packet_type.try_into()?);
 
-            let time = reader.read_u24::<BigEndian>()?;
+            let time = reader.read_i24::<BigEndian>()?;
             composition_time = Some(time);
         }
         Ok(VideoTagHeader::Legacy(LegacyVideoTagHeader {
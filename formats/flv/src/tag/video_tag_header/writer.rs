@@ -47,8 +47,8 @@ impl<W: io::Write> WriteTo<W> for LegacyVideoTagHeader {
         {
             let avc_packet_type_u8: u8 = self.avc_packet_type.expect("this cannot be none").into();
             writer.write_u8(avc_packet_type_u8)?;
-            let composition_time_u32 = self.composition_time.expect("this cannot be none");
-            writer.write_u24::<BigEndian>(composition_time_u32)?;
+            let composition_time = self.composition_time.expect("this cannot be none");
+            writer.write_i24::<BigEndian>(composition_time)?;
         }
 
         Ok(())
@@ -80,7 +80,7 @@ pub struct VideoTagHeaderWithoutMultiTrack {
     pub codec_id: VideoCodecCommon,
     pub frame_type: FrameTypeFLV,
     pub video_command: Option<VideoCommand>,
-    pub composition_time: Option<u32>,
+    pub composition_time: Option<i32>,
     pub timestamp_nano: Option<u32>,
     pub track_type: Option<AvMultiTrackType>,
     // for debug
@@ -0,0 +1,141 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use num::ToPrimitive;
+use tokio_util::bytes::Bytes;
+
+use crate::errors::{Mp4Error, Mp4Result};
+
+use super::{write_box, write_full_box};
+
+/// @see: ISO/IEC 14496-12 Section 8.8.3.1 sample_flags
+/// sample_depends_on = 2, the sample does not depend on others
+pub const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+/// sample_depends_on = 1 and sample_is_non_sync_sample = 1
+pub const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+// tfhd flags
+const DEFAULT_BASE_IS_MOOF: u32 = 0x02_0000;
+// trun flags
+const DATA_OFFSET_PRESENT: u32 = 0x00_0001;
+const SAMPLE_DURATION_PRESENT: u32 = 0x00_0100;
+const SAMPLE_SIZE_PRESENT: u32 = 0x00_0200;
+const SAMPLE_FLAGS_PRESENT: u32 = 0x00_0400;
+const SAMPLE_COMPOSITION_TIME_OFFSETS_PRESENT: u32 = 0x00_0800;
+
+#[derive(Debug, Clone)]
+pub struct FragmentSample {
+    /// in track timescale
+    pub duration: u32,
+    /// in track timescale, pts - dts
+    pub composition_time_offset: i32,
+    pub flags: u32,
+    pub data: Bytes,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackFragment {
+    pub track_id: u32,
+    /// in track timescale
+    pub base_media_decode_time: u64,
+    pub samples: Vec<FragmentSample>,
+}
+
+impl TrackFragment {
+    pub fn data_size(&self) -> usize {
+        self.samples.iter().map(|s| s.data.len()).sum()
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.samples.iter().map(|s| s.duration as u64).sum()
+    }
+}
+
+/// writes a moof followed by a mdat holding the sample data of every track fragment,
+/// in the same order as the fragments
+pub fn write_media_segment(
+    buf: &mut Vec<u8>,
+    sequence_number: u32,
+    fragments: &[TrackFragment],
+) -> Mp4Result<()> {
+    let moof_start = buf.len();
+    let mut data_offset_positions = Vec::with_capacity(fragments.len());
+    write_box(buf, b"moof", |buf| {
+        write_full_box(buf, b"mfhd", 0, 0, |buf| {
+            buf.write_u32::<BigEndian>(sequence_number)?;
+            Ok(())
+        })?;
+        for fragment in fragments {
+            write_traf(buf, fragment, &mut data_offset_positions)?;
+        }
+        Ok(())
+    })?;
+
+    // data_offset is relative to the first byte of moof since default-base-is-moof is set
+    let moof_size = buf.len() - moof_start;
+    let mut data_offset = moof_size + 8;
+    for (position, fragment) in data_offset_positions.iter().zip(fragments) {
+        let offset = data_offset
+            .to_i32()
+            .ok_or_else(|| Mp4Error::BoxTooLarge("moof".to_owned(), data_offset))?;
+        buf[*position..*position + 4].copy_from_slice(&offset.to_be_bytes());
+        data_offset += fragment.data_size();
+    }
+
+    write_box(buf, b"mdat", |buf| {
+        for fragment in fragments {
+            for sample in &fragment.samples {
+                buf.extend_from_slice(&sample.data);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.8.6 Track Fragment Box
+fn write_traf(
+    buf: &mut Vec<u8>,
+    fragment: &TrackFragment,
+    data_offset_positions: &mut Vec<usize>,
+) -> Mp4Result<()> {
+    write_box(buf, b"traf", |buf| {
+        write_full_box(buf, b"tfhd", 0, DEFAULT_BASE_IS_MOOF, |buf| {
+            buf.write_u32::<BigEndian>(fragment.track_id)?;
+            Ok(())
+        })?;
+        write_full_box(buf, b"tfdt", 1, 0, |buf| {
+            buf.write_u64::<BigEndian>(fragment.base_media_decode_time)?;
+            Ok(())
+        })?;
+        write_trun(buf, fragment, data_offset_positions)
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.8.8 Track Fragment Run Box
+/// version 1 is used so that composition offsets are signed
+fn write_trun(
+    buf: &mut Vec<u8>,
+    fragment: &TrackFragment,
+    data_offset_positions: &mut Vec<usize>,
+) -> Mp4Result<()> {
+    let flags = DATA_OFFSET_PRESENT
+        | SAMPLE_DURATION_PRESENT
+        | SAMPLE_SIZE_PRESENT
+        | SAMPLE_FLAGS_PRESENT
+        | SAMPLE_COMPOSITION_TIME_OFFSETS_PRESENT;
+    write_full_box(buf, b"trun", 1, flags, |buf| {
+        buf.write_u32::<BigEndian>(fragment.samples.len().to_u32().unwrap())?;
+        data_offset_positions.push(buf.len());
+        buf.write_i32::<BigEndian>(0)?; // data_offset, patched after moof is complete
+        for sample in &fragment.samples {
+            let size = sample
+                .data
+                .len()
+                .to_u32()
+                .ok_or_else(|| Mp4Error::BoxTooLarge("sample".to_owned(), sample.data.len()))?;
+            buf.write_u32::<BigEndian>(sample.duration)?;
+            buf.write_u32::<BigEndian>(size)?;
+            buf.write_u32::<BigEndian>(sample.flags)?;
+            buf.write_i32::<BigEndian>(sample.composition_time_offset)?;
+        }
+        Ok(())
+    })
+}
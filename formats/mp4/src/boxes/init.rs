@@ -0,0 +1,355 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use num::ToPrimitive;
+use tokio_util::bytes::Bytes;
+
+use crate::errors::{Mp4Error, Mp4Result};
+
+use super::{write_box, write_full_box, write_unity_matrix};
+
+pub const VIDEO_HANDLER_TYPE: [u8; 4] = *b"vide";
+pub const AUDIO_HANDLER_TYPE: [u8; 4] = *b"soun";
+
+/// timescale of the movie header, track timescales are carried by mdhd
+const MOVIE_TIMESCALE: u32 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct VideoTrack {
+    pub track_id: u32,
+    pub timescale: u32,
+    pub width: u16,
+    pub height: u16,
+    /// serialized AVCDecoderConfigurationRecord, the payload of avcC
+    pub avc_decoder_configuration_record: Bytes,
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioTrack {
+    pub track_id: u32,
+    /// equal to the sample rate
+    pub timescale: u32,
+    pub channel_count: u16,
+    pub sample_rate: u32,
+    /// the samples of an access unit at the sample rate, 2048 for he-aac
+    pub samples_per_frame: u32,
+    /// serialized AudioSpecificConfig, the DecoderSpecificInfo of esds
+    pub audio_specific_config: Bytes,
+}
+
+#[derive(Debug, Clone)]
+pub enum TrackConfig {
+    Video(VideoTrack),
+    Audio(AudioTrack),
+}
+
+impl TrackConfig {
+    pub fn track_id(&self) -> u32 {
+        match self {
+            Self::Video(track) => track.track_id,
+            Self::Audio(track) => track.track_id,
+        }
+    }
+
+    pub fn timescale(&self) -> u32 {
+        match self {
+            Self::Video(track) => track.timescale,
+            Self::Audio(track) => track.timescale,
+        }
+    }
+
+    pub fn handler_type(&self) -> [u8; 4] {
+        match self {
+            Self::Video(_) => VIDEO_HANDLER_TYPE,
+            Self::Audio(_) => AUDIO_HANDLER_TYPE,
+        }
+    }
+}
+
+/// @see: ISO/IEC 14496-12 Section 4.3 File Type Box
+pub fn write_ftyp(buf: &mut Vec<u8>) -> Mp4Result<()> {
+    write_box(buf, b"ftyp", |buf| {
+        buf.extend_from_slice(b"iso6"); // major_brand
+        buf.write_u32::<BigEndian>(0)?; // minor_version
+        for brand in [b"iso6", b"cmfc", b"mp41", b"avc1"] {
+            buf.extend_from_slice(brand);
+        }
+        Ok(())
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.2.1 Movie Box
+pub fn write_moov(buf: &mut Vec<u8>, tracks: &[TrackConfig]) -> Mp4Result<()> {
+    write_box(buf, b"moov", |buf| {
+        let next_track_id = tracks.iter().map(|t| t.track_id()).max().unwrap_or(0) + 1;
+        write_mvhd(buf, next_track_id)?;
+        for track in tracks {
+            write_trak(buf, track)?;
+        }
+        write_box(buf, b"mvex", |buf| {
+            for track in tracks {
+                write_trex(buf, track.track_id())?;
+            }
+            Ok(())
+        })
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.2.2 Movie Header Box
+fn write_mvhd(buf: &mut Vec<u8>, next_track_id: u32) -> Mp4Result<()> {
+    write_full_box(buf, b"mvhd", 0, 0, |buf| {
+        buf.write_u32::<BigEndian>(0)?; // creation_time
+        buf.write_u32::<BigEndian>(0)?; // modification_time
+        buf.write_u32::<BigEndian>(MOVIE_TIMESCALE)?;
+        buf.write_u32::<BigEndian>(0)?; // duration, unknown for fragmented files
+        buf.write_u32::<BigEndian>(0x0001_0000)?; // rate, 1.0
+        buf.write_u16::<BigEndian>(0x0100)?; // volume, 1.0
+        buf.write_u16::<BigEndian>(0)?; // reserved
+        buf.write_u64::<BigEndian>(0)?; // reserved
+        write_unity_matrix(buf)?;
+        for _ in 0..6 {
+            buf.write_u32::<BigEndian>(0)?; // pre_defined
+        }
+        buf.write_u32::<BigEndian>(next_track_id)?;
+        Ok(())
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.3.1 Track Box
+fn write_trak(buf: &mut Vec<u8>, track: &TrackConfig) -> Mp4Result<()> {
+    write_box(buf, b"trak", |buf| {
+        write_tkhd(buf, track)?;
+        write_box(buf, b"mdia", |buf| {
+            write_mdhd(buf, track.timescale())?;
+            write_hdlr(buf, track.handler_type())?;
+            write_minf(buf, track)
+        })
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.3.2 Track Header Box
+fn write_tkhd(buf: &mut Vec<u8>, track: &TrackConfig) -> Mp4Result<()> {
+    // track_enabled | track_in_movie
+    write_full_box(buf, b"tkhd", 0, 0x000003, |buf| {
+        buf.write_u32::<BigEndian>(0)?; // creation_time
+        buf.write_u32::<BigEndian>(0)?; // modification_time
+        buf.write_u32::<BigEndian>(track.track_id())?;
+        buf.write_u32::<BigEndian>(0)?; // reserved
+        buf.write_u32::<BigEndian>(0)?; // duration
+        buf.write_u64::<BigEndian>(0)?; // reserved
+        buf.write_i16::<BigEndian>(0)?; // layer
+        buf.write_i16::<BigEndian>(0)?; // alternate_group
+        let (volume, width, height) = match track {
+            TrackConfig::Video(video) => (0, video.width, video.height),
+            TrackConfig::Audio(_) => (0x0100, 0, 0),
+        };
+        buf.write_i16::<BigEndian>(volume)?;
+        buf.write_u16::<BigEndian>(0)?; // reserved
+        write_unity_matrix(buf)?;
+        // 16.16 fixed point
+        buf.write_u32::<BigEndian>((width as u32) << 16)?;
+        buf.write_u32::<BigEndian>((height as u32) << 16)?;
+        Ok(())
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.4.2 Media Header Box
+fn write_mdhd(buf: &mut Vec<u8>, timescale: u32) -> Mp4Result<()> {
+    write_full_box(buf, b"mdhd", 0, 0, |buf| {
+        buf.write_u32::<BigEndian>(0)?; // creation_time
+        buf.write_u32::<BigEndian>(0)?; // modification_time
+        buf.write_u32::<BigEndian>(timescale)?;
+        buf.write_u32::<BigEndian>(0)?; // duration
+        // pad(1) + language(15), 'und' packed as 5 bits per letter
+        let language = b"und"
+            .iter()
+            .fold(0u16, |acc, c| (acc << 5) | ((c - 0x60) as u16));
+        buf.write_u16::<BigEndian>(language)?;
+        buf.write_u16::<BigEndian>(0)?; // pre_defined
+        Ok(())
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.4.3 Handler Reference Box
+fn write_hdlr(buf: &mut Vec<u8>, handler_type: [u8; 4]) -> Mp4Result<()> {
+    write_full_box(buf, b"hdlr", 0, 0, |buf| {
+        buf.write_u32::<BigEndian>(0)?; // pre_defined
+        buf.extend_from_slice(&handler_type);
+        for _ in 0..3 {
+            buf.write_u32::<BigEndian>(0)?; // reserved
+        }
+        let name: &[u8] = if handler_type == VIDEO_HANDLER_TYPE {
+            b"VideoHandler\0"
+        } else {
+            b"SoundHandler\0"
+        };
+        buf.extend_from_slice(name);
+        Ok(())
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.4.4 Media Information Box
+fn write_minf(buf: &mut Vec<u8>, track: &TrackConfig) -> Mp4Result<()> {
+    write_box(buf, b"minf", |buf| {
+        match track {
+            TrackConfig::Video(_) => {
+                write_full_box(buf, b"vmhd", 0, 1, |buf| {
+                    buf.write_u16::<BigEndian>(0)?; // graphicsmode
+                    for _ in 0..3 {
+                        buf.write_u16::<BigEndian>(0)?; // opcolor
+                    }
+                    Ok(())
+                })?;
+            }
+            TrackConfig::Audio(_) => {
+                write_full_box(buf, b"smhd", 0, 0, |buf| {
+                    buf.write_i16::<BigEndian>(0)?; // balance
+                    buf.write_u16::<BigEndian>(0)?; // reserved
+                    Ok(())
+                })?;
+            }
+        }
+        write_box(buf, b"dinf", |buf| {
+            write_full_box(buf, b"dref", 0, 0, |buf| {
+                buf.write_u32::<BigEndian>(1)?; // entry_count
+                // media data is in the same file
+                write_full_box(buf, b"url ", 0, 1, |_| Ok(()))
+            })
+        })?;
+        write_stbl(buf, track)
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.5.1 Sample Table Box
+/// all sample tables are empty, samples are described by the movie fragments
+fn write_stbl(buf: &mut Vec<u8>, track: &TrackConfig) -> Mp4Result<()> {
+    write_box(buf, b"stbl", |buf| {
+        write_full_box(buf, b"stsd", 0, 0, |buf| {
+            buf.write_u32::<BigEndian>(1)?; // entry_count
+            match track {
+                TrackConfig::Video(video) => write_avc1(buf, video),
+                TrackConfig::Audio(audio) => write_mp4a(buf, audio),
+            }
+        })?;
+        for box_type in [b"stts", b"stsc", b"stco"] {
+            write_full_box(buf, box_type, 0, 0, |buf| {
+                buf.write_u32::<BigEndian>(0)?; // entry_count
+                Ok(())
+            })?;
+        }
+        write_full_box(buf, b"stsz", 0, 0, |buf| {
+            buf.write_u32::<BigEndian>(0)?; // sample_size
+            buf.write_u32::<BigEndian>(0)?; // sample_count
+            Ok(())
+        })
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.5.2.2 SampleEntry
+fn write_sample_entry_header(buf: &mut Vec<u8>) -> Mp4Result<()> {
+    buf.extend_from_slice(&[0; 6]); // reserved
+    buf.write_u16::<BigEndian>(1)?; // data_reference_index
+    Ok(())
+}
+
+/// @see: ISO/IEC 14496-15 Section 5.4.2.1 AVCSampleEntry
+fn write_avc1(buf: &mut Vec<u8>, video: &VideoTrack) -> Mp4Result<()> {
+    write_box(buf, b"avc1", |buf| {
+        write_sample_entry_header(buf)?;
+        buf.write_u16::<BigEndian>(0)?; // pre_defined
+        buf.write_u16::<BigEndian>(0)?; // reserved
+        for _ in 0..3 {
+            buf.write_u32::<BigEndian>(0)?; // pre_defined
+        }
+        buf.write_u16::<BigEndian>(video.width)?;
+        buf.write_u16::<BigEndian>(video.height)?;
+        buf.write_u32::<BigEndian>(0x0048_0000)?; // horizresolution, 72 dpi
+        buf.write_u32::<BigEndian>(0x0048_0000)?; // vertresolution, 72 dpi
+        buf.write_u32::<BigEndian>(0)?; // reserved
+        buf.write_u16::<BigEndian>(1)?; // frame_count
+        buf.extend_from_slice(&[0; 32]); // compressorname
+        buf.write_u16::<BigEndian>(0x0018)?; // depth
+        buf.write_i16::<BigEndian>(-1)?; // pre_defined
+        write_box(buf, b"avcC", |buf| {
+            buf.extend_from_slice(&video.avc_decoder_configuration_record);
+            Ok(())
+        })
+    })
+}
+
+/// @see: ISO/IEC 14496-14 Section 6.7 MP4AudioSampleEntry
+fn write_mp4a(buf: &mut Vec<u8>, audio: &AudioTrack) -> Mp4Result<()> {
+    write_box(buf, b"mp4a", |buf| {
+        write_sample_entry_header(buf)?;
+        buf.write_u64::<BigEndian>(0)?; // reserved
+        buf.write_u16::<BigEndian>(audio.channel_count)?;
+        buf.write_u16::<BigEndian>(16)?; // samplesize
+        buf.write_u16::<BigEndian>(0)?; // pre_defined
+        buf.write_u16::<BigEndian>(0)?; // reserved
+        // 16.16 fixed point, rates above 65535 can not be represented here,
+        // the real rate is carried by the AudioSpecificConfig anyway
+        let sample_rate = audio.sample_rate.to_u16().unwrap_or(0);
+        buf.write_u32::<BigEndian>((sample_rate as u32) << 16)?;
+        write_esds(buf, audio)
+    })
+}
+
+/// @see: ISO/IEC 14496-1 Section 7.2.2.3 BaseDescriptor, the size is coded in 7 bit groups
+fn write_descriptor<F>(buf: &mut Vec<u8>, tag: u8, body: F) -> Mp4Result<()>
+where
+    F: FnOnce(&mut Vec<u8>) -> Mp4Result<()>,
+{
+    let mut payload = Vec::new();
+    body(&mut payload)?;
+    let size = payload.len();
+    if size >= (1 << 28) {
+        return Err(Mp4Error::BoxTooLarge(format!("descriptor {}", tag), size));
+    }
+    buf.write_u8(tag)?;
+    // always use the 4 bytes form to keep the layout stable
+    for shift in [21, 14, 7] {
+        buf.write_u8(0x80 | ((size >> shift) & 0x7F) as u8)?;
+    }
+    buf.write_u8((size & 0x7F) as u8)?;
+    buf.extend_from_slice(&payload);
+    Ok(())
+}
+
+/// @see: ISO/IEC 14496-14 Section 5.6 ESDBox
+fn write_esds(buf: &mut Vec<u8>, audio: &AudioTrack) -> Mp4Result<()> {
+    write_full_box(buf, b"esds", 0, 0, |buf| {
+        // ES_DescrTag
+        write_descriptor(buf, 0x03, |buf| {
+            buf.write_u16::<BigEndian>(0)?; // ES_ID
+            buf.write_u8(0)?; // streamDependenceFlag, URL_Flag, OCRstreamFlag, streamPriority
+            // DecoderConfigDescrTag
+            write_descriptor(buf, 0x04, |buf| {
+                buf.write_u8(0x40)?; // objectTypeIndication, Audio ISO/IEC 14496-3
+                buf.write_u8((0x05 << 2) | 1)?; // streamType AudioStream, upStream 0, reserved 1
+                buf.write_u24::<BigEndian>(0)?; // bufferSizeDB
+                buf.write_u32::<BigEndian>(0)?; // maxBitrate
+                buf.write_u32::<BigEndian>(0)?; // avgBitrate
+                // DecSpecificInfoTag
+                write_descriptor(buf, 0x05, |buf| {
+                    buf.extend_from_slice(&audio.audio_specific_config);
+                    Ok(())
+                })
+            })?;
+            // SLConfigDescrTag
+            write_descriptor(buf, 0x06, |buf| {
+                buf.write_u8(0x02)?; // predefined, reserved for use in MP4 files
+                Ok(())
+            })
+        })
+    })
+}
+
+/// @see: ISO/IEC 14496-12 Section 8.8.3 Track Extends Box
+fn write_trex(buf: &mut Vec<u8>, track_id: u32) -> Mp4Result<()> {
+    write_full_box(buf, b"trex", 0, 0, |buf| {
+        buf.write_u32::<BigEndian>(track_id)?;
+        buf.write_u32::<BigEndian>(1)?; // default_sample_description_index
+        buf.write_u32::<BigEndian>(0)?; // default_sample_duration
+        buf.write_u32::<BigEndian>(0)?; // default_sample_size
+        buf.write_u32::<BigEndian>(0)?; // default_sample_flags
+        Ok(())
+    })
+}
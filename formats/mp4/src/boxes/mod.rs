@@ -0,0 +1,56 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use num::ToPrimitive;
+
+use crate::errors::{Mp4Error, Mp4Result};
+
+pub mod fragment;
+pub mod init;
+
+pub type FourCC = [u8; 4];
+
+/// @see: ISO/IEC 14496-12 Section 4.2 Object Structure
+/// writes a box header with a placeholder size, runs the body writer,
+/// then patches the size with the actual box length
+pub fn write_box<F>(buf: &mut Vec<u8>, box_type: &FourCC, body: F) -> Mp4Result<()>
+where
+    F: FnOnce(&mut Vec<u8>) -> Mp4Result<()>,
+{
+    let start = buf.len();
+    buf.write_u32::<BigEndian>(0)?;
+    buf.extend_from_slice(box_type);
+    body(buf)?;
+    let size = buf.len() - start;
+    let size_u32 = size.to_u32().ok_or_else(|| {
+        Mp4Error::BoxTooLarge(String::from_utf8_lossy(box_type).to_string(), size)
+    })?;
+    buf[start..start + 4].copy_from_slice(&size_u32.to_be_bytes());
+    Ok(())
+}
+
+/// @see: ISO/IEC 14496-12 Section 4.2 Object Structure, FullBox
+pub fn write_full_box<F>(
+    buf: &mut Vec<u8>,
+    box_type: &FourCC,
+    version: u8,
+    flags: u32,
+    body: F,
+) -> Mp4Result<()>
+where
+    F: FnOnce(&mut Vec<u8>) -> Mp4Result<()>,
+{
+    write_box(buf, box_type, |buf| {
+        buf.write_u8(version)?;
+        buf.write_u24::<BigEndian>(flags & 0xFF_FFFF)?;
+        body(buf)
+    })
+}
+
+/// unity transformation matrix shared by mvhd and tkhd
+pub(crate) const UNITY_MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+pub(crate) fn write_unity_matrix(buf: &mut Vec<u8>) -> Mp4Result<()> {
+    for value in UNITY_MATRIX {
+        buf.write_u32::<BigEndian>(value)?;
+    }
+    Ok(())
+}
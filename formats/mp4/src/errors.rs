@@ -0,0 +1,21 @@
+use std::io;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Mp4Error {
+    #[error("Io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("box {0} is too large: {1} bytes")]
+    BoxTooLarge(String, usize),
+    #[error("missing codec config: {0}")]
+    MissingCodecConfig(String),
+    #[error("invalid codec config: {0}")]
+    InvalidCodecConfig(String),
+    #[error("unsupported codec: {0}")]
+    UnsupportedCodec(String),
+    #[error("invalid timestamp: {0}")]
+    InvalidTimestamp(String),
+}
+
+pub type Mp4Result<T> = Result<T, Mp4Error>;
@@ -0,0 +1,6 @@
+pub mod boxes;
+pub mod errors;
+pub mod writer;
+
+#[cfg(test)]
+mod test;
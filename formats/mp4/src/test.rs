@@ -0,0 +1,330 @@
+#[cfg(test)]
+mod tests {
+    use codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecificConfig;
+    use codec_bitstream::reader::BitstreamReader;
+    use codec_common::{
+        FrameType, MediaFrameTimestamp,
+        audio::AudioConfig,
+        video::{H264VideoConfig, VideoCodecCommon, VideoConfig, VideoFrameInfo, VideoFrameUnit},
+    };
+    use codec_h264::{
+        nalu::NalUnit,
+        nalu_header::NaluHeader,
+        pps::Pps,
+        sps::{Sps, chroma_format_idc::ChromaFormatIdc},
+    };
+    use tokio_util::bytes::Bytes;
+    use utils::traits::reader::{BitwiseReadFrom, ReadFrom};
+
+    use crate::{
+        boxes::fragment::{NON_SYNC_SAMPLE_FLAGS, SYNC_SAMPLE_FLAGS},
+        writer::{AUDIO_TRACK_ID, FragmentedMp4Writer, VIDEO_TIMESCALE, VIDEO_TRACK_ID},
+    };
+
+    const SPS: [u8; 26] = [
+        0x67, 0x64, 0x00, 0x1e, 0xac, 0xd9, 0x40, 0xd8, 0x3d, 0xe6, 0xf0, 0x11, 0x00, 0x00, 0x03,
+        0x00, 0x01, 0x00, 0x00, 0x03, 0x00, 0x30, 0x0f, 0x16, 0x2d, 0x96,
+    ];
+    const PPS: [u8; 4] = [0x68, 0xef, 0x8f, 0xcb];
+    // AAC LC, 44100Hz, stereo
+    const ASC: [u8; 2] = [0x12, 0x10];
+
+    /// a parsed box, `offset` is the position of the box header in the parsed buffer
+    #[derive(Debug, Clone, Copy)]
+    struct Mp4Box<'a> {
+        box_type: [u8; 4],
+        offset: usize,
+        payload: &'a [u8],
+    }
+
+    impl<'a> Mp4Box<'a> {
+        fn children(&self, header_len: usize) -> Vec<Mp4Box<'a>> {
+            parse_boxes(&self.payload[header_len..])
+        }
+
+        fn child(&self, box_type: &[u8; 4]) -> Mp4Box<'a> {
+            find_box(&self.children(0), box_type)
+        }
+
+        fn u32_at(&self, offset: usize) -> u32 {
+            u32::from_be_bytes(self.payload[offset..offset + 4].try_into().unwrap())
+        }
+
+        fn u64_at(&self, offset: usize) -> u64 {
+            u64::from_be_bytes(self.payload[offset..offset + 8].try_into().unwrap())
+        }
+    }
+
+    fn parse_boxes(data: &[u8]) -> Vec<Mp4Box<'_>> {
+        let mut result = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let size = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+            assert!(
+                size >= 8 && offset + size <= data.len(),
+                "bad box size {}",
+                size
+            );
+            result.push(Mp4Box {
+                box_type: data[offset + 4..offset + 8].try_into().unwrap(),
+                offset,
+                payload: &data[offset + 8..offset + size],
+            });
+            offset += size;
+        }
+        result
+    }
+
+    fn find_box<'a>(boxes: &[Mp4Box<'a>], box_type: &[u8; 4]) -> Mp4Box<'a> {
+        let found = boxes
+            .iter()
+            .find(|b| &b.box_type == box_type)
+            .unwrap_or_else(|| panic!("box {} not found", String::from_utf8_lossy(box_type)));
+        *found
+    }
+
+    fn video_config() -> (Sps, VideoConfig) {
+        let sps_nalu = NalUnit::read_from(&mut SPS.as_slice()).unwrap();
+        let pps_nalu = NalUnit::read_from(&mut PPS.as_slice()).unwrap();
+        let sps = Sps::try_from(&sps_nalu).unwrap();
+        let chroma_format_idc = sps
+            .get_chroma_format_idc()
+            .unwrap_or(ChromaFormatIdc::Chroma420);
+        let pps = Pps::try_from((chroma_format_idc, &pps_nalu)).unwrap();
+        (
+            sps.clone(),
+            VideoConfig::H264(H264VideoConfig {
+                sps: Some(sps),
+                pps: Some(pps),
+                sps_ext: None,
+                avc_decoder_configuration_record: None,
+            }),
+        )
+    }
+
+    fn audio_config() -> AudioConfig {
+        let mut reader = BitstreamReader::new(&ASC);
+        AudioConfig::AAC(AudioSpecificConfig::read_from(&mut reader).unwrap())
+    }
+
+    fn video_frame(
+        frame_type: FrameType,
+        dts_ms: u64,
+        pts_ms: u64,
+    ) -> (VideoFrameInfo, VideoFrameUnit) {
+        let nal_unit_type = if frame_type == FrameType::KeyFrame {
+            0x65
+        } else {
+            0x41
+        };
+        let mut timestamp = MediaFrameTimestamp::with_timestamp_ms(dts_ms);
+        timestamp.set_pts_ms(pts_ms);
+        (
+            VideoFrameInfo::new(VideoCodecCommon::AVC, frame_type, timestamp),
+            VideoFrameUnit::H264 {
+                nal_units: vec![NalUnit {
+                    header: NaluHeader::try_from(nal_unit_type).unwrap(),
+                    body: Bytes::from(vec![0x88, 0x84, 0x21, dts_ms as u8]),
+                }],
+            },
+        )
+    }
+
+    #[test]
+    fn init_segment_describes_tracks() {
+        let (sps, video) = video_config();
+        let mut writer = FragmentedMp4Writer::new();
+        assert!(writer.init_segment().is_err());
+        writer.set_video_config(&video).unwrap();
+        writer.set_audio_config(&audio_config()).unwrap();
+        let init = writer.init_segment().unwrap();
+
+        let boxes = parse_boxes(&init);
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(&boxes[0].box_type, b"ftyp");
+        assert_eq!(&boxes[0].payload[..4], b"iso6");
+        let moov = find_box(&boxes, b"moov");
+        let children = moov.children(0);
+        let traks: Vec<&Mp4Box> = children.iter().filter(|b| &b.box_type == b"trak").collect();
+        assert_eq!(traks.len(), 2);
+        assert_eq!(moov.child(b"mvhd").u32_at(4 + 92), AUDIO_TRACK_ID + 1);
+
+        // video track
+        let tkhd = traks[0].child(b"tkhd");
+        assert_eq!(tkhd.u32_at(4 + 8), VIDEO_TRACK_ID);
+        assert_eq!(tkhd.u32_at(4 + 72) >> 16, sps.get_video_width() as u32);
+        assert_eq!(tkhd.u32_at(4 + 76) >> 16, sps.get_video_height() as u32);
+        let mdia = traks[0].child(b"mdia");
+        assert_eq!(mdia.child(b"mdhd").u32_at(4 + 8), VIDEO_TIMESCALE);
+        assert_eq!(&mdia.child(b"hdlr").payload[8..12], b"vide");
+        let stsd = mdia.child(b"minf").child(b"stbl").child(b"stsd");
+        let avc1 = find_box(&stsd.children(8), b"avc1");
+        let avcc = find_box(&avc1.children(78), b"avcC");
+        // configurationVersion, profile, compatibility, level
+        assert_eq!(&avcc.payload[..4], &[0x01, 0x64, 0x00, 0x1e]);
+        // 4 bytes nalu length
+        assert_eq!(avcc.payload[4] & 0b11, 3);
+        // one sps
+        assert_eq!(avcc.payload[5] & 0x1F, 1);
+
+        // audio track
+        let tkhd = traks[1].child(b"tkhd");
+        assert_eq!(tkhd.u32_at(4 + 8), AUDIO_TRACK_ID);
+        let mdia = traks[1].child(b"mdia");
+        assert_eq!(mdia.child(b"mdhd").u32_at(4 + 8), 44100);
+        assert_eq!(&mdia.child(b"hdlr").payload[8..12], b"soun");
+        let stsd = mdia.child(b"minf").child(b"stbl").child(b"stsd");
+        let mp4a = find_box(&stsd.children(8), b"mp4a");
+        assert_eq!(u16::from_be_bytes([mp4a.payload[16], mp4a.payload[17]]), 2);
+        assert_eq!(mp4a.u32_at(24) >> 16, 44100);
+        let esds = find_box(&mp4a.children(28), b"esds");
+        // DecSpecificInfo tag, 4 bytes size, then the audio specific config
+        let dsi = esds
+            .payload
+            .windows(7)
+            .position(|w| w == [0x05, 0x80, 0x80, 0x80, 0x02, ASC[0], ASC[1]]);
+        assert!(dsi.is_some());
+
+        let mvex = moov.child(b"mvex");
+        let trex: Vec<u32> = mvex
+            .children(0)
+            .iter()
+            .filter(|b| &b.box_type == b"trex")
+            .map(|b| b.u32_at(4))
+            .collect();
+        assert_eq!(trex, vec![VIDEO_TRACK_ID, AUDIO_TRACK_ID]);
+    }
+
+    #[test]
+    fn media_segments_are_cut_at_keyframes() {
+        let (_, video) = video_config();
+        let mut writer = FragmentedMp4Writer::new();
+        writer.set_video_config(&video).unwrap();
+        writer.set_audio_config(&audio_config()).unwrap();
+
+        // frames before the first keyframe are dropped
+        let (info, payload) = video_frame(FrameType::CodedFrames, 0, 0);
+        assert!(writer.write_video(&info, &payload).unwrap().is_none());
+
+        let mut segments = Vec::new();
+        // two gops of 3 frames at 25fps, with a composition offset of 80ms on p frames
+        for index in 0..7u64 {
+            let dts = 1000 + index * 40;
+            let frame_type = if index % 3 == 0 {
+                FrameType::KeyFrame
+            } else {
+                FrameType::CodedFrames
+            };
+            let pts = if frame_type == FrameType::KeyFrame {
+                dts
+            } else {
+                dts + 80
+            };
+            let (info, payload) = video_frame(frame_type, dts, pts);
+            if let Some(segment) = writer.write_video(&info, &payload).unwrap() {
+                segments.push(segment);
+            }
+            // one aac frame per video frame, 1024 samples is about 23.2ms at 44100Hz
+            let audio_ts_nano = (1000 + index * 40) * 1_000_000;
+            if let Some(segment) = writer
+                .write_audio(audio_ts_nano, Bytes::from(vec![0x21, index as u8]))
+                .unwrap()
+            {
+                segments.push(segment);
+            }
+        }
+        if let Some(segment) = writer.flush().unwrap() {
+            segments.push(segment);
+        }
+        assert_eq!(segments.len(), 3);
+
+        let mut next_decode_time = [None, None];
+        for (index, segment) in segments.iter().enumerate() {
+            let boxes = parse_boxes(segment);
+            assert_eq!(boxes.len(), 2);
+            let moof = find_box(&boxes, b"moof");
+            let mdat = find_box(&boxes, b"mdat");
+            assert_eq!(moof.child(b"mfhd").u32_at(4), index as u32 + 1);
+
+            for traf in moof.children(0).iter().filter(|b| &b.box_type == b"traf") {
+                let track_id = traf.child(b"tfhd").u32_at(4);
+                let tfdt = traf.child(b"tfdt");
+                assert_eq!(tfdt.payload[0], 1);
+                let base_media_decode_time = tfdt.u64_at(4);
+                let slot = (track_id - 1) as usize;
+                if let Some(expected) = next_decode_time[slot] {
+                    assert_eq!(base_media_decode_time, expected);
+                }
+
+                let trun = traf.child(b"trun");
+                assert_eq!(trun.payload[0], 1);
+                let sample_count = trun.u32_at(4) as usize;
+                let data_offset = trun.u32_at(8) as usize;
+                // data offset is relative to moof, points into mdat payload
+                let mut position = data_offset - (mdat.offset - moof.offset) - 8;
+                let mut total_duration = 0u64;
+                for sample in 0..sample_count {
+                    let entry = 12 + sample * 16;
+                    let duration = trun.u32_at(entry);
+                    let size = trun.u32_at(entry + 4) as usize;
+                    let flags = trun.u32_at(entry + 8);
+                    let cto = trun.u32_at(entry + 12) as i32;
+                    let data = &mdat.payload[position..position + size];
+                    position += size;
+                    total_duration += duration as u64;
+
+                    if track_id == VIDEO_TRACK_ID {
+                        assert_eq!(duration, 3600);
+                        // 4 bytes length prefix + 1 byte nalu header + 4 bytes body
+                        assert_eq!(u32::from_be_bytes(data[..4].try_into().unwrap()), 5);
+                        if sample == 0 {
+                            assert_eq!(flags, SYNC_SAMPLE_FLAGS);
+                            assert_eq!(cto, 0);
+                            assert_eq!(data[4], 0x65);
+                        } else {
+                            assert_eq!(flags, NON_SYNC_SAMPLE_FLAGS);
+                            assert_eq!(cto, 7200);
+                            assert_eq!(data[4], 0x41);
+                        }
+                    } else {
+                        assert_eq!(track_id, AUDIO_TRACK_ID);
+                        assert_eq!(flags, SYNC_SAMPLE_FLAGS);
+                        assert_eq!(cto, 0);
+                        assert_eq!(data[0], 0x21);
+                        assert!(duration > 0);
+                    }
+                }
+                if track_id == VIDEO_TRACK_ID && index < 2 {
+                    assert_eq!(sample_count, 3);
+                }
+                next_decode_time[slot] = Some(base_media_decode_time + total_duration);
+            }
+        }
+        // the first video fragment starts at the first keyframe
+        let first = parse_boxes(&segments[0]);
+        let traf = find_box(&find_box(&first, b"moof").children(0), b"traf");
+        assert_eq!(traf.child(b"tfdt").u64_at(4), 90000);
+    }
+
+    #[test]
+    fn audio_only_segments_are_cut_by_duration() {
+        let mut writer = FragmentedMp4Writer::new().with_audio_only_fragment_duration_ms(100);
+        writer.set_audio_config(&audio_config()).unwrap();
+        let mut segments = Vec::new();
+        for index in 0..20u64 {
+            let timestamp_nano = (index * 1024 * 1_000_000_000).div_ceil(44100);
+            if let Some(segment) = writer
+                .write_audio(timestamp_nano, Bytes::from_static(&[0x21, 0x00]))
+                .unwrap()
+            {
+                segments.push(segment);
+            }
+        }
+        assert!(!segments.is_empty());
+        let boxes = parse_boxes(&segments[0]);
+        let traf = find_box(&find_box(&boxes, b"moof").children(0), b"traf");
+        let trun = traf.child(b"trun");
+        assert!(trun.u32_at(4) >= 4);
+        assert_eq!(trun.u32_at(12), 1024);
+    }
+}
@@ -0,0 +1,408 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use codec_common::{
+    FrameType,
+    audio::AudioConfig,
+    video::{VideoConfig, VideoFrameInfo, VideoFrameUnit},
+};
+use codec_h264::avc_decoder_configuration_record::AvcDecoderConfigurationRecord;
+use num::ToPrimitive;
+use tokio_util::bytes::Bytes;
+use utils::traits::writer::WriteTo;
+
+use crate::{
+    boxes::{
+        fragment::{
+            FragmentSample, NON_SYNC_SAMPLE_FLAGS, SYNC_SAMPLE_FLAGS, TrackFragment,
+            write_media_segment,
+        },
+        init::{AudioTrack, TrackConfig, VideoTrack, write_ftyp, write_moov},
+    },
+    errors::{Mp4Error, Mp4Result},
+};
+
+pub const VIDEO_TRACK_ID: u32 = 1;
+pub const AUDIO_TRACK_ID: u32 = 2;
+pub const VIDEO_TIMESCALE: u32 = 90000;
+/// samples per AAC frame, for the configs out of the GA family which tell no frame length
+const AAC_FRAME_SAMPLES: u32 = 1024;
+/// 25fps in 90kHz, used when the duration of the last video sample is unknown
+const DEFAULT_VIDEO_SAMPLE_DURATION: u32 = 3600;
+/// fragment duration for audio only streams, as there is no keyframe to cut on
+const DEFAULT_AUDIO_ONLY_FRAGMENT_DURATION_MS: u64 = 1000;
+
+#[derive(Debug)]
+struct PendingSample {
+    /// decode timestamp in track timescale
+    dts: u64,
+    composition_time_offset: i32,
+    is_sync: bool,
+    data: Bytes,
+}
+
+#[derive(Debug)]
+struct TrackState {
+    config: TrackConfig,
+    pending: Vec<PendingSample>,
+    /// decode time of the next fragment in track timescale,
+    /// advanced by the durations written so that tfdt is always continuous
+    next_decode_time: Option<u64>,
+    last_sample_duration: Option<u32>,
+}
+
+impl TrackState {
+    fn new(config: TrackConfig) -> Self {
+        Self {
+            config,
+            pending: Vec::new(),
+            next_decode_time: None,
+            last_sample_duration: None,
+        }
+    }
+
+    fn timescale(&self) -> u32 {
+        self.config.timescale()
+    }
+
+    fn default_sample_duration(&self) -> u32 {
+        match &self.config {
+            TrackConfig::Video(_) => DEFAULT_VIDEO_SAMPLE_DURATION,
+            TrackConfig::Audio(track) => track.samples_per_frame,
+        }
+    }
+
+    /// takes pending samples with dts before `end_dts`, or all of them if `end_dts` is None
+    fn take_fragment(&mut self, end_dts: Option<u64>) -> Option<TrackFragment> {
+        let count = match end_dts {
+            Some(end) => self.pending.iter().take_while(|s| s.dts < end).count(),
+            None => self.pending.len(),
+        };
+        if count == 0 {
+            return None;
+        }
+
+        let taken: Vec<PendingSample> = self.pending.drain(..count).collect();
+        // the duration of the last sample is known if there is a following sample
+        let following_dts = self.pending.first().map(|s| s.dts).or(end_dts);
+        let base_media_decode_time = *self.next_decode_time.get_or_insert(taken[0].dts);
+        let mut samples = Vec::with_capacity(taken.len());
+        for (index, sample) in taken.iter().enumerate() {
+            let next_dts = taken.get(index + 1).map(|s| s.dts).or(following_dts);
+            let duration = match next_dts {
+                Some(next) if next > sample.dts => (next - sample.dts).to_u32().unwrap_or(u32::MAX),
+                _ => self
+                    .last_sample_duration
+                    .unwrap_or_else(|| self.default_sample_duration()),
+            };
+            self.last_sample_duration = Some(duration);
+            samples.push(FragmentSample {
+                duration,
+                composition_time_offset: sample.composition_time_offset,
+                flags: if sample.is_sync {
+                    SYNC_SAMPLE_FLAGS
+                } else {
+                    NON_SYNC_SAMPLE_FLAGS
+                },
+                data: sample.data.clone(),
+            });
+        }
+
+        let fragment = TrackFragment {
+            track_id: self.config.track_id(),
+            base_media_decode_time,
+            samples,
+        };
+        self.next_decode_time = Some(base_media_decode_time + fragment.duration());
+        Some(fragment)
+    }
+}
+
+/// Muxes AVC and AAC frames into fragmented MP4 (CMAF compatible),
+/// the init segment is ftyp + moov, each media segment is moof + mdat
+/// and starts with a video keyframe if there is a video track
+#[derive(Debug)]
+pub struct FragmentedMp4Writer {
+    video: Option<TrackState>,
+    audio: Option<TrackState>,
+    sequence_number: u32,
+    audio_only_fragment_duration_ms: u64,
+}
+
+impl Default for FragmentedMp4Writer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn nano_to_timescale(nano: u64, timescale: u32) -> u64 {
+    ((nano as u128) * (timescale as u128) / 1_000_000_000u128) as u64
+}
+
+impl FragmentedMp4Writer {
+    pub fn new() -> Self {
+        Self {
+            video: None,
+            audio: None,
+            sequence_number: 0,
+            audio_only_fragment_duration_ms: DEFAULT_AUDIO_ONLY_FRAGMENT_DURATION_MS,
+        }
+    }
+
+    pub fn with_audio_only_fragment_duration_ms(mut self, duration_ms: u64) -> Self {
+        self.audio_only_fragment_duration_ms = duration_ms;
+        self
+    }
+
+    pub fn has_video(&self) -> bool {
+        self.video.is_some()
+    }
+
+    pub fn has_audio(&self) -> bool {
+        self.audio.is_some()
+    }
+
+    /// sets or replaces the video track, a new init segment must be emitted after a replacement
+    pub fn set_video_config(&mut self, config: &VideoConfig) -> Mp4Result<()> {
+        let track = match config {
+            VideoConfig::H264(h264) => {
+                let sps = h264.sps.as_ref().ok_or_else(|| {
+                    Mp4Error::MissingCodecConfig("h264 sps is missing".to_owned())
+                })?;
+                let record = match &h264.avc_decoder_configuration_record {
+                    Some(record) => record.clone(),
+                    None => {
+                        let pps = h264.pps.as_ref().ok_or_else(|| {
+                            Mp4Error::MissingCodecConfig("h264 pps is missing".to_owned())
+                        })?;
+                        AvcDecoderConfigurationRecord::from((sps, pps))
+                    }
+                };
+                let mut bytes = Vec::new();
+                record.write_to(&mut bytes).map_err(|err| {
+                    Mp4Error::InvalidCodecConfig(format!(
+                        "write avc decoder configuration record failed: {}",
+                        err
+                    ))
+                })?;
+                VideoTrack {
+                    track_id: VIDEO_TRACK_ID,
+                    timescale: VIDEO_TIMESCALE,
+                    width: sps.get_video_width().to_u16().unwrap_or(0),
+                    height: sps.get_video_height().to_u16().unwrap_or(0),
+                    avc_decoder_configuration_record: Bytes::from(bytes),
+                }
+            }
+            VideoConfig::AV1(_) => {
+                return Err(Mp4Error::UnsupportedCodec(
+                    "av1 video is not supported in mp4 yet".to_owned(),
+                ));
+            }
+        };
+        self.video = Some(TrackState::new(TrackConfig::Video(track)));
+        Ok(())
+    }
+
+    /// sets or replaces the audio track, a new init segment must be emitted after a replacement
+    pub fn set_audio_config(&mut self, config: &AudioConfig) -> Mp4Result<()> {
+        let track = match config {
+            AudioConfig::AAC(asc) => {
+                // the rate the decoder outputs, the timestamps of he-aac tick at the sbr rate
+                let sample_rate = asc.effective_sample_rate().ok_or_else(|| {
+                    Mp4Error::InvalidCodecConfig(format!(
+                        "unknown aac sampling frequency: {:?}",
+                        asc.sampling_frequency_index
+                    ))
+                })?;
+                let mut bytes = Vec::new();
+                config.write_to(&mut bytes).map_err(|err| {
+                    Mp4Error::InvalidCodecConfig(format!(
+                        "write audio specific config failed: {}",
+                        err
+                    ))
+                })?;
+                // the layout of channel configuration 0 is in the program config element,
+                // stereo if there is no layout to tell
+                let channel_count = asc
+                    .channel_positions()
+                    .map_err(|err| {
+                        Mp4Error::InvalidCodecConfig(format!("invalid channel layout: {}", err))
+                    })?
+                    .map_or(2, |positions| positions.total() as u16);
+                AudioTrack {
+                    track_id: AUDIO_TRACK_ID,
+                    timescale: sample_rate,
+                    channel_count,
+                    sample_rate,
+                    samples_per_frame: asc.samples_per_frame().unwrap_or(AAC_FRAME_SAMPLES),
+                    audio_specific_config: Bytes::from(bytes),
+                }
+            }
+        };
+        self.audio = Some(TrackState::new(TrackConfig::Audio(track)));
+        Ok(())
+    }
+
+    fn tracks(&self) -> Vec<TrackConfig> {
+        self.video
+            .iter()
+            .chain(self.audio.iter())
+            .map(|track| track.config.clone())
+            .collect()
+    }
+
+    /// ftyp + moov describing every configured track
+    pub fn init_segment(&self) -> Mp4Result<Bytes> {
+        let tracks = self.tracks();
+        if tracks.is_empty() {
+            return Err(Mp4Error::MissingCodecConfig(
+                "neither video nor audio config is set".to_owned(),
+            ));
+        }
+        let mut buf = Vec::new();
+        write_ftyp(&mut buf)?;
+        write_moov(&mut buf, &tracks)?;
+        Ok(Bytes::from(buf))
+    }
+
+    /// queues a video frame, returns the finished media segment
+    /// if this frame is a keyframe that starts a new one
+    pub fn write_video(
+        &mut self,
+        frame_info: &VideoFrameInfo,
+        payload: &VideoFrameUnit,
+    ) -> Mp4Result<Option<Bytes>> {
+        let is_sync = match frame_info.frame_type {
+            FrameType::KeyFrame => true,
+            FrameType::CodedFrames => false,
+            FrameType::SequenceStart | FrameType::SequenceEnd => return Ok(None),
+        };
+        let Some(video) = self.video.as_ref() else {
+            return Err(Mp4Error::MissingCodecConfig(
+                "video config is not set".to_owned(),
+            ));
+        };
+        if !is_sync && video.pending.is_empty() && video.next_decode_time.is_none() {
+            // nothing is decodable before the first keyframe
+            return Ok(None);
+        }
+        let timescale = video.timescale();
+        let dts = nano_to_timescale(frame_info.timestamp.dts(), timescale);
+        let pts = nano_to_timescale(frame_info.timestamp.pts(), timescale);
+        if let Some(last) = video.pending.last()
+            && dts < last.dts
+        {
+            return Err(Mp4Error::InvalidTimestamp(format!(
+                "video dts goes backwards, last: {}, current: {}",
+                last.dts, dts
+            )));
+        }
+        let composition_time_offset = (pts as i64 - dts as i64).to_i32().ok_or_else(|| {
+            Mp4Error::InvalidTimestamp(format!(
+                "composition time offset out of range, pts: {}, dts: {}",
+                pts, dts
+            ))
+        })?;
+        let data = match payload {
+            VideoFrameUnit::H264 { nal_units } => {
+                // AVC samples are length prefixed nal units, the length size is 4 bytes
+                let mut buf = Vec::new();
+                for nalu in nal_units {
+                    let mut bytes = Vec::new();
+                    nalu.write_to(&mut bytes).map_err(|err| {
+                        Mp4Error::InvalidCodecConfig(format!("write nalu failed: {}", err))
+                    })?;
+                    buf.write_u32::<BigEndian>(bytes.len().to_u32().unwrap())?;
+                    buf.extend_from_slice(&bytes);
+                }
+                Bytes::from(buf)
+            }
+            VideoFrameUnit::AV1 { .. } => {
+                return Err(Mp4Error::UnsupportedCodec(
+                    "av1 video is not supported in mp4 yet".to_owned(),
+                ));
+            }
+        };
+
+        let segment = if is_sync && video.pending.iter().any(|s| s.is_sync) {
+            self.cut_fragment(Some((dts, timescale)))?
+        } else {
+            None
+        };
+        if let Some(video) = self.video.as_mut() {
+            video.pending.push(PendingSample {
+                dts,
+                composition_time_offset,
+                is_sync,
+                data,
+            });
+        }
+        Ok(segment)
+    }
+
+    /// queues a raw AAC access unit, without any ADTS or FLV tag header
+    pub fn write_audio(
+        &mut self,
+        timestamp_nano: u64,
+        access_unit: Bytes,
+    ) -> Mp4Result<Option<Bytes>> {
+        let Some(audio) = self.audio.as_mut() else {
+            return Err(Mp4Error::MissingCodecConfig(
+                "audio config is not set".to_owned(),
+            ));
+        };
+        let timescale = audio.timescale();
+        let dts = nano_to_timescale(timestamp_nano, timescale);
+        if let Some(last) = audio.pending.last()
+            && dts < last.dts
+        {
+            return Err(Mp4Error::InvalidTimestamp(format!(
+                "audio dts goes backwards, last: {}, current: {}",
+                last.dts, dts
+            )));
+        }
+        audio.pending.push(PendingSample {
+            dts,
+            composition_time_offset: 0,
+            is_sync: true,
+            data: access_unit,
+        });
+
+        if self.video.is_some() {
+            return Ok(None);
+        }
+        let first_dts = audio.pending[0].dts;
+        let fragment_duration = self.audio_only_fragment_duration_ms * timescale as u64 / 1000;
+        if dts - first_dts >= fragment_duration && audio.pending.len() > 1 {
+            return self.cut_fragment(Some((dts, timescale)));
+        }
+        Ok(None)
+    }
+
+    /// writes out everything pending, used at the end of the stream
+    pub fn flush(&mut self) -> Mp4Result<Option<Bytes>> {
+        self.cut_fragment(None)
+    }
+
+    /// cuts a media segment ending before `end`, which is a dts in the given timescale
+    fn cut_fragment(&mut self, end: Option<(u64, u32)>) -> Mp4Result<Option<Bytes>> {
+        let mut fragments = Vec::with_capacity(2);
+        for track in [self.video.as_mut(), self.audio.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            let end_dts = end.map(|(dts, timescale)| {
+                ((dts as u128) * (track.timescale() as u128) / (timescale as u128)) as u64
+            });
+            if let Some(fragment) = track.take_fragment(end_dts) {
+                fragments.push(fragment);
+            }
+        }
+        if fragments.is_empty() {
+            return Ok(None);
+        }
+
+        self.sequence_number += 1;
+        let mut buf = Vec::new();
+        write_media_segment(&mut buf, self.sequence_number, &fragments)?;
+        Ok(Some(Bytes::from(buf)))
+    }
+}
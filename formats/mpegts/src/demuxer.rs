@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use tokio_util::bytes::{Bytes, BytesMut};
+
+use crate::{
+    packet::{PAT_PID, SYNC_BYTE, TS_PACKET_SIZE, TsPacket},
+    pes::PesHeader,
+    psi::{Pat, Pmt},
+};
+
+/// stream_type values from ISO/IEC 13818-1, Table 2-34, only the ones we can ingest are listed
+pub mod stream_type {
+    pub const AAC_ADTS: u8 = 0x0F;
+    pub const H264: u8 = 0x1B;
+}
+
+/// a reassembled PES packet of an elementary stream
+#[derive(Debug, Clone)]
+pub struct PesPacket {
+    pub pid: u16,
+    pub stream_type: u8,
+    /// in 90kHz
+    pub pts: Option<u64>,
+    /// in 90kHz
+    pub dts: Option<u64>,
+    pub random_access: bool,
+    /// the latest pcr base seen on the pcr pid when this packet completes, in 90kHz
+    pub pcr: Option<u64>,
+    pub payload: Bytes,
+}
+
+#[derive(Debug)]
+struct PesAssembler {
+    stream_type: u8,
+    continuity_counter: Option<u8>,
+    random_access: bool,
+    /// set when a packet is lost, data is dropped until the next payload unit start
+    broken: bool,
+    data: BytesMut,
+}
+
+impl PesAssembler {
+    fn new(stream_type: u8) -> Self {
+        Self {
+            stream_type,
+            continuity_counter: None,
+            random_access: false,
+            broken: false,
+            data: BytesMut::new(),
+        }
+    }
+
+    /// expected pes size including the 6 leading bytes, None if unbounded or unknown yet
+    fn expected_size(&self) -> Option<usize> {
+        if self.data.len() < 6 {
+            return None;
+        }
+        let length = ((self.data[4] as usize) << 8) | self.data[5] as usize;
+        if length == 0 { None } else { Some(6 + length) }
+    }
+}
+
+/// demuxes a single program transport stream into PES packets.
+/// bytes can be pushed in chunks of any size, partial ts packets are buffered
+#[derive(Debug, Default)]
+pub struct TsDemuxer {
+    buffer: BytesMut,
+    pmt_pid: Option<u16>,
+    pcr_pid: Option<u16>,
+    last_pcr: Option<u64>,
+    streams: HashMap<u16, PesAssembler>,
+}
+
+impl TsDemuxer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn last_pcr(&self) -> Option<u64> {
+        self.last_pcr
+    }
+
+    /// pid -> stream_type of the elementary streams announced by the PMT
+    pub fn streams(&self) -> HashMap<u16, u8> {
+        self.streams
+            .iter()
+            .map(|(pid, assembler)| (*pid, assembler.stream_type))
+            .collect()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<PesPacket> {
+        self.buffer.extend_from_slice(bytes);
+        let mut result = vec![];
+        loop {
+            if let Some(position) = self.buffer.iter().position(|b| *b == SYNC_BYTE) {
+                if position != 0 {
+                    tracing::warn!("ts sync lost, skipping {} bytes", position);
+                    let _ = self.buffer.split_to(position);
+                }
+            } else {
+                self.buffer.clear();
+                break;
+            }
+            if self.buffer.len() < TS_PACKET_SIZE {
+                break;
+            }
+            // a sync byte inside the payload may fool us, make sure the next packet is aligned too
+            if self.buffer.len() > TS_PACKET_SIZE && self.buffer[TS_PACKET_SIZE] != SYNC_BYTE {
+                tracing::warn!("ts packet not followed by a sync byte, resyncing");
+                let _ = self.buffer.split_to(1);
+                continue;
+            }
+            let packet_bytes = self.buffer.split_to(TS_PACKET_SIZE);
+            match TsPacket::parse(&packet_bytes) {
+                Ok(packet) => self.process_packet(&packet, &mut result),
+                Err(err) => {
+                    tracing::warn!("parse ts packet failed, skipped: {}", err);
+                }
+            }
+        }
+        result
+    }
+
+    /// emits all pending PES packets, used at the end of stream
+    /// since unbounded video PES packets only complete on the next payload unit start
+    pub fn flush(&mut self) -> Vec<PesPacket> {
+        let last_pcr = self.last_pcr;
+        let mut pids: Vec<_> = self.streams.keys().copied().collect();
+        pids.sort();
+        pids.into_iter()
+            .filter_map(|pid| {
+                let assembler = self.streams.get_mut(&pid)?;
+                Self::emit(pid, assembler, last_pcr)
+            })
+            .collect()
+    }
+
+    fn process_packet(&mut self, packet: &TsPacket, output: &mut Vec<PesPacket>) {
+        let header = &packet.header;
+        if header.transport_error_indicator {
+            tracing::warn!(
+                "ts packet with transport error on pid {}, skipped",
+                header.pid
+            );
+            return;
+        }
+        if Some(header.pid) == self.pcr_pid
+            && let Some(pcr) = packet.adaptation_field.and_then(|field| field.pcr)
+        {
+            self.last_pcr = Some(pcr);
+        }
+
+        if header.pid == PAT_PID {
+            if header.payload_unit_start_indicator {
+                match Pat::parse(packet.payload) {
+                    Ok(pat) => self.on_pat(pat),
+                    Err(err) => tracing::warn!("parse PAT failed: {}", err),
+                }
+            }
+            return;
+        }
+        if Some(header.pid) == self.pmt_pid {
+            if header.payload_unit_start_indicator {
+                match Pmt::parse(packet.payload) {
+                    Ok(pmt) => self.on_pmt(pmt),
+                    Err(err) => tracing::warn!("parse PMT failed: {}", err),
+                }
+            }
+            return;
+        }
+
+        let last_pcr = self.last_pcr;
+        let Some(assembler) = self.streams.get_mut(&header.pid) else {
+            return;
+        };
+        if !header.has_payload() {
+            return;
+        }
+
+        if let Some(last) = assembler.continuity_counter {
+            let discontinuity = packet
+                .adaptation_field
+                .is_some_and(|field| field.discontinuity_indicator);
+            if header.continuity_counter == last {
+                // duplicate packet
+                return;
+            }
+            if !discontinuity && (last + 1) & 0x0F != header.continuity_counter {
+                tracing::warn!(
+                    "continuity counter mismatch on pid {}, expect {}, got {}, dropping current pes",
+                    header.pid,
+                    (last + 1) & 0x0F,
+                    header.continuity_counter
+                );
+                assembler.broken = true;
+                assembler.data.clear();
+            }
+        }
+        assembler.continuity_counter = Some(header.continuity_counter);
+
+        if header.payload_unit_start_indicator {
+            output.extend(Self::emit(header.pid, assembler, last_pcr));
+            assembler.broken = false;
+            assembler.random_access = packet
+                .adaptation_field
+                .is_some_and(|field| field.random_access_indicator);
+        } else if assembler.broken || assembler.data.is_empty() {
+            return;
+        }
+        assembler.data.extend_from_slice(packet.payload);
+
+        if let Some(expected) = assembler.expected_size()
+            && assembler.data.len() >= expected
+        {
+            // bounded pes packets can be emitted as soon as complete
+            output.extend(Self::emit(header.pid, assembler, last_pcr));
+        }
+    }
+
+    fn on_pat(&mut self, pat: Pat) {
+        let pmt_pid = pat
+            .programs
+            .iter()
+            .find(|program| program.program_number != 0)
+            .map(|program| program.program_map_pid);
+        if pmt_pid != self.pmt_pid {
+            tracing::info!("ts program map pid: {:?}", pmt_pid);
+            self.pmt_pid = pmt_pid;
+        }
+    }
+
+    fn on_pmt(&mut self, pmt: Pmt) {
+        self.pcr_pid = Some(pmt.pcr_pid);
+        self.streams.retain(|pid, _| {
+            pmt.streams
+                .iter()
+                .any(|stream| stream.elementary_pid == *pid)
+        });
+        for stream in &pmt.streams {
+            let assembler = self
+                .streams
+                .entry(stream.elementary_pid)
+                .or_insert_with(|| {
+                    tracing::info!(
+                        "ts elementary stream found, pid: {}, stream_type: {:#04x}",
+                        stream.elementary_pid,
+                        stream.stream_type
+                    );
+                    PesAssembler::new(stream.stream_type)
+                });
+            if assembler.stream_type != stream.stream_type {
+                *assembler = PesAssembler::new(stream.stream_type);
+            }
+        }
+    }
+
+    fn emit(pid: u16, assembler: &mut PesAssembler, pcr: Option<u64>) -> Option<PesPacket> {
+        if assembler.data.is_empty() || assembler.broken {
+            assembler.data.clear();
+            return None;
+        }
+        let expected_size = assembler.expected_size();
+        let mut data = assembler.data.split();
+        if let Some(expected) = expected_size {
+            if data.len() < expected {
+                tracing::warn!(
+                    "truncated pes on pid {}, expect {} bytes, got {}, dropped",
+                    pid,
+                    expected,
+                    data.len()
+                );
+                return None;
+            }
+            data.truncate(expected);
+        }
+        let header = match PesHeader::parse(&data) {
+            Ok(header) => header,
+            Err(err) => {
+                tracing::warn!("parse pes header on pid {} failed, dropped: {}", pid, err);
+                return None;
+            }
+        };
+        let payload = data.split_off(header.header_length).freeze();
+        Some(PesPacket {
+            pid,
+            stream_type: assembler.stream_type,
+            pts: header.pts,
+            dts: header.dts.or(header.pts),
+            random_access: assembler.random_access,
+            pcr,
+            payload,
+        })
+    }
+}
@@ -0,0 +1,29 @@
+use std::io;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MpegTsError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid ts packet size: {0}")]
+    InvalidPacketSize(usize),
+    #[error("sync byte mismatch: {0:#04x}")]
+    SyncByteMismatch(u8),
+    #[error("invalid adaptation field: {0}")]
+    InvalidAdaptationField(String),
+    #[error("invalid psi section: {0}")]
+    InvalidSection(String),
+    #[error(
+        "psi section crc mismatch, table_id: {table_id}, expected: {expected:#010x}, actual: {actual:#010x}"
+    )]
+    CrcMismatch {
+        table_id: u8,
+        expected: u32,
+        actual: u32,
+    },
+    #[error("invalid pes packet: {0}")]
+    InvalidPes(String),
+}
+
+pub type MpegTsResult<T> = Result<T, MpegTsError>;
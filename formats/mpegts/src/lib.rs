@@ -0,0 +1,8 @@
+pub mod demuxer;
+pub mod errors;
+pub mod packet;
+pub mod pes;
+pub mod psi;
+
+#[cfg(test)]
+mod test;
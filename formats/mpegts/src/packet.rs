@@ -0,0 +1,122 @@
+use crate::errors::{MpegTsError, MpegTsResult};
+
+pub const TS_PACKET_SIZE: usize = 188;
+pub const SYNC_BYTE: u8 = 0x47;
+pub const PAT_PID: u16 = 0x0000;
+pub const NULL_PID: u16 = 0x1FFF;
+
+/// ISO/IEC 13818-1, 2.4.3.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TsPacketHeader {
+    pub transport_error_indicator: bool,
+    pub payload_unit_start_indicator: bool,
+    pub transport_priority: bool,
+    pub pid: u16,
+    pub transport_scrambling_control: u8,
+    pub adaptation_field_control: u8,
+    pub continuity_counter: u8,
+}
+
+impl TsPacketHeader {
+    pub fn has_adaptation_field(&self) -> bool {
+        self.adaptation_field_control & 0b10 != 0
+    }
+
+    pub fn has_payload(&self) -> bool {
+        self.adaptation_field_control & 0b01 != 0
+    }
+}
+
+/// ISO/IEC 13818-1, 2.4.3.4, only the fields we care about are kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AdaptationField {
+    pub discontinuity_indicator: bool,
+    pub random_access_indicator: bool,
+    /// program_clock_reference_base, in 90kHz
+    pub pcr: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct TsPacket<'a> {
+    pub header: TsPacketHeader,
+    pub adaptation_field: Option<AdaptationField>,
+    pub payload: &'a [u8],
+}
+
+impl<'a> TsPacket<'a> {
+    pub fn parse(bytes: &'a [u8]) -> MpegTsResult<Self> {
+        if bytes.len() != TS_PACKET_SIZE {
+            return Err(MpegTsError::InvalidPacketSize(bytes.len()));
+        }
+        if bytes[0] != SYNC_BYTE {
+            return Err(MpegTsError::SyncByteMismatch(bytes[0]));
+        }
+        let header = TsPacketHeader {
+            transport_error_indicator: bytes[1] & 0x80 != 0,
+            payload_unit_start_indicator: bytes[1] & 0x40 != 0,
+            transport_priority: bytes[1] & 0x20 != 0,
+            pid: (((bytes[1] & 0x1F) as u16) << 8) | bytes[2] as u16,
+            transport_scrambling_control: (bytes[3] >> 6) & 0b11,
+            adaptation_field_control: (bytes[3] >> 4) & 0b11,
+            continuity_counter: bytes[3] & 0x0F,
+        };
+
+        let mut offset = 4;
+        let adaptation_field = if header.has_adaptation_field() {
+            let length = bytes[offset] as usize;
+            offset += 1;
+            if offset + length > TS_PACKET_SIZE {
+                return Err(MpegTsError::InvalidAdaptationField(format!(
+                    "adaptation field length {} exceeds packet size",
+                    length
+                )));
+            }
+            let field = parse_adaptation_field(&bytes[offset..offset + length])?;
+            offset += length;
+            Some(field)
+        } else {
+            None
+        };
+
+        let payload = if header.has_payload() {
+            &bytes[offset..]
+        } else {
+            &[]
+        };
+
+        Ok(Self {
+            header,
+            adaptation_field,
+            payload,
+        })
+    }
+}
+
+fn parse_adaptation_field(bytes: &[u8]) -> MpegTsResult<AdaptationField> {
+    if bytes.is_empty() {
+        // a zero length adaptation field is used for one byte of stuffing
+        return Ok(AdaptationField::default());
+    }
+    let flags = bytes[0];
+    let pcr = if flags & 0x10 != 0 {
+        if bytes.len() < 7 {
+            return Err(MpegTsError::InvalidAdaptationField(format!(
+                "pcr flag is set but only {} bytes left",
+                bytes.len() - 1
+            )));
+        }
+        let base = ((bytes[1] as u64) << 25)
+            | ((bytes[2] as u64) << 17)
+            | ((bytes[3] as u64) << 9)
+            | ((bytes[4] as u64) << 1)
+            | ((bytes[5] as u64) >> 7);
+        Some(base)
+    } else {
+        None
+    };
+    Ok(AdaptationField {
+        discontinuity_indicator: flags & 0x80 != 0,
+        random_access_indicator: flags & 0x40 != 0,
+        pcr,
+    })
+}
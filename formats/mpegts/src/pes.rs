@@ -0,0 +1,83 @@
+use crate::errors::{MpegTsError, MpegTsResult};
+
+pub const PES_START_CODE_PREFIX: [u8; 3] = [0x00, 0x00, 0x01];
+
+/// PES packet header, ISO/IEC 13818-1, 2.4.3.6.
+/// only streams carrying the optional pes header (audio / video) are supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PesHeader {
+    pub stream_id: u8,
+    /// 0 means unbounded, which is allowed for video elementary streams
+    pub pes_packet_length: u16,
+    /// in 90kHz
+    pub pts: Option<u64>,
+    /// in 90kHz
+    pub dts: Option<u64>,
+    /// bytes consumed by the header, the elementary stream data follows
+    pub header_length: usize,
+}
+
+impl PesHeader {
+    pub fn parse(bytes: &[u8]) -> MpegTsResult<Self> {
+        if bytes.len() < 9 {
+            return Err(MpegTsError::InvalidPes(format!(
+                "pes header too short: {}",
+                bytes.len()
+            )));
+        }
+        if bytes[0..3] != PES_START_CODE_PREFIX {
+            return Err(MpegTsError::InvalidPes(format!(
+                "invalid start code prefix: {:?}",
+                &bytes[0..3]
+            )));
+        }
+        let stream_id = bytes[3];
+        let pes_packet_length = ((bytes[4] as u16) << 8) | bytes[5] as u16;
+        if bytes[6] >> 6 != 0b10 {
+            return Err(MpegTsError::InvalidPes(format!(
+                "unsupported pes header for stream id {:#04x}",
+                stream_id
+            )));
+        }
+        let pts_dts_flags = bytes[7] >> 6;
+        let pes_header_data_length = bytes[8] as usize;
+        let header_length = 9 + pes_header_data_length;
+        if bytes.len() < header_length {
+            return Err(MpegTsError::InvalidPes(format!(
+                "pes header data length {} exceeds {} bytes",
+                pes_header_data_length,
+                bytes.len()
+            )));
+        }
+        let (pts, dts) = match pts_dts_flags {
+            0b10 => (Some(parse_timestamp(&bytes[9..])?), None),
+            0b11 => (
+                Some(parse_timestamp(&bytes[9..])?),
+                Some(parse_timestamp(&bytes[14..])?),
+            ),
+            _ => (None, None),
+        };
+        Ok(Self {
+            stream_id,
+            pes_packet_length,
+            pts,
+            dts,
+            header_length,
+        })
+    }
+}
+
+/// 33 bit timestamp spread over 5 bytes with marker bits
+fn parse_timestamp(bytes: &[u8]) -> MpegTsResult<u64> {
+    if bytes.len() < 5 {
+        return Err(MpegTsError::InvalidPes(format!(
+            "timestamp too short: {}",
+            bytes.len()
+        )));
+    }
+    Ok((((bytes[0] >> 1) & 0x07) as u64) << 30
+        | (bytes[1] as u64) << 22
+        | ((bytes[2] >> 1) as u64) << 15
+        | (bytes[3] as u64) << 7
+        | (bytes[4] >> 1) as u64)
+}
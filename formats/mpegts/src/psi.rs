@@ -0,0 +1,180 @@
+use crate::errors::{MpegTsError, MpegTsResult};
+
+pub const TABLE_ID_PAT: u8 = 0x00;
+pub const TABLE_ID_PMT: u8 = 0x02;
+
+/// crc32 used by psi sections, ISO/IEC 13818-1, Annex A
+pub fn crc32_mpeg2(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// the long form section syntax shared by PAT and PMT, ISO/IEC 13818-1, 2.4.4
+#[derive(Debug)]
+struct Section<'a> {
+    table_id: u8,
+    table_id_extension: u16,
+    version_number: u8,
+    /// bytes after last_section_number, crc excluded
+    body: &'a [u8],
+}
+
+/// parse the section starting a ts packet payload, which has the pointer_field set.
+/// sections spanning multiple ts packets are not supported, which is fine for PAT/PMT
+/// of the single program streams we ingest
+fn parse_section(payload: &[u8]) -> MpegTsResult<Section<'_>> {
+    let pointer_field = *payload
+        .first()
+        .ok_or_else(|| MpegTsError::InvalidSection("empty payload".to_owned()))?
+        as usize;
+    let bytes = payload.get(1 + pointer_field..).ok_or_else(|| {
+        MpegTsError::InvalidSection(format!("pointer field {} out of range", pointer_field))
+    })?;
+    if bytes.len() < 3 {
+        return Err(MpegTsError::InvalidSection(format!(
+            "section header too short: {}",
+            bytes.len()
+        )));
+    }
+    let table_id = bytes[0];
+    let section_length = (((bytes[1] & 0x0F) as usize) << 8) | bytes[2] as usize;
+    if section_length < 9 || 3 + section_length > bytes.len() {
+        return Err(MpegTsError::InvalidSection(format!(
+            "invalid section length {} for table {}, {} bytes available",
+            section_length,
+            table_id,
+            bytes.len()
+        )));
+    }
+    let section = &bytes[..3 + section_length];
+    let (data, crc) = section.split_at(section.len() - 4);
+    let expected = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+    let actual = crc32_mpeg2(data);
+    if expected != actual {
+        return Err(MpegTsError::CrcMismatch {
+            table_id,
+            expected,
+            actual,
+        });
+    }
+    Ok(Section {
+        table_id,
+        table_id_extension: ((data[3] as u16) << 8) | data[4] as u16,
+        version_number: (data[5] >> 1) & 0x1F,
+        body: &data[8..],
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramAssociation {
+    pub program_number: u16,
+    /// network_PID if program_number is 0
+    pub program_map_pid: u16,
+}
+
+/// Program Association Table, ISO/IEC 13818-1, 2.4.4.3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pat {
+    pub transport_stream_id: u16,
+    pub version_number: u8,
+    pub programs: Vec<ProgramAssociation>,
+}
+
+impl Pat {
+    pub fn parse(payload: &[u8]) -> MpegTsResult<Self> {
+        let section = parse_section(payload)?;
+        if section.table_id != TABLE_ID_PAT {
+            return Err(MpegTsError::InvalidSection(format!(
+                "expect PAT table id, got {}",
+                section.table_id
+            )));
+        }
+        let programs = section
+            .body
+            .chunks_exact(4)
+            .map(|chunk| ProgramAssociation {
+                program_number: ((chunk[0] as u16) << 8) | chunk[1] as u16,
+                program_map_pid: (((chunk[2] & 0x1F) as u16) << 8) | chunk[3] as u16,
+            })
+            .collect();
+        Ok(Self {
+            transport_stream_id: section.table_id_extension,
+            version_number: section.version_number,
+            programs,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementaryStreamInfo {
+    pub stream_type: u8,
+    pub elementary_pid: u16,
+}
+
+/// Program Map Table, ISO/IEC 13818-1, 2.4.4.8
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pmt {
+    pub program_number: u16,
+    pub version_number: u8,
+    pub pcr_pid: u16,
+    pub streams: Vec<ElementaryStreamInfo>,
+}
+
+impl Pmt {
+    pub fn parse(payload: &[u8]) -> MpegTsResult<Self> {
+        let section = parse_section(payload)?;
+        if section.table_id != TABLE_ID_PMT {
+            return Err(MpegTsError::InvalidSection(format!(
+                "expect PMT table id, got {}",
+                section.table_id
+            )));
+        }
+        let body = section.body;
+        if body.len() < 4 {
+            return Err(MpegTsError::InvalidSection(format!(
+                "PMT body too short: {}",
+                body.len()
+            )));
+        }
+        let pcr_pid = (((body[0] & 0x1F) as u16) << 8) | body[1] as u16;
+        let program_info_length = (((body[2] & 0x0F) as usize) << 8) | body[3] as usize;
+        let mut cursor = body.get(4 + program_info_length..).ok_or_else(|| {
+            MpegTsError::InvalidSection(format!(
+                "program info length {} out of range",
+                program_info_length
+            ))
+        })?;
+
+        let mut streams = vec![];
+        while cursor.len() >= 5 {
+            let es_info_length = (((cursor[3] & 0x0F) as usize) << 8) | cursor[4] as usize;
+            streams.push(ElementaryStreamInfo {
+                stream_type: cursor[0],
+                elementary_pid: (((cursor[1] & 0x1F) as u16) << 8) | cursor[2] as u16,
+            });
+            cursor = cursor.get(5 + es_info_length..).ok_or_else(|| {
+                MpegTsError::InvalidSection(format!(
+                    "es info length {} out of range",
+                    es_info_length
+                ))
+            })?;
+        }
+
+        Ok(Self {
+            program_number: section.table_id_extension,
+            version_number: section.version_number,
+            pcr_pid,
+            streams,
+        })
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        demuxer::{PesPacket, TsDemuxer, stream_type},
+        packet::{TS_PACKET_SIZE, TsPacket},
+        psi::{Pat, Pmt, crc32_mpeg2},
+    };
+
+    /// PAT + PMT (pmt pid 0x1000, h264 on 0x100 carrying the pcr, adts aac on 0x101),
+    /// 11 h264 frames at 25fps starting from pts 126000, each followed by an audio pes with 2 adts frames
+    const H264_AAC_TS: &[u8] = include_bytes!("../test_data/h264_aac.ts");
+    const VIDEO_PID: u16 = 0x100;
+    const AUDIO_PID: u16 = 0x101;
+
+    fn demux_all(chunk_size: usize) -> Vec<PesPacket> {
+        let mut demuxer = TsDemuxer::new();
+        let mut packets = vec![];
+        for chunk in H264_AAC_TS.chunks(chunk_size) {
+            packets.extend(demuxer.push(chunk));
+        }
+        packets.extend(demuxer.flush());
+        packets
+    }
+
+    #[test]
+    fn test_crc32_mpeg2() {
+        assert_eq!(crc32_mpeg2(b"123456789"), 0x0376E6E7);
+    }
+
+    #[test]
+    fn test_parse_psi() {
+        let pat_packet = TsPacket::parse(&H264_AAC_TS[..TS_PACKET_SIZE]).unwrap();
+        assert!(pat_packet.header.payload_unit_start_indicator);
+        let pat = Pat::parse(pat_packet.payload).unwrap();
+        assert_eq!(pat.programs.len(), 1);
+        assert_eq!(pat.programs[0].program_number, 1);
+        assert_eq!(pat.programs[0].program_map_pid, 0x1000);
+
+        let pmt_packet = TsPacket::parse(&H264_AAC_TS[TS_PACKET_SIZE..TS_PACKET_SIZE * 2]).unwrap();
+        assert_eq!(pmt_packet.header.pid, 0x1000);
+        let pmt = Pmt::parse(pmt_packet.payload).unwrap();
+        assert_eq!(pmt.pcr_pid, VIDEO_PID);
+        assert_eq!(pmt.streams.len(), 2);
+        assert_eq!(pmt.streams[0].stream_type, stream_type::H264);
+        assert_eq!(pmt.streams[0].elementary_pid, VIDEO_PID);
+        assert_eq!(pmt.streams[1].stream_type, stream_type::AAC_ADTS);
+        assert_eq!(pmt.streams[1].elementary_pid, AUDIO_PID);
+    }
+
+    #[test]
+    fn test_psi_crc_mismatch() {
+        let mut bytes = H264_AAC_TS[..TS_PACKET_SIZE].to_vec();
+        bytes[12] ^= 0xFF;
+        let packet = TsPacket::parse(&bytes).unwrap();
+        assert!(Pat::parse(packet.payload).is_err());
+    }
+
+    #[test]
+    fn test_demux_h264_aac() {
+        let packets = demux_all(H264_AAC_TS.len());
+        let video: Vec<_> = packets.iter().filter(|p| p.pid == VIDEO_PID).collect();
+        let audio: Vec<_> = packets.iter().filter(|p| p.pid == AUDIO_PID).collect();
+        assert_eq!(video.len(), 11);
+        assert_eq!(audio.len(), 11);
+
+        for (i, pes) in video.iter().enumerate() {
+            assert_eq!(pes.stream_type, stream_type::H264);
+            assert_eq!(pes.pts, Some(126000 + i as u64 * 3600));
+            assert_eq!(pes.dts, pes.pts);
+            assert_eq!(pes.random_access, i == 0);
+            // every access unit starts with an access unit delimiter
+            assert_eq!(&pes.payload[..6], &[0, 0, 0, 1, 0x09, 0xF0]);
+            assert!(pes.pcr.is_some_and(|pcr| pcr < pes.dts.unwrap()));
+        }
+        for pes in &audio {
+            assert_eq!(pes.stream_type, stream_type::AAC_ADTS);
+            assert_eq!(&pes.payload[..2], &[0xFF, 0xF1]);
+            assert_eq!(pes.payload.len(), 2 * 57);
+        }
+    }
+
+    #[test]
+    fn test_demux_in_small_chunks() {
+        let whole = demux_all(H264_AAC_TS.len());
+        let chunked = demux_all(100);
+        assert_eq!(whole.len(), chunked.len());
+        for (a, b) in whole.iter().zip(chunked.iter()) {
+            assert_eq!(a.pid, b.pid);
+            assert_eq!(a.pts, b.pts);
+            assert_eq!(a.payload, b.payload);
+        }
+    }
+
+    #[test]
+    fn test_demux_resync_after_garbage() {
+        let mut bytes = vec![0x12, 0x34, 0x56];
+        bytes.extend_from_slice(H264_AAC_TS);
+        let mut demuxer = TsDemuxer::new();
+        let mut packets = demuxer.push(&bytes);
+        packets.extend(demuxer.flush());
+        assert_eq!(packets.iter().filter(|p| p.pid == VIDEO_PID).count(), 11);
+    }
+
+    #[test]
+    fn test_demux_drops_pes_on_packet_loss() {
+        // drop the second packet of the first video pes, which spans several ts packets
+        let video_packet_indexes: Vec<_> = H264_AAC_TS
+            .chunks(TS_PACKET_SIZE)
+            .enumerate()
+            .filter(|(_, bytes)| TsPacket::parse(bytes).unwrap().header.pid == VIDEO_PID)
+            .map(|(index, _)| index)
+            .collect();
+        let lost = video_packet_indexes[1];
+        let bytes: Vec<u8> = H264_AAC_TS
+            .chunks(TS_PACKET_SIZE)
+            .enumerate()
+            .filter(|(index, _)| *index != lost)
+            .flat_map(|(_, bytes)| bytes.to_vec())
+            .collect();
+
+        let mut demuxer = TsDemuxer::new();
+        let mut packets = demuxer.push(&bytes);
+        packets.extend(demuxer.flush());
+        let video: Vec<_> = packets.iter().filter(|p| p.pid == VIDEO_PID).collect();
+        assert_eq!(video.len(), 10);
+        assert_eq!(video[0].pts, Some(126000 + 3600));
+    }
+}
@@ -14,3 +14,43 @@ pub mod csid {
     pub const AUDIO: u8 = 0x07;
     pub const VIDEO: u8 = 0x06;
 }
+
+/// message stream ids are allocated by createStream, 0 is the net connection itself
+pub mod message_stream {
+    use super::csid::{AUDIO, NET_CONNECTION_COMMAND, NET_CONNECTION_COMMAND2, VIDEO};
+
+    /// what a message stream sends, each kind goes on a chunk stream of its own,
+    /// so that the header compression of one never refers to the headers of another
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MessageCategory {
+        Command = 0,
+        Data = 1,
+        Audio = 2,
+        Video = 3,
+    }
+
+    /// the chunk streams of message stream 1 start here, after the well-known ones
+    const FIRST_STREAM_CSID: u32 = 9;
+    const CSIDS_PER_STREAM: u32 = 4;
+    const MAX_CSID: u32 = 65599;
+
+    /// the largest message stream id whose chunk streams are still addressable
+    pub const MAX_MESSAGE_STREAM_ID: u32 =
+        (MAX_CSID - FIRST_STREAM_CSID - (CSIDS_PER_STREAM - 1)) / CSIDS_PER_STREAM + 1;
+
+    /// the same message stream and category always map to the same chunk stream,
+    /// message stream 0 keeps the well-known csids
+    pub fn chunk_stream_id(message_stream_id: u32, category: MessageCategory) -> u32 {
+        if message_stream_id == 0 {
+            return match category {
+                MessageCategory::Command => NET_CONNECTION_COMMAND.into(),
+                MessageCategory::Data => NET_CONNECTION_COMMAND2.into(),
+                MessageCategory::Audio => AUDIO.into(),
+                MessageCategory::Video => VIDEO.into(),
+            };
+        }
+        (message_stream_id - 1)
+            .saturating_mul(CSIDS_PER_STREAM)
+            .saturating_add(FIRST_STREAM_CSID + category as u32)
+    }
+}
@@ -35,6 +35,12 @@ pub enum ChunkMessageError {
     MetaDataError(#[from] amf_formats::errors::AmfError),
     #[error("get system time failed: {0}, this is wired")]
     SystemTimeError(#[from] SystemTimeError),
+    #[error("message on csid {csid} is too large: {size} bytes, limit: {limit}")]
+    MessageTooLarge {
+        csid: u32,
+        size: usize,
+        limit: usize,
+    },
     #[error("not error, just not a full chunk message")]
     IncompleteChunk,
 }
@@ -12,6 +12,8 @@ use crate::{
 pub mod consts;
 pub mod errors;
 pub mod reader;
+#[cfg(test)]
+mod test;
 pub mod writer;
 
 #[repr(u8)]
@@ -10,6 +10,8 @@ use utils::{
     traits::reader::{ReadFrom, ReadRemainingFrom},
 };
 
+use amf_formats::limits::ReadLimits;
+
 use crate::{
     chunk::errors::ChunkMessageError,
     message::{self, RtmpMessageType},
@@ -30,14 +32,20 @@ pub struct ChunkPayload {
     pub remaining_length: usize,
 }
 
-#[derive(Debug, Default)]
-pub struct ReadContext {
+/// what the compressed headers of a chunk stream refer to
+#[derive(Debug, Default, Clone, Copy)]
+struct MessageHeaderState {
     timestamp: u64,
     timestamp_delta: u64,
     extended_timestamp_enabled: bool,
     message_length: u32,
     message_stream_id: u32,
     message_type_id: u8,
+}
+
+#[derive(Debug, Default)]
+pub struct ReadContext {
+    header: MessageHeaderState,
     pub incomplete_chunk: Option<ChunkPayload>,
 }
 
@@ -48,6 +56,8 @@ pub struct Reader {
     context: ChunkStreamReadContext,
     chunk_size: usize,
     bytes_received: u32,
+    max_message_size: Option<usize>,
+    amf_limits: ReadLimits,
 }
 
 impl Reader {
@@ -56,9 +66,21 @@ impl Reader {
             context: HashMap::new(),
             chunk_size: 128,
             bytes_received: 0,
+            max_message_size: None,
+            amf_limits: ReadLimits::default(),
         }
     }
 
+    /// messages declaring a length above this limit are rejected before any payload is buffered
+    pub fn set_max_message_size(&mut self, size: Option<usize>) {
+        self.max_message_size = size;
+    }
+
+    /// bounds each amf value of the command messages
+    pub fn set_amf_limits(&mut self, limits: ReadLimits) {
+        self.amf_limits = limits;
+    }
+
     #[inline]
     pub fn get_bytes_read(&self) -> u32 {
         self.bytes_received
@@ -84,10 +106,15 @@ impl Reader {
         if common_header.is_none() {
             return Ok(None);
         }
-        let common_header = common_header.expect("this cannot be none");
+        let (common_header, previous_header) = common_header.expect("this cannot be none");
 
         let bytes = self.read_chunk_body(reader, common_header.basic_header.chunk_stream_id)?;
         if bytes.is_none() {
+            // the same header is read again with more data, it must not be applied twice
+            self.context
+                .get_mut(&common_header.basic_header.chunk_stream_id)
+                .expect("this cannot be none")
+                .header = previous_header;
             return Ok(None);
         } else {
             // reset incomplete chunk after a full read
@@ -139,9 +166,13 @@ impl Reader {
                     | RtmpMessageType::AMF3SharedObject => amf_formats::Version::Amf3,
                     _ => amf_formats::Version::Amf0,
                 };
+                let amf_options = amf_formats::ReadOptions {
+                    version: amf_version,
+                    limits: self.amf_limits,
+                };
                 RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     message::RtmpUserMessageBody::read_remaining_from(
-                        (amf_version, c2s, &common_header),
+                        (amf_options, c2s, &common_header),
                         &mut bytes.reader(),
                     )?,
                 ))
@@ -166,21 +197,23 @@ impl Reader {
 
         let ctx = ctx.expect("this cannot be none");
 
-        if ctx.incomplete_chunk.is_none() {
-            ctx.incomplete_chunk = Some(ChunkPayload {
-                payload: BytesMut::with_capacity(ctx.message_length as usize),
-                total_length: ctx.message_length as usize,
-                remaining_length: ctx.message_length as usize,
-            })
-        }
-
-        let chunk = ctx.incomplete_chunk.as_mut().expect("this cannot be none");
-
-        let bytes_need = min(self.chunk_size, chunk.remaining_length);
+        let message_length = ctx.header.message_length as usize;
+        let remaining_length = ctx
+            .incomplete_chunk
+            .as_ref()
+            .map_or(message_length, |chunk| chunk.remaining_length);
+        let bytes_need = min(self.chunk_size, remaining_length);
+        // nothing is kept until the whole chunk arrived, the header is read again then
         if reader.remaining() < bytes_need {
             return Ok(None);
         }
 
+        let chunk = ctx.incomplete_chunk.get_or_insert_with(|| ChunkPayload {
+            payload: BytesMut::with_capacity(message_length),
+            total_length: message_length,
+            remaining_length: message_length,
+        });
+
         let mut bytes = vec![0; bytes_need];
         reader.read_exact(&mut bytes)?;
 
@@ -194,10 +227,11 @@ impl Reader {
         Err(ChunkMessageError::IncompleteChunk)
     }
 
+    /// the header state before this chunk is returned along, to roll back a chunk not fully arrived
     fn read_to_common_header(
         &mut self,
         reader: &mut Cursor<&BytesMut>,
-    ) -> ChunkMessageResult<Option<ChunkMessageCommonHeader>> {
+    ) -> ChunkMessageResult<Option<(ChunkMessageCommonHeader, MessageHeaderState)>> {
         let basic_header = self.read_basic_header(reader)?;
         if basic_header.is_none() {
             return Ok(None);
@@ -224,59 +258,90 @@ impl Reader {
             return Ok(None);
         }
         let message_header = message_header.expect("this cannot be none");
+        self.check_message_length(csid, &message_header)?;
 
         let context = self
             .context
             .get_mut(&csid)
             .unwrap_or_else(|| panic!("the context map should have this key: {}", csid));
+        let previous_header = context.header;
+        let header = &mut context.header;
         match &message_header {
             ChunkMessageHeader::Type0(header0) => {
-                context.message_length = header0.message_length;
-                context.message_type_id = header0.message_type_id;
-                context.timestamp = header0.timestamp as u64;
-                context.extended_timestamp_enabled = header0.timestamp >= MAX_TIMESTAMP;
-                context.message_stream_id = header0.message_stream_id;
-                context.timestamp_delta = 0;
+                header.message_length = header0.message_length;
+                header.message_type_id = header0.message_type_id;
+                header.timestamp = header0.timestamp as u64;
+                header.extended_timestamp_enabled = header0.timestamp >= MAX_TIMESTAMP;
+                header.message_stream_id = header0.message_stream_id;
+                header.timestamp_delta = 0;
             }
             ChunkMessageHeader::Type1(header1) => {
-                context.message_length = header1.message_length;
-                context.message_type_id = header1.message_type_id;
-                context.timestamp_delta = header1.timestamp_delta as u64;
-                context.timestamp += header1.timestamp_delta as u64;
-                context.extended_timestamp_enabled = header1.timestamp_delta >= MAX_TIMESTAMP;
+                header.message_length = header1.message_length;
+                header.message_type_id = header1.message_type_id;
+                header.timestamp_delta = header1.timestamp_delta as u64;
+                header.timestamp += header1.timestamp_delta as u64;
+                header.extended_timestamp_enabled = header1.timestamp_delta >= MAX_TIMESTAMP;
             }
             ChunkMessageHeader::Type2(header2) => {
-                context.timestamp_delta = header2.timestamp_delta as u64;
-                context.timestamp += header2.timestamp_delta as u64;
-                context.extended_timestamp_enabled = header2.timestamp_delta >= MAX_TIMESTAMP;
+                header.timestamp_delta = header2.timestamp_delta as u64;
+                header.timestamp += header2.timestamp_delta as u64;
+                header.extended_timestamp_enabled = header2.timestamp_delta >= MAX_TIMESTAMP;
             }
             ChunkMessageHeader::Type3(_) => {
-                //TODO - better check this out
-                if context.extended_timestamp_enabled {
+                if header.extended_timestamp_enabled {
                     if reader.remaining() < 4 {
                         return Ok(None);
                     }
-                    let timestamp_delta = reader.read_u32::<BigEndian>()?;
-                    context.timestamp_delta = timestamp_delta as u64;
-                    context.timestamp += timestamp_delta as u64;
-                } else {
-                    context.timestamp += context.timestamp_delta;
+                    // repeats the extended field of the header this one stands for
+                    reader.read_u32::<BigEndian>()?;
+                }
+                // a continuation chunk carries on the message already started
+                if context.incomplete_chunk.is_none() {
+                    header.timestamp += header.timestamp_delta;
                 }
             }
         }
 
-        Ok(Some(ChunkMessageCommonHeader {
-            basic_header,
-            timestamp: context.timestamp as u32,
-            message_length: context.message_length,
-            message_type_id: context.message_type_id,
-            message_stream_id: context.message_stream_id,
-            extended_timestamp_enabled: context.extended_timestamp_enabled,
-            runtime_stat: RuntimeStat {
-                read_time_ns: get_timestamp_ns().unwrap_or(0),
-                ..Default::default()
+        let header = &context.header;
+        Ok(Some((
+            ChunkMessageCommonHeader {
+                basic_header,
+                timestamp: header.timestamp as u32,
+                message_length: header.message_length,
+                message_type_id: header.message_type_id,
+                message_stream_id: header.message_stream_id,
+                extended_timestamp_enabled: header.extended_timestamp_enabled,
+                runtime_stat: RuntimeStat {
+                    read_time_ns: get_timestamp_ns().unwrap_or(0),
+                    ..Default::default()
+                },
             },
-        }))
+            previous_header,
+        )))
+    }
+
+    fn check_message_length(
+        &mut self,
+        csid: Csid,
+        message_header: &ChunkMessageHeader,
+    ) -> ChunkMessageResult<()> {
+        let message_length = match message_header {
+            ChunkMessageHeader::Type0(header0) => header0.message_length,
+            ChunkMessageHeader::Type1(header1) => header1.message_length,
+            _ => return Ok(()),
+        };
+        if let Some(limit) = self.max_message_size
+            && message_length as usize > limit
+        {
+            // the context is not trustworthy anymore, drop it
+            self.context.remove(&csid);
+            return Err(ChunkMessageError::MessageTooLarge {
+                csid,
+                size: message_length as usize,
+                limit,
+            });
+        }
+        Ok(())
     }
 
     fn read_basic_header(
@@ -336,27 +401,27 @@ impl Reader {
                 if reader.remaining() < 11 {
                     Ok(None)
                 } else {
-                    Ok(Some(ChunkMessageHeader::Type0(
-                        self.read_message_header_type0(reader)?,
-                    )))
+                    Ok(self
+                        .read_message_header_type0(reader)?
+                        .map(ChunkMessageHeader::Type0))
                 }
             }
             1 => {
                 if reader.remaining() < 7 {
                     Ok(None)
                 } else {
-                    Ok(Some(ChunkMessageHeader::Type1(
-                        self.read_message_header_type1(reader)?,
-                    )))
+                    Ok(self
+                        .read_message_header_type1(reader)?
+                        .map(ChunkMessageHeader::Type1))
                 }
             }
             2 => {
                 if reader.remaining() < 3 {
                     Ok(None)
                 } else {
-                    Ok(Some(ChunkMessageHeader::Type2(
-                        self.read_message_header_type2(reader)?,
-                    )))
+                    Ok(self
+                        .read_message_header_type2(reader)?
+                        .map(ChunkMessageHeader::Type2))
                 }
             }
             3 => Ok(Some(ChunkMessageHeader::Type3(ChunkMessageHeaderType3 {}))),
@@ -367,7 +432,7 @@ impl Reader {
     fn read_message_header_type0(
         &mut self,
         reader: &mut Cursor<&BytesMut>,
-    ) -> ChunkMessageResult<ChunkMessageHeaderType0> {
+    ) -> ChunkMessageResult<Option<ChunkMessageHeaderType0>> {
         let mut header0 = ChunkMessageHeaderType0 {
             timestamp: reader.read_u24::<BigEndian>()?,
             message_length: reader.read_u24::<BigEndian>()?,
@@ -375,37 +440,46 @@ impl Reader {
             message_stream_id: reader.read_u32::<LittleEndian>()?,
         };
         if header0.timestamp >= MAX_TIMESTAMP {
+            if reader.remaining() < 4 {
+                return Ok(None);
+            }
             header0.timestamp = reader.read_u32::<BigEndian>()?;
         }
-        Ok(header0)
+        Ok(Some(header0))
     }
 
     fn read_message_header_type1(
         &mut self,
         reader: &mut Cursor<&BytesMut>,
-    ) -> ChunkMessageResult<ChunkMessageHeaderType1> {
+    ) -> ChunkMessageResult<Option<ChunkMessageHeaderType1>> {
         let mut header1 = ChunkMessageHeaderType1 {
             timestamp_delta: reader.read_u24::<BigEndian>()?,
             message_length: reader.read_u24::<BigEndian>()?,
             message_type_id: reader.read_u8()?,
         };
         if header1.timestamp_delta >= MAX_TIMESTAMP {
+            if reader.remaining() < 4 {
+                return Ok(None);
+            }
             header1.timestamp_delta = reader.read_u32::<BigEndian>()?;
         }
-        Ok(header1)
+        Ok(Some(header1))
     }
 
     fn read_message_header_type2(
         &mut self,
         reader: &mut Cursor<&BytesMut>,
-    ) -> ChunkMessageResult<ChunkMessageHeaderType2> {
+    ) -> ChunkMessageResult<Option<ChunkMessageHeaderType2>> {
         let mut header2 = ChunkMessageHeaderType2 {
             timestamp_delta: reader.read_u24::<BigEndian>()?,
         };
         if header2.timestamp_delta >= MAX_TIMESTAMP {
+            if reader.remaining() < 4 {
+                return Ok(None);
+            }
             header2.timestamp_delta = reader.read_u32::<BigEndian>()?;
         }
-        Ok(header2)
+        Ok(Some(header2))
     }
 }
 
@@ -414,3 +488,112 @@ impl Default for Reader {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amf_formats::{
+        errors::AmfError,
+        limits::{ReadLimit, ReadLimits},
+    };
+    use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+    use std::io::Cursor;
+    use tokio_util::bytes::BytesMut;
+    use utils::error_chain::{ErrorChainExt, find_source};
+
+    use super::Reader;
+    use crate::chunk::errors::ChunkMessageError;
+
+    fn type0_chunk_header(csid: u8, message_length: u32, message_type: u8) -> Vec<u8> {
+        let mut bytes = vec![csid & 0b0011_1111];
+        bytes.write_u24::<BigEndian>(0).unwrap();
+        bytes.write_u24::<BigEndian>(message_length).unwrap();
+        bytes.write_u8(message_type).unwrap();
+        bytes.write_u32::<LittleEndian>(1).unwrap();
+        bytes
+    }
+
+    fn type0_video_chunk_header(csid: u8, message_length: u32) -> BytesMut {
+        BytesMut::from(type0_chunk_header(csid, message_length, 9).as_slice())
+    }
+
+    #[test]
+    fn oversize_message_is_rejected() {
+        let mut reader = Reader::new();
+        reader.set_max_message_size(Some(1024));
+        let bytes = type0_video_chunk_header(6, 10 * 1024 * 1024);
+        let mut cursor = Cursor::new(&bytes);
+        match reader.read(&mut cursor, true) {
+            Err(ChunkMessageError::MessageTooLarge { csid, size, limit }) => {
+                assert_eq!(csid, 6);
+                assert_eq!(size, 10 * 1024 * 1024);
+                assert_eq!(limit, 1024);
+            }
+            other => panic!("expect MessageTooLarge, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn message_within_limit_is_buffered() {
+        let mut reader = Reader::new();
+        reader.set_max_message_size(Some(1024));
+        let bytes = type0_video_chunk_header(6, 1024);
+        let mut cursor = Cursor::new(&bytes);
+        // header is accepted, the payload is simply not there yet
+        assert!(matches!(reader.read(&mut cursor, true), Ok(None)));
+    }
+
+    #[test]
+    fn amf_errors_of_commands_are_kept_as_sources() {
+        // connect, then an unknown amf0 marker where the transaction id should be
+        let mut body = vec![0x02, 0x00, 0x07];
+        body.extend_from_slice(b"connect");
+        body.push(0x42);
+        let mut bytes = type0_chunk_header(3, body.len() as u32, 20);
+        bytes.extend_from_slice(&body);
+        let bytes = BytesMut::from(bytes.as_slice());
+
+        let err = Reader::new()
+            .read(&mut Cursor::new(&bytes), true)
+            .unwrap_err();
+        assert!(matches!(err, ChunkMessageError::MetaDataError(_)));
+        assert!(matches!(
+            find_source::<AmfError>(&err),
+            Some(AmfError::Unknown { marker: 0x42 })
+        ));
+        assert_eq!(
+            err.chain().to_string(),
+            "error while read or write meta data message: unknown marker: 66"
+        );
+    }
+
+    #[test]
+    fn amf_limits_apply_to_commands() {
+        // connect, transaction id 1, a command object nesting 3 objects
+        let mut body = vec![0x02, 0x00, 0x07];
+        body.extend_from_slice(b"connect");
+        body.push(0x00);
+        body.extend_from_slice(&1f64.to_be_bytes());
+        body.extend_from_slice(&[0x03, 0x00, 0x01, b'a', 0x03, 0x00, 0x01, b'b', 0x03]);
+        body.extend_from_slice(&[0x00, 0x00, 0x09].repeat(3));
+        let mut bytes = type0_chunk_header(3, body.len() as u32, 20);
+        bytes.extend_from_slice(&body);
+        let bytes = BytesMut::from(bytes.as_slice());
+
+        assert!(Reader::new().read(&mut Cursor::new(&bytes), true).is_ok());
+
+        let mut reader = Reader::new();
+        reader.set_amf_limits(ReadLimits {
+            max_depth: 2,
+            ..Default::default()
+        });
+        let err = reader.read(&mut Cursor::new(&bytes), true).unwrap_err();
+        assert!(matches!(
+            find_source::<AmfError>(&err),
+            Some(AmfError::LimitExceeded {
+                limit: ReadLimit::Depth,
+                value: 3,
+                max: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+use std::io::Cursor;
+
+use tokio_util::bytes::{Buf, Bytes, BytesMut};
+
+use super::{
+    ChunkMessage, RtmpChunkMessageBody, consts::MAX_TIMESTAMP, errors::ChunkMessageError,
+    reader::Reader, writer::Writer,
+};
+use crate::message::{RtmpMessageType, RtmpUserMessageBody};
+
+const CHUNK_SIZE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Sent {
+    message_stream_id: u32,
+    message_type_id: u8,
+    timestamp: u32,
+    payload: Bytes,
+}
+
+/// every byte tells which stream, message type and message it belongs to
+fn payload(
+    message_stream_id: u32,
+    message_type: RtmpMessageType,
+    index: usize,
+    len: usize,
+) -> Bytes {
+    let message_type: u8 = message_type.into();
+    (0..len)
+        .map(|i| match i % 3 {
+            0 => message_stream_id as u8,
+            1 => message_type,
+            _ => index as u8,
+        })
+        .collect::<Vec<u8>>()
+        .into()
+}
+
+async fn chunk_size_set_writer() -> Writer {
+    let mut writer = Writer::new();
+    writer.write_set_chunk_size(CHUNK_SIZE as u32).unwrap();
+    writer.write_to(&mut Vec::new()).await.unwrap();
+    writer
+}
+
+async fn write(writer: &mut Writer, message: &Sent) -> Vec<u8> {
+    match message.message_type_id.try_into().unwrap() {
+        RtmpMessageType::Audio => writer.write_audio(
+            message.payload.clone(),
+            message.timestamp,
+            message.message_stream_id,
+        ),
+        RtmpMessageType::Video => writer.write_video(
+            message.payload.clone(),
+            message.timestamp,
+            message.message_stream_id,
+        ),
+        _ => writer.write_meta(
+            message.payload.clone(),
+            message.timestamp,
+            message.message_stream_id,
+        ),
+    }
+    .unwrap();
+    let mut bytes = Vec::new();
+    writer.write_to(&mut bytes).await.unwrap();
+    bytes
+}
+
+/// the continuation chunks of a message without extended timestamps are 1 byte of header and the payload
+fn split_chunks(bytes: &[u8], payload_len: usize) -> Vec<&[u8]> {
+    let mut continuation_lens = Vec::new();
+    let mut remaining = payload_len;
+    while remaining > CHUNK_SIZE {
+        let len = (remaining - CHUNK_SIZE).min(CHUNK_SIZE);
+        remaining -= len;
+        continuation_lens.push(1 + len);
+    }
+    let first_chunk_len = bytes.len() - continuation_lens.iter().sum::<usize>();
+    let mut chunks = vec![&bytes[..first_chunk_len]];
+    let mut offset = first_chunk_len;
+    for len in continuation_lens {
+        chunks.push(&bytes[offset..offset + len]);
+        offset += len;
+    }
+    chunks
+}
+
+/// feeds the bytes a few at a time, like a socket would
+fn read_all(bytes: &[u8], step: usize) -> Vec<ChunkMessage> {
+    let mut reader = Reader::new();
+    reader.set_chunk_size(CHUNK_SIZE);
+    let mut buffer = BytesMut::new();
+    let mut fed = 0;
+    let mut messages = Vec::new();
+    loop {
+        let mut cursor = Cursor::new(&buffer);
+        let result = reader.read(&mut cursor, false);
+        let position = cursor.position() as usize;
+        match result {
+            Ok(Some(message)) => {
+                buffer.advance(position);
+                messages.push(message);
+                continue;
+            }
+            Err(ChunkMessageError::IncompleteChunk) => {
+                buffer.advance(position);
+                continue;
+            }
+            Ok(None) => {}
+            Err(err) => panic!("read failed: {}", err),
+        }
+        if fed == bytes.len() {
+            assert!(buffer.is_empty(), "{} bytes left unread", buffer.len());
+            return messages;
+        }
+        let end = (fed + step).min(bytes.len());
+        buffer.extend_from_slice(&bytes[fed..end]);
+        fed = end;
+    }
+}
+
+fn received(messages: Vec<ChunkMessage>) -> Vec<Sent> {
+    messages
+        .into_iter()
+        .map(|message| {
+            let payload = match message.chunk_message_body {
+                RtmpChunkMessageBody::RtmpUserMessage(body) => match *body {
+                    RtmpUserMessageBody::Audio { payload }
+                    | RtmpUserMessageBody::Video { payload }
+                    | RtmpUserMessageBody::MetaData { payload } => payload,
+                    body => panic!("unexpected message: {:?}", body),
+                },
+                body => panic!("unexpected message: {:?}", body),
+            };
+            Sent {
+                message_stream_id: message.header.message_stream_id,
+                message_type_id: message.header.message_type_id,
+                timestamp: message.header.timestamp,
+                payload,
+            }
+        })
+        .collect()
+}
+
+fn stream_messages(message_stream_id: u32, base_timestamp: u32) -> Vec<Sent> {
+    let mut messages = vec![Sent {
+        message_stream_id,
+        message_type_id: RtmpMessageType::AMF0Data.into(),
+        timestamp: base_timestamp,
+        payload: payload(message_stream_id, RtmpMessageType::AMF0Data, 0, 10),
+    }];
+    for index in 0..20 {
+        // stream 2 restarts its video timestamps half way
+        let video_timestamp = if message_stream_id == 2 && index >= 10 {
+            (index - 10) as u32 * 33
+        } else {
+            base_timestamp + index as u32 * (30 + message_stream_id * 5)
+        };
+        messages.push(Sent {
+            message_stream_id,
+            message_type_id: RtmpMessageType::Video.into(),
+            timestamp: video_timestamp,
+            payload: payload(
+                message_stream_id,
+                RtmpMessageType::Video,
+                index,
+                100 + (index % 3) * 70,
+            ),
+        });
+        messages.push(Sent {
+            message_stream_id,
+            message_type_id: RtmpMessageType::Audio.into(),
+            timestamp: base_timestamp + index as u32 * (20 + message_stream_id),
+            payload: payload(message_stream_id, RtmpMessageType::Audio, index, 20),
+        });
+    }
+    messages
+}
+
+#[tokio::test]
+async fn interleaved_message_streams_do_not_share_headers() {
+    let first = stream_messages(1, 0);
+    let second = stream_messages(2, 5000);
+
+    let mut writer = chunk_size_set_writer().await;
+    let mut compressed_headers = 0;
+    let mut bytes = Vec::new();
+    for (message1, message2) in first.iter().zip(second.iter()) {
+        let bytes1 = write(&mut writer, message1).await;
+        let bytes2 = write(&mut writer, message2).await;
+        compressed_headers += [&bytes1, &bytes2]
+            .iter()
+            .filter(|bytes| bytes[0] >> 6 != 0)
+            .count();
+
+        // the chunks of the two messages go out in turns
+        let chunks1 = split_chunks(&bytes1, message1.payload.len());
+        let chunks2 = split_chunks(&bytes2, message2.payload.len());
+        for index in 0..chunks1.len().max(chunks2.len()) {
+            for chunks in [&chunks1, &chunks2] {
+                if let Some(chunk) = chunks.get(index) {
+                    bytes.extend_from_slice(chunk);
+                }
+            }
+        }
+    }
+    assert!(compressed_headers > first.len());
+
+    for step in [1, 7, bytes.len()] {
+        let (received1, received2): (Vec<_>, Vec<_>) = received(read_all(&bytes, step))
+            .into_iter()
+            .partition(|message| message.message_stream_id == 1);
+        assert_eq!(received1, first);
+        assert_eq!(received2, second);
+    }
+}
+
+#[tokio::test]
+async fn extended_timestamps_survive_compression_and_continuation_chunks() {
+    let mut timestamps: Vec<u32> = (0..6)
+        .map(|index| MAX_TIMESTAMP - 80 + index * 40)
+        .collect();
+    // deltas too large for the 24 bits field, twice to get a type 3 header with the extended field
+    let last = *timestamps.last().unwrap();
+    timestamps.extend([last + 0x0100_0000, last + 0x0200_0000]);
+
+    let sent: Vec<Sent> = timestamps
+        .into_iter()
+        .enumerate()
+        .map(|(index, timestamp)| Sent {
+            message_stream_id: 1,
+            message_type_id: RtmpMessageType::Video.into(),
+            timestamp,
+            payload: payload(1, RtmpMessageType::Video, index, 150),
+        })
+        .collect();
+
+    let mut writer = chunk_size_set_writer().await;
+    let mut bytes = Vec::new();
+    for message in &sent {
+        bytes.extend(write(&mut writer, message).await);
+    }
+
+    for step in [1, 5, bytes.len()] {
+        assert_eq!(received(read_all(&bytes, step)), sent);
+    }
+}
@@ -39,7 +39,10 @@ use crate::{
 use super::{
     ChunkBasicHeader, ChunkBasicHeaderType, ChunkMessage, ChunkMessageCommonHeader,
     ChunkMessageHeader, Csid, RtmpChunkMessageBody,
-    consts::{MAX_TIMESTAMP, csid},
+    consts::{
+        MAX_TIMESTAMP, csid,
+        message_stream::{self, MessageCategory},
+    },
     errors::ChunkMessageResult,
 };
 
@@ -47,11 +50,14 @@ use super::{
 struct WriteContext {
     timestamp: u32,
     timestamp_delta: u32,
-    extended_timestamp_enabled: bool,
+    /// only a type 1 or 2 header sets the delta a type 3 header repeats,
+    /// peers disagree on what a type 3 header right after a type 0 one means
+    timestamp_delta_known: bool,
+    /// the extended timestamp field of the last header, repeated by the type 3 headers after it
+    extended_timestamp: Option<u32>,
     message_length: u32,
     message_stream_id: u32,
     message_type_id: u8,
-    previous_message_header: Option<ChunkMessageHeader>,
 }
 
 type ChunkMessageWriteContext = HashMap<Csid, WriteContext>;
@@ -121,7 +127,8 @@ impl Writer {
             }
             Some(len) => {
                 self.write_basic_header(&basic_header)?;
-                self.write_message_header(&message_header, basic_header.chunk_stream_id)?;
+                let extended_timestamp =
+                    self.write_message_header(&message_header, basic_header.chunk_stream_id)?;
 
                 let mut cursor_buf = Cursor::new(bytes);
                 let mut tmp_buf = Vec::new();
@@ -151,8 +158,9 @@ impl Writer {
                         header_type: basic_header.header_type.clone(),
                         chunk_stream_id: basic_header.chunk_stream_id,
                     })?;
-                    if value.header.timestamp >= MAX_TIMESTAMP {
-                        self.inner.write_u32::<BigEndian>(value.header.timestamp)?;
+                    // the continuation chunks repeat the extended field of the first one
+                    if let Some(extended_timestamp) = extended_timestamp {
+                        self.inner.write_u32::<BigEndian>(extended_timestamp)?;
                     }
                     self.inner.reserve(bytes_to_write);
                     self.inner.write_all(&tmp_buf)?;
@@ -388,7 +396,7 @@ impl Writer {
         let version = message.command_object.object_encoding;
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::Connect(message)),
                 )),
@@ -408,8 +416,10 @@ impl Writer {
         level: &str,
         description: &str,
         encoding: amf_formats::Version,
+        enhanced_properties: Option<HashMap<String, amf_formats::Value>>,
     ) -> ChunkMessageResult<()> {
-        let mut properties = HashMap::new();
+        // enhanced rtmp capabilities, like videoFourCcInfoMap and capsEx
+        let mut properties = enhanced_properties.unwrap_or_default();
         properties.insert("fmsVer".into(), amf_formats::string(fmsver, encoding));
         properties.insert(
             "capabilities".into(),
@@ -429,7 +439,7 @@ impl Writer {
         );
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::S2Command(RtmpS2CCommands::Connect(
                         ConnectCommandResponse {
@@ -448,7 +458,7 @@ impl Writer {
     pub fn write_call_request(&mut self, message: CallCommandRequest) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::Call(message)),
                 )),
@@ -457,6 +467,17 @@ impl Writer {
         )
     }
 
+    /// the end of the bandwidth check legacy encoders wait for after the connect response,
+    /// as nginx-rtmp sends it, the server calls onBWDone on the client
+    pub fn write_on_bw_done(&mut self) -> ChunkMessageResult<()> {
+        self.write_call_request(CallCommandRequest {
+            procedure_name: s2c_command_names::ON_BW_DONE.to_owned(),
+            transaction_id: 0.0,
+            command_object: None,
+            optional_arguments: None,
+        })
+    }
+
     pub fn write_call_response(
         &mut self,
         success: bool,
@@ -466,7 +487,7 @@ impl Writer {
     ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::S2Command(RtmpS2CCommands::Call(CallCommandResponse {
                         command_name: if success {
@@ -490,7 +511,7 @@ impl Writer {
     ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::CreateStream(message)),
                 )),
@@ -508,7 +529,7 @@ impl Writer {
     ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::S2Command(RtmpS2CCommands::CreateStream(
                         CreateStreamCommandResponse {
@@ -527,7 +548,7 @@ impl Writer {
     pub fn write_play_request(&mut self, message: PlayCommand) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::Play(message)),
                 )),
@@ -539,7 +560,7 @@ impl Writer {
     pub fn write_play2_request(&mut self, message: Play2Command) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::Play2(message)),
                 )),
@@ -554,7 +575,7 @@ impl Writer {
     ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::DeleteStream(message)),
                 )),
@@ -569,7 +590,7 @@ impl Writer {
     ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::ReceiveAudio(message)),
                 )),
@@ -584,7 +605,7 @@ impl Writer {
     ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::ReceiveVideo(message)),
                 )),
@@ -596,7 +617,7 @@ impl Writer {
     pub fn write_publish_request(&mut self, message: PublishCommand) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::Publish(message)),
                 )),
@@ -608,7 +629,7 @@ impl Writer {
     pub fn write_seek_request(&mut self, message: SeekCommand) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::Seek(message)),
                 )),
@@ -620,7 +641,7 @@ impl Writer {
     pub fn write_pause_request(&mut self, message: PauseCommand) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(0)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::C2SCommand(RtmpC2SCommands::Pause(message)),
                 )),
@@ -636,6 +657,7 @@ impl Writer {
         description: &str,
         encoding: amf_formats::Version,
         additional: Option<HashMap<String, amf_formats::Value>>,
+        message_stream_id: u32,
     ) -> ChunkMessageResult<()> {
         let mut info_object = HashMap::new();
         info_object.insert("level".into(), amf_formats::string(level, encoding));
@@ -649,7 +671,7 @@ impl Writer {
         }
         self.write(
             ChunkMessage {
-                header: Self::make_command_common_header()?,
+                header: Self::make_command_common_header(message_stream_id)?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::S2Command(RtmpS2CCommands::OnStatus(OnStatusCommand {
                         command_name: ON_STATUS.into(),
@@ -662,33 +684,54 @@ impl Writer {
         )
     }
 
-    fn make_command_common_header() -> ChunkMessageResult<ChunkMessageCommonHeader> {
-        let timestamp = get_timestamp_ms()? as u32;
+    /// commands of the net connection go on message stream 0,
+    /// the responses to a net stream, like onStatus, go on the message stream of it
+    fn make_command_common_header(
+        message_stream_id: u32,
+    ) -> ChunkMessageResult<ChunkMessageCommonHeader> {
+        Self::make_message_stream_common_header(
+            message_stream_id,
+            MessageCategory::Command,
+            RtmpMessageType::AMF0Command,
+            get_timestamp_ms()? as u32,
+        )
+    }
+
+    fn make_message_stream_common_header(
+        message_stream_id: u32,
+        category: MessageCategory,
+        message_type: RtmpMessageType,
+        timestamp: u32,
+    ) -> ChunkMessageResult<ChunkMessageCommonHeader> {
         Ok(ChunkMessageCommonHeader {
-            basic_header: ChunkBasicHeader::new(0, csid::NET_CONNECTION_COMMAND.into())?,
+            basic_header: ChunkBasicHeader::new(
+                0,
+                message_stream::chunk_stream_id(message_stream_id, category),
+            )?,
             timestamp,
             message_length: 0, //NOTE - length will be justified later
-            message_type_id: RtmpMessageType::AMF0Command.into(),
-            message_stream_id: 0, //TODO - check this out
+            message_type_id: message_type.into(),
+            message_stream_id,
             extended_timestamp_enabled: timestamp >= MAX_TIMESTAMP,
             // we do not need this to write
             runtime_stat: Default::default(),
         })
     }
 
-    pub fn write_meta(&mut self, meta: Bytes, timestamp: u32) -> ChunkMessageResult<()> {
+    pub fn write_meta(
+        &mut self,
+        meta: Bytes,
+        timestamp: u32,
+        message_stream_id: u32,
+    ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: ChunkMessageCommonHeader {
-                    basic_header: ChunkBasicHeader::new(0, csid::NET_CONNECTION_COMMAND2.into())?,
+                header: Self::make_message_stream_common_header(
+                    message_stream_id,
+                    MessageCategory::Data,
+                    RtmpMessageType::AMF0Data,
                     timestamp,
-                    message_length: 0,
-                    message_type_id: RtmpMessageType::AMF0Data.into(),
-                    message_stream_id: 0,
-                    extended_timestamp_enabled: timestamp >= MAX_TIMESTAMP,
-                    // we do not need this to write
-                    runtime_stat: Default::default(),
-                },
+                )?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::MetaData { payload: meta },
                 )),
@@ -697,19 +740,20 @@ impl Writer {
         )
     }
 
-    pub fn write_audio(&mut self, message: Bytes, timestamp: u32) -> ChunkMessageResult<()> {
+    pub fn write_audio(
+        &mut self,
+        message: Bytes,
+        timestamp: u32,
+        message_stream_id: u32,
+    ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: ChunkMessageCommonHeader {
-                    basic_header: ChunkBasicHeader::new(0, csid::AUDIO.into())?,
+                header: Self::make_message_stream_common_header(
+                    message_stream_id,
+                    MessageCategory::Audio,
+                    RtmpMessageType::Audio,
                     timestamp,
-                    message_length: 0,
-                    message_type_id: RtmpMessageType::Audio.into(),
-                    message_stream_id: 0,
-                    extended_timestamp_enabled: timestamp >= MAX_TIMESTAMP,
-                    // we do not need this to write
-                    runtime_stat: Default::default(),
-                },
+                )?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::Audio { payload: message },
                 )),
@@ -718,19 +762,20 @@ impl Writer {
         )
     }
 
-    pub fn write_video(&mut self, message: Bytes, timestamp: u32) -> ChunkMessageResult<()> {
+    pub fn write_video(
+        &mut self,
+        message: Bytes,
+        timestamp: u32,
+        message_stream_id: u32,
+    ) -> ChunkMessageResult<()> {
         self.write(
             ChunkMessage {
-                header: ChunkMessageCommonHeader {
-                    basic_header: ChunkBasicHeader::new(0, csid::VIDEO.into())?,
+                header: Self::make_message_stream_common_header(
+                    message_stream_id,
+                    MessageCategory::Video,
+                    RtmpMessageType::Video,
                     timestamp,
-                    message_length: 0,
-                    message_type_id: RtmpMessageType::Video.into(),
-                    message_stream_id: 0,
-                    extended_timestamp_enabled: timestamp >= MAX_TIMESTAMP,
-                    // we do not need this to write
-                    runtime_stat: Default::default(),
-                },
+                )?,
                 chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(Box::new(
                     RtmpUserMessageBody::Video { payload: message },
                 )),
@@ -739,104 +784,50 @@ impl Writer {
         )
     }
 
+    /// the header is compressed against the previous message of the same chunk stream only
     fn justify_message_type(
-        &mut self,
+        &self,
         value: &ChunkMessageCommonHeader,
     ) -> (ChunkBasicHeader, ChunkMessageHeader) {
-        let basic_header = value.basic_header.clone();
+        let mut basic_header = value.basic_header.clone();
+        let type0 = ChunkMessageHeader::Type0(super::ChunkMessageHeaderType0 {
+            timestamp: value.timestamp,
+            message_length: value.message_length,
+            message_type_id: value.message_type_id,
+            message_stream_id: value.message_stream_id,
+        });
 
         // no context at all, this must be the first message of this chunk stream
-        if !self.context.contains_key(&basic_header.chunk_stream_id) {
-            return (
-                basic_header,
-                ChunkMessageHeader::Type0(super::ChunkMessageHeaderType0 {
-                    timestamp: value.timestamp,
-                    message_length: value.message_length,
-                    message_type_id: value.message_type_id,
-                    message_stream_id: value.message_stream_id,
-                }),
-            );
-        }
-
-        let ctx = self
-            .context
-            .get_mut(&basic_header.chunk_stream_id)
-            .unwrap_or_else(|| {
-                panic!(
-                    "there should be {} in context map",
-                    basic_header.chunk_stream_id
-                )
-            });
-
-        // maybe something weird happened, but we don't know what type the last message header is
-        if ctx.previous_message_header.is_none() {
-            return (
-                basic_header,
-                ChunkMessageHeader::Type0(super::ChunkMessageHeaderType0 {
-                    timestamp: value.timestamp,
-                    message_length: value.message_length,
-                    message_type_id: value.message_type_id,
-                    message_stream_id: value.message_stream_id,
-                }),
-            );
-        }
-
-        let previous_message_header = ctx
-            .previous_message_header
-            .as_ref()
-            .expect("this must be valid");
-
-        if let ChunkMessageHeader::Type0(_) = previous_message_header {
-            // see: 5.3.1.2.4. Type 3
-            if value.timestamp == ctx.timestamp * 2 {
-                // make sure the timestamp_delta is correct when writing Type3 header
-                ctx.timestamp_delta = ctx.timestamp;
-                return (
-                    basic_header,
-                    ChunkMessageHeader::Type3(super::ChunkMessageHeaderType3 {}),
-                );
-            }
-        }
-
-        if ctx.message_length == value.message_length
-            && ctx.message_stream_id == value.message_stream_id
-            && ctx.message_type_id == value.message_type_id
-            && ctx.timestamp_delta == value.timestamp - ctx.timestamp
+        let Some(ctx) = self.context.get(&basic_header.chunk_stream_id) else {
+            return (basic_header, type0);
+        };
+
+        // the deltas can not go backwards, nor carry another message stream id
+        let timestamp_delta = match value.timestamp.checked_sub(ctx.timestamp) {
+            Some(delta) if ctx.message_stream_id == value.message_stream_id => delta,
+            _ => return (basic_header, type0),
+        };
+
+        let message_header = if ctx.message_length != value.message_length
+            || ctx.message_type_id != value.message_type_id
         {
-            (
-                basic_header,
-                ChunkMessageHeader::Type3(super::ChunkMessageHeaderType3 {}),
-            )
-        } else if ctx.message_length == value.message_length
-            && ctx.message_stream_id == value.message_stream_id
-            && ctx.message_type_id == value.message_type_id
-        {
-            (
-                basic_header,
-                ChunkMessageHeader::Type2(super::ChunkMessageHeaderType2 {
-                    timestamp_delta: value.timestamp - ctx.timestamp,
-                }),
-            )
-        } else if ctx.message_stream_id == value.message_stream_id {
-            (
-                basic_header,
-                ChunkMessageHeader::Type1(super::ChunkMessageHeaderType1 {
-                    timestamp_delta: value.timestamp - ctx.timestamp,
-                    message_length: value.message_length,
-                    message_type_id: value.message_type_id,
-                }),
-            )
+            ChunkMessageHeader::Type1(super::ChunkMessageHeaderType1 {
+                timestamp_delta,
+                message_length: value.message_length,
+                message_type_id: value.message_type_id,
+            })
+        } else if ctx.timestamp_delta_known && ctx.timestamp_delta == timestamp_delta {
+            ChunkMessageHeader::Type3(super::ChunkMessageHeaderType3 {})
         } else {
-            (
-                basic_header,
-                ChunkMessageHeader::Type0(super::ChunkMessageHeaderType0 {
-                    timestamp: value.timestamp,
-                    message_length: value.message_length,
-                    message_type_id: value.message_type_id,
-                    message_stream_id: value.message_stream_id,
-                }),
-            )
-        }
+            ChunkMessageHeader::Type2(super::ChunkMessageHeaderType2 { timestamp_delta })
+        };
+        basic_header.fmt = match message_header {
+            ChunkMessageHeader::Type0(_) => 0,
+            ChunkMessageHeader::Type1(_) => 1,
+            ChunkMessageHeader::Type2(_) => 2,
+            ChunkMessageHeader::Type3(_) => 3,
+        };
+        (basic_header, message_header)
     }
 
     fn write_basic_header(&mut self, header: &ChunkBasicHeader) -> ChunkMessageResult<()> {
@@ -861,113 +852,83 @@ impl Writer {
         Ok(())
     }
 
+    /// returns the extended timestamp field written, if any
     fn write_message_header(
         &mut self,
         header: &ChunkMessageHeader,
         csid: Csid,
-    ) -> ChunkMessageResult<()> {
+    ) -> ChunkMessageResult<Option<u32>> {
         self.inner.reserve(20);
-        match header {
+        if !matches!(header, ChunkMessageHeader::Type0(_)) && !self.context.contains_key(&csid) {
+            return Err(super::errors::ChunkMessageError::InvalidMessageHead(
+                format!(
+                    "invalid message header, got a header: {:?} while no context found for csid: {}",
+                    header, csid
+                ),
+            ));
+        }
+
+        let extended_timestamp = match header {
             ChunkMessageHeader::Type0(header) => {
-                let extended_timestamp_enabled = header.timestamp >= MAX_TIMESTAMP;
-                let timestamp_field = header.timestamp.min(MAX_TIMESTAMP);
-                self.inner.write_u24::<BigEndian>(timestamp_field)?;
+                let extended_timestamp =
+                    (header.timestamp >= MAX_TIMESTAMP).then_some(header.timestamp);
+                self.inner
+                    .write_u24::<BigEndian>(header.timestamp.min(MAX_TIMESTAMP))?;
                 self.inner.write_u24::<BigEndian>(header.message_length)?;
                 self.inner.write_u8(header.message_type_id)?;
                 self.inner
                     .write_u32::<LittleEndian>(header.message_stream_id)?;
-                if extended_timestamp_enabled {
-                    self.inner.write_u32::<BigEndian>(header.timestamp)?;
-                }
 
-                // insert default if not exist, very rusty way
-                self.context.entry(csid).or_default();
-
-                let ctx = self
-                    .context
-                    .get_mut(&csid)
-                    .unwrap_or_else(|| panic!("there should be {} in context map", csid));
-
-                ctx.extended_timestamp_enabled = extended_timestamp_enabled;
+                let ctx = self.context.entry(csid).or_default();
                 ctx.timestamp = header.timestamp;
                 ctx.message_length = header.message_length;
                 ctx.message_stream_id = header.message_stream_id;
                 ctx.message_type_id = header.message_type_id;
                 ctx.timestamp_delta = 0;
+                ctx.timestamp_delta_known = false;
+                ctx.extended_timestamp = extended_timestamp;
+                extended_timestamp
             }
             ChunkMessageHeader::Type1(header) => {
-                if !self.context.contains_key(&csid) {
-                    return Err(super::errors::ChunkMessageError::InvalidMessageHead(
-                        format!(
-                            "invalid message header, got a type 1 header: {:?} while no context found for csid: {}",
-                            header, csid
-                        ),
-                    ));
-                }
-
-                let extended_timestamp_enabled = header.timestamp_delta >= MAX_TIMESTAMP;
-                let timestamp_delta_field = header.timestamp_delta.min(MAX_TIMESTAMP);
-                self.inner.write_u24::<BigEndian>(timestamp_delta_field)?;
+                let extended_timestamp =
+                    (header.timestamp_delta >= MAX_TIMESTAMP).then_some(header.timestamp_delta);
+                self.inner
+                    .write_u24::<BigEndian>(header.timestamp_delta.min(MAX_TIMESTAMP))?;
                 self.inner.write_u24::<BigEndian>(header.message_length)?;
                 self.inner.write_u8(header.message_type_id)?;
-                if extended_timestamp_enabled {
-                    self.inner.write_u32::<BigEndian>(header.timestamp_delta)?;
-                }
 
-                let ctx = self
-                    .context
-                    .get_mut(&csid)
-                    .unwrap_or_else(|| panic!("there should be {} in context map", csid));
-
-                ctx.extended_timestamp_enabled = extended_timestamp_enabled;
+                let ctx = self.context.get_mut(&csid).expect("checked above");
+                ctx.timestamp = ctx.timestamp.wrapping_add(header.timestamp_delta);
                 ctx.timestamp_delta = header.timestamp_delta;
-                ctx.timestamp += header.timestamp_delta;
+                ctx.timestamp_delta_known = true;
                 ctx.message_length = header.message_length;
                 ctx.message_type_id = header.message_type_id;
+                ctx.extended_timestamp = extended_timestamp;
+                extended_timestamp
             }
             ChunkMessageHeader::Type2(header) => {
-                if !self.context.contains_key(&csid) {
-                    return Err(super::errors::ChunkMessageError::InvalidMessageHead(
-                        format!(
-                            "invalid message header, got a type 2 header: {:?} while no context found for csid: {}",
-                            header, csid
-                        ),
-                    ));
-                }
-
-                let extended_timestamp_enabled = header.timestamp_delta >= MAX_TIMESTAMP;
-                let timestamp_delta_field = header.timestamp_delta.min(MAX_TIMESTAMP);
-                self.inner.write_u24::<BigEndian>(timestamp_delta_field)?;
-                if extended_timestamp_enabled {
-                    self.inner.write_u32::<BigEndian>(header.timestamp_delta)?;
-                }
-
-                let ctx = self
-                    .context
-                    .get_mut(&csid)
-                    .unwrap_or_else(|| panic!("there should be {} in context map", csid));
+                let extended_timestamp =
+                    (header.timestamp_delta >= MAX_TIMESTAMP).then_some(header.timestamp_delta);
+                self.inner
+                    .write_u24::<BigEndian>(header.timestamp_delta.min(MAX_TIMESTAMP))?;
 
-                ctx.extended_timestamp_enabled = extended_timestamp_enabled;
+                let ctx = self.context.get_mut(&csid).expect("checked above");
+                ctx.timestamp = ctx.timestamp.wrapping_add(header.timestamp_delta);
                 ctx.timestamp_delta = header.timestamp_delta;
-                ctx.timestamp += header.timestamp_delta;
+                ctx.timestamp_delta_known = true;
+                ctx.extended_timestamp = extended_timestamp;
+                extended_timestamp
             }
-            ChunkMessageHeader::Type3(header) => {
-                let ctx = self.context.get(&csid);
-                if let Some(ctx) = ctx {
-                    if ctx.extended_timestamp_enabled {
-                        self.inner.write_u32::<BigEndian>(ctx.timestamp_delta)?;
-                    }
-                } else {
-                    return Err(super::errors::ChunkMessageError::InvalidMessageHead(
-                        format!(
-                            "invalid message header, got a type 3 header: {:?} while no context found for csid: {}",
-                            header, csid
-                        ),
-                    ));
-                }
+            ChunkMessageHeader::Type3(_) => {
+                let ctx = self.context.get_mut(&csid).expect("checked above");
+                ctx.timestamp = ctx.timestamp.wrapping_add(ctx.timestamp_delta);
+                ctx.extended_timestamp
             }
+        };
+        if let Some(extended_timestamp) = extended_timestamp {
+            self.inner.write_u32::<BigEndian>(extended_timestamp)?;
         }
-        Ok(())
+        Ok(extended_timestamp)
     }
 }
 
@@ -12,12 +12,35 @@ pub mod c2s_command_names {
     pub const PUBLISH: &str = "publish";
     pub const SEEK: &str = "seek";
     pub const PAUSE: &str = "pause";
+    /// legacy clients check the bandwidth with it once they hear onBWDone
+    pub const CHECK_BW: &str = "_checkbw";
+}
+
+/// the properties of the command object of connect, looked up in every connect
+pub mod connect_object_keys {
+    pub const APP: &str = "app";
+    pub const FLASH_VER: &str = "flashver";
+    pub const SWF_URL: &str = "swfUrl";
+    pub const TC_URL: &str = "tcUrl";
+    pub const FPAD: &str = "fpad";
+    pub const AUDIO_CODECS: &str = "audioCodecs";
+    pub const VIDEO_CODECS: &str = "videoCodecs";
+    pub const VIDEO_FUNCTION: &str = "videoFunction";
+    pub const PAGE_URL: &str = "pageUrl";
+    pub const OBJECT_ENCODING: &str = "objectEncoding";
+    // the below are from enhanced rtmp
+    pub const FOUR_CC_LIST: &str = "fourCcList";
+    pub const VIDEO_FOUR_CC_INFO_MAP: &str = "videoFourCcInfoMap";
+    pub const AUDIO_FOUR_CC_INFO_MAP: &str = "audioFourCcInfoMap";
+    pub const CAPS_EX: &str = "capsEx";
 }
 
 pub mod s2c_command_names {
     pub const RESULT: &str = "_result";
     pub const ERROR: &str = "_error";
     pub const ON_STATUS: &str = "onStatus";
+    /// the bandwidth check of flash media server is done, legacy encoders wait for it to publish
+    pub const ON_BW_DONE: &str = "onBWDone";
 }
 
 pub const AMF0_ENCODING: u8 = 0;
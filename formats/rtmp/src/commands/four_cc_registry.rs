@@ -0,0 +1,202 @@
+use std::collections::{BTreeMap, HashMap};
+
+use amf_formats::{amf0, amf3};
+
+use super::{CapsExInfo, FourCCInfo};
+
+/// the FourCC key matching any codec, it overrides the flags of specific codecs
+pub const FOUR_CC_WILDCARD: &str = "*";
+
+pub mod connect_property_names {
+    pub const VIDEO_FOUR_CC_INFO_MAP: &str = "videoFourCcInfoMap";
+    pub const AUDIO_FOUR_CC_INFO_MAP: &str = "audioFourCcInfoMap";
+    pub const CAPS_EX: &str = "capsEx";
+}
+
+/// the codecs one end of the connection can handle, keyed by FourCC strings like "avc1" or "Opus"
+#[derive(Debug, Default, Clone)]
+pub struct FourCCRegistry {
+    video: BTreeMap<String, FourCCInfo>,
+    audio: BTreeMap<String, FourCCInfo>,
+    caps_ex: CapsExInfo,
+}
+
+impl FourCCRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_video<S: Into<String>>(mut self, four_cc: S, info: FourCCInfo) -> Self {
+        self.video.insert(four_cc.into(), info);
+        self
+    }
+
+    pub fn with_audio<S: Into<String>>(mut self, four_cc: S, info: FourCCInfo) -> Self {
+        self.audio.insert(four_cc.into(), info);
+        self
+    }
+
+    pub fn with_caps_ex(mut self, caps_ex: CapsExInfo) -> Self {
+        self.caps_ex = caps_ex;
+        self
+    }
+
+    pub fn caps_ex(&self) -> CapsExInfo {
+        self.caps_ex
+    }
+
+    pub fn video_info(&self, four_cc: &str) -> Option<FourCCInfo> {
+        Self::lookup(&self.video, four_cc)
+    }
+
+    pub fn audio_info(&self, four_cc: &str) -> Option<FourCCInfo> {
+        Self::lookup(&self.audio, four_cc)
+    }
+
+    pub fn can_forward_video(&self, four_cc: &str) -> bool {
+        self.video_info(four_cc)
+            .is_some_and(|info| info.can_forward)
+    }
+
+    pub fn can_forward_audio(&self, four_cc: &str) -> bool {
+        self.audio_info(four_cc)
+            .is_some_and(|info| info.can_forward)
+    }
+
+    fn lookup(infos: &BTreeMap<String, FourCCInfo>, four_cc: &str) -> Option<FourCCInfo> {
+        infos
+            .get(FOUR_CC_WILDCARD)
+            .or_else(|| infos.get(four_cc))
+            .copied()
+    }
+
+    /// videoFourCcInfoMap, audioFourCcInfoMap and capsEx,
+    /// to be merged into the properties of the connect response
+    pub fn to_connect_properties(
+        &self,
+        version: amf_formats::Version,
+    ) -> HashMap<String, amf_formats::Value> {
+        let mut properties = HashMap::new();
+        properties.insert(
+            connect_property_names::VIDEO_FOUR_CC_INFO_MAP.to_owned(),
+            Self::info_map(&self.video, version),
+        );
+        properties.insert(
+            connect_property_names::AUDIO_FOUR_CC_INFO_MAP.to_owned(),
+            Self::info_map(&self.audio, version),
+        );
+        properties.insert(
+            connect_property_names::CAPS_EX.to_owned(),
+            amf_formats::number(u8::from(self.caps_ex), version),
+        );
+        properties
+    }
+
+    fn info_map(
+        infos: &BTreeMap<String, FourCCInfo>,
+        version: amf_formats::Version,
+    ) -> amf_formats::Value {
+        let flags = infos
+            .iter()
+            .map(|(four_cc, info)| (four_cc.clone(), u8::from(*info)));
+        match version {
+            amf_formats::Version::Amf0 => {
+                amf0::object(flags.map(|(four_cc, flag)| (four_cc, amf0::number(flag)))).into()
+            }
+            amf_formats::Version::Amf3 => {
+                amf3::object(flags.map(|(four_cc, flag)| (four_cc, amf3::number(flag)))).into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Cursor};
+
+    use amf_formats::{AmfComplexObject, amf0};
+    use tokio_util::either::Either;
+    use utils::traits::writer::WriteTo;
+
+    use super::{FourCCRegistry, connect_property_names};
+    use crate::commands::{
+        CapsExInfo, ConnectCommandResponse, FourCCInfo, writer::RtmpCommandWriteWrapper,
+    };
+
+    const FORWARD: FourCCInfo = FourCCInfo {
+        can_decode: false,
+        can_encode: false,
+        can_forward: true,
+    };
+
+    fn registry() -> FourCCRegistry {
+        FourCCRegistry::new()
+            .with_video("avc1", FORWARD)
+            .with_video("hvc1", FORWARD)
+            .with_audio("mp4a", FORWARD)
+            .with_audio(
+                "Opus",
+                FourCCInfo {
+                    can_decode: true,
+                    ..FORWARD
+                },
+            )
+            .with_caps_ex(CapsExInfo {
+                support_mod_ex: true,
+                support_timestamp_nano: true,
+                ..Default::default()
+            })
+    }
+
+    fn flags(properties: &HashMap<String, amf_formats::Value>, key: &str) -> HashMap<String, u8> {
+        properties
+            .extract_object_field(key)
+            .unwrap()
+            .map(|(four_cc, flag)| (four_cc, flag.try_as_f64().unwrap() as u8))
+            .collect()
+    }
+
+    #[test]
+    fn lookup_honors_wildcard() {
+        let registry = registry();
+        assert!(registry.can_forward_video("avc1"));
+        assert!(!registry.can_forward_video("av01"));
+        assert!(registry.audio_info("Opus").unwrap().can_decode);
+
+        let registry = registry.with_video(super::FOUR_CC_WILDCARD, FourCCInfo::default());
+        assert!(!registry.can_forward_video("avc1"));
+        assert!(registry.can_forward_audio("mp4a"));
+    }
+
+    #[test]
+    fn connect_response_advertises_registry() {
+        let response = ConnectCommandResponse {
+            success: true,
+            transaction_id: 1,
+            properties: Some(registry().to_connect_properties(amf_formats::Version::Amf0)),
+            information: Some(Either::Right(HashMap::new())),
+        };
+        let mut bytes = Vec::new();
+        RtmpCommandWriteWrapper::new(&response, amf_formats::Version::Amf0)
+            .write_to(&mut bytes)
+            .unwrap();
+
+        let values = amf0::Value::read_all(Cursor::new(bytes)).unwrap();
+        assert_eq!(values[0].try_as_str(), Some("_result"));
+        let properties: HashMap<String, amf_formats::Value> =
+            values[2].clone().try_into_pairs().unwrap().collect();
+
+        assert_eq!(
+            flags(&properties, connect_property_names::VIDEO_FOUR_CC_INFO_MAP),
+            HashMap::from([("avc1".to_owned(), 0x04), ("hvc1".to_owned(), 0x04)])
+        );
+        assert_eq!(
+            flags(&properties, connect_property_names::AUDIO_FOUR_CC_INFO_MAP),
+            HashMap::from([("mp4a".to_owned(), 0x04), ("Opus".to_owned(), 0x05)])
+        );
+        assert_eq!(
+            properties.extract_number_field(connect_property_names::CAPS_EX),
+            Some(12.0)
+        );
+    }
+}
@@ -1,15 +1,20 @@
 use std::collections::HashMap;
 
-use amf_formats::AmfComplexObject;
+use amf_formats::{AmfComplexObject, amf0};
 use tokio_util::either::Either;
 
 use crate::chunk::errors::ChunkMessageError;
+use consts::connect_object_keys as keys;
 
 pub mod consts;
 pub mod errors;
+pub mod four_cc_registry;
 pub mod reader;
 pub mod writer;
 
+#[cfg(test)]
+mod test;
+
 /// The [audio|video]FourCcInfoMap properties are designed to enable setting capability flags
 /// for each supported codec in the context of E-RTMP streaming.
 /// A FourCC key is a four-character code used to specify a video or audio codec.
@@ -143,9 +148,8 @@ pub struct ConnectCommandRequestObject {
     pub audio_four_cc_info: Option<HashMap<String, FourCCInfo>>,
 }
 
-impl TryFrom<HashMap<String, amf_formats::Value>> for ConnectCommandRequestObject {
-    type Error = ChunkMessageError;
-    fn try_from(value: HashMap<String, amf_formats::Value>) -> Result<Self, Self::Error> {
+impl ConnectCommandRequestObject {
+    fn from_command_object<O: AmfComplexObject>(value: &O) -> Result<Self, ChunkMessageError> {
         let extract_string_array_field = |key: &str| match value.extract_array_field(key) {
             Some(values) => {
                 let mut result = vec![];
@@ -175,32 +179,32 @@ impl TryFrom<HashMap<String, amf_formats::Value>> for ConnectCommandRequestObjec
 
         let command_object = ConnectCommandRequestObject {
             app: value
-                .extract_string_field("app")
+                .extract_string_field(keys::APP)
                 .unwrap_or("default".into()),
             flash_version: value
-                .extract_string_field("flashver")
+                .extract_string_field(keys::FLASH_VER)
                 .unwrap_or("default".into()),
             swf_url: value
-                .extract_string_field("swfUrl")
+                .extract_string_field(keys::SWF_URL)
                 .unwrap_or("default".into()),
             tc_url: value
-                .extract_string_field("tcUrl")
+                .extract_string_field(keys::TC_URL)
                 .unwrap_or("default".into()),
-            fpad: value.extract_bool_field("fpad").unwrap_or(false),
+            fpad: value.extract_bool_field(keys::FPAD).unwrap_or(false),
             audio_codecs: value
-                .extract_number_field("audioCodecs")
+                .extract_number_field(keys::AUDIO_CODECS)
                 .unwrap_or(0.into()) as u16,
             video_codecs: value
-                .extract_number_field("videoCodecs")
+                .extract_number_field(keys::VIDEO_CODECS)
                 .unwrap_or(0.into()) as u16,
             video_function: value
-                .extract_number_field("videoFunction")
+                .extract_number_field(keys::VIDEO_FUNCTION)
                 .unwrap_or(0.into()) as u16,
             page_url: value
-                .extract_string_field("pageUrl")
+                .extract_string_field(keys::PAGE_URL)
                 .unwrap_or("default".into()),
             object_encoding: match value
-                .extract_number_field("objectEncoding")
+                .extract_number_field(keys::OBJECT_ENCODING)
                 .unwrap_or((amf_formats::Version::Amf0 as u8).into())
                 as u8
             {
@@ -208,11 +212,11 @@ impl TryFrom<HashMap<String, amf_formats::Value>> for ConnectCommandRequestObjec
                 3 => amf_formats::Version::Amf3,
                 v => return Err(ChunkMessageError::UnknownAmfVersion(v)),
             },
-            four_cc_list: extract_string_array_field("fourCcList"),
-            video_four_cc_info: extract_four_cc_info("videoFourCcInfoMap"),
-            audio_four_cc_info: extract_four_cc_info("audioFourCcInfoMap"),
+            four_cc_list: extract_string_array_field(keys::FOUR_CC_LIST),
+            video_four_cc_info: extract_four_cc_info(keys::VIDEO_FOUR_CC_INFO_MAP),
+            audio_four_cc_info: extract_four_cc_info(keys::AUDIO_FOUR_CC_INFO_MAP),
             caps_ex_info: value
-                .extract_number_field("capsEx")
+                .extract_number_field(keys::CAPS_EX)
                 .map(|v| (v as u8).into()),
         };
 
@@ -220,36 +224,57 @@ impl TryFrom<HashMap<String, amf_formats::Value>> for ConnectCommandRequestObjec
     }
 }
 
+impl TryFrom<HashMap<String, amf_formats::Value>> for ConnectCommandRequestObject {
+    type Error = ChunkMessageError;
+    fn try_from(value: HashMap<String, amf_formats::Value>) -> Result<Self, Self::Error> {
+        Self::from_command_object(&value)
+    }
+}
+
+/// the fields are looked up in the object as read, none of the other properties is copied
+impl TryFrom<&amf0::ValueRef<'_>> for ConnectCommandRequestObject {
+    type Error = ChunkMessageError;
+    fn try_from(value: &amf0::ValueRef<'_>) -> Result<Self, Self::Error> {
+        Self::from_command_object(value)
+    }
+}
+
 impl From<ConnectCommandRequestObject> for HashMap<String, amf_formats::Value> {
     fn from(value: ConnectCommandRequestObject) -> Self {
         let mut map: HashMap<String, amf_formats::Value> = HashMap::new();
         let version = value.object_encoding;
-        map.insert("app".into(), amf_formats::string(value.app, version));
+        map.insert(keys::APP.into(), amf_formats::string(value.app, version));
         map.insert(
-            "flashver".into(),
+            keys::FLASH_VER.into(),
             amf_formats::string(value.flash_version, version),
         );
-        map.insert("swfUrl".into(), amf_formats::string(value.swf_url, version));
-        map.insert("tcUrl".into(), amf_formats::string(value.tc_url, version));
-        map.insert("fpad".into(), amf_formats::bool(value.fpad, version));
         map.insert(
-            "audioCodecs".into(),
+            keys::SWF_URL.into(),
+            amf_formats::string(value.swf_url, version),
+        );
+        map.insert(
+            keys::TC_URL.into(),
+            amf_formats::string(value.tc_url, version),
+        );
+        map.insert(keys::FPAD.into(), amf_formats::bool(value.fpad, version));
+        map.insert(
+            keys::AUDIO_CODECS.into(),
             amf_formats::number(value.audio_codecs, version),
         );
         map.insert(
-            "videoCodecs".into(),
+            keys::VIDEO_CODECS.into(),
             amf_formats::number(value.video_codecs, version),
         );
         map.insert(
-            "videoFunction".into(),
+            keys::VIDEO_FUNCTION.into(),
             amf_formats::number(value.video_function, version),
         );
         map.insert(
-            "pageUrl".into(),
+            keys::PAGE_URL.into(),
             amf_formats::string(value.page_url, version),
         );
         map.insert(
-            "objectEncoding".into(),
+            keys::OBJECT_ENCODING.into(),
             amf_formats::number::<u8>(
                 match value.object_encoding {
                     amf_formats::Version::Amf0 => 0,
@@ -258,6 +283,12 @@ impl From<ConnectCommandRequestObject> for HashMap<String, amf_formats::Value> {
                 version,
             ),
         );
+        if let Some(caps_ex_info) = value.caps_ex_info {
+            map.insert(
+                keys::CAPS_EX.into(),
+                amf_formats::number(u8::from(caps_ex_info), version),
+            );
+        }
         map
     }
 }
@@ -287,6 +318,14 @@ pub struct CallCommandRequest {
     pub optional_arguments: Option<Either<amf_formats::Value, HashMap<String, amf_formats::Value>>>,
 }
 
+/// a command the server does not act on, e.g., the replies of legacy clients to onBWDone.
+/// it is read leniently, the arguments after the transaction id are left out
+#[derive(Debug)]
+pub struct UnknownCommand {
+    pub command_name: String,
+    pub transaction_id: Option<f64>,
+}
+
 #[derive(Debug)]
 pub struct CallCommandResponse {
     pub command_name: String,
@@ -330,6 +369,20 @@ pub struct PlayCommand {
     pub reset: bool,
 }
 
+impl PlayCommand {
+    /// plays the live stream from now on
+    pub fn new(stream_name: String) -> Self {
+        Self {
+            _command_name: consts::c2s_command_names::PLAY.to_string(),
+            _transaction_id: 0,
+            stream_name,
+            start: -2,
+            duration: -1,
+            reset: true,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Play2Command {
     _command_name: String, // "play2"
@@ -343,7 +396,17 @@ pub struct DeleteStreamCommand {
     _command_name: String, // "deleteStream"
     _transaction_id: u8,   // 0
     // command_object is null
-    stream_id: f64,
+    pub stream_id: f64,
+}
+
+impl DeleteStreamCommand {
+    pub fn new(stream_id: f64) -> Self {
+        Self {
+            _command_name: consts::c2s_command_names::DELETE_STREAM.to_string(),
+            _transaction_id: 0,
+            stream_id,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -371,6 +434,17 @@ pub struct PublishCommand {
     pub publishing_type: String, // "live", "record", "append"
 }
 
+impl PublishCommand {
+    pub fn new(publishing_name: String, publishing_type: String) -> Self {
+        Self {
+            _command_name: consts::c2s_command_names::PUBLISH.to_string(),
+            _transaction_id: 0,
+            publishing_name,
+            publishing_type,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SeekCommand {
     _command_name: String, // "seek"
@@ -401,6 +475,7 @@ pub enum RtmpC2SCommands {
     Publish(PublishCommand),
     Seek(SeekCommand),
     Pause(PauseCommand),
+    Unknown(UnknownCommand),
 }
 
 #[derive(Debug)]
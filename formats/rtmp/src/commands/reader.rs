@@ -5,21 +5,23 @@ use super::{
     ConnectCommandResponse, CreateStreamCommandRequest, CreateStreamCommandResponse,
     DeleteStreamCommand, OnStatusCommand, PauseCommand, Play2Command, PlayCommand, PublishCommand,
     ReceiveAudioCommand, ReceiveVideoCommand, RtmpC2SCommands, RtmpS2CCommands,
-    RtmpS2CCommandsType, SeekCommand, consts::c2s_command_names,
+    RtmpS2CCommandsType, SeekCommand, UnknownCommand, consts::c2s_command_names,
 };
 
+use amf_formats::{amf0, errors::AmfError};
 use num::ToPrimitive;
 use std::{
     backtrace::Backtrace,
+    collections::HashMap,
     io::{self},
 };
 use tokio_util::either::Either;
 use utils::traits::reader::ReadRemainingFrom;
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for RtmpC2SCommands {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for RtmpC2SCommands {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let command_name =
@@ -30,11 +32,47 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for RtmpC2SCommands
                 }
             })?;
 
-        match command_name.as_str() {
+        Self::read_remaining_of(&command_name, header, reader)
+    }
+}
+
+impl RtmpC2SCommands {
+    /// reads a command out of the payload of its message, the keys and strings of amf0 commands
+    /// are borrowed from the payload until the fields are copied into the command
+    pub fn read_from_payload(
+        header: amf_formats::ReadOptions,
+        payload: &[u8],
+    ) -> Result<Self, ChunkMessageError> {
+        if header.version != amf_formats::Version::Amf0 {
+            return Self::read_remaining_from(header, &mut &payload[..]);
+        }
+        let mut reader = amf0::RefReader::with_limits(payload, header.limits);
+        let command_name = reader.read()?;
+        let command_name =
+            command_name
+                .try_as_str()
+                .ok_or_else(|| ChunkMessageError::UnexpectedAmfType {
+                    amf_type: "expect string type".to_owned(),
+                    backtrace: Backtrace::capture(),
+                })?;
+        match command_name {
+            c2s_command_names::CONNECT => Ok(RtmpC2SCommands::Connect(
+                ConnectCommandRequest::read_from_ref_reader(&mut reader)?,
+            )),
+            command_name => Self::read_remaining_of(command_name, header, &mut reader.remaining()),
+        }
+    }
+
+    /// the command after its name
+    fn read_remaining_of<R: io::Read>(
+        command_name: &str,
+        header: amf_formats::ReadOptions,
+        reader: &mut R,
+    ) -> Result<Self, ChunkMessageError> {
+        match command_name {
             c2s_command_names::CONNECT => Ok(RtmpC2SCommands::Connect(
                 ConnectCommandRequest::read_remaining_from(header, reader)?,
             )),
-            c2s_command_names::CLOSE => todo!(), // FIXME no spec on this one
             c2s_command_names::CREATE_STREAM => Ok(RtmpC2SCommands::CreateStream(
                 CreateStreamCommandRequest::read_remaining_from(header, reader)?,
             )),
@@ -47,7 +85,6 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for RtmpC2SCommands
             c2s_command_names::DELETE_STREAM => Ok(RtmpC2SCommands::DeleteStream(
                 DeleteStreamCommand::read_remaining_from(header, reader)?,
             )),
-            c2s_command_names::CLOSE_STREAM => todo!(), // FIXME no spec on this one
             c2s_command_names::RECEIVE_AUDIO => Ok(RtmpC2SCommands::ReceiveAudio(
                 ReceiveAudioCommand::read_remaining_from(header, reader)?,
             )),
@@ -63,20 +100,58 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for RtmpC2SCommands
             c2s_command_names::PAUSE => Ok(RtmpC2SCommands::Pause(
                 PauseCommand::read_remaining_from(header, reader)?,
             )),
-            procedure_name => Ok(RtmpC2SCommands::Call(
-                CallCommandRequest::read_remaining_from(
+            // FIXME no spec on close and closeStream
+            c2s_command_names::CLOSE
+            | c2s_command_names::CLOSE_STREAM
+            | c2s_command_names::CHECK_BW
+            | s2c_command_names::RESULT
+            | s2c_command_names::ERROR => Ok(RtmpC2SCommands::Unknown(
+                UnknownCommand::read_remaining_from((header, command_name.to_owned()), reader)?,
+            )),
+            procedure_name => {
+                match CallCommandRequest::read_remaining_from(
                     (header, procedure_name.to_owned()),
                     reader,
-                )?,
-            )), // call
+                ) {
+                    Ok(request) => Ok(RtmpC2SCommands::Call(request)),
+                    // the limits bound whatever the client sends
+                    Err(err @ ChunkMessageError::MetaDataError(AmfError::LimitExceeded { .. })) => {
+                        Err(err)
+                    }
+                    Err(err) => {
+                        // the clients call whatever they like, a call not read is not an error
+                        tracing::warn!("call {} can not be read: {}", procedure_name, err);
+                        Ok(RtmpC2SCommands::Unknown(UnknownCommand {
+                            command_name: procedure_name.to_owned(),
+                            transaction_id: None,
+                        }))
+                    }
+                }
+            }
         }
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for ConnectCommandRequest {
+impl<R: io::Read> ReadRemainingFrom<(amf_formats::ReadOptions, String), R> for UnknownCommand {
+    type Error = ChunkMessageError;
+    fn read_remaining_from(
+        header: (amf_formats::ReadOptions, String),
+        reader: &mut R,
+    ) -> Result<Self, Self::Error> {
+        let transaction_id = amf_formats::Value::read_number(reader, header.0)
+            .ok()
+            .flatten();
+        Ok(UnknownCommand {
+            command_name: header.1,
+            transaction_id,
+        })
+    }
+}
+
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for ConnectCommandRequest {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -113,10 +188,58 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for ConnectCommandR
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for CreateStreamCommandRequest {
+impl ConnectCommandRequest {
+    /// like `read_remaining_from`, the command object is read borrowed
+    fn read_from_ref_reader(reader: &mut amf0::RefReader) -> Result<Self, ChunkMessageError> {
+        let transaction_id = reader
+            .read()?
+            .try_as_f64()
+            .ok_or_else(|| ChunkMessageError::UnexpectedAmfType {
+                amf_type: "expect number type".to_owned(),
+                backtrace: Backtrace::capture(),
+            })?
+            .to_u8()
+            .expect("trasaction id overflow u8");
+        if transaction_id != 1 {
+            tracing::warn!(
+                "connect transaction_id should be 1, got {} instead",
+                transaction_id
+            );
+        }
+        let command_object = match reader.read()? {
+            value @ (amf0::ValueRef::Object { .. } | amf0::ValueRef::ECMAArray(_)) => {
+                ConnectCommandRequestObject::try_from(&value)?
+            }
+            value => match amf_formats::Value::from(value.into_owned()).try_into_pairs() {
+                Ok(pairs) => pairs.collect::<HashMap<_, _>>().try_into()?,
+                Err(_) => {
+                    return Err(ChunkMessageError::UnexpectedAmfType {
+                        amf_type: "expect a key-value pair type".to_string(),
+                        backtrace: Backtrace::capture(),
+                    });
+                }
+            },
+        };
+
+        let optional_user_arguments = reader.read().ok().and_then(|value| {
+            amf_formats::Value::from(value.into_owned())
+                .try_into_pairs()
+                .ok()
+                .map(|pairs| pairs.collect())
+        });
+        Ok(ConnectCommandRequest {
+            command_name: c2s_command_names::CONNECT.to_string(),
+            transaction_id,
+            command_object,
+            optional_user_arguments,
+        })
+    }
+}
+
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for CreateStreamCommandRequest {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id =
@@ -136,10 +259,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for CreateStreamCom
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for PlayCommand {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for PlayCommand {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -188,10 +311,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for PlayCommand {
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for Play2Command {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for Play2Command {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -225,10 +348,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for Play2Command {
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for DeleteStreamCommand {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for DeleteStreamCommand {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -264,10 +387,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for DeleteStreamCom
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for ReceiveAudioCommand {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for ReceiveAudioCommand {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -305,10 +428,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for ReceiveAudioCom
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for ReceiveVideoCommand {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for ReceiveVideoCommand {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -346,10 +469,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for ReceiveVideoCom
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for PublishCommand {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for PublishCommand {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -404,10 +527,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for PublishCommand
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for SeekCommand {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for SeekCommand {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -445,10 +568,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for SeekCommand {
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for PauseCommand {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for PauseCommand {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header)?
@@ -493,10 +616,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for PauseCommand {
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<(amf_formats::Version, String), R> for CallCommandRequest {
+impl<R: io::Read> ReadRemainingFrom<(amf_formats::ReadOptions, String), R> for CallCommandRequest {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: (amf_formats::Version, String),
+        header: (amf_formats::ReadOptions, String),
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let transaction_id = amf_formats::Value::read_number(reader.by_ref(), header.0)?
@@ -516,12 +639,46 @@ impl<R: io::Read> ReadRemainingFrom<(amf_formats::Version, String), R> for CallC
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<(amf_formats::Version, RtmpS2CCommandsType), R>
+impl RtmpS2CCommands {
+    /// reads a response or a call of the server out of the payload of its message, for clients.
+    /// a _result or _error does not name the request it answers, only its transaction id does,
+    /// so the one of createStream is told apart by the stream id it carries where the others carry objects,
+    /// and the one of connect by the transaction id 1. the other calls of the server are read as responses
+    pub fn read_from_payload(
+        header: amf_formats::ReadOptions,
+        payload: &[u8],
+    ) -> Result<Self, ChunkMessageError> {
+        let mut reader = payload;
+        let command_name =
+            amf_formats::Value::read_string(&mut reader, header)?.ok_or_else(|| {
+                ChunkMessageError::UnexpectedAmfType {
+                    amf_type: "expect string type".to_owned(),
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        let command_type = match command_name.as_str() {
+            s2c_command_names::ON_STATUS => RtmpS2CCommandsType::OnStatus,
+            s2c_command_names::RESULT | s2c_command_names::ERROR => {
+                let transaction_id = amf_formats::Value::read_number(&mut reader, header)?;
+                amf_formats::Value::read_object(&mut reader, header)?;
+                match amf_formats::Value::read_number(&mut reader, header) {
+                    Ok(Some(_)) => RtmpS2CCommandsType::CreateStream,
+                    _ if transaction_id == Some(1.0) => RtmpS2CCommandsType::Connect,
+                    _ => RtmpS2CCommandsType::Call,
+                }
+            }
+            _ => RtmpS2CCommandsType::Call,
+        };
+        Self::read_remaining_from((header, command_type), &mut &payload[..])
+    }
+}
+
+impl<R: io::Read> ReadRemainingFrom<(amf_formats::ReadOptions, RtmpS2CCommandsType), R>
     for RtmpS2CCommands
 {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: (amf_formats::Version, RtmpS2CCommandsType),
+        header: (amf_formats::ReadOptions, RtmpS2CCommandsType),
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         match header.1 {
@@ -541,10 +698,10 @@ impl<R: io::Read> ReadRemainingFrom<(amf_formats::Version, RtmpS2CCommandsType),
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for ConnectCommandResponse {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for ConnectCommandResponse {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let command_name =
@@ -587,10 +744,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for ConnectCommandR
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for CallCommandResponse {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for CallCommandResponse {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let command_name = amf_formats::Value::read_string(reader, header)?.ok_or_else(|| {
@@ -616,10 +773,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for CallCommandResp
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for CreateStreamCommandResponse {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for CreateStreamCommandResponse {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let command_name =
@@ -660,10 +817,10 @@ impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for CreateStreamCom
     }
 }
 
-impl<R: io::Read> ReadRemainingFrom<amf_formats::Version, R> for OnStatusCommand {
+impl<R: io::Read> ReadRemainingFrom<amf_formats::ReadOptions, R> for OnStatusCommand {
     type Error = ChunkMessageError;
     fn read_remaining_from(
-        header: amf_formats::Version,
+        header: amf_formats::ReadOptions,
         reader: &mut R,
     ) -> Result<Self, Self::Error> {
         let command_name =
@@ -0,0 +1,294 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    io::Cursor,
+};
+
+use amf_formats::{ReadOptions, amf0};
+use utils::traits::reader::ReadRemainingFrom;
+
+use super::{RtmpC2SCommands, RtmpS2CCommands};
+
+/// counts the allocations of each thread, so that the tests running along do not count
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations_of<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+fn command_payload(values: Vec<amf0::Value>) -> Vec<u8> {
+    let mut payload = Vec::new();
+    amf0::Value::write_all(&mut payload, &values, false).unwrap();
+    payload
+}
+
+/// what obs sends, with the enhanced rtmp fourCcList
+fn connect_payload() -> Vec<u8> {
+    let command_object = amf0::object(
+        [
+            ("app", amf0::string("live")),
+            ("type", amf0::string("nonprivate")),
+            ("flashVer", amf0::string("FMLE/3.0 (compatible; FMSc/1.0)")),
+            ("swfUrl", amf0::string("rtmp://127.0.0.1:1935/live")),
+            ("tcUrl", amf0::string("rtmp://127.0.0.1:1935/live")),
+            ("fpad", amf0::bool(false)),
+            ("capabilities", amf0::number(15)),
+            ("audioCodecs", amf0::number(4071)),
+            ("videoCodecs", amf0::number(252)),
+            ("videoFunction", amf0::number(1)),
+            ("objectEncoding", amf0::number(0)),
+            (
+                "fourCcList",
+                amf0::array(vec![
+                    amf0::string("av01"),
+                    amf0::string("vp09"),
+                    amf0::string("hvc1"),
+                ]),
+            ),
+        ]
+        .into_iter(),
+    );
+    let user_arguments = amf0::object([("token", amf0::string("secret"))].into_iter());
+    command_payload(vec![
+        amf0::string("connect"),
+        amf0::number(1),
+        command_object,
+        user_arguments,
+    ])
+}
+
+fn read_owned(payload: &[u8]) -> RtmpC2SCommands {
+    RtmpC2SCommands::read_remaining_from(ReadOptions::default(), &mut Cursor::new(payload)).unwrap()
+}
+
+fn read_borrowed(payload: &[u8]) -> RtmpC2SCommands {
+    RtmpC2SCommands::read_from_payload(ReadOptions::default(), payload).unwrap()
+}
+
+#[test]
+fn commands_read_from_the_payload_are_the_ones_read_owned() {
+    let payloads = [
+        connect_payload(),
+        command_payload(vec![
+            amf0::string("createStream"),
+            amf0::number(2),
+            amf0::Value::Null,
+        ]),
+        command_payload(vec![
+            amf0::string("publish"),
+            amf0::number(0),
+            amf0::Value::Null,
+            amf0::string("test"),
+            amf0::string("live"),
+        ]),
+        command_payload(vec![
+            amf0::string("play"),
+            amf0::number(0),
+            amf0::Value::Null,
+            amf0::string("test"),
+            amf0::number(-2),
+        ]),
+        command_payload(vec![
+            amf0::string("releaseStream"),
+            amf0::number(3),
+            amf0::Value::Null,
+            amf0::string("test"),
+        ]),
+    ];
+    for payload in payloads {
+        let owned = format!("{:?}", read_owned(&payload));
+        assert_eq!(format!("{:?}", read_borrowed(&payload)), owned);
+    }
+
+    let RtmpC2SCommands::Connect(connect) = read_borrowed(&connect_payload()) else {
+        panic!("not a connect command");
+    };
+    assert_eq!(connect.command_object.app, "live");
+    assert_eq!(connect.command_object.tc_url, "rtmp://127.0.0.1:1935/live");
+    assert_eq!(
+        connect.command_object.four_cc_list,
+        Some(vec![
+            "av01".to_owned(),
+            "vp09".to_owned(),
+            "hvc1".to_owned()
+        ])
+    );
+    assert_eq!(
+        connect
+            .optional_user_arguments
+            .and_then(|arguments| arguments["token"].try_as_str().map(str::to_owned)),
+        Some("secret".to_owned())
+    );
+}
+
+#[test]
+fn commands_the_server_does_not_act_on_are_read_as_unknown() {
+    let commands = [
+        // the bandwidth check of legacy encoders once they hear onBWDone
+        (
+            command_payload(vec![
+                amf0::string("_checkbw"),
+                amf0::number(2),
+                amf0::Value::Null,
+            ]),
+            "_checkbw",
+            Some(2.0),
+        ),
+        // some of them answer onBWDone as if it was a call of theirs
+        (
+            command_payload(vec![amf0::string("_result"), amf0::number(0)]),
+            "_result",
+            Some(0.0),
+        ),
+        (
+            command_payload(vec![amf0::string("closeStream"), amf0::number(0)]),
+            "closeStream",
+            Some(0.0),
+        ),
+        // a call without its transaction id
+        (
+            command_payload(vec![amf0::string("setChallenge")]),
+            "setChallenge",
+            None,
+        ),
+    ];
+    for (payload, name, transaction) in commands {
+        for command in [read_owned(&payload), read_borrowed(&payload)] {
+            let RtmpC2SCommands::Unknown(command) = command else {
+                panic!("{} is read as {:?}", name, command);
+            };
+            assert_eq!(command.command_name, name);
+            assert_eq!(command.transaction_id, transaction);
+        }
+    }
+}
+
+#[test]
+fn responses_of_the_server_are_told_apart_for_clients() {
+    let options = ReadOptions::from(amf_formats::Version::Amf0);
+    let connected = command_payload(vec![
+        amf0::string("_result"),
+        amf0::number(1),
+        amf0::object([("fmsVer", amf0::string("FMS/3,0,1,123"))].into_iter()),
+        amf0::object(
+            [
+                ("level", amf0::string("status")),
+                ("code", amf0::string("NetConnection.Connect.Success")),
+            ]
+            .into_iter(),
+        ),
+    ]);
+    let Ok(RtmpS2CCommands::Connect(response)) =
+        RtmpS2CCommands::read_from_payload(options, &connected)
+    else {
+        panic!("connect response is not read");
+    };
+    assert!(response.success);
+
+    let created = command_payload(vec![
+        amf0::string("_result"),
+        amf0::number(2),
+        amf0::Value::Null,
+        amf0::number(1),
+    ]);
+    let Ok(RtmpS2CCommands::CreateStream(response)) =
+        RtmpS2CCommands::read_from_payload(options, &created)
+    else {
+        panic!("create stream response is not read");
+    };
+    assert_eq!((response.transaction_id, response.stream_id), (2.0, 1.0));
+
+    let published = command_payload(vec![
+        amf0::string("onStatus"),
+        amf0::number(0),
+        amf0::Value::Null,
+        amf0::object(
+            [
+                ("level", amf0::string("status")),
+                ("code", amf0::string("NetStream.Publish.Start")),
+                ("description", amf0::string("publish start")),
+            ]
+            .into_iter(),
+        ),
+    ]);
+    let Ok(RtmpS2CCommands::OnStatus(status)) =
+        RtmpS2CCommands::read_from_payload(options, &published)
+    else {
+        panic!("onStatus is not read");
+    };
+    assert_eq!(
+        status.info_object["code"].try_as_str(),
+        Some("NetStream.Publish.Start")
+    );
+
+    for (payload, name) in [
+        (
+            command_payload(vec![
+                amf0::string("_error"),
+                amf0::number(3),
+                amf0::Value::Null,
+                amf0::object([("code", amf0::string("NetConnection.Call.Failed"))].into_iter()),
+            ]),
+            "_error",
+        ),
+        (
+            command_payload(vec![
+                amf0::string("onBWDone"),
+                amf0::number(0),
+                amf0::Value::Null,
+            ]),
+            "onBWDone",
+        ),
+    ] {
+        let Ok(RtmpS2CCommands::Call(response)) =
+            RtmpS2CCommands::read_from_payload(options, &payload)
+        else {
+            panic!("{} is not read", name);
+        };
+        assert_eq!(response.command_name, name);
+    }
+}
+
+/// parsing connect commands owned and borrowed
+#[test]
+fn connect_command_parse_benchmark() {
+    const COMMANDS: usize = 10_000;
+    let payload = connect_payload();
+
+    let (_, owned_allocations) = allocations_of(|| {
+        for _ in 0..COMMANDS {
+            std::hint::black_box(read_owned(&payload));
+        }
+    });
+    let (_, borrowed_allocations) = allocations_of(|| {
+        for _ in 0..COMMANDS {
+            std::hint::black_box(read_borrowed(&payload));
+        }
+    });
+    assert!(
+        borrowed_allocations * 2 < owned_allocations,
+        "{} allocations borrowed, {} owned",
+        borrowed_allocations,
+        owned_allocations
+    );
+}
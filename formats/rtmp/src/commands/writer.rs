@@ -10,7 +10,7 @@ use super::{
     CallCommandRequest, CallCommandResponse, ConnectCommandRequest, ConnectCommandResponse,
     CreateStreamCommandRequest, CreateStreamCommandResponse, DeleteStreamCommand, OnStatusCommand,
     PauseCommand, Play2Command, PlayCommand, PublishCommand, ReceiveAudioCommand,
-    ReceiveVideoCommand, RtmpC2SCommands, RtmpS2CCommands, SeekCommand,
+    ReceiveVideoCommand, RtmpC2SCommands, RtmpS2CCommands, SeekCommand, UnknownCommand,
     consts::{c2s_command_names, s2c_command_names},
 };
 
@@ -60,10 +60,25 @@ impl<'a, W: io::Write> WriteTo<W> for RtmpCommandWriteWrapper<'a, RtmpC2SCommand
             RtmpC2SCommands::Pause(command) => {
                 RtmpCommandWriteWrapper::new(command, amf_version).write_to(writer)
             }
+            RtmpC2SCommands::Unknown(command) => {
+                RtmpCommandWriteWrapper::new(command, amf_version).write_to(writer)
+            }
         }
     }
 }
 
+/// the arguments left out on reading are not written
+impl<'a, W: io::Write> WriteTo<W> for RtmpCommandWriteWrapper<'a, UnknownCommand> {
+    type Error = ChunkMessageError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        let (command, version) = (self.0, self.1);
+        amf_formats::Value::write_str(&command.command_name, writer, version)?;
+        amf_formats::Value::write_number(command.transaction_id.unwrap_or(0.0), writer, version)?;
+        amf_formats::Value::write_null(writer, version)?;
+        Ok(())
+    }
+}
+
 impl<'a, W: io::Write> WriteTo<W> for RtmpCommandWriteWrapper<'a, ConnectCommandRequest> {
     type Error = ChunkMessageError;
     fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
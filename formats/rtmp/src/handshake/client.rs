@@ -0,0 +1,93 @@
+use std::{
+    fmt::Debug,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use utils::traits::writer::WriteTo;
+
+use super::{
+    C0S0Packet, C1S1Packet, HandshakeClientState, RTMP_VERSION,
+    consts::RTMP_HANDSHAKE_SIZE,
+    errors::{HandshakeError, HandshakeResult},
+};
+
+/// the simple handshake, which every server takes.
+/// the complex one is only asked for by servers checking for flash players
+#[derive(Debug)]
+pub struct HandshakeClient<T: AsyncRead + AsyncWrite + Unpin + Debug + Send> {
+    io: T,
+    s1_bytes: Vec<u8>,
+    state: HandshakeClientState,
+}
+
+impl<T> HandshakeClient<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Debug + Send,
+{
+    pub fn new(io: T) -> Self {
+        Self {
+            io,
+            s1_bytes: Vec::with_capacity(RTMP_HANDSHAKE_SIZE),
+            state: HandshakeClientState::Uninitialized,
+        }
+    }
+
+    pub async fn handshake(mut self) -> HandshakeResult<()> {
+        loop {
+            tracing::debug!("client handshake with state: {:?}", self.state);
+            match self.state {
+                HandshakeClientState::Uninitialized => {
+                    self.write_c0c1().await?;
+                    self.state = HandshakeClientState::C0C1Rent;
+                }
+                HandshakeClientState::C0C1Rent => {
+                    self.read_s0s1().await?;
+                    self.state = HandshakeClientState::S0S1Recived;
+                }
+                HandshakeClientState::S0S1Recived => {
+                    // c2 echoes s1
+                    self.io.write_all(&self.s1_bytes).await?;
+                    self.io.flush().await?;
+                    self.state = HandshakeClientState::AckSent;
+                }
+                HandshakeClientState::AckSent => {
+                    let mut s2_bytes = [0; RTMP_HANDSHAKE_SIZE];
+                    self.io.read_exact(&mut s2_bytes).await?;
+                    self.state = HandshakeClientState::Done;
+                }
+                HandshakeClientState::Done => break,
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_c0c1(&mut self) -> HandshakeResult<()> {
+        let mut bytes = Vec::with_capacity(1 + RTMP_HANDSHAKE_SIZE);
+        C0S0Packet {
+            version: RTMP_VERSION,
+        }
+        .write_to(&mut bytes)?;
+        let mut random_bytes: [u8; 1528] = [0; 1528];
+        utils::random::random_fill(&mut random_bytes);
+        C1S1Packet {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?,
+            _zeros: 0,
+            random_bytes,
+        }
+        .write_to(&mut bytes)?;
+        self.io.write_all(&bytes).await?;
+        self.io.flush().await?;
+        Ok(())
+    }
+
+    async fn read_s0s1(&mut self) -> HandshakeResult<()> {
+        let version = self.io.read_u8().await?;
+        if version != u8::from(RTMP_VERSION) {
+            return Err(HandshakeError::BadVersion(version));
+        }
+        self.s1_bytes.resize(RTMP_HANDSHAKE_SIZE, 0);
+        self.io.read_exact(&mut self.s1_bytes).await?;
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use core::time;
 
+pub mod client;
 pub mod consts;
 pub mod digest;
 pub mod errors;
@@ -73,7 +74,7 @@ impl From<Version> for u8 {
 ///   Handshake Done      |          Handshake Done
 ///        |              |               |
 ///     Pictorial Representation of Handshake
-#[derive(PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum HandshakeClientState {
     Uninitialized,
     C0C1Rent,
@@ -0,0 +1,147 @@
+use byteorder::{BigEndian, ByteOrder};
+use tokio_util::bytes::Bytes;
+
+use super::{RtmpMessageType, RtmpUserMessageBody, errors::AggregateError};
+use crate::chunk::{ChunkMessageCommonHeader, RuntimeStat};
+
+/// type, size, timestamp, extended timestamp and stream id of a sub-message, framed like a flv tag header
+pub const SUB_MESSAGE_HEADER_SIZE: usize = 11;
+/// the size of the previous sub-message, header included, after each body
+pub const BACK_POINTER_SIZE: usize = 4;
+
+// @see: 7.1.6. Aggregate Message
+/// +---------+-------------------------+
+/// | Header  | Aggregate Message body  |
+/// +---------+-------------------------+
+///
+/// +--------+-------+---------+--------+-------+---------+ - - - -
+/// |Header 0|Message|Back     |Header 1|Message|Back     |
+/// |        |Data 0 |Pointer 0|        |Data 1 |Pointer 1|
+/// +--------+-------+---------+--------+-------+---------+ - - - -
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggregateSubMessage {
+    /// audio, video or data
+    pub message_type: RtmpMessageType,
+    /// the timestamp of the sub-message moved by the offset between the aggregate and its first sub-message
+    pub timestamp: u32,
+    pub payload: Bytes,
+}
+
+impl AggregateSubMessage {
+    /// the header of the sub-message as if it was received on its own, on the chunk stream of the aggregate
+    pub fn header(&self, aggregate: &ChunkMessageCommonHeader) -> ChunkMessageCommonHeader {
+        ChunkMessageCommonHeader {
+            basic_header: aggregate.basic_header.clone(),
+            timestamp: self.timestamp,
+            message_length: self.payload.len() as u32,
+            message_type_id: self.message_type.into(),
+            message_stream_id: aggregate.message_stream_id,
+            extended_timestamp_enabled: aggregate.extended_timestamp_enabled,
+            runtime_stat: RuntimeStat {
+                read_time_ns: aggregate.runtime_stat.read_time_ns,
+                process_time_ns: aggregate.runtime_stat.process_time_ns,
+            },
+        }
+    }
+
+    pub fn into_body(self) -> RtmpUserMessageBody {
+        match self.message_type {
+            RtmpMessageType::Audio => RtmpUserMessageBody::Audio {
+                payload: self.payload,
+            },
+            RtmpMessageType::Video => RtmpUserMessageBody::Video {
+                payload: self.payload,
+            },
+            _ => RtmpUserMessageBody::MetaData {
+                payload: self.payload,
+            },
+        }
+    }
+}
+
+/// the sub-messages of an aggregate message and the ones skipped
+#[derive(Debug, Default)]
+pub struct AggregateMessage {
+    pub messages: Vec<AggregateSubMessage>,
+    /// why the sub-messages which could not be parsed were skipped, in order
+    pub skipped: Vec<AggregateError>,
+}
+
+impl AggregateMessage {
+    /// splits the payload of an aggregate message with `timestamp` into its sub-messages.
+    /// a sub-message whose back-pointer does not match its size is skipped up to the first back-pointer
+    /// matching the distance from its start, the rest of the payload is skipped if there is none
+    pub fn split(payload: &Bytes, timestamp: u32) -> Self {
+        let mut result = Self::default();
+        // the first sub-message has the timestamp of the aggregate, the rest keep their distance to it
+        let mut timestamp_offset = None;
+        let mut offset = 0;
+        while offset < payload.len() {
+            let remaining = payload.len() - offset;
+            if remaining < SUB_MESSAGE_HEADER_SIZE + BACK_POINTER_SIZE {
+                result
+                    .skipped
+                    .push(AggregateError::Truncated { offset, remaining });
+                break;
+            }
+            let header = &payload[offset..offset + SUB_MESSAGE_HEADER_SIZE];
+            let message_type_id = header[0];
+            let data_size = BigEndian::read_u24(&header[1..4]) as usize;
+            let sub_timestamp = BigEndian::read_u24(&header[4..7]) | (header[7] as u32) << 24;
+
+            let back_pointer_at = offset + SUB_MESSAGE_HEADER_SIZE + data_size;
+            let expected = (SUB_MESSAGE_HEADER_SIZE + data_size) as u32;
+            let back_pointer = payload
+                .get(back_pointer_at..back_pointer_at + BACK_POINTER_SIZE)
+                .map(BigEndian::read_u32);
+            if back_pointer != Some(expected) {
+                result.skipped.push(AggregateError::BackPointerMismatch {
+                    offset,
+                    back_pointer,
+                    expected,
+                });
+                match Self::resync(payload, offset) {
+                    Some(next) => {
+                        offset = next;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+
+            let timestamp_offset =
+                *timestamp_offset.get_or_insert(timestamp.wrapping_sub(sub_timestamp));
+            let message_type = match RtmpMessageType::try_from(message_type_id) {
+                Ok(
+                    message_type @ (RtmpMessageType::Audio
+                    | RtmpMessageType::Video
+                    | RtmpMessageType::AMF0Data
+                    | RtmpMessageType::AMF3Data),
+                ) => Some(message_type),
+                _ => None,
+            };
+            match message_type {
+                Some(message_type) => result.messages.push(AggregateSubMessage {
+                    message_type,
+                    timestamp: sub_timestamp.wrapping_add(timestamp_offset),
+                    payload: payload.slice(offset + SUB_MESSAGE_HEADER_SIZE..back_pointer_at),
+                }),
+                None => result.skipped.push(AggregateError::UnexpectedMessageType {
+                    offset,
+                    message_type_id,
+                }),
+            }
+            offset = back_pointer_at + BACK_POINTER_SIZE;
+        }
+        result
+    }
+
+    /// where the sub-message after the broken one at `start` begins, found by its back-pointer
+    fn resync(payload: &Bytes, start: usize) -> Option<usize> {
+        (start + SUB_MESSAGE_HEADER_SIZE..=payload.len().checked_sub(BACK_POINTER_SIZE)?)
+            .find(|at| {
+                BigEndian::read_u32(&payload[*at..*at + BACK_POINTER_SIZE]) as usize == at - start
+            })
+            .map(|at| at + BACK_POINTER_SIZE)
+    }
+}
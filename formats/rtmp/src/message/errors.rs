@@ -1 +1,16 @@
+use thiserror::Error;
 
+/// why a sub-message of an aggregate message is skipped, offsets are from the start of the payload
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AggregateError {
+    #[error("sub-message at {offset} is truncated, {remaining} bytes left")]
+    Truncated { offset: usize, remaining: usize },
+    #[error("back-pointer of sub-message at {offset} is {back_pointer:?}, expect {expected}")]
+    BackPointerMismatch {
+        offset: usize,
+        back_pointer: Option<u32>,
+        expected: u32,
+    },
+    #[error("sub-message at {offset} has unexpected message type: {message_type_id}")]
+    UnexpectedMessageType { offset: usize, message_type_id: u8 },
+}
@@ -10,9 +10,12 @@ use crate::{
 // difference between rtmp message and rtmp chunk stream message:
 /// https://stackoverflow.com/questions/59709461/difference-between-chunk-message-header-and-message-header-in-rtmp
 /// https://www.youtube.com/watch?v=AoRepm5ks80&t=1279s
+pub mod aggregate;
 pub mod consts;
 pub mod errors;
 pub mod reader;
+#[cfg(test)]
+mod test;
 pub mod writer;
 
 // @see: 6.1.1. Message Header
@@ -76,7 +79,7 @@ impl Debug for RtmpUserMessageBody {
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RtmpMessageType {
     AMF3Command = 17,
     AMF0Command = 20,
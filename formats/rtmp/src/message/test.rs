@@ -0,0 +1,140 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use tokio_util::bytes::Bytes;
+
+use super::{
+    RtmpMessageType,
+    aggregate::{AggregateMessage, AggregateSubMessage},
+    errors::AggregateError,
+};
+
+/// a sub-message framed like a flv tag, followed by its back-pointer
+fn sub_message(message_type: u8, timestamp: u32, body: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![message_type];
+    bytes.write_u24::<BigEndian>(body.len() as u32).unwrap();
+    bytes.write_u24::<BigEndian>(timestamp & 0xFF_FFFF).unwrap();
+    bytes.write_u8((timestamp >> 24) as u8).unwrap();
+    bytes.write_u24::<BigEndian>(0).unwrap();
+    bytes.extend_from_slice(body);
+    bytes
+        .write_u32::<BigEndian>(11 + body.len() as u32)
+        .unwrap();
+    bytes
+}
+
+const VIDEO_KEY_FRAME: &[u8] = &[0x17, 0x01, 0x00, 0x00, 0x00, 0xAA, 0xBB];
+const AUDIO_FRAME: &[u8] = &[0xAF, 0x01, 0x21, 0x10];
+const VIDEO_INTER_FRAME: &[u8] = &[0x27, 0x01, 0x00, 0x00, 0x00, 0xCC];
+
+/// video at 1000, audio at 1010 and video at 1040
+fn two_video_one_audio() -> [Vec<u8>; 3] {
+    [
+        sub_message(9, 1000, VIDEO_KEY_FRAME),
+        sub_message(8, 1010, AUDIO_FRAME),
+        sub_message(9, 1040, VIDEO_INTER_FRAME),
+    ]
+}
+
+fn expected(message_type: RtmpMessageType, timestamp: u32, body: &[u8]) -> AggregateSubMessage {
+    AggregateSubMessage {
+        message_type,
+        timestamp,
+        payload: Bytes::copy_from_slice(body),
+    }
+}
+
+#[test]
+fn aggregate_is_split_with_the_timestamps_moved_to_the_aggregate() {
+    let payload = Bytes::from(two_video_one_audio().concat());
+    let aggregate = AggregateMessage::split(&payload, 5000);
+    assert!(aggregate.skipped.is_empty(), "{:?}", aggregate.skipped);
+    assert_eq!(
+        aggregate.messages,
+        vec![
+            expected(RtmpMessageType::Video, 5000, VIDEO_KEY_FRAME),
+            expected(RtmpMessageType::Audio, 5010, AUDIO_FRAME),
+            expected(RtmpMessageType::Video, 5040, VIDEO_INTER_FRAME),
+        ]
+    );
+
+    // the offset might be negative, the timestamps wrap like the ones of rtmp
+    let aggregate = AggregateMessage::split(&payload, 0xFFFF_FFF0);
+    let timestamps: Vec<_> = aggregate
+        .messages
+        .iter()
+        .map(|message| message.timestamp)
+        .collect();
+    assert_eq!(timestamps, vec![0xFFFF_FFF0, 0xFFFF_FFFA, 0x18]);
+}
+
+#[test]
+fn corrupted_sub_message_is_skipped_by_its_back_pointer() {
+    let [first, mut middle, last] = two_video_one_audio();
+    // the size of the audio sub-message is off, its back-pointer is intact
+    middle[3] += 3;
+    let middle_at = first.len();
+    let payload = Bytes::from([first, middle, last].concat());
+    let aggregate = AggregateMessage::split(&payload, 1000);
+    assert_eq!(
+        aggregate.messages,
+        vec![
+            expected(RtmpMessageType::Video, 1000, VIDEO_KEY_FRAME),
+            expected(RtmpMessageType::Video, 1040, VIDEO_INTER_FRAME),
+        ]
+    );
+    assert_eq!(aggregate.skipped.len(), 1);
+    assert!(matches!(
+        aggregate.skipped[0],
+        AggregateError::BackPointerMismatch {
+            offset,
+            expected: 18,
+            ..
+        } if offset == middle_at
+    ));
+}
+
+#[test]
+fn sub_messages_of_other_types_are_skipped() {
+    let [first, _, last] = two_video_one_audio();
+    let command = sub_message(20, 1020, &[0x02, 0x00, 0x01, b'a']);
+    let command_at = first.len();
+    let aggregate = AggregateMessage::split(&Bytes::from([first, command, last].concat()), 1000);
+    assert_eq!(aggregate.messages.len(), 2);
+    assert_eq!(
+        aggregate.skipped,
+        vec![AggregateError::UnexpectedMessageType {
+            offset: command_at,
+            message_type_id: 20,
+        }]
+    );
+}
+
+#[test]
+fn broken_tail_is_skipped() {
+    let [first, middle, mut last] = two_video_one_audio();
+    // the back-pointer of the last sub-message is gone with a few bytes of its body
+    last.truncate(last.len() - 6);
+    let last_at = first.len() + middle.len();
+    let payload = Bytes::from([first, middle, last].concat());
+    let aggregate = AggregateMessage::split(&payload, 1000);
+    assert_eq!(aggregate.messages.len(), 2);
+    assert_eq!(
+        aggregate.skipped,
+        vec![AggregateError::BackPointerMismatch {
+            offset: last_at,
+            back_pointer: None,
+            expected: 17,
+        }]
+    );
+
+    // less than a header is left
+    let payload = payload.slice(..last_at + 5);
+    let aggregate = AggregateMessage::split(&payload, 1000);
+    assert_eq!(aggregate.messages.len(), 2);
+    assert_eq!(
+        aggregate.skipped,
+        vec![AggregateError::Truncated {
+            offset: last_at,
+            remaining: 5,
+        }]
+    );
+}
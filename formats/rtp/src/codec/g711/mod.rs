@@ -0,0 +1,70 @@
+pub mod packetizer;
+pub mod sequencer;
+#[cfg(test)]
+mod test;
+
+use codec_common::audio::{
+    AudioCodecCommon, SoundInfoCommon, SoundRateCommon, SoundSizeCommon, SoundTypeCommon,
+};
+
+use crate::payload_types::rtp_payload_type::{PCMA_AUDIO, PCMU_AUDIO};
+
+/// G.711 is sampled at 8kHz, one byte a sample, RFC 3551 4.5.14
+pub const G711_CLOCKRATE: u64 = 8000;
+/// samples of a 20ms packet, the default packetization interval of RFC 3551 4.5
+pub const G711_SAMPLES_PER_PACKET: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum G711Law {
+    /// PCMU
+    MuLaw,
+    /// PCMA
+    ALaw,
+}
+
+impl G711Law {
+    pub fn from_encoding_name(encoding_name: &str) -> Option<Self> {
+        match encoding_name.to_lowercase().as_str() {
+            "pcmu" => Some(Self::MuLaw),
+            "pcma" => Some(Self::ALaw),
+            _ => None,
+        }
+    }
+
+    pub fn payload_type(&self) -> u8 {
+        match self {
+            Self::MuLaw => PCMU_AUDIO,
+            Self::ALaw => PCMA_AUDIO,
+        }
+    }
+
+    /// flv carries G.711 with the legacy sound formats 7 and 8,
+    /// players expect the 5.5kHz rate bits there as no rate index fits 8kHz
+    pub fn sound_info() -> SoundInfoCommon {
+        SoundInfoCommon {
+            sound_rate: SoundRateCommon::KHZ5D5,
+            sound_size: SoundSizeCommon::Bit16,
+            sound_type: SoundTypeCommon::Mono,
+        }
+    }
+}
+
+impl From<G711Law> for AudioCodecCommon {
+    fn from(value: G711Law) -> Self {
+        match value {
+            G711Law::MuLaw => Self::G711MULawLogarithmicPCM,
+            G711Law::ALaw => Self::G711ALawLogarithmicPCM,
+        }
+    }
+}
+
+impl TryFrom<AudioCodecCommon> for G711Law {
+    type Error = AudioCodecCommon;
+    fn try_from(value: AudioCodecCommon) -> Result<Self, Self::Error> {
+        match value {
+            AudioCodecCommon::G711MULawLogarithmicPCM => Ok(Self::MuLaw),
+            AudioCodecCommon::G711ALawLogarithmicPCM => Ok(Self::ALaw),
+            _ => Err(value),
+        }
+    }
+}
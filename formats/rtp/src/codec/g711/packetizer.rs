@@ -0,0 +1,150 @@
+use std::cmp;
+
+use num::ToPrimitive;
+use tokio_util::bytes::Bytes;
+use utils::{random, traits::dynamic_sized_packet::DynamicSizedPacket};
+
+use crate::{
+    errors::RtpError,
+    header::{RtpHeader, RtpHeaderBuilder},
+    packet::{
+        RtpTrivialPacket,
+        packetizer::{
+            RtpPacketizerAudioItem, RtpPacketizerItem, RtpTrivialPacketPacketizer,
+            wallclock_to_rtp_timestamp,
+        },
+    },
+};
+
+use super::{G711_CLOCKRATE, G711_SAMPLES_PER_PACKET, G711Law};
+
+#[derive(Debug)]
+pub struct RtpG711PacketPacketizer {
+    law: G711Law,
+    rtp_header: RtpHeader,
+    first_frame_timestamp: Option<u64>,
+    last_frame_timestamp: Option<u64>,
+    rtp_timestamp_base: u64,
+    rtp_clockrate: u64,
+    frames: Vec<Bytes>,
+    mtu: usize,
+    started: bool,
+}
+
+impl RtpG711PacketPacketizer {
+    pub fn new(mtu: usize, law: G711Law, ssrc: u32) -> Self {
+        Self {
+            law,
+            rtp_header: RtpHeaderBuilder::new()
+                .version(2)
+                .payload_type(law.payload_type())
+                .ssrc(ssrc)
+                .sequence_number(random::random_u16())
+                .build(),
+            first_frame_timestamp: None,
+            last_frame_timestamp: None,
+            rtp_timestamp_base: random::random_u32() as u64,
+            rtp_clockrate: G711_CLOCKRATE,
+            frames: vec![],
+            mtu,
+            started: false,
+        }
+    }
+
+    pub fn mtu(&mut self, mtu: usize) -> &mut Self {
+        self.mtu = mtu;
+        self
+    }
+}
+
+impl RtpTrivialPacketPacketizer for RtpG711PacketPacketizer {
+    fn build(&mut self) -> Result<Vec<RtpTrivialPacket>, RtpError> {
+        let max_samples = cmp::min(
+            G711_SAMPLES_PER_PACKET,
+            self.mtu
+                .checked_sub(self.rtp_header.get_packet_bytes_count())
+                .ok_or(RtpError::MTUTooSmall(self.mtu))?,
+        );
+        if max_samples == 0 {
+            return Err(RtpError::MTUTooSmall(self.mtu));
+        }
+        let (Some(last_frame_timestamp), Some(first_frame_timestamp)) =
+            (self.last_frame_timestamp, self.first_frame_timestamp)
+        else {
+            return Err(RtpError::G711PacketizationFailed(
+                "frame timestamp is not set".to_owned(),
+            ));
+        };
+        let frame_rtp_timestamp = wallclock_to_rtp_timestamp(
+            last_frame_timestamp,
+            first_frame_timestamp,
+            self.rtp_timestamp_base,
+            self.rtp_clockrate,
+        ) as u32;
+
+        let mut result = vec![];
+        // one byte a sample, so the offset in samples is the offset in bytes
+        let mut samples_offset: u32 = 0;
+        for frame in self.frames.drain(..) {
+            for samples in frame.chunks(max_samples) {
+                let header = RtpHeader {
+                    // the first packet of a talkspurt, RFC 3551 4.1
+                    marker: !self.started,
+                    // the samples are taken at 8kHz, whatever rate the timestamps tick at
+                    timestamp: frame_rtp_timestamp.wrapping_add(
+                        (u64::from(samples_offset) * self.rtp_clockrate / G711_CLOCKRATE) as u32,
+                    ),
+                    ..self.rtp_header.clone()
+                };
+                result.push(RtpTrivialPacket::new(header, frame.slice_ref(samples)));
+                self.started = true;
+                samples_offset =
+                    samples_offset.wrapping_add(samples.len().to_u32().expect("u32 overflow"));
+                self.rtp_header.sequence_number = self.rtp_header.sequence_number.wrapping_add(1);
+            }
+        }
+        Ok(result)
+    }
+
+    fn packetize(&mut self, item: RtpPacketizerItem) -> Result<(), RtpError> {
+        match item {
+            RtpPacketizerItem::Audio(RtpPacketizerAudioItem::G711(g711_item))
+                if g711_item.law == self.law =>
+            {
+                self.frames.extend(g711_item.frames);
+                Ok(())
+            }
+            _ => {
+                debug_assert!(false, "only g711 {:?} audio item is supported", self.law);
+                Err(RtpError::G711PacketizationFailed(format!(
+                    "only g711 {:?} audio item is supported",
+                    self.law
+                )))
+            }
+        }
+    }
+
+    fn set_rtp_header(&mut self, mut header: RtpHeader) {
+        header.sequence_number = self.rtp_header.sequence_number;
+        self.rtp_header = header;
+    }
+
+    fn set_frame_timestamp(&mut self, timestamp: u64) {
+        self.last_frame_timestamp = Some(timestamp);
+        if self.first_frame_timestamp.is_none() {
+            self.first_frame_timestamp = Some(timestamp);
+        }
+    }
+
+    fn get_rtp_clockrate(&self) -> u64 {
+        self.rtp_clockrate
+    }
+
+    fn set_rtp_clockrate(&mut self, clockrate: u64) {
+        self.rtp_clockrate = clockrate;
+    }
+
+    fn rtp_header(&self) -> &RtpHeader {
+        &self.rtp_header
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+
+use tokio_util::bytes::Bytes;
+
+use crate::{
+    errors::RtpError,
+    header::RtpHeader,
+    packet::{
+        RtpTrivialPacket,
+        sequencer::{RtpBufferAudioItem, RtpBufferItem, RtpBufferedSequencer},
+    },
+};
+
+use super::G711Law;
+
+#[derive(Debug)]
+pub struct RtpG711BufferItem {
+    pub law: G711Law,
+    pub rtp_header: RtpHeader,
+    /// the samples, one byte each
+    pub payload: Bytes,
+}
+
+/// G.711 payloads are raw samples, RFC 3551 4.5.14,
+/// so every packet makes an audio frame on its own
+#[derive(Debug)]
+pub struct RtpG711Sequencer {
+    law: G711Law,
+    buffer: VecDeque<RtpG711BufferItem>,
+}
+
+impl RtpG711Sequencer {
+    pub fn new(law: G711Law) -> Self {
+        Self {
+            law,
+            buffer: VecDeque::new(),
+        }
+    }
+}
+
+impl RtpBufferedSequencer for RtpG711Sequencer {
+    fn enqueue(&mut self, packet: RtpTrivialPacket) -> Result<(), RtpError> {
+        if packet.header.payload_type != self.law.payload_type() {
+            tracing::warn!(
+                "g711 {:?} sequencer got payload type {}, expected {}",
+                self.law,
+                packet.header.payload_type,
+                self.law.payload_type()
+            );
+        }
+        if packet.payload.is_empty() {
+            return Err(RtpError::EmptyPayload);
+        }
+        self.buffer.push_back(RtpG711BufferItem {
+            law: self.law,
+            rtp_header: packet.header,
+            payload: packet.payload,
+        });
+        Ok(())
+    }
+
+    fn try_dump(&mut self) -> Vec<RtpBufferItem> {
+        self.buffer
+            .drain(..)
+            .map(|item| RtpBufferItem::Audio(RtpBufferAudioItem::G711(item)))
+            .collect()
+    }
+}
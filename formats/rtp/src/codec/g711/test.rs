@@ -0,0 +1,200 @@
+#[cfg(test)]
+mod tests {
+    use codec_common::{
+        FrameType,
+        audio::{AudioCodecCommon, AudioFrameInfo},
+    };
+    use stream_center::gop::MediaFrame;
+    use tokio_util::bytes::Bytes;
+    use utils::traits::writer::WriteTo;
+
+    use crate::{
+        codec::g711::{
+            G711_CLOCKRATE, G711_SAMPLES_PER_PACKET, G711Law, packetizer::RtpG711PacketPacketizer,
+            sequencer::RtpG711Sequencer,
+        },
+        packet::{
+            RtpTrivialPacket,
+            packetizer::{RtpPacketizerItem, RtpTrivialPacketPacketizer},
+            sequencer::RtpBufferedSequencer,
+        },
+        payload_types::rtp_payload_type::{
+            audio_get_rtp_clockrate, get_audio_rtp_payload_type, get_rtp_clockrate,
+            get_rtp_payload_type,
+        },
+    };
+
+    fn audio_frame(codec_id: AudioCodecCommon, timestamp_ms: u64, payload: Bytes) -> MediaFrame {
+        let sound_info = G711Law::sound_info();
+        MediaFrame::Audio {
+            frame_info: AudioFrameInfo::new(
+                codec_id,
+                FrameType::CodedFrames,
+                sound_info.sound_rate,
+                sound_info.sound_size,
+                sound_info.sound_type,
+                timestamp_ms * 1_000_000,
+            ),
+            payload,
+        }
+    }
+
+    fn samples(count: usize, seed: u8) -> Bytes {
+        (0..count)
+            .map(|i| (i as u8).wrapping_add(seed))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    fn packetize(
+        packetizer: &mut RtpG711PacketPacketizer,
+        frame: MediaFrame,
+    ) -> Vec<RtpTrivialPacket> {
+        packetizer.set_frame_timestamp(frame.get_presentation_timestamp_ms());
+        packetizer
+            .packetize(RtpPacketizerItem::from_media_frame(frame).unwrap())
+            .unwrap();
+        packetizer.build().unwrap()
+    }
+
+    #[test]
+    fn test_static_payload_types() {
+        assert_eq!(get_rtp_payload_type("PCMU"), Some(0));
+        assert_eq!(get_rtp_payload_type("PCMA"), Some(8));
+        assert_eq!(get_rtp_clockrate("pcmu"), Some(G711_CLOCKRATE));
+        assert_eq!(get_rtp_clockrate("pcma"), Some(G711_CLOCKRATE));
+        assert_eq!(
+            get_audio_rtp_payload_type(AudioCodecCommon::G711MULawLogarithmicPCM),
+            Some(0)
+        );
+        assert_eq!(
+            get_audio_rtp_payload_type(AudioCodecCommon::G711ALawLogarithmicPCM),
+            Some(8)
+        );
+        assert_eq!(
+            audio_get_rtp_clockrate(AudioCodecCommon::G711ALawLogarithmicPCM),
+            Some(8000)
+        );
+        assert_eq!(G711Law::from_encoding_name("PCMU"), Some(G711Law::MuLaw));
+        assert_eq!(G711Law::from_encoding_name("pcma"), Some(G711Law::ALaw));
+        assert_eq!(G711Law::from_encoding_name("L16"), None);
+    }
+
+    #[test]
+    fn test_packetize_20ms_packets() {
+        let mut packetizer = RtpG711PacketPacketizer::new(1400, G711Law::ALaw, 1234);
+        let first = packetize(
+            &mut packetizer,
+            audio_frame(AudioCodecCommon::G711ALawLogarithmicPCM, 0, samples(320, 0)),
+        );
+        assert_eq!(first.len(), 2);
+        assert!(first[0].header.marker);
+        assert!(!first[1].header.marker);
+        for packet in &first {
+            assert_eq!(packet.header.payload_type, 8);
+            assert_eq!(packet.header.ssrc, 1234);
+            assert_eq!(packet.payload.len(), G711_SAMPLES_PER_PACKET);
+        }
+        assert_eq!(
+            first[1].header.sequence_number,
+            first[0].header.sequence_number.wrapping_add(1)
+        );
+        assert_eq!(
+            first[1].header.timestamp,
+            first[0].header.timestamp.wrapping_add(160)
+        );
+
+        let second = packetize(
+            &mut packetizer,
+            audio_frame(
+                AudioCodecCommon::G711ALawLogarithmicPCM,
+                40,
+                samples(160, 1),
+            ),
+        );
+        assert_eq!(second.len(), 1);
+        assert!(!second[0].header.marker);
+        assert_eq!(
+            second[0].header.sequence_number,
+            first[1].header.sequence_number.wrapping_add(1)
+        );
+        assert_eq!(
+            second[0].header.timestamp,
+            first[0].header.timestamp.wrapping_add(320)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (law, codec_id) in [
+            (G711Law::MuLaw, AudioCodecCommon::G711MULawLogarithmicPCM),
+            (G711Law::ALaw, AudioCodecCommon::G711ALawLogarithmicPCM),
+        ] {
+            let mut packetizer = RtpG711PacketPacketizer::new(1400, law, 1234);
+            let payloads: Vec<_> = (0..5).map(|i| samples(160, i)).collect();
+            let packets: Vec<_> = payloads
+                .iter()
+                .enumerate()
+                .flat_map(|(i, payload)| {
+                    packetize(
+                        &mut packetizer,
+                        audio_frame(codec_id, i as u64 * 20, payload.clone()),
+                    )
+                })
+                .collect();
+            assert_eq!(packets.len(), payloads.len());
+            let timestamp_base = packets[0].header.timestamp;
+
+            let mut sequencer = RtpG711Sequencer::new(law);
+            for packet in packets {
+                sequencer.enqueue(packet).unwrap();
+            }
+            let frames: Vec<_> = sequencer
+                .try_dump()
+                .into_iter()
+                .map(|item| item.to_media_frame(timestamp_base, G711_CLOCKRATE))
+                .collect();
+            assert_eq!(frames.len(), payloads.len());
+            for (i, (frame, expected)) in frames.iter().zip(payloads.iter()).enumerate() {
+                let MediaFrame::Audio {
+                    frame_info,
+                    payload,
+                } = frame
+                else {
+                    panic!("expect audio frame, got {:?}", frame);
+                };
+                assert_eq!(frame_info.codec_id, codec_id);
+                assert_eq!(frame_info.timestamp_nano, i as u64 * 20_000_000);
+                assert_eq!(payload, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_flv_audio_tag_header() {
+        for (law, sound_format) in [(G711Law::ALaw, 7_u8), (G711Law::MuLaw, 8_u8)] {
+            let mut packetizer = RtpG711PacketPacketizer::new(1400, law, 1234);
+            let packets = packetize(
+                &mut packetizer,
+                audio_frame(law.into(), 0, samples(G711_SAMPLES_PER_PACKET, 0)),
+            );
+            let timestamp_base = packets[0].header.timestamp;
+            let mut sequencer = RtpG711Sequencer::new(law);
+            sequencer.enqueue(packets[0].clone()).unwrap();
+            let frame = sequencer
+                .try_dump()
+                .pop()
+                .unwrap()
+                .to_media_frame(timestamp_base, G711_CLOCKRATE);
+
+            let tag = frame.to_flv_tag(4).unwrap();
+            // no aac packet type, the legacy header is a single byte
+            assert_eq!(tag.tag_header.data_size, 1 + 160);
+            let mut bytes = Vec::new();
+            tag.write_to(&mut bytes).unwrap();
+            // SoundFormat, SoundRate 5.5kHz, SoundSize 16 bits, SoundType mono
+            assert_eq!(bytes[11], (sound_format << 4) | 0b0010);
+            assert_eq!(&bytes[12..], &samples(G711_SAMPLES_PER_PACKET, 0)[..]);
+        }
+    }
+}
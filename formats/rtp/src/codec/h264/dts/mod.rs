@@ -0,0 +1,234 @@
+use std::collections::{HashMap, VecDeque};
+
+use codec_common::video::VideoFrameUnit;
+use codec_h264::{
+    nalu::NalUnit,
+    nalu_type::NALUType,
+    poc::PicOrderCounter,
+    pps::Pps,
+    slice_header::{SliceHeaderPicOrder, SliceHeaderPrefix},
+    sps::{Sps, chroma_format_idc::ChromaFormatIdc},
+};
+use stream_center::gop::MediaFrame;
+
+#[cfg(test)]
+mod test;
+
+/// the dpb holds 16 frames at most, no stream reorders deeper than that
+pub const MAX_REORDER_FRAMES: usize = 16;
+/// frames held for a stream with b-frames the sps of which does not tell how deep it reorders
+pub const DEFAULT_REORDER_FRAMES: usize = 2;
+
+/// frames the stream may output after a frame decoded later, 0 if it never reorders
+pub fn reorder_frames(sps: &Sps, default_reorder_frames: usize) -> usize {
+    // pic_order_cnt_type 2 means the output order is the decoding order
+    if sps.pic_order_cnt_type == 2 {
+        return 0;
+    }
+    // baseline and the intra profiles have no b slices
+    let intra_profile = matches!(sps.profile_idc, 110 | 122 | 244) && sps.constraint_set3_flag;
+    if sps.profile_idc == 66 || sps.profile_idc == 44 || intra_profile {
+        return 0;
+    }
+    sps.max_num_reorder_frames()
+        .map_or(default_reorder_frames, |frames| frames as usize)
+        .min(MAX_REORDER_FRAMES)
+}
+
+/// Gives the h264 frames unpacked from rtp a decode timestamp.
+/// rtp carries the presentation time only, the frames come with dts = pts,
+/// which makes the composition time of the frames decoded ahead of a b-frame negative.
+///
+/// The frames are held back by the reorder depth of the stream,
+/// each one gets the smallest pts among itself and the frames held after it as the dts,
+/// which is never after its own pts and never before the dts of the frames before it
+/// as long as the stream does not reorder deeper than told.
+/// The picture order counts of the slices tell when it does, the depth is raised then.
+/// Streams that never reorder pass as they are
+#[derive(Debug)]
+pub struct H264DtsDeriver {
+    default_reorder_frames: usize,
+    reorder_frames: usize,
+    sps: HashMap<u8, Sps>,
+    pps: HashMap<u8, Pps>,
+    counter: PicOrderCounter,
+    /// of the pictures since the last idr, in decoding order
+    recent_pic_order_cnts: VecDeque<i64>,
+    pending: VecDeque<MediaFrame>,
+    last_dts_nano: Option<u64>,
+}
+
+impl H264DtsDeriver {
+    /// `sps` and `pps` are the ones known ahead, e.g., from the sprop-parameter-sets of the sdp
+    pub fn new(default_reorder_frames: usize, sps: Option<Sps>, pps: Option<Pps>) -> Self {
+        let mut result = Self {
+            default_reorder_frames: default_reorder_frames.min(MAX_REORDER_FRAMES),
+            reorder_frames: default_reorder_frames.min(MAX_REORDER_FRAMES),
+            sps: HashMap::new(),
+            pps: HashMap::new(),
+            counter: PicOrderCounter::default(),
+            recent_pic_order_cnts: VecDeque::with_capacity(MAX_REORDER_FRAMES),
+            pending: VecDeque::new(),
+            last_dts_nano: None,
+        };
+        if let Some(sps) = sps {
+            result.on_sps(sps);
+        }
+        if let Some(pps) = pps {
+            result.pps.insert(pps.pic_parameter_set_id, pps);
+        }
+        result
+    }
+
+    /// frames held back at most
+    pub fn reorder_frames(&self) -> usize {
+        self.reorder_frames
+    }
+
+    /// the frames whose dts is known by now, in decoding order.
+    /// frames other than h264 ones pass as they are
+    pub fn push(&mut self, frame: MediaFrame) -> Vec<MediaFrame> {
+        let MediaFrame::Video {
+            payload: VideoFrameUnit::H264 { nal_units },
+            ..
+        } = &frame
+        else {
+            return vec![frame];
+        };
+        self.on_nal_units(nal_units);
+        if self.reorder_frames == 0 && self.pending.is_empty() {
+            self.last_dts_nano = Some(frame.get_decode_timestamp_ns());
+            return vec![frame];
+        }
+        self.pending.push_back(frame);
+        let mut result = Vec::new();
+        while self.pending.len() > self.reorder_frames {
+            result.push(self.pop_pending());
+        }
+        result
+    }
+
+    /// the frames held back, used when the stream ends
+    pub fn flush(&mut self) -> Vec<MediaFrame> {
+        let mut result = Vec::with_capacity(self.pending.len());
+        while !self.pending.is_empty() {
+            result.push(self.pop_pending());
+        }
+        result
+    }
+
+    fn pop_pending(&mut self) -> MediaFrame {
+        let window_min_pts = self
+            .pending
+            .iter()
+            .take(self.reorder_frames + 1)
+            .map(MediaFrame::get_presentation_timestamp_ns)
+            .min()
+            .unwrap_or_default();
+        let dts = self
+            .last_dts_nano
+            .map_or(window_min_pts, |last| last.max(window_min_pts));
+        let mut frame = self.pending.pop_front().unwrap();
+        if dts > frame.get_presentation_timestamp_ns() {
+            tracing::warn!(
+                "h264 stream reorders deeper than {} frames, the dts is after the pts, dts ns: {}, pts ns: {}",
+                self.reorder_frames,
+                dts,
+                frame.get_presentation_timestamp_ns()
+            );
+        }
+        frame.set_decode_timestamp_ns(dts);
+        self.last_dts_nano = Some(dts);
+        frame
+    }
+
+    fn on_sps(&mut self, sps: Sps) {
+        let reorder_frames = reorder_frames(&sps, self.default_reorder_frames);
+        if reorder_frames != self.reorder_frames {
+            tracing::info!(
+                "h264 dts derivation holds {} frames back, was {}, profile idc: {}, pic order cnt type: {}",
+                reorder_frames,
+                self.reorder_frames,
+                sps.profile_idc,
+                sps.pic_order_cnt_type
+            );
+        }
+        self.reorder_frames = reorder_frames;
+        self.sps.insert(sps.seq_parameter_set_id, sps);
+    }
+
+    fn on_nal_units(&mut self, nal_units: &[NalUnit]) {
+        let mut first_slice = None;
+        for nalu in nal_units {
+            match nalu.header.nal_unit_type {
+                NALUType::SPS => match Sps::try_from(nalu) {
+                    Ok(sps) => self.on_sps(sps),
+                    Err(err) => tracing::warn!("parse sps for the h264 dts failed: {}", err),
+                },
+                NALUType::PPS => {
+                    let chroma_format_idc = self
+                        .sps
+                        .values()
+                        .next()
+                        .and_then(Sps::get_chroma_format_idc)
+                        .unwrap_or(ChromaFormatIdc::Chroma420);
+                    match Pps::try_from((chroma_format_idc, nalu)) {
+                        Ok(pps) => {
+                            self.pps.insert(pps.pic_parameter_set_id, pps);
+                        }
+                        Err(err) => tracing::warn!("parse pps for the h264 dts failed: {}", err),
+                    }
+                }
+                t if t.is_vcl() && first_slice.is_none() => first_slice = Some(nalu),
+                _ => {}
+            }
+        }
+        if let Some(slice) = first_slice {
+            self.on_first_slice(slice);
+        }
+    }
+
+    /// raises the reorder depth if the picture is output before more frames decoded ahead of it than that
+    fn on_first_slice(&mut self, slice: &NalUnit) {
+        let Some(pic_order_cnt) = self.pic_order_cnt(slice) else {
+            return;
+        };
+        if slice.header.nal_unit_type == NALUType::IDRSlice {
+            self.recent_pic_order_cnts.clear();
+        }
+        let depth = self
+            .recent_pic_order_cnts
+            .iter()
+            .filter(|recent| **recent > pic_order_cnt)
+            .count();
+        if depth > self.reorder_frames {
+            tracing::warn!(
+                "h264 stream reorders {} frames, more than the {} told, picture order count: {}",
+                depth,
+                self.reorder_frames,
+                pic_order_cnt
+            );
+            self.reorder_frames = depth;
+        }
+        if self.recent_pic_order_cnts.len() == MAX_REORDER_FRAMES {
+            self.recent_pic_order_cnts.pop_front();
+        }
+        self.recent_pic_order_cnts.push_back(pic_order_cnt);
+    }
+
+    fn pic_order_cnt(&mut self, slice: &NalUnit) -> Option<i64> {
+        let prefix = SliceHeaderPrefix::try_from(slice).ok()?;
+        let pps = self.pps.get(&(prefix.pic_parameter_set_id as u8))?;
+        let sps = self.sps.get(&pps.seq_parameter_set_id)?;
+        let idr_pic_flag = slice.header.nal_unit_type == NALUType::IDRSlice;
+        SliceHeaderPicOrder::try_from((slice, sps, pps))
+            .and_then(|header| {
+                self.counter
+                    .next(slice.header.nal_ref_idc, idr_pic_flag, sps, &header)
+            })
+            .inspect_err(|err| {
+                tracing::debug!("picture order count of h264 slice unknown: {}", err);
+            })
+            .ok()
+    }
+}
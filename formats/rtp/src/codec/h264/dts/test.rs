@@ -0,0 +1,276 @@
+#[cfg(test)]
+mod tests {
+    use bitstream_io::{BigEndian, BitWrite, BitWriter};
+    use codec_common::{
+        FrameType, MediaFrameTimestamp,
+        video::{VideoCodecCommon, VideoFrameInfo, VideoFrameUnit},
+    };
+    use codec_h264::{nalu::NalUnit, nalu_header::NaluHeader, sps::Sps};
+    use stream_center::gop::MediaFrame;
+    use tokio_util::bytes::Bytes;
+    use utils::traits::reader::ReadFrom;
+
+    use crate::codec::h264::dts::{DEFAULT_REORDER_FRAMES, H264DtsDeriver, reorder_frames};
+
+    const FRAME_NANO: u64 = 40_000_000;
+
+    type Writer<'a> = BitWriter<&'a mut Vec<u8>, BigEndian>;
+
+    fn write_ue(writer: &mut Writer, value: u64) {
+        let code = value + 1;
+        let bits = 64 - code.leading_zeros();
+        writer.write_var(bits - 1, 0_u64).unwrap();
+        writer.write_var(bits, code).unwrap();
+    }
+
+    fn nalu(nal_header: u8, write: impl FnOnce(&mut Writer)) -> NalUnit {
+        let mut bytes = Vec::new();
+        let mut writer = BitWriter::endian(&mut bytes, BigEndian);
+        write(&mut writer);
+        writer.write_bit(true).unwrap();
+        writer.byte_align().unwrap();
+        NalUnit {
+            header: NaluHeader::try_from(nal_header).unwrap(),
+            body: Bytes::from(bytes),
+        }
+    }
+
+    /// main profile, no vui, frame_num and pic_order_cnt_lsb take 4 and 6 bits
+    fn sps(pic_order_cnt_type: u64) -> NalUnit {
+        nalu(0x67, |writer| {
+            writer.write::<8, u8>(77).unwrap();
+            writer.write::<8, u8>(0).unwrap();
+            writer.write::<8, u8>(30).unwrap();
+            write_ue(writer, 0);
+            write_ue(writer, 0);
+            write_ue(writer, pic_order_cnt_type);
+            if pic_order_cnt_type == 0 {
+                write_ue(writer, 2);
+            }
+            write_ue(writer, 2);
+            writer.write_bit(false).unwrap();
+            write_ue(writer, 19);
+            write_ue(writer, 14);
+            // frame_mbs_only_flag, direct_8x8_inference_flag, frame_cropping_flag, vui_parameters_present_flag
+            writer.write::<4, u8>(0b1100).unwrap();
+        })
+    }
+
+    fn pps() -> NalUnit {
+        nalu(0x68, |writer| {
+            write_ue(writer, 0);
+            write_ue(writer, 0);
+            writer.write::<2, u8>(0).unwrap();
+            write_ue(writer, 0);
+            write_ue(writer, 0);
+            write_ue(writer, 0);
+            writer.write::<3, u8>(0).unwrap();
+            // pic_init_qp_minus26, pic_init_qs_minus26, chroma_qp_index_offset are se(0)
+            writer.write::<3, u8>(0b111).unwrap();
+            writer.write::<3, u8>(0b100).unwrap();
+        })
+    }
+
+    /// `kind` is 'I' for an idr, 'P' or 'B' otherwise, b frames are not referenced.
+    /// the picture order count is `pic_order_cnt_lsb` with pic_order_cnt_type 0,
+    /// the frames are shown `pic_order_cnt_lsb / 2` frames after the idr
+    fn frame(
+        kind: char,
+        frame_num: u64,
+        pic_order_cnt_lsb: u64,
+        pic_order_cnt_type: u64,
+    ) -> MediaFrame {
+        let (nal_header, slice_type) = match kind {
+            'I' => (0x65, 7),
+            'P' => (0x61, 5),
+            _ => (0x01, 6),
+        };
+        let slice = nalu(nal_header, |writer| {
+            write_ue(writer, 0);
+            write_ue(writer, slice_type);
+            write_ue(writer, 0);
+            writer.write_var(4, frame_num).unwrap();
+            if kind == 'I' {
+                write_ue(writer, 0);
+            }
+            if pic_order_cnt_type == 0 {
+                writer.write_var(6, pic_order_cnt_lsb).unwrap();
+            }
+            writer.write::<16, u16>(0xABCD).unwrap();
+        });
+        let mut nal_units = vec![];
+        if kind == 'I' {
+            nal_units.push(sps(pic_order_cnt_type));
+            nal_units.push(pps());
+        }
+        nal_units.push(slice);
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                if kind == 'I' {
+                    FrameType::KeyFrame
+                } else {
+                    FrameType::CodedFrames
+                },
+                MediaFrameTimestamp::with_timestamp_nano(pic_order_cnt_lsb / 2 * FRAME_NANO),
+            ),
+            payload: VideoFrameUnit::H264 { nal_units },
+        }
+    }
+
+    /// I P B B P B B ... in decoding order, I B B P B B P ... in output order
+    fn ipbb_frames() -> Vec<MediaFrame> {
+        let mut frames = vec![frame('I', 0, 0, 0)];
+        for gop in 0..4 {
+            let base = gop * 6;
+            let frame_num = gop + 1;
+            frames.push(frame('P', frame_num, base + 6, 0));
+            frames.push(frame('B', frame_num + 1, base + 2, 0));
+            frames.push(frame('B', frame_num + 1, base + 4, 0));
+        }
+        frames
+    }
+
+    fn derive(deriver: &mut H264DtsDeriver, frames: Vec<MediaFrame>) -> Vec<MediaFrame> {
+        let mut result: Vec<_> = frames
+            .into_iter()
+            .flat_map(|frame| deriver.push(frame))
+            .collect();
+        result.extend(deriver.flush());
+        result
+    }
+
+    /// the SI24 after the frame type and the avc packet type of the tag
+    fn flv_composition_time_ms(frame: &MediaFrame) -> i32 {
+        let tag = frame.to_flv_tag_bytes(4).unwrap();
+        let cts = &tag[13..16];
+        (i32::from_be_bytes([cts[0], cts[1], cts[2], 0])) >> 8
+    }
+
+    #[test]
+    fn b_frames_get_the_dts_in_decoding_order() {
+        let frames = ipbb_frames();
+        let input_pts: Vec<u64> = frames
+            .iter()
+            .map(MediaFrame::get_presentation_timestamp_ns)
+            .collect();
+        // dts = pts makes the frame decoded ahead of the b-frames go back in time
+        assert!(
+            frames
+                .windows(2)
+                .any(|pair| pair[1].get_decode_timestamp_ns() < pair[0].get_decode_timestamp_ns())
+        );
+
+        let mut deriver = H264DtsDeriver::new(DEFAULT_REORDER_FRAMES, None, None);
+        let derived = derive(&mut deriver, frames);
+        assert_eq!(
+            derived
+                .iter()
+                .map(MediaFrame::get_presentation_timestamp_ns)
+                .collect::<Vec<_>>(),
+            input_pts
+        );
+        assert!(
+            derived
+                .windows(2)
+                .all(|pair| pair[0].get_decode_timestamp_ns() <= pair[1].get_decode_timestamp_ns())
+        );
+        for frame in &derived {
+            assert!(frame.get_decode_timestamp_ns() <= frame.get_presentation_timestamp_ns());
+            assert!(flv_composition_time_ms(frame) >= 0);
+        }
+        // the p frames are shown 2 frames after they are decoded
+        assert_eq!(
+            derived[4].get_presentation_timestamp_ms() - derived[4].get_decode_timestamp_ms(),
+            80
+        );
+        assert_eq!(flv_composition_time_ms(&derived[4]), 80);
+    }
+
+    #[test]
+    fn reorder_depth_is_raised_by_the_picture_order_counts() {
+        // told the stream does not reorder
+        let mut deriver = H264DtsDeriver::new(0, None, None);
+        let frames = ipbb_frames();
+        let derived: Vec<_> = frames
+            .into_iter()
+            .take(4)
+            .flat_map(|frame| deriver.push(frame))
+            .collect();
+        // the first b-frame is output before the p frame decoded ahead of it
+        assert_eq!(deriver.reorder_frames(), 1);
+        assert_eq!(derived.len(), 3);
+
+        let mut deriver = H264DtsDeriver::new(0, None, None);
+        let derived = derive(&mut deriver, ipbb_frames());
+        // only the b-frames of the group the reordering is found in are decoded after they are shown
+        let late: Vec<usize> = derived
+            .iter()
+            .enumerate()
+            .filter(|(_, frame)| {
+                frame.get_decode_timestamp_ns() > frame.get_presentation_timestamp_ns()
+            })
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(late, [2, 3]);
+        assert!(
+            derived
+                .windows(2)
+                .all(|pair| pair[0].get_decode_timestamp_ns() <= pair[1].get_decode_timestamp_ns())
+        );
+    }
+
+    #[test]
+    fn streams_that_never_reorder_pass_as_they_are() {
+        let mut deriver = H264DtsDeriver::new(DEFAULT_REORDER_FRAMES, None, None);
+        for (i, frame) in (0..10)
+            .map(|i| frame(if i == 0 { 'I' } else { 'P' }, i, i * 2, 2))
+            .enumerate()
+        {
+            let pts = frame.get_presentation_timestamp_ns();
+            let passed = deriver.push(frame);
+            assert_eq!(deriver.reorder_frames(), 0);
+            assert_eq!(passed.len(), 1, "frame {}", i);
+            assert_eq!(passed[0].get_decode_timestamp_ns(), pts);
+        }
+        assert!(deriver.flush().is_empty());
+    }
+
+    #[test]
+    fn reorder_depth_from_the_vui() {
+        // x264 high profile, the vui has the bitstream restriction
+        let bytes: Vec<u8> = (0..54)
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(
+                    &"6764002cacd940780227e5c044000003000400000300c83c60c658"[i..i + 2],
+                    16,
+                )
+                .unwrap()
+            })
+            .collect();
+        let x264 = Sps::try_from(&NalUnit::read_from(&mut &bytes[..]).unwrap()).unwrap();
+        let told = x264.max_num_reorder_frames().unwrap() as usize;
+        assert_eq!(reorder_frames(&x264, told + 3), told);
+        assert_eq!(
+            H264DtsDeriver::new(told + 3, Some(x264), None).reorder_frames(),
+            told
+        );
+
+        let baseline = Sps::try_from(&sps_nalu_with_profile(66)).unwrap();
+        assert_eq!(reorder_frames(&baseline, DEFAULT_REORDER_FRAMES), 0);
+        let main = Sps::try_from(&sps(0)).unwrap();
+        assert_eq!(
+            reorder_frames(&main, DEFAULT_REORDER_FRAMES),
+            DEFAULT_REORDER_FRAMES
+        );
+    }
+
+    fn sps_nalu_with_profile(profile_idc: u8) -> NalUnit {
+        let mut nalu = sps(0);
+        let mut body = nalu.body.to_vec();
+        body[0] = profile_idc;
+        nalu.body = Bytes::from(body);
+        nalu
+    }
+}
@@ -10,9 +10,9 @@ use super::paramters::packetization_mode::PacketizationMode;
 pub enum RtpH264Error {
     #[error("io error: {0}")]
     Io(#[from] io::Error),
-    #[error("h264 codec error: {0:?}")]
+    #[error("h264 codec error: {0}")]
     H264CodecError(#[from] H264CodecError),
-    #[error("RTP error: {0:?}")]
+    #[error("RTP error: {0}")]
     RtpError(#[from] RtpError),
     #[error("invalid mtu: {0}")]
     InvalidMTU(usize),
@@ -22,6 +22,8 @@ pub enum RtpH264Error {
     InvalidH264PacketType(u8),
     #[error("rtp sequencing h264 nal from fu packets failed: {0}")]
     SequenceFUPacketsFailed(String),
+    #[error("reassembled fragments too large: {size} bytes, limit: {limit}")]
+    FragmentsTooLarge { size: usize, limit: usize },
     #[error("unexpected packet type: {0}")]
     UnexpectedPacketType(String),
     #[error("unsupported packetization mode: {0}")]
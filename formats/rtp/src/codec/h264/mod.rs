@@ -1,4 +1,5 @@
 pub mod aggregation;
+pub mod dts;
 pub mod errors;
 pub mod fragmented;
 pub mod packet;
@@ -1,5 +1,7 @@
 pub mod packetizer;
 pub mod sequencer;
+#[cfg(test)]
+mod test;
 use super::{RtpH264NalUnit, errors::RtpH264Error};
 use crate::{
     header::RtpHeader,
@@ -7,11 +9,14 @@ use crate::{
     util::{RtpPacketTrait, RtpPaddedPacketTrait, padding::rtp_need_padding},
 };
 use std::io::{self};
-use tokio_util::bytes::{Buf, Bytes};
-use utils::traits::{
-    dynamic_sized_packet::DynamicSizedPacket,
-    reader::{ReadFrom, ReadRemainingFrom},
-    writer::WriteTo,
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use utils::{
+    bytes::pool,
+    traits::{
+        dynamic_sized_packet::DynamicSizedPacket,
+        reader::{ReadFrom, ReadRemainingFrom},
+        writer::WriteTo,
+    },
 };
 
 #[derive(Debug)]
@@ -82,14 +87,26 @@ impl<W: io::Write> WriteTo<W> for RtpH264Packet {
     }
 }
 
-impl TryInto<RtpTrivialPacket> for RtpH264Packet {
-    type Error = RtpH264Error;
-    fn try_into(self) -> Result<RtpTrivialPacket, Self::Error> {
-        let mut payload = Vec::with_capacity(1500);
-        self.payload.write_to(&mut payload)?;
+impl RtpH264Packet {
+    /// the trivial packet with the payload serialized at the end of `buffer` and split off of it,
+    /// so the packets of a frame share the buffer
+    pub fn into_trivial_packet_in(
+        self,
+        buffer: &mut BytesMut,
+    ) -> Result<RtpTrivialPacket, RtpH264Error> {
+        buffer.reserve(self.payload.get_packet_bytes_count());
+        self.payload.write_to(&mut buffer.writer())?;
         Ok(RtpTrivialPacket {
             header: self.header,
-            payload: Bytes::from_owner(payload),
+            payload: buffer.split().freeze(),
         })
     }
 }
+
+impl TryInto<RtpTrivialPacket> for RtpH264Packet {
+    type Error = RtpH264Error;
+    fn try_into(self) -> Result<RtpTrivialPacket, Self::Error> {
+        let mut buffer = pool::take(self.payload.get_packet_bytes_count());
+        self.into_trivial_packet_in(&mut buffer)
+    }
+}
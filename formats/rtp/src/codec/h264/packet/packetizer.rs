@@ -24,6 +24,7 @@ use num::ToPrimitive;
 use std::cmp;
 use std::io::Read;
 use tokio_util::bytes::{Buf, Bytes};
+use utils::bytes::pool;
 use utils::random::{random_u16, random_u32};
 use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
 use utils::traits::writer::WriteTo;
@@ -297,7 +298,7 @@ impl RtpTrivialPacketPacketizer for RtpH264PacketPacketizer {
     fn build(&mut self) -> Result<Vec<RtpTrivialPacket>, RtpError> {
         let packets = self
             .make_packets()
-            .map_err(|e| RtpError::H264PacketizationFailed(format!("{}", e)))?;
+            .map_err(|err| RtpError::H264PacketizationFailed(Box::new(err)))?;
         let packets_cnt = packets.len();
 
         let mut header = self.rtp_header().clone();
@@ -307,23 +308,26 @@ impl RtpTrivialPacketPacketizer for RtpH264PacketPacketizer {
             self.rtp_timestamp_base,
             self.rtp_clockrate,
         ) as u32;
+        // the payloads of the frame are serialized into one buffer, back to the pool once
+        // all of its packets are sent and no more kept for retransmission
+        let mut buffer = pool::take(
+            packets
+                .iter()
+                .map(|item| item.get_packet_bytes_count())
+                .sum(),
+        );
         let mut result = vec![];
         for (idx, item) in packets.into_iter().enumerate() {
             let marker = idx == packets_cnt - 1;
-            let trivial_packet: RtpTrivialPacket = RtpH264Packet {
+            let trivial_packet = RtpH264Packet {
                 header: RtpHeader {
                     marker,
                     ..header.clone()
                 },
                 payload: item,
             }
-            .try_into()
-            .map_err(|err| {
-                RtpError::H264PacketizationFailed(format!(
-                    "convert to trivial packet failed: {}",
-                    err
-                ))
-            })?;
+            .into_trivial_packet_in(&mut buffer)
+            .map_err(|err| RtpError::H264PacketizationFailed(Box::new(err)))?;
             header.sequence_number = header.sequence_number.wrapping_add(1);
             result.push(trivial_packet);
         }
@@ -352,9 +356,11 @@ impl RtpTrivialPacketPacketizer for RtpH264PacketPacketizer {
             },
             _ => {
                 debug_assert!(false, "only h264 video item is supported");
-                Err(RtpError::H264PacketizationFailed(
-                    "only h264 video item is supported".to_string(),
-                ))
+                Err(RtpError::H264PacketizationFailed(Box::new(
+                    RtpH264Error::UnexpectedPacketType(
+                        "only h264 video item is supported".to_string(),
+                    ),
+                )))
             }
         }
     }
@@ -368,10 +374,20 @@ impl RtpTrivialPacketPacketizer for RtpH264PacketPacketizer {
         self.rtp_clockrate
     }
 
+    fn set_rtp_clockrate(&mut self, clockrate: u64) {
+        self.rtp_clockrate = clockrate;
+    }
+
     fn set_frame_timestamp(&mut self, timestamp: u64) {
-        self.last_frame_timestamp = Some(timestamp);
+        self.set_frame_timestamps(timestamp, timestamp);
+    }
+
+    /// the pts of a B-frame can be less than the pts of the first frame but never its dts,
+    /// so the rtp timestamps of the pts are based on the first dts
+    fn set_frame_timestamps(&mut self, pts_ms: u64, dts_ms: u64) {
+        self.last_frame_timestamp = Some(pts_ms);
         if self.first_frame_timestamp.is_none() {
-            self.first_frame_timestamp = Some(timestamp)
+            self.first_frame_timestamp = Some(dts_ms)
         }
     }
 
@@ -0,0 +1,84 @@
+use crate::codec::h264::{errors::RtpH264Error, packet::sequencer::RtpH264BufferItem};
+use codec_h264::{nalu_type::NALUType, slice_header::SliceHeaderPrefix};
+use utils::traits::buffer::GenericFragmentComposer;
+
+/// Groups nal units into access units, the boundaries are found by, in order:
+/// 1. the rtp marker bit, which is set on the last packet of an access unit
+/// 2. a VCL nal unit with first_mb_in_slice equal to 0,
+///    or an AUD/SPS/PPS/SEI nal unit, following a VCL nal unit
+///    @see: Recommendation  ITU-T H.264 (V15) (08/2024) Section 7.4.1.2.3
+/// 3. a change of the rtp timestamp
+#[derive(Default)]
+pub struct AccessUnitGrouper {
+    buffer: Option<RtpH264BufferItem>,
+}
+
+impl AccessUnitGrouper {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// takes the pending nal units out, they might not be a whole access unit
+    pub fn flush(&mut self) -> Option<RtpH264BufferItem> {
+        self.buffer.take()
+    }
+
+    fn starts_new_access_unit(buffer: &RtpH264BufferItem, packet: &RtpH264BufferItem) -> bool {
+        if packet.rtp_header.timestamp != buffer.rtp_header.timestamp {
+            return true;
+        }
+        if !buffer
+            .nal_units
+            .iter()
+            .any(|nalu| nalu.header.nal_unit_type.is_vcl())
+        {
+            return false;
+        }
+        let Some(first) = packet.nal_units.first() else {
+            return false;
+        };
+        match first.header.nal_unit_type {
+            NALUType::AccessUnitDelimiter | NALUType::SPS | NALUType::PPS | NALUType::SEI => true,
+            t if t.is_vcl() => SliceHeaderPrefix::try_from(first)
+                .map(|prefix| prefix.is_first_slice())
+                .unwrap_or_else(|err| {
+                    tracing::warn!("parse slice header failed: {}, nalu: {:?}", err, first);
+                    false
+                }),
+            _ => false,
+        }
+    }
+}
+
+impl GenericFragmentComposer for AccessUnitGrouper {
+    type Error = RtpH264Error;
+    type In = RtpH264BufferItem;
+    type Out = Vec<RtpH264BufferItem>;
+    fn enqueue(&mut self, packet: Self::In) -> Result<Option<Self::Out>, Self::Error> {
+        let mut result = Vec::new();
+        // the marker bit is meaningless for interleaved items as they are not in decoding order
+        let marker = packet.rtp_header.marker && packet.decode_order_number.is_none();
+        let merge = self
+            .buffer
+            .as_ref()
+            .is_some_and(|buffer| !Self::starts_new_access_unit(buffer, &packet));
+        if merge {
+            if let Some(buffer) = self.buffer.as_mut() {
+                buffer.merge(packet);
+            }
+        } else if let Some(mut out) = self.buffer.replace(packet) {
+            out.access_unit_complete = true;
+            result.push(out);
+        }
+
+        if marker && let Some(mut out) = self.buffer.take() {
+            out.access_unit_complete = true;
+            result.push(out);
+        }
+
+        if result.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(result))
+    }
+}
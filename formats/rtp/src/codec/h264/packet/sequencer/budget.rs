@@ -0,0 +1,120 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use super::RtpH264BufferItem;
+
+pub const DEFAULT_FRAGMENTS_BUFFER_BYTES: usize = 2 * 1024 * 1024;
+pub const DEFAULT_DE_INTERLEAVING_BUFFER_BYTES: usize = 4 * 1024 * 1024;
+pub const DEFAULT_DECODER_BUFFER_BYTES: usize = 4 * 1024 * 1024;
+
+/// byte budgets of the buffers inside the h264 sequencer,
+/// the oldest items are evicted when a budget is exceeded
+#[derive(Debug, Clone, Copy)]
+pub struct RtpH264BufferConfig {
+    /// all the nal units being reassembled from fu packets
+    pub fragments_bytes: usize,
+    pub de_interleaving_bytes: usize,
+    pub decoder_bytes: usize,
+    /// evict the items without an idr slice first
+    pub retain_idr: bool,
+}
+
+impl Default for RtpH264BufferConfig {
+    fn default() -> Self {
+        Self {
+            fragments_bytes: DEFAULT_FRAGMENTS_BUFFER_BYTES,
+            de_interleaving_bytes: DEFAULT_DE_INTERLEAVING_BUFFER_BYTES,
+            decoder_bytes: DEFAULT_DECODER_BUFFER_BYTES,
+            retain_idr: true,
+        }
+    }
+}
+
+/// gauges of the bytes held by each buffer of one sequencer
+#[derive(Debug, Default)]
+pub struct RtpH264BufferMetrics {
+    fragments_bytes: AtomicUsize,
+    de_interleaving_bytes: AtomicUsize,
+    decoder_bytes: AtomicUsize,
+    discontinuities: AtomicU64,
+}
+
+impl RtpH264BufferMetrics {
+    pub fn fragments_bytes(&self) -> usize {
+        self.fragments_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn de_interleaving_bytes(&self) -> usize {
+        self.de_interleaving_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn decoder_bytes(&self) -> usize {
+        self.decoder_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_buffered(&self) -> usize {
+        self.fragments_bytes() + self.de_interleaving_bytes() + self.decoder_bytes()
+    }
+
+    /// times any of the buffers evicted data to fit in its budget
+    pub fn discontinuities(&self) -> u64 {
+        self.discontinuities.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_fragments_bytes(&self, bytes: usize) {
+        self.fragments_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_de_interleaving_bytes(&self, bytes: usize) {
+        self.de_interleaving_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_decoder_bytes(&self, bytes: usize) {
+        self.decoder_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_discontinuity(&self) {
+        self.discontinuities.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// evicts buffered items until `incoming` more bytes fit in the budget,
+/// the item taking the place of an evicted one is marked as discontinuous.
+/// returns the bytes evicted and whether the incoming item directly follows an evicted one
+pub(crate) fn evict_for_incoming<T>(
+    buffer: &mut VecDeque<T>,
+    buffered_bytes: usize,
+    incoming: usize,
+    budget: usize,
+    retain_idr: bool,
+    item_of: impl Fn(&mut T) -> &mut RtpH264BufferItem,
+) -> (usize, bool) {
+    let mut evicted_bytes = 0;
+    let mut follows_evicted = false;
+    while !buffer.is_empty() && buffered_bytes - evicted_bytes + incoming > budget {
+        let index = if retain_idr {
+            buffer
+                .iter_mut()
+                .position(|item| !item_of(item).is_idr)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        let mut evicted = buffer.remove(index).unwrap();
+        let evicted = item_of(&mut evicted);
+        evicted_bytes += evicted.size();
+        tracing::warn!(
+            "evicted item from rtp h264 buffer because of byte budget: {}, rtp_header: {:?}, is_idr: {}",
+            budget,
+            evicted.rtp_header,
+            evicted.is_idr
+        );
+        match buffer.get_mut(index) {
+            Some(next) => item_of(next).discontinuity = true,
+            None => follows_evicted = true,
+        }
+    }
+    (evicted_bytes, follows_evicted)
+}
@@ -1,9 +1,12 @@
-use super::{DEFAULT_BUFFER_CAPACITY, RtpH264BufferItem};
+use super::{
+    RtpH264BufferItem,
+    budget::{self, RtpH264BufferConfig, RtpH264BufferMetrics},
+};
 use crate::{
     codec::h264::{paramters::RtpH264Fmtp, util::don_diff},
     errors::RtpError,
 };
-use std::{collections::VecDeque, time};
+use std::{collections::VecDeque, sync::Arc, time};
 use utils::traits::buffer::GenericSequencer;
 
 #[derive(Debug, Default)]
@@ -33,12 +36,25 @@ pub struct DeInterleavingBuffer {
     initial_buffering_until: Option<time::Instant>,
     pdon: u64,
     buffer: VecDeque<(u64, RtpH264BufferItem)>,
+    buffer_bytes: usize,
+    config: RtpH264BufferConfig,
+    metrics: Arc<RtpH264BufferMetrics>,
 }
 impl DeInterleavingBuffer {
-    pub fn new(parameters: RtpH264DeInterleavingParameters) -> Self {
-        let deint_buf_size_req = parameters
-            .sprop_deint_buf_req
-            .unwrap_or(DEFAULT_BUFFER_CAPACITY as u64);
+    pub fn new(
+        parameters: RtpH264DeInterleavingParameters,
+        config: RtpH264BufferConfig,
+        metrics: Arc<RtpH264BufferMetrics>,
+    ) -> Self {
+        if let Some(deint_buf_req) = parameters.sprop_deint_buf_req
+            && deint_buf_req > config.de_interleaving_bytes as u64
+        {
+            tracing::warn!(
+                "sprop-deint-buf-req: {} exceeds the de-interleaving buffer budget: {}, nal units might be evicted",
+                deint_buf_req,
+                config.de_interleaving_bytes
+            );
+        }
         let initial_buf_time = parameters.sprop_init_buf_time;
         Self {
             parameters,
@@ -46,9 +62,17 @@ impl DeInterleavingBuffer {
                 time::Instant::now() + time::Duration::from_millis(time / 9_0000 * 1000)
             }),
             pdon: 0,
-            buffer: VecDeque::with_capacity(deint_buf_size_req as usize / 1000 / 10),
+            buffer: VecDeque::new(),
+            buffer_bytes: 0,
+            config,
+            metrics,
         }
     }
+
+    fn update_buffer_bytes(&mut self) {
+        self.buffer_bytes = self.buffer.iter().map(|(_, item)| item.size()).sum();
+        self.metrics.set_de_interleaving_bytes(self.buffer_bytes);
+    }
     pub fn calculate_abs_don(&mut self) {
         if self.buffer.is_empty() {
             return;
@@ -122,9 +146,24 @@ impl GenericSequencer for DeInterleavingBuffer {
     type In = RtpH264BufferItem;
     type Out = RtpH264BufferItem;
     type Error = RtpError;
-    fn enqueue(&mut self, packet: Self::In) -> Result<(), Self::Error> {
+    fn enqueue(&mut self, mut packet: Self::In) -> Result<(), Self::Error> {
+        let size = packet.size();
+        let (evicted_bytes, follows_evicted) = budget::evict_for_incoming(
+            &mut self.buffer,
+            self.buffer_bytes,
+            size,
+            self.config.de_interleaving_bytes,
+            self.config.retain_idr,
+            |(_, item)| item,
+        );
+        if evicted_bytes > 0 {
+            self.metrics.on_discontinuity();
+        }
+        packet.discontinuity |= follows_evicted;
+
         self.buffer.push_back((0, packet));
         self.calculate_abs_don();
+        self.update_buffer_bytes();
         Ok(())
     }
 
@@ -199,6 +238,7 @@ impl GenericSequencer for DeInterleavingBuffer {
         if let Some(item) = result.last() {
             self.pdon = item.decode_order_number.unwrap() as u64;
         }
+        self.update_buffer_bytes();
         result
     }
 }
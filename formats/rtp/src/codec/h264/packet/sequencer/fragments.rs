@@ -1,13 +1,15 @@
+use super::budget::{RtpH264BufferConfig, RtpH264BufferMetrics};
 use crate::{
     codec::h264::{
         errors::RtpH264Error, fragmented::FragmentedUnit, packet::sequencer::RtpH264BufferItem,
     },
     header::RtpHeader,
 };
-use codec_h264::nalu::NalUnit;
+use codec_h264::{nalu::NalUnit, nalu_type::NALUType};
 use std::{
     collections::HashMap,
     io::{self, Read},
+    sync::Arc,
 };
 use tokio_util::bytes::{BufMut, BytesMut};
 use utils::traits::{buffer::GenericFragmentComposer, reader::ReadFrom};
@@ -15,17 +17,87 @@ use utils::traits::{buffer::GenericFragmentComposer, reader::ReadFrom};
 pub struct FragmentItem {
     fragment: BytesMut,
     don: Option<u16>,
+    /// the order the first fragment arrived in, the smallest is evicted first
+    started: u64,
+    /// the rtp sequence number the following fragment must carry
+    next_sequence_number: u16,
 }
+
+impl FragmentItem {
+    fn is_idr(&self) -> bool {
+        self.fragment
+            .first()
+            .is_some_and(|header| header & 0x1F == u8::from(NALUType::IDRSlice))
+    }
+}
+
 pub struct RtpH264FragmentsBuffer {
     nal_fragments: HashMap<u32, FragmentItem>,
     fragment_buffer_capacity: usize,
+    config: RtpH264BufferConfig,
+    metrics: Arc<RtpH264BufferMetrics>,
+    started_cnt: u64,
+    /// a nal unit was evicted, the next composed one is marked as discontinuous
+    discontinuity: bool,
 }
 
 impl RtpH264FragmentsBuffer {
-    pub fn new(capacity: usize) -> Self {
+    /// `capacity` caps a single nal unit, `config.fragments_bytes` caps all the nal units in reassembling
+    pub fn new(
+        capacity: usize,
+        config: RtpH264BufferConfig,
+        metrics: Arc<RtpH264BufferMetrics>,
+    ) -> Self {
         Self {
             nal_fragments: HashMap::new(),
             fragment_buffer_capacity: capacity,
+            config,
+            metrics,
+            started_cnt: 0,
+            discontinuity: false,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.fragment_buffer_capacity
+    }
+
+    pub fn buffered_bytes(&self) -> usize {
+        self.nal_fragments
+            .values()
+            .map(|item| item.fragment.len())
+            .sum()
+    }
+
+    /// evicts the oldest nal units other than the one of `timestamp` until the budget is met
+    fn evict_over_budget(&mut self, timestamp: u32) {
+        let mut buffered_bytes = self.buffered_bytes();
+        let mut evicted = false;
+        while buffered_bytes > self.config.fragments_bytes {
+            let oldest = |retain_idr: bool| {
+                self.nal_fragments
+                    .iter()
+                    .filter(|(key, item)| **key != timestamp && !(retain_idr && item.is_idr()))
+                    .min_by_key(|(_, item)| item.started)
+                    .map(|(key, _)| *key)
+            };
+            let Some(key) = oldest(self.config.retain_idr).or_else(|| oldest(false)) else {
+                break;
+            };
+            let dropped = self.nal_fragments.remove(&key).unwrap();
+            buffered_bytes -= dropped.fragment.len();
+            evicted = true;
+            tracing::warn!(
+                "evicted fragments because of byte budget: {}, rtp timestamp: {}, length: {}, don: {:?}",
+                self.config.fragments_bytes,
+                key,
+                dropped.fragment.len(),
+                dropped.don
+            );
+        }
+        if evicted {
+            self.discontinuity = true;
+            self.metrics.on_discontinuity();
         }
     }
 }
@@ -70,6 +142,8 @@ impl GenericFragmentComposer for RtpH264FragmentsBuffer {
                     .put_u8((indicator.nal_ref_idc << 5) | (fu_header.nalu_type)); // F and NRI from indicator, and NaluType from fu_header
                 fragmentation_buffer.fragment.extend_from_slice(&payload);
                 fragmentation_buffer.don = don;
+                fragmentation_buffer.next_sequence_number =
+                    item.rtp_header.sequence_number.wrapping_add(1);
             } else {
                 // happy path, insert new fragment item
                 let mut buffer = BytesMut::new();
@@ -78,25 +152,60 @@ impl GenericFragmentComposer for RtpH264FragmentsBuffer {
                 let fragment = FragmentItem {
                     fragment: buffer,
                     don,
+                    started: self.started_cnt,
+                    next_sequence_number: item.rtp_header.sequence_number.wrapping_add(1),
                 };
+                self.started_cnt += 1;
                 self.nal_fragments
                     .insert(item.rtp_header.timestamp, fragment);
             }
         } else if let Some(fragmentation_buffer) =
             self.nal_fragments.get_mut(&item.rtp_header.timestamp)
         {
+            let expected = fragmentation_buffer.next_sequence_number;
+            let sequence_number = item.rtp_header.sequence_number;
+            if sequence_number != expected {
+                if (sequence_number.wrapping_sub(expected) as i16) < 0 {
+                    // a fragment already composed arrives again
+                    tracing::debug!(
+                        "dropping stale FU packet, sequence number: {}, expected: {}",
+                        sequence_number,
+                        expected
+                    );
+                    return Ok(None);
+                }
+                // fragments in between are lost, the nal unit can not be composed
+                let dropped = self.nal_fragments.remove(&item.rtp_header.timestamp);
+                self.discontinuity = true;
+                self.metrics.on_discontinuity();
+                self.metrics.set_fragments_bytes(self.buffered_bytes());
+                return Err(RtpH264Error::SequenceFUPacketsFailed(format!(
+                    "FU packets are not continuous, expect sequence number: {}, got: {}, dropping {} bytes, fu_header: {:?}",
+                    expected,
+                    sequence_number,
+                    dropped.map_or(0, |item| item.fragment.len()),
+                    fu_header
+                )));
+            }
+            fragmentation_buffer.next_sequence_number = sequence_number.wrapping_add(1);
             // happy path, not first fragment, and already have fragment with the same timestamp
-            if fragmentation_buffer.fragment.len() >= self.fragment_buffer_capacity
-                && !fu_header.end_bit
-            {
-                // not going to end, but exceeds the fragment buffer
-                let dropped_length = fragmentation_buffer.fragment.len() + payload.len();
+            let buffered_length = fragmentation_buffer.fragment.len() + payload.len();
+            if buffered_length > self.fragment_buffer_capacity {
+                // exceeds the fragment buffer, the whole nalu is dropped
                 let dropped_don = fragmentation_buffer.don;
                 self.nal_fragments.remove(&item.rtp_header.timestamp);
-                return Err(RtpH264Error::SequenceFUPacketsFailed(format!(
+                self.metrics.set_fragments_bytes(self.buffered_bytes());
+                tracing::error!(
                     "fragment buffer exceeds capacity: {}, dropping all data, buffer length: {}, don: {:?}, fu_header: {:?}",
-                    self.fragment_buffer_capacity, dropped_length, dropped_don, fu_header
-                )));
+                    self.fragment_buffer_capacity,
+                    buffered_length,
+                    dropped_don,
+                    fu_header
+                );
+                return Err(RtpH264Error::FragmentsTooLarge {
+                    size: buffered_length,
+                    limit: self.fragment_buffer_capacity,
+                });
             } else {
                 // happy path, just extend fragmentation
                 fragmentation_buffer.fragment.extend_from_slice(&payload);
@@ -112,6 +221,8 @@ impl GenericFragmentComposer for RtpH264FragmentsBuffer {
             )));
         }
 
+        self.evict_over_budget(item.rtp_header.timestamp);
+
         if fu_header.end_bit {
             // fragment of this timestamp should be complete
             if let Some(fragmentation_buffer) =
@@ -119,10 +230,12 @@ impl GenericFragmentComposer for RtpH264FragmentsBuffer {
             {
                 // happy path, gather fragmentation and try to parse as nalu
                 let mut reader = io::Cursor::new(fragmentation_buffer.fragment.as_ref());
+                self.metrics.set_fragments_bytes(self.buffered_bytes());
                 let nalu = NalUnit::read_from(reader.by_ref())?;
                 let don = fragmentation_buffer.don;
-                let item =
+                let mut item =
                     RtpH264BufferItem::new(vec![nalu], item.rtp_header, don, None, None, None);
+                item.discontinuity = std::mem::take(&mut self.discontinuity);
 
                 return Ok(Some(item));
             } else {
@@ -133,6 +246,171 @@ impl GenericFragmentComposer for RtpH264FragmentsBuffer {
                 )));
             }
         }
+        self.metrics.set_fragments_bytes(self.buffered_bytes());
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::bytes::Bytes;
+    use utils::traits::buffer::GenericFragmentComposer;
+
+    use super::{RtpH264FragmentsBuffer, RtpH264FragmentsBufferItem};
+    use crate::{
+        codec::h264::{
+            errors::RtpH264Error,
+            fragmented::{
+                FUAPacket, FUHeader, FragmentationUnitPacketType, FragmentedUnit, FuIndicator,
+            },
+            packet::sequencer::budget::RtpH264BufferConfig,
+        },
+        header::RtpHeader,
+    };
+
+    fn fu_a(
+        sequence_number: u16,
+        start_bit: bool,
+        end_bit: bool,
+        payload_size: usize,
+    ) -> RtpH264FragmentsBufferItem {
+        fragment(3000, sequence_number, 5, start_bit, end_bit, payload_size)
+    }
+
+    fn fragment(
+        timestamp: u32,
+        sequence_number: u16,
+        nalu_type: u8,
+        start_bit: bool,
+        end_bit: bool,
+        payload_size: usize,
+    ) -> RtpH264FragmentsBufferItem {
+        RtpH264FragmentsBufferItem {
+            rtp_header: RtpHeader {
+                timestamp,
+                sequence_number,
+                ..Default::default()
+            },
+            fragment: FragmentedUnit::FuA(FUAPacket {
+                indicator: FuIndicator {
+                    forbidden_zero_bit: false,
+                    nal_ref_idc: 3,
+                    fu_type: FragmentationUnitPacketType::FUA,
+                },
+                fu_header: FUHeader {
+                    start_bit,
+                    end_bit,
+                    reserved_bit: false,
+                    nalu_type,
+                },
+                payload: Bytes::from(vec![0x88; payload_size]),
+            }),
+        }
+    }
+
+    #[test]
+    fn oversize_fragments_are_rejected() {
+        let mut buffer = RtpH264FragmentsBuffer::new(3000, Default::default(), Default::default());
+        assert!(matches!(
+            buffer.enqueue(fu_a(0, true, false, 1400)),
+            Ok(None)
+        ));
+        assert!(matches!(
+            buffer.enqueue(fu_a(1, false, false, 1400)),
+            Ok(None)
+        ));
+        match buffer.enqueue(fu_a(2, false, true, 1400)) {
+            Err(RtpH264Error::FragmentsTooLarge { size, limit }) => {
+                assert_eq!(size, 1 + 1400 * 3);
+                assert_eq!(limit, 3000);
+            }
+            _ => panic!("expect FragmentsTooLarge"),
+        }
+        // the buffer is dropped, the following fragment has no start
+        assert!(buffer.enqueue(fu_a(3, false, true, 10)).is_err());
+    }
+
+    #[test]
+    fn fragments_within_capacity_are_composed() {
+        let mut buffer = RtpH264FragmentsBuffer::new(3000, Default::default(), Default::default());
+        assert!(matches!(
+            buffer.enqueue(fu_a(0, true, false, 1000)),
+            Ok(None)
+        ));
+        let item = buffer.enqueue(fu_a(1, false, true, 1000)).unwrap().unwrap();
+        assert!(item.is_idr);
+        assert_eq!(item.nal_units.len(), 1);
+    }
+
+    #[test]
+    fn fragments_with_a_gap_are_dropped() {
+        let mut buffer = RtpH264FragmentsBuffer::new(3000, Default::default(), Default::default());
+        assert!(matches!(
+            buffer.enqueue(fu_a(0, true, false, 100)),
+            Ok(None)
+        ));
+        assert!(matches!(
+            buffer.enqueue(fu_a(1, false, false, 100)),
+            Ok(None)
+        ));
+        // a duplicate of a composed fragment is ignored
+        assert!(matches!(
+            buffer.enqueue(fu_a(1, false, false, 100)),
+            Ok(None)
+        ));
+        // the fragment of sequence number 2 is lost
+        assert!(matches!(
+            buffer.enqueue(fu_a(3, false, false, 100)),
+            Err(RtpH264Error::SequenceFUPacketsFailed(_))
+        ));
+        assert_eq!(buffer.buffered_bytes(), 0);
+        assert_eq!(buffer.metrics.discontinuities(), 1);
+        // the end fragment has nothing to complete
+        assert!(buffer.enqueue(fu_a(4, false, true, 100)).is_err());
+
+        // the next nal unit is composed and marked as discontinuous
+        assert!(matches!(
+            buffer.enqueue(fu_a(5, true, false, 100)),
+            Ok(None)
+        ));
+        let item = buffer.enqueue(fu_a(6, false, true, 100)).unwrap().unwrap();
+        assert!(item.discontinuity);
+        assert_eq!(item.nal_units.len(), 1);
+    }
+
+    #[test]
+    fn stale_fragments_are_evicted_by_bytes() {
+        let config = RtpH264BufferConfig {
+            fragments_bytes: 3000,
+            ..Default::default()
+        };
+        let mut buffer = RtpH264FragmentsBuffer::new(3000, config, Default::default());
+        // an idr nal unit and a non-idr one never get their end fragments
+        assert!(matches!(
+            buffer.enqueue(fragment(1000, 0, 5, true, false, 1000)),
+            Ok(None)
+        ));
+        assert!(matches!(
+            buffer.enqueue(fragment(2000, 1, 1, true, false, 1000)),
+            Ok(None)
+        ));
+        assert_eq!(buffer.buffered_bytes(), 2002);
+
+        // the non-idr one is evicted first even though it is newer
+        assert!(matches!(
+            buffer.enqueue(fragment(3000, 2, 1, true, false, 1000)),
+            Ok(None)
+        ));
+        assert!(buffer.nal_fragments.contains_key(&1000));
+        assert!(!buffer.nal_fragments.contains_key(&2000));
+        assert_eq!(buffer.metrics.fragments_bytes(), 2002);
+        assert_eq!(buffer.metrics.discontinuities(), 1);
+
+        let item = buffer
+            .enqueue(fragment(3000, 3, 1, false, true, 10))
+            .unwrap()
+            .unwrap();
+        assert!(item.discontinuity);
+        assert_eq!(buffer.metrics.fragments_bytes(), 1001);
+    }
+}
@@ -1,7 +1,7 @@
 use super::RtpH264Packet;
 use crate::codec::h264::aggregation::{AggregatedPayload, AggregationPacketType};
+use crate::codec::h264::packet::sequencer::access_unit_grouper::AccessUnitGrouper;
 use crate::codec::h264::packet::sequencer::fragments::RtpH264FragmentsBufferItem;
-use crate::codec::h264::packet::sequencer::timestamp_grouper::TimestampGrouper;
 use crate::{
     codec::h264::{
         RtpH264NalUnit,
@@ -15,15 +15,20 @@ use crate::{
     header::RtpHeader,
     packet::sequencer::{RtpBufferItem, RtpBufferVideoItem, RtpBufferedSequencer},
 };
+use budget::{RtpH264BufferConfig, RtpH264BufferMetrics};
 use codec_h264::{nalu::NalUnit, nalu_type::NALUType, pps::Pps, sps::Sps};
 use de_interleaving::{DeInterleavingBuffer, RtpH264DeInterleavingParameters};
 use fragments::RtpH264FragmentsBuffer;
 use std::collections::VecDeque;
+use std::sync::Arc;
 use std::vec;
 use utils::traits::buffer::{GenericFragmentComposer, GenericSequencer};
+pub mod access_unit_grouper;
+pub mod budget;
 pub mod de_interleaving;
 pub mod fragments;
-pub mod timestamp_grouper;
+#[cfg(test)]
+mod test;
 
 #[derive(Debug, Clone)]
 pub struct RtpH264BufferItem {
@@ -34,6 +39,10 @@ pub struct RtpH264BufferItem {
     pub rtp_header: RtpHeader,
     pub decode_order_number: Option<u16>,
     pub timestamp_offset: Option<u32>,
+    /// true if the nal units are known to be a whole access unit
+    pub access_unit_complete: bool,
+    /// true if data before this item was evicted by a buffer budget
+    pub discontinuity: bool,
 }
 
 impl RtpH264BufferItem {
@@ -57,9 +66,21 @@ impl RtpH264BufferItem {
             rtp_header: rtp_header.clone(),
             decode_order_number,
             timestamp_offset,
+            access_unit_complete: false,
+            discontinuity: false,
         }
     }
 
+    /// bytes of the nal units, including the attached parameter sets
+    pub fn size(&self) -> usize {
+        self.nal_units
+            .iter()
+            .chain(self.sps.iter())
+            .chain(self.pps.iter())
+            .map(|nal| 1 + nal.body.len())
+            .sum()
+    }
+
     pub fn merge(&mut self, other: Self) {
         assert_eq!(self.rtp_header.timestamp, other.rtp_header.timestamp);
         assert_eq!(self.timestamp_offset, other.timestamp_offset);
@@ -76,20 +97,24 @@ impl RtpH264BufferItem {
         if self.pps.is_none() {
             self.pps = other.pps;
         }
+        self.discontinuity |= other.discontinuity;
     }
 }
 
-const DEFAULT_BUFFER_CAPACITY: usize = 1000 * 1000;
+/// caps the size of a single nal unit reassembled from fu packets by default
 const DEFAULT_FRAGMENT_BUFFER_CAPACITY: usize = 500000;
 
 pub struct RtpH264Sequencer {
-    buffer_capacity: usize,
+    buffer_config: RtpH264BufferConfig,
+    buffer_metrics: Arc<RtpH264BufferMetrics>,
     decode_order_number_cycles: usize,
     packetization_mode: PacketizationMode,
     decoder_buffer: VecDeque<RtpH264BufferItem>,
+    decoder_buffer_bytes: usize,
     de_interleaving_buffer: Option<DeInterleavingBuffer>,
     fragments_buffer: Option<RtpH264FragmentsBuffer>,
-    timestamp_grouper: Option<TimestampGrouper>,
+    access_unit_grouper: Option<AccessUnitGrouper>,
+    keep_access_unit_delimiters: bool,
     sps: Option<NalUnit>,
     pps: Option<NalUnit>,
 }
@@ -100,48 +125,86 @@ impl RtpH264Sequencer {
         de_interleaving_parameters: RtpH264DeInterleavingParameters,
         initial_sps: Option<Sps>,
         initial_pps: Option<Pps>,
+        buffer_config: RtpH264BufferConfig,
     ) -> Self {
         tracing::info!(
-            "creating h264 rtp sequencer with: {}, {:?}",
+            "creating h264 rtp sequencer with: {}, {:?}, {:?}",
             packetization_mode,
-            de_interleaving_parameters
+            de_interleaving_parameters,
+            buffer_config
         );
+        let buffer_metrics: Arc<RtpH264BufferMetrics> = Default::default();
         Self {
-            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            buffer_config,
             fragments_buffer: Some(RtpH264FragmentsBuffer::new(
                 DEFAULT_FRAGMENT_BUFFER_CAPACITY,
+                buffer_config,
+                buffer_metrics.clone(),
             )),
             decode_order_number_cycles: 0,
 
             packetization_mode,
 
-            decoder_buffer: VecDeque::with_capacity(DEFAULT_BUFFER_CAPACITY),
+            decoder_buffer: VecDeque::new(),
+            decoder_buffer_bytes: 0,
             de_interleaving_buffer: if packetization_mode == PacketizationMode::Interleaved {
-                Some(DeInterleavingBuffer::new(de_interleaving_parameters))
+                Some(DeInterleavingBuffer::new(
+                    de_interleaving_parameters,
+                    buffer_config,
+                    buffer_metrics.clone(),
+                ))
             } else {
                 None
             },
-            timestamp_grouper: Some(TimestampGrouper::new()),
+            access_unit_grouper: Some(AccessUnitGrouper::new()),
+            keep_access_unit_delimiters: true,
             sps: initial_sps.map(|v| (&v).into()),
             pps: initial_pps.map(|v| (&v).into()),
+            buffer_metrics,
         }
     }
 
-    fn enqueue_decoder_buffer(&mut self, mut item: RtpH264BufferItem) -> RtpH264Result<()> {
-        if let Some(timestamp_grouper) = self.timestamp_grouper.as_mut() {
-            let groupped = timestamp_grouper.enqueue(item)?;
-            if groupped.is_none() {
-                return Ok(());
+    /// caps the size of a single nal unit reassembled from fu packets
+    pub fn with_fragment_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.fragments_buffer = Some(RtpH264FragmentsBuffer::new(
+            capacity,
+            self.buffer_config,
+            self.buffer_metrics.clone(),
+        ));
+        self
+    }
+
+    /// bytes held by the buffers of this sequencer, updated as packets come and go
+    pub fn buffer_metrics(&self) -> Arc<RtpH264BufferMetrics> {
+        self.buffer_metrics.clone()
+    }
+
+    /// whether AUD nal units are passed through, they are still used to find access unit boundaries
+    pub fn with_access_unit_delimiters(mut self, keep: bool) -> Self {
+        self.keep_access_unit_delimiters = keep;
+        self
+    }
+
+    fn enqueue_decoder_buffer(&mut self, item: RtpH264BufferItem) -> RtpH264Result<()> {
+        if let Some(access_unit_grouper) = self.access_unit_grouper.as_mut() {
+            if let Some(groupped) = access_unit_grouper.enqueue(item)? {
+                groupped
+                    .into_iter()
+                    .for_each(|item| self.push_decoder_buffer(item));
             }
-            item = groupped.unwrap();
+        } else {
+            self.push_decoder_buffer(item);
         }
+        Ok(())
+    }
 
-        if self.decoder_buffer.len() >= self.buffer_capacity {
-            let dropped = self.decoder_buffer.pop_front();
-            tracing::warn!(
-                "dropped item from rtp h264 sequencer because of capacity exceed: {:?}",
-                dropped
-            );
+    fn push_decoder_buffer(&mut self, mut item: RtpH264BufferItem) {
+        if !self.keep_access_unit_delimiters {
+            item.nal_units
+                .retain(|nal| nal.header.nal_unit_type != NALUType::AccessUnitDelimiter);
+            if item.nal_units.is_empty() {
+                return;
+            }
         }
 
         for nal in &item.nal_units {
@@ -155,9 +218,24 @@ impl RtpH264Sequencer {
             }
         }
 
-        self.decoder_buffer.push_back(item);
+        let size = item.size();
+        let (evicted_bytes, follows_evicted) = budget::evict_for_incoming(
+            &mut self.decoder_buffer,
+            self.decoder_buffer_bytes,
+            size,
+            self.buffer_config.decoder_bytes,
+            self.buffer_config.retain_idr,
+            |item| item,
+        );
+        if evicted_bytes > 0 {
+            self.buffer_metrics.on_discontinuity();
+        }
+        item.discontinuity |= follows_evicted;
 
-        Ok(())
+        self.decoder_buffer_bytes = self.decoder_buffer_bytes - evicted_bytes + size;
+        self.buffer_metrics
+            .set_decoder_bytes(self.decoder_buffer_bytes);
+        self.decoder_buffer.push_back(item);
     }
 
     fn enqueue_de_interleaving_buffer(&mut self, item: RtpH264BufferItem) -> RtpH264Result<()> {
@@ -361,11 +439,25 @@ impl RtpH264Sequencer {
         Ok(())
     }
 
+    /// moves the nal units waiting for an access unit boundary to the decoder buffer,
+    /// used when the stream ends
+    pub fn flush(&mut self) {
+        if let Some(item) = self
+            .access_unit_grouper
+            .as_mut()
+            .and_then(|grouper| grouper.flush())
+        {
+            self.push_decoder_buffer(item);
+        }
+    }
+
     pub fn try_dump_packets(&mut self) -> Vec<RtpH264BufferItem> {
         let mut result: Vec<RtpH264BufferItem> = Vec::with_capacity(self.decoder_buffer.len());
         while let Some(item) = self.decoder_buffer.pop_front() {
             result.push(item);
         }
+        self.decoder_buffer_bytes = 0;
+        self.buffer_metrics.set_decoder_bytes(0);
         result
     }
 }
@@ -374,10 +466,14 @@ impl RtpBufferedSequencer for RtpH264Sequencer {
     fn enqueue(&mut self, packet: crate::packet::RtpTrivialPacket) -> Result<(), RtpError> {
         let h264_packet: RtpH264Packet = packet
             .try_into()
-            .map_err(|err| RtpError::H264SequenceFailed(format!("{}", err)))?;
+            .map_err(|err| RtpError::H264SequenceFailed(Box::new(err)))?;
 
-        self.on_packet(h264_packet)
-            .map_err(|err| RtpError::H264SequenceFailed(format!("{}", err)))?;
+        self.on_packet(h264_packet).map_err(|err| match err {
+            RtpH264Error::FragmentsTooLarge { size, limit } => {
+                RtpError::AccessUnitTooLarge { size, limit }
+            }
+            err => RtpError::H264SequenceFailed(Box::new(err)),
+        })?;
         Ok(())
     }
 
@@ -388,4 +484,8 @@ impl RtpBufferedSequencer for RtpH264Sequencer {
             .map(|item| RtpBufferItem::Video(RtpBufferVideoItem::H264(item)))
             .collect()
     }
+
+    fn flush(&mut self) {
+        RtpH264Sequencer::flush(self)
+    }
 }
@@ -0,0 +1,264 @@
+#[cfg(test)]
+mod tests {
+    use codec_h264::{nalu::NalUnit, nalu_header::NaluHeader, nalu_type::NALUType};
+    use tokio_util::bytes::Bytes;
+
+    use crate::{
+        codec::h264::{
+            RtpH264NalUnit,
+            packet::{
+                RtpH264Packet,
+                sequencer::{
+                    RtpH264BufferItem, RtpH264Sequencer, budget::RtpH264BufferConfig,
+                    de_interleaving::RtpH264DeInterleavingParameters,
+                },
+            },
+            paramters::packetization_mode::PacketizationMode,
+            single_nalu::SingleNalUnit,
+        },
+        header::RtpHeader,
+    };
+
+    // first_mb_in_slice = 0, slice_type = 5, pic_parameter_set_id = 0
+    const FIRST_SLICE: [u8; 2] = [0b1001_1010, 0x00];
+    // first_mb_in_slice = 60, slice_type = 5, pic_parameter_set_id = 0
+    const SECOND_SLICE: [u8; 3] = [0x07, 0xA6, 0x80];
+
+    fn nalu(nal_header: u8, body: &[u8]) -> NalUnit {
+        NalUnit {
+            header: NaluHeader::try_from(nal_header).unwrap(),
+            body: Bytes::copy_from_slice(body),
+        }
+    }
+
+    fn packet(sequence_number: u16, timestamp: u32, marker: bool, nalu: NalUnit) -> RtpH264Packet {
+        RtpH264Packet {
+            header: RtpHeader {
+                marker,
+                sequence_number,
+                timestamp,
+                ..Default::default()
+            },
+            payload: RtpH264NalUnit::SingleNalu(SingleNalUnit(nalu)),
+        }
+    }
+
+    fn new_sequencer() -> RtpH264Sequencer {
+        sequencer_with_budget(RtpH264BufferConfig::default())
+    }
+
+    fn sequencer_with_budget(buffer_config: RtpH264BufferConfig) -> RtpH264Sequencer {
+        RtpH264Sequencer::new(
+            PacketizationMode::NonInterleaved,
+            RtpH264DeInterleavingParameters::default(),
+            None,
+            None,
+            buffer_config,
+        )
+    }
+
+    /// a whole access unit of one slice, `size` bytes including the nal header
+    fn slice(nal_header: u8, size: usize) -> NalUnit {
+        let mut body = FIRST_SLICE.to_vec();
+        body.resize(size - 1, 0);
+        nalu(nal_header, &body)
+    }
+
+    fn nalu_types(item: &RtpH264BufferItem) -> Vec<NALUType> {
+        item.nal_units
+            .iter()
+            .map(|nalu| nalu.header.nal_unit_type)
+            .collect()
+    }
+
+    #[test]
+    fn marker_bit_terminates_access_unit() {
+        let mut sequencer = new_sequencer();
+        sequencer
+            .on_packet(packet(0, 3000, false, nalu(0x41, &FIRST_SLICE)))
+            .unwrap();
+        assert!(sequencer.try_dump_packets().is_empty());
+        sequencer
+            .on_packet(packet(1, 3000, true, nalu(0x41, &SECOND_SLICE)))
+            .unwrap();
+
+        // no need to wait for the next access unit
+        let items = sequencer.try_dump_packets();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].nal_units.len(), 2);
+        assert!(items[0].access_unit_complete);
+    }
+
+    #[test]
+    fn marker_less_stream_is_split_by_first_mb_in_slice() {
+        let mut sequencer = new_sequencer();
+        sequencer
+            .on_packet(packet(0, 3000, false, nalu(0x41, &FIRST_SLICE)))
+            .unwrap();
+        sequencer
+            .on_packet(packet(1, 3000, false, nalu(0x41, &SECOND_SLICE)))
+            .unwrap();
+        assert!(sequencer.try_dump_packets().is_empty());
+
+        // next picture starts at macroblock 0 with the same timestamp
+        sequencer
+            .on_packet(packet(2, 3000, false, nalu(0x41, &FIRST_SLICE)))
+            .unwrap();
+        let items = sequencer.try_dump_packets();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].nal_units.len(), 2);
+        assert!(items[0].access_unit_complete);
+
+        // a timestamp change is still a boundary
+        sequencer
+            .on_packet(packet(3, 6000, false, nalu(0x41, &SECOND_SLICE)))
+            .unwrap();
+        let items = sequencer.try_dump_packets();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].nal_units.len(), 1);
+
+        sequencer.flush();
+        let items = sequencer.try_dump_packets();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].rtp_header.timestamp, 6000);
+        assert!(!items[0].access_unit_complete);
+    }
+
+    #[test]
+    fn duplicate_timestamp_field_pair() {
+        let mut sequencer = new_sequencer();
+        // top and bottom field of the same frame share the rtp timestamp,
+        // each field is a primary coded picture and thus an access unit
+        for (sequence_number, body) in [FIRST_SLICE.as_slice(), SECOND_SLICE.as_slice()]
+            .into_iter()
+            .cycle()
+            .take(4)
+            .enumerate()
+        {
+            sequencer
+                .on_packet(packet(
+                    sequence_number as u16,
+                    9000,
+                    false,
+                    nalu(0x41, body),
+                ))
+                .unwrap();
+        }
+        sequencer
+            .on_packet(packet(4, 12000, true, nalu(0x41, &FIRST_SLICE)))
+            .unwrap();
+
+        let items = sequencer.try_dump_packets();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].rtp_header.timestamp, 9000);
+        assert_eq!(items[0].nal_units.len(), 2);
+        assert_eq!(items[1].rtp_header.timestamp, 9000);
+        assert_eq!(items[1].nal_units.len(), 2);
+        assert_eq!(items[2].rtp_header.timestamp, 12000);
+        assert!(items.iter().all(|item| item.access_unit_complete));
+    }
+
+    #[test]
+    fn access_unit_delimiters() {
+        let feed = |sequencer: &mut RtpH264Sequencer| {
+            sequencer
+                .on_packet(packet(0, 3000, false, nalu(0x09, &[0x30])))
+                .unwrap();
+            sequencer
+                .on_packet(packet(1, 3000, false, nalu(0x41, &SECOND_SLICE)))
+                .unwrap();
+            // an AUD after a VCL nal unit starts a new access unit even without first_mb_in_slice == 0
+            sequencer
+                .on_packet(packet(2, 3000, false, nalu(0x09, &[0x30])))
+                .unwrap();
+        };
+
+        let mut sequencer = new_sequencer();
+        feed(&mut sequencer);
+        let items = sequencer.try_dump_packets();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            nalu_types(&items[0]),
+            vec![NALUType::AccessUnitDelimiter, NALUType::NonIDRSlice]
+        );
+
+        let mut sequencer = new_sequencer().with_access_unit_delimiters(false);
+        feed(&mut sequencer);
+        let items = sequencer.try_dump_packets();
+        assert_eq!(items.len(), 1);
+        assert_eq!(nalu_types(&items[0]), vec![NALUType::NonIDRSlice]);
+        // a lone AUD is dropped entirely
+        sequencer.flush();
+        assert!(sequencer.try_dump_packets().is_empty());
+    }
+
+    #[test]
+    fn decoder_buffer_evicts_by_bytes() {
+        let mut sequencer = sequencer_with_budget(RtpH264BufferConfig {
+            decoder_bytes: 1000,
+            ..Default::default()
+        });
+        // plenty of small items fit in the budget
+        for i in 0..100 {
+            sequencer
+                .on_packet(packet(i, i as u32 * 3000, true, slice(0x41, 10)))
+                .unwrap();
+        }
+        let metrics = sequencer.buffer_metrics();
+        assert_eq!(metrics.decoder_bytes(), 1000);
+        assert_eq!(metrics.discontinuities(), 0);
+        assert_eq!(sequencer.try_dump_packets().len(), 100);
+        assert_eq!(metrics.decoder_bytes(), 0);
+
+        // a few big ones do not
+        for i in 0..3 {
+            sequencer
+                .on_packet(packet(i, i as u32 * 3000, true, slice(0x41, 400)))
+                .unwrap();
+        }
+        assert_eq!(metrics.decoder_bytes(), 800);
+        assert_eq!(metrics.discontinuities(), 1);
+        let items = sequencer.try_dump_packets();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].rtp_header.timestamp, 3000);
+        assert!(items[0].discontinuity);
+        assert!(!items[1].discontinuity);
+    }
+
+    #[test]
+    fn decoder_buffer_retains_idr() {
+        let feed = |sequencer: &mut RtpH264Sequencer| {
+            sequencer
+                .on_packet(packet(0, 0, true, slice(0x65, 400)))
+                .unwrap();
+            for i in 1..4 {
+                sequencer
+                    .on_packet(packet(i, i as u32 * 3000, true, slice(0x41, 400)))
+                    .unwrap();
+            }
+            sequencer.try_dump_packets()
+        };
+
+        let mut sequencer = sequencer_with_budget(RtpH264BufferConfig {
+            decoder_bytes: 1000,
+            retain_idr: true,
+            ..Default::default()
+        });
+        let items = feed(&mut sequencer);
+        assert_eq!(items.len(), 2);
+        assert!(items[0].is_idr);
+        assert!(!items[0].discontinuity);
+        assert_eq!(items[1].rtp_header.timestamp, 9000);
+        assert!(items[1].discontinuity);
+
+        let mut sequencer = sequencer_with_budget(RtpH264BufferConfig {
+            decoder_bytes: 1000,
+            retain_idr: false,
+            ..Default::default()
+        });
+        let items = feed(&mut sequencer);
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|item| !item.is_idr));
+        assert_eq!(items[0].rtp_header.timestamp, 6000);
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod tests {
+    use codec_common::{
+        FrameType, MediaFrameTimestamp,
+        video::{VideoCodecCommon, VideoFrameInfo, VideoFrameUnit},
+    };
+    use codec_h264::{nalu::NalUnit, nalu_header::NaluHeader};
+    use stream_center::gop::MediaFrame;
+    use tokio_util::bytes::Bytes;
+
+    use crate::{
+        codec::h264::{
+            packet::packetizer::RtpH264PacketPacketizer,
+            paramters::packetization_mode::PacketizationMode,
+        },
+        packet::packetizer::{RtpPacketizerItem, RtpTrivialPacketPacketizer},
+    };
+
+    /// (dts, pts) in milliseconds of an IBBP gop in decode order,
+    /// displayed as I B B P, the P-frame is decoded ahead of the B-frames it is referenced by
+    const IBBP: [(u64, u64); 4] = [(0, 40), (40, 160), (80, 80), (120, 120)];
+
+    fn video_frame(index: usize, dts_ms: u64, pts_ms: u64) -> MediaFrame {
+        let (nal_header, frame_type) = if index == 0 {
+            (0x65, FrameType::KeyFrame)
+        } else {
+            (0x41, FrameType::CodedFrames)
+        };
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                frame_type,
+                MediaFrameTimestamp::new(pts_ms * 1_000_000, dts_ms * 1_000_000),
+            ),
+            payload: VideoFrameUnit::H264 {
+                nal_units: vec![NalUnit {
+                    header: NaluHeader::try_from(nal_header).unwrap(),
+                    body: Bytes::from(vec![0x88, index as u8]),
+                }],
+            },
+        }
+    }
+
+    #[test]
+    fn test_rtp_timestamps_follow_pts_of_b_frames() {
+        let mut packetizer =
+            RtpH264PacketPacketizer::new(1400, PacketizationMode::NonInterleaved, 1234);
+        let clockrate = packetizer.get_rtp_clockrate();
+        let packets: Vec<_> = IBBP
+            .iter()
+            .enumerate()
+            .map(|(index, (dts_ms, pts_ms))| {
+                let frame = video_frame(index, *dts_ms, *pts_ms);
+                packetizer.set_frame_timestamps(
+                    frame.get_presentation_timestamp_ms(),
+                    frame.get_decode_timestamp_ms(),
+                );
+                packetizer
+                    .packetize(RtpPacketizerItem::from_media_frame(frame).unwrap())
+                    .unwrap();
+                let mut packets = packetizer.build().unwrap();
+                assert_eq!(packets.len(), 1);
+                packets.pop().unwrap()
+            })
+            .collect();
+
+        // the rtp timestamps are based on the dts of the first frame
+        let rtp_timestamp_base = packets[0]
+            .header
+            .timestamp
+            .wrapping_sub((IBBP[0].1 * clockrate / 1000) as u32);
+        for (packet, (_, pts_ms)) in packets.iter().zip(IBBP) {
+            assert_eq!(
+                packet.header.timestamp.wrapping_sub(rtp_timestamp_base) as u64,
+                pts_ms * clockrate / 1000
+            );
+            assert!(packet.header.marker);
+        }
+        // the packets go out in decode order
+        for pair in packets.windows(2) {
+            assert_eq!(
+                pair[1].header.sequence_number,
+                pair[0].header.sequence_number.wrapping_add(1)
+            );
+        }
+        // the P-frame is sent ahead of the B-frames but presented after them
+        let offset = |index: usize| {
+            packets[index]
+                .header
+                .timestamp
+                .wrapping_sub(rtp_timestamp_base)
+        };
+        assert!(offset(1) > offset(2) && offset(2) > offset(0));
+    }
+
+    #[test]
+    fn rtp_timestamps_tick_at_the_advertised_clock_rate() {
+        const CLOCK_RATE: u64 = 65536;
+        let mut packetizer =
+            RtpH264PacketPacketizer::new(1400, PacketizationMode::NonInterleaved, 1234);
+        packetizer.set_rtp_clockrate(CLOCK_RATE);
+        assert_eq!(packetizer.get_rtp_clockrate(), CLOCK_RATE);
+        // 25 fps for 10 minutes, the timestamps do not drift from the frame times
+        let frame_ms = |index: u64| index * 40;
+        let timestamps: Vec<_> = (0..15_000)
+            .map(|index| {
+                let frame = video_frame(index as usize % 2, frame_ms(index), frame_ms(index));
+                packetizer.set_frame_timestamps(
+                    frame.get_presentation_timestamp_ms(),
+                    frame.get_decode_timestamp_ms(),
+                );
+                packetizer
+                    .packetize(RtpPacketizerItem::from_media_frame(frame).unwrap())
+                    .unwrap();
+                packetizer.build().unwrap().pop().unwrap().header.timestamp
+            })
+            .collect();
+        for (index, timestamp) in timestamps.iter().enumerate() {
+            assert_eq!(
+                timestamp.wrapping_sub(timestamps[0]) as u64,
+                frame_ms(index as u64) * CLOCK_RATE / 1000
+            );
+        }
+    }
+}
@@ -666,6 +666,22 @@ impl FromStr for RtpH264Fmtp {
     }
 }
 
+/// the fmtp parameters as in sdp, e.g., "profile-level-id=42e01f;packetization-mode=1"
+#[cfg(feature = "serde")]
+impl serde::Serialize for RtpH264Fmtp {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RtpH264Fmtp {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fmtp = String::deserialize(deserializer)?;
+        fmtp.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl fmt::Display for RtpH264Fmtp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut result: Vec<String> = Vec::new();
@@ -34,3 +34,24 @@ impl fmt::Display for PacketizationMode {
         }
     }
 }
+
+/// the number of the mode, as in the packetization-mode fmtp parameter
+#[cfg(feature = "serde")]
+impl serde::Serialize for PacketizationMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mode = match self {
+            Self::SingleNalu => 0,
+            Self::NonInterleaved => 1,
+            Self::Interleaved => 2,
+        };
+        serializer.serialize_u8(mode)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PacketizationMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mode = u8::deserialize(deserializer)?;
+        mode.to_string().parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -38,4 +38,37 @@ mod tests {
         assert_eq!(parsed.sprop_init_buf_time, Some(102478));
         assert_eq!(parsed.max_mbps, None);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn fmtp_serializes_to_its_sdp_string() {
+        let parameters = "profile-level-id=42e016;packetization-mode=1;sprop-parameter-sets=Z2QAHqzZQNg95vARAAADAAEAAAMAMA8WLZY=,aO+Pyw==";
+        let parsed: RtpH264Fmtp = parameters.parse().unwrap();
+        let json = serde_json::to_string(&parsed).unwrap();
+        assert_eq!(json, format!("\"{}\"", parameters));
+        let deserialized: RtpH264Fmtp = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.to_string(), parameters);
+        assert_eq!(
+            deserialized.packetization_mode,
+            Some(PacketizationMode::NonInterleaved)
+        );
+        assert!(serde_json::from_str::<RtpH264Fmtp>("\"packetization-mode=3\"").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn packetization_mode_serializes_to_its_number() {
+        for (mode, number) in [
+            (PacketizationMode::SingleNalu, "0"),
+            (PacketizationMode::NonInterleaved, "1"),
+            (PacketizationMode::Interleaved, "2"),
+        ] {
+            assert_eq!(serde_json::to_string(&mode).unwrap(), number);
+            assert_eq!(
+                serde_json::from_str::<PacketizationMode>(number).unwrap(),
+                mode
+            );
+        }
+        assert!(serde_json::from_str::<PacketizationMode>("3").is_err());
+    }
 }
@@ -1,3 +1,4 @@
+pub mod g711;
 pub mod h264;
 pub mod mpeg4_generic;
 pub mod opus;
@@ -22,6 +22,8 @@ pub enum RtpMpeg4Error {
     AccessUnitEmpty,
     #[error("Access Unit Fragment overread, expected {0} bytes, read {1} bytes instead")]
     AccessUnitOverread(usize, usize),
+    #[error("no au header found")]
+    NoAuHeader,
     #[error("Au header count {0} and au count {1} mismatch")]
     AuHeaderCountMissmatch(usize, usize),
     #[error("Rtp Error: {0}")]
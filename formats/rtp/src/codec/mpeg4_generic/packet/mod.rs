@@ -3,8 +3,11 @@ pub mod packetizer;
 pub mod reader;
 pub mod sequencer;
 pub mod writer;
-use tokio_util::bytes::{Buf, Bytes};
-use utils::traits::{reader::ReadRemainingFrom, writer::WriteTo};
+use tokio_util::bytes::{Buf, BufMut};
+use utils::{
+    bytes::pool,
+    traits::{reader::ReadRemainingFrom, writer::WriteTo},
+};
 
 use super::{
     access_unit::AccessUnitSection, au_header::AuHeaderSection, auxiliary::AuxiliaryData,
@@ -41,8 +44,9 @@ impl TryFrom<(RtpTrivialPacket, &RtpMpeg4Fmtp)> for RtpMpeg4GenericPacket {
 impl TryFrom<(RtpMpeg4GenericPacket, &RtpMpeg4Fmtp)> for RtpTrivialPacket {
     type Error = RtpMpeg4Error;
     fn try_from(value: (RtpMpeg4GenericPacket, &RtpMpeg4Fmtp)) -> Result<Self, Self::Error> {
-        let mut payload = Vec::with_capacity(1500);
         let (packet, params) = value;
+        let mut buffer = pool::take(1500);
+        let mut payload = (&mut *buffer).writer();
         if let Some(au_header) = packet.au_header_section.as_ref() {
             AuHeaderSectionWriteWrapper(au_header, params).write_to(&mut payload);
         }
@@ -52,7 +56,7 @@ impl TryFrom<(RtpMpeg4GenericPacket, &RtpMpeg4Fmtp)> for RtpTrivialPacket {
         packet.au_section.write_to(&mut payload)?;
         Ok(Self {
             header: packet.header,
-            payload: Bytes::from_owner(payload),
+            payload: buffer.split_freeze(),
         })
     }
 }
@@ -11,6 +11,7 @@ use crate::{
     packet::packetizer::{RtpTrivialPacketPacketizer, wallclock_to_rtp_timestamp},
     payload_types::rtp_payload_type::{audio_get_rtp_clockrate, get_audio_rtp_payload_type},
 };
+use codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecificConfig;
 use codec_common::audio::AudioCodecCommon;
 use num::ToPrimitive;
 use std::{
@@ -26,12 +27,34 @@ use utils::{
     traits::dynamic_sized_packet::{DynamicSizedBitsPacket, DynamicSizedPacket},
 };
 
+/// the rate the decoder of the config outputs and the samples of a frame at it, 2048 for he-aac.
+/// the well known rate if the config can not be read
+fn clock_of(params: &RtpMpeg4Fmtp) -> (u64, u64) {
+    let config = AudioSpecificConfig::try_from(params).ok();
+    (
+        config
+            .as_ref()
+            .and_then(|config| config.effective_sample_rate())
+            .map(u64::from)
+            .unwrap_or_else(|| audio_get_rtp_clockrate(AudioCodecCommon::AAC).unwrap()),
+        config
+            .as_ref()
+            .and_then(|config| config.samples_per_frame())
+            .map_or(1024, u64::from),
+    )
+}
+
+/// the AU-headers-length field before the au headers, RFC 3640 3.2.1
+const AU_HEADERS_LENGTH_BYTES: usize = 2;
+
 #[derive(Debug)]
 pub struct RtpMpeg4GenericPacketPacketizer {
     params: RtpMpeg4Fmtp,
     au_index: u64,
     rtp_header: RtpHeader,
     rtp_clockrate: u64,
+    /// the rtp timestamps of the access units of a frame are this far apart
+    samples_per_frame: u64,
     first_frame_timestamp: Option<u64>,
     last_frame_timestamp: Option<u64>,
     rtp_timestamp_base: u64,
@@ -41,6 +64,7 @@ pub struct RtpMpeg4GenericPacketPacketizer {
 
 impl RtpMpeg4GenericPacketPacketizer {
     pub fn new(mtu: usize, params: RtpMpeg4Fmtp, ssrc: u32) -> Self {
+        let (rtp_clockrate, samples_per_frame) = clock_of(&params);
         Self {
             params,
             au_index: 0,
@@ -51,7 +75,8 @@ impl RtpMpeg4GenericPacketPacketizer {
                 .sequence_number(random::random_u16())
                 .build(),
             access_units: vec![],
-            rtp_clockrate: audio_get_rtp_clockrate(AudioCodecCommon::AAC).unwrap(),
+            rtp_clockrate,
+            samples_per_frame,
             first_frame_timestamp: None,
             last_frame_timestamp: None,
             rtp_timestamp_base: random::random_u32() as u64,
@@ -59,6 +84,7 @@ impl RtpMpeg4GenericPacketPacketizer {
         }
     }
     pub fn params(&mut self, params: RtpMpeg4Fmtp) -> &mut Self {
+        (self.rtp_clockrate, self.samples_per_frame) = clock_of(&params);
         self.params = params;
         self
     }
@@ -107,6 +133,7 @@ impl RtpMpeg4GenericPacketPacketizer {
             let frag_au_size = cmp::min(
                 self.mtu
                     - self.rtp_header.get_packet_bytes_count()
+                    - AU_HEADERS_LENGTH_BYTES
                     - au_header_bits_cnt.div_ceil(8),
                 reader.remaining(),
             );
@@ -163,7 +190,7 @@ impl RtpTrivialPacketPacketizer for RtpMpeg4GenericPacketPacketizer {
                     .rap_flag(Some(false))
                     .stream_state(Some(0))
                     .build(&self.params, true, true)
-                    .map_err(|e| RtpError::Mpeg4PacketizationFailed(format!("{}", e)))?,
+                    .map_err(|err| RtpError::Mpeg4PacketizationFailed(Box::new(err)))?,
                 &self.params,
             )
             .get_packet_bits_count()
@@ -179,7 +206,7 @@ impl RtpTrivialPacketPacketizer for RtpMpeg4GenericPacketPacketizer {
                 // TODO: assume au header takes 10 bytes, this could be optimized
                 result.extend(
                     self.packetize_fragmentated(&mut au_index, au, self.rtp_header.timestamp)
-                        .map_err(|e| RtpError::Mpeg4PacketizationFailed(format!("{}", e)))?,
+                        .map_err(|err| RtpError::Mpeg4PacketizationFailed(Box::new(err)))?,
                 );
                 self.au_index = au_index;
             } else {
@@ -193,7 +220,7 @@ impl RtpTrivialPacketPacketizer for RtpMpeg4GenericPacketPacketizer {
                     .rap_flag(Some(false))
                     .stream_state(None)
                     .build(&self.params, true, false)
-                    .map_err(|e| RtpError::Mpeg4PacketizationFailed(format!("{}", e)))?;
+                    .map_err(|err| RtpError::Mpeg4PacketizationFailed(Box::new(err)))?;
 
                 result.push(RtpMpeg4GenericPacket {
                     header: self.rtp_header.clone(),
@@ -218,13 +245,9 @@ impl RtpTrivialPacketPacketizer for RtpMpeg4GenericPacketPacketizer {
         let mut trivial_packets = Vec::with_capacity(result.len());
         let mut rtp_timestamp_delta = 0;
         for pkt in result {
-            let mut trivial_packet: crate::packet::RtpTrivialPacket =
-                (pkt, &self.params).try_into().map_err(|e| {
-                    RtpError::Mpeg4PacketizationFailed(format!(
-                        "unable to convert to rtp packet: {}",
-                        e
-                    ))
-                })?;
+            let mut trivial_packet: crate::packet::RtpTrivialPacket = (pkt, &self.params)
+                .try_into()
+                .map_err(|err| RtpError::Mpeg4PacketizationFailed(Box::new(err)))?;
             trivial_packet.header.timestamp = wallclock_to_rtp_timestamp(
                 self.last_frame_timestamp.unwrap(),
                 self.first_frame_timestamp.unwrap(),
@@ -232,7 +255,7 @@ impl RtpTrivialPacketPacketizer for RtpMpeg4GenericPacketPacketizer {
                 self.rtp_clockrate,
             ) as u32
                 + rtp_timestamp_delta;
-            rtp_timestamp_delta += 1024;
+            rtp_timestamp_delta += self.samples_per_frame as u32;
             trivial_packet.header.sequence_number = self.rtp_header.sequence_number;
             self.rtp_header.sequence_number = self.rtp_header.sequence_number.wrapping_add(1);
             trivial_packet.header.marker = true;
@@ -246,17 +269,19 @@ impl RtpTrivialPacketPacketizer for RtpMpeg4GenericPacketPacketizer {
         item: crate::packet::packetizer::RtpPacketizerItem,
     ) -> Result<(), RtpError> {
         match item {
-            crate::packet::packetizer::RtpPacketizerItem::Audio(audio_item) => match audio_item {
-                crate::packet::packetizer::RtpPacketizerAudioItem::AAC(mpeg4_item) => {
-                    self.access_units.extend(mpeg4_item.access_units);
-                    Ok(())
-                }
-            },
+            crate::packet::packetizer::RtpPacketizerItem::Audio(
+                crate::packet::packetizer::RtpPacketizerAudioItem::AAC(mpeg4_item),
+            ) => {
+                self.access_units.extend(mpeg4_item.access_units);
+                Ok(())
+            }
             _ => {
                 debug_assert!(false, "only mpeg4 audio item is supported");
-                Err(RtpError::Mpeg4PacketizationFailed(
-                    "only mpeg4 audio item is supported".to_string(),
-                ))
+                Err(RtpError::Mpeg4PacketizationFailed(Box::new(
+                    RtpMpeg4Error::PacketizeToRtpFailed(
+                        "only mpeg4 audio item is supported".to_string(),
+                    ),
+                )))
             }
         }
     }
@@ -279,6 +304,10 @@ impl RtpTrivialPacketPacketizer for RtpMpeg4GenericPacketPacketizer {
         self.rtp_clockrate
     }
 
+    fn set_rtp_clockrate(&mut self, clockrate: u64) {
+        self.rtp_clockrate = clockrate;
+    }
+
     fn rtp_header(&self) -> &RtpHeader {
         &self.rtp_header
     }
@@ -105,39 +105,39 @@ impl RtpBufferedSequencer for RtpMpeg4GenericSequencer {
             (&self.params, &packet.header),
             &mut packet.payload.reader(),
         )
-        .map_err(|err| RtpError::Mpeg4SequenceFailed(format!("{}", err)))?;
+        .map_err(|err| RtpError::Mpeg4SequenceFailed(Box::new(err)))?;
 
         let au_headers = packet
             .au_header_section
             .map(|item| item.au_headers)
             .unwrap_or(vec![]);
         if au_headers.is_empty() {
-            return Err(RtpError::Mpeg4SequenceFailed(
-                "invalid mpeg4 rtp packet: no au header found".to_owned(),
-            ));
+            return Err(RtpError::Mpeg4SequenceFailed(Box::new(
+                RtpMpeg4Error::NoAuHeader,
+            )));
         }
 
         match packet.au_section.access_units_or_fragment {
             Either::Left(aus) => {
                 if aus.len() != au_headers.len() {
-                    return Err(RtpError::Mpeg4SequenceFailed(format!(
-                        "au headers count {} and au count {} mismatch",
-                        au_headers.len(),
-                        aus.len()
+                    return Err(RtpError::Mpeg4SequenceFailed(Box::new(
+                        RtpMpeg4Error::AuHeaderCountMissmatch(au_headers.len(), aus.len()),
                     )));
                 }
                 self.on_access_units(packet.header, aus)
-                    .map_err(|err| RtpError::Mpeg4SequenceFailed(format!("{}", err)))?;
+                    .map_err(|err| RtpError::Mpeg4SequenceFailed(Box::new(err)))?;
             }
             Either::Right(frag) => {
                 if au_headers.len() != 1 {
-                    return Err(RtpError::Mpeg4SequenceFailed(format!(
-                        "{} au headers found for fragment packet",
-                        au_headers.len()
+                    return Err(RtpError::Mpeg4SequenceFailed(Box::new(
+                        RtpMpeg4Error::UnexpectedFragmentPacket(format!(
+                            "{} au headers found for fragment packet",
+                            au_headers.len()
+                        )),
                     )));
                 }
                 self.on_fragmented(packet.header, frag)
-                    .map_err(|err| RtpError::Mpeg4SequenceFailed(format!("{}", err)))?;
+                    .map_err(|err| RtpError::Mpeg4SequenceFailed(Box::new(err)))?;
             }
         }
 
@@ -1,5 +1,5 @@
 use super::errors::{RtpMpeg4Error, RtpMpeg4Result};
-use bitstream_io::BitRead;
+use bitstream_io::{BitRead, BitWrite};
 use std::{fmt, str::FromStr};
 use tokio_util::bytes::Bytes;
 use utils::traits::writer::BitwiseWriteTo;
@@ -269,6 +269,8 @@ impl TryFrom<&codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecif
         let mut config = vec![];
         let mut writer = bitstream_io::BitWriter::endian(&mut config, bitstream_io::BigEndian);
         value.write_to(&mut writer)?;
+        // he-aac configs end out of byte alignment
+        writer.byte_align()?;
         result.config = Bytes::from_owner(bytes_to_hex(&config));
         Ok(result)
     }
@@ -1,18 +1,25 @@
 use std::{io, string};
 use thiserror::Error;
 
+use crate::codec::{h264::errors::RtpH264Error, mpeg4_generic::errors::RtpMpeg4Error};
+
 #[derive(Debug, Error)]
 pub enum RtpError {
     #[error("io error: {0}")]
     Io(#[from] io::Error),
+    // boxed, the codec errors hold rtp errors in turn
     #[error("h264 sequence failed: {0}")]
-    H264SequenceFailed(String),
+    H264SequenceFailed(#[source] Box<RtpH264Error>),
     #[error("h264 packetization failed: {0}")]
-    H264PacketizationFailed(String),
+    H264PacketizationFailed(#[source] Box<RtpH264Error>),
+    #[error("access unit too large: {size} bytes, limit: {limit}")]
+    AccessUnitTooLarge { size: usize, limit: usize },
     #[error("mpeg4 sequence failed: {0}")]
-    Mpeg4SequenceFailed(String),
+    Mpeg4SequenceFailed(#[source] Box<RtpMpeg4Error>),
     #[error("mpeg4 packetization failed: {0}")]
-    Mpeg4PacketizationFailed(String),
+    Mpeg4PacketizationFailed(#[source] Box<RtpMpeg4Error>),
+    #[error("g711 packetization failed: {0}")]
+    G711PacketizationFailed(String),
     #[error("unknown rtcp payload type: {0}")]
     UnknownRtcpPayloadType(u8),
     #[error("wrong payload type: {0}")]
@@ -39,15 +46,51 @@ pub enum RtpError {
     EmptyPayload,
     #[error("Bad padding size: {0}")]
     BadPaddingSize(usize),
+    #[error("unsupported rtp version: {0}")]
+    BadRtpVersion(u8),
+    #[error("truncated rtp header, {field} needs {needed} bytes, {remaining} remaining")]
+    TruncatedRtpHeader {
+        field: &'static str,
+        needed: usize,
+        remaining: usize,
+    },
     #[error("too many csrc for a rtp header, exceeds 31")]
     TooManyCSRC,
     #[error("too many report blocks in a report packet, exceeds 31")]
     TooManyReportBlocks,
+    #[error("app packet subtype exceeds 31: {0}")]
+    AppSubtypeTooLarge(u8),
+    #[error("app packet name must be 4 ascii characters: {0:?}")]
+    InvalidAppName([u8; 4]),
+    #[error("app packet data must be a multiple of 32 bits, got {0} bytes")]
+    AppDataNotAligned(usize),
+    #[error("feedback message type exceeds 31: {0}")]
+    FeedbackFormatTooLarge(u8),
+    #[error("feedback control information must be a multiple of 32 bits, got {0} bytes")]
+    FeedbackDataNotAligned(usize),
+    #[error("invalid receiver estimated max bitrate: {0}")]
+    InvalidRemb(String),
+    #[error("invalid redundant audio data: {0}")]
+    InvalidRed(String),
+    #[error("invalid ulpfec packet: {0}")]
+    InvalidUlpfec(String),
+
+    #[error("invalid one-byte header extension element, id: {id}, data length: {len}")]
+    InvalidHeaderExtension { id: u8, len: usize },
 
     #[error("MTU is too small: {0}")]
     MTUTooSmall(usize),
     #[error("payload too large, exceeds u16 length: {0}")]
     PayloadTooLarge(usize),
+
+    #[error("invalid srtp key params: {0}")]
+    InvalidSrtpKeyParams(String),
+    #[error("srtp packet too short: {0} bytes")]
+    SrtpPacketTooShort(usize),
+    #[error("srtp authentication failed")]
+    SrtpAuthenticationFailed,
+    #[error("srtp packet replayed, index: {0}")]
+    SrtpReplayed(u64),
 }
 
 pub type RtpResult<T> = Result<T, RtpError>;
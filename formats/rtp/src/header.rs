@@ -1,5 +1,8 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{self, Cursor};
+use std::{
+    io::{self, Cursor},
+    time::Duration,
+};
 use tokio_util::bytes::{Buf, Bytes};
 use utils::{
     system::time::get_timestamp_ms,
@@ -60,6 +63,25 @@ impl Default for RtpHeader {
     }
 }
 
+pub const RTP_VERSION: u8 = 2;
+/// the header without csrc and extension
+pub const RTP_FIXED_HEADER_SIZE: usize = 12;
+
+/// profile of the one-byte header extensions, RFC 8285 4.2
+pub const ONE_BYTE_EXTENSION_PROFILE: u16 = 0xBEDE;
+
+/// @see: https://webrtc.googlesource.com/src/+/refs/heads/main/docs/native-code/rtp-hdrext/abs-send-time
+pub const ABS_SEND_TIME_URI: &str = "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time";
+
+// @see: RFC 3550 5.3.1 RTP Header Extension
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |      defined by profile       |           length              |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                        header extension                       |
+/// |                             ....                              |
+/// length counts the 32-bit words of the extension
 #[derive(Debug, Clone)]
 pub struct RtpHeaderExtension {
     profile_defined: u16,
@@ -67,6 +89,67 @@ pub struct RtpHeaderExtension {
     bytes: Bytes,
 }
 
+impl RtpHeaderExtension {
+    /// the bytes are zero padded to 32-bit words
+    pub fn new(profile_defined: u16, bytes: &[u8]) -> RtpResult<Self> {
+        let words = bytes.len().div_ceil(4);
+        if words > u16::MAX as usize {
+            return Err(RtpError::PayloadTooLarge(bytes.len()));
+        }
+        let mut padded = vec![0; words * 4];
+        padded[..bytes.len()].copy_from_slice(bytes);
+        Ok(Self {
+            profile_defined,
+            length: words as u16,
+            bytes: Bytes::from(padded),
+        })
+    }
+
+    /// a single one-byte header element, RFC 8285 4.2
+    pub fn one_byte(id: u8, data: &[u8]) -> RtpResult<Self> {
+        if !(1..=14).contains(&id) || data.is_empty() || data.len() > 16 {
+            return Err(RtpError::InvalidHeaderExtension {
+                id,
+                len: data.len(),
+            });
+        }
+        let mut bytes = Vec::with_capacity(1 + data.len());
+        bytes.push((id << 4) | (data.len() as u8 - 1));
+        bytes.extend_from_slice(data);
+        Self::new(ONE_BYTE_EXTENSION_PROFILE, &bytes)
+    }
+
+    /// the send time as 6.18 fixed point seconds, only the lower 6 bits of the seconds are kept.
+    /// unix time works as well as ntp time since the offset between them is a multiple of 64 seconds
+    pub fn abs_send_time(id: u8, since_epoch: Duration) -> RtpResult<Self> {
+        let value = abs_send_time_of(since_epoch);
+        Self::one_byte(id, &value.to_be_bytes()[1..])
+    }
+
+    pub fn profile_defined(&self) -> u16 {
+        self.profile_defined
+    }
+
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+}
+
+/// 24 bits of 6.18 fixed point seconds
+pub fn abs_send_time_of(since_epoch: Duration) -> u32 {
+    let seconds = since_epoch.as_secs() & 0b11_1111;
+    let fraction = ((since_epoch.subsec_nanos() as u64) << 18) / 1_000_000_000;
+    ((seconds << 18) | fraction) as u32
+}
+
+impl RtpHeader {
+    /// also sets the X bit
+    pub fn set_extension(&mut self, extension: RtpHeaderExtension) {
+        self.extension = true;
+        self.header_extension = Some(extension);
+    }
+}
+
 impl DynamicSizedPacket for RtpHeaderExtension {
     fn get_packet_bytes_count(&self) -> usize {
         2 // profile defined
@@ -94,13 +177,16 @@ impl<R: io::Read> ReadFrom<R> for RtpHeader {
     fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
         let first_byte = reader.read_u8()?;
         let version = (first_byte >> 6) & 0b11;
+        if version != RTP_VERSION {
+            return Err(RtpError::BadRtpVersion(version));
+        }
         let padding = ((first_byte >> 5) & 0b1) == 0b1;
         let extension = ((first_byte >> 4) & 0b1) == 0b1;
         let csrc_count = first_byte & 0b1111;
 
         let second_byte = reader.read_u8()?;
         let marker = ((second_byte >> 7) & 0b1) == 0b1;
-        let payload_type = first_byte & 0b0111_1111;
+        let payload_type = second_byte & 0b0111_1111;
 
         let sequence_number = reader.read_u16::<BigEndian>()?;
         let timestamp = reader.read_u32::<BigEndian>()?;
@@ -131,14 +217,36 @@ impl<R: io::Read> ReadFrom<R> for RtpHeader {
     }
 }
 
+/// the lengths a header declares are checked against the bytes at hand before reading them
+fn ensure_remaining<R: AsRef<[u8]>>(
+    reader: &Cursor<R>,
+    field: &'static str,
+    needed: usize,
+) -> RtpResult<()> {
+    let remaining = reader.remaining();
+    if remaining < needed {
+        return Err(RtpError::TruncatedRtpHeader {
+            field,
+            needed,
+            remaining,
+        });
+    }
+    Ok(())
+}
+
+/// None if the fixed header is not complete yet,
+/// the csrc list and the extension it declares must follow in full
 impl<R: AsRef<[u8]>> TryReadFrom<R> for RtpHeader {
     type Error = RtpError;
     fn try_read_from(reader: &mut Cursor<R>) -> Result<Option<Self>, Self::Error> {
-        if reader.remaining() < 12 {
+        if reader.remaining() < RTP_FIXED_HEADER_SIZE {
             return Ok(None);
         }
         let first_byte = reader.read_u8()?;
         let version = (first_byte >> 6) & 0b11;
+        if version != RTP_VERSION {
+            return Err(RtpError::BadRtpVersion(version));
+        }
         let padding = ((first_byte >> 5) & 0b1) == 0b1;
         let extension = ((first_byte >> 4) & 0b1) == 0b1;
         let csrc_count = first_byte & 0b1111;
@@ -151,15 +259,24 @@ impl<R: AsRef<[u8]>> TryReadFrom<R> for RtpHeader {
         let timestamp = reader.read_u32::<BigEndian>()?;
         let ssrc = reader.read_u32::<BigEndian>()?;
 
-        if reader.remaining() < (csrc_count * 4) as usize {
-            return Ok(None);
-        }
-
+        ensure_remaining(reader, "csrc list", csrc_count as usize * 4)?;
         let mut csrc_list = Vec::with_capacity(csrc_count as usize);
         for _ in 0..csrc_count {
             csrc_list.push(reader.read_u32::<BigEndian>()?);
         }
 
+        let header_extension = if extension {
+            ensure_remaining(reader, "header extension", 4)?;
+            let length = {
+                let bytes = reader.chunk();
+                u16::from_be_bytes([bytes[2], bytes[3]]) as usize
+            };
+            ensure_remaining(reader, "header extension", 4 + length * 4)?;
+            Some(RtpHeaderExtension::read_from(reader)?)
+        } else {
+            None
+        };
+
         Ok(Some(Self {
             version,
             padding,
@@ -171,11 +288,7 @@ impl<R: AsRef<[u8]>> TryReadFrom<R> for RtpHeader {
             timestamp,
             ssrc,
             csrc_list,
-            header_extension: if !extension {
-                None
-            } else {
-                Some(RtpHeaderExtension::read_from(reader)?)
-            },
+            header_extension,
         }))
     }
 }
@@ -185,7 +298,7 @@ impl<R: io::Read> ReadFrom<R> for RtpHeaderExtension {
     fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
         let profile_defined = reader.read_u16::<BigEndian>()?;
         let length = reader.read_u16::<BigEndian>()?;
-        let mut bytes = vec![0; length as usize];
+        let mut bytes = vec![0; length as usize * 4];
         reader.read_exact(&mut bytes)?;
 
         Ok(Self {
@@ -284,7 +397,7 @@ impl RtpHeaderBuilder {
     }
 
     pub fn extension(&mut self, extension: RtpHeaderExtension) -> &mut Self {
-        self.header.header_extension = Some(extension);
+        self.header.set_extension(extension);
         self
     }
 
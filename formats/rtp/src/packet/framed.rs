@@ -4,7 +4,9 @@ use tokio_util::{
     bytes::{Buf, BufMut},
     codec::{Decoder, Encoder},
 };
-use utils::traits::{reader::TryReadFrom, writer::WriteTo};
+use utils::traits::{
+    dynamic_sized_packet::DynamicSizedPacket, reader::TryReadFrom, writer::WriteTo,
+};
 
 use crate::errors::RtpError;
 
@@ -20,6 +22,8 @@ impl Encoder<RtpTrivialPacket> for RtpTrivialPacketFramed {
         item: RtpTrivialPacket,
         dst: &mut tokio_util::bytes::BytesMut,
     ) -> Result<(), Self::Error> {
+        // reserved at once rather than grown piece by piece while writing
+        dst.reserve(item.get_packet_bytes_count());
         let mut bytes_writer = dst.writer();
         item.write_to(&mut bytes_writer)
     }
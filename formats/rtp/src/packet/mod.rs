@@ -1,6 +1,12 @@
 pub mod framed;
 pub mod packetizer;
+pub mod red;
+pub mod rewriter;
+pub mod rtx;
 pub mod sequencer;
+pub mod ulpfec;
+#[cfg(test)]
+mod test;
 use crate::{
     errors::RtpError,
     header::RtpHeader,
@@ -33,6 +39,11 @@ impl RtpTrivialPacket {
         result.header.padding = rtp_need_padding(raw_size);
         result
     }
+
+    /// the contributing sources of a mixed packet, empty if the packet is not mixed
+    pub fn csrc_list(&self) -> &[u32] {
+        &self.header.csrc_list
+    }
 }
 
 impl DynamicSizedPacket for RtpTrivialPacket {
@@ -82,19 +93,22 @@ impl<R: AsRef<[u8]>> TryReadFrom<R> for RtpTrivialPacket {
         let payload = reader.copy_to_bytes(payload_size);
 
         let header = header.unwrap();
-        if header.padding {
-            let padding_size = *payload.last().unwrap() as usize;
-            if padding_size > payload_size {
-                return Err(RtpError::BadPaddingSize(padding_size));
-            }
-
-            Ok(Some(Self {
-                header,
-                payload: payload.slice(..payload_size - padding_size),
-            }))
-        } else {
-            Ok(Some(Self { header, payload }))
+        if !header.padding {
+            return Ok(Some(Self { header, payload }));
+        }
+        // the last octet counts the padding, itself included, RFC 3550 5.1
+        let padding_size = payload[payload_size - 1] as usize;
+        if padding_size == 0 || padding_size > payload_size {
+            return Err(RtpError::BadPaddingSize(padding_size));
+        }
+        // the codecs can not make anything of a packet with nothing but padding
+        if padding_size == payload_size {
+            return Err(RtpError::EmptyPayload);
         }
+        Ok(Some(Self {
+            header,
+            payload: payload.slice(..payload_size - padding_size),
+        }))
     }
 }
 
@@ -6,7 +6,7 @@ use codec_h264::nalu::NalUnit;
 use stream_center::gop::MediaFrame;
 use tokio_util::bytes::{Bytes, BytesMut};
 
-use crate::{errors::RtpError, header::RtpHeader};
+use crate::{codec::g711::G711Law, errors::RtpError, header::RtpHeader};
 
 use super::RtpTrivialPacket;
 
@@ -46,6 +46,12 @@ pub struct RtpTrivialPacketizerAACItem {
     pub access_units: Vec<Bytes>,
 }
 
+#[derive(Debug)]
+pub struct RtpTrivialPacketizerG711Item {
+    pub law: G711Law,
+    pub frames: Vec<Bytes>,
+}
+
 #[derive(Debug)]
 pub enum RtpPacketizerVideoItem {
     H264(RtpTrivialPacketizerH264Item),
@@ -54,6 +60,7 @@ pub enum RtpPacketizerVideoItem {
 #[derive(Debug)]
 pub enum RtpPacketizerAudioItem {
     AAC(RtpTrivialPacketizerAACItem),
+    G711(RtpTrivialPacketizerG711Item),
 }
 
 #[derive(Debug)]
@@ -72,7 +79,10 @@ impl RtpPacketizerItem {
                 VideoFrameUnit::H264 { nal_units } => Some(RtpPacketizerItem::Video(
                     RtpPacketizerVideoItem::H264(RtpTrivialPacketizerH264Item { nalus: nal_units }),
                 )),
-                _ => unimplemented!("unsupported video format {:?}", payload),
+                VideoFrameUnit::AV1 { .. } => {
+                    tracing::debug!("av1 frame, no rtp packetizer, ignore");
+                    None
+                }
             },
             MediaFrame::Audio {
                 frame_info,
@@ -83,6 +93,13 @@ impl RtpPacketizerItem {
                         access_units: vec![payload],
                     }),
                 )),
+                codec_id @ (AudioCodecCommon::G711MULawLogarithmicPCM
+                | AudioCodecCommon::G711ALawLogarithmicPCM) => Some(RtpPacketizerItem::Audio(
+                    RtpPacketizerAudioItem::G711(RtpTrivialPacketizerG711Item {
+                        law: codec_id.try_into().unwrap(),
+                        frames: vec![payload],
+                    }),
+                )),
                 _ => unimplemented!("unsupported audio format {:?}", frame_info),
             },
             MediaFrame::VideoConfig {
@@ -106,15 +123,24 @@ impl RtpPacketizerItem {
                         RtpTrivialPacketizerH264Item { nalus: nal_units },
                     )))
                 }
+                VideoConfig::AV1(_) => {
+                    tracing::debug!("av1 config frame, no rtp packetizer, ignore");
+                    None
+                }
             },
             MediaFrame::AudioConfig {
                 timestamp_nano: _,
                 sound_info: _,
                 config: _,
+                track_id: _,
             } => {
                 tracing::debug!("audio config frame, ignore");
                 None
             }
+            MediaFrame::AudioChannelConfig { .. } => {
+                tracing::debug!("audio channel config frame, ignore");
+                None
+            }
             MediaFrame::Script {
                 timestamp_nano: _,
                 on_meta_data: _,
@@ -123,6 +149,14 @@ impl RtpPacketizerItem {
                 tracing::debug!("script frame, ignore");
                 None
             }
+            MediaFrame::Data { .. } => {
+                tracing::debug!("data frame, ignore");
+                None
+            }
+            MediaFrame::EndOfStream { .. } | MediaFrame::SourceError { .. } => {
+                tracing::debug!("end of stream, ignore");
+                None
+            }
         }
     }
 }
@@ -130,7 +164,15 @@ impl RtpPacketizerItem {
 pub trait RtpTrivialPacketPacketizer {
     fn set_rtp_header(&mut self, header: RtpHeader);
     fn set_frame_timestamp(&mut self, timestamp: u64);
+    /// frames are packetized in decode order, the rtp timestamp is the presentation time,
+    /// packetizers of codecs with B-frames anchor it at the decode time of the first frame
+    fn set_frame_timestamps(&mut self, pts_ms: u64, _dts_ms: u64) {
+        self.set_frame_timestamp(pts_ms);
+    }
     fn get_rtp_clockrate(&self) -> u64;
+    /// the rate the sdp advertises for the payload type, the rtp timestamps tick at it
+    /// even if it is not the well-known one of the codec
+    fn set_rtp_clockrate(&mut self, clockrate: u64);
     fn rtp_header(&self) -> &RtpHeader;
     fn packetize(&mut self, item: RtpPacketizerItem) -> Result<(), RtpError>;
     fn build(&mut self) -> Result<Vec<RtpTrivialPacket>, RtpError>;
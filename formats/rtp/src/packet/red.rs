@@ -0,0 +1,118 @@
+use tokio_util::bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{
+    errors::{RtpError, RtpResult},
+    header::RtpHeader,
+};
+
+use super::RtpTrivialPacket;
+
+/// the header of a redundant block, the one of the primary block is 1 byte
+pub const RED_BLOCK_HEADER_BYTES: usize = 4;
+pub const RED_PRIMARY_HEADER_BYTES: usize = 1;
+/// the block length field is 10 bits
+pub const RED_MAX_BLOCK_LENGTH: usize = 0x3ff;
+/// the timestamp offset field is 14 bits
+pub const RED_MAX_TIMESTAMP_OFFSET: u32 = 0x3fff;
+
+/// a block of a red packet, the primary one is the last
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedBlock {
+    pub payload_type: u8,
+    pub timestamp: u32,
+    pub payload: Bytes,
+}
+
+// @see: RFC 2198 3. Payload Format
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |F|   block PT  |  timestamp offset         |   block length    |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |0|   block PT  |
+/// +-+-+-+-+-+-+-+-+
+///
+/// the payload of the previous packet goes ahead of the one of the packet,
+/// it is left out if it is too far back or too large for the header fields to tell.
+/// the red packet keeps the sequence number, timestamp and marker of the packet
+pub fn red_encapsulate(
+    packet: &RtpTrivialPacket,
+    payload_type: u8,
+    previous: Option<&RtpTrivialPacket>,
+) -> RtpTrivialPacket {
+    let redundant = previous.filter(|previous| {
+        let offset = packet
+            .header
+            .timestamp
+            .wrapping_sub(previous.header.timestamp);
+        offset <= RED_MAX_TIMESTAMP_OFFSET && previous.payload.len() <= RED_MAX_BLOCK_LENGTH
+    });
+    let mut payload = BytesMut::with_capacity(
+        RED_BLOCK_HEADER_BYTES
+            + RED_PRIMARY_HEADER_BYTES
+            + redundant.map_or(0, |previous| previous.payload.len())
+            + packet.payload.len(),
+    );
+    if let Some(previous) = redundant {
+        let offset = packet
+            .header
+            .timestamp
+            .wrapping_sub(previous.header.timestamp);
+        payload.put_u8(0x80 | (previous.header.payload_type & 0x7f));
+        payload.put_u8((offset >> 6) as u8);
+        payload.put_u8((((offset & 0x3f) << 2) as u8) | ((previous.payload.len() >> 8) as u8));
+        payload.put_u8(previous.payload.len() as u8);
+    }
+    payload.put_u8(packet.header.payload_type & 0x7f);
+    if let Some(previous) = redundant {
+        payload.put_slice(&previous.payload);
+    }
+    payload.put_slice(&packet.payload);
+    RtpTrivialPacket::new(
+        RtpHeader {
+            payload_type,
+            ..packet.header.clone()
+        },
+        payload.freeze(),
+    )
+}
+
+/// the blocks of a red packet, the redundant ones first and the primary one last
+pub fn red_decapsulate(packet: &RtpTrivialPacket) -> RtpResult<Vec<RedBlock>> {
+    let invalid = |reason: &str| RtpError::InvalidRed(reason.to_owned());
+    let mut reader = packet.payload.clone();
+    let mut headers = Vec::new();
+    let primary_payload_type = loop {
+        if !reader.has_remaining() {
+            return Err(invalid("the block headers end without a primary block"));
+        }
+        let first = reader.get_u8();
+        if first & 0x80 == 0 {
+            break first;
+        }
+        if reader.remaining() < RED_BLOCK_HEADER_BYTES - 1 {
+            return Err(invalid("truncated block header"));
+        }
+        let rest = [reader.get_u8(), reader.get_u8(), reader.get_u8()];
+        let offset = ((rest[0] as u32) << 6) | ((rest[1] as u32) >> 2);
+        let length = (((rest[1] & 0x03) as usize) << 8) | rest[2] as usize;
+        headers.push((first & 0x7f, offset, length));
+    };
+    let mut blocks = Vec::with_capacity(headers.len() + 1);
+    for (payload_type, offset, length) in headers {
+        if reader.remaining() < length {
+            return Err(invalid("a block is longer than the packet"));
+        }
+        blocks.push(RedBlock {
+            payload_type,
+            timestamp: packet.header.timestamp.wrapping_sub(offset),
+            payload: reader.split_to(length),
+        });
+    }
+    blocks.push(RedBlock {
+        payload_type: primary_payload_type,
+        timestamp: packet.header.timestamp,
+        payload: reader,
+    });
+    Ok(blocks)
+}
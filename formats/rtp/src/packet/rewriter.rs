@@ -0,0 +1,364 @@
+use crate::header::RtpHeader;
+
+/// a larger step forward of the sequence numbers is taken as another stream, RFC 3550 A.1
+const MAX_DROPOUT: i16 = 3000;
+/// a larger step backward of the sequence numbers is taken as another stream, RFC 3550 A.1
+const MAX_MISORDER: i16 = 100;
+/// the timestamp step assumed between two sources if none is seen yet, in milliseconds
+const DEFAULT_STEP_MS: u64 = 20;
+
+/// the last packet of the source the offsets are computed for
+#[derive(Debug, Clone, Copy)]
+struct SourcePosition {
+    ssrc: u32,
+    sequence_number: u16,
+    timestamp: u32,
+}
+
+/// keeps the ssrc, sequence numbers and timestamps of an outgoing rtp stream continuous
+/// while the packets fed to it come from different sources, e.g., a backup encoder taking over,
+/// so the decoder of the receiver does not reset
+#[derive(Debug)]
+pub struct RtpRewriter {
+    /// the ssrc of the first packet, kept for the whole stream
+    ssrc: Option<u32>,
+    source: Option<SourcePosition>,
+    sequence_number_offset: u16,
+    timestamp_offset: u32,
+    /// the sequence number and timestamp of the latest rewritten packet
+    last_out: Option<(u16, u32)>,
+    /// the latest timestamp step of the source, the next source continues after it
+    timestamp_step: u32,
+    max_timestamp_step: u32,
+    switch_pending: bool,
+}
+
+impl RtpRewriter {
+    pub fn new(clockrate: u64) -> Self {
+        Self {
+            ssrc: None,
+            source: None,
+            sequence_number_offset: 0,
+            timestamp_offset: 0,
+            last_out: None,
+            timestamp_step: (clockrate * DEFAULT_STEP_MS / 1000) as u32,
+            // a step of more than a second is a jump, not the frame interval
+            max_timestamp_step: clockrate as u32,
+            switch_pending: false,
+        }
+    }
+
+    /// the next packet comes from another source though its ssrc may be the same
+    pub fn switch_source(&mut self) {
+        self.switch_pending = true;
+    }
+
+    /// the packets of the first source keep their sequence numbers and timestamps,
+    /// the ones of a later source continue right after the last rewritten packet
+    pub fn rewrite(&mut self, header: &mut RtpHeader) {
+        let ssrc = *self.ssrc.get_or_insert(header.ssrc);
+        let input = SourcePosition {
+            ssrc: header.ssrc,
+            sequence_number: header.sequence_number,
+            timestamp: header.timestamp,
+        };
+        match self.source {
+            Some(source) if !self.switch_pending && source.ssrc == input.ssrc => {
+                let delta = input.sequence_number.wrapping_sub(source.sequence_number) as i16;
+                if !(-MAX_MISORDER..=MAX_DROPOUT).contains(&delta) {
+                    self.rebase(&input);
+                } else if delta > 0 {
+                    let step = input.timestamp.wrapping_sub(source.timestamp);
+                    if step > 0 && step <= self.max_timestamp_step {
+                        self.timestamp_step = step;
+                    }
+                    self.source = Some(input);
+                }
+            }
+            _ => self.rebase(&input),
+        }
+        self.switch_pending = false;
+
+        header.ssrc = ssrc;
+        header.sequence_number = input
+            .sequence_number
+            .wrapping_add(self.sequence_number_offset);
+        header.timestamp = input.timestamp.wrapping_add(self.timestamp_offset);
+        let is_newer = self.last_out.is_none_or(|(sequence_number, _)| {
+            header.sequence_number.wrapping_sub(sequence_number) as i16 > 0
+        });
+        if is_newer {
+            self.last_out = Some((header.sequence_number, header.timestamp));
+        }
+    }
+
+    fn rebase(&mut self, input: &SourcePosition) {
+        if let Some((sequence_number, timestamp)) = self.last_out {
+            self.sequence_number_offset = sequence_number
+                .wrapping_add(1)
+                .wrapping_sub(input.sequence_number);
+            self.timestamp_offset = timestamp
+                .wrapping_add(self.timestamp_step)
+                .wrapping_sub(input.timestamp);
+            tracing::info!(
+                "rtp source switched to ssrc {}, sequence number offset: {}, timestamp offset: {}",
+                input.ssrc,
+                self.sequence_number_offset,
+                self.timestamp_offset
+            );
+        }
+        self.source = Some(*input);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CLOCKRATE: u64 = 90000;
+    /// 25 fps
+    const FRAME_STEP: u32 = 3600;
+
+    fn header(ssrc: u32, sequence_number: u16, timestamp: u32) -> RtpHeader {
+        RtpHeader {
+            payload_type: 96,
+            ssrc,
+            sequence_number,
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    fn rewrite(
+        rewriter: &mut RtpRewriter,
+        ssrc: u32,
+        sequence_number: u16,
+        timestamp: u32,
+    ) -> RtpHeader {
+        let mut header = header(ssrc, sequence_number, timestamp);
+        rewriter.rewrite(&mut header);
+        header
+    }
+
+    #[test]
+    fn first_source_passes_through() {
+        let mut rewriter = RtpRewriter::new(CLOCKRATE);
+        for index in 0..10u16 {
+            let timestamp = 1000 + index as u32 * FRAME_STEP;
+            let header = rewrite(&mut rewriter, 7, 65530u16.wrapping_add(index), timestamp);
+            assert_eq!(header.ssrc, 7);
+            assert_eq!(header.sequence_number, 65530u16.wrapping_add(index));
+            assert_eq!(header.timestamp, timestamp);
+        }
+    }
+
+    #[test]
+    fn another_ssrc_continues_the_stream() {
+        let mut rewriter = RtpRewriter::new(CLOCKRATE);
+        for index in 0..10u16 {
+            rewrite(&mut rewriter, 7, 100 + index, index as u32 * FRAME_STEP);
+        }
+        let header = rewrite(&mut rewriter, 8, 40000, 123_456_789);
+        assert_eq!(header.ssrc, 7);
+        assert_eq!(header.sequence_number, 110);
+        assert_eq!(header.timestamp, 10 * FRAME_STEP);
+        let header = rewrite(&mut rewriter, 8, 40001, 123_456_789 + FRAME_STEP);
+        assert_eq!(header.sequence_number, 111);
+        assert_eq!(header.timestamp, 11 * FRAME_STEP);
+    }
+
+    #[test]
+    fn sequence_jump_of_the_same_ssrc_is_another_source() {
+        let mut rewriter = RtpRewriter::new(CLOCKRATE);
+        rewrite(&mut rewriter, 7, 0, 0);
+        rewrite(&mut rewriter, 7, 1, FRAME_STEP);
+        let header = rewrite(&mut rewriter, 7, 30000, 0);
+        assert_eq!(header.sequence_number, 2);
+        assert_eq!(header.timestamp, 2 * FRAME_STEP);
+
+        // the sequence numbers of a fresh packetizer start over, after a switch they are continued
+        rewriter.switch_source();
+        let header = rewrite(&mut rewriter, 7, 30001, 0);
+        assert_eq!(header.sequence_number, 3);
+        assert_eq!(header.timestamp, 3 * FRAME_STEP);
+    }
+
+    #[test]
+    fn misordered_and_lost_packets_keep_their_offsets() {
+        let mut rewriter = RtpRewriter::new(CLOCKRATE);
+        rewrite(&mut rewriter, 7, 10, 0);
+        rewrite(&mut rewriter, 8, 500, 0);
+        assert_eq!(
+            rewrite(&mut rewriter, 8, 503, FRAME_STEP).sequence_number,
+            14
+        );
+        assert_eq!(
+            rewrite(&mut rewriter, 8, 502, FRAME_STEP).sequence_number,
+            13
+        );
+        // a timestamp jump of the same source is kept, e.g., a paused and resumed encoder
+        let header = rewrite(&mut rewriter, 8, 504, 100 * FRAME_STEP);
+        assert_eq!(header.sequence_number, 15);
+        assert_eq!(
+            header.timestamp,
+            100 * FRAME_STEP + CLOCKRATE as u32 * 20 / 1000
+        );
+    }
+
+    mod failover {
+        use std::{collections::HashMap, time::Duration};
+
+        use stream_center::{
+            events::StreamCenterEvent,
+            failover::BACKUP_STREAM_KEY,
+            gop::MediaFrame,
+            identity::StreamIdentity,
+            stream_center::StreamCenter,
+            stream_source::{MediaSelection, PlayProtocol, PublishProtocol},
+            watchdog::IdleWatchdog,
+        };
+        use test_support::{
+            flv::FRAME_INTERVAL_MS,
+            frames::{audio_frame, video_frame},
+        };
+        use tokio::sync::mpsc;
+
+        use crate::{
+            codec::h264::{
+                packet::packetizer::RtpH264PacketPacketizer,
+                paramters::packetization_mode::PacketizationMode,
+            },
+            packet::{
+                packetizer::{RtpPacketizerItem, RtpTrivialPacketPacketizer},
+                rewriter::RtpRewriter,
+            },
+        };
+
+        const STALL_AFTER: Duration = Duration::from_secs(10);
+        const GOP_SIZE: u64 = 10;
+        /// larger than the mtu, so each frame is fragmented
+        const FRAME_BYTES: usize = 3000;
+
+        fn stream_id(stream_name: &str) -> StreamIdentity {
+            StreamIdentity::new("live", stream_name).unwrap()
+        }
+
+        async fn send_frames(sender: &mpsc::Sender<MediaFrame>, indexes: std::ops::Range<u64>) {
+            for index in indexes {
+                sender
+                    .send(video_frame(index, GOP_SIZE, FRAME_BYTES))
+                    .await
+                    .unwrap();
+                // the audio keeps the mix queue of the stream flowing
+                sender.send(audio_frame(index, 4)).await.unwrap();
+            }
+        }
+
+        /// a subscriber packetizing what it gets like an rtsp play session
+        struct FakeSubscriber {
+            receiver: mpsc::Receiver<MediaFrame>,
+            packetizer: RtpH264PacketPacketizer,
+            rewriter: RtpRewriter,
+            sent: Vec<(u16, u32)>,
+        }
+
+        impl FakeSubscriber {
+            async fn drain(&mut self) {
+                while let Ok(Some(frame)) =
+                    tokio::time::timeout(Duration::from_millis(200), self.receiver.recv()).await
+                {
+                    assert!(!frame.is_control());
+                    self.packetizer.set_frame_timestamps(
+                        frame.get_presentation_timestamp_ms(),
+                        frame.get_decode_timestamp_ms(),
+                    );
+                    let Some(item) = RtpPacketizerItem::from_media_frame(frame) else {
+                        continue;
+                    };
+                    self.packetizer.packetize(item).unwrap();
+                    for mut packet in self.packetizer.build().unwrap() {
+                        self.rewriter.rewrite(&mut packet.header);
+                        self.sent
+                            .push((packet.header.sequence_number, packet.header.timestamp));
+                    }
+                }
+            }
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn rtp_stream_goes_on_across_failover() {
+            let mut stream_center = StreamCenter::new();
+            stream_center.set_idle_watchdog(
+                "live",
+                IdleWatchdog::On {
+                    stall_after: STALL_AFTER,
+                    reap_after: STALL_AFTER * 3,
+                },
+            );
+            let event_sender: mpsc::UnboundedSender<StreamCenterEvent> =
+                stream_center.get_event_sender();
+            tokio::spawn(async move {
+                let _ = stream_center.run().await;
+            });
+            let primary = StreamCenter::publish(
+                &event_sender,
+                PublishProtocol::RTMP,
+                &stream_id("stream_a"),
+                &HashMap::from([(BACKUP_STREAM_KEY.to_owned(), "stream_a_backup".to_owned())]),
+            )
+            .await
+            .unwrap();
+            let backup = StreamCenter::publish(
+                &event_sender,
+                PublishProtocol::RTMP,
+                &stream_id("stream_a_backup"),
+                &HashMap::new(),
+            )
+            .await
+            .unwrap();
+            let response = StreamCenter::subscribe(
+                &event_sender,
+                PlayProtocol::RTSP,
+                &stream_id("stream_a"),
+                &HashMap::new(),
+                MediaSelection::video_only(),
+            )
+            .await
+            .unwrap();
+            let packetizer =
+                RtpH264PacketPacketizer::new(1400, PacketizationMode::NonInterleaved, 1234);
+            let clockrate = packetizer.get_rtp_clockrate();
+            let mut subscriber = FakeSubscriber {
+                receiver: response.media_receiver,
+                packetizer,
+                rewriter: RtpRewriter::new(clockrate),
+                sent: vec![],
+            };
+
+            send_frames(&primary, 0..GOP_SIZE).await;
+            send_frames(&backup, 1000..1000 + GOP_SIZE).await;
+            subscriber.drain().await;
+            tokio::time::sleep(STALL_AFTER).await;
+            send_frames(&backup, 1000 + GOP_SIZE..1000 + 2 * GOP_SIZE).await;
+            subscriber.drain().await;
+            send_frames(&primary, GOP_SIZE..3 * GOP_SIZE).await;
+            subscriber.drain().await;
+
+            // every frame is fragmented, so a lost frame would show up as a gap
+            assert!(subscriber.sent.len() > 4 * GOP_SIZE as usize * 3);
+            let max_step = (FRAME_INTERVAL_MS * clockrate / 1000) as u32;
+            for pair in subscriber.sent.windows(2) {
+                let ((sequence_number, timestamp), (next_sequence_number, next_timestamp)) =
+                    (pair[0], pair[1]);
+                assert_eq!(next_sequence_number, sequence_number.wrapping_add(1));
+                let step = next_timestamp.wrapping_sub(timestamp);
+                assert!(
+                    step <= max_step,
+                    "rtp timestamp {} -> {}",
+                    timestamp,
+                    next_timestamp
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use tokio_util::bytes::{BufMut, BytesMut};
+
+use crate::{
+    errors::{RtpError, RtpResult},
+    header::RtpHeader,
+};
+
+use super::RtpTrivialPacket;
+
+/// the original sequence number a retransmission puts before the payload
+pub const RTX_OSN_BYTES: usize = 2;
+
+// @see: RFC 4588 4. RTP Payload Format
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                         RTP Header                            |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |            OSN                |                               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+/// |                  Original RTP Packet Payload                  |
+/// |                                                               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// the retransmission stream is ssrc multiplexed, it has its own ssrc and sequence numbers,
+/// the timestamp and marker are those of the original packet
+pub fn rtx_encapsulate(
+    packet: &RtpTrivialPacket,
+    payload_type: u8,
+    ssrc: u32,
+    sequence_number: u16,
+) -> RtpTrivialPacket {
+    let mut payload = BytesMut::with_capacity(RTX_OSN_BYTES + packet.payload.len());
+    payload.put_u16(packet.header.sequence_number);
+    payload.put_slice(&packet.payload);
+    RtpTrivialPacket::new(
+        RtpHeader {
+            payload_type,
+            sequence_number,
+            ssrc,
+            ..packet.header.clone()
+        },
+        payload.freeze(),
+    )
+}
+
+/// restores the original packet from a retransmission
+pub fn rtx_decapsulate(
+    packet: &RtpTrivialPacket,
+    original_payload_type: u8,
+    original_ssrc: u32,
+) -> RtpResult<RtpTrivialPacket> {
+    if packet.payload.len() < 2 {
+        return Err(RtpError::EmptyPayload);
+    }
+    let original_sequence_number = u16::from_be_bytes([packet.payload[0], packet.payload[1]]);
+    Ok(RtpTrivialPacket::new(
+        RtpHeader {
+            payload_type: original_payload_type,
+            sequence_number: original_sequence_number,
+            ssrc: original_ssrc,
+            ..packet.header.clone()
+        },
+        packet.payload.slice(2..),
+    ))
+}
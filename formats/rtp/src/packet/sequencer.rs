@@ -1,6 +1,7 @@
 use super::RtpTrivialPacket;
 use crate::{
     codec::{
+        g711::{G711Law, sequencer::RtpG711BufferItem},
         h264::packet::sequencer::RtpH264BufferItem,
         mpeg4_generic::packet::sequencer::RtpMpeg4GenericBufferItem,
     },
@@ -9,7 +10,7 @@ use crate::{
 };
 use codec_common::{
     FrameType, MediaFrameTimestamp,
-    audio::{AudioCodecCommon, AudioFrameInfo, SoundInfoCommon},
+    audio::{AudioCodecCommon, AudioFrameInfo},
     video::VideoFrameInfo,
 };
 use std::{cmp, collections::VecDeque};
@@ -27,6 +28,7 @@ pub enum RtpBufferVideoItem {
 #[derive(Debug)]
 pub enum RtpBufferAudioItem {
     AAC(RtpMpeg4GenericBufferItem),
+    G711(RtpG711BufferItem),
 }
 
 #[derive(Debug)]
@@ -40,6 +42,7 @@ impl RtpBufferItem {
         match self {
             Self::Audio(audio) => match audio {
                 RtpBufferAudioItem::AAC(aac) => aac.access_unit.presentation_timestamp_ms,
+                RtpBufferAudioItem::G711(g711) => g711.rtp_header.timestamp,
             },
             Self::Video(video) => match video {
                 RtpBufferVideoItem::H264(h264) => h264
@@ -54,6 +57,7 @@ impl RtpBufferItem {
         match self {
             Self::Audio(audio) => match audio {
                 RtpBufferAudioItem::AAC(aac) => aac.rtp_header.sequence_number,
+                RtpBufferAudioItem::G711(g711) => g711.rtp_header.sequence_number,
             },
             Self::Video(video) => match video {
                 RtpBufferVideoItem::H264(h264) => h264.rtp_header.sequence_number,
@@ -94,19 +98,31 @@ impl RtpBufferItem {
                         .write_to(&mut bytes.as_mut().writer())
                         .unwrap();
                     MediaFrame::Audio {
-                        frame_info: AudioFrameInfo {
-                            codec_id: AudioCodecCommon::AAC,
-                            frame_type: FrameType::CodedFrames,
-                            sound_info: SoundInfoCommon {
-                                sound_rate: codec_common::audio::SoundRateCommon::KHZ44,
-                                sound_size: codec_common::audio::SoundSizeCommon::Bit16,
-                                sound_type: codec_common::audio::SoundTypeCommon::Stereo,
-                            },
-                            timestamp_nano: pts_nano,
-                        },
+                        frame_info: AudioFrameInfo::new(
+                            AudioCodecCommon::AAC,
+                            FrameType::CodedFrames,
+                            codec_common::audio::SoundRateCommon::KHZ44,
+                            codec_common::audio::SoundSizeCommon::Bit16,
+                            codec_common::audio::SoundTypeCommon::Stereo,
+                            pts_nano,
+                        ),
                         payload: bytes.freeze(),
                     }
                 }
+                RtpBufferAudioItem::G711(g711) => {
+                    let sound_info = G711Law::sound_info();
+                    MediaFrame::Audio {
+                        frame_info: AudioFrameInfo::new(
+                            g711.law.into(),
+                            FrameType::CodedFrames,
+                            sound_info.sound_rate,
+                            sound_info.sound_size,
+                            sound_info.sound_type,
+                            pts_nano,
+                        ),
+                        payload: g711.payload,
+                    }
+                }
             },
             RtpBufferItem::Video(video) => match video {
                 RtpBufferVideoItem::H264(h264) => {
@@ -122,15 +138,15 @@ impl RtpBufferItem {
                     }
                     nal_units.extend(h264.nal_units);
                     MediaFrame::Video {
-                        frame_info: VideoFrameInfo {
-                            codec_id: codec_common::video::VideoCodecCommon::AVC,
-                            frame_type: if is_idr {
+                        frame_info: VideoFrameInfo::new(
+                            codec_common::video::VideoCodecCommon::AVC,
+                            if is_idr {
                                 FrameType::KeyFrame
                             } else {
                                 FrameType::CodedFrames
                             },
-                            timestamp: MediaFrameTimestamp::with_timestamp_nano(pts_nano),
-                        },
+                            MediaFrameTimestamp::with_timestamp_nano(pts_nano),
+                        ),
                         payload: codec_common::video::VideoFrameUnit::H264 { nal_units },
                     }
                 }
@@ -142,6 +158,8 @@ impl RtpBufferItem {
 pub trait RtpBufferedSequencer {
     fn enqueue(&mut self, packet: RtpTrivialPacket) -> Result<(), RtpError>;
     fn try_dump(&mut self) -> Vec<RtpBufferItem>;
+    /// makes the items held for more packets ready, used when the stream ends
+    fn flush(&mut self) {}
 }
 
 /// RtpTrivialSequencer takes rtp packets from outside systems,
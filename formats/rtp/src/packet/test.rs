@@ -0,0 +1,423 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tokio_util::bytes::Bytes;
+    use utils::traits::{
+        reader::{ReadFrom, TryReadFrom},
+        writer::WriteTo,
+    };
+
+    use crate::{
+        errors::RtpError,
+        header::{RTP_FIXED_HEADER_SIZE, RtpHeader, RtpHeaderBuilder, RtpHeaderExtension},
+        packet::{
+            RtpTrivialPacket,
+            red::{RED_MAX_BLOCK_LENGTH, red_decapsulate, red_encapsulate},
+            ulpfec::{ulpfec_encode, ulpfec_protected, ulpfec_recover},
+        },
+    };
+
+    /// xorshift64, good enough to shake the parser without pulling in a fuzzer
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Option<RtpTrivialPacket>, RtpError> {
+        RtpTrivialPacket::try_read_from(&mut Cursor::new(bytes))
+    }
+
+    /// the parser may refuse anything, but what it accepts must fit in the buffer
+    fn parse_without_panic(bytes: &[u8]) {
+        if let Ok(Some(packet)) = parse(bytes) {
+            assert_eq!(packet.header.version, 2);
+            assert_eq!(packet.csrc_list().len(), packet.header.csrc_count as usize);
+            assert!(
+                RTP_FIXED_HEADER_SIZE + packet.csrc_list().len() * 4 + packet.payload.len()
+                    <= bytes.len()
+            );
+            let mut written = vec![];
+            packet.write_to(&mut written).unwrap();
+        }
+        let _ = RtpHeader::read_from(&mut Cursor::new(bytes));
+    }
+
+    fn header_with_csrcs(csrcs: &[u32]) -> RtpHeader {
+        let mut builder = RtpHeaderBuilder::new();
+        builder
+            .version(2)
+            .payload_type(96)
+            .sequence_number(1234)
+            .timestamp(90000)
+            .ssrc(0x1122_3344)
+            .marker(true);
+        for csrc in csrcs {
+            builder.csrc(*csrc).unwrap();
+        }
+        builder.build()
+    }
+
+    /// a mixed packet with an extension, padded since the payload is not 32-bit aligned
+    fn sample_packet_bytes() -> Vec<u8> {
+        let mut header = header_with_csrcs(&[1, 2, 3]);
+        header.set_extension(RtpHeaderExtension::one_byte(3, &[0xAA, 0xBB, 0xCC]).unwrap());
+        let packet = RtpTrivialPacket::new(header, Bytes::from_static(&[0x65, 1, 2, 3, 4]));
+        let mut bytes = vec![];
+        packet.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn mixed_padded_packet_round_trip() {
+        let bytes = sample_packet_bytes();
+        let packet = parse(&bytes).unwrap().unwrap();
+        assert_eq!(packet.csrc_list(), &[1, 2, 3]);
+        assert!(packet.header.padding);
+        assert!(packet.header.marker);
+        assert_eq!(packet.header.payload_type, 96);
+        assert_eq!(packet.header.sequence_number, 1234);
+        assert_eq!(packet.header.ssrc, 0x1122_3344);
+        assert_eq!(
+            packet
+                .header
+                .header_extension
+                .as_ref()
+                .unwrap()
+                .bytes()
+                .len(),
+            4
+        );
+        assert_eq!(packet.payload, Bytes::from_static(&[0x65, 1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn read_from_takes_the_payload_type_from_the_second_byte() {
+        let mut bytes = vec![];
+        header_with_csrcs(&[7]).write_to(&mut bytes).unwrap();
+        let header = RtpHeader::read_from(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(header.payload_type, 96);
+        assert_eq!(header.csrc_list, vec![7]);
+    }
+
+    #[test]
+    fn short_fixed_header_needs_more_bytes() {
+        let bytes = sample_packet_bytes();
+        assert!(
+            parse(&bytes[..RTP_FIXED_HEADER_SIZE - 1])
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn csrc_count_beyond_the_buffer_is_truncated_header() {
+        let mut bytes = vec![];
+        header_with_csrcs(&[]).write_to(&mut bytes).unwrap();
+        bytes[0] |= 0x0F;
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(matches!(
+            parse(&bytes),
+            Err(RtpError::TruncatedRtpHeader {
+                field: "csrc list",
+                needed: 60,
+                remaining: 8
+            })
+        ));
+        assert!(RtpHeader::read_from(&mut Cursor::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn extension_beyond_the_buffer_is_truncated_header() {
+        let mut bytes = vec![];
+        header_with_csrcs(&[]).write_to(&mut bytes).unwrap();
+        bytes[0] |= 0x10;
+        // the extension header itself is cut
+        assert!(matches!(
+            parse(&[&bytes[..], &[0xBE, 0xDE]].concat()),
+            Err(RtpError::TruncatedRtpHeader {
+                field: "header extension",
+                needed: 4,
+                remaining: 2
+            })
+        ));
+        // 2 words declared, 1 present
+        assert!(matches!(
+            parse(&[&bytes[..], &[0xBE, 0xDE, 0x00, 0x02, 1, 2, 3, 4]].concat()),
+            Err(RtpError::TruncatedRtpHeader {
+                field: "header extension",
+                needed: 12,
+                remaining: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn version_other_than_2_is_rejected() {
+        let mut bytes = sample_packet_bytes();
+        for version in [0, 1, 3] {
+            bytes[0] = (bytes[0] & 0b0011_1111) | (version << 6);
+            assert!(matches!(parse(&bytes), Err(RtpError::BadRtpVersion(v)) if v == version));
+            assert!(matches!(
+                RtpHeader::read_from(&mut Cursor::new(&bytes)),
+                Err(RtpError::BadRtpVersion(v)) if v == version
+            ));
+        }
+    }
+
+    fn padded_packet_bytes(payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut header = header_with_csrcs(&[]);
+        header.padding = true;
+        header.write_to(&mut bytes).unwrap();
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn padding_longer_than_the_payload_is_bad_padding() {
+        assert!(matches!(
+            parse(&padded_packet_bytes(&[1, 2, 0, 200])),
+            Err(RtpError::BadPaddingSize(200))
+        ));
+    }
+
+    #[test]
+    fn zero_padding_count_is_bad_padding() {
+        assert!(matches!(
+            parse(&padded_packet_bytes(&[1, 2, 3, 0])),
+            Err(RtpError::BadPaddingSize(0))
+        ));
+    }
+
+    #[test]
+    fn padding_only_packet_is_empty_payload() {
+        assert!(matches!(
+            parse(&padded_packet_bytes(&[0, 0, 0, 4])),
+            Err(RtpError::EmptyPayload)
+        ));
+        assert!(matches!(
+            parse(&padded_packet_bytes(&[])),
+            Err(RtpError::EmptyPayload)
+        ));
+        let packet = parse(&padded_packet_bytes(&[9, 0, 0, 3])).unwrap().unwrap();
+        assert_eq!(packet.payload, Bytes::from_static(&[9]));
+    }
+
+    #[test]
+    fn random_buffers_do_not_panic() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..20_000 {
+            let len = rng.below(80);
+            let mut bytes = rng.bytes(len);
+            // most random first bytes are not version 2, keep half of them past the version check
+            if let Some(first) = bytes.first_mut()
+                && rng.next() & 1 == 0
+            {
+                *first = (*first & 0b0011_1111) | 0b1000_0000;
+            }
+            parse_without_panic(&bytes);
+        }
+    }
+
+    #[test]
+    fn mutated_packets_do_not_panic() {
+        let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+        let sample = sample_packet_bytes();
+        for _ in 0..20_000 {
+            let mut bytes = sample.clone();
+            for _ in 0..1 + rng.below(3) {
+                match rng.below(6) {
+                    // csrc count
+                    0 => bytes[0] = (bytes[0] & 0xF0) | rng.below(16) as u8,
+                    // padding and extension bits
+                    1 => bytes[0] ^= [0x20, 0x10, 0x30][rng.below(3)],
+                    // extension length, right after the 3 csrcs
+                    2 => bytes[RTP_FIXED_HEADER_SIZE + 12 + 3] = rng.next() as u8,
+                    // padding count
+                    3 => *bytes.last_mut().unwrap() = rng.next() as u8,
+                    // truncation
+                    4 => bytes.truncate(rng.below(bytes.len() + 1)),
+                    // any bit
+                    _ => {
+                        let index = rng.below(bytes.len().max(1));
+                        if let Some(byte) = bytes.get_mut(index) {
+                            *byte ^= 1 << rng.below(8);
+                        }
+                    }
+                }
+                if bytes.len() <= RTP_FIXED_HEADER_SIZE + 16 {
+                    break;
+                }
+            }
+            parse_without_panic(&bytes);
+        }
+    }
+
+    /// a frame of packets of different sizes, one mixed and one with an extension
+    fn video_packets(rng: &mut Rng) -> Vec<RtpTrivialPacket> {
+        (0..5_u16)
+            .map(|index| {
+                let mut header = header_with_csrcs(if index == 1 { &[5, 6] } else { &[] });
+                header.sequence_number = 65534_u16.wrapping_add(index);
+                header.marker = index == 4;
+                if index == 2 {
+                    header.set_extension(RtpHeaderExtension::one_byte(1, &[1, 2, 3]).unwrap());
+                }
+                RtpTrivialPacket::new(header, Bytes::from(rng.bytes(200 + 37 * index as usize)))
+            })
+            .collect()
+    }
+
+    fn assert_same_packet(recovered: &RtpTrivialPacket, original: &RtpTrivialPacket) {
+        let (mut recovered_bytes, mut original_bytes) = (vec![], vec![]);
+        recovered.write_to(&mut recovered_bytes).unwrap();
+        original.write_to(&mut original_bytes).unwrap();
+        assert_eq!(recovered_bytes, original_bytes);
+    }
+
+    #[test]
+    fn any_single_loss_of_an_ulpfec_group_is_recovered() {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        let packets = video_packets(&mut rng);
+        let fec = ulpfec_encode(&packets, 101, 0xFEC0, 7).unwrap();
+        assert_eq!(fec.header.payload_type, 101);
+        assert_eq!(fec.header.ssrc, 0xFEC0);
+        assert_eq!(fec.header.timestamp, packets[4].header.timestamp);
+        // the sequence numbers wrap inside the group
+        assert_eq!(ulpfec_protected(&fec).unwrap(), vec![65534, 65535, 0, 1, 2]);
+
+        // the receiver parses the fec packet off the wire
+        let mut bytes = vec![];
+        fec.write_to(&mut bytes).unwrap();
+        let fec = parse(&bytes).unwrap().unwrap();
+        for lost in 0..packets.len() {
+            let received: Vec<_> = packets
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != lost)
+                .map(|(_, packet)| packet.clone())
+                .collect();
+            let recovered = ulpfec_recover(&fec, &received, 0x1122_3344)
+                .unwrap()
+                .unwrap();
+            assert_same_packet(&recovered, &packets[lost]);
+        }
+
+        // nothing to recover, or too much
+        assert!(
+            ulpfec_recover(&fec, &packets, 0x1122_3344)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            ulpfec_recover(&fec, &packets[2..], 0x1122_3344)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn ulpfec_groups_fit_the_short_mask() {
+        let mut rng = Rng(0x1234_5678_9ABC_DEF0);
+        let mut packets = video_packets(&mut rng);
+        packets[4].header.sequence_number = packets[0].header.sequence_number.wrapping_add(16);
+        assert!(matches!(
+            ulpfec_encode(&packets, 101, 1, 1),
+            Err(RtpError::InvalidUlpfec(_))
+        ));
+        assert!(matches!(
+            ulpfec_encode(&[], 101, 1, 1),
+            Err(RtpError::InvalidUlpfec(_))
+        ));
+    }
+
+    fn audio_packet(index: u16, payload: Vec<u8>) -> RtpTrivialPacket {
+        let mut header = header_with_csrcs(&[]);
+        header.payload_type = 97;
+        header.marker = true;
+        header.sequence_number = index;
+        header.timestamp = 1024 * index as u32;
+        RtpTrivialPacket::new(header, Bytes::from(payload))
+    }
+
+    #[test]
+    fn red_carries_the_previous_frame_to_recover_a_loss() {
+        let mut rng = Rng(0x0DDB_1A5E_5BAD_5EED);
+        let frames: Vec<_> = (0..4)
+            .map(|index| audio_packet(index, rng.bytes(100 + index as usize)))
+            .collect();
+        let red: Vec<_> = frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                red_encapsulate(
+                    frame,
+                    100,
+                    index.checked_sub(1).map(|previous| &frames[previous]),
+                )
+            })
+            .collect();
+        assert!(red.iter().all(|packet| packet.header.payload_type == 100));
+        assert_eq!(red[2].header.sequence_number, 2);
+
+        // the first one has nothing before it
+        let blocks = red_decapsulate(&red[0]).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].payload, frames[0].payload);
+
+        // the second frame is lost, the third packet brings it back
+        let mut bytes = vec![];
+        red[2].write_to(&mut bytes).unwrap();
+        let blocks = red_decapsulate(&parse(&bytes).unwrap().unwrap()).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].payload_type, 97);
+        assert_eq!(blocks[0].timestamp, frames[1].header.timestamp);
+        assert_eq!(blocks[0].payload, frames[1].payload);
+        assert_eq!(blocks[1].payload_type, 97);
+        assert_eq!(blocks[1].timestamp, frames[2].header.timestamp);
+        assert_eq!(blocks[1].payload, frames[2].payload);
+    }
+
+    #[test]
+    fn red_leaves_out_what_its_header_can_not_tell() {
+        let large = audio_packet(0, vec![1; RED_MAX_BLOCK_LENGTH + 1]);
+        let next = audio_packet(1, vec![2; 10]);
+        assert_eq!(
+            red_decapsulate(&red_encapsulate(&next, 100, Some(&large)))
+                .unwrap()
+                .len(),
+            1
+        );
+        let long_ago = audio_packet(0, vec![1; 10]);
+        let mut late = audio_packet(1, vec![2; 10]);
+        late.header.timestamp = 0x4000;
+        assert_eq!(
+            red_decapsulate(&red_encapsulate(&late, 100, Some(&long_ago)))
+                .unwrap()
+                .len(),
+            1
+        );
+        // a block longer than the packet
+        let mut truncated = red_encapsulate(&next, 100, Some(&long_ago));
+        truncated.payload = truncated.payload.slice(..8);
+        assert!(matches!(
+            red_decapsulate(&truncated),
+            Err(RtpError::InvalidRed(_))
+        ));
+    }
+}
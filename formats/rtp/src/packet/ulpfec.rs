@@ -0,0 +1,243 @@
+use std::io::Cursor;
+
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use utils::traits::{reader::TryReadFrom, writer::WriteTo};
+
+use crate::{
+    errors::{RtpError, RtpResult},
+    header::{RTP_FIXED_HEADER_SIZE, RtpHeader},
+};
+
+use super::RtpTrivialPacket;
+
+pub const ULPFEC_FEC_HEADER_BYTES: usize = 10;
+/// the level header with the short mask, the one sent
+pub const ULPFEC_LEVEL_HEADER_BYTES: usize = 4;
+/// the level header with the long mask
+pub const ULPFEC_LONG_LEVEL_HEADER_BYTES: usize = 8;
+/// what a fec packet adds to the largest packet of its group
+pub const ULPFEC_HEADER_BYTES: usize = ULPFEC_FEC_HEADER_BYTES + ULPFEC_LEVEL_HEADER_BYTES;
+/// the bits of the short mask
+pub const ULPFEC_MAX_GROUP_SIZE: usize = 16;
+
+/// the fields of a packet the recovery fields are the xor of, RFC 5109 7.3,
+/// the tail is everything after the fixed header: the csrcs, the extension, the payload and the padding
+#[derive(Debug, Default)]
+struct RecoveryBits {
+    first: u8,
+    second: u8,
+    timestamp: u32,
+    length: u16,
+    tail: Vec<u8>,
+}
+
+impl RecoveryBits {
+    fn of(packet: &RtpTrivialPacket) -> RtpResult<Self> {
+        let mut bytes = Vec::new();
+        packet.write_to(&mut bytes)?;
+        let tail = bytes.split_off(RTP_FIXED_HEADER_SIZE);
+        Ok(Self {
+            first: bytes[0],
+            second: bytes[1],
+            timestamp: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            length: u16::try_from(tail.len()).map_err(|_| RtpError::PayloadTooLarge(tail.len()))?,
+            tail,
+        })
+    }
+
+    /// the tails are taken as padded with zeros up to the protection length
+    fn xor(&mut self, other: &Self, protection_length: usize) {
+        self.first ^= other.first;
+        self.second ^= other.second;
+        self.timestamp ^= other.timestamp;
+        self.length ^= other.length;
+        self.tail.resize(protection_length, 0);
+        self.tail
+            .iter_mut()
+            .zip(&other.tail)
+            .for_each(|(byte, other)| *byte ^= other);
+    }
+}
+
+// @see: RFC 5109 7.3 FEC Header for FEC Packets, 7.4 FEC Level Header for FEC Packets
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |E|L|P|X|  CC   |M| PT recovery |            SN base            |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                          TS recovery                          |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |        length recovery        |       Protection Length       |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |             mask              |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// one level 0 fec packet protecting all of the packets, each of them can be recovered
+/// from the fec packet and the others. the packets are in the order of their sequence numbers,
+/// 16 at most from the first one. the fec packet has the timestamp of the last packet
+pub fn ulpfec_encode(
+    packets: &[RtpTrivialPacket],
+    payload_type: u8,
+    ssrc: u32,
+    sequence_number: u16,
+) -> RtpResult<RtpTrivialPacket> {
+    let (Some(first), Some(last)) = (packets.first(), packets.last()) else {
+        return Err(RtpError::InvalidUlpfec("nothing to protect".to_owned()));
+    };
+    let base = first.header.sequence_number;
+    let mut mask = 0_u16;
+    for packet in packets {
+        let offset = packet.header.sequence_number.wrapping_sub(base) as usize;
+        if offset >= ULPFEC_MAX_GROUP_SIZE {
+            return Err(RtpError::InvalidUlpfec(format!(
+                "sequence number {} is out of the mask of base {}",
+                packet.header.sequence_number, base
+            )));
+        }
+        mask |= 0x8000 >> offset;
+    }
+    let bits = packets
+        .iter()
+        .map(RecoveryBits::of)
+        .collect::<RtpResult<Vec<_>>>()?;
+    let protection_length = bits.iter().map(|bits| bits.tail.len()).max().unwrap_or(0);
+    let mut recovery = RecoveryBits::default();
+    bits.iter()
+        .for_each(|bits| recovery.xor(bits, protection_length));
+
+    let mut payload = BytesMut::with_capacity(ULPFEC_HEADER_BYTES + protection_length);
+    // E and L are 0, the short mask is used
+    payload.put_u8(recovery.first & 0x3f);
+    payload.put_u8(recovery.second);
+    payload.put_u16(base);
+    payload.put_u32(recovery.timestamp);
+    payload.put_u16(recovery.length);
+    payload.put_u16(protection_length as u16);
+    payload.put_u16(mask);
+    payload.put_slice(&recovery.tail);
+    Ok(RtpTrivialPacket::new(
+        RtpHeader {
+            payload_type,
+            sequence_number,
+            timestamp: last.header.timestamp,
+            ssrc,
+            ..Default::default()
+        },
+        payload.freeze(),
+    ))
+}
+
+/// the sequence numbers the fec packet protects
+pub fn ulpfec_protected(fec: &RtpTrivialPacket) -> RtpResult<Vec<u16>> {
+    Ok(UlpfecHeader::read(fec)?.protected())
+}
+
+/// the packet of the group of the fec packet missing from the received ones, with the ssrc of the media.
+/// None if none or more than one of the group is missing
+pub fn ulpfec_recover(
+    fec: &RtpTrivialPacket,
+    received: &[RtpTrivialPacket],
+    media_ssrc: u32,
+) -> RtpResult<Option<RtpTrivialPacket>> {
+    let header = UlpfecHeader::read(fec)?;
+    let protected = header.protected();
+    let mut missing = protected.iter().filter(|sequence_number| {
+        !received
+            .iter()
+            .any(|packet| packet.header.sequence_number == **sequence_number)
+    });
+    let (Some(missing), None) = (missing.next(), missing.next()) else {
+        return Ok(None);
+    };
+    let protection_length = header.recovery.tail.len();
+    let mut recovery = header.recovery;
+    for packet in received
+        .iter()
+        .filter(|packet| protected.contains(&packet.header.sequence_number))
+    {
+        recovery.xor(&RecoveryBits::of(packet)?, protection_length);
+    }
+    let length = recovery.length as usize;
+    if length > protection_length {
+        return Err(RtpError::InvalidUlpfec(format!(
+            "recovered length {} exceeds the protection length {}",
+            length, protection_length
+        )));
+    }
+    let mut bytes = BytesMut::with_capacity(RTP_FIXED_HEADER_SIZE + length);
+    bytes.put_u8(0x80 | (recovery.first & 0x3f));
+    bytes.put_u8(recovery.second);
+    bytes.put_u16(*missing);
+    bytes.put_u32(recovery.timestamp);
+    bytes.put_u32(media_ssrc);
+    bytes.put_slice(&recovery.tail[..length]);
+    RtpTrivialPacket::try_read_from(&mut Cursor::new(bytes.freeze()))
+}
+
+/// the fec header and the level 0 header of a fec packet, with the recovery fields
+struct UlpfecHeader {
+    base: u16,
+    mask: u64,
+    mask_bits: usize,
+    recovery: RecoveryBits,
+}
+
+impl UlpfecHeader {
+    fn read(fec: &RtpTrivialPacket) -> RtpResult<Self> {
+        let invalid = |reason: String| RtpError::InvalidUlpfec(reason);
+        let mut reader = fec.payload.clone();
+        if reader.remaining() < ULPFEC_HEADER_BYTES {
+            return Err(invalid(format!(
+                "{} bytes are too short",
+                reader.remaining()
+            )));
+        }
+        let first = reader.get_u8();
+        if first & 0x80 != 0 {
+            return Err(invalid("the extension flag is reserved".to_owned()));
+        }
+        let long_mask = first & 0x40 != 0;
+        let second = reader.get_u8();
+        let base = reader.get_u16();
+        let timestamp = reader.get_u32();
+        let length = reader.get_u16();
+        let protection_length = reader.get_u16() as usize;
+        let (mask, mask_bits) = if long_mask {
+            if reader.remaining() < ULPFEC_LONG_LEVEL_HEADER_BYTES - 2 {
+                return Err(invalid("truncated long mask".to_owned()));
+            }
+            (
+                ((reader.get_u16() as u64) << 32) | reader.get_u32() as u64,
+                48,
+            )
+        } else {
+            (reader.get_u16() as u64, 16)
+        };
+        if reader.remaining() < protection_length {
+            return Err(invalid(format!(
+                "protection length {} exceeds the {} bytes left",
+                protection_length,
+                reader.remaining()
+            )));
+        }
+        Ok(Self {
+            base,
+            mask,
+            mask_bits,
+            recovery: RecoveryBits {
+                first,
+                second,
+                timestamp,
+                length,
+                tail: reader[..protection_length].to_vec(),
+            },
+        })
+    }
+
+    fn protected(&self) -> Vec<u16> {
+        (0..self.mask_bits)
+            .filter(|offset| self.mask & (1 << (self.mask_bits - 1 - offset)) != 0)
+            .map(|offset| self.base.wrapping_add(offset as u16))
+            .collect()
+    }
+}
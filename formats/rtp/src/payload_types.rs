@@ -10,14 +10,38 @@ pub enum PayloadType {
 pub mod rtp_payload_type {
     use codec_common::{audio::AudioCodecCommon, video::VideoCodecCommon};
 
+    /// static payload types, RFC 3551 6
+    pub const PCMU_AUDIO: u8 = 0;
+    pub const PCMA_AUDIO: u8 = 8;
     pub const MGEP4_AUDIO: u8 = 97;
     pub const H264_VIDEO: u8 = 96;
+    /// retransmission payload types, RFC 4588
+    pub const H264_VIDEO_RTX: u8 = 98;
+    pub const MGEP4_AUDIO_RTX: u8 = 99;
+    pub const RTX_ENCODING_NAME: &str = "rtx";
+    /// redundant audio of any audio payload type, RFC 2198
+    pub const RED_AUDIO: u8 = 100;
+    pub const RED_ENCODING_NAME: &str = "red";
+    /// parity fec of any video payload type, RFC 5109
+    pub const ULPFEC_VIDEO: u8 = 101;
+    pub const ULPFEC_ENCODING_NAME: &str = "ulpfec";
+
+    /// the payload type to retransmit the original payload type with
+    pub fn get_rtx_payload_type(payload_type: u8) -> Option<u8> {
+        match payload_type {
+            H264_VIDEO => Some(H264_VIDEO_RTX),
+            MGEP4_AUDIO => Some(MGEP4_AUDIO_RTX),
+            _ => None,
+        }
+    }
 
     pub fn get_rtp_payload_type(encoding_name: &str) -> Option<u8> {
         match encoding_name.to_lowercase().as_str() {
             "mpeg4-generic" => Some(MGEP4_AUDIO),
             "aac" => Some(MGEP4_AUDIO),
             "h264" => Some(H264_VIDEO),
+            "pcmu" => Some(PCMU_AUDIO),
+            "pcma" => Some(PCMA_AUDIO),
             _ => None,
         }
     }
@@ -27,6 +51,8 @@ pub mod rtp_payload_type {
             "mpeg4-generic" => Some(1000),
             "aac" => Some(1000),
             "h264" => Some(90000),
+            "pcmu" => Some(8000),
+            "pcma" => Some(8000),
             _ => None,
         }
     }
@@ -34,6 +60,8 @@ pub mod rtp_payload_type {
     pub fn audio_get_rtp_encoding_name(codec: AudioCodecCommon) -> Option<&'static str> {
         match codec {
             AudioCodecCommon::AAC => Some("mpeg4-generic"),
+            AudioCodecCommon::G711MULawLogarithmicPCM => Some("PCMU"),
+            AudioCodecCommon::G711ALawLogarithmicPCM => Some("PCMA"),
             _ => None,
         }
     }
@@ -1 +1,3 @@
 pub mod avp;
+#[cfg(feature = "srtp")]
+pub mod savp;
@@ -0,0 +1,32 @@
+use aes::Aes128;
+use ctr::{
+    Ctr128BE,
+    cipher::{KeyIvInit, StreamCipher},
+};
+
+use super::{MASTER_KEY_BYTES, SrtpMasterKey};
+
+/// @see: RFC 3711 4.3.2
+pub(super) const LABEL_RTP_ENCRYPTION: u8 = 0x00;
+pub(super) const LABEL_RTP_AUTHENTICATION: u8 = 0x01;
+pub(super) const LABEL_RTP_SALT: u8 = 0x02;
+pub(super) const LABEL_RTCP_ENCRYPTION: u8 = 0x03;
+pub(super) const LABEL_RTCP_AUTHENTICATION: u8 = 0x04;
+pub(super) const LABEL_RTCP_SALT: u8 = 0x05;
+
+/// AES in counter mode, the iv is the first counter block, RFC 3711 4.1.1
+pub(super) fn apply_aes_cm_keystream(key: &[u8; MASTER_KEY_BYTES], iv: &[u8; 16], data: &mut [u8]) {
+    let mut cipher = Ctr128BE::<Aes128>::new(key.into(), iv.into());
+    cipher.apply_keystream(data);
+}
+
+/// fills `output` with the session key of `label`, RFC 3711 4.3.1.
+/// the key derivation rate is 0, so a session key is derived once for all the packets
+pub(super) fn derive_session_key(master: &SrtpMasterKey, label: u8, output: &mut [u8]) {
+    // x = key_id XOR master_salt, the key_id is the label followed by 48 zero bits
+    let mut iv = [0u8; 16];
+    iv[..master.salt.len()].copy_from_slice(&master.salt);
+    iv[7] ^= label;
+    output.fill(0);
+    apply_aes_cm_keystream(&master.key, &iv, output);
+}
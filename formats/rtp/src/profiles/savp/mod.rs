@@ -0,0 +1,420 @@
+//! the secure real-time transport protocol, RFC 3711,
+//! with the AES_CM_128_HMAC_SHA1_80 crypto suite and keys signaled by sdes, RFC 4568
+
+use std::{collections::HashMap, fmt};
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use key_derivation::{
+    LABEL_RTCP_AUTHENTICATION, LABEL_RTCP_ENCRYPTION, LABEL_RTCP_SALT, LABEL_RTP_AUTHENTICATION,
+    LABEL_RTP_ENCRYPTION, LABEL_RTP_SALT, apply_aes_cm_keystream, derive_session_key,
+};
+use sha1::Sha1;
+use utils::random::random_fill;
+
+use crate::errors::{RtpError, RtpResult};
+
+mod key_derivation;
+#[cfg(test)]
+mod test;
+
+/// the name of the crypto suite in a=crypto
+pub const AES_CM_128_HMAC_SHA1_80: &str = "AES_CM_128_HMAC_SHA1_80";
+pub const MASTER_KEY_BYTES: usize = 16;
+pub const MASTER_SALT_BYTES: usize = 14;
+const AUTH_KEY_BYTES: usize = 20;
+/// the hmac-sha1 is truncated to 80 bits
+pub const AUTH_TAG_BYTES: usize = 10;
+/// the E flag and the index srtcp packets end with, before the tag
+pub const SRTCP_INDEX_BYTES: usize = 4;
+const SRTCP_E_FLAG: u32 = 0x8000_0000;
+const MAX_SRTCP_INDEX: u32 = 0x7FFF_FFFF;
+const RTP_FIXED_HEADER_BYTES: usize = 12;
+const RTCP_FIXED_HEADER_BYTES: usize = 8;
+/// the packets this far behind the latest one are taken as replayed, RFC 3711 3.3.2
+const REPLAY_WINDOW_SIZE: u64 = 64;
+
+/// the master key and salt of a crypto context, the session keys are derived from them
+#[derive(Clone, PartialEq, Eq)]
+pub struct SrtpMasterKey {
+    pub key: [u8; MASTER_KEY_BYTES],
+    pub salt: [u8; MASTER_SALT_BYTES],
+}
+
+impl fmt::Debug for SrtpMasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SrtpMasterKey {{ .. }}")
+    }
+}
+
+impl SrtpMasterKey {
+    pub fn random() -> Self {
+        let mut key = [0; MASTER_KEY_BYTES];
+        let mut salt = [0; MASTER_SALT_BYTES];
+        random_fill(&mut key);
+        random_fill(&mut salt);
+        Self { key, salt }
+    }
+
+    /// the key-params of a=crypto, "inline:" followed by the base64 of the key and the salt,
+    /// @see: RFC 4568 6.1
+    pub fn to_key_params(&self) -> String {
+        let mut bytes = Vec::with_capacity(MASTER_KEY_BYTES + MASTER_SALT_BYTES);
+        bytes.extend_from_slice(&self.key);
+        bytes.extend_from_slice(&self.salt);
+        format!("inline:{}", base64::prelude::BASE64_STANDARD.encode(bytes))
+    }
+
+    /// a lifetime is ignored, the keys are not renewed, an mki is not supported
+    pub fn from_key_params(key_params: &str) -> RtpResult<Self> {
+        let key_info = key_params
+            .strip_prefix("inline:")
+            .ok_or_else(|| RtpError::InvalidSrtpKeyParams(format!("not inline: {}", key_params)))?;
+        let mut parts = key_info.split('|');
+        let key_salt = parts.next().unwrap_or_default();
+        if parts.any(|part| part.contains(':')) {
+            return Err(RtpError::InvalidSrtpKeyParams(format!(
+                "mki is not supported: {}",
+                key_params
+            )));
+        }
+        let bytes = base64::prelude::BASE64_STANDARD
+            .decode(key_salt)
+            .map_err(|err| RtpError::InvalidSrtpKeyParams(format!("{}: {}", key_params, err)))?;
+        if bytes.len() != MASTER_KEY_BYTES + MASTER_SALT_BYTES {
+            return Err(RtpError::InvalidSrtpKeyParams(format!(
+                "expect {} bytes of key and salt, got {}",
+                MASTER_KEY_BYTES + MASTER_SALT_BYTES,
+                bytes.len()
+            )));
+        }
+        let (key, salt) = bytes.split_at(MASTER_KEY_BYTES);
+        Ok(Self {
+            key: key.try_into().unwrap(),
+            salt: salt.try_into().unwrap(),
+        })
+    }
+}
+
+/// the keys derived for rtp or for rtcp, RFC 3711 4.3
+#[derive(Clone)]
+struct SessionKeys {
+    cipher_key: [u8; MASTER_KEY_BYTES],
+    salt: [u8; MASTER_SALT_BYTES],
+    mac: Hmac<Sha1>,
+}
+
+impl SessionKeys {
+    fn derive(master: &SrtpMasterKey, [encryption, authentication, salting]: [u8; 3]) -> Self {
+        let mut cipher_key = [0; MASTER_KEY_BYTES];
+        let mut salt = [0; MASTER_SALT_BYTES];
+        let mut auth_key = [0; AUTH_KEY_BYTES];
+        derive_session_key(master, encryption, &mut cipher_key);
+        derive_session_key(master, salting, &mut salt);
+        derive_session_key(master, authentication, &mut auth_key);
+        Self {
+            cipher_key,
+            salt,
+            mac: Hmac::<Sha1>::new_from_slice(&auth_key).unwrap(),
+        }
+    }
+
+    /// the counter starts at salt * 2^16 XOR ssrc * 2^64 XOR index * 2^16, RFC 3711 4.1.1
+    fn apply_keystream(&self, ssrc: u32, index: u64, data: &mut [u8]) {
+        let mut iv = [0u8; 16];
+        iv[..MASTER_SALT_BYTES].copy_from_slice(&self.salt);
+        for (byte, ssrc_byte) in iv[4..8].iter_mut().zip(ssrc.to_be_bytes()) {
+            *byte ^= ssrc_byte;
+        }
+        for (byte, index_byte) in iv[8..14].iter_mut().zip(&index.to_be_bytes()[2..]) {
+            *byte ^= index_byte;
+        }
+        apply_aes_cm_keystream(&self.cipher_key, &iv, data);
+    }
+
+    fn tag(&self, parts: &[&[u8]]) -> [u8; AUTH_TAG_BYTES] {
+        let mut mac = self.mac.clone();
+        parts.iter().for_each(|part| mac.update(part));
+        mac.finalize().into_bytes()[..AUTH_TAG_BYTES]
+            .try_into()
+            .unwrap()
+    }
+
+    /// compared in constant time
+    fn verify(&self, parts: &[&[u8]], tag: &[u8]) -> bool {
+        let mut mac = self.mac.clone();
+        parts.iter().for_each(|part| mac.update(part));
+        mac.verify_truncated_left(tag).is_ok()
+    }
+}
+
+/// tells the replayed indexes from the new ones, RFC 3711 3.3.2
+#[derive(Debug, Default, Clone, Copy)]
+struct ReplayWindow {
+    highest: Option<u64>,
+    /// bit n is set if the index highest - n was received
+    received: u64,
+}
+
+impl ReplayWindow {
+    fn accepts(&self, index: u64) -> bool {
+        match self.highest {
+            Some(highest) if index <= highest => {
+                let behind = highest - index;
+                behind < REPLAY_WINDOW_SIZE && self.received & (1 << behind) == 0
+            }
+            _ => true,
+        }
+    }
+
+    fn mark(&mut self, index: u64) {
+        match self.highest {
+            Some(highest) if index <= highest => self.received |= 1 << (highest - index),
+            Some(highest) => {
+                let ahead = index - highest;
+                self.received = if ahead < REPLAY_WINDOW_SIZE {
+                    (self.received << ahead) | 1
+                } else {
+                    1
+                };
+                self.highest = Some(index);
+            }
+            None => {
+                self.received = 1;
+                self.highest = Some(index);
+            }
+        }
+    }
+}
+
+/// the rollover counter of an rtp stream, the packet index is roc * 2^16 + sequence number
+#[derive(Debug, Clone, Copy)]
+struct RtpStreamState {
+    roc: u32,
+    highest_sequence_number: u16,
+    replay: ReplayWindow,
+}
+
+impl RtpStreamState {
+    fn new(sequence_number: u16) -> Self {
+        Self {
+            roc: 0,
+            highest_sequence_number: sequence_number,
+            replay: ReplayWindow::default(),
+        }
+    }
+
+    /// the roc the sequence number most likely belongs to, RFC 3711 3.3.1 and Appendix A
+    fn estimate_roc(&self, sequence_number: u16) -> u32 {
+        let highest = self.highest_sequence_number;
+        if highest < 0x8000 {
+            if sequence_number > highest && sequence_number - highest > 0x8000 {
+                // the one before the wrap, there is none before the first roc
+                self.roc.saturating_sub(1)
+            } else {
+                self.roc
+            }
+        } else if highest - 0x8000 > sequence_number {
+            self.roc.wrapping_add(1)
+        } else {
+            self.roc
+        }
+    }
+
+    fn update(&mut self, roc: u32, sequence_number: u16) {
+        if roc == self.roc.wrapping_add(1) {
+            self.roc = roc;
+            self.highest_sequence_number = sequence_number;
+        } else if roc == self.roc && sequence_number > self.highest_sequence_number {
+            self.highest_sequence_number = sequence_number;
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct RtcpStreamState {
+    next_index: u32,
+    replay: ReplayWindow,
+}
+
+/// protects or unprotects the rtp and rtcp packets of a session with one master key,
+/// the state of each stream is kept by ssrc, a context either protects a stream or unprotects it
+pub struct SrtpContext {
+    rtp: SessionKeys,
+    rtcp: SessionKeys,
+    rtp_streams: HashMap<u32, RtpStreamState>,
+    rtcp_streams: HashMap<u32, RtcpStreamState>,
+}
+
+impl fmt::Debug for SrtpContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SrtpContext")
+            .field("rtp_streams", &self.rtp_streams)
+            .field("rtcp_streams", &self.rtcp_streams)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SrtpContext {
+    pub fn new(master: &SrtpMasterKey) -> Self {
+        Self {
+            rtp: SessionKeys::derive(
+                master,
+                [
+                    LABEL_RTP_ENCRYPTION,
+                    LABEL_RTP_AUTHENTICATION,
+                    LABEL_RTP_SALT,
+                ],
+            ),
+            rtcp: SessionKeys::derive(
+                master,
+                [
+                    LABEL_RTCP_ENCRYPTION,
+                    LABEL_RTCP_AUTHENTICATION,
+                    LABEL_RTCP_SALT,
+                ],
+            ),
+            rtp_streams: HashMap::new(),
+            rtcp_streams: HashMap::new(),
+        }
+    }
+
+    /// encrypts the payload of a serialized rtp packet and appends the tag, RFC 3711 3.3
+    pub fn protect_rtp(&mut self, packet: &[u8]) -> RtpResult<Vec<u8>> {
+        let header_bytes = rtp_header_bytes(packet)?;
+        let (sequence_number, ssrc) = rtp_sequence_number_and_ssrc(packet);
+        let state = self
+            .rtp_streams
+            .entry(ssrc)
+            .or_insert_with(|| RtpStreamState::new(sequence_number));
+        let roc = state.estimate_roc(sequence_number);
+        let index = ((roc as u64) << 16) | sequence_number as u64;
+
+        let mut protected = Vec::with_capacity(packet.len() + AUTH_TAG_BYTES);
+        protected.extend_from_slice(packet);
+        self.rtp
+            .apply_keystream(ssrc, index, &mut protected[header_bytes..]);
+        let tag = self.rtp.tag(&[&protected, &roc.to_be_bytes()]);
+        protected.extend_from_slice(&tag);
+        state.update(roc, sequence_number);
+        Ok(protected)
+    }
+
+    /// authenticates an srtp packet and decrypts its payload, RFC 3711 3.4
+    pub fn unprotect_rtp(&mut self, packet: &[u8]) -> RtpResult<Vec<u8>> {
+        if packet.len() < RTP_FIXED_HEADER_BYTES + AUTH_TAG_BYTES {
+            return Err(RtpError::SrtpPacketTooShort(packet.len()));
+        }
+        let (authenticated, tag) = packet.split_at(packet.len() - AUTH_TAG_BYTES);
+        let header_bytes = rtp_header_bytes(authenticated)?;
+        let (sequence_number, ssrc) = rtp_sequence_number_and_ssrc(authenticated);
+        // a forged packet must not move the state of the stream
+        let mut state = self
+            .rtp_streams
+            .get(&ssrc)
+            .copied()
+            .unwrap_or_else(|| RtpStreamState::new(sequence_number));
+        let roc = state.estimate_roc(sequence_number);
+        let index = ((roc as u64) << 16) | sequence_number as u64;
+        if !state.replay.accepts(index) {
+            return Err(RtpError::SrtpReplayed(index));
+        }
+        if !self.rtp.verify(&[authenticated, &roc.to_be_bytes()], tag) {
+            return Err(RtpError::SrtpAuthenticationFailed);
+        }
+
+        let mut unprotected = authenticated.to_vec();
+        self.rtp
+            .apply_keystream(ssrc, index, &mut unprotected[header_bytes..]);
+        state.update(roc, sequence_number);
+        state.replay.mark(index);
+        self.rtp_streams.insert(ssrc, state);
+        Ok(unprotected)
+    }
+
+    /// encrypts a serialized rtcp compound packet but the first header,
+    /// and appends the index and the tag, RFC 3711 3.4
+    pub fn protect_rtcp(&mut self, packet: &[u8]) -> RtpResult<Vec<u8>> {
+        if packet.len() < RTCP_FIXED_HEADER_BYTES {
+            return Err(RtpError::SrtpPacketTooShort(packet.len()));
+        }
+        let ssrc = rtcp_ssrc(packet);
+        let state = self.rtcp_streams.entry(ssrc).or_default();
+        let index = state.next_index;
+        state.next_index = (index + 1) & MAX_SRTCP_INDEX;
+
+        let mut protected = Vec::with_capacity(packet.len() + SRTCP_INDEX_BYTES + AUTH_TAG_BYTES);
+        protected.extend_from_slice(packet);
+        self.rtcp.apply_keystream(
+            ssrc,
+            index as u64,
+            &mut protected[RTCP_FIXED_HEADER_BYTES..],
+        );
+        protected.extend_from_slice(&(SRTCP_E_FLAG | index).to_be_bytes());
+        let tag = self.rtcp.tag(&[&protected]);
+        protected.extend_from_slice(&tag);
+        Ok(protected)
+    }
+
+    /// authenticates an srtcp packet and decrypts it if it is encrypted
+    pub fn unprotect_rtcp(&mut self, packet: &[u8]) -> RtpResult<Vec<u8>> {
+        if packet.len() < RTCP_FIXED_HEADER_BYTES + SRTCP_INDEX_BYTES + AUTH_TAG_BYTES {
+            return Err(RtpError::SrtpPacketTooShort(packet.len()));
+        }
+        let (authenticated, tag) = packet.split_at(packet.len() - AUTH_TAG_BYTES);
+        let (compound, e_and_index) =
+            authenticated.split_at(authenticated.len() - SRTCP_INDEX_BYTES);
+        let e_and_index = u32::from_be_bytes(e_and_index.try_into().unwrap());
+        let index = (e_and_index & MAX_SRTCP_INDEX) as u64;
+        let ssrc = rtcp_ssrc(compound);
+        let mut state = self.rtcp_streams.get(&ssrc).copied().unwrap_or_default();
+        if !state.replay.accepts(index) {
+            return Err(RtpError::SrtpReplayed(index));
+        }
+        if !self.rtcp.verify(&[authenticated], tag) {
+            return Err(RtpError::SrtpAuthenticationFailed);
+        }
+
+        let mut unprotected = compound.to_vec();
+        if e_and_index & SRTCP_E_FLAG != 0 {
+            self.rtcp
+                .apply_keystream(ssrc, index, &mut unprotected[RTCP_FIXED_HEADER_BYTES..]);
+        }
+        state.replay.mark(index);
+        self.rtcp_streams.insert(ssrc, state);
+        Ok(unprotected)
+    }
+}
+
+/// the fixed header, the csrcs and the header extension, they are authenticated but not encrypted
+fn rtp_header_bytes(packet: &[u8]) -> RtpResult<usize> {
+    if packet.len() < RTP_FIXED_HEADER_BYTES {
+        return Err(RtpError::SrtpPacketTooShort(packet.len()));
+    }
+    let csrc_count = (packet[0] & 0x0F) as usize;
+    let mut header_bytes = RTP_FIXED_HEADER_BYTES + csrc_count * 4;
+    if packet[0] & 0x10 != 0 {
+        if packet.len() < header_bytes + 4 {
+            return Err(RtpError::SrtpPacketTooShort(packet.len()));
+        }
+        let extension_words =
+            u16::from_be_bytes([packet[header_bytes + 2], packet[header_bytes + 3]]) as usize;
+        header_bytes += 4 + extension_words * 4;
+    }
+    if packet.len() < header_bytes {
+        return Err(RtpError::SrtpPacketTooShort(packet.len()));
+    }
+    Ok(header_bytes)
+}
+
+fn rtp_sequence_number_and_ssrc(packet: &[u8]) -> (u16, u32) {
+    (
+        u16::from_be_bytes([packet[2], packet[3]]),
+        u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]),
+    )
+}
+
+/// the ssrc of the sender of the first packet of the compound
+fn rtcp_ssrc(packet: &[u8]) -> u32 {
+    u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]])
+}
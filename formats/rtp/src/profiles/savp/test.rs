@@ -0,0 +1,204 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::RtpError,
+        profiles::savp::{
+            SrtpContext, SrtpMasterKey,
+            key_derivation::{
+                LABEL_RTP_AUTHENTICATION, LABEL_RTP_ENCRYPTION, LABEL_RTP_SALT,
+                apply_aes_cm_keystream, derive_session_key,
+            },
+        },
+    };
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn master_key(key: &str, salt: &str) -> SrtpMasterKey {
+        SrtpMasterKey {
+            key: hex(key).try_into().unwrap(),
+            salt: hex(salt).try_into().unwrap(),
+        }
+    }
+
+    /// the rtp vectors of libsrtp, an empty header but the sequence number, version 0
+    fn libsrtp_rtp_packet(sequence_number: u16, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0u8; 12];
+        packet[2..4].copy_from_slice(&sequence_number.to_be_bytes());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn libsrtp_rtp_key() -> SrtpMasterKey {
+        master_key(
+            "0dcd213e4cbcf28f017f6994401e2889",
+            "62776038c06dc9419f6dd9433e7c",
+        )
+    }
+
+    fn libsrtp_rtcp_key() -> SrtpMasterKey {
+        master_key(
+            "fda62595d7f6926f7d9c024cc9209f34",
+            "a9651985540b47be2f27a8b88123",
+        )
+    }
+
+    const LIBSRTP_RTCP_ENCRYPTED: &str = concat!(
+        "80c8000666ef91ffcd34c578b28be16bc509d577e4ce5f208021bd667465e95f",
+        "49e5f5c0684ee56a78077546ed90f6dc9def3bdff279a9d8",
+        "80000001",
+        "60c0aeb56f40880e28ba"
+    );
+    const LIBSRTP_RTCP_DECRYPTED: &str = concat!(
+        "80c8000666ef91ffdf4880dd61a62ed3d8bcdebe0000000900001604",
+        "81ca000666ef91ff0110526e5435436d4a687a7965744178772b0000"
+    );
+
+    #[test]
+    fn aes_cm_keystream_matches_rfc3711_b2() {
+        let key = hex("2B7E151628AED2A6ABF7158809CF4F3C");
+        let iv = hex("F0F1F2F3F4F5F6F7F8F9FAFBFCFD0000");
+        let mut keystream = vec![0u8; 48];
+        apply_aes_cm_keystream(
+            &key.try_into().unwrap(),
+            &iv.try_into().unwrap(),
+            &mut keystream,
+        );
+        assert_eq!(
+            keystream,
+            hex(concat!(
+                "E03EAD0935C95E80E166B16DD92B4EB4",
+                "D23513162B02D0F72A43A2FE4A5F97AB",
+                "41E95B3BB0A2E8DD477901E4FCA894C0"
+            ))
+        );
+    }
+
+    #[test]
+    fn session_keys_match_rfc3711_b3() {
+        let master = master_key(
+            "E1F97A0D3E018BE0D64FA32C06DE4139",
+            "0EC675AD498AFEEBB6960B3AABE6",
+        );
+        let mut cipher_key = [0u8; 16];
+        let mut salt = [0u8; 14];
+        let mut auth_key = [0u8; 20];
+        derive_session_key(&master, LABEL_RTP_ENCRYPTION, &mut cipher_key);
+        derive_session_key(&master, LABEL_RTP_SALT, &mut salt);
+        derive_session_key(&master, LABEL_RTP_AUTHENTICATION, &mut auth_key);
+        assert_eq!(cipher_key.to_vec(), hex("C61E7A93744F39EE10734AFE3FF7A087"));
+        assert_eq!(salt.to_vec(), hex("30CBBC08863D8C85D49DB34A9AE1"));
+        assert_eq!(
+            auth_key.to_vec(),
+            hex("CEBE321F6FF7716B6FD4AB49AF256A156D38BAA4")
+        );
+    }
+
+    #[test]
+    fn rtp_is_protected_as_libsrtp_does() {
+        let payload = hex("000102030405");
+        let cases = [
+            (5000, "6dd37ed599b72d28b1f3a1f00cfbfd08"),
+            (5001, "da470b2a745365bd2febdc4b6d23f3de"),
+        ];
+        let mut sender = SrtpContext::new(&libsrtp_rtp_key());
+        let mut receiver = SrtpContext::new(&libsrtp_rtp_key());
+        for (sequence_number, protected_payload) in cases {
+            let packet = libsrtp_rtp_packet(sequence_number, &payload);
+            let protected = sender.protect_rtp(&packet).unwrap();
+            assert_eq!(
+                protected,
+                libsrtp_rtp_packet(sequence_number, &hex(protected_payload))
+            );
+            assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+        }
+    }
+
+    #[test]
+    fn rtcp_is_unprotected_as_libsrtp_protects_it() {
+        let mut receiver = SrtpContext::new(&libsrtp_rtcp_key());
+        assert_eq!(
+            receiver
+                .unprotect_rtcp(&hex(LIBSRTP_RTCP_ENCRYPTED))
+                .unwrap(),
+            hex(LIBSRTP_RTCP_DECRYPTED)
+        );
+
+        // libsrtp starts from index 1
+        let mut sender = SrtpContext::new(&libsrtp_rtcp_key());
+        sender.protect_rtcp(&hex(LIBSRTP_RTCP_DECRYPTED)).unwrap();
+        assert_eq!(
+            sender.protect_rtcp(&hex(LIBSRTP_RTCP_DECRYPTED)).unwrap(),
+            hex(LIBSRTP_RTCP_ENCRYPTED)
+        );
+    }
+
+    #[test]
+    fn tampered_and_replayed_packets_are_rejected() {
+        let mut sender = SrtpContext::new(&libsrtp_rtp_key());
+        let mut receiver = SrtpContext::new(&libsrtp_rtp_key());
+        let protected = sender
+            .protect_rtp(&libsrtp_rtp_packet(1, b"payload"))
+            .unwrap();
+
+        let mut tampered = protected.clone();
+        tampered[14] ^= 0x01;
+        assert!(matches!(
+            receiver.unprotect_rtp(&tampered),
+            Err(RtpError::SrtpAuthenticationFailed)
+        ));
+        receiver.unprotect_rtp(&protected).unwrap();
+        assert!(matches!(
+            receiver.unprotect_rtp(&protected),
+            Err(RtpError::SrtpReplayed(1))
+        ));
+
+        let mut receiver = SrtpContext::new(&libsrtp_rtcp_key());
+        let mut tampered = hex(LIBSRTP_RTCP_ENCRYPTED);
+        tampered[20] ^= 0x01;
+        assert!(matches!(
+            receiver.unprotect_rtcp(&tampered),
+            Err(RtpError::SrtpAuthenticationFailed)
+        ));
+        receiver
+            .unprotect_rtcp(&hex(LIBSRTP_RTCP_ENCRYPTED))
+            .unwrap();
+        assert!(matches!(
+            receiver.unprotect_rtcp(&hex(LIBSRTP_RTCP_ENCRYPTED)),
+            Err(RtpError::SrtpReplayed(1))
+        ));
+    }
+
+    #[test]
+    fn rollover_counter_follows_the_sequence_number_wrap() {
+        let mut sender = SrtpContext::new(&libsrtp_rtp_key());
+        let mut receiver = SrtpContext::new(&libsrtp_rtp_key());
+        let sequence_numbers = [65533u16, 65535, 65534, 0, 1, 65532, 2];
+        for sequence_number in sequence_numbers {
+            let packet = libsrtp_rtp_packet(sequence_number, b"wrapping");
+            let protected = sender.protect_rtp(&packet).unwrap();
+            assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+        }
+        assert_eq!(sender.rtp_streams[&0].roc, 1);
+        assert_eq!(receiver.rtp_streams[&0].roc, 1);
+    }
+
+    #[test]
+    fn master_key_round_trips_key_params() {
+        let master = SrtpMasterKey::random();
+        let key_params = master.to_key_params();
+        assert!(key_params.starts_with("inline:"));
+        assert_eq!(SrtpMasterKey::from_key_params(&key_params).unwrap(), master);
+        assert_eq!(
+            SrtpMasterKey::from_key_params(&format!("{}|2^20", key_params)).unwrap(),
+            master
+        );
+        assert!(SrtpMasterKey::from_key_params(&format!("{}|2^20|1:4", key_params)).is_err());
+        assert!(SrtpMasterKey::from_key_params("inline:AAAA").is_err());
+        assert!(SrtpMasterKey::from_key_params("uri:AAAA").is_err());
+    }
+}
@@ -8,8 +8,8 @@ use utils::traits::{
 };
 
 use crate::{
-    errors::RtpError,
-    util::padding::{rtp_get_padding_size, rtp_make_padding_bytes},
+    errors::{RtpError, RtpResult},
+    util::padding::{rtp_get_padding_size, rtp_make_padding_bytes, rtp_need_padding},
 };
 
 use super::{RtcpPacketSizeTrait, common_header::RtcpCommonHeader, payload_types::RtcpPayloadType};
@@ -27,9 +27,10 @@ use super::{RtcpPacketSizeTrait, common_header::RtcpCommonHeader, payload_types:
 /// |                   application-dependent data                ...
 /// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct RtcpAppPacket {
     pub header: RtcpCommonHeader,
+    pub subtype: u8,
     pub ssrc: u32,
     pub name: [u8; 4],
     pub payload: Bytes,
@@ -43,7 +44,20 @@ impl RtcpPacketSizeTrait for RtcpAppPacket {
          + self.payload.len()
     }
     fn get_header(&self) -> RtcpCommonHeader {
-        todo!()
+        let raw_size = self.get_packet_bytes_count_without_padding();
+        RtcpCommonHeader {
+            version: 2,
+            padding: rtp_need_padding(raw_size),
+            count: self.subtype,
+            payload_type: RtcpPayloadType::App,
+            length: (self.get_packet_bytes_count() / 4 - 1) as u16,
+        }
+    }
+}
+
+impl RtcpAppPacket {
+    pub fn builder() -> RtcpAppPacketBuilder {
+        RtcpAppPacketBuilder::new()
     }
 }
 
@@ -69,6 +83,7 @@ impl<R: io::Read> ReadRemainingFrom<RtcpCommonHeader, R> for RtcpAppPacket {
         let mut payload = Vec::new();
         reader.read_to_end(&mut payload)?;
         Ok(Self {
+            subtype: header.count,
             header,
             ssrc,
             name,
@@ -91,3 +106,51 @@ impl<W: io::Write> WriteTo<W> for RtcpAppPacket {
         Ok(())
     }
 }
+
+#[derive(Debug, Default)]
+pub struct RtcpAppPacketBuilder(RtcpAppPacket);
+
+impl RtcpAppPacketBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn ssrc(mut self, ssrc: u32) -> Self {
+        self.0.ssrc = ssrc;
+        self
+    }
+
+    pub fn subtype(mut self, subtype: u8) -> Self {
+        self.0.subtype = subtype;
+        self
+    }
+
+    pub fn name(mut self, name: [u8; 4]) -> Self {
+        self.0.name = name;
+        self
+    }
+
+    pub fn payload(mut self, payload: Bytes) -> Self {
+        self.0.payload = payload;
+        self
+    }
+
+    pub fn build(mut self) -> RtpResult<RtcpAppPacket> {
+        if self.0.subtype > 31 {
+            return Err(RtpError::AppSubtypeTooLarge(self.0.subtype));
+        }
+        if !self
+            .0
+            .name
+            .iter()
+            .all(|c| c.is_ascii_graphic() || *c == b' ')
+        {
+            return Err(RtpError::InvalidAppName(self.0.name));
+        }
+        if !self.0.payload.len().is_multiple_of(4) {
+            return Err(RtpError::AppDataNotAligned(self.0.payload.len()));
+        }
+        self.0.header = self.0.get_header();
+        Ok(self.0)
+    }
+}
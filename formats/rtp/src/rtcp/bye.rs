@@ -79,20 +79,19 @@ impl<R: io::Read> ReadRemainingFrom<RtcpCommonHeader, R> for RtcpByePacket {
             ssrc_list.push(reader.read_u32::<BigEndian>()?);
         }
 
-        let length = reader.read_u8()?;
-        let mut buffer = vec![0_u8; length as usize];
-        reader.read_exact(&mut buffer)?;
-        let leave_reason = if !buffer.is_empty() {
-            Some(String::from_utf8(buffer).unwrap())
-        } else {
-            None
+        // the reason is optional, anything after it is padding up to the next word
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest)?;
+        let leave_reason = match rest.split_first() {
+            Some((&length, reason)) if length != 0 => {
+                let reason = reason
+                    .get(..length as usize)
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                Some(String::from_utf8(reason.to_vec())?)
+            }
+            _ => None,
         };
 
-        let padding_bytes = rtp_get_padding_size(length as usize);
-        if padding_bytes != 0 {
-            reader.read_exact(&mut vec![0_u8; padding_bytes])?;
-        }
-
         Ok(Self {
             header,
             ssrc_list,
@@ -146,7 +145,7 @@ impl RtcpByePacketBuilder {
         self
     }
 
-    pub fn build(self) -> RtpResult<RtcpByePacket> {
+    pub fn build(mut self) -> RtpResult<RtcpByePacket> {
         if self.0.ssrc_list.len() > 31 {
             return Err(RtpError::TooManyCSRC);
         }
@@ -157,6 +156,7 @@ impl RtcpByePacketBuilder {
             return Err(RtpError::ByeReasonTooLarge(reason.clone()));
         }
 
+        self.0.header = self.0.get_header();
         Ok(self.0)
     }
 }
@@ -146,6 +146,16 @@ impl RtcpCompoundPacket {
             return Err(RtpError::EmptyRtcpCompoundPacket);
         }
 
+        // reduced-size rtcp, RFC 5506, feedback can be sent without the reports
+        if self.packets().iter().all(|packet| {
+            matches!(
+                packet.payload_type(),
+                RtcpPayloadType::TransportFeedback | RtcpPayloadType::PayloadSpecificFeedback
+            )
+        }) {
+            return Ok(());
+        }
+
         {
             let payload_type = self.packets()[0].payload_type();
             if payload_type != RtcpPayloadType::SenderReport
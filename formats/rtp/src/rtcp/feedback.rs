@@ -0,0 +1,368 @@
+use std::io;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use tokio_util::bytes::{BufMut, Bytes, BytesMut};
+use utils::traits::{
+    dynamic_sized_packet::DynamicSizedPacket, fixed_packet::FixedPacket, reader::ReadRemainingFrom,
+    writer::WriteTo,
+};
+
+use crate::{
+    errors::{RtpError, RtpResult},
+    util::padding::{rtp_get_padding_size, rtp_make_padding_bytes, rtp_need_padding},
+};
+
+use super::{RtcpPacketSizeTrait, common_header::RtcpCommonHeader, payload_types::RtcpPayloadType};
+
+/// FMT of the generic NACK in transport layer feedback messages, RFC 4585 6.2.1
+pub const FMT_GENERIC_NACK: u8 = 1;
+/// FMT of the application layer feedback in payload specific feedback messages, RFC 4585 6.4
+pub const FMT_APPLICATION_LAYER_FEEDBACK: u8 = 15;
+const REMB_IDENTIFIER: [u8; 4] = *b"REMB";
+/// of the mantissa of a REMB bitrate
+const REMB_MANTISSA_BITS: u32 = 18;
+
+// @see: RFC 4585 6.1 Common Packet Format for Feedback Messages
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |V=2|P|   FMT   |       PT      |          length               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                  SSRC of packet sender                        |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                  SSRC of media source                         |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// :            Feedback Control Information (FCI)                 :
+/// :                                                               :
+///
+/// both RTPFB and PSFB share this format, only the generic NACK FCI is interpreted
+
+#[derive(Debug, Default, Clone)]
+pub struct RtcpFeedbackPacket {
+    pub header: RtcpCommonHeader,
+    /// RTPFB or PSFB
+    pub payload_type: RtcpPayloadType,
+    /// feedback message type, carried in the count field of the header
+    pub fmt: u8,
+    pub sender_ssrc: u32,
+    pub media_ssrc: u32,
+    pub fci: Bytes,
+}
+
+// @see: RFC 4585 6.2.1 Generic NACK
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |            PID                |             BLP               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NackItem {
+    /// sequence number of a lost packet
+    pub pid: u16,
+    /// bit i set means pid + i + 1 is lost too
+    pub blp: u16,
+}
+
+impl NackItem {
+    pub fn lost_sequence_numbers(&self) -> impl Iterator<Item = u16> + use<> {
+        let Self { pid, blp } = *self;
+        std::iter::once(pid).chain(
+            (0..16_u16)
+                .filter(move |i| blp & (1 << i) != 0)
+                .map(move |i| pid.wrapping_add(i + 1)),
+        )
+    }
+
+    /// packs the sequence numbers into as few items as possible,
+    /// they are expected in sending order
+    pub fn from_lost_sequence_numbers<I: IntoIterator<Item = u16>>(lost: I) -> Vec<Self> {
+        let mut items: Vec<Self> = vec![];
+        for sequence_number in lost {
+            if let Some(last) = items.last_mut() {
+                let distance = sequence_number.wrapping_sub(last.pid);
+                if distance == 0 {
+                    continue;
+                }
+                if distance <= 16 {
+                    last.blp |= 1 << (distance - 1);
+                    continue;
+                }
+            }
+            items.push(Self {
+                pid: sequence_number,
+                blp: 0,
+            });
+        }
+        items
+    }
+}
+
+// @see: draft-alvestrand-rmcat-remb-03 2.2 Receiver Estimated Max Bitrate
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |  Unique identifier 'R' 'E' 'M' 'B'                            |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |  Num SSRC     | BR Exp    |  BR Mantissa                      |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |   SSRC feedback                                               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |  ...                                                          |
+///
+/// the media ssrc of the feedback message is 0, the ssrcs the bitrate applies to are listed
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Remb {
+    /// rounded down to what the mantissa and the exponent can tell
+    pub bitrate_bps: u64,
+    pub ssrcs: Vec<u32>,
+}
+
+impl Remb {
+    fn to_fci(&self) -> RtpResult<Bytes> {
+        let num_ssrc: u8 =
+            self.ssrcs.len().try_into().map_err(|_| {
+                RtpError::InvalidRemb(format!("too many ssrcs: {}", self.ssrcs.len()))
+            })?;
+        let exp = (u64::BITS - self.bitrate_bps.leading_zeros()).saturating_sub(REMB_MANTISSA_BITS);
+        let mantissa = (self.bitrate_bps >> exp) as u32;
+        let mut fci = BytesMut::with_capacity(8 + self.ssrcs.len() * 4);
+        fci.put_slice(&REMB_IDENTIFIER);
+        fci.put_u8(num_ssrc);
+        fci.put_u8(((exp as u8) << 2) | (mantissa >> 16) as u8);
+        fci.put_u16(mantissa as u16);
+        self.ssrcs.iter().for_each(|ssrc| fci.put_u32(*ssrc));
+        Ok(fci.freeze())
+    }
+
+    fn from_fci(fci: &[u8]) -> RtpResult<Self> {
+        if fci.len() < 8 || fci[..4] != REMB_IDENTIFIER {
+            return Err(RtpError::InvalidRemb(format!(
+                "not a remb of {} bytes",
+                fci.len()
+            )));
+        }
+        let num_ssrc = fci[4] as usize;
+        let exp = (fci[5] >> 2) as u32;
+        let mantissa = ((fci[5] as u64 & 0x03) << 16) | u16::from_be_bytes([fci[6], fci[7]]) as u64;
+        let ssrcs: Vec<u32> = fci[8..]
+            .chunks_exact(4)
+            .take(num_ssrc)
+            .map(|chunk| u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        if ssrcs.len() != num_ssrc {
+            return Err(RtpError::InvalidRemb(format!(
+                "{} ssrcs told, {} given",
+                num_ssrc,
+                ssrcs.len()
+            )));
+        }
+        Ok(Self {
+            bitrate_bps: ((mantissa as u128) << exp).min(u64::MAX as u128) as u64,
+            ssrcs,
+        })
+    }
+}
+
+impl RtcpFeedbackPacket {
+    pub fn builder() -> RtcpFeedbackPacketBuilder {
+        RtcpFeedbackPacketBuilder::new()
+    }
+
+    pub fn is_generic_nack(&self) -> bool {
+        self.payload_type == RtcpPayloadType::TransportFeedback && self.fmt == FMT_GENERIC_NACK
+    }
+
+    pub fn nack_items(&self) -> RtpResult<Vec<NackItem>> {
+        if !self.is_generic_nack() {
+            return Err(RtpError::WrongPayloadType(format!(
+                "expect generic nack, got {:?} with fmt {}",
+                self.payload_type, self.fmt
+            )));
+        }
+        Ok(self
+            .fci
+            .chunks_exact(4)
+            .map(|chunk| NackItem {
+                pid: u16::from_be_bytes([chunk[0], chunk[1]]),
+                blp: u16::from_be_bytes([chunk[2], chunk[3]]),
+            })
+            .collect())
+    }
+
+    pub fn is_remb(&self) -> bool {
+        self.payload_type == RtcpPayloadType::PayloadSpecificFeedback
+            && self.fmt == FMT_APPLICATION_LAYER_FEEDBACK
+            && self.fci.starts_with(&REMB_IDENTIFIER)
+    }
+
+    pub fn remb(&self) -> RtpResult<Remb> {
+        if self.payload_type != RtcpPayloadType::PayloadSpecificFeedback
+            || self.fmt != FMT_APPLICATION_LAYER_FEEDBACK
+        {
+            return Err(RtpError::WrongPayloadType(format!(
+                "expect application layer feedback, got {:?} with fmt {}",
+                self.payload_type, self.fmt
+            )));
+        }
+        Remb::from_fci(&self.fci)
+    }
+
+    /// all sequence numbers reported lost by a generic nack
+    pub fn lost_sequence_numbers(&self) -> RtpResult<Vec<u16>> {
+        Ok(self
+            .nack_items()?
+            .iter()
+            .flat_map(|item| item.lost_sequence_numbers())
+            .collect())
+    }
+}
+
+impl RtcpPacketSizeTrait for RtcpFeedbackPacket {
+    fn get_packet_bytes_count_without_padding(&self) -> usize {
+        RtcpCommonHeader::bytes_count() // header
+         + 4 // sender ssrc
+         + 4 // media ssrc
+         + self.fci.len()
+    }
+    fn get_header(&self) -> RtcpCommonHeader {
+        let raw_size = self.get_packet_bytes_count_without_padding();
+        RtcpCommonHeader {
+            version: 2,
+            padding: rtp_need_padding(raw_size),
+            count: self.fmt,
+            payload_type: self.payload_type,
+            length: (self.get_packet_bytes_count() / 4 - 1) as u16,
+        }
+    }
+}
+
+impl DynamicSizedPacket for RtcpFeedbackPacket {
+    fn get_packet_bytes_count(&self) -> usize {
+        let raw_bytes_count = self.get_packet_bytes_count_without_padding();
+        raw_bytes_count + rtp_get_padding_size(raw_bytes_count)
+    }
+}
+
+impl<R: io::Read> ReadRemainingFrom<RtcpCommonHeader, R> for RtcpFeedbackPacket {
+    type Error = RtpError;
+    fn read_remaining_from(header: RtcpCommonHeader, reader: &mut R) -> Result<Self, Self::Error> {
+        if header.payload_type != RtcpPayloadType::TransportFeedback
+            && header.payload_type != RtcpPayloadType::PayloadSpecificFeedback
+        {
+            return Err(RtpError::WrongPayloadType(format!(
+                "expect feedback payload type got {:?} instead",
+                header.payload_type
+            )));
+        }
+        let sender_ssrc = reader.read_u32::<BigEndian>()?;
+        let media_ssrc = reader.read_u32::<BigEndian>()?;
+        let mut fci = Vec::new();
+        reader.read_to_end(&mut fci)?;
+        Ok(Self {
+            payload_type: header.payload_type,
+            fmt: header.count,
+            header,
+            sender_ssrc,
+            media_ssrc,
+            fci: Bytes::from(fci),
+        })
+    }
+}
+
+impl<W: io::Write> WriteTo<W> for RtcpFeedbackPacket {
+    type Error = RtpError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        let raw_size = self.get_packet_bytes_count_without_padding();
+        self.get_header().write_to(writer)?;
+        writer.write_u32::<BigEndian>(self.sender_ssrc)?;
+        writer.write_u32::<BigEndian>(self.media_ssrc)?;
+        writer.write_all(&self.fci)?;
+        if let Some(padding) = rtp_make_padding_bytes(raw_size) {
+            writer.write_all(&padding)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct RtcpFeedbackPacketBuilder(RtcpFeedbackPacket);
+
+impl Default for RtcpFeedbackPacketBuilder {
+    fn default() -> Self {
+        Self(RtcpFeedbackPacket {
+            payload_type: RtcpPayloadType::TransportFeedback,
+            ..Default::default()
+        })
+    }
+}
+
+impl RtcpFeedbackPacketBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn payload_type(mut self, payload_type: RtcpPayloadType) -> Self {
+        self.0.payload_type = payload_type;
+        self
+    }
+
+    pub fn fmt(mut self, fmt: u8) -> Self {
+        self.0.fmt = fmt;
+        self
+    }
+
+    pub fn sender_ssrc(mut self, ssrc: u32) -> Self {
+        self.0.sender_ssrc = ssrc;
+        self
+    }
+
+    pub fn media_ssrc(mut self, ssrc: u32) -> Self {
+        self.0.media_ssrc = ssrc;
+        self
+    }
+
+    pub fn fci(mut self, fci: Bytes) -> Self {
+        self.0.fci = fci;
+        self
+    }
+
+    /// makes a generic nack of the items
+    pub fn nack_items(self, items: &[NackItem]) -> Self {
+        let mut fci = BytesMut::with_capacity(items.len() * 4);
+        items.iter().for_each(|item| {
+            fci.put_u16(item.pid);
+            fci.put_u16(item.blp);
+        });
+        self.payload_type(RtcpPayloadType::TransportFeedback)
+            .fmt(FMT_GENERIC_NACK)
+            .fci(fci.freeze())
+    }
+
+    /// makes a receiver estimated max bitrate of the remb
+    pub fn remb(self, remb: &Remb) -> RtpResult<Self> {
+        Ok(self
+            .payload_type(RtcpPayloadType::PayloadSpecificFeedback)
+            .fmt(FMT_APPLICATION_LAYER_FEEDBACK)
+            .media_ssrc(0)
+            .fci(remb.to_fci()?))
+    }
+
+    pub fn build(mut self) -> RtpResult<RtcpFeedbackPacket> {
+        if self.0.payload_type != RtcpPayloadType::TransportFeedback
+            && self.0.payload_type != RtcpPayloadType::PayloadSpecificFeedback
+        {
+            return Err(RtpError::WrongPayloadType(format!(
+                "expect feedback payload type got {:?} instead",
+                self.0.payload_type
+            )));
+        }
+        if self.0.fmt > 31 {
+            return Err(RtpError::FeedbackFormatTooLarge(self.0.fmt));
+        }
+        if !self.0.fci.len().is_multiple_of(4) {
+            return Err(RtpError::FeedbackDataNotAligned(self.0.fci.len()));
+        }
+        self.0.header = self.0.get_header();
+        Ok(self.0)
+    }
+}
@@ -3,6 +3,7 @@ use std::io::{self, Cursor, Read};
 use app::RtcpAppPacket;
 use bye::RtcpByePacket;
 use common_header::RtcpCommonHeader;
+use feedback::RtcpFeedbackPacket;
 use payload_types::RtcpPayloadType;
 use receiver_report::RtcpReceiverReport;
 use report_block::ReportBlock;
@@ -21,6 +22,7 @@ pub mod app;
 pub mod bye;
 pub mod common_header;
 pub mod compound_packet;
+pub mod feedback;
 pub mod framed;
 pub mod payload_types;
 pub mod receiver_report;
@@ -29,6 +31,9 @@ pub mod sdes;
 pub mod sender_report;
 pub mod simple_ntp;
 
+#[cfg(test)]
+mod test;
+
 pub trait RtcpPacketSizeTrait: DynamicSizedPacket {
     fn get_packet_bytes_count_without_padding(&self) -> usize;
     fn get_header(&self) -> RtcpCommonHeader;
@@ -50,6 +55,8 @@ pub enum RtcpPacket {
     SourceDescription(RtcpSourceDescriptionPacket),
     Bye(RtcpByePacket),
     App(RtcpAppPacket),
+    /// RTPFB or PSFB
+    Feedback(RtcpFeedbackPacket),
 }
 
 impl RtcpPacketTrait for RtcpPacket {
@@ -60,6 +67,7 @@ impl RtcpPacketTrait for RtcpPacket {
             RtcpPacket::SourceDescription(_) => RtcpPayloadType::SourceDescription,
             RtcpPacket::Bye(_) => RtcpPayloadType::Bye,
             RtcpPacket::App(_) => RtcpPayloadType::App,
+            RtcpPacket::Feedback(packet) => packet.payload_type,
         }
     }
 
@@ -70,6 +78,7 @@ impl RtcpPacketTrait for RtcpPacket {
             RtcpPacket::SourceDescription(_) => None,
             RtcpPacket::Bye(_) => None,
             RtcpPacket::App(packet) => Some(packet.ssrc),
+            RtcpPacket::Feedback(packet) => Some(packet.sender_ssrc),
         }
     }
 
@@ -86,6 +95,7 @@ impl RtcpPacketTrait for RtcpPacket {
             }
             RtcpPacket::Bye(packet) => packet.ssrc_list.clone(),
             RtcpPacket::App(_) => vec![],
+            RtcpPacket::Feedback(_) => vec![],
         }
     }
 
@@ -122,6 +132,7 @@ impl RtcpPacketSizeTrait for RtcpPacket {
             }
             RtcpPacket::Bye(packet) => packet.get_packet_bytes_count_without_padding(),
             RtcpPacket::App(packet) => packet.get_packet_bytes_count_without_padding(),
+            RtcpPacket::Feedback(packet) => packet.get_packet_bytes_count_without_padding(),
         }
     }
     fn get_header(&self) -> RtcpCommonHeader {
@@ -131,6 +142,7 @@ impl RtcpPacketSizeTrait for RtcpPacket {
             RtcpPacket::SourceDescription(packet) => packet.get_header(),
             RtcpPacket::Bye(packet) => packet.get_header(),
             RtcpPacket::App(packet) => packet.get_header(),
+            RtcpPacket::Feedback(packet) => packet.get_header(),
         }
     }
 }
@@ -158,8 +170,11 @@ impl<R: AsRef<[u8]>> TryReadRemainingFrom<RtcpCommonHeader, R> for RtcpPacket {
 
         // ignore padding bytes
         if header.padding && !remaining_bytes.is_empty() {
-            let padding_bytes = *remaining_bytes.last().unwrap();
-            remaining_bytes.truncate(padding_bytes as usize);
+            let padding_bytes = *remaining_bytes.last().unwrap() as usize;
+            if padding_bytes == 0 || padding_bytes > remaining_bytes.len() {
+                return Err(RtpError::BadPaddingSize(padding_bytes));
+            }
+            remaining_bytes.truncate(remaining_bytes.len() - padding_bytes);
         }
 
         let mut cursor = Cursor::new(&remaining_bytes);
@@ -183,6 +198,11 @@ impl<R: AsRef<[u8]>> TryReadRemainingFrom<RtcpCommonHeader, R> for RtcpPacket {
                 header,
                 cursor.by_ref(),
             )?))),
+            RtcpPayloadType::TransportFeedback | RtcpPayloadType::PayloadSpecificFeedback => {
+                Ok(Some(Self::Feedback(
+                    RtcpFeedbackPacket::read_remaining_from(header, cursor.by_ref())?,
+                )))
+            }
         }
     }
 }
@@ -196,6 +216,7 @@ impl<W: io::Write> WriteTo<W> for RtcpPacket {
             RtcpPacket::SourceDescription(packet) => packet.write_to(writer),
             RtcpPacket::Bye(packet) => packet.write_to(writer),
             RtcpPacket::App(packet) => packet.write_to(writer),
+            RtcpPacket::Feedback(packet) => packet.write_to(writer),
         }
     }
 }
@@ -9,6 +9,10 @@ pub enum RtcpPayloadType {
     SourceDescription = 202,
     Bye = 203,
     App = 204,
+    /// RTPFB, RFC 4585
+    TransportFeedback = 205,
+    /// PSFB, RFC 4585
+    PayloadSpecificFeedback = 206,
 }
 
 impl TryFrom<u8> for RtcpPayloadType {
@@ -20,6 +24,8 @@ impl TryFrom<u8> for RtcpPayloadType {
             202 => Ok(Self::SourceDescription),
             203 => Ok(Self::Bye),
             204 => Ok(Self::App),
+            205 => Ok(Self::TransportFeedback),
+            206 => Ok(Self::PayloadSpecificFeedback),
             _ => Err(RtpError::UnknownRtcpPayloadType(value)),
         }
     }
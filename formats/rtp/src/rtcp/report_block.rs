@@ -1,5 +1,8 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io;
+use std::{
+    io,
+    time::{Duration, SystemTime},
+};
 use utils::traits::{fixed_packet::FixedPacket, reader::ReadFrom, writer::WriteTo};
 
 use crate::errors::RtpError;
@@ -75,6 +78,24 @@ impl ReportBlock {
     pub fn builder() -> RtcpReportBlockBuilder {
         Default::default()
     }
+
+    /// the round trip time to the reporter, RFC 3550 6.4.1, the block is about the packets of the side it `arrived` at.
+    /// None if the reporter has not got a sender report of that side yet
+    pub fn round_trip_time(&self, arrived: SystemTime) -> Option<Duration> {
+        let lsr = u32::from(self.last_sender_report_timestamp);
+        if lsr == 0 {
+            return None;
+        }
+        let now = u32::from(SimpleShortNtp::from(arrived));
+        let rtt = now
+            .wrapping_sub(lsr)
+            .wrapping_sub(self.delay_since_last_sender_report);
+        // the clock went backwards or the report is bogus
+        if rtt > i32::MAX as u32 {
+            return None;
+        }
+        Some(Duration::from_nanos(rtt as u64 * 1_000_000_000 / 65536))
+    }
 }
 
 impl RtcpReportBlockBuilder {
@@ -122,6 +122,23 @@ pub struct SDESItem {
     item_body: SDESBody,
 }
 
+impl SDESItem {
+    pub fn new(item_type: SDESItemType, value: String) -> RtpResult<Self> {
+        Ok(Self {
+            item_type,
+            item_body: SDESBody::try_from(value)?,
+        })
+    }
+
+    pub fn item_type(&self) -> SDESItemType {
+        self.item_type
+    }
+
+    pub fn value(&self) -> &str {
+        &self.item_body.value
+    }
+}
+
 impl DynamicSizedPacket for SDESItem {
     fn get_packet_bytes_count(&self) -> usize {
         1 + self.item_body.get_packet_bytes_count()
@@ -131,11 +148,8 @@ impl DynamicSizedPacket for SDESItem {
 impl<R: io::Read> ReadFrom<R> for SDESItem {
     type Error = RtpError;
     fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
+        // private items are read as text, the way the builder writes them
         let item_type: SDESItemType = reader.read_u8()?.try_into()?;
-        if item_type == SDESItemType::PRIV {
-            todo!()
-        }
-
         let item_body = SDESBody::read_from(reader.by_ref())?;
         Ok(Self {
             item_type,
@@ -183,22 +197,35 @@ impl<R: io::Read> ReadFrom<R> for SDESChunk {
     type Error = RtpError;
     fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
         let ssrc = reader.read_u32::<BigEndian>()?;
-        let bytes_read = 0;
+        // bytes read after the ssrc, which is word aligned already
+        let mut bytes_read = 0;
         let mut items = Vec::new();
         loop {
             let item_type = reader.read_u8()?;
-            if item_type != 0 {
-                items.push(SDESItem::read_from(reader)?);
-            } else {
-                if bytes_read % 4 == 0 {
-                    break;
-                }
-                let bytes_to_align_word = 4 - (bytes_read % 4);
-                for _ in 0..bytes_to_align_word {
-                    // skip padding bytes
+            bytes_read += 1;
+            if item_type == 0 {
+                // the item list ends with a null octet, followed by null octets up to the next word
+                for _ in 0..rtp_get_padding_size(bytes_read) {
                     let _ = reader.read_u8()?;
                 }
+                break;
+            }
+
+            let item_type: SDESItemType = item_type.try_into()?;
+            if item_type == SDESItemType::PRIV {
+                // the prefix and the value of private items are opaque bytes, skipped by their length
+                let length = reader.read_u8()?;
+                let mut skipped = vec![0_u8; length as usize];
+                reader.read_exact(&mut skipped)?;
+                bytes_read += 1 + skipped.len();
+                continue;
             }
+            let item_body = SDESBody::read_from(reader.by_ref())?;
+            bytes_read += item_body.get_packet_bytes_count();
+            items.push(SDESItem {
+                item_type,
+                item_body,
+            });
         }
 
         Ok(Self { ssrc, items })
@@ -319,6 +346,13 @@ impl RtcpSourceDescriptionPacket {
             })
         })
     }
+    /// the items of every chunk of the ssrc
+    pub fn items_of(&self, ssrc: u32) -> impl Iterator<Item = &SDESItem> {
+        self.chunks
+            .iter()
+            .filter(move |v| v.ssrc == ssrc)
+            .flat_map(|v| v.items.iter())
+    }
 }
 
 #[derive(Debug, Default)]
@@ -387,7 +421,7 @@ impl RtcpSourceDescriptionPacketBuilder {
     }
 
     pub fn note(self, ssrc: u32, note: String) -> RtpResult<Self> {
-        self.item_from_parts(ssrc, SDESItemType::TOOL, note)
+        self.item_from_parts(ssrc, SDESItemType::NOTE, note)
     }
 
     pub fn private(self, ssrc: u32, value: String) -> RtpResult<Self> {
@@ -1,6 +1,9 @@
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Copy, Default)]
+/// offset in seconds between unix epoch and ntp epoch
+const NTP_UNIX_EPOCH_OFFSET: u64 = 0x83AA7E80;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct SimpleNtp {
     seconds: u32,
     fraction: u32,
@@ -27,12 +30,10 @@ impl From<SystemTime> for SimpleNtp {
             .duration_since(UNIX_EPOCH)
             .unwrap_or_else(|_| Duration::from_secs(0))
             .as_nanos() as u64;
-        let mut seconds = duration / 1_000_000_000;
-        seconds += 0x83AA7E80; //offset in seconds between unix epoch and ntp epoch
+        let seconds = duration / 1_000_000_000 + NTP_UNIX_EPOCH_OFFSET;
         let mut fraction = duration % 1_000_000_000;
         fraction <<= 32;
         fraction /= 1_000_000_000;
-        seconds <<= 32;
         Self {
             seconds: seconds as u32,
             fraction: fraction as u32,
@@ -43,11 +44,12 @@ impl From<SystemTime> for SimpleNtp {
 impl From<SimpleNtp> for SystemTime {
     fn from(value: SimpleNtp) -> Self {
         let value: u64 = value.into();
-        let mut seconds = value >> 32;
+        let Some(seconds) = (value >> 32).checked_sub(NTP_UNIX_EPOCH_OFFSET) else {
+            return UNIX_EPOCH;
+        };
         let mut fraction = value & 0xFFFF_FFFF;
         fraction *= 1_000_000_000;
         fraction >>= 32;
-        seconds -= 0x83AA7E80;
         let duration = seconds * 1_000_000_000 + fraction;
 
         UNIX_EPOCH
@@ -59,7 +61,8 @@ impl From<SimpleNtp> for SystemTime {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+/// the middle 32 bits of an ntp timestamp, RFC 3550 4
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct SimpleShortNtp {
     seconds: u16,
     fraction: u16,
@@ -78,7 +81,7 @@ impl From<SimpleNtp> for SimpleShortNtp {
     fn from(value: SimpleNtp) -> Self {
         Self {
             seconds: value.seconds as u16,
-            fraction: value.fraction as u16,
+            fraction: (value.fraction >> 16) as u16,
         }
     }
 }
@@ -93,17 +96,14 @@ impl From<SimpleShortNtp> for SimpleNtp {
     fn from(value: SimpleShortNtp) -> Self {
         SimpleNtp {
             seconds: value.seconds as u32,
-            fraction: value.fraction as u32,
+            fraction: (value.fraction as u32) << 16,
         }
     }
 }
 
 impl From<SystemTime> for SimpleShortNtp {
     fn from(value: SystemTime) -> Self {
-        let ntp: SimpleNtp = value.into();
-        let bits: u64 = ntp.into();
-        let short_bits = ((bits >> 16) & 0xFFFF_FFFF) as u32;
-        Self::from(short_bits)
+        SimpleNtp::from(value).into()
     }
 }
 
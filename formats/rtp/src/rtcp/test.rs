@@ -0,0 +1,581 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use tokio_util::bytes::Bytes;
+    use utils::traits::{
+        dynamic_sized_packet::DynamicSizedPacket,
+        reader::{TryReadFrom, TryReadRemainingFrom},
+        writer::WriteTo,
+    };
+
+    use crate::{
+        errors::RtpError,
+        rtcp::{
+            RtcpPacket, RtcpPacketSizeTrait, RtcpPacketTrait,
+            app::RtcpAppPacket,
+            bye::RtcpByePacket,
+            common_header::RtcpCommonHeader,
+            compound_packet::RtcpCompoundPacket,
+            feedback::{NackItem, Remb, RtcpFeedbackPacket},
+            payload_types::RtcpPayloadType,
+            receiver_report::RtcpReceiverReport,
+            report_block::ReportBlock,
+            sdes::{RtcpSourceDescriptionPacket, SDESItemType},
+            sender_report::RtcpSenderReport,
+            simple_ntp::{SimpleNtp, SimpleShortNtp},
+        },
+    };
+
+    fn report_blocks(count: usize) -> Vec<ReportBlock> {
+        (0..count)
+            .map(|i| {
+                ReportBlock::builder()
+                    .ssrc(i as u32 + 1)
+                    .cumulative_packet_lost(i as i32)
+                    .highest_sequence_number_received(i as u16)
+                    .interarrival_jitter(i as u32 * 10)
+                    .build()
+            })
+            .collect()
+    }
+
+    /// the header stored by the builder must describe exactly the bytes written
+    fn check_header(packet: &RtcpPacket) -> Vec<u8> {
+        let header = packet.get_header();
+        let mut bytes = Vec::new();
+        packet.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), packet.get_packet_bytes_count());
+        assert_eq!((header.length as usize + 1) * 4, bytes.len());
+        assert_eq!(
+            header.padding,
+            packet.get_packet_bytes_count_without_padding() != bytes.len()
+        );
+        if header.padding {
+            assert_eq!(
+                *bytes.last().unwrap() as usize,
+                bytes.len() - packet.get_packet_bytes_count_without_padding()
+            );
+        }
+        bytes
+    }
+
+    fn read_back(bytes: &[u8]) -> RtcpPacket {
+        let mut cursor = Cursor::new(bytes);
+        let header = RtcpCommonHeader::try_read_from(&mut cursor)
+            .unwrap()
+            .unwrap();
+        let packet = RtcpPacket::try_read_remaining_from(header, &mut cursor)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cursor.position() as usize, bytes.len());
+        packet
+    }
+
+    #[test]
+    fn test_report_headers_for_every_block_count() {
+        for count in 0..=31 {
+            let sr = RtcpSenderReport::builder()
+                .ssrc(1234)
+                .report_blocks(report_blocks(count))
+                .build()
+                .unwrap();
+            assert_eq!(sr.header, sr.get_header());
+            assert_eq!(sr.header.count as usize, count);
+            let bytes = check_header(&RtcpPacket::SenderReport(sr));
+            assert_eq!(bytes.len(), 28 + count * 24);
+            let parsed = read_back(&bytes);
+            assert_eq!(parsed.sender_ssrc(), Some(1234));
+            assert_eq!(parsed.report_blocks().unwrap().len(), count);
+
+            let rr = RtcpReceiverReport::builder()
+                .ssrc(5678)
+                .report_blocks(report_blocks(count))
+                .build()
+                .unwrap();
+            assert_eq!(rr.header, rr.get_header());
+            assert_eq!(rr.header.count as usize, count);
+            let bytes = check_header(&RtcpPacket::ReceiverReport(rr));
+            assert_eq!(bytes.len(), 8 + count * 24);
+            let parsed = read_back(&bytes);
+            assert_eq!(parsed.sender_ssrc(), Some(5678));
+            assert_eq!(parsed.report_blocks().unwrap().len(), count);
+        }
+
+        assert!(matches!(
+            RtcpSenderReport::builder()
+                .report_blocks(report_blocks(32))
+                .build(),
+            Err(RtpError::TooManyReportBlocks)
+        ));
+        assert!(matches!(
+            RtcpReceiverReport::builder()
+                .report_blocks(report_blocks(32))
+                .build(),
+            Err(RtpError::TooManyReportBlocks)
+        ));
+    }
+
+    #[test]
+    fn test_sender_report_extension_padding() {
+        for extension_len in 1..=8 {
+            let extension = Bytes::from(vec![0xAB; extension_len]);
+            let sr = RtcpSenderReport::builder()
+                .ssrc(1)
+                .report_blocks(report_blocks(2))
+                .extension(extension.clone())
+                .build()
+                .unwrap();
+            assert_eq!(sr.header.padding, extension_len % 4 != 0);
+            let bytes = check_header(&RtcpPacket::SenderReport(sr));
+            match read_back(&bytes) {
+                RtcpPacket::SenderReport(parsed) => {
+                    assert_eq!(parsed.profile_specific_extension, Some(extension));
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sdes_headers_and_item_padding() {
+        for chunk_count in 1..=4 {
+            for value_len in 0..=8 {
+                let mut builder = RtcpSourceDescriptionPacket::builder();
+                for ssrc in 0..chunk_count {
+                    builder = builder
+                        .cname(ssrc, "c".repeat(value_len))
+                        .unwrap()
+                        .note(ssrc, "n".repeat(value_len + 1))
+                        .unwrap();
+                }
+                let sdes = builder.build().unwrap();
+                assert_eq!(sdes.header, sdes.get_header());
+                assert_eq!(sdes.header.count, chunk_count as u8);
+                // chunks are always word aligned
+                assert!(!sdes.header.padding);
+
+                let bytes = check_header(&RtcpPacket::SourceDescription(sdes));
+                match read_back(&bytes) {
+                    RtcpPacket::SourceDescription(parsed) => {
+                        assert_eq!(parsed.chunks.len(), chunk_count as usize);
+                        assert_eq!(
+                            parsed.get_cname_of(chunk_count - 1),
+                            Some("c".repeat(value_len))
+                        );
+                        assert!(parsed.chunks.iter().all(|chunk| chunk.items.len() == 2));
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        assert!(matches!(
+            RtcpSourceDescriptionPacket::builder().cname(1, "c".repeat(256)),
+            Err(RtpError::SDESValueTooLarge(_))
+        ));
+        let mut builder = RtcpSourceDescriptionPacket::builder();
+        for ssrc in 0..32 {
+            builder = builder.cname(ssrc, "c".to_owned()).unwrap();
+        }
+        assert!(matches!(builder.build(), Err(RtpError::SDESTooManyChunks)));
+    }
+
+    #[test]
+    fn test_sdes_multi_item_chunk_lengths() {
+        // ssrc, the items and the null octet ending the item list, up to the next word
+        let cases = [
+            // 4 + 18 + 17 + 1 = 40
+            ("c".repeat(16), "yam_server/rtsp".to_owned(), None, 40),
+            // 4 + 18 + 18 = 40, the item list ends with a whole word of null octets
+            ("c".repeat(16), "t".repeat(16), None, 44),
+            // 4 + 18 + 17 + 5 + 1 = 45
+            (
+                "c".repeat(16),
+                "yam_server/rtsp".to_owned(),
+                Some("yam".to_owned()),
+                48,
+            ),
+        ];
+        for (cname, tool, name, chunk_len) in cases {
+            let mut builder = RtcpSourceDescriptionPacket::builder()
+                .cname(7, cname.clone())
+                .unwrap()
+                .tool(7, tool.clone())
+                .unwrap();
+            if let Some(name) = name.clone() {
+                builder = builder.name(7, name).unwrap();
+            }
+            let sdes = builder.build().unwrap();
+            assert_eq!(sdes.chunks[0].get_packet_bytes_count(), chunk_len);
+            assert_eq!(sdes.get_packet_bytes_count(), 4 + chunk_len);
+
+            let bytes = check_header(&RtcpPacket::SourceDescription(sdes));
+            assert_eq!(bytes.last(), Some(&0));
+            let RtcpPacket::SourceDescription(parsed) = read_back(&bytes) else {
+                unreachable!()
+            };
+            let items: Vec<_> = parsed
+                .items_of(7)
+                .map(|item| (item.item_type(), item.value().to_owned()))
+                .collect();
+            let mut expected = vec![(SDESItemType::CNAME, cname), (SDESItemType::TOOL, tool)];
+            expected.extend(name.map(|name| (SDESItemType::NAME, name)));
+            assert_eq!(items, expected);
+        }
+    }
+
+    #[test]
+    fn test_sdes_private_items_are_skipped() {
+        let bytes = [
+            0x81, 202, 0, 5, // header, 1 chunk of 5 words
+            0, 0, 0, 7, // ssrc
+            1, 3, b'c', b'a', b'm', // cname
+            8, 4, 2, 0xFF, 0xFE, 0x80, // priv, a prefix of 2 bytes and a value, not utf8
+            6, 1, b'x', // tool
+            0, 0, // the null octet ending the item list, up to the next word
+        ];
+        let RtcpPacket::SourceDescription(parsed) = read_back(&bytes) else {
+            unreachable!()
+        };
+        let items: Vec<_> = parsed
+            .items_of(7)
+            .map(|item| (item.item_type(), item.value().to_owned()))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                (SDESItemType::CNAME, "cam".to_owned()),
+                (SDESItemType::TOOL, "x".to_owned())
+            ]
+        );
+
+        // cut in the middle of the private item
+        let mut cursor = Cursor::new(&bytes[..16]);
+        let header = RtcpCommonHeader::try_read_from(&mut cursor)
+            .unwrap()
+            .unwrap();
+        assert!(!matches!(
+            RtcpPacket::try_read_remaining_from(header, &mut cursor),
+            Ok(Some(_))
+        ));
+    }
+
+    #[test]
+    fn test_bye_headers_and_reason_padding() {
+        for ssrc_count in 0..=31 {
+            for reason_len in [None, Some(0), Some(1), Some(2), Some(3), Some(4), Some(7)] {
+                let mut builder = RtcpByePacket::builder().ssrcs((0..ssrc_count).collect());
+                if let Some(len) = reason_len {
+                    builder = builder.reason("r".repeat(len));
+                }
+                let bye = builder.build().unwrap();
+                assert_eq!(bye.header, bye.get_header());
+                assert_eq!(bye.header.count, ssrc_count as u8);
+
+                let bytes = check_header(&RtcpPacket::Bye(bye));
+                match read_back(&bytes) {
+                    RtcpPacket::Bye(parsed) => {
+                        assert_eq!(parsed.ssrc_list, (0..ssrc_count).collect::<Vec<_>>());
+                        assert_eq!(
+                            parsed.leave_reason,
+                            reason_len.filter(|len| *len > 0).map(|len| "r".repeat(len))
+                        );
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        assert!(matches!(
+            RtcpByePacket::builder().ssrcs((0..32).collect()).build(),
+            Err(RtpError::TooManyCSRC)
+        ));
+        assert!(matches!(
+            RtcpByePacket::builder()
+                .ssrc(1)
+                .reason("r".repeat(256))
+                .build(),
+            Err(RtpError::ByeReasonTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_app_headers() {
+        for subtype in 0..=31 {
+            for payload_len in [0, 4, 8, 12] {
+                let payload = Bytes::from(vec![subtype; payload_len]);
+                let app = RtcpAppPacket::builder()
+                    .ssrc(42)
+                    .subtype(subtype)
+                    .name(*b"TEST")
+                    .payload(payload.clone())
+                    .build()
+                    .unwrap();
+                assert_eq!(app.header, app.get_header());
+                assert_eq!(app.header.count, subtype);
+                assert!(!app.header.padding);
+
+                let bytes = check_header(&RtcpPacket::App(app));
+                assert_eq!(bytes.len(), 12 + payload_len);
+                match read_back(&bytes) {
+                    RtcpPacket::App(parsed) => {
+                        assert_eq!(parsed.subtype, subtype);
+                        assert_eq!(parsed.ssrc, 42);
+                        assert_eq!(&parsed.name, b"TEST");
+                        assert_eq!(parsed.payload, payload);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        assert!(matches!(
+            RtcpAppPacket::builder().subtype(32).name(*b"TEST").build(),
+            Err(RtpError::AppSubtypeTooLarge(32))
+        ));
+        assert!(matches!(
+            RtcpAppPacket::builder().name([0, 1, 2, 3]).build(),
+            Err(RtpError::InvalidAppName(_))
+        ));
+        assert!(matches!(
+            RtcpAppPacket::builder()
+                .name(*b"TEST")
+                .payload(Bytes::from_static(&[1, 2, 3]))
+                .build(),
+            Err(RtpError::AppDataNotAligned(3))
+        ));
+    }
+
+    #[test]
+    fn test_nack_items() {
+        let item = NackItem {
+            pid: 100,
+            blp: 0b1000_0000_0000_0101,
+        };
+        assert_eq!(
+            item.lost_sequence_numbers().collect::<Vec<_>>(),
+            vec![100, 101, 103, 116]
+        );
+
+        let items = NackItem::from_lost_sequence_numbers([u16::MAX, 0, 15, 16, 40]);
+        assert_eq!(
+            items,
+            vec![
+                NackItem {
+                    pid: u16::MAX,
+                    blp: 0b1000_0000_0000_0001,
+                },
+                NackItem { pid: 16, blp: 0 },
+                NackItem { pid: 40, blp: 0 },
+            ]
+        );
+        assert_eq!(
+            items
+                .iter()
+                .flat_map(|item| item.lost_sequence_numbers())
+                .collect::<Vec<_>>(),
+            vec![u16::MAX, 0, 15, 16, 40]
+        );
+    }
+
+    #[test]
+    fn test_feedback_round_trip() {
+        let items = NackItem::from_lost_sequence_numbers([7, 9, 1000]);
+        let nack = RtcpFeedbackPacket::builder()
+            .sender_ssrc(1)
+            .media_ssrc(2)
+            .nack_items(&items)
+            .build()
+            .unwrap();
+        assert_eq!(nack.header, nack.get_header());
+        assert!(nack.is_generic_nack());
+
+        let bytes = check_header(&RtcpPacket::Feedback(nack));
+        assert_eq!(bytes.len(), 12 + items.len() * 4);
+        match read_back(&bytes) {
+            RtcpPacket::Feedback(parsed) => {
+                assert_eq!(parsed.sender_ssrc, 1);
+                assert_eq!(parsed.media_ssrc, 2);
+                assert_eq!(parsed.nack_items().unwrap(), items);
+                assert_eq!(parsed.lost_sequence_numbers().unwrap(), vec![7, 9, 1000]);
+            }
+            _ => unreachable!(),
+        }
+
+        // a picture loss indication is parsed but not interpreted
+        let pli = RtcpFeedbackPacket::builder()
+            .payload_type(RtcpPayloadType::PayloadSpecificFeedback)
+            .fmt(1)
+            .build()
+            .unwrap();
+        match read_back(&check_header(&RtcpPacket::Feedback(pli))) {
+            RtcpPacket::Feedback(parsed) => {
+                assert!(!parsed.is_generic_nack());
+                assert!(parsed.nack_items().is_err());
+            }
+            _ => unreachable!(),
+        }
+
+        assert!(matches!(
+            RtcpFeedbackPacket::builder().fmt(32).build(),
+            Err(RtpError::FeedbackFormatTooLarge(32))
+        ));
+        assert!(matches!(
+            RtcpFeedbackPacket::builder()
+                .fci(Bytes::from_static(&[1, 2]))
+                .build(),
+            Err(RtpError::FeedbackDataNotAligned(2))
+        ));
+        assert!(matches!(
+            RtcpFeedbackPacket::builder()
+                .payload_type(RtcpPayloadType::App)
+                .build(),
+            Err(RtpError::WrongPayloadType(_))
+        ));
+    }
+
+    #[test]
+    fn test_remb_round_trip() {
+        let remb = Remb {
+            bitrate_bps: 1_500_000,
+            ssrcs: vec![0x1234_5678, 9],
+        };
+        let packet = RtcpFeedbackPacket::builder()
+            .sender_ssrc(1)
+            .remb(&remb)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(packet.is_remb());
+        assert!(!packet.is_generic_nack());
+        let bytes = check_header(&RtcpPacket::Feedback(packet));
+        assert_eq!(bytes.len(), 12 + 8 + 2 * 4);
+        // 1500000 is 187500 << 3
+        assert_eq!(&bytes[12..20], b"REMB\x02\x0e\xdc\x6c");
+        match read_back(&bytes) {
+            RtcpPacket::Feedback(parsed) => {
+                assert_eq!(parsed.media_ssrc, 0);
+                assert_eq!(parsed.remb().unwrap(), remb);
+            }
+            _ => unreachable!(),
+        }
+
+        // the low bits beyond the mantissa are lost
+        let precise = Remb {
+            bitrate_bps: (1 << 20) + 1,
+            ssrcs: vec![],
+        };
+        let packet = RtcpFeedbackPacket::builder()
+            .remb(&precise)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(packet.remb().unwrap().bitrate_bps, 1 << 20);
+
+        let pli = RtcpFeedbackPacket::builder()
+            .payload_type(RtcpPayloadType::PayloadSpecificFeedback)
+            .fmt(15)
+            .fci(Bytes::from_static(b"REMB\x02\x00\x00\x01\x00\x00\x00\x01"))
+            .build()
+            .unwrap();
+        assert!(matches!(pli.remb(), Err(RtpError::InvalidRemb(_))));
+    }
+
+    #[test]
+    fn test_reduced_size_compound() {
+        let nack = RtcpFeedbackPacket::builder()
+            .media_ssrc(2)
+            .nack_items(&NackItem::from_lost_sequence_numbers([1, 2]))
+            .build()
+            .unwrap();
+        let compound = RtcpCompoundPacket::builder()
+            .packet(RtcpPacket::Feedback(nack))
+            .build()
+            .unwrap();
+        let mut bytes = Vec::new();
+        compound.write_to(&mut bytes).unwrap();
+        let parsed = RtcpCompoundPacket::try_read_from(&mut Cursor::new(&bytes))
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.packets().len(), 1);
+        match &parsed.packets()[0] {
+            RtcpPacket::Feedback(parsed) => {
+                assert_eq!(parsed.lost_sequence_numbers().unwrap(), vec![1, 2]);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_ntp_timestamps() {
+        // unix time, ntp and the middle 32 bits of it
+        let cases = [
+            (Duration::ZERO, 0x83AA7E80_00000000u64, 0x7E800000u32),
+            (Duration::from_millis(1500), 0x83AA7E81_80000000, 0x7E818000),
+            (Duration::from_millis(250), 0x83AA7E80_40000000, 0x7E804000),
+            (
+                Duration::from_secs(5650576) + Duration::from_millis(500),
+                0x8400B710_80000000,
+                0xB7108000,
+            ),
+        ];
+        for (since_epoch, ntp, short) in cases {
+            let time = UNIX_EPOCH + since_epoch;
+            assert_eq!(u64::from(SimpleNtp::from(time)), ntp, "{:?}", since_epoch);
+            assert_eq!(u32::from(SimpleShortNtp::from(time)), short);
+            assert_eq!(u32::from(SimpleShortNtp::from(SimpleNtp::from(ntp))), short);
+            assert_eq!(SystemTime::from(SimpleNtp::from(ntp)), time);
+        }
+        assert_eq!(
+            u32::from(SimpleShortNtp::from(SimpleNtp::from(0x12345678_9ABCDEF0))),
+            0x56789ABC
+        );
+        // before the unix epoch
+        assert_eq!(SystemTime::from(SimpleNtp::from(0)), UNIX_EPOCH);
+    }
+
+    #[test]
+    fn test_round_trip_time_of_report_block() {
+        // arrival, lsr and dlsr in short ntp, the rtt
+        let cases = [
+            // RFC 3550 6.4.1, figure 2
+            (0xB7108000u32, 0xB7052000u32, 0x00054000u32, Some(6125)),
+            // the seconds of the short ntp wrap
+            (0x00010000, 0xFFFF8000, 0x00004000, Some(1250)),
+            // the reporter held the sender report for 50ms of 150ms
+            (
+                0x7E818000,
+                0x7E818000 - 65536 * 3 / 20,
+                65536 / 20,
+                Some(100),
+            ),
+            // no sender report got yet
+            (0xB7108000, 0, 0, None),
+            // held longer than since it was sent
+            (0xB7108000, 0xB7100000, 0x00010000, None),
+        ];
+        for (arrival, lsr, dlsr, rtt_ms) in cases {
+            let block = ReportBlock::builder()
+                .ssrc(1)
+                .last_sr(SimpleShortNtp::from(lsr))
+                .delay_since_last_sr(dlsr)
+                .build();
+            let arrival = SystemTime::from(SimpleNtp::from(
+                0x84000000_00000000 | (arrival as u64) << 16,
+            ));
+            assert_eq!(
+                block
+                    .round_trip_time(arrival)
+                    .map(|rtt| rtt.as_millis() as u64),
+                rtt_ms,
+                "lsr: {:08x}, dlsr: {:08x}",
+                lsr,
+                dlsr
+            );
+        }
+    }
+}
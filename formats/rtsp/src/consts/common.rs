@@ -5,3 +5,5 @@ pub const LF_STR: &str = "\n";
 pub const CRLF_STR: &str = "\r\n";
 pub const SPACE: u8 = b' ';
 pub const SPACE_STR: &str = " ";
+/// bodies over this are rejected unless the reader is given a limit of its own
+pub const DEFAULT_MAX_BODY_SIZE: usize = 64 * 1024;
@@ -23,6 +23,13 @@ pub enum RtspMessageError {
     MissingContentType,
     #[error("Missing Content-Length header for a message with a body")]
     MissingContentLength,
+    #[error("Message body of {length} bytes is over the maximum of {max_size} bytes")]
+    BodyTooLarge {
+        length: usize,
+        max_size: usize,
+        /// of the message, so the request can be answered with 413
+        cseq: Option<u32>,
+    },
     #[error("Invalid message format: {0}")]
     InvalidRtspMessageFormat(String),
     #[error("Invalid Url: {0}")]
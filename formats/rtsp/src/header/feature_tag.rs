@@ -0,0 +1,31 @@
+//! option tags in Require, Proxy-Require, Supported and Unsupported headers
+//! @see: RFC 7826 Section 4.5
+
+/// the client sends audio to the server on an extra sendonly media
+/// @see: ONVIF Streaming Specification Section 5.3
+pub const ONVIF_BACKCHANNEL: &str = "www.onvif.org/ver20/backchannel";
+
+/// the client follows a REDIRECT request of the server to a session it set up,
+/// the RFC leaves the way a client tells so to the servers, this is ours
+/// @see: RFC 7826 Section 13.10
+pub const METHOD_REDIRECT: &str = "method.redirect";
+
+/// splits a comma separated list of option tags, the empty ones are skipped
+pub fn parse_feature_tags(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+}
+
+/// the required tags not in `supported`, in the order they are required
+pub fn unsupported_feature_tags<'a, S: AsRef<str>>(
+    required: &'a [String],
+    supported: &[S],
+) -> Vec<&'a str> {
+    required
+        .iter()
+        .map(String::as_str)
+        .filter(|tag| !supported.iter().any(|supported| supported.as_ref() == *tag))
+        .collect()
+}
@@ -71,4 +71,6 @@ pub const USER_AGENT: &str = "User-Agent";
 
 pub const VIA: &str = "Via";
 
+pub const WARNING: &str = "Warning";
+
 pub const WWW_AUTHENTICATE: &str = "WWW-Authenticate";
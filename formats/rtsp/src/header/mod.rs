@@ -1,14 +1,27 @@
+pub mod feature_tag;
 pub mod header_names;
+pub mod rtp_info;
+pub mod scale;
+pub mod session;
+#[cfg(test)]
+mod test;
 pub mod transport;
+pub mod via;
 use std::{
     fmt,
     io::{self, Read},
     str::FromStr,
 };
 
+use rtp_info::RtpInfoHeader;
+use session::SessionHeader;
 use tokio_util::bytes::Buf;
 use transport::TransportHeader;
-use utils::traits::reader::{ReadFrom, TryReadFrom};
+use utils::traits::{
+    reader::{ReadFrom, TryReadFrom},
+    writer::WriteTo,
+};
+use via::ViaHeader;
 
 use crate::{consts::common::CRLF_STR, errors::RtspMessageError, util::TextReader};
 
@@ -87,6 +100,7 @@ pub enum RtspHeader {
 
     Via,
 
+    Warning,
     WWWAuthenticate,
 }
 
@@ -165,6 +179,7 @@ impl From<&RtspHeader> for &str {
             RtspHeader::UserAgent => header_names::USER_AGENT,
 
             RtspHeader::Via => header_names::VIA,
+            RtspHeader::Warning => header_names::WARNING,
 
             RtspHeader::WWWAuthenticate => header_names::WWW_AUTHENTICATE,
         }
@@ -254,6 +269,7 @@ impl FromStr for RtspHeader {
             header_names::USER_AGENT => Ok(Self::UserAgent),
 
             header_names::VIA => Ok(Self::Via),
+            header_names::WARNING => Ok(Self::Warning),
 
             header_names::WWW_AUTHENTICATE => Ok(Self::WWWAuthenticate),
 
@@ -319,6 +335,69 @@ impl RtspHeaders {
         self.get_unique(RtspHeader::Transport)
             .and_then(|trans| trans.parse().ok())
     }
+
+    pub fn session(&self) -> Option<SessionHeader> {
+        self.get_unique(RtspHeader::Session)
+            .and_then(|session| session.parse().ok())
+    }
+
+    pub fn rtp_info(&self) -> Option<RtpInfoHeader> {
+        self.get_unique(RtspHeader::RtpInfo)
+            .and_then(|rtp_info| rtp_info.parse().ok())
+    }
+
+    /// the proxies of all the Via headers, in the order the message passed them
+    pub fn via(&self) -> Option<ViaHeader> {
+        let values = self.get(RtspHeader::Via);
+        if values.is_empty() {
+            return None;
+        }
+        let mut via = ViaHeader::default();
+        for value in values {
+            via.append(value.parse().ok()?);
+        }
+        Some(via)
+    }
+
+    /// all the option tags in the Require headers
+    pub fn require(&self) -> Vec<String> {
+        self.feature_tags(RtspHeader::Require)
+    }
+
+    /// all the option tags in the Supported headers
+    pub fn supported(&self) -> Vec<String> {
+        self.feature_tags(RtspHeader::Supported)
+    }
+
+    /// all the option tags in the Unsupported headers
+    pub fn unsupported(&self) -> Vec<String> {
+        self.feature_tags(RtspHeader::Unsupported)
+    }
+
+    fn feature_tags(&self, key: RtspHeader) -> Vec<String> {
+        self.get(key)
+            .into_iter()
+            .flat_map(|value| feature_tag::parse_feature_tags(value))
+            .map(|tag| tag.to_owned())
+            .collect()
+    }
+
+    pub fn content_type(&self) -> Option<&String> {
+        self.get_unique(RtspHeader::ContentType)
+    }
+
+    pub fn content_length(&self) -> Result<Option<usize>, RtspMessageError> {
+        self.get_unique(RtspHeader::ContentLength)
+            .map(|length| {
+                length.trim().parse::<usize>().map_err(|err| {
+                    RtspMessageError::InvalidRtspMessageFormat(format!(
+                        "invalid content length: {}, {}",
+                        length, err
+                    ))
+                })
+            })
+            .transpose()
+    }
 }
 
 impl fmt::Display for RtspHeaders {
@@ -329,6 +408,16 @@ impl fmt::Display for RtspHeaders {
     }
 }
 
+impl<W: io::Write> WriteTo<W> for RtspHeaders {
+    type Error = RtspMessageError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        for (key, value) in self.entries() {
+            write!(writer, "{}: {}{}", key, value, CRLF_STR)?;
+        }
+        Ok(())
+    }
+}
+
 impl<R: io::BufRead> ReadFrom<R> for RtspHeaders {
     type Error = RtspMessageError;
     fn read_from(reader: &mut R) -> Result<Self, Self::Error> {
@@ -354,7 +443,8 @@ impl<R: AsRef<[u8]>> TryReadFrom<R> for RtspHeaders {
         let mut text_reader = TextReader::new(reader.by_ref());
         let mut headers = vec![];
         loop {
-            let line = text_reader.read_line()?;
+            // a line without LF is not complete yet, more bytes are on the way
+            let line = text_reader.try_read_line()?;
             if line.is_none() {
                 // at least CRLF should be there
                 return Ok(None);
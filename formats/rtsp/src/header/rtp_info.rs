@@ -0,0 +1,135 @@
+use std::{fmt, str::FromStr};
+
+use crate::errors::RtspMessageError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpInfo {
+    pub url: String,
+    pub seq: Option<u16>,
+    pub rtptime: Option<u32>,
+}
+
+impl RtpInfo {
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        Self {
+            url: url.into(),
+            seq: None,
+            rtptime: None,
+        }
+    }
+
+    pub fn with_seq(mut self, seq: u16) -> Self {
+        self.seq = Some(seq);
+        self
+    }
+
+    pub fn with_rtptime(mut self, rtptime: u32) -> Self {
+        self.rtptime = Some(rtptime);
+        self
+    }
+}
+
+impl fmt::Display for RtpInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "url={}", self.url)?;
+        if let Some(seq) = self.seq {
+            write!(f, ";seq={}", seq)?;
+        }
+        if let Some(rtptime) = self.rtptime {
+            write!(f, ";rtptime={}", rtptime)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RtpInfo {
+    type Err = RtspMessageError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut url = None;
+        let mut seq = None;
+        let mut rtptime = None;
+        for param in s.split(';') {
+            let (k, v) = param.split_once('=').unwrap_or((param, ""));
+            let v = v.trim();
+            match k.trim() {
+                "url" => url = Some(v.trim_matches('"').to_owned()),
+                "seq" => {
+                    seq = Some(v.parse().map_err(|err| {
+                        RtspMessageError::InvalidRtspMessageFormat(format!(
+                            "[rtp-info header] parse seq failed: {}, {}",
+                            v, err
+                        ))
+                    })?)
+                }
+                "rtptime" => {
+                    rtptime = Some(v.parse().map_err(|err| {
+                        RtspMessageError::InvalidRtspMessageFormat(format!(
+                            "[rtp-info header] parse rtptime failed: {}, {}",
+                            v, err
+                        ))
+                    })?)
+                }
+                _ => {
+                    // ignore
+                }
+            }
+        }
+
+        let url = url.ok_or_else(|| {
+            RtspMessageError::InvalidRtspMessageFormat(format!(
+                "[rtp-info header] url is missing: {}",
+                s
+            ))
+        })?;
+        Ok(Self { url, seq, rtptime })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RtpInfoHeader(pub Vec<RtpInfo>);
+
+impl RtpInfoHeader {
+    pub fn new(items: Vec<RtpInfo>) -> Self {
+        Self(items)
+    }
+
+    pub fn push(&mut self, item: RtpInfo) {
+        self.0.push(item);
+    }
+
+    pub fn items(&self) -> &Vec<RtpInfo> {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for RtpInfoHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.0
+                .iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        )
+    }
+}
+
+impl FromStr for RtpInfoHeader {
+    type Err = RtspMessageError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Self::default();
+        for item in s.split(',') {
+            if item.trim().is_empty() {
+                continue;
+            }
+            result.push(item.trim().parse()?);
+        }
+        Ok(result)
+    }
+}
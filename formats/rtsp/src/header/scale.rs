@@ -0,0 +1,50 @@
+use std::{fmt, str::FromStr};
+
+use crate::errors::RtspMessageError;
+
+/// playback rate relative to realtime, 2.0 plays twice as fast, negative plays backwards
+/// @see: RFC 7826 Section 18.46
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleHeader(pub f64);
+
+impl ScaleHeader {
+    pub const NORMAL: Self = Self(1.0);
+    /// the slowest and the fastest rates taken, backwards or not
+    pub const MIN_RATE: f64 = 1.0 / 64.0;
+    pub const MAX_RATE: f64 = 64.0;
+
+    #[inline]
+    pub fn is_reverse(&self) -> bool {
+        self.0 < 0.0
+    }
+}
+
+impl fmt::Display for ScaleHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.fract() == 0.0 {
+            write!(f, "{:.1}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl FromStr for ScaleHeader {
+    type Err = RtspMessageError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scale: f64 = s.trim().parse().map_err(|err| {
+            RtspMessageError::InvalidRtspMessageFormat(format!(
+                "[scale header] parse scale failed: {}, {}",
+                s, err
+            ))
+        })?;
+        // zero is not a rate, nor is one no player keeps pace with
+        if !(Self::MIN_RATE..=Self::MAX_RATE).contains(&scale.abs()) {
+            return Err(RtspMessageError::InvalidRtspMessageFormat(format!(
+                "[scale header] invalid scale: {}",
+                s
+            )));
+        }
+        Ok(Self(scale))
+    }
+}
@@ -0,0 +1,62 @@
+use std::{fmt, str::FromStr};
+
+use crate::errors::RtspMessageError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionHeader {
+    pub id: String,
+    /// timeout in seconds
+    pub timeout: Option<u64>,
+}
+
+impl SessionHeader {
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        Self {
+            id: id.into(),
+            timeout: None,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl fmt::Display for SessionHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        if let Some(timeout) = self.timeout {
+            write!(f, ";timeout={}", timeout)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SessionHeader {
+    type Err = RtspMessageError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = s.split(';');
+        let id = params.next().unwrap_or_default().trim();
+        if id.is_empty() {
+            return Err(RtspMessageError::InvalidRtspMessageFormat(format!(
+                "[session header] empty session id: {}",
+                s
+            )));
+        }
+
+        let mut result = Self::new(id);
+        for param in params {
+            let (k, v) = param.split_once('=').unwrap_or((param, ""));
+            if k.trim().eq_ignore_ascii_case("timeout") {
+                result.timeout = Some(v.trim().parse().map_err(|err| {
+                    RtspMessageError::InvalidRtspMessageFormat(format!(
+                        "[session header] parse timeout failed: {}, {}",
+                        v, err
+                    ))
+                })?);
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,239 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        consts::status::RtspStatus,
+        header::{
+            RtspHeader, RtspHeaders,
+            feature_tag::{self, ONVIF_BACKCHANNEL},
+            rtp_info::{RtpInfo, RtpInfoHeader},
+            scale::ScaleHeader,
+            session::SessionHeader,
+            transport::{TransportCast, TransportHeader, TransportMode, TransportProtocol},
+            via::{Via, ViaHeader},
+        },
+        response::RtspResponse,
+    };
+
+    #[test]
+    fn transport_udp_round_trip() {
+        let text = "RTP/AVP/UDP;unicast;mode=PLAY;client_port=8000-8001;server_port=9000-9001";
+        let parsed: TransportHeader = text.parse().unwrap();
+        assert_eq!(parsed.profile, Some(TransportProtocol::RtpAvpUdp));
+        assert_eq!(parsed.cast, Some(TransportCast::Unicast));
+        assert_eq!(parsed.mode, vec![TransportMode::Play]);
+        assert_eq!(parsed.client_port, Some((8000, 8001)));
+        assert_eq!(parsed.server_port, Some((9000, 9001)));
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(
+            parsed.to_string().parse::<TransportHeader>().unwrap(),
+            parsed
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn transport_serializes_to_its_header_value() {
+        let text = "RTP/AVP/TCP;unicast;interleaved=0-1;ssrc=0A13C760;mode=RECORD";
+        let parsed: TransportHeader = text.parse().unwrap();
+        let json = serde_json::to_string(&parsed).unwrap();
+        assert_eq!(json, format!("\"{}\"", text));
+        assert_eq!(
+            serde_json::from_str::<TransportHeader>(&json).unwrap(),
+            parsed
+        );
+        assert!(serde_json::from_str::<TransportHeader>("\"RTP/AVP;ttl=x\"").is_err());
+    }
+
+    #[test]
+    fn transport_tcp_round_trip() {
+        let text = "RTP/AVP/TCP;unicast;interleaved=0-1;ssrc=0A13C760;mode=RECORD";
+        let parsed: TransportHeader = text.parse().unwrap();
+        assert_eq!(parsed.profile, Some(TransportProtocol::RtpAvpTcp));
+        assert_eq!(parsed.interleaved, Some((0, 1)));
+        assert_eq!(parsed.ssrc_list, vec![0x0A13C760]);
+        assert_eq!(parsed.mode, vec![TransportMode::Record]);
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn transport_short_profile_and_quoted_mode() {
+        let parsed: TransportHeader = "RTP/AVP;multicast;ttl=16;port=3456;mode=\"PLAY\""
+            .parse()
+            .unwrap();
+        assert_eq!(parsed.profile, Some(TransportProtocol::RtpAvpUdp));
+        assert_eq!(parsed.cast, Some(TransportCast::Multicast));
+        assert_eq!(parsed.ttl, Some(16));
+        assert_eq!(parsed.port, Some((3456, 3456)));
+        assert_eq!(parsed.mode, vec![TransportMode::Play]);
+        assert_eq!(
+            parsed.to_string(),
+            "RTP/AVP/UDP;multicast;ttl=16;mode=PLAY;port=3456"
+        );
+        assert_eq!(
+            parsed.to_string().parse::<TransportHeader>().unwrap(),
+            parsed
+        );
+    }
+
+    #[test]
+    fn session_round_trip() {
+        let session: SessionHeader = "12345678;timeout=60".parse().unwrap();
+        assert_eq!(session, SessionHeader::new("12345678").with_timeout(60));
+        assert_eq!(session.to_string(), "12345678;timeout=60");
+
+        let session: SessionHeader = "abcdef".parse().unwrap();
+        assert_eq!(session.id, "abcdef");
+        assert_eq!(session.timeout, None);
+        assert_eq!(session.to_string(), "abcdef");
+
+        assert!("".parse::<SessionHeader>().is_err());
+        assert!("abc;timeout=x".parse::<SessionHeader>().is_err());
+    }
+
+    #[test]
+    fn rtp_info_round_trip() {
+        let text = "url=rtsp://example.com/foo/audio;seq=232433;rtptime=972948234,\
+url=rtsp://example.com/foo/video;seq=42397";
+        assert!(text.parse::<RtpInfoHeader>().is_err());
+
+        let text = "url=rtsp://example.com/foo/audio;seq=23243;rtptime=972948234,\
+url=rtsp://example.com/foo/video;seq=42397";
+        let parsed: RtpInfoHeader = text.parse().unwrap();
+        assert_eq!(
+            parsed,
+            RtpInfoHeader::new(vec![
+                RtpInfo::new("rtsp://example.com/foo/audio")
+                    .with_seq(23243)
+                    .with_rtptime(972948234),
+                RtpInfo::new("rtsp://example.com/foo/video").with_seq(42397),
+            ])
+        );
+        assert_eq!(parsed.to_string(), text);
+
+        let quoted: RtpInfoHeader = "url=\"rtsp://example.com/foo/video\";rtptime=1"
+            .parse()
+            .unwrap();
+        assert_eq!(quoted.items()[0].url, "rtsp://example.com/foo/video");
+        assert_eq!(quoted.items()[0].rtptime, Some(1));
+    }
+
+    #[test]
+    fn response_builder_typed_headers() {
+        let transport: TransportHeader =
+            "RTP/AVP/UDP;unicast;client_port=8000-8001".parse().unwrap();
+        let session = SessionHeader::new("47112344").with_timeout(60);
+        let rtp_info = RtpInfoHeader::new(vec![
+            RtpInfo::new("rtsp://example.com/foo/video")
+                .with_seq(1)
+                .with_rtptime(0),
+        ]);
+        let response = RtspResponse::builder()
+            .status(RtspStatus::OK)
+            .header(RtspHeader::CSeq, "3")
+            .transport(&transport)
+            .session(&session)
+            .rtp_info(&rtp_info)
+            .build()
+            .unwrap();
+
+        assert_eq!(response.headers().transport(), Some(transport));
+        assert_eq!(response.headers().session(), Some(session));
+        assert_eq!(response.headers().rtp_info(), Some(rtp_info));
+        assert_eq!(
+            format!("{}", response).trim_end(),
+            "RTSP/2.0 200 OK\r\n\
+CSeq: 3\r\n\
+Transport: RTP/AVP/UDP;unicast;client_port=8000-8001\r\n\
+Session: 47112344;timeout=60\r\n\
+RTP-Info: url=rtsp://example.com/foo/video;seq=1;rtptime=0"
+        );
+    }
+
+    #[test]
+    fn scale_round_trip() {
+        for (text, scale, formatted) in [
+            ("2.0", 2.0, "2.0"),
+            ("-1", -1.0, "-1.0"),
+            (" 0.5", 0.5, "0.5"),
+            ("-2.25", -2.25, "-2.25"),
+        ] {
+            let parsed: ScaleHeader = text.parse().unwrap();
+            assert_eq!(parsed, ScaleHeader(scale));
+            assert_eq!(parsed.is_reverse(), scale < 0.0);
+            assert_eq!(parsed.to_string(), formatted);
+        }
+        assert_eq!(ScaleHeader::NORMAL.to_string(), "1.0");
+        for text in ["", "0", "-0.0", "fast", "inf", "NaN", "1e-300", "-128"] {
+            assert!(text.parse::<ScaleHeader>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn require_tags() {
+        let mut headers = RtspHeaders::default();
+        headers.push(
+            RtspHeader::Require,
+            format!("{}, play.basic", ONVIF_BACKCHANNEL),
+        );
+        headers.push(RtspHeader::Require, "setup.rtp.rtcp.mux");
+        let required = headers.require();
+        assert_eq!(
+            required,
+            vec![ONVIF_BACKCHANNEL, "play.basic", "setup.rtp.rtcp.mux"]
+        );
+        assert_eq!(
+            feature_tag::unsupported_feature_tags(&required, &[ONVIF_BACKCHANNEL, "play.basic"]),
+            vec!["setup.rtp.rtcp.mux"]
+        );
+        assert!(headers.unsupported().is_empty());
+    }
+
+    #[test]
+    fn via_chain_round_trip() {
+        let text = "RTSP/2.0/TCP proxy1.example.com:554;received=192.0.2.10, \
+RTSP/2.0/TCP 198.51.100.3;ttl=16;maddr=224.2.0.1;hidden, \
+1.0 fred (Edge Proxy, v2)";
+        let parsed: ViaHeader = text.parse().unwrap();
+        assert_eq!(
+            parsed,
+            ViaHeader::new(vec![
+                Via::new("RTSP/2.0/TCP", "proxy1.example.com:554")
+                    .with_param("received", Some("192.0.2.10".to_owned())),
+                Via::new("RTSP/2.0/TCP", "198.51.100.3")
+                    .with_param("ttl", Some("16".to_owned()))
+                    .with_param("maddr", Some("224.2.0.1".to_owned()))
+                    .with_param("hidden", None),
+                Via::new("1.0", "fred").with_comment("Edge Proxy, v2"),
+            ])
+        );
+        assert_eq!(parsed.items()[0].received(), Some("192.0.2.10"));
+        assert_eq!(parsed.items()[1].param("TTL"), Some("16"));
+        assert_eq!(parsed.items()[2].received(), None);
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.to_string().parse::<ViaHeader>().unwrap(), parsed);
+
+        // the chain goes on over the Via headers of a message
+        let mut headers = RtspHeaders::default();
+        headers.push(RtspHeader::Via, "RTSP/2.0/TCP proxy1.example.com");
+        headers.push(RtspHeader::Via, "RTSP/2.0/TCP proxy2.example.com, 1.0 fred");
+        let chain = headers.via().unwrap();
+        assert_eq!(
+            chain
+                .items()
+                .iter()
+                .map(|via| via.sent_by.as_str())
+                .collect::<Vec<_>>(),
+            vec!["proxy1.example.com", "proxy2.example.com", "fred"]
+        );
+        assert_eq!(RtspHeaders::default().via(), None);
+
+        for text in [
+            "RTSP/2.0/TCP",
+            "RTSP/2.0/TCP proxy (unclosed",
+            "RTSP/2.0/TCP proxy;;ttl=1",
+            "RTSP/2.0/TCP two hosts",
+        ] {
+            assert!(text.parse::<ViaHeader>().is_err(), "{}", text);
+        }
+    }
+}
@@ -4,7 +4,7 @@ use num::Integer;
 
 use crate::errors::RtspMessageError;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TransportProtocol {
     RtpAvpUdp,
     RtpAvpTcp,
@@ -30,6 +30,14 @@ impl TransportProtocol {
             Self::RtpAvpTcp | Self::RtpAvpfTcp | Self::RtpSavpTcp | Self::RtpSavpfTcp
         )
     }
+
+    /// the secure rtp profiles, RFC 3711 and RFC 5124
+    pub fn is_secure(&self) -> bool {
+        matches!(
+            self,
+            Self::RtpSavpUdp | Self::RtpSavpTcp | Self::RtpSavpfUdp | Self::RtpSavpfTcp
+        )
+    }
 }
 
 impl fmt::Display for TransportProtocol {
@@ -53,7 +61,7 @@ impl fmt::Debug for TransportProtocol {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum TransportMode {
     Play,
     Record,
@@ -76,7 +84,7 @@ impl fmt::Debug for TransportMode {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TransportCast {
     Unicast,
     Multicast,
@@ -97,7 +105,7 @@ impl fmt::Debug for TransportCast {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum Addr {
     Port(u16),
     Host(String),
@@ -120,6 +128,13 @@ impl fmt::Debug for Addr {
     }
 }
 
+fn format_number_range<T: fmt::Display + PartialEq>(range: &(T, T)) -> String {
+    if range.0 == range.1 {
+        return format!("{}", range.0);
+    }
+    format!("{}-{}", range.0, range.1)
+}
+
 fn parse_number_range<T: FromStr + Integer + Copy>(s: &str) -> Result<(T, T), T::Err> {
     if !s.contains('-') {
         let port: T = s.parse::<T>()?;
@@ -152,7 +167,7 @@ impl FromStr for Addr {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Setup {
     Active,
     Passive,
@@ -175,7 +190,7 @@ impl fmt::Debug for Setup {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Connection {
     New,
     Existing,
@@ -196,7 +211,7 @@ impl fmt::Debug for Connection {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct TransportHeader {
     pub profile: Option<TransportProtocol>,
     pub cast: Option<TransportCast>,
@@ -229,7 +244,7 @@ impl fmt::Display for TransportHeader {
             result.push(format!("{}", cast));
         }
         if let Some(interleaved) = &self.interleaved {
-            result.push(format!("interleaved={}-{}", interleaved.0, interleaved.1));
+            result.push(format!("interleaved={}", format_number_range(interleaved)));
         }
         if let Some(ttl) = &self.ttl {
             result.push(format!("ttl={}", ttl));
@@ -242,7 +257,7 @@ impl fmt::Display for TransportHeader {
                 "ssrc={}",
                 self.ssrc_list
                     .iter()
-                    .map(|ssrc| ssrc.to_string())
+                    .map(|ssrc| format!("{:08X}", ssrc))
                     .collect::<Vec<String>>()
                     .join("/")
             ));
@@ -290,13 +305,13 @@ impl fmt::Display for TransportHeader {
             result.push(format!("MIKEY={}", mikey));
         }
         if let Some(client_port) = &self.client_port {
-            result.push(format!("client_port={}-{}", client_port.0, client_port.1));
+            result.push(format!("client_port={}", format_number_range(client_port)));
         }
         if let Some(server_port) = &self.server_port {
-            result.push(format!("server_port={}-{}", server_port.0, server_port.1));
+            result.push(format!("server_port={}", format_number_range(server_port)));
         }
         if let Some(port) = &self.port {
-            result.push(format!("port={}-{}", port.0, port.1));
+            result.push(format!("port={}", format_number_range(port)));
         }
         if self.append {
             result.push("append".to_string());
@@ -308,6 +323,22 @@ impl fmt::Display for TransportHeader {
     }
 }
 
+/// the header value, e.g., "RTP/AVP/TCP;unicast;interleaved=0-1"
+#[cfg(feature = "serde")]
+impl serde::Serialize for TransportHeader {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TransportHeader {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let header = String::deserialize(deserializer)?;
+        header.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl FromStr for TransportHeader {
     type Err = RtspMessageError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -351,7 +382,9 @@ impl FromStr for TransportHeader {
                         match mode.trim().to_uppercase().as_str() {
                             "PLAY" | "\"PLAY\"" => result.mode.push(TransportMode::Play),
                             "RECORD" | "\"RECORD\"" => result.mode.push(TransportMode::Record),
-                            _ => result.mode.push(TransportMode::Other(v.to_owned())),
+                            _ => result
+                                .mode
+                                .push(TransportMode::Other(mode.trim().to_owned())),
                         }
                     }
                 }
@@ -374,12 +407,14 @@ impl FromStr for TransportHeader {
                 }
                 "ssrc" => {
                     for ssrc in v.split('/') {
-                        result.ssrc_list.push(ssrc.parse().map_err(|err| {
-                            RtspMessageError::InvalidRtspMessageFormat(format!(
-                                "[transport header] parse ssrc failed: {}, {}",
-                                v, err
-                            ))
-                        })?);
+                        result
+                            .ssrc_list
+                            .push(u32::from_str_radix(ssrc, 16).map_err(|err| {
+                                RtspMessageError::InvalidRtspMessageFormat(format!(
+                                    "[transport header] parse ssrc failed: {}, {}",
+                                    v, err
+                                ))
+                            })?);
                     }
                 }
                 "RTCP-mux" => result.rtcp_mux = true,
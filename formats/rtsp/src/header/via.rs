@@ -0,0 +1,181 @@
+use std::{fmt, str::FromStr};
+
+use crate::errors::RtspMessageError;
+
+/// a proxy the message passed, the first one the closest to the sender
+/// @see: RFC 7826 Section 18.57, RFC 2326 Section 12.43
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Via {
+    /// RTSP/2.0/TCP, or the protocol version alone as RFC 2326 takes it from http, e.g. 1.0
+    pub protocol: String,
+    /// host and port, or a pseudonym of the proxy
+    pub sent_by: String,
+    /// ttl, maddr, received and extensions, in the order they come
+    pub params: Vec<(String, Option<String>)>,
+    /// the comment after the proxy in the http style, e.g. the software of the proxy
+    pub comment: Option<String>,
+}
+
+impl Via {
+    pub fn new<P: Into<String>, S: Into<String>>(protocol: P, sent_by: S) -> Self {
+        Self {
+            protocol: protocol.into(),
+            sent_by: sent_by.into(),
+            params: Vec::new(),
+            comment: None,
+        }
+    }
+
+    pub fn with_param<K: Into<String>>(mut self, key: K, value: Option<String>) -> Self {
+        self.params.push((key.into(), value));
+        self
+    }
+
+    pub fn with_comment<S: Into<String>>(mut self, comment: S) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// the address the proxy got the message from
+    pub fn received(&self) -> Option<&str> {
+        self.param("received")
+    }
+}
+
+impl fmt::Display for Via {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.protocol, self.sent_by)?;
+        for (key, value) in &self.params {
+            match value {
+                Some(value) => write!(f, ";{}={}", key, value)?,
+                None => write!(f, ";{}", key)?,
+            }
+        }
+        if let Some(comment) = &self.comment {
+            write!(f, " ({})", comment)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Via {
+    type Err = RtspMessageError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            RtspMessageError::InvalidRtspMessageFormat(format!("[via header] {}: {}", reason, s))
+        };
+        let s = s.trim();
+        let (s, comment) = match s.find('(') {
+            Some(start) => {
+                let comment = s[start..]
+                    .strip_prefix('(')
+                    .and_then(|comment| comment.strip_suffix(')'))
+                    .ok_or_else(|| invalid("unclosed comment"))?;
+                (s[..start].trim_end(), Some(comment.to_owned()))
+            }
+            None => (s, None),
+        };
+        let (protocol, rest) = s
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .ok_or_else(|| invalid("sent by is missing"))?;
+        let mut parts = rest.trim_start().split(';');
+        let sent_by = parts.next().unwrap_or_default().trim();
+        if protocol.is_empty() || sent_by.is_empty() || sent_by.contains(char::is_whitespace) {
+            return Err(invalid("bad protocol or sent by"));
+        }
+        let mut params = Vec::new();
+        for param in parts {
+            let param = param.trim();
+            if param.is_empty() {
+                return Err(invalid("empty param"));
+            }
+            let (key, value) = match param.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(value.trim().to_owned())),
+                None => (param, None),
+            };
+            params.push((key.to_owned(), value));
+        }
+        Ok(Self {
+            protocol: protocol.to_owned(),
+            sent_by: sent_by.to_owned(),
+            params,
+            comment,
+        })
+    }
+}
+
+/// the proxies of a message, in the order the message passed them
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ViaHeader(pub Vec<Via>);
+
+impl ViaHeader {
+    pub fn new(items: Vec<Via>) -> Self {
+        Self(items)
+    }
+
+    pub fn push(&mut self, item: Via) {
+        self.0.push(item);
+    }
+
+    pub fn items(&self) -> &Vec<Via> {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn append(&mut self, mut other: Self) {
+        self.0.append(&mut other.0);
+    }
+}
+
+impl fmt::Display for ViaHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.0
+                .iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+impl FromStr for ViaHeader {
+    type Err = RtspMessageError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Self::default();
+        // a comma in a comment does not end the item
+        let mut depth = 0usize;
+        let mut start = 0;
+        for (index, c) in s.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    let item = s[start..index].trim();
+                    if !item.is_empty() {
+                        result.push(item.parse()?);
+                    }
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        let item = s[start..].trim();
+        if !item.is_empty() {
+            result.push(item.parse()?);
+        }
+        Ok(result)
+    }
+}
@@ -6,6 +6,8 @@ pub mod reader;
 pub mod writer;
 
 pub(crate) const DOLLAR_SIGN: u8 = 0x24;
+/// the sign, the channel id and the length
+pub(crate) const INTERLEAVED_HEADER_BYTES: usize = 4;
 
 ///  0                   1                   2                   3
 ///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
@@ -29,6 +31,6 @@ impl RtspInterleavedPacket {
 
 impl DynamicSizedPacket for RtspInterleavedPacket {
     fn get_packet_bytes_count(&self) -> usize {
-        4 + self.payload.len()
+        INTERLEAVED_HEADER_BYTES + self.payload.len()
     }
 }
@@ -1,25 +1,25 @@
 use std::{
-    fmt::{self, Write},
+    fmt,
     io::{self, BufRead, Read, Seek},
     str::FromStr,
 };
 
 use byteorder::ReadBytesExt;
 use consts::{
-    common::{LF, SPACE_STR},
+    common::{CR, DEFAULT_MAX_BODY_SIZE, LF, SPACE_STR},
     methods::RtspMethod,
     version::RtspVersion,
 };
 use errors::RtspMessageError;
-use interleaved::{DOLLAR_SIGN, RtspInterleavedPacket};
+use interleaved::{DOLLAR_SIGN, INTERLEAVED_HEADER_BYTES, RtspInterleavedPacket};
 use request::RtspRequest;
 use response::RtspResponse;
 use tokio_util::{
-    bytes::Buf,
+    bytes::{Buf, BufMut},
     codec::{Decoder, Encoder},
 };
+use util::TextReader;
 use utils::traits::{
-    dynamic_sized_packet::DynamicSizedPacket,
     reader::{ReadFrom, TryReadFrom, TryReadRemainingFrom},
     writer::WriteTo,
 };
@@ -28,9 +28,12 @@ pub mod consts;
 pub mod errors;
 pub mod header;
 pub mod interleaved;
+pub mod parameters;
 pub mod request;
 pub mod response;
 pub mod sdp_extension;
+#[cfg(test)]
+mod test;
 pub mod time;
 mod util;
 
@@ -57,14 +60,27 @@ impl<R: io::BufRead> ReadFrom<R> for RtspMessage {
 impl<R: AsRef<[u8]>> TryReadFrom<R> for RtspMessage {
     type Error = RtspMessageError;
     fn try_read_from(reader: &mut io::Cursor<R>) -> Result<Option<Self>, Self::Error> {
+        Self::try_read_with_limit(reader, DEFAULT_MAX_BODY_SIZE)
+    }
+}
+
+impl RtspMessage {
+    /// the next message, None until all of it is there. the empty lines ahead of it are skipped,
+    /// as some clients end their bodies with a CRLF the Content-Length does not count,
+    /// and a body over max_body_size is rejected
+    pub fn try_read_with_limit<R: AsRef<[u8]>>(
+        reader: &mut io::Cursor<R>,
+        max_body_size: usize,
+    ) -> Result<Option<Self>, RtspMessageError> {
+        TextReader::new(reader.by_ref()).skip_empty_lines()?;
         if !reader.has_remaining() {
             return Ok(None);
         }
 
         let first_byte = reader.read_u8().unwrap();
         if first_byte == DOLLAR_SIGN {
-            RtspInterleavedPacket::try_read_remaining_from(first_byte, reader)
-                .map(|interleaved| interleaved.map(Self::Interleaved))?;
+            return RtspInterleavedPacket::try_read_remaining_from(first_byte, reader)
+                .map(|interleaved| interleaved.map(Self::Interleaved));
         }
         reader.seek_relative(-1).unwrap();
 
@@ -76,13 +92,13 @@ impl<R: AsRef<[u8]>> TryReadFrom<R> for RtspMessage {
         if let Some((first_word, _)) = first_line.split_once(SPACE_STR) {
             if let Ok(method) = RtspMethod::from_str(first_word) {
                 reader.consume(first_word.len());
-                return RtspRequest::try_read_remaining_from(method, reader)
+                return RtspRequest::try_read_remaining_with_limit(method, reader, max_body_size)
                     .map(|req| req.map(Self::Request));
             }
 
             if let Ok(version) = RtspVersion::from_str(first_word) {
                 reader.consume(first_word.len());
-                return RtspResponse::try_read_remaining_from(version, reader)
+                return RtspResponse::try_read_remaining_with_limit(version, reader, max_body_size)
                     .map(|res| res.map(Self::Response));
             }
         }
@@ -98,11 +114,10 @@ impl<W: io::Write> WriteTo<W> for RtspMessage {
     type Error = RtspMessageError;
     fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
         match self {
-            Self::Request(req) => write!(writer, "{}", req)?,
-            Self::Response(res) => write!(writer, "{}", res)?,
-            Self::Interleaved(interleaved) => interleaved.write_to(writer)?,
+            Self::Request(req) => req.write_to(writer),
+            Self::Response(res) => res.write_to(writer),
+            Self::Interleaved(interleaved) => interleaved.write_to(writer),
         }
-        Ok(())
     }
 }
 
@@ -111,17 +126,49 @@ impl fmt::Display for RtspMessage {
         match self {
             Self::Request(req) => write!(f, "{}", req),
             Self::Response(res) => write!(f, "{}", res),
-            Self::Interleaved(interleaved) => {
-                let mut bytes = Vec::with_capacity(interleaved.get_packet_bytes_count());
-                interleaved.write_to(&mut bytes).unwrap();
-                write!(f, "{}", String::from_utf8_lossy(bytes.as_ref()))
-            }
+            Self::Interleaved(interleaved) => write!(
+                f,
+                "interleaved packet of channel {}, {} bytes",
+                interleaved.channel_id,
+                interleaved.payload.len()
+            ),
         }
     }
 }
 
+/// where the decoder is between two polls
+#[derive(Debug, Default, Clone, Copy)]
+enum RtspDecodeState {
+    /// at the start of a message
+    #[default]
+    Head,
+    /// the header of an interleaved packet is consumed, the payload is not all there yet
+    InterleavedPayload { channel_id: u8, length: usize },
+}
+
+/// rtsp messages and interleaved packets over a tcp connection,
+/// the payload of an interleaved packet is taken without copying once it is all received
 #[derive(Debug)]
-pub struct RtspMessageFramed;
+pub struct RtspMessageFramed {
+    state: RtspDecodeState,
+    /// messages with a longer body are rejected
+    max_body_size: usize,
+}
+
+impl Default for RtspMessageFramed {
+    fn default() -> Self {
+        Self::with_max_body_size(DEFAULT_MAX_BODY_SIZE)
+    }
+}
+
+impl RtspMessageFramed {
+    pub fn with_max_body_size(max_body_size: usize) -> Self {
+        Self {
+            state: RtspDecodeState::Head,
+            max_body_size,
+        }
+    }
+}
 
 impl Encoder<RtspMessage> for RtspMessageFramed {
     type Error = RtspMessageError;
@@ -131,8 +178,7 @@ impl Encoder<RtspMessage> for RtspMessageFramed {
         item: RtspMessage,
         dst: &mut tokio_util::bytes::BytesMut,
     ) -> Result<(), Self::Error> {
-        dst.write_fmt(format_args!("{}", item))?;
-        Ok(())
+        item.write_to(&mut dst.writer())
     }
 }
 
@@ -144,14 +190,49 @@ impl Decoder for RtspMessageFramed {
         &mut self,
         src: &mut tokio_util::bytes::BytesMut,
     ) -> Result<Option<Self::Item>, Self::Error> {
-        let (res, position) = {
-            let mut cursor = io::Cursor::new(&src);
-            let res = RtspMessage::try_read_from(cursor.by_ref());
-            (res, cursor.position())
+        if let RtspDecodeState::Head = self.state {
+            // the empty lines between two messages
+            let empty = src
+                .iter()
+                .take_while(|&&byte| byte == CR || byte == LF)
+                .count();
+            src.advance(empty);
+            if src.is_empty() {
+                return Ok(None);
+            }
+            if src.first() != Some(&DOLLAR_SIGN) {
+                let (res, position) = {
+                    let mut cursor = io::Cursor::new(&src);
+                    let res = RtspMessage::try_read_with_limit(cursor.by_ref(), self.max_body_size);
+                    (res, cursor.position())
+                };
+                if let Ok(Some(_)) = res {
+                    src.advance(position as usize);
+                }
+                return res;
+            }
+            if src.len() < INTERLEAVED_HEADER_BYTES {
+                return Ok(None);
+            }
+            self.state = RtspDecodeState::InterleavedPayload {
+                channel_id: src[1],
+                length: u16::from_be_bytes([src[2], src[3]]) as usize,
+            };
+            src.advance(INTERLEAVED_HEADER_BYTES);
+        }
+
+        let RtspDecodeState::InterleavedPayload { channel_id, length } = self.state else {
+            unreachable!("the state is set above");
         };
-        if res.is_ok() {
-            src.advance(position as usize);
+        if src.len() < length {
+            // room for the rest of the payload, so it is not read in many small chunks
+            src.reserve(length - src.len());
+            return Ok(None);
         }
-        res
+        self.state = RtspDecodeState::Head;
+        Ok(Some(RtspMessage::Interleaved(RtspInterleavedPacket {
+            channel_id,
+            payload: src.split_to(length).freeze(),
+        })))
     }
 }
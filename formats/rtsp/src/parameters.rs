@@ -0,0 +1,80 @@
+use std::{fmt, str::FromStr};
+
+use crate::{consts::common::CRLF_STR, errors::RtspMessageError};
+
+pub const TEXT_PARAMETERS_CONTENT_TYPE: &str = "text/parameters";
+
+/// Body of GET_PARAMETER and SET_PARAMETER with content type text/parameters,
+/// one parameter per line, the value is absent for GET_PARAMETER requests
+/// @see: RFC 7826 Section 13.8, 13.9
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TextParameters(Vec<(String, Option<String>)>);
+
+impl TextParameters {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push<S: Into<String>>(&mut self, name: S, value: Option<String>) {
+        self.0.push((name.into(), value));
+    }
+
+    pub fn with<S: Into<String>, V: ToString>(mut self, name: S, value: V) -> Self {
+        self.push(name, Some(value.to_string()));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .and_then(|(_, v)| v.as_ref())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.0.iter().map(|(k, _)| k)
+    }
+
+    pub fn entries(&self) -> &Vec<(String, Option<String>)> {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for TextParameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|(name, value)| match value {
+            Some(value) => write!(f, "{}: {}{}", name, value, CRLF_STR),
+            None => write!(f, "{}{}", name, CRLF_STR),
+        })
+    }
+}
+
+impl FromStr for TextParameters {
+    type Err = RtspMessageError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut result = Self::new();
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match line.split_once(':') {
+                Some((name, value)) => {
+                    if name.trim().is_empty() {
+                        return Err(RtspMessageError::InvalidRtspMessageFormat(format!(
+                            "[text parameters] empty parameter name: {}",
+                            line
+                        )));
+                    }
+                    result.push(name.trim(), Some(value.trim().to_owned()))
+                }
+                None => result.push(line, None),
+            }
+        }
+        Ok(result)
+    }
+}
@@ -1,13 +1,13 @@
 use std::{
-    fmt::{Debug, Write},
+    fmt::Debug,
     io::{self, Read},
 };
 
 use tokio_util::{
-    bytes::Buf,
+    bytes::{Buf, BufMut},
     codec::{Decoder, Encoder},
 };
-use utils::traits::reader::TryReadFrom;
+use utils::traits::{reader::TryReadFrom, writer::WriteTo};
 
 use crate::errors::RtspMessageError;
 
@@ -24,8 +24,7 @@ impl Encoder<RtspRequest> for RtspRequestFramed {
         item: RtspRequest,
         dst: &mut tokio_util::bytes::BytesMut,
     ) -> Result<(), Self::Error> {
-        dst.write_fmt(format_args!("{}", item))?;
-        Ok(())
+        item.write_to(&mut dst.writer())
     }
 }
 
@@ -3,6 +3,7 @@ pub mod framed;
 pub mod reader;
 #[cfg(test)]
 mod test;
+pub mod writer;
 use std::fmt;
 
 use url::Url;
@@ -44,6 +45,10 @@ impl RtspRequest {
         &self.headers
     }
 
+    pub fn headers_mut(&mut self) -> &mut RtspHeaders {
+        &mut self.headers
+    }
+
     pub fn body(&self) -> Option<&String> {
         self.body.as_ref()
     }
@@ -57,8 +62,9 @@ impl fmt::Display for RtspRequest {
             self.method, self.uri, self.version, CRLF_STR
         )?;
         write!(f, "{}{}", self.headers, CRLF_STR)?;
+        // the body may be large or not printable, it is encoded by WriteTo only
         if let Some(body) = &self.body {
-            f.write_str(body)?;
+            write!(f, "[{} bytes body]", body.len())?;
         }
         Ok(())
     }
@@ -1,13 +1,13 @@
 use super::RtspRequest;
 use crate::{
     consts::{
-        common::{CR_STR, LF, LF_STR, SPACE, SPACE_STR},
+        common::{DEFAULT_MAX_BODY_SIZE, LF, SPACE, SPACE_STR},
         methods::RtspMethod,
         version::RtspVersion,
     },
     errors::RtspMessageError,
-    header::{RtspHeader, RtspHeaders},
-    util::TextReader,
+    header::RtspHeaders,
+    util::{TextReader, try_read_body},
 };
 use std::{
     io::{self, BufRead, Read},
@@ -77,6 +77,18 @@ impl<R: AsRef<[u8]>> TryReadRemainingFrom<RtspMethod, R> for RtspRequest {
         header: RtspMethod,
         reader: &mut io::Cursor<R>,
     ) -> Result<Option<Self>, Self::Error> {
+        Self::try_read_remaining_with_limit(header, reader, DEFAULT_MAX_BODY_SIZE)
+    }
+}
+
+impl RtspRequest {
+    /// the request after its method, None until all of the body its Content-Length tells is there,
+    /// a body over max_body_size is rejected
+    pub fn try_read_remaining_with_limit<R: AsRef<[u8]>>(
+        header: RtspMethod,
+        reader: &mut io::Cursor<R>,
+        max_body_size: usize,
+    ) -> Result<Option<Self>, RtspMessageError> {
         if !reader.has_remaining() {
             return Ok(None);
         }
@@ -103,27 +115,8 @@ impl<R: AsRef<[u8]>> TryReadRemainingFrom<RtspMethod, R> for RtspRequest {
         }
         let headers = headers.unwrap();
 
-        let content_length = headers
-            .get(RtspHeader::ContentLength)
-            .first()
-            .map(|s| s.parse::<usize>().ok());
-
-        let mut text_reader = TextReader::new(reader.by_ref());
-        text_reader.skip_empty_lines()?;
-        let body = if let Some(Some(length)) = content_length {
-            let body_str = text_reader.try_read_exact(length)?;
-            if body_str.is_none() {
-                return Ok(None);
-            }
-            Some({
-                body_str
-                    .unwrap()
-                    .trim_start_matches(CR_STR)
-                    .trim_start_matches(LF_STR)
-                    .to_owned()
-            })
-        } else {
-            None
+        let Some(body) = try_read_body(reader.by_ref(), &headers, max_body_size)? else {
+            return Ok(None);
         };
 
         Ok(Some(Self {
@@ -3,14 +3,25 @@ mod tests {
     use std::io::{self, Read};
 
     use url::Url;
-    use utils::traits::reader::{ReadFrom, TryReadFrom};
+    use utils::traits::{
+        reader::{ReadFrom, TryReadFrom},
+        writer::WriteTo,
+    };
 
     use crate::{
         consts::{methods::RtspMethod, version::RtspVersion},
+        errors::RtspMessageError,
         header::RtspHeader,
+        parameters::TextParameters,
         request::RtspRequest,
     };
 
+    fn encoded(request: &RtspRequest) -> String {
+        let mut bytes = Vec::new();
+        request.write_to(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
     #[test]
     fn options() {
         let request = RtspRequest::builder()
@@ -30,10 +41,10 @@ User-Agent: PhonyClient/1.2\r\n\
 Proxy-Require: gzipped-messages\r\n\
 Supported: play.basic\r\n\r\n";
 
-        assert_eq!(format!("{}", request.unwrap()).trim_end(), text.trim_end());
+        assert_eq!(encoded(&request.unwrap()).trim_end(), text.trim_end());
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -55,10 +66,10 @@ Supported: play.basic\r\n\r\n";
 CSeq: 312\r\n\
 User-Agent: PhonyClient/1.2\r\n\
 Accept: application/sdp, application/example\r\n\r\n";
-        assert_eq!(text.trim_end(), format!("{}", request.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request.unwrap()).trim_end());
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -81,10 +92,10 @@ CSeq: 302\r\n\
 Transport: RTP/AVP;unicast;dest_addr=\":4588\"/\":4589\", RTP/AVP/TCP;unicast;interleaved=0-1\r\n\
 Accept-Ranges: npt, clock\r\n\
 User-Agent: PhonyClient/1.2\r\n\r\n";
-        assert_eq!(text.trim_end(), format!("{}", request).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request).trim_end());
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -105,10 +116,10 @@ CSeq: 836\r\n\
 Session: ULExwZCXh2pd0xuFgkgZJW\r\n\
 Range: npt=3.52-\r\n\
 User-Agent: PhonyClient/1.2\r\n\r\n";
-        assert_eq!(text.trim_end(), format!("{}", request).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request).trim_end());
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -135,10 +146,10 @@ RTP-Info: url=\"rtsp://example.com/fizzle/foo/audio\" ssrc=0D12F123:seq=14783;rt
 Session: CDtUJfDQXJWtJ7Iqua2xOi\r\n\
 Date: Mon, 08 Mar 2010 13:37:16 GMT\r\n\r\n";
         let request = request.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", request).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request).trim_end());
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -157,10 +168,10 @@ CSeq: 834\r\n\
 Session: OoOUPyUwt0VeY9fFRHuZ6L\r\n\
 User-Agent: PhonyClient/1.2\r\n\r\n";
         let request = request.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", request).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request).trim_end());
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -179,10 +190,10 @@ User-Agent: PhonyClient/1.2\r\n\r\n";
 CSeq: 892\r\n\
 Session: OccldOFFq23KwjYpAnBbUr\r\n\
 User-Agent: PhonyClient/1.2\r\n\r\n";
-        assert_eq!(text.trim_end(), format!("{}", request).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request).trim_end());
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -209,13 +220,13 @@ Content-Length: 24\r\n";
 
         let body = "packets_received\r\njitter";
         let text = format!("{}\r\n{}", text, body);
-        assert_eq!(text.trim_end(), format!("{}", request).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request).trim_end());
         assert!(request.body().is_some());
         assert_eq!(request.body().unwrap(), body);
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
         let parsed = parsed.unwrap();
-        assert_eq!(text, format!("{}", parsed));
+        assert_eq!(text, encoded(&parsed));
         assert!(parsed.clone().body().is_some());
         assert_eq!(parsed.clone().body().unwrap(), body);
     }
@@ -227,7 +238,7 @@ CSeq: 431\r\n\
 User-Agent: PhonyClient/1.2\r\n\
 Session: OccldOFFq23KwjYpAnBbUr\r\n\
 Content-Type: text/parameters\r\n\
-Content-Length: 24\r\n\r\n";
+Content-Length: 24\r\n";
         let body = "packets_received\r\njitt";
         let text = format!("{}\r\n{}", text, body);
 
@@ -238,6 +249,54 @@ Content-Length: 24\r\n\r\n";
         assert!(parsed.is_none());
     }
 
+    #[test]
+    fn get_parameter_keepalive() {
+        let text = "GET_PARAMETER rtsp://example.com/fizzle/foo RTSP/2.0\r\n\
+CSeq: 432\r\n\
+Session: OccldOFFq23KwjYpAnBbUr\r\n\
+Content-Length: 0\r\n\r\n";
+        let mut cursor = io::Cursor::new(text.as_bytes());
+        let parsed = RtspRequest::try_read_from(cursor.by_ref())
+            .unwrap()
+            .unwrap();
+        assert!(parsed.body().is_none());
+        assert_eq!(cursor.position() as usize, text.len());
+    }
+
+    #[test]
+    fn body_without_content_type() {
+        let text = "SET_PARAMETER rtsp://example.com/fizzle/foo RTSP/2.0\r\n\
+CSeq: 433\r\n\
+Content-Length: 10\r\n\r\n\
+speed: 2.0";
+        assert!(matches!(
+            RtspRequest::read_from(&mut text.as_bytes()),
+            Err(RtspMessageError::MissingContentType)
+        ));
+    }
+
+    #[test]
+    fn text_parameters() {
+        let parameters: TextParameters = "packets_received\r\njitter\r\n".parse().unwrap();
+        assert_eq!(
+            parameters.names().collect::<Vec<_>>(),
+            vec!["packets_received", "jitter"]
+        );
+        assert_eq!(parameters.get("jitter"), None);
+        assert_eq!(parameters.to_string(), "packets_received\r\njitter\r\n");
+
+        let parameters: TextParameters = "barparam: barstuff\r\nSpeed: 2.0".parse().unwrap();
+        assert_eq!(parameters.get("barparam").unwrap(), "barstuff");
+        assert_eq!(parameters.get("speed").unwrap(), "2.0");
+        assert_eq!(
+            parameters,
+            TextParameters::new()
+                .with("barparam", "barstuff")
+                .with("Speed", "2.0")
+        );
+        assert!(": nothing".parse::<TextParameters>().is_err());
+    }
+
     #[test]
     fn set_parameter() {
         let request = RtspRequest::builder()
@@ -262,14 +321,14 @@ Content-Type: text/parameters\r\n\
 Content-Length: 18\r\n";
         let body = "barparam: barstuff";
         let text = format!("{}\r\n{}", text, body);
-        assert_eq!(text.trim_end(), format!("{}", request).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request).trim_end());
         assert!(request.body().is_some());
         assert_eq!(request.body().unwrap(), body);
 
         let parsed = RtspRequest::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
         let parsed = parsed.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", parsed));
+        assert_eq!(text.trim_end(), encoded(&parsed));
         assert_eq!(body, parsed.body().unwrap());
     }
 
@@ -299,7 +358,7 @@ Terminate-Reason: Server-Admin ;time=19960213T143205Z\r\n\
 Session: uZ3ci0K+Ld-M\r\n\
 Date: Thu, 13 Feb 1996 14:30:43 GMT\r\n\r\n";
         let request = request.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", request).trim_end());
+        assert_eq!(text.trim_end(), encoded(&request).trim_end());
     }
 
     #[test]
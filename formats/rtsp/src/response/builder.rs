@@ -1,7 +1,10 @@
 use crate::{
     consts::{status::RtspStatus, version::RtspVersion},
     errors::{RtspMessageError, RtspMessageResult},
-    header::{RtspHeader, RtspHeaders},
+    header::{
+        RtspHeader, RtspHeaders, rtp_info::RtpInfoHeader, scale::ScaleHeader,
+        session::SessionHeader, transport::TransportHeader,
+    },
 };
 
 use super::RtspResponse;
@@ -43,6 +46,22 @@ impl RtspResponseBuilder {
         self
     }
 
+    pub fn session(self, session: &SessionHeader) -> Self {
+        self.header(RtspHeader::Session, session.to_string())
+    }
+
+    pub fn transport(self, transport: &TransportHeader) -> Self {
+        self.header(RtspHeader::Transport, transport.to_string())
+    }
+
+    pub fn rtp_info(self, rtp_info: &RtpInfoHeader) -> Self {
+        self.header(RtspHeader::RtpInfo, rtp_info.to_string())
+    }
+
+    pub fn scale(self, scale: ScaleHeader) -> Self {
+        self.header(RtspHeader::Scale, scale.to_string())
+    }
+
     pub fn content_type(self, content_type: String) -> Self {
         self.header(RtspHeader::ContentType, content_type)
     }
@@ -1,10 +1,10 @@
-use std::{fmt::Write, io::Read};
+use std::io::Read;
 
 use tokio_util::{
-    bytes::Buf,
+    bytes::{Buf, BufMut},
     codec::{Decoder, Encoder},
 };
-use utils::traits::reader::TryReadFrom;
+use utils::traits::{reader::TryReadFrom, writer::WriteTo};
 
 use crate::errors::RtspMessageError;
 
@@ -20,8 +20,7 @@ impl Encoder<RtspResponse> for RtspResponseFramed {
         item: RtspResponse,
         dst: &mut tokio_util::bytes::BytesMut,
     ) -> Result<(), Self::Error> {
-        dst.write_fmt(format_args!("{}", item))?;
-        Ok(())
+        item.write_to(&mut dst.writer())
     }
 }
 
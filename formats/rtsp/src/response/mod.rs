@@ -3,6 +3,7 @@ pub mod framed;
 pub mod reader;
 #[cfg(test)]
 mod test;
+pub mod writer;
 use std::fmt;
 
 use builder::RtspResponseBuilder;
@@ -54,8 +55,9 @@ impl fmt::Display for RtspResponse {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{} {}{}", self.version, self.status, CRLF_STR)?;
         write!(f, "{}{}", self.headers, CRLF_STR)?;
+        // the body may be large or not printable, it is encoded by WriteTo only
         if let Some(body) = &self.body {
-            f.write_str(body)?;
+            write!(f, "[{} bytes body]", body.len())?;
         }
         Ok(())
     }
@@ -2,7 +2,10 @@
 mod tests {
     use std::io::Read;
 
-    use utils::traits::reader::{ReadFrom, TryReadFrom};
+    use utils::traits::{
+        reader::{ReadFrom, TryReadFrom},
+        writer::WriteTo,
+    };
 
     use crate::{
         consts::{status::RtspStatus, version::RtspVersion},
@@ -10,6 +13,12 @@ mod tests {
         response::RtspResponse,
     };
 
+    fn encoded(response: &RtspResponse) -> String {
+        let mut bytes = Vec::new();
+        response.write_to(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
     #[test]
     fn options() {
         let response = RtspResponse::builder()
@@ -32,10 +41,10 @@ CSeq: 1\r\n\
 Public: DESCRIBE, SETUP, TEARDOWN, PLAY, PAUSE, OPTIONS\r\n\
 Supported: play.basic, setup.rtp.rtcp.mux, play.scale\r\n\
 Server: PhonyServer/1.1\r\n\r\n";
-        assert_eq!(text.trim_end(), format!("{}", response.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response.unwrap()).trim_end());
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -82,13 +91,13 @@ Content-Type: application/sdp\r\n\
 Content-Length: 343\r\n",
             body
         );
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         assert_eq!(response.body().clone().unwrap(), body);
 
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
         let parsed = parsed.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", parsed).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed).trim_end());
         assert!(parsed.body().is_some());
         assert_eq!(parsed.body().clone().unwrap().trim_end(), body);
     }
@@ -118,10 +127,10 @@ Transport: RTP/AVP;unicast;dest_addr=\"192.0.2.53:4588\"/\"192.0.2.53:4589\"; sr
 Accept-Ranges: npt\r\n\
 Media-Properties: Random-Access=3.2, Time-Progressing, Time-Duration=3600.0\r\n\
 Media-Range: npt=0-2893.23\r\n\r\n";
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -150,10 +159,10 @@ Range: npt=3.51-324.39\r\n\
 Seek-Style: First-Prior\r\n\
 Session: ULExwZCXh2pd0xuFgkgZJW\r\n\
 RTP-Info: url=\"rtsp://example.com/audio\" ssrc=0D12F123:seq=14783;rtptime=2345962545\r\n\r\n";
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -171,10 +180,10 @@ CSeq: 854\r\n\
 User-Agent: PhonyClient/1.2\r\n\
 Session: CDtUJfDQXJWtJ7Iqua2xOi\r\n\r\n";
         let response = response.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -194,10 +203,10 @@ Date: Thu, 23 Jan 1997 15:35:06 GMT\r\n\
 Session: OoOUPyUwt0VeY9fFRHuZ6L\r\n\
 Range: npt=45.76-75.00\r\n\r\n";
         let response = response.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -213,10 +222,10 @@ Range: npt=45.76-75.00\r\n\r\n";
 CSeq: 892\r\n\
 Server: PhonyServer/1.0\r\n\r\n";
         let response = response.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -243,13 +252,13 @@ Content-Length: 36\r\n";
         let body = "packets_received: 10\r\njitter: 0.3838";
         let text = format!("{}\r\n{}", text, body);
         let response = response.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         assert!(response.body().is_some());
         assert_eq!(response.body().clone().unwrap(), body);
 
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
-        assert_eq!(text.trim_end(), format!("{}", parsed.unwrap()).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed.unwrap()).trim_end());
     }
 
     #[test]
@@ -293,14 +302,14 @@ Content-Length: 18\r\n";
         let body = "barparam: barstuff";
         let text = format!("{}\r\n{}", text, body);
         let response = response.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         assert!(response.body().is_some());
         assert_eq!(body, response.body().clone().unwrap());
 
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
         let parsed = parsed.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", parsed).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed).trim_end());
         assert!(parsed.body().is_some());
         assert_eq!(body, parsed.body().clone().unwrap());
     }
@@ -320,12 +329,12 @@ CSeq: 732\r\n\
 User-Agent: PhonyClient/1.2\r\n\
 Session: uZ3ci0K+Ld-M\r\n\r\n";
         let response = response.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", response).trim_end());
+        assert_eq!(text.trim_end(), encoded(&response).trim_end());
         assert!(response.body().is_none());
         let parsed = RtspResponse::read_from(&mut text.as_bytes());
         assert!(parsed.is_ok());
         let parsed = parsed.unwrap();
-        assert_eq!(text.trim_end(), format!("{}", parsed).trim_end());
+        assert_eq!(text.trim_end(), encoded(&parsed).trim_end());
         assert!(parsed.body().is_none());
     }
 
@@ -0,0 +1,15 @@
+use std::io;
+
+use utils::traits::writer::WriteTo;
+
+use crate::{consts::common::CRLF_STR, errors::RtspMessageError, util::write_headers_and_body};
+
+use super::RtspResponse;
+
+impl<W: io::Write> WriteTo<W> for RtspResponse {
+    type Error = RtspMessageError;
+    fn write_to(&self, writer: &mut W) -> Result<(), Self::Error> {
+        write!(writer, "{} {}{}", self.version, self.status, CRLF_STR)?;
+        write_headers_and_body(writer, &self.headers, self.body.as_deref())
+    }
+}
@@ -5,7 +5,7 @@ use url::Url;
 
 use crate::{errors::RtspMessageError, time::TimeRange};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RtspSDPControl {
     Absolute(Url),
     Relative(String),
@@ -0,0 +1,149 @@
+use sdp_formats::{
+    attributes::{SDPAttribute, SDPTrivialAttribute, media_direction::MediaDirection},
+    session::{SDPMediaDescription, SDPMediaType},
+};
+
+use super::attribute::RtspSDPControl;
+
+/// the attributes of one media description in an rtsp session,
+/// kept in the order they are parsed so the ones unknown here survive serializing back,
+/// e.g. the vendor attributes of ONVIF cameras
+#[derive(Debug, Clone, Default)]
+pub struct RtspMediaAttributes(Vec<SDPAttribute>);
+
+impl RtspMediaAttributes {
+    pub fn new(attributes: Vec<SDPAttribute>) -> Self {
+        Self(attributes)
+    }
+
+    pub fn attributes(&self) -> &[SDPAttribute] {
+        &self.0
+    }
+
+    pub fn into_attributes(self) -> Vec<SDPAttribute> {
+        self.0
+    }
+
+    pub fn push(&mut self, attribute: SDPAttribute) {
+        self.0.push(attribute);
+    }
+
+    /// the first attribute named `name` that is not one of the typed attributes
+    pub fn get(&self, name: &str) -> Option<&SDPTrivialAttribute> {
+        self.0.iter().find_map(|attr| match attr {
+            SDPAttribute::Trivial(attr) if attr.name == name => Some(attr),
+            _ => None,
+        })
+    }
+
+    pub fn control(&self) -> Option<RtspSDPControl> {
+        self.get("control")
+            .and_then(|attr| RtspSDPControl::try_from(attr).ok())
+    }
+
+    pub fn direction(&self) -> Option<MediaDirection> {
+        self.0.iter().find_map(|attr| match attr {
+            SDPAttribute::MediaDirection(direction) => Some(*direction),
+            _ => None,
+        })
+    }
+
+    /// replaces the direction in place, or appends one if there is none
+    pub fn set_direction(&mut self, direction: MediaDirection) {
+        match self
+            .0
+            .iter_mut()
+            .find(|attr| matches!(attr, SDPAttribute::MediaDirection(_)))
+        {
+            Some(attr) => *attr = SDPAttribute::MediaDirection(direction),
+            None => self.0.push(SDPAttribute::MediaDirection(direction)),
+        }
+    }
+}
+
+impl From<&SDPMediaDescription> for RtspMediaAttributes {
+    fn from(value: &SDPMediaDescription) -> Self {
+        Self(value.attributes.clone())
+    }
+}
+
+/// an audio media the server describes as sendonly, i.e. the client sends audio on it
+/// @see: ONVIF Streaming Specification Section 5.3.1
+pub fn is_onvif_backchannel(media: &SDPMediaDescription) -> bool {
+    matches!(media.media_line.media_type, SDPMediaType::Audio)
+        && matches!(
+            RtspMediaAttributes::from(media).direction(),
+            Some(MediaDirection::SendOnly)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use sdp_formats::{attributes::media_direction::MediaDirection, session::Sdp};
+
+    use super::{RtspMediaAttributes, is_onvif_backchannel};
+
+    const ONVIF_SDP: &str = "v=0\r\n\
+o=- 1718123456789012 1718123456789012 IN IP4 192.168.1.64\r\n\
+s=Media Presentation\r\n\
+e=NONE\r\n\
+b=AS:5100\r\n\
+t=0 0\r\n\
+a=control:rtsp://192.168.1.64:554/Streaming/channels/101/\r\n\
+m=video 0 RTP/AVP 96\r\n\
+c=IN IP4 0.0.0.0\r\n\
+b=AS:5000\r\n\
+a=recvonly\r\n\
+a=x-dimensions:1920,1080\r\n\
+a=control:rtsp://192.168.1.64:554/Streaming/channels/101/trackID=1\r\n\
+a=rtpmap:96 H264/90000\r\n\
+a=fmtp:96 profile-level-id=420029; packetization-mode=1; sprop-parameter-sets=Z01AKI2NQDwBE/LCAAAOEAACvyAI,aO44gA==\r\n\
+m=audio 0 RTP/AVP 0\r\n\
+c=IN IP4 0.0.0.0\r\n\
+b=AS:50\r\n\
+a=recvonly\r\n\
+a=control:rtsp://192.168.1.64:554/Streaming/channels/101/trackID=2\r\n\
+a=rtpmap:0 PCMU/8000\r\n\
+a=Media_header:MEDIAINFO=494D4B48010200000400000110710110401F000000FA000000000000000000000000000000000000;\r\n\
+a=appversion:1.0\r\n\
+m=audio 0 RTP/AVP 0\r\n\
+c=IN IP4 0.0.0.0\r\n\
+b=AS:50\r\n\
+a=sendonly\r\n\
+a=control:rtsp://192.168.1.64:554/Streaming/channels/101/trackID=4\r\n\
+a=rtpmap:0 PCMU/8000\r\n";
+
+    #[test]
+    fn onvif_sdp_round_trip() {
+        let sdp = Sdp::reader().read_from(ONVIF_SDP).unwrap();
+        assert_eq!(sdp.to_string(), ONVIF_SDP);
+
+        let backchannels: Vec<_> = sdp
+            .media_description
+            .iter()
+            .filter(|media| is_onvif_backchannel(media))
+            .collect();
+        assert_eq!(backchannels.len(), 1);
+        let attributes = RtspMediaAttributes::from(backchannels[0]);
+        assert_eq!(
+            attributes.control().unwrap().url_to_str(),
+            "rtsp://192.168.1.64:554/Streaming/channels/101/trackID=4"
+        );
+
+        let audio = RtspMediaAttributes::from(&sdp.media_description[1]);
+        assert_eq!(
+            audio.get("appversion").unwrap().value.as_deref(),
+            Some("1.0")
+        );
+        let mut media = sdp.media_description[1].clone();
+        let mut attributes = audio.clone();
+        attributes.set_direction(MediaDirection::Inactive);
+        media.attributes = attributes.into_attributes();
+        assert_eq!(
+            media.to_string(),
+            sdp.media_description[1]
+                .to_string()
+                .replace("a=recvonly", "a=inactive")
+        );
+    }
+}
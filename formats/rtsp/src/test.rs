@@ -0,0 +1,381 @@
+#[cfg(test)]
+mod tests {
+    use tokio_util::{
+        bytes::BytesMut,
+        codec::{Decoder, Encoder},
+    };
+    use url::Url;
+    use utils::traits::writer::WriteTo;
+
+    use crate::{
+        RtspMessage, RtspMessageFramed,
+        consts::{methods::RtspMethod, status::RtspStatus},
+        errors::RtspMessageError,
+        header::RtspHeader,
+        interleaved::RtspInterleavedPacket,
+        request::RtspRequest,
+        response::RtspResponse,
+    };
+
+    #[test]
+    fn interleaved_packet_is_encoded_byte_exact() {
+        let payload: Vec<u8> = (0..=u8::MAX).rev().collect();
+        let packet = RtspInterleavedPacket::builder()
+            .channel(1)
+            .payload(&payload)
+            .build();
+        let mut dst = BytesMut::new();
+        RtspMessageFramed::default()
+            .encode(RtspMessage::Interleaved(packet), &mut dst)
+            .unwrap();
+
+        let mut expected = vec![b'$', 1, 0x01, 0x00];
+        expected.extend_from_slice(&payload);
+        assert_eq!(&dst[..], &expected[..]);
+
+        let Some(RtspMessage::Interleaved(decoded)) =
+            RtspMessageFramed::default().decode(&mut dst).unwrap()
+        else {
+            panic!("expect an interleaved packet");
+        };
+        assert_eq!(decoded.channel_id, 1);
+        assert_eq!(&decoded.payload[..], &payload[..]);
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn sdp_body_with_empty_lines_survives_a_round_trip() {
+        // an empty line and multi-byte characters within the body, the length is in bytes
+        let sdp = "v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=直播 ⚡\r\n\r\nm=video 0 RTP/AVP 96\r\n\r\n";
+        let request = RtspRequest::builder()
+            .method(RtspMethod::Announce)
+            .uri("rtsp://example.com/live/stream".parse::<Url>().unwrap())
+            .header(RtspHeader::CSeq, "2")
+            .header(RtspHeader::ContentType, "application/sdp")
+            .body(sdp.to_owned())
+            .build()
+            .unwrap();
+        assert!(format!("{}", request).ends_with(&format!("[{} bytes body]", sdp.len())));
+
+        let mut dst = BytesMut::new();
+        RtspMessageFramed::default()
+            .encode(RtspMessage::Request(request.clone()), &mut dst)
+            .unwrap();
+        assert!(dst.ends_with(sdp.as_bytes()));
+        // a partial message is left in the buffer
+        let mut partial = BytesMut::from(&dst[..dst.len() - 3]);
+        assert!(
+            RtspMessageFramed::default()
+                .decode(&mut partial)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(partial.len(), dst.len() - 3);
+
+        let Some(RtspMessage::Request(decoded)) =
+            RtspMessageFramed::default().decode(&mut dst).unwrap()
+        else {
+            panic!("expect a request");
+        };
+        assert_eq!(decoded.method(), RtspMethod::Announce);
+        assert_eq!(decoded.body().unwrap(), sdp);
+        assert!(dst.is_empty());
+    }
+
+    #[test]
+    fn body_not_matching_content_length_is_not_encoded() {
+        let mut request = RtspRequest::builder()
+            .method(RtspMethod::SetParameter)
+            .uri("rtsp://example.com/live/stream".parse::<Url>().unwrap())
+            .header(RtspHeader::ContentType, "text/parameters")
+            .body("barparam: barstuff\r\n".to_owned())
+            .build()
+            .unwrap();
+        request.headers_mut().set(RtspHeader::ContentLength, "4");
+        let result = RtspMessageFramed::default()
+            .encode(RtspMessage::Request(request.clone()), &mut BytesMut::new());
+        assert!(matches!(
+            result,
+            Err(RtspMessageError::InvalidRtspMessageFormat(_))
+        ));
+    }
+
+    #[test]
+    fn content_length_is_written_for_a_body_without_one() {
+        let mut request = RtspRequest::builder()
+            .method(RtspMethod::SetParameter)
+            .uri("rtsp://example.com/live/stream".parse::<Url>().unwrap())
+            .header(RtspHeader::CSeq, "3")
+            .header(RtspHeader::ContentType, "text/parameters")
+            .body("barparam: barstuff\r\n".to_owned())
+            .build()
+            .unwrap();
+        request.headers_mut().remove(RtspHeader::ContentLength);
+        let mut dst = BytesMut::new();
+        RtspMessageFramed::default()
+            .encode(RtspMessage::Request(request), &mut dst)
+            .unwrap();
+        assert!(dst.ends_with(b"Content-Length: 20\r\n\r\nbarparam: barstuff\r\n"));
+
+        let Some(RtspMessage::Request(decoded)) =
+            RtspMessageFramed::default().decode(&mut dst).unwrap()
+        else {
+            panic!("expect a request");
+        };
+        assert_eq!(decoded.body().unwrap(), "barparam: barstuff\r\n");
+        assert!(dst.is_empty());
+    }
+
+    fn encoded(message: &RtspMessage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        message.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn interleaved(channel: u8, payload: &[u8]) -> RtspMessage {
+        RtspMessage::Interleaved(
+            RtspInterleavedPacket::builder()
+                .channel(channel)
+                .payload(payload)
+                .build(),
+        )
+    }
+
+    /// interleaved packets with bytes looking like the start or the end of a message,
+    /// an empty one, and requests and responses in between
+    fn mixed_messages() -> Vec<RtspMessage> {
+        let uri = "rtsp://example.com/live/stream".parse::<Url>().unwrap();
+        vec![
+            interleaved(0, &[b'$', b'\r', b'\n', b'\r', b'\n', 0xff]),
+            RtspMessage::Request(
+                RtspRequest::builder()
+                    .method(RtspMethod::Options)
+                    .uri(uri.clone())
+                    .header(RtspHeader::CSeq, "1")
+                    .build()
+                    .unwrap(),
+            ),
+            interleaved(1, &[]),
+            RtspMessage::Request(
+                RtspRequest::builder()
+                    .method(RtspMethod::SetParameter)
+                    .uri(uri)
+                    .header(RtspHeader::CSeq, "2")
+                    .header(RtspHeader::ContentType, "text/parameters")
+                    .body("barparam: barstuff\r\n".to_owned())
+                    .build()
+                    .unwrap(),
+            ),
+            RtspMessage::Response(
+                RtspResponse::builder()
+                    .status(RtspStatus::OK)
+                    .header(RtspHeader::CSeq, "3")
+                    .build()
+                    .unwrap(),
+            ),
+            interleaved(3, &(0..=u8::MAX).collect::<Vec<_>>()),
+        ]
+    }
+
+    fn decode_all(framed: &mut RtspMessageFramed, src: &mut BytesMut, decoded: &mut Vec<Vec<u8>>) {
+        while let Some(message) = framed.decode(src).unwrap() {
+            decoded.push(encoded(&message));
+        }
+    }
+
+    #[test]
+    fn messages_split_at_any_two_points_are_decoded_in_order() {
+        let messages = mixed_messages();
+        let expected: Vec<Vec<u8>> = messages.iter().map(encoded).collect();
+        let stream = expected.concat();
+        for first in 0..=stream.len() {
+            for second in first..=stream.len() {
+                let mut framed = RtspMessageFramed::default();
+                let mut src = BytesMut::new();
+                let mut decoded = Vec::new();
+                for piece in [&stream[..first], &stream[first..second], &stream[second..]] {
+                    src.extend_from_slice(piece);
+                    decode_all(&mut framed, &mut src, &mut decoded);
+                }
+                assert_eq!(decoded, expected, "split at {} and {}", first, second);
+                assert!(src.is_empty());
+            }
+        }
+    }
+
+    /// bodies starting with or missing the final CRLF, a CRLF after a body its Content-Length does not
+    /// count, and a request without Content-Length
+    fn messages_with_bodies() -> (Vec<u8>, Vec<RtspMessage>) {
+        let uri = "rtsp://example.com/live/stream".parse::<Url>().unwrap();
+        let sdp = "v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=live\r\nm=video 0 RTP/AVP 96\r\na=control:*";
+        let announce = RtspMessage::Request(
+            RtspRequest::builder()
+                .method(RtspMethod::Announce)
+                .uri(uri.clone())
+                .header(RtspHeader::CSeq, "1")
+                .header(RtspHeader::ContentType, "application/sdp")
+                .body(sdp.to_owned())
+                .build()
+                .unwrap(),
+        );
+        let set_parameter = RtspMessage::Request(
+            RtspRequest::builder()
+                .method(RtspMethod::SetParameter)
+                .uri(uri.clone())
+                .header(RtspHeader::CSeq, "2")
+                .header(RtspHeader::ContentType, "text/parameters")
+                .body("\r\nbarparam: barstuff\r\n".to_owned())
+                .build()
+                .unwrap(),
+        );
+        let without_content_length = RtspMessage::Request(
+            RtspRequest::builder()
+                .method(RtspMethod::SetParameter)
+                .uri(uri)
+                .header(RtspHeader::CSeq, "3")
+                .build()
+                .unwrap(),
+        );
+        let describe = RtspMessage::Response(
+            RtspResponse::builder()
+                .status(RtspStatus::OK)
+                .header(RtspHeader::CSeq, "4")
+                .header(RtspHeader::ContentType, "application/sdp")
+                .body(sdp.to_owned())
+                .build()
+                .unwrap(),
+        );
+        let stream = [
+            encoded(&announce),
+            b"\r\n".to_vec(),
+            encoded(&set_parameter),
+            encoded(&without_content_length),
+            encoded(&describe),
+        ]
+        .concat();
+        (
+            stream,
+            vec![announce, set_parameter, without_content_length, describe],
+        )
+    }
+
+    #[test]
+    fn bodies_arriving_byte_by_byte_are_taken_verbatim() {
+        let (stream, messages) = messages_with_bodies();
+        let expected: Vec<Vec<u8>> = messages.iter().map(encoded).collect();
+        // where each message ends in the stream, the CRLF after the first one is not counted
+        let ends: Vec<usize> = expected
+            .iter()
+            .enumerate()
+            .scan(0, |at, (i, message)| {
+                *at += message.len() + if i == 1 { 2 } else { 0 };
+                Some(*at)
+            })
+            .collect();
+        let mut framed = RtspMessageFramed::default();
+        let mut src = BytesMut::new();
+        let mut decoded = Vec::new();
+        for (i, byte) in stream.iter().enumerate() {
+            src.extend_from_slice(&[*byte]);
+            decode_all(&mut framed, &mut src, &mut decoded);
+            // a message is taken with its last byte, not before
+            let received = ends.iter().filter(|&&end| end <= i + 1).count();
+            assert_eq!(decoded.len(), received, "{} bytes received", i + 1);
+        }
+        assert_eq!(decoded, expected);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn bodies_split_at_any_point_are_taken_verbatim() {
+        let (stream, messages) = messages_with_bodies();
+        for split in 0..=stream.len() {
+            let mut framed = RtspMessageFramed::default();
+            let mut src = BytesMut::new();
+            let mut decoded = Vec::new();
+            for piece in [&stream[..split], &stream[split..]] {
+                src.extend_from_slice(piece);
+                while let Some(message) = framed.decode(&mut src).unwrap() {
+                    decoded.push(message);
+                }
+            }
+            assert_eq!(decoded.len(), messages.len(), "split at {}", split);
+            let bodies: Vec<_> = decoded
+                .iter()
+                .map(|message| match message {
+                    RtspMessage::Request(request) => request.body().cloned(),
+                    RtspMessage::Response(response) => response.body().clone(),
+                    RtspMessage::Interleaved(_) => panic!("expect no interleaved packet"),
+                })
+                .collect();
+            assert_eq!(
+                bodies,
+                vec![
+                    Some("v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=live\r\nm=video 0 RTP/AVP 96\r\na=control:*".to_owned()),
+                    Some("\r\nbarparam: barstuff\r\n".to_owned()),
+                    None,
+                    Some("v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=live\r\nm=video 0 RTP/AVP 96\r\na=control:*".to_owned()),
+                ],
+                "split at {}",
+                split
+            );
+            assert!(src.is_empty());
+        }
+    }
+
+    #[test]
+    fn body_over_the_limit_is_rejected_before_it_arrives() {
+        let head = "ANNOUNCE rtsp://example.com/live/stream RTSP/1.0\r\n\
+CSeq: 7\r\n\
+Content-Type: application/sdp\r\n\
+Content-Length: 17\r\n\r\n";
+        let mut framed = RtspMessageFramed::with_max_body_size(16);
+        let mut src = BytesMut::from(head);
+        assert!(matches!(
+            framed.decode(&mut src),
+            Err(RtspMessageError::BodyTooLarge {
+                length: 17,
+                max_size: 16,
+                cseq: Some(7)
+            })
+        ));
+
+        let head = head.replace("Content-Length: 17", "Content-Length: 16");
+        let mut src = BytesMut::from(format!("{}v=0\r\ns=live\r\nt=0", head).as_str());
+        let Some(RtspMessage::Request(request)) = RtspMessageFramed::with_max_body_size(16)
+            .decode(&mut src)
+            .unwrap()
+        else {
+            panic!("expect a request");
+        };
+        assert_eq!(request.body().unwrap(), "v=0\r\ns=live\r\nt=0");
+    }
+
+    #[test]
+    fn large_interleaved_payload_is_taken_once_complete() {
+        let payload: Vec<u8> = (0..u16::MAX).map(|i| i as u8).collect();
+        let stream = encoded(&interleaved(2, &payload));
+        let mut framed = RtspMessageFramed::default();
+        let mut src = BytesMut::new();
+        let mut chunks = stream.chunks(1000);
+
+        src.extend_from_slice(chunks.next().unwrap());
+        assert!(framed.decode(&mut src).unwrap().is_none());
+        // the header is consumed, the payload is not scanned again on the next polls
+        assert_eq!(src.len(), 1000 - 4);
+        assert!(src.capacity() >= payload.len());
+
+        let mut decoded = None;
+        for chunk in chunks {
+            assert!(decoded.is_none());
+            src.extend_from_slice(chunk);
+            decoded = framed.decode(&mut src).unwrap();
+        }
+        let Some(RtspMessage::Interleaved(packet)) = decoded else {
+            panic!("expect an interleaved packet");
+        };
+        assert_eq!(packet.channel_id, 2);
+        assert_eq!(&packet.payload[..], &payload[..]);
+        assert!(src.is_empty());
+    }
+}
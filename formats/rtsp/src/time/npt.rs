@@ -13,6 +13,21 @@ pub enum Npt {
     Now,
 }
 
+impl Npt {
+    /// the seconds from the start of the stream, None for now
+    pub fn seconds(&self) -> Option<f64> {
+        match self {
+            Self::Seconds(seconds) => Some(*seconds),
+            Self::HHMMSS {
+                hours,
+                minutes,
+                seconds,
+            } => Some(*hours as f64 * 3600.0 + *minutes as f64 * 60.0 + seconds),
+            Self::Now => None,
+        }
+    }
+}
+
 impl FromStr for Npt {
     type Err = RtspMessageError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
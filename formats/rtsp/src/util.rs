@@ -1,6 +1,12 @@
 use std::io;
 
-use crate::consts::common::{CR, CRLF_STR, LF};
+use utils::traits::writer::WriteTo;
+
+use crate::{
+    consts::common::{CR, CRLF_STR, LF},
+    errors::RtspMessageError,
+    header::{RtspHeader, RtspHeaders},
+};
 
 pub struct TextReader<R: io::BufRead> {
     inner: R,
@@ -82,3 +88,63 @@ impl<R: io::BufRead> TextReader<R> {
         self.read_exact(len).map(Some)
     }
 }
+
+/// the body of a message as long as its Content-Length says, taken verbatim once all of it is there.
+/// Ok(None) while the body is not all received, Ok(Some(None)) for a message without body,
+/// as one without Content-Length is. a body over max_body_size is rejected before it is received
+pub(crate) fn try_read_body<R: io::BufRead>(
+    reader: &mut R,
+    headers: &RtspHeaders,
+    max_body_size: usize,
+) -> Result<Option<Option<String>>, RtspMessageError> {
+    let length = match headers.content_length()? {
+        // Content-Length: 0 is a message without body, e.g., a GET_PARAMETER keepalive
+        None | Some(0) => return Ok(Some(None)),
+        Some(length) => length,
+    };
+    if length > max_body_size {
+        return Err(RtspMessageError::BodyTooLarge {
+            length,
+            max_size: max_body_size,
+            cseq: headers.cseq(),
+        });
+    }
+    if headers.content_type().is_none() {
+        return Err(RtspMessageError::MissingContentType);
+    }
+    Ok(TextReader::new(reader).try_read_exact(length)?.map(Some))
+}
+
+/// writes the headers and the body verbatim, the Content-Length of a body is written after the headers
+/// if they have none, the one they have must match the body
+pub(crate) fn write_headers_and_body<W: io::Write>(
+    writer: &mut W,
+    headers: &RtspHeaders,
+    body: Option<&str>,
+) -> Result<(), RtspMessageError> {
+    let content_length = headers.content_length()?;
+    if let Some(body) = body
+        && let Some(length) = content_length
+        && length != body.len()
+    {
+        return Err(RtspMessageError::InvalidRtspMessageFormat(format!(
+            "content length {} does not match the body of {} bytes",
+            length,
+            body.len()
+        )));
+    }
+    headers.write_to(writer)?;
+    let body = body.unwrap_or_default();
+    if content_length.is_none() && !body.is_empty() {
+        write!(
+            writer,
+            "{}: {}{}",
+            RtspHeader::ContentLength,
+            body.len(),
+            CRLF_STR
+        )?;
+    }
+    writer.write_all(CRLF_STR.as_bytes())?;
+    writer.write_all(body.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use crate::errors::SDPError;
+use std::{fmt, str::FromStr};
+
+/// the keys of the secure rtp of a media, signaled by sdes
+/// a=crypto:<tag> <crypto-suite> <key-params> *(<session-param>)
+/// @see: RFC 4568 9.1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crypto {
+    pub tag: u32,
+    pub suite: String,
+    /// one or more "<key-method>:<key-info>" joined by ';'
+    pub key_params: String,
+    pub session_params: Vec<String>,
+}
+
+impl FromStr for Crypto {
+    type Err = SDPError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_ascii_whitespace();
+        let (Some(tag), Some(suite), Some(key_params)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(SDPError::InvalidAttributeLine(format!(
+                "invalid crypto: {}",
+                s
+            )));
+        };
+        let tag = tag.parse().map_err(|err| {
+            SDPError::InvalidAttributeLine(format!("parse crypto tag failed: {}, {}", tag, err))
+        })?;
+        Ok(Self {
+            tag,
+            suite: suite.to_owned(),
+            key_params: key_params.to_owned(),
+            session_params: fields.map(|param| param.to_owned()).collect(),
+        })
+    }
+}
+
+impl fmt::Display for Crypto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.tag, self.suite, self.key_params)?;
+        for param in &self.session_params {
+            write!(f, " {}", param)?;
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 pub mod conf_type;
+pub mod crypto;
 pub mod fmtp;
 pub mod media_direction;
 pub mod orient;
@@ -6,6 +7,7 @@ pub mod rtpmap;
 use std::{fmt, str::FromStr};
 
 use conf_type::ConferenceType;
+use crypto::Crypto;
 use fmtp::FormatParameters;
 use media_direction::MediaDirection;
 use orient::Orient;
@@ -54,6 +56,7 @@ pub enum SDPAttribute {
     Framerate(f64),  // TODO: bound to none zero
     Quality(u64),    // TODO: bound to none zero
     Fmtp(FormatParameters),
+    Crypto(Crypto),
 }
 
 #[derive(Debug, Clone)]
@@ -98,6 +101,7 @@ impl FromStr for SDPAttribute {
                 SDPError::InvalidAttributeLine(format!("parse quality failed: {}, {}", v, err))
             })?)),
             "fmtp" => Ok(Self::Fmtp(v.parse()?)),
+            "crypto" => Ok(Self::Crypto(v.parse()?)),
             _ => Ok(Self::Trivial(SDPTrivialAttribute {
                 name: k.to_owned(),
                 value: if v.is_empty() {
@@ -130,6 +134,7 @@ impl fmt::Display for SDPAttribute {
             Self::Framerate(fr) => write!(f, "framerate:{}", fr),
             Self::Quality(qu) => write!(f, "quality:{}", qu),
             Self::Fmtp(fmtp) => write!(f, "fmtp:{}", fmtp),
+            Self::Crypto(crypto) => write!(f, "crypto:{}", crypto),
             Self::Trivial(trivial) => {
                 write!(f, "{}", trivial.name)?;
                 if let Some(value) = &trivial.value {
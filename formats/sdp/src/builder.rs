@@ -162,6 +162,12 @@ pub struct SdpMediaBuilder {
     media: SDPMediaDescription,
 }
 
+impl From<SDPMediaDescription> for SdpMediaBuilder {
+    fn from(value: SDPMediaDescription) -> Self {
+        Self { media: value }
+    }
+}
+
 impl SdpMediaBuilder {
     pub fn new() -> Self {
         Self::default()
@@ -603,4 +603,33 @@ a=rtpmap:99 h263-1998/90000\r\n";
         assert_eq!(actual.as_str(), TIME_ZONES_SDPEXPECTED);
         Ok(())
     }
+
+    #[test]
+    fn test_crypto_attribute() -> SDPResult<()> {
+        let sdp_str = "v=0\r\n\
+o=- 0 0 IN IP4 127.0.0.1\r\n\
+s=-\r\n\
+t=0 0\r\n\
+m=video 0 RTP/SAVP 96\r\n\
+a=rtpmap:96 H264/90000\r\n\
+a=crypto:1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^20|1:4 UNENCRYPTED_SRTCP\r\n";
+        let sdp = SessionDescriptionReader::new().read_from(sdp_str)?;
+        let crypto = sdp.media_description[0]
+            .attributes
+            .iter()
+            .find_map(|attr| match attr {
+                SDPAttribute::Crypto(crypto) => Some(crypto),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(crypto.tag, 1);
+        assert_eq!(crypto.suite, "AES_CM_128_HMAC_SHA1_80");
+        assert_eq!(
+            crypto.key_params,
+            "inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^20|1:4"
+        );
+        assert_eq!(crypto.session_params, vec!["UNENCRYPTED_SRTCP"]);
+        assert_eq!(format!("{}", sdp), sdp_str);
+        Ok(())
+    }
 }
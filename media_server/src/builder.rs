@@ -0,0 +1,232 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use http_server::config::HttpServerConfig;
+use rtmp_server::config::RtmpServerConfig;
+use rtsp_server::{config::RtspServerConfig, middleware::RtspMiddleware};
+use server_utils::{
+    egress_shaping::{EgressShaper, EgressShapingConfig},
+    ingest_limit::{IngestLimitConfig, IngestRateLimiter},
+    supervisor::RestartPolicy,
+};
+use srt_server::config::SrtServerConfig;
+use stream_center::{
+    audio_continuity::AudioGapConcealment, backpressure::BackpressurePolicy, dvr::DvrWindow,
+    gop_budget::GopCacheBudgetConfig, identity::StreamIdentity, latency::LatencyConfig,
+    recovery_point::RecoveryPointJoin, slice_integrity::SliceIntegrity,
+    stream_center::StreamCenter, takeover::TakeoverPolicy,
+    timestamp_normalization::TimestampNormalization, trace::PipelineTracer, watchdog::IdleWatchdog,
+};
+
+use crate::server::{MediaServer, PendingServers};
+
+/// how the stream center treats the streams, the defaults of the stream center if left out
+#[derive(Debug, Default, Clone)]
+pub struct StreamCenterOptions {
+    /// records pipeline events of every stream
+    pub tracer: Option<Arc<dyn PipelineTracer>>,
+    /// by app
+    pub takeover_policies: HashMap<String, TakeoverPolicy>,
+    /// for apps without a policy of their own
+    pub default_takeover_policy: Option<TakeoverPolicy>,
+    /// by app
+    pub idle_watchdogs: HashMap<String, IdleWatchdog>,
+    /// for apps without a watchdog of their own
+    pub default_idle_watchdog: Option<IdleWatchdog>,
+    /// by app
+    pub recovery_point_joins: HashMap<String, RecoveryPointJoin>,
+    /// for apps without a recovery point join of their own
+    pub default_recovery_point_join: Option<RecoveryPointJoin>,
+    /// by app
+    pub audio_gap_concealments: HashMap<String, AudioGapConcealment>,
+    /// for apps without an audio gap concealment of their own
+    pub default_audio_gap_concealment: Option<AudioGapConcealment>,
+    /// by app
+    pub timestamp_normalizations: HashMap<String, TimestampNormalization>,
+    /// for apps without a timestamp normalization of their own
+    pub default_timestamp_normalization: Option<TimestampNormalization>,
+    /// by app
+    pub slice_integrities: HashMap<String, SliceIntegrity>,
+    /// for apps without a slice integrity of their own
+    pub default_slice_integrity: Option<SliceIntegrity>,
+    /// by app
+    pub dvr_windows: HashMap<String, DvrWindow>,
+    /// for apps without a dvr window of their own
+    pub default_dvr_window: Option<DvrWindow>,
+    /// by app
+    pub backpressure_policies: HashMap<String, BackpressurePolicy>,
+    /// for apps without a backpressure policy of their own
+    pub default_backpressure_policy: Option<BackpressurePolicy>,
+    /// latency measurement is disabled if not set
+    pub latency: Option<LatencyConfig>,
+    /// the stream each alias plays, the admin api may point them elsewhere at runtime
+    pub aliases: HashMap<StreamIdentity, StreamIdentity>,
+    /// the memory the gop caches of all the streams share, 1 GiB if not set
+    pub gop_cache_budget: Option<GopCacheBudgetConfig>,
+    /// stamp frames with the crc of their payload and check it after the stages keeping it
+    #[cfg(feature = "frame-crc")]
+    pub frame_crc_validation: bool,
+}
+
+impl StreamCenterOptions {
+    fn build(self) -> StreamCenter {
+        let mut stream_center = match self.tracer {
+            Some(tracer) => StreamCenter::with_tracer(tracer),
+            None => StreamCenter::new(),
+        };
+        for (app, policy) in self.takeover_policies {
+            stream_center.set_takeover_policy(&app, policy);
+        }
+        if let Some(policy) = self.default_takeover_policy {
+            stream_center.set_default_takeover_policy(policy);
+        }
+        for (app, watchdog) in self.idle_watchdogs {
+            stream_center.set_idle_watchdog(&app, watchdog);
+        }
+        if let Some(watchdog) = self.default_idle_watchdog {
+            stream_center.set_default_idle_watchdog(watchdog);
+        }
+        for (app, join) in self.recovery_point_joins {
+            stream_center.set_recovery_point_join(&app, join);
+        }
+        if let Some(join) = self.default_recovery_point_join {
+            stream_center.set_default_recovery_point_join(join);
+        }
+        for (app, concealment) in self.audio_gap_concealments {
+            stream_center.set_audio_gap_concealment(&app, concealment);
+        }
+        if let Some(concealment) = self.default_audio_gap_concealment {
+            stream_center.set_default_audio_gap_concealment(concealment);
+        }
+        for (app, normalization) in self.timestamp_normalizations {
+            stream_center.set_timestamp_normalization(&app, normalization);
+        }
+        if let Some(normalization) = self.default_timestamp_normalization {
+            stream_center.set_default_timestamp_normalization(normalization);
+        }
+        for (app, integrity) in self.slice_integrities {
+            stream_center.set_slice_integrity(&app, integrity);
+        }
+        if let Some(integrity) = self.default_slice_integrity {
+            stream_center.set_default_slice_integrity(integrity);
+        }
+        for (app, window) in self.dvr_windows {
+            stream_center.set_dvr_window(&app, window);
+        }
+        if let Some(window) = self.default_dvr_window {
+            stream_center.set_default_dvr_window(window);
+        }
+        for (app, policy) in self.backpressure_policies {
+            stream_center.set_backpressure_policy(&app, policy);
+        }
+        if let Some(policy) = self.default_backpressure_policy {
+            stream_center.set_default_backpressure_policy(policy);
+        }
+        if let Some(latency) = self.latency {
+            stream_center.set_latency_measurement(latency);
+        }
+        for (alias, canonical) in self.aliases {
+            if let Err(err) = stream_center.set_alias(alias.clone(), Some(canonical)) {
+                tracing::error!("skip alias {}: {}", alias, err);
+            }
+        }
+        if let Some(budget) = self.gop_cache_budget {
+            stream_center.set_gop_cache_budget(budget);
+        }
+        #[cfg(feature = "frame-crc")]
+        stream_center.set_frame_crc_validation(self.frame_crc_validation);
+        stream_center
+    }
+}
+
+/// the servers to run around a stream center, a server is left out unless its config is given
+#[derive(Default)]
+pub struct MediaServerBuilder {
+    rtmp: Option<RtmpServerConfig>,
+    rtsp: Option<RtspServerConfig>,
+    rtsp_middlewares: Vec<Arc<dyn RtspMiddleware>>,
+    http: Option<HttpServerConfig>,
+    srt: Option<SrtServerConfig>,
+    stream_center: StreamCenterOptions,
+    ingest_limit: IngestLimitConfig,
+    egress_shaping: EgressShapingConfig,
+    data_dir: Option<PathBuf>,
+    restart_policy: RestartPolicy,
+}
+
+impl MediaServerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rtmp(mut self, config: RtmpServerConfig) -> Self {
+        self.rtmp = Some(config);
+        self
+    }
+
+    pub fn with_rtsp(mut self, config: RtspServerConfig) -> Self {
+        self.rtsp = Some(config);
+        self
+    }
+
+    /// runs around the requests of all rtsp sessions, after the ones added before it
+    pub fn with_rtsp_middleware(mut self, middleware: Arc<dyn RtspMiddleware>) -> Self {
+        self.rtsp_middlewares.push(middleware);
+        self
+    }
+
+    pub fn with_http(mut self, config: HttpServerConfig) -> Self {
+        self.http = Some(config);
+        self
+    }
+
+    pub fn with_srt(mut self, config: SrtServerConfig) -> Self {
+        self.srt = Some(config);
+        self
+    }
+
+    pub fn with_stream_center_options(mut self, options: StreamCenterOptions) -> Self {
+        self.stream_center = options;
+        self
+    }
+
+    /// shared by the servers publishers connect to
+    pub fn with_ingest_limit(mut self, config: IngestLimitConfig) -> Self {
+        self.ingest_limit = config;
+        self
+    }
+
+    /// the bitrate caps of the players of the rtmp, rtsp and http-flv servers
+    pub fn with_egress_shaping(mut self, config: EgressShapingConfig) -> Self {
+        self.egress_shaping = config;
+        self
+    }
+
+    /// where the stream center is snapshotted on shutdown and restored from on start,
+    /// nothing is kept across restarts if not set
+    pub fn with_data_dir(mut self, data_dir: PathBuf) -> Self {
+        self.data_dir = Some(data_dir);
+        self
+    }
+
+    /// how soon the servers and the stream center are run again once they crash
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart_policy = policy;
+        self
+    }
+
+    /// nothing runs until the media server is started
+    pub fn build(self) -> MediaServer {
+        MediaServer::new(PendingServers {
+            stream_center: self.stream_center.build(),
+            rtmp: self.rtmp,
+            rtsp: self.rtsp,
+            rtsp_middlewares: self.rtsp_middlewares,
+            http: self.http,
+            srt: self.srt,
+            ingest_limiter: IngestRateLimiter::new(self.ingest_limit),
+            egress_shaper: EgressShaper::new(self.egress_shaping),
+            data_dir: self.data_dir,
+            restart_policy: self.restart_policy,
+        })
+    }
+}
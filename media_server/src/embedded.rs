@@ -0,0 +1,123 @@
+use stream_center::{
+    events::{PublishResponse, StreamCenterEvent, SubscribeResponse},
+    gop::MediaFrame,
+    identity::StreamIdentity,
+    stream_center::StreamCenter,
+    takeover::PublisherKicked,
+};
+use tokio::sync::{
+    mpsc::{self, error::TryRecvError},
+    oneshot,
+};
+use uuid::Uuid;
+
+use crate::errors::{MediaServerError, MediaServerResult};
+
+/// a stream published by the host process, sequence headers go first as with any publisher.
+/// the stream stays published until unpublish is called
+#[derive(Debug)]
+pub struct EmbeddedPublisher {
+    stream_id: StreamIdentity,
+    publisher_id: Uuid,
+    media_sender: mpsc::Sender<MediaFrame>,
+    kicked_receiver: oneshot::Receiver<PublisherKicked>,
+    stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+}
+
+impl EmbeddedPublisher {
+    pub(crate) fn new(
+        stream_id: StreamIdentity,
+        response: PublishResponse,
+        stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    ) -> Self {
+        Self {
+            stream_id,
+            publisher_id: response.publisher_id,
+            media_sender: response.media_sender,
+            kicked_receiver: response.kicked_receiver,
+            stream_center_event_sender,
+        }
+    }
+
+    pub fn stream_id(&self) -> &StreamIdentity {
+        &self.stream_id
+    }
+
+    /// waits while the stream is busy fanning out the frames sent before
+    pub async fn send(&self, frame: MediaFrame) -> MediaServerResult<()> {
+        self.media_sender
+            .send(frame)
+            .await
+            .map_err(|_| MediaServerError::StreamClosed(self.stream_id.clone()))
+    }
+
+    /// Some if another publisher took the stream over or the idle watchdog reaped it
+    pub fn kicked(&mut self) -> Option<PublisherKicked> {
+        self.kicked_receiver.try_recv().ok()
+    }
+
+    /// a kicked publisher leaves the stream of the publisher taking it over alone
+    pub async fn unpublish(self) -> MediaServerResult<()> {
+        StreamCenter::unpublish_publisher(
+            &self.stream_center_event_sender,
+            &self.stream_id,
+            self.publisher_id,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// a stream played by the host process
+#[derive(Debug)]
+pub struct EmbeddedSubscriber {
+    stream_id: StreamIdentity,
+    subscribe_id: Uuid,
+    media_receiver: mpsc::Receiver<MediaFrame>,
+    stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+}
+
+impl EmbeddedSubscriber {
+    pub(crate) fn new(
+        stream_id: StreamIdentity,
+        response: SubscribeResponse,
+        stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    ) -> Self {
+        Self {
+            stream_id,
+            subscribe_id: response.subscribe_id,
+            media_receiver: response.media_receiver,
+            stream_center_event_sender,
+        }
+    }
+
+    pub fn stream_id(&self) -> &StreamIdentity {
+        &self.stream_id
+    }
+
+    /// None once the stream is unpublished
+    pub async fn recv(&mut self) -> Option<MediaFrame> {
+        self.media_receiver.recv().await
+    }
+
+    /// Ok(None) if no frame is ready yet
+    pub fn try_recv(&mut self) -> MediaServerResult<Option<MediaFrame>> {
+        match self.media_receiver.try_recv() {
+            Ok(frame) => Ok(Some(frame)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => {
+                Err(MediaServerError::StreamClosed(self.stream_id.clone()))
+            }
+        }
+    }
+
+    pub async fn unsubscribe(self) -> MediaServerResult<()> {
+        StreamCenter::unsubscribe(
+            &self.stream_center_event_sender,
+            self.subscribe_id,
+            &self.stream_id,
+        )
+        .await?;
+        Ok(())
+    }
+}
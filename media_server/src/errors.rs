@@ -0,0 +1,14 @@
+use stream_center::{errors::StreamCenterError, identity::StreamIdentity};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MediaServerError {
+    #[error("stream center error: {0}")]
+    StreamCenterError(#[from] StreamCenterError),
+    #[error("the media server is started already")]
+    AlreadyStarted,
+    #[error("the stream is closed: {0}")]
+    StreamClosed(StreamIdentity),
+}
+
+pub type MediaServerResult<T> = Result<T, MediaServerError>;
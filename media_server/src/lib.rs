@@ -0,0 +1,93 @@
+//! the stream center and the servers around it as a library, for embedding them in another process
+//! without the yam_server binary.
+//!
+//! ```
+//! use codec_common::{
+//!     FrameType, MediaFrameTimestamp,
+//!     video::{H264VideoConfig, VideoCodecCommon, VideoConfig, VideoFrameInfo, VideoFrameUnit},
+//! };
+//! use codec_h264::{nalu::NalUnit, nalu_header::NaluHeader};
+//! use media_server::builder::MediaServerBuilder;
+//! use stream_center::{
+//!     gop::MediaFrame, notification::StreamNotification, stream_source::MediaSelection,
+//! };
+//! use tokio_util::bytes::Bytes;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! // no server config given, so the streams only live in this process
+//! let mut server = MediaServerBuilder::new().build();
+//! let mut events = server.stream_events();
+//! server.start()?;
+//!
+//! let publisher = server.publish("live", "synthetic").await?;
+//! assert!(matches!(
+//!     events.recv().await?,
+//!     StreamNotification::Published { .. }
+//! ));
+//! let mut subscriber = server
+//!     .subscribe("live", "synthetic", MediaSelection::default())
+//!     .await?;
+//!
+//! publisher
+//!     .send(MediaFrame::VideoConfig {
+//!         timestamp_nano: 0,
+//!         config: Box::new(VideoConfig::H264(H264VideoConfig {
+//!             sps: None,
+//!             pps: None,
+//!             sps_ext: None,
+//!             avc_decoder_configuration_record: None,
+//!         })),
+//!     })
+//!     .await?;
+//! for index in 0..25 {
+//!     let (nalu_header, frame_type) = if index == 0 {
+//!         (0x65, FrameType::KeyFrame)
+//!     } else {
+//!         (0x41, FrameType::CodedFrames)
+//!     };
+//!     publisher
+//!         .send(MediaFrame::Video {
+//!             frame_info: VideoFrameInfo::new(
+//!                 VideoCodecCommon::AVC,
+//!                 frame_type,
+//!                 MediaFrameTimestamp::with_timestamp_ms(index * 40),
+//!             ),
+//!             payload: VideoFrameUnit::H264 {
+//!                 nal_units: vec![NalUnit {
+//!                     header: NaluHeader::try_from(nalu_header)?,
+//!                     body: Bytes::from_static(&[0; 16]),
+//!                 }],
+//!             },
+//!         })
+//!         .await?;
+//! }
+//!
+//! let frame = subscriber.recv().await.expect("the stream is still published");
+//! assert_eq!(frame.get_decode_timestamp_ms(), 0);
+//!
+//! subscriber.unsubscribe().await?;
+//! publisher.unpublish().await?;
+//! assert!(matches!(
+//!     events.recv().await?,
+//!     StreamNotification::Subscribed { .. }
+//! ));
+//! assert!(matches!(
+//!     events.recv().await?,
+//!     StreamNotification::Unsubscribed { .. }
+//! ));
+//! assert!(matches!(
+//!     events.recv().await?,
+//!     StreamNotification::Unpublished { .. }
+//! ));
+//! server.shutdown().await;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod builder;
+pub mod embedded;
+pub mod errors;
+pub mod server;
+#[cfg(test)]
+mod test;
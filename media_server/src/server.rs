@@ -0,0 +1,363 @@
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use http_server::{
+    config::HttpServerConfig,
+    events::{DEFAULT_EVENT_LOG_CAPACITY, EventLog},
+    server::HttpServer,
+};
+use rtmp_server::{config::RtmpServerConfig, relay::RelayRunner, server::RtmpServer};
+use rtsp_server::{config::RtspServerConfig, middleware::RtspMiddleware, server::RtspServer};
+use server_utils::{
+    egress_shaping::EgressShaper,
+    ingest_limit::IngestRateLimiter,
+    relay::RelayRegistry,
+    session_registry::SessionRegistry,
+    supervisor::{RestartPolicy, SupervisedTasks},
+};
+use srt_server::{config::SrtServerConfig, server::SrtServer};
+use stream_center::{
+    events::StreamCenterEvent,
+    identity::StreamIdentity,
+    notification::StreamNotification,
+    snapshot::StreamCenterSnapshot,
+    stream_center::StreamCenter,
+    stream_source::{MediaSelection, PlayProtocol, PublishProtocol},
+};
+use tokio::{
+    sync::{Mutex, broadcast, mpsc},
+    task::JoinHandle,
+};
+
+use crate::{
+    embedded::{EmbeddedPublisher, EmbeddedSubscriber},
+    errors::{MediaServerError, MediaServerResult},
+};
+
+/// how long shutdown waits for the stream center to be snapshotted
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the names the servers and the stream center are supervised by
+pub const RTMP_TASK: &str = "rtmp";
+pub const HTTP_TASK: &str = "http";
+pub const RTSP_TASK: &str = "rtsp";
+pub const SRT_TASK: &str = "srt";
+pub const STREAM_CENTER_TASK: &str = "stream_center";
+
+/// built by the builder, taken by start
+pub(crate) struct PendingServers {
+    pub(crate) stream_center: StreamCenter,
+    pub(crate) rtmp: Option<RtmpServerConfig>,
+    pub(crate) rtsp: Option<RtspServerConfig>,
+    pub(crate) rtsp_middlewares: Vec<Arc<dyn RtspMiddleware>>,
+    pub(crate) http: Option<HttpServerConfig>,
+    pub(crate) srt: Option<SrtServerConfig>,
+    pub(crate) ingest_limiter: IngestRateLimiter,
+    pub(crate) egress_shaper: EgressShaper,
+    pub(crate) data_dir: Option<PathBuf>,
+    pub(crate) restart_policy: RestartPolicy,
+}
+
+/// a stream center and the servers around it, run on the tasks of the current tokio runtime.
+/// streams can be published and played in process even before it is started
+pub struct MediaServer {
+    stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    /// never read, kept to hand out new receivers
+    notifications: broadcast::Receiver<StreamNotification>,
+    session_registry: SessionRegistry,
+    /// the relays to and from other servers, kept in the data dir
+    relays: RelayRegistry,
+    /// where the stream center snapshot is kept across restarts
+    data_dir: Option<PathBuf>,
+    pending: Option<PendingServers>,
+    /// the servers and the stream center, run again once they crash
+    supervised_tasks: SupervisedTasks,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MediaServer {
+    pub(crate) fn new(pending: PendingServers) -> Self {
+        Self {
+            stream_center_event_sender: pending.stream_center.get_event_sender(),
+            notifications: pending.stream_center.subscribe_notifications(),
+            session_registry: SessionRegistry::default(),
+            // a relay to the rtmp server here would loop back
+            relays: RelayRegistry::open(
+                pending.data_dir.clone(),
+                pending
+                    .rtmp
+                    .iter()
+                    .map(|config| SocketAddr::new(config.address, config.port))
+                    .collect(),
+            ),
+            data_dir: pending.data_dir.clone(),
+            pending: Some(pending),
+            supervised_tasks: SupervisedTasks::default(),
+            tasks: Vec::new(),
+        }
+    }
+
+    /// spawns the stream center and the servers, each under a supervisor running it again if it panics or fails.
+    /// a server is run again with fresh listeners, the streams in the stream center and the sessions
+    /// of the other servers go on meanwhile.
+    /// the snapshot in the data dir, if any, is restored first and its sources started again
+    pub fn start(&mut self) -> MediaServerResult<()> {
+        let Some(pending) = self.pending.take() else {
+            return Err(MediaServerError::AlreadyStarted);
+        };
+        let PendingServers {
+            mut stream_center,
+            rtmp,
+            rtsp,
+            rtsp_middlewares,
+            http,
+            srt,
+            ingest_limiter,
+            egress_shaper,
+            data_dir,
+            restart_policy,
+        } = pending;
+
+        let mut restored_sources = match data_dir.as_deref().map(StreamCenterSnapshot::take) {
+            None | Some(Ok(None)) => vec![],
+            Some(Ok(Some(snapshot))) => stream_center.restore(snapshot),
+            Some(Err(err)) => {
+                tracing::error!("load stream center snapshot failed, start afresh: {}", err);
+                vec![]
+            }
+        };
+
+        if let Some(config) = rtmp {
+            tracing::info!("rtmp server is starting with config: {:?}", config);
+            let rtmp_server = Arc::new(Mutex::new(RtmpServer::new(
+                config,
+                ingest_limiter.clone(),
+                egress_shaper.clone(),
+                self.session_registry.clone(),
+                self.stream_center_event_sender.clone(),
+            )));
+            self.tasks
+                .push(tokio::spawn(self.supervised_tasks.clone().supervise(
+                    RTMP_TASK,
+                    restart_policy,
+                    move || {
+                        let rtmp_server = rtmp_server.clone();
+                        async move { rtmp_server.lock_owned().await.run().await }
+                    },
+                )));
+        }
+
+        if let Some(config) = http {
+            tracing::info!("http server is starting with config: {:?}", config);
+            let event_log = EventLog::new(DEFAULT_EVENT_LOG_CAPACITY);
+            self.tasks.push(tokio::spawn(
+                event_log.clone().run(self.notifications.resubscribe()),
+            ));
+            let http_server = HttpServer::new(
+                config,
+                egress_shaper.clone(),
+                self.session_registry.clone(),
+                self.stream_center_event_sender.clone(),
+            )
+            .with_relays(self.relays.clone())
+            .with_event_log(event_log)
+            .with_supervised_tasks(self.supervised_tasks.clone());
+            if !restored_sources.is_empty() {
+                self.tasks.push(tokio::spawn(
+                    http_server.restore_sources(std::mem::take(&mut restored_sources)),
+                ));
+            }
+            let http_server = Arc::new(Mutex::new(http_server));
+            self.tasks
+                .push(tokio::spawn(self.supervised_tasks.clone().supervise(
+                    HTTP_TASK,
+                    restart_policy,
+                    move || {
+                        let http_server = http_server.clone();
+                        async move { http_server.lock_owned().await.run().await }
+                    },
+                )));
+        }
+
+        if let Some(config) = rtsp {
+            tracing::info!("rtsp server is starting with config: {:?}", config);
+            let rtsp_server = rtsp_middlewares.into_iter().fold(
+                RtspServer::new(
+                    self.stream_center_event_sender.clone(),
+                    config,
+                    ingest_limiter.clone(),
+                    egress_shaper,
+                    self.session_registry.clone(),
+                ),
+                |rtsp_server, middleware| rtsp_server.with_middleware(middleware),
+            );
+            let rtsp_server = Arc::new(rtsp_server);
+            self.tasks
+                .push(tokio::spawn(self.supervised_tasks.clone().supervise(
+                    RTSP_TASK,
+                    restart_policy,
+                    move || {
+                        let rtsp_server = rtsp_server.clone();
+                        async move { rtsp_server.run().await }
+                    },
+                )));
+        }
+
+        if let Some(config) = srt {
+            // the config is not logged as a whole, it carries the passphrase
+            tracing::info!(
+                "srt server is starting on {}:{}, latency: {:?}",
+                config.address,
+                config.port,
+                config.latency
+            );
+            let srt_server = Arc::new(SrtServer::new(
+                self.stream_center_event_sender.clone(),
+                config,
+                ingest_limiter,
+            ));
+            self.tasks
+                .push(tokio::spawn(self.supervised_tasks.clone().supervise(
+                    SRT_TASK,
+                    restart_policy,
+                    move || {
+                        let srt_server = srt_server.clone();
+                        async move { srt_server.run().await }
+                    },
+                )));
+        }
+
+        if !restored_sources.is_empty() {
+            tracing::warn!(
+                "http server is not enabled, {} vod sources of the snapshot are not restored",
+                restored_sources.len()
+            );
+        }
+
+        let relay_runner = RelayRunner::new(
+            self.relays.clone(),
+            self.stream_center_event_sender.clone(),
+            self.notifications.resubscribe(),
+        );
+        self.tasks.push(tokio::spawn(relay_runner.run()));
+
+        // run again on the same state, the channels the servers hold to it stay open
+        let stream_center = Arc::new(Mutex::new(stream_center));
+        self.tasks
+            .push(tokio::spawn(self.supervised_tasks.clone().supervise(
+                STREAM_CENTER_TASK,
+                restart_policy,
+                move || {
+                    let stream_center = stream_center.clone();
+                    async move { stream_center.lock_owned().await.run().await }
+                },
+            )));
+        tracing::info!("stream center is started, all servers are started");
+        Ok(())
+    }
+
+    /// stops the servers and the stream center, sessions already accepted
+    /// go on until their connections fail to reach the stream center.
+    /// the stream center is snapshotted to the data dir first, if there is one
+    pub async fn shutdown(&mut self) {
+        if let Some(data_dir) = &self.data_dir
+            && self.pending.is_none()
+            && !self.tasks.is_empty()
+        {
+            let snapshot = tokio::time::timeout(
+                SNAPSHOT_TIMEOUT,
+                StreamCenter::snapshot(&self.stream_center_event_sender),
+            )
+            .await;
+            match snapshot {
+                Ok(Ok(snapshot)) => match snapshot.save(data_dir) {
+                    Ok(()) => tracing::info!(
+                        "stream center snapshot with {} sources saved to {}",
+                        snapshot.sources.len(),
+                        data_dir.display()
+                    ),
+                    Err(err) => tracing::error!("save stream center snapshot failed: {}", err),
+                },
+                Ok(Err(err)) => tracing::error!("snapshot stream center failed: {}", err),
+                Err(_) => tracing::error!("snapshot stream center timed out"),
+            }
+        }
+        for task in &self.tasks {
+            task.abort();
+        }
+        for task in self.tasks.drain(..) {
+            let _ = task.await;
+        }
+        // a server never started can not be started after shutdown either
+        self.pending = None;
+        tracing::info!("media server is shut down");
+    }
+
+    /// notifications of the streams published or gone from now on
+    pub fn stream_events(&self) -> broadcast::Receiver<StreamNotification> {
+        self.notifications.resubscribe()
+    }
+
+    /// for talking to the stream center directly, see the associated functions of StreamCenter
+    pub fn stream_center_event_sender(&self) -> mpsc::UnboundedSender<StreamCenterEvent> {
+        self.stream_center_event_sender.clone()
+    }
+
+    /// the sessions of the rtmp, rtsp and http-flv servers, the ones listed by the admin api
+    pub fn session_registry(&self) -> &SessionRegistry {
+        &self.session_registry
+    }
+
+    /// the servers and the stream center, how often they crashed and were run again
+    pub fn supervised_tasks(&self) -> &SupervisedTasks {
+        &self.supervised_tasks
+    }
+
+    /// the relays changed by the admin api, they are applied once the server is started
+    pub fn relays(&self) -> &RelayRegistry {
+        &self.relays
+    }
+
+    /// publishes a stream whose frames are sent by the host process
+    pub async fn publish(
+        &self,
+        app: &str,
+        stream_name: &str,
+    ) -> MediaServerResult<EmbeddedPublisher> {
+        let stream_id = StreamIdentity::new(app, stream_name)?;
+        let response = StreamCenter::publish_kickable(
+            &self.stream_center_event_sender,
+            PublishProtocol::Embedded,
+            &stream_id,
+            &HashMap::new(),
+        )
+        .await?;
+        Ok(EmbeddedPublisher::new(
+            stream_id,
+            response,
+            self.stream_center_event_sender.clone(),
+        ))
+    }
+
+    /// plays a stream in the host process, whichever server it is published to
+    pub async fn subscribe(
+        &self,
+        app: &str,
+        stream_name: &str,
+        media_selection: MediaSelection,
+    ) -> MediaServerResult<EmbeddedSubscriber> {
+        let stream_id = StreamIdentity::new(app, stream_name)?;
+        let response = StreamCenter::subscribe(
+            &self.stream_center_event_sender,
+            PlayProtocol::Embedded,
+            &stream_id,
+            &HashMap::new(),
+            media_selection,
+        )
+        .await?;
+        Ok(EmbeddedSubscriber::new(
+            stream_id,
+            response,
+            self.stream_center_event_sender.clone(),
+        ))
+    }
+}
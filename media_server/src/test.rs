@@ -0,0 +1,265 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        net::{Ipv4Addr, TcpListener},
+        time::Duration,
+    };
+
+    use amf_formats::limits::{
+        DEFAULT_MAX_DECODED_BYTES, DEFAULT_MAX_DEPTH, DEFAULT_MAX_ELEMENTS,
+        DEFAULT_MAX_STRING_LENGTH,
+    };
+    use rtmp_server::config::{
+        DEFAULT_FORWARD_AUDIO_FOUR_CC, DEFAULT_FORWARD_VIDEO_FOUR_CC, DEFAULT_PEER_BANDWIDTH,
+        DEFAULT_WINDOW_ACK_SIZE, RtmpServerConfig,
+    };
+    use server_utils::{
+        relay::{RelayAction, RelayRule},
+        supervisor::RestartPolicy,
+    };
+    use stream_center::{
+        notification::StreamNotification,
+        rtmp_control::PeerBandwidthLimitType,
+        snapshot::StreamCenterSnapshot,
+        stream_center::StreamCenter,
+        stream_source::{MediaSelection, PublishProtocol},
+        takeover::{KickReason, TakeoverPolicy},
+    };
+
+    use crate::{
+        builder::{MediaServerBuilder, StreamCenterOptions},
+        errors::MediaServerError,
+        server::{RTMP_TASK, STREAM_CENTER_TASK},
+    };
+
+    fn rtmp_config(port: u16) -> RtmpServerConfig {
+        RtmpServerConfig {
+            address: Ipv4Addr::LOCALHOST.into(),
+            port,
+            chunk_size: 4096,
+            window_ack_size: DEFAULT_WINDOW_ACK_SIZE,
+            peer_bandwidth: DEFAULT_PEER_BANDWIDTH,
+            peer_bandwidth_limit_type: PeerBandwidthLimitType::Dynamic,
+            apps: HashMap::new(),
+            write_timeout_ms: 10000,
+            read_timeout_ms: 10000,
+            amf_max_depth: DEFAULT_MAX_DEPTH,
+            amf_max_decoded_bytes: DEFAULT_MAX_DECODED_BYTES,
+            amf_max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            amf_max_elements: DEFAULT_MAX_ELEMENTS,
+            rtmps: None,
+            forward_video_four_cc: DEFAULT_FORWARD_VIDEO_FOUR_CC
+                .iter()
+                .map(|v| v.to_string())
+                .collect(),
+            forward_audio_four_cc: DEFAULT_FORWARD_AUDIO_FOUR_CC
+                .iter()
+                .map(|v| v.to_string())
+                .collect(),
+            trusted_proxies: Default::default(),
+            send_on_bw_done: true,
+        }
+    }
+
+    /// tries to connect for up to a second
+    async fn accepts_connections(port: u16) -> bool {
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+                .await
+                .is_ok()
+            {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        false
+    }
+
+    /// polls `check` for up to 5 seconds
+    async fn eventually(mut check: impl FnMut() -> bool) -> bool {
+        for _ in 0..500 {
+            if check() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn a_media_server_starts_once() {
+        let mut server = MediaServerBuilder::new().build();
+        server.start().unwrap();
+        assert!(matches!(
+            server.start(),
+            Err(MediaServerError::AlreadyStarted)
+        ));
+        server.shutdown().await;
+        assert!(matches!(
+            server.start(),
+            Err(MediaServerError::AlreadyStarted)
+        ));
+    }
+
+    #[tokio::test]
+    async fn an_embedded_publisher_is_kicked_by_the_next_one() {
+        let mut server = MediaServerBuilder::new()
+            .with_stream_center_options(StreamCenterOptions {
+                default_takeover_policy: Some(TakeoverPolicy::KickOld),
+                ..Default::default()
+            })
+            .build();
+        let mut events = server.stream_events();
+        server.start().unwrap();
+
+        let mut first = server.publish("live", "test").await.unwrap();
+        assert!(first.kicked().is_none());
+        let second = server.publish("live", "test").await.unwrap();
+
+        let mut kicked = None;
+        for _ in 0..10 {
+            kicked = first.kicked();
+            if kicked.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            kicked.unwrap().reason,
+            KickReason::Takeover(TakeoverPolicy::KickOld)
+        );
+
+        let mut notifications = Vec::new();
+        while let Ok(notification) = events.try_recv() {
+            notifications.push(notification);
+        }
+        assert!(matches!(
+            notifications.as_slice(),
+            [
+                StreamNotification::Published {
+                    protocol: PublishProtocol::Embedded,
+                    ..
+                },
+                StreamNotification::Kicked {
+                    reason: KickReason::Takeover(TakeoverPolicy::KickOld),
+                    ..
+                },
+                StreamNotification::Published { .. },
+            ]
+        ));
+
+        // the stream belongs to the second publisher now
+        first.unpublish().await.unwrap();
+        second.unpublish().await.unwrap();
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn takeover_policies_survive_a_restart_through_the_data_dir() {
+        let data_dir =
+            std::env::temp_dir().join(format!("media-server-restart-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        let mut server = MediaServerBuilder::new()
+            .with_stream_center_options(StreamCenterOptions {
+                default_takeover_policy: Some(TakeoverPolicy::KickOld),
+                ..Default::default()
+            })
+            .with_data_dir(data_dir.clone())
+            .build();
+        server.start().unwrap();
+        server.shutdown().await;
+        assert!(StreamCenterSnapshot::path(&data_dir).exists());
+
+        let mut server = MediaServerBuilder::new()
+            .with_data_dir(data_dir.clone())
+            .build();
+        server.start().unwrap();
+        // restored once, the next start begins afresh unless it is shut down gracefully again
+        assert!(!StreamCenterSnapshot::path(&data_dir).exists());
+        let snapshot = StreamCenter::snapshot(&server.stream_center_event_sender())
+            .await
+            .unwrap();
+        assert_eq!(snapshot.default_takeover_policy, TakeoverPolicy::KickOld);
+        server.shutdown().await;
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+
+    #[tokio::test]
+    async fn relays_survive_a_restart_through_the_data_dir() {
+        let data_dir =
+            std::env::temp_dir().join(format!("media-server-relays-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        let mut server = MediaServerBuilder::new()
+            .with_data_dir(data_dir.clone())
+            .build();
+        server.start().unwrap();
+        let added = server
+            .relays()
+            .add(RelayRule::Push {
+                pattern: "live/*".parse().unwrap(),
+                target: "rtmp://192.0.2.1/live".to_owned(),
+            })
+            .await
+            .unwrap();
+        server.shutdown().await;
+
+        let mut server = MediaServerBuilder::new()
+            .with_data_dir(data_dir.clone())
+            .build();
+        server.start().unwrap();
+        assert_eq!(server.relays().relays(), vec![added.relay.clone()]);
+        assert_eq!(server.relays().audit(), vec![added.clone()]);
+        let removed = server.relays().remove(added.relay.id).unwrap();
+        assert_eq!(removed.action, RelayAction::Removed);
+        server.shutdown().await;
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+
+    #[tokio::test]
+    async fn a_crashed_server_comes_back_and_the_streams_stay() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut server = MediaServerBuilder::new()
+            .with_rtmp(rtmp_config(port))
+            .with_restart_policy(RestartPolicy {
+                initial_backoff: Duration::from_millis(50),
+                ..Default::default()
+            })
+            .build();
+        server.start().unwrap();
+        let tasks = server.supervised_tasks().clone();
+        let publisher = server.publish("live", "test").await.unwrap();
+        assert!(eventually(|| tasks.get(RTMP_TASK).is_some_and(|health| health.running)).await);
+        assert!(accepts_connections(port).await);
+
+        assert!(tasks.inject_panic(RTMP_TASK));
+        assert!(
+            eventually(|| tasks
+                .get(RTMP_TASK)
+                .is_some_and(|health| health.restarts == 1 && health.running))
+            .await
+        );
+        let health = tasks.get(RTMP_TASK).unwrap();
+        assert!(health.last_crash.is_some());
+        assert_eq!(
+            health.last_crash_reason.as_deref(),
+            Some("panicked: crash injected into rtmp")
+        );
+
+        // a fresh listener on the same port
+        assert!(accepts_connections(port).await);
+
+        // the stream center went on meanwhile
+        assert_eq!(tasks.get(STREAM_CENTER_TASK).unwrap().restarts, 0);
+        server
+            .subscribe("live", "test", MediaSelection::default())
+            .await
+            .unwrap();
+        publisher.unpublish().await.unwrap();
+        server.shutdown().await;
+    }
+}
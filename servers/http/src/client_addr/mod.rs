@@ -0,0 +1,76 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+use rocket::{
+    Request,
+    http::HeaderMap,
+    request::{FromRequest, Outcome},
+};
+use unified_io::proxy_protocol::TrustedProxies;
+
+use crate::server::HttpServerContext;
+
+#[cfg(test)]
+mod test;
+
+pub const REAL_IP_HEADER: &str = "X-Real-IP";
+pub const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+/// the address of a request not coming from a socket, such as one of a local client
+pub const UNKNOWN_CLIENT_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+
+/// the address of the client of a request, the one a trusted proxy tells in the headers,
+/// the headers of any other peer are ignored so they cannot be spoofed.
+/// a request with no peer, such as one of a local client, is of UNKNOWN_CLIENT_ADDR
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddr(pub SocketAddr);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientAddr {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // the headers of a peer that is not known can not be trusted
+        let Some(remote) = request.remote() else {
+            return Outcome::Success(Self(UNKNOWN_CLIENT_ADDR));
+        };
+        let resolved = match request.rocket().state::<HttpServerContext>() {
+            Some(ctx) => {
+                resolve_client_addr(remote, request.headers(), &ctx.config.trusted_proxies)
+            }
+            None => remote,
+        };
+        Outcome::Success(Self(resolved))
+    }
+}
+
+/// X-Real-IP if the proxy sets it, the nearest untrusted hop of X-Forwarded-For otherwise.
+/// the port of the client is not told, it is 0 then
+pub fn resolve_client_addr(
+    remote: SocketAddr,
+    headers: &HeaderMap<'_>,
+    trusted_proxies: &TrustedProxies,
+) -> SocketAddr {
+    if !trusted_proxies.contains(remote.ip()) {
+        return remote;
+    }
+    if let Some(ip) = headers
+        .get_one(REAL_IP_HEADER)
+        .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+    {
+        return SocketAddr::new(ip, 0);
+    }
+    // each proxy appends the peer it got the request from, the ones on the right are the closest
+    let hops: Vec<IpAddr> = headers
+        .get(FORWARDED_FOR_HEADER)
+        .flat_map(|value| value.split(','))
+        .map(|hop| hop.trim().parse::<IpAddr>())
+        .collect::<Result<_, _>>()
+        .unwrap_or_default();
+    hops.iter()
+        .rev()
+        .find(|ip| !trusted_proxies.contains(**ip))
+        .or(hops.first())
+        .map_or(remote, |ip| SocketAddr::new(*ip, 0))
+}
@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use rocket::http::{Header, HeaderMap};
+    use unified_io::proxy_protocol::TrustedProxies;
+
+    use crate::client_addr::{FORWARDED_FOR_HEADER, REAL_IP_HEADER, resolve_client_addr};
+
+    fn headers(items: &[(&'static str, &'static str)]) -> HeaderMap<'static> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in items {
+            headers.add(Header::new(*name, *value));
+        }
+        headers
+    }
+
+    #[test]
+    fn headers_of_trusted_proxies_tell_the_client() {
+        let trusted: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+        let proxy: SocketAddr = "10.0.0.2:50000".parse().unwrap();
+        let client: SocketAddr = "203.0.113.7:0".parse().unwrap();
+
+        let real_ip = headers(&[
+            (REAL_IP_HEADER, "203.0.113.7"),
+            (FORWARDED_FOR_HEADER, "1.1.1.1"),
+        ]);
+        assert_eq!(resolve_client_addr(proxy, &real_ip, &trusted), client);
+
+        // the spoofed hop on the left is skipped, the nearest one the proxies do not know is the client
+        let forwarded = headers(&[
+            (FORWARDED_FOR_HEADER, "198.51.100.1, 203.0.113.7"),
+            (FORWARDED_FOR_HEADER, "10.0.0.9"),
+        ]);
+        assert_eq!(resolve_client_addr(proxy, &forwarded, &trusted), client);
+
+        // all hops are proxies
+        let internal = headers(&[(FORWARDED_FOR_HEADER, "10.0.0.8, 10.0.0.9")]);
+        assert_eq!(
+            resolve_client_addr(proxy, &internal, &trusted),
+            "10.0.0.8:0".parse().unwrap()
+        );
+
+        for unusable in [
+            headers(&[]),
+            headers(&[(REAL_IP_HEADER, "not an ip")]),
+            headers(&[(FORWARDED_FOR_HEADER, "203.0.113.7, unknown")]),
+        ] {
+            assert_eq!(resolve_client_addr(proxy, &unusable, &trusted), proxy);
+        }
+    }
+
+    #[test]
+    fn headers_of_other_peers_are_ignored() {
+        let direct: SocketAddr = "198.51.100.1:40000".parse().unwrap();
+        let spoofed = headers(&[
+            (REAL_IP_HEADER, "203.0.113.7"),
+            (FORWARDED_FOR_HEADER, "203.0.113.7"),
+        ]);
+        let trusted: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+        assert_eq!(resolve_client_addr(direct, &spoofed, &trusted), direct);
+        assert_eq!(
+            resolve_client_addr(direct, &spoofed, &TrustedProxies::default()),
+            direct
+        );
+    }
+}
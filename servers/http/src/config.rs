@@ -1,6 +1,7 @@
-use std::net::IpAddr;
+use std::{net::IpAddr, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
+use unified_io::proxy_protocol::TrustedProxies;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(crate = "rocket::serde")]
@@ -11,4 +12,12 @@ pub struct HttpServerConfig {
     pub port: u16,
     // number of threads to use for executing futures
     pub workers: u64,
+    // directory of the files that can be played by the vod api, the api is disabled if not set
+    pub vod_dir: Option<PathBuf>,
+    // directory the packet captures of rtp sessions are written to, the capture api is disabled if not set
+    #[serde(default)]
+    pub capture_dir: Option<PathBuf>,
+    // proxies whose X-Real-IP and X-Forwarded-For headers tell the address of the client
+    #[serde(default)]
+    pub trusted_proxies: TrustedProxies,
 }
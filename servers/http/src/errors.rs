@@ -1,8 +1,10 @@
 use rocket::Responder;
+use rtp_session::errors::RtpSessionError;
+use server_utils::session_registry::capture::SessionCaptureError;
 use stream_center::errors::StreamCenterError;
 use thiserror::Error;
 
-use crate::sessions::httpflv::errors::HttpFlvSessionError;
+use crate::sessions::{httpflv::errors::HttpFlvSessionError, vod::errors::VodError};
 
 #[derive(Error, Debug, Responder)]
 pub enum HttpServerError {
@@ -23,10 +25,10 @@ impl From<HttpFlvSessionError> for HttpServerError {
     fn from(value: HttpFlvSessionError) -> Self {
         match value {
             HttpFlvSessionError::StreamCenterError(err) => match err {
-                StreamCenterError::StreamNotFound(id) => Self::NotFound(format!(
-                    "stream not found, app: {}, stream: {}",
-                    id.app, id.stream_name
-                )),
+                StreamCenterError::StreamNotFound(id) => {
+                    Self::NotFound(format!("stream not found: {}", id))
+                }
+                StreamCenterError::InvalidStreamIdentity(_) => Self::BadRequest(err.to_string()),
                 StreamCenterError::InvalidStreamType(t) => {
                     Self::BadRequest(format!("bad stream type: {}", t))
                 }
@@ -36,3 +38,40 @@ impl From<HttpFlvSessionError> for HttpServerError {
         }
     }
 }
+
+impl From<VodError> for HttpServerError {
+    fn from(value: VodError) -> Self {
+        match value {
+            VodError::InvalidPath(_)
+            | VodError::InvalidSpeed(_)
+            | VodError::InvalidScale(_)
+            | VodError::EmptyFile => Self::BadRequest(format!("{}", value)),
+            VodError::Io(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Self::NotFound(format!("vod file not found: {}", err))
+            }
+            VodError::FlvError(err) => Self::BadRequest(format!("bad flv file: {}", err)),
+            VodError::StreamCenterError(StreamCenterError::DuplicateStream(id)) => {
+                Self::BadRequest(format!("stream is already publishing: {}", id))
+            }
+            _ => Self::InternalError("internal error".to_string()),
+        }
+    }
+}
+
+impl From<SessionCaptureError> for HttpServerError {
+    fn from(value: SessionCaptureError) -> Self {
+        match value {
+            SessionCaptureError::SessionNotFound(_)
+            | SessionCaptureError::NoSessionOfStream(_)
+            | SessionCaptureError::NotCaptured(_) => Self::NotFound(value.to_string()),
+            SessionCaptureError::NotRtp(_) => Self::BadRequest(value.to_string()),
+            SessionCaptureError::Capture(RtpSessionError::CaptureRunning(_)) => {
+                Self::BadRequest(value.to_string())
+            }
+            _ => {
+                tracing::error!("packet capture failed: {}", value);
+                Self::InternalError("internal error".to_string())
+            }
+        }
+    }
+}
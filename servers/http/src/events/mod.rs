@@ -0,0 +1,374 @@
+//! the notifications of the stream center numbered and kept for the event stream of the dashboard,
+//! a client reconnecting with the id of the last event it got is given the ones it missed
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use rocket::serde::Serialize;
+use stream_center::{
+    drain::DrainOutcome,
+    events::StreamConfigChange,
+    notification::StreamNotification,
+    stream_source::{PlayProtocol, PublishProtocol},
+    takeover::KickReason,
+};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+#[cfg(test)]
+mod test;
+
+/// events kept for the clients reconnecting
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 1024;
+/// events a client may fall behind by, it skips ahead to the latest then
+const EVENT_FAN_OUT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde", tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    Published {
+        stream: String,
+        protocol: PublishProtocol,
+    },
+    Restored {
+        stream: String,
+        protocol: PublishProtocol,
+    },
+    Unpublished {
+        stream: String,
+    },
+    /// a publisher taken over by another one, idle for too long or drained
+    Kicked {
+        stream: String,
+        reason: KickReason,
+    },
+    ConfigChanged {
+        stream: String,
+        #[serde(flatten)]
+        change: StreamConfigChange,
+    },
+    PublisherStalled {
+        stream: String,
+        idle_ms: u64,
+    },
+    PublisherResumed {
+        stream: String,
+        stalled_for_ms: u64,
+    },
+    /// negative if the timestamps went backwards
+    TimestampDiscontinuity {
+        stream: String,
+        jump_ms: i64,
+    },
+    Drained {
+        stream: String,
+        outcome: DrainOutcome,
+    },
+    AliasChanged {
+        stream: String,
+        canonical: Option<String>,
+        previous: Option<String>,
+    },
+    SubscribeMissed {
+        stream: String,
+        protocol: PlayProtocol,
+    },
+    Subscribed {
+        stream: String,
+        subscriber_id: Uuid,
+        protocol: PlayProtocol,
+        /// the name the subscriber asked for, if it is an alias of the stream
+        alias: Option<String>,
+    },
+    Unsubscribed {
+        stream: String,
+        subscriber_id: Uuid,
+    },
+    /// this many events are missed, by the log or by the client
+    Lagged {
+        missed: u64,
+    },
+}
+
+impl StreamEvent {
+    /// the name of the event in the stream, the type in its json
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Published { .. } => "published",
+            Self::Restored { .. } => "restored",
+            Self::Unpublished { .. } => "unpublished",
+            Self::Kicked { .. } => "kicked",
+            Self::ConfigChanged { .. } => "config_changed",
+            Self::PublisherStalled { .. } => "publisher_stalled",
+            Self::PublisherResumed { .. } => "publisher_resumed",
+            Self::TimestampDiscontinuity { .. } => "timestamp_discontinuity",
+            Self::Drained { .. } => "drained",
+            Self::AliasChanged { .. } => "alias_changed",
+            Self::SubscribeMissed { .. } => "subscribe_missed",
+            Self::Subscribed { .. } => "subscribed",
+            Self::Unsubscribed { .. } => "unsubscribed",
+            Self::Lagged { .. } => "lagged",
+        }
+    }
+
+    /// `app/stream`, None for the lagged marker
+    pub fn stream(&self) -> Option<&str> {
+        match self {
+            Self::Published { stream, .. }
+            | Self::Restored { stream, .. }
+            | Self::Unpublished { stream }
+            | Self::Kicked { stream, .. }
+            | Self::ConfigChanged { stream, .. }
+            | Self::PublisherStalled { stream, .. }
+            | Self::PublisherResumed { stream, .. }
+            | Self::TimestampDiscontinuity { stream, .. }
+            | Self::Drained { stream, .. }
+            | Self::AliasChanged { stream, .. }
+            | Self::SubscribeMissed { stream, .. }
+            | Self::Subscribed { stream, .. }
+            | Self::Unsubscribed { stream, .. } => Some(stream),
+            Self::Lagged { .. } => None,
+        }
+    }
+}
+
+impl From<StreamNotification> for StreamEvent {
+    fn from(notification: StreamNotification) -> Self {
+        match notification {
+            StreamNotification::Published {
+                stream_id,
+                protocol,
+            } => Self::Published {
+                stream: stream_id.to_string(),
+                protocol,
+            },
+            StreamNotification::Restored {
+                stream_id,
+                protocol,
+            } => Self::Restored {
+                stream: stream_id.to_string(),
+                protocol,
+            },
+            StreamNotification::Unpublished { stream_id } => Self::Unpublished {
+                stream: stream_id.to_string(),
+            },
+            StreamNotification::Kicked { stream_id, reason } => Self::Kicked {
+                stream: stream_id.to_string(),
+                reason,
+            },
+            StreamNotification::ConfigChanged { stream_id, change } => Self::ConfigChanged {
+                stream: stream_id.to_string(),
+                change,
+            },
+            StreamNotification::PublisherStalled { stream_id, idle } => Self::PublisherStalled {
+                stream: stream_id.to_string(),
+                idle_ms: idle.as_millis() as u64,
+            },
+            StreamNotification::PublisherResumed {
+                stream_id,
+                stalled_for,
+            } => Self::PublisherResumed {
+                stream: stream_id.to_string(),
+                stalled_for_ms: stalled_for.as_millis() as u64,
+            },
+            StreamNotification::TimestampDiscontinuity {
+                stream_id,
+                jump_nano,
+            } => Self::TimestampDiscontinuity {
+                stream: stream_id.to_string(),
+                jump_ms: jump_nano / 1_000_000,
+            },
+            StreamNotification::Drained { stream_id, outcome } => Self::Drained {
+                stream: stream_id.to_string(),
+                outcome,
+            },
+            StreamNotification::AliasChanged {
+                alias,
+                canonical,
+                previous,
+            } => Self::AliasChanged {
+                stream: alias.to_string(),
+                canonical: canonical.map(|canonical| canonical.to_string()),
+                previous: previous.map(|previous| previous.to_string()),
+            },
+            StreamNotification::SubscribeMissed {
+                stream_id,
+                protocol,
+            } => Self::SubscribeMissed {
+                stream: stream_id.to_string(),
+                protocol,
+            },
+            StreamNotification::Subscribed {
+                stream_id,
+                subscriber_id,
+                protocol,
+            } => Self::Subscribed {
+                stream: stream_id.to_string(),
+                subscriber_id,
+                protocol,
+                alias: None,
+            },
+            StreamNotification::SubscribedViaAlias {
+                stream_id,
+                alias,
+                subscriber_id,
+                protocol,
+            } => Self::Subscribed {
+                stream: stream_id.to_string(),
+                subscriber_id,
+                protocol,
+                alias: Some(alias.to_string()),
+            },
+            StreamNotification::Unsubscribed {
+                stream_id,
+                subscriber_id,
+            } => Self::Unsubscribed {
+                stream: stream_id.to_string(),
+                subscriber_id,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LoggedEvent {
+    /// counts from 1 in the process, the id of the event in the stream
+    pub id: u64,
+    #[serde(flatten)]
+    pub event: StreamEvent,
+}
+
+/// which events a client of the stream wants
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    /// of the `app/stream` the events are about
+    pub stream_prefix: Option<String>,
+    /// the kinds of the events, any if None
+    pub kinds: Option<HashSet<String>>,
+}
+
+impl EventFilter {
+    /// the lagged marker passes any filter
+    pub fn matches(&self, event: &StreamEvent) -> bool {
+        let Some(stream) = event.stream() else {
+            return true;
+        };
+        self.stream_prefix
+            .as_ref()
+            .is_none_or(|prefix| stream.starts_with(prefix.as_str()))
+            && self
+                .kinds
+                .as_ref()
+                .is_none_or(|kinds| kinds.contains(event.kind()))
+    }
+}
+
+/// what a client of the stream starts from
+#[derive(Debug)]
+pub struct EventSubscription {
+    /// the events after the last one the client got, the oldest first
+    pub replay: Vec<Arc<LoggedEvent>>,
+    /// the events after the last one the client got which are no longer kept
+    pub missed: u64,
+    /// the events after the replayed ones
+    pub receiver: broadcast::Receiver<Arc<LoggedEvent>>,
+}
+
+#[derive(Debug)]
+struct EventLogState {
+    next_id: u64,
+    /// the latest events, the oldest first
+    events: VecDeque<Arc<LoggedEvent>>,
+}
+
+/// numbers the events and keeps the latest of them
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    state: Arc<Mutex<EventLogState>>,
+    capacity: usize,
+    sender: broadcast::Sender<Arc<LoggedEvent>>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_fan_out_capacity(capacity, EVENT_FAN_OUT_CAPACITY)
+    }
+
+    /// `fan_out_capacity` events a client may fall behind by
+    pub fn with_fan_out_capacity(capacity: usize, fan_out_capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(EventLogState {
+                next_id: 1,
+                events: VecDeque::with_capacity(capacity),
+            })),
+            capacity: capacity.max(1),
+            sender: broadcast::channel(fan_out_capacity.max(1)).0,
+        }
+    }
+
+    pub fn push(&self, event: StreamEvent) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let event = Arc::new(LoggedEvent {
+            id: state.next_id,
+            event,
+        });
+        state.next_id += 1;
+        if state.events.len() == self.capacity {
+            state.events.pop_front();
+        }
+        state.events.push_back(event.clone());
+        // sent under the lock, a subscription gets every event either replayed or received
+        let _ = self.sender.send(event.clone());
+        event.id
+    }
+
+    /// the events after `last_event_id` and the ones to come, all of them to come if None
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> EventSubscription {
+        let state = self.state.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let Some(last_event_id) = last_event_id else {
+            return EventSubscription {
+                replay: vec![],
+                missed: 0,
+                receiver,
+            };
+        };
+        // an id from before a restart of the server counts from 1 again
+        let last_event_id = if last_event_id >= state.next_id {
+            0
+        } else {
+            last_event_id
+        };
+        let oldest_kept = state.events.front().map_or(state.next_id, |event| event.id);
+        EventSubscription {
+            replay: state
+                .events
+                .iter()
+                .filter(|event| event.id > last_event_id)
+                .cloned()
+                .collect(),
+            missed: oldest_kept.saturating_sub(last_event_id + 1),
+            receiver,
+        }
+    }
+
+    /// logs the notifications until the stream center is gone
+    pub async fn run(self, mut notifications: broadcast::Receiver<StreamNotification>) {
+        loop {
+            match notifications.recv().await {
+                Ok(notification) => {
+                    self.push(notification.into());
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("event log missed {} stream notifications", missed);
+                    self.push(StreamEvent::Lagged { missed });
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+        tracing::info!("event log exits, the stream center is gone");
+    }
+}
@@ -0,0 +1,218 @@
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, time::Duration};
+
+    use rocket::{
+        http::{Header, Status},
+        local::asynchronous::{Client, LocalResponse},
+    };
+    use serde_json::Value;
+    use server_utils::{egress_shaping::EgressShaper, session_registry::SessionRegistry};
+    use stream_center::{
+        identity::StreamIdentity,
+        notification::StreamNotification,
+        stream_source::{PlayProtocol, PublishProtocol},
+    };
+    use tokio::{io::AsyncReadExt, sync::broadcast};
+    use uuid::Uuid;
+
+    use crate::{
+        config::HttpServerConfig, events::EventLog, routes::events::LAST_EVENT_ID_HEADER,
+        server::HttpServer,
+    };
+
+    /// an event as sent in the stream
+    #[derive(Debug)]
+    struct SentEvent {
+        id: Option<u64>,
+        name: String,
+        data: Value,
+    }
+
+    async fn client(event_log: &EventLog) -> Client {
+        let server = HttpServer::new(
+            HttpServerConfig {
+                address: Ipv4Addr::LOCALHOST.into(),
+                port: 8080,
+                workers: 1,
+                vod_dir: None,
+                capture_dir: None,
+                trusted_proxies: Default::default(),
+            },
+            EgressShaper::default(),
+            SessionRegistry::default(),
+            tokio::sync::mpsc::unbounded_channel().0,
+        )
+        .with_event_log(event_log.clone());
+        Client::tracked(server.build()).await.unwrap()
+    }
+
+    fn stream_id(key: &str) -> StreamIdentity {
+        let (app, stream) = key.split_once('/').unwrap();
+        StreamIdentity::new(app, stream).unwrap()
+    }
+
+    fn published(key: &str) -> StreamNotification {
+        StreamNotification::Published {
+            stream_id: stream_id(key),
+            protocol: PublishProtocol::RTMP,
+        }
+    }
+
+    /// reads until `count` events are sent, the heartbeats are skipped
+    async fn read_events(response: &mut LocalResponse<'_>, count: usize) -> Vec<SentEvent> {
+        let mut text = String::new();
+        let mut buffer = vec![0; 4096];
+        let mut events = vec![];
+        while events.len() < count {
+            let read = tokio::time::timeout(Duration::from_secs(2), response.read(&mut buffer))
+                .await
+                .expect("event stream stalled")
+                .unwrap();
+            assert_ne!(read, 0, "event stream ended");
+            text.push_str(std::str::from_utf8(&buffer[..read]).unwrap());
+            while let Some(end) = text.find("\n\n") {
+                let frame: String = text.drain(..end + 2).collect();
+                let (mut id, mut name, mut data) = (None, String::new(), None);
+                for line in frame.lines() {
+                    if let Some(value) = line.strip_prefix("id:") {
+                        id = Some(value.trim().parse().unwrap());
+                    } else if let Some(value) = line.strip_prefix("event:") {
+                        name = value.trim().to_string();
+                    } else if let Some(value) = line.strip_prefix("data:") {
+                        data = Some(serde_json::from_str(value.trim()).unwrap());
+                    }
+                }
+                if let Some(data) = data {
+                    events.push(SentEvent { id, name, data });
+                }
+            }
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn consumers_see_only_the_events_their_filters_match() {
+        let event_log = EventLog::new(16);
+        let (notification_sender, notifications) = broadcast::channel(16);
+        tokio::spawn(event_log.clone().run(notifications));
+        let client = client(&event_log).await;
+        let mut live = client.get("/api/events?stream=live/").dispatch().await;
+        assert_eq!(live.status(), Status::Ok);
+        let mut leaving = client
+            .get("/api/events?types=unpublished,subscribed")
+            .dispatch()
+            .await;
+        assert_eq!(leaving.status(), Status::Ok);
+
+        let subscriber_id = Uuid::now_v7();
+        for notification in [
+            published("live/a"),
+            published("court/b"),
+            StreamNotification::Subscribed {
+                stream_id: stream_id("court/b"),
+                subscriber_id,
+                protocol: PlayProtocol::HTTPFLV,
+            },
+            StreamNotification::Unpublished {
+                stream_id: stream_id("live/a"),
+            },
+        ] {
+            notification_sender.send(notification).unwrap();
+        }
+
+        let events = read_events(&mut live, 2).await;
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.id, event.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(Some(1), "published"), (Some(4), "unpublished")]
+        );
+        assert_eq!(events[0].data["stream"], "live/a");
+        assert_eq!(events[0].data["protocol"], "RTMP");
+        assert_eq!(events[0].data["id"], 1);
+
+        let events = read_events(&mut leaving, 2).await;
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.id, event.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(Some(3), "subscribed"), (Some(4), "unpublished")]
+        );
+        assert_eq!(events[0].data["subscriber_id"], subscriber_id.to_string());
+        assert_eq!(events[0].data["protocol"], "HTTPFLV");
+        assert_eq!(events[1].data["stream"], "live/a");
+    }
+
+    #[tokio::test]
+    async fn reconnecting_consumers_resume_from_the_log() {
+        let event_log = EventLog::new(4);
+        let client = client(&event_log).await;
+        let mut response = client.get("/api/events").dispatch().await;
+        for key in ["live/a", "live/b"] {
+            event_log.push(published(key).into());
+        }
+        let events = read_events(&mut response, 2).await;
+        assert_eq!(events[1].id, Some(2));
+        drop(response);
+
+        // missed while disconnected
+        for key in ["live/c", "live/d"] {
+            event_log.push(published(key).into());
+        }
+        let mut response = client
+            .get("/api/events")
+            .header(Header::new(LAST_EVENT_ID_HEADER, "2"))
+            .dispatch()
+            .await;
+        event_log.push(published("live/e").into());
+        let events = read_events(&mut response, 3).await;
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.id, event.data["stream"].as_str().unwrap()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some(3), "live/c"),
+                (Some(4), "live/d"),
+                (Some(5), "live/e")
+            ]
+        );
+        drop(response);
+
+        // the log keeps the latest 4, the one before them is missed
+        let mut response = client
+            .get("/api/events?types=published")
+            .header(Header::new(LAST_EVENT_ID_HEADER, "0"))
+            .dispatch()
+            .await;
+        let events = read_events(&mut response, 5).await;
+        assert_eq!(events[0].id, None);
+        assert_eq!(events[0].name, "lagged");
+        assert_eq!(events[0].data["missed"], 1);
+        assert_eq!(
+            events[1..]
+                .iter()
+                .map(|event| event.id.unwrap())
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4, 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_consumers_skip_ahead_with_a_lagged_marker() {
+        let event_log = EventLog::with_fan_out_capacity(16, 2);
+        let client = client(&event_log).await;
+        let mut response = client.get("/api/events").dispatch().await;
+        for index in 0..5 {
+            event_log.push(published(&format!("live/{}", index)).into());
+        }
+        let events = read_events(&mut response, 3).await;
+        assert_eq!(events[0].name, "lagged");
+        assert_eq!(events[0].data["missed"], 3);
+        assert_eq!(events[1].id, Some(4));
+        assert_eq!(events[2].id, Some(5));
+    }
+}
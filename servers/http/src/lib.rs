@@ -1,5 +1,7 @@
+pub mod client_addr;
 pub mod config;
 pub mod errors;
+pub mod events;
 pub mod routes;
 pub mod server;
 pub mod sessions;
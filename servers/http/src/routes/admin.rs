@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use rocket::{
+    State, delete, get, post, put,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use stream_center::{
+    drain::DrainRequest, errors::StreamCenterError, identity::StreamIdentity,
+    stream_center::StreamCenter,
+};
+use uuid::Uuid;
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+};
+
+/// how long drained publishers may stay if the request does not say
+pub const DEFAULT_DRAIN_GRACE_MS: u64 = 30_000;
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DrainRequestBody {
+    /// `host[:port]` the publishers are asked to reconnect to
+    target: String,
+    description: Option<String>,
+    grace_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DrainResponse {
+    drain_id: Uuid,
+    /// `app/stream` of the streams whose publishers are drained
+    streams: Vec<String>,
+    grace_ms: u64,
+}
+
+/// asks the publishers to move to the target host, the ones still here after the grace period are disconnected.
+/// the sessions whose protocols can tell their clients to move, e.g. rtsp, are asked to as well
+#[post("/admin/drain", data = "<request>")]
+pub(crate) async fn drain(
+    ctx: &State<HttpServerContext>,
+    request: Json<DrainRequestBody>,
+) -> HttpServerResult<Json<DrainResponse>> {
+    tracing::info!("get drain request: {:?}", request);
+    let request = request.into_inner();
+    if request.target.is_empty() || request.target.contains('/') {
+        return Err(HttpServerError::BadRequest(format!(
+            "bad drain target: {}, expect host[:port]",
+            request.target
+        )));
+    }
+    let grace_ms = request.grace_ms.unwrap_or(DEFAULT_DRAIN_GRACE_MS);
+    let drain_request = DrainRequest {
+        target: request.target,
+        description: request.description,
+        grace: Duration::from_millis(grace_ms),
+    };
+    ctx.session_registry.drain(drain_request.clone());
+    let summary = StreamCenter::drain(&ctx.stream_center_event_sender, drain_request)
+        .await
+        .map_err(|err| {
+            tracing::error!("drain failed: {}", err);
+            HttpServerError::InternalError("internal error".to_string())
+        })?;
+    let mut streams: Vec<_> = summary
+        .streams
+        .iter()
+        .map(|stream_id| stream_id.to_string())
+        .collect();
+    streams.sort();
+    Ok(Json(DrainResponse {
+        drain_id: summary.drain_id,
+        streams,
+        grace_ms,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AliasRequestBody {
+    /// `app/stream` of the canonical stream
+    stream: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AliasView {
+    /// `app/stream` of the alias
+    alias: String,
+    /// `app/stream` of the canonical stream
+    stream: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AliasChange {
+    alias: String,
+    /// null if the alias is removed
+    stream: Option<String>,
+    /// the stream the alias pointed to before, null if it was not an alias
+    previous: Option<String>,
+}
+
+/// the aliases and the streams they point to, ordered by alias
+#[get("/admin/aliases")]
+pub(crate) async fn aliases(
+    ctx: &State<HttpServerContext>,
+) -> HttpServerResult<Json<Vec<AliasView>>> {
+    let aliases = StreamCenter::aliases(&ctx.stream_center_event_sender)
+        .await
+        .map_err(|err| {
+            tracing::error!("list aliases failed: {}", err);
+            HttpServerError::InternalError("internal error".to_string())
+        })?;
+    Ok(Json(
+        aliases
+            .into_iter()
+            .map(|(alias, stream)| AliasView {
+                alias: alias.to_string(),
+                stream: stream.to_string(),
+            })
+            .collect(),
+    ))
+}
+
+/// points the alias to the stream, the players of the alias so far stay on the stream they play
+#[put("/admin/aliases/<app>/<stream>", data = "<request>")]
+pub(crate) async fn point_alias(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+    request: Json<AliasRequestBody>,
+) -> HttpServerResult<Json<AliasChange>> {
+    tracing::info!("get alias request: {}/{}, {:?}", app, stream, request);
+    let canonical: StreamIdentity = request
+        .stream
+        .parse()
+        .map_err(|err: StreamCenterError| HttpServerError::BadRequest(err.to_string()))?;
+    set_alias(ctx, app, stream, Some(canonical)).await
+}
+
+#[delete("/admin/aliases/<app>/<stream>")]
+pub(crate) async fn remove_alias(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+) -> HttpServerResult<Json<AliasChange>> {
+    tracing::info!("get remove alias request: {}/{}", app, stream);
+    set_alias(ctx, app, stream, None).await
+}
+
+async fn set_alias(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+    canonical: Option<StreamIdentity>,
+) -> HttpServerResult<Json<AliasChange>> {
+    let alias = super::stream_identity(app, stream)?;
+    let previous =
+        StreamCenter::point_alias(&ctx.stream_center_event_sender, &alias, canonical.as_ref())
+            .await
+            .map_err(|err| match err {
+                StreamCenterError::InvalidAlias(_) => HttpServerError::BadRequest(err.to_string()),
+                _ => {
+                    tracing::error!("set alias {} failed: {}", alias, err);
+                    HttpServerError::InternalError("internal error".to_string())
+                }
+            })?;
+    if canonical.is_none() && previous.is_none() {
+        return Err(HttpServerError::NotFound(format!(
+            "alias not found: {}",
+            alias
+        )));
+    }
+    Ok(Json(AliasChange {
+        alias: alias.to_string(),
+        stream: canonical.map(|canonical| canonical.to_string()),
+        previous: previous.map(|previous| previous.to_string()),
+    }))
+}
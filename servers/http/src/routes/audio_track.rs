@@ -0,0 +1,77 @@
+use rocket::{
+    State, post,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use stream_center::{
+    errors::StreamCenterError, stream_center::StreamCenter, stream_source::MediaSelection,
+};
+use uuid::Uuid;
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SwitchAudioTrackRequest {
+    track_id: u8,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SwitchAudioTrackResponse {
+    subscriber_id: Uuid,
+    track_id: u8,
+}
+
+/// switches a subscriber to another audio track without resubscribing,
+/// the subscriber id of an http-flv play is in its X-Subscriber-Id response header
+#[post(
+    "/streams/<app>/<stream>/subscribers/<subscriber_id>/audio_track",
+    data = "<request>"
+)]
+pub(crate) async fn switch(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+    subscriber_id: &str,
+    request: Json<SwitchAudioTrackRequest>,
+) -> HttpServerResult<Json<SwitchAudioTrackResponse>> {
+    let subscriber_id = Uuid::parse_str(subscriber_id).map_err(|err| {
+        HttpServerError::BadRequest(format!("bad subscriber id: {}, {}", subscriber_id, err))
+    })?;
+    let stream_id = super::stream_identity(app, stream)?;
+    let map_err = |err: StreamCenterError| match err {
+        StreamCenterError::StreamNotFound(id) => {
+            HttpServerError::NotFound(format!("stream not found: {}", id))
+        }
+        _ => HttpServerError::InternalError("internal error".to_string()),
+    };
+    // only the track changes, the rest of the selection is kept
+    let description = StreamCenter::describe(&ctx.stream_center_event_sender, &stream_id)
+        .await
+        .map_err(map_err)?;
+    let Some(subscriber) = description.subscribers.get(&subscriber_id) else {
+        return Err(HttpServerError::NotFound(format!(
+            "subscriber {} of stream {} not found",
+            subscriber_id, stream_id
+        )));
+    };
+    let media_selection = subscriber.media_selection;
+    StreamCenter::update_media_selection(
+        &ctx.stream_center_event_sender,
+        subscriber_id,
+        &stream_id,
+        MediaSelection {
+            audio_track: request.track_id,
+            ..media_selection
+        },
+    )
+    .await
+    .map_err(map_err)?;
+    Ok(Json(SwitchAudioTrackResponse {
+        subscriber_id,
+        track_id: request.track_id,
+    }))
+}
@@ -0,0 +1,194 @@
+use std::{
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use rocket::{
+    State, delete, get, post,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use rtp_session::{
+    capture::{CaptureFormat, CaptureLimits},
+    errors::RtpSessionError,
+};
+use server_utils::session_registry::capture::SessionCapture;
+use uuid::Uuid;
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CaptureRequestBody {
+    /// rtpdump or pcap, pcap if not set
+    format: Option<String>,
+    /// the capture stops by itself at whichever limit comes first
+    max_bytes: Option<u64>,
+    max_duration_ms: Option<u64>,
+}
+
+impl CaptureRequestBody {
+    fn params(&self) -> HttpServerResult<(CaptureFormat, CaptureLimits)> {
+        let format = match &self.format {
+            Some(format) => format
+                .parse()
+                .map_err(|err: RtpSessionError| HttpServerError::BadRequest(err.to_string()))?,
+            None => CaptureFormat::Pcap,
+        };
+        let default = CaptureLimits::default();
+        let limits = CaptureLimits {
+            max_bytes: self.max_bytes.unwrap_or(default.max_bytes),
+            max_duration: self
+                .max_duration_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_duration),
+        };
+        if limits.max_bytes == 0 || limits.max_duration.is_zero() {
+            return Err(HttpServerError::BadRequest(
+                "capture limits must be positive".to_string(),
+            ));
+        }
+        Ok((format, limits))
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CaptureView {
+    session_id: Uuid,
+    /// where the capture is written
+    path: String,
+    format: String,
+    /// unix millis
+    started_at: u64,
+    max_bytes: u64,
+    max_duration_ms: u64,
+    /// written so far
+    packets: u64,
+    bytes: u64,
+    /// left out as the writer fell behind
+    dropped: u64,
+    running: bool,
+    /// why it stopped, once it has
+    stop_reason: Option<String>,
+}
+
+impl From<SessionCapture> for CaptureView {
+    fn from(value: SessionCapture) -> Self {
+        let info = value.info;
+        Self {
+            session_id: value.session_id,
+            path: info.path.display().to_string(),
+            format: info.format.to_string(),
+            started_at: info
+                .started_at
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or(0),
+            max_bytes: info.limits.max_bytes,
+            max_duration_ms: info.limits.max_duration.as_millis() as u64,
+            packets: info.packets,
+            bytes: info.bytes,
+            dropped: info.dropped,
+            running: info.stopped.is_none(),
+            stop_reason: info.stopped.map(|stop| stop.to_string()),
+        }
+    }
+}
+
+fn capture_dir(ctx: &HttpServerContext) -> HttpServerResult<&Path> {
+    ctx.config
+        .capture_dir
+        .as_deref()
+        .ok_or_else(|| HttpServerError::NotFound("packet capture is not enabled".to_string()))
+}
+
+fn session_id(id: &str) -> HttpServerResult<Uuid> {
+    Uuid::parse_str(id)
+        .map_err(|err| HttpServerError::BadRequest(format!("bad session id: {}, {}", id, err)))
+}
+
+/// the capture running of each session, or its latest one
+#[get("/captures")]
+pub(crate) async fn list(ctx: &State<HttpServerContext>) -> Json<Vec<CaptureView>> {
+    Json(
+        ctx.session_registry
+            .captures()
+            .into_iter()
+            .map(CaptureView::from)
+            .collect(),
+    )
+}
+
+/// captures the rtp and rtcp packets the session receives and sends to a file in the capture dir,
+/// until it is stopped or a limit is reached
+#[post("/sessions/<id>/capture", data = "<request>")]
+pub(crate) async fn start_session(
+    ctx: &State<HttpServerContext>,
+    id: &str,
+    request: Json<CaptureRequestBody>,
+) -> HttpServerResult<Json<CaptureView>> {
+    tracing::info!("get capture request of session {}: {:?}", id, request);
+    let dir = capture_dir(ctx)?;
+    let session_id = session_id(id)?;
+    let (format, limits) = request.params()?;
+    let capture = ctx
+        .session_registry
+        .start_capture(&session_id, dir, format, limits)
+        .await?;
+    Ok(Json(capture.into()))
+}
+
+/// the packets captured so far are still written to the file
+#[delete("/sessions/<id>/capture")]
+pub(crate) async fn stop_session(
+    ctx: &State<HttpServerContext>,
+    id: &str,
+) -> HttpServerResult<Json<CaptureView>> {
+    capture_dir(ctx)?;
+    let session_id = session_id(id)?;
+    Ok(Json(ctx.session_registry.stop_capture(&session_id)?.into()))
+}
+
+/// captures each rtp session publishing or playing the stream, to a file of its own
+#[post("/streams/<app>/<stream>/capture", data = "<request>")]
+pub(crate) async fn start_stream(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+    request: Json<CaptureRequestBody>,
+) -> HttpServerResult<Json<Vec<CaptureView>>> {
+    tracing::info!(
+        "get capture request of stream {}/{}: {:?}",
+        app,
+        stream,
+        request
+    );
+    let dir = capture_dir(ctx)?;
+    let stream_key = super::stream_identity(app, stream)?.to_string();
+    let (format, limits) = request.params()?;
+    let captures = ctx
+        .session_registry
+        .start_stream_capture(&stream_key, dir, format, limits)
+        .await?;
+    Ok(Json(captures.into_iter().map(CaptureView::from).collect()))
+}
+
+#[delete("/streams/<app>/<stream>/capture")]
+pub(crate) async fn stop_stream(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+) -> HttpServerResult<Json<Vec<CaptureView>>> {
+    capture_dir(ctx)?;
+    let stream_key = super::stream_identity(app, stream)?.to_string();
+    Ok(Json(
+        ctx.session_registry
+            .stop_stream_capture(&stream_key)
+            .into_iter()
+            .map(CaptureView::from)
+            .collect(),
+    ))
+}
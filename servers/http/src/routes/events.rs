@@ -0,0 +1,101 @@
+use std::{convert::Infallible, time::Duration};
+
+use rocket::{
+    Request, State, get,
+    request::{FromRequest, Outcome},
+    response::stream::{Event, EventStream},
+};
+use tokio::sync::broadcast;
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    events::{EventFilter, EventSubscription, LoggedEvent, StreamEvent},
+    server::HttpServerContext,
+};
+
+/// a comment is sent this often, for the proxies in between not to time the stream out
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+pub const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// the id of the last event an event source got before it reconnected, if any
+#[derive(Debug, Clone, Copy)]
+pub struct LastEventId(Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Self(
+            request
+                .headers()
+                .get_one(LAST_EVENT_ID_HEADER)
+                .and_then(|id| id.trim().parse().ok()),
+        ))
+    }
+}
+
+fn to_event(event: &LoggedEvent) -> Event {
+    Event::json(event)
+        .id(event.id.to_string())
+        .event(event.event.kind())
+}
+
+/// not numbered, a client reconnecting after it resumes from the last event before it
+fn lagged(missed: u64) -> Event {
+    let marker = StreamEvent::Lagged { missed };
+    Event::json(&marker).event(marker.kind())
+}
+
+/// the events of the streams as they happen, json in server-sent events.
+/// `stream` keeps the events of the `app/stream` keys starting with it,
+/// `types` the events of the comma separated types
+#[get("/events?<stream>&<types>")]
+pub(crate) async fn events(
+    ctx: &State<HttpServerContext>,
+    last_event_id: LastEventId,
+    stream: Option<String>,
+    types: Option<String>,
+) -> HttpServerResult<EventStream![]> {
+    let event_log = ctx
+        .event_log
+        .clone()
+        .ok_or_else(|| HttpServerError::NotFound("events are not enabled".to_string()))?;
+    let filter = EventFilter {
+        stream_prefix: stream.filter(|prefix| !prefix.is_empty()),
+        kinds: types.map(|types| {
+            types
+                .split(',')
+                .map(|kind| kind.trim().to_string())
+                .filter(|kind| !kind.is_empty())
+                .collect()
+        }),
+    };
+    let EventSubscription {
+        replay,
+        missed,
+        mut receiver,
+    } = event_log.subscribe(last_event_id.0);
+    Ok(EventStream! {
+        if missed > 0 {
+            yield lagged(missed);
+        }
+        for event in replay {
+            if filter.matches(&event.event) {
+                yield to_event(&event);
+            }
+        }
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if filter.matches(&event.event) {
+                        yield to_event(&event);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => yield lagged(missed),
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+    .heartbeat(HEARTBEAT_INTERVAL))
+}
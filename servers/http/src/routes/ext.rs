@@ -1,17 +1,16 @@
 use rocket::request::FromParam;
 
+/// matches the path params with the extension, the name itself is taken from the url of the request
 macro_rules! generate_from_param_ext {
     ($struct_name: ident, $ext: expr) => {
-        pub(crate) struct $struct_name<'a>(pub &'a str);
-        impl<'a> FromParam<'a> for $struct_name<'a> {
+        pub(crate) struct $struct_name;
+        impl<'a> FromParam<'a> for $struct_name {
             type Error = ();
 
             fn from_param(param: &'a str) -> Result<Self, Self::Error> {
                 match param.strip_suffix($ext) {
                     None => Err(()),
-                    Some(x) => FromParam::from_param(x)
-                        .map(|x| $struct_name(x))
-                        .map_err(|_| ()),
+                    Some(_) => Ok($struct_name),
                 }
             }
         }
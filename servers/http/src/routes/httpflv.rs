@@ -1,15 +1,26 @@
 use std::{collections::HashMap, io::Cursor};
 
 use rocket::{
-    FromForm, Request, Response, State, get,
-    http::{ContentType, Header},
+    FromForm, Request, Response, Route, State, get,
+    http::{ContentType, Header, uri::Origin},
     response::Responder,
 };
-use server_utils::stream_properities::StreamProperties;
+use server_utils::{
+    egress_shaping::MAX_BITRATE_KEY,
+    log_context::{StreamRole, session_span, stream_span},
+    metrics::ConnectionMetricsGuard,
+    session_registry::{SessionCounters, SessionProtocol, SessionRole},
+    stream_properities::StreamProperties,
+};
+use stream_center::{audio_track::DEFAULT_AUDIO_TRACK, stream_source::MediaSelection};
 use tokio::sync::mpsc::{self, UnboundedReceiver};
-use tokio_util::bytes::BytesMut;
+use tokio_util::bytes::Bytes;
+use tracing::Instrument;
+use utils::media_url::{MediaUrl, TOKEN_KEY};
+use uuid::Uuid;
 
 use crate::{
+    client_addr::ClientAddr,
     errors::{HttpServerError, HttpServerResult},
     server::HttpServerContext,
     sessions::httpflv::session::{HttpFlvSession, HttpFlvSessionConfig},
@@ -18,8 +29,11 @@ use crate::{
 use super::ext::FlvStreamName;
 
 pub struct HttpFlvStream {
-    receiver: UnboundedReceiver<BytesMut>,
-    bytes_buffer: Option<Cursor<BytesMut>>,
+    subscriber_id: Uuid,
+    receiver: UnboundedReceiver<Bytes>,
+    bytes_buffer: Option<Cursor<Bytes>>,
+    /// the bytes of the body are counted in the session registry
+    counters: SessionCounters,
 }
 
 impl tokio::io::AsyncRead for HttpFlvStream {
@@ -44,6 +58,7 @@ impl tokio::io::AsyncRead for HttpFlvStream {
 
         match Pin::new(&mut self.receiver).poll_recv(cx) {
             Poll::Ready(Some(bytes)) => {
+                self.counters.on_sent(bytes.len());
                 self.bytes_buffer = Some(Cursor::new(bytes));
                 self.poll_read(cx, buf)
             }
@@ -59,6 +74,10 @@ impl<'r> Responder<'r, 'r> for HttpFlvStream {
         Response::build()
             .header(ContentType::new("video", "x-flv"))
             .header(Header::new("Access-Control-Allow-Origin", "*"))
+            .header(Header::new(
+                "X-Subscriber-Id",
+                self.subscriber_id.to_string(),
+            ))
             .streamed_body(self)
             .ok()
     }
@@ -74,42 +93,88 @@ pub struct HttpFlvPullRequest {
     #[field(name = uncased("video_only"))]
     #[field(name = uncased("video-only"))]
     video_only: Option<bool>,
+    /// either "audio" or "video"
+    #[field(name = uncased("only"))]
+    only: Option<String>,
     #[field(name = uncased("backtrackGopCnt"))]
     #[field(name = uncased("backtrack-gop-cnt"))]
     #[field(name = uncased("backtrack_gop_cnt"))]
     backtrack_gop_cnt: Option<usize>,
+    #[field(name = uncased("audio_track"))]
+    #[field(name = uncased("audioTrack"))]
+    #[field(name = uncased("audio-track"))]
+    audio_track: Option<u8>,
+    /// caps the bitrate of this viewer, below the cap of the server only
+    #[field(name = uncased("max_bitrate_kbps"))]
+    #[field(name = uncased("maxBitrateKbps"))]
+    #[field(name = uncased("max-bitrate-kbps"))]
+    max_bitrate_kbps: Option<u64>,
     #[field(name = "ctx")]
     _ctx: Option<String>,
 }
 
-#[get("/<app>/<stream>?<params..>")]
+/// the url of the request under the mount point of the route,
+/// the app and the stream of which are told the same way as the ones of rtmp and rtsp
+fn request_url(route: &Route, uri: &Origin<'_>) -> HttpServerResult<MediaUrl> {
+    let path_and_query = uri.to_string();
+    let path_and_query = path_and_query
+        .strip_prefix(route.uri.base())
+        .unwrap_or(&path_and_query);
+    MediaUrl::new("http", "localhost", None)
+        .with_path(path_and_query)
+        .map_err(|err| HttpServerError::BadRequest(format!("bad url: {}, {}", uri, err)))
+}
+
+/// the app and the stream are taken from the url, the params make sure the stream ends with .flv
+#[get("/<_app>/<_stream>?<params..>")]
 pub(crate) async fn serve(
     ctx: &State<HttpServerContext>,
-    app: &str,
-    stream: FlvStreamName<'_>,
+    client_addr: ClientAddr,
+    route: &Route,
+    uri: &Origin<'_>,
+    _app: &str,
+    _stream: FlvStreamName,
     params: HttpFlvPullRequest,
 ) -> HttpServerResult<HttpFlvStream> {
-    let stream = stream.0;
+    let remote = client_addr.0;
+    let url = request_url(route, uri)?;
+    let (app, stream) = url
+        .app_and_stream()
+        .map_err(|err| HttpServerError::BadRequest(err.to_string()))?;
+    let stream_id = super::stream_identity(app, stream.strip_suffix(".flv").unwrap_or(stream))?;
+    let stream_key = stream_id.to_string();
+    let span = stream_span(
+        &session_span("http-flv", remote),
+        &stream_key,
+        StreamRole::Subscribe,
+    );
     tracing::info!(
-        "get http flv pull request, app: {}, stream: {}, params: {:?}",
-        app,
-        stream,
+        "get http flv pull request, stream: {}, params: {:?}",
+        stream_id,
         params
     );
-    if app.is_empty() {
-        return Err(HttpServerError::BadRequest(format!(
-            "bad app and stream, app: {}, stream: {}",
-            app, stream
-        )));
-    }
 
-    let mut ctx_params: HashMap<String, String> = HashMap::new();
+    let media_selection = match params.only.as_deref() {
+        Some("audio") => MediaSelection::audio_only(),
+        Some("video") => MediaSelection::video_only(),
+        Some(only) => {
+            return Err(HttpServerError::BadRequest(format!(
+                "bad only param: {}, expect audio or video",
+                only
+            )));
+        }
+        None if params.audio_only.unwrap_or(false) => MediaSelection::audio_only(),
+        None if params.video_only.unwrap_or(false) => MediaSelection::video_only(),
+        None => MediaSelection::default(),
+    };
+    let media_selection = MediaSelection {
+        audio_track: params.audio_track.unwrap_or(DEFAULT_AUDIO_TRACK),
+        ..media_selection
+    };
 
-    if params.audio_only.unwrap_or(false) {
-        ctx_params.insert(super::params::AUDIO_ONLY_KEY.to_string(), "".to_string());
-    }
-    if params.video_only.unwrap_or(false) {
-        ctx_params.insert(super::params::VIDEO_ONLY_KEY.to_string(), "".to_string());
+    let mut ctx_params: HashMap<String, String> = HashMap::new();
+    if let Some(token) = url.token() {
+        ctx_params.insert(TOKEN_KEY.to_owned(), token.to_owned());
     }
     if let Some(cnt) = params.backtrack_gop_cnt {
         ctx_params.insert(
@@ -117,6 +182,10 @@ pub(crate) async fn serve(
             cnt.to_string(),
         );
     }
+    if let Some(kbps) = params.max_bitrate_kbps {
+        ctx_params.insert(MAX_BITRATE_KEY.to_string(), kbps.to_string());
+    }
+    let shaper = ctx.egress_shaper.subscriber(&stream_key, &ctx_params);
 
     let (response_sender, response_receiver) = mpsc::unbounded_channel();
 
@@ -128,23 +197,45 @@ pub(crate) async fn serve(
         },
         ctx.stream_center_event_sender.clone(),
         StreamProperties {
-            app: app.to_string(),
-            stream_name: stream.to_string(),
+            stream_id,
             stream_context: ctx_params,
         },
+        media_selection,
+        shaper,
         response_sender,
     );
 
     // have to split subscribe from serve_pull_request so we can return 404 if not found
-    let subscribe_response = session.subscribe_from_stream_center().await?;
-
-    tokio::spawn(async move {
-        let _ = session.serve_pull_request(subscribe_response).await;
-        let _ = session.unsubscribe_from_stream_center().await;
-    });
+    let subscribe_response = session
+        .subscribe_from_stream_center()
+        .instrument(span.clone())
+        .await?;
+    let subscriber_id = subscribe_response.subscribe_id;
+    let mut registry_handle = ctx
+        .session_registry
+        .register(SessionProtocol::HttpFlv, remote);
+    registry_handle.set_role(SessionRole::Subscriber, &stream_key);
+    let counters = registry_handle.counters();
+
+    tokio::spawn(
+        async move {
+            let _connection = ConnectionMetricsGuard::accepted("http_flv");
+            tokio::select! {
+                _ = session.serve_pull_request(subscribe_response) => {}
+                // the flv body ends once the session is dropped
+                _ = registry_handle.close_requested() => {
+                    tracing::info!("http flv session is closed by admin");
+                }
+            }
+            let _ = session.unsubscribe_from_stream_center().await;
+        }
+        .instrument(span),
+    );
 
     Ok(HttpFlvStream {
+        subscriber_id,
         receiver: response_receiver,
         bytes_buffer: Default::default(),
+        counters,
     })
 }
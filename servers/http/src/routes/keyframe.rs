@@ -0,0 +1,55 @@
+use rocket::{State, get, http::ContentType};
+use stream_center::{errors::StreamCenterError, stream_center::StreamCenter};
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+};
+
+/// the latest IDR access unit of a stream with its sps and pps,
+/// AnnexB by default, 4 bytes length prefixed nalus with `format=avcc`
+#[get("/streams/<app>/<stream>/keyframe.h264?<format>")]
+pub(crate) async fn keyframe(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+    format: Option<&str>,
+) -> HttpServerResult<(ContentType, Vec<u8>)> {
+    let avcc = match format {
+        None | Some("annexb") => false,
+        Some("avcc") => true,
+        Some(format) => {
+            return Err(HttpServerError::BadRequest(format!(
+                "unknown keyframe format: {}, expect annexb or avcc",
+                format
+            )));
+        }
+    };
+    let stream_id = super::stream_identity(app, stream)?;
+    let snapshot = StreamCenter::keyframe(&ctx.stream_center_event_sender, &stream_id)
+        .await
+        .map_err(|err| match err {
+            StreamCenterError::StreamNotFound(id) => {
+                HttpServerError::NotFound(format!("stream not found: {}", id))
+            }
+            _ => HttpServerError::InternalError("internal error".to_string()),
+        })?
+        .ok_or_else(|| {
+            HttpServerError::NotFound(format!("no h264 keyframe of stream {} yet", stream_id))
+        })?;
+    let result = if avcc {
+        snapshot.to_avcc()
+    } else {
+        snapshot.to_annexb()
+    };
+    let bytes = result.map_err(|err| {
+        tracing::error!("serialize keyframe of stream {} failed: {}", stream_id, err);
+        HttpServerError::InternalError("internal error".to_string())
+    })?;
+    let content_type = if avcc {
+        ContentType::Binary
+    } else {
+        ContentType::new("video", "h264")
+    };
+    Ok((content_type, bytes))
+}
@@ -0,0 +1,10 @@
+use rocket::{get, http::ContentType};
+
+/// all the metrics in the prometheus text exposition format
+#[get("/metrics")]
+pub(crate) fn metrics() -> (ContentType, String) {
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        utils::metrics::global().encode(),
+    )
+}
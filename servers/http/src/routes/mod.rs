@@ -1,9 +1,27 @@
+pub mod admin;
+pub mod audio_track;
+pub mod capture;
+pub mod events;
 mod ext;
 pub mod hello;
 pub mod httpflv;
+pub mod keyframe;
+pub mod metrics;
+pub mod relays;
+pub mod sessions;
+pub mod stats;
+pub mod trace;
+pub mod vod;
+
+use stream_center::identity::StreamIdentity;
+
+use crate::errors::{HttpServerError, HttpServerResult};
+
+/// the stream of the app and the stream segments of a route, invalid names are bad requests
+pub(crate) fn stream_identity(app: &str, stream: &str) -> HttpServerResult<StreamIdentity> {
+    StreamIdentity::new(app, stream).map_err(|err| HttpServerError::BadRequest(err.to_string()))
+}
 
 pub mod params {
-    pub const AUDIO_ONLY_KEY: &str = "audioOnly";
-    pub const VIDEO_ONLY_KEY: &str = "videoOnly";
     pub const BACKTRACK_GOP_KEY: &str = "backtraceGopCnt";
 }
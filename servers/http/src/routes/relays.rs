@@ -0,0 +1,85 @@
+use rocket::{
+    State, delete, get, post,
+    serde::{Serialize, json::Json},
+};
+use server_utils::relay::{
+    Relay, RelayAuditEntry, RelayRegistry, RelayRule,
+    errors::{RelayError, RelayResult},
+};
+use uuid::Uuid;
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RelaysResponse {
+    /// the change made by the request, null for a listing
+    change: Option<RelayAuditEntry>,
+    /// in the order they were added
+    relays: Vec<Relay>,
+    /// the latest changes, the oldest first
+    audit: Vec<RelayAuditEntry>,
+}
+
+fn registry(ctx: &HttpServerContext) -> HttpServerResult<&RelayRegistry> {
+    ctx.relays
+        .as_ref()
+        .ok_or_else(|| HttpServerError::NotFound("relays are not enabled".to_string()))
+}
+
+fn respond(
+    registry: &RelayRegistry,
+    change: RelayResult<Option<RelayAuditEntry>>,
+) -> HttpServerResult<Json<RelaysResponse>> {
+    let change = change.map_err(|err| match err {
+        RelayError::InvalidPattern(_)
+        | RelayError::InvalidUrl(_)
+        | RelayError::UnresolvedHost(_)
+        | RelayError::Loop(_) => HttpServerError::BadRequest(err.to_string()),
+        RelayError::NotFound(_) => HttpServerError::NotFound(err.to_string()),
+        _ => {
+            tracing::error!("change relays failed: {}", err);
+            HttpServerError::InternalError("internal error".to_string())
+        }
+    })?;
+    Ok(Json(RelaysResponse {
+        change,
+        relays: registry.relays(),
+        audit: registry.audit(),
+    }))
+}
+
+/// the pull rules and the push targets, with the latest changes of them
+#[get("/relays")]
+pub(crate) async fn list(ctx: &State<HttpServerContext>) -> HttpServerResult<Json<RelaysResponse>> {
+    let registry = registry(ctx)?;
+    respond(registry, Ok(None))
+}
+
+/// takes effect at once: a push starts for the streams live already, a pull for the next subscribe
+/// of a stream not published. a url to this server is refused
+#[post("/relays", data = "<rule>")]
+pub(crate) async fn add(
+    ctx: &State<HttpServerContext>,
+    rule: Json<RelayRule>,
+) -> HttpServerResult<Json<RelaysResponse>> {
+    tracing::info!("get relay request: {:?}", rule);
+    let registry = registry(ctx)?;
+    let change = registry.add(rule.into_inner()).await.map(Some);
+    respond(registry, change)
+}
+
+/// the relays running for the rule are stopped, they delete their streams on the other servers
+#[delete("/relays/<id>")]
+pub(crate) async fn remove(
+    ctx: &State<HttpServerContext>,
+    id: &str,
+) -> HttpServerResult<Json<RelaysResponse>> {
+    let relay_id = Uuid::parse_str(id)
+        .map_err(|err| HttpServerError::BadRequest(format!("bad relay id: {}, {}", id, err)))?;
+    let registry = registry(ctx)?;
+    respond(registry, registry.remove(relay_id).map(Some))
+}
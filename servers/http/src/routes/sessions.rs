@@ -0,0 +1,91 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    State, delete, get,
+    serde::{Serialize, json::Json},
+};
+use server_utils::session_registry::SessionInfo;
+use uuid::Uuid;
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionView {
+    id: Uuid,
+    /// rtmp, rtsp, http-flv or srt
+    protocol: String,
+    peer_addr: String,
+    /// pending until the session publishes or plays, then publisher or subscriber
+    role: String,
+    /// `app/stream` of the streams published or played
+    stream_keys: Vec<String>,
+    /// unix millis
+    created_at: u64,
+    /// unix millis of the latest bytes counted, lags behind by up to a second
+    last_active: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    /// the session was asked to close and is closing
+    close_requested: bool,
+}
+
+impl From<SessionInfo> for SessionView {
+    fn from(value: SessionInfo) -> Self {
+        let unix_millis = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or(0)
+        };
+        Self {
+            id: value.id,
+            protocol: value.protocol.to_string(),
+            peer_addr: value.peer_addr.to_string(),
+            role: value.role.to_string(),
+            stream_keys: value.stream_keys,
+            created_at: unix_millis(value.created_at),
+            last_active: unix_millis(value.last_active),
+            bytes_in: value.bytes_in,
+            bytes_out: value.bytes_out,
+            close_requested: value.close_requested,
+        }
+    }
+}
+
+/// the sessions of all the servers, the most recently active first
+#[get("/sessions?<limit>")]
+pub(crate) async fn list(
+    ctx: &State<HttpServerContext>,
+    limit: Option<usize>,
+) -> Json<Vec<SessionView>> {
+    Json(
+        ctx.session_registry
+            .list(limit)
+            .into_iter()
+            .map(SessionView::from)
+            .collect(),
+    )
+}
+
+/// asks the session to close the way its protocol does, the streams it published or played are left
+/// once it is closed
+#[delete("/sessions/<id>")]
+pub(crate) async fn close(
+    ctx: &State<HttpServerContext>,
+    id: &str,
+) -> HttpServerResult<Json<SessionView>> {
+    let session_id = Uuid::parse_str(id)
+        .map_err(|err| HttpServerError::BadRequest(format!("bad session id: {}, {}", id, err)))?;
+    let mut session = ctx
+        .session_registry
+        .get(&session_id)
+        .ok_or_else(|| HttpServerError::NotFound(format!("session not found: {}", session_id)))?;
+    // false if the session is gone in between, which is what is asked anyway
+    ctx.session_registry.close(&session_id);
+    session.close_requested = true;
+    tracing::info!("session {} is asked to close: {:?}", session_id, session);
+    Ok(Json(session.into()))
+}
@@ -0,0 +1,279 @@
+use std::{collections::BTreeMap, time::UNIX_EPOCH};
+
+use amf_formats::amf0;
+use codec_common::video::VideoCodecCommon;
+use rocket::{
+    State, get,
+    serde::{Serialize, json::Json},
+};
+use server_utils::supervisor::TaskHealth;
+use stream_center::{
+    audio_continuity::AudioConcealmentStats, audio_track::AudioTrack, errors::StreamCenterError,
+    events::StreamDescription, identity::StreamIdentity, latency::LatencySummary,
+    rate_estimate::RateEstimateStats, rtcp_peer::RtcpPeer, rtmp_control::RtmpControl,
+    rtp_receive::RtpReceiveStats, stream_center::StreamCenter, stream_source::PlayProtocol,
+    timestamp_normalization::TimestampNormalizationStats,
+};
+use uuid::Uuid;
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct StreamStats {
+    /// `app/stream` of the stream, the canonical one if asked for by an alias
+    stream_key: StreamIdentity,
+    subscribers: usize,
+    /// the subscribers who asked for an alias, by `app/stream` of the alias
+    alias_subscribers: BTreeMap<StreamIdentity, usize>,
+    /// the subscribers sending to multicast groups, counted in subscribers once whatever
+    /// the number of receivers of the group
+    multicast_subscribers: usize,
+    /// null until the publisher sent a video sequence header
+    video_codec: Option<VideoCodecCommon>,
+    width: Option<u64>,
+    height: Option<u64>,
+    config_generation: u64,
+    /// ingest to sink latency, null if latency measurement is disabled
+    latency: Option<LatencySummary>,
+    /// what the rtp participants of the publisher and the subscribers sent in rtcp sdes
+    rtcp_peers: Vec<RtcpPeerStats>,
+    /// the protocol control settings in effect on the rtmp connections of the publisher and the subscribers
+    rtmp_connections: Vec<RtmpConnectionStats>,
+    /// how the rtp packets of each track of an rtp based publisher arrive, ordered by track
+    rtp_receive: Vec<RtpReceiveStats>,
+    /// bytes held by the buffers reassembling the h264 of an rtp based publisher, of all its tracks
+    bytes_buffered: u64,
+    /// times those buffers dropped data to fit in their budgets
+    buffer_discontinuities: u64,
+    /// 0 to 100 from the loss and jitter of the worst track of an rtp based publisher,
+    /// null until its first receiver report
+    health: Option<u8>,
+    /// the audio tracks a subscriber may pick with audio_track, ordered by track id
+    audio_tracks: Vec<AudioTrack>,
+    /// the holes in the audio of the publisher concealed so far, with silence or stretched timestamps
+    audio_concealment: AudioConcealmentStats,
+    /// the discontinuities and the drift correction of the timestamps of the publisher,
+    /// all zero unless the app normalizes them
+    timestamp_normalization: TimestampNormalizationStats,
+    /// the frame rate and bitrates measured from the publisher, estimated_fields tells
+    /// which of the onMetaData fields are filled with them rather than told by the publisher
+    rate_estimate: RateEstimateStats,
+    /// the onMetaData of the stream with the updates of the publisher merged in,
+    /// the nonstandard fields included, null until there is any
+    metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RtcpPeerStats {
+    /// null for the peers of the publisher
+    subscriber_id: Option<Uuid>,
+    #[serde(flatten)]
+    peer: RtcpPeer,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RtmpConnectionStats {
+    /// null for the publisher
+    subscriber_id: Option<Uuid>,
+    #[serde(flatten)]
+    control: RtmpControl,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TaskStats {
+    /// rtmp, rtsp, http, srt or stream_center
+    name: String,
+    /// false while the task waits to be restarted after a crash
+    running: bool,
+    restarts: u64,
+    /// unix millis, null if it never crashed
+    last_crash: Option<u64>,
+    /// the panic message or the error of the last crash
+    last_crash_reason: Option<String>,
+}
+
+impl From<TaskHealth> for TaskStats {
+    fn from(value: TaskHealth) -> Self {
+        Self {
+            name: value.name,
+            running: value.running,
+            restarts: value.restarts,
+            last_crash: value.last_crash.map(|time| {
+                time.duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_millis() as u64)
+                    .unwrap_or(0)
+            }),
+            last_crash_reason: value.last_crash_reason,
+        }
+    }
+}
+
+/// the servers and the stream center, how often they were restarted after crashing
+#[get("/stats/tasks")]
+pub(crate) async fn tasks(
+    ctx: &State<HttpServerContext>,
+) -> HttpServerResult<Json<Vec<TaskStats>>> {
+    let supervised_tasks = ctx
+        .supervised_tasks
+        .as_ref()
+        .ok_or_else(|| HttpServerError::NotFound("tasks are not supervised".to_string()))?;
+    Ok(Json(
+        supervised_tasks
+            .list()
+            .into_iter()
+            .map(TaskStats::from)
+            .collect(),
+    ))
+}
+
+#[get("/streams/<app>/<stream>/stats")]
+pub(crate) async fn stats(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+) -> HttpServerResult<Json<StreamStats>> {
+    let stream_id = super::stream_identity(app, stream)?;
+    let description = StreamCenter::describe(&ctx.stream_center_event_sender, &stream_id)
+        .await
+        .map_err(|err| match err {
+            StreamCenterError::StreamNotFound(id) => {
+                HttpServerError::NotFound(format!("stream not found: {}", id))
+            }
+            _ => HttpServerError::InternalError("internal error".to_string()),
+        })?;
+    let dimensions = description
+        .video_config
+        .as_ref()
+        .and_then(|config| config.dimensions());
+    let mut alias_subscribers = BTreeMap::new();
+    for alias in description
+        .subscribers
+        .values()
+        .filter_map(|subscriber| subscriber.alias.as_ref())
+    {
+        *alias_subscribers.entry(alias.clone()).or_default() += 1;
+    }
+    Ok(Json(StreamStats {
+        stream_key: description.stream_id.clone(),
+        subscribers: description.subscribers.len(),
+        alias_subscribers,
+        multicast_subscribers: description
+            .subscribers
+            .values()
+            .filter(|subscriber| subscriber.play_protocol == PlayProtocol::RTSPMulticast)
+            .count(),
+        video_codec: description
+            .video_config
+            .as_ref()
+            .map(VideoCodecCommon::from),
+        width: dimensions.map(|(width, _)| width),
+        height: dimensions.map(|(_, height)| height),
+        config_generation: description.config_generation,
+        latency: description.latency,
+        rtcp_peers: rtcp_peers(&description),
+        rtmp_connections: rtmp_connections(&description),
+        bytes_buffered: description
+            .publisher_rtp_receive
+            .iter()
+            .map(|stats| stats.bytes_buffered)
+            .sum(),
+        buffer_discontinuities: description
+            .publisher_rtp_receive
+            .iter()
+            .map(|stats| stats.buffer_discontinuities)
+            .sum(),
+        rtp_receive: description.publisher_rtp_receive,
+        health: description.health,
+        audio_tracks: description.audio_tracks,
+        audio_concealment: description.audio_concealment,
+        timestamp_normalization: description.timestamp_normalization,
+        rate_estimate: description.rate_estimate,
+        metadata: description.meta_data.as_ref().map(|meta_data| {
+            Vec::<(String, amf0::Value)>::from(meta_data)
+                .into_iter()
+                .map(|(key, value)| (key, amf0_to_json(value)))
+                .collect()
+        }),
+    }))
+}
+
+/// amf3 values nested in amf0 ones are not expected in metadata, they are dumped as their debug output
+fn amf0_to_json(value: amf0::Value) -> serde_json::Value {
+    match value {
+        amf0::Value::Number(number) => serde_json::Number::from_f64(number)
+            .map_or(serde_json::Value::Null, serde_json::Value::Number),
+        amf0::Value::Boolean(bool) => serde_json::Value::Bool(bool),
+        amf0::Value::String(string) | amf0::Value::XMLDocument(string) => {
+            serde_json::Value::String(string)
+        }
+        amf0::Value::Object { entries, .. } | amf0::Value::ECMAArray(entries) => {
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, amf0_to_json(value)))
+                    .collect(),
+            )
+        }
+        amf0::Value::StrictArray(values) => {
+            serde_json::Value::Array(values.into_iter().map(amf0_to_json).collect())
+        }
+        amf0::Value::Date {
+            millis_timestamp, ..
+        } => serde_json::Value::from(millis_timestamp.as_millis() as u64),
+        amf0::Value::AVMPlus(value) => serde_json::Value::String(format!("{:?}", value)),
+        amf0::Value::Null
+        | amf0::Value::Undefined
+        | amf0::Value::Reference { .. }
+        | amf0::Value::ObjectEnd => serde_json::Value::Null,
+    }
+}
+
+/// the peers of the publisher first, then the ones of each subscriber
+fn rtcp_peers(description: &StreamDescription) -> Vec<RtcpPeerStats> {
+    let mut subscribers: Vec<_> = description.subscribers.values().collect();
+    subscribers.sort_by_key(|subscriber| subscriber.id);
+    let publisher = description
+        .publisher_rtcp_peers
+        .iter()
+        .map(|peer| (None, peer));
+    let subscribers = subscribers.into_iter().flat_map(|subscriber| {
+        subscriber
+            .rtcp_peers
+            .iter()
+            .map(|peer| (Some(subscriber.id), peer))
+    });
+    publisher
+        .chain(subscribers)
+        .map(|(subscriber_id, peer)| RtcpPeerStats {
+            subscriber_id,
+            peer: peer.clone(),
+        })
+        .collect()
+}
+
+/// the publisher first, then the subscribers ordered by id
+fn rtmp_connections(description: &StreamDescription) -> Vec<RtmpConnectionStats> {
+    let mut subscribers: Vec<_> = description
+        .subscribers
+        .values()
+        .filter_map(|subscriber| Some((Some(subscriber.id), subscriber.rtmp_control?)))
+        .collect();
+    subscribers.sort_by_key(|(subscriber_id, _)| *subscriber_id);
+    description
+        .publisher_rtmp_control
+        .map(|control| (None, control))
+        .into_iter()
+        .chain(subscribers)
+        .map(|(subscriber_id, control)| RtmpConnectionStats {
+            subscriber_id,
+            control,
+        })
+        .collect()
+}
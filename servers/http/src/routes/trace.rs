@@ -0,0 +1,29 @@
+use rocket::{State, get, serde::json::Json};
+use stream_center::{errors::StreamCenterError, stream_center::StreamCenter, trace::TraceRecord};
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+};
+
+/// recent pipeline events of a stream, oldest first
+#[get("/streams/<app>/<stream>/trace")]
+pub(crate) async fn trace(
+    ctx: &State<HttpServerContext>,
+    app: &str,
+    stream: &str,
+) -> HttpServerResult<Json<Vec<TraceRecord>>> {
+    let stream_id = super::stream_identity(app, stream)?;
+    let records = StreamCenter::trace(&ctx.stream_center_event_sender, &stream_id)
+        .await
+        .map_err(|err| match err {
+            StreamCenterError::StreamNotFound(id) => {
+                HttpServerError::NotFound(format!("stream not found: {}", id))
+            }
+            StreamCenterError::TracingDisabled => {
+                HttpServerError::NotFound("pipeline tracing is disabled".to_string())
+            }
+            _ => HttpServerError::InternalError("internal error".to_string()),
+        })?;
+    Ok(Json(records))
+}
@@ -0,0 +1,100 @@
+use rocket::{
+    State, post,
+    serde::{Deserialize, json::Json},
+};
+
+use crate::{
+    errors::{HttpServerError, HttpServerResult},
+    server::HttpServerContext,
+    sessions::vod::source::{FlvFileSource, FlvFileSourceConfig, resolve_vod_path},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct VodStartRequest {
+    /// relative to the configured vod directory
+    path: String,
+    app: String,
+    stream: String,
+    speed: Option<f64>,
+    scale: Option<f64>,
+    start_offset_ms: Option<u64>,
+    #[serde(rename = "loop")]
+    loop_playback: Option<bool>,
+    idle_timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct VodStopRequest {
+    app: String,
+    stream: String,
+}
+
+#[post("/vod/start", data = "<request>")]
+pub(crate) async fn start(
+    ctx: &State<HttpServerContext>,
+    request: Json<VodStartRequest>,
+) -> HttpServerResult<()> {
+    tracing::info!("get vod start request: {:?}", request);
+    let Some(vod_dir) = ctx.config.vod_dir.as_ref() else {
+        return Err(HttpServerError::NotFound(
+            "vod is not enabled on this server".to_string(),
+        ));
+    };
+    if request.app.is_empty() || request.stream.is_empty() {
+        return Err(HttpServerError::BadRequest(format!(
+            "bad app and stream, app: {}, stream: {}",
+            request.app, request.stream
+        )));
+    }
+    let stream_id = super::stream_identity(&request.app, &request.stream)?;
+    if ctx.vod_sources.is_running(&stream_id) {
+        return Err(HttpServerError::BadRequest(format!(
+            "vod is already playing to stream: {}",
+            stream_id
+        )));
+    }
+
+    let default_config = FlvFileSourceConfig::default();
+    let config = FlvFileSourceConfig {
+        speed: request.speed.unwrap_or(default_config.speed),
+        scale: request.scale.unwrap_or(default_config.scale),
+        start_offset_ms: request
+            .start_offset_ms
+            .unwrap_or(default_config.start_offset_ms),
+        loop_playback: request
+            .loop_playback
+            .unwrap_or(default_config.loop_playback),
+        idle_timeout_ms: request
+            .idle_timeout_ms
+            .unwrap_or(default_config.idle_timeout_ms),
+    };
+    let path = resolve_vod_path(vod_dir, &request.path)?;
+    let source = FlvFileSource::open(
+        &path,
+        stream_id,
+        config,
+        ctx.stream_center_event_sender.clone(),
+    )
+    .await?
+    .restorable(request.path.clone());
+    ctx.vod_sources.start(source).await?;
+    Ok(())
+}
+
+#[post("/vod/stop", data = "<request>")]
+pub(crate) async fn stop(
+    ctx: &State<HttpServerContext>,
+    request: Json<VodStopRequest>,
+) -> HttpServerResult<()> {
+    tracing::info!("get vod stop request: {:?}", request);
+    let stream_id = super::stream_identity(&request.app, &request.stream)?;
+    if !ctx.vod_sources.stop(&stream_id) {
+        return Err(HttpServerError::NotFound(format!(
+            "no vod is playing to stream: {}",
+            stream_id
+        )));
+    }
+    Ok(())
+}
@@ -1,18 +1,34 @@
 use figment::{Figment, providers::Serialized};
-use rocket::{Config, config::Ident, routes};
-use stream_center::events::StreamCenterEvent;
+use rocket::{Build, Config, Rocket, config::Ident, routes};
+use server_utils::{
+    egress_shaping::EgressShaper, relay::RelayRegistry, session_registry::SessionRegistry,
+    supervisor::SupervisedTasks,
+};
+use stream_center::{events::StreamCenterEvent, snapshot::SourceSnapshot};
 use tokio::sync::mpsc;
 
 use crate::{
     config::HttpServerConfig,
     errors::HttpServerResult,
+    events::EventLog,
     routes::{self, hello::hello},
+    sessions::vod::source::VodSourceRegistry,
 };
 
 #[derive(Clone)]
 pub struct HttpServerContext {
     pub config: HttpServerConfig,
     pub stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    pub vod_sources: VodSourceRegistry,
+    pub egress_shaper: EgressShaper,
+    /// the sessions of all the servers, listed and closed by the admin api
+    pub session_registry: SessionRegistry,
+    /// the relays changed by the admin api, None if relays are not enabled
+    pub relays: Option<RelayRegistry>,
+    /// the events streamed to the dashboard, None if they are not enabled
+    pub event_log: Option<EventLog>,
+    /// the server tasks run under watch, None if nothing watches them
+    pub supervised_tasks: Option<SupervisedTasks>,
 }
 
 pub struct HttpServer {
@@ -22,34 +38,108 @@ pub struct HttpServer {
 impl HttpServer {
     pub fn new(
         config: HttpServerConfig,
+        egress_shaper: EgressShaper,
+        session_registry: SessionRegistry,
         stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
     ) -> Self {
         Self {
             context: HttpServerContext {
                 config,
                 stream_center_event_sender,
+                vod_sources: VodSourceRegistry::default(),
+                egress_shaper,
+                session_registry,
+                relays: None,
+                event_log: None,
+                supervised_tasks: None,
             },
         }
     }
 
-    pub async fn run(&mut self) -> HttpServerResult<()> {
-        tracing::info!("http server is running, config: {:?}", self.context.config);
+    pub fn with_relays(mut self, relays: RelayRegistry) -> Self {
+        self.context.relays = Some(relays);
+        self
+    }
+
+    /// the log is fed by whoever runs the stream center, see `EventLog::run`
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.context.event_log = Some(event_log);
+        self
+    }
+
+    pub fn with_supervised_tasks(mut self, supervised_tasks: SupervisedTasks) -> Self {
+        self.context.supervised_tasks = Some(supervised_tasks);
+        self
+    }
+
+    /// the rocket instance served by `run`,
+    /// tests can dispatch requests to it in process with a local client
+    pub fn build(&self) -> Rocket<Build> {
         let figment = Figment::from(Config {
             log_level: rocket::config::LogLevel::Off,
             ident: Ident::try_new("yam_server/http").unwrap(),
-            ip_header: Some("X-Real-IP".into()),
+            // anyone may set the header, the client addr guard trusts the proxies only
+            ip_header: None,
             keep_alive: 5,
             ..Default::default()
         })
         .merge(Serialized::defaults(&self.context.config));
 
-        match rocket::custom(figment)
+        rocket::custom(figment)
             .manage(self.context.clone())
+            .mount("/", routes![routes::metrics::metrics])
             .mount("/rest/v1", routes![hello])
             .mount("/live_stream/v1", routes![routes::httpflv::serve])
-            .launch()
-            .await
-        {
+            .mount(
+                "/api",
+                routes![
+                    routes::vod::start,
+                    routes::vod::stop,
+                    routes::trace::trace,
+                    routes::stats::stats,
+                    routes::stats::tasks,
+                    routes::keyframe::keyframe,
+                    routes::admin::drain,
+                    routes::admin::aliases,
+                    routes::admin::point_alias,
+                    routes::admin::remove_alias,
+                    routes::sessions::list,
+                    routes::sessions::close,
+                    routes::capture::list,
+                    routes::capture::start_session,
+                    routes::capture::stop_session,
+                    routes::capture::start_stream,
+                    routes::capture::stop_stream,
+                    routes::relays::list,
+                    routes::relays::add,
+                    routes::relays::remove,
+                    routes::events::events,
+                    routes::audio_track::switch
+                ],
+            )
+    }
+
+    /// plays the sources of a restored snapshot again, to be run along with the server
+    pub fn restore_sources(
+        &self,
+        sources: Vec<SourceSnapshot>,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let context = self.context.clone();
+        async move {
+            context
+                .vod_sources
+                .restore_all(
+                    context.config.vod_dir.as_deref(),
+                    sources,
+                    &context.stream_center_event_sender,
+                )
+                .await
+        }
+    }
+
+    pub async fn run(&mut self) -> HttpServerResult<()> {
+        tracing::info!("http server is running, config: {:?}", self.context.config);
+        match self.build().launch().await {
             Ok(res) => {
                 tracing::info!(
                     "http server exit successfully, config: {:?}",
@@ -9,10 +9,10 @@ pub enum HttpFlvSessionError {
     #[error("io error: {0}")]
     Io(#[from] io::Error),
     #[error("stream event channel send failed: {0:?}")]
-    StreamEventSendFailed(Option<StreamCenterEvent>),
-    #[error("stream center process event failed: {0:?}")]
+    StreamEventSendFailed(Option<Box<StreamCenterEvent>>),
+    #[error("stream center process event failed: {0}")]
     StreamCenterError(#[from] StreamCenterError),
-    #[error("process flv tag bytes failed: {0:?}")]
+    #[error("process flv tag bytes failed: {0}")]
     FlvError(#[from] FLVError),
 }
 
@@ -1,20 +1,17 @@
 use super::errors::HttpFlvSessionResult;
-use crate::routes::params::{AUDIO_ONLY_KEY, VIDEO_ONLY_KEY};
 use byteorder::{BigEndian, WriteBytesExt};
 use codec_common::video::{H264VideoConfig, VideoConfig};
-use flv_formats::{header::FLVHeader, tag::flv_tag_header::FLVTagHeader};
-use num::ToPrimitive;
-use server_utils::stream_properities::StreamProperties;
-use std::io;
+use flv_formats::header::FLVHeader;
+use server_utils::{egress_shaping::SubscriberShaper, stream_properities::StreamProperties};
 use stream_center::{
     events::{StreamCenterEvent, SubscribeResponse},
     gop::MediaFrame,
+    serialized::{FlvTimestampRebase, SerializedFlavor, SerializedFrameCache},
     stream_center::StreamCenter,
-    stream_source::{PlayProtocol, StreamIdentifier},
+    stream_source::{MediaSelection, PlayProtocol},
 };
 use tokio::sync::mpsc;
-use tokio_util::bytes::BytesMut;
-use utils::traits::fixed_packet::FixedPacket;
+use tokio_util::bytes::Bytes;
 use utils::traits::writer::WriteTo;
 use uuid::Uuid;
 
@@ -30,9 +27,13 @@ pub struct HttpFlvSession {
     _config: HttpFlvSessionConfig,
     stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
     stream_properties: StreamProperties,
+    media_selection: MediaSelection,
+    shaper: SubscriberShaper,
     play_id: Option<Uuid>,
-    http_response_bytes_sender: mpsc::UnboundedSender<BytesMut>,
+    http_response_bytes_sender: mpsc::UnboundedSender<Bytes>,
     nalu_length_size: Option<u8>,
+    /// the tags are shared with the other viewers of the stream, the timestamps are patched per viewer
+    timestamp_rebase: FlvTimestampRebase,
 
     has_video: bool,
     has_audio: bool,
@@ -43,13 +44,18 @@ impl HttpFlvSession {
         config: HttpFlvSessionConfig,
         stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
         stream_properties: StreamProperties,
-        http_response_bytes_sender: mpsc::UnboundedSender<BytesMut>,
+        media_selection: MediaSelection,
+        shaper: SubscriberShaper,
+        http_response_bytes_sender: mpsc::UnboundedSender<Bytes>,
     ) -> Self {
         Self {
             _config: config.clone(),
             nalu_length_size: None,
+            timestamp_rebase: FlvTimestampRebase::default(),
             stream_center_event_sender,
             stream_properties,
+            media_selection,
+            shaper,
             play_id: None,
             http_response_bytes_sender,
             has_audio: true,
@@ -62,34 +68,55 @@ impl HttpFlvSession {
         mut response: SubscribeResponse,
     ) -> HttpFlvSessionResult<()> {
         self.play_id = Some(response.subscribe_id);
-        self.has_video = self
-            .stream_properties
-            .stream_context
-            .get(AUDIO_ONLY_KEY)
-            .map_or_else(|| true, |_| false)
-            && response.has_video;
-        self.has_audio = self
-            .stream_properties
-            .stream_context
-            .get(VIDEO_ONLY_KEY)
-            .map_or_else(|| true, |_| false)
-            && response.has_audio;
+        self.has_video = self.media_selection.video && response.has_video;
+        self.has_audio = self.media_selection.audio && response.has_audio;
 
-        let mut bytes = Vec::with_capacity(4096);
+        // the pieces of the response body sent along with the next frame
+        let mut pieces = Vec::with_capacity(4);
 
         {
             let flv_file_header = FLVHeader::new(self.has_audio, self.has_video);
-            bytes.reserve(9 + 4);
+            let mut bytes = Vec::with_capacity(9 + 4);
             flv_file_header.write_to(&mut bytes)?;
             bytes.write_u32::<BigEndian>(0)?;
+            pieces.push(Bytes::from(bytes));
         }
 
         let mut has_video_sequence_header = !self.has_video;
         let mut has_audio_sequence_header = !self.has_audio;
+        // a video sequence header arrived mid-stream, written right before the next key frame
+        let mut pending_video_config: Option<MediaFrame> = None;
         loop {
             match response.media_receiver.recv().await {
-                None => {}
+                None => return Ok(()),
+                // the frames before it are sent already, dropping the response sender ends the flv body.
+                // a stalled publisher ends it too, the player reconnects
+                Some(MediaFrame::EndOfStream { reason, .. }) => {
+                    tracing::info!(
+                        "end the http flv response, reason: {}. stream: {:?}",
+                        reason,
+                        self.stream_properties
+                    );
+                    return Ok(());
+                }
+                Some(MediaFrame::SourceError { code, detail, .. }) => {
+                    tracing::error!(
+                        "end the http flv response, stream failed: {}, {}. stream: {:?}",
+                        code,
+                        detail,
+                        self.stream_properties
+                    );
+                    return Ok(());
+                }
                 Some(frame) => {
+                    if has_video_sequence_header
+                        && self.has_video
+                        && frame.is_video()
+                        && frame.is_sequence_header()
+                    {
+                        pending_video_config = Some(frame);
+                        continue;
+                    }
                     if !has_audio_sequence_header {
                         has_audio_sequence_header = frame.is_audio() && frame.is_sequence_header();
                     }
@@ -105,12 +132,29 @@ impl HttpFlvSession {
                         continue;
                     }
 
-                    self.write_flv_tag(frame, &mut bytes)?;
+                    // over the bitrate cap, the video is dropped up to the next key frame
+                    if !self.shaper.admit(&frame) {
+                        continue;
+                    }
+                    if frame.is_video_key_frame()
+                        && let Some(video_config) = pending_video_config.take()
+                    {
+                        self.write_flv_tag(
+                            &video_config,
+                            &response.serialized_frames,
+                            &mut pieces,
+                        )?;
+                    }
 
-                    let res = self
-                        .http_response_bytes_sender
-                        .send(BytesMut::from(&bytes[..]));
-                    bytes.clear();
+                    let ingest_time = frame.get_ingest_time();
+                    self.write_flv_tag(&frame, &response.serialized_frames, &mut pieces)?;
+
+                    let res = pieces
+                        .drain(..)
+                        .try_for_each(|bytes| self.http_response_bytes_sender.send(bytes));
+                    if let Some(probe) = &response.latency_probe {
+                        probe.on_frame_sent(ingest_time);
+                    }
                     if res.is_err() {
                         tracing::error!(
                             "send http response bytes to http request handler failed: {:?},
@@ -125,15 +169,17 @@ impl HttpFlvSession {
         }
     }
 
-    pub fn write_flv_tag<W: io::Write>(
+    /// the tag is taken from the serialized frames of the stream if another viewer serialized it
+    pub fn write_flv_tag(
         &mut self,
-        frame: MediaFrame,
-        bytes_buffer: &mut W,
+        frame: &MediaFrame,
+        serialized_frames: &SerializedFrameCache,
+        pieces: &mut Vec<Bytes>,
     ) -> HttpFlvSessionResult<()> {
         if let MediaFrame::VideoConfig {
             timestamp_nano: _,
             config,
-        } = &frame
+        } = frame
         {
             self.nalu_length_size = match config.as_ref() {
                 VideoConfig::H264(H264VideoConfig {
@@ -144,16 +190,28 @@ impl HttpFlvSession {
                 }) => avc_decoder_configuration_record
                     .as_ref()
                     .map(|v| v.length_size_minus_one.checked_add(1).unwrap()),
+                // obus are not length prefixed
+                VideoConfig::AV1(_) => None,
             }
         }
-        let tag = frame.to_flv_tag(self.nalu_length_size.unwrap_or(4))?;
-        tag.write_to(bytes_buffer)?;
-        bytes_buffer.write_u32::<BigEndian>(
-            tag.tag_header
-                .data_size
-                .checked_add(FLVTagHeader::bytes_count().to_u32().unwrap())
-                .unwrap(),
+        let nalu_length_size = self.nalu_length_size.unwrap_or(4);
+        let tag = serialized_frames.get_or_serialize(
+            frame,
+            SerializedFlavor::FlvTag { nalu_length_size },
+            |frame| frame.to_flv_tag_bytes(nalu_length_size),
         )?;
+        // the tag may come from the serialized frames, muxed by another viewer
+        #[cfg(feature = "frame-crc")]
+        stream_center::frame_crc::verify_flv_tag_bytes(
+            "httpflv_sink",
+            &self.stream_properties.stream_id.clone(),
+            frame,
+            &tag,
+            nalu_length_size,
+        );
+        let (tag, rest) = self.timestamp_rebase.rebase(frame, tag);
+        pieces.push(tag);
+        pieces.extend(rest);
         Ok(())
     }
 
@@ -164,10 +222,7 @@ impl HttpFlvSession {
         StreamCenter::unsubscribe(
             &self.stream_center_event_sender,
             self.play_id.unwrap(),
-            &StreamIdentifier {
-                stream_name: self.stream_properties.stream_name.clone(),
-                app: self.stream_properties.app.clone(),
-            },
+            &self.stream_properties.stream_id.clone(),
         )
         .await
         .map_err(|err| err.into())
@@ -177,11 +232,9 @@ impl HttpFlvSession {
         StreamCenter::subscribe(
             &self.stream_center_event_sender,
             PlayProtocol::HTTPFLV,
-            &StreamIdentifier {
-                stream_name: self.stream_properties.stream_name.clone(),
-                app: self.stream_properties.app.clone(),
-            },
+            &self.stream_properties.stream_id.clone(),
             &self.stream_properties.stream_context,
+            self.media_selection,
         )
         .await
         .map_err(|err| err.into())
@@ -1 +1,2 @@
 pub mod httpflv;
+pub mod vod;
@@ -0,0 +1,29 @@
+use std::io;
+
+use flv_formats::errors::{FLVError, FlvStreamError};
+use stream_center::errors::StreamCenterError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum VodError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("parse flv file failed: {0}")]
+    FlvError(#[from] FLVError),
+    #[error("read flv file failed: {0}")]
+    FlvStreamError(#[from] FlvStreamError),
+    #[error("stream center process event failed: {0}")]
+    StreamCenterError(#[from] StreamCenterError),
+    #[error("invalid vod file path: {0}")]
+    InvalidPath(String),
+    #[error("invalid playback speed: {0}")]
+    InvalidSpeed(f64),
+    #[error("invalid playback scale: {0}")]
+    InvalidScale(f64),
+    #[error("frame deadline out of range at playback rate: {0}")]
+    PacingOutOfRange(f64),
+    #[error("no media frame found in file")]
+    EmptyFile,
+}
+
+pub type VodResult<T> = Result<T, VodError>;
@@ -0,0 +1,5 @@
+pub mod errors;
+pub mod source;
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,768 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use codec_common::video::{H264VideoConfig, VideoConfig};
+use flv_formats::{
+    stream::{FlvReadMode, FlvRecoveryStats, FlvTagStream},
+    tag::on_meta_data::ScriptKeyframeInfo,
+};
+use num::ToPrimitive;
+use stream_center::{
+    events::{RecordingPublishResponse, StreamCenterEvent},
+    gop::MediaFrame,
+    identity::StreamIdentity,
+    playback::is_valid_scale,
+    snapshot::{SourceDefinition, SourceSnapshot, SourceSnapshotter, VodSourceDefinition},
+    stream_center::StreamCenter,
+    stream_source::PublishProtocol,
+};
+use tokio::{
+    io::{AsyncRead, AsyncSeek},
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use utils::{error_chain::ErrorChainExt, frame_grid::FrameGrid};
+use uuid::Uuid;
+
+use super::errors::{VodError, VodResult};
+
+/// how often the stream center is asked whether anyone is still watching
+const SUBSCRIBER_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// gap between the last frame of a loop and the first frame of the next one,
+/// used when the file tells no frame interval or frame rate
+const DEFAULT_LOOP_GAP_NANO: u64 = 40_000_000;
+
+#[derive(Debug, Clone)]
+pub struct FlvFileSourceConfig {
+    /// playback speed factor, 1.0 plays in realtime
+    pub speed: f64,
+    /// trick play, above 1.0 or below 0 only key frames are delivered,
+    /// negative ones play backwards from the start offset, or from the end if it is 0
+    pub scale: f64,
+    /// playback starts from the keyframe nearest to this offset
+    pub start_offset_ms: u64,
+    /// restart from the first media tag when the end of file is reached
+    pub loop_playback: bool,
+    /// stop if nobody subscribes within this duration
+    pub idle_timeout_ms: u64,
+}
+
+impl Default for FlvFileSourceConfig {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            scale: 1.0,
+            start_offset_ms: 0,
+            loop_playback: false,
+            idle_timeout_ms: 30_000,
+        }
+    }
+}
+
+/// resolves a path from the vod api against the vod directory,
+/// anything that could escape the directory is rejected
+pub fn resolve_vod_path(vod_dir: &Path, path: &str) -> VodResult<PathBuf> {
+    let relative = Path::new(path);
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(VodError::InvalidPath(path.to_owned()));
+    }
+    Ok(vod_dir.join(relative))
+}
+
+#[derive(Debug)]
+pub struct PacedFrame {
+    pub frame: MediaFrame,
+    /// when the frame should be delivered
+    pub deadline: Instant,
+}
+
+/// Demuxes an flv file and schedules its frames by tag timestamps.
+/// The damage files of other tools often carry, wrong PreviousTagSize fields and garbage between tags,
+/// is skipped over.
+/// Output timestamps start from 0 at the playback start and keep increasing across loops,
+/// scale changes and backward playback, they advance by the media time played.
+#[derive(Debug)]
+pub struct FlvFilePlayer<R> {
+    reader: FlvTagStream<R>,
+    speed: f64,
+    scale: f64,
+    loop_playback: bool,
+    /// metadata and sequence headers, delivered before any other frame
+    pending_frames: VecDeque<MediaFrame>,
+    /// offset of the first tag after the leading sequence headers, where a loop restarts
+    first_media_position: u64,
+    nalu_size_length: u8,
+    /// tag positions of the key frames, from the metadata or scanned for the first backward playback
+    keyframe_positions: Option<Vec<u64>>,
+    /// position of the latest tag read, or where the playback starts
+    position: Option<u64>,
+    /// key frames passed by a fast forward, every stride-th one is delivered
+    keyframes_seen: u64,
+    /// index into keyframe_positions of the key frame a backward playback delivered last
+    reverse_cursor: Option<usize>,
+
+    start_instant: Option<Instant>,
+    /// the instant an output timestamp is delivered at, deadlines of later frames follow from it
+    pace_origin: Option<(Instant, u64)>,
+    /// the file timestamp that maps to the start of the current loop
+    base_timestamp_nano: Option<u64>,
+    loop_offset_nano: u64,
+    last_timestamp_nano: u64,
+    last_frame_gap_nano: u64,
+    /// the grid of the fixed frame rate the sps declares, the video frames played at scale 1
+    /// are paced on it rather than by their timestamps, which some muxers quantize to milliseconds
+    frame_grid: Option<FrameGrid>,
+    frames_since_restart: u64,
+    /// file timestamp of the latest media frame read, or the start offset
+    file_timestamp_nano: u64,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> FlvFilePlayer<R> {
+    pub async fn new(reader: R, config: &FlvFileSourceConfig) -> VodResult<Self> {
+        if !config.speed.is_finite() || config.speed <= 0.0 {
+            return Err(VodError::InvalidSpeed(config.speed));
+        }
+        if !is_valid_scale(config.scale) {
+            return Err(VodError::InvalidScale(config.scale));
+        }
+        let mut player = Self {
+            reader: FlvTagStream::new(reader)
+                .await?
+                .with_read_mode(FlvReadMode::Recover),
+            speed: config.speed,
+            scale: config.scale,
+            loop_playback: config.loop_playback,
+            pending_frames: VecDeque::new(),
+            first_media_position: 0,
+            nalu_size_length: 4,
+            keyframe_positions: None,
+            position: None,
+            keyframes_seen: 0,
+            reverse_cursor: None,
+            start_instant: None,
+            pace_origin: None,
+            base_timestamp_nano: None,
+            loop_offset_nano: 0,
+            last_timestamp_nano: 0,
+            last_frame_gap_nano: 0,
+            frame_grid: None,
+            frames_since_restart: 0,
+            file_timestamp_nano: config.start_offset_ms.saturating_mul(1_000_000),
+        };
+        let keyframes = player.read_leading_frames().await?;
+        if let Some(keyframes) = keyframes.as_ref().filter(|keyframes| !keyframes.is_empty()) {
+            player.keyframe_positions = Some(
+                keyframes
+                    .iter()
+                    .filter_map(|keyframe| keyframe.file_position.to_u64())
+                    .filter(|position| *position >= player.first_media_position)
+                    .collect(),
+            );
+        }
+        let start_position = if config.start_offset_ms > 0 {
+            let position = match keyframes {
+                Some(keyframes) if !keyframes.is_empty() => {
+                    // the positions in the index of some muxers are a few bytes off
+                    let position = player.seek_by_index(&keyframes, config.start_offset_ms);
+                    player.reader.seek_and_resync(position).await?
+                }
+                _ => player.seek_by_scan(config.start_offset_ms).await?,
+            };
+            player.position = Some(position);
+            position
+        } else {
+            player.first_media_position
+        };
+        tracing::info!(
+            "flv file player start position: {}, start offset: {}ms",
+            start_position,
+            config.start_offset_ms
+        );
+        player.reader.seek(start_position).await?;
+        Ok(player)
+    }
+
+    /// collects metadata and sequence headers until the first media frame
+    async fn read_leading_frames(&mut self) -> VodResult<Option<Vec<ScriptKeyframeInfo>>> {
+        let mut keyframes = None;
+        loop {
+            let Some((position, tag)) = self.reader.next_tag().await? else {
+                return Err(VodError::EmptyFile);
+            };
+            let mut frame = MediaFrame::from_flv_tag(tag, self.nalu_size_length)?;
+            if let MediaFrame::Script { on_meta_data, .. } = &frame {
+                if let Some(on_meta_data) = on_meta_data.as_ref() {
+                    keyframes = on_meta_data.keyframes.clone();
+                }
+            } else if !frame.is_sequence_header() {
+                self.first_media_position = position;
+                return Ok(keyframes);
+            }
+            self.update_video_config(&frame);
+            frame.set_decode_timestamp_ns(0);
+            frame.set_presentation_timestamp_ns(0);
+            self.pending_frames.push_back(frame);
+        }
+    }
+
+    /// picks the last indexed keyframe not later than the offset
+    fn seek_by_index(&self, keyframes: &[ScriptKeyframeInfo], start_offset_ms: u64) -> u64 {
+        keyframes
+            .iter()
+            .filter(|keyframe| keyframe.time * 1000.0 <= start_offset_ms as f64)
+            .filter_map(|keyframe| keyframe.file_position.to_u64())
+            .rfind(|position| *position >= self.first_media_position)
+            .unwrap_or(self.first_media_position)
+    }
+
+    /// picks the last keyframe not later than the offset by walking through the tags
+    async fn seek_by_scan(&mut self, start_offset_ms: u64) -> VodResult<u64> {
+        // an offset past the end of the file starts at the last keyframe
+        let offset_nano = start_offset_ms.saturating_mul(1_000_000);
+        let mut result = self.first_media_position;
+        let mut first_timestamp_nano = None;
+        self.reader.seek(self.first_media_position).await?;
+        while let Some((position, tag)) = self.reader.next_tag().await? {
+            let frame = MediaFrame::from_flv_tag(tag, self.nalu_size_length)?;
+            self.update_video_config(&frame);
+            let first = *first_timestamp_nano.get_or_insert(frame.get_decode_timestamp_ns());
+            if frame.get_decode_timestamp_ns().saturating_sub(first) > offset_nano {
+                break;
+            }
+            if frame.is_video_key_frame() {
+                result = position;
+            }
+        }
+        Ok(result)
+    }
+
+    fn update_video_config(&mut self, frame: &MediaFrame) {
+        if let MediaFrame::VideoConfig { config, .. } = frame {
+            let frame_rate = config.nominal_frame_rate();
+            if self.frame_grid.as_ref().map(FrameGrid::frame_rate) != frame_rate {
+                self.frame_grid = frame_rate.and_then(FrameGrid::new);
+            }
+            match config.as_ref() {
+                VideoConfig::H264(H264VideoConfig {
+                    avc_decoder_configuration_record: Some(record),
+                    ..
+                }) => {
+                    self.nalu_size_length = record.length_size_minus_one.checked_add(1).unwrap();
+                }
+                VideoConfig::H264(_) | VideoConfig::AV1(_) => {}
+            }
+        }
+    }
+
+    /// the next frame read gets the output timestamp right after the last one
+    fn reanchor(&mut self) {
+        self.loop_offset_nano = self
+            .last_timestamp_nano
+            .checked_add(match &self.frame_grid {
+                Some(frame_grid) => frame_grid.interval_nano().round() as u64,
+                None if self.last_frame_gap_nano > 0 => self.last_frame_gap_nano,
+                None => DEFAULT_LOOP_GAP_NANO,
+            })
+            .unwrap();
+        self.base_timestamp_nano = None;
+        self.frames_since_restart = 0;
+    }
+
+    async fn restart(&mut self) -> VodResult<()> {
+        self.reanchor();
+        self.reader.seek(self.first_media_position).await?;
+        tracing::info!(
+            "flv file player loops back, timestamp offset: {}ns",
+            self.loop_offset_nano
+        );
+        Ok(())
+    }
+
+    /// the damage of the file skipped so far
+    pub fn recovery_stats(&self) -> FlvRecoveryStats {
+        self.reader.recovery_stats()
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// where the playback is in the file, a playback started from it goes on from the key frame before
+    pub fn position_ms(&self) -> u64 {
+        self.file_timestamp_nano / 1_000_000
+    }
+
+    /// a fast forward or backward playback delivers every stride-th key frame,
+    /// so about one key frame goes out per gop duration whatever the scale
+    fn keyframe_stride(&self) -> u64 {
+        self.scale.abs().ceil().max(1.0) as u64
+    }
+
+    /// the playback goes on from where it is at the new scale,
+    /// timestamps keep increasing and the pacing restarts from now
+    pub fn set_scale(&mut self, scale: f64) -> VodResult<()> {
+        if !is_valid_scale(scale) {
+            return Err(VodError::InvalidScale(scale));
+        }
+        if scale == self.scale {
+            return Ok(());
+        }
+        tracing::info!(
+            "flv file player scale changes from {} to {}",
+            self.scale,
+            scale
+        );
+        self.scale = scale;
+        self.keyframes_seen = 0;
+        self.reverse_cursor = None;
+        if self.frames_since_restart > 0 {
+            self.reanchor();
+            self.pace_origin = Some((Instant::now(), self.loop_offset_nano));
+        }
+        Ok(())
+    }
+
+    async fn next_forward_frame(&mut self) -> VodResult<Option<MediaFrame>> {
+        let fast_forward = self.scale > 1.0;
+        loop {
+            let frame = match self.reader.next_tag().await? {
+                Some((position, tag)) => {
+                    self.position = Some(position);
+                    MediaFrame::from_flv_tag(tag, self.nalu_size_length)?
+                }
+                None => {
+                    if let Some(position) = self.reader.truncated_at() {
+                        tracing::warn!("flv file truncated at tag position {}", position);
+                    }
+                    let recovery = self.reader.recovery_stats();
+                    if recovery != FlvRecoveryStats::default() {
+                        tracing::warn!(
+                            "flv file damaged, {} wrong previous tag sizes, {} bytes of garbage skipped in {} places",
+                            recovery.previous_tag_size_mismatches,
+                            recovery.skipped_bytes,
+                            recovery.resyncs
+                        );
+                    }
+                    if !self.loop_playback || self.frames_since_restart == 0 {
+                        return Ok(None);
+                    }
+                    self.restart().await?;
+                    continue;
+                }
+            };
+            self.update_video_config(&frame);
+            if !fast_forward || frame.is_sequence_header() {
+                return Ok(Some(frame));
+            }
+            if !frame.is_video_key_frame() {
+                continue;
+            }
+            self.keyframes_seen += 1;
+            if (self.keyframes_seen - 1).is_multiple_of(self.keyframe_stride()) {
+                return Ok(Some(frame));
+            }
+        }
+    }
+
+    async fn scan_keyframe_positions(&mut self) -> VodResult<Vec<u64>> {
+        let mut positions = vec![];
+        self.reader.seek(self.first_media_position).await?;
+        while let Some((position, tag)) = self.reader.next_tag().await? {
+            let frame = MediaFrame::from_flv_tag(tag, self.nalu_size_length)?;
+            self.update_video_config(&frame);
+            if frame.is_video_key_frame() {
+                positions.push(position);
+            }
+        }
+        Ok(positions)
+    }
+
+    /// walks the key frame index backwards, the playback is over at the first key frame
+    async fn next_reverse_frame(&mut self) -> VodResult<Option<MediaFrame>> {
+        if self.keyframe_positions.is_none() {
+            self.keyframe_positions = Some(self.scan_keyframe_positions().await?);
+        }
+        let positions = self.keyframe_positions.as_ref().unwrap();
+        let cursor = match self.reverse_cursor {
+            Some(cursor) => cursor.checked_sub(self.keyframe_stride().to_usize().unwrap()),
+            // from the key frame at or before the playback position, or from the last one
+            None => match self.position {
+                Some(position) => positions.iter().rposition(|v| *v <= position),
+                None => positions.len().checked_sub(1),
+            },
+        };
+        let Some((cursor, position)) =
+            cursor.and_then(|cursor| Some((cursor, *positions.get(cursor)?)))
+        else {
+            return Ok(None);
+        };
+        self.reverse_cursor = Some(cursor);
+        self.position = Some(position);
+        self.reader.seek_and_resync(position).await?;
+        let Some((_, tag)) = self.reader.next_tag().await? else {
+            return Ok(None);
+        };
+        Ok(Some(MediaFrame::from_flv_tag(tag, self.nalu_size_length)?))
+    }
+
+    /// returns the next frame and its delivery time without waiting for it,
+    /// None means the playback is over
+    pub async fn next_frame(&mut self) -> VodResult<Option<PacedFrame>> {
+        let start_instant = *self.start_instant.get_or_insert_with(Instant::now);
+        if let Some(frame) = self.pending_frames.pop_front() {
+            return Ok(Some(PacedFrame {
+                frame,
+                deadline: start_instant,
+            }));
+        }
+
+        let frame = if self.scale < 0.0 {
+            self.next_reverse_frame().await?
+        } else {
+            self.next_forward_frame().await?
+        };
+        let Some(mut frame) = frame else {
+            return Ok(None);
+        };
+        self.frames_since_restart += 1;
+
+        let dts = frame.get_decode_timestamp_ns();
+        let pts = frame.get_presentation_timestamp_ns();
+        self.file_timestamp_nano = dts;
+        let base = *self.base_timestamp_nano.get_or_insert(dts);
+        // the media time played since the anchor, backwards or not
+        let played = if self.scale < 0.0 {
+            base.saturating_sub(dts)
+        } else {
+            dts.saturating_sub(base)
+        };
+        let output_dts = played.checked_add(self.loop_offset_nano).unwrap();
+        let output_pts = output_dts.checked_add(pts.saturating_sub(dts)).unwrap();
+        frame.set_decode_timestamp_ns(output_dts);
+        frame.set_presentation_timestamp_ns(output_pts);
+        if output_dts > self.last_timestamp_nano {
+            self.last_frame_gap_nano = output_dts - self.last_timestamp_nano;
+            self.last_timestamp_nano = output_dts;
+        }
+
+        // trick play delivers key frames only, they are off the grid
+        let paced_dts = match self.frame_grid.as_mut() {
+            Some(frame_grid) if self.scale == 1.0 && matches!(frame, MediaFrame::Video { .. }) => {
+                frame_grid.place(Some(output_dts))
+            }
+            _ => output_dts,
+        };
+        let (pace_instant, pace_timestamp_nano) =
+            *self.pace_origin.get_or_insert((start_instant, 0));
+        let rate = self.speed * self.scale.abs();
+        let deadline = Duration::try_from_secs_f64(
+            paced_dts.saturating_sub(pace_timestamp_nano) as f64 / 1_000_000_000.0 / rate,
+        )
+        .ok()
+        .and_then(|ahead| pace_instant.checked_add(ahead))
+        .ok_or(VodError::PacingOutOfRange(rate))?;
+        Ok(Some(PacedFrame { frame, deadline }))
+    }
+}
+
+/// the definition of a playing vod source, kept up to date for the snapshots of the stream center
+#[derive(Debug)]
+struct VodSnapshotter {
+    definition: Mutex<VodSourceDefinition>,
+}
+
+impl SourceSnapshotter for VodSnapshotter {
+    fn definition(&self) -> SourceDefinition {
+        SourceDefinition::Vod(self.definition.lock().unwrap().clone())
+    }
+}
+
+/// Plays an flv file into the stream center as if it was published by a client.
+#[derive(Debug)]
+pub struct FlvFileSource<R> {
+    stream_id: StreamIdentity,
+    config: FlvFileSourceConfig,
+    player: FlvFilePlayer<R>,
+    stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    /// None if the source is not played again after a restart
+    snapshotter: Option<Arc<VodSnapshotter>>,
+}
+
+impl FlvFileSource<tokio::fs::File> {
+    pub async fn open(
+        path: &Path,
+        stream_id: StreamIdentity,
+        config: FlvFileSourceConfig,
+        stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    ) -> VodResult<Self> {
+        let file = tokio::fs::File::open(path).await?;
+        Self::new(file, stream_id, config, stream_center_event_sender).await
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> FlvFileSource<R> {
+    pub async fn new(
+        reader: R,
+        stream_id: StreamIdentity,
+        config: FlvFileSourceConfig,
+        stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    ) -> VodResult<Self> {
+        let player = FlvFilePlayer::new(reader, &config).await?;
+        Ok(Self {
+            stream_id,
+            config,
+            player,
+            stream_center_event_sender,
+            snapshotter: None,
+        })
+    }
+
+    /// snapshotted along with the stream center, to be played again from `path` in the vod directory
+    /// at the position reached after a restart
+    pub fn restorable(mut self, path: String) -> Self {
+        self.snapshotter = Some(Arc::new(VodSnapshotter {
+            definition: Mutex::new(VodSourceDefinition {
+                path,
+                speed: self.config.speed,
+                scale: self.player.scale(),
+                loop_playback: self.config.loop_playback,
+                idle_timeout_ms: self.config.idle_timeout_ms,
+                position_ms: self.player.position_ms(),
+            }),
+        }));
+        self
+    }
+
+    pub fn stream_id(&self) -> &StreamIdentity {
+        &self.stream_id
+    }
+
+    /// published as a recording, so that viewers can change the playback scale
+    pub async fn publish(&self) -> VodResult<RecordingPublishResponse> {
+        let response = match &self.snapshotter {
+            Some(snapshotter) => {
+                StreamCenter::publish_restorable_recording(
+                    &self.stream_center_event_sender,
+                    PublishProtocol::VOD,
+                    &self.stream_id,
+                    &HashMap::new(),
+                    snapshotter.clone(),
+                )
+                .await?
+            }
+            None => {
+                StreamCenter::publish_recording(
+                    &self.stream_center_event_sender,
+                    PublishProtocol::VOD,
+                    &self.stream_id,
+                    &HashMap::new(),
+                )
+                .await?
+            }
+        };
+        Ok(response)
+    }
+
+    fn update_snapshot(&self) {
+        if let Some(snapshotter) = &self.snapshotter {
+            let mut definition = snapshotter.definition.lock().unwrap();
+            definition.scale = self.player.scale();
+            definition.position_ms = self.player.position_ms();
+        }
+    }
+
+    pub async fn unpublish(&mut self) -> VodResult<()> {
+        Ok(StreamCenter::unpublish(&self.stream_center_event_sender, &self.stream_id).await?)
+    }
+
+    /// delivers frames until the end of file, an explicit stop, the last subscriber leaving,
+    /// or nobody subscribing within the idle timeout
+    pub async fn serve(
+        &mut self,
+        publish: RecordingPublishResponse,
+        mut stop_receiver: oneshot::Receiver<()>,
+    ) -> VodResult<()> {
+        let RecordingPublishResponse {
+            media_sender,
+            mut scale_receiver,
+        } = publish;
+        let idle_deadline = Instant::now() + Duration::from_millis(self.config.idle_timeout_ms);
+        let mut subscriber_check = tokio::time::interval(SUBSCRIBER_CHECK_INTERVAL);
+        let mut had_subscriber = false;
+        let mut next = self.player.next_frame().await?;
+        while let Some(PacedFrame { frame, deadline }) = next.take() {
+            tokio::select! {
+                _ = &mut stop_receiver => {
+                    tracing::info!("vod source stopped: {}", self.stream_id);
+                    return Ok(());
+                }
+                _ = subscriber_check.tick() => {
+                    let description =
+                        StreamCenter::describe(&self.stream_center_event_sender, &self.stream_id)
+                            .await?;
+                    if !description.subscribers.is_empty() {
+                        had_subscriber = true;
+                    } else if had_subscriber {
+                        tracing::info!("last subscriber left, vod source stops: {}", self.stream_id);
+                        return Ok(());
+                    } else if Instant::now() >= idle_deadline {
+                        tracing::info!("no subscriber, vod source stops: {}", self.stream_id);
+                        return Ok(());
+                    }
+                    next = Some(PacedFrame { frame, deadline });
+                    continue;
+                }
+                Some(scale) = scale_receiver.recv() => {
+                    self.player.set_scale(scale)?;
+                    self.update_snapshot();
+                    // read already, it goes out right away and the new scale paces from it
+                    next = Some(PacedFrame { frame, deadline: Instant::now() });
+                    continue;
+                }
+                _ = tokio::time::sleep_until(deadline) => {}
+            }
+            if media_sender.send(frame).await.is_err() {
+                tracing::info!("stream center closed the vod source: {}", self.stream_id);
+                return Ok(());
+            }
+            self.update_snapshot();
+            next = self.player.next_frame().await?;
+        }
+        tracing::info!("vod source reached the end of file: {}", self.stream_id);
+        Ok(())
+    }
+
+    pub async fn run(mut self, stop_receiver: oneshot::Receiver<()>) -> VodResult<()> {
+        let publish = self.publish().await?;
+        let result = self.serve(publish, stop_receiver).await;
+        if let Err(err) = self.unpublish().await {
+            tracing::error!("unpublish vod source failed: {}", err);
+        }
+        result
+    }
+}
+
+/// the run of a vod source and how to stop it
+type RunningVodSource = (Uuid, oneshot::Sender<()>);
+
+/// running vod sources, so that they can be stopped explicitly
+#[derive(Debug, Clone, Default)]
+pub struct VodSourceRegistry {
+    sources: Arc<Mutex<HashMap<StreamIdentity, RunningVodSource>>>,
+}
+
+impl VodSourceRegistry {
+    /// publishes the source and plays it in background
+    pub async fn start<R>(&self, mut source: FlvFileSource<R>) -> VodResult<()>
+    where
+        R: AsyncRead + AsyncSeek + Unpin + Send + 'static,
+    {
+        let publish = source.publish().await?;
+        let stream_id = source.stream_id().clone();
+        let id = Uuid::now_v7();
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        self.sources
+            .lock()
+            .unwrap()
+            .insert(stream_id.clone(), (id, stop_sender));
+
+        let registry = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = source.serve(publish, stop_receiver).await {
+                tracing::error!("vod source {} exit with error: {}", stream_id, err.chain());
+            }
+            if let Err(err) = source.unpublish().await {
+                tracing::error!("unpublish vod source {} failed: {}", stream_id, err);
+            }
+            let mut sources = registry.sources.lock().unwrap();
+            if sources
+                .get(&stream_id)
+                .is_some_and(|(current, _)| *current == id)
+            {
+                sources.remove(&stream_id);
+            }
+        });
+        Ok(())
+    }
+
+    /// plays the source of a restored snapshot again, from the position it reached
+    pub async fn restore(
+        &self,
+        vod_dir: &Path,
+        stream_id: StreamIdentity,
+        definition: VodSourceDefinition,
+        stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    ) -> VodResult<()> {
+        let path = resolve_vod_path(vod_dir, &definition.path)?;
+        let config = FlvFileSourceConfig {
+            speed: definition.speed,
+            scale: definition.scale,
+            start_offset_ms: definition.position_ms,
+            loop_playback: definition.loop_playback,
+            idle_timeout_ms: definition.idle_timeout_ms,
+        };
+        let source = FlvFileSource::open(&path, stream_id, config, stream_center_event_sender)
+            .await?
+            .restorable(definition.path);
+        self.start(source).await
+    }
+
+    /// restores the vod sources of a snapshot, the ones failing to start are logged and left
+    pub async fn restore_all(
+        &self,
+        vod_dir: Option<&Path>,
+        sources: Vec<SourceSnapshot>,
+        stream_center_event_sender: &mpsc::UnboundedSender<StreamCenterEvent>,
+    ) {
+        for source in sources {
+            let stream_id = source.stream_id();
+            let SourceDefinition::Vod(definition) = source.definition;
+            let Some(vod_dir) = vod_dir else {
+                tracing::warn!(
+                    "vod is not enabled on this server, source of stream {} is not restored",
+                    stream_id
+                );
+                continue;
+            };
+            match self
+                .restore(
+                    vod_dir,
+                    stream_id.clone(),
+                    definition,
+                    stream_center_event_sender.clone(),
+                )
+                .await
+            {
+                Ok(()) => tracing::info!("vod source of stream {} is restored", stream_id),
+                Err(err) => tracing::error!(
+                    "restore vod source of stream {} failed: {}",
+                    stream_id,
+                    err.chain()
+                ),
+            }
+        }
+    }
+
+    /// returns false if no source is playing to the stream
+    pub fn stop(&self, stream_id: &StreamIdentity) -> bool {
+        match self.sources.lock().unwrap().remove(stream_id) {
+            Some((_, stop_sender)) => {
+                let _ = stop_sender.send(());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self, stream_id: &StreamIdentity) -> bool {
+        self.sources.lock().unwrap().contains_key(stream_id)
+    }
+}
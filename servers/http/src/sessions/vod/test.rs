@@ -0,0 +1,772 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::Cursor,
+        path::Path,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::{Duration, SystemTime},
+    };
+
+    use byteorder::{BigEndian, WriteBytesExt};
+    use codec_common::{
+        FrameType, MediaFrameTimestamp,
+        audio::AudioCodecCommon,
+        video::{VideoCodecCommon, VideoConfig, VideoFrameInfo, VideoFrameUnit},
+    };
+    use codec_h264::{
+        avc_decoder_configuration_record::AvcDecoderConfigurationRecord, nalu::NalUnit,
+        nalu_header::NaluHeader, pps::Pps, sps::Sps, vui::TimingInfo,
+    };
+    use flv_formats::{
+        header::FLVHeader, stream::FlvRecoveryStats, tag::on_meta_data::ScriptKeyframeInfo,
+    };
+    use stream_center::{
+        events::{StreamCenterEvent, StreamDescription, SubscribeResponse, SubscriberInfo},
+        gop::MediaFrame,
+        identity::StreamIdentity,
+        make_fake_on_meta_data,
+        notification::StreamNotification,
+        snapshot::{SourceDefinition, StreamCenterSnapshot},
+        stream_center::StreamCenter,
+        stream_source::{MediaSelection, ParsedContext, PlayProtocol, PlayStat, PublishProtocol},
+    };
+    use tokio::{
+        sync::{mpsc, oneshot},
+        time::Instant,
+    };
+    use tokio_util::bytes::Bytes;
+    use utils::traits::{reader::ReadFrom, writer::WriteTo};
+    use uuid::Uuid;
+
+    use crate::sessions::vod::source::{
+        FlvFilePlayer, FlvFileSource, FlvFileSourceConfig, VodSourceRegistry, resolve_vod_path,
+    };
+
+    const FRAME_INTERVAL_MS: u64 = 40;
+    const GOP_SIZE: u64 = 50;
+
+    fn video_frame(index: u64) -> MediaFrame {
+        let (nal_header, frame_type) = if index.is_multiple_of(GOP_SIZE) {
+            (0x65, FrameType::KeyFrame)
+        } else {
+            (0x41, FrameType::CodedFrames)
+        };
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                frame_type,
+                MediaFrameTimestamp::with_timestamp_ms(index * FRAME_INTERVAL_MS),
+            ),
+            payload: VideoFrameUnit::H264 {
+                nal_units: vec![NalUnit {
+                    header: NaluHeader::try_from(nal_header).unwrap(),
+                    body: Bytes::copy_from_slice(&(index as u32).to_be_bytes()),
+                }],
+            },
+        }
+    }
+
+    fn frame_index(frame: &MediaFrame) -> u64 {
+        match frame {
+            MediaFrame::Video {
+                payload: VideoFrameUnit::H264 { nal_units },
+                ..
+            } => u32::from_be_bytes(nal_units[0].body[..4].try_into().unwrap()) as u64,
+            _ => panic!("not a video frame: {:?}", frame),
+        }
+    }
+
+    fn write_tag(buf: &mut Vec<u8>, frame: &MediaFrame) -> u64 {
+        let position = buf.len() as u64;
+        frame.to_flv_tag(4).unwrap().write_to(buf).unwrap();
+        let tag_size = buf.len() as u64 - position;
+        buf.write_u32::<BigEndian>(tag_size as u32).unwrap();
+        position
+    }
+
+    fn script_frame(keyframes: Vec<ScriptKeyframeInfo>) -> MediaFrame {
+        let mut on_meta_data =
+            make_fake_on_meta_data(AudioCodecCommon::AAC, VideoCodecCommon::AVC, 720.0, 1280.0);
+        on_meta_data.keyframes = Some(keyframes);
+        MediaFrame::Script {
+            timestamp_nano: 0,
+            on_meta_data: Box::new(Some(on_meta_data)),
+            payload: Bytes::new(),
+        }
+    }
+
+    /// a video only flv file of `frame_count` frames at 25 fps, a keyframe every 2 seconds
+    fn make_flv_file(frame_count: u64, with_keyframes_index: bool) -> Vec<u8> {
+        let build = |keyframes: Option<Vec<ScriptKeyframeInfo>>| {
+            let mut buf = Vec::new();
+            FLVHeader::new(false, true).write_to(&mut buf).unwrap();
+            buf.write_u32::<BigEndian>(0).unwrap();
+            if let Some(keyframes) = keyframes {
+                write_tag(&mut buf, &script_frame(keyframes));
+            }
+            let mut keyframes = vec![];
+            for index in 0..frame_count {
+                let position = write_tag(&mut buf, &video_frame(index));
+                if index.is_multiple_of(GOP_SIZE) {
+                    keyframes.push(ScriptKeyframeInfo {
+                        file_position: position as f64,
+                        time: (index * FRAME_INTERVAL_MS) as f64 / 1000.0,
+                    });
+                }
+            }
+            (buf, keyframes)
+        };
+        if !with_keyframes_index {
+            return build(None).0;
+        }
+        // numbers are of fixed size in amf, so the positions do not move on the second pass
+        let (_, keyframes) = build(Some(
+            (0..frame_count.div_ceil(GOP_SIZE))
+                .map(|_| ScriptKeyframeInfo {
+                    file_position: 0.0,
+                    time: 0.0,
+                })
+                .collect(),
+        ));
+        build(Some(keyframes)).0
+    }
+
+    fn stream_id() -> StreamIdentity {
+        StreamIdentity::new("live", "vod").unwrap()
+    }
+
+    /// answers publish, describe and set scale events like the stream center does,
+    /// delivered frames are forwarded to the returned receiver along with their arrival time
+    fn fake_stream_center(
+        subscriber_cnt: Arc<AtomicUsize>,
+    ) -> (
+        mpsc::UnboundedSender<StreamCenterEvent>,
+        mpsc::UnboundedReceiver<(Instant, MediaFrame)>,
+        oneshot::Receiver<()>,
+    ) {
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        let (frame_sender, frame_receiver) = mpsc::unbounded_channel();
+        let (unpublish_sender, unpublish_receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let mut unpublish_sender = Some(unpublish_sender);
+            let mut playback_control = None;
+            while let Some(event) = event_receiver.recv().await {
+                match event {
+                    StreamCenterEvent::Publish {
+                        playback,
+                        result_sender,
+                        ..
+                    } => {
+                        playback_control = playback;
+                        let (media_sender, mut media_receiver) = mpsc::channel(16);
+                        let frame_sender = frame_sender.clone();
+                        tokio::spawn(async move {
+                            while let Some(frame) = media_receiver.recv().await {
+                                let _ = frame_sender.send((Instant::now(), frame));
+                            }
+                        });
+                        let _ = result_sender.send(Ok(media_sender));
+                    }
+                    StreamCenterEvent::Describe {
+                        stream_id,
+                        result_sender,
+                    } => {
+                        let subscribers = (0..subscriber_cnt.load(Ordering::SeqCst))
+                            .map(|_| {
+                                let id = Uuid::now_v7();
+                                (
+                                    id,
+                                    SubscriberInfo {
+                                        id,
+                                        play_protocol: PlayProtocol::RTMP,
+                                        context: HashMap::new(),
+                                        parsed_context: ParsedContext::from(&HashMap::new()),
+                                        media_selection: MediaSelection::default(),
+                                        play_stat: PlayStat::default(),
+                                        rtcp_peers: Vec::new(),
+                                        rtmp_control: None,
+                                        alias: None,
+                                    },
+                                )
+                            })
+                            .collect();
+                        let _ = result_sender.send(Ok(StreamDescription {
+                            publish_protocol: PublishProtocol::VOD,
+                            stream_id,
+                            video_config: None,
+                            has_video: true,
+                            audio_conifg: None,
+                            audio_channels: None,
+                            has_audio: false,
+                            config_generation: 0,
+                            publish_start_time: SystemTime::now(),
+                            stalled: false,
+                            subscribers,
+                            publisher_rtcp_peers: Vec::new(),
+                            publisher_rtmp_control: None,
+                            publisher_rtp_receive: Vec::new(),
+                            health: None,
+                            audio_tracks: Vec::new(),
+                            audio_concealment: Default::default(),
+                            timestamp_normalization: Default::default(),
+                            latency: None,
+                            rate_estimate: Default::default(),
+                            meta_data: None,
+                        }));
+                    }
+                    StreamCenterEvent::SetScale {
+                        scale,
+                        result_sender,
+                        ..
+                    } => {
+                        let result = match &playback_control {
+                            Some(playback) if playback.scale_sender.send(scale).is_ok() => scale,
+                            _ => 1.0,
+                        };
+                        let _ = result_sender.send(Ok(result));
+                    }
+                    StreamCenterEvent::Unpublish { result_sender, .. } => {
+                        let _ = result_sender.send(Ok(()));
+                        if let Some(sender) = unpublish_sender.take() {
+                            let _ = sender.send(());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+        (event_sender, frame_receiver, unpublish_receiver)
+    }
+
+    async fn assert_paced(speed: f64) {
+        let frame_count = 60_000 / FRAME_INTERVAL_MS;
+        let (event_sender, mut frame_receiver, _) =
+            fake_stream_center(Arc::new(AtomicUsize::new(1)));
+        let source = FlvFileSource::new(
+            Cursor::new(make_flv_file(frame_count, false)),
+            stream_id(),
+            FlvFileSourceConfig {
+                speed,
+                ..Default::default()
+            },
+            event_sender,
+        )
+        .await
+        .unwrap();
+        let (_stop_sender, stop_receiver) = oneshot::channel();
+        source.run(stop_receiver).await.unwrap();
+
+        let mut first_arrival = None;
+        let mut received = 0;
+        while let Ok((arrival, frame)) = frame_receiver.try_recv() {
+            let first_arrival = *first_arrival.get_or_insert(arrival);
+            let expected = Duration::from_nanos(frame.get_decode_timestamp_ns()).div_f64(speed);
+            let actual = arrival - first_arrival;
+            assert!(
+                actual.abs_diff(expected) <= Duration::from_millis(10),
+                "frame {} arrived at {:?}, expected {:?}",
+                received,
+                actual,
+                expected
+            );
+            received += 1;
+        }
+        assert_eq!(received, frame_count);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn paces_frames_by_timestamp() {
+        assert_paced(1.0).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn paces_frames_with_speed_factor() {
+        assert_paced(2.0).await;
+        assert_paced(0.5).await;
+    }
+
+    async fn first_media_frame_after_seek(with_keyframes_index: bool, start_offset_ms: u64) {
+        let mut player = FlvFilePlayer::new(
+            Cursor::new(make_flv_file(500, with_keyframes_index)),
+            &FlvFileSourceConfig {
+                start_offset_ms,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let mut frame = player.next_frame().await.unwrap().unwrap().frame;
+        if with_keyframes_index {
+            // metadata goes first
+            assert!(frame.is_script());
+            frame = player.next_frame().await.unwrap().unwrap().frame;
+        }
+        assert!(frame.is_video_key_frame());
+        // the keyframe at 10s is the nearest one before 11s
+        assert_eq!(frame_index(&frame), 250);
+        assert_eq!(frame.get_decode_timestamp_ns(), 0);
+        let frame = player.next_frame().await.unwrap().unwrap().frame;
+        assert_eq!(frame_index(&frame), 251);
+        assert_eq!(frame.get_decode_timestamp_ms(), FRAME_INTERVAL_MS);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn seeks_with_keyframes_index() {
+        first_media_frame_after_seek(true, 11_000).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn seeks_by_scanning_without_index() {
+        first_media_frame_after_seek(false, 11_000).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn plays_past_wrong_previous_tag_sizes_and_garbage() {
+        let mut file = make_flv_file(50, false);
+        let mut tag_starts = vec![];
+        let mut position = 9 + 4;
+        while position < file.len() {
+            tag_starts.push(position);
+            let data_size = u32::from_be_bytes([
+                0,
+                file[position + 1],
+                file[position + 2],
+                file[position + 3],
+            ]);
+            position += 11 + data_size as usize + 4;
+        }
+        // the PreviousTagSize field after the 10th frame, and junk before the 30th
+        file[tag_starts[10] - 1] ^= 0xFF;
+        file.splice(tag_starts[30]..tag_starts[30], [0xFF; 100]);
+
+        let mut player = FlvFilePlayer::new(Cursor::new(file), &FlvFileSourceConfig::default())
+            .await
+            .unwrap();
+        let mut indexes = vec![];
+        while let Some(paced) = player.next_frame().await.unwrap() {
+            indexes.push(frame_index(&paced.frame));
+        }
+        assert_eq!(indexes, (0..50).collect::<Vec<_>>());
+        assert_eq!(
+            player.recovery_stats(),
+            FlvRecoveryStats {
+                previous_tag_size_mismatches: 1,
+                resyncs: 1,
+                skipped_bytes: 100,
+            }
+        );
+    }
+
+    /// high 4.4 by x264, its vui declaring a fixed 30000/1001 frames per second
+    fn ntsc_video_config() -> MediaFrame {
+        const SPS: [u8; 27] = [
+            0x67, 0x64, 0x00, 0x2c, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0xc0, 0x44, 0x00,
+            0x00, 0x03, 0x00, 0x04, 0x00, 0x00, 0x03, 0x00, 0xc8, 0x3c, 0x60, 0xc6, 0x58,
+        ];
+        const PPS: [u8; 4] = [0x68, 0xef, 0x8f, 0xcb];
+        let mut sps = Sps::try_from(&NalUnit::read_from(&mut SPS.as_slice()).unwrap()).unwrap();
+        sps.vui_parameters.as_mut().unwrap().timing_info = Some(TimingInfo {
+            num_units_in_tick: 1001,
+            time_scale: 60000,
+            fixed_frame_rate_flag: true,
+        });
+        let pps = Pps::try_from((
+            sps.get_chroma_format_idc().unwrap(),
+            &NalUnit::read_from(&mut PPS.as_slice()).unwrap(),
+        ))
+        .unwrap();
+        MediaFrame::VideoConfig {
+            timestamp_nano: 0,
+            config: Box::new(VideoConfig::from(AvcDecoderConfigurationRecord::from((
+                &sps, &pps,
+            )))),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn video_is_paced_on_the_nominal_frame_rate_of_the_sps() {
+        let frame_rate = 30000.0 / 1001.0;
+        let mut buf = Vec::new();
+        FLVHeader::new(false, true).write_to(&mut buf).unwrap();
+        buf.write_u32::<BigEndian>(0).unwrap();
+        write_tag(&mut buf, &ntsc_video_config());
+        // flv timestamps are in milliseconds, the intervals beat between 33ms and 34ms
+        for index in 0..150 {
+            let mut frame = video_frame(index);
+            let dts_nano = (index as f64 * 1000.0 / frame_rate).round() as u64 * 1_000_000;
+            frame.set_decode_timestamp_ns(dts_nano);
+            frame.set_presentation_timestamp_ns(dts_nano);
+            write_tag(&mut buf, &frame);
+        }
+        let mut player = FlvFilePlayer::new(Cursor::new(buf), &FlvFileSourceConfig::default())
+            .await
+            .unwrap();
+        let start = Instant::now();
+        let mut deadlines = vec![];
+        while let Some(paced) = player.next_frame().await.unwrap() {
+            if matches!(paced.frame, MediaFrame::Video { .. }) {
+                deadlines.push(paced.deadline - start);
+            }
+        }
+        assert_eq!(deadlines.len(), 150);
+        for (index, deadline) in deadlines.iter().enumerate() {
+            let expected = Duration::from_secs_f64(index as f64 / frame_rate);
+            assert!(
+                deadline.abs_diff(expected) < Duration::from_micros(1),
+                "frame {} is due at {:?}, expected {:?}",
+                index,
+                deadline,
+                expected
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn start_offset_past_the_end_plays_from_the_last_keyframe() {
+        let mut player = FlvFilePlayer::new(
+            Cursor::new(make_flv_file(500, false)),
+            &FlvFileSourceConfig {
+                start_offset_ms: u64::MAX,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let frame = player.next_frame().await.unwrap().unwrap().frame;
+        assert!(frame.is_video_key_frame());
+        assert_eq!(frame_index(&frame), 450);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn loop_keeps_timestamps_increasing() {
+        let mut player = FlvFilePlayer::new(
+            Cursor::new(make_flv_file(5, false)),
+            &FlvFileSourceConfig {
+                loop_playback: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let mut last_dts = None;
+        for i in 0..12u64 {
+            let frame = player.next_frame().await.unwrap().unwrap().frame;
+            assert_eq!(frame_index(&frame), i % 5);
+            assert_eq!(frame.get_decode_timestamp_ms(), i * FRAME_INTERVAL_MS);
+            assert!(last_dts < Some(frame.get_decode_timestamp_ns()));
+            last_dts = Some(frame.get_decode_timestamp_ns());
+        }
+
+        let mut player = FlvFilePlayer::new(
+            Cursor::new(make_flv_file(5, false)),
+            &FlvFileSourceConfig::default(),
+        )
+        .await
+        .unwrap();
+        for _ in 0..5 {
+            assert!(player.next_frame().await.unwrap().is_some());
+        }
+        assert!(player.next_frame().await.unwrap().is_none());
+    }
+
+    /// the video frames of a player until the end, with their delivery time since the start
+    async fn play_to_end(
+        with_keyframes_index: bool,
+        config: FlvFileSourceConfig,
+    ) -> Vec<(u64, u64, Duration)> {
+        let mut player = FlvFilePlayer::new(
+            Cursor::new(make_flv_file(500, with_keyframes_index)),
+            &config,
+        )
+        .await
+        .unwrap();
+        let start = Instant::now();
+        let mut frames = vec![];
+        while let Some(paced) = player.next_frame().await.unwrap() {
+            if paced.frame.is_video() {
+                frames.push((
+                    frame_index(&paced.frame),
+                    paced.frame.get_decode_timestamp_ms(),
+                    paced.deadline - start,
+                ));
+            }
+        }
+        frames
+    }
+
+    /// key frames every 2 seconds of media time, sent every second of wall clock at scale 2
+    fn assert_trick_play(frames: &[(u64, u64, Duration)], indexes: &[u64]) {
+        assert_eq!(
+            frames.iter().map(|(index, ..)| *index).collect::<Vec<_>>(),
+            indexes
+        );
+        for (i, (_, dts, deadline)) in frames.iter().enumerate() {
+            assert_eq!(*dts, i as u64 * 2 * GOP_SIZE * FRAME_INTERVAL_MS);
+            assert_eq!(*deadline, Duration::from_millis(*dts / 2));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fast_forward_delivers_every_other_keyframe_at_scale_2() {
+        for with_keyframes_index in [true, false] {
+            let frames = play_to_end(
+                with_keyframes_index,
+                FlvFileSourceConfig {
+                    scale: 2.0,
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert_trick_play(&frames, &[0, 100, 200, 300, 400]);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reverse_walks_keyframes_backwards_at_scale_minus_2() {
+        for with_keyframes_index in [true, false] {
+            let frames = play_to_end(
+                with_keyframes_index,
+                FlvFileSourceConfig {
+                    scale: -2.0,
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert_trick_play(&frames, &[450, 350, 250, 150, 50]);
+
+            let frames = play_to_end(
+                with_keyframes_index,
+                FlvFileSourceConfig {
+                    scale: -2.0,
+                    start_offset_ms: 11_000,
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert_trick_play(&frames, &[250, 150, 50]);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejects_invalid_scale() {
+        for scale in [0.0, f64::NAN, f64::INFINITY, 1e-300, -128.0] {
+            assert!(
+                FlvFilePlayer::new(
+                    Cursor::new(make_flv_file(5, false)),
+                    &FlvFileSourceConfig {
+                        scale,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .is_err()
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn scale_set_through_stream_center_reaches_source() {
+        let (event_sender, mut frame_receiver, _) =
+            fake_stream_center(Arc::new(AtomicUsize::new(1)));
+        let source = FlvFileSource::new(
+            Cursor::new(make_flv_file(500, false)),
+            stream_id(),
+            FlvFileSourceConfig::default(),
+            event_sender.clone(),
+        )
+        .await
+        .unwrap();
+        let (_stop_sender, stop_receiver) = oneshot::channel();
+        let handle = tokio::spawn(source.run(stop_receiver));
+
+        tokio::time::sleep(Duration::from_millis(1010)).await;
+        assert_eq!(
+            StreamCenter::set_scale(&event_sender, &stream_id(), 2.0)
+                .await
+                .unwrap(),
+            2.0
+        );
+        handle.await.unwrap().unwrap();
+
+        let mut frames = vec![];
+        while let Ok((arrival, frame)) = frame_receiver.try_recv() {
+            frames.push((
+                arrival,
+                frame_index(&frame),
+                frame.get_decode_timestamp_ns(),
+            ));
+        }
+        assert!(frames.windows(2).all(|pair| pair[0].2 < pair[1].2));
+        // the frame read before the change goes out as is
+        let indexes = frames
+            .iter()
+            .map(|(_, index, _)| *index)
+            .collect::<Vec<_>>();
+        assert_eq!(indexes[..27], (0..27).collect::<Vec<_>>());
+        assert_eq!(indexes[27..], [50, 150, 250, 350, 450]);
+        // 4 seconds of media time between the key frames go out in 2 seconds
+        for pair in frames[27..].windows(2) {
+            assert_eq!(pair[1].2 - pair[0].2, 4_000_000_000);
+            assert!(
+                (pair[1].0 - pair[0].0).abs_diff(Duration::from_secs(2))
+                    <= Duration::from_millis(10)
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_when_last_subscriber_leaves() {
+        let subscriber_cnt = Arc::new(AtomicUsize::new(1));
+        let (event_sender, _frame_receiver, unpublish_receiver) =
+            fake_stream_center(subscriber_cnt.clone());
+        let source = FlvFileSource::new(
+            Cursor::new(make_flv_file(500, false)),
+            stream_id(),
+            FlvFileSourceConfig {
+                loop_playback: true,
+                ..Default::default()
+            },
+            event_sender,
+        )
+        .await
+        .unwrap();
+        let (_stop_sender, stop_receiver) = oneshot::channel();
+        let handle = tokio::spawn(source.run(stop_receiver));
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert!(!handle.is_finished());
+        subscriber_cnt.store(0, Ordering::SeqCst);
+        tokio::time::timeout(Duration::from_secs(2), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        unpublish_receiver.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_on_explicit_stop() {
+        let (event_sender, _frame_receiver, unpublish_receiver) =
+            fake_stream_center(Arc::new(AtomicUsize::new(1)));
+        let source = FlvFileSource::new(
+            Cursor::new(make_flv_file(500, false)),
+            stream_id(),
+            FlvFileSourceConfig::default(),
+            event_sender,
+        )
+        .await
+        .unwrap();
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        let handle = tokio::spawn(source.run(stop_receiver));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        stop_sender.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+        unpublish_receiver.await.unwrap();
+    }
+
+    #[test]
+    fn vod_path_must_stay_in_vod_dir() {
+        let vod_dir = Path::new("/data/records");
+        assert_eq!(
+            resolve_vod_path(vod_dir, "live/a.flv").unwrap(),
+            Path::new("/data/records/live/a.flv")
+        );
+        assert!(resolve_vod_path(vod_dir, "../a.flv").is_err());
+        assert!(resolve_vod_path(vod_dir, "live/../../a.flv").is_err());
+        assert!(resolve_vod_path(vod_dir, "/etc/passwd").is_err());
+        assert!(resolve_vod_path(vod_dir, "").is_err());
+    }
+
+    fn start_stream_center(
+        stream_center: StreamCenter,
+    ) -> mpsc::UnboundedSender<StreamCenterEvent> {
+        let mut stream_center = stream_center;
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        event_sender
+    }
+
+    /// the first video frame a new subscriber of the stream gets, and the subscriber,
+    /// which keeps the vod source playing
+    async fn first_video_frame(
+        event_sender: &mpsc::UnboundedSender<StreamCenterEvent>,
+    ) -> (u64, SubscribeResponse) {
+        let mut subscribe = StreamCenter::subscribe(
+            event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        loop {
+            let frame = subscribe.media_receiver.recv().await.unwrap();
+            if frame.is_video() && !frame.is_sequence_header() {
+                return (frame_index(&frame), subscribe);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn restored_vod_source_resumes_from_its_position() {
+        let dir = std::env::temp_dir().join(format!("vod-restore-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (vod_dir, data_dir) = (dir.join("vod"), dir.join("data"));
+        std::fs::create_dir_all(vod_dir.join("live")).unwrap();
+        std::fs::write(vod_dir.join("live/a.flv"), make_flv_file(500, true)).unwrap();
+
+        let event_sender = start_stream_center(StreamCenter::new());
+        let vod_sources = VodSourceRegistry::default();
+        let source = FlvFileSource::open(
+            &resolve_vod_path(&vod_dir, "live/a.flv").unwrap(),
+            stream_id(),
+            FlvFileSourceConfig::default(),
+            event_sender.clone(),
+        )
+        .await
+        .unwrap()
+        .restorable("live/a.flv".to_owned());
+        vod_sources.start(source).await.unwrap();
+        let (index, _subscriber) = first_video_frame(&event_sender).await;
+        assert_eq!(index, 0);
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+
+        StreamCenter::snapshot(&event_sender)
+            .await
+            .unwrap()
+            .save(&data_dir)
+            .unwrap();
+        // the server goes down
+        assert!(vod_sources.stop(&stream_id()));
+
+        let snapshot = StreamCenterSnapshot::take(&data_dir).unwrap().unwrap();
+        assert_eq!(snapshot.sources.len(), 1);
+        let SourceDefinition::Vod(definition) = &snapshot.sources[0].definition;
+        assert_eq!(definition.path, "live/a.flv");
+        assert!(definition.position_ms >= 2000, "{:?}", definition);
+        let resumed_at = definition.position_ms / 2000 * GOP_SIZE;
+
+        let mut stream_center = StreamCenter::new();
+        let sources = stream_center.restore(snapshot);
+        let mut notifications = stream_center.subscribe_notifications();
+        let event_sender = start_stream_center(stream_center);
+        VodSourceRegistry::default()
+            .restore_all(Some(&vod_dir), sources, &event_sender)
+            .await;
+        assert!(matches!(
+            notifications.recv().await.unwrap(),
+            StreamNotification::Restored { stream_id: restored, protocol: PublishProtocol::VOD }
+                if restored == stream_id()
+        ));
+        // from the key frame before the position, not from the start
+        assert_eq!(first_video_frame(&event_sender).await.0, resumed_at);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -4,6 +4,7 @@ use std::{
     time::Duration,
 };
 
+use amf_formats::limits::ReadLimits;
 use flv_formats::tag::{FLVTag, flv_tag_header::FLVTagType};
 use num::ToPrimitive;
 use rtmp_formats::{
@@ -16,10 +17,10 @@ use rtmp_formats::{
 };
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt, BufWriter},
-    net::TcpStream,
     time,
 };
 use tokio_util::bytes::{Buf, BytesMut};
+use unified_io::tcp::BoxedStream;
 use utils::traits::writer::WriteTo;
 
 use crate::errors::{RtmpServerError, RtmpServerResult};
@@ -29,23 +30,24 @@ pub struct RtmpChunkStream {
     chunk_reader: chunk::reader::Reader,
     chunk_writer: chunk::writer::Writer,
     read_buffer: BytesMut,
-    stream: BufWriter<TcpStream>,
+    stream: BufWriter<BoxedStream>,
     read_timeout_ms: u64,
     write_timeout_ms: u64,
 
-    chunk_size: u32,
     ack_window_size_read: Option<u32>,
     ack_window_size_write: Option<SetPeerBandwidth>,
     acknowledged_sequence_number: Option<u32>,
     total_wrote_bytes: u64,
     read_buffer_capacity: usize,
+    peer_closed: bool,
+    /// the stream is of a client connected to another server, which reads the responses of the server
+    client: bool,
 }
 
 impl RtmpChunkStream {
     pub fn new(
         read_buffer_capacity: u64,
-        io: TcpStream,
-        chunk_size: u32,
+        io: BoxedStream,
         read_timeout_ms: u64,
         write_timeout_ms: u64,
     ) -> Self {
@@ -58,22 +60,41 @@ impl RtmpChunkStream {
             stream: BufWriter::new(io),
             read_timeout_ms,
             write_timeout_ms,
-            chunk_size,
             ack_window_size_read: None,
             ack_window_size_write: None,
             acknowledged_sequence_number: None,
             total_wrote_bytes: 0,
+            peer_closed: false,
+            client: false,
         }
     }
 
+    /// for a client connected to another server, e.g., a relay
+    pub fn set_client(&mut self, client: bool) {
+        self.client = client;
+    }
+
+    pub fn set_max_message_size(&mut self, size: usize) {
+        self.chunk_reader.set_max_message_size(Some(size));
+    }
+
+    pub fn set_amf_limits(&mut self, limits: ReadLimits) {
+        self.chunk_reader.set_amf_limits(limits);
+    }
+
     pub fn total_wrote_bytes(&self) -> u64 {
         self.total_wrote_bytes
     }
 
+    /// whether the peer has closed its write side, read_chunk yields None from then on
+    pub fn is_peer_closed(&self) -> bool {
+        self.peer_closed
+    }
+
     pub async fn read_chunk(&mut self) -> RtmpServerResult<Option<ChunkMessage>> {
         loop {
             let mut buf = Cursor::new(&self.read_buffer);
-            match self.chunk_reader.read(&mut buf, true) {
+            match self.chunk_reader.read(&mut buf, !self.client) {
                 Ok(Some(chunk_message)) => {
                     self.read_buffer.advance(buf.position() as usize);
 
@@ -114,6 +135,7 @@ impl RtmpChunkStream {
                 Ok(Ok(len)) => {
                     if len == 0 {
                         if self.read_buffer.is_empty() {
+                            self.peer_closed = true;
                             return Ok(None);
                         } else {
                             return Err(RtmpServerError::Io(io::Error::new(
@@ -184,26 +206,43 @@ impl RtmpChunkStream {
         handshake::server::HandshakeServer::new(&mut self.stream)
             .handshake(false)
             .await?;
-        self.chunk_writer.write_set_chunk_size(self.chunk_size)?;
         Ok(())
     }
 
-    pub async fn write_tag(&mut self, tag: FLVTag) -> RtmpServerResult<()> {
+    /// the handshake of a client, the simple one
+    pub async fn handshake_as_client(&mut self) -> RtmpServerResult<()> {
+        handshake::client::HandshakeClient::new(&mut self.stream)
+            .handshake()
+            .await?;
+        Ok(())
+    }
+
+    /// the tag goes on the chunk streams of the message stream, so streams of a connection never share one
+    pub async fn write_tag(&mut self, tag: FLVTag, message_stream_id: u32) -> RtmpServerResult<()> {
         let mut payload_bytes = BytesMut::zeroed(tag.tag_header.data_size.to_usize().unwrap());
         let mut writer: Cursor<&mut [u8]> = io::Cursor::new(payload_bytes.as_mut());
         tag.body_with_filter.write_to(&mut writer)?;
         match tag.tag_header.tag_type {
             FLVTagType::Audio => {
-                self.chunk_writer
-                    .write_audio(payload_bytes.freeze(), tag.tag_header.timestamp)?;
+                self.chunk_writer.write_audio(
+                    payload_bytes.freeze(),
+                    tag.tag_header.timestamp,
+                    message_stream_id,
+                )?;
             }
             FLVTagType::Video => {
-                self.chunk_writer
-                    .write_video(payload_bytes.freeze(), tag.tag_header.timestamp)?;
+                self.chunk_writer.write_video(
+                    payload_bytes.freeze(),
+                    tag.tag_header.timestamp,
+                    message_stream_id,
+                )?;
             }
             FLVTagType::Script => {
-                self.chunk_writer
-                    .write_meta(payload_bytes.freeze(), tag.tag_header.timestamp)?;
+                self.chunk_writer.write_meta(
+                    payload_bytes.freeze(),
+                    tag.tag_header.timestamp,
+                    message_stream_id,
+                )?;
             }
         }
         self.flush_chunk().await?;
@@ -303,6 +342,12 @@ impl RtmpChunkStream {
         self.ack_window_size_read = Some(request.size);
     }
 
+    /// closes the write side, after what was written is flushed
+    pub async fn shutdown(&mut self) -> RtmpServerResult<()> {
+        self.stream.shutdown().await?;
+        Ok(())
+    }
+
     pub fn chunk_writer(&mut self) -> &mut rtmp_formats::chunk::writer::Writer {
         &mut self.chunk_writer
     }
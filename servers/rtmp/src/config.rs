@@ -1,17 +1,436 @@
-use std::net::IpAddr;
+use std::{collections::HashMap, net::IpAddr, str::FromStr};
+
+use amf_formats::limits::{
+    DEFAULT_MAX_DECODED_BYTES, DEFAULT_MAX_DEPTH, DEFAULT_MAX_ELEMENTS, DEFAULT_MAX_STRING_LENGTH,
+    ReadLimits,
+};
+use rtmp_formats::{
+    commands::{CapsExInfo, FourCCInfo, four_cc_registry::FourCCRegistry},
+    protocol_control::consts::MAX_CHUNK_SIZE,
+};
+use stream_center::rtmp_control::{PeerBandwidthLimitType, RtmpControl};
+use unified_io::{proxy_protocol::TrustedProxies, tls::TlsListenerConfig};
+
+use crate::errors::{RtmpServerError, RtmpServerResult};
+
+/// the codecs the stream center can remux, publishers of other codecs are rejected
+pub const DEFAULT_FORWARD_VIDEO_FOUR_CC: [&str; 1] = ["avc1"];
+pub const DEFAULT_FORWARD_AUDIO_FOUR_CC: [&str; 2] = ["mp4a", ".mp3"];
+
+/// sent once a client connects, unless the app overrides them
+pub const DEFAULT_WINDOW_ACK_SIZE: u32 = 4096;
+pub const DEFAULT_PEER_BANDWIDTH: u32 = 4096;
+
+fn default_window_ack_size() -> u32 {
+    DEFAULT_WINDOW_ACK_SIZE
+}
+
+fn default_peer_bandwidth() -> u32 {
+    DEFAULT_PEER_BANDWIDTH
+}
+
+fn default_peer_bandwidth_limit_type() -> PeerBandwidthLimitType {
+    PeerBandwidthLimitType::Dynamic
+}
+
+fn default_amf_max_depth() -> usize {
+    DEFAULT_MAX_DEPTH
+}
+
+fn default_amf_max_decoded_bytes() -> usize {
+    DEFAULT_MAX_DECODED_BYTES
+}
+
+fn default_amf_max_string_length() -> usize {
+    DEFAULT_MAX_STRING_LENGTH
+}
+
+fn default_amf_max_elements() -> usize {
+    DEFAULT_MAX_ELEMENTS
+}
+
+fn default_send_on_bw_done() -> bool {
+    true
+}
+
+fn default_forward_video_four_cc() -> Vec<String> {
+    DEFAULT_FORWARD_VIDEO_FOUR_CC
+        .iter()
+        .map(|v| v.to_string())
+        .collect()
+}
+
+fn default_forward_audio_four_cc() -> Vec<String> {
+    DEFAULT_FORWARD_AUDIO_FOUR_CC
+        .iter()
+        .map(|v| v.to_string())
+        .collect()
+}
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct RtmpServerConfig {
     pub address: IpAddr,
     pub port: u16,
+    /// of the chunks the server sends, in [1, 0xFFFFFF]
     pub chunk_size: u32,
+    /// sent in WindowAckSize and SetPeerBandwidth once a client connects
+    #[serde(default = "default_window_ack_size")]
+    pub window_ack_size: u32,
+    #[serde(default = "default_peer_bandwidth")]
+    pub peer_bandwidth: u32,
+    #[serde(default = "default_peer_bandwidth_limit_type")]
+    pub peer_bandwidth_limit_type: PeerBandwidthLimitType,
+    /// overrides of the protocol control settings above by the app clients connect to
+    #[serde(default)]
+    pub apps: HashMap<String, RtmpAppConfig>,
     pub write_timeout_ms: u64,
     pub read_timeout_ms: u64,
+    /// bound each amf value of the commands clients send, @see: `ReadLimits`
+    #[serde(default = "default_amf_max_depth")]
+    pub amf_max_depth: usize,
+    #[serde(default = "default_amf_max_decoded_bytes")]
+    pub amf_max_decoded_bytes: usize,
+    #[serde(default = "default_amf_max_string_length")]
+    pub amf_max_string_length: usize,
+    #[serde(default = "default_amf_max_elements")]
+    pub amf_max_elements: usize,
+    /// rtmps listener, enabled if set
+    pub rtmps: Option<TlsListenerConfig>,
+    /// advertised in the connect response and enforced on publish
+    #[serde(default = "default_forward_video_four_cc")]
+    pub forward_video_four_cc: Vec<String>,
+    #[serde(default = "default_forward_audio_four_cc")]
+    pub forward_audio_four_cc: Vec<String>,
+    /// the load balancers sending a proxy protocol header ahead of the connections of the clients
+    #[serde(default)]
+    pub trusted_proxies: TrustedProxies,
+    /// call onBWDone on the clients after the connect response, as nginx-rtmp does,
+    /// legacy encoders wait for it and never publish otherwise
+    #[serde(default = "default_send_on_bw_done")]
+    pub send_on_bw_done: bool,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+impl RtmpServerConfig {
+    /// the protocol control settings of the server and of each app, validated,
+    /// the settings an app leaves out are the ones of the server
+    pub fn controls(&self) -> RtmpServerResult<RtmpControls> {
+        let default = RtmpControl {
+            chunk_size: self.chunk_size,
+            window_ack_size: self.window_ack_size,
+            peer_bandwidth: self.peer_bandwidth,
+            peer_bandwidth_limit_type: self.peer_bandwidth_limit_type,
+        };
+        validate_control(&default)
+            .map_err(|err| RtmpServerError::InvalidConfig(format!("the rtmp server {}", err)))?;
+        let apps = self
+            .apps
+            .iter()
+            .map(|(app, config)| {
+                let control = config.apply_to(default);
+                validate_control(&control).map_err(|err| {
+                    RtmpServerError::InvalidConfig(format!("the rtmp app {} {}", app, err))
+                })?;
+                Ok((app.clone(), control))
+            })
+            .collect::<RtmpServerResult<_>>()?;
+        Ok(RtmpControls { default, apps })
+    }
+
+    /// the limits of the amf values clients send, validated.
+    /// a zero limit would refuse every connect command
+    pub fn amf_limits(&self) -> RtmpServerResult<ReadLimits> {
+        let limits = ReadLimits {
+            max_depth: self.amf_max_depth,
+            max_decoded_bytes: self.amf_max_decoded_bytes,
+            max_string_length: self.amf_max_string_length,
+            max_elements: self.amf_max_elements,
+        };
+        if limits.max_depth == 0
+            || limits.max_decoded_bytes == 0
+            || limits.max_string_length == 0
+            || limits.max_elements == 0
+        {
+            return Err(RtmpServerError::InvalidConfig(format!(
+                "the rtmp amf limits must not be zero: {:?}",
+                limits
+            )));
+        }
+        Ok(limits)
+    }
+}
+
+/// the ranges of @see: RTMP spec 5.4
+fn validate_control(control: &RtmpControl) -> Result<(), String> {
+    if !(1..=MAX_CHUNK_SIZE).contains(&control.chunk_size) {
+        return Err(format!(
+            "chunk size {} is out of [1, {}]",
+            control.chunk_size, MAX_CHUNK_SIZE
+        ));
+    }
+    if control.window_ack_size == 0 {
+        return Err("window ack size must not be zero".to_owned());
+    }
+    if control.peer_bandwidth == 0 {
+        return Err("peer bandwidth must not be zero".to_owned());
+    }
+    Ok(())
+}
+
+/// the protocol control settings of an app, the ones left out are the ones of the server
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RtmpAppConfig {
+    pub chunk_size: Option<u32>,
+    pub window_ack_size: Option<u32>,
+    pub peer_bandwidth: Option<u32>,
+    pub peer_bandwidth_limit_type: Option<PeerBandwidthLimitType>,
+}
+
+impl RtmpAppConfig {
+    fn apply_to(&self, control: RtmpControl) -> RtmpControl {
+        RtmpControl {
+            chunk_size: self.chunk_size.unwrap_or(control.chunk_size),
+            window_ack_size: self.window_ack_size.unwrap_or(control.window_ack_size),
+            peer_bandwidth: self.peer_bandwidth.unwrap_or(control.peer_bandwidth),
+            peer_bandwidth_limit_type: self
+                .peer_bandwidth_limit_type
+                .unwrap_or(control.peer_bandwidth_limit_type),
+        }
+    }
+}
+
+/// parses comma separated `<setting>=<value>`, like `chunk_size=4096,peer_bandwidth_limit_type=hard`.
+/// the ranges are validated along with the config of the server
+impl FromStr for RtmpAppConfig {
+    type Err = RtmpServerError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| {
+            RtmpServerError::InvalidConfig(format!("invalid rtmp app config: {}, {}", s, reason))
+        };
+        let parse_size = |value: &str| {
+            value
+                .parse::<u32>()
+                .map_err(|err| invalid(format!("{}: {}", value, err)))
+        };
+        let mut config = Self::default();
+        for setting in s.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            let Some((key, value)) = setting.split_once('=') else {
+                return Err(invalid(format!(
+                    "expect <setting>=<value>, got {}",
+                    setting
+                )));
+            };
+            let value = value.trim();
+            match key.trim() {
+                "chunk_size" => config.chunk_size = Some(parse_size(value)?),
+                "window_ack_size" => config.window_ack_size = Some(parse_size(value)?),
+                "peer_bandwidth" => config.peer_bandwidth = Some(parse_size(value)?),
+                "peer_bandwidth_limit_type" => {
+                    config.peer_bandwidth_limit_type = Some(match value {
+                        "hard" => PeerBandwidthLimitType::Hard,
+                        "soft" => PeerBandwidthLimitType::Soft,
+                        "dynamic" => PeerBandwidthLimitType::Dynamic,
+                        _ => return Err(invalid(format!("unknown limit type {}", value))),
+                    })
+                }
+                key => return Err(invalid(format!("unknown setting {}", key))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// the protocol control settings sent to the clients of each app
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtmpControls {
+    pub default: RtmpControl,
+    pub apps: HashMap<String, RtmpControl>,
+}
+
+impl RtmpControls {
+    /// the app is matched by its exact name in the connect command
+    pub fn of_app(&self, app: &str) -> RtmpControl {
+        self.apps.get(app).copied().unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct RtmpSessionConfig {
-    pub chunk_size: u32,
+    pub controls: RtmpControls,
+    pub amf_limits: ReadLimits,
     pub write_timeout_ms: u64,
     pub read_timeout_ms: u64,
+    pub forward_video_four_cc: Vec<String>,
+    pub forward_audio_four_cc: Vec<String>,
+    pub send_on_bw_done: bool,
+}
+
+impl RtmpSessionConfig {
+    pub fn four_cc_registry(&self) -> FourCCRegistry {
+        let forward = FourCCInfo {
+            can_forward: true,
+            ..Default::default()
+        };
+        let registry = FourCCRegistry::new().with_caps_ex(CapsExInfo {
+            support_reconnect: true,
+            support_mod_ex: true,
+            support_timestamp_nano: true,
+            ..Default::default()
+        });
+        let registry = self
+            .forward_video_four_cc
+            .iter()
+            .fold(registry, |registry, four_cc| {
+                registry.with_video(four_cc.as_str(), forward)
+            });
+        self.forward_audio_four_cc
+            .iter()
+            .fold(registry, |registry, four_cc| {
+                registry.with_audio(four_cc.as_str(), forward)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, net::Ipv4Addr};
+
+    use amf_formats::limits::{
+        DEFAULT_MAX_DECODED_BYTES, DEFAULT_MAX_DEPTH, DEFAULT_MAX_ELEMENTS,
+        DEFAULT_MAX_STRING_LENGTH,
+    };
+    use stream_center::rtmp_control::{PeerBandwidthLimitType, RtmpControl};
+
+    use super::{RtmpAppConfig, RtmpServerConfig};
+    use crate::errors::RtmpServerError;
+
+    fn server_config(apps: &[(&str, &str)]) -> RtmpServerConfig {
+        RtmpServerConfig {
+            address: Ipv4Addr::LOCALHOST.into(),
+            port: 1935,
+            chunk_size: 60000,
+            window_ack_size: 2_500_000,
+            peer_bandwidth: 2_500_000,
+            peer_bandwidth_limit_type: PeerBandwidthLimitType::Dynamic,
+            apps: apps
+                .iter()
+                .map(|(app, config)| (app.to_string(), config.parse().unwrap()))
+                .collect(),
+            write_timeout_ms: 10000,
+            read_timeout_ms: 10000,
+            amf_max_depth: DEFAULT_MAX_DEPTH,
+            amf_max_decoded_bytes: DEFAULT_MAX_DECODED_BYTES,
+            amf_max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            amf_max_elements: DEFAULT_MAX_ELEMENTS,
+            rtmps: None,
+            forward_video_four_cc: Vec::new(),
+            forward_audio_four_cc: Vec::new(),
+            trusted_proxies: Default::default(),
+            send_on_bw_done: true,
+        }
+    }
+
+    #[test]
+    fn app_overrides_take_precedence_over_the_server() {
+        let controls = server_config(&[
+            ("studio", "chunk_size=16777215"),
+            (
+                "mobile",
+                "chunk_size=128, window_ack_size=500000,peer_bandwidth_limit_type=hard",
+            ),
+        ])
+        .controls()
+        .unwrap();
+
+        let server = RtmpControl {
+            chunk_size: 60000,
+            window_ack_size: 2_500_000,
+            peer_bandwidth: 2_500_000,
+            peer_bandwidth_limit_type: PeerBandwidthLimitType::Dynamic,
+        };
+        assert_eq!(controls.of_app("live"), server);
+        // apps are matched by their exact names
+        assert_eq!(controls.of_app("Studio"), server);
+        assert_eq!(
+            controls.of_app("studio"),
+            RtmpControl {
+                chunk_size: 16777215,
+                ..server
+            }
+        );
+        assert_eq!(
+            controls.of_app("mobile"),
+            RtmpControl {
+                chunk_size: 128,
+                window_ack_size: 500_000,
+                peer_bandwidth_limit_type: PeerBandwidthLimitType::Hard,
+                ..server
+            }
+        );
+    }
+
+    #[test]
+    fn out_of_range_settings_fail_validation() {
+        for app in [
+            "chunk_size=0",
+            "chunk_size=16777216",
+            "window_ack_size=0",
+            "peer_bandwidth=0",
+        ] {
+            assert!(matches!(
+                server_config(&[("live", app)]).controls(),
+                Err(RtmpServerError::InvalidConfig(_))
+            ));
+        }
+
+        let mut config = server_config(&[]);
+        config.chunk_size = 0;
+        assert!(matches!(
+            config.controls(),
+            Err(RtmpServerError::InvalidConfig(_))
+        ));
+        // the server settings are validated even if every app overrides them
+        config.apps = HashMap::from([(
+            "live".to_owned(),
+            RtmpAppConfig {
+                chunk_size: Some(4096),
+                ..Default::default()
+            },
+        )]);
+        assert!(config.controls().is_err());
+
+        assert!(config.amf_limits().is_ok());
+        config.amf_max_depth = 0;
+        assert!(matches!(
+            config.amf_limits(),
+            Err(RtmpServerError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn app_config_is_parsed_from_settings() {
+        assert_eq!(
+            "".parse::<RtmpAppConfig>().unwrap(),
+            RtmpAppConfig::default()
+        );
+        assert_eq!(
+            "peer_bandwidth=1000,peer_bandwidth_limit_type=soft"
+                .parse::<RtmpAppConfig>()
+                .unwrap(),
+            RtmpAppConfig {
+                peer_bandwidth: Some(1000),
+                peer_bandwidth_limit_type: Some(PeerBandwidthLimitType::Soft),
+                ..Default::default()
+            }
+        );
+        for config in [
+            "chunk_size",
+            "chunk_size=-1",
+            "chunk_size=big",
+            "peer_bandwidth_limit_type=strict",
+            "max_chunk_size=4096",
+        ] {
+            assert!(config.parse::<RtmpAppConfig>().is_err());
+        }
+    }
 }
@@ -8,6 +8,31 @@ pub mod response_code {
     pub const NET_STREAM_PLAY_START: &str = "NetStream.Play.Start";
     pub const NET_STREAM_PLAY_RESET: &str = "NetStream.Play.Reset";
     pub const NET_STREAM_PLAY_NOT_FOUND: &str = "NetStream.Play.StreamNotFound";
+    // Another play of the stream being played switched the audio track.
+    // level: status
+    pub const NET_STREAM_PLAY_SWITCH: &str = "NetStream.Play.Switch";
+    // The publisher of the stream stopped sending data, the play goes on if it resumes.
+    // level: status
+    pub const NET_STREAM_PLAY_UNPUBLISH_NOTIFY: &str = "NetStream.Play.UnpublishNotify";
+    // The stream ended, nothing more is played.
+    // level: status
+    pub const NET_STREAM_PLAY_STOP: &str = "NetStream.Play.Stop";
+    // The stream failed, nothing more is played.
+    // level: error
+    pub const NET_STREAM_PLAY_FAILED: &str = "NetStream.Play.Failed";
+    // The publisher exceeded the ingest limits of the server and is disconnected.
+    // level: error
+    pub const NET_STREAM_PUBLISH_REJECTED: &str = "NetStream.Publish.Rejected";
+    // The server can not keep up with the stream, the publisher is asked to lower its bitrate.
+    // level: warning
+    pub const NET_STREAM_PUBLISH_INSUFFICIENT_BW: &str = "NetStream.Publish.InsufficientBW";
+    // The server keeps up with the stream again after NetStream.Publish.InsufficientBW.
+    // level: status
+    pub const NET_STREAM_PUBLISH_SUFFICIENT_BW: &str = "NetStream.Publish.SufficientBW";
+    // The publisher is taken over by a new publisher of the same stream,
+    // or reaped after sending no data for a while, and is disconnected.
+    // level: status
+    pub const NET_STREAM_UNPUBLISH_SUCCESS: &str = "NetStream.Unpublish.Success";
 
     // The NetConnection.call() method was not able to invoke the server-side method or command.
     // level: error
@@ -1,6 +1,7 @@
 use std::{backtrace::Backtrace, io};
 
 use rtmp_formats::{chunk::errors::ChunkMessageError, handshake::errors::HandshakeError};
+use server_utils::ingest_limit::errors::IngestLimitError;
 use stream_center::errors::StreamCenterError;
 use thiserror::Error;
 
@@ -8,9 +9,9 @@ use thiserror::Error;
 pub enum RtmpServerError {
     #[error("io error: {0}")]
     Io(#[from] io::Error),
-    #[error("handshake failed: {0:?}")]
+    #[error("handshake failed: {0}")]
     HandshakeFailed(#[from] HandshakeError),
-    #[error("chunk message read failed: {0:?}")]
+    #[error("chunk message read failed: {0}")]
     ChunkMessageReadFailed(#[from] ChunkMessageError),
     #[error("flv tag demux error: {0}")]
     FlvDemuxError(#[from] flv_formats::errors::FLVError),
@@ -26,6 +27,16 @@ pub enum RtmpServerError {
     VideoCodecDemuxFailed(String),
     #[error("video codec mux failed: {0}")]
     VideoCodecMuxFailed(String),
+    #[error("ingest limit exceeded: {0}")]
+    IngestLimitExceeded(#[from] IngestLimitError),
+    #[error("codec can not be forwarded: {0}")]
+    UnsupportedCodec(String),
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("relay to {address} rejected: {reason}")]
+    RelayRejected { address: String, reason: String },
+    #[error("tls error: {0}")]
+    TlsError(#[from] unified_io::errors::UnifiedIOError),
 }
 
 pub type RtmpServerResult<T> = Result<T, RtmpServerError>;
@@ -3,5 +3,7 @@ pub mod chunk_stream;
 pub mod config;
 pub mod consts;
 pub mod errors;
+pub mod message_stream;
+pub mod relay;
 pub mod server;
 pub mod session;
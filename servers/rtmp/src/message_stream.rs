@@ -0,0 +1,30 @@
+use std::collections::BTreeSet;
+
+use rtmp_formats::chunk::consts::message_stream::MAX_MESSAGE_STREAM_ID;
+
+/// a connection creating more streams than this is most likely leaking them
+pub const MAX_MESSAGE_STREAMS_PER_CONNECTION: usize = 64;
+
+/// the message stream ids of a connection, given out on createStream and taken back on deleteStream.
+/// the chunk streams of a message stream are derived from its id, see chunk::consts::message_stream
+#[derive(Debug, Default)]
+pub struct MessageStreamAllocator {
+    allocated: BTreeSet<u32>,
+}
+
+impl MessageStreamAllocator {
+    /// the lowest free id, 0 is the net connection and never given out
+    pub fn allocate(&mut self) -> Option<u32> {
+        if self.allocated.len() >= MAX_MESSAGE_STREAMS_PER_CONNECTION {
+            return None;
+        }
+        let id = (1..=MAX_MESSAGE_STREAM_ID).find(|id| !self.allocated.contains(id))?;
+        self.allocated.insert(id);
+        Some(id)
+    }
+
+    /// false if the id was not allocated
+    pub fn release(&mut self, id: u32) -> bool {
+        self.allocated.remove(&id)
+    }
+}
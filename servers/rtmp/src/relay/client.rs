@@ -0,0 +1,287 @@
+use std::io;
+
+use flv_formats::tag::{
+    FLVTag,
+    flv_tag_body::FLVTagBodyWithFilter,
+    flv_tag_header::{FLVTagHeader, FLVTagType},
+};
+use num::ToPrimitive;
+use rtmp_formats::{
+    chunk::{ChunkMessage, RtmpChunkMessageBody},
+    commands::{
+        ConnectCommandRequest, ConnectCommandRequestObject, CreateStreamCommandRequest,
+        DeleteStreamCommand, PlayCommand, PublishCommand, RtmpS2CCommands,
+        consts::c2s_command_names,
+    },
+    message::{RtmpUserMessageBody, aggregate::AggregateMessage},
+};
+use server_utils::relay::RelayEndpoint;
+use tokio_util::bytes::{Buf, Bytes};
+use unified_io::tcp::BoxedStream;
+use utils::traits::reader::ReadRemainingFrom;
+
+use crate::{
+    chunk_stream::RtmpChunkStream,
+    consts::{response_code, response_level},
+    errors::{RtmpServerError, RtmpServerResult},
+};
+
+const READ_BUFFER_CAPACITY: u64 = 4096;
+const CHUNK_SIZE: u32 = 4096;
+/// how long the other server has to answer a command, and to send something while playing
+const RELAY_TIMEOUT_MS: u64 = 10000;
+const CONNECT_TRANSACTION_ID: u8 = 1;
+const CREATE_STREAM_TRANSACTION_ID: f64 = 2.0;
+
+/// what the other server sends while a relay plays
+#[derive(Debug)]
+pub enum PlayedMessage {
+    /// a tag per message, and one per sub-message of an aggregate
+    Tags(Vec<FLVTag>),
+    /// the code of an onStatus
+    Status(String),
+    /// neither, e.g., a user control event
+    Other,
+}
+
+/// a rtmp client connection of a relay to another server, publishing or playing one stream there
+#[derive(Debug)]
+pub struct RelayClient {
+    chunk_stream: RtmpChunkStream,
+    endpoint: RelayEndpoint,
+    /// given by createStream
+    message_stream_id: u32,
+}
+
+impl RelayClient {
+    /// handshakes, connects to the app of the endpoint and creates a stream
+    pub async fn connect(io: BoxedStream, endpoint: RelayEndpoint) -> RtmpServerResult<Self> {
+        let mut chunk_stream =
+            RtmpChunkStream::new(READ_BUFFER_CAPACITY, io, RELAY_TIMEOUT_MS, RELAY_TIMEOUT_MS);
+        chunk_stream.set_client(true);
+        chunk_stream.handshake_as_client().await?;
+        chunk_stream.chunk_writer().write_set_chunk_size(CHUNK_SIZE)?;
+        chunk_stream
+            .chunk_writer()
+            .write_connect_request(ConnectCommandRequest {
+                command_name: c2s_command_names::CONNECT.to_owned(),
+                transaction_id: CONNECT_TRANSACTION_ID,
+                command_object: ConnectCommandRequestObject {
+                    app: endpoint.app.clone(),
+                    tc_url: endpoint.tc_url.clone(),
+                    ..Default::default()
+                },
+                optional_user_arguments: None,
+            })?;
+        chunk_stream.flush_chunk().await?;
+        let mut client = Self {
+            chunk_stream,
+            endpoint,
+            message_stream_id: 0,
+        };
+
+        loop {
+            if let RtmpS2CCommands::Connect(response) = client.read_command().await? {
+                if !response.success {
+                    return Err(client.rejected(format!("connect: {:?}", response.information)));
+                }
+                break;
+            }
+        }
+
+        client
+            .chunk_stream
+            .chunk_writer()
+            .write_create_stream_request(CreateStreamCommandRequest {
+                command_name: c2s_command_names::CREATE_STREAM.to_owned(),
+                transaction_id: CREATE_STREAM_TRANSACTION_ID,
+                command_object: None,
+            })?;
+        client.chunk_stream.flush_chunk().await?;
+        loop {
+            if let RtmpS2CCommands::CreateStream(response) = client.read_command().await? {
+                if !response.success {
+                    return Err(client.rejected("createStream".to_owned()));
+                }
+                client.message_stream_id = response.stream_id.to_u32().unwrap_or(1);
+                break;
+            }
+        }
+        Ok(client)
+    }
+
+    pub fn endpoint(&self) -> &RelayEndpoint {
+        &self.endpoint
+    }
+
+    pub async fn publish(&mut self) -> RtmpServerResult<()> {
+        self.chunk_stream
+            .chunk_writer()
+            .write_publish_request(PublishCommand::new(
+                self.endpoint.stream_name.clone(),
+                "live".to_owned(),
+            ))?;
+        self.chunk_stream.flush_chunk().await?;
+        self.wait_for_status(response_code::NET_STREAM_PUBLISH_START_SUCCESS)
+            .await
+    }
+
+    pub async fn play(&mut self) -> RtmpServerResult<()> {
+        self.chunk_stream
+            .chunk_writer()
+            .write_play_request(PlayCommand::new(self.endpoint.stream_name.clone()))?;
+        self.chunk_stream.flush_chunk().await?;
+        self.wait_for_status(response_code::NET_STREAM_PLAY_START)
+            .await
+    }
+
+    pub async fn write_tag(&mut self, tag: FLVTag) -> RtmpServerResult<()> {
+        self.chunk_stream
+            .write_tag(tag, self.message_stream_id)
+            .await
+    }
+
+    /// the next message of the stream played
+    pub async fn read_played(&mut self) -> RtmpServerResult<PlayedMessage> {
+        let Some(message) = self.chunk_stream.read_chunk().await? else {
+            if self.chunk_stream.is_peer_closed() {
+                return Err(self.peer_closed());
+            }
+            return Ok(PlayedMessage::Other);
+        };
+        let ChunkMessage {
+            header,
+            chunk_message_body: RtmpChunkMessageBody::RtmpUserMessage(body),
+        } = message
+        else {
+            return Ok(PlayedMessage::Other);
+        };
+        let payloads = match *body {
+            RtmpUserMessageBody::Audio { payload } => {
+                vec![(FLVTagType::Audio, header.timestamp, payload)]
+            }
+            RtmpUserMessageBody::Video { payload } => {
+                vec![(FLVTagType::Video, header.timestamp, payload)]
+            }
+            RtmpUserMessageBody::MetaData { payload } => {
+                vec![(FLVTagType::Script, header.timestamp, payload)]
+            }
+            RtmpUserMessageBody::Aggregate { payload } => {
+                AggregateMessage::split(&payload, header.timestamp)
+                    .messages
+                    .into_iter()
+                    .filter_map(|message| {
+                        let timestamp = message.header(&header).timestamp;
+                        match message.into_body() {
+                            RtmpUserMessageBody::Audio { payload } => {
+                                Some((FLVTagType::Audio, timestamp, payload))
+                            }
+                            RtmpUserMessageBody::Video { payload } => {
+                                Some((FLVTagType::Video, timestamp, payload))
+                            }
+                            RtmpUserMessageBody::MetaData { payload } => {
+                                Some((FLVTagType::Script, timestamp, payload))
+                            }
+                            _ => None,
+                        }
+                    })
+                    .collect()
+            }
+            RtmpUserMessageBody::S2Command(RtmpS2CCommands::OnStatus(status)) => {
+                let code = status
+                    .info_object
+                    .get("code")
+                    .and_then(|v| v.try_as_str())
+                    .unwrap_or_default();
+                return Ok(PlayedMessage::Status(code.to_owned()));
+            }
+            _ => return Ok(PlayedMessage::Other),
+        };
+        payloads
+            .into_iter()
+            .map(|(tag_type, timestamp, payload)| to_flv_tag(tag_type, timestamp, payload))
+            .collect::<RtmpServerResult<Vec<_>>>()
+            .map(PlayedMessage::Tags)
+    }
+
+    /// deletes the stream and closes the connection, the other server unpublishes or stops playing at once
+    pub async fn close(mut self) -> RtmpServerResult<()> {
+        self.chunk_stream
+            .chunk_writer()
+            .write_delete_stream_request(DeleteStreamCommand::new(
+                self.message_stream_id as f64,
+            ))?;
+        self.chunk_stream.flush_chunk().await?;
+        self.chunk_stream.shutdown().await?;
+        Ok(())
+    }
+
+    async fn read_command(&mut self) -> RtmpServerResult<RtmpS2CCommands> {
+        loop {
+            let Some(message) = self.chunk_stream.read_chunk().await? else {
+                if self.chunk_stream.is_peer_closed() {
+                    return Err(self.peer_closed());
+                }
+                continue;
+            };
+            if let RtmpChunkMessageBody::RtmpUserMessage(body) = message.chunk_message_body
+                && let RtmpUserMessageBody::S2Command(command) = *body
+            {
+                return Ok(command);
+            }
+        }
+    }
+
+    /// the onStatus of the publish or the play, a code of the error level fails it
+    async fn wait_for_status(&mut self, expected_code: &str) -> RtmpServerResult<()> {
+        loop {
+            let RtmpS2CCommands::OnStatus(status) = self.read_command().await? else {
+                continue;
+            };
+            let field = |key: &str| {
+                status
+                    .info_object
+                    .get(key)
+                    .and_then(|v| v.try_as_str())
+                    .unwrap_or_default()
+                    .to_owned()
+            };
+            let code = field("code");
+            if code == expected_code {
+                return Ok(());
+            }
+            if field("level") == response_level::ERROR {
+                return Err(self.rejected(format!("{}, {}", code, field("description"))));
+            }
+        }
+    }
+
+    fn peer_closed(&self) -> RtmpServerError {
+        RtmpServerError::Io(io::Error::new(
+            io::ErrorKind::ConnectionReset,
+            format!("{} closed the relay connection", self.endpoint.address()),
+        ))
+    }
+
+    fn rejected(&self, reason: String) -> RtmpServerError {
+        RtmpServerError::RelayRejected {
+            address: self.endpoint.address(),
+            reason,
+        }
+    }
+}
+
+fn to_flv_tag(tag_type: FLVTagType, timestamp: u32, payload: Bytes) -> RtmpServerResult<FLVTag> {
+    let tag_header = FLVTagHeader {
+        tag_type,
+        data_size: payload.len().to_u32().unwrap(),
+        timestamp,
+        filter_enabled: false,
+    };
+    let body_with_filter =
+        FLVTagBodyWithFilter::read_remaining_from(&tag_header, &mut payload.reader())?;
+    Ok(FLVTag {
+        tag_header,
+        body_with_filter,
+    })
+}
@@ -0,0 +1,408 @@
+//! runs the relays of a relay registry: a push relay publishes a stream live here to another server,
+//! a pull relay plays a stream subscribed to here and not published from another server.
+//! the relays are started and stopped as the registry changes and the streams come and go
+
+use std::{collections::HashMap, fmt::Debug, io, sync::Arc, time::Duration};
+
+use codec_common::video::{H264VideoConfig, VideoCodecCommon, VideoConfig};
+use futures::future::BoxFuture;
+use server_utils::relay::{Relay, RelayEndpoint, RelayRegistry, RelayRule};
+use stream_center::{
+    events::StreamCenterEvent,
+    gop::{FlvVideoHeader, MediaFrame},
+    identity::StreamIdentity,
+    notification::StreamNotification,
+    stream_center::StreamCenter,
+    stream_source::{MediaSelection, PlayProtocol, PublishProtocol},
+};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot},
+    task::JoinHandle,
+};
+use unified_io::tcp::BoxedStream;
+use uuid::Uuid;
+
+use crate::{
+    consts::response_code,
+    errors::{RtmpServerError, RtmpServerResult},
+};
+
+use client::{PlayedMessage, RelayClient};
+
+pub mod client;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const FRAME_BATCH: usize = 128;
+
+/// opens the connections of the relays to other servers
+pub trait RelayConnector: Debug + Send + Sync {
+    fn connect<'a>(
+        &'a self,
+        endpoint: &'a RelayEndpoint,
+    ) -> BoxFuture<'a, RtmpServerResult<BoxedStream>>;
+}
+
+/// connects over tcp
+#[derive(Debug, Default)]
+pub struct TcpRelayConnector;
+
+impl RelayConnector for TcpRelayConnector {
+    fn connect<'a>(
+        &'a self,
+        endpoint: &'a RelayEndpoint,
+    ) -> BoxFuture<'a, RtmpServerResult<BoxedStream>> {
+        Box::pin(async move {
+            let host = endpoint.host.trim_start_matches('[').trim_end_matches(']');
+            let stream = tokio::time::timeout(
+                CONNECT_TIMEOUT,
+                tokio::net::TcpStream::connect((host, endpoint.port)),
+            )
+            .await
+            .map_err(|_| {
+                RtmpServerError::Io(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("connect to {} timed out", endpoint.address()),
+                ))
+            })??;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream) as BoxedStream)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct RelayTask {
+    /// dropped to stop the relay, which deletes its stream on the other server before closing
+    _stop_sender: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+/// starts and stops the relays of a registry
+#[derive(Debug)]
+pub struct RelayRunner {
+    registry: RelayRegistry,
+    connector: Arc<dyn RelayConnector>,
+    stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    notifications: broadcast::Receiver<StreamNotification>,
+    /// by the relay and the stream relayed
+    tasks: HashMap<(Uuid, StreamIdentity), RelayTask>,
+}
+
+impl RelayRunner {
+    pub fn new(
+        registry: RelayRegistry,
+        stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+        notifications: broadcast::Receiver<StreamNotification>,
+    ) -> Self {
+        Self {
+            registry,
+            connector: Arc::new(TcpRelayConnector),
+            stream_center_event_sender,
+            notifications,
+            tasks: HashMap::new(),
+        }
+    }
+
+    pub fn with_connector(mut self, connector: Arc<dyn RelayConnector>) -> Self {
+        self.connector = connector;
+        self
+    }
+
+    /// runs until the stream center is gone, the relays running are stopped then
+    pub async fn run(mut self) {
+        let mut relays = self.registry.watch();
+        let current = relays.borrow_and_update().clone();
+        self.sync(&current).await;
+        loop {
+            tokio::select! {
+                changed = relays.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let current = relays.borrow_and_update().clone();
+                    self.sync(&current).await;
+                }
+                notification = self.notifications.recv() => match notification {
+                    Ok(notification) => self.on_notification(notification),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("relay runner missed {} stream notifications", missed);
+                        let current = relays.borrow().clone();
+                        self.sync(&current).await;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+        tracing::info!("relay runner exits, {} relays stopped", self.tasks.len());
+    }
+
+    /// stops the relays removed and starts the pushes of the streams live already
+    async fn sync(&mut self, relays: &[Relay]) {
+        self.tasks.retain(|(relay_id, stream_id), task| {
+            let kept = relays.iter().any(|relay| relay.id == *relay_id);
+            if !kept {
+                tracing::info!("relay {} of stream {} is removed, stop it", relay_id, stream_id);
+            }
+            kept && !task.handle.is_finished()
+        });
+        if !relays
+            .iter()
+            .any(|relay| matches!(relay.rule, RelayRule::Push { .. }))
+        {
+            return;
+        }
+        let streams = match StreamCenter::streams(&self.stream_center_event_sender).await {
+            Ok(streams) => streams,
+            Err(err) => {
+                tracing::error!("list streams to push failed: {}", err);
+                return;
+            }
+        };
+        for stream_id in streams {
+            self.start_pushes(relays, &stream_id);
+        }
+    }
+
+    fn on_notification(&mut self, notification: StreamNotification) {
+        match notification {
+            StreamNotification::Published { stream_id, .. }
+            | StreamNotification::Restored { stream_id, .. } => {
+                self.start_pushes(&self.registry.relays(), &stream_id)
+            }
+            // the relay subscribes to the streams it pushes, which are published
+            StreamNotification::SubscribeMissed {
+                stream_id,
+                protocol,
+            } if protocol != PlayProtocol::Relay => {
+                if let Some(relay) = self.registry.pull_relay_of(&stream_id) {
+                    self.start(&relay, &stream_id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn start_pushes(&mut self, relays: &[Relay], stream_id: &StreamIdentity) {
+        for relay in relays {
+            if matches!(relay.rule, RelayRule::Push { .. })
+                && relay.rule.pattern().matches(stream_id)
+            {
+                self.start(relay, stream_id);
+            }
+        }
+    }
+
+    /// a relay runs once per stream, until it fails or the stream ends
+    fn start(&mut self, relay: &Relay, stream_id: &StreamIdentity) {
+        let key = (relay.id, stream_id.clone());
+        if self
+            .tasks
+            .get(&key)
+            .is_some_and(|task| !task.handle.is_finished())
+        {
+            return;
+        }
+        let endpoint = match relay.rule.endpoint_for(stream_id) {
+            Ok(endpoint) => endpoint,
+            Err(err) => {
+                tracing::error!("relay {} of stream {} failed: {}", relay.id, stream_id, err);
+                return;
+            }
+        };
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        let connector = self.connector.clone();
+        let sender = self.stream_center_event_sender.clone();
+        let relay_id = relay.id;
+        let stream_id = stream_id.clone();
+        let pull = matches!(relay.rule, RelayRule::Pull { .. });
+        let handle = tokio::spawn(async move {
+            let kind = if pull { "pull" } else { "push" };
+            tracing::info!(
+                "{} relay {} of stream {} started, {} {}",
+                kind,
+                relay_id,
+                stream_id,
+                endpoint.tc_url,
+                endpoint.stream_name
+            );
+            let result = if pull {
+                pull_stream(connector, endpoint, &stream_id, sender, stop_receiver).await
+            } else {
+                push_stream(connector, endpoint, &stream_id, sender, stop_receiver).await
+            };
+            match result {
+                Ok(()) => tracing::info!("{} relay {} of stream {} stopped", kind, relay_id, stream_id),
+                Err(err) => tracing::warn!(
+                    "{} relay {} of stream {} failed: {}",
+                    kind,
+                    relay_id,
+                    stream_id,
+                    err
+                ),
+            }
+        });
+        self.tasks.insert(
+            key,
+            RelayTask {
+                _stop_sender: stop_sender,
+                handle,
+            },
+        );
+    }
+}
+
+/// plays the stream here and publishes it to the other server until it ends or the relay is stopped
+async fn push_stream(
+    connector: Arc<dyn RelayConnector>,
+    endpoint: RelayEndpoint,
+    stream_id: &StreamIdentity,
+    stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    mut stop_receiver: oneshot::Receiver<()>,
+) -> RtmpServerResult<()> {
+    let response = StreamCenter::subscribe(
+        &stream_center_event_sender,
+        PlayProtocol::Relay,
+        stream_id,
+        &HashMap::new(),
+        MediaSelection::default(),
+    )
+    .await?;
+    let mut media_receiver = response.media_receiver;
+    let result = async {
+        let io = connector.connect(&endpoint).await?;
+        let mut client = RelayClient::connect(io, endpoint).await?;
+        client.publish().await?;
+        let mut frames = Vec::with_capacity(FRAME_BATCH);
+        let mut nalu_size_length = 4;
+        'push: loop {
+            frames.clear();
+            tokio::select! {
+                _ = &mut stop_receiver => break,
+                len = media_receiver.recv_many(&mut frames, FRAME_BATCH) => {
+                    if len == 0 {
+                        break;
+                    }
+                    for frame in &frames {
+                        if frame.is_final() {
+                            break 'push;
+                        }
+                        if frame.is_control() {
+                            continue;
+                        }
+                        if let MediaFrame::VideoConfig { config, .. } = frame
+                            && let VideoConfig::H264(H264VideoConfig {
+                                avc_decoder_configuration_record: Some(record),
+                                ..
+                            }) = config.as_ref()
+                        {
+                            nalu_size_length = record.length_size_minus_one.checked_add(1).unwrap();
+                        }
+                        let video_codec_id = match frame {
+                            MediaFrame::VideoConfig { config, .. } => Some(config.as_ref().into()),
+                            _ => frame.video_codec_id(),
+                        };
+                        // the other server is not asked which codecs it takes, avc goes as every server reads it
+                        let video_header = match video_codec_id {
+                            None | Some(VideoCodecCommon::AVC) => FlvVideoHeader::Legacy,
+                            Some(_) => FlvVideoHeader::Enhanced,
+                        };
+                        let tag = frame.to_flv_tag_with_video_header(nalu_size_length, video_header)?;
+                        client.write_tag(tag).await?;
+                    }
+                }
+                message = client.read_played() => match message {
+                    Ok(_) => {}
+                    // the server stays silent while a stream is published to it
+                    Err(RtmpServerError::Io(err))
+                        if matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => {}
+                    Err(err) => return Err(err),
+                },
+            }
+        }
+        client.close().await
+    }
+    .await;
+    if let Err(err) = StreamCenter::unsubscribe(
+        &stream_center_event_sender,
+        response.subscribe_id,
+        stream_id,
+    )
+    .await
+    {
+        tracing::warn!("unsubscribe push relay of stream {} failed: {}", stream_id, err);
+    }
+    result
+}
+
+/// plays the stream from the other server and publishes it here until it ends there or the relay is stopped
+async fn pull_stream(
+    connector: Arc<dyn RelayConnector>,
+    endpoint: RelayEndpoint,
+    stream_id: &StreamIdentity,
+    stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    mut stop_receiver: oneshot::Receiver<()>,
+) -> RtmpServerResult<()> {
+    let io = connector.connect(&endpoint).await?;
+    let mut client = RelayClient::connect(io, endpoint).await?;
+    client.play().await?;
+    let response = StreamCenter::publish_kickable(
+        &stream_center_event_sender,
+        PublishProtocol::Relay,
+        stream_id,
+        &HashMap::new(),
+    )
+    .await?;
+    let mut kicked_receiver = response.kicked_receiver;
+    let mut nalu_size_length = 4;
+    let result = 'pull: loop {
+        tokio::select! {
+            _ = &mut stop_receiver => break Ok(()),
+            Ok(kicked) = &mut kicked_receiver => {
+                tracing::info!("pull relay of stream {} is kicked: {:?}", stream_id, kicked.reason);
+                break Ok(());
+            }
+            message = client.read_played() => match message {
+                Ok(PlayedMessage::Tags(tags)) => {
+                    for tag in tags {
+                        let frame = match MediaFrame::from_flv_tag(tag, nalu_size_length) {
+                            Ok(frame) => frame,
+                            Err(err) => break 'pull Err(err.into()),
+                        };
+                        if let MediaFrame::VideoConfig { config, .. } = &frame
+                            && let VideoConfig::H264(H264VideoConfig {
+                                avc_decoder_configuration_record: Some(record),
+                                ..
+                            }) = config.as_ref()
+                        {
+                            nalu_size_length = record.length_size_minus_one.checked_add(1).unwrap();
+                        }
+                        if response.media_sender.send(frame).await.is_err() {
+                            break 'pull Err(RtmpServerError::StreamIsGone);
+                        }
+                    }
+                }
+                Ok(PlayedMessage::Status(code))
+                    if code == response_code::NET_STREAM_PLAY_STOP
+                        || code == response_code::NET_STREAM_PLAY_UNPUBLISH_NOTIFY =>
+                {
+                    break Ok(());
+                }
+                Ok(_) => {}
+                Err(err) => break Err(err),
+            },
+        }
+    };
+    if let Err(err) = StreamCenter::unpublish_publisher(
+        &stream_center_event_sender,
+        stream_id,
+        response.publisher_id,
+    )
+    .await
+    {
+        tracing::warn!("unpublish pull relay of stream {} failed: {}", stream_id, err);
+    }
+    if let Err(err) = client.close().await {
+        tracing::debug!("close pull relay of stream {} failed: {}", stream_id, err);
+    }
+    result
+}
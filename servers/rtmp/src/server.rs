@@ -1,64 +1,211 @@
+use std::net::SocketAddr;
+
+use amf_formats::limits::ReadLimits;
+use server_utils::{
+    egress_shaping::EgressShaper,
+    ingest_limit::IngestRateLimiter,
+    log_context::session_span,
+    metrics::ConnectionMetricsGuard,
+    session_registry::{SessionHandle, SessionProtocol, SessionRegistry, io::CountedStream},
+    supervisor::AbortOnDrop,
+};
 use stream_center::events::StreamCenterEvent;
 use tokio::sync::mpsc;
+use tracing::Instrument;
+use unified_io::{
+    channel::ChannelListener,
+    proxy_protocol::resolve_peer_addr,
+    tcp::{AsyncReadWrite, BoxedStream},
+    tls::TlsAcceptor,
+};
 
-use crate::config::RtmpSessionConfig;
+use crate::config::{RtmpControls, RtmpSessionConfig};
 
 use super::{config::RtmpServerConfig, errors::RtmpServerResult, session::RtmpSession};
 
 #[derive(Debug)]
 pub struct RtmpServer {
     config: RtmpServerConfig,
+    ingest_limiter: IngestRateLimiter,
+    egress_shaper: EgressShaper,
+    session_registry: SessionRegistry,
     stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
 }
 
 impl RtmpServer {
     pub fn new(
         config: RtmpServerConfig,
+        ingest_limiter: IngestRateLimiter,
+        egress_shaper: EgressShaper,
+        session_registry: SessionRegistry,
         stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
     ) -> Self {
         Self {
             config,
+            ingest_limiter,
+            egress_shaper,
+            session_registry,
             stream_center_event_sender,
         }
     }
 
     pub async fn run(&mut self) -> RtmpServerResult<()> {
         tracing::info!("rtmp server is running: {:?}", self.config);
+        let controls = self.config.controls()?;
+        let amf_limits = self.config.amf_limits()?;
         let listener =
             tokio::net::TcpListener::bind((self.config.address, self.config.port)).await?;
+        let rtmps = match &self.config.rtmps {
+            Some(rtmps) => {
+                let acceptor = TlsAcceptor::new(rtmps.tls.clone())?;
+                // a run after a crash spawns a reloader of its own
+                let reloader = AbortOnDrop(acceptor.spawn_reloader());
+                let listener =
+                    tokio::net::TcpListener::bind((self.config.address, rtmps.port)).await?;
+                tracing::info!("rtmps is listening on port: {}", rtmps.port);
+                Some((listener, acceptor, reloader))
+            }
+            None => None,
+        };
         loop {
-            let (tcp_stream, addr) = listener.accept().await?;
+            let (mut tcp_stream, addr, tls_acceptor) = tokio::select! {
+                accepted = listener.accept() => {
+                    let (tcp_stream, addr) = accepted?;
+                    (tcp_stream, addr, None)
+                }
+                accepted = async {
+                    match &rtmps {
+                        Some((listener, acceptor, _)) => listener
+                            .accept()
+                            .await
+                            .map(|(tcp_stream, addr)| (tcp_stream, addr, Some(acceptor.clone()))),
+                        None => std::future::pending().await,
+                    }
+                } => accepted?,
+            };
             let peer_addr = tcp_stream.peer_addr();
             tracing::info!(
-                "got new rtmp connection, addr: {}, peer addr: {:?}",
+                "got new rtmp connection, addr: {}, peer addr: {:?}, tls: {}",
                 addr,
-                peer_addr
-            );
-            let mut session = RtmpSession::new(
-                tcp_stream,
-                self.stream_center_event_sender.clone(),
-                RtmpSessionConfig {
-                    chunk_size: self.config.chunk_size,
-                    write_timeout_ms: self.config.write_timeout_ms,
-                    read_timeout_ms: self.config.read_timeout_ms,
-                },
+                peer_addr,
+                tls_acceptor.is_some()
             );
+            let session = self.new_session(&controls, amf_limits);
+            let session_registry = self.session_registry.clone();
+            let trusted_proxies = self.config.trusted_proxies.clone();
             tokio::spawn(async move {
-                match session.run().await {
-                    Ok(()) => {
-                        tracing::info!(
-                            "rtmp session successfully closed, addr: {}, peer addr: {:?}",
-                            addr,
-                            peer_addr
-                        );
+                // the proxy protocol header comes ahead of the tls handshake,
+                // both run in the session task so that a slow or broken peer does not block the accept loop
+                let addr = match resolve_peer_addr(&mut tcp_stream, addr, &trusted_proxies).await {
+                    Ok(client_addr) => {
+                        if client_addr != addr {
+                            tracing::info!(
+                                "rtmp connection of {} is proxied by {}",
+                                client_addr,
+                                addr
+                            );
+                        }
+                        client_addr
                     }
                     Err(err) => {
-                        tracing::error!("{:?}", err);
+                        tracing::warn!("refuse rtmp connection, addr: {}, err: {}", addr, err);
+                        return;
                     }
                 };
-                session.log_stats().await;
-                let _ = session.clean_up().await;
+                async move {
+                    let registry_handle = session_registry.register(SessionProtocol::Rtmp, addr);
+                    let io: BoxedStream = match tls_acceptor {
+                        Some(acceptor) => match acceptor.accept_stream(tcp_stream).await {
+                            Ok(tls_stream) => Box::new(tls_stream),
+                            Err(err) => {
+                                tracing::warn!(
+                                    "rtmps handshake failed, addr: {}, peer addr: {:?}, err: {}",
+                                    addr,
+                                    peer_addr,
+                                    err
+                                );
+                                return;
+                            }
+                        },
+                        None => Box::new(tcp_stream),
+                    };
+                    Self::run_session(session(io, registry_handle), addr).await;
+                }
+                .instrument(session_span("rtmp", addr))
+                .await
             });
         }
     }
+
+    /// serves the connections handed over by `listener` instead of binding sockets,
+    /// e.g. the in-memory ones of tests
+    pub async fn serve<T: AsyncReadWrite + 'static>(
+        &mut self,
+        mut listener: ChannelListener<T>,
+    ) -> RtmpServerResult<()> {
+        tracing::info!(
+            "rtmp server is serving a channel listener: {:?}",
+            self.config
+        );
+        let controls = self.config.controls()?;
+        let amf_limits = self.config.amf_limits()?;
+        while let Some((io, addr)) = listener.accept().await {
+            tracing::info!("got new rtmp connection from channel, addr: {}", addr);
+            let session = self.new_session(&controls, amf_limits);
+            let registry_handle = self.session_registry.register(SessionProtocol::Rtmp, addr);
+            tokio::spawn(
+                async move {
+                    Self::run_session(session(Box::new(io), registry_handle), addr).await;
+                }
+                .instrument(session_span("rtmp", addr)),
+            );
+        }
+        Ok(())
+    }
+
+    /// captures the server state a session needs, so it can be created in the session task
+    fn new_session(
+        &self,
+        controls: &RtmpControls,
+        amf_limits: ReadLimits,
+    ) -> impl FnOnce(BoxedStream, SessionHandle) -> RtmpSession + Send + 'static {
+        let stream_center_event_sender = self.stream_center_event_sender.clone();
+        let session_config = RtmpSessionConfig {
+            controls: controls.clone(),
+            amf_limits,
+            write_timeout_ms: self.config.write_timeout_ms,
+            read_timeout_ms: self.config.read_timeout_ms,
+            forward_video_four_cc: self.config.forward_video_four_cc.clone(),
+            forward_audio_four_cc: self.config.forward_audio_four_cc.clone(),
+            send_on_bw_done: self.config.send_on_bw_done,
+        };
+        let ingest_limiter = self.ingest_limiter.clone();
+        let egress_shaper = self.egress_shaper.clone();
+        move |io, registry_handle| {
+            // the bytes of the connection are counted in the session registry
+            let io = Box::new(CountedStream::new(io, registry_handle.counters()));
+            RtmpSession::new(
+                io,
+                stream_center_event_sender,
+                session_config,
+                ingest_limiter,
+                egress_shaper,
+                registry_handle,
+            )
+        }
+    }
+
+    async fn run_session(mut session: RtmpSession, addr: SocketAddr) {
+        let _connection = ConnectionMetricsGuard::accepted("rtmp");
+        match session.run().await {
+            Ok(()) => {
+                tracing::info!("rtmp session successfully closed, addr: {}", addr);
+            }
+            Err(err) => {
+                tracing::error!("{:?}", err);
+            }
+        };
+        session.log_stats().await;
+        let _ = session.clean_up().await;
+    }
 }
@@ -3,13 +3,19 @@ use super::{
     consts::{response_code, response_level},
     errors::RtmpServerResult,
 };
-use crate::{chunk_stream::RtmpChunkStream, errors::RtmpServerError};
-use ::stream_center::{events::StreamCenterEvent, stream_source::StreamIdentifier};
-use codec_common::video::{H264VideoConfig, VideoConfig};
+use crate::{
+    chunk_stream::RtmpChunkStream, errors::RtmpServerError, message_stream::MessageStreamAllocator,
+};
+use ::stream_center::{events::StreamCenterEvent, identity::StreamIdentity};
+use codec_common::video::{H264VideoConfig, VideoCodecCommon, VideoConfig};
 use flv_formats::tag::{
     FLVTag,
-    flv_tag_body::FLVTagBodyWithFilter,
+    audio_tag_header_info::AudioTagHeaderWithoutMultiTrack,
+    enhanced::{ex_audio::ex_audio_header::AudioFourCC, ex_video::ex_video_header::VideoFourCC},
+    flv_tag_body::{FLVTagBody, FLVTagBodyWithFilter},
     flv_tag_header::{FLVTagHeader, FLVTagType},
+    video_tag_header::VideoTagHeader,
+    video_tag_header_info::VideoTagHeaderWithoutMultiTrack,
 };
 use num::ToPrimitive;
 use rtmp_formats::{
@@ -20,44 +26,46 @@ use rtmp_formats::{
         CallCommandRequest, ConnectCommandRequest, ConnectCommandRequestObject,
         CreateStreamCommandRequest, DeleteStreamCommand, PauseCommand, Play2Command, PlayCommand,
         PublishCommand, ReceiveAudioCommand, ReceiveVideoCommand, RtmpC2SCommands, SeekCommand,
-        consts::RESPONSE_STREAM_ID,
+        four_cc_registry::{FOUR_CC_WILDCARD, FourCCRegistry},
     },
-    message::RtmpUserMessageBody,
+    message::{RtmpUserMessageBody, aggregate::AggregateMessage},
     protocol_control::SetPeerBandWidthLimitType,
     user_control::UserControlEvent,
 };
 use server_utils::{
+    egress_shaping::EgressShaper,
+    ingest_limit::IngestRateLimiter,
+    log_context::{StreamRole, stream_span},
     runtime_handle::{PlayHandle, PublishHandle, SessionRuntime},
-    stream_properities::StreamProperties,
+    session_registry::{SessionHandle, SessionRole},
 };
 use std::{
-    backtrace::Backtrace,
-    collections::HashMap,
-    io::{self, Cursor, Read},
-    sync::Arc,
-    time::SystemTime,
+    backtrace::Backtrace, collections::HashMap, io, ops::ControlFlow, sync::Arc, time::SystemTime,
 };
 use stream_center::{
+    backpressure::BackpressureAdvisory,
+    drain::DrainRequest,
     events::SubscribeResponse,
-    gop::MediaFrame,
+    gop::{FlvVideoHeader, MediaFrame},
+    rtmp_control::{PeerBandwidthLimitType, RtmpControl},
     stream_center::StreamCenter,
-    stream_source::{PlayProtocol, PublishProtocol},
+    stream_source::{AUDIO_TRACK_KEY, MediaSelection, PlayProtocol, PublishProtocol},
+    takeover::{KickReason, PublisherKicked},
 };
-use tokio::{
-    net::TcpStream,
-    sync::{
-        RwLock,
-        mpsc::{self},
-    },
+use tokio::sync::{
+    RwLock,
+    mpsc::{self},
+    oneshot,
 };
 use tokio_util::{
     bytes::{Buf, Bytes},
     either::Either,
 };
+use tracing::{Instrument, Span};
+use unified_io::tcp::BoxedStream;
 use url::Url;
 use utils::{
-    system::time::get_timestamp_ns,
-    traits::reader::{ReadFrom, ReadRemainingFrom},
+    media_url::MediaUrl, system::time::get_timestamp_ns, traits::reader::ReadRemainingFrom,
 };
 use uuid::Uuid;
 
@@ -65,35 +73,81 @@ use uuid::Uuid;
 pub struct RtmpSession {
     chunk_stream: RtmpChunkStream,
     runtime_handle: SessionRuntime,
-    stream_properties: StreamProperties,
+    /// none until the client publishes or plays a stream
+    stream_id: Option<StreamIdentity>,
+    stream_context: HashMap<String, String>,
     video_nalu_size_length: Option<u8>,
+    /// the codec and the timestamp of the last enhanced video tag played,
+    /// the sequence of it is ended once the stream stops
+    enhanced_video_sequence: Option<(VideoCodecCommon, u32)>,
     connect_info: ConnectCommandRequestObject,
+    /// the app connected to, the names of the streams published or played are under it
+    connect_url: MediaUrl,
     total_wrote_bytes: usize,
     config: RtmpSessionConfig,
+    /// the protocol control settings of the app connected to, the ones of the server until connected
+    control: RtmpControl,
+    four_cc_registry: FourCCRegistry,
+    ingest_limiter: IngestRateLimiter,
+    egress_shaper: EgressShaper,
     stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    message_streams: MessageStreamAllocator,
+    /// the message stream publishing or playing, responses and media of it go there
+    message_stream_id: u32,
+    /// set while publishing
+    publisher_id: Option<Uuid>,
+    kicked_receiver: Option<oneshot::Receiver<PublisherKicked>>,
+    drain_receiver: Option<oneshot::Receiver<DrainRequest>>,
+    /// the advisories of the stream center while publishing to a congested stream
+    backpressure_receiver: Option<mpsc::UnboundedReceiver<BackpressureAdvisory>>,
+    /// the entry of the session in the session registry, the admin api may ask to close it
+    registry_handle: SessionHandle,
+    /// the span of the connection, the session is created in the task instrumented with it
+    session_span: Span,
+    /// set once publishing or playing, the processing of the stream runs in it
+    stream_span: Option<Span>,
 }
 
 impl RtmpSession {
     pub fn new(
-        io: TcpStream,
+        io: BoxedStream,
         stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
         config: RtmpSessionConfig,
+        ingest_limiter: IngestRateLimiter,
+        egress_shaper: EgressShaper,
+        registry_handle: SessionHandle,
     ) -> Self {
+        let mut chunk_stream =
+            RtmpChunkStream::new(4096, io, config.read_timeout_ms, config.write_timeout_ms);
+        chunk_stream.set_max_message_size(ingest_limiter.config().max_rtmp_message_size);
+        chunk_stream.set_amf_limits(config.amf_limits);
+        let four_cc_registry = config.four_cc_registry();
+        let control = config.controls.default;
         Self {
-            chunk_stream: RtmpChunkStream::new(
-                4096,
-                io,
-                config.chunk_size,
-                config.read_timeout_ms,
-                config.write_timeout_ms,
-            ),
-            stream_properties: StreamProperties::default(),
+            chunk_stream,
+            stream_id: None,
+            stream_context: HashMap::new(),
             video_nalu_size_length: None,
+            enhanced_video_sequence: None,
             connect_info: Default::default(),
+            connect_url: MediaUrl::new(RTMP_SCHEME, DEFAULT_HOST, None),
             runtime_handle: SessionRuntime::Unknown,
             total_wrote_bytes: 0,
             config,
+            control,
+            four_cc_registry,
+            ingest_limiter,
+            egress_shaper,
             stream_center_event_sender,
+            message_streams: MessageStreamAllocator::default(),
+            message_stream_id: 0,
+            publisher_id: None,
+            kicked_receiver: None,
+            drain_receiver: None,
+            backpressure_receiver: None,
+            registry_handle,
+            session_span: Span::current(),
+            stream_span: None,
         }
     }
 
@@ -101,74 +155,156 @@ impl RtmpSession {
         self.chunk_stream.handshake().await?;
 
         loop {
-            let play_handle = match &self.runtime_handle {
-                SessionRuntime::Play(handle) => Some(handle.clone()),
-                _ => None,
-            };
+            let stream_span = self.stream_span.clone().unwrap_or_else(Span::none);
+            if self.run_once().instrument(stream_span).await?.is_break() {
+                return Ok(());
+            }
+        }
+    }
 
-            if let Some(play_handle) = play_handle {
-                // let play_id = play_handle.read().await.play_id.clone();
-                let res = self.playing(play_handle).await;
-                match res {
-                    Ok(_) => {
-                        tracing::info!("play session successfully end");
-                    }
-                    Err(err) => {
-                        tracing::info!("play session end with err: {:?}", err);
-                    }
+    /// plays to the end, or reads and processes one message
+    async fn run_once(&mut self) -> RtmpServerResult<ControlFlow<()>> {
+        let play_handle = match &self.runtime_handle {
+            SessionRuntime::Play(handle) => Some(handle.clone()),
+            _ => None,
+        };
+
+        if let Some(play_handle) = play_handle {
+            // let play_id = play_handle.read().await.play_id.clone();
+            let res = self.playing(play_handle).await;
+            match res {
+                Ok(_) => {
+                    tracing::info!("play session successfully end");
+                }
+                Err(err) => {
+                    tracing::info!("play session end with err: {:?}", err);
                 }
-                return Ok(());
             }
+            return Ok(ControlFlow::Break(()));
+        }
 
-            match self.chunk_stream.read_chunk().await {
-                Ok(maybe_chunk) => match maybe_chunk {
-                    Some(message) => {
-                        self.process_message(message).await?;
-                    }
-                    None => {
-                        if let SessionRuntime::Publish(handle) = &self.runtime_handle {
-                            let current_time = SystemTime::now();
-                            if current_time
-                                .duration_since(
-                                    handle.read().await.no_data_since.unwrap_or(current_time),
-                                )
-                                .expect("stop time must be before")
-                                .as_secs()
-                                > 10
-                            {
-                                // 10 seconds after publish stop, and no data received, we close this session
-                                tracing::info!("publish session timeout, closing");
-                                return Ok(());
-                            }
+        // a publisher taken over by another one, or drained, is told so while waiting for the next chunk
+        let incoming = tokio::select! {
+            kicked = Self::receive(&mut self.kicked_receiver) => Incoming::Kicked(kicked),
+            drain = Self::receive(&mut self.drain_receiver) => Incoming::Drain(drain),
+            advisory = Self::receive_advisory(&mut self.backpressure_receiver) => {
+                Incoming::Backpressure(advisory)
+            }
+            chunk = self.chunk_stream.read_chunk() => Incoming::Chunk(chunk),
+            _ = self.registry_handle.close_requested() => Incoming::Close,
+        };
+        let chunk = match incoming {
+            Incoming::Close => {
+                self.on_close_requested().await?;
+                return Ok(ControlFlow::Break(()));
+            }
+            Incoming::Kicked(Some(kicked)) => {
+                return self
+                    .on_publisher_kicked(kicked)
+                    .await
+                    .map(ControlFlow::Break);
+            }
+            Incoming::Kicked(None) => {
+                // the stream is gone without takeover
+                self.kicked_receiver = None;
+                return Ok(ControlFlow::Continue(()));
+            }
+            Incoming::Drain(drain) => {
+                self.drain_receiver = None;
+                if let Some(request) = drain {
+                    self.on_drain(request).await?;
+                }
+                return Ok(ControlFlow::Continue(()));
+            }
+            Incoming::Backpressure(advisory) => {
+                match advisory {
+                    Some(advisory) => self.on_backpressure(advisory).await?,
+                    None => self.backpressure_receiver = None,
+                }
+                return Ok(ControlFlow::Continue(()));
+            }
+            Incoming::Chunk(chunk) => chunk,
+        };
+
+        match chunk {
+            Ok(maybe_chunk) => match maybe_chunk {
+                Some(message) => {
+                    self.process_message(message).await?;
+                }
+                None => {
+                    if let SessionRuntime::Publish(handle) = &self.runtime_handle {
+                        let current_time = SystemTime::now();
+                        if current_time
+                            .duration_since(
+                                handle.read().await.no_data_since.unwrap_or(current_time),
+                            )
+                            .expect("stop time must be before")
+                            .as_secs()
+                            > 10
+                        {
+                            // 10 seconds after publish stop, and no data received, we close this session
+                            tracing::info!("publish session timeout, closing");
+                            return Ok(ControlFlow::Break(()));
                         }
                     }
-                },
-                Err(err) => match err {
-                    RtmpServerError::ChunkMessageReadFailed(
-                        ChunkMessageError::UnknownMessageType { type_id, backtrace },
-                    ) => {
-                        tracing::error!(
-                            "got unknown message: type_id: {}, backtrace: {}",
-                            type_id,
-                            backtrace
-                        );
-                    }
-                    RtmpServerError::Io(io_err) => {
-                        if io_err.kind() == io::ErrorKind::WouldBlock {
-                            continue;
-                        }
-                        if io_err.kind() == io::ErrorKind::ConnectionReset {
-                            tracing::info!("connect reset by peer");
-                            return Ok(());
-                        }
-                        tracing::error!("io error: {:?}", io_err);
+                }
+            },
+            Err(err) => match err {
+                RtmpServerError::ChunkMessageReadFailed(
+                    ChunkMessageError::UnknownMessageType { type_id, backtrace },
+                ) => {
+                    tracing::error!(
+                        "got unknown message: type_id: {}, backtrace: {}",
+                        type_id,
+                        backtrace
+                    );
+                }
+                err @ RtmpServerError::ChunkMessageReadFailed(
+                    ChunkMessageError::MessageTooLarge { .. },
+                ) => {
+                    tracing::error!(
+                        "rejecting oversize message, stream: {}, err: {}",
+                        self.stream_key(),
+                        err
+                    );
+                    self.ingest_limiter.metrics().on_oversize_rejected();
+                    let _ = self.reject_publish(&format!("{}", err)).await;
+                    return Err(err);
+                }
+                RtmpServerError::Io(io_err) => {
+                    if io_err.kind() == io::ErrorKind::WouldBlock {
+                        return Ok(ControlFlow::Continue(()));
                     }
-                    err => {
-                        tracing::error!("{:?}", err);
-                        panic!();
+                    if io_err.kind() == io::ErrorKind::ConnectionReset {
+                        tracing::info!("connect reset by peer");
+                        return Ok(ControlFlow::Break(()));
                     }
-                },
-            }
+                    tracing::error!("io error: {:?}", io_err);
+                }
+                err => {
+                    tracing::error!("{:?}", err);
+                    panic!();
+                }
+            },
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// pends forever without a receiver, None if the sender is gone
+    async fn receive<T>(receiver: &mut Option<oneshot::Receiver<T>>) -> Option<T> {
+        match receiver {
+            Some(receiver) => receiver.await.ok(),
+            None => std::future::pending().await,
+        }
+    }
+
+    /// pends forever without a receiver, None if the stream is gone
+    async fn receive_advisory(
+        receiver: &mut Option<mpsc::UnboundedReceiver<BackpressureAdvisory>>,
+    ) -> Option<BackpressureAdvisory> {
+        match receiver {
+            Some(receiver) => receiver.recv().await,
+            None => std::future::pending().await,
         }
     }
 
@@ -178,7 +314,10 @@ impl RtmpSession {
                 let play_id = play_handle.read().await.play_id;
                 self.unsubscribe_from_stream_center(play_id).await?
             }
-            SessionRuntime::Publish(_publish_handle) => self.unpublish_from_stream_center().await?,
+            SessionRuntime::Publish(_publish_handle) => {
+                self.ingest_limiter.release(&self.stream_key());
+                self.unpublish_from_stream_center().await?
+            }
             _ => {}
         }
         Ok(())
@@ -207,20 +346,84 @@ impl RtmpSession {
 
     async fn playing(&mut self, play_handle: Arc<RwLock<PlayHandle>>) -> RtmpServerResult<()> {
         let mut messages = Vec::with_capacity(128);
+        let latency_probe = play_handle.read().await.latency_probe.clone();
+        let mut shaper = self
+            .egress_shaper
+            .subscriber(&self.stream_key(), &self.stream_context);
         loop {
-            let mut handle = play_handle.write().await;
             messages.clear();
-            match handle
-                .stream_data_consumer
-                .recv_many(&mut messages, 128)
-                .await
-            {
-                0 => {
-                    tracing::error!("channel closed while trying to play");
-                    return Err(RtmpServerError::StreamIsGone);
+            // the player might send commands like receiveAudio/receiveVideo while playing,
+            // the lock must be released before processing them.
+            // read_chunk only keeps partial data in its own read buffer, so it's fine to drop it
+            let incoming = {
+                let mut handle = play_handle.write().await;
+                tokio::select! {
+                    len = handle.stream_data_consumer.recv_many(&mut messages, 128) => {
+                        if len == 0 {
+                            tracing::error!("channel closed while trying to play");
+                            return Err(RtmpServerError::StreamIsGone);
+                        }
+                        None
+                    }
+                    chunk = self.chunk_stream.read_chunk() => Some(chunk),
+                    _ = self.registry_handle.close_requested() => {
+                        drop(handle);
+                        self.on_close_requested().await?;
+                        return Ok(());
+                    }
                 }
-                _len => {
+            };
+
+            match incoming {
+                None => {
                     for message in &messages {
+                        if let MediaFrame::EndOfStream { reason, .. } = message {
+                            if reason.is_final() {
+                                self.write_video_sequence_end().await?;
+                            }
+                            self.chunk_stream.chunk_writer().write_on_status_response(
+                                response_level::STATUS,
+                                response_code::NET_STREAM_PLAY_UNPUBLISH_NOTIFY,
+                                &reason.to_string(),
+                                self.connect_info.object_encoding,
+                                None,
+                                self.message_stream_id,
+                            )?;
+                            if reason.is_final() {
+                                self.chunk_stream.chunk_writer().write_on_status_response(
+                                    response_level::STATUS,
+                                    response_code::NET_STREAM_PLAY_STOP,
+                                    &reason.to_string(),
+                                    self.connect_info.object_encoding,
+                                    None,
+                                    self.message_stream_id,
+                                )?;
+                            }
+                            self.chunk_stream.flush_chunk().await?;
+                            if reason.is_final() {
+                                tracing::info!("stream ended, reason: {}", reason);
+                                return Ok(());
+                            }
+                            continue;
+                        }
+                        if let MediaFrame::SourceError { code, detail, .. } = message {
+                            tracing::error!("stream failed: {}, {}", code, detail);
+                            self.write_video_sequence_end().await?;
+                            self.chunk_stream.chunk_writer().write_on_status_response(
+                                response_level::ERROR,
+                                response_code::NET_STREAM_PLAY_FAILED,
+                                &code.to_string(),
+                                self.connect_info.object_encoding,
+                                None,
+                                self.message_stream_id,
+                            )?;
+                            self.chunk_stream.flush_chunk().await?;
+                            return Ok(());
+                        }
+                        // over the bitrate cap, the video is dropped up to the next key frame
+                        if !shaper.admit(message) {
+                            continue;
+                        }
                         if let MediaFrame::VideoConfig {
                             timestamp_nano: _,
                             config,
@@ -237,13 +440,68 @@ impl RtmpSession {
                                         .as_ref()
                                         .map(|v| v.length_size_minus_one.checked_add(1).unwrap());
                                 }
+                                // obus are not length prefixed
+                                VideoConfig::AV1(_) => {}
                             }
                         }
-                        let tag = message.to_flv_tag(self.video_nalu_size_length.unwrap_or(4))?;
-                        self.chunk_stream.write_tag(tag).await?;
+                        let nalu_size_length = self.video_nalu_size_length.unwrap_or(4);
+                        let video_codec_id = match message {
+                            MediaFrame::VideoConfig { config, .. } => Some(config.as_ref().into()),
+                            _ => message.video_codec_id(),
+                        };
+                        let video_header = video_codec_id
+                            .map_or(FlvVideoHeader::Legacy, |codec_id| {
+                                self.video_header_for(codec_id)
+                            });
+                        let tag = if self.timestamp_nano_negotiated() {
+                            message
+                                .to_flv_tag_with_timestamp_nano(nalu_size_length, video_header)?
+                        } else {
+                            message.to_flv_tag_with_video_header(nalu_size_length, video_header)?
+                        };
+                        if let Some(codec_id) = video_codec_id
+                            && let FLVTagBody::Video {
+                                header: VideoTagHeader::Enhanced(_),
+                                ..
+                            } = &tag.body_with_filter.body
+                        {
+                            self.enhanced_video_sequence =
+                                Some((codec_id, tag.tag_header.timestamp));
+                        }
+                        #[cfg(feature = "frame-crc")]
+                        ::stream_center::frame_crc::verify_flv_tag(
+                            "rtmp_sink",
+                            self.stream_id()?,
+                            message,
+                            &tag,
+                            nalu_size_length,
+                        );
+                        self.chunk_stream
+                            .write_tag(tag, self.message_stream_id)
+                            .await?;
+                        if let Some(probe) = &latency_probe {
+                            probe.on_frame_sent(message.get_ingest_time());
+                        }
                         // message.log_runtime_stat();
                     }
                 }
+                Some(Ok(Some(message))) => self.process_message(message).await?,
+                Some(Ok(None)) => {
+                    if self.chunk_stream.is_peer_closed() {
+                        tracing::info!("player closed the connection");
+                        return Ok(());
+                    }
+                }
+                Some(Err(RtmpServerError::Io(io_err))) => match io_err.kind() {
+                    // players usually stay silent while playing
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {}
+                    io::ErrorKind::ConnectionReset => {
+                        tracing::info!("connect reset by peer");
+                        return Ok(());
+                    }
+                    _ => return Err(RtmpServerError::Io(io_err)),
+                },
+                Some(Err(err)) => return Err(err),
             }
         }
     }
@@ -323,19 +581,309 @@ impl RtmpSession {
         Ok(())
     }
 
+    fn stream_key(&self) -> String {
+        self.stream_id
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default()
+    }
+
+    /// the commands and the messages of a stream only come after it is published or played
+    fn stream_id(&self) -> RtmpServerResult<&StreamIdentity> {
+        self.stream_id.as_ref().ok_or_else(|| {
+            RtmpServerError::InvalidStreamParam("no stream is published or played".to_owned())
+        })
+    }
+
+    /// the server always advertises ModEx and nano timestamps, so it is up to the client
+    /// the codecs other than avc go with the ExVideoTagHeader to players that listed their FourCC
+    /// in the fourCcList of the connect, avc keeps the legacy header every player reads.
+    /// codecs without a legacy CodecID, like av1, go enhanced anyway.
+    /// the video of a stream is one track, so it never takes a multitrack header
+    fn video_header_for(&self, codec_id: VideoCodecCommon) -> FlvVideoHeader {
+        let Ok(four_cc) = TryInto::<VideoFourCC>::try_into(codec_id) else {
+            return FlvVideoHeader::Legacy;
+        };
+        if four_cc == VideoFourCC::AVC {
+            return FlvVideoHeader::Legacy;
+        }
+        let four_cc = four_cc_to_string(four_cc.into());
+        let listed = self
+            .connect_info
+            .four_cc_list
+            .as_ref()
+            .is_some_and(|list| list.iter().any(|v| *v == four_cc || v == FOUR_CC_WILDCARD));
+        if listed {
+            FlvVideoHeader::Enhanced
+        } else {
+            FlvVideoHeader::Legacy
+        }
+    }
+
+    /// a SequenceEnd for the enhanced video played, for the player to flush its decoder
+    async fn write_video_sequence_end(&mut self) -> RtmpServerResult<()> {
+        let Some((codec_id, timestamp)) = self.enhanced_video_sequence.take() else {
+            return Ok(());
+        };
+        let tag =
+            MediaFrame::video_sequence_end_flv_tag(codec_id, timestamp, FlvVideoHeader::Enhanced)?;
+        self.chunk_stream
+            .write_tag(tag, self.message_stream_id)
+            .await
+    }
+
+    fn timestamp_nano_negotiated(&self) -> bool {
+        self.connect_info
+            .caps_ex_info
+            .is_some_and(|caps| caps.support_mod_ex && caps.support_timestamp_nano)
+    }
+
+    async fn check_ingest_limit(&mut self, bytes: usize) -> RtmpServerResult<()> {
+        if let Err(err) = self.ingest_limiter.check(&self.stream_key(), bytes) {
+            tracing::error!("ingest limit exceeded, disconnecting publisher: {}", err);
+            let _ = self.reject_publish(&format!("{}", err)).await;
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    /// the stream center removed the stream already, so there is nothing to unpublish or release
+    /// tells the peer the connection is closed, the stream is left by the clean up
+    async fn on_close_requested(&mut self) -> RtmpServerResult<()> {
+        tracing::info!("session is closed by admin, stream: {}", self.stream_key());
+        self.chunk_stream.chunk_writer().write_on_status_response(
+            response_level::STATUS,
+            response_code::NET_CONNECTION_CONNECT_CLOSED,
+            "session is closed by admin",
+            self.connect_info.object_encoding,
+            None,
+            self.message_stream_id,
+        )?;
+        self.chunk_stream.flush_chunk().await?;
+        Ok(())
+    }
+
+    async fn on_publisher_kicked(&mut self, kicked: PublisherKicked) -> RtmpServerResult<()> {
+        let description = match kicked.reason {
+            KickReason::Takeover(policy) => {
+                tracing::info!(
+                    "publisher {:?} is taken over by a new publisher, policy: {:?}, idle for {:?}. stream: {}",
+                    self.publisher_id,
+                    policy,
+                    kicked.idle,
+                    self.stream_key()
+                );
+                "stream is taken over by a new publisher"
+            }
+            KickReason::IdleTimeout => {
+                tracing::warn!(
+                    "publisher {:?} is reaped, no audio or video for {:?}. stream: {}",
+                    self.publisher_id,
+                    kicked.idle,
+                    self.stream_key()
+                );
+                "stream is reaped after sending no data"
+            }
+            KickReason::Drain => {
+                tracing::info!(
+                    "drained publisher {:?} is kicked, idle for {:?}. stream: {}",
+                    self.publisher_id,
+                    kicked.idle,
+                    self.stream_key()
+                );
+                "server is drained"
+            }
+        };
+        self.runtime_handle = SessionRuntime::Unknown;
+        self.publisher_id = None;
+        self.kicked_receiver = None;
+        self.drain_receiver = None;
+        self.backpressure_receiver = None;
+        self.chunk_stream.chunk_writer().write_on_status_response(
+            response_level::STATUS,
+            response_code::NET_STREAM_UNPUBLISH_SUCCESS,
+            description,
+            self.connect_info.object_encoding,
+            None,
+            self.message_stream_id,
+        )?;
+        self.chunk_stream.flush_chunk().await?;
+        Ok(())
+    }
+
+    /// the publisher is asked to lower its bitrate while the stream is congested,
+    /// with the score and the bitrates in the info object for the encoders adapting to them
+    async fn on_backpressure(&mut self, advisory: BackpressureAdvisory) -> RtmpServerResult<()> {
+        let encoding = self.connect_info.object_encoding;
+        let mut info = HashMap::new();
+        let (level, code, description) = match advisory {
+            BackpressureAdvisory::Congested {
+                score,
+                ingest_kbps,
+                suggested_kbps,
+            } => {
+                tracing::warn!(
+                    "advise publisher {:?} to lower the bitrate from {}kbps to {}kbps, congestion score: {:.2}",
+                    self.publisher_id,
+                    ingest_kbps,
+                    suggested_kbps,
+                    score
+                );
+                info.insert("score".to_string(), amf_formats::number(score, encoding));
+                info.insert(
+                    "ingestKbps".to_string(),
+                    amf_formats::number(ingest_kbps as f64, encoding),
+                );
+                info.insert(
+                    "suggestedKbps".to_string(),
+                    amf_formats::number(suggested_kbps as f64, encoding),
+                );
+                (
+                    response_level::WARNING,
+                    response_code::NET_STREAM_PUBLISH_INSUFFICIENT_BW,
+                    "server can not keep up with the stream, lower the bitrate",
+                )
+            }
+            BackpressureAdvisory::Relieved { score } => {
+                tracing::info!(
+                    "publisher {:?} is relieved, congestion score: {:.2}",
+                    self.publisher_id,
+                    score
+                );
+                info.insert("score".to_string(), amf_formats::number(score, encoding));
+                (
+                    response_level::STATUS,
+                    response_code::NET_STREAM_PUBLISH_SUFFICIENT_BW,
+                    "server keeps up with the stream again",
+                )
+            }
+        };
+        self.chunk_stream.chunk_writer().write_on_status_response(
+            level,
+            code,
+            description,
+            encoding,
+            Some(info),
+            self.message_stream_id,
+        )?;
+        self.chunk_stream.flush_chunk().await?;
+        Ok(())
+    }
+
+    /// clients advertising the reconnect capability are asked to reconnect to the target,
+    /// the others are left publishing until the stream center kicks them after the grace period
+    async fn on_drain(&mut self, request: DrainRequest) -> RtmpServerResult<()> {
+        let can_reconnect = self
+            .connect_info
+            .caps_ex_info
+            .is_some_and(|caps| caps.support_reconnect);
+        if !can_reconnect {
+            tracing::info!(
+                "server is drained, the publisher can not reconnect and is kicked after {:?}. stream: {}",
+                request.grace,
+                self.stream_key()
+            );
+            return Ok(());
+        }
+        let tc_url =
+            reconnect_tc_url(&self.connect_info.tc_url, &request.target).unwrap_or_else(|| {
+                format!(
+                    "rtmp://{}/{}",
+                    request.target,
+                    self.connect_url.app().unwrap_or_default()
+                )
+            });
+        tracing::info!(
+            "server is drained, asking the publisher to reconnect to {}. stream: {}",
+            tc_url,
+            self.stream_key()
+        );
+        self.write_reconnect_command(&tc_url, request.description.as_deref())
+            .await
+    }
+
+    /// tells the publisher why before the connection is closed
+    async fn reject_unsupported_codec(&mut self, err: RtmpServerError) -> RtmpServerError {
+        if let RtmpServerError::UnsupportedCodec(codec) = &err {
+            tracing::error!("rejecting publisher of codec {}", codec);
+            let _ = self
+                .reject_publish(&format!(
+                    "codec {} can not be forwarded by the server",
+                    codec
+                ))
+                .await;
+        }
+        err
+    }
+
+    /// codecs without a FourCC, like the legacy flv only ones, are only accepted by a wildcard
+    fn check_codec_forwardable(&self, body: &FLVTagBody) -> RtmpServerResult<()> {
+        let (codec, forwardable) = match body {
+            FLVTagBody::Video { header, .. } => {
+                let codec_id = VideoTagHeaderWithoutMultiTrack::try_from(header)?.codec_id;
+                match TryInto::<VideoFourCC>::try_into(codec_id) {
+                    Ok(four_cc) => {
+                        let four_cc = four_cc_to_string(four_cc.into());
+                        let forwardable = self.four_cc_registry.can_forward_video(&four_cc);
+                        (four_cc, forwardable)
+                    }
+                    Err(_) => (
+                        format!("{:?}", codec_id),
+                        self.four_cc_registry.can_forward_video(FOUR_CC_WILDCARD),
+                    ),
+                }
+            }
+            FLVTagBody::Audio { header, .. } => {
+                let codec_id = AudioTagHeaderWithoutMultiTrack::try_from(header)?.codec_id;
+                match TryInto::<AudioFourCC>::try_into(codec_id) {
+                    Ok(four_cc) => {
+                        let four_cc = four_cc_to_string(four_cc.into());
+                        let forwardable = self.four_cc_registry.can_forward_audio(&four_cc);
+                        (four_cc, forwardable)
+                    }
+                    Err(_) => (
+                        format!("{:?}", codec_id),
+                        self.four_cc_registry.can_forward_audio(FOUR_CC_WILDCARD),
+                    ),
+                }
+            }
+            FLVTagBody::Script { .. } => return Ok(()),
+        };
+        if !forwardable {
+            return Err(RtmpServerError::UnsupportedCodec(codec));
+        }
+        Ok(())
+    }
+
+    async fn reject_publish(&mut self, description: &str) -> RtmpServerResult<()> {
+        self.chunk_stream.chunk_writer().write_on_status_response(
+            response_level::ERROR,
+            response_code::NET_STREAM_PUBLISH_REJECTED,
+            description,
+            self.connect_info.object_encoding,
+            None,
+            self.message_stream_id,
+        )?;
+        self.chunk_stream.flush_chunk().await?;
+        Ok(())
+    }
+
     async fn process_audio(
         &mut self,
         publish_handle: Arc<RwLock<PublishHandle>>,
         header: ChunkMessageCommonHeader,
         audio: Bytes,
     ) -> RtmpServerResult<()> {
+        self.check_ingest_limit(audio.len()).await?;
         let mut handle = publish_handle.write().await;
         handle.no_data_since = None;
-        let media_frames = self.chunked_rtmp_frame_to_media_frame(
+        let media_frames = match self.chunked_rtmp_frame_to_media_frame(
             &header,
             RtmpUserMessageBody::Audio { payload: audio },
-            None,
-        )?;
+        ) {
+            Ok(media_frames) => media_frames,
+            Err(err) => return Err(self.reject_unsupported_codec(err).await),
+        };
         for media_frame in media_frames {
             let res = handle
                 .stream_data_producer
@@ -358,13 +906,16 @@ impl RtmpSession {
         header: ChunkMessageCommonHeader,
         video: Bytes,
     ) -> RtmpServerResult<()> {
+        self.check_ingest_limit(video.len()).await?;
         let mut handle = publish_handle.write().await;
         handle.no_data_since = None;
-        let media_frames = self.chunked_rtmp_frame_to_media_frame(
+        let media_frames = match self.chunked_rtmp_frame_to_media_frame(
             &header,
             RtmpUserMessageBody::Video { payload: video },
-            None,
-        )?;
+        ) {
+            Ok(media_frames) => media_frames,
+            Err(err) => return Err(self.reject_unsupported_codec(err).await),
+        };
         for media_frame in media_frames {
             let res = handle
                 .stream_data_producer
@@ -388,12 +939,12 @@ impl RtmpSession {
         header: ChunkMessageCommonHeader,
         payload: Bytes,
     ) -> RtmpServerResult<()> {
+        self.check_ingest_limit(payload.len()).await?;
         let mut handle = publish_handle.write().await;
         handle.no_data_since = None;
         let media_frames = self.chunked_rtmp_frame_to_media_frame(
             &header,
             RtmpUserMessageBody::MetaData { payload },
-            None,
         )?;
 
         for media_frame in media_frames {
@@ -413,30 +964,40 @@ impl RtmpSession {
         Ok(())
     }
 
+    /// the sub-messages go the way of the ones received on their own,
+    /// the ones which can not be parsed are skipped
     async fn process_aggregate(
         &mut self,
         publish_handle: Arc<RwLock<PublishHandle>>,
         header: ChunkMessageCommonHeader,
         aggregate: Bytes,
     ) -> RtmpServerResult<()> {
-        let mut handle = publish_handle.write().await;
-        handle.no_data_since = None;
-        let media_frames = self.chunked_rtmp_frame_to_media_frame(
-            &header,
-            RtmpUserMessageBody::Aggregate { payload: aggregate },
-            None,
-        )?;
-        for media_frame in media_frames {
-            let res = handle
-                .stream_data_producer
-                .send(media_frame)
-                .await
-                .map_err(|_err| RtmpServerError::ChannelSendFailed {
-                    backtrace: Backtrace::capture(),
-                });
-            res?;
+        let aggregate = AggregateMessage::split(&aggregate, header.timestamp);
+        for skipped in &aggregate.skipped {
+            tracing::warn!(
+                "skip sub-message of aggregate message: {}, stream: {}",
+                skipped,
+                self.stream_key()
+            );
+        }
+        for message in aggregate.messages {
+            let header = message.header(&header);
+            match message.into_body() {
+                RtmpUserMessageBody::Audio { payload } => {
+                    self.process_audio(publish_handle.clone(), header, payload)
+                        .await?
+                }
+                RtmpUserMessageBody::Video { payload } => {
+                    self.process_video(publish_handle.clone(), header, payload)
+                        .await?
+                }
+                RtmpUserMessageBody::MetaData { payload } => {
+                    self.process_meta(publish_handle.clone(), header, payload)
+                        .await?
+                }
+                _ => unreachable!("aggregates only carry audio, video and data"),
+            }
         }
-
         Ok(())
     }
 
@@ -444,23 +1005,13 @@ impl RtmpSession {
         &mut self,
         header: &ChunkMessageCommonHeader,
         frame: RtmpUserMessageBody,
-        timestamp_delta_nano: Option<u64>,
     ) -> RtmpServerResult<Vec<MediaFrame>> {
         let flv_tags = match frame {
             RtmpUserMessageBody::Audio { payload } => {
                 let flv_tag_header = FLVTagHeader {
                     tag_type: FLVTagType::Audio,
                     data_size: payload.len().to_u32().unwrap(),
-                    timestamp: header
-                        .timestamp
-                        .checked_add(
-                            timestamp_delta_nano
-                                .unwrap_or(0)
-                                .checked_div(1_000_000)
-                                .and_then(|v| v.to_u32())
-                                .unwrap(),
-                        )
-                        .unwrap(),
+                    timestamp: header.timestamp,
                     filter_enabled: false,
                 };
                 vec![(flv_tag_header, payload)]
@@ -469,16 +1020,7 @@ impl RtmpSession {
                 let flv_tag_header = FLVTagHeader {
                     tag_type: FLVTagType::Video,
                     data_size: payload.len().to_u32().unwrap(),
-                    timestamp: header
-                        .timestamp
-                        .checked_add(
-                            timestamp_delta_nano
-                                .unwrap_or(0)
-                                .checked_div(1_000_000)
-                                .and_then(|v| v.to_u32())
-                                .unwrap(),
-                        )
-                        .unwrap(),
+                    timestamp: header.timestamp,
                     filter_enabled: false,
                 };
                 vec![(flv_tag_header, payload)]
@@ -488,23 +1030,11 @@ impl RtmpSession {
                 let flv_tag_header = FLVTagHeader {
                     tag_type: FLVTagType::Script,
                     data_size: payload.len().to_u32().unwrap(),
-                    timestamp: header
-                        .timestamp
-                        .checked_add(
-                            timestamp_delta_nano
-                                .unwrap_or(0)
-                                .checked_div(1_000_000)
-                                .and_then(|v| v.to_u32())
-                                .unwrap(),
-                        )
-                        .unwrap(),
+                    timestamp: header.timestamp,
                     filter_enabled: false,
                 };
                 vec![(flv_tag_header, payload)]
             }
-            RtmpUserMessageBody::Aggregate { ref payload } => {
-                return self.parse_aggregate_frame(header, payload);
-            }
             _ => {
                 todo!()
             }
@@ -513,6 +1043,7 @@ impl RtmpSession {
         for (tag_header, payload) in flv_tags {
             let flv_tag_body =
                 FLVTagBodyWithFilter::read_remaining_from(&tag_header, &mut payload.reader())?;
+            self.check_codec_forwardable(&flv_tag_body.body)?;
             let flv_tag = FLVTag {
                 tag_header,
                 body_with_filter: flv_tag_body,
@@ -542,66 +1073,13 @@ impl RtmpSession {
                             unimplemented!()
                         }
                     }
+                    VideoConfig::AV1(_) => {}
                 }
             }
         }
         Ok(result)
     }
 
-    pub fn parse_aggregate_frame(
-        &mut self,
-        header: &ChunkMessageCommonHeader,
-        payload: &Bytes,
-    ) -> RtmpServerResult<Vec<MediaFrame>> {
-        let mut cursor = Cursor::new(payload);
-        let mut timestamp_delta_nano = None;
-        let mut result = vec![];
-        while cursor.has_remaining() {
-            let flv_tag_header = FLVTagHeader::read_from(cursor.by_ref())?;
-            let mut body_bytes = vec![0; flv_tag_header.data_size.to_usize().unwrap()];
-            cursor.read_exact(&mut body_bytes)?;
-            // skip prev tag size
-            cursor.advance(4);
-            if timestamp_delta_nano.is_none() {
-                timestamp_delta_nano = Some(
-                    header
-                        .timestamp
-                        .checked_mul(1_000_000)
-                        .unwrap()
-                        .to_u64()
-                        .unwrap()
-                        .checked_sub(
-                            flv_tag_header
-                                .timestamp
-                                .checked_mul(1_000_000)
-                                .unwrap()
-                                .to_u64()
-                                .unwrap(),
-                        )
-                        .unwrap(),
-                );
-            }
-
-            let rtmp_message = match flv_tag_header.tag_type {
-                FLVTagType::Audio => RtmpUserMessageBody::Audio {
-                    payload: body_bytes.into(),
-                },
-                FLVTagType::Video => RtmpUserMessageBody::Video {
-                    payload: body_bytes.into(),
-                },
-                FLVTagType::Script => RtmpUserMessageBody::MetaData {
-                    payload: body_bytes.into(),
-                },
-            };
-
-            let frames =
-                self.chunked_rtmp_frame_to_media_frame(header, rtmp_message, timestamp_delta_nano)?;
-            assert!(frames.len() == 1);
-            result.extend(frames);
-        }
-        Ok(result)
-    }
-
     async fn process_user_command(
         &mut self,
         command: RtmpC2SCommands,
@@ -622,7 +1100,7 @@ impl RtmpSession {
             RtmpC2SCommands::Play(request) => self.process_play_request(request, header).await?,
             RtmpC2SCommands::Play2(request) => self.process_play2_request(request)?,
             RtmpC2SCommands::Publish(request) => {
-                self.process_publish_command(request).await?;
+                self.process_publish_command(request, header).await?;
             }
             RtmpC2SCommands::ReceiveAudio(request) => {
                 self.process_receive_audio_request(request).await?
@@ -631,6 +1109,9 @@ impl RtmpSession {
                 self.process_receive_video_request(request).await?
             }
             RtmpC2SCommands::Seek(request) => self.process_seek_request(request)?,
+            RtmpC2SCommands::Unknown(command) => {
+                tracing::debug!("ignore command: {:?}", command);
+            }
         };
         Ok(())
     }
@@ -639,15 +1120,28 @@ impl RtmpSession {
         &mut self,
         request: ConnectCommandRequest,
     ) -> RtmpServerResult<()> {
+        self.connect_url =
+            connect_url(&request.command_object.tc_url, &request.command_object.app)?;
+        let app = self.connect_url.app().unwrap_or_default().to_owned();
+
+        // the settings of the app go out before the connect response, @see: RTMP spec 7.2.1.1
+        self.control = self.config.controls.of_app(&app);
+        let limit_type = match self.control.peer_bandwidth_limit_type {
+            PeerBandwidthLimitType::Hard => SetPeerBandWidthLimitType::Hard,
+            PeerBandwidthLimitType::Soft => SetPeerBandWidthLimitType::Soft,
+            PeerBandwidthLimitType::Dynamic => SetPeerBandWidthLimitType::Dynamic,
+        };
         self.chunk_stream
             .chunk_writer()
-            .write_window_ack_size_message(4096)?;
+            .write_window_ack_size_message(self.control.window_ack_size)?;
         self.chunk_stream
             .chunk_writer()
-            .write_set_peer_bandwidth(4096, SetPeerBandWidthLimitType::Dynamic)?;
+            .write_set_peer_bandwidth(self.control.peer_bandwidth, limit_type)?;
+        self.chunk_stream
+            .chunk_writer()
+            .write_set_chunk_size(self.control.chunk_size)?;
         self.chunk_stream.flush_chunk().await?;
-
-        self.stream_properties.app = request.command_object.app.clone();
+        tracing::info!("protocol control of app {}: {:?}", app, self.control);
 
         self.connect_info = request.command_object;
 
@@ -660,7 +1154,15 @@ impl RtmpSession {
             super::consts::response_level::STATUS,
             "Connection Succeeded.",
             self.connect_info.object_encoding,
+            Some(
+                self.four_cc_registry
+                    .to_connect_properties(self.connect_info.object_encoding),
+            ),
         )?;
+        if self.config.send_on_bw_done {
+            // the replies of the client, _checkbw or _result, are ignored
+            self.chunk_stream.chunk_writer().write_on_bw_done()?;
+        }
         self.chunk_stream.flush_chunk().await?;
 
         tracing::info!("connect done, connect_info: {:?}", self.connect_info,);
@@ -672,19 +1174,31 @@ impl RtmpSession {
         &mut self,
         request: CreateStreamCommandRequest,
     ) -> RtmpServerResult<()> {
+        let message_stream_id = self.message_streams.allocate();
+        if message_stream_id.is_none() {
+            tracing::warn!(
+                "too many streams created on one connection, rejecting createStream: {:?}",
+                request
+            );
+        }
         self.chunk_stream
             .chunk_writer()
             .write_create_stream_response(
-                true,
+                message_stream_id.is_some(),
                 request.transaction_id,
                 None,
-                RESPONSE_STREAM_ID.into(),
+                message_stream_id.unwrap_or(0).into(),
             )?;
         self.chunk_stream.flush_chunk().await?;
         Ok(())
     }
 
-    async fn process_publish_command(&mut self, request: PublishCommand) -> RtmpServerResult<()> {
+    async fn process_publish_command(
+        &mut self,
+        request: PublishCommand,
+        header: ChunkMessageCommonHeader,
+    ) -> RtmpServerResult<()> {
+        self.message_stream_id = header.message_stream_id;
         self.publish_to_stream_center(&request.publishing_name)
             .await?;
 
@@ -694,19 +1208,19 @@ impl RtmpSession {
             "publish start",
             self.connect_info.object_encoding,
             None,
+            self.message_stream_id,
         )?;
         self.chunk_stream.flush_chunk().await?;
 
         tracing::info!(
-            "process publish command success, stream_type={}, stream_name={}",
+            "process publish command success, stream_type={}, stream={}",
             request.publishing_type,
-            request.publishing_name,
+            self.stream_key(),
         );
         Ok(())
     }
 
-    // for enhanced rtmp reconnect command, might not be useful
-    #[allow(dead_code)]
+    /// the enhanced rtmp reconnect request, sent on the net connection
     async fn write_reconnect_command(
         &mut self,
         new_tc_url: &str,
@@ -723,20 +1237,26 @@ impl RtmpSession {
             description.unwrap_or("The streaming server is undergoing updates."),
             self.connect_info.object_encoding,
             Some(tc_url_arg),
+            0,
         )?;
         self.chunk_stream.flush_chunk().await?;
         Ok(())
     }
 
     async fn unpublish_from_stream_center(&self) -> RtmpServerResult<()> {
-        StreamCenter::unpublish(
-            &self.stream_center_event_sender,
-            &StreamIdentifier {
-                stream_name: self.stream_properties.stream_name.to_owned(),
-                app: self.stream_properties.app.to_owned(),
-            },
-        )
-        .await?;
+        let stream_id = self.stream_id()?;
+        match self.publisher_id {
+            // do not unpublish the stream of whoever took it over
+            Some(publisher_id) => {
+                StreamCenter::unpublish_publisher(
+                    &self.stream_center_event_sender,
+                    stream_id,
+                    publisher_id,
+                )
+                .await?
+            }
+            None => StreamCenter::unpublish(&self.stream_center_event_sender, stream_id).await?,
+        }
         Ok(())
     }
 
@@ -745,7 +1265,17 @@ impl RtmpSession {
         request: DeleteStreamCommand,
     ) -> RtmpServerResult<()> {
         tracing::info!("process delete stream command, request: {:?}", request);
-        let _ = self.unpublish_from_stream_center().await;
+        let message_stream_id = request.stream_id as u32;
+        if !self.message_streams.release(message_stream_id) {
+            tracing::warn!(
+                "deleting stream {} which is not created on this connection",
+                message_stream_id
+            );
+        }
+        // a play is unsubscribed as the connection closes, its stream is not unpublished for the others
+        if let SessionRuntime::Publish(_) = self.runtime_handle {
+            let _ = self.unpublish_from_stream_center().await;
+        }
 
         self.chunk_stream.chunk_writer().write_on_status_response(
             response_level::STATUS,
@@ -753,6 +1283,7 @@ impl RtmpSession {
             "delete stream success",
             self.connect_info.object_encoding,
             None,
+            message_stream_id,
         )?;
 
         self.chunk_stream.flush_chunk().await?;
@@ -766,30 +1297,68 @@ impl RtmpSession {
             return Ok(());
         }
 
-        if stream_name.is_empty() {
-            return Err(RtmpServerError::InvalidStreamParam(
-                "stream publish need at least stream_name, got empty".to_owned(),
-            ));
-        }
+        let url = self.stream_url(stream_name)?;
+        let stream_id = StreamIdentity::from_media_url(&url).map_err(|err| {
+            RtmpServerError::InvalidStreamParam(format!(
+                "bad stream to publish: {}, {}",
+                stream_name, err
+            ))
+        })?;
 
-        self.stream_properties.stream_name = stream_name.to_string();
-        let media_sender = StreamCenter::publish(
+        self.stream_id = Some(stream_id);
+        self.stream_context
+            .extend(url.query().iter().map(|(k, v)| (k.clone(), v.clone())));
+        let response = StreamCenter::publish_kickable(
             &self.stream_center_event_sender,
             PublishProtocol::RTMP,
-            &StreamIdentifier {
-                stream_name: stream_name.to_owned(),
-                app: self.stream_properties.app.clone(),
-            },
-            &self.stream_properties.stream_context,
+            self.stream_id()?,
+            &self.stream_context,
         )
         .await?;
+        self.publisher_id = Some(response.publisher_id);
+        self.report_rtmp_control(None);
+        self.kicked_receiver = Some(response.kicked_receiver);
+        self.drain_receiver = Some(response.drain_receiver);
+        self.backpressure_receiver = Some(response.backpressure_receiver);
         self.runtime_handle = SessionRuntime::Publish(Arc::new(RwLock::new(PublishHandle {
-            stream_data_producer: media_sender,
+            stream_data_producer: response.media_sender,
             no_data_since: None,
         })));
+        self.registry_handle
+            .set_role(SessionRole::Publisher, &self.stream_key());
+        self.stream_span = Some(stream_span(
+            &self.session_span,
+            &self.stream_key(),
+            StreamRole::Publish,
+        ));
         Ok(())
     }
 
+    /// the url of a stream published or played, the name comes with a query of its own
+    fn stream_url(&self, stream_name: &str) -> RtmpServerResult<MediaUrl> {
+        self.connect_url.with_stream(stream_name).map_err(|err| {
+            RtmpServerError::InvalidStreamParam(format!(
+                "bad stream name: {}, {}",
+                stream_name, err
+            ))
+        })
+    }
+
+    /// the settings in effect show up in the stats of the stream
+    fn report_rtmp_control(&self, subscriber_id: Option<Uuid>) {
+        let Some(stream_id) = &self.stream_id else {
+            return;
+        };
+        if let Err(err) = StreamCenter::negotiate_rtmp_control(
+            &self.stream_center_event_sender,
+            stream_id,
+            subscriber_id,
+            self.control,
+        ) {
+            tracing::warn!("report rtmp control to stream center failed: {}", err);
+        }
+    }
+
     async fn process_call_request(&mut self, request: CallCommandRequest) -> RtmpServerResult<()> {
         tracing::info!("process call request: {:?}", request);
         let command_name = &request.procedure_name;
@@ -847,74 +1416,73 @@ impl RtmpSession {
         StreamCenter::subscribe(
             &self.stream_center_event_sender,
             PlayProtocol::RTMP,
-            &StreamIdentifier {
-                stream_name: self.stream_properties.stream_name.to_owned(),
-                app: self.stream_properties.app.to_owned(),
-            },
-            &self.stream_properties.stream_context,
+            self.stream_id()?,
+            &self.stream_context,
+            MediaSelection::from(&self.stream_context),
         )
         .await
         .map_err(|err| err.into())
     }
 
-    async fn unsubscribe_from_stream_center(&self, uuid: Uuid) -> RtmpServerResult<()> {
-        StreamCenter::unsubscribe(
+    async fn update_media_selection(
+        &self,
+        play_id: Uuid,
+        media_selection: MediaSelection,
+    ) -> RtmpServerResult<()> {
+        StreamCenter::update_media_selection(
             &self.stream_center_event_sender,
-            uuid,
-            &StreamIdentifier {
-                stream_name: self.stream_properties.stream_name.clone(),
-                app: self.stream_properties.app.clone(),
-            },
+            play_id,
+            self.stream_id()?,
+            media_selection,
         )
         .await
         .map_err(|err| err.into())
     }
 
+    async fn unsubscribe_from_stream_center(&self, uuid: Uuid) -> RtmpServerResult<()> {
+        StreamCenter::unsubscribe(&self.stream_center_event_sender, uuid, self.stream_id()?)
+            .await
+            .map_err(|err| err.into())
+    }
+
     async fn process_play_request(
         &mut self,
         request: PlayCommand,
         header: ChunkMessageCommonHeader,
     ) -> RtmpServerResult<()> {
         tracing::info!("got play request: {:?}", request);
-        let stream_play_path = format!("rtmp://fake_host/{}", request.stream_name);
-        let url = Url::parse(&stream_play_path).map_err(|err| {
+        let url = self.stream_url(&request.stream_name)?;
+        let stream_id = StreamIdentity::from_media_url(&url).map_err(|err| {
             RtmpServerError::InvalidStreamParam(format!(
-                "stream play code parse failed: {:?}, should be url path format, got: {}",
-                err, request.stream_name
+                "bad stream to play: {}, {}",
+                request.stream_name, err
             ))
         })?;
-
-        let stream_name = url.path_segments();
-        if stream_name.is_none() {
-            return Err(RtmpServerError::InvalidStreamParam(format!(
-                "stream play code parse failed, no stream_name: {}",
-                request.stream_name
-            )));
-        }
-        let stream_name = stream_name.unwrap().collect::<Vec<&str>>().first().cloned();
-        if stream_name.is_none() {
-            return Err(RtmpServerError::InvalidStreamParam(format!(
-                "stream play code parse failed, no stream_name: {}",
-                request.stream_name
-            )));
+        // a play without the param goes back to the default track
+        self.stream_context.remove(AUDIO_TRACK_KEY);
+        for (k, v) in url.query() {
+            self.stream_context.insert(k.clone(), v.clone());
         }
-        let stream_name = stream_name.unwrap();
-        for (k, v) in url.query_pairs() {
-            self.stream_properties
-                .stream_context
-                .insert(k.to_string(), v.to_string());
+
+        if let SessionRuntime::Play(handle) = &self.runtime_handle
+            && self.stream_id.as_ref() == Some(&stream_id)
+        {
+            return self
+                .switch_audio_track(handle.clone(), header.message_stream_id)
+                .await;
         }
 
         let _start = request.start; // this might by useful
         let _duration = request.duration; // this might by useful
         let reset = request.reset; // this should be ignored
 
-        self.stream_properties.stream_name = stream_name.to_string();
+        self.stream_id = Some(stream_id);
         let subscribe_result = self.subscribe_from_stream_center().await;
+        self.message_stream_id = header.message_stream_id;
 
         self.chunk_stream
             .chunk_writer()
-            .write_set_chunk_size(self.config.chunk_size)?;
+            .write_set_chunk_size(self.control.chunk_size)?;
         self.chunk_stream.flush_chunk().await?;
 
         self.chunk_stream
@@ -930,16 +1498,28 @@ impl RtmpSession {
                     "stream not found",
                     self.connect_info.object_encoding,
                     None,
+                    self.message_stream_id,
                 )?;
             }
             Ok(response) => {
+                self.report_rtmp_control(Some(response.subscribe_id));
+                let media_selection = MediaSelection::from(&self.stream_context);
                 self.runtime_handle = SessionRuntime::Play(Arc::new(RwLock::new(PlayHandle {
                     stream_data_consumer: response.media_receiver,
-                    receive_audio: response.has_audio,
-                    receive_video: response.has_video,
+                    receive_audio: media_selection.audio,
+                    receive_video: media_selection.video,
+                    audio_track: media_selection.audio_track,
                     buffer_length: None,
                     play_id: response.subscribe_id,
+                    latency_probe: response.latency_probe,
                 })));
+                self.registry_handle
+                    .set_role(SessionRole::Subscriber, &self.stream_key());
+                self.stream_span = Some(stream_span(
+                    &self.session_span,
+                    &self.stream_key(),
+                    StreamRole::Subscribe,
+                ));
                 if reset {
                     self.chunk_stream.chunk_writer().write_on_status_response(
                         response_level::STATUS,
@@ -947,6 +1527,7 @@ impl RtmpSession {
                         "reset stream",
                         self.connect_info.object_encoding,
                         None,
+                        self.message_stream_id,
                     )?;
                 }
                 self.chunk_stream.chunk_writer().write_on_status_response(
@@ -955,6 +1536,7 @@ impl RtmpSession {
                     "play start",
                     self.connect_info.object_encoding,
                     None,
+                    self.message_stream_id,
                 )?;
             }
         }
@@ -963,6 +1545,37 @@ impl RtmpSession {
         Ok(())
     }
 
+    /// another play of the stream being played only picks another audio track,
+    /// the subscription stays and the new track starts with its sequence header
+    async fn switch_audio_track(
+        &mut self,
+        play_handle: Arc<RwLock<PlayHandle>>,
+        message_stream_id: u32,
+    ) -> RtmpServerResult<()> {
+        let audio_track = MediaSelection::from(&self.stream_context).audio_track;
+        let (play_id, media_selection) = {
+            let mut handle = play_handle.write().await;
+            handle.audio_track = audio_track;
+            (handle.play_id, handle.media_selection())
+        };
+        tracing::info!(
+            "switch to audio track {} of the playing stream",
+            audio_track
+        );
+        self.update_media_selection(play_id, media_selection)
+            .await?;
+        self.chunk_stream.chunk_writer().write_on_status_response(
+            response_level::STATUS,
+            response_code::NET_STREAM_PLAY_SWITCH,
+            "audio track switched",
+            self.connect_info.object_encoding,
+            None,
+            message_stream_id,
+        )?;
+        self.chunk_stream.flush_chunk().await?;
+        Ok(())
+    }
+
     fn process_play2_request(&mut self, _request: Play2Command) -> RtmpServerResult<()> {
         todo!()
     }
@@ -971,34 +1584,42 @@ impl RtmpSession {
         &mut self,
         request: ReceiveAudioCommand,
     ) -> RtmpServerResult<()> {
-        match &mut self.runtime_handle {
-            SessionRuntime::Play(handle) => handle.write().await.receive_audio = request.bool_flag,
+        let (play_id, media_selection) = match &self.runtime_handle {
+            SessionRuntime::Play(handle) => {
+                let mut handle = handle.write().await;
+                handle.receive_audio = request.bool_flag;
+                (handle.play_id, handle.media_selection())
+            }
             _ => {
                 tracing::warn!(
                     "got unexpected receive_audio request while not in play session: {:?}, ignore.",
                     request
                 );
+                return Ok(());
             }
         };
-        Ok(())
+        self.update_media_selection(play_id, media_selection).await
     }
 
     async fn process_receive_video_request(
         &mut self,
         request: ReceiveVideoCommand,
     ) -> RtmpServerResult<()> {
-        match &mut self.runtime_handle {
+        let (play_id, media_selection) = match &self.runtime_handle {
             SessionRuntime::Play(handle) => {
-                handle.write().await.receive_video = request.bool_flag;
+                let mut handle = handle.write().await;
+                handle.receive_video = request.bool_flag;
+                (handle.play_id, handle.media_selection())
             }
             _ => {
                 tracing::warn!(
                     "got unexpected receive_video request while not in play session: {:?}, ignore.",
                     request
                 );
+                return Ok(());
             }
         };
-        Ok(())
+        self.update_media_selection(play_id, media_selection).await
     }
 
     fn process_seek_request(&mut self, _request: SeekCommand) -> RtmpServerResult<()> {
@@ -1039,3 +1660,43 @@ impl RtmpSession {
         Ok(())
     }
 }
+
+enum Incoming {
+    Kicked(Option<PublisherKicked>),
+    Drain(Option<DrainRequest>),
+    Backpressure(Option<BackpressureAdvisory>),
+    Chunk(RtmpServerResult<Option<ChunkMessage>>),
+    /// the admin api asked to close the session
+    Close,
+}
+
+const RTMP_SCHEME: &str = "rtmp";
+/// the host of the app when the tcUrl does not tell
+const DEFAULT_HOST: &str = "localhost";
+
+/// the host and the query of the tcUrl, the app of the connect command if it has one,
+/// the app of the tcUrl otherwise
+fn connect_url(tc_url: &str, app: &str) -> RtmpServerResult<MediaUrl> {
+    let base = MediaUrl::parse(tc_url)
+        .inspect_err(|err| tracing::warn!("bad tcUrl of connect: {}, {}", tc_url, err))
+        .unwrap_or_else(|_| MediaUrl::new(RTMP_SCHEME, DEFAULT_HOST, None));
+    if app.is_empty() {
+        return Ok(base);
+    }
+    base.with_path(app).map_err(|err| {
+        RtmpServerError::InvalidStreamParam(format!("bad app of connect: {}, {}", app, err))
+    })
+}
+
+/// the tcUrl the client connected with, with the host and port of the target
+fn reconnect_tc_url(tc_url: &str, target: &str) -> Option<String> {
+    let mut url = Url::parse(tc_url).ok()?;
+    let target = Url::parse(&format!("{}://{}", url.scheme(), target)).ok()?;
+    url.set_host(target.host_str()).ok()?;
+    url.set_port(target.port()).ok()?;
+    Some(url.into())
+}
+
+fn four_cc_to_string(four_cc: u32) -> String {
+    String::from_utf8_lossy(&four_cc.to_be_bytes()).into_owned()
+}
@@ -0,0 +1,671 @@
+//! byte accurate captures of the rtp and rtcp packets of a session, in both directions,
+//! for offline debugging without tcpdump on the host.
+//! the packets are queued to a writer task, a capture falling behind drops its own packets, never the media
+
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use futures::{Sink, SinkExt, Stream, StreamExt, ready};
+use tokio::sync::{mpsc, watch};
+use tokio_util::bytes::Bytes;
+use unified_io::{UnderlyingIO, UnifiedIO};
+
+use crate::errors::{RtpSessionError, RtpSessionResult};
+
+pub mod writer;
+
+/// the packets queued to the writer task, the ones recorded past it are dropped
+pub const CAPTURE_QUEUE_PACKETS: usize = 1024;
+pub const DEFAULT_CAPTURE_MAX_BYTES: u64 = 64 * 1024 * 1024;
+pub const DEFAULT_CAPTURE_MAX_DURATION: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// the format of rtptools, the directions of the packets are not kept
+    RtpDump,
+    /// raw ip records with an udp header between the endpoints of the session
+    Pcap,
+}
+
+impl CaptureFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CaptureFormat::RtpDump => "rtpdump",
+            CaptureFormat::Pcap => "pcap",
+        }
+    }
+}
+
+impl fmt::Display for CaptureFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl FromStr for CaptureFormat {
+    type Err = RtpSessionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rtpdump" => Ok(CaptureFormat::RtpDump),
+            "pcap" => Ok(CaptureFormat::Pcap),
+            _ => Err(RtpSessionError::InvalidCaptureFormat(s.to_owned())),
+        }
+    }
+}
+
+/// a capture stops by itself at whichever comes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureLimits {
+    /// of the file, the header included
+    pub max_bytes: u64,
+    pub max_duration: Duration,
+}
+
+impl Default for CaptureLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_CAPTURE_MAX_BYTES,
+            max_duration: DEFAULT_CAPTURE_MAX_DURATION,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    Rtp,
+    Rtcp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    Received,
+    Sent,
+}
+
+/// a packet as it was on the wire, srtp protected if the session is
+#[derive(Debug, Clone)]
+pub struct CapturedPacket {
+    pub kind: PacketKind,
+    pub direction: PacketDirection,
+    /// when it arrived or was sent
+    pub at: SystemTime,
+    /// none for interleaved channels
+    pub local_addr: Option<SocketAddr>,
+    pub peer_addr: Option<SocketAddr>,
+    pub bytes: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureStop {
+    /// stopped by the tap, or the session is gone
+    Requested,
+    MaxBytes,
+    MaxDuration,
+    WriteFailed(String),
+}
+
+impl fmt::Display for CaptureStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureStop::Requested => write!(f, "requested"),
+            CaptureStop::MaxBytes => write!(f, "max bytes reached"),
+            CaptureStop::MaxDuration => write!(f, "max duration reached"),
+            CaptureStop::WriteFailed(err) => write!(f, "write failed: {}", err),
+        }
+    }
+}
+
+/// a snapshot of a capture, the counters are of the packets written so far
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureInfo {
+    pub path: PathBuf,
+    pub format: CaptureFormat,
+    pub limits: CaptureLimits,
+    pub started_at: SystemTime,
+    pub packets: u64,
+    pub bytes: u64,
+    /// recorded while the writer fell behind
+    pub dropped: u64,
+    /// none while it runs
+    pub stopped: Option<CaptureStop>,
+}
+
+/// shared by the tap and the writer task
+#[derive(Debug)]
+struct CaptureState {
+    path: PathBuf,
+    format: CaptureFormat,
+    limits: CaptureLimits,
+    started_at: SystemTime,
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    dropped: AtomicU64,
+    stopped: watch::Sender<Option<CaptureStop>>,
+}
+
+/// a capture running or over, kept by the tap until the next one starts
+#[derive(Debug, Clone)]
+pub struct PacketCapture {
+    state: Arc<CaptureState>,
+}
+
+impl PacketCapture {
+    pub fn info(&self) -> CaptureInfo {
+        CaptureInfo {
+            path: self.state.path.clone(),
+            format: self.state.format,
+            limits: self.state.limits,
+            started_at: self.state.started_at,
+            packets: self.state.packets.load(Ordering::Relaxed),
+            bytes: self.state.bytes.load(Ordering::Relaxed),
+            dropped: self.state.dropped.load(Ordering::Relaxed),
+            stopped: self.state.stopped.borrow().clone(),
+        }
+    }
+
+    /// resolves once the file is flushed and closed
+    pub async fn stopped(&self) -> CaptureStop {
+        let mut stopped = self.state.stopped.subscribe();
+        // the sender is kept by the state, so waiting never fails
+        let stop = stopped.wait_for(Option::is_some).await;
+        stop.ok()
+            .and_then(|stop| stop.clone())
+            .unwrap_or(CaptureStop::Requested)
+    }
+}
+
+#[derive(Debug, Default)]
+struct TapState {
+    sender: Option<mpsc::Sender<CapturedPacket>>,
+    capture: Option<PacketCapture>,
+}
+
+/// the switch of the captures of a session, the rtp sessions record their packets through it
+/// and the admin api starts and stops the captures
+#[derive(Debug, Clone, Default)]
+pub struct CaptureTap {
+    /// checked before the lock, so the packets of sessions not captured cost a load
+    armed: Arc<AtomicBool>,
+    state: Arc<Mutex<TapState>>,
+}
+
+impl CaptureTap {
+    /// creates the file and starts to write the packets recorded from now on
+    pub async fn start(
+        &self,
+        path: PathBuf,
+        format: CaptureFormat,
+        limits: CaptureLimits,
+    ) -> RtpSessionResult<PacketCapture> {
+        if let Some(running) = self.running() {
+            return Err(RtpSessionError::CaptureRunning(
+                running.state.path.display().to_string(),
+            ));
+        }
+        let file = tokio::fs::File::create(&path).await?;
+        let state = Arc::new(CaptureState {
+            path,
+            format,
+            limits,
+            started_at: SystemTime::now(),
+            packets: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            stopped: watch::Sender::new(None),
+        });
+        let (sender, receiver) = mpsc::channel(CAPTURE_QUEUE_PACKETS);
+        let capture = PacketCapture {
+            state: state.clone(),
+        };
+        {
+            let mut tap = self.state.lock().unwrap();
+            // started by another request in between
+            if tap.sender.as_ref().is_some_and(|sender| !sender.is_closed()) {
+                return Err(RtpSessionError::CaptureRunning(
+                    tap.capture
+                        .as_ref()
+                        .map(|capture| capture.state.path.display().to_string())
+                        .unwrap_or_default(),
+                ));
+            }
+            tap.sender = Some(sender);
+            tap.capture = Some(capture.clone());
+            self.armed.store(true, Ordering::Release);
+        }
+        tracing::info!(
+            "capture to {} started, format: {}, limits: {:?}",
+            state.path.display(),
+            format,
+            limits
+        );
+        tokio::spawn(writer::write_capture(file, state, receiver));
+        Ok(capture)
+    }
+
+    /// the packets recorded so far are still written, none if there is no capture running
+    pub fn stop(&self) -> Option<PacketCapture> {
+        let running = self.running()?;
+        let mut tap = self.state.lock().unwrap();
+        tap.sender = None;
+        self.armed.store(false, Ordering::Release);
+        Some(running)
+    }
+
+    /// the capture running, or the latest one if it is over
+    pub fn capture(&self) -> Option<PacketCapture> {
+        self.state.lock().unwrap().capture.clone()
+    }
+
+    fn running(&self) -> Option<PacketCapture> {
+        let tap = self.state.lock().unwrap();
+        tap.sender
+            .as_ref()
+            .filter(|sender| !sender.is_closed())
+            .and(tap.capture.clone())
+    }
+
+    /// queues the packet to the writer, dropped if the writer falls behind
+    pub fn record(
+        &self,
+        kind: PacketKind,
+        direction: PacketDirection,
+        local_addr: Option<SocketAddr>,
+        peer_addr: Option<SocketAddr>,
+        bytes: &Bytes,
+    ) {
+        if !self.armed.load(Ordering::Acquire) {
+            return;
+        }
+        let mut tap = self.state.lock().unwrap();
+        let Some(sender) = tap.sender.as_ref() else {
+            return;
+        };
+        let packet = CapturedPacket {
+            kind,
+            direction,
+            at: SystemTime::now(),
+            local_addr,
+            peer_addr,
+            bytes: bytes.clone(),
+        };
+        match sender.try_send(packet) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                if let Some(capture) = &tap.capture {
+                    capture.state.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            // the writer reached a limit
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                tap.sender = None;
+                self.armed.store(false, Ordering::Release);
+            }
+        }
+    }
+}
+
+/// records the packets received from and sent to the inner io, each item is one packet
+#[derive(Debug)]
+pub struct CaptureIo {
+    inner: Pin<Box<dyn UnifiedIO>>,
+    tap: CaptureTap,
+    kind: PacketKind,
+    local_addr: Option<SocketAddr>,
+    peer_addr: Option<SocketAddr>,
+}
+
+impl CaptureIo {
+    pub fn new(inner: Pin<Box<dyn UnifiedIO>>, tap: CaptureTap, kind: PacketKind) -> Self {
+        Self {
+            local_addr: inner.get_local_addr(),
+            peer_addr: inner.get_peer_addr(),
+            inner,
+            tap,
+            kind,
+        }
+    }
+}
+
+impl UnifiedIO for CaptureIo {
+    fn get_underlying_io_type(&self) -> UnderlyingIO {
+        self.inner.get_underlying_io_type()
+    }
+}
+
+impl Stream for CaptureIo {
+    type Item = Result<Bytes, io::Error>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = ready!(self.inner.poll_next_unpin(cx));
+        if let Some(Ok(packet)) = &item {
+            self.tap.record(
+                self.kind,
+                PacketDirection::Received,
+                self.local_addr,
+                self.peer_addr,
+                packet,
+            );
+        }
+        Poll::Ready(item)
+    }
+}
+
+impl Sink<Bytes> for CaptureIo {
+    type Error = io::Error;
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        self.tap.record(
+            self.kind,
+            PacketDirection::Sent,
+            self.local_addr,
+            self.peer_addr,
+            &item,
+        );
+        self.inner.start_send_unpin(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::bytes::Bytes;
+    use unified_io::channel::ChannelIo;
+
+    use super::{
+        CAPTURE_QUEUE_PACKETS, CaptureFormat, CaptureIo, CaptureLimits, CaptureStop, CaptureTap,
+        PacketDirection, PacketKind,
+        writer::{
+            FALLBACK_LOCAL_ADDR, FALLBACK_PEER_ADDR, FALLBACK_RTCP_PORT, PCAP_LINKTYPE_RAW,
+            PCAP_MAGIC, RTPDUMP_SHEBANG,
+        },
+    };
+
+    /// a packet read back from a capture
+    #[derive(Debug, PartialEq, Eq)]
+    struct Record {
+        /// for rtpdump, none for rtcp
+        original_length: Option<u16>,
+        source: Option<SocketAddr>,
+        destination: Option<SocketAddr>,
+        bytes: Vec<u8>,
+    }
+
+    fn u16_be(bytes: &[u8]) -> u16 {
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    fn u32_le(bytes: &[u8]) -> u32 {
+        u32::from_le_bytes(bytes[..4].try_into().unwrap())
+    }
+
+    /// the source of the file header and the packets
+    fn read_rtpdump(file: &[u8]) -> (SocketAddr, Vec<Record>) {
+        let line_end = file.iter().position(|byte| *byte == b'\n').unwrap();
+        let line = std::str::from_utf8(&file[..line_end]).unwrap();
+        let source = line
+            .strip_prefix(RTPDUMP_SHEBANG)
+            .unwrap()
+            .trim()
+            .replace('/', ":")
+            .parse()
+            .unwrap();
+        let mut rest = &file[line_end + 1 + 16..];
+        let mut records = Vec::new();
+        while !rest.is_empty() {
+            let length = u16_be(rest) as usize;
+            let original_length = u16_be(&rest[2..]);
+            records.push(Record {
+                original_length: (original_length != 0).then_some(original_length),
+                source: None,
+                destination: None,
+                bytes: rest[8..length].to_vec(),
+            });
+            rest = &rest[length..];
+        }
+        (source, records)
+    }
+
+    fn read_pcap(file: &[u8]) -> Vec<Record> {
+        assert_eq!(u32_le(file), PCAP_MAGIC);
+        assert_eq!(u32_le(&file[20..]), PCAP_LINKTYPE_RAW);
+        let mut rest = &file[24..];
+        let mut records = Vec::new();
+        while !rest.is_empty() {
+            let length = u32_le(&rest[8..]) as usize;
+            assert_eq!(length, u32_le(&rest[12..]) as usize);
+            let ip = &rest[16..16 + length];
+            assert_eq!(ip[0], 0x45);
+            assert_eq!(ip[9], 17);
+            assert_eq!(u16_be(&ip[2..]) as usize, length);
+            let address = |at: usize| Ipv4Addr::new(ip[at], ip[at + 1], ip[at + 2], ip[at + 3]);
+            let udp = &ip[20..];
+            assert_eq!(u16_be(&udp[4..]) as usize, udp.len());
+            records.push(Record {
+                original_length: None,
+                source: Some((address(12), u16_be(udp)).into()),
+                destination: Some((address(16), u16_be(&udp[2..])).into()),
+                bytes: udp[8..].to_vec(),
+            });
+            rest = &rest[16 + length..];
+        }
+        records
+    }
+
+    fn capture_path(name: &str, format: CaptureFormat) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "rtp-capture-{}-{}.{}",
+            name,
+            std::process::id(),
+            format.extension()
+        ))
+    }
+
+    fn rtp(sequence_number: u16) -> Bytes {
+        let mut packet = vec![0x80, 96];
+        packet.extend_from_slice(&sequence_number.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0x0b, 0xb8, 0, 0, 0x12, 0x34]);
+        packet.extend_from_slice(b"payload");
+        Bytes::from(packet)
+    }
+
+    const LOCAL: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 30000);
+    const CAMERA: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::new(10, 0, 0, 9)), 6970);
+
+    /// 3 rtp packets from the camera, a receiver report to it
+    fn record_sequence(tap: &CaptureTap) -> Vec<Bytes> {
+        let report = Bytes::from_static(&[0x80, 201, 0, 1, 0, 0, 0x56, 0x78]);
+        let packets = vec![rtp(1), rtp(2), rtp(3), report.clone()];
+        for packet in &packets[..3] {
+            tap.record(PacketKind::Rtp, PacketDirection::Received, Some(LOCAL), Some(CAMERA), packet);
+        }
+        tap.record(PacketKind::Rtcp, PacketDirection::Sent, Some(LOCAL), Some(CAMERA), &report);
+        packets
+    }
+
+    #[tokio::test]
+    async fn rtpdump_captures_are_read_back_as_recorded() {
+        let path = capture_path("rtpdump", CaptureFormat::RtpDump);
+        let tap = CaptureTap::default();
+        // nothing is kept before the capture starts
+        tap.record(PacketKind::Rtp, PacketDirection::Received, Some(LOCAL), Some(CAMERA), &rtp(0));
+        let capture = tap
+            .start(path.clone(), CaptureFormat::RtpDump, CaptureLimits::default())
+            .await
+            .unwrap();
+        assert!(tap
+            .start(path.clone(), CaptureFormat::RtpDump, CaptureLimits::default())
+            .await
+            .is_err());
+        let packets = record_sequence(&tap);
+        tap.stop().unwrap();
+        assert_eq!(capture.stopped().await, CaptureStop::Requested);
+
+        let file = std::fs::read(&path).unwrap();
+        let (source, records) = read_rtpdump(&file);
+        assert_eq!(source, CAMERA);
+        assert_eq!(records.len(), 4);
+        for (record, packet) in records.iter().zip(&packets) {
+            assert_eq!(record.bytes, packet.to_vec());
+        }
+        assert_eq!(records[0].original_length, Some(packets[0].len() as u16));
+        assert_eq!(records[3].original_length, None);
+        let info = capture.info();
+        assert_eq!((info.packets, info.bytes, info.dropped), (4, file.len() as u64, 0));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn pcap_captures_keep_the_directions() {
+        let path = capture_path("pcap", CaptureFormat::Pcap);
+        let tap = CaptureTap::default();
+        let capture = tap
+            .start(path.clone(), CaptureFormat::Pcap, CaptureLimits::default())
+            .await
+            .unwrap();
+        let packets = record_sequence(&tap);
+        tap.stop().unwrap();
+        capture.stopped().await;
+
+        let records = read_pcap(&std::fs::read(&path).unwrap());
+        assert_eq!(records.len(), 4);
+        for (record, packet) in records.iter().zip(&packets) {
+            assert_eq!(record.bytes, packet.to_vec());
+        }
+        assert_eq!((records[0].source, records[0].destination), (Some(CAMERA), Some(LOCAL)));
+        assert_eq!((records[3].source, records[3].destination), (Some(LOCAL), Some(CAMERA)));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn a_capture_stops_by_itself_at_its_size() {
+        let path = capture_path("cap", CaptureFormat::Pcap);
+        let tap = CaptureTap::default();
+        let limits = CaptureLimits {
+            max_bytes: 200,
+            max_duration: Duration::from_secs(60),
+        };
+        let capture = tap.start(path.clone(), CaptureFormat::Pcap, limits).await.unwrap();
+        for sequence_number in 0..10 {
+            tap.record(PacketKind::Rtp, PacketDirection::Received, None, None, &rtp(sequence_number));
+        }
+        assert_eq!(capture.stopped().await, CaptureStop::MaxBytes);
+        let file = std::fs::read(&path).unwrap();
+        assert!(file.len() <= 200);
+        // the header and 2 packets of 63 bytes in their records
+        assert_eq!(read_pcap(&file).len(), 2);
+        // the tap sees the capture is over, a new one may start
+        tap.record(PacketKind::Rtp, PacketDirection::Received, None, None, &rtp(10));
+        assert!(tap.stop().is_none());
+        assert_eq!(tap.capture().unwrap().info().stopped, Some(CaptureStop::MaxBytes));
+
+        let capture = tap
+            .start(path.clone(), CaptureFormat::Pcap, CaptureLimits {
+                max_duration: Duration::from_millis(10),
+                ..limits
+            })
+            .await
+            .unwrap();
+        assert_eq!(capture.stopped().await, CaptureStop::MaxDuration);
+        assert_eq!(read_pcap(&std::fs::read(&path).unwrap()).len(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn a_writer_falling_behind_drops_the_capture_not_the_packets() {
+        let path = capture_path("behind", CaptureFormat::RtpDump);
+        let (inner, mut peer) = ChannelIo::pair(16);
+        let tap = CaptureTap::default();
+        let mut io = CaptureIo::new(Box::pin(inner), tap.clone(), PacketKind::Rtp);
+        let capture = tap
+            .start(path.clone(), CaptureFormat::RtpDump, CaptureLimits::default())
+            .await
+            .unwrap();
+        // the writer task does not run until the test yields, so the queue fills up
+        for sequence_number in 0..CAPTURE_QUEUE_PACKETS {
+            tap.record(
+                PacketKind::Rtp,
+                PacketDirection::Received,
+                None,
+                None,
+                &rtp(sequence_number as u16),
+            );
+        }
+        for sequence_number in 0..3 {
+            io.feed(rtp(sequence_number)).await.unwrap();
+        }
+        peer.send(rtp(3)).await.unwrap();
+        assert_eq!(io.next().await.unwrap().unwrap(), rtp(3));
+        for sequence_number in 0..3 {
+            assert_eq!(peer.next().await.unwrap().unwrap(), rtp(sequence_number));
+        }
+        tap.stop();
+        capture.stopped().await;
+
+        let info = capture.info();
+        assert_eq!(info.dropped, 4);
+        assert_eq!(info.packets, CAPTURE_QUEUE_PACKETS as u64);
+        let (_, records) = read_rtpdump(&std::fs::read(&path).unwrap());
+        assert_eq!(records.len(), CAPTURE_QUEUE_PACKETS);
+        assert!(info.started_at <= SystemTime::now());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn the_packets_of_both_directions_are_captured_through_the_io() {
+        let path = capture_path("io", CaptureFormat::Pcap);
+        let (inner, mut peer) = ChannelIo::pair(16);
+        let tap = CaptureTap::default();
+        let mut io = CaptureIo::new(Box::pin(inner), tap.clone(), PacketKind::Rtcp);
+        let capture = tap
+            .start(path.clone(), CaptureFormat::Pcap, CaptureLimits::default())
+            .await
+            .unwrap();
+        let report = Bytes::from_static(&[0x80, 201, 0, 1, 0, 0, 0x56, 0x78]);
+        io.send(report.clone()).await.unwrap();
+        assert_eq!(peer.next().await.unwrap().unwrap(), report);
+        peer.send(report.clone()).await.unwrap();
+        assert_eq!(io.next().await.unwrap().unwrap(), report);
+        tap.stop();
+        capture.stopped().await;
+
+        // an interleaved channel has no addresses, the synthetic ones tell the directions
+        let records = read_pcap(&std::fs::read(&path).unwrap());
+        let local: SocketAddr = (FALLBACK_LOCAL_ADDR, FALLBACK_RTCP_PORT).into();
+        let remote: SocketAddr = (FALLBACK_PEER_ADDR, FALLBACK_RTCP_PORT).into();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record.source.unwrap(), record.destination.unwrap()))
+                .collect::<Vec<_>>(),
+            vec![(local, remote), (remote, local)]
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+}
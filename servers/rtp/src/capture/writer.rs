@@ -0,0 +1,277 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, atomic::Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+    sync::mpsc,
+};
+
+use super::{CaptureFormat, CaptureState, CaptureStop, CapturedPacket, PacketDirection, PacketKind};
+
+/// the registered ports of rtp and rtcp, for the packets of interleaved channels without addresses of their own
+pub const FALLBACK_RTP_PORT: u16 = 5004;
+pub const FALLBACK_RTCP_PORT: u16 = 5005;
+/// synthetic addresses of the two sides of an interleaved channel, so the directions still tell apart in a pcap
+pub const FALLBACK_LOCAL_ADDR: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
+pub const FALLBACK_PEER_ADDR: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 2);
+
+/// how often the buffered records are flushed to the file while packets keep coming
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const RTPDUMP_SHEBANG: &str = "#!rtpplay1.0";
+/// the length and the original length of the packet, and its offset in milliseconds
+pub const RTPDUMP_PACKET_HEADER_BYTES: usize = 8;
+
+pub const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+/// LINKTYPE_RAW, the records start with the ip header
+pub const PCAP_LINKTYPE_RAW: u32 = 101;
+pub const PCAP_SNAPLEN: u32 = 65535;
+const IPV4_HEADER_BYTES: usize = 20;
+const IPV6_HEADER_BYTES: usize = 40;
+const UDP_HEADER_BYTES: usize = 8;
+const IP_PROTOCOL_UDP: u8 = 17;
+const IP_TTL: u8 = 64;
+
+impl CapturedPacket {
+    fn fallback_port(&self) -> u16 {
+        match self.kind {
+            PacketKind::Rtp => FALLBACK_RTP_PORT,
+            PacketKind::Rtcp => FALLBACK_RTCP_PORT,
+        }
+    }
+
+    /// the side that sent the packet and the one that received it
+    pub fn endpoints(&self) -> (SocketAddr, SocketAddr) {
+        let local = self
+            .local_addr
+            .unwrap_or_else(|| (FALLBACK_LOCAL_ADDR, self.fallback_port()).into());
+        let peer = self
+            .peer_addr
+            .unwrap_or_else(|| (FALLBACK_PEER_ADDR, self.fallback_port()).into());
+        match self.direction {
+            PacketDirection::Received => (peer, local),
+            PacketDirection::Sent => (local, peer),
+        }
+    }
+}
+
+/// `#!rtpplay1.0 address/port` and the binary header after it, the source is the sender of the first packet
+pub fn rtpdump_file_header(started_at: SystemTime, source: SocketAddr) -> Vec<u8> {
+    let since_epoch = started_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut header = format!("{} {}/{}\n", RTPDUMP_SHEBANG, source.ip(), source.port()).into_bytes();
+    header.extend_from_slice(&(since_epoch.as_secs() as u32).to_be_bytes());
+    header.extend_from_slice(&since_epoch.subsec_micros().to_be_bytes());
+    let address = match source.ip() {
+        IpAddr::V4(address) => u32::from(address),
+        IpAddr::V6(_) => 0,
+    };
+    header.extend_from_slice(&address.to_be_bytes());
+    header.extend_from_slice(&source.port().to_be_bytes());
+    // padding
+    header.extend_from_slice(&[0, 0]);
+    header
+}
+
+/// the original length is 0 for rtcp, as rtpdump tells them apart
+pub fn rtpdump_packet(packet: &CapturedPacket, started_at: SystemTime) -> Vec<u8> {
+    let bytes = &packet.bytes[..packet.bytes.len().min(u16::MAX as usize - RTPDUMP_PACKET_HEADER_BYTES)];
+    let offset_ms = packet
+        .at
+        .duration_since(started_at)
+        .unwrap_or_default()
+        .as_millis() as u32;
+    let original_length = match packet.kind {
+        PacketKind::Rtp => packet.bytes.len().min(u16::MAX as usize) as u16,
+        PacketKind::Rtcp => 0,
+    };
+    let mut record = Vec::with_capacity(RTPDUMP_PACKET_HEADER_BYTES + bytes.len());
+    record.extend_from_slice(&((RTPDUMP_PACKET_HEADER_BYTES + bytes.len()) as u16).to_be_bytes());
+    record.extend_from_slice(&original_length.to_be_bytes());
+    record.extend_from_slice(&offset_ms.to_be_bytes());
+    record.extend_from_slice(bytes);
+    record
+}
+
+/// pcap 2.4 in little endian, with raw ip records
+pub fn pcap_file_header() -> Vec<u8> {
+    let mut header = Vec::with_capacity(24);
+    header.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&4u16.to_le_bytes());
+    // thiszone and sigfigs
+    header.extend_from_slice(&0i32.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+    header.extend_from_slice(&PCAP_LINKTYPE_RAW.to_le_bytes());
+    header
+}
+
+/// the packet in an ip and an udp header between the endpoints of its direction,
+/// ipv6 if either of them is, with the ipv4 one mapped
+pub fn pcap_packet(packet: &CapturedPacket) -> Vec<u8> {
+    let (source, destination) = packet.endpoints();
+    let max_payload = PCAP_SNAPLEN as usize - IPV6_HEADER_BYTES - UDP_HEADER_BYTES;
+    let payload = &packet.bytes[..packet.bytes.len().min(max_payload)];
+    let udp_length = (UDP_HEADER_BYTES + payload.len()) as u16;
+    let mut ip = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => ipv4_header(source, destination, udp_length),
+        (source, destination) => ipv6_header(to_ipv6(source), to_ipv6(destination), udp_length),
+    };
+    ip.extend_from_slice(&source.port().to_be_bytes());
+    ip.extend_from_slice(&destination.port().to_be_bytes());
+    ip.extend_from_slice(&udp_length.to_be_bytes());
+    // no checksum
+    ip.extend_from_slice(&0u16.to_be_bytes());
+    ip.extend_from_slice(payload);
+
+    let since_epoch = packet.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut record = Vec::with_capacity(16 + ip.len());
+    record.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+    record.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+    record.extend_from_slice(&(ip.len() as u32).to_le_bytes());
+    record.extend_from_slice(&(ip.len() as u32).to_le_bytes());
+    record.extend_from_slice(&ip);
+    record
+}
+
+fn to_ipv6(address: IpAddr) -> std::net::Ipv6Addr {
+    match address {
+        IpAddr::V4(address) => address.to_ipv6_mapped(),
+        IpAddr::V6(address) => address,
+    }
+}
+
+fn ipv4_header(source: Ipv4Addr, destination: Ipv4Addr, udp_length: u16) -> Vec<u8> {
+    let mut header = Vec::with_capacity(IPV4_HEADER_BYTES + udp_length as usize);
+    // version 4, 5 words
+    header.push(0x45);
+    header.push(0);
+    header.extend_from_slice(&(IPV4_HEADER_BYTES as u16 + udp_length).to_be_bytes());
+    // identification, then don't fragment
+    header.extend_from_slice(&[0, 0, 0x40, 0]);
+    header.push(IP_TTL);
+    header.push(IP_PROTOCOL_UDP);
+    header.extend_from_slice(&[0, 0]);
+    header.extend_from_slice(&source.octets());
+    header.extend_from_slice(&destination.octets());
+    let checksum = internet_checksum(&header);
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+    header
+}
+
+fn ipv6_header(
+    source: std::net::Ipv6Addr,
+    destination: std::net::Ipv6Addr,
+    udp_length: u16,
+) -> Vec<u8> {
+    let mut header = Vec::with_capacity(IPV6_HEADER_BYTES + udp_length as usize);
+    // version 6, no traffic class nor flow label
+    header.extend_from_slice(&[0x60, 0, 0, 0]);
+    header.extend_from_slice(&udp_length.to_be_bytes());
+    header.push(IP_PROTOCOL_UDP);
+    header.push(IP_TTL);
+    header.extend_from_slice(&source.octets());
+    header.extend_from_slice(&destination.octets());
+    header
+}
+
+fn internet_checksum(bytes: &[u8]) -> u16 {
+    let mut sum = bytes
+        .chunks(2)
+        .map(|word| u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0)))
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// writes the packets queued by the tap until it stops the capture or a limit is reached,
+/// the receiver is dropped then so the tap sees the capture is over
+pub(super) async fn write_capture(
+    file: File,
+    state: Arc<CaptureState>,
+    mut packets: mpsc::Receiver<CapturedPacket>,
+) {
+    let mut writer = BufWriter::new(file);
+    let deadline = tokio::time::Instant::now() + state.limits.max_duration;
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    let mut header_written = false;
+    let stop = loop {
+        let packet = tokio::select! {
+            packet = packets.recv() => match packet {
+                Some(packet) => packet,
+                None => break CaptureStop::Requested,
+            },
+            _ = tokio::time::sleep_until(deadline) => break CaptureStop::MaxDuration,
+            _ = flush.tick() => {
+                if let Err(err) = writer.flush().await {
+                    break CaptureStop::WriteFailed(err.to_string());
+                }
+                continue;
+            }
+        };
+        let mut record = Vec::new();
+        if !header_written {
+            record = match state.format {
+                CaptureFormat::RtpDump => rtpdump_file_header(state.started_at, packet.endpoints().0),
+                CaptureFormat::Pcap => pcap_file_header(),
+            };
+        }
+        record.extend(match state.format {
+            CaptureFormat::RtpDump => rtpdump_packet(&packet, state.started_at),
+            CaptureFormat::Pcap => pcap_packet(&packet),
+        });
+        if state.bytes.load(Ordering::Relaxed) + record.len() as u64 > state.limits.max_bytes {
+            break CaptureStop::MaxBytes;
+        }
+        if let Err(err) = writer.write_all(&record).await {
+            break CaptureStop::WriteFailed(err.to_string());
+        }
+        header_written = true;
+        state.packets.fetch_add(1, Ordering::Relaxed);
+        state.bytes.fetch_add(record.len() as u64, Ordering::Relaxed);
+    };
+    // the packets queued meanwhile are dropped with the receiver
+    drop(packets);
+    let stop = match finish(&mut writer, &state, header_written).await {
+        Err(err) if !matches!(stop, CaptureStop::WriteFailed(_)) => {
+            CaptureStop::WriteFailed(err.to_string())
+        }
+        _ => stop,
+    };
+    tracing::info!(
+        "capture to {} stopped: {}, packets: {}, bytes: {}, dropped: {}",
+        state.path.display(),
+        stop,
+        state.packets.load(Ordering::Relaxed),
+        state.bytes.load(Ordering::Relaxed),
+        state.dropped.load(Ordering::Relaxed)
+    );
+    state.stopped.send_replace(Some(stop));
+}
+
+/// a capture without packets still gets the header of its format, so it opens as an empty capture
+async fn finish(
+    writer: &mut BufWriter<File>,
+    state: &CaptureState,
+    header_written: bool,
+) -> io::Result<()> {
+    if !header_written {
+        let header = match state.format {
+            CaptureFormat::RtpDump => rtpdump_file_header(
+                state.started_at,
+                (Ipv4Addr::UNSPECIFIED, 0).into(),
+            ),
+            CaptureFormat::Pcap => pcap_file_header(),
+        };
+        writer.write_all(&header).await?;
+        state.bytes.fetch_add(header.len() as u64, Ordering::Relaxed);
+    }
+    writer.flush().await
+}
@@ -1,5 +1,8 @@
 use std::io;
+
+use rtp_formats::{packet::RtpTrivialPacket, rtcp::RtcpPacket};
 use thiserror::Error;
+use tokio::sync::mpsc::error::SendTimeoutError;
 
 #[derive(Debug, Error)]
 pub enum RtpSessionError {
@@ -12,11 +15,19 @@ pub enum RtpSessionError {
     #[error("RTCP packet channel disconnected")]
     RtcpPacketChannelDisconnected,
     #[error("send rtp packet to channel failed: {0}")]
-    SendRtpPacketToChannelFailed(String),
+    SendRtpPacketToChannelFailed(#[source] SendTimeoutError<RtpTrivialPacket>),
     #[error("send rtcp packet to channel failed: {0}")]
-    SendRtcpPacketToChannelFailed(String),
+    SendRtcpPacketToChannelFailed(#[source] SendTimeoutError<RtcpPacket>),
     #[error("not a valid rtp session configuration: {0}")]
     InvalidRtpSessionConfiguration(String),
+    #[error("invalid fec config: {0}")]
+    InvalidFecConfig(String),
+    #[error("invalid capture format: {0}, expect rtpdump or pcap")]
+    InvalidCaptureFormat(String),
+    #[error("a capture is running already: {0}")]
+    CaptureRunning(String),
+    #[error("rtcp app data too large: {0} bytes")]
+    AppDataTooLarge(usize),
     #[error("gracefully exit")]
     GracefulExit,
 }
@@ -0,0 +1,335 @@
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rtp_formats::packet::{RtpTrivialPacket, red::red_encapsulate, ulpfec::ulpfec_encode};
+use utils::{random::random_u16, traits::dynamic_sized_packet::DynamicSizedPacket};
+
+use crate::errors::RtpSessionError;
+
+pub use rtp_formats::packet::ulpfec::ULPFEC_MAX_GROUP_SIZE;
+
+pub const DEFAULT_ULPFEC_GROUP_SIZE: usize = 8;
+
+/// how the packets sent to the players of a stream are protected against loss
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FecConfig {
+    /// the audio packets carry the payload of the one before them, RFC 2198
+    pub red: bool,
+    /// a parity packet follows every this many video packets, and the last packet of a frame, RFC 5109.
+    /// off if not set
+    pub ulpfec_group_size: Option<usize>,
+}
+
+/// parses `off` or a comma separated list of `red` and `ulpfec[:<group size>]`, e.g., `red,ulpfec:10`,
+/// the group size is between 2 and 16
+impl FromStr for FecConfig {
+    type Err = RtpSessionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RtpSessionError::InvalidFecConfig(s.trim().to_owned());
+        let mut config = Self::default();
+        if s.trim() == "off" {
+            return Ok(config);
+        }
+        for item in s.split(',').map(str::trim) {
+            match item.split_once(':') {
+                None if item == "red" => config.red = true,
+                None if item == "ulpfec" => {
+                    config.ulpfec_group_size = Some(DEFAULT_ULPFEC_GROUP_SIZE)
+                }
+                Some(("ulpfec", group_size)) => {
+                    let group_size = group_size.trim().parse().map_err(|_| invalid())?;
+                    if !(2..=ULPFEC_MAX_GROUP_SIZE).contains(&group_size) {
+                        return Err(invalid());
+                    }
+                    config.ulpfec_group_size = Some(group_size);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// a stage of the sending path protecting the packets against loss,
+/// it may replace a packet before it is sent and send packets of its own right after it
+pub trait RtpProtection: Send {
+    /// the packet sent in place of the media packet
+    fn protect(&mut self, packet: RtpTrivialPacket) -> RtpTrivialPacket {
+        packet
+    }
+
+    /// the packets sent right after the packet, it is sent as protected
+    fn packets_after(&mut self, _packet: &RtpTrivialPacket) -> Vec<RtpTrivialPacket> {
+        Vec::new()
+    }
+}
+
+/// sends the payload of the previous packet again along with each packet,
+/// so a single loss is recovered from the next packet
+#[derive(Debug)]
+pub struct RedProtection {
+    payload_type: u8,
+    /// the previous payload is left out if the packet would grow beyond it
+    max_packet_size: usize,
+    previous: Option<RtpTrivialPacket>,
+}
+
+impl RedProtection {
+    pub fn new(payload_type: u8, max_packet_size: usize) -> Self {
+        Self {
+            payload_type,
+            max_packet_size,
+            previous: None,
+        }
+    }
+}
+
+impl RtpProtection for RedProtection {
+    fn protect(&mut self, packet: RtpTrivialPacket) -> RtpTrivialPacket {
+        let previous = self.previous.replace(packet.clone());
+        let red = red_encapsulate(&packet, self.payload_type, previous.as_ref());
+        if red.get_packet_bytes_count() <= self.max_packet_size {
+            return red;
+        }
+        red_encapsulate(&packet, self.payload_type, None)
+    }
+}
+
+/// sends a parity packet after each group of packets, any one packet of a group is recovered
+/// from the others and the parity packet. the parity packets have their own ssrc and sequence numbers,
+/// as rtx does, so the media keeps its sequence numbers
+#[derive(Debug)]
+pub struct UlpfecProtection {
+    payload_type: u8,
+    ssrc: u32,
+    group_size: usize,
+    sequence_number: u16,
+    group: Vec<RtpTrivialPacket>,
+}
+
+impl UlpfecProtection {
+    pub fn new(payload_type: u8, ssrc: u32, group_size: usize) -> Self {
+        Self {
+            payload_type,
+            ssrc,
+            group_size: group_size.clamp(1, ULPFEC_MAX_GROUP_SIZE),
+            sequence_number: random_u16(),
+            group: Vec::new(),
+        }
+    }
+
+    fn close_group(&mut self) -> Option<RtpTrivialPacket> {
+        let group = std::mem::take(&mut self.group);
+        if group.is_empty() {
+            return None;
+        }
+        match ulpfec_encode(&group, self.payload_type, self.ssrc, self.sequence_number) {
+            Ok(fec) => {
+                self.sequence_number = self.sequence_number.wrapping_add(1);
+                Some(fec)
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "the group of {} packets is not protected: {}",
+                    group.len(),
+                    err
+                );
+                None
+            }
+        }
+    }
+}
+
+impl RtpProtection for UlpfecProtection {
+    /// the group is closed once full, or at the end of a frame so the frame is not held
+    /// for the packets of the next one
+    fn packets_after(&mut self, packet: &RtpTrivialPacket) -> Vec<RtpTrivialPacket> {
+        let mut packets = Vec::new();
+        // the sequence numbers jumped, the group so far is closed before
+        if self.group.last().is_some_and(|last| {
+            last.header.sequence_number.wrapping_add(1) != packet.header.sequence_number
+        }) {
+            packets.extend(self.close_group());
+        }
+        self.group.push(packet.clone());
+        if self.group.len() >= self.group_size || packet.header.marker {
+            packets.extend(self.close_group());
+        }
+        packets
+    }
+}
+
+/// what the protection of a sending session costs
+#[derive(Debug, Default)]
+pub struct FecMetrics {
+    media_bytes: AtomicU64,
+    redundant_bytes: AtomicU64,
+    fec_packets: AtomicU64,
+    fec_bytes: AtomicU64,
+}
+
+impl FecMetrics {
+    /// of the packets as they are before protected
+    pub fn media_bytes(&self) -> u64 {
+        self.media_bytes.load(Ordering::Relaxed)
+    }
+
+    /// added to the media packets, e.g., the previous payloads of red
+    pub fn redundant_bytes(&self) -> u64 {
+        self.redundant_bytes.load(Ordering::Relaxed)
+    }
+
+    /// sent besides the media packets, e.g., the parity packets of ulpfec
+    pub fn fec_packets(&self) -> u64 {
+        self.fec_packets.load(Ordering::Relaxed)
+    }
+
+    pub fn fec_bytes(&self) -> u64 {
+        self.fec_bytes.load(Ordering::Relaxed)
+    }
+
+    /// the bytes sent for the protection over the bytes of the media, 0 before any media
+    pub fn overhead(&self) -> f64 {
+        match self.media_bytes() {
+            0 => 0.0,
+            media_bytes => (self.redundant_bytes() + self.fec_bytes()) as f64 / media_bytes as f64,
+        }
+    }
+
+    pub(crate) fn on_protected(&self, media_bytes: usize, protected_bytes: usize) {
+        self.media_bytes
+            .fetch_add(media_bytes as u64, Ordering::Relaxed);
+        self.redundant_bytes.fetch_add(
+            protected_bytes.saturating_sub(media_bytes) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    pub(crate) fn on_fec_sent(&self, bytes: usize) {
+        self.fec_packets.fetch_add(1, Ordering::Relaxed);
+        self.fec_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rtp_formats::{
+        header::RtpHeader,
+        packet::{red::red_decapsulate, ulpfec::ulpfec_recover},
+    };
+    use tokio_util::bytes::Bytes;
+
+    use super::*;
+
+    fn packet(sequence_number: u16, marker: bool) -> RtpTrivialPacket {
+        RtpTrivialPacket::new(
+            RtpHeader {
+                payload_type: 96,
+                sequence_number,
+                timestamp: sequence_number as u32 / 4 * 3000,
+                ssrc: 1,
+                marker,
+                ..Default::default()
+            },
+            Bytes::from(vec![sequence_number as u8; 100 + sequence_number as usize]),
+        )
+    }
+
+    #[test]
+    fn parse_fec_config() {
+        assert_eq!("off".parse::<FecConfig>().unwrap(), FecConfig::default());
+        assert_eq!(
+            "red, ulpfec:10".parse::<FecConfig>().unwrap(),
+            FecConfig {
+                red: true,
+                ulpfec_group_size: Some(10),
+            }
+        );
+        assert_eq!(
+            "ulpfec".parse::<FecConfig>().unwrap().ulpfec_group_size,
+            Some(DEFAULT_ULPFEC_GROUP_SIZE)
+        );
+        for invalid in ["", "ulpfec:1", "ulpfec:17", "ulpfec:x", "red:2", "fec"] {
+            assert!(invalid.parse::<FecConfig>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn ulpfec_groups_close_when_full_or_at_the_end_of_a_frame() {
+        let mut protection = UlpfecProtection::new(101, 2, 4);
+        let packets: Vec<_> = (0..10).map(|seq| packet(seq, seq == 5)).collect();
+        let mut sent = Vec::new();
+        for packet in &packets {
+            sent.push((packet.clone(), false));
+            for fec in protection.packets_after(packet) {
+                sent.push((fec, true));
+            }
+        }
+        // 0-3 full, 4-5 end of frame, 6-9 full
+        let fec_positions: Vec<_> = sent
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, fec))| *fec)
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(fec_positions, vec![4, 7, 12]);
+        let fec: Vec<_> = sent
+            .iter()
+            .filter(|(_, fec)| *fec)
+            .map(|(packet, _)| packet)
+            .collect();
+        assert!(
+            fec.iter()
+                .all(|fec| fec.header.payload_type == 101 && fec.header.ssrc == 2)
+        );
+        assert_eq!(
+            fec[1].header.sequence_number,
+            fec[0].header.sequence_number.wrapping_add(1)
+        );
+        // any single loss of the last group
+        for lost in 6..10 {
+            let received: Vec<_> = packets[6..]
+                .iter()
+                .filter(|packet| packet.header.sequence_number != lost)
+                .cloned()
+                .collect();
+            let recovered = ulpfec_recover(fec[2], &received, 1).unwrap().unwrap();
+            assert_eq!(recovered.header.sequence_number, lost);
+            assert_eq!(recovered.payload, packets[lost as usize].payload);
+        }
+        // a jump of the sequence numbers closes the group before
+        assert_eq!(protection.packets_after(&packet(20, false)).len(), 0);
+        assert_eq!(protection.packets_after(&packet(30, false)).len(), 1);
+    }
+
+    #[test]
+    fn red_leaves_out_the_previous_payload_over_the_max_packet_size() {
+        let first = packet(0, true);
+        let second = packet(1, true);
+        let mut protection = RedProtection::new(100, 1500);
+        protection.protect(first.clone());
+        let blocks = red_decapsulate(&protection.protect(second.clone())).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].payload, first.payload);
+
+        let mut protection = RedProtection::new(100, second.get_packet_bytes_count() + 8);
+        protection.protect(first);
+        let red = protection.protect(second.clone());
+        assert_eq!(red.header.payload_type, 100);
+        assert_eq!(red_decapsulate(&red).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn overhead_is_the_share_of_the_protection() {
+        let metrics = FecMetrics::default();
+        assert_eq!(metrics.overhead(), 0.0);
+        metrics.on_protected(1000, 1000);
+        metrics.on_protected(1000, 1100);
+        metrics.on_fec_sent(300);
+        assert_eq!(metrics.redundant_bytes(), 100);
+        assert_eq!(metrics.fec_packets(), 1);
+        assert!((metrics.overhead() - 0.2).abs() < 1e-9);
+    }
+}
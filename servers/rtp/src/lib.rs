@@ -1,8 +1,18 @@
+pub mod capture;
 pub mod channel;
 pub mod errors;
+pub mod fec;
+pub mod metrics;
+pub mod pacing;
 pub mod participant;
+pub mod participant_observer;
+pub mod retransmission;
+pub mod rtcp_app;
 pub mod rtcp_context;
 pub mod rtcp_observer;
 pub mod rtp_observer;
+pub mod sdes;
 pub mod session;
 pub mod simple_statistics;
+#[cfg(feature = "srtp")]
+pub mod srtp;
@@ -0,0 +1,179 @@
+use std::{sync::LazyLock, time::SystemTime};
+
+use rtp_formats::{packet::RtpTrivialPacket, rtcp::compound_packet::RtcpCompoundPacket};
+use utils::metrics::{self, Counter, DEFAULT_SECONDS_BUCKETS, Family, Histogram};
+
+use crate::{
+    participant_observer::{ParticipantEvent, ParticipantObserver},
+    rtcp_context::RtpSessionObserver,
+    rtcp_observer::RtcpObserver,
+    rtp_observer::RtpObserver,
+};
+
+/// a jump larger than this is taken as a restart of the sequence numbers, not as a loss
+const MAX_DROPOUT: u16 = 3000;
+
+static PACKETS_RECEIVED: LazyLock<Counter> = LazyLock::new(|| {
+    metrics::global().counter(
+        "media_server_rtp_packets_received_total",
+        "rtp packets received by all the rtp sessions",
+    )
+});
+
+static PACKETS_LOST: LazyLock<Counter> = LazyLock::new(|| {
+    metrics::global().counter(
+        "media_server_rtp_packets_lost_total",
+        "gaps in the sequence numbers of the received rtp packets",
+    )
+});
+
+static PACKETS_REORDERED: LazyLock<Counter> = LazyLock::new(|| {
+    metrics::global().counter(
+        "media_server_rtp_packets_reordered_total",
+        "rtp packets received after a packet with a later sequence number",
+    )
+});
+
+static RTCP_RTT: LazyLock<Histogram> = LazyLock::new(|| {
+    metrics::global().histogram(
+        "media_server_rtcp_rtt_seconds",
+        "round trip time computed from the report blocks of the peers",
+        DEFAULT_SECONDS_BUCKETS,
+    )
+});
+
+static PARTICIPANTS_JOINED: LazyLock<Counter> = LazyLock::new(|| {
+    metrics::global().counter(
+        "media_server_rtp_participants_joined_total",
+        "sources heard by the rtp sessions for the first time",
+    )
+});
+
+static PARTICIPANTS_LEFT: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_rtp_participants_left_total",
+        "sources gone from the rtp sessions, by a bye or by a timeout",
+        &["reason"],
+    )
+});
+
+static SSRC_COLLISIONS: LazyLock<Counter> = LazyLock::new(|| {
+    metrics::global().counter(
+        "media_server_rtp_ssrc_collisions_total",
+        "known ssrcs heard from another transport address",
+    )
+});
+
+/// reports the packets and the rtcp round trips of an rtp session to the metrics registry
+#[derive(Debug, Default)]
+pub struct RtpSessionMetrics {
+    /// the ssrc and the highest sequence number received from it
+    highest_received: Option<(u32, u16)>,
+}
+
+impl RtpSessionObserver for RtpSessionMetrics {}
+
+impl RtpSessionMetrics {
+    /// the series show up before the first packet
+    pub fn new() -> Self {
+        LazyLock::force(&PACKETS_RECEIVED);
+        LazyLock::force(&PACKETS_LOST);
+        LazyLock::force(&PACKETS_REORDERED);
+        LazyLock::force(&RTCP_RTT);
+        LazyLock::force(&PARTICIPANTS_JOINED);
+        LazyLock::force(&PARTICIPANTS_LEFT);
+        LazyLock::force(&SSRC_COLLISIONS);
+        Default::default()
+    }
+
+    /// returns the lost and whether the packet is reordered
+    fn on_sequence_number(&mut self, ssrc: u32, sequence_number: u16) -> (u64, bool) {
+        let Some((highest_ssrc, highest)) = self.highest_received else {
+            self.highest_received = Some((ssrc, sequence_number));
+            return (0, false);
+        };
+        let delta = sequence_number.wrapping_sub(highest);
+        if highest_ssrc != ssrc || (MAX_DROPOUT..u16::MAX - MAX_DROPOUT).contains(&delta) {
+            self.highest_received = Some((ssrc, sequence_number));
+            return (0, false);
+        }
+        if delta == 0 {
+            // duplicate
+            return (0, false);
+        }
+        if delta < MAX_DROPOUT {
+            self.highest_received = Some((ssrc, sequence_number));
+            return ((delta - 1) as u64, false);
+        }
+        (0, true)
+    }
+}
+
+impl RtcpObserver for RtpSessionMetrics {
+    fn on_rtcp_compound_packet_received(
+        &mut self,
+        _packet: &RtcpCompoundPacket,
+        _timestamp: SystemTime,
+    ) {
+    }
+
+    fn on_rtcp_compound_packet_sent(
+        &mut self,
+        _packet: &RtcpCompoundPacket,
+        _timestamp: SystemTime,
+    ) {
+    }
+}
+
+impl ParticipantObserver for RtpSessionMetrics {
+    fn on_participant_event(&mut self, event: &ParticipantEvent, _timestamp: SystemTime) {
+        match event {
+            ParticipantEvent::Joined { .. } => PARTICIPANTS_JOINED.inc(),
+            ParticipantEvent::Left { .. } => PARTICIPANTS_LEFT.with_labels(&["bye"]).inc(),
+            ParticipantEvent::TimedOut { .. } => PARTICIPANTS_LEFT.with_labels(&["timeout"]).inc(),
+            ParticipantEvent::SsrcCollision { .. } => SSRC_COLLISIONS.inc(),
+            ParticipantEvent::RoundTripMeasured {
+                round_trip_time, ..
+            } => RTCP_RTT.observe(round_trip_time.as_secs_f64()),
+            ParticipantEvent::Described { .. } | ParticipantEvent::ReceptionReported { .. } => {}
+        }
+    }
+}
+
+impl RtpObserver for RtpSessionMetrics {
+    fn on_rtp_packet_received(&mut self, packet: &RtpTrivialPacket, _timestamp: SystemTime) {
+        PACKETS_RECEIVED.inc();
+        let (lost, reordered) =
+            self.on_sequence_number(packet.header.ssrc, packet.header.sequence_number);
+        if lost > 0 {
+            PACKETS_LOST.inc_by(lost);
+        }
+        if reordered {
+            PACKETS_REORDERED.inc();
+        }
+    }
+
+    fn on_rtp_packet_sent(&mut self, _packet: &RtpTrivialPacket, _timestamp: SystemTime) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::RtpSessionMetrics;
+
+    #[test]
+    fn losses_and_reorders_of_sequence_numbers() {
+        let mut metrics = RtpSessionMetrics::new();
+        assert_eq!(metrics.on_sequence_number(1, 65533), (0, false));
+        assert_eq!(metrics.on_sequence_number(1, 65534), (0, false));
+        // 65535 and 0 are missing across the wrap
+        assert_eq!(metrics.on_sequence_number(1, 1), (2, false));
+        assert_eq!(metrics.on_sequence_number(1, 0), (0, true));
+        assert_eq!(metrics.on_sequence_number(1, 1), (0, false));
+        // a restart of the sequence numbers is not a loss
+        assert_eq!(metrics.on_sequence_number(1, 30000), (0, false));
+        assert_eq!(metrics.on_sequence_number(1, 30001), (0, false));
+        // neither is a new ssrc
+        assert_eq!(metrics.on_sequence_number(2, 100), (0, false));
+        assert_eq!(metrics.on_sequence_number(2, 102), (1, false));
+    }
+}
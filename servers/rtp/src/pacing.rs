@@ -0,0 +1,445 @@
+use std::{collections::VecDeque, time::Duration};
+
+use rtp_formats::packet::RtpTrivialPacket;
+use tokio::time::Instant;
+use utils::{frame_grid::FrameGrid, traits::dynamic_sized_packet::DynamicSizedPacket};
+
+/// enough for the sequence headers and the first keyframe of most streams
+pub const DEFAULT_PACING_BURST_BYTES: usize = 256 * 1024;
+pub const DEFAULT_PACING_CATCH_UP_SPEED: f64 = 1.5;
+pub const DEFAULT_PACING_MAX_LEAD: Duration = Duration::from_millis(100);
+/// the rtp thread stops taking packets from the command channel while this many are queued
+pub const MAX_PACED_PACKETS: usize = 2048;
+/// a larger step of the decode timestamps is a discontinuity, the packets after it are not held for it
+const MAX_DTS_STEP_MS: f64 = 1000.0;
+
+/// how the packets of a sending session are spread by the decode timestamps of their frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PacingConfig {
+    /// bytes sent as they come when the session starts, so the player shows the first keyframe quickly
+    pub burst_bytes: usize,
+    /// how much faster than real time the queued packets are sent,
+    /// so a session replaying the gop cache catches up with live
+    pub catch_up_speed: f64,
+    /// how far the playback clock may run ahead of the sent packets, absorbs the jitter of live frames
+    pub max_lead: Duration,
+}
+
+impl Default for PacingConfig {
+    fn default() -> Self {
+        Self {
+            burst_bytes: DEFAULT_PACING_BURST_BYTES,
+            catch_up_speed: DEFAULT_PACING_CATCH_UP_SPEED,
+            max_lead: DEFAULT_PACING_MAX_LEAD,
+        }
+    }
+}
+
+/// the decode timestamp the session is at, in milliseconds, it advances by the catch up speed
+#[derive(Debug, Clone, Copy)]
+struct PlaybackClock {
+    instant: Instant,
+    position: f64,
+    /// the decode timestamp of the latest sent frame
+    sent_dts: f64,
+}
+
+impl PlaybackClock {
+    fn position(&self, now: Instant, speed: f64) -> f64 {
+        self.position + now.saturating_duration_since(self.instant).as_secs_f64() * 1000.0 * speed
+    }
+
+    fn due_at(&self, dts: f64, speed: f64) -> Instant {
+        let ahead = (dts - self.position).max(0.0);
+        self.instant + Duration::from_secs_f64(ahead / 1000.0 / speed)
+    }
+}
+
+#[derive(Debug)]
+struct PacedPacket {
+    packet: RtpTrivialPacket,
+    /// none for the packets not carrying frames, they are sent right after the ones queued before them
+    dts: Option<f64>,
+}
+
+/// queues the packets of a sending session until the playback clock reaches their frames,
+/// the packets are sent in the order they are queued
+#[derive(Debug)]
+pub struct RtpPacer {
+    config: PacingConfig,
+    burst_left: usize,
+    clock: Option<PlaybackClock>,
+    queue: VecDeque<PacedPacket>,
+    /// the grid of the nominal frame rate of the stream, the frames are paced on it
+    frame_grid: Option<FrameGrid>,
+    /// the dts of the latest queued frame, as pushed and as placed on the grid
+    pushed_frame: Option<(u64, f64)>,
+    /// the playback speed the player asked for, the clock runs at it times the catch up speed
+    speed: f64,
+}
+
+impl RtpPacer {
+    pub fn new(config: PacingConfig) -> Self {
+        Self {
+            config,
+            burst_left: config.burst_bytes,
+            clock: None,
+            queue: VecDeque::new(),
+            frame_grid: None,
+            pushed_frame: None,
+            speed: 1.0,
+        }
+    }
+
+    /// the clock goes on from where it is at the new speed, the packets queued already are paced by it too
+    pub fn set_speed(&mut self, speed: f64, now: Instant) {
+        if speed == self.speed {
+            return;
+        }
+        let clock_speed = self.clock_speed();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.position = clock.position(now, clock_speed);
+            clock.instant = now;
+        }
+        self.speed = speed;
+    }
+
+    fn clock_speed(&self) -> f64 {
+        self.config.catch_up_speed * self.speed
+    }
+
+    /// the fixed frame rate the stream declares, the frames pushed from now on are paced on its grid
+    /// rather than by their timestamps, which some encoders quantize to milliseconds
+    pub fn set_nominal_frame_rate(&mut self, frame_rate: Option<f64>) {
+        if self.frame_grid.as_ref().map(FrameGrid::frame_rate) == frame_rate {
+            return;
+        }
+        self.frame_grid = frame_rate.and_then(FrameGrid::new);
+        self.pushed_frame = None;
+    }
+
+    /// the dts is the decode timestamp of the frame the packet carries, in milliseconds
+    pub fn push(&mut self, packet: RtpTrivialPacket, dts: Option<u64>) {
+        let dts = dts.map(|dts| self.frame_dts(dts));
+        self.queue.push_back(PacedPacket { packet, dts });
+    }
+
+    /// the dts the frame is paced by, the packets of a frame share it
+    fn frame_dts(&mut self, dts: u64) -> f64 {
+        let Some(frame_grid) = self.frame_grid.as_mut() else {
+            return dts as f64;
+        };
+        match self.pushed_frame {
+            Some((pushed, placed)) if pushed == dts => placed,
+            _ => {
+                let placed = frame_grid.place(Some(dts * 1_000_000)) as f64 / 1e6;
+                self.pushed_frame = Some((dts, placed));
+                placed
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// when the first queued packet is due, none if there is no packet
+    pub fn next_due(&self, now: Instant) -> Option<Instant> {
+        self.queue.front().map(|paced| self.due_at(paced, now))
+    }
+
+    /// the first queued packet if it is due
+    pub fn pop_due(&mut self, now: Instant) -> Option<RtpTrivialPacket> {
+        if self.due_at(self.queue.front()?, now) > now {
+            return None;
+        }
+        let PacedPacket { packet, dts } = self.queue.pop_front()?;
+        // the burst ends with the first packet beyond it
+        self.burst_left = self
+            .burst_left
+            .saturating_sub(packet.get_packet_bytes_count());
+        if let Some(dts) = dts {
+            self.on_frame_sent(dts, now);
+        }
+        Some(packet)
+    }
+
+    fn due_at(&self, paced: &PacedPacket, now: Instant) -> Instant {
+        if paced.packet.get_packet_bytes_count() <= self.burst_left {
+            return now;
+        }
+        match (self.clock, paced.dts) {
+            (Some(clock), Some(dts)) if dts <= clock.sent_dts + MAX_DTS_STEP_MS => {
+                clock.due_at(dts, self.clock_speed())
+            }
+            _ => now,
+        }
+    }
+
+    /// the clock follows the sent frames, it is held back to the max lead ahead of them
+    fn on_frame_sent(&mut self, dts: f64, now: Instant) {
+        let lead = self.config.max_lead.as_secs_f64() * 1000.0;
+        let position = self
+            .clock
+            .filter(|clock| dts <= clock.sent_dts + MAX_DTS_STEP_MS)
+            .map_or(dts, |clock| clock.position(now, self.clock_speed()));
+        self.clock = Some(PlaybackClock {
+            instant: now,
+            position: position.clamp(dts, dts + lead),
+            sent_dts: dts,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rtp_formats::header::RtpHeader;
+    use tokio::sync::mpsc;
+    use tokio_util::bytes::Bytes;
+
+    use super::*;
+
+    const FRAME_INTERVAL_MS: u64 = 40;
+
+    fn packet(sequence_number: u16) -> RtpTrivialPacket {
+        RtpTrivialPacket::new(
+            RtpHeader {
+                payload_type: 96,
+                sequence_number,
+                ssrc: 1,
+                ..Default::default()
+            },
+            Bytes::from(vec![0; 1000]),
+        )
+    }
+
+    fn config() -> PacingConfig {
+        PacingConfig {
+            burst_bytes: 3000,
+            catch_up_speed: 1.5,
+            max_lead: Duration::from_millis(100),
+        }
+    }
+
+    /// runs the pacer like the rtp thread does, returns the dts of each sent packet and when it is sent
+    async fn drive(
+        mut pacer: RtpPacer,
+        mut rx: mpsc::Receiver<(u64, RtpTrivialPacket)>,
+    ) -> Vec<(u64, Instant)> {
+        let mut dts_of = std::collections::HashMap::new();
+        let mut sent = Vec::new();
+        let mut closed = false;
+        while !closed || !pacer.is_empty() {
+            let next_due = pacer.next_due(Instant::now());
+            tokio::select! {
+                packet = rx.recv(), if !closed => match packet {
+                    None => closed = true,
+                    Some((dts, packet)) => {
+                        dts_of.insert(packet.header.sequence_number, dts);
+                        pacer.push(packet, Some(dts));
+                    }
+                },
+                _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {}
+            }
+            let now = Instant::now();
+            while let Some(packet) = pacer.pop_due(now) {
+                sent.push((dts_of[&packet.header.sequence_number], now));
+            }
+        }
+        sent
+    }
+
+    /// every 5th live frame is late
+    fn late_ms(frame: u64) -> u64 {
+        if frame.is_multiple_of(5) { 30 } else { 0 }
+    }
+
+    fn ms(duration: Duration) -> f64 {
+        duration.as_secs_f64() * 1000.0
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replayed_gop_is_spread_by_dts_after_the_burst() {
+        let (tx, rx) = mpsc::channel(1000);
+        let start = Instant::now();
+        let mut sequence_number = 0;
+        // a keyframe of 5 packets and 24 frames of 2 packets, all cached
+        for frame in 0..25 {
+            for _ in 0..if frame == 0 { 5 } else { 2 } {
+                tx.send((frame * FRAME_INTERVAL_MS, packet(sequence_number)))
+                    .await
+                    .unwrap();
+                sequence_number += 1;
+            }
+        }
+        drop(tx);
+        let sent = drive(RtpPacer::new(config()), rx).await;
+        assert_eq!(sent.len(), 53);
+
+        // the keyframe is sent at once though it is beyond the burst
+        assert!(sent[..5].iter().all(|(dts, at)| *dts == 0 && *at == start));
+        let mut frames: Vec<(u64, Instant)> = Vec::new();
+        for (dts, at) in sent {
+            match frames.last() {
+                Some((last, _)) if *last == dts => {}
+                _ => frames.push((dts, at)),
+            }
+        }
+        assert_eq!(frames.len(), 25);
+        for pair in frames.windows(2) {
+            let spacing = ms(pair[1].1 - pair[0].1);
+            let expected = FRAME_INTERVAL_MS as f64 / config().catch_up_speed;
+            assert!(
+                (spacing - expected).abs() < 1.0,
+                "spacing {spacing}ms, expect {expected}ms"
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn live_tail_passes_through_once_caught_up() {
+        let (tx, rx) = mpsc::channel(1000);
+        let start = Instant::now();
+        let live_edge = 24 * FRAME_INTERVAL_MS;
+        tokio::spawn(async move {
+            let mut sequence_number = 0;
+            for frame in 0..=24 {
+                tx.send((frame * FRAME_INTERVAL_MS, packet(sequence_number)))
+                    .await
+                    .unwrap();
+                sequence_number += 1;
+            }
+            for frame in 25..200 {
+                let dts = frame * FRAME_INTERVAL_MS;
+                let arrival = Duration::from_millis(dts - live_edge + late_ms(frame));
+                tokio::time::sleep_until(start + arrival).await;
+                for _ in 0..3 {
+                    tx.send((dts, packet(sequence_number))).await.unwrap();
+                    sequence_number += 1;
+                }
+            }
+        });
+        let sent = drive(RtpPacer::new(config()), rx).await;
+        assert_eq!(sent.len(), 25 + 175 * 3);
+
+        // the backlog of the replay is gone in live_edge / (speed - 1)
+        let caught_up =
+            Duration::from_secs_f64(live_edge as f64 / 1000.0 / (config().catch_up_speed - 1.0));
+        for (dts, at) in sent {
+            if dts <= live_edge {
+                continue;
+            }
+            let arrival = Duration::from_millis(dts - live_edge + late_ms(dts / FRAME_INTERVAL_MS));
+            let held = (at - start).checked_sub(arrival).unwrap();
+            if arrival > caught_up + Duration::from_millis(100) {
+                assert!(
+                    held < Duration::from_millis(1),
+                    "frame {dts} is held for {:?}",
+                    held
+                );
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dts_jumps_and_packets_without_dts_are_not_held() {
+        let start = Instant::now();
+        let mut pacer = RtpPacer::new(PacingConfig {
+            burst_bytes: 0,
+            ..config()
+        });
+        pacer.push(packet(0), Some(0));
+        pacer.push(packet(1), Some(600));
+        pacer.push(packet(2), None);
+        pacer.push(packet(3), Some(60_000));
+        pacer.push(packet(4), Some(0));
+
+        assert_eq!(pacer.pop_due(start).unwrap().header.sequence_number, 0);
+        assert!(pacer.pop_due(start).is_none());
+        let due = pacer.next_due(start).unwrap();
+        assert!((ms(due - start) - 400.0).abs() < 1.0);
+
+        tokio::time::sleep_until(due).await;
+        let now = Instant::now();
+        let sent: Vec<_> = std::iter::from_fn(|| pacer.pop_due(now))
+            .map(|packet| packet.header.sequence_number)
+            .collect();
+        assert_eq!(sent, vec![1, 2, 3, 4]);
+        assert!(pacer.is_empty());
+    }
+
+    /// the spacing of the frames sent, each popped as soon as it is due
+    fn frame_spacings(mut pacer: RtpPacer, dts: &[u64]) -> Vec<f64> {
+        for (sequence_number, dts) in dts.iter().enumerate() {
+            pacer.push(packet(sequence_number as u16), Some(*dts));
+        }
+        let mut now = Instant::now();
+        let mut sent = Vec::new();
+        while let Some(due) = pacer.next_due(now) {
+            now = now.max(due);
+            pacer.pop_due(now).unwrap();
+            sent.push(now);
+        }
+        sent.windows(2).map(|pair| ms(pair[1] - pair[0])).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ms_quantized_frames_are_paced_evenly_on_the_nominal_frame_rate() {
+        let frame_rate = 30000.0 / 1001.0;
+        // the encoder rounds the dts to milliseconds, the intervals beat between 33ms and 34ms
+        let dts: Vec<u64> = (0..90)
+            .map(|frame| (frame as f64 * 1000.0 / frame_rate).round() as u64)
+            .collect();
+        let config = PacingConfig {
+            burst_bytes: 0,
+            ..config()
+        };
+        let jitter = |spacings: &[f64]| {
+            let max = spacings.iter().cloned().fold(f64::MIN, f64::max);
+            let min = spacings.iter().cloned().fold(f64::MAX, f64::min);
+            max - min
+        };
+
+        let by_timestamps = frame_spacings(RtpPacer::new(config), &dts);
+        let mut pacer = RtpPacer::new(config);
+        pacer.set_nominal_frame_rate(Some(frame_rate));
+        let by_frame_rate = frame_spacings(pacer, &dts);
+
+        let expected = 1000.0 / frame_rate / config.catch_up_speed;
+        assert!(jitter(&by_timestamps) > 0.5, "{:?}", by_timestamps);
+        assert!(jitter(&by_frame_rate) < 0.01, "{:?}", by_frame_rate);
+        assert!(
+            by_frame_rate
+                .iter()
+                .all(|spacing| (spacing - expected).abs() < 0.01),
+            "spacing {:?}, expect {expected}ms",
+            by_frame_rate
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn frames_are_paced_by_the_speed_of_the_player() {
+        let dts: Vec<u64> = (0..25).map(|frame| frame * FRAME_INTERVAL_MS).collect();
+        let config = PacingConfig {
+            burst_bytes: 0,
+            ..config()
+        };
+        for speed in [0.5, 2.0] {
+            let mut pacer = RtpPacer::new(config);
+            pacer.set_speed(speed, Instant::now());
+            let expected = FRAME_INTERVAL_MS as f64 / config.catch_up_speed / speed;
+            let spacings = frame_spacings(pacer, &dts);
+            assert!(
+                spacings
+                    .iter()
+                    .all(|spacing| (spacing - expected).abs() < 0.01),
+                "spacing {:?}, expect {expected}ms at speed {speed}",
+                spacings
+            );
+        }
+    }
+}
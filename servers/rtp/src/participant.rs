@@ -1,50 +1,52 @@
-use crate::{rtcp_observer::RtcpObserver, rtp_observer::RtpObserver};
-use num::ToPrimitive;
+use crate::{
+    rtcp_observer::RtcpObserver, rtp_observer::RtpObserver, sdes::SourceDescription,
+    simple_statistics::RxStats,
+};
 use rtp_formats::{
+    packet::RtpTrivialPacket,
     rtcp::{
         RtcpPacket, compound_packet::RtcpCompoundPacket, report_block::ReportBlock,
         simple_ntp::SimpleNtp,
     },
-    sequence_number::SequenceNumber,
 };
-use std::time::{SystemTime, UNIX_EPOCH};
-use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// the cumulative number of packets lost is a signed 24 bits field
+const MAX_CUMULATIVE_PACKETS_LOST: i64 = (1 << 23) - 1;
+const MIN_CUMULATIVE_PACKETS_LOST: i64 = -(1 << 23);
 
 #[derive(Debug, Clone)]
 pub struct RtpParticipant {
     ssrc: u32,
-    cname: Option<String>,
+    description: SourceDescription,
     joined_at: SystemTime,
-    rtp_clockrate: u64,
-
-    max_rtp_sequence_number: SequenceNumber,
-    rtp_first_sequence_number: SequenceNumber,
-    rtp_bad_sequence_number: u64,
-    rtp_packets_sent: u64,
-    rtp_bytes_sent: u64,
-    rtp_packets_probation: u16,
-    rtp_packets_sent_prior: u64,
-    rtp_packets_expected_prior: u64,
+    /// the reception of the rtp packets of the participant
+    rx_stats: RxStats,
 
     last_sr_timestamp_ntp: Option<SimpleNtp>,
     last_sr_timestamp: Option<SystemTime>,
+    /// from the latest report block of the participant about the packets of this side
+    round_trip_time: Option<Duration>,
 
     first_rtp_sent_timestamp: Option<SystemTime>,
     first_rtp_sent_timestamp_rtp: Option<u32>,
     last_rtp_sent_timestamp: Option<SystemTime>,
-    last_rtp_sent_timestamp_rtp: Option<u32>,
-    last_rtp_interarrvial_jitter: u64,
     last_rtp_sent_rtcp_report_round: u64,
     last_rtcp_sent_timestamp: Option<SystemTime>,
     rtcp_report_round: u64,
 
     is_sender: bool,
     bye_sent_timestamp: Option<SystemTime>,
-}
 
-const MAX_DROPOUT: u16 = 3000;
-const MAX_MISORDERR: u16 = 100;
-const MIN_SEQUENTIAL: u16 = 2;
+    /// the latest rtp or rtcp packet of the participant
+    last_heard_timestamp: SystemTime,
+    /// the transport addresses its rtp and rtcp packets come from
+    rtp_source: Option<SocketAddr>,
+    rtcp_source: Option<SocketAddr>,
+}
 
 impl RtcpObserver for RtpParticipant {
     fn on_rtcp_compound_packet_sent(&mut self, packet: &RtcpCompoundPacket, timestamp: SystemTime) {
@@ -63,7 +65,9 @@ impl RtcpObserver for RtpParticipant {
             RtcpPacket::Bye(_) => {
                 self.bye_sent_timestamp = Some(timestamp);
             }
-            RtcpPacket::SourceDescription(sdes) => self.cname = sdes.get_cname_of(self.ssrc),
+            RtcpPacket::SourceDescription(sdes) => {
+                self.description.update(sdes, self.ssrc);
+            }
             _ => {}
         });
     }
@@ -73,9 +77,6 @@ impl RtcpObserver for RtpParticipant {
         _packet: &RtcpCompoundPacket,
         _timestamp: SystemTime,
     ) {
-        let expected_rtp_packets = self.max_rtp_sequence_number - self.rtp_first_sequence_number;
-        self.rtp_packets_expected_prior = expected_rtp_packets.value();
-        self.rtp_packets_sent_prior = self.rtp_packets_sent;
     }
 }
 
@@ -95,58 +96,6 @@ impl RtpObserver for RtpParticipant {
         self.is_sender = true;
         self.last_rtp_sent_rtcp_report_round = self.rtcp_report_round;
 
-        self.update_sequence_number(packet.header.sequence_number);
-        if self.rtp_packets_probation == 0 {
-            self.rtp_bytes_sent
-                .checked_add_signed(packet.get_packet_bytes_count().to_i64().unwrap())
-                .unwrap();
-
-            let r: i64 = timestamp
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis()
-                .to_i64()
-                .and_then(|v| v.checked_mul(self.rtp_clockrate.to_i64().unwrap()))
-                .and_then(|v| v.checked_div(1000))
-                .unwrap();
-
-            let prev_r: i64 = self
-                .last_rtp_sent_timestamp
-                .map(|v| v.duration_since(UNIX_EPOCH).unwrap().as_millis())
-                .and_then(|v| v.to_i64())
-                .and_then(|v| v.checked_mul(self.rtp_clockrate.to_i64().unwrap()))
-                .and_then(|v| v.checked_div(1000))
-                .unwrap();
-
-            let d: i64 = r
-                .checked_sub(packet.header.timestamp.to_i64().unwrap())
-                .and_then(|v| {
-                    v.checked_sub(
-                        prev_r
-                            .checked_sub(
-                                self.last_rtp_sent_timestamp_rtp
-                                    .unwrap_or(0)
-                                    .to_i64()
-                                    .unwrap(),
-                            )
-                            .unwrap(),
-                    )
-                })
-                .unwrap();
-
-            let jitter_delta = d
-                .abs()
-                .checked_sub(self.last_rtp_interarrvial_jitter.to_i64().unwrap())
-                .and_then(|v| v.checked_div(16))
-                .unwrap();
-            self.last_rtp_interarrvial_jitter = self
-                .last_rtp_interarrvial_jitter
-                .to_i64()
-                .and_then(|v| v.checked_add(jitter_delta))
-                .and_then(|v| v.to_u64())
-                .unwrap();
-        }
-        self.last_rtp_sent_timestamp_rtp = Some(packet.header.timestamp);
         self.last_rtp_sent_timestamp = Some(timestamp);
         if self.first_rtp_sent_timestamp.is_none() {
             self.first_rtp_sent_timestamp = Some(timestamp);
@@ -158,50 +107,62 @@ impl RtpObserver for RtpParticipant {
 }
 
 impl RtpParticipant {
-    pub fn new(ssrc: u32, cname: Option<String>, rtp_clockrate: u64) -> Self {
+    pub fn new(ssrc: u32, description: SourceDescription, rtp_clockrate: u64) -> Self {
+        Self::new_at(ssrc, description, rtp_clockrate, SystemTime::now())
+    }
+
+    /// joined at the timestamp
+    pub fn new_at(
+        ssrc: u32,
+        description: SourceDescription,
+        rtp_clockrate: u64,
+        timestamp: SystemTime,
+    ) -> Self {
         Self {
             ssrc,
-            cname,
-            joined_at: SystemTime::now(),
-            rtp_clockrate,
+            description,
+            joined_at: timestamp,
+            rx_stats: RxStats::new(ssrc, rtp_clockrate),
             is_sender: false,
-            max_rtp_sequence_number: Default::default(),
-            rtp_first_sequence_number: Default::default(),
-            rtp_bad_sequence_number: u16::MAX as u64 + 2,
-            rtp_packets_sent: 0,
-            rtp_packets_expected_prior: 0,
-            rtp_packets_sent_prior: 0,
-            rtp_bytes_sent: 0,
-            rtp_packets_probation: MIN_SEQUENTIAL,
 
             last_sr_timestamp_ntp: Default::default(),
             last_sr_timestamp: None,
+            round_trip_time: None,
 
             first_rtp_sent_timestamp: None,
             first_rtp_sent_timestamp_rtp: None,
             last_rtp_sent_timestamp: None,
-            last_rtp_sent_timestamp_rtp: None,
-            last_rtp_interarrvial_jitter: 0,
             last_rtp_sent_rtcp_report_round: 0,
             last_rtcp_sent_timestamp: None,
             rtcp_report_round: 0,
             bye_sent_timestamp: None,
+
+            last_heard_timestamp: timestamp,
+            rtp_source: None,
+            rtcp_source: None,
         }
     }
 
+    /// about the rtp packets received from the participant, RFC 3550 6.4.1
     pub fn generate_report_block(&self, current_timestamp: SystemTime) -> ReportBlock {
+        let stats = self.rx_stats.snapshot(current_timestamp);
+        let (highest_sequence_number, cycles) = stats.highest_sequence_number();
         ReportBlock::builder()
             .ssrc(self.ssrc)
-            .fraction_lost(self.rtp_fraction_lost_report())
-            .cumulative_packet_lost(self.rtp_cumulative_packets_lost_report().to_i32().unwrap())
-            .highest_sequence_number_received(self.max_rtp_sequence_number.number())
-            .highest_sequence_number_cycles(self.max_rtp_sequence_number.round())
-            .interarrival_jitter(self.last_rtp_interarrvial_jitter.to_u32().unwrap())
+            .fraction_lost(stats.fraction_lost as f64 / 256.0)
+            .cumulative_packet_lost(Self::cumulative_packets_lost(stats.packets_lost))
+            .highest_sequence_number_received(highest_sequence_number)
+            .highest_sequence_number_cycles(cycles)
+            .interarrival_jitter(stats.jitter)
             .last_sr(self.last_sr_timestamp_ntp.unwrap_or_default())
             .delay_since_last_sr(
                 self.last_sr_timestamp
                     .map(|v| {
-                        current_timestamp.duration_since(v).unwrap().as_nanos() * 65536
+                        current_timestamp
+                            .duration_since(v)
+                            .unwrap_or_default()
+                            .as_nanos()
+                            * 65536
                             / 1_000_000_000
                     })
                     .unwrap_or(0) as u32,
@@ -209,15 +170,13 @@ impl RtpParticipant {
             .build()
     }
 
-    pub fn reset_sequence_number(&mut self, sequence_number: u16) {
-        self.rtp_first_sequence_number = sequence_number.into();
-        self.max_rtp_sequence_number = self.rtp_first_sequence_number;
-        self.rtp_packets_sent = 0;
-        self.rtp_bad_sequence_number = u16::MAX as u64 + 2;
+    /// the packets lost since the start, clamped to the 24 bits of the field
+    fn cumulative_packets_lost(packets_lost: i64) -> i32 {
+        packets_lost.clamp(MIN_CUMULATIVE_PACKETS_LOST, MAX_CUMULATIVE_PACKETS_LOST) as i32
     }
 
-    pub fn reset(&mut self, ssrc: u32, cname: Option<String>, rtp_clockrate: u64) {
-        *self = Self::new(ssrc, cname, rtp_clockrate)
+    pub fn reset(&mut self, ssrc: u32, description: SourceDescription, rtp_clockrate: u64) {
+        *self = Self::new(ssrc, description, rtp_clockrate)
     }
 
     pub fn ssrc(&self) -> u32 {
@@ -225,7 +184,12 @@ impl RtpParticipant {
     }
 
     pub fn cname(&self) -> Option<&String> {
-        self.cname.as_ref()
+        self.description.cname.as_ref()
+    }
+
+    /// the sdes items the participant sent, or sends if it is this side
+    pub fn description(&self) -> &SourceDescription {
+        &self.description
     }
 
     pub fn is_sender(&self) -> bool {
@@ -236,53 +200,58 @@ impl RtpParticipant {
         self.bye_sent_timestamp.is_some()
     }
 
-    fn update_sequence_number(&mut self, sequence_number: u16) -> bool {
-        let delta = sequence_number.saturating_sub(self.max_rtp_sequence_number.number());
-        // probation provides a small gap between the first packet arrive and this participant got statisticed
-        if self.rtp_packets_probation > 0 {
-            if sequence_number == self.max_rtp_sequence_number.next() {
-                self.rtp_packets_probation -= 1;
-                self.max_rtp_sequence_number.add_number(1);
-                if self.rtp_packets_probation == 0 {
-                    self.reset_sequence_number(sequence_number);
-                    self.rtp_packets_sent.checked_add(1).unwrap();
-                    return true;
-                }
-            } else {
-                self.rtp_packets_probation = MIN_SEQUENTIAL - 1;
-                self.max_rtp_sequence_number.set_number(sequence_number);
-            }
-            return false;
-        } else if delta < MAX_DROPOUT {
-            if sequence_number < self.max_rtp_sequence_number.number() {
-                // sequence number wrapped
-                self.max_rtp_sequence_number.add_round(1);
-            }
-            self.max_rtp_sequence_number.set_number(sequence_number);
-        } else if delta <= u16::MAX - MAX_MISORDERR + 1 {
-            if sequence_number as u64 == self.rtp_bad_sequence_number {
-                // two sequential packets with very large sequence number gap,
-                // maybe the peer reset the sequence number without telling everyone
-                self.reset_sequence_number(sequence_number);
-            } else {
-                self.rtp_bad_sequence_number =
-                    (sequence_number.to_u64().unwrap().checked_add(1).unwrap()) & (u16::MAX as u64);
-                return false;
-            }
+    /// records a packet arriving from the participant, the first source address of rtp and of rtcp
+    /// is kept, Err with it if the packet comes from another one
+    pub fn heard_from(
+        &mut self,
+        source: Option<SocketAddr>,
+        rtcp: bool,
+        timestamp: SystemTime,
+    ) -> Result<(), SocketAddr> {
+        let known_source = if rtcp {
+            &mut self.rtcp_source
         } else {
-            // duplicate or reordered packet
+            &mut self.rtp_source
+        };
+        match (*known_source, source) {
+            (Some(known), Some(source)) if known != source => return Err(known),
+            (None, Some(source)) => *known_source = Some(source),
+            _ => {}
         }
-        self.rtp_packets_sent.checked_add(1).unwrap();
-        true
+        self.last_heard_timestamp = self.last_heard_timestamp.max(timestamp);
+        Ok(())
+    }
+
+    pub fn last_heard_timestamp(&self) -> SystemTime {
+        self.last_heard_timestamp
+    }
+
+    /// the participant is taken as a receiver until it sends rtp again
+    pub fn stop_sending(&mut self) {
+        self.is_sender = false;
+    }
+
+    /// the packet is one of the rtp stream of the participant, not one it contributed to
+    pub fn on_rtp_packet_arrived(&mut self, packet: &RtpTrivialPacket, timestamp: SystemTime) {
+        self.rx_stats.on_packet(packet, timestamp);
+    }
+
+    /// None until the participant reports on a sender report of this side
+    pub fn round_trip_time(&self) -> Option<Duration> {
+        self.round_trip_time
+    }
+
+    pub fn on_round_trip_time(&mut self, rtt: Duration) {
+        self.round_trip_time = Some(rtt);
     }
 
-    pub fn rtp_fraction_lost_report(&self) -> f64 {
-        self.rtp_packets_sent_prior as f64 / self.rtp_packets_expected_prior as f64
+    pub fn rx_stats(&self) -> &RxStats {
+        &self.rx_stats
     }
 
-    pub fn rtp_cumulative_packets_lost_report(&self) -> i64 {
-        let expected_rtp_packets = self.max_rtp_sequence_number - self.rtp_first_sequence_number;
-        expected_rtp_packets.value() as i64 - self.rtp_packets_sent as i64
+    /// a report block about the participant was just sent
+    pub fn on_reported(&mut self) {
+        self.rx_stats.on_reported();
     }
 
     pub fn get_latest_packet_sent_timestamp(&self) -> Option<SystemTime> {
@@ -313,3 +282,141 @@ impl RtpParticipant {
         self.first_rtp_sent_timestamp_rtp
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use rtp_formats::{
+        header::RtpHeader,
+        packet::RtpTrivialPacket,
+        rtcp::{
+            RtcpPacket, compound_packet::RtcpCompoundPacket, sender_report::RtcpSenderReport,
+            simple_ntp::SimpleNtp,
+        },
+    };
+    use tokio_util::bytes::Bytes;
+
+    use super::RtpParticipant;
+    use crate::rtcp_observer::RtcpObserver;
+
+    fn participant() -> RtpParticipant {
+        RtpParticipant::new(100, Default::default(), 90000)
+    }
+
+    fn arrive(participant: &mut RtpParticipant, sequence_numbers: &[u16], start: SystemTime) {
+        for (i, sequence_number) in sequence_numbers.iter().enumerate() {
+            let packet = RtpTrivialPacket::new(
+                RtpHeader {
+                    payload_type: 96,
+                    sequence_number: *sequence_number,
+                    timestamp: i as u32 * 3000,
+                    ssrc: 100,
+                    ..Default::default()
+                },
+                Bytes::from(vec![0; 100]),
+            );
+            participant.on_rtp_packet_arrived(&packet, start + Duration::from_secs(i as u64) / 30);
+        }
+    }
+
+    #[test]
+    fn report_block_numbers_of_the_arrivals() {
+        // the sequence numbers arrived, the fraction lost in 1/256, the cumulative lost,
+        // the highest sequence number and its cycles
+        let cases = [
+            ("in order", (0..10).collect::<Vec<u16>>(), 0, 0, 9, 0),
+            ("2 of 10 lost", vec![0, 1, 2, 4, 5, 6, 8, 9], 51, 2, 9, 0),
+            ("duplicates", vec![0, 1, 2, 2, 3, 3, 4], 0, -2, 4, 0),
+            ("reordered", vec![0, 2, 1, 3], 0, 0, 3, 0),
+            (
+                "1 of 10 lost across the wrap",
+                vec![65530, 65531, 65532, 65534, 65535, 0, 1, 2, 3],
+                25,
+                1,
+                3,
+                1,
+            ),
+        ];
+        let start = SystemTime::now();
+        for (name, sequence_numbers, fraction_lost, cumulative, highest, cycles) in cases {
+            let mut participant = participant();
+            arrive(&mut participant, &sequence_numbers, start);
+            let block = participant.generate_report_block(start + Duration::from_secs(1));
+            assert_eq!(block.ssrc, 100, "{}", name);
+            assert_eq!(
+                (block.fraction_lost * 256.0) as u8,
+                fraction_lost,
+                "{}",
+                name
+            );
+            assert_eq!(block.cumulative_packet_lost, cumulative, "{}", name);
+            assert_eq!(block.highest_sequence_number_received, highest, "{}", name);
+            assert_eq!(block.sequence_number_cycles, cycles, "{}", name);
+        }
+    }
+
+    #[test]
+    fn cumulative_packets_lost_is_clamped_to_24_bits() {
+        let cases = [
+            (0, 0),
+            (-2, -2),
+            (8_388_607, 8_388_607),
+            (8_388_608, 8_388_607),
+            (i64::MAX, 8_388_607),
+            (-8_388_608, -8_388_608),
+            (-8_388_609, -8_388_608),
+        ];
+        for (packets_lost, field) in cases {
+            assert_eq!(
+                RtpParticipant::cumulative_packets_lost(packets_lost),
+                field,
+                "{}",
+                packets_lost
+            );
+        }
+    }
+
+    #[test]
+    fn report_block_echoes_the_last_sender_report() {
+        let received_at = SystemTime::now();
+        let sender_report = RtcpCompoundPacket::builder()
+            .packet(RtcpPacket::SenderReport(
+                RtcpSenderReport::builder()
+                    .ssrc(100)
+                    .ntp(SimpleNtp::from(0x12345678_9ABCDEF0))
+                    .build()
+                    .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        // the time after the sender report arrived, the lsr and the dlsr in 1/65536 seconds
+        let cases = [
+            (None, 0, 0),
+            (Some(Duration::from_millis(1500)), 0x56789ABC, 98304),
+            (Some(Duration::from_millis(250)), 0x56789ABC, 16384),
+            (Some(Duration::ZERO), 0x56789ABC, 0),
+        ];
+        for (after, lsr, dlsr) in cases {
+            let mut participant = participant();
+            arrive(&mut participant, &[0, 1], received_at);
+            if after.is_some() {
+                participant.on_rtcp_compound_packet_sent(&sender_report, received_at);
+            }
+            let block = participant.generate_report_block(received_at + after.unwrap_or_default());
+            assert_eq!(
+                u32::from(block.last_sender_report_timestamp),
+                lsr,
+                "{:?}",
+                after
+            );
+            assert_eq!(block.delay_since_last_sender_report, dlsr, "{:?}", after);
+        }
+
+        // the clock went backwards, no delay rather than a panic
+        let mut participant = participant();
+        participant.on_rtcp_compound_packet_sent(&sender_report, received_at);
+        let block = participant.generate_report_block(received_at - Duration::from_secs(1));
+        assert_eq!(block.delay_since_last_sender_report, 0);
+    }
+}
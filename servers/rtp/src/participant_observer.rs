@@ -0,0 +1,57 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime},
+};
+
+use crate::{sdes::SourceDescription, simple_statistics::RxStatsSnapshot};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParticipantEvent {
+    /// the first packet of the ssrc arrived
+    Joined { ssrc: u32 },
+    /// the participant sent a bye, RFC 3550 6.3.7
+    Left { ssrc: u32, reason: Option<String> },
+    /// nothing arrived from the participant for 5 report intervals, RFC 3550 6.3.5
+    TimedOut { ssrc: u32, silent_for: Duration },
+    /// packets of a known ssrc arrived from another transport address, they are ignored
+    SsrcCollision {
+        ssrc: u32,
+        known_source: SocketAddr,
+        source: SocketAddr,
+    },
+    /// the participant sent sdes items it did not send before, or with other values
+    Described {
+        ssrc: u32,
+        description: SourceDescription,
+    },
+    /// a report block about the rtp packets of the participant was sent, these are its numbers,
+    /// with the latest round trip time to the participant if there is one
+    ReceptionReported {
+        ssrc: u32,
+        stats: RxStatsSnapshot,
+        round_trip_time: Option<Duration>,
+    },
+    /// a report block of the participant about the rtp packets of this side gave the round trip time to it
+    RoundTripMeasured {
+        ssrc: u32,
+        round_trip_time: Duration,
+    },
+}
+
+impl ParticipantEvent {
+    pub fn ssrc(&self) -> u32 {
+        match self {
+            Self::Joined { ssrc }
+            | Self::Left { ssrc, .. }
+            | Self::TimedOut { ssrc, .. }
+            | Self::SsrcCollision { ssrc, .. }
+            | Self::Described { ssrc, .. }
+            | Self::ReceptionReported { ssrc, .. }
+            | Self::RoundTripMeasured { ssrc, .. } => *ssrc,
+        }
+    }
+}
+
+pub trait ParticipantObserver: Send {
+    fn on_participant_event(&mut self, _event: &ParticipantEvent, _timestamp: SystemTime) {}
+}
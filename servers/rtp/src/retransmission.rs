@@ -0,0 +1,302 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use rtp_formats::{
+    packet::{RtpTrivialPacket, rtx::rtx_encapsulate},
+    rtcp::feedback::RtcpFeedbackPacket,
+};
+use utils::{random::random_u16, traits::dynamic_sized_packet::DynamicSizedPacket};
+
+pub const DEFAULT_HISTORY_MAX_PACKETS: usize = 512;
+pub const DEFAULT_HISTORY_MAX_BYTES: usize = 1024 * 1024;
+
+/// how many sent packets are kept to answer nacks, per ssrc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetransmissionConfig {
+    pub max_packets: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for RetransmissionConfig {
+    fn default() -> Self {
+        Self {
+            max_packets: DEFAULT_HISTORY_MAX_PACKETS,
+            max_bytes: DEFAULT_HISTORY_MAX_BYTES,
+        }
+    }
+}
+
+/// negotiated by sdp, retransmissions are sent as is if not set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtxParameters {
+    pub payload_type: u8,
+    pub ssrc: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct RetransmissionMetrics {
+    nacks_received: AtomicU64,
+    packets_requested: AtomicU64,
+    packets_retransmitted: AtomicU64,
+}
+
+impl RetransmissionMetrics {
+    pub fn nacks_received(&self) -> u64 {
+        self.nacks_received.load(Ordering::Relaxed)
+    }
+
+    /// lost packets reported by the nacks
+    pub fn packets_requested(&self) -> u64 {
+        self.packets_requested.load(Ordering::Relaxed)
+    }
+
+    /// requested packets still in the history
+    pub fn packets_retransmitted(&self) -> u64 {
+        self.packets_retransmitted.load(Ordering::Relaxed)
+    }
+}
+
+/// the latest sent packets of one ssrc, the oldest ones are dropped when either budget is exceeded
+#[derive(Debug)]
+pub struct RtpPacketHistory {
+    config: RetransmissionConfig,
+    bytes: usize,
+    packets: VecDeque<RtpTrivialPacket>,
+}
+
+impl RtpPacketHistory {
+    pub fn new(config: RetransmissionConfig) -> Self {
+        Self {
+            config,
+            bytes: 0,
+            packets: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, packet: RtpTrivialPacket) {
+        self.bytes += packet.get_packet_bytes_count();
+        self.packets.push_back(packet);
+        while self.packets.len() > self.config.max_packets
+            || (self.bytes > self.config.max_bytes && self.packets.len() > 1)
+        {
+            if let Some(dropped) = self.packets.pop_front() {
+                self.bytes -= dropped.get_packet_bytes_count();
+            }
+        }
+    }
+
+    pub fn get(&self, sequence_number: u16) -> Option<&RtpTrivialPacket> {
+        let first = self.packets.front()?.header.sequence_number;
+        // packets are usually pushed with consecutive sequence numbers
+        let index = sequence_number.wrapping_sub(first) as usize;
+        if let Some(packet) = self.packets.get(index)
+            && packet.header.sequence_number == sequence_number
+        {
+            return Some(packet);
+        }
+        self.packets
+            .iter()
+            .rev()
+            .find(|packet| packet.header.sequence_number == sequence_number)
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+/// keeps the sent packets and answers generic nacks with them
+#[derive(Debug)]
+pub struct RtpRetransmitter {
+    config: RetransmissionConfig,
+    histories: HashMap<u32, RtpPacketHistory>,
+    rtx: Option<RtxParameters>,
+    rtx_sequence_number: u16,
+    metrics: Arc<RetransmissionMetrics>,
+}
+
+impl RtpRetransmitter {
+    pub fn new(
+        config: RetransmissionConfig,
+        rtx: Option<RtxParameters>,
+        metrics: Arc<RetransmissionMetrics>,
+    ) -> Self {
+        Self {
+            config,
+            histories: HashMap::new(),
+            rtx,
+            rtx_sequence_number: random_u16(),
+            metrics,
+        }
+    }
+
+    pub fn on_packet_sent(&mut self, packet: &RtpTrivialPacket) {
+        self.histories
+            .entry(packet.header.ssrc)
+            .or_insert_with(|| RtpPacketHistory::new(self.config))
+            .push(packet.clone());
+    }
+
+    /// the packets to send for the nack, in the order they are requested
+    pub fn on_nack(&mut self, nack: &RtcpFeedbackPacket) -> Vec<RtpTrivialPacket> {
+        let lost = match nack.lost_sequence_numbers() {
+            Ok(lost) => lost,
+            Err(err) => {
+                tracing::warn!("ignore feedback packet: {}", err);
+                return vec![];
+            }
+        };
+        self.metrics.nacks_received.fetch_add(1, Ordering::Relaxed);
+        self.metrics
+            .packets_requested
+            .fetch_add(lost.len() as u64, Ordering::Relaxed);
+
+        let Some(history) = self.histories.get(&nack.media_ssrc) else {
+            tracing::debug!("got nack for unknown ssrc: {}", nack.media_ssrc);
+            return vec![];
+        };
+        let mut packets = Vec::with_capacity(lost.len());
+        for sequence_number in lost {
+            let Some(packet) = history.get(sequence_number) else {
+                tracing::debug!(
+                    "nacked packet {} of ssrc {} is no longer kept",
+                    sequence_number,
+                    nack.media_ssrc
+                );
+                continue;
+            };
+            packets.push(match self.rtx {
+                None => packet.clone(),
+                Some(rtx) => {
+                    let rtx_packet = rtx_encapsulate(
+                        packet,
+                        rtx.payload_type,
+                        rtx.ssrc,
+                        self.rtx_sequence_number,
+                    );
+                    self.rtx_sequence_number = self.rtx_sequence_number.wrapping_add(1);
+                    rtx_packet
+                }
+            });
+        }
+        self.metrics
+            .packets_retransmitted
+            .fetch_add(packets.len() as u64, Ordering::Relaxed);
+        packets
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rtp_formats::{header::RtpHeader, packet::rtx::rtx_decapsulate, rtcp::feedback::NackItem};
+    use tokio_util::bytes::Bytes;
+
+    use super::*;
+
+    fn packet(sequence_number: u16) -> RtpTrivialPacket {
+        RtpTrivialPacket::new(
+            RtpHeader {
+                payload_type: 96,
+                sequence_number,
+                timestamp: sequence_number as u32 * 3000,
+                ssrc: 1,
+                ..Default::default()
+            },
+            Bytes::from(vec![sequence_number as u8; 100]),
+        )
+    }
+
+    fn nack(lost: &[u16]) -> RtcpFeedbackPacket {
+        RtcpFeedbackPacket::builder()
+            .media_ssrc(1)
+            .nack_items(&NackItem::from_lost_sequence_numbers(lost.iter().copied()))
+            .build()
+            .unwrap()
+    }
+
+    fn assert_same(packet: &RtpTrivialPacket, expected: &RtpTrivialPacket) {
+        assert_eq!(packet.header.payload_type, expected.header.payload_type);
+        assert_eq!(
+            packet.header.sequence_number,
+            expected.header.sequence_number
+        );
+        assert_eq!(packet.header.timestamp, expected.header.timestamp);
+        assert_eq!(packet.header.ssrc, expected.header.ssrc);
+        assert_eq!(packet.payload, expected.payload);
+    }
+
+    #[test]
+    fn test_history_budget() {
+        let mut history = RtpPacketHistory::new(RetransmissionConfig {
+            max_packets: 4,
+            max_bytes: usize::MAX,
+        });
+        (u16::MAX - 2..=u16::MAX)
+            .chain(0..3)
+            .for_each(|seq| history.push(packet(seq)));
+        assert_eq!(history.len(), 4);
+        assert!(history.get(u16::MAX - 1).is_none());
+        assert_eq!(
+            history.get(u16::MAX).unwrap().header.sequence_number,
+            u16::MAX
+        );
+        assert_eq!(history.get(2).unwrap().header.sequence_number, 2);
+
+        let packet_bytes = packet(0).get_packet_bytes_count();
+        let mut history = RtpPacketHistory::new(RetransmissionConfig {
+            max_packets: usize::MAX,
+            max_bytes: packet_bytes * 2,
+        });
+        (0..5).for_each(|seq| history.push(packet(seq)));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.bytes(), packet_bytes * 2);
+        assert!(history.get(2).is_none());
+        assert!(history.get(3).is_some());
+    }
+
+    #[test]
+    fn test_retransmit() {
+        let metrics = Arc::new(RetransmissionMetrics::default());
+        let mut retransmitter = RtpRetransmitter::new(Default::default(), None, metrics.clone());
+        (0..10).for_each(|seq| retransmitter.on_packet_sent(&packet(seq)));
+
+        let resent = retransmitter.on_nack(&nack(&[3, 5, 100]));
+        assert_eq!(resent.len(), 2);
+        assert_same(&resent[0], &packet(3));
+        assert_same(&resent[1], &packet(5));
+        assert_eq!(metrics.nacks_received(), 1);
+        assert_eq!(metrics.packets_requested(), 3);
+        assert_eq!(metrics.packets_retransmitted(), 2);
+
+        let rtx = RtxParameters {
+            payload_type: 98,
+            ssrc: 2,
+        };
+        let mut retransmitter = RtpRetransmitter::new(Default::default(), Some(rtx), metrics);
+        (0..10).for_each(|seq| retransmitter.on_packet_sent(&packet(seq)));
+        let resent = retransmitter.on_nack(&nack(&[3, 5]));
+        assert_eq!(resent.len(), 2);
+        assert_eq!(
+            resent[1].header.sequence_number,
+            resent[0].header.sequence_number.wrapping_add(1)
+        );
+        resent.iter().zip([3, 5]).for_each(|(resent, seq)| {
+            assert_eq!(resent.header.payload_type, 98);
+            assert_eq!(resent.header.ssrc, 2);
+            assert_same(&rtx_decapsulate(resent, 96, 1).unwrap(), &packet(seq));
+        });
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use futures::future::BoxFuture;
+use rtp_formats::rtcp::{RtcpPacket, app::RtcpAppPacket, compound_packet::RtcpCompoundPacket};
+use tokio::sync::mpsc;
+use tokio_util::bytes::Bytes;
+use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
+
+use crate::errors::{RtpSessionError, RtpSessionResult};
+
+/// the received app packets waiting for their handlers, the ones arriving while it is full are dropped
+pub const APP_HANDLER_QUEUE_SIZE: usize = 64;
+/// the app packets waiting for the reports to go out with, the ones sent while it is full are dropped
+pub const MAX_PENDING_APP_PACKETS: usize = 64;
+/// the compound packets carrying app packets are kept below the mtu of the path
+pub const MAX_RTCP_COMPOUND_BYTES: usize = 1200;
+/// leaves room for the reports and the sdes of the session next to an app packet
+pub const MAX_APP_DATA_BYTES: usize = 1024;
+
+/// the 4 ascii characters naming the application an app packet is of, RFC 3550 6.7
+pub type RtcpAppName = [u8; 4];
+
+/// handles the app packets of the names it is registered for on a session,
+/// called one packet after another off the rtcp thread, so it may take its time
+pub trait RtcpAppHandler: Send {
+    fn on_app_packet(&mut self, ssrc: u32, name: RtcpAppName, data: Bytes) -> BoxFuture<'_, ()>;
+}
+
+impl<F, Fut> RtcpAppHandler for F
+where
+    F: FnMut(u32, RtcpAppName, Bytes) -> Fut + Send,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn on_app_packet(&mut self, ssrc: u32, name: RtcpAppName, data: Bytes) -> BoxFuture<'_, ()> {
+        Box::pin(self(ssrc, name, data))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RtcpAppMetrics {
+    received: AtomicU64,
+    unhandled: AtomicU64,
+    dropped: AtomicU64,
+    sent: AtomicU64,
+    send_dropped: AtomicU64,
+}
+
+impl RtcpAppMetrics {
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// received with a name no handler is registered for, they are ignored
+    pub fn unhandled(&self) -> u64 {
+        self.unhandled.load(Ordering::Relaxed)
+    }
+
+    /// received while the handlers were behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// not sent as too many were waiting, or as one did not fit next to the reports
+    pub fn send_dropped(&self) -> u64 {
+        self.send_dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn on_send_dropped(&self, name: RtcpAppName, reason: &str) {
+        tracing::warn!(
+            "drop rtcp app packet {:?} to send as {}",
+            String::from_utf8_lossy(&name),
+            reason
+        );
+        self.send_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// an app packet to send, it goes out with the next reports of the session,
+/// under the ssrc of the session
+#[derive(Debug, Clone)]
+pub struct RtcpAppMessage(RtcpAppPacket);
+
+impl RtcpAppMessage {
+    /// the data is padded to 32 bits by the application, up to MAX_APP_DATA_BYTES
+    pub fn new(name: RtcpAppName, data: Bytes) -> RtpSessionResult<Self> {
+        if data.len() > MAX_APP_DATA_BYTES {
+            return Err(RtpSessionError::AppDataTooLarge(data.len()));
+        }
+        Ok(Self(
+            RtcpAppPacket::builder().name(name).payload(data).build()?,
+        ))
+    }
+
+    pub fn name(&self) -> RtcpAppName {
+        self.0.name
+    }
+}
+
+/// hands the received app packets of the registered names to their handlers
+pub(crate) struct RtcpAppInbox {
+    names: HashSet<RtcpAppName>,
+    handler_tx: mpsc::Sender<RtcpAppPacket>,
+    metrics: Arc<RtcpAppMetrics>,
+}
+
+impl RtcpAppInbox {
+    pub(crate) fn new(
+        names: HashSet<RtcpAppName>,
+        handler_tx: mpsc::Sender<RtcpAppPacket>,
+        metrics: Arc<RtcpAppMetrics>,
+    ) -> Self {
+        Self {
+            names,
+            handler_tx,
+            metrics,
+        }
+    }
+
+    /// never waits for the handlers
+    pub(crate) fn on_rtcp_compound_packet_received(&self, packet: &RtcpCompoundPacket) {
+        for app in packet.packets().iter().filter_map(|item| match item {
+            RtcpPacket::App(app) => Some(app),
+            _ => None,
+        }) {
+            self.metrics.received.fetch_add(1, Ordering::Relaxed);
+            if !self.names.contains(&app.name) {
+                self.metrics.unhandled.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            if let Err(err) = self.handler_tx.try_send(app.clone()) {
+                tracing::warn!(
+                    "drop rtcp app packet {:?} of ssrc {} as the handlers are busy: {}",
+                    String::from_utf8_lossy(&app.name),
+                    app.ssrc,
+                    err
+                );
+                self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// runs the handlers on the received app packets until the rtcp thread goes away
+pub(crate) async fn run_app_handlers(
+    mut handler_rx: mpsc::Receiver<RtcpAppPacket>,
+    mut handlers: HashMap<RtcpAppName, Box<dyn RtcpAppHandler>>,
+) {
+    while let Some(app) = handler_rx.recv().await {
+        if let Some(handler) = handlers.get_mut(&app.name) {
+            handler.on_app_packet(app.ssrc, app.name, app.payload).await;
+        }
+    }
+}
+
+/// the app packets waiting to go out with the reports of the session, in the order they were sent.
+/// they only ride the compound packets the report interval sends, so they count in the rtcp bandwidth share
+pub(crate) struct RtcpAppOutbox {
+    pending: VecDeque<RtcpAppMessage>,
+    metrics: Arc<RtcpAppMetrics>,
+}
+
+impl RtcpAppOutbox {
+    pub(crate) fn new(metrics: Arc<RtcpAppMetrics>) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            metrics,
+        }
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.pending.len() >= MAX_PENDING_APP_PACKETS
+    }
+
+    pub(crate) fn push(&mut self, message: RtcpAppMessage) {
+        self.pending.push_back(message);
+    }
+
+    /// the waiting packets fitting in the bytes left next to the reports, the rest waits for the next reports.
+    /// one that does not fit even alone is dropped
+    pub(crate) fn take_fitting(&mut self, ssrc: u32, mut room: usize) -> Vec<RtcpPacket> {
+        let mut packets = Vec::new();
+        while let Some(message) = self.pending.front() {
+            let size = message.0.get_packet_bytes_count();
+            if size > room {
+                if !packets.is_empty() {
+                    break;
+                }
+                if let Some(message) = self.pending.pop_front() {
+                    self.metrics
+                        .on_send_dropped(message.name(), "it does not fit next to the reports");
+                }
+                continue;
+            }
+            room -= size;
+            let Some(RtcpAppMessage(mut packet)) = self.pending.pop_front() else {
+                break;
+            };
+            packet.ssrc = ssrc;
+            packets.push(RtcpPacket::App(packet));
+        }
+        self.metrics
+            .sent
+            .fetch_add(packets.len() as u64, Ordering::Relaxed);
+        packets
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use rtp_formats::rtcp::RtcpPacket;
+    use tokio_util::bytes::Bytes;
+
+    use super::{MAX_APP_DATA_BYTES, RtcpAppMessage, RtcpAppMetrics, RtcpAppOutbox};
+    use crate::errors::RtpSessionError;
+
+    #[test]
+    fn app_packets_to_send_are_taken_as_they_fit() {
+        assert!(matches!(
+            RtcpAppMessage::new(*b"TELE", Bytes::from(vec![0; MAX_APP_DATA_BYTES + 4])),
+            Err(RtpSessionError::AppDataTooLarge(_))
+        ));
+        assert!(matches!(
+            RtcpAppMessage::new(*b"TELE", Bytes::from_static(&[0; 3])),
+            Err(RtpSessionError::RtpFormatError(_))
+        ));
+
+        let metrics = Arc::new(RtcpAppMetrics::default());
+        let mut outbox = RtcpAppOutbox::new(Arc::clone(&metrics));
+        for len in [100, 100, 400] {
+            outbox.push(RtcpAppMessage::new(*b"TELE", Bytes::from(vec![0; len])).unwrap());
+        }
+        // 12 bytes of header, ssrc and name each
+        let packets = outbox.take_fitting(0x1234, 300);
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|packet| matches!(
+            packet,
+            RtcpPacket::App(app) if app.ssrc == 0x1234 && app.name == *b"TELE"
+        )));
+        // too large for what is left next to the reports, the one after it still goes out
+        outbox.push(RtcpAppMessage::new(*b"TELE", Bytes::from(vec![0; 40])).unwrap());
+        assert_eq!(outbox.take_fitting(0x1234, 300).len(), 1);
+        assert_eq!(metrics.sent(), 3);
+        assert_eq!(metrics.send_dropped(), 1);
+        assert!(outbox.take_fitting(0x1234, 300).is_empty());
+    }
+}
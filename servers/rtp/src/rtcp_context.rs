@@ -1,8 +1,10 @@
 use crate::{
     errors::{RtpSessionError, RtpSessionResult},
     participant::RtpParticipant,
+    participant_observer::{ParticipantEvent, ParticipantObserver},
     rtcp_observer::RtcpObserver,
     rtp_observer::RtpObserver,
+    sdes::SourceDescription,
 };
 use num::ToPrimitive;
 use rtp_formats::rtcp::{
@@ -11,8 +13,8 @@ use rtp_formats::rtcp::{
     sender_report::RtcpSenderReport,
 };
 use std::{
-    collections::HashMap,
-    ops::Mul,
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use utils::{
@@ -20,7 +22,17 @@ use utils::{
     traits::dynamic_sized_packet::DynamicSizedPacket,
 };
 
-pub trait RtpSessionObserver: RtpObserver + RtcpObserver + Send + Sync {}
+pub trait RtpSessionObserver:
+    RtpObserver + RtcpObserver + ParticipantObserver + Send + Sync
+{
+}
+
+/// late packets of a participant that sent a bye don't bring it back for this long
+const BYE_HOLD: Duration = Duration::from_secs(2);
+/// RFC 3550 6.3.5, members are dropped after this many deterministic report intervals of silence
+const MEMBER_TIMEOUT_INTERVALS: u32 = 5;
+/// the report count of a sender or receiver report is 5 bits
+const MAX_REPORT_BLOCKS: usize = 31;
 
 pub(crate) struct RtcpContext {
     ssrc: u32,
@@ -28,6 +40,11 @@ pub(crate) struct RtcpContext {
     tn: SystemTime,
     pmembers: u64,
     participants: HashMap<u32, RtpParticipant>,
+    /// the participants that sent a bye and when
+    departed: HashMap<u32, SystemTime>,
+    /// ssrc collisions already reported
+    collisions: HashSet<(u32, SocketAddr)>,
+    /// 5% of the session bandwidth, in kbps
     rtcp_bw: u64,
     avg_rtcp_size: u64,
     initial: bool,
@@ -42,32 +59,7 @@ impl RtcpObserver for RtcpContext {
         packet: &rtp_formats::rtcp::compound_packet::RtcpCompoundPacket,
         timestamp: SystemTime,
     ) {
-        packet.packets().iter().for_each(|item| {
-            if let Some(ssrc) = item.sender_ssrc() {
-                self.add_participant(ssrc, None);
-                self.participants
-                    .entry(ssrc)
-                    .and_modify(|p| p.on_rtcp_compound_packet_sent(packet, timestamp));
-            }
-
-            item.csrc_list().iter().for_each(|csrc| {
-                self.add_participant(*csrc, None);
-                self.participants
-                    .entry(*csrc)
-                    .and_modify(|p| p.on_rtcp_compound_packet_sent(packet, timestamp));
-            });
-
-            if let RtcpPacket::Bye(bye) = item {
-                bye.ssrc_list
-                    .iter()
-                    .for_each(|v| self.on_bye_packet_received(*v, bye.leave_reason.clone()));
-            }
-        });
-        self.update_avg_rtcp_size(packet.get_packet_bytes_count().to_u64().unwrap());
-
-        self.session_observers
-            .iter_mut()
-            .for_each(|item| item.on_rtcp_compound_packet_received(packet, timestamp));
+        self.on_rtcp_compound_packet_received_from(packet, None, timestamp);
     }
 
     fn on_rtcp_compound_packet_sent(
@@ -88,6 +80,7 @@ impl RtcpObserver for RtcpContext {
         self.participants
             .entry(self.ssrc)
             .and_modify(|p| p.on_rtcp_compound_packet_received(packet, timestamp));
+        self.on_reception_reported(timestamp);
 
         self.session_observers
             .iter_mut()
@@ -101,22 +94,7 @@ impl RtpObserver for RtcpContext {
         packet: &rtp_formats::packet::RtpTrivialPacket,
         timestamp: SystemTime,
     ) {
-        self.add_participant(packet.header.ssrc, None);
-        packet.header.csrc_list.iter().for_each(|csrc| {
-            self.add_participant(*csrc, None);
-        });
-        self.participants.entry(packet.header.ssrc).and_modify(|p| {
-            p.on_rtp_packet_sent(packet, timestamp);
-        });
-        packet.header.csrc_list.iter().for_each(|csrc| {
-            self.participants
-                .entry(*csrc)
-                .and_modify(|p| p.on_rtp_packet_sent(packet, timestamp));
-        });
-
-        self.session_observers
-            .iter_mut()
-            .for_each(|item| item.on_rtp_packet_received(packet, timestamp));
+        self.on_rtp_packet_received_from(packet, None, timestamp);
     }
 
     fn on_rtp_packet_sent(
@@ -138,7 +116,7 @@ impl RtcpContext {
     pub fn new(
         session_bandwidth: u64,
         rtp_clockrate: u64,
-        cname: Option<String>,
+        description: SourceDescription,
         ssrc: u32,
     ) -> Self {
         let mut ctx = RtcpContext {
@@ -147,6 +125,8 @@ impl RtcpContext {
             tn: UNIX_EPOCH,
             pmembers: 0,
             participants: HashMap::new(),
+            departed: HashMap::new(),
+            collisions: HashSet::new(),
             rtcp_bw: 0,
             avg_rtcp_size: 0,
             initial: true,
@@ -155,7 +135,7 @@ impl RtcpContext {
             session_observers: Vec::new(),
         };
 
-        ctx.reset(None, cname, session_bandwidth, rtp_clockrate);
+        ctx.reset(Some(ssrc), description, session_bandwidth, rtp_clockrate);
         ctx
     }
 
@@ -163,10 +143,14 @@ impl RtcpContext {
         self.session_observers.push(observer);
     }
 
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
     pub fn reset(
         &mut self,
         ssrc: Option<u32>,
-        cname: Option<String>,
+        description: SourceDescription,
         session_bandwidth: u64,
         rtp_clockrate: u64,
     ) {
@@ -181,9 +165,11 @@ impl RtcpContext {
         self.initial = true;
         self.about_to_send_bye = false;
         self.participants.clear();
+        self.departed.clear();
+        self.collisions.clear();
         self.participants.insert(
             self.ssrc,
-            RtpParticipant::new(self.ssrc, cname, rtp_clockrate),
+            RtpParticipant::new(self.ssrc, description, rtp_clockrate),
         );
 
         let t_d = self.compute_deterministic_interval_ms();
@@ -206,30 +192,184 @@ impl RtcpContext {
         self.participants.values().filter(|p| !p.bye_sent()).count() as u64
     }
 
-    fn add_participant(&mut self, ssrc: u32, cname: Option<String>) {
-        self.participants.entry(ssrc).or_insert(RtpParticipant::new(
+    /// `source` is the transport address the packet came from, if the transport tells
+    pub fn on_rtp_packet_received_from(
+        &mut self,
+        packet: &rtp_formats::packet::RtpTrivialPacket,
+        source: Option<SocketAddr>,
+        timestamp: SystemTime,
+    ) {
+        // the contributing sources are heard through the mixer
+        let heard = std::iter::once((packet.header.ssrc, source))
+            .chain(packet.csrc_list().iter().map(|csrc| (*csrc, None)));
+        for (ssrc, source) in heard {
+            if self.hear_from(ssrc, source, false, timestamp) {
+                self.participants.entry(ssrc).and_modify(|p| {
+                    p.on_rtp_packet_sent(packet, timestamp);
+                    if ssrc == packet.header.ssrc {
+                        p.on_rtp_packet_arrived(packet, timestamp);
+                    }
+                });
+            }
+        }
+
+        self.session_observers
+            .iter_mut()
+            .for_each(|item| item.on_rtp_packet_received(packet, timestamp));
+    }
+
+    /// `source` is the transport address the packet came from, if the transport tells
+    pub fn on_rtcp_compound_packet_received_from(
+        &mut self,
+        packet: &RtcpCompoundPacket,
+        source: Option<SocketAddr>,
+        timestamp: SystemTime,
+    ) {
+        packet.packets().iter().for_each(|item| {
+            if let RtcpPacket::Bye(bye) = item {
+                bye.ssrc_list.iter().for_each(|ssrc| {
+                    self.on_bye_packet_received(*ssrc, bye.leave_reason.clone(), timestamp)
+                });
+                return;
+            }
+            let round_trip_time = self.round_trip_time(item, timestamp);
+            // the report blocks are about the sources heard by the sender, not from them
+            let heard = match item {
+                RtcpPacket::SourceDescription(_) => item.csrc_list(),
+                _ => item.sender_ssrc().into_iter().collect(),
+            };
+            for ssrc in heard {
+                if !self.hear_from(ssrc, source, true, timestamp) {
+                    continue;
+                }
+                let Some(participant) = self.participants.get_mut(&ssrc) else {
+                    continue;
+                };
+                let described = participant.description().clone();
+                participant.on_rtcp_compound_packet_sent(packet, timestamp);
+                if let Some(round_trip_time) = round_trip_time {
+                    participant.on_round_trip_time(round_trip_time);
+                }
+                if *participant.description() != described {
+                    let description = participant.description().clone();
+                    tracing::info!("participant {} described itself: {:?}", ssrc, description);
+                    self.notify(ParticipantEvent::Described { ssrc, description }, timestamp);
+                }
+                if let Some(round_trip_time) = round_trip_time {
+                    self.notify(
+                        ParticipantEvent::RoundTripMeasured {
+                            ssrc,
+                            round_trip_time,
+                        },
+                        timestamp,
+                    );
+                }
+            }
+        });
+        self.update_avg_rtcp_size(packet.get_packet_bytes_count().to_u64().unwrap());
+
+        self.session_observers
+            .iter_mut()
+            .for_each(|item| item.on_rtcp_compound_packet_received(packet, timestamp));
+    }
+
+    /// from the report block of a sender or receiver report about the packets of this side, RFC 3550 6.4.1
+    fn round_trip_time(&self, item: &RtcpPacket, timestamp: SystemTime) -> Option<Duration> {
+        let blocks = match item {
+            RtcpPacket::SenderReport(report) => &report.report_blocks,
+            RtcpPacket::ReceiverReport(report) => &report.report_blocks,
+            _ => return None,
+        };
+        blocks
+            .iter()
+            .find(|block| block.ssrc == self.ssrc)?
+            .round_trip_time(timestamp)
+    }
+
+    /// adds the participant on its first packet,
+    /// false if the packet is not taken as one of the participant
+    fn hear_from(
+        &mut self,
+        ssrc: u32,
+        source: Option<SocketAddr>,
+        rtcp: bool,
+        timestamp: SystemTime,
+    ) -> bool {
+        if ssrc == self.ssrc {
+            tracing::warn!(
+                "a peer sent packets with the ssrc of this session: {}",
+                ssrc
+            );
+            return false;
+        }
+        if self.departed.contains_key(&ssrc) {
+            return false;
+        }
+        self.add_participant(ssrc, timestamp);
+        let Some(participant) = self.participants.get_mut(&ssrc) else {
+            return false;
+        };
+        match (participant.heard_from(source, rtcp, timestamp), source) {
+            (Err(known_source), Some(source)) => {
+                if self.collisions.insert((ssrc, source)) {
+                    tracing::warn!(
+                        "ssrc collision, packets of ssrc {} arrived from {} while it is known from {}, they are ignored",
+                        ssrc,
+                        source,
+                        known_source
+                    );
+                    self.notify(
+                        ParticipantEvent::SsrcCollision {
+                            ssrc,
+                            known_source,
+                            source,
+                        },
+                        timestamp,
+                    );
+                }
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn notify(&mut self, event: ParticipantEvent, timestamp: SystemTime) {
+        self.session_observers
+            .iter_mut()
+            .for_each(|item| item.on_participant_event(&event, timestamp));
+    }
+
+    fn add_participant(&mut self, ssrc: u32, timestamp: SystemTime) {
+        if self.participants.contains_key(&ssrc) {
+            return;
+        }
+        self.participants.insert(
             ssrc,
-            cname,
-            self.rtp_clockrate,
-        ));
+            RtpParticipant::new_at(ssrc, Default::default(), self.rtp_clockrate, timestamp),
+        );
         self.pmembers = self.members_count();
+        tracing::info!("participant {} joined", ssrc);
+        self.notify(ParticipantEvent::Joined { ssrc }, timestamp);
     }
 
+    /// RFC 3550 A.7, the interval of the rtcp bandwidth shared by the members, not shorter than the minimum
     fn compute_deterministic_interval_ms(&self) -> f64 {
         let c: f64;
         let n: f64;
         let senders = self.senders_count() as f64;
         let members = self.members_count() as f64;
+        // the rtcp bandwidth is in kbps, bits per ms, the packet size in bytes
+        let ms_per_packet = (self.avg_rtcp_size as f64) * 8.0 / (self.rtcp_bw.max(1) as f64);
         if senders / members > 0.25 {
             if self.participants.get(&self.ssrc).unwrap().is_sender() {
-                c = (self.avg_rtcp_size as f64) * 4.0 / (self.rtcp_bw as f64);
+                c = ms_per_packet * 4.0;
                 n = senders;
             } else {
-                c = (self.avg_rtcp_size as f64) * 4.0 / ((self.rtcp_bw as f64) * 3.0);
+                c = ms_per_packet * 4.0 / 3.0;
                 n = members - senders;
             }
         } else {
-            c = (self.avg_rtcp_size as f64) / (self.rtcp_bw as f64);
+            c = ms_per_packet;
             n = members;
         }
 
@@ -238,8 +378,10 @@ impl RtcpContext {
         t_d
     }
 
+    /// randomized by half of the deterministic interval either way,
+    /// divided by e - 3/2 to make up for the timer reconsideration of the other members, RFC 3550 A.7
     fn compute_interval_ms(t_d: f64) -> f64 {
-        uniform_random_f64(0.5 * t_d, 1.5 * t_d)
+        uniform_random_f64(0.5 * t_d, 1.5 * t_d) / (std::f64::consts::E - 1.5)
     }
 
     fn update_avg_rtcp_size(&mut self, packet_size: u64) {
@@ -251,93 +393,138 @@ impl RtcpContext {
         current_timestamp > self.tn
     }
 
-    pub fn check_timeout(&mut self) {
-        let tc = SystemTime::now();
+    /// RFC 3550 6.3.5, senders silent for 2 report intervals are taken as receivers,
+    /// members silent for 5 deterministic report intervals are dropped
+    pub fn check_timeout(&mut self, current_timestamp: SystemTime) {
         let t_d = self.compute_deterministic_interval_ms();
         let t = Self::compute_interval_ms(t_d);
-        let is_sender = self.participants.get(&self.ssrc).unwrap().is_sender();
+        let sender_timeout = Duration::from_millis(t as u64).saturating_mul(2);
+        let member_timeout =
+            Duration::from_millis(t_d as u64).saturating_mul(MEMBER_TIMEOUT_INTERVALS);
+
+        self.departed.retain(|_, left_at| {
+            current_timestamp
+                .duration_since(*left_at)
+                .unwrap_or_default()
+                < BYE_HOLD
+        });
+
+        let self_ssrc = self.ssrc;
+        let mut timed_out = Vec::new();
         self.participants.retain(|ssrc, p| {
-            if ssrc.eq(&self.ssrc) {
+            if *ssrc == self_ssrc {
                 return true;
             }
-            if p.is_sender()
-                && tc
-                    .duration_since(
-                        p.get_latest_packet_sent_timestamp()
-                            .unwrap_or(p.get_joined_timestamp()),
-                    )
-                    .unwrap()
-                    .as_millis()
-                    .gt(&t.to_u128().unwrap().checked_mul(2).unwrap())
-            {
+            let silent_for = current_timestamp
+                .duration_since(p.last_heard_timestamp())
+                .unwrap_or_default();
+            if silent_for > member_timeout {
+                timed_out.push((*ssrc, silent_for));
                 return false;
             }
-
-            const M: u64 = 5;
-            if is_sender
-                && tc
-                    .duration_since(
-                        p.get_latest_packet_sent_timestamp()
-                            .unwrap_or(p.get_joined_timestamp()),
-                    )
-                    .unwrap()
-                    .as_millis()
-                    .gt(&t.to_u128().unwrap().checked_mul(M.into()).unwrap())
-            {
-                return false;
+            if p.is_sender() && silent_for > sender_timeout {
+                p.stop_sending();
             }
-
             true
         });
+        if timed_out.is_empty() {
+            return;
+        }
+
+        for (ssrc, silent_for) in timed_out {
+            tracing::info!(
+                "participant {} timed out after {:?} of silence",
+                ssrc,
+                silent_for
+            );
+            self.collisions.retain(|(collided, _)| *collided != ssrc);
+            self.notify(
+                ParticipantEvent::TimedOut { ssrc, silent_for },
+                current_timestamp,
+            );
+        }
+        self.reverse_reconsideration(current_timestamp);
     }
 
-    fn on_bye_packet_received(&mut self, _ssrc: u32, _reason: Option<String>) {
-        let tc = SystemTime::now();
+    fn on_bye_packet_received(&mut self, ssrc: u32, reason: Option<String>, timestamp: SystemTime) {
+        if ssrc == self.ssrc || self.participants.remove(&ssrc).is_none() {
+            return;
+        }
+        tracing::info!("participant {} left, reason: {:?}", ssrc, reason);
+        self.departed.insert(ssrc, timestamp);
+        self.collisions.retain(|(collided, _)| *collided != ssrc);
+        self.notify(ParticipantEvent::Left { ssrc, reason }, timestamp);
+        self.reverse_reconsideration(timestamp);
+    }
 
+    /// RFC 3550 6.3.4, the report times are pulled in as the members leave
+    fn reverse_reconsideration(&mut self, tc: SystemTime) {
         let members_count = self.members_count();
+        if members_count < self.pmembers {
+            let ratio = members_count.to_f64().unwrap() / self.pmembers.to_f64().unwrap();
+            self.tn = tc
+                .checked_add(
+                    self.tn
+                        .duration_since(tc)
+                        .unwrap_or_default()
+                        .mul_f64(ratio),
+                )
+                .unwrap();
+            self.tp = tc
+                .checked_sub(
+                    tc.duration_since(self.tp)
+                        .unwrap_or_default()
+                        .mul_f64(ratio),
+                )
+                .unwrap_or(self.tp);
+        }
         self.pmembers = members_count;
+    }
 
-        // reverse reconsideration
-        self.tn = tc
-            .checked_add(Duration::from_millis(
-                (self
-                    .tn
-                    .duration_since(tc)
-                    .unwrap_or_default()
-                    .as_millis()
-                    .to_f64()
-                    .unwrap()
-                    * members_count.to_f64().unwrap()
-                    / self.pmembers.to_f64().unwrap())
-                .to_u64()
-                .unwrap(),
-            ))
-            .unwrap();
-        self.tp = tc
-            .checked_sub(Duration::from_millis(
-                (tc.duration_since(self.tp)
-                    .unwrap_or_default()
-                    .as_millis()
-                    .to_f64()
-                    .unwrap()
-                    * members_count.to_f64().unwrap()
-                    / self.pmembers.to_f64().unwrap())
-                .to_u64()
-                .unwrap(),
-            ))
-            .unwrap();
+    /// the participants of the report blocks, the ones rtp packets arrived from
+    fn reported_participants(&self) -> impl Iterator<Item = &RtpParticipant> {
+        self.participants
+            .values()
+            .filter(|p| p.ssrc() != self.ssrc && p.rx_stats().packets_received() > 0)
+            .take(MAX_REPORT_BLOCKS)
     }
 
     fn generate_report_blocks(
         &self,
         current_timestamp: SystemTime,
     ) -> Vec<rtp_formats::rtcp::report_block::ReportBlock> {
-        let count = self.participants.len().min(31);
-        let iter = self.participants.values().take(count);
-        iter.map(|v| v.generate_report_block(current_timestamp))
+        self.reported_participants()
+            .map(|v| v.generate_report_block(current_timestamp))
             .collect()
     }
 
+    /// the observers get the numbers of the report blocks just sent, the next fraction lost is of the packets after them
+    fn on_reception_reported(&mut self, timestamp: SystemTime) {
+        let reported: Vec<_> = self
+            .reported_participants()
+            .map(|p| {
+                (
+                    p.ssrc(),
+                    p.rx_stats().snapshot(timestamp),
+                    p.round_trip_time(),
+                )
+            })
+            .collect();
+        for (ssrc, stats, round_trip_time) in reported {
+            if let Some(participant) = self.participants.get_mut(&ssrc) {
+                participant.on_reported();
+            }
+            self.notify(
+                ParticipantEvent::ReceptionReported {
+                    ssrc,
+                    stats,
+                    round_trip_time,
+                },
+                timestamp,
+            );
+        }
+    }
+
     fn generate_sender_report(
         &self,
         rtp_timestamp: u32,
@@ -357,14 +544,15 @@ impl RtcpContext {
         current_timestamp: SystemTime,
     ) -> RtpSessionResult<RtcpReceiverReport> {
         rtp_formats::rtcp::receiver_report::RtcpReceiverReport::builder()
+            .ssrc(self.ssrc)
             .report_blocks(self.generate_report_blocks(current_timestamp))
             .build()
             .map_err(RtpSessionError::RtpFormatError)
     }
 
+    /// every compound packet carries the sdes of this side, RFC 3550 6.1
     fn generate_sdes(&self) -> RtpSessionResult<RtcpSourceDescriptionPacket> {
-        let cname = self
-            .participants
+        self.participants
             .get(&self.ssrc)
             .unwrap_or_else(|| {
                 panic!(
@@ -372,17 +560,8 @@ impl RtcpContext {
                     self.ssrc
                 )
             })
-            .cname();
-        let builder = RtcpSourceDescriptionPacket::builder();
-        if let Some(cname) = cname {
-            builder
-                .cname(self.ssrc, cname.clone())
-                .unwrap()
-                .build()
-                .map_err(RtpSessionError::RtpFormatError)
-        } else {
-            builder.build().map_err(RtpSessionError::RtpFormatError)
-        }
+            .description()
+            .to_packet(self.ssrc)
     }
 
     pub fn generate_rtcp_compound_packet(
@@ -405,21 +584,14 @@ impl RtcpContext {
             if let Some(first_rtp_sent_timestamp) = first_rtp_sent_timestamp
                 && let Some(first_rtp_sent_timestamp_rtp) = first_rtp_sent_timestamp_rtp
             {
-                let rtp_timestamp = first_rtp_sent_timestamp_rtp
-                    .checked_add(
-                        (current_timestamp
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs_f64()
-                            - first_rtp_sent_timestamp
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs_f64())
-                        .mul(self.rtp_clockrate.to_f64().unwrap())
-                        .to_u32()
-                        .unwrap(),
-                    )
-                    .unwrap();
+                // in the ticks of the clock rate of the packets sent, the rtp timestamps wrap around
+                let elapsed_ticks = current_timestamp
+                    .duration_since(first_rtp_sent_timestamp)
+                    .unwrap_or_default()
+                    .as_nanos()
+                    * u128::from(self.rtp_clockrate)
+                    / 1_000_000_000;
+                let rtp_timestamp = first_rtp_sent_timestamp_rtp.wrapping_add(elapsed_ticks as u32);
                 builder = builder.packet(RtcpPacket::SenderReport(
                     self.generate_sender_report(rtp_timestamp, current_timestamp)?,
                 ));
@@ -442,3 +614,497 @@ impl RtcpContext {
         builder.build().map_err(RtpSessionError::RtpFormatError)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime},
+    };
+
+    use rtp_formats::{
+        header::RtpHeader,
+        packet::RtpTrivialPacket,
+        rtcp::{
+            RtcpPacket,
+            bye::RtcpByePacket,
+            compound_packet::RtcpCompoundPacket,
+            receiver_report::RtcpReceiverReport,
+            report_block::ReportBlock,
+            sdes::{RtcpSourceDescriptionPacket, SDESItemType},
+        },
+    };
+    use tokio_util::bytes::Bytes;
+
+    use super::{RtcpContext, RtpSessionObserver};
+    use crate::{
+        participant_observer::{ParticipantEvent, ParticipantObserver},
+        rtcp_observer::RtcpObserver,
+        rtp_observer::RtpObserver,
+        sdes::{SdesConfig, SourceDescription},
+    };
+
+    #[derive(Default)]
+    struct EventRecorder(Arc<Mutex<Vec<ParticipantEvent>>>);
+
+    impl RtpSessionObserver for EventRecorder {}
+
+    impl RtpObserver for EventRecorder {
+        fn on_rtp_packet_received(&mut self, _packet: &RtpTrivialPacket, _timestamp: SystemTime) {}
+
+        fn on_rtp_packet_sent(&mut self, _packet: &RtpTrivialPacket, _timestamp: SystemTime) {}
+    }
+
+    impl RtcpObserver for EventRecorder {
+        fn on_rtcp_compound_packet_received(
+            &mut self,
+            _packet: &RtcpCompoundPacket,
+            _timestamp: SystemTime,
+        ) {
+        }
+
+        fn on_rtcp_compound_packet_sent(
+            &mut self,
+            _packet: &RtcpCompoundPacket,
+            _timestamp: SystemTime,
+        ) {
+        }
+    }
+
+    impl ParticipantObserver for EventRecorder {
+        fn on_participant_event(&mut self, event: &ParticipantEvent, _timestamp: SystemTime) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn context() -> (RtcpContext, Arc<Mutex<Vec<ParticipantEvent>>>) {
+        context_at(90000)
+    }
+
+    fn context_at(rtp_clockrate: u64) -> (RtcpContext, Arc<Mutex<Vec<ParticipantEvent>>>) {
+        let config = SdesConfig {
+            tool: "yam_server/rtsp".to_owned(),
+            name: Some("yam".to_owned()),
+            email: None,
+        };
+        let mut context = RtcpContext::new(
+            10000,
+            rtp_clockrate,
+            SourceDescription::local("server".to_owned(), &config),
+            1,
+        );
+        let recorder = EventRecorder::default();
+        let events = Arc::clone(&recorder.0);
+        context.with_observer(Box::new(recorder));
+        (context, events)
+    }
+
+    fn rtp(ssrc: u32, sequence_number: u16) -> RtpTrivialPacket {
+        RtpTrivialPacket::new(
+            RtpHeader {
+                payload_type: 96,
+                sequence_number,
+                timestamp: sequence_number as u32 * 3000,
+                ssrc,
+                ..Default::default()
+            },
+            Bytes::from(vec![0; 100]),
+        )
+    }
+
+    fn receiver_report(ssrc: u32, bye: bool) -> RtcpCompoundPacket {
+        let mut builder = RtcpCompoundPacket::builder()
+            .packet(RtcpPacket::ReceiverReport(
+                RtcpReceiverReport::builder().ssrc(ssrc).build().unwrap(),
+            ))
+            .packet(RtcpPacket::SourceDescription(
+                RtcpSourceDescriptionPacket::builder()
+                    .cname(ssrc, format!("client-{}", ssrc))
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            ));
+        if bye {
+            builder = builder.packet(RtcpPacket::Bye(
+                RtcpByePacket::builder()
+                    .ssrc(ssrc)
+                    .reason("teardown".to_owned())
+                    .build()
+                    .unwrap(),
+            ));
+        }
+        builder.build().unwrap()
+    }
+
+    fn source(port: u16) -> Option<SocketAddr> {
+        Some(SocketAddr::from(([192, 168, 1, 2], port)))
+    }
+
+    #[test]
+    fn participants_leave_by_bye_or_by_timeout() {
+        let (mut context, events) = context();
+        let start = SystemTime::now();
+        for sequence_number in 0..3 {
+            let timestamp = start + Duration::from_millis(sequence_number as u64 * 40);
+            context.on_rtp_packet_received_from(
+                &rtp(100, sequence_number),
+                source(5000),
+                timestamp,
+            );
+            context.on_rtp_packet_received_from(
+                &rtp(200, sequence_number),
+                source(6000),
+                timestamp,
+            );
+        }
+        assert!(context.participants.contains_key(&100));
+        assert!(context.participants.contains_key(&200));
+
+        // 100 says bye, 200 goes on with rtcp for a while then goes silent
+        context.on_rtcp_compound_packet_received_from(
+            &receiver_report(100, true),
+            source(5001),
+            start + Duration::from_secs(1),
+        );
+        assert!(!context.participants.contains_key(&100));
+        context.on_rtcp_compound_packet_received_from(
+            &receiver_report(200, false),
+            source(6001),
+            start + Duration::from_secs(10),
+        );
+
+        // a late packet of 100 does not bring it back
+        context.on_rtp_packet_received_from(
+            &rtp(100, 3),
+            source(5000),
+            start + Duration::from_secs(2),
+        );
+        assert!(!context.participants.contains_key(&100));
+
+        // 5 intervals of at least 5s after the last report of 200 are not over yet
+        context.check_timeout(start + Duration::from_secs(20));
+        assert!(context.participants.contains_key(&200));
+        context.check_timeout(start + Duration::from_secs(10 + 60));
+        assert!(!context.participants.contains_key(&200));
+        assert_eq!(context.participants.len(), 1);
+        assert!(context.participants.contains_key(&context.ssrc));
+
+        let described = |ssrc: u32| ParticipantEvent::Described {
+            ssrc,
+            description: SourceDescription {
+                cname: Some(format!("client-{}", ssrc)),
+                ..Default::default()
+            },
+        };
+        let events = events.lock().unwrap();
+        assert_eq!(
+            events[..5],
+            [
+                ParticipantEvent::Joined { ssrc: 100 },
+                ParticipantEvent::Joined { ssrc: 200 },
+                described(100),
+                ParticipantEvent::Left {
+                    ssrc: 100,
+                    reason: Some("teardown".to_owned())
+                },
+                described(200),
+            ]
+        );
+        assert!(matches!(
+            events[5],
+            ParticipantEvent::TimedOut { ssrc: 200, silent_for } if silent_for == Duration::from_secs(60)
+        ));
+        assert_eq!(events.len(), 6);
+    }
+
+    #[test]
+    fn ssrc_collision_is_reported_once() {
+        let (mut context, events) = context();
+        let start = SystemTime::now();
+        context.on_rtp_packet_received_from(&rtp(100, 0), source(5000), start);
+        for sequence_number in 1..4 {
+            context.on_rtp_packet_received_from(
+                &rtp(100, sequence_number),
+                source(7000),
+                start + Duration::from_secs(sequence_number as u64),
+            );
+        }
+        // the packets of the other address don't keep the participant alive
+        assert_eq!(context.participants[&100].last_heard_timestamp(), start);
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            [
+                ParticipantEvent::Joined { ssrc: 100 },
+                ParticipantEvent::SsrcCollision {
+                    ssrc: 100,
+                    known_source: source(5000).unwrap(),
+                    source: source(7000).unwrap(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sdes_is_sent_and_peers_are_described() {
+        let (mut context, events) = context();
+        let start = SystemTime::now();
+        let packet = context
+            .generate_rtcp_compound_packet(start, false, None, vec![])
+            .unwrap();
+        let sdes = packet
+            .packets()
+            .iter()
+            .find_map(|packet| match packet {
+                RtcpPacket::SourceDescription(sdes) => Some(sdes),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(sdes.chunks.len(), 1);
+        let items: Vec<_> = sdes
+            .items_of(context.ssrc)
+            .map(|item| (item.item_type(), item.value()))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                (SDESItemType::CNAME, "server"),
+                (SDESItemType::NAME, "yam"),
+                (SDESItemType::TOOL, "yam_server/rtsp"),
+            ]
+        );
+
+        // the same items again are not news
+        let described = |tool: &str| {
+            RtcpCompoundPacket::builder()
+                .packet(RtcpPacket::ReceiverReport(
+                    RtcpReceiverReport::builder().ssrc(100).build().unwrap(),
+                ))
+                .packet(RtcpPacket::SourceDescription(
+                    RtcpSourceDescriptionPacket::builder()
+                        .cname(100, "player".to_owned())
+                        .unwrap()
+                        .tool(100, tool.to_owned())
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                ))
+                .build()
+                .unwrap()
+        };
+        context.on_rtcp_compound_packet_received_from(&described("VLC"), source(5001), start);
+        context.on_rtcp_compound_packet_received_from(&described("VLC"), source(5001), start);
+        context.on_rtcp_compound_packet_received_from(&described("ffplay"), source(5001), start);
+        assert_eq!(
+            context.participants[&100].description().tool.as_deref(),
+            Some("ffplay")
+        );
+
+        let description = |tool: &str| SourceDescription {
+            cname: Some("player".to_owned()),
+            tool: Some(tool.to_owned()),
+            ..Default::default()
+        };
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            [
+                ParticipantEvent::Joined { ssrc: 100 },
+                ParticipantEvent::Described {
+                    ssrc: 100,
+                    description: description("VLC"),
+                },
+                ParticipantEvent::Described {
+                    ssrc: 100,
+                    description: description("ffplay"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn receiver_reports_carry_the_reception_of_the_peers() {
+        let (mut context, events) = context();
+        let start = SystemTime::now();
+        // 2 of 10 are lost, the packets arrive at the pace of their timestamps, 3000 at 90khz
+        for sequence_number in (0..10).filter(|n| *n != 3 && *n != 7) {
+            context.on_rtp_packet_received_from(
+                &rtp(100, sequence_number),
+                source(5000),
+                start + Duration::from_secs(sequence_number as u64) / 30,
+            );
+        }
+        let now = start + Duration::from_millis(500);
+        let packet = context
+            .generate_rtcp_compound_packet(now, false, None, vec![])
+            .unwrap();
+        let report = packet
+            .packets()
+            .iter()
+            .find_map(|packet| match packet {
+                RtcpPacket::ReceiverReport(report) => Some(report),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(report.sender_ssrc, 1);
+        // nothing about this side itself
+        assert_eq!(report.report_blocks.len(), 1);
+        let block = &report.report_blocks[0];
+        assert_eq!(block.ssrc, 100);
+        assert_eq!(block.fraction_lost, 51.0 / 256.0);
+        assert_eq!(block.cumulative_packet_lost, 2);
+        assert_eq!(block.highest_sequence_number_received, 9);
+        assert_eq!(block.interarrival_jitter, 0);
+
+        context.on_rtcp_compound_packet_sent(&packet, now);
+        let reported = events
+            .lock()
+            .unwrap()
+            .iter()
+            .find_map(|event| match event {
+                ParticipantEvent::ReceptionReported {
+                    ssrc: 100, stats, ..
+                } => Some(stats.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(reported.packets_lost, 2);
+        assert_eq!(reported.fraction_lost, 51);
+        assert_eq!(reported.bitrate_short, 8 * 8 * 112);
+
+        // the next report is about the packets since
+        context.on_rtp_packet_received_from(
+            &rtp(100, 10),
+            source(5000),
+            start + Duration::from_secs(10) / 30,
+        );
+        let packet = context
+            .generate_rtcp_compound_packet(now, false, None, vec![])
+            .unwrap();
+        let Some(RtcpPacket::ReceiverReport(report)) = packet.packets().first() else {
+            panic!("no receiver report in {:?}", packet);
+        };
+        assert_eq!(report.report_blocks[0].fraction_lost, 0.0);
+        assert_eq!(report.report_blocks[0].cumulative_packet_lost, 2);
+    }
+
+    #[test]
+    fn round_trip_time_is_measured_from_the_report_blocks_about_this_side() {
+        let (mut context, events) = context();
+        let sent_at = SystemTime::now();
+        let report_about = |ssrc: u32| {
+            RtcpCompoundPacket::builder()
+                .packet(RtcpPacket::ReceiverReport(
+                    RtcpReceiverReport::builder()
+                        .ssrc(100)
+                        .report_blocks(vec![
+                            ReportBlock::builder()
+                                .ssrc(ssrc)
+                                .last_sr(sent_at)
+                                // the peer held the sender report for 50ms
+                                .delay_since_last_sr(65536 / 20)
+                                .build(),
+                        ])
+                        .build()
+                        .unwrap(),
+                ))
+                .build()
+                .unwrap()
+        };
+        let measured = |events: &Arc<Mutex<Vec<ParticipantEvent>>>| {
+            events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|event| match event {
+                    ParticipantEvent::RoundTripMeasured {
+                        ssrc,
+                        round_trip_time,
+                    } => Some((*ssrc, round_trip_time.as_millis())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // about another source of this session
+        context.on_rtcp_compound_packet_received_from(
+            &report_about(7),
+            source(5001),
+            sent_at + Duration::from_millis(150),
+        );
+        assert!(measured(&events).is_empty());
+
+        context.on_rtcp_compound_packet_received_from(
+            &report_about(1),
+            source(5001),
+            sent_at + Duration::from_millis(150),
+        );
+        assert_eq!(measured(&events), vec![(100, 100)]);
+
+        // the reception reports of the peer carry the latest one
+        context.on_rtp_packet_received_from(&rtp(100, 0), source(5000), sent_at);
+        let now = sent_at + Duration::from_millis(200);
+        let packet = context
+            .generate_rtcp_compound_packet(now, false, None, vec![])
+            .unwrap();
+        context.on_rtcp_compound_packet_sent(&packet, now);
+        let round_trip_time = events
+            .lock()
+            .unwrap()
+            .iter()
+            .find_map(|event| match event {
+                ParticipantEvent::ReceptionReported {
+                    ssrc: 100,
+                    round_trip_time,
+                    ..
+                } => Some(*round_trip_time),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(round_trip_time.map(|rtt| rtt.as_millis()), Some(100));
+    }
+
+    #[test]
+    fn sender_reports_tick_at_the_clock_rate_of_the_session() {
+        // as a camera declaring H264/65536 has its packets sent
+        const CLOCK_RATE: u64 = 65536;
+        // the timestamps wrap around in between the reports
+        const FIRST_RTP_TIMESTAMP: u32 = u32::MAX - 1000;
+        let (mut context, _) = context_at(CLOCK_RATE);
+        let sent_at = SystemTime::now();
+        let packet = RtpTrivialPacket::new(
+            RtpHeader {
+                payload_type: 96,
+                timestamp: FIRST_RTP_TIMESTAMP,
+                ssrc: 1,
+                ..Default::default()
+            },
+            Bytes::from(vec![0; 100]),
+        );
+        context.on_rtp_packet_sent(&packet, sent_at);
+
+        for elapsed_ms in [0, 40, 1000, 2500, 60_000] {
+            let now = sent_at + Duration::from_millis(elapsed_ms);
+            let compound = context
+                .generate_rtcp_compound_packet(now, false, None, vec![])
+                .unwrap();
+            let Some(RtcpPacket::SenderReport(report)) = compound.packets().first() else {
+                panic!("no sender report in {:?}", compound);
+            };
+            let ntp: SystemTime = report.sender_info.ntp_timestamp.into();
+            let ntp_elapsed = ntp.duration_since(sent_at).unwrap_or_default();
+            assert!(
+                ntp_elapsed.abs_diff(Duration::from_millis(elapsed_ms)) < Duration::from_micros(1)
+            );
+            assert_eq!(
+                report
+                    .sender_info
+                    .rtp_timestamp
+                    .wrapping_sub(FIRST_RTP_TIMESTAMP) as u64,
+                elapsed_ms * CLOCK_RATE / 1000
+            );
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::{fs, io, path::Path};
+
+use base64::Engine;
+use rtp_formats::rtcp::sdes::{RtcpSourceDescriptionPacket, SDESItem, SDESItemType};
+use sha2::{Digest, Sha256};
+use utils::random::random_fill;
+
+use crate::errors::RtpSessionResult;
+
+/// the file in the data dir keeping the secret the cnames are derived from
+pub const CNAME_SEED_FILE: &str = "rtcp_cname_seed";
+const CNAME_SEED_BYTES: usize = 16;
+/// 96 bits, as the random identifiers of RFC 7022 4.2
+const CNAME_BYTES: usize = 12;
+
+/// the sdes items sent along with the cname
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdesConfig {
+    pub tool: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// derives the cnames of the streams from a secret of the server instance, RFC 7022 4.2.
+/// the cname of a stream stays the same across restarts and tells nothing about the host
+#[derive(Debug, Clone, Copy)]
+pub struct CnameGenerator {
+    seed: [u8; CNAME_SEED_BYTES],
+}
+
+impl CnameGenerator {
+    /// reads the secret kept in the data dir, a new one is saved there if there is none
+    pub fn load_or_create(data_dir: &Path) -> io::Result<Self> {
+        let path = data_dir.join(CNAME_SEED_FILE);
+        match fs::read(&path) {
+            Ok(seed) => match seed.try_into() {
+                Ok(seed) => return Ok(Self { seed }),
+                Err(seed) => tracing::warn!(
+                    "cname seed file {} has {} bytes instead of {}, a new one is created",
+                    path.display(),
+                    seed.len(),
+                    CNAME_SEED_BYTES
+                ),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let generator = Self::random();
+        fs::create_dir_all(data_dir)?;
+        fs::write(&path, generator.seed)?;
+        tracing::info!("new cname seed saved to {}", path.display());
+        Ok(generator)
+    }
+
+    /// a secret of this process, the cnames change on restart
+    pub fn random() -> Self {
+        let mut seed = [0; CNAME_SEED_BYTES];
+        random_fill(&mut seed);
+        Self { seed }
+    }
+
+    /// shared by all the media of the stream, so that receivers can synchronize them, RFC 3550 6.5.1
+    pub fn cname(&self, stream_key: &str) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.seed)
+            .chain_update(stream_key.as_bytes())
+            .finalize();
+        base64::prelude::BASE64_STANDARD.encode(&digest[..CNAME_BYTES])
+    }
+}
+
+/// the sdes items of a participant, the latest value of each type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceDescription {
+    pub cname: Option<String>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub loc: Option<String>,
+    pub tool: Option<String>,
+    pub note: Option<String>,
+}
+
+impl SourceDescription {
+    /// what this side of a session sends
+    pub fn local(cname: String, config: &SdesConfig) -> Self {
+        Self {
+            cname: Some(cname),
+            name: config.name.clone(),
+            email: config.email.clone(),
+            tool: Some(config.tool.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// takes the items of the ssrc in the packet, true if any of them changed
+    pub fn update(&mut self, sdes: &RtcpSourceDescriptionPacket, ssrc: u32) -> bool {
+        let mut changed = false;
+        for item in sdes.items_of(ssrc) {
+            let field = match item.item_type() {
+                SDESItemType::CNAME => &mut self.cname,
+                SDESItemType::NAME => &mut self.name,
+                SDESItemType::EMAIL => &mut self.email,
+                SDESItemType::PHONE => &mut self.phone,
+                SDESItemType::LOC => &mut self.loc,
+                SDESItemType::TOOL => &mut self.tool,
+                SDESItemType::NOTE => &mut self.note,
+                SDESItemType::PRIV => continue,
+            };
+            if field.as_deref() != Some(item.value()) {
+                *field = Some(item.value().to_owned());
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// a single chunk, cname first
+    pub fn to_packet(&self, ssrc: u32) -> RtpSessionResult<RtcpSourceDescriptionPacket> {
+        let items = [
+            (SDESItemType::CNAME, &self.cname),
+            (SDESItemType::NAME, &self.name),
+            (SDESItemType::EMAIL, &self.email),
+            (SDESItemType::PHONE, &self.phone),
+            (SDESItemType::LOC, &self.loc),
+            (SDESItemType::TOOL, &self.tool),
+            (SDESItemType::NOTE, &self.note),
+        ];
+        let mut builder = RtcpSourceDescriptionPacket::builder();
+        for (item_type, value) in items {
+            if let Some(value) = value {
+                builder = builder.item(ssrc, SDESItem::new(item_type, value.clone())?);
+            }
+        }
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use rtp_formats::rtcp::sdes::{RtcpSourceDescriptionPacket, SDESItemType};
+
+    use super::{CNAME_SEED_FILE, CnameGenerator, SdesConfig, SourceDescription};
+
+    fn data_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rtp-session-sdes-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn cname_is_stable_across_restarts() {
+        let dir = data_dir("restart");
+        let cname = CnameGenerator::load_or_create(&dir)
+            .unwrap()
+            .cname("live/test");
+        // 96 bits in base64
+        assert_eq!(cname.len(), 16);
+        assert!(!cname.contains("live"));
+
+        let restarted = CnameGenerator::load_or_create(&dir).unwrap();
+        assert_eq!(restarted.cname("live/test"), cname);
+        assert_ne!(restarted.cname("live/other"), cname);
+
+        // another instance, or one that lost its data dir
+        let other = CnameGenerator::load_or_create(&data_dir("other")).unwrap();
+        assert_ne!(other.cname("live/test"), cname);
+
+        // a broken seed is replaced
+        std::fs::write(dir.join(CNAME_SEED_FILE), b"short").unwrap();
+        let replaced = CnameGenerator::load_or_create(&dir).unwrap();
+        assert_ne!(replaced.cname("live/test"), cname);
+        assert_eq!(
+            CnameGenerator::load_or_create(&dir)
+                .unwrap()
+                .cname("live/test"),
+            replaced.cname("live/test")
+        );
+    }
+
+    #[test]
+    fn source_description_round_trip() {
+        let config = SdesConfig {
+            tool: "yam_server/rtsp".to_owned(),
+            name: None,
+            email: Some("ops@example.com".to_owned()),
+        };
+        let local = SourceDescription::local("abc".to_owned(), &config);
+        let packet = local.to_packet(7).unwrap();
+        let items: Vec<_> = packet.items_of(7).map(|item| item.item_type()).collect();
+        assert_eq!(
+            items,
+            [SDESItemType::CNAME, SDESItemType::EMAIL, SDESItemType::TOOL]
+        );
+
+        let mut remote = SourceDescription::default();
+        assert!(!remote.update(&packet, 8));
+        assert!(remote.update(&packet, 7));
+        assert_eq!(remote, local);
+        assert!(!remote.update(&packet, 7));
+
+        let renamed = RtcpSourceDescriptionPacket::builder()
+            .cname(7, "abc".to_owned())
+            .unwrap()
+            .name(7, "camera".to_owned())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(remote.update(&renamed, 7));
+        assert_eq!(remote.name.as_deref(), Some("camera"));
+        assert_eq!(remote.tool.as_deref(), Some("yam_server/rtsp"));
+    }
+}
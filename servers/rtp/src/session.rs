@@ -1,31 +1,143 @@
 use crate::{
+    capture::{CaptureIo, CaptureTap, PacketKind},
     errors::{RtpSessionError, RtpSessionResult},
+    fec::{FecMetrics, RtpProtection},
+    pacing::{MAX_PACED_PACKETS, PacingConfig, RtpPacer},
+    retransmission::{
+        RetransmissionConfig, RetransmissionMetrics, RtpRetransmitter, RtxParameters,
+    },
+    rtcp_app::{
+        APP_HANDLER_QUEUE_SIZE, MAX_PENDING_APP_PACKETS, MAX_RTCP_COMPOUND_BYTES, RtcpAppHandler,
+        RtcpAppInbox, RtcpAppMessage, RtcpAppMetrics, RtcpAppName, RtcpAppOutbox, run_app_handlers,
+    },
     rtcp_context::{RtcpContext, RtpSessionObserver},
     rtcp_observer::RtcpObserver,
     rtp_observer::RtpObserver,
+    sdes::SourceDescription,
 };
 use futures::{FutureExt, SinkExt, StreamExt, select};
 use rtp_formats::{
-    packet::{RtpTrivialPacket, framed::RtpTrivialPacketFramed},
-    rtcp::{RtcpPacket, compound_packet::RtcpCompoundPacket, framed::RtcpPacketFramed},
+    header::RtpHeaderExtension,
+    packet::{RtpTrivialPacket, framed::RtpTrivialPacketFramed, rewriter::RtpRewriter},
+    rtcp::{
+        RtcpPacket, compound_packet::RtcpCompoundPacket, feedback::RtcpFeedbackPacket,
+        framed::RtcpPacketFramed,
+    },
 };
 use std::{
+    collections::HashMap,
     io,
     pin::Pin,
     sync::Arc,
-    time::{Duration, SystemTime},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use tokio::sync::{
-    RwLock,
-    mpsc::{self, error::TryRecvError},
+use tokio::{
+    sync::{
+        RwLock,
+        mpsc::{
+            self,
+            error::{SendTimeoutError, TryRecvError},
+        },
+        oneshot,
+    },
+    time::Instant,
 };
 use unified_io::{UnifiedIO, UnifiyStreamed};
+use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
+#[cfg(feature = "srtp")]
+use {crate::srtp::SrtpIo, rtp_formats::profiles::savp::SrtpMasterKey};
+
+/// how long the rtcp loop waits for the packets of the peers before checking the report interval of this side
+const RTCP_FEEDBACK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// how often the participants are checked for timeouts
+const PARTICIPANT_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
 
 pub enum RtpSessionCommand {
     Stop,
     Start,
     Rtp(RtpTrivialPacket),
+    /// a packet of a frame, paced by the decode timestamp of the frame in milliseconds if the session is paced
+    PacedRtp {
+        packet: RtpTrivialPacket,
+        dts: u64,
+        /// the fixed frame rate the stream declares, the frames are paced on its grid
+        nominal_frame_rate: Option<f64>,
+        /// the playback speed the player asked for, 1.0 plays in real time
+        speed: f64,
+    },
     Rtcp(RtcpPacket),
+    /// goes out with the next reports, dropped if too many are waiting
+    App(RtcpAppMessage),
+    /// the stream ended, a rtcp bye with the reason goes out before the session stops
+    Bye {
+        reason: Option<String>,
+    },
+}
+
+/// the frame a paced packet carries
+#[derive(Debug, Clone, Copy)]
+struct PacedFrame {
+    /// the decode timestamp of the frame in milliseconds
+    dts: u64,
+    nominal_frame_rate: Option<f64>,
+    speed: f64,
+}
+
+/// the reason of a bye, and the command thread waiting for it to be sent
+type ByeRequest = (Option<String>, oneshot::Sender<()>);
+
+/// what the rtp thread of a sending session does to a packet before it is sent
+struct RtpSender {
+    rewriter: Option<RtpRewriter>,
+    retransmitter: Option<RtpRetransmitter>,
+    pacer: Option<RtpPacer>,
+    abs_send_time_id: Option<u8>,
+    protection: Vec<Box<dyn RtpProtection>>,
+    fec_metrics: Arc<FecMetrics>,
+}
+
+impl RtpSender {
+    /// protects the packet, stamps the send time and keeps the packet to answer nacks,
+    /// the packets of the protection go right after it
+    async fn send(
+        &mut self,
+        io: &mut UnifiyStreamed<RtpTrivialPacketFramed>,
+        rtcp_context: &RwLock<RtcpContext>,
+        mut packet: RtpTrivialPacket,
+    ) -> RtpSessionResult<()> {
+        if !self.protection.is_empty() {
+            let media_bytes = packet.get_packet_bytes_count();
+            for stage in self.protection.iter_mut() {
+                packet = stage.protect(packet);
+            }
+            self.fec_metrics
+                .on_protected(media_bytes, packet.get_packet_bytes_count());
+        }
+        let now = SystemTime::now();
+        if let Some(id) = self.abs_send_time_id {
+            let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+            packet
+                .header
+                .set_extension(RtpHeaderExtension::abs_send_time(id, since_epoch)?);
+        }
+        rtcp_context.write().await.on_rtp_packet_sent(&packet, now);
+        if let Some(retransmitter) = self.retransmitter.as_mut() {
+            retransmitter.on_packet_sent(&packet);
+        }
+        let packets_after: Vec<_> = self
+            .protection
+            .iter_mut()
+            .flat_map(|stage| stage.packets_after(&packet))
+            .collect();
+        io.send(packet).await?;
+        // neither counted as sent packets nor kept for nacks, as retransmissions
+        for packet in packets_after {
+            self.fec_metrics
+                .on_fec_sent(packet.get_packet_bytes_count());
+            io.send(packet).await?;
+        }
+        Ok(())
+    }
 }
 
 pub struct RtpSession {
@@ -34,12 +146,32 @@ pub struct RtpSession {
     rtp_tx: Option<mpsc::Sender<RtpTrivialPacket>>,
     // rtp and rtcp observer
     rtcp_context: Arc<RwLock<RtcpContext>>,
+    // answers nacks of the peer, sending sessions only
+    retransmitter: Option<RtpRetransmitter>,
+    retransmission_metrics: Arc<RetransmissionMetrics>,
+    // spreads the sent packets by the timestamps of their frames, sending sessions only
+    pacing: Option<PacingConfig>,
+    // the extmap id of abs-send-time, the sent packets are stamped with it if set
+    abs_send_time_id: Option<u8>,
+    // keeps the sent packets continuous across source switches, sending sessions only
+    rewriter: Option<RtpRewriter>,
+    // protect the sent packets against loss in order, sending sessions only
+    protection: Vec<Box<dyn RtpProtection>>,
+    fec_metrics: Arc<FecMetrics>,
+    // records the packets as they are on the wire while a capture runs
+    capture: Option<CaptureTap>,
+    // handle the received rtcp app packets by their names
+    app_handlers: HashMap<RtcpAppName, Box<dyn RtcpAppHandler>>,
+    app_metrics: Arc<RtcpAppMetrics>,
+    // protects the rtp and rtcp packets with srtp if set
+    #[cfg(feature = "srtp")]
+    srtp: Option<SrtpMasterKey>,
 }
 
 impl RtpSession {
     pub fn new(
         ssrc: u32,
-        cname: Option<String>,
+        description: SourceDescription,
         session_bandwidth: u64,
         rtp_clockrate: u64,
         command_rx: mpsc::Receiver<RtpSessionCommand>,
@@ -51,42 +183,177 @@ impl RtpSession {
             rtcp_context: Arc::new(RwLock::new(RtcpContext::new(
                 session_bandwidth,
                 rtp_clockrate,
-                cname,
+                description,
                 ssrc,
             ))),
+            retransmitter: None,
+            retransmission_metrics: Default::default(),
+            pacing: None,
+            abs_send_time_id: None,
+            rewriter: None,
+            protection: Vec::new(),
+            fec_metrics: Default::default(),
+            capture: None,
+            app_handlers: HashMap::new(),
+            app_metrics: Default::default(),
+            #[cfg(feature = "srtp")]
+            srtp: None,
         }
     }
 
+    /// keep the sent packets to retransmit the ones nacked by the peer,
+    /// wrapped in the rtx payload format if rtx is negotiated
+    pub fn with_retransmission(
+        mut self,
+        config: RetransmissionConfig,
+        rtx: Option<RtxParameters>,
+    ) -> Self {
+        self.retransmitter = Some(RtpRetransmitter::new(
+            config,
+            rtx,
+            Arc::clone(&self.retransmission_metrics),
+        ));
+        self
+    }
+
+    /// queue the packets of frames until their decode timestamps are due,
+    /// so a replayed gop cache is not sent at once
+    pub fn with_pacing(mut self, config: PacingConfig) -> Self {
+        self.pacing = Some(config);
+        self
+    }
+
+    /// stamp the packets with abs-send-time when they are actually sent
+    pub fn with_abs_send_time(mut self, id: u8) -> Self {
+        self.abs_send_time_id = Some(id);
+        self
+    }
+
+    /// rewrite the ssrc, sequence numbers and timestamps of the packets to send,
+    /// so the receiver sees one continuous stream even if the packets come from another source
+    pub fn with_rewriter(mut self, rewriter: RtpRewriter) -> Self {
+        self.rewriter = Some(rewriter);
+        self
+    }
+
+    /// add a stage protecting the sent packets against loss, the stages run in the order they are added
+    pub fn with_protection(mut self, protection: Box<dyn RtpProtection>) -> Self {
+        self.protection.push(protection);
+        self
+    }
+
+    /// record the rtp and rtcp packets of both directions to the captures started on the tap,
+    /// srtp protected if the session is
+    pub fn with_capture(mut self, tap: CaptureTap) -> Self {
+        self.capture = Some(tap);
+        self
+    }
+
+    /// hand the received rtcp app packets of the name to the handler, the ones of other names are ignored.
+    /// a handler registered for the same name again replaces the one before
+    pub fn with_app_handler(mut self, name: RtcpAppName, handler: Box<dyn RtcpAppHandler>) -> Self {
+        self.app_handlers.insert(name, handler);
+        self
+    }
+
+    /// protect the sent rtp and rtcp packets and unprotect the received ones with the master key,
+    /// the peer uses the same key for both directions
+    #[cfg(feature = "srtp")]
+    pub fn with_srtp(mut self, master: SrtpMasterKey) -> Self {
+        self.srtp = Some(master);
+        self
+    }
+
+    pub fn retransmission_metrics(&self) -> Arc<RetransmissionMetrics> {
+        Arc::clone(&self.retransmission_metrics)
+    }
+
+    pub fn fec_metrics(&self) -> Arc<FecMetrics> {
+        Arc::clone(&self.fec_metrics)
+    }
+
+    pub fn app_metrics(&self) -> Arc<RtcpAppMetrics> {
+        Arc::clone(&self.app_metrics)
+    }
+
     pub async fn run(
         &mut self,
         send: bool,
         rtp_io: Pin<Box<dyn UnifiedIO>>,
         rtcp_io: Pin<Box<dyn UnifiedIO>>,
     ) -> RtpSessionResult<()> {
+        // next to the wire, below srtp
+        let (rtp_io, rtcp_io) = match &self.capture {
+            Some(tap) => (
+                Box::pin(CaptureIo::new(rtp_io, tap.clone(), PacketKind::Rtp))
+                    as Pin<Box<dyn UnifiedIO>>,
+                Box::pin(CaptureIo::new(rtcp_io, tap.clone(), PacketKind::Rtcp))
+                    as Pin<Box<dyn UnifiedIO>>,
+            ),
+            None => (rtp_io, rtcp_io),
+        };
+        #[cfg(feature = "srtp")]
+        let (rtp_io, rtcp_io) = match &self.srtp {
+            Some(master) => (
+                Box::pin(SrtpIo::rtp(rtp_io, master)) as Pin<Box<dyn UnifiedIO>>,
+                Box::pin(SrtpIo::rtcp(rtcp_io, master)) as Pin<Box<dyn UnifiedIO>>,
+            ),
+            None => (rtp_io, rtcp_io),
+        };
         let (rtp_sender, rtp_receiver) = mpsc::channel(1000);
         let (rtcp_sender, rtcp_receiver) = mpsc::channel(1000);
+        let (nack_sender, nack_receiver) = mpsc::channel(100);
+        let (bye_sender, bye_receiver) = mpsc::channel(1);
+        let (app_sender, app_receiver) = mpsc::channel(MAX_PENDING_APP_PACKETS);
+        let (app_handler_sender, app_handler_receiver) = mpsc::channel(APP_HANDLER_QUEUE_SIZE);
+        let app_handlers = std::mem::take(&mut self.app_handlers);
+        let app_inbox = RtcpAppInbox::new(
+            app_handlers.keys().copied().collect(),
+            app_handler_sender,
+            Arc::clone(&self.app_metrics),
+        );
+        let app_outbox = RtcpAppOutbox::new(Arc::clone(&self.app_metrics));
+        let sender = RtpSender {
+            rewriter: self.rewriter.take().filter(|_| send),
+            retransmitter: self.retransmitter.take().filter(|_| send),
+            pacer: self.pacing.filter(|_| send).map(RtpPacer::new),
+            abs_send_time_id: self.abs_send_time_id,
+            protection: match send {
+                true => std::mem::take(&mut self.protection),
+                false => Vec::new(),
+            },
+            fec_metrics: Arc::clone(&self.fec_metrics),
+        };
+        let nack_sender = sender.retransmitter.as_ref().map(|_| nack_sender);
         select! {
-            result = Self::run_rtp(send, rtp_io, self.rtcp_context.clone(), self.rtp_tx.clone(), rtp_receiver).fuse() => {
+            result = Self::run_rtp(send, rtp_io, self.rtcp_context.clone(), self.rtp_tx.clone(), rtp_receiver, sender, nack_receiver).fuse() => {
                 if let Err(err) = &result {
                     tracing::error!("rtp thread got error: {}", err);
                 }
                 tracing::info!("rtp session is about to exit because rtp thread exited, {:?}", result);
                 result
             }
-            result = Self::run_rtcp(send, rtcp_io, self.rtcp_context.clone(), rtcp_receiver).fuse() => {
+            result = Self::run_rtcp(rtcp_io, self.rtcp_context.clone(), rtcp_receiver, nack_sender, bye_receiver, app_inbox, app_receiver, app_outbox).fuse() => {
                 if let Err(err) = &result {
                     tracing::error!("rtcp thread got error: {}", err);
                 }
                 tracing::info!("rtp session is about to exit because rtcp thread exited, {:?}", result);
                 result
             }
-            result = Self::run_command(self.command_rx.clone(), rtp_sender, rtcp_sender).fuse() => {
+            result = Self::run_command(self.command_rx.clone(), rtp_sender, rtcp_sender, bye_sender, app_sender, self.app_metrics.clone()).fuse() => {
                 if let Err(err) = &result && !matches!(err, RtpSessionError::GracefulExit) {
                     tracing::error!("command thread got error: {}", err);
                 }
                 tracing::info!("rtp session is about to exit because command thread exited, {:?}", result);
                 result
             }
+            result = Self::run_participant_sweep(self.rtcp_context.clone()).fuse() => {
+                result
+            }
+            // ends with the rtcp thread, which ends the session
+            _ = run_app_handlers(app_handler_receiver, app_handlers).fuse() => {
+                Ok(())
+            }
 
         }
     }
@@ -96,31 +363,72 @@ impl RtpSession {
         rtp_io: Pin<Box<dyn UnifiedIO>>,
         rtcp_context: Arc<RwLock<RtcpContext>>,
         rtp_tx: Option<mpsc::Sender<RtpTrivialPacket>>,
-        mut rtp_rx: mpsc::Receiver<RtpTrivialPacket>,
+        mut rtp_rx: mpsc::Receiver<(RtpTrivialPacket, Option<PacedFrame>)>,
+        mut sender: RtpSender,
+        mut nack_rx: mpsc::Receiver<RtcpFeedbackPacket>,
     ) -> RtpSessionResult<()> {
+        let rtp_source = rtp_io.get_peer_addr();
         let mut io = UnifiyStreamed::new(rtp_io, RtpTrivialPacketFramed);
         if send {
             loop {
-                match rtp_rx.recv().await {
-                    None => {
-                        return Err(RtpSessionError::RtpPacketChannelDisconnected);
-                    }
-                    Some(packet) => {
-                        rtcp_context
-                            .write()
-                            .await
-                            .on_rtp_packet_sent(&packet, SystemTime::now());
-                        io.send(packet).await?;
+                let next_due = sender
+                    .pacer
+                    .as_ref()
+                    .and_then(|pacer| pacer.next_due(Instant::now()));
+                // stop taking packets while the pacer is full, so the command channel pushes back
+                let accepting = sender
+                    .pacer
+                    .as_ref()
+                    .is_none_or(|pacer| pacer.len() < MAX_PACED_PACKETS);
+                tokio::select! {
+                    packet = rtp_rx.recv(), if accepting => match packet {
+                        None => {
+                            return Err(RtpSessionError::RtpPacketChannelDisconnected);
+                        }
+                        Some((mut packet, frame)) => {
+                            // rewritten before queued, so the retransmissions are rewritten too
+                            if let Some(rewriter) = sender.rewriter.as_mut() {
+                                rewriter.rewrite(&mut packet.header);
+                            }
+                            match sender.pacer.as_mut() {
+                                Some(pacer) => {
+                                    if let Some(frame) = frame {
+                                        pacer.set_nominal_frame_rate(frame.nominal_frame_rate);
+                                        pacer.set_speed(frame.speed, Instant::now());
+                                    }
+                                    pacer.push(packet, frame.map(|frame| frame.dts))
+                                }
+                                None => sender.send(&mut io, &rtcp_context, packet).await?,
+                            }
+                        }
+                    },
+                    _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {}
+                    Some(nack) = nack_rx.recv(), if sender.retransmitter.is_some() => {
+                        // retransmissions are not counted as sent packets
+                        let packets = sender
+                            .retransmitter
+                            .as_mut()
+                            .map(|retransmitter| retransmitter.on_nack(&nack))
+                            .unwrap_or_default();
+                        for packet in packets {
+                            io.send(packet).await?;
+                        }
                     }
                 }
+                let now = Instant::now();
+                while let Some(packet) = sender.pacer.as_mut().and_then(|pacer| pacer.pop_due(now))
+                {
+                    sender.send(&mut io, &rtcp_context, packet).await?;
+                }
             }
         } else if let Some(rtp_tx) = rtp_tx {
             loop {
                 let packet = Self::receive_rtp(&mut io).await?;
-                rtcp_context
-                    .write()
-                    .await
-                    .on_rtp_packet_received(&packet, SystemTime::now());
+                rtcp_context.write().await.on_rtp_packet_received_from(
+                    &packet,
+                    rtp_source,
+                    SystemTime::now(),
+                );
                 rtp_tx
                     .send_timeout(packet, Duration::from_secs(1))
                     .await
@@ -132,7 +440,7 @@ impl RtpSession {
                         return Err(RtpSessionError::RtpPacketChannelDisconnected);
                     }
                     Err(_) => {}
-                    Ok(packet) => {
+                    Ok((packet, _)) => {
                         io.send(packet).await?;
                     }
                 }
@@ -145,21 +453,53 @@ impl RtpSession {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn run_rtcp(
-        send: bool,
         rtcp_io: Pin<Box<dyn UnifiedIO>>,
         rtcp_context: Arc<RwLock<RtcpContext>>,
         mut rtcp_rx: mpsc::Receiver<RtcpPacket>,
+        nack_tx: Option<mpsc::Sender<RtcpFeedbackPacket>>,
+        mut bye_rx: mpsc::Receiver<ByeRequest>,
+        app_inbox: RtcpAppInbox,
+        mut app_rx: mpsc::Receiver<RtcpAppMessage>,
+        mut app_outbox: RtcpAppOutbox,
     ) -> RtpSessionResult<()> {
+        let rtcp_source = rtcp_io.get_peer_addr();
         let mut io = UnifiyStreamed::new(rtcp_io, RtcpPacketFramed);
         let mut rtcp_buffer = Vec::new();
         loop {
-            if !send {
-                let packet = Self::receive_rtcp(&mut io).await?;
+            // the reports of this side go out on the interval whether the peers report or not
+            let received = match tokio::time::timeout(
+                RTCP_FEEDBACK_POLL_INTERVAL,
+                Self::receive_rtcp(&mut io),
+            )
+            .await
+            {
+                Err(_) => None,
+                Ok(packet) => Some(packet?),
+            };
+            if let Some(packet) = received {
                 rtcp_context
                     .write()
                     .await
-                    .on_rtcp_compound_packet_received(&packet, SystemTime::now());
+                    .on_rtcp_compound_packet_received_from(&packet, rtcp_source, SystemTime::now());
+                app_inbox.on_rtcp_compound_packet_received(&packet);
+                if let Some(nack_tx) = &nack_tx {
+                    packet
+                        .packets()
+                        .iter()
+                        .filter_map(|item| match item {
+                            RtcpPacket::Feedback(feedback) if feedback.is_generic_nack() => {
+                                Some(feedback)
+                            }
+                            _ => None,
+                        })
+                        .for_each(|nack| {
+                            if let Err(err) = nack_tx.try_send(nack.clone()) {
+                                tracing::warn!("drop nack as the rtp thread is busy: {}", err);
+                            }
+                        });
+                }
             }
             match rtcp_rx.try_recv() {
                 Err(TryRecvError::Disconnected) => {
@@ -170,34 +510,81 @@ impl RtpSession {
                     rtcp_buffer.push(packet);
                 }
             }
+            while !app_outbox.is_full()
+                && let Ok(message) = app_rx.try_recv()
+            {
+                app_outbox.push(message);
+            }
 
             let now = SystemTime::now();
-            {
-                rtcp_context.write().await.check_timeout();
-                if !rtcp_context.read().await.timed_out(now) {
-                    continue;
-                }
+            // the bye goes out right away, along with the reports
+            if let Ok((reason, sent)) = bye_rx.try_recv() {
+                let packet = rtcp_context.read().await.generate_rtcp_compound_packet(
+                    now,
+                    true,
+                    reason,
+                    std::mem::take(&mut rtcp_buffer),
+                )?;
+                io.send(packet.clone()).await?;
+                rtcp_context
+                    .write()
+                    .await
+                    .on_rtcp_compound_packet_sent(&packet, now);
+                let _ = sent.send(());
+                continue;
+            }
+            if !rtcp_context.read().await.timed_out(now) {
+                continue;
             }
-            let packet = rtcp_context.read().await.generate_rtcp_compound_packet(
-                now,
-                false,
-                None,
-                rtcp_buffer.clone(),
-            )?;
+            let packet = {
+                let rtcp_context = rtcp_context.read().await;
+                let reports = rtcp_context.generate_rtcp_compound_packet(
+                    now,
+                    false,
+                    None,
+                    rtcp_buffer.clone(),
+                )?;
+                // the app packets fill what the reports leave of the compound packet
+                let apps = app_outbox.take_fitting(
+                    rtcp_context.ssrc(),
+                    MAX_RTCP_COMPOUND_BYTES.saturating_sub(reports.get_packet_bytes_count()),
+                );
+                if apps.is_empty() {
+                    reports
+                } else {
+                    rtcp_buffer.extend(apps);
+                    rtcp_context.generate_rtcp_compound_packet(
+                        now,
+                        false,
+                        None,
+                        rtcp_buffer.clone(),
+                    )?
+                }
+            };
             rtcp_buffer.clear();
             io.send(packet.clone()).await?;
             rtcp_context
                 .write()
                 .await
                 .on_rtcp_compound_packet_sent(&packet, now);
-            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    async fn run_participant_sweep(rtcp_context: Arc<RwLock<RtcpContext>>) -> RtpSessionResult<()> {
+        let mut interval = tokio::time::interval(PARTICIPANT_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            rtcp_context.write().await.check_timeout(SystemTime::now());
         }
     }
 
     async fn run_command(
         command_rx: Arc<RwLock<mpsc::Receiver<RtpSessionCommand>>>,
-        rtp_tx: mpsc::Sender<RtpTrivialPacket>,
+        rtp_tx: mpsc::Sender<(RtpTrivialPacket, Option<PacedFrame>)>,
         rtcp_tx: mpsc::Sender<RtcpPacket>,
+        bye_tx: mpsc::Sender<ByeRequest>,
+        app_tx: mpsc::Sender<RtcpAppMessage>,
+        app_metrics: Arc<RtcpAppMetrics>,
     ) -> RtpSessionResult<()> {
         loop {
             match command_rx.write().await.recv().await {
@@ -215,23 +602,65 @@ impl RtpSession {
                         tracing::info!("rtp session is grecefully stopping");
                         return Err(RtpSessionError::GracefulExit);
                     }
-                    RtpSessionCommand::Rtp(packet) => rtp_tx
-                        .send_timeout(packet, Duration::from_secs(1))
-                        .await
-                        .map_err(|err| {
-                            RtpSessionError::SendRtpPacketToChannelFailed(format!("{}", err))
-                        })?,
+                    RtpSessionCommand::Rtp(packet) => {
+                        Self::forward_rtp(&rtp_tx, packet, None).await?
+                    }
+                    RtpSessionCommand::PacedRtp {
+                        packet,
+                        dts,
+                        nominal_frame_rate,
+                        speed,
+                    } => {
+                        let frame = PacedFrame {
+                            dts,
+                            nominal_frame_rate,
+                            speed,
+                        };
+                        Self::forward_rtp(&rtp_tx, packet, Some(frame)).await?
+                    }
                     RtpSessionCommand::Rtcp(packet) => rtcp_tx
                         .send_timeout(packet, Duration::from_secs(1))
                         .await
-                        .map_err(|err| {
-                            RtpSessionError::SendRtcpPacketToChannelFailed(format!("{}", err))
-                        })?,
+                        .map_err(RtpSessionError::SendRtcpPacketToChannelFailed)?,
+                    RtpSessionCommand::App(message) => {
+                        let name = message.name();
+                        if app_tx.try_send(message).is_err() {
+                            app_metrics.on_send_dropped(name, "too many are waiting");
+                        }
+                    }
+                    RtpSessionCommand::Bye { reason } => {
+                        tracing::info!("rtp session is sending bye, reason: {:?}", reason);
+                        let (sent_tx, sent_rx) = oneshot::channel();
+                        if bye_tx.send((reason, sent_tx)).await.is_err()
+                            || tokio::time::timeout(Duration::from_secs(1), sent_rx)
+                                .await
+                                .is_err()
+                        {
+                            tracing::warn!("rtcp bye is not sent before the rtp session stops");
+                        }
+                        return Err(RtpSessionError::GracefulExit);
+                    }
                 },
             }
         }
     }
 
+    async fn forward_rtp(
+        rtp_tx: &mpsc::Sender<(RtpTrivialPacket, Option<PacedFrame>)>,
+        packet: RtpTrivialPacket,
+        frame: Option<PacedFrame>,
+    ) -> RtpSessionResult<()> {
+        rtp_tx
+            .send_timeout((packet, frame), Duration::from_secs(1))
+            .await
+            .map_err(|err| {
+                RtpSessionError::SendRtpPacketToChannelFailed(match err {
+                    SendTimeoutError::Timeout((packet, _)) => SendTimeoutError::Timeout(packet),
+                    SendTimeoutError::Closed((packet, _)) => SendTimeoutError::Closed(packet),
+                })
+            })
+    }
+
     pub async fn with_observer(self, observer: Box<dyn RtpSessionObserver>) -> Self {
         self.rtcp_context.write().await.with_observer(observer);
         self
@@ -265,3 +694,281 @@ impl RtpSession {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, time::Duration};
+
+    use futures::{SinkExt, StreamExt};
+    use rtp_formats::{
+        header::RtpHeader,
+        packet::RtpTrivialPacket,
+        rtcp::{
+            RtcpPacket, app::RtcpAppPacket, compound_packet::RtcpCompoundPacket,
+            sdes::RtcpSourceDescriptionPacket, sender_report::RtcpSenderReport,
+            simple_ntp::SimpleNtp,
+        },
+    };
+    use tokio::sync::mpsc;
+    use tokio_util::bytes::Bytes;
+    use unified_io::channel::ChannelIo;
+    use utils::traits::{reader::TryReadFrom, writer::WriteTo};
+
+    use super::{RtpSession, RtpSessionCommand};
+    use crate::{
+        errors::RtpSessionError,
+        rtcp_app::{RtcpAppMessage, RtcpAppName},
+        sdes::{SdesConfig, SourceDescription},
+    };
+
+    fn session(
+        ssrc: u32,
+        command_rx: mpsc::Receiver<RtpSessionCommand>,
+        rtp_tx: Option<mpsc::Sender<RtpTrivialPacket>>,
+    ) -> RtpSession {
+        RtpSession::new(
+            ssrc,
+            SourceDescription::local(
+                format!("session-{}", ssrc),
+                &SdesConfig {
+                    tool: "test".to_owned(),
+                    name: None,
+                    email: None,
+                },
+            ),
+            1_000_000,
+            90000,
+            command_rx,
+            rtp_tx,
+        )
+    }
+
+    /// the app packets a session hands to its handler
+    type HandledApps = mpsc::Receiver<(u32, RtcpAppName, Bytes)>;
+
+    fn with_telemetry_handler(session: RtpSession) -> (RtpSession, HandledApps) {
+        let (handled_tx, handled_rx) = mpsc::channel(10);
+        let session = session.with_app_handler(
+            *b"TELE",
+            Box::new(move |ssrc, name, data| {
+                let handled_tx = handled_tx.clone();
+                async move {
+                    let _ = handled_tx.send((ssrc, name, data)).await;
+                }
+            }),
+        );
+        (session, handled_rx)
+    }
+
+    fn to_bytes(packet: &RtcpCompoundPacket) -> Bytes {
+        let mut bytes = Vec::new();
+        packet.write_to(&mut bytes).unwrap();
+        Bytes::from(bytes)
+    }
+
+    #[tokio::test]
+    async fn bye_is_sent_with_the_reason_before_the_session_stops() {
+        let (rtp_server, mut rtp_client) = ChannelIo::pair(100);
+        let (rtcp_server, mut rtcp_client) = ChannelIo::pair(100);
+        let (command_tx, command_rx) = mpsc::channel(100);
+        let mut session = RtpSession::new(
+            0x1234,
+            SourceDescription::local(
+                "live/test".to_owned(),
+                &SdesConfig {
+                    tool: "test".to_owned(),
+                    name: None,
+                    email: None,
+                },
+            ),
+            1_000_000,
+            90000,
+            command_rx,
+            None,
+        );
+        let handle = tokio::spawn(async move {
+            session
+                .run(true, Box::pin(rtp_server), Box::pin(rtcp_server))
+                .await
+        });
+
+        let packet = RtpTrivialPacket::new(
+            RtpHeader {
+                payload_type: 96,
+                sequence_number: 1,
+                timestamp: 3000,
+                ssrc: 0x1234,
+                ..Default::default()
+            },
+            Bytes::from_static(b"payload"),
+        );
+        command_tx
+            .send(RtpSessionCommand::Rtp(packet))
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(1), rtp_client.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        command_tx
+            .send(RtpSessionCommand::Bye {
+                reason: Some("unpublished".to_owned()),
+            })
+            .await
+            .unwrap();
+
+        let bytes = tokio::time::timeout(Duration::from_secs(1), rtcp_client.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let compound = RtcpCompoundPacket::try_read_from(&mut Cursor::new(bytes))
+            .unwrap()
+            .unwrap();
+        assert!(compound.packets().iter().any(|packet| matches!(
+            packet,
+            RtcpPacket::Bye(bye)
+                if bye.ssrc_list == [0x1234] && bye.leave_reason.as_deref() == Some("unpublished")
+        )));
+        assert!(matches!(
+            handle.await.unwrap(),
+            Err(RtpSessionError::GracefulExit)
+        ));
+    }
+
+    #[tokio::test]
+    async fn app_packets_go_out_with_the_reports_to_the_handler_of_their_name() {
+        let (sender_rtp, receiver_rtp) = ChannelIo::pair(100);
+        let (sender_rtcp, receiver_rtcp) = ChannelIo::pair(100);
+        let (sender_command_tx, sender_command_rx) = mpsc::channel(100);
+        let (_receiver_command_tx, receiver_command_rx) = mpsc::channel(100);
+        let (rtp_tx, _rtp_rx) = mpsc::channel(100);
+        let mut sender = session(0x1234, sender_command_rx, None);
+        let sent = sender.app_metrics();
+        let (mut receiver, mut handled) =
+            with_telemetry_handler(session(0x5678, receiver_command_rx, Some(rtp_tx)));
+        let received = receiver.app_metrics();
+        tokio::spawn(async move {
+            sender
+                .run(true, Box::pin(sender_rtp), Box::pin(sender_rtcp))
+                .await
+        });
+        tokio::spawn(async move {
+            receiver
+                .run(false, Box::pin(receiver_rtp), Box::pin(receiver_rtcp))
+                .await
+        });
+
+        sender_command_tx
+            .send(RtpSessionCommand::App(
+                RtcpAppMessage::new(*b"TELE", Bytes::from_static(b"lat:1,lon:2;")).unwrap(),
+            ))
+            .await
+            .unwrap();
+        // the first reports go out within a few seconds
+        let app = tokio::time::timeout(Duration::from_secs(10), handled.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(app, (0x1234, *b"TELE", Bytes::from_static(b"lat:1,lon:2;")));
+        assert_eq!(sent.sent(), 1);
+        assert_eq!(sent.send_dropped(), 0);
+        assert_eq!(received.received(), 1);
+        assert_eq!(received.unhandled(), 0);
+    }
+
+    #[tokio::test]
+    async fn app_packets_of_unknown_names_are_counted_and_the_reports_next_to_them_still_count() {
+        let (rtp_server, mut rtp_client) = ChannelIo::pair(100);
+        let (rtcp_server, mut rtcp_client) = ChannelIo::pair(100);
+        let (_command_tx, command_rx) = mpsc::channel(100);
+        let (rtp_tx, _rtp_rx) = mpsc::channel(100);
+        let (mut session, mut handled) =
+            with_telemetry_handler(session(0x5678, command_rx, Some(rtp_tx)));
+        let metrics = session.app_metrics();
+        tokio::spawn(async move {
+            session
+                .run(false, Box::pin(rtp_server), Box::pin(rtcp_server))
+                .await
+        });
+
+        // a report block is only sent about a peer rtp packets arrived from
+        let rtp = RtpTrivialPacket::new(
+            RtpHeader {
+                payload_type: 96,
+                sequence_number: 1,
+                timestamp: 3000,
+                ssrc: 0x1234,
+                ..Default::default()
+            },
+            Bytes::from_static(b"payload"),
+        );
+        let mut bytes = Vec::new();
+        rtp.write_to(&mut bytes).unwrap();
+        rtp_client.send(Bytes::from(bytes)).await.unwrap();
+        let app = |name: RtcpAppName| {
+            RtcpPacket::App(
+                RtcpAppPacket::builder()
+                    .ssrc(0x1234)
+                    .name(name)
+                    .payload(Bytes::from_static(b"data"))
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let compound = RtcpCompoundPacket::builder()
+            .packet(RtcpPacket::SenderReport(
+                RtcpSenderReport::builder()
+                    .ssrc(0x1234)
+                    .ntp(SimpleNtp::from(0x12345678_9ABCDEF0))
+                    .build()
+                    .unwrap(),
+            ))
+            .packet(RtcpPacket::SourceDescription(
+                RtcpSourceDescriptionPacket::builder()
+                    .cname(0x1234, "camera".to_owned())
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            ))
+            .packet(app(*b"XYZW"))
+            .packet(app(*b"TELE"))
+            .build()
+            .unwrap();
+        rtcp_client.send(to_bytes(&compound)).await.unwrap();
+
+        let handled_app = tokio::time::timeout(Duration::from_secs(1), handled.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(handled_app, (0x1234, *b"TELE", Bytes::from_static(b"data")));
+        assert_eq!(metrics.received(), 2);
+        assert_eq!(metrics.unhandled(), 1);
+        assert_eq!(metrics.dropped(), 0);
+
+        // the receiver report echoes the sender report next to the unknown app packet
+        let block = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let bytes = rtcp_client.next().await.unwrap().unwrap();
+                let compound = RtcpCompoundPacket::try_read_from(&mut Cursor::new(bytes))
+                    .unwrap()
+                    .unwrap();
+                let block = compound.packets().iter().find_map(|packet| match packet {
+                    RtcpPacket::ReceiverReport(report) => report
+                        .report_blocks
+                        .iter()
+                        .find(|block| block.ssrc == 0x1234)
+                        .cloned(),
+                    _ => None,
+                });
+                if let Some(block) = block {
+                    return block;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(u32::from(block.last_sender_report_timestamp), 0x56789ABC);
+    }
+}
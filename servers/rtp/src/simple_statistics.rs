@@ -1,9 +1,15 @@
-use std::fmt;
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+use rtp_formats::packet::RtpTrivialPacket;
 use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
 
 use crate::{
-    rtcp_context::RtpSessionObserver, rtcp_observer::RtcpObserver, rtp_observer::RtpObserver,
+    participant_observer::ParticipantObserver, rtcp_context::RtpSessionObserver,
+    rtcp_observer::RtcpObserver, rtp_observer::RtpObserver,
 };
 
 #[derive(Default)]
@@ -22,6 +28,8 @@ pub struct RtpSessionSimpleStatistics {
 
 impl RtpSessionObserver for RtpSessionSimpleStatistics {}
 
+impl ParticipantObserver for RtpSessionSimpleStatistics {}
+
 impl RtpSessionSimpleStatistics {
     pub fn new() -> Self {
         Default::default()
@@ -123,3 +131,380 @@ impl RtpObserver for RtpSessionSimpleStatistics {
         self.total_rtp_bytes_sent += packet.get_packet_bytes_count() as u64;
     }
 }
+
+/// a jump larger than this is taken as a restart of the sequence numbers, not as a loss, RFC 3550 A.1
+const MAX_DROPOUT: u16 = 3000;
+/// a packet this far behind the highest sequence number is a late or duplicate one
+const MAX_MISORDER: u16 = 100;
+const SEQUENCE_NUMBER_MOD: u32 = 1 << 16;
+
+/// the windows the receive bitrate is averaged over
+pub const BITRATE_SHORT_WINDOW: Duration = Duration::from_secs(1);
+pub const BITRATE_LONG_WINDOW: Duration = Duration::from_secs(10);
+
+/// the reception of the rtp packets of one ssrc, the numbers of the report blocks about it, RFC 3550 6.4.1
+#[derive(Debug, Clone)]
+pub struct RxStats {
+    ssrc: u32,
+    clock_rate: u64,
+    /// None until the first packet
+    base_sequence_number: Option<u32>,
+    max_sequence_number: u16,
+    /// the wraps of the sequence number, shifted by 16 bits
+    cycles: u32,
+    /// the next sequence number of a restart of the sequence numbers
+    bad_sequence_number: Option<u32>,
+    received: u64,
+    expected_prior: u64,
+    received_prior: u64,
+    /// the arrival time and the rtp timestamp of the previous packet
+    last_arrival: Option<(SystemTime, u32)>,
+    /// in rtp timestamp units
+    jitter: f64,
+    /// the arrival time and bytes of the packets of the long window
+    arrivals: VecDeque<(SystemTime, usize)>,
+}
+
+/// the reception of an ssrc at a moment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RxStatsSnapshot {
+    pub ssrc: u32,
+    pub clock_rate: u64,
+    pub packets_received: u64,
+    pub packets_expected: u64,
+    /// negative if duplicates arrived
+    pub packets_lost: i64,
+    /// of the packets expected since the last report, in 1/256
+    pub fraction_lost: u8,
+    pub extended_highest_sequence_number: u32,
+    /// interarrival jitter in rtp timestamp units
+    pub jitter: u32,
+    /// bits per second of the short and the long window
+    pub bitrate_short: u64,
+    pub bitrate_long: u64,
+}
+
+impl RxStatsSnapshot {
+    pub fn jitter_ms(&self) -> f64 {
+        if self.clock_rate == 0 {
+            return 0.0;
+        }
+        self.jitter as f64 * 1000.0 / self.clock_rate as f64
+    }
+
+    /// the highest sequence number received and the wraps before it
+    pub fn highest_sequence_number(&self) -> (u16, u16) {
+        (
+            self.extended_highest_sequence_number as u16,
+            (self.extended_highest_sequence_number >> 16) as u16,
+        )
+    }
+}
+
+impl RxStats {
+    pub fn new(ssrc: u32, clock_rate: u64) -> Self {
+        Self {
+            ssrc,
+            clock_rate,
+            base_sequence_number: None,
+            max_sequence_number: 0,
+            cycles: 0,
+            bad_sequence_number: None,
+            received: 0,
+            expected_prior: 0,
+            received_prior: 0,
+            last_arrival: None,
+            jitter: 0.0,
+            arrivals: VecDeque::new(),
+        }
+    }
+
+    pub fn on_packet(&mut self, packet: &RtpTrivialPacket, arrival: SystemTime) {
+        self.on_arrival(
+            packet.header.sequence_number,
+            packet.header.timestamp,
+            packet.get_packet_bytes_count(),
+            arrival,
+        );
+    }
+
+    pub fn on_arrival(
+        &mut self,
+        sequence_number: u16,
+        rtp_timestamp: u32,
+        bytes: usize,
+        arrival: SystemTime,
+    ) {
+        if !self.update_sequence_number(sequence_number) {
+            return;
+        }
+        self.received += 1;
+        self.update_jitter(rtp_timestamp, arrival);
+        self.arrivals.push_back((arrival, bytes));
+        self.expire_arrivals(arrival);
+    }
+
+    pub fn packets_received(&self) -> u64 {
+        self.received
+    }
+
+    pub fn extended_highest_sequence_number(&self) -> u32 {
+        self.cycles | self.max_sequence_number as u32
+    }
+
+    pub fn packets_expected(&self) -> u64 {
+        match self.base_sequence_number {
+            None => 0,
+            Some(base) => {
+                (self.extended_highest_sequence_number() as u64 + 1).saturating_sub(base as u64)
+            }
+        }
+    }
+
+    pub fn packets_lost(&self) -> i64 {
+        self.packets_expected() as i64 - self.received as i64
+    }
+
+    /// of the packets expected since the last report in 1/256, RFC 3550 A.3
+    pub fn fraction_lost(&self) -> u8 {
+        let expected_interval = self.packets_expected() - self.expected_prior;
+        let received_interval = self.received - self.received_prior;
+        if expected_interval == 0 || received_interval >= expected_interval {
+            return 0;
+        }
+        (((expected_interval - received_interval) << 8) / expected_interval) as u8
+    }
+
+    /// starts the interval of the next fraction lost
+    pub fn on_reported(&mut self) {
+        self.expected_prior = self.packets_expected();
+        self.received_prior = self.received;
+    }
+
+    /// in rtp timestamp units
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// bits per second of the packets arrived in the window before now
+    pub fn bitrate(&self, window: Duration, now: SystemTime) -> u64 {
+        let since = now.checked_sub(window).unwrap_or(UNIX_EPOCH);
+        let bytes: usize = self
+            .arrivals
+            .iter()
+            .rev()
+            .take_while(|(arrival, _)| *arrival > since)
+            .map(|(_, bytes)| bytes)
+            .sum();
+        (bytes as f64 * 8.0 / window.as_secs_f64()) as u64
+    }
+
+    pub fn snapshot(&self, now: SystemTime) -> RxStatsSnapshot {
+        RxStatsSnapshot {
+            ssrc: self.ssrc,
+            clock_rate: self.clock_rate,
+            packets_received: self.received,
+            packets_expected: self.packets_expected(),
+            packets_lost: self.packets_lost(),
+            fraction_lost: self.fraction_lost(),
+            extended_highest_sequence_number: self.extended_highest_sequence_number(),
+            jitter: self.jitter as u32,
+            bitrate_short: self.bitrate(BITRATE_SHORT_WINDOW, now),
+            bitrate_long: self.bitrate(BITRATE_LONG_WINDOW, now),
+        }
+    }
+
+    fn restart(&mut self, sequence_number: u16) {
+        self.base_sequence_number = Some(sequence_number as u32);
+        self.max_sequence_number = sequence_number;
+        self.cycles = 0;
+        self.bad_sequence_number = None;
+        self.received = 0;
+        self.expected_prior = 0;
+        self.received_prior = 0;
+    }
+
+    /// RFC 3550 A.1 without the probation, the ssrc is known from the session setup.
+    /// false if the packet is not counted
+    fn update_sequence_number(&mut self, sequence_number: u16) -> bool {
+        if self.base_sequence_number.is_none() {
+            self.restart(sequence_number);
+            return true;
+        }
+        let delta = sequence_number.wrapping_sub(self.max_sequence_number);
+        if delta < MAX_DROPOUT {
+            if sequence_number < self.max_sequence_number {
+                self.cycles = self.cycles.wrapping_add(SEQUENCE_NUMBER_MOD);
+            }
+            self.max_sequence_number = sequence_number;
+        } else if delta <= u16::MAX - MAX_MISORDER + 1 {
+            if self.bad_sequence_number == Some(sequence_number as u32) {
+                // two sequential packets after a very large jump, the peer restarted its sequence numbers
+                self.restart(sequence_number);
+            } else {
+                self.bad_sequence_number =
+                    Some((sequence_number as u32 + 1) & (SEQUENCE_NUMBER_MOD - 1));
+                return false;
+            }
+        }
+        // duplicates and late packets are counted, so lost ones may go negative
+        true
+    }
+
+    /// RFC 3550 A.8, J += (|D(i-1, i)| - J) / 16
+    fn update_jitter(&mut self, rtp_timestamp: u32, arrival: SystemTime) {
+        if let Some((last_arrival, last_rtp_timestamp)) = self.last_arrival {
+            let arrival_delta = match arrival.duration_since(last_arrival) {
+                Ok(delta) => delta.as_secs_f64(),
+                Err(err) => -err.duration().as_secs_f64(),
+            } * self.clock_rate as f64;
+            let rtp_delta = rtp_timestamp.wrapping_sub(last_rtp_timestamp) as i32 as f64;
+            let d = arrival_delta - rtp_delta;
+            self.jitter += (d.abs() - self.jitter) / 16.0;
+        }
+        self.last_arrival = Some((arrival, rtp_timestamp));
+    }
+
+    fn expire_arrivals(&mut self, now: SystemTime) {
+        let Some(since) = now.checked_sub(BITRATE_LONG_WINDOW) else {
+            return;
+        };
+        while self
+            .arrivals
+            .front()
+            .is_some_and(|(arrival, _)| *arrival <= since)
+        {
+            self.arrivals.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use super::{BITRATE_LONG_WINDOW, BITRATE_SHORT_WINDOW, RxStats};
+
+    /// 20ms packets of 8khz audio, 160 timestamp units each
+    const PACKET_DURATION_MS: u64 = 20;
+    const PACKET_TIMESTAMP: u32 = 160;
+
+    /// packets of the scripted stream arriving at the millisecond,
+    /// the rtp timestamp follows the sequence number counted from the first one of the stream
+    fn arrive(stats: &mut RxStats, start: SystemTime, first: u16, arrivals: &[(u16, u64)]) {
+        for (sequence_number, arrival_ms) in arrivals {
+            stats.on_arrival(
+                *sequence_number,
+                sequence_number.wrapping_sub(first) as u32 * PACKET_TIMESTAMP,
+                100,
+                start + Duration::from_millis(*arrival_ms),
+            );
+        }
+    }
+
+    #[test]
+    fn loss_and_jitter_match_the_rfc() {
+        let mut stats = RxStats::new(1, 8000);
+        let start = SystemTime::now();
+        // 3 and 7 are lost, 5 is 10ms late
+        arrive(
+            &mut stats,
+            start,
+            0,
+            &[
+                (0, 0),
+                (1, 20),
+                (2, 40),
+                (4, 80),
+                (5, 110),
+                (6, 120),
+                (8, 160),
+                (9, 180),
+            ],
+        );
+        // D of 5 is 240 - 160 = 80, of 6 is 80 - 160 = -80, the others are 0
+        let mut jitter = 0.0_f64;
+        for d in [0.0, 0.0, 0.0, 80.0, 80.0, 0.0, 0.0] {
+            jitter += (d - jitter) / 16.0;
+        }
+        assert!((stats.jitter() - jitter).abs() < 1e-6);
+        assert!((stats.jitter() - 8.514404296875).abs() < 1e-6);
+
+        let snapshot = stats.snapshot(start + Duration::from_millis(180));
+        assert_eq!(snapshot.packets_expected, 10);
+        assert_eq!(snapshot.packets_received, 8);
+        assert_eq!(snapshot.packets_lost, 2);
+        // 2 of 10, 2 * 256 / 10
+        assert_eq!(snapshot.fraction_lost, 51);
+        assert_eq!(snapshot.jitter, 8);
+        assert!((snapshot.jitter_ms() - 1.0).abs() < 0.1);
+        assert_eq!(snapshot.highest_sequence_number(), (9, 0));
+
+        // the next report is about the packets after this one
+        stats.on_reported();
+        arrive(&mut stats, start, 0, &[(10, 200), (11, 220), (13, 260)]);
+        let snapshot = stats.snapshot(start + Duration::from_millis(260));
+        assert_eq!(snapshot.packets_lost, 3);
+        // 1 of 4
+        assert_eq!(snapshot.fraction_lost, 64);
+    }
+
+    #[test]
+    fn loss_is_counted_across_sequence_number_wraps() {
+        let mut stats = RxStats::new(1, 8000);
+        let start = SystemTime::now();
+        arrive(
+            &mut stats,
+            start,
+            65533,
+            &[(65533, 0), (65534, 20), (0, 60), (1, 80), (2, 100)],
+        );
+        let snapshot = stats.snapshot(start + Duration::from_millis(100));
+        assert_eq!(snapshot.extended_highest_sequence_number, 65536 + 2);
+        assert_eq!(snapshot.highest_sequence_number(), (2, 1));
+        assert_eq!(snapshot.packets_expected, 6);
+        assert_eq!(snapshot.packets_lost, 1);
+        // a steady stream has no jitter, the timestamps wrap along
+        assert_eq!(snapshot.jitter, 0);
+
+        // a late packet of before the wrap does not add a cycle
+        arrive(&mut stats, start, 65533, &[(65535, 120)]);
+        let snapshot = stats.snapshot(start + Duration::from_millis(120));
+        assert_eq!(snapshot.highest_sequence_number(), (2, 1));
+        assert_eq!(snapshot.packets_lost, 0);
+    }
+
+    #[test]
+    fn sequence_numbers_restart_after_two_sequential_jumps() {
+        let mut stats = RxStats::new(1, 8000);
+        let start = SystemTime::now();
+        arrive(&mut stats, start, 100, &[(100, 0), (101, 20), (30000, 40)]);
+        // a single jump is not counted
+        assert_eq!(stats.packets_received(), 2);
+        assert_eq!(stats.packets_lost(), 0);
+
+        arrive(&mut stats, start, 100, &[(30001, 60), (30002, 80)]);
+        assert_eq!(stats.packets_received(), 2);
+        assert_eq!(stats.packets_expected(), 2);
+    }
+
+    #[test]
+    fn bitrate_is_averaged_over_the_windows() {
+        let mut stats = RxStats::new(1, 8000);
+        let start = SystemTime::now();
+        // 100 bytes each 20ms for 10s is 40kbps
+        let arrivals: Vec<_> = (0..500)
+            .map(|i| (i as u16, i as u64 * PACKET_DURATION_MS))
+            .collect();
+        arrive(&mut stats, start, 0, &arrivals);
+        let now = start + Duration::from_millis(499 * PACKET_DURATION_MS);
+        assert_eq!(stats.bitrate(BITRATE_SHORT_WINDOW, now), 40_000);
+        assert_eq!(stats.bitrate(BITRATE_LONG_WINDOW, now), 40_000);
+
+        // silent for 2s, the short window is empty, the long one has 8s left
+        let now = now + Duration::from_secs(2);
+        let snapshot = stats.snapshot(now);
+        assert_eq!(snapshot.bitrate_short, 0);
+        assert_eq!(snapshot.bitrate_long, 32_000);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use rtp_formats::profiles::savp::{SrtpContext, SrtpMasterKey};
+use tokio_util::bytes::Bytes;
+use unified_io::{UnderlyingIO, UnifiedIO};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SrtpPacketKind {
+    Rtp,
+    Rtcp,
+}
+
+/// protects the packets sent on the inner io and unprotects the ones received from it,
+/// each item of the inner io is one packet, i.e. a datagram or an interleaved frame.
+/// the received packets failing the authentication or replayed are dropped
+#[derive(Debug)]
+pub struct SrtpIo {
+    inner: Pin<Box<dyn UnifiedIO>>,
+    context: SrtpContext,
+    kind: SrtpPacketKind,
+}
+
+impl SrtpIo {
+    pub fn rtp(inner: Pin<Box<dyn UnifiedIO>>, master: &SrtpMasterKey) -> Self {
+        Self {
+            inner,
+            context: SrtpContext::new(master),
+            kind: SrtpPacketKind::Rtp,
+        }
+    }
+
+    pub fn rtcp(inner: Pin<Box<dyn UnifiedIO>>, master: &SrtpMasterKey) -> Self {
+        Self {
+            inner,
+            context: SrtpContext::new(master),
+            kind: SrtpPacketKind::Rtcp,
+        }
+    }
+}
+
+impl UnifiedIO for SrtpIo {
+    fn get_underlying_io_type(&self) -> UnderlyingIO {
+        self.inner.get_underlying_io_type()
+    }
+}
+
+impl Stream for SrtpIo {
+    type Item = Result<Bytes, io::Error>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let packet = match ready!(self.inner.poll_next_unpin(cx)) {
+                Some(Ok(packet)) => packet,
+                other => return Poll::Ready(other),
+            };
+            let kind = self.kind;
+            let unprotected = match kind {
+                SrtpPacketKind::Rtp => self.context.unprotect_rtp(&packet),
+                SrtpPacketKind::Rtcp => self.context.unprotect_rtcp(&packet),
+            };
+            match unprotected {
+                Ok(unprotected) => return Poll::Ready(Some(Ok(Bytes::from(unprotected)))),
+                Err(err) => {
+                    tracing::warn!("drop {:?} packet failed to unprotect: {}", kind, err);
+                }
+            }
+        }
+    }
+}
+
+impl Sink<Bytes> for SrtpIo {
+    type Error = io::Error;
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        let protected = match self.kind {
+            SrtpPacketKind::Rtp => self.context.protect_rtp(&item),
+            SrtpPacketKind::Rtcp => self.context.protect_rtcp(&item),
+        }
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        self.inner.start_send_unpin(Bytes::from(protected))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
+    use rtp_formats::{
+        header::RtpHeader,
+        packet::RtpTrivialPacket,
+        profiles::savp::{SrtpContext, SrtpMasterKey},
+    };
+    use tokio::sync::mpsc;
+    use tokio_util::bytes::Bytes;
+    use unified_io::channel::ChannelIo;
+    use utils::traits::writer::WriteTo;
+
+    use super::SrtpIo;
+    use crate::{
+        sdes::SourceDescription,
+        session::{RtpSession, RtpSessionCommand},
+    };
+
+    fn packet(sequence_number: u16, payload: &'static [u8]) -> RtpTrivialPacket {
+        RtpTrivialPacket::new(
+            RtpHeader {
+                payload_type: 96,
+                sequence_number,
+                timestamp: 3000,
+                ssrc: 0x1234,
+                ..Default::default()
+            },
+            Bytes::from_static(payload),
+        )
+    }
+
+    #[tokio::test]
+    async fn sent_packets_are_protected_end_to_end() {
+        let master = SrtpMasterKey::random();
+        let (rtp_server, mut rtp_client) = ChannelIo::pair(100);
+        let (rtcp_server, _rtcp_client) = ChannelIo::pair(100);
+        let (command_tx, command_rx) = mpsc::channel(100);
+        let mut session = RtpSession::new(
+            0x1234,
+            SourceDescription::default(),
+            1_000_000,
+            90000,
+            command_rx,
+            None,
+        )
+        .with_srtp(master.clone());
+        let handle = tokio::spawn(async move {
+            session
+                .run(true, Box::pin(rtp_server), Box::pin(rtcp_server))
+                .await
+        });
+
+        let mut client = SrtpContext::new(&master);
+        for sequence_number in [65534, 65535, 0] {
+            let sent = packet(sequence_number, b"secret payload");
+            command_tx
+                .send(RtpSessionCommand::Rtp(sent.clone()))
+                .await
+                .unwrap();
+            let received = tokio::time::timeout(Duration::from_secs(1), rtp_client.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert!(
+                !received
+                    .windows(b"secret payload".len())
+                    .any(|window| window == b"secret payload")
+            );
+
+            let unprotected = client.unprotect_rtp(&received).unwrap();
+            let mut expected = Vec::new();
+            sent.write_to(&mut expected).unwrap();
+            assert_eq!(unprotected, expected);
+        }
+
+        command_tx.send(RtpSessionCommand::Stop).await.unwrap();
+        let _ = handle.await;
+    }
+
+    #[tokio::test]
+    async fn packets_failed_to_unprotect_are_dropped() {
+        let master = SrtpMasterKey::random();
+        let (server, mut client) = ChannelIo::pair(100);
+        let mut server = SrtpIo::rtcp(Box::pin(server), &master);
+        let report = Bytes::from_static(b"\x81\xc9\x00\x01\x00\x00\x56\x78");
+
+        let mut sender = SrtpContext::new(&master);
+        let protected = Bytes::from(sender.protect_rtcp(&report).unwrap());
+        client.send(report.clone()).await.unwrap();
+        client.send(protected.clone()).await.unwrap();
+        client.send(protected).await.unwrap();
+        let mut forged = SrtpContext::new(&SrtpMasterKey::random());
+        client
+            .send(Bytes::from(forged.protect_rtcp(&report).unwrap()))
+            .await
+            .unwrap();
+        drop(client);
+
+        assert_eq!(server.next().await.unwrap().unwrap(), report);
+        assert!(server.next().await.is_none());
+    }
+}
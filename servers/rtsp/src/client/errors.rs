@@ -0,0 +1,41 @@
+use rtsp_formats::consts::{methods::RtspMethod, status::RtspStatus};
+use thiserror::Error;
+
+use crate::errors::RtspServerError;
+
+#[derive(Debug, Error)]
+pub enum RtspClientError {
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("rtsp message error: {0}")]
+    RtspMessageError(#[from] rtsp_formats::errors::RtspMessageError),
+    #[error("sdp error: {0}")]
+    SdpError(#[from] sdp_formats::errors::SDPError),
+    #[error("connect failed: {0}")]
+    ConnectFailed(#[from] unified_io::errors::UnifiedIOError),
+    #[error("media error: {0}")]
+    MediaError(#[from] RtspServerError),
+    #[error("{method} answered with {status}")]
+    UnexpectedStatus {
+        method: RtspMethod,
+        status: RtspStatus,
+    },
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("redirected more than {0} times")]
+    TooManyRedirects(usize),
+    #[error("invalid redirect: {0}")]
+    InvalidRedirect(String),
+    #[error("invalid transport: {0}")]
+    InvalidTransport(String),
+    #[error("no media to play: {0}")]
+    NoMedia(String),
+    #[error("{0} is not answered in time")]
+    Timeout(RtspMethod),
+    #[error("{0} is not valid in this state")]
+    InvalidState(RtspMethod),
+    #[error("the connection is closed by the server")]
+    ConnectionClosed,
+}
+
+pub type RtspClientResult<T> = Result<T, RtspClientError>;
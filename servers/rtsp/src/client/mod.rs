@@ -0,0 +1,763 @@
+//! plays the streams of other rtsp servers and cameras, e.g., to pull them in:
+//! OPTIONS, DESCRIBE, SETUP of the medias picked and PLAY, then keeps the session alive
+
+pub mod auth;
+pub mod errors;
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use auth::{RtspAuthenticator, RtspChallenge, RtspCredentials};
+use errors::{RtspClientError, RtspClientResult};
+use futures::{SinkExt, StreamExt, future::BoxFuture};
+use rtp_formats::codec::h264::packet::sequencer::budget::RtpH264BufferConfig;
+use rtsp_formats::{
+    RtspMessage, RtspMessageFramed,
+    consts::{methods::RtspMethod, status::RtspStatus, version::RtspVersion},
+    header::{
+        RtspHeader,
+        session::SessionHeader,
+        transport::{TransportCast, TransportHeader, TransportProtocol},
+    },
+    interleaved::RtspInterleavedPacket,
+    request::RtspRequest,
+    response::RtspResponse,
+    sdp_extension::attribute::RtspSDPControl,
+};
+use sdp_formats::{
+    attributes::SDPAttribute,
+    session::{SDPMediaDescription, SDPMediaType, Sdp},
+};
+use tokio::{net::UdpSocket, sync::mpsc};
+use unified_io::{
+    UnifiedIO, UnifiyStreamed,
+    channel::{ChannelIo, ChannelSender, channel},
+    tcp::TcpIO,
+    udp::UdpIO,
+};
+use url::Url;
+
+use crate::{
+    media_session::RtspMediaSession,
+    payload_type_map::{PayloadTypeMap, RtpPayloadDemuxer},
+};
+
+pub const CLIENT_AGENT: &str = "yam_server/rtsp_client";
+const DEFAULT_RTSP_PORT: u16 = 554;
+/// the session timeout of RFC 7826 Section 18.49 if the server does not tell
+const DEFAULT_SESSION_TIMEOUT_SECS: u64 = 60;
+/// interleaved packets a media has not read yet, the later ones are dropped
+const INTERLEAVED_BUFFER_PACKETS: usize = 1024;
+
+/// the connection or the rtp and rtcp of a media
+pub type BoxedIO = Pin<Box<dyn UnifiedIO>>;
+
+/// how the rtp and rtcp of the medias come to the client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RtspClientTransport {
+    /// to udp ports of the client
+    #[default]
+    Udp,
+    /// interleaved on the rtsp connection
+    Interleaved,
+}
+
+#[derive(Debug, Clone)]
+pub struct RtspClientConfig {
+    pub transport: RtspClientTransport,
+    /// answers the 401s, the user info of the url is taken if not set
+    pub credentials: Option<RtspCredentials>,
+    /// the medias of these types are set up, all the medias described if empty
+    pub media_types: Vec<SDPMediaType>,
+    /// the Location of 3xx answers is followed this many times at most
+    pub max_redirects: usize,
+    /// how long a request waits for its answer
+    pub request_timeout: Duration,
+    /// the version of the requests, RTSP/1.0 as most cameras speak
+    pub version: RtspVersion,
+}
+
+impl Default for RtspClientConfig {
+    fn default() -> Self {
+        Self {
+            transport: RtspClientTransport::default(),
+            credentials: None,
+            media_types: Vec::new(),
+            max_redirects: 3,
+            request_timeout: Duration::from_secs(10),
+            version: RtspVersion::V1,
+        }
+    }
+}
+
+/// opens the rtsp connections of the clients, a new one is opened to follow a redirect
+pub trait RtspConnector: Send + Sync {
+    fn connect<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, RtspClientResult<BoxedIO>>;
+}
+
+/// tcp to the host and port of the url, 554 if it has none
+#[derive(Debug, Default)]
+pub struct TcpRtspConnector;
+
+impl RtspConnector for TcpRtspConnector {
+    fn connect<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, RtspClientResult<BoxedIO>> {
+        Box::pin(async move {
+            if url.scheme() != "rtsp" {
+                return Err(RtspClientError::IoError(std::io::Error::other(format!(
+                    "unsupported scheme: {}",
+                    url
+                ))));
+            }
+            let host = url.host_str().unwrap_or_default();
+            let port = url.port().unwrap_or(DEFAULT_RTSP_PORT);
+            let stream = tokio::net::TcpStream::connect((host, port)).await?;
+            let io: BoxedIO = Box::pin(TcpIO::new(stream));
+            Ok(io)
+        })
+    }
+}
+
+/// a media the client set up, its rtp and rtcp come through the ios
+#[derive(Debug)]
+pub struct RtspClientMedia {
+    pub media: SDPMediaDescription,
+    /// the control url the media is set up with
+    pub uri: Url,
+    /// as the server answered
+    pub transport: TransportHeader,
+    pub rtp_io: BoxedIO,
+    pub rtcp_io: BoxedIO,
+}
+
+impl RtspClientMedia {
+    /// the sequencers of the payload types of the media, as a published media gets them on the server
+    pub fn demuxer(
+        &self,
+        max_access_unit_size: usize,
+        h264_buffer: RtpH264BufferConfig,
+    ) -> RtspClientResult<RtpPayloadDemuxer> {
+        let payload_types = PayloadTypeMap::from_media(&self.media);
+        let preferred = payload_types
+            .preferred(&self.media.media_line.media_type)
+            .ok_or_else(|| {
+                RtspClientError::NoMedia(format!(
+                    "no supported format in {}",
+                    self.media.media_line.media_type
+                ))
+            })?
+            .payload_type();
+        let (demuxer, _) = RtspMediaSession::create_rtp_demuxer(
+            &payload_types,
+            preferred,
+            max_access_unit_size,
+            h264_buffer,
+            true,
+        )?;
+        Ok(demuxer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtspClientState {
+    Init,
+    /// some medias are set up
+    Ready,
+    Playing,
+}
+
+pub struct RtspClient {
+    connector: Arc<dyn RtspConnector>,
+    config: RtspClientConfig,
+    /// the presentation, without the user info
+    url: Url,
+    io: UnifiyStreamed<RtspMessageFramed>,
+    /// the address of the server the udp medias come from
+    server_ip: Option<IpAddr>,
+    cseq: u32,
+    credentials: Option<RtspCredentials>,
+    authenticator: Option<RtspAuthenticator>,
+    /// the methods in Public of the answer to OPTIONS
+    public: Vec<String>,
+    sdp: Option<Sdp>,
+    content_base: Option<Url>,
+    session: Option<SessionHeader>,
+    state: RtspClientState,
+    /// the interleaved channels of the medias, their packets go to the ios of the medias
+    interleaved: HashMap<u8, ChannelSender>,
+    /// what the medias send on their interleaved channels, e.g., rtcp receiver reports
+    outgoing_tx: mpsc::Sender<RtspInterleavedPacket>,
+    outgoing_rx: mpsc::Receiver<RtspInterleavedPacket>,
+}
+
+impl std::fmt::Debug for RtspClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RtspClient")
+            .field("url", &self.url)
+            .field("cseq", &self.cseq)
+            .field("session", &self.session)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+fn without_user_info(url: &Url) -> Url {
+    let mut url = url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url
+}
+
+/// the 3xx answers with a Location to follow
+fn is_redirect(status: RtspStatus) -> bool {
+    matches!(
+        status,
+        RtspStatus::MovedPermanently | RtspStatus::Found | RtspStatus::SeeOther
+    )
+}
+
+fn same_server(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme()
+        && a.host_str() == b.host_str()
+        && a.port_or_known_default() == b.port_or_known_default()
+}
+
+/// where a control attribute points, relative ones are under the base
+fn control_url(base: &Url, attributes: &[SDPAttribute]) -> RtspClientResult<Url> {
+    let control = attributes.iter().find_map(|attr| match attr {
+        SDPAttribute::Trivial(attr) if attr.name == "control" => {
+            RtspSDPControl::try_from(attr).ok()
+        }
+        _ => None,
+    });
+    match control {
+        Some(RtspSDPControl::Absolute(url)) => Ok(url),
+        Some(RtspSDPControl::Relative(path)) => Ok(Url::parse(&format!(
+            "{}/{}",
+            base.as_str().trim_end_matches('/'),
+            path.trim_start_matches('/')
+        ))
+        .map_err(rtsp_formats::errors::RtspMessageError::from)?),
+        Some(RtspSDPControl::Asterisk) | None => Ok(base.clone()),
+    }
+}
+
+impl RtspClient {
+    pub async fn connect(
+        url: Url,
+        config: RtspClientConfig,
+        connector: Arc<dyn RtspConnector>,
+    ) -> RtspClientResult<Self> {
+        let credentials = config
+            .credentials
+            .clone()
+            .or_else(|| RtspCredentials::from_url(&url));
+        let url = without_user_info(&url);
+        let io = connector.connect(&url).await?;
+        let (outgoing_tx, outgoing_rx) = mpsc::channel(INTERLEAVED_BUFFER_PACKETS);
+        Ok(Self {
+            connector,
+            config,
+            server_ip: io.get_peer_addr().map(|addr| addr.ip()),
+            io: UnifiyStreamed::new_byte_stream(io, RtspMessageFramed::default()),
+            url,
+            cseq: 0,
+            credentials,
+            authenticator: None,
+            public: Vec::new(),
+            sdp: None,
+            content_base: None,
+            session: None,
+            state: RtspClientState::Init,
+            interleaved: HashMap::new(),
+            outgoing_tx,
+            outgoing_rx,
+        })
+    }
+
+    /// the presentation played, the last one redirected to
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn state(&self) -> RtspClientState {
+        self.state
+    }
+
+    pub fn session(&self) -> Option<&SessionHeader> {
+        self.session.as_ref()
+    }
+
+    pub fn sdp(&self) -> Option<&Sdp> {
+        self.sdp.as_ref()
+    }
+
+    /// half the session timeout
+    pub fn keepalive_interval(&self) -> Duration {
+        let timeout = self
+            .session
+            .as_ref()
+            .and_then(|session| session.timeout)
+            .unwrap_or(DEFAULT_SESSION_TIMEOUT_SECS);
+        Duration::from_millis(timeout * 500).max(Duration::from_secs(1))
+    }
+
+    /// describes the presentation, sets up the medias picked by the config and plays them.
+    /// the medias come as long as `keep_alive` runs
+    pub async fn start(&mut self) -> RtspClientResult<Vec<RtspClientMedia>> {
+        let sdp = self.describe().await?;
+        let base = self
+            .content_base
+            .clone()
+            .unwrap_or_else(|| self.url.clone());
+        let mut medias = Vec::new();
+        for media in &sdp.media_description {
+            if !self.config.media_types.is_empty()
+                && !self
+                    .config
+                    .media_types
+                    .contains(&media.media_line.media_type)
+            {
+                continue;
+            }
+            medias.push(self.setup(media, &base).await?);
+        }
+        if medias.is_empty() {
+            return Err(RtspClientError::NoMedia(format!(
+                "none of {:?} is described by {}",
+                self.config.media_types, self.url
+            )));
+        }
+        self.play(&control_url(&base, &sdp.attributes)?).await?;
+        Ok(medias)
+    }
+
+    /// OPTIONS and DESCRIBE, following the redirects
+    pub async fn describe(&mut self) -> RtspClientResult<Sdp> {
+        let mut redirects = 0;
+        loop {
+            let options = self
+                .request(RtspMethod::Options, self.url.clone(), vec![])
+                .await?;
+            let response = if is_redirect(options.status()) {
+                options
+            } else {
+                if options.status() == RtspStatus::OK {
+                    self.public = options
+                        .headers()
+                        .get_unique(RtspHeader::Public)
+                        .map(|public| public.split(',').map(|m| m.trim().to_owned()).collect())
+                        .unwrap_or_default();
+                }
+                self.request(
+                    RtspMethod::Describe,
+                    self.url.clone(),
+                    vec![(RtspHeader::Accept, "application/sdp".to_owned())],
+                )
+                .await?
+            };
+            if is_redirect(response.status()) {
+                if redirects == self.config.max_redirects {
+                    return Err(RtspClientError::TooManyRedirects(redirects));
+                }
+                redirects += 1;
+                self.redirect(&response).await?;
+                continue;
+            }
+            if response.status() != RtspStatus::OK {
+                return Err(RtspClientError::UnexpectedStatus {
+                    method: RtspMethod::Describe,
+                    status: response.status(),
+                });
+            }
+            let sdp: Sdp = response.body().as_deref().unwrap_or_default().parse()?;
+            let headers = response.headers();
+            self.content_base = headers
+                .get_unique(RtspHeader::ContentBase)
+                .or_else(|| headers.get_unique(RtspHeader::ContentLocation))
+                .and_then(|base| self.url.join(base.trim()).ok());
+            self.sdp = Some(sdp.clone());
+            return Ok(sdp);
+        }
+    }
+
+    /// to the Location of a 3xx, on a new connection if it is on another server
+    async fn redirect(&mut self, response: &RtspResponse) -> RtspClientResult<()> {
+        let location = response
+            .headers()
+            .get_unique(RtspHeader::Location)
+            .ok_or_else(|| {
+                RtspClientError::InvalidRedirect(format!("{} without Location", response.status()))
+            })?;
+        let location = self
+            .url
+            .join(location.trim())
+            .map_err(|err| RtspClientError::InvalidRedirect(format!("{}: {}", location, err)))?;
+        tracing::info!("redirected from {} to {}", self.url, location);
+        if self.credentials.is_none() {
+            self.credentials = RtspCredentials::from_url(&location);
+        }
+        let location = without_user_info(&location);
+        if !same_server(&self.url, &location) {
+            let io = self.connector.connect(&location).await?;
+            self.server_ip = io.get_peer_addr().map(|addr| addr.ip());
+            self.io = UnifiyStreamed::new_byte_stream(io, RtspMessageFramed::default());
+            self.authenticator = None;
+        }
+        self.url = location;
+        Ok(())
+    }
+
+    /// binds the rtp port and the rtcp one right after it, as most servers expect
+    async fn bind_udp_pair() -> RtspClientResult<(UdpSocket, UdpSocket)> {
+        let mut last_err = None;
+        for _ in 0..16 {
+            let rtp = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+            let rtp_port = rtp.local_addr()?.port();
+            if rtp_port % 2 != 0 || rtp_port == u16::MAX {
+                continue;
+            }
+            match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, rtp_port + 1)).await {
+                Ok(rtcp) => return Ok((rtp, rtcp)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(RtspClientError::IoError(last_err.unwrap_or_else(|| {
+            std::io::Error::other("no even udp port is free")
+        })))
+    }
+
+    /// the ios of a media interleaved on the channel, what the media sends goes out on the connection
+    fn interleaved_io(&mut self, channel_id: u8) -> BoxedIO {
+        let (incoming_tx, incoming_rx) = channel(INTERLEAVED_BUFFER_PACKETS);
+        let (media_tx, mut media_rx) = channel(INTERLEAVED_BUFFER_PACKETS);
+        self.interleaved.insert(channel_id, incoming_tx);
+        let outgoing_tx = self.outgoing_tx.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(payload)) = media_rx.next().await {
+                let packet = RtspInterleavedPacket {
+                    channel_id,
+                    payload,
+                };
+                if outgoing_tx.send(packet).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Box::pin(ChannelIo::new(incoming_rx, media_tx))
+    }
+
+    pub async fn setup(
+        &mut self,
+        media: &SDPMediaDescription,
+        base: &Url,
+    ) -> RtspClientResult<RtspClientMedia> {
+        let uri = control_url(base, &media.attributes)?;
+        let mut transport = TransportHeader {
+            cast: Some(TransportCast::Unicast),
+            ..Default::default()
+        };
+        let udp_sockets = match self.config.transport {
+            RtspClientTransport::Udp => {
+                let (rtp, rtcp) = Self::bind_udp_pair().await?;
+                transport.profile = Some(TransportProtocol::RtpAvpUdp);
+                transport.client_port = Some((rtp.local_addr()?.port(), rtcp.local_addr()?.port()));
+                Some((rtp, rtcp))
+            }
+            RtspClientTransport::Interleaved => {
+                let channel = (0..=u8::MAX - 1)
+                    .step_by(2)
+                    .find(|channel| !self.interleaved.contains_key(channel))
+                    .ok_or_else(|| {
+                        RtspClientError::InvalidTransport("no interleaved channel left".to_owned())
+                    })?;
+                transport.profile = Some(TransportProtocol::RtpAvpTcp);
+                transport.interleaved = Some((channel, channel + 1));
+                None
+            }
+        };
+        let response = self
+            .request(
+                RtspMethod::Setup,
+                uri.clone(),
+                vec![(RtspHeader::Transport, transport.to_string())],
+            )
+            .await?;
+        if response.status() != RtspStatus::OK {
+            return Err(RtspClientError::UnexpectedStatus {
+                method: RtspMethod::Setup,
+                status: response.status(),
+            });
+        }
+        let answered = response.headers().transport().ok_or_else(|| {
+            RtspClientError::InvalidTransport(format!("SETUP of {} answered no Transport", uri))
+        })?;
+        if self.session.is_none() {
+            self.session = response.headers().session();
+        }
+
+        let (rtp_io, rtcp_io): (BoxedIO, BoxedIO) = match udp_sockets {
+            Some((rtp, rtcp)) => {
+                let (server_rtp_port, server_rtcp_port) =
+                    answered.server_port.ok_or_else(|| {
+                        RtspClientError::InvalidTransport(format!(
+                            "no server_port answered: {}",
+                            answered
+                        ))
+                    })?;
+                let server_ip = self.server_ip().await?;
+                (
+                    Box::pin(
+                        UdpIO::with_socket(rtp, SocketAddr::new(server_ip, server_rtp_port))
+                            .await?,
+                    ),
+                    Box::pin(
+                        UdpIO::with_socket(rtcp, SocketAddr::new(server_ip, server_rtcp_port))
+                            .await?,
+                    ),
+                )
+            }
+            None => {
+                // the server may pick other channels
+                let (rtp_channel, rtcp_channel) = answered
+                    .interleaved
+                    .or(transport.interleaved)
+                    .unwrap_or_default();
+                (
+                    self.interleaved_io(rtp_channel),
+                    self.interleaved_io(rtcp_channel),
+                )
+            }
+        };
+        self.state = RtspClientState::Ready;
+        tracing::info!("{} is set up, transport: {}", uri, answered);
+        Ok(RtspClientMedia {
+            media: media.clone(),
+            uri,
+            transport: answered,
+            rtp_io,
+            rtcp_io,
+        })
+    }
+
+    /// the peer of the connection, or the host of the url if it is not a socket
+    async fn server_ip(&self) -> RtspClientResult<IpAddr> {
+        if let Some(ip) = self.server_ip {
+            return Ok(ip);
+        }
+        let host = self.url.host_str().unwrap_or_default();
+        let port = self.url.port().unwrap_or(DEFAULT_RTSP_PORT);
+        tokio::net::lookup_host((host, port))
+            .await?
+            .next()
+            .map(|addr| addr.ip())
+            .ok_or_else(|| {
+                RtspClientError::IoError(std::io::Error::other(format!("{} is not resolved", host)))
+            })
+    }
+
+    /// plays the medias set up from the start, `uri` is the aggregate control of the presentation
+    pub async fn play(&mut self, uri: &Url) -> RtspClientResult<RtspResponse> {
+        if self.state == RtspClientState::Init {
+            return Err(RtspClientError::InvalidState(RtspMethod::Play));
+        }
+        let response = self
+            .request(
+                RtspMethod::Play,
+                uri.clone(),
+                vec![(RtspHeader::Range, "npt=0-".to_owned())],
+            )
+            .await?;
+        if response.status() != RtspStatus::OK {
+            return Err(RtspClientError::UnexpectedStatus {
+                method: RtspMethod::Play,
+                status: response.status(),
+            });
+        }
+        self.state = RtspClientState::Playing;
+        Ok(response)
+    }
+
+    /// ends the session, the connection stays open
+    pub async fn teardown(&mut self) -> RtspClientResult<()> {
+        if self.session.is_none() {
+            return Ok(());
+        }
+        let uri = self
+            .content_base
+            .clone()
+            .unwrap_or_else(|| self.url.clone());
+        let response = self.request(RtspMethod::TearDown, uri, vec![]).await;
+        self.session = None;
+        self.state = RtspClientState::Init;
+        self.interleaved.clear();
+        response.map(|_| ())
+    }
+
+    /// reads the connection for the interleaved packets of the medias and sends the keepalives
+    /// until the connection is closed. it may be dropped any time, e.g., for `teardown`
+    pub async fn keep_alive(&mut self) -> RtspClientResult<()> {
+        let interval = self.keepalive_interval();
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        // GET_PARAMETER without a body, OPTIONS for the servers not listing it
+        let method = if self.public.is_empty()
+            || self
+                .public
+                .iter()
+                .any(|method| method.eq_ignore_ascii_case("GET_PARAMETER"))
+        {
+            RtspMethod::GetParameter
+        } else {
+            RtspMethod::Options
+        };
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let uri = self.content_base.clone().unwrap_or_else(|| self.url.clone());
+                    self.send_request(method, uri, vec![]).await?;
+                }
+                Some(packet) = self.outgoing_rx.recv() => {
+                    self.io.send(RtspMessage::Interleaved(packet)).await?;
+                }
+                message = self.io.next() => match message {
+                    Some(Ok(RtspMessage::Response(response))) => {
+                        if response.status() != RtspStatus::OK {
+                            tracing::warn!("keepalive of {} is answered with {}", self.url, response.status());
+                        }
+                    }
+                    Some(Ok(message)) => self.on_message(message).await?,
+                    Some(Err(err)) => return Err(err.into()),
+                    None => return Err(RtspClientError::ConnectionClosed),
+                },
+            }
+        }
+    }
+
+    /// the interleaved packets go to their medias, the requests of the server are answered
+    async fn on_message(&mut self, message: RtspMessage) -> RtspClientResult<()> {
+        match message {
+            RtspMessage::Interleaved(packet) => {
+                let Some(sender) = self.interleaved.get(&packet.channel_id) else {
+                    tracing::trace!("drop packet of unknown channel {}", packet.channel_id);
+                    return Ok(());
+                };
+                if sender.try_send(packet.payload).is_err() {
+                    tracing::warn!(
+                        "media of channel {} is behind, drop a packet",
+                        packet.channel_id
+                    );
+                }
+            }
+            RtspMessage::Request(request) => {
+                tracing::info!("server asks {}", request.method());
+                let status = match request.method() {
+                    RtspMethod::GetParameter
+                    | RtspMethod::SetParameter
+                    | RtspMethod::PlayNotify => RtspStatus::OK,
+                    _ => RtspStatus::NotImplemented,
+                };
+                let mut response = RtspResponse::builder().status(status).build()?;
+                response.set_version(request.version().clone());
+                if let Some(cseq) = request.headers().cseq() {
+                    response
+                        .headers_mut()
+                        .set(RtspHeader::CSeq, cseq.to_string());
+                }
+                self.io.send(RtspMessage::Response(response)).await?;
+            }
+            RtspMessage::Response(response) => {
+                tracing::debug!("drop stale response: {}", response);
+            }
+        }
+        Ok(())
+    }
+
+    /// sends the request with the CSeq, the session and the credentials, returns its CSeq
+    async fn send_request(
+        &mut self,
+        method: RtspMethod,
+        uri: Url,
+        headers: Vec<(RtspHeader, String)>,
+    ) -> RtspClientResult<u32> {
+        self.cseq += 1;
+        let mut builder = RtspRequest::builder()
+            .method(method)
+            .version(self.config.version.clone())
+            .header(RtspHeader::CSeq, self.cseq.to_string())
+            .header(RtspHeader::UserAgent, CLIENT_AGENT)
+            .headers(headers);
+        if let Some(session) = &self.session {
+            builder = builder.header(RtspHeader::Session, session.id.clone());
+        }
+        if let Some(authenticator) = &mut self.authenticator {
+            builder = builder.header(
+                RtspHeader::Authorization,
+                authenticator.authorization(method, &uri),
+            );
+        }
+        let request = builder.uri(uri).build()?;
+        tracing::debug!("send request: {}", request);
+        self.io.send(RtspMessage::Request(request)).await?;
+        Ok(self.cseq)
+    }
+
+    async fn wait_response(&mut self, cseq: u32) -> RtspClientResult<RtspResponse> {
+        loop {
+            match self.io.next().await {
+                Some(Ok(RtspMessage::Response(response)))
+                    if response.headers().cseq() == Some(cseq) =>
+                {
+                    return Ok(response);
+                }
+                Some(Ok(message)) => self.on_message(message).await?,
+                Some(Err(err)) => return Err(err.into()),
+                None => return Err(RtspClientError::ConnectionClosed),
+            }
+        }
+    }
+
+    /// the answer to the request, asked again with the credentials once for a 401
+    pub async fn request(
+        &mut self,
+        method: RtspMethod,
+        uri: Url,
+        headers: Vec<(RtspHeader, String)>,
+    ) -> RtspClientResult<RtspResponse> {
+        let mut answered_challenge = false;
+        loop {
+            let cseq = self
+                .send_request(method, uri.clone(), headers.clone())
+                .await?;
+            let response =
+                tokio::time::timeout(self.config.request_timeout, self.wait_response(cseq))
+                    .await
+                    .map_err(|_| RtspClientError::Timeout(method))??;
+            if response.status() != RtspStatus::Unauthorized {
+                return Ok(response);
+            }
+            let challenge =
+                RtspChallenge::pick(response.headers().get(RtspHeader::WWWAuthenticate));
+            // a stale nonce is answered again, the credentials were taken
+            let stale = matches!(challenge, Some(RtspChallenge::Digest { stale: true, .. }));
+            if answered_challenge && !stale {
+                return Err(RtspClientError::Unauthorized(format!(
+                    "{} of {} is refused with the credentials",
+                    method, uri
+                )));
+            }
+            let (Some(credentials), Some(challenge)) = (self.credentials.clone(), challenge) else {
+                return Err(RtspClientError::Unauthorized(format!(
+                    "{} of {} needs credentials",
+                    method, uri
+                )));
+            };
+            tracing::info!("{} of {} is challenged: {:?}", method, uri, challenge);
+            self.authenticator = Some(RtspAuthenticator::new(credentials, challenge)?);
+            answered_challenge = true;
+        }
+    }
+}
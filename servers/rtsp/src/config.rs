@@ -1,7 +1,49 @@
-use std::net::IpAddr;
+use std::{collections::HashMap, net::IpAddr, path::PathBuf};
+
+use rtp_formats::codec::h264::packet::sequencer::budget::RtpH264BufferConfig;
+use rtp_session::{
+    fec::FecConfig, pacing::PacingConfig, retransmission::RetransmissionConfig, sdes::SdesConfig,
+};
+use stream_center::identity::StreamIdentity;
+use unified_io::{proxy_protocol::TrustedProxies, tls::TlsListenerConfig};
+
+use crate::{describe::DescribeConfig, multicast::MulticastGroup};
 
 #[derive(Debug)]
 pub struct RtspServerConfig {
     pub address: IpAddr,
     pub port: u16,
+    /// rtsps listener, enabled if set
+    pub rtsps: Option<TlsListenerConfig>,
+    /// sent packets kept per play session to answer nacks
+    pub retransmission: RetransmissionConfig,
+    /// offer rtx in DESCRIBE, nacked packets are resent as is otherwise
+    pub offer_rtx: bool,
+    /// spreads the packets of play sessions by the timestamps of their frames, they are sent as they come if not set
+    pub pacing: Option<PacingConfig>,
+    /// byte budgets of the buffers reassembling h264 from published rtp packets
+    pub h264_buffer: RtpH264BufferConfig,
+    /// pass the access unit delimiters of published h264 on, they are dropped otherwise
+    pub h264_access_unit_delimiters: bool,
+    /// frames held back to derive the dts of published h264 with b-frames, when the sps does not tell how many
+    pub h264_reorder_frames: usize,
+    /// accept clients requiring the onvif backchannel, the audio they send is dropped
+    pub onvif_backchannel: bool,
+    /// rtsps clients get the medias described as RTP/SAVP with a=crypto keys and protected with srtp,
+    /// only if built with the srtp feature
+    pub srtp: bool,
+    /// the groups streams are multicast to, for clients asking for multicast in SETUP
+    pub multicast: HashMap<StreamIdentity, MulticastGroup>,
+    /// how the packets played of a stream are protected against loss, not at all for the streams left out
+    pub fec: HashMap<StreamIdentity, FecConfig>,
+    /// the items sent in rtcp sdes besides the cname
+    pub sdes: SdesConfig,
+    /// keeps the secret the cnames are derived from across restarts, a new one per run if not set
+    pub data_dir: Option<PathBuf>,
+    /// how DESCRIBE waits for the sequence headers of streams published a moment ago
+    pub describe: DescribeConfig,
+    /// the load balancers sending a proxy protocol header ahead of the connections of the clients
+    pub trusted_proxies: TrustedProxies,
+    /// requests with a longer body are answered with 413 and the connection is closed
+    pub max_body_size: usize,
 }
@@ -0,0 +1,76 @@
+use rtsp_formats::sdp_extension::attribute::RtspSDPControl;
+use sdp_formats::{attributes::SDPAttribute, session::Sdp};
+use url::Url;
+
+/// what the uri of a request controls in a described session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlTarget {
+    /// the session as a whole
+    Aggregate,
+    /// the media at `index` in the sdp
+    Media {
+        index: usize,
+        control: RtspSDPControl,
+    },
+    Unknown,
+}
+
+/// the control attribute among the attributes of a session or a media
+pub fn control_attribute(attributes: &[SDPAttribute]) -> Option<RtspSDPControl> {
+    attributes.iter().find_map(|attr| match attr {
+        SDPAttribute::Trivial(attr) if attr.name == "control" => {
+            RtspSDPControl::try_from(attr).ok()
+        }
+        _ => None,
+    })
+}
+
+/// the session is controlled as a whole, i.e., PLAY and PAUSE are for all of its medias
+pub fn is_aggregate_controlled(sdp: &Sdp) -> bool {
+    control_attribute(&sdp.attributes).is_some()
+}
+
+fn trimmed_path(uri: &Url) -> &str {
+    uri.path().trim_end_matches('/')
+}
+
+/// resolves `uri` against the controls of `sdp` described with `base` as its Content-Base.
+/// a relative control is the last segment of the uri, clients put it after the query too.
+/// the segments are matched whole, so `trackID=1` is not `trackID=10`
+/// @see: RFC 7826 Appendix D.1.1
+pub fn resolve_control(sdp: &Sdp, base: &Url, uri: &Url) -> ControlTarget {
+    let path = trimmed_path(uri);
+    let is_control = |control: &RtspSDPControl| match control {
+        RtspSDPControl::Relative(relative) => {
+            let segment = format!("/{}", relative.trim_matches('/'));
+            path.ends_with(&segment)
+                || uri
+                    .query()
+                    .is_some_and(|query| query.trim_end_matches('/').ends_with(&segment))
+        }
+        RtspSDPControl::Absolute(absolute) => path == trimmed_path(absolute),
+        RtspSDPControl::Asterisk => false,
+    };
+    let media = sdp
+        .media_description
+        .iter()
+        .enumerate()
+        .find_map(|(index, media)| {
+            control_attribute(&media.attributes)
+                .filter(is_control)
+                .map(|control| ControlTarget::Media { index, control })
+        });
+    if let Some(media) = media {
+        return media;
+    }
+    let is_aggregate = path == trimmed_path(base)
+        || matches!(
+            control_attribute(&sdp.attributes),
+            Some(RtspSDPControl::Absolute(absolute)) if path == trimmed_path(&absolute)
+        );
+    if is_aggregate {
+        ControlTarget::Aggregate
+    } else {
+        ControlTarget::Unknown
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use codec_common::{audio::AudioConfig, video::VideoConfig};
+use num::ToPrimitive;
+use rtp_formats::{
+    codec::{
+        h264::paramters::{RtpH264FmtpBuilder, packetization_mode::PacketizationMode},
+        mpeg4_generic::parameters::RtpMpeg4Fmtp,
+    },
+    payload_types::rtp_payload_type::{
+        audio_get_rtp_clockrate, audio_get_rtp_encoding_name, get_audio_rtp_payload_type,
+        get_video_rtp_payload_type, video_get_rtp_clockrate, video_get_rtp_encoding_name,
+    },
+};
+use rtsp_formats::sdp_extension::attribute::RtspSDPControl;
+use sdp_formats::{
+    attributes::{SDPAttribute, fmtp::FormatParameters, rtpmap::RtpMap},
+    builder::SdpMediaBuilder,
+    session::{SDPMediaDescription, SDPMediaProtocol, SDPMediaType},
+};
+use stream_center::{events::StreamDescription, identity::StreamIdentity};
+
+use crate::errors::RtspServerResult;
+
+pub const DEFAULT_DESCRIBE_WAIT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy)]
+pub struct DescribeConfig {
+    /// how long DESCRIBE waits for the stream to be published and its sequence headers to come
+    pub wait: Duration,
+    /// answer the medias known so far once the wait is over, 453 otherwise
+    pub minimal_sdp_on_timeout: bool,
+}
+
+impl Default for DescribeConfig {
+    fn default() -> Self {
+        Self {
+            wait: DEFAULT_DESCRIBE_WAIT,
+            minimal_sdp_on_timeout: false,
+        }
+    }
+}
+
+/// a media as its sequence header describes it, before the options of a session are added
+#[derive(Debug, Clone)]
+pub struct DescribedMedia {
+    pub payload_type: u8,
+    pub clock_rate: u64,
+    /// the rtpmap, the fmtp and the control of the media
+    pub media: SDPMediaDescription,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DescribedMedias {
+    pub audio: Option<DescribedMedia>,
+    pub video: Option<DescribedMedia>,
+}
+
+impl DescribedMedias {
+    pub fn new(description: &StreamDescription) -> RtspServerResult<Self> {
+        let audio = match &description.audio_conifg {
+            Some(audio_config) if description.has_audio => {
+                Some(describe_audio(audio_config, description.audio_channels)?)
+            }
+            _ => None,
+        };
+        let video = match &description.video_config {
+            Some(video_config) if description.has_video => describe_video(video_config),
+            _ => None,
+        };
+        Ok(Self { audio, video })
+    }
+}
+
+#[derive(Debug)]
+struct CachedMedias {
+    /// tells a publish from the one before it, the config generation starts over on publish
+    publish_start_time: SystemTime,
+    config_generation: u64,
+    medias: DescribedMedias,
+}
+
+/// the medias of the streams described so far, shared by the sessions of a server.
+/// a stream is described again once its publisher changes the sequence headers or it is published again
+#[derive(Debug, Clone, Default)]
+pub struct DescribeCache {
+    streams: Arc<Mutex<HashMap<StreamIdentity, CachedMedias>>>,
+}
+
+impl DescribeCache {
+    pub fn get_or_describe(
+        &self,
+        description: &StreamDescription,
+    ) -> RtspServerResult<DescribedMedias> {
+        let mut streams = self.streams.lock().unwrap();
+        if let Some(cached) = streams.get(&description.stream_id)
+            && cached.publish_start_time == description.publish_start_time
+            && cached.config_generation == description.config_generation
+        {
+            return Ok(cached.medias.clone());
+        }
+        let medias = DescribedMedias::new(description)?;
+        tracing::info!(
+            "describe stream {} of config generation {}",
+            description.stream_id,
+            description.config_generation
+        );
+        streams.insert(
+            description.stream_id.clone(),
+            CachedMedias {
+                publish_start_time: description.publish_start_time,
+                config_generation: description.config_generation,
+                medias: medias.clone(),
+            },
+        );
+        Ok(medias)
+    }
+}
+
+fn describe_audio(
+    audio_config: &AudioConfig,
+    audio_channels: Option<u8>,
+) -> RtspServerResult<DescribedMedia> {
+    let codec_id = audio_config.into();
+    let payload_type = get_audio_rtp_payload_type(codec_id).unwrap();
+    let clock_rate = match audio_config {
+        // mpeg4-generic ticks at the rate the decoder outputs, RFC 3640
+        AudioConfig::AAC(aac_config) => aac_config.effective_sample_rate().map(u64::from),
+    }
+    .unwrap_or_else(|| audio_get_rtp_clockrate(codec_id).unwrap());
+    let mut media = SdpMediaBuilder::new()
+        .media_type(SDPMediaType::Audio)
+        .port(0.into())
+        .protocol(SDPMediaProtocol::RtpAvp)
+        .media_format(payload_type.to_string())
+        .attribute(SDPAttribute::Trivial(
+            (&RtspSDPControl::Relative("control=audio".to_owned())).into(),
+        ))
+        .rtpmap(RtpMap {
+            payload_type,
+            encoding_name: audio_get_rtp_encoding_name(codec_id).unwrap().to_string(),
+            clock_rate,
+            encoding_params: audio_channels.map(u64::from),
+        });
+    match audio_config {
+        AudioConfig::AAC(aac_config) => {
+            let audio_fmtp: RtpMpeg4Fmtp = aac_config.try_into()?;
+            media = media.fmtp(FormatParameters {
+                fmt: payload_type,
+                params: format!("{}", audio_fmtp),
+            });
+        }
+    }
+    Ok(DescribedMedia {
+        payload_type,
+        clock_rate,
+        media: media.build(),
+    })
+}
+
+/// None for codecs without rtp payload format, av1 for example
+fn describe_video(video_config: &VideoConfig) -> Option<DescribedMedia> {
+    let codec_id = video_config.into();
+    let payload_type = get_video_rtp_payload_type(codec_id)?;
+    let clock_rate = video_get_rtp_clockrate(codec_id).unwrap().to_u64().unwrap();
+    let mut media = SdpMediaBuilder::new()
+        .media_type(SDPMediaType::Video)
+        .port(0.into())
+        .protocol(SDPMediaProtocol::RtpAvp)
+        .media_format(payload_type.to_string())
+        .attribute(SDPAttribute::Trivial(
+            (&RtspSDPControl::Relative("control=video".to_owned())).into(),
+        ))
+        .rtpmap(RtpMap {
+            payload_type,
+            encoding_name: video_get_rtp_encoding_name(codec_id).unwrap().to_string(),
+            clock_rate,
+            encoding_params: None,
+        });
+    match video_config {
+        VideoConfig::H264(h264_config) => {
+            let video_fmtp = RtpH264FmtpBuilder::from(h264_config)
+                .packetization_mode(PacketizationMode::NonInterleaved)
+                .build();
+            media = media.fmtp(FormatParameters {
+                fmt: payload_type,
+                params: format!("{}", video_fmtp),
+            });
+        }
+        VideoConfig::AV1(_) => {}
+    }
+    Some(DescribedMedia {
+        payload_type,
+        clock_rate,
+        media: media.build(),
+    })
+}
@@ -2,6 +2,7 @@ use rtp_formats::{
     codec::{h264::paramters::errors::H264SDPError, mpeg4_generic::errors::RtpMpeg4Error},
     errors::RtpError,
 };
+use server_utils::ingest_limit::errors::IngestLimitError;
 use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum RtspServerError {
@@ -23,6 +24,8 @@ pub enum RtspServerError {
     InvalidMediaDescription(String),
     #[error("invalid transport: {0}")]
     InvalidTransport(String),
+    #[error("invalid multicast group: {0}")]
+    InvalidMulticastGroup(String),
     #[error("unknown encoding name: {0}")]
     InvalidEncodingName(String),
     #[error("invalid H264 SDP Parameters: {0}")]
@@ -39,8 +42,16 @@ pub enum RtspServerError {
     UnableToPlayAudio(String),
     #[error("codec parameters error: {0}")]
     CodecParametersError(String),
+    #[error("codec error: {0}")]
+    CodecCommonError(#[from] codec_common::errors::CodecCommonError),
     #[error("rtp packetize failed: {0}")]
     RtpPacketizeFailed(#[from] RtpError),
+    #[error("ingest limit exceeded: {0}")]
+    IngestLimitExceeded(#[from] IngestLimitError),
+    #[error("oversize frame: {0}")]
+    OversizeFrame(#[source] RtpError),
+    #[error("tls error: {0}")]
+    TlsError(#[from] unified_io::errors::UnifiedIOError),
     #[error("Gracefully exit")]
     GracefulExit,
 }
@@ -1,11 +1,21 @@
 #![feature(if_let_guard)]
 use rtsp_formats::{consts::status::RtspStatus, response::RtspResponse};
+pub mod client;
 pub mod config;
+pub mod control;
+pub mod describe;
 pub mod errors;
 pub mod media_session;
 pub mod middleware;
+pub mod multicast;
+pub mod parameters;
+pub mod payload_type_map;
+pub mod rtp_io;
+pub mod sdes;
 pub mod server;
 pub mod session;
+#[cfg(test)]
+mod test;
 pub const SERVER_AGENT: &str = "yam_server/rtsp";
 
 #[inline(always)]
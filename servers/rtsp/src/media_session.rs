@@ -1,43 +1,53 @@
 use std::{
-    io, net::{IpAddr, SocketAddr}, pin::Pin, time::Duration
+    io, net::SocketAddr, pin::Pin, sync::{atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering}, Arc}, time::Duration
 };
 use codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecificConfig;
 use codec_h264::avc_decoder_configuration_record::AvcDecoderConfigurationRecord;
 use futures::SinkExt;
 use rtp_formats::{
     codec::{
-        h264::{packet::{packetizer::RtpH264PacketPacketizer, sequencer::RtpH264Sequencer}, paramters::RtpH264Fmtp},
+        g711::{packetizer::RtpG711PacketPacketizer, sequencer::RtpG711Sequencer, G711Law},
+        h264::{dts::H264DtsDeriver, packet::{packetizer::RtpH264PacketPacketizer, sequencer::{budget::{RtpH264BufferConfig, RtpH264BufferMetrics}, RtpH264Sequencer}}, paramters::RtpH264Fmtp},
         mpeg4_generic::{packet::{packetizer::RtpMpeg4GenericPacketPacketizer, sequencer::RtpMpeg4GenericSequencer}, parameters::RtpMpeg4Fmtp},
-    }, packet::{packetizer::{RtpPacketizerItem, RtpTrivialPacketPacketizer}, sequencer::{RtpBufferedSequencer, RtpTrivialSequencer}, RtpTrivialPacket}, payload_types::rtp_payload_type::get_rtp_clockrate, rtcp::RtcpPacket
+    }, errors::RtpError, header::{RtpHeaderExtension, ABS_SEND_TIME_URI}, packet::{packetizer::{RtpPacketizerItem, RtpTrivialPacketPacketizer}, red::RED_PRIMARY_HEADER_BYTES, rewriter::RtpRewriter, sequencer::{RtpBufferedSequencer, RtpTrivialSequencer}, rtx::RTX_OSN_BYTES, ulpfec::ULPFEC_HEADER_BYTES, RtpTrivialPacket}, payload_types::rtp_payload_type::{RED_ENCODING_NAME, RTX_ENCODING_NAME, ULPFEC_ENCODING_NAME}, rtcp::RtcpPacket
 };
 use rtp_session::{
+    capture::CaptureTap,
+    fec::{FecConfig, FecMetrics, RedProtection, UlpfecProtection},
+    metrics::RtpSessionMetrics,
+    rtcp_context::RtpSessionObserver,
+    pacing::PacingConfig,
+    retransmission::{RetransmissionConfig, RetransmissionMetrics, RtxParameters},
     session::{RtpSession, RtpSessionCommand},
     simple_statistics::RtpSessionSimpleStatistics,
 };
 use rtsp_formats::{
-    header::transport::{TransportHeader, TransportProtocol}, interleaved::RtspInterleavedPacket,
+    header::transport::{TransportCast, TransportHeader, TransportProtocol}, interleaved::RtspInterleavedPacket,
     sdp_extension::attribute::RtspSDPControl,
 };
 use sdp_formats::{
     attributes::{fmtp::FormatParameters, rtpmap::RtpMap, SDPAttribute}, session::{SDPBandwidthType, SDPMediaDescription, SDPMediaType}
 };
-use stream_center::{gop::MediaFrame};
-use tokio::sync::broadcast::error::TryRecvError;
+use server_utils::ingest_limit::IngestRateLimiter;
+use stream_center::{gop::MediaFrame, parameter_sets::{ParameterSetCarriage, ParameterSetPlacement}};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 use tracing::{Instrument, Span};
-use unified_io::{UnifiedIO, channel::ChannelIo, udp::UdpIO};
+use unified_io::{UnifiedIO, channel::ChannelIo};
 use url::Url;
-use utils::{random::{random_u16, random_u32}, traits::buffer::GenericSequencer};
+use utils::{random::random_u32, traits::{buffer::GenericSequencer, dynamic_sized_packet::DynamicSizedPacket}};
 use crate::{
-    SERVER_AGENT,
     errors::{RtspServerError, RtspServerResult},
+    payload_type_map::{PayloadFormat, PayloadTypeMap, RtpPayloadDemuxer},
+    rtp_io::{RtpIo, RtpIoFactory},
+    sdes::SessionSdes,
 };
+#[cfg(feature = "srtp")]
+use rtp_formats::profiles::savp::{SrtpMasterKey, AES_CM_128_HMAC_SHA1_80, AUTH_TAG_BYTES};
 
-#[derive(Debug)]
-struct StreamProperties {
-    pub(crate) stream_name: String,
-    pub(crate) sub_stream_name: String,
-    pub(crate) uri: Url,
-}
+/// the largest rtp packet sent to a client who did not ask for a Blocksize
+pub const DEFAULT_RTP_PACKET_SIZE: usize = 1400;
+/// the smallest Blocksize honored, a smaller one leaves hardly any room for the payload
+pub const MIN_RTP_PACKET_SIZE: usize = 64;
 
 #[derive(Debug, Clone)]
 pub enum RtspSessionCommand {
@@ -45,32 +55,86 @@ pub enum RtspSessionCommand {
     Start,
     Rtp(RtpTrivialPacket),
     Rtcp(RtcpPacket),
+    /// the playback speed of the play sessions, the rtp packets are paced by it
+    Speed(f64),
+}
+
+/// where the rtp stream of a play media session is, a PLAY resuming from pause continues from here
+#[derive(Debug)]
+pub struct RtpPlayPosition {
+    clockrate: u64,
+    next_sequence_number: AtomicU32,
+    last_timestamp: AtomicU32,
+    /// nothing to continue from until the first packet
+    started: AtomicBool,
+}
+
+impl RtpPlayPosition {
+    pub fn new(clockrate: u64) -> Self {
+        Self {
+            clockrate,
+            next_sequence_number: AtomicU32::new(0),
+            last_timestamp: AtomicU32::new(0),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn on_packet_sent(&self, sequence_number: u16, timestamp: u32) {
+        self.next_sequence_number.store(sequence_number.wrapping_add(1) as u32, Ordering::Relaxed);
+        self.last_timestamp.store(timestamp, Ordering::Relaxed);
+        self.started.store(true, Ordering::Release);
+    }
+
+    /// the seq and rtptime of the rtp-info of a resuming PLAY, RFC 7826 18.45.
+    /// the live stream goes on during the pause, so the rtp time moves on by the pause duration
+    pub fn resume_point(&self, paused_for: Duration) -> Option<(u16, u32)> {
+        if !self.started.load(Ordering::Acquire) {
+            return None;
+        }
+        let elapsed = (paused_for.as_millis() as u64).saturating_mul(self.clockrate) / 1000;
+        Some((
+            self.next_sequence_number.load(Ordering::Relaxed) as u16,
+            self.last_timestamp.load(Ordering::Relaxed).wrapping_add(elapsed as u32),
+        ))
+    }
+}
+
+/// the state of a publish media session, boxed in the runtime handler as it is by far the largest
+struct PublishHandler {
+    media_frame_sender: tokio::sync::mpsc::Sender<MediaFrame>,
+    rtp_receiver: tokio::sync::mpsc::Receiver<RtpTrivialPacket>,
+    rtp_sequencer: RtpTrivialSequencer,
+    rtp_demuxer: RtpPayloadDemuxer,
+    /// gives h264 frames their dts, rtp carries the pts only
+    dts_deriver: Option<H264DtsDeriver>,
+    payload_types: PayloadTypeMap,
+    /// the payload type whose sequence header was sent to the stream center last
+    announced_payload_type: Option<u8>,
+    ingest_limiter: IngestRateLimiter,
+    stream_key: String,
 }
 
 enum RuntimeHandler {
     Play{
         media_frame_receiver: tokio::sync::mpsc::Receiver<MediaFrame>,
         rtp_packetizer: Box<dyn RtpTrivialPacketPacketizer + Send>,
+        play_position: Arc<RtpPlayPosition>,
+        parameter_sets: ParameterSetPlacement,
+        /// the fixed frame rate the sequence header of the stream declares
+        nominal_frame_rate: Option<f64>,
+        speed: f64,
     },
-    Publish{
-        media_frame_sender: tokio::sync::mpsc::Sender<MediaFrame>,
+    Publish(Box<PublishHandler>),
+    /// audio sent by the client on an onvif backchannel, dropped as there is no sink for it
+    Backchannel{
         rtp_receiver: tokio::sync::mpsc::Receiver<RtpTrivialPacket>,
-        rtp_sequencer: RtpTrivialSequencer,
-        rtp_unpacker: Box<dyn RtpBufferedSequencer + Send>,
-        
-        control: Box<RtspSDPControl>,
-        bandwidth: Option<u64>,
-        rtpmap: RtpMap,
-        fmtp: Option<FormatParameters>,
-        media_description: Box<SDPMediaDescription>,
     },
-    None,
 }
 
 pub struct RtspMediaSession {
     peer_addr: SocketAddr,
     session_id: String,
-    stream_properities: StreamProperties,
+    uri: Url,
 
     pub(crate) local_rtp_port: u16,
     pub(crate) local_rtcp_port: u16,
@@ -89,6 +153,11 @@ pub struct RtspMediaSession {
     first_rtp_packet_timestamp: Option<u32>,
     rtp_clockrate: u64,
     ssrc: u32,
+    packets_sent: Arc<AtomicU64>,
+    retransmission_metrics: Arc<RetransmissionMetrics>,
+    fec_metrics: Arc<FecMetrics>,
+    buffer_metrics: Arc<RtpH264BufferMetrics>,
+    unknown_payload_type_packets: Arc<AtomicU64>,
 }
 
 impl RtspMediaSession {
@@ -103,26 +172,71 @@ impl RtspMediaSession {
         transport: TransportHeader,
         rtsp_command_rx: tokio::sync::broadcast::Receiver<RtspSessionCommand>,
         media_frame_receiver: tokio::sync::mpsc::Receiver<MediaFrame>,
+        retransmission: RetransmissionConfig,
+        fec: FecConfig,
+        pacing: Option<PacingConfig>,
+        max_packet_size: usize,
+        sdes: &SessionSdes,
+        capture: &CaptureTap,
+        rtp_io_factory: &dyn RtpIoFactory,
     ) -> RtspServerResult<Self> {
-        if transport.profile.is_none() || transport.client_port.is_none() {
+        // a multicast group is sent to its own ports
+        let peer_ports = match transport.cast {
+            Some(TransportCast::Multicast) => transport.port,
+            _ => transport.client_port,
+        };
+        if transport.profile.is_none() || peer_ports.is_none() {
             return Err(RtspServerError::InvalidTransport(format!(
                 "transport profile or client port is none, {:?}",
-                &transport
+                transport
             )));
         }
-        let fmtp = media_sdp.get_fmtp();
-        if fmtp.is_none() {
-            tracing::error!("fmtp not found in media attributes");
-            return Err(RtspServerError::InvalidMediaDescription("fmtp not found in media description".to_string()));
-        }
+        // the fmtp of the payload type sent, rather than the first one of the media
+        let fmtp = PayloadTypeMap::from_media(media_sdp)
+            .get(rtpmap.payload_type)
+            .and_then(|format| format.fmtp.clone());
         let ssrc = random_u32();
-        let rtp_packetizer = Self::create_rtp_packetizer(ssrc, &fmtp.unwrap(), rtpmap.encoding_name.clone())?;
+        let rtx = Self::negotiated_rtx_payload_type(media_sdp, rtpmap.payload_type)
+            .map(|payload_type| RtxParameters { payload_type, ssrc: random_u32() });
+        let abs_send_time_id = Self::negotiated_abs_send_time_id(media_sdp);
+        // the protection described to the player, as configured for the stream
+        let red = Self::negotiated_red_payload_type(media_sdp, rtpmap.payload_type).filter(|_| fec.red);
+        let ulpfec = Self::negotiated_ulpfec_payload_type(media_sdp).zip(fec.ulpfec_group_size);
+        #[cfg(feature = "srtp")]
+        let srtp = Self::negotiated_srtp_key(media_sdp, &transport)?;
+        // the header extension and the osn of retransmissions are added to the packets later
+        let mut packetizer_mtu = max_packet_size;
+        if let Some(id) = abs_send_time_id {
+            packetizer_mtu -= RtpHeaderExtension::abs_send_time(id, Duration::ZERO)?.get_packet_bytes_count();
+        }
+        if rtx.is_some() {
+            packetizer_mtu -= RTX_OSN_BYTES;
+        }
+        // so is the authentication tag of srtp, after the packets are padded to 32 bits
+        #[cfg(feature = "srtp")]
+        if srtp.is_some() {
+            packetizer_mtu -= AUTH_TAG_BYTES.next_multiple_of(4);
+        }
+        // red has the previous payload left out if it does not fit, the parity packets are as long as the longest they protect
+        let red_max_packet_size = packetizer_mtu;
+        if red.is_some() {
+            packetizer_mtu -= RED_PRIMARY_HEADER_BYTES;
+        }
+        if ulpfec.is_some() {
+            packetizer_mtu -= ULPFEC_HEADER_BYTES;
+        }
+        let mut rtp_packetizer = Self::create_rtp_packetizer(ssrc, &fmtp, rtpmap.encoding_name.clone(), packetizer_mtu)?;
+        // the payload type advertised in the sdp, rather than the well-known one of the codec
+        let mut rtp_header = rtp_packetizer.rtp_header().clone();
+        rtp_header.payload_type = rtpmap.payload_type;
+        rtp_packetizer.set_rtp_header(rtp_header);
         let (rtp_command_tx, rtp_command_rx) =
             tokio::sync::mpsc::channel::<RtpSessionCommand>(1000);
         
-        let (client_rtp_port, client_rtcp_port) = transport.client_port.unwrap();
+        let (client_rtp_port, client_rtcp_port) = peer_ports.unwrap();
         let ((rtp_io, rtp_port), (rtcp_io, rtcp_port)) =
             Self::create_rtp_io_pair(
+                rtp_io_factory,
                 peer_addr,
                 client_rtp_port,
                 client_rtcp_port,
@@ -130,31 +244,59 @@ impl RtspMediaSession {
             ).await?;
         tracing::debug!("new rtsp play session with rtp port: {}, rtcp port: {}, client rtp port: {}, client rtcp port: {}",
             rtp_port, rtcp_port, client_rtp_port, client_rtcp_port);
-        let rtp_clockrate = get_rtp_clockrate(&rtpmap.encoding_name).unwrap();
+        // the rate advertised to the player, the rtp timestamps and the sender reports tick at it
+        let rtp_clockrate = rtpmap.clock_rate;
+        if rtp_clockrate == 0 {
+            return Err(RtspServerError::InvalidMediaDescription(format!("clock rate of 0 in rtpmap: {}", rtpmap)));
+        }
+        if rtp_clockrate != rtp_packetizer.get_rtp_clockrate() {
+            tracing::warn!("{} is advertised at {}Hz rather than {}Hz, the packets tick at the advertised rate", rtpmap.encoding_name, rtp_clockrate, rtp_packetizer.get_rtp_clockrate());
+        }
+        rtp_packetizer.set_rtp_clockrate(rtp_clockrate);
         let rtp_session = RtpSession::new(
             ssrc,
-            Some(SERVER_AGENT.to_owned()),
+            sdes.local().clone(),
             10000,
             rtp_clockrate,
             rtp_command_rx,
             None,
-        );
-        tracing::info!("new rtsp media play session is created");
+        ).with_retransmission(retransmission, rtx)
+        // the frames of a stream failing over to its backup go on in the same rtp stream
+        .with_rewriter(RtpRewriter::new(rtp_clockrate));
+        let rtp_session = match abs_send_time_id {
+            Some(id) => rtp_session.with_abs_send_time(id),
+            None => rtp_session,
+        };
+        let rtp_session = match pacing {
+            Some(pacing) => rtp_session.with_pacing(pacing),
+            None => rtp_session,
+        };
+        let rtp_session = match red {
+            Some(payload_type) => rtp_session.with_protection(Box::new(RedProtection::new(payload_type, red_max_packet_size))),
+            None => rtp_session,
+        };
+        let rtp_session = match ulpfec {
+            Some((payload_type, group_size)) => rtp_session.with_protection(Box::new(UlpfecProtection::new(payload_type, random_u32(), group_size))),
+            None => rtp_session,
+        };
+        #[cfg(feature = "srtp")]
+        let rtp_session = match srtp {
+            Some(master) => rtp_session.with_srtp(master),
+            None => rtp_session,
+        };
+        let retransmission_metrics = rtp_session.retransmission_metrics();
+        let fec_metrics = rtp_session.fec_metrics();
+        tracing::info!("new rtsp media play session is created, rtx: {:?}, red: {:?}, ulpfec: {:?}, abs-send-time id: {:?}, pacing: {:?}, max packet size: {}", rtx, red, ulpfec, abs_send_time_id, pacing, max_packet_size);
 
-        let stream_name = uri.path();
         let rtp_session_span = tracing::debug_span!("rtp play session",
             rtsp_session_id = %session_id,
             rtsp_uri = %uri,
             rtsp_control = %control,
         );
-        Self::start_rtp_session(true, rtp_session, rtp_io, rtcp_io, rtp_session_span).await?;
+        Self::start_rtp_session(true, rtp_session, rtp_io, rtcp_io, sdes.observer(control.to_string(), Default::default()), capture.clone(), rtp_session_span).await?;
         Ok(Self {
             peer_addr,
-            stream_properities: StreamProperties {
-                stream_name: stream_name.to_owned(),
-                sub_stream_name: "".to_owned(),
-                uri,
-            },
+            uri,
             session_id,
             transport,
             rtp_session_command_tx: rtp_command_tx,
@@ -169,15 +311,25 @@ impl RtspMediaSession {
             media_type: media_sdp.media_line.media_type.clone(),
             session_handler: RuntimeHandler::Play {
                 media_frame_receiver,
-                rtp_packetizer
+                rtp_packetizer,
+                play_position: Arc::new(RtpPlayPosition::new(rtp_clockrate)),
+                parameter_sets: ParameterSetPlacement::new(ParameterSetCarriage::InBand),
+                nominal_frame_rate: None,
+                speed: 1.0,
             },
 
             first_rtp_packet_timestamp: None,
             rtp_clockrate,
             ssrc,
+            packets_sent: Default::default(),
+            retransmission_metrics,
+            fec_metrics,
+            buffer_metrics: Default::default(),
+            unknown_payload_type_packets: Default::default(),
         })
 
     }
+    #[allow(clippy::too_many_arguments)]
     pub async fn new_publish_session(
         peer_addr: SocketAddr,
         uri: Url,
@@ -186,26 +338,41 @@ impl RtspMediaSession {
         transport: TransportHeader,
         rtsp_command_rx: tokio::sync::broadcast::Receiver<RtspSessionCommand>,
         media_frame_sender: tokio::sync::mpsc::Sender<MediaFrame>,
+        ingest_limiter: IngestRateLimiter,
+        stream_key: String,
+        h264_buffer: RtpH264BufferConfig,
+        h264_access_unit_delimiters: bool,
+        h264_reorder_frames: usize,
+        sdes: &SessionSdes,
+        capture: &CaptureTap,
+        rtp_io_factory: &dyn RtpIoFactory,
     ) -> RtspServerResult<Self> {
         let control = Self::extract_control_attribute(&media_description)?;
-        let rtpmap: RtpMap = media_description.get_rtp_map().ok_or(RtspServerError::InvalidMediaDescription(
-            format!("no rtpmap found in media description: {}", media_description)
-        ))?;
-        let fmtp = media_description.get_fmtp();
+        let payload_types = PayloadTypeMap::from_media(&media_description);
+        let PayloadFormat { rtpmap, fmtp, .. } = payload_types
+            .preferred(&media_description.media_line.media_type)
+            .cloned()
+            .ok_or(RtspServerError::InvalidMediaDescription(
+                format!("no supported rtpmap found in media description: {}", media_description)
+            ))?;
         let bandwidth = Self::extract_bandwidth(&media_description).ok();
 
         if transport.profile.is_none() || transport.client_port.is_none() {
             return Err(RtspServerError::InvalidTransport(format!(
                 "transport profile or client port is none, {:?}",
-                &transport
+                transport
             )));
         }
 
-        let unpacker = Self::create_rtp_unpacker(
-            media_description.media_line.media_type.clone(),
-            &rtpmap,
-            &fmtp,
+        let (rtp_demuxer, buffer_metrics) = Self::create_rtp_demuxer(
+            &payload_types,
+            rtpmap.payload_type,
+            ingest_limiter.config().max_rtp_access_unit_size,
+            h264_buffer,
+            h264_access_unit_delimiters,
         )?;
+        let unknown_payload_type_packets = rtp_demuxer.unknown_payload_type_packets();
+        let dts_deriver = Self::create_dts_deriver(&rtpmap, &fmtp, h264_reorder_frames);
 
         let (rtp_command_tx, rtp_command_rx) =
             tokio::sync::mpsc::channel::<RtpSessionCommand>(1000);
@@ -214,6 +381,7 @@ impl RtspMediaSession {
         let (client_rtp_port, client_rtcp_port) = transport.client_port.unwrap();
         let ((rtp_io, rtp_port), (rtcp_io, rtcp_port)) =
             Self::create_rtp_io_pair(
+                rtp_io_factory,
                 peer_addr,
                 client_rtp_port,
                 client_rtcp_port,
@@ -224,7 +392,7 @@ impl RtspMediaSession {
         let ssrc = random_u32();
         let rtp_session = RtpSession::new(
             ssrc,
-            Some(SERVER_AGENT.to_owned()),
+            sdes.local().clone(),
             bandwidth.unwrap_or(500),
             rtpmap.clock_rate,
             rtp_command_rx,
@@ -233,21 +401,16 @@ impl RtspMediaSession {
 
         tracing::info!("new rtsp media publish session is created");
 
-        let stream_name = uri.path();
         let rtp_session_span = tracing::debug_span!("rtp publish session",
             rtsp_session_id = %session_id,
             rtsp_uri = %uri,
             rtsp_control = %control,
         );
-        Self::start_rtp_session(false, rtp_session, rtp_io, rtcp_io, rtp_session_span).await?;
+        Self::start_rtp_session(false, rtp_session, rtp_io, rtcp_io, sdes.observer(control.to_string(), buffer_metrics.clone()), capture.clone(), rtp_session_span).await?;
 
         Ok(Self {
             peer_addr,
-            stream_properities: StreamProperties {
-                stream_name: stream_name.to_owned(),
-                sub_stream_name: control.to_string(),
-                uri,
-            },
+            uri,
             session_id,
             transport,
             rtp_session_command_tx: rtp_command_tx,
@@ -261,28 +424,228 @@ impl RtspMediaSession {
             rtsp_session_command_rx: rtsp_command_rx,
             media_type: media_description.media_line.media_type.clone(),
             rtp_clockrate: rtpmap.clock_rate,
-            session_handler: RuntimeHandler::Publish {
+            session_handler: RuntimeHandler::Publish(Box::new(PublishHandler {
                 media_frame_sender,
                 rtp_receiver: rtp_rx,
                 rtp_sequencer: RtpTrivialSequencer::new(200, 10),
-                rtp_unpacker: unpacker,
-                control: Box::new(control),
-                bandwidth,
-                rtpmap,
-                fmtp,
-                media_description: Box::new(media_description)
-            },
+                rtp_demuxer,
+                dts_deriver,
+                payload_types,
+                announced_payload_type: None,
+                ingest_limiter,
+                stream_key,
+            })),
 
             first_rtp_packet_timestamp: None,
             ssrc,
+            packets_sent: Default::default(),
+            retransmission_metrics: Default::default(),
+            fec_metrics: Default::default(),
+            buffer_metrics,
+            unknown_payload_type_packets,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new_backchannel_session(
+        peer_addr: SocketAddr,
+        uri: Url,
+        session_id: String,
+        media_description: &SDPMediaDescription,
+        transport: TransportHeader,
+        rtsp_command_rx: tokio::sync::broadcast::Receiver<RtspSessionCommand>,
+        sdes: &SessionSdes,
+        capture: &CaptureTap,
+        rtp_io_factory: &dyn RtpIoFactory,
+    ) -> RtspServerResult<Self> {
+        let control = Self::extract_control_attribute(media_description)?;
+        let rtpmap: RtpMap = media_description.get_rtp_map().ok_or(RtspServerError::InvalidMediaDescription(
+            format!("no rtpmap found in media description: {}", media_description)
+        ))?;
+
+        if transport.profile.is_none() || transport.client_port.is_none() {
+            return Err(RtspServerError::InvalidTransport(format!(
+                "transport profile or client port is none, {:?}",
+                transport
+            )));
+        }
+
+        let (rtp_command_tx, rtp_command_rx) =
+            tokio::sync::mpsc::channel::<RtpSessionCommand>(1000);
+        let (rtp_tx, rtp_rx) = tokio::sync::mpsc::channel::<RtpTrivialPacket>(1000);
+
+        let (client_rtp_port, client_rtcp_port) = transport.client_port.unwrap();
+        let ((rtp_io, rtp_port), (rtcp_io, rtcp_port)) =
+            Self::create_rtp_io_pair(
+                rtp_io_factory,
+                peer_addr,
+                client_rtp_port,
+                client_rtcp_port,
+                transport.profile.unwrap()
+            ).await?;
+        tracing::debug!("new rtsp backchannel session with rtp port: {}, rtcp port: {}, client rtp port: {}, client rtcp port: {}",
+            rtp_port, rtcp_port, client_rtp_port, client_rtcp_port);
+        let ssrc = random_u32();
+        let rtp_session = RtpSession::new(
+            ssrc,
+            sdes.local().clone(),
+            Self::extract_bandwidth(media_description).unwrap_or(64),
+            rtpmap.clock_rate,
+            rtp_command_rx,
+            Some(rtp_tx),
+        );
+        #[cfg(feature = "srtp")]
+        let rtp_session = match Self::negotiated_srtp_key(media_description, &transport)? {
+            Some(master) => rtp_session.with_srtp(master),
+            None => rtp_session,
+        };
+
+        tracing::info!("new rtsp media backchannel session is created");
+
+        let rtp_session_span = tracing::debug_span!("rtp backchannel session",
+            rtsp_session_id = %session_id,
+            rtsp_uri = %uri,
+            rtsp_control = %control,
+        );
+        Self::start_rtp_session(false, rtp_session, rtp_io, rtcp_io, sdes.observer(control.to_string(), Default::default()), capture.clone(), rtp_session_span).await?;
+
+        Ok(Self {
+            peer_addr,
+            uri,
+            session_id,
+            transport,
+            rtp_session_command_tx: rtp_command_tx,
+
+            local_rtp_port: rtp_port,
+            local_rtcp_port: rtcp_port,
+
+            interleaved_rtcp_io: None,
+            interleaved_rtp_io: None,
+
+            rtsp_session_command_rx: rtsp_command_rx,
+            media_type: media_description.media_line.media_type.clone(),
+            rtp_clockrate: rtpmap.clock_rate,
+            session_handler: RuntimeHandler::Backchannel { rtp_receiver: rtp_rx },
+
+            first_rtp_packet_timestamp: None,
+            ssrc,
+            packets_sent: Default::default(),
+            retransmission_metrics: Default::default(),
+            fec_metrics: Default::default(),
+            buffer_metrics: Default::default(),
+            unknown_payload_type_packets: Default::default(),
+        })
+    }
+
+    /// number of rtp packets handed to the rtp session
+    pub(crate) fn packets_sent(&self) -> Arc<AtomicU64> {
+        self.packets_sent.clone()
+    }
+
+    /// None if this is not a play session
+    pub(crate) fn play_position(&self) -> Option<Arc<RtpPlayPosition>> {
+        match &self.session_handler {
+            RuntimeHandler::Play { play_position, .. } => Some(play_position.clone()),
+            _ => None,
+        }
+    }
+
+    /// nacks from the player and the packets resent for them
+    pub(crate) fn retransmission_metrics(&self) -> Arc<RetransmissionMetrics> {
+        self.retransmission_metrics.clone()
+    }
+
+    /// what the red and ulpfec protection of a play session sent
+    pub(crate) fn fec_metrics(&self) -> Arc<FecMetrics> {
+        self.fec_metrics.clone()
+    }
+
+    /// bytes held by the h264 sequencer of a publish session
+    pub(crate) fn buffer_metrics(&self) -> Arc<RtpH264BufferMetrics> {
+        self.buffer_metrics.clone()
+    }
+
+    /// packets of a publish session dropped for payload types the sdp did not map
+    pub(crate) fn unknown_payload_type_packets(&self) -> Arc<AtomicU64> {
+        self.unknown_payload_type_packets.clone()
+    }
+
+    /// the rtx payload type whose fmtp has apt set to the payload type, RFC 4588 8.1
+    pub(crate) fn negotiated_rtx_payload_type(media_sdp: &SDPMediaDescription, payload_type: u8) -> Option<u8> {
+        let apt = format!("apt={}", payload_type);
+        media_sdp.attributes.iter().find_map(|attr| match attr {
+            SDPAttribute::Fmtp(fmtp) if fmtp.params.split(';').any(|param| param.trim() == apt) => {
+                media_sdp.attributes.iter().find_map(|attr| match attr {
+                    SDPAttribute::RtpMap(rtpmap) if rtpmap.payload_type == fmtp.fmt
+                        && rtpmap.encoding_name.eq_ignore_ascii_case(RTX_ENCODING_NAME) => Some(fmtp.fmt),
+                    _ => None,
+                })
+            }
+            _ => None,
+        })
+    }
+
+    /// the red payload type whose fmtp has the redundancy of the payload type only, e.g., `100 96/96`, RFC 2198 5
+    pub(crate) fn negotiated_red_payload_type(media_sdp: &SDPMediaDescription, payload_type: u8) -> Option<u8> {
+        media_sdp.attributes.iter().find_map(|attr| match attr {
+            SDPAttribute::Fmtp(fmtp) if fmtp.params.trim().split('/').all(|block| block.trim().parse() == Ok(payload_type)) => {
+                media_sdp.attributes.iter().find_map(|attr| match attr {
+                    SDPAttribute::RtpMap(rtpmap) if rtpmap.payload_type == fmtp.fmt
+                        && rtpmap.encoding_name.eq_ignore_ascii_case(RED_ENCODING_NAME) => Some(fmtp.fmt),
+                    _ => None,
+                })
+            }
+            _ => None,
+        })
+    }
+
+    /// the ulpfec payload type of the media, it protects any payload type, RFC 5109 14.1
+    pub(crate) fn negotiated_ulpfec_payload_type(media_sdp: &SDPMediaDescription) -> Option<u8> {
+        media_sdp.attributes.iter().find_map(|attr| match attr {
+            SDPAttribute::RtpMap(rtpmap) if rtpmap.encoding_name.eq_ignore_ascii_case(ULPFEC_ENCODING_NAME) => Some(rtpmap.payload_type),
+            _ => None,
+        })
+    }
+
+    /// a=extmap:<id>[/<direction>] <uri>, RFC 8285 5
+    pub(crate) fn negotiated_abs_send_time_id(media_sdp: &SDPMediaDescription) -> Option<u8> {
+        media_sdp.attributes.iter().find_map(|attr| match attr {
+            SDPAttribute::Trivial(attr) if attr.name == "extmap" => {
+                let mut parts = attr.value.as_ref()?.split_whitespace();
+                let id = parts.next()?.split('/').next()?.parse::<u8>().ok()?;
+                (parts.next()? == ABS_SEND_TIME_URI && (1..=14).contains(&id)).then_some(id)
+            }
+            _ => None,
+        })
+    }
+
+    /// the master key in a=crypto of the media if it is set up with a secure profile, RFC 4568 7.1.1.
+    /// the client protects its rtcp with the same key
+    #[cfg(feature = "srtp")]
+    pub(crate) fn negotiated_srtp_key(media_sdp: &SDPMediaDescription, transport: &TransportHeader) -> RtspServerResult<Option<SrtpMasterKey>> {
+        if !transport.profile.as_ref().is_some_and(TransportProtocol::is_secure) {
+            return Ok(None);
+        }
+        let key_params = media_sdp.attributes.iter().find_map(|attr| match attr {
+            SDPAttribute::Crypto(crypto) if crypto.suite == AES_CM_128_HMAC_SHA1_80 => Some(&crypto.key_params),
+            _ => None,
+        }).ok_or_else(|| RtspServerError::InvalidTransport(format!(
+            "no {} key is described for the secure profile, {:?}",
+            AES_CM_128_HMAC_SHA1_80,
+            transport
+        )))?;
+        SrtpMasterKey::from_key_params(key_params)
+            .map(Some)
+            .map_err(|err| RtspServerError::InvalidTransport(err.to_string()))
+    }
+
     async fn start_rtp_session(
         send: bool,
         rtp_session: RtpSession,
         rtp_io: Pin<Box<dyn UnifiedIO>>,
         rtcp_io: Pin<Box<dyn UnifiedIO>>,
+        observer: Box<dyn RtpSessionObserver>,
+        capture: CaptureTap,
         span: Span,
     ) -> RtspServerResult<tokio::task::JoinHandle<()>> {
         span.in_scope(|| {
@@ -291,8 +654,13 @@ impl RtspMediaSession {
         let res = tokio::task::spawn(
             async move {
                 match rtp_session
+                    .with_capture(capture)
                     .with_observer(Box::new(RtpSessionSimpleStatistics::new()))
                     .await
+                    .with_observer(Box::new(RtpSessionMetrics::new()))
+                    .await
+                    .with_observer(observer)
+                    .await
                     .run(send, rtp_io, rtcp_io)
                     .await
                 {
@@ -311,22 +679,30 @@ impl RtspMediaSession {
 
     fn create_rtp_packetizer(
         ssrc: u32,
-        fmtp: &FormatParameters,
+        fmtp: &Option<FormatParameters>,
         encoding_name: String,
+        mtu: usize,
     ) -> RtspServerResult<Box<dyn RtpTrivialPacketPacketizer + Send>> {
-        tracing::info!("got {} encoding, creating packetizer with fmtp: {}", encoding_name, fmtp);
+        tracing::info!("got {} encoding, creating packetizer with fmtp: {:?}, mtu: {}", encoding_name, fmtp, mtu);
+        if let Some(law) = G711Law::from_encoding_name(&encoding_name) {
+            return Ok(Box::new(RtpG711PacketPacketizer::new(mtu, law, ssrc)));
+        }
+        let Some(fmtp) = fmtp else {
+            tracing::error!("fmtp not found in media attributes");
+            return Err(RtspServerError::InvalidMediaDescription("fmtp not found in media description".to_string()));
+        };
         match encoding_name.to_lowercase().as_str() {
             "h264" => {
                 let h264_fmtp: RtpH264Fmtp = fmtp.params.parse()?;
                 let packetizer = RtpH264PacketPacketizer::new(
-                    1400, h264_fmtp.packetization_mode.unwrap_or_default(), ssrc
+                    mtu, h264_fmtp.packetization_mode.unwrap_or_default(), ssrc
                 );
                 Ok(Box::new(packetizer))
             },
             "mpeg4-generic" => {
                 let aac_fmtp: RtpMpeg4Fmtp = fmtp.params.parse()?;
                 let packetizer = RtpMpeg4GenericPacketPacketizer::new(
-                    1400, aac_fmtp, ssrc
+                    mtu, aac_fmtp, ssrc
                 );
                 Ok(Box::new(packetizer))
             }
@@ -337,11 +713,34 @@ impl RtspMediaSession {
         }
     }
 
-    fn create_rtp_unpacker(
+    /// for h264 only, starting with the parameter sets of the sdp
+    fn create_dts_deriver(
+        rtpmap: &RtpMap,
+        fmtp: &Option<FormatParameters>,
+        h264_reorder_frames: usize,
+    ) -> Option<H264DtsDeriver> {
+        if !rtpmap.encoding_name.eq_ignore_ascii_case("h264") {
+            return None;
+        }
+        let sprop_parameter_sets = fmtp
+            .as_ref()
+            .and_then(|fmtp| fmtp.params.parse::<RtpH264Fmtp>().ok())
+            .and_then(|fmtp| fmtp.sprop_parameter_sets);
+        Some(H264DtsDeriver::new(
+            h264_reorder_frames,
+            sprop_parameter_sets.as_ref().and_then(|v| v.sps.clone()),
+            sprop_parameter_sets.and_then(|v| v.pps),
+        ))
+    }
+
+    pub(crate) fn create_rtp_unpacker(
         media_type: SDPMediaType,
         rtpmap: &RtpMap,
         fmtp: &Option<FormatParameters>,
-    ) -> RtspServerResult<Box<dyn RtpBufferedSequencer + Send>> {
+        max_access_unit_size: usize,
+        h264_buffer: RtpH264BufferConfig,
+        h264_access_unit_delimiters: bool,
+    ) -> RtspServerResult<(Box<dyn RtpBufferedSequencer + Send>, Arc<RtpH264BufferMetrics>)> {
         match rtpmap.encoding_name.to_lowercase().as_str() {
             "h264" => {
                 tracing::info!(
@@ -368,8 +767,12 @@ impl RtspMediaSession {
                         (&h264_fmtp).into(), 
                         h264_fmtp.sprop_parameter_sets.as_ref().and_then(|v| v.sps.clone()), 
                         h264_fmtp.sprop_parameter_sets.as_ref().and_then(|v| v.pps.clone()),
-                    );
-                Ok(Box::new(unpacker))
+                        h264_buffer,
+                    )
+                    .with_fragment_buffer_capacity(max_access_unit_size)
+                    .with_access_unit_delimiters(h264_access_unit_delimiters);
+                let buffer_metrics = unpacker.buffer_metrics();
+                Ok((Box::new(unpacker), buffer_metrics))
             }
             "mpeg4-generic" => {
                 if matches!(media_type, SDPMediaType::Audio) {
@@ -389,7 +792,7 @@ impl RtspMediaSession {
                         params
                     };
                     let unpacker = RtpMpeg4GenericSequencer::new(params, 10000, 10);
-                    Ok(Box::new(unpacker))
+                    Ok((Box::new(unpacker), Default::default()))
                 } else {
                     Err(RtspServerError::InvalidParamForRtpUnpacker(format!(
                         "get mpeg4-generic format but not for audio: {}",
@@ -397,6 +800,19 @@ impl RtspMediaSession {
                     )))
                 }
             }
+            "pcmu" | "pcma" => {
+                if !matches!(media_type, SDPMediaType::Audio) {
+                    return Err(RtspServerError::InvalidParamForRtpUnpacker(format!(
+                        "get {} format but not for audio: {}",
+                        rtpmap.encoding_name,
+                        media_type
+                    )));
+                }
+                // static payload types, there is nothing to read from fmtp
+                let law = G711Law::from_encoding_name(&rtpmap.encoding_name).unwrap();
+                tracing::info!("got {} encoding, creating g711 sequencer, rtpmap: {}", rtpmap.encoding_name, rtpmap);
+                Ok((Box::new(RtpG711Sequencer::new(law)), Default::default()))
+            }
             _ => {
                 tracing::warn!("unknown encoding_name: {}", rtpmap.encoding_name);
                 Err(RtspServerError::InvalidEncodingName(
@@ -406,70 +822,62 @@ impl RtspMediaSession {
         }
     }
 
+    /// a sequencer for each payload type of the map, the ones that fail to be created are left out
+    /// unless it is the preferred one
+    pub(crate) fn create_rtp_demuxer(
+        payload_types: &PayloadTypeMap,
+        preferred_payload_type: u8,
+        max_access_unit_size: usize,
+        h264_buffer: RtpH264BufferConfig,
+        h264_access_unit_delimiters: bool,
+    ) -> RtspServerResult<(RtpPayloadDemuxer, Arc<RtpH264BufferMetrics>)> {
+        let mut demuxer = RtpPayloadDemuxer::new();
+        let mut buffer_metrics = Default::default();
+        for format in payload_types.iter() {
+            let payload_type = format.payload_type();
+            match Self::create_rtp_unpacker(format.media_type.clone(), &format.rtpmap, &format.fmtp, max_access_unit_size, h264_buffer, h264_access_unit_delimiters) {
+                Ok((unpacker, metrics)) => {
+                    if payload_type == preferred_payload_type {
+                        buffer_metrics = metrics;
+                    }
+                    demuxer = demuxer.with_sequencer(payload_type, unpacker);
+                }
+                Err(err) if payload_type != preferred_payload_type => {
+                    tracing::warn!("leaving payload type {} out, create rtp unpacker failed: {}", payload_type, err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok((demuxer, buffer_metrics))
+    }
+
     async fn create_rtp_io_pair(
+        rtp_io_factory: &dyn RtpIoFactory,
         peer_addr: SocketAddr,
         peer_rtp_port: u16,
         peer_rtcp_port: u16,
         protocol: TransportProtocol,
-    ) -> RtspServerResult<(
-        (Pin<Box<dyn UnifiedIO>>, u16),
-        (Pin<Box<dyn UnifiedIO>>, u16),
-    )> {
-        let (rtp_io, rtp_port, rtcp_io, rtcp_port) = if protocol.is_udp()
-        {
-            let ((rtp_io, rtp_port), (rtcp_io, rtcp_port)) =
-                Self::create_udp_io_pair(peer_addr.ip(), peer_rtp_port, peer_rtcp_port).await?;
-            tracing::info!(
-                "created udp io, rtp port: {}, rtcp port: {}",
-                rtp_port,
-                rtcp_port
-            );
-
-            (rtp_io, rtp_port, rtcp_io, rtcp_port)
+    ) -> RtspServerResult<(RtpIo, RtpIo)> {
+        if protocol.is_udp() {
+            rtp_io_factory.create_pair(peer_addr, peer_rtp_port, peer_rtcp_port).await
         } else if protocol.is_tcp() {
             todo!()
         } else {
-            return Err(RtspServerError::InvalidTransport(format!(
+            Err(RtspServerError::InvalidTransport(format!(
                 "unsupported protocol: {:?}",
                 protocol
-            )));
-        };
-        Ok(((Box::pin(rtp_io), rtp_port), (Box::pin(rtcp_io), rtcp_port)))
-    }
-
-    async fn create_udp_io_pair(
-        peer_ip: IpAddr,
-        peer_rtp_port: u16,
-        peer_rtcp_port: u16,
-    ) -> RtspServerResult<((UdpIO, u16), (UdpIO, u16))> {
-        let (rtp_port, rtp_io) =
-            UdpIO::new_with_remote_addr(random_u16(), SocketAddr::new(peer_ip, peer_rtp_port))
-                .await
-                .map_err(|err| {
-                    tracing::error!("failed to create udp io: {}", err);
-                    RtspServerError::IoError(io::Error::other(format!(
-                        "failed to create udp io: {}",
-                        err
-                    )))
-                })?;
-        let (rtcp_port, rtcp_io) =
-            UdpIO::new_with_remote_addr(rtp_port + 1, SocketAddr::new(peer_ip, peer_rtcp_port))
-                .await
-                .map_err(|err| {
-                    tracing::error!("failed to create udp io: {}", err);
-                    RtspServerError::IoError(io::Error::other(format!(
-                        "failed to create udp io: {}",
-                        err
-                    )))
-                })?;
-        Ok(((rtp_io, rtp_port), (rtcp_io, rtcp_port)))
+            )))
+        }
     }
 
     pub async fn run(&mut self) -> RtspServerResult<()> {
         let span = tracing::debug_span!("rtsp media session",
             session_id = %self.session_id,
-            uri = %self.stream_properities.uri,
+            peer_addr = %self.peer_addr,
+            uri = %self.uri,
             transport = %self.transport,
+            ssrc = self.ssrc,
+            clock_rate = self.rtp_clockrate,
         );
         span.in_scope(async || {
             match &mut self
@@ -489,72 +897,123 @@ impl RtspMediaSession {
                 }
             }
         }).await?;
+        // the commands are taken between the packets, the stop is waited for along with them as well
+        let mut stop_receiver = self.rtsp_session_command_rx.resubscribe();
         loop {
             self.process_commands(&span).await?;
             match &mut self.session_handler {
-                RuntimeHandler::Play { media_frame_receiver, rtp_packetizer } => {
+                RuntimeHandler::Play { media_frame_receiver, rtp_packetizer, play_position, parameter_sets, nominal_frame_rate, speed } => {
                     match tokio::time::timeout(
                         Duration::from_secs(2),
                         Self::process_play(
                             &span,
                             media_frame_receiver,
                             rtp_packetizer,
-                            &mut self.rtp_session_command_tx
+                            play_position,
+                            parameter_sets,
+                            nominal_frame_rate,
+                            *speed,
+                            &mut self.rtp_session_command_tx,
+                            &self.packets_sent,
                         )).await
                     {
                         Err(_) => {},
                         Ok(res) => res?,
                     }
                 }
-                RuntimeHandler::Publish {
-                    media_frame_sender,
-                    rtp_receiver,
-                    rtp_sequencer,
-                    rtp_unpacker,
-                    control: _,
-                    bandwidth: _,
-                    rtpmap,
-                    fmtp,
-                    media_description: _
-                } => {
-                    match tokio::time::timeout(
-                        Duration::from_secs(2),
-                        Self::process_publish(
-                            &span,
-                            rtp_receiver,
-                            rtp_sequencer,
-                            rtp_unpacker,
-                            media_frame_sender,
-                            &mut self.first_rtp_packet_timestamp,
-                            fmtp,
-                            rtpmap,
-                            self.rtp_clockrate)
-                    ).await {
+                RuntimeHandler::Publish(publish) => {
+                    let PublishHandler {
+                        media_frame_sender,
+                        rtp_receiver,
+                        rtp_sequencer,
+                        rtp_demuxer,
+                        dts_deriver,
+                        payload_types,
+                        announced_payload_type,
+                        ingest_limiter,
+                        stream_key,
+                    } = publish.as_mut();
+                    let published = tokio::select! {
+                        published = tokio::time::timeout(
+                            Duration::from_secs(2),
+                            Self::process_publish(
+                                &span,
+                                rtp_receiver,
+                                rtp_sequencer,
+                                rtp_demuxer,
+                                dts_deriver,
+                                media_frame_sender,
+                                &mut self.first_rtp_packet_timestamp,
+                                payload_types,
+                                announced_payload_type,
+                                ingest_limiter,
+                                stream_key)
+                        ) => Some(published),
+                        _ = Self::stop_requested(&mut stop_receiver) => None,
+                    };
+                    let Some(published) = published else {
+                        return span.in_scope(async || self.stop().await).await;
+                    };
+                    match published {
                         Err(_) => {}
+                        Ok(Err(err @ (RtspServerError::IngestLimitExceeded(_) | RtspServerError::OversizeFrame(_)))) => {
+                            tracing::error!("disconnecting publisher: {}", err);
+                            let _ = self.rtp_session_command_tx.send(RtpSessionCommand::Stop).await;
+                            return Err(err);
+                        }
                         Ok(res) => res?,
                     }
                 }
-                RuntimeHandler::None => {
-                    tracing::warn!("no session handler, rtsp media session is idle");
-                    tokio::time::sleep(Duration::from_secs(1)).await;
+                RuntimeHandler::Backchannel { rtp_receiver } => {
+                    match tokio::time::timeout(Duration::from_secs(2), rtp_receiver.recv()).await {
+                        Err(_) => {}
+                        Ok(None) => return Err(RtspServerError::IoError(io::Error::other(
+                            "rtp data channel from rtp session to rtsp media session is closed unexpected",
+                        ))),
+                        Ok(Some(packet)) => span.in_scope(|| {
+                            tracing::trace!("dropping backchannel rtp packet, sequence number: {}", packet.header.sequence_number);
+                        }),
+                    }
                 }
             }
             
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn process_play(
         span: &Span,
         media_frame_receiver: &mut tokio::sync::mpsc::Receiver<MediaFrame>,
         rtp_packetizer: &mut Box<dyn RtpTrivialPacketPacketizer + Send>,
+        play_position: &RtpPlayPosition,
+        parameter_sets: &mut ParameterSetPlacement,
+        nominal_frame_rate: &mut Option<f64>,
+        speed: f64,
         rtp_sender: &mut tokio::sync::mpsc::Sender<RtpSessionCommand>,
+        packets_sent: &AtomicU64,
     ) -> RtspServerResult<()> {
         match media_frame_receiver.recv().await {
             None => Err(RtspServerError::IoError(io::Error::other(
                 "media frame channel from stream center to rtsp media session is closed unexpected",
             ))),
+            // the packets of the frames before it are queued already, the bye goes after them
+            Some(MediaFrame::EndOfStream { reason, .. }) if reason.is_final() => {
+                Self::send_bye(span, rtp_sender, reason.to_string()).await
+            }
+            Some(MediaFrame::SourceError { code, .. }) => {
+                Self::send_bye(span, rtp_sender, code.to_string()).await
+            }
             Some(frame) => span.in_scope(async || {
-                rtp_packetizer.set_frame_timestamp(frame.get_presentation_timestamp_ms());
+                if let MediaFrame::VideoConfig { config, .. } = &frame {
+                    *nominal_frame_rate = config.nominal_frame_rate();
+                }
+                let dts = frame.get_decode_timestamp_ms();
+                rtp_packetizer.set_frame_timestamps(
+                    frame.get_presentation_timestamp_ms(),
+                    dts,
+                );
+                // rtp players take the parameter sets in band, ahead of the IDRs
+                let frame = parameter_sets.place(frame);
                 if let Some(item) = RtpPacketizerItem::from_media_frame(frame) {
                 rtp_packetizer.packetize(item).inspect_err(|err| {
                     tracing::error!("error while packetizing media frame to rtp: {}", err);
@@ -563,8 +1022,12 @@ impl RtspMediaSession {
                     tracing::error!("error while building rtp packets from packetizer: {}", err);
                 })?;
                 for packet in packets {
-                    match rtp_sender.send(RtpSessionCommand::Rtp(packet)).await {
-                        Ok(()) => {}
+                    let (sequence_number, timestamp) = (packet.header.sequence_number, packet.header.timestamp);
+                    match rtp_sender.send(RtpSessionCommand::PacedRtp { packet, dts, nominal_frame_rate: *nominal_frame_rate, speed }).await {
+                        Ok(()) => {
+                            packets_sent.fetch_add(1, Ordering::Relaxed);
+                            play_position.on_packet_sent(sequence_number, timestamp);
+                        }
                         Err(err) => {
                             tracing::error!(
                                 "send rtp packet to rtp session failed: {}",
@@ -583,97 +1046,207 @@ impl RtspMediaSession {
         }
     }
 
+    /// the stream ended, the media session stops once the rtp session sent the bye
+    async fn send_bye(
+        span: &Span,
+        rtp_sender: &mut tokio::sync::mpsc::Sender<RtpSessionCommand>,
+        reason: String,
+    ) -> RtspServerResult<()> {
+        span.in_scope(|| tracing::info!("stream ended, sending rtcp bye, reason: {}", reason));
+        if let Err(err) = rtp_sender.send(RtpSessionCommand::Bye { reason: Some(reason) }).await {
+            span.in_scope(|| tracing::error!("send bye to rtp session failed: {}", err));
+        }
+        Err(RtspServerError::GracefulExit)
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn process_publish(
         span: &Span,
         rtp_rx: &mut tokio::sync::mpsc::Receiver<RtpTrivialPacket>,
         rtp_sequencer: &mut RtpTrivialSequencer,
-        rtp_unpacker: &mut Box<dyn RtpBufferedSequencer + Send>,
+        rtp_demuxer: &mut RtpPayloadDemuxer,
+        dts_deriver: &mut Option<H264DtsDeriver>,
         media_frame_sender: &mut tokio::sync::mpsc::Sender<MediaFrame>,
         first_rtp_timestamp: &mut Option<u32>,
-        fmtp: &Option<FormatParameters>,
-        rtpmap: &RtpMap,
-        rtp_clockrate: u64,
+        payload_types: &PayloadTypeMap,
+        announced_payload_type: &mut Option<u8>,
+        ingest_limiter: &IngestRateLimiter,
+        stream_key: &str,
     ) -> RtspServerResult<()> {
         match rtp_rx.recv().await {
             None => Err(RtspServerError::IoError(io::Error::other(
                 "rtp data channel from rtp session to rtsp media session is closed unexpected",
             ))),
             Some(data) => span.in_scope(async || {
+                ingest_limiter.check(stream_key, data.payload.len()).inspect_err(|err| {
+                    tracing::error!("ingest limit exceeded, stream: {}, err: {}", stream_key, err);
+                })?;
                 rtp_sequencer.enqueue(data).unwrap();
                 let packets =  rtp_sequencer.try_dump();
 
                 for packet in packets {
-                    if let Err(err) = rtp_unpacker.enqueue(packet) {
-                        tracing::error!(
-                            "push new rtp packet to rtp sequencer failed with error: {}",
-                            err
-                        );
+                    match rtp_demuxer.enqueue(packet) {
+                        Ok(()) => {}
+                        Err(err @ RtpError::AccessUnitTooLarge { .. }) => {
+                            tracing::error!("oversize access unit, stream: {}, err: {}", stream_key, err);
+                            ingest_limiter.metrics().on_oversize_rejected();
+                            return Err(RtspServerError::OversizeFrame(err));
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                "push new rtp packet to rtp sequencer failed with error: {}",
+                                err
+                            );
+                        }
                     }
                 }
-                let ready_packets = rtp_unpacker.try_dump();
-                if first_rtp_timestamp.is_none() && !ready_packets.is_empty() {
-                    *first_rtp_timestamp = Some(ready_packets[0].get_presentation_timestamp_ms());
-                    // time to send audio/video configs
-                    if let Some(fmtp) = fmtp {
-                        match rtpmap.encoding_name.to_lowercase().as_str() {
-                            "h264" => {
-                                let h264_fmtp: RtpH264Fmtp = fmtp.params.parse()?;
-                                let config: AvcDecoderConfigurationRecord =
-                                    (&h264_fmtp).try_into()?;
-                                tracing::debug!("make avc decoder configuration record from fmtp: {:#?}", config);
-                                let h264_sequence_header = MediaFrame::VideoConfig {
-                                    timestamp_nano: 0,
-                                        config: Box::new(config.into()),
-                                    };
-                                    
-                                    media_frame_sender.send(h264_sequence_header).await.map_err(|err| {
-                                        tracing::error!("send h264 sequence header to stream center failed: {}", err);
-                                        RtspServerError::IoError(io::Error::other(format!("channel send h264 sequence header to stream center failed: {}", err)))
-                                    })?;
-                                    tracing::info!("publish h264 video sequence header to stream center succeed");
-                                }
-                                "mpeg4-generic" => {
-                                    let aac_fmtp: RtpMpeg4Fmtp = fmtp.params.parse()?;                                    
-                                    let config: AudioSpecificConfig = (&aac_fmtp).try_into()?;
-                                    tracing::debug!("make aac specific config from fmtp: {:#?}", config);
-                                    let aac_sequence_header = MediaFrame::AudioConfig {
-                                        timestamp_nano: 0,
-                                        sound_info: (&config).try_into().map_err(|e| {
-                                            RtspServerError::CodecParametersError(format!("convert aac specific config to sound info failed: {}", e))
-                                        })?,
-                                        config: Box::new(config.into())
-                                    };
-                                    media_frame_sender.send(aac_sequence_header).await.map_err(|err| {
-                                        tracing::error!("send aac sequence header to stream center failed: {}", err);
-                                        RtspServerError::IoError(io::Error::other(format!("channel send aac sequence header to stream center failed: {}", err)))
-                                    })?;
-                                    tracing::info!("publish aac audio sequence header to stream center succeed");
-                                }
-                                _ => {
-                                    unimplemented!()
-                                }
-                            }
+                Self::publish_ready_frames(rtp_demuxer, dts_deriver, media_frame_sender, first_rtp_timestamp, payload_types, announced_payload_type).await
+            }).await,
+        }
+    }
+
+    /// the frames the sequencers have ready go to the stream center,
+    /// after the sequence header of their payload type if it was not sent yet
+    async fn publish_ready_frames(
+        rtp_demuxer: &mut RtpPayloadDemuxer,
+        dts_deriver: &mut Option<H264DtsDeriver>,
+        media_frame_sender: &mut tokio::sync::mpsc::Sender<MediaFrame>,
+        first_rtp_timestamp: &mut Option<u32>,
+        payload_types: &PayloadTypeMap,
+        announced_payload_type: &mut Option<u8>,
+    ) -> RtspServerResult<()> {
+        for (payload_type, ready_packets) in rtp_demuxer.try_dump() {
+            // the demuxer has the sequencers of the mapped payload types only
+            let format = payload_types.get(payload_type).unwrap();
+            let PayloadFormat { rtpmap, fmtp, .. } = format;
+            if first_rtp_timestamp.is_none() {
+                *first_rtp_timestamp = Some(ready_packets[0].get_presentation_timestamp_ms());
+            }
+            // time to send audio/video configs, again once the publisher switches to another payload type
+            if *announced_payload_type != Some(payload_type) {
+                *announced_payload_type = Some(payload_type);
+                if let Some(fmtp) = fmtp {
+                    match rtpmap.encoding_name.to_lowercase().as_str() {
+                        "h264" => {
+                            let h264_fmtp: RtpH264Fmtp = fmtp.params.parse()?;
+                            let config: AvcDecoderConfigurationRecord =
+                                (&h264_fmtp).try_into()?;
+                            tracing::debug!("make avc decoder configuration record from fmtp: {:#?}", config);
+                            let h264_sequence_header = MediaFrame::VideoConfig {
+                                timestamp_nano: 0,
+                                config: Box::new(config.into()),
+                            };
+
+                            media_frame_sender.send(h264_sequence_header).await.map_err(|err| {
+                                tracing::error!("send h264 sequence header to stream center failed: {}", err);
+                                RtspServerError::IoError(io::Error::other(format!("channel send h264 sequence header to stream center failed: {}", err)))
+                            })?;
+                            tracing::info!("publish h264 video sequence header to stream center succeed");
                         }
-                    }
-                    for packet in ready_packets {
-                        match media_frame_sender.send(packet.to_media_frame(first_rtp_timestamp.unwrap(), rtp_clockrate)).await {
-                            Ok(()) => {}
-                            Err(err) => {
-                                tracing::error!(
-                                    "send unpacked rtp media packets to rtsp session failed: {}",
-                                    err
-                                );
-                                return Err(RtspServerError::IoError(io::Error::other(format!(
-                                    "send unpacked rtp media packets to rtsp session failed: {}",
-                                    err
-                                ))));
-                            }
+                        "mpeg4-generic" => {
+                            let aac_fmtp: RtpMpeg4Fmtp = fmtp.params.parse()?;
+                            let config: AudioSpecificConfig = (&aac_fmtp).try_into()?;
+                            tracing::debug!("make aac specific config from fmtp: {:#?}", config);
+                            let aac_sequence_header = MediaFrame::AudioConfig {
+                                timestamp_nano: 0,
+                                sound_info: (&config).try_into()?,
+                                config: Box::new(config.into()),
+                                track_id: 0,
+                            };
+                            media_frame_sender.send(aac_sequence_header).await.map_err(|err| {
+                                tracing::error!("send aac sequence header to stream center failed: {}", err);
+                                RtspServerError::IoError(io::Error::other(format!("channel send aac sequence header to stream center failed: {}", err)))
+                            })?;
+                            tracing::info!("publish aac audio sequence header to stream center succeed");
+                        }
+                        "pcmu" | "pcma" => {
+                            tracing::debug!("g711 has no sequence header, ignore fmtp: {}", fmtp);
+                        }
+                        _ => {
+                            unimplemented!()
                         }
                     }
-                Ok(())
-            }).await,
+                }
+            }
+            let frames = ready_packets.into_iter().map(|packet| packet.to_media_frame(first_rtp_timestamp.unwrap(), format.clock_rate()));
+            let frames: Vec<MediaFrame> = match dts_deriver {
+                Some(dts_deriver) => frames.flat_map(|frame| dts_deriver.push(frame)).collect(),
+                None => frames.collect(),
+            };
+            Self::send_published_frames(media_frame_sender, frames).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_published_frames(
+        media_frame_sender: &mut tokio::sync::mpsc::Sender<MediaFrame>,
+        frames: Vec<MediaFrame>,
+    ) -> RtspServerResult<()> {
+        for frame in frames {
+            match media_frame_sender.send(frame).await {
+                Ok(()) => {}
+                Err(err) => {
+                    tracing::error!(
+                        "send unpacked rtp media packets to rtsp session failed: {}",
+                        err
+                    );
+                    return Err(RtspServerError::IoError(io::Error::other(format!(
+                        "send unpacked rtp media packets to rtsp session failed: {}",
+                        err
+                    ))));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// resolves once the rtsp session is torn down
+    async fn stop_requested(command_rx: &mut tokio::sync::broadcast::Receiver<RtspSessionCommand>) {
+        loop {
+            match command_rx.recv().await {
+                Ok(RtspSessionCommand::Stop) | Err(RecvError::Closed) => return,
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+            }
+        }
+    }
+
+    async fn stop(&mut self) -> RtspServerResult<()> {
+        tracing::info!("rtsp session is stopping");
+        if let Err(err) = self.flush_publish().await {
+            tracing::warn!("failed to flush the published frames: {}", err);
+        }
+        self.rtp_session_command_tx
+            .send(RtpSessionCommand::Stop).await
+            .map_err(|err| {
+                tracing::error!("failed to stop rtp session: {:?}", err);
+                RtspServerError::IoError(io::Error::other(format!(
+                    "failed to stop rtp session: {:?}",
+                    err
+                )))
+            })?;
+        Err(RtspServerError::GracefulExit)
+    }
+
+    /// the publisher is torn down, the access units held for their boundaries and the frames held for their dts
+    /// go to the stream center before it is unpublished
+    async fn flush_publish(&mut self) -> RtspServerResult<()> {
+        let RuntimeHandler::Publish(publish) = &mut self.session_handler else {
+            return Ok(());
+        };
+        let PublishHandler {
+            media_frame_sender,
+            rtp_demuxer,
+            dts_deriver,
+            payload_types,
+            announced_payload_type,
+            ..
+        } = publish.as_mut();
+        rtp_demuxer.flush();
+        Self::publish_ready_frames(rtp_demuxer, dts_deriver, media_frame_sender, &mut self.first_rtp_packet_timestamp, payload_types, announced_payload_type).await?;
+        if let Some(dts_deriver) = dts_deriver {
+            Self::send_published_frames(media_frame_sender, dts_deriver.flush()).await?;
         }
+        Ok(())
     }
 
     async fn process_commands(&mut self, span: &Span) -> RtspServerResult<()> {
@@ -692,19 +1265,7 @@ impl RtspMediaSession {
             Err(_) => Ok(()),
             Ok(command) => match command {
                 RtspSessionCommand::Start => Ok(()),
-                RtspSessionCommand::Stop => {
-                    tracing::info!("rtsp session is stopping");
-                    self.rtp_session_command_tx
-                        .send(RtpSessionCommand::Stop).await
-                        .map_err(|err| {
-                            tracing::error!("failed to stop rtp session: {:?}", err);
-                            RtspServerError::IoError(io::Error::other(format!(
-                                "failed to stop rtp session: {:?}",
-                                err
-                            )))
-                        })?;
-                    Err(RtspServerError::GracefulExit)
-                }
+                RtspSessionCommand::Stop => self.stop().await,
                 RtspSessionCommand::Rtp(packet) => self
                     .rtp_session_command_tx
                     .send(RtpSessionCommand::Rtp(packet)).await
@@ -725,6 +1286,13 @@ impl RtspMediaSession {
                             err
                         )))
                     }),
+                RtspSessionCommand::Speed(new_speed) => {
+                    if let RuntimeHandler::Play { speed, .. } = &mut self.session_handler {
+                        tracing::info!("play speed changes from {} to {}", speed, new_speed);
+                        *speed = new_speed;
+                    }
+                    Ok(())
+                }
             },
         }).await
     }
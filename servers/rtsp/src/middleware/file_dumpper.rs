@@ -1,50 +1,58 @@
-use crate::middleware::RtspMiddleware;
+use std::{ops::ControlFlow, sync::Mutex};
+
+use crate::middleware::{RtspMiddleware, SessionContext};
 use debug_tools::dump::DumpTool;
+use futures::future::BoxFuture;
+use rtsp_formats::{request::RtspRequest, response::RtspResponse};
+use utils::traits::writer::WriteTo;
 
 pub struct DialogFileDumpper {
-    file_dump: debug_tools::dump::file_dump::FileDump,
+    file_dump: Mutex<debug_tools::dump::file_dump::FileDump>,
 }
 
 impl DialogFileDumpper {
     pub fn new(file_path: &str) -> Self {
         Self {
-            file_dump: debug_tools::dump::file_dump::FileDump::new(file_path).unwrap(),
+            file_dump: Mutex::new(debug_tools::dump::file_dump::FileDump::new(file_path).unwrap()),
         }
     }
 }
 
 impl RtspMiddleware for DialogFileDumpper {
-    fn pre_request(
-        &mut self,
-        request: rtsp_formats::request::RtspRequest,
-    ) -> crate::errors::RtspServerResult<rtsp_formats::request::RtspRequest> {
-        self.file_dump
+    fn on_request<'a>(
+        &'a self,
+        request: &'a mut RtspRequest,
+        _context: &'a SessionContext,
+    ) -> BoxFuture<'a, ControlFlow<RtspResponse>> {
+        let mut file_dump = self.file_dump.lock().unwrap();
+        file_dump
             .dump_bytes(&format!("--- REQUEST {} ---\n", chrono::Utc::now()).as_bytes())
             .unwrap();
-        self.file_dump
-            .dump_bytes(&format!("{}", request).as_bytes())
-            .unwrap();
-        Ok(request)
+        let mut bytes = Vec::new();
+        request.write_to(&mut bytes).unwrap();
+        file_dump.dump_bytes(&bytes).unwrap();
+        Box::pin(async { ControlFlow::Continue(()) })
     }
 
-    fn pre_response(
-        &mut self,
-        request: &rtsp_formats::request::RtspRequest,
-        response: rtsp_formats::response::RtspResponse,
-    ) -> crate::errors::RtspServerResult<rtsp_formats::response::RtspResponse> {
-        self.file_dump
+    fn on_response<'a>(
+        &'a self,
+        response: &'a mut RtspResponse,
+        context: &'a SessionContext,
+    ) -> BoxFuture<'a, ()> {
+        let mut file_dump = self.file_dump.lock().unwrap();
+        file_dump
             .dump_bytes(
                 &format!(
                     "--- RESPONSE to {} AT {} ---\n",
-                    request.method(),
+                    context.method,
                     chrono::Utc::now()
                 )
                 .as_bytes(),
             )
             .unwrap();
-        self.file_dump
-            .dump_bytes(&format!("{}", response).as_bytes())
-            .unwrap();
-        Ok(response)
+        let mut bytes = Vec::new();
+        response.write_to(&mut bytes).unwrap();
+        file_dump.dump_bytes(&bytes).unwrap();
+        Box::pin(async {})
     }
 }
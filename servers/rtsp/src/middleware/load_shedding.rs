@@ -0,0 +1,179 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    net::SocketAddr,
+    ops::ControlFlow,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use super::{RtspMiddleware, SessionContext};
+use futures::future::BoxFuture;
+use rtsp_formats::{
+    consts::{methods::RtspMethod, status::RtspStatus},
+    header::RtspHeader,
+    request::RtspRequest,
+    response::RtspResponse,
+};
+use url::Url;
+
+/// a load of the server set by whoever measures it, e.g., the cpu usage sampled by the embedder
+#[derive(Debug, Clone, Default)]
+pub struct LoadGauge(Arc<AtomicU64>);
+
+impl LoadGauge {
+    pub fn set(&self, load: f64) {
+        self.0.store(load.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// when the server is too loaded to take new players
+#[derive(Debug, Clone)]
+pub enum LoadThreshold {
+    /// the connections other than the one asking reach the count
+    Sessions(usize),
+    /// the gauge reaches the load
+    Gauge { gauge: LoadGauge, max_load: f64 },
+}
+
+impl LoadThreshold {
+    pub fn is_crossed(&self, sessions: usize) -> bool {
+        match self {
+            Self::Sessions(max_sessions) => sessions >= *max_sessions,
+            Self::Gauge { gauge, max_load } => gauge.get() >= *max_load,
+        }
+    }
+}
+
+/// picks the server a player is redirected to, `host[:port]`,
+/// None serves the player here despite the load
+pub trait RedirectPolicy: Send + Sync + fmt::Debug {
+    fn target<'a>(&'a self, context: &'a SessionContext) -> BoxFuture<'a, Option<String>>;
+}
+
+/// the targets in turn
+#[derive(Debug)]
+pub struct RoundRobinRedirect {
+    targets: Vec<String>,
+    next: AtomicUsize,
+}
+
+impl RoundRobinRedirect {
+    pub fn new(targets: Vec<String>) -> Self {
+        Self {
+            targets,
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl RedirectPolicy for RoundRobinRedirect {
+    fn target<'a>(&'a self, _context: &'a SessionContext) -> BoxFuture<'a, Option<String>> {
+        let target = match self.targets.len() {
+            0 => None,
+            len => Some(self.targets[self.next.fetch_add(1, Ordering::Relaxed) % len].clone()),
+        };
+        Box::pin(async { target })
+    }
+}
+
+/// the uri of the stream of `uri` on `target`, `host[:port]`, with the query of `uri`.
+/// the path is cut to the app and the stream, the track controls of the server are left out
+pub fn redirected_uri(uri: &Url, target: &str) -> Option<Url> {
+    let mut redirected = Url::parse(&format!("{}://{}/", uri.scheme(), target)).ok()?;
+    if redirected.host_str().is_none() || redirected.path() != "/" || redirected.query().is_some() {
+        return None;
+    }
+    let segments: Vec<_> = uri.path_segments()?.take(2).collect();
+    // the segments are still percent encoded
+    redirected.set_path(&segments.join("/"));
+    redirected.set_query(uri.query());
+    Some(redirected)
+}
+
+/// answers 302 with the server picked by the policy to the players asking for a stream
+/// while the threshold is crossed, DESCRIBE and a PLAY not set up are asked by players only.
+/// a connection is counted from its first request until it is gone
+/// @see: RFC 7826 Section 17.3.3
+#[derive(Debug)]
+pub struct LoadShedder {
+    threshold: LoadThreshold,
+    policy: Arc<dyn RedirectPolicy>,
+    sessions: Mutex<HashSet<SocketAddr>>,
+}
+
+impl LoadShedder {
+    pub fn new(threshold: LoadThreshold, policy: Arc<dyn RedirectPolicy>) -> Self {
+        Self {
+            threshold,
+            policy,
+            sessions: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// counts the connection from `peer_addr` and tells whether the others cross the threshold
+    fn is_overloaded(&self, peer_addr: SocketAddr) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.insert(peer_addr);
+        self.threshold.is_crossed(sessions.len() - 1)
+    }
+
+    async fn shed(
+        &self,
+        request: &RtspRequest,
+        context: &SessionContext,
+    ) -> ControlFlow<RtspResponse> {
+        let Some(target) = self.policy.target(context).await else {
+            tracing::warn!("rtsp server is overloaded, no server to redirect to, serving here");
+            return ControlFlow::Continue(());
+        };
+        let Some(location) = redirected_uri(request.uri(), &target) else {
+            tracing::error!("invalid redirect target {}, serving here", target);
+            return ControlFlow::Continue(());
+        };
+        tracing::info!(
+            "rtsp server is overloaded, redirect {} to {}",
+            context.peer_addr,
+            location
+        );
+        ControlFlow::Break(
+            RtspResponse::builder()
+                .status(RtspStatus::Found)
+                .header(RtspHeader::Location, location.to_string())
+                .build()
+                .unwrap(),
+        )
+    }
+}
+
+impl RtspMiddleware for LoadShedder {
+    fn on_request<'a>(
+        &'a self,
+        request: &'a mut RtspRequest,
+        context: &'a SessionContext,
+    ) -> BoxFuture<'a, ControlFlow<RtspResponse>> {
+        let overloaded = self.is_overloaded(context.peer_addr);
+        let asks_for_stream = match request.method() {
+            RtspMethod::Describe => true,
+            RtspMethod::Play => context.session_id.is_none(),
+            _ => false,
+        };
+        Box::pin(async move {
+            if overloaded && asks_for_stream {
+                self.shed(request, context).await
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+    }
+
+    fn on_session_end(&self, peer_addr: SocketAddr) {
+        self.sessions.lock().unwrap().remove(&peer_addr);
+    }
+}
@@ -1,19 +1,106 @@
-use crate::errors::RtspServerResult;
-use rtsp_formats::{request::RtspRequest, response::RtspResponse};
+use futures::future::BoxFuture;
+use rtsp_formats::{
+    consts::methods::RtspMethod,
+    header::{transport::TransportHeader, via::ViaHeader},
+    request::RtspRequest,
+    response::RtspResponse,
+};
+use std::{fmt, net::SocketAddr, ops::ControlFlow, sync::Arc, time::Instant};
+use url::Url;
+
 pub mod file_dumpper;
+pub mod load_shedding;
+pub mod request_logger;
 pub mod response_header_appender;
+pub mod session_limiter;
+
+/// what a middleware knows about the session and the request being handled
+#[derive(Debug, Clone)]
+pub struct SessionContext {
+    pub peer_addr: SocketAddr,
+    /// None until a SETUP succeeds
+    pub session_id: Option<String>,
+    /// the transport answered to the latest SETUP
+    pub transport: Option<TransportHeader>,
+    pub method: RtspMethod,
+    pub uri: Url,
+    pub cseq: Option<u32>,
+    /// the proxies the request passed, the peer is the last of them
+    pub via: Option<ViaHeader>,
+    /// when the request was read from the connection
+    pub received_at: Instant,
+}
+
+/// cross-cutting behavior plugged around the request handlers of rtsp sessions,
+/// a middleware registered on the server is shared by all its sessions
+pub trait RtspMiddleware: Send + Sync {
+    /// `Break` answers the request right away,
+    /// neither the later middlewares nor the request handler see it then
+    fn on_request<'a>(
+        &'a self,
+        request: &'a mut RtspRequest,
+        context: &'a SessionContext,
+    ) -> BoxFuture<'a, ControlFlow<RtspResponse>> {
+        let _ = (request, context);
+        Box::pin(async { ControlFlow::Continue(()) })
+    }
+
+    /// sees every response, including the ones a middleware answered early with
+    fn on_response<'a>(
+        &'a self,
+        response: &'a mut RtspResponse,
+        context: &'a SessionContext,
+    ) -> BoxFuture<'a, ()> {
+        let _ = (response, context);
+        Box::pin(async {})
+    }
+
+    /// the connection of the session is gone
+    fn on_session_end(&self, peer_addr: SocketAddr) {
+        let _ = peer_addr;
+    }
+}
+
+/// requests go through the middlewares in registration order,
+/// responses in the reverse order so the first middleware has the last word
+#[derive(Clone, Default)]
+pub struct RtspMiddlewareChain {
+    middlewares: Vec<Arc<dyn RtspMiddleware>>,
+}
+
+impl fmt::Debug for RtspMiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rtsp middlewares", self.middlewares.len())
+    }
+}
+
+impl RtspMiddlewareChain {
+    pub fn push(&mut self, middleware: Arc<dyn RtspMiddleware>) {
+        self.middlewares.push(middleware);
+    }
+
+    pub async fn on_request(
+        &self,
+        request: &mut RtspRequest,
+        context: &SessionContext,
+    ) -> ControlFlow<RtspResponse> {
+        for middleware in &self.middlewares {
+            if let ControlFlow::Break(response) = middleware.on_request(request, context).await {
+                return ControlFlow::Break(response);
+            }
+        }
+        ControlFlow::Continue(())
+    }
 
-pub trait RtspMiddleware {
-    fn pre_request(&mut self, request: RtspRequest) -> RtspServerResult<RtspRequest> {
-        Ok(request)
+    pub async fn on_response(&self, response: &mut RtspResponse, context: &SessionContext) {
+        for middleware in self.middlewares.iter().rev() {
+            middleware.on_response(response, context).await;
+        }
     }
 
-    fn pre_response(
-        &mut self,
-        request: &RtspRequest,
-        response: RtspResponse,
-    ) -> RtspServerResult<RtspResponse> {
-        let _ = request;
-        Ok(response)
+    pub fn on_session_end(&self, peer_addr: SocketAddr) {
+        for middleware in &self.middlewares {
+            middleware.on_session_end(peer_addr);
+        }
     }
 }
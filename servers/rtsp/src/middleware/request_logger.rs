@@ -0,0 +1,28 @@
+use super::{RtspMiddleware, SessionContext};
+use futures::future::BoxFuture;
+use rtsp_formats::response::RtspResponse;
+
+/// one structured log line per request, with the time taken to answer it
+#[derive(Debug, Default)]
+pub struct RequestLogger;
+
+impl RtspMiddleware for RequestLogger {
+    fn on_response<'a>(
+        &'a self,
+        response: &'a mut RtspResponse,
+        context: &'a SessionContext,
+    ) -> BoxFuture<'a, ()> {
+        tracing::info!(
+            peer_addr = %context.peer_addr,
+            session_id = ?context.session_id,
+            method = %context.method,
+            uri = %context.uri,
+            cseq = ?context.cseq,
+            via = ?context.via.as_ref().map(|via| via.to_string()),
+            status = response.status() as u16,
+            latency_us = context.received_at.elapsed().as_micros() as u64,
+            "rtsp request handled"
+        );
+        Box::pin(async {})
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    ops::ControlFlow,
+    sync::Mutex,
+};
+
+use super::{RtspMiddleware, SessionContext};
+use crate::rtsp_server_simple_response;
+use futures::future::BoxFuture;
+use rtsp_formats::{consts::status::RtspStatus, request::RtspRequest, response::RtspResponse};
+
+/// answers 503 to the connections of an ip beyond the first `max_sessions_per_ip`,
+/// a connection is counted from its first request until it is gone
+#[derive(Debug)]
+pub struct SessionLimiter {
+    max_sessions_per_ip: usize,
+    sessions: Mutex<HashMap<IpAddr, HashSet<SocketAddr>>>,
+}
+
+impl SessionLimiter {
+    pub fn new(max_sessions_per_ip: usize) -> Self {
+        Self {
+            max_sessions_per_ip,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// whether the connection from `peer_addr` is within the limit of its ip
+    fn admit(&self, peer_addr: SocketAddr) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let sessions = sessions.entry(peer_addr.ip()).or_default();
+        if sessions.contains(&peer_addr) {
+            return true;
+        }
+        if sessions.len() >= self.max_sessions_per_ip {
+            return false;
+        }
+        sessions.insert(peer_addr);
+        true
+    }
+}
+
+impl RtspMiddleware for SessionLimiter {
+    fn on_request<'a>(
+        &'a self,
+        _request: &'a mut RtspRequest,
+        context: &'a SessionContext,
+    ) -> BoxFuture<'a, ControlFlow<RtspResponse>> {
+        let result = if self.admit(context.peer_addr) {
+            ControlFlow::Continue(())
+        } else {
+            tracing::warn!(
+                "too many rtsp sessions from {}, limit: {}",
+                context.peer_addr.ip(),
+                self.max_sessions_per_ip
+            );
+            ControlFlow::Break(rtsp_server_simple_response(RtspStatus::ServiceUnavailable))
+        };
+        Box::pin(async { result })
+    }
+
+    fn on_session_end(&self, peer_addr: SocketAddr) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(addrs) = sessions.get_mut(&peer_addr.ip()) {
+            addrs.remove(&peer_addr);
+            if addrs.is_empty() {
+                sessions.remove(&peer_addr.ip());
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    str::FromStr,
+    sync::{Arc, Mutex, atomic::AtomicBool},
+};
+
+use rtp_session::{
+    capture::CaptureTap, fec::FecConfig, retransmission::RetransmissionConfig,
+    sdes::SourceDescription,
+};
+use rtsp_formats::{
+    header::transport::{TransportCast, TransportHeader, TransportProtocol},
+    sdp_extension::{attribute::RtspSDPControl, media::is_onvif_backchannel},
+};
+use sdp_formats::{
+    attributes::SDPAttribute,
+    session::{SDPMediaType, Sdp},
+};
+use server_utils::{runtime_handle::PlayHandle, stream_properities::StreamProperties};
+use stream_center::{
+    events::StreamCenterEvent,
+    identity::StreamIdentity,
+    stream_center::StreamCenter,
+    stream_source::{MediaSelection, PlayProtocol},
+};
+use tokio::sync::{RwLock, broadcast, mpsc::UnboundedSender};
+use unified_io::udp::MulticastOptions;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    errors::{RtspServerError, RtspServerResult},
+    media_session::{DEFAULT_RTP_PACKET_SIZE, RtspMediaSession, RtspSessionCommand},
+    payload_type_map::PayloadTypeMap,
+    rtp_io::MulticastRtpIoFactory,
+    sdes::SessionSdes,
+    session::spawn_frame_distribution,
+};
+
+/// where the rtp of a stream is multicast to.
+/// the n-th media of the stream is sent to port + 2n, its rtcp to the port right after
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastGroup {
+    pub address: Ipv4Addr,
+    pub port: u16,
+    pub options: MulticastOptions,
+}
+
+impl MulticastGroup {
+    /// the rtp and rtcp ports of the media at `index` of the sdp
+    pub fn media_ports(&self, index: usize) -> Option<(u16, u16)> {
+        let rtp_port = u16::try_from(index)
+            .ok()?
+            .checked_mul(2)?
+            .checked_add(self.port)?;
+        Some((rtp_port, rtp_port.checked_add(1)?))
+    }
+
+    /// the transport answered to a SETUP of the media at `index` asking for multicast
+    pub fn transport(&self, requested: &TransportHeader, index: usize) -> Option<TransportHeader> {
+        Some(TransportHeader {
+            profile: Some(TransportProtocol::RtpAvpUdp),
+            cast: Some(TransportCast::Multicast),
+            ttl: u8::try_from(self.options.ttl).ok(),
+            mode: requested.mode.clone(),
+            port: Some(self.media_ports(index)?),
+            destination: Some(self.address.to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+/// `<group>:<port>[/<ttl>]`, e.g. 239.255.0.1:5004/16, the port is the even rtp port
+impl FromStr for MulticastGroup {
+    type Err = RtspServerError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RtspServerError::InvalidMulticastGroup(s.to_owned());
+        let (addr, ttl) = match s.trim().split_once('/') {
+            Some((addr, ttl)) => (addr, Some(ttl.trim().parse::<u8>().map_err(|_| invalid())?)),
+            None => (s.trim(), None),
+        };
+        let addr: SocketAddrV4 = addr.trim().parse().map_err(|_| invalid())?;
+        if !addr.ip().is_multicast() || !addr.port().is_multiple_of(2) || ttl == Some(0) {
+            return Err(invalid());
+        }
+        let mut options = MulticastOptions::default();
+        if let Some(ttl) = ttl {
+            options.ttl = ttl as u32;
+        }
+        Ok(Self {
+            address: *addr.ip(),
+            port: addr.port(),
+            options,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ActiveDelivery {
+    /// the subscription of the delivery, tells it from a later delivery of the same stream
+    play_id: Uuid,
+    sessions: usize,
+    rtsp_command_tx: broadcast::Sender<RtspSessionCommand>,
+}
+
+type ActiveDeliveries = Arc<Mutex<HashMap<StreamIdentity, ActiveDelivery>>>;
+
+/// the multicast groups of streams and the deliveries to them, shared by the sessions of a server.
+/// a stream is subscribed once for its group however many sessions play it
+#[derive(Debug, Clone, Default)]
+pub struct MulticastDeliveries {
+    groups: Arc<HashMap<StreamIdentity, MulticastGroup>>,
+    active: ActiveDeliveries,
+}
+
+impl MulticastDeliveries {
+    pub fn new(groups: HashMap<StreamIdentity, MulticastGroup>) -> Self {
+        Self {
+            groups: Arc::new(groups),
+            active: Default::default(),
+        }
+    }
+
+    pub fn group(&self, stream_id: &StreamIdentity) -> Option<MulticastGroup> {
+        self.groups.get(stream_id).copied()
+    }
+
+    /// sessions playing the stream from its group, its receivers are not known
+    pub fn sessions(&self, stream_id: &StreamIdentity) -> usize {
+        self.active
+            .lock()
+            .unwrap()
+            .get(stream_id)
+            .map_or(0, |delivery| delivery.sessions)
+    }
+
+    /// joins a session to the delivery of the stream to its group,
+    /// the first session starts the delivery
+    #[allow(clippy::too_many_arguments)]
+    pub async fn join(
+        &self,
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_prop: &StreamProperties,
+        uri: &Url,
+        sdp: &Sdp,
+        retransmission: RetransmissionConfig,
+        fec: FecConfig,
+        local: SourceDescription,
+    ) -> RtspServerResult<MulticastLease> {
+        let stream_id = stream_prop.stream_id.clone();
+        if let Some(lease) = self.attach(&stream_id) {
+            return Ok(lease);
+        }
+        let group = self.group(&stream_id).ok_or_else(|| {
+            RtspServerError::InvalidTransport(format!("no multicast group for {}", stream_id))
+        })?;
+        let delivery = start_delivery(
+            stream_center_event_sender,
+            &stream_id,
+            stream_prop,
+            uri,
+            sdp,
+            group,
+            retransmission,
+            fec,
+            local,
+            self.active.clone(),
+        )
+        .await?;
+        let mut active = self.active.lock().unwrap();
+        if let Some(existing) = active.get_mut(&stream_id) {
+            // another session started a delivery meanwhile
+            let _ = delivery.rtsp_command_tx.send(RtspSessionCommand::Stop);
+            existing.sessions += 1;
+            return Ok(self.lease(stream_id, existing.play_id));
+        }
+        let play_id = delivery.play_id;
+        active.insert(stream_id.clone(), delivery);
+        Ok(self.lease(stream_id, play_id))
+    }
+
+    fn attach(&self, stream_id: &StreamIdentity) -> Option<MulticastLease> {
+        let mut active = self.active.lock().unwrap();
+        let delivery = active.get_mut(stream_id)?;
+        delivery.sessions += 1;
+        Some(self.lease(stream_id.clone(), delivery.play_id))
+    }
+
+    fn lease(&self, stream_id: StreamIdentity, play_id: Uuid) -> MulticastLease {
+        MulticastLease {
+            stream_id,
+            play_id,
+            active: self.active.clone(),
+        }
+    }
+}
+
+/// held by a session playing a stream from its group, the delivery stops with the last lease
+#[derive(Debug)]
+pub struct MulticastLease {
+    stream_id: StreamIdentity,
+    play_id: Uuid,
+    active: ActiveDeliveries,
+}
+
+impl Drop for MulticastLease {
+    fn drop(&mut self) {
+        let mut active = self.active.lock().unwrap();
+        let Some(delivery) = active
+            .get_mut(&self.stream_id)
+            .filter(|delivery| delivery.play_id == self.play_id)
+        else {
+            return;
+        };
+        delivery.sessions -= 1;
+        if delivery.sessions == 0 {
+            tracing::info!(
+                "last session of multicast delivery left, stream: {}",
+                self.stream_id
+            );
+            let delivery = active.remove(&self.stream_id).unwrap();
+            let _ = delivery.rtsp_command_tx.send(RtspSessionCommand::Stop);
+        }
+    }
+}
+
+/// a play media session sending to the group for each media of the sdp, fed by one subscription
+#[allow(clippy::too_many_arguments)]
+async fn start_delivery(
+    stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+    stream_id: &StreamIdentity,
+    stream_prop: &StreamProperties,
+    uri: &Url,
+    sdp: &Sdp,
+    group: MulticastGroup,
+    retransmission: RetransmissionConfig,
+    fec: FecConfig,
+    local: SourceDescription,
+    active: ActiveDeliveries,
+) -> RtspServerResult<ActiveDelivery> {
+    let (rtsp_command_tx, _) = broadcast::channel(1000);
+    let sdes = SessionSdes::new(local);
+    let rtp_io_factory = MulticastRtpIoFactory::new(group.options);
+    let session_id = format!("multicast-{}", Uuid::now_v7());
+    let mut frame_distributors = Vec::new();
+    for (index, media) in sdp.media_description.iter().enumerate() {
+        if is_onvif_backchannel(media) {
+            continue;
+        }
+        let control = media.attributes.iter().find_map(|attr| match attr {
+            SDPAttribute::Trivial(attr) if attr.name == "control" => {
+                RtspSDPControl::try_from(attr).ok()
+            }
+            _ => None,
+        });
+        let rtpmap = PayloadTypeMap::from_media(media)
+            .preferred(&media.media_line.media_type)
+            .map(|format| format.rtpmap.clone());
+        let (Some(control), Some(rtpmap)) = (control, rtpmap) else {
+            tracing::warn!("media control or rtpmap attribute not found: {:?}", media);
+            continue;
+        };
+        let transport = group
+            .transport(&TransportHeader::default(), index)
+            .ok_or_else(|| {
+                RtspServerError::InvalidTransport(format!(
+                    "no ports left in multicast group for media {}",
+                    index
+                ))
+            })?;
+        let (media_frame_tx, media_frame_rx) = tokio::sync::mpsc::channel(1000);
+        let media_session = RtspMediaSession::new_play_session(
+            SocketAddr::from((group.address, 0)),
+            uri.clone(),
+            &control,
+            media,
+            &rtpmap,
+            session_id.clone(),
+            transport,
+            rtsp_command_tx.subscribe(),
+            media_frame_rx,
+            retransmission,
+            fec,
+            // the receivers of the group join it at any time, the delivery has no startup to pace
+            None,
+            DEFAULT_RTP_PACKET_SIZE,
+            &sdes,
+            // shared by the receivers of the group, none of their sessions captures it
+            &CaptureTap::default(),
+            &rtp_io_factory,
+        )
+        .await;
+        let mut media_session = match media_session {
+            Ok(media_session) => media_session,
+            Err(err) => {
+                let _ = rtsp_command_tx.send(RtspSessionCommand::Stop);
+                return Err(err);
+            }
+        };
+        tokio::task::spawn(async move {
+            if let Err(err) = media_session.run().await {
+                tracing::error!("multicast media session error: {}", err);
+            }
+        });
+        frame_distributors.push((
+            matches!(media.media_line.media_type, SDPMediaType::Video),
+            media_frame_tx,
+        ));
+    }
+
+    let media_selection = MediaSelection {
+        audio: frame_distributors.iter().any(|(is_video, _)| !is_video),
+        video: frame_distributors.iter().any(|(is_video, _)| *is_video),
+        data: false,
+        ..Default::default()
+    };
+    let subscribe_response = match StreamCenter::subscribe(
+        stream_center_event_sender,
+        PlayProtocol::RTSPMulticast,
+        stream_id,
+        &stream_prop.stream_context,
+        media_selection,
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(err) => {
+            let _ = rtsp_command_tx.send(RtspSessionCommand::Stop);
+            return Err(err.into());
+        }
+    };
+    let play_id = subscribe_response.subscribe_id;
+    sdes.attach(
+        stream_center_event_sender.clone(),
+        stream_id.clone(),
+        Some(play_id),
+    );
+    tracing::info!(
+        "multicast delivery started, stream: {}, group: {}:{}, play id: {}",
+        stream_id,
+        group.address,
+        group.port,
+        play_id
+    );
+    let distribution = spawn_frame_distribution(
+        Arc::new(RwLock::new(PlayHandle {
+            stream_data_consumer: subscribe_response.media_receiver,
+            play_id,
+            receive_audio: media_selection.audio,
+            receive_video: media_selection.video,
+            audio_track: media_selection.audio_track,
+            buffer_length: None,
+            latency_probe: subscribe_response.latency_probe,
+        })),
+        frame_distributors,
+        // the group goes on for the other receivers while one of them pauses
+        Arc::new(AtomicBool::new(false)),
+        rtsp_command_tx.clone(),
+        // one delivery serves all the receivers of the group, it is not shaped per receiver
+        None,
+        // the media sessions of the group send the bye, the sessions of the receivers go on
+        None,
+    );
+    let stream_center_event_sender = stream_center_event_sender.clone();
+    let stream_id = stream_id.clone();
+    tokio::task::spawn(async move {
+        let _ = distribution.await;
+        // the stream may end before the sessions leave, the later ones start a new delivery
+        {
+            let mut active = active.lock().unwrap();
+            if active
+                .get(&stream_id)
+                .is_some_and(|delivery| delivery.play_id == play_id)
+            {
+                active.remove(&stream_id);
+            }
+        }
+        if let Err(err) =
+            StreamCenter::unsubscribe(&stream_center_event_sender, play_id, &stream_id).await
+        {
+            tracing::warn!("multicast delivery unsubscribe failed: {}", err);
+        }
+        tracing::info!("multicast delivery stopped, stream: {}", stream_id);
+    });
+    Ok(ActiveDelivery {
+        play_id,
+        sessions: 1,
+        rtsp_command_tx,
+    })
+}
@@ -0,0 +1,308 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use rtp_formats::codec::h264::packet::sequencer::budget::RtpH264BufferMetrics;
+use rtp_session::{fec::FecMetrics, retransmission::RetransmissionMetrics};
+
+use crate::errors::RtspServerError;
+
+/// Parameters known to GET_PARAMETER and SET_PARAMETER
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtspParameter {
+    /// npt seconds since PLAY
+    Position,
+    StreamName,
+    PacketsSent,
+    Speed,
+    NacksReceived,
+    PacketsRetransmitted,
+    /// bytes held by the sequencers of a publish session
+    BytesBuffered,
+    BufferDiscontinuities,
+    /// packets of a publish session dropped for payload types the sdp did not map
+    UnknownPayloadTypePackets,
+    /// the ulpfec parity packets sent to a player
+    FecPacketsSent,
+    /// the bytes of the red and ulpfec protection over the bytes of the media sent to a player
+    FecOverhead,
+}
+
+impl RtspParameter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Position => "position",
+            Self::StreamName => "stream_name",
+            Self::PacketsSent => "packets_sent",
+            Self::Speed => "speed",
+            Self::NacksReceived => "nacks_received",
+            Self::PacketsRetransmitted => "packets_retransmitted",
+            Self::BytesBuffered => "bytes_buffered",
+            Self::BufferDiscontinuities => "buffer_discontinuities",
+            Self::UnknownPayloadTypePackets => "unknown_payload_type_packets",
+            Self::FecPacketsSent => "fec_packets_sent",
+            Self::FecOverhead => "fec_overhead",
+        }
+    }
+}
+
+impl fmt::Display for RtspParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for RtspParameter {
+    type Err = RtspServerError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "position" => Ok(Self::Position),
+            "stream_name" => Ok(Self::StreamName),
+            "packets_sent" => Ok(Self::PacketsSent),
+            "speed" => Ok(Self::Speed),
+            "nacks_received" => Ok(Self::NacksReceived),
+            "packets_retransmitted" => Ok(Self::PacketsRetransmitted),
+            "bytes_buffered" => Ok(Self::BytesBuffered),
+            "buffer_discontinuities" => Ok(Self::BufferDiscontinuities),
+            "unknown_payload_type_packets" => Ok(Self::UnknownPayloadTypePackets),
+            "fec_packets_sent" => Ok(Self::FecPacketsSent),
+            "fec_overhead" => Ok(Self::FecOverhead),
+            _ => Err(RtspServerError::InvalidRequest(format!(
+                "unknown parameter: {}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RtspParameterStore {
+    stream_name: Option<String>,
+    play_started_at: Option<Instant>,
+    /// the position stands still while paused
+    paused_at: Option<Instant>,
+    speed: f64,
+    is_live: bool,
+    packets_sent: Vec<Arc<AtomicU64>>,
+    retransmission_metrics: Vec<Arc<RetransmissionMetrics>>,
+    fec_metrics: Vec<Arc<FecMetrics>>,
+    buffer_metrics: Vec<Arc<RtpH264BufferMetrics>>,
+    unknown_payload_type_packets: Vec<Arc<AtomicU64>>,
+}
+
+impl Default for RtspParameterStore {
+    fn default() -> Self {
+        Self {
+            stream_name: None,
+            play_started_at: None,
+            paused_at: None,
+            speed: 1.0,
+            // until the PLAY finds the stream replays a recording
+            is_live: true,
+            packets_sent: Vec::new(),
+            retransmission_metrics: Vec::new(),
+            fec_metrics: Vec::new(),
+            buffer_metrics: Vec::new(),
+            unknown_payload_type_packets: Vec::new(),
+        }
+    }
+}
+
+impl RtspParameterStore {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_stream_name<S: Into<String>>(&mut self, stream_name: S) {
+        self.stream_name = Some(stream_name.into());
+    }
+
+    /// a speed set before is clamped again if the stream turns out to be live
+    pub fn set_live(&mut self, is_live: bool) {
+        self.is_live = is_live;
+        if is_live {
+            self.speed = 1.0;
+        }
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.is_live
+    }
+
+    pub fn on_play(&mut self, now: Instant) {
+        self.play_started_at = Some(now);
+        self.paused_at = None;
+    }
+
+    pub fn on_pause(&mut self, now: Instant) {
+        if self.play_started_at.is_some() && self.paused_at.is_none() {
+            self.paused_at = Some(now);
+        }
+    }
+
+    /// returns how long the play was paused.
+    /// live streams resume from the live edge, so their position moves on by the pause duration
+    pub fn on_resume(&mut self, now: Instant) -> Duration {
+        let paused_for = self
+            .paused_at
+            .take()
+            .map(|paused_at| now.saturating_duration_since(paused_at))
+            .unwrap_or_default();
+        if !self.is_live
+            && let Some(started) = self.play_started_at.as_mut()
+        {
+            *started += paused_for;
+        }
+        paused_for
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// the counter is increased by the media session for each rtp packet it sends
+    pub fn add_packets_counter(&mut self, counter: Arc<AtomicU64>) {
+        self.packets_sent.push(counter);
+    }
+
+    /// updated by the rtp session of each play media session
+    pub fn add_retransmission_metrics(&mut self, metrics: Arc<RetransmissionMetrics>) {
+        self.retransmission_metrics.push(metrics);
+    }
+
+    /// updated by the rtp session of each play media session
+    pub fn add_fec_metrics(&mut self, metrics: Arc<FecMetrics>) {
+        self.fec_metrics.push(metrics);
+    }
+
+    /// updated by the sequencer of each publish media session
+    pub fn add_buffer_metrics(&mut self, metrics: Arc<RtpH264BufferMetrics>) {
+        self.buffer_metrics.push(metrics);
+    }
+
+    /// increased by the demuxer of each publish media session
+    pub fn add_unknown_payload_type_counter(&mut self, counter: Arc<AtomicU64>) {
+        self.unknown_payload_type_packets.push(counter);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self {
+            is_live: self.is_live,
+            ..Default::default()
+        };
+    }
+
+    pub fn position_at(&self, now: Instant) -> f64 {
+        let now = self.paused_at.map_or(now, |paused_at| paused_at.min(now));
+        self.play_started_at
+            .map(|started| now.saturating_duration_since(started).as_secs_f64() * self.speed)
+            .unwrap_or_default()
+    }
+
+    pub fn packets_sent(&self) -> u64 {
+        self.packets_sent
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn nacks_received(&self) -> u64 {
+        self.retransmission_metrics
+            .iter()
+            .map(|metrics| metrics.nacks_received())
+            .sum()
+    }
+
+    pub fn packets_retransmitted(&self) -> u64 {
+        self.retransmission_metrics
+            .iter()
+            .map(|metrics| metrics.packets_retransmitted())
+            .sum()
+    }
+
+    pub fn fec_packets_sent(&self) -> u64 {
+        self.fec_metrics
+            .iter()
+            .map(|metrics| metrics.fec_packets())
+            .sum()
+    }
+
+    /// of all the medias together, 0 before any media is sent
+    pub fn fec_overhead(&self) -> f64 {
+        let (media_bytes, fec_bytes) =
+            self.fec_metrics
+                .iter()
+                .fold((0, 0), |(media_bytes, fec_bytes), metrics| {
+                    (
+                        media_bytes + metrics.media_bytes(),
+                        fec_bytes + metrics.redundant_bytes() + metrics.fec_bytes(),
+                    )
+                });
+        match media_bytes {
+            0 => 0.0,
+            media_bytes => fec_bytes as f64 / media_bytes as f64,
+        }
+    }
+
+    pub fn bytes_buffered(&self) -> usize {
+        self.buffer_metrics
+            .iter()
+            .map(|metrics| metrics.bytes_buffered())
+            .sum()
+    }
+
+    pub fn buffer_discontinuities(&self) -> u64 {
+        self.buffer_metrics
+            .iter()
+            .map(|metrics| metrics.discontinuities())
+            .sum()
+    }
+
+    pub fn unknown_payload_type_packets(&self) -> u64 {
+        self.unknown_payload_type_packets
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// returns the speed that actually takes effect,
+    /// live streams can only be delivered at 1.0
+    pub fn set_speed(&mut self, speed: f64) -> Result<f64, RtspServerError> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(RtspServerError::InvalidRequest(format!(
+                "invalid speed: {}",
+                speed
+            )));
+        }
+        self.speed = if self.is_live { 1.0 } else { speed };
+        Ok(self.speed)
+    }
+
+    pub fn get(&self, parameter: RtspParameter) -> String {
+        match parameter {
+            RtspParameter::Position => format!("{:.3}", self.position_at(Instant::now())),
+            RtspParameter::StreamName => self.stream_name.clone().unwrap_or_default(),
+            RtspParameter::PacketsSent => self.packets_sent().to_string(),
+            RtspParameter::Speed => format!("{:.1}", self.speed),
+            RtspParameter::NacksReceived => self.nacks_received().to_string(),
+            RtspParameter::PacketsRetransmitted => self.packets_retransmitted().to_string(),
+            RtspParameter::BytesBuffered => self.bytes_buffered().to_string(),
+            RtspParameter::BufferDiscontinuities => self.buffer_discontinuities().to_string(),
+            RtspParameter::UnknownPayloadTypePackets => {
+                self.unknown_payload_type_packets().to_string()
+            }
+            RtspParameter::FecPacketsSent => self.fec_packets_sent().to_string(),
+            RtspParameter::FecOverhead => format!("{:.3}", self.fec_overhead()),
+        }
+    }
+}
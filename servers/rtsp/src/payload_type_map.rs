@@ -0,0 +1,274 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use rtp_formats::{
+    codec::h264::paramters::{RtpH264Fmtp, packetization_mode::PacketizationMode},
+    errors::RtpError,
+    packet::{
+        RtpTrivialPacket,
+        sequencer::{RtpBufferItem, RtpBufferedSequencer},
+    },
+    payload_types::rtp_payload_type::{
+        PCMA_AUDIO, PCMU_AUDIO, RED_ENCODING_NAME, RTX_ENCODING_NAME, ULPFEC_ENCODING_NAME,
+        get_rtp_clockrate,
+    },
+};
+use sdp_formats::{
+    attributes::{SDPAttribute, fmtp::FormatParameters, rtpmap::RtpMap},
+    session::{SDPMediaDescription, SDPMediaType},
+};
+
+/// a payload type as the sdp maps it, with the fmtp of the same payload type
+#[derive(Debug, Clone)]
+pub struct PayloadFormat {
+    pub media_type: SDPMediaType,
+    pub rtpmap: RtpMap,
+    pub fmtp: Option<FormatParameters>,
+}
+
+impl PayloadFormat {
+    pub fn payload_type(&self) -> u8 {
+        self.rtpmap.payload_type
+    }
+
+    /// the rate the rtp timestamps of the payload type tick at, as the sdp declares it.
+    /// it is honored even if it is not the well-known rate of the codec
+    pub fn clock_rate(&self) -> u64 {
+        self.rtpmap.clock_rate
+    }
+
+    /// the codecs the server packetizes and unpacks
+    pub fn is_supported(&self) -> bool {
+        get_rtp_clockrate(&self.rtpmap.encoding_name).is_some()
+    }
+
+    /// h264 is sent in packetization mode 1 at best, interleaved is not supported
+    fn rank(&self) -> u8 {
+        if !self.rtpmap.encoding_name.eq_ignore_ascii_case("h264") {
+            return 0;
+        }
+        let mode = self
+            .fmtp
+            .as_ref()
+            .and_then(|fmtp| fmtp.params.parse::<RtpH264Fmtp>().ok())
+            .and_then(|fmtp| fmtp.packetization_mode)
+            .unwrap_or_default();
+        match mode {
+            PacketizationMode::NonInterleaved => 0,
+            PacketizationMode::SingleNalu => 1,
+            PacketizationMode::Interleaved => 2,
+        }
+    }
+}
+
+/// the payload types of a rtsp session, as the sdp of the peer advertises them.
+/// the packets are told by their payload types rather than by the well-known ones of the codecs,
+/// so a peer may put any codec on any dynamic payload type
+#[derive(Debug, Clone, Default)]
+pub struct PayloadTypeMap {
+    /// in the order of the m= lines, the peer lists the formats it prefers first, RFC 3264 5.1
+    formats: Vec<PayloadFormat>,
+}
+
+impl PayloadTypeMap {
+    pub fn from_media(media: &SDPMediaDescription) -> Self {
+        Self::from_medias([media])
+    }
+
+    /// the payload types of all the medias, the first media to map a payload type keeps it.
+    /// the rtx, red and ulpfec payload types are left out, they protect the formats of others
+    pub fn from_medias<'a>(medias: impl IntoIterator<Item = &'a SDPMediaDescription>) -> Self {
+        let mut formats: Vec<PayloadFormat> = Vec::new();
+        for media in medias {
+            for format in &media.media_line.format {
+                let Ok(payload_type) = format.parse::<u8>() else {
+                    tracing::warn!("media format is not a rtp payload type: {}", format);
+                    continue;
+                };
+                if formats
+                    .iter()
+                    .any(|known| known.payload_type() == payload_type)
+                {
+                    tracing::warn!("payload type {} is mapped more than once", payload_type);
+                    continue;
+                }
+                let rtpmap = media
+                    .attributes
+                    .iter()
+                    .find_map(|attr| match attr {
+                        SDPAttribute::RtpMap(rtpmap) if rtpmap.payload_type == payload_type => {
+                            Some(rtpmap.clone())
+                        }
+                        _ => None,
+                    })
+                    .or_else(|| static_rtp_map(payload_type));
+                let Some(rtpmap) = rtpmap else {
+                    tracing::warn!("no rtpmap found for payload type {}", payload_type);
+                    continue;
+                };
+                if [RTX_ENCODING_NAME, RED_ENCODING_NAME, ULPFEC_ENCODING_NAME]
+                    .iter()
+                    .any(|name| rtpmap.encoding_name.eq_ignore_ascii_case(name))
+                {
+                    continue;
+                }
+                // the timestamps could not be mapped to any time
+                if rtpmap.clock_rate == 0 {
+                    tracing::warn!("payload type {} has a clock rate of 0", payload_type);
+                    continue;
+                }
+                let media_type = &media.media_line.media_type;
+                if matches!(media_type, SDPMediaType::Video)
+                    && let Some(well_known) = get_rtp_clockrate(&rtpmap.encoding_name)
+                    && well_known != rtpmap.clock_rate
+                {
+                    tracing::warn!(
+                        "payload type {} declares {} at {}Hz rather than {}Hz, its timestamps tick at the declared rate",
+                        payload_type,
+                        rtpmap.encoding_name,
+                        rtpmap.clock_rate,
+                        well_known
+                    );
+                }
+                let fmtp = media.attributes.iter().find_map(|attr| match attr {
+                    SDPAttribute::Fmtp(fmtp) if fmtp.fmt == payload_type => Some(fmtp.clone()),
+                    _ => None,
+                });
+                formats.push(PayloadFormat {
+                    media_type: media_type.clone(),
+                    rtpmap,
+                    fmtp,
+                });
+            }
+        }
+        Self { formats }
+    }
+
+    pub fn get(&self, payload_type: u8) -> Option<&PayloadFormat> {
+        self.formats
+            .iter()
+            .find(|format| format.payload_type() == payload_type)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PayloadFormat> {
+        self.formats.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+
+    /// the format to send a media in, and to take the clock rate and the parameter sets of a publish from.
+    /// the first supported codec in the order of the peer wins,
+    /// among the payload types of that codec h264 prefers packetization mode 1 to 0 to 2,
+    /// the one listed first otherwise
+    pub fn preferred(&self, media_type: &SDPMediaType) -> Option<&PayloadFormat> {
+        let codec = &self
+            .formats
+            .iter()
+            .find(|format| &format.media_type == media_type && format.is_supported())?
+            .rtpmap
+            .encoding_name;
+        self.formats
+            .iter()
+            .filter(|format| {
+                &format.media_type == media_type
+                    && format.rtpmap.encoding_name.eq_ignore_ascii_case(codec)
+            })
+            // the first of the equal ranks
+            .min_by_key(|format| format.rank())
+    }
+}
+
+/// the static payload types of the codecs the server supports, which need no rtpmap, RFC 3551 6
+fn static_rtp_map(payload_type: u8) -> Option<RtpMap> {
+    let encoding_name = match payload_type {
+        PCMU_AUDIO => "PCMU",
+        PCMA_AUDIO => "PCMA",
+        _ => return None,
+    };
+    Some(RtpMap {
+        payload_type,
+        encoding_name: encoding_name.to_owned(),
+        clock_rate: 8000,
+        encoding_params: None,
+    })
+}
+
+/// routes the packets of a rtp session to the sequencers of their payload types,
+/// the packets of a payload type without a sequencer are counted and dropped
+#[derive(Default)]
+pub struct RtpPayloadDemuxer {
+    sequencers: BTreeMap<u8, Box<dyn RtpBufferedSequencer + Send>>,
+    unknown_payload_type_packets: Arc<AtomicU64>,
+}
+
+impl RtpPayloadDemuxer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_sequencer(
+        mut self,
+        payload_type: u8,
+        sequencer: Box<dyn RtpBufferedSequencer + Send>,
+    ) -> Self {
+        self.sequencers.insert(payload_type, sequencer);
+        self
+    }
+
+    pub fn payload_types(&self) -> impl Iterator<Item = u8> + '_ {
+        self.sequencers.keys().copied()
+    }
+
+    /// the packets dropped for their payload types so far
+    pub fn unknown_payload_type_packets(&self) -> Arc<AtomicU64> {
+        self.unknown_payload_type_packets.clone()
+    }
+
+    pub fn enqueue(&mut self, packet: RtpTrivialPacket) -> Result<(), RtpError> {
+        let payload_type = packet.header.payload_type;
+        match self.sequencers.get_mut(&payload_type) {
+            Some(sequencer) => sequencer.enqueue(packet),
+            None => {
+                let dropped = self
+                    .unknown_payload_type_packets
+                    .fetch_add(1, Ordering::Relaxed);
+                if dropped == 0 {
+                    tracing::warn!(
+                        "dropping rtp packets of unknown payload type {}",
+                        payload_type
+                    );
+                } else {
+                    tracing::trace!(
+                        "dropping rtp packet of unknown payload type {}, sequence number: {}",
+                        payload_type,
+                        packet.header.sequence_number
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// the items held for more packets are ready afterwards, used when the stream ends
+    pub fn flush(&mut self) {
+        self.sequencers
+            .values_mut()
+            .for_each(|sequencer| sequencer.flush());
+    }
+
+    /// the ready items of the payload types that have any
+    pub fn try_dump(&mut self) -> Vec<(u8, Vec<RtpBufferItem>)> {
+        self.sequencers
+            .iter_mut()
+            .map(|(payload_type, sequencer)| (*payload_type, sequencer.try_dump()))
+            .filter(|(_, items)| !items.is_empty())
+            .collect()
+    }
+}
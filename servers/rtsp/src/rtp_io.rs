@@ -0,0 +1,148 @@
+use std::{
+    fmt::Debug,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+    pin::Pin,
+};
+
+use futures::future::BoxFuture;
+use unified_io::{
+    UnifiedIO,
+    udp::{MulticastOptions, UdpIO},
+};
+use utils::random::random_u16;
+
+use crate::errors::{RtspServerError, RtspServerResult};
+
+/// an io carrying rtp or rtcp packets and the local port announced for it in the Transport header
+pub type RtpIo = (Pin<Box<dyn UnifiedIO>>, u16);
+
+/// opens the rtp and rtcp ios of a media session whose client asked for udp transport
+pub trait RtpIoFactory: Debug + Send + Sync {
+    fn create_pair(
+        &self,
+        peer_addr: SocketAddr,
+        peer_rtp_port: u16,
+        peer_rtcp_port: u16,
+    ) -> BoxFuture<'_, RtspServerResult<(RtpIo, RtpIo)>>;
+}
+
+/// binds udp sockets on two consecutive ports
+#[derive(Debug, Default)]
+pub struct UdpRtpIoFactory;
+
+impl RtpIoFactory for UdpRtpIoFactory {
+    fn create_pair(
+        &self,
+        peer_addr: SocketAddr,
+        peer_rtp_port: u16,
+        peer_rtcp_port: u16,
+    ) -> BoxFuture<'_, RtspServerResult<(RtpIo, RtpIo)>> {
+        Box::pin(async move {
+            let ((rtp_io, rtp_port), (rtcp_io, rtcp_port)) =
+                create_udp_io_pair(peer_addr.ip(), peer_rtp_port, peer_rtcp_port).await?;
+            tracing::info!(
+                "created udp io, rtp port: {}, rtcp port: {}",
+                rtp_port,
+                rtcp_port
+            );
+            let rtp_io: Pin<Box<dyn UnifiedIO>> = Box::pin(rtp_io);
+            let rtcp_io: Pin<Box<dyn UnifiedIO>> = Box::pin(rtcp_io);
+            Ok(((rtp_io, rtp_port), (rtcp_io, rtcp_port)))
+        })
+    }
+}
+
+/// sends to the ports of a multicast group, the peer address is the group address
+#[derive(Debug, Default)]
+pub struct MulticastRtpIoFactory {
+    options: MulticastOptions,
+}
+
+impl MulticastRtpIoFactory {
+    pub fn new(options: MulticastOptions) -> Self {
+        Self { options }
+    }
+
+    async fn create_sender(&self, group: SocketAddrV4) -> RtspServerResult<(UdpIO, u16)> {
+        let io = UdpIO::new_multicast_sender(
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            group,
+            &self.options,
+        )
+        .await
+        .map_err(|err| {
+            tracing::error!("failed to create multicast udp io to {}: {}", group, err);
+            RtspServerError::IoError(io::Error::other(format!(
+                "failed to create multicast udp io to {}: {}",
+                group, err
+            )))
+        })?;
+        let local_port = io
+            .get_local_addr()
+            .map(|addr| addr.port())
+            .unwrap_or_default();
+        Ok((io, local_port))
+    }
+}
+
+impl RtpIoFactory for MulticastRtpIoFactory {
+    fn create_pair(
+        &self,
+        peer_addr: SocketAddr,
+        peer_rtp_port: u16,
+        peer_rtcp_port: u16,
+    ) -> BoxFuture<'_, RtspServerResult<(RtpIo, RtpIo)>> {
+        Box::pin(async move {
+            let IpAddr::V4(group) = peer_addr.ip() else {
+                return Err(RtspServerError::InvalidTransport(format!(
+                    "multicast group is not ipv4: {}",
+                    peer_addr.ip()
+                )));
+            };
+            let rtp_io = self
+                .create_sender(SocketAddrV4::new(group, peer_rtp_port))
+                .await?;
+            let rtcp_io = self
+                .create_sender(SocketAddrV4::new(group, peer_rtcp_port))
+                .await?;
+            tracing::info!(
+                "created multicast udp io, group: {}, rtp port: {}, rtcp port: {}",
+                group,
+                peer_rtp_port,
+                peer_rtcp_port
+            );
+            let rtp_io: RtpIo = (Box::pin(rtp_io.0), rtp_io.1);
+            let rtcp_io: RtpIo = (Box::pin(rtcp_io.0), rtcp_io.1);
+            Ok((rtp_io, rtcp_io))
+        })
+    }
+}
+
+async fn create_udp_io_pair(
+    peer_ip: IpAddr,
+    peer_rtp_port: u16,
+    peer_rtcp_port: u16,
+) -> RtspServerResult<((UdpIO, u16), (UdpIO, u16))> {
+    let (rtp_port, rtp_io) =
+        UdpIO::new_with_remote_addr(random_u16(), SocketAddr::new(peer_ip, peer_rtp_port))
+            .await
+            .map_err(|err| {
+                tracing::error!("failed to create udp io: {}", err);
+                RtspServerError::IoError(io::Error::other(format!(
+                    "failed to create udp io: {}",
+                    err
+                )))
+            })?;
+    let (rtcp_port, rtcp_io) =
+        UdpIO::new_with_remote_addr(rtp_port + 1, SocketAddr::new(peer_ip, peer_rtcp_port))
+            .await
+            .map_err(|err| {
+                tracing::error!("failed to create udp io: {}", err);
+                RtspServerError::IoError(io::Error::other(format!(
+                    "failed to create udp io: {}",
+                    err
+                )))
+            })?;
+    Ok(((rtp_io, rtp_port), (rtcp_io, rtcp_port)))
+}
@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use rtp_formats::{
+    codec::h264::packet::sequencer::budget::RtpH264BufferMetrics, packet::RtpTrivialPacket,
+    rtcp::compound_packet::RtcpCompoundPacket,
+};
+use rtp_session::{
+    participant_observer::{ParticipantEvent, ParticipantObserver},
+    rtcp_context::RtpSessionObserver,
+    rtcp_observer::RtcpObserver,
+    rtp_observer::RtpObserver,
+    sdes::{CnameGenerator, SdesConfig, SourceDescription},
+    simple_statistics::RxStatsSnapshot,
+};
+use stream_center::{
+    events::StreamCenterEvent,
+    identity::StreamIdentity,
+    rtcp_peer::{MAX_RTCP_PEERS, RtcpPeer},
+    rtp_receive::RtpReceiveStats,
+    stream_center::StreamCenter,
+};
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+use crate::SERVER_AGENT;
+
+/// what the rtp sessions of the server say about themselves in rtcp
+#[derive(Debug, Clone)]
+pub struct RtspSdes {
+    pub config: SdesConfig,
+    pub cnames: CnameGenerator,
+}
+
+/// a cname secret of this process only, it changes on restart
+impl Default for RtspSdes {
+    fn default() -> Self {
+        Self {
+            config: SdesConfig {
+                tool: SERVER_AGENT.to_owned(),
+                name: None,
+                email: None,
+            },
+            cnames: CnameGenerator::random(),
+        }
+    }
+}
+
+impl RtspSdes {
+    /// the same for all the media sessions of a stream
+    pub fn describe(&self, stream_key: &str) -> SourceDescription {
+        SourceDescription::local(self.cnames.cname(stream_key), &self.config)
+    }
+}
+
+#[derive(Debug)]
+struct StreamOfPeers {
+    stream_center_event_sender: UnboundedSender<StreamCenterEvent>,
+    stream_id: StreamIdentity,
+    subscriber_id: Option<Uuid>,
+}
+
+#[derive(Debug, Default)]
+struct RtcpPeerState {
+    peers: HashMap<u32, RtcpPeer>,
+    stream: Option<StreamOfPeers>,
+}
+
+/// the sdes of the media sessions of an rtsp session, the local one they send and the ones their peers sent.
+/// the peers are reported to the stream center once the session publishes or plays a stream,
+/// so is the reception of the tracks it publishes
+#[derive(Debug, Clone)]
+pub struct SessionSdes {
+    local: SourceDescription,
+    state: Arc<Mutex<RtcpPeerState>>,
+}
+
+impl SessionSdes {
+    pub fn new(local: SourceDescription) -> Self {
+        Self {
+            local,
+            state: Default::default(),
+        }
+    }
+
+    pub fn local(&self) -> &SourceDescription {
+        &self.local
+    }
+
+    /// reports the peers known so far and the later ones as the peers of the publisher,
+    /// or of the subscriber if there is a subscriber id
+    pub fn attach(
+        &self,
+        stream_center_event_sender: UnboundedSender<StreamCenterEvent>,
+        stream_id: StreamIdentity,
+        subscriber_id: Option<Uuid>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let stream = StreamOfPeers {
+            stream_center_event_sender,
+            stream_id,
+            subscriber_id,
+        };
+        state
+            .peers
+            .values()
+            .for_each(|peer| Self::report(&stream, peer.clone()));
+        state.stream = Some(stream);
+    }
+
+    /// to be added to the rtp session of each track of the media sessions,
+    /// the buffers reassembling the track are reported along with its reception
+    pub fn observer(
+        &self,
+        track: String,
+        buffer_metrics: Arc<RtpH264BufferMetrics>,
+    ) -> Box<dyn RtpSessionObserver> {
+        Box::new(RtcpPeerObserver(self.clone(), track, buffer_metrics))
+    }
+
+    fn on_described(&self, ssrc: u32, description: &SourceDescription) {
+        let peer = RtcpPeer {
+            ssrc,
+            cname: description.cname.clone(),
+            name: description.name.clone(),
+            email: description.email.clone(),
+            tool: description.tool.clone(),
+        };
+        let mut state = self.state.lock().unwrap();
+        if state.peers.len() >= MAX_RTCP_PEERS && !state.peers.contains_key(&ssrc) {
+            tracing::warn!(
+                "ignore the description of rtcp peer {}, too many peers",
+                ssrc
+            );
+            return;
+        }
+        if let Some(stream) = &state.stream {
+            Self::report(stream, peer.clone());
+        }
+        state.peers.insert(ssrc, peer);
+    }
+
+    /// the reports before the stream is published are of no use, they are dropped
+    fn on_reception_reported(
+        &self,
+        track: &str,
+        stats: &RxStatsSnapshot,
+        round_trip_time: Option<Duration>,
+        buffer_metrics: &RtpH264BufferMetrics,
+    ) {
+        let state = self.state.lock().unwrap();
+        let Some(stream) = state
+            .stream
+            .as_ref()
+            .filter(|stream| stream.subscriber_id.is_none())
+        else {
+            return;
+        };
+        let stats = RtpReceiveStats {
+            track: track.to_owned(),
+            ssrc: stats.ssrc,
+            packets_received: stats.packets_received,
+            packets_lost: stats.packets_lost,
+            fraction_lost: stats.fraction_lost as f64 / 256.0,
+            jitter_ms: stats.jitter_ms(),
+            bitrate_1s: stats.bitrate_short,
+            bitrate_10s: stats.bitrate_long,
+            rtt_ms: round_trip_time.map(|rtt| rtt.as_secs_f64() * 1000.0),
+            bytes_buffered: buffer_metrics.bytes_buffered() as u64,
+            buffer_discontinuities: buffer_metrics.discontinuities(),
+        };
+        if let Err(err) = StreamCenter::report_rtp_receive(
+            &stream.stream_center_event_sender,
+            &stream.stream_id,
+            stats,
+        ) {
+            tracing::warn!("report rtp receive stats to stream center failed: {}", err);
+        }
+    }
+
+    fn report(stream: &StreamOfPeers, peer: RtcpPeer) {
+        if let Err(err) = StreamCenter::describe_rtcp_peer(
+            &stream.stream_center_event_sender,
+            &stream.stream_id,
+            stream.subscriber_id,
+            peer,
+        ) {
+            tracing::warn!("report rtcp peer to stream center failed: {}", err);
+        }
+    }
+}
+
+/// the session, the track of the rtp session and the buffers reassembling it
+struct RtcpPeerObserver(SessionSdes, String, Arc<RtpH264BufferMetrics>);
+
+impl RtpSessionObserver for RtcpPeerObserver {}
+
+impl ParticipantObserver for RtcpPeerObserver {
+    fn on_participant_event(&mut self, event: &ParticipantEvent, _timestamp: SystemTime) {
+        match event {
+            ParticipantEvent::Described { ssrc, description } => {
+                self.0.on_described(*ssrc, description)
+            }
+            ParticipantEvent::ReceptionReported {
+                stats,
+                round_trip_time,
+                ..
+            } => self
+                .0
+                .on_reception_reported(&self.1, stats, *round_trip_time, &self.2),
+            _ => {}
+        }
+    }
+}
+
+impl RtpObserver for RtcpPeerObserver {
+    fn on_rtp_packet_received(&mut self, _packet: &RtpTrivialPacket, _timestamp: SystemTime) {}
+
+    fn on_rtp_packet_sent(&mut self, _packet: &RtpTrivialPacket, _timestamp: SystemTime) {}
+}
+
+impl RtcpObserver for RtcpPeerObserver {
+    fn on_rtcp_compound_packet_received(
+        &mut self,
+        _packet: &RtcpCompoundPacket,
+        _timestamp: SystemTime,
+    ) {
+    }
+
+    fn on_rtcp_compound_packet_sent(
+        &mut self,
+        _packet: &RtcpCompoundPacket,
+        _timestamp: SystemTime,
+    ) {
+    }
+}
@@ -1,57 +1,283 @@
-use crate::{config::RtspServerConfig, errors::RtspServerResult, middleware, session::RtspSession};
+use std::{collections::HashMap, net::SocketAddr, pin::Pin, sync::Arc};
+
+use crate::{
+    config::RtspServerConfig,
+    describe::DescribeCache,
+    errors::RtspServerResult,
+    middleware::{
+        RtspMiddleware, RtspMiddlewareChain, file_dumpper::DialogFileDumpper,
+        response_header_appender::ResponseHeaderAppender,
+    },
+    multicast::MulticastDeliveries,
+    rtp_io::{RtpIoFactory, UdpRtpIoFactory},
+    sdes::RtspSdes,
+    session::RtspSession,
+};
+use rtp_session::{fec::FecConfig, sdes::CnameGenerator};
+use server_utils::{
+    egress_shaping::EgressShaper,
+    ingest_limit::IngestRateLimiter,
+    log_context::session_span,
+    metrics::ConnectionMetricsGuard,
+    session_registry::{SessionHandle, SessionProtocol, SessionRegistry, io::CountedIO},
+    supervisor::AbortOnDrop,
+};
+use stream_center::identity::StreamIdentity;
 use tokio::sync::mpsc::UnboundedSender;
-use unified_io::tcp::TcpIO;
+use tracing::Instrument;
+use unified_io::{
+    UnifiedIO, channel::ChannelListener, proxy_protocol::resolve_peer_addr, tcp::TcpIO,
+    tls::TlsAcceptor,
+};
+use utils::error_chain::ErrorChainExt;
 
 #[derive(Debug)]
 pub struct RtspServer {
     stream_center_event_sender: UnboundedSender<stream_center::events::StreamCenterEvent>,
     config: RtspServerConfig,
+    ingest_limiter: IngestRateLimiter,
+    egress_shaper: EgressShaper,
+    session_registry: SessionRegistry,
+    rtp_io_factory: Arc<dyn RtpIoFactory>,
+    middlewares: RtspMiddlewareChain,
+    multicast: MulticastDeliveries,
+    fec: Arc<HashMap<StreamIdentity, FecConfig>>,
+    sdes: RtspSdes,
+    describe_cache: DescribeCache,
 }
 
 impl RtspServer {
     pub fn new(
         stream_center_event_sender: UnboundedSender<stream_center::events::StreamCenterEvent>,
         config: RtspServerConfig,
+        ingest_limiter: IngestRateLimiter,
+        egress_shaper: EgressShaper,
+        session_registry: SessionRegistry,
     ) -> Self {
+        let mut middlewares = RtspMiddlewareChain::default();
+        middlewares.push(Arc::new(ResponseHeaderAppender));
+        let multicast = MulticastDeliveries::new(config.multicast.clone());
+        let fec = Arc::new(config.fec.clone());
+        let cnames = match &config.data_dir {
+            None => CnameGenerator::random(),
+            Some(data_dir) => CnameGenerator::load_or_create(data_dir).unwrap_or_else(|err| {
+                tracing::error!(
+                    "load cname seed from {} failed, the cnames change on restart: {}",
+                    data_dir.display(),
+                    err
+                );
+                CnameGenerator::random()
+            }),
+        };
+        let sdes = RtspSdes {
+            config: config.sdes.clone(),
+            cnames,
+        };
         Self {
             stream_center_event_sender,
             config,
+            ingest_limiter,
+            egress_shaper,
+            session_registry,
+            rtp_io_factory: Arc::new(UdpRtpIoFactory),
+            middlewares,
+            multicast,
+            fec,
+            sdes,
+            describe_cache: DescribeCache::default(),
         }
     }
 
+    /// where the rtp and rtcp ios of media sessions come from, udp sockets by default
+    pub fn with_rtp_io_factory(mut self, rtp_io_factory: Arc<dyn RtpIoFactory>) -> Self {
+        self.rtp_io_factory = rtp_io_factory;
+        self
+    }
+
+    /// runs `middleware` around the requests of all sessions, after the ones registered before it
+    pub fn with_middleware(mut self, middleware: Arc<dyn RtspMiddleware>) -> Self {
+        self.middlewares.push(middleware);
+        self
+    }
+
     pub async fn run(&self) -> RtspServerResult<()> {
         tracing::info!("rtsp server is starting with config: {:?}", self.config);
         let listener =
             tokio::net::TcpListener::bind((self.config.address, self.config.port)).await?;
+        let rtsps = match &self.config.rtsps {
+            Some(rtsps) => {
+                let acceptor = TlsAcceptor::new(rtsps.tls.clone())?;
+                // a run after a crash spawns a reloader of its own
+                let reloader = AbortOnDrop(acceptor.spawn_reloader());
+                let listener =
+                    tokio::net::TcpListener::bind((self.config.address, rtsps.port)).await?;
+                tracing::info!("rtsps is listening on port: {}", rtsps.port);
+                Some((listener, acceptor, reloader))
+            }
+            None => None,
+        };
+        if self.config.srtp && !cfg!(feature = "srtp") {
+            tracing::warn!("srtp is configured but the server is built without the srtp feature");
+        }
+        let srtp = self.config.srtp && cfg!(feature = "srtp");
         loop {
-            let (tcp_stream, addr) = listener.accept().await?;
-            tracing::info!("got new rtsp connection, peer addr: {}", addr);
-
-            let mut session = RtspSession::new(
-                self.stream_center_event_sender.clone(),
-                Box::pin(TcpIO::new(tcp_stream)),
-                addr.to_owned(),
-            )
-            .with_middleware(Box::new(middleware::file_dumpper::DialogFileDumpper::new(
-                format!(
-                    "./debug/rtsp-{}.log",
-                    chrono::Local::now().format("%Y%m%d-%H%M%S")
-                )
-                .as_str(),
-            )))
-            .with_middleware(Box::new(
-                middleware::response_header_appender::ResponseHeaderAppender {},
-            ));
+            let (mut tcp_stream, addr, tls_acceptor) = tokio::select! {
+                accepted = listener.accept() => {
+                    let (tcp_stream, addr) = accepted?;
+                    (tcp_stream, addr, None)
+                }
+                accepted = async {
+                    match &rtsps {
+                        Some((listener, acceptor, _)) => listener
+                            .accept()
+                            .await
+                            .map(|(tcp_stream, addr)| (tcp_stream, addr, Some(acceptor.clone()))),
+                        None => std::future::pending().await,
+                    }
+                } => accepted?,
+            };
+            tracing::info!(
+                "got new rtsp connection, peer addr: {}, tls: {}",
+                addr,
+                tls_acceptor.is_some()
+            );
+
+            let new_session = self.new_session();
+            let session_registry = self.session_registry.clone();
+            let trusted_proxies = self.config.trusted_proxies.clone();
+            // the keys in a=crypto are only as safe as the connection they are sent on
+            let srtp = srtp && tls_acceptor.is_some();
             tokio::task::spawn(async move {
-                match session.run().await {
-                    Ok(()) => {
-                        tracing::info!("rtsp session gracefully closed, peer addr: {}", addr);
+                // the proxy protocol header comes ahead of the tls handshake
+                let addr = match resolve_peer_addr(&mut tcp_stream, addr, &trusted_proxies).await {
+                    Ok(client_addr) => {
+                        if client_addr != addr {
+                            tracing::info!(
+                                "rtsp connection of {} is proxied by {}",
+                                client_addr,
+                                addr
+                            );
+                        }
+                        client_addr
                     }
                     Err(err) => {
-                        tracing::error!("rtsp session exit with error: {}", err);
+                        tracing::warn!("refuse rtsp connection, peer addr: {}, err: {}", addr, err);
+                        return;
                     }
                 };
+                async move {
+                    let registry_handle = session_registry.register(SessionProtocol::Rtsp, addr);
+                    let io = match tls_acceptor {
+                        Some(acceptor) => match acceptor.accept(tcp_stream).await {
+                            Ok(io) => io,
+                            Err(err) => {
+                                tracing::warn!(
+                                    "rtsps handshake failed, peer addr: {}, err: {}",
+                                    addr,
+                                    err
+                                );
+                                return;
+                            }
+                        },
+                        None => TcpIO::new(tcp_stream),
+                    };
+                    let session = new_session(Box::pin(io), addr, registry_handle)
+                        .with_srtp(srtp)
+                        .with_middleware(Arc::new(DialogFileDumpper::new(
+                            format!(
+                                "./debug/rtsp-{}.log",
+                                chrono::Local::now().format("%Y%m%d-%H%M%S")
+                            )
+                            .as_str(),
+                        )));
+                    Self::run_session(session, addr).await;
+                }
+                .instrument(session_span("rtsp", addr))
+                .await
             });
         }
     }
+
+    /// serves the connections handed over by `listener` instead of binding sockets,
+    /// e.g. the in-memory ones of tests
+    pub async fn serve<T: UnifiedIO + 'static>(
+        &self,
+        mut listener: ChannelListener<T>,
+    ) -> RtspServerResult<()> {
+        tracing::info!(
+            "rtsp server is serving a channel listener: {:?}",
+            self.config
+        );
+        while let Some((io, addr)) = listener.accept().await {
+            tracing::info!("got new rtsp connection from channel, peer addr: {}", addr);
+            let new_session = self.new_session();
+            let registry_handle = self.session_registry.register(SessionProtocol::Rtsp, addr);
+            tokio::task::spawn(
+                async move {
+                    Self::run_session(new_session(Box::pin(io), addr, registry_handle), addr).await;
+                }
+                .instrument(session_span("rtsp", addr)),
+            );
+        }
+        Ok(())
+    }
+
+    /// captures the server state a session needs, so it can be created in the session task
+    /// once the address of the client is known
+    fn new_session(
+        &self,
+    ) -> impl FnOnce(Pin<Box<dyn UnifiedIO + Send>>, SocketAddr, SessionHandle) -> RtspSession
+    + Send
+    + 'static {
+        let stream_center_event_sender = self.stream_center_event_sender.clone();
+        let ingest_limiter = self.ingest_limiter.clone();
+        let egress_shaper = self.egress_shaper.clone();
+        let retransmission = self.config.retransmission;
+        let offer_rtx = self.config.offer_rtx;
+        let pacing = self.config.pacing;
+        let h264_buffer = self.config.h264_buffer;
+        let h264_access_unit_delimiters = self.config.h264_access_unit_delimiters;
+        let h264_reorder_frames = self.config.h264_reorder_frames;
+        let onvif_backchannel = self.config.onvif_backchannel;
+        let rtp_io_factory = self.rtp_io_factory.clone();
+        let middlewares = self.middlewares.clone();
+        let multicast = self.multicast.clone();
+        let fec = self.fec.clone();
+        let sdes = self.sdes.clone();
+        let describe = self.config.describe;
+        let describe_cache = self.describe_cache.clone();
+        let max_body_size = self.config.max_body_size;
+        move |io, addr, registry_handle| {
+            // the bytes of the rtsp connection, interleaved rtp included, are counted in the session registry
+            let io = Box::pin(CountedIO::new(io, registry_handle.counters()));
+            RtspSession::new(stream_center_event_sender, io, addr, ingest_limiter)
+                .with_retransmission(retransmission, offer_rtx)
+                .with_pacing(pacing)
+                .with_h264_buffer(h264_buffer)
+                .with_h264_access_unit_delimiters(h264_access_unit_delimiters)
+                .with_h264_reorder_frames(h264_reorder_frames)
+                .with_onvif_backchannel(onvif_backchannel)
+                .with_rtp_io_factory(rtp_io_factory)
+                .with_middlewares(middlewares)
+                .with_multicast(multicast)
+                .with_fec(fec)
+                .with_sdes(sdes)
+                .with_describe(describe, describe_cache)
+                .with_max_body_size(max_body_size)
+                .with_egress_shaper(egress_shaper)
+                .with_registry_handle(registry_handle)
+        }
+    }
+
+    async fn run_session(mut session: RtspSession, addr: SocketAddr) {
+        let _connection = ConnectionMetricsGuard::accepted("rtsp");
+        match session.run().await {
+            Ok(()) => {
+                tracing::info!("rtsp session gracefully closed, peer addr: {}", addr);
+            }
+            Err(err) => {
+                tracing::error!("rtsp session exit with error: {}", err.chain());
+            }
+        };
+    }
 }
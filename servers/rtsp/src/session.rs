@@ -1,23 +1,36 @@
 use crate::{
+    SERVER_AGENT,
+    control::{ControlTarget, is_aggregate_controlled, resolve_control},
+    describe::{DescribeCache, DescribeConfig, DescribedMedias},
     errors::{RtspServerError, RtspServerResult},
-    media_session::{RtspMediaSession, RtspSessionCommand},
-    middleware::RtspMiddleware,
+    media_session::{
+        DEFAULT_RTP_PACKET_SIZE, MIN_RTP_PACKET_SIZE, RtpPlayPosition, RtspMediaSession,
+        RtspSessionCommand,
+    },
+    middleware::{
+        RtspMiddleware, RtspMiddlewareChain, SessionContext, load_shedding::redirected_uri,
+    },
+    multicast::{MulticastDeliveries, MulticastLease},
+    parameters::{RtspParameter, RtspParameterStore},
+    payload_type_map::PayloadTypeMap,
+    rtp_io::{RtpIoFactory, UdpRtpIoFactory},
     rtsp_server_simple_response,
+    sdes::{RtspSdes, SessionSdes},
 };
 use chrono::TimeDelta;
-use codec_common::audio::AudioConfig;
 use futures::{SinkExt, StreamExt};
 use num::ToPrimitive;
 use rtp_formats::{
-    codec::{
-        h264::paramters::{RtpH264Fmtp, RtpH264FmtpBuilder, packetization_mode::PacketizationMode},
-        mpeg4_generic::parameters::RtpMpeg4Fmtp,
-    },
+    codec::h264::{dts::DEFAULT_REORDER_FRAMES, packet::sequencer::budget::RtpH264BufferConfig},
+    header::ABS_SEND_TIME_URI,
     payload_types::rtp_payload_type::{
-        audio_get_rtp_clockrate, audio_get_rtp_encoding_name, get_audio_rtp_payload_type,
-        get_video_rtp_payload_type, video_get_rtp_clockrate, video_get_rtp_encoding_name,
+        RED_AUDIO, RED_ENCODING_NAME, RTX_ENCODING_NAME, ULPFEC_ENCODING_NAME, ULPFEC_VIDEO,
+        get_rtx_payload_type,
     },
 };
+use rtp_session::{
+    capture::CaptureTap, fec::FecConfig, pacing::PacingConfig, retransmission::RetransmissionConfig,
+};
 use rtsp_formats::{
     RtspMessage, RtspMessageFramed,
     consts::{
@@ -27,44 +40,151 @@ use rtsp_formats::{
     errors::RtspMessageError,
     header::{
         RtspHeader,
-        transport::{TransportHeader, TransportMode},
+        feature_tag::{self, METHOD_REDIRECT, ONVIF_BACKCHANNEL},
+        rtp_info::{RtpInfo, RtpInfoHeader},
+        scale::ScaleHeader,
+        session::SessionHeader,
+        transport::{TransportCast, TransportHeader, TransportMode, TransportProtocol},
     },
     interleaved::RtspInterleavedPacket,
+    parameters::{TEXT_PARAMETERS_CONTENT_TYPE, TextParameters},
     request::RtspRequest,
     response::{RtspResponse, builder::RtspResponseBuilder},
-    sdp_extension::attribute::RtspSDPControl,
+    sdp_extension::{attribute::RtspSDPControl, media::is_onvif_backchannel},
+    time::{MediaTimeFormat, TimeRange},
 };
-use scopeguard::defer;
+use scopeguard::ScopeGuard;
 use sdp_formats::{
-    attributes::{SDPAttribute, fmtp::FormatParameters, rtpmap::RtpMap},
+    attributes::{
+        SDPAttribute, SDPTrivialAttribute, fmtp::FormatParameters, media_direction::MediaDirection,
+        rtpmap::RtpMap,
+    },
     builder::{SdpBuilder, SdpMediaBuilder},
     session::{SDPAddrType, SDPMediaDescription, SDPMediaType, SDPNetType, Sdp},
 };
 use server_utils::{
+    egress_shaping::{EgressShaper, SubscriberShaper},
+    ingest_limit::IngestRateLimiter,
+    log_context::{StreamRole, stream_span},
     runtime_handle::{PlayHandle, PublishHandle, SessionRuntime},
+    session_registry::{SessionHandle, SessionRole},
     stream_properities::StreamProperties,
 };
-use std::{collections::HashMap, net::SocketAddr, pin::Pin, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    ops::ControlFlow,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
 use stream_center::{
+    drain::DrainRequest,
+    dvr::DVR_START_KEY,
     errors::StreamCenterError,
     gop::MediaFrame,
+    identity::StreamIdentity,
     stream_center::StreamCenter,
-    stream_source::{PlayProtocol, PublishProtocol, StreamIdentifier},
+    stream_source::{MediaSelection, PlayProtocol, PublishProtocol},
+};
+use tokio::{
+    sync::{
+        RwLock,
+        mpsc::{UnboundedReceiver, UnboundedSender},
+    },
+    task::AbortHandle,
 };
-use tokio::sync::{RwLock, mpsc::UnboundedSender};
-use tracing::Instrument;
+use tracing::{Instrument, Span};
 use unified_io::{UnifiedIO, UnifiyStreamed};
 use url::Url;
 use uuid::Uuid;
+#[cfg(feature = "srtp")]
+use {
+    rtp_formats::profiles::savp::{AES_CM_128_HMAC_SHA1_80, SrtpMasterKey},
+    sdp_formats::{attributes::crypto::Crypto, session::SDPMediaProtocol},
+};
 
 #[derive(Debug)]
 pub struct RtspMediaSessionHandler {
-    pub(crate) peer_addr: SocketAddr,
     pub(crate) uri: Url,
-    pub(crate) session_id: String,
     pub(crate) media_sdp: SDPMediaDescription,
     pub(crate) transport: TransportHeader,
     pub(crate) media_frame_sender: Option<tokio::sync::mpsc::Sender<MediaFrame>>,
+    /// None if this is not a play media session
+    pub(crate) play_position: Option<Arc<RtpPlayPosition>>,
+    /// the index of the media in the sdp
+    pub(crate) media_index: usize,
+    /// the task the media session runs in, None for the multicast ones
+    pub(crate) task: Option<AbortHandle>,
+}
+
+impl RtspMediaSessionHandler {
+    /// the media session is gone with its rtp ios right away, without an rtcp bye,
+    /// e.g., its media is set up again
+    fn abort(&self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// the media sessions in the order of their medias in the sdp
+fn in_sdp_order(
+    sessions: &HashMap<String, RtspMediaSessionHandler>,
+) -> Vec<&RtspMediaSessionHandler> {
+    let mut sessions: Vec<_> = sessions.values().collect();
+    sessions.sort_by_key(|session| session.media_index);
+    sessions
+}
+
+/// @see: RFC 7826 Appendix B
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RtspSessionState {
+    Init,
+    Ready,
+    Play,
+    Record,
+}
+
+impl RtspSessionState {
+    fn allowed_methods(&self) -> &'static [RtspMethod] {
+        match self {
+            Self::Init => &[
+                RtspMethod::Options,
+                RtspMethod::Describe,
+                RtspMethod::Announce,
+                RtspMethod::Setup,
+                RtspMethod::GetParameter,
+                RtspMethod::SetParameter,
+            ],
+            Self::Ready => &[
+                RtspMethod::Options,
+                RtspMethod::Setup,
+                RtspMethod::Play,
+                RtspMethod::Pause,
+                RtspMethod::Record,
+                RtspMethod::TearDown,
+                RtspMethod::GetParameter,
+                RtspMethod::SetParameter,
+            ],
+            Self::Play => &[
+                RtspMethod::Options,
+                RtspMethod::Pause,
+                RtspMethod::TearDown,
+                RtspMethod::GetParameter,
+                RtspMethod::SetParameter,
+            ],
+            Self::Record => &[
+                RtspMethod::Options,
+                RtspMethod::TearDown,
+                RtspMethod::GetParameter,
+                RtspMethod::SetParameter,
+            ],
+        }
+    }
 }
 
 pub struct RtspSession {
@@ -72,32 +192,69 @@ pub struct RtspSession {
     io: UnifiyStreamed<RtspMessageFramed>,
     peer_addr: SocketAddr,
     sdp: Option<Sdp>,
+    /// the uri the controls of the sdp are relative to, the one of the DESCRIBE or the ANNOUNCE
+    content_base: Option<Url>,
     range: Option<String>,
     session_id: Option<String>,
+    /// the uri of the SETUP the session id was given to
+    session_uri: Option<Url>,
     timeout_ms: u64,
     media_sessions: Arc<RwLock<HashMap<String, RtspMediaSessionHandler>>>,
     stream_properities: Option<StreamProperties>,
     runtime_handle: SessionRuntime,
     rtsp_command_tx: tokio::sync::broadcast::Sender<RtspSessionCommand>,
-    middlewares: Vec<Box<dyn RtspMiddleware + Send>>,
-}
-
-impl RtspMiddleware for RtspSession {
-    fn pre_request(&mut self, request: RtspRequest) -> RtspServerResult<RtspRequest> {
-        self.middlewares
-            .iter_mut()
-            .try_fold(request, |req, mid| mid.pre_request(req))
-    }
-
-    fn pre_response(
-        &mut self,
-        request: &RtspRequest,
-        response: RtspResponse,
-    ) -> RtspServerResult<RtspResponse> {
-        self.middlewares
-            .iter_mut()
-            .try_fold(response, |res, mid| mid.pre_response(request, res))
-    }
+    /// the reason the stream played by the session ended, told by the frame distribution
+    stream_ended_tx: UnboundedSender<String>,
+    stream_ended_rx: UnboundedReceiver<String>,
+    middlewares: RtspMiddlewareChain,
+    /// the transport answered to the latest SETUP
+    transport: Option<TransportHeader>,
+    ingest_limiter: IngestRateLimiter,
+    egress_shaper: EgressShaper,
+    parameters: RtspParameterStore,
+    retransmission: RetransmissionConfig,
+    offer_rtx: bool,
+    /// play sessions are paced by it if set
+    pacing: Option<PacingConfig>,
+    h264_buffer: RtpH264BufferConfig,
+    /// the aud nal units of published h264 are passed on
+    h264_access_unit_delimiters: bool,
+    h264_reorder_frames: usize,
+    onvif_backchannel: bool,
+    /// the client required the onvif backchannel
+    backchannel_required: bool,
+    rtp_io_factory: Arc<dyn RtpIoFactory>,
+    /// set while the play is paused, the frames are dropped instead of distributed meanwhile
+    play_paused: Arc<AtomicBool>,
+    multicast: MulticastDeliveries,
+    fec: Arc<HashMap<StreamIdentity, FecConfig>>,
+    /// the protection described in the latest DESCRIBE, the play sessions send it
+    fec_config: FecConfig,
+    /// set while the session plays a stream from its multicast group
+    multicast_lease: Option<MulticastLease>,
+    /// the largest rtp packet sent to the client, lowered by the Blocksize of a SETUP
+    max_rtp_packet_size: usize,
+    /// the span of the connection, the session is created in the task instrumented with it
+    session_span: Span,
+    /// set once publishing or playing, the media sessions of the stream run in it
+    stream_span: Option<Span>,
+    sdes: RtspSdes,
+    /// created with the first media session, shared by all of them
+    session_sdes: Option<SessionSdes>,
+    /// the medias are described as RTP/SAVP and played protected with srtp
+    srtp: bool,
+    /// the entry of the session in the session registry, the admin api may ask to close it
+    registry_handle: Option<SessionHandle>,
+    /// of the entry in the session registry, the packets of the media sessions are captured through it
+    capture_tap: CaptureTap,
+    describe: DescribeConfig,
+    describe_cache: DescribeCache,
+    /// the client told it follows a REDIRECT of the server
+    redirect_supported: bool,
+    /// the CSeq of the latest request of the server to the client
+    server_cseq: u32,
+    /// when a session redirected on drain is closed if the client is still here
+    drain_deadline: Option<tokio::time::Instant>,
 }
 
 impl RtspSession {
@@ -105,40 +262,208 @@ impl RtspSession {
         stream_center_event_sender: UnboundedSender<stream_center::events::StreamCenterEvent>,
         io: Pin<Box<dyn UnifiedIO + Send>>,
         peer_addr: SocketAddr,
+        ingest_limiter: IngestRateLimiter,
     ) -> Self {
         let (rtsp_command_tx, _) = tokio::sync::broadcast::channel(1000);
+        let (stream_ended_tx, stream_ended_rx) = tokio::sync::mpsc::unbounded_channel();
         Self {
             stream_center_event_sender,
-            io: UnifiyStreamed::new(io, RtspMessageFramed),
+            io: UnifiyStreamed::new_byte_stream(io, RtspMessageFramed::default()),
             peer_addr,
             sdp: None,
+            content_base: None,
             range: None,
             session_id: Default::default(),
+            session_uri: None,
             timeout_ms: 60_000,
             media_sessions: Arc::new(RwLock::new(HashMap::new())),
             stream_properities: Default::default(),
             runtime_handle: SessionRuntime::Unknown,
             rtsp_command_tx,
-            middlewares: vec![],
+            stream_ended_tx,
+            stream_ended_rx,
+            middlewares: RtspMiddlewareChain::default(),
+            transport: None,
+            ingest_limiter,
+            egress_shaper: EgressShaper::default(),
+            parameters: RtspParameterStore::new(),
+            retransmission: RetransmissionConfig::default(),
+            offer_rtx: false,
+            pacing: None,
+            h264_buffer: RtpH264BufferConfig::default(),
+            h264_access_unit_delimiters: true,
+            h264_reorder_frames: DEFAULT_REORDER_FRAMES,
+            onvif_backchannel: false,
+            backchannel_required: false,
+            rtp_io_factory: Arc::new(UdpRtpIoFactory),
+            play_paused: Arc::new(AtomicBool::new(false)),
+            multicast: MulticastDeliveries::default(),
+            fec: Default::default(),
+            fec_config: FecConfig::default(),
+            multicast_lease: None,
+            max_rtp_packet_size: DEFAULT_RTP_PACKET_SIZE,
+            session_span: Span::current(),
+            stream_span: None,
+            sdes: RtspSdes::default(),
+            session_sdes: None,
+            srtp: false,
+            registry_handle: None,
+            capture_tap: CaptureTap::default(),
+            describe: DescribeConfig::default(),
+            describe_cache: DescribeCache::default(),
+            redirect_supported: false,
+            server_cseq: 0,
+            drain_deadline: None,
         }
     }
 
-    pub fn with_middleware(mut self, middleware: Box<dyn RtspMiddleware + Send>) -> Self {
+    /// how play sessions answer the nacks of players
+    pub fn with_retransmission(
+        mut self,
+        retransmission: RetransmissionConfig,
+        offer_rtx: bool,
+    ) -> Self {
+        self.retransmission = retransmission;
+        self.offer_rtx = offer_rtx;
+        self
+    }
+
+    /// spread the packets of play sessions by the timestamps of their frames instead of sending them as they come
+    pub fn with_pacing(mut self, pacing: Option<PacingConfig>) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    /// byte budgets of the h264 sequencers of publish sessions
+    pub fn with_h264_buffer(mut self, h264_buffer: RtpH264BufferConfig) -> Self {
+        self.h264_buffer = h264_buffer;
+        self
+    }
+
+    /// pass the access unit delimiters of published h264 on, they are dropped otherwise
+    pub fn with_h264_access_unit_delimiters(mut self, keep: bool) -> Self {
+        self.h264_access_unit_delimiters = keep;
+        self
+    }
+
+    /// frames the dts derivation of h264 publish sessions holds back when the sps does not tell
+    pub fn with_h264_reorder_frames(mut self, h264_reorder_frames: usize) -> Self {
+        self.h264_reorder_frames = h264_reorder_frames;
+        self
+    }
+
+    /// whether clients requiring the onvif backchannel get an extra sendonly audio media
+    pub fn with_onvif_backchannel(mut self, enable: bool) -> Self {
+        self.onvif_backchannel = enable;
+        self
+    }
+
+    /// where the rtp and rtcp ios of media sessions come from, udp sockets by default
+    pub fn with_rtp_io_factory(mut self, rtp_io_factory: Arc<dyn RtpIoFactory>) -> Self {
+        self.rtp_io_factory = rtp_io_factory;
+        self
+    }
+
+    /// the streams clients may ask to play from multicast groups, shared by the sessions of a server
+    pub fn with_multicast(mut self, multicast: MulticastDeliveries) -> Self {
+        self.multicast = multicast;
+        self
+    }
+
+    /// how the packets played of each stream are protected against loss, shared by the sessions of a server
+    pub fn with_fec(mut self, fec: Arc<HashMap<StreamIdentity, FecConfig>>) -> Self {
+        self.fec = fec;
+        self
+    }
+
+    /// the bitrate caps of the players, shared by the sessions of a server
+    pub fn with_egress_shaper(mut self, egress_shaper: EgressShaper) -> Self {
+        self.egress_shaper = egress_shaper;
+        self
+    }
+
+    /// how DESCRIBE waits for the sequence headers, and the medias described, shared by the sessions of a server
+    pub fn with_describe(
+        mut self,
+        describe: DescribeConfig,
+        describe_cache: DescribeCache,
+    ) -> Self {
+        self.describe = describe;
+        self.describe_cache = describe_cache;
+        self
+    }
+
+    /// requests with a longer body are answered with 413 and the connection is closed
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        *self.io.codec_mut() = RtspMessageFramed::with_max_body_size(max_body_size);
+        self
+    }
+
+    /// the cname secret and the other sdes items sent by the media sessions, shared by the sessions of a server
+    pub fn with_sdes(mut self, sdes: RtspSdes) -> Self {
+        self.sdes = sdes;
+        self
+    }
+
+    /// describe the medias as RTP/SAVP with a key in a=crypto for each, the medias are played protected with it,
+    /// for the clients connected over tls, takes effect if built with the srtp feature
+    pub fn with_srtp(mut self, enable: bool) -> Self {
+        self.srtp = enable && cfg!(feature = "srtp");
+        self
+    }
+
+    /// the entry of the session in the session registry of the server
+    pub fn with_registry_handle(mut self, registry_handle: SessionHandle) -> Self {
+        self.capture_tap = registry_handle.capture_tap();
+        self.registry_handle = Some(registry_handle);
+        self
+    }
+
+    pub fn with_middleware(mut self, middleware: Arc<dyn RtspMiddleware>) -> Self {
         self.middlewares.push(middleware);
         self
     }
 
+    /// replaces the middlewares of the session, e.g. with the ones shared by the server
+    pub fn with_middlewares(mut self, middlewares: RtspMiddlewareChain) -> Self {
+        self.middlewares = middlewares;
+        self
+    }
+
+    fn middleware_context(&self, request: &RtspRequest, received_at: Instant) -> SessionContext {
+        SessionContext {
+            peer_addr: self.peer_addr,
+            session_id: self.session_id.clone(),
+            transport: self.transport.clone(),
+            method: request.method(),
+            uri: request.uri().clone(),
+            cseq: request.headers().cseq(),
+            via: request.headers().via(),
+            received_at,
+        }
+    }
+
     pub async fn send_response(
         &mut self,
         request: &RtspRequest,
-        response: RtspResponse,
+        mut response: RtspResponse,
+        received_at: Instant,
     ) -> RtspServerResult<()> {
-        let mut response = self.pre_response(request, response)?;
+        response.headers_mut().set(
+            RtspHeader::CSeq,
+            request.headers().cseq().unwrap_or(0).to_string(),
+        );
+        response.set_version(request.version().clone());
         if let Some(session_id) = self.session_id.as_ref()
             && !request.headers().contains(RtspHeader::Session)
         {
-            response.headers_mut().push(RtspHeader::Session, session_id);
+            response.headers_mut().push(
+                RtspHeader::Session,
+                SessionHeader::new(session_id).to_string(),
+            );
         }
+        let context = self.middleware_context(request, received_at);
+        self.middlewares.on_response(&mut response, &context).await;
         tracing::debug!("sending rtsp response: {:?}", response);
         self.io.send(RtspMessage::Response(response)).await?;
         Ok(())
@@ -178,6 +503,13 @@ impl RtspSession {
                     tracing::error!("error while unpublish stream: {}", err);
                 });
             }
+            SessionRuntime::Unknown if self.multicast_lease.is_some() => {
+                tracing::info!(
+                    "multicast play session is about to exit, session_id={:?}",
+                    self.session_id
+                );
+                self.multicast_lease = None;
+            }
             SessionRuntime::Unknown => {
                 tracing::info!(
                     "unknown session is about to exit, session_id={:?}",
@@ -187,47 +519,215 @@ impl RtspSession {
         }
     }
 
+    /// back to the init state, the requests with the old session id are answered with 454
+    async fn reset_session(&mut self) {
+        self.media_sessions.write().await.clear();
+        self.session_id = None;
+        self.session_uri = None;
+        self.transport = None;
+        self.sdp = None;
+        self.content_base = None;
+        self.range = None;
+        self.parameters.reset();
+        self.play_paused.store(false, Ordering::Release);
+    }
+
+    /// the stream played ended, the session is torn down as if the client asked.
+    /// the media sessions stop on their own once their rtcp bye is sent,
+    /// the connection stays open for the client to set up again
+    async fn on_stream_ended(&mut self, reason: &str) {
+        tracing::info!(
+            "stream ended, reason: {}, tear down session_id={:?}",
+            reason,
+            self.session_id
+        );
+        self.reset_session().await;
+    }
+
+    /// the stream span once known, the session span before
+    fn span(&self) -> Span {
+        self.stream_span
+            .clone()
+            .unwrap_or_else(|| self.session_span.clone())
+    }
+
     pub async fn run(&mut self) -> RtspServerResult<()> {
         tracing::info!("rtsp session is running");
         loop {
-            match self.read_rtsp_message().await {
+            let span = self.span();
+            match self.read_rtsp_message().instrument(span).await {
                 Ok(()) => {}
                 Err(err) => {
                     tracing::error!("error while reading rtsp message: {}", err);
                     self.on_session_pre_exit().await;
+                    self.middlewares.on_session_end(self.peer_addr);
                     return Err(err);
                 }
             }
         }
     }
 
+    /// None for a close, pends forever without a registry handle
+    async fn close_or_drain_requested(
+        registry_handle: &mut Option<SessionHandle>,
+    ) -> Option<DrainRequest> {
+        match registry_handle {
+            Some(registry_handle) => registry_handle.close_or_drain_requested().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// pends forever until the session is redirected on drain
+    async fn drain_deadline(deadline: Option<tokio::time::Instant>) {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// moves the client to the target of the drain, with a REDIRECT if the client told it follows one,
+    /// the session is torn down with a TEARDOWN otherwise. a connection not set up yet is just closed
+    /// @see: RFC 7826 Section 13.10
+    async fn on_drain(&mut self, drain: DrainRequest) -> RtspServerResult<()> {
+        let (Some(session_id), Some(session_uri)) =
+            (self.session_id.clone(), self.session_uri.clone())
+        else {
+            tracing::info!("rtsp connection without session is closed on drain");
+            return Err(RtspServerError::GracefulExit);
+        };
+        let location = redirected_uri(&session_uri, &drain.target);
+        if self.redirect_supported
+            && let Some(location) = location
+        {
+            let closed_at =
+                chrono::Utc::now() + TimeDelta::from_std(drain.grace).unwrap_or(TimeDelta::zero());
+            tracing::info!(
+                "redirect rtsp session on drain, session_id={}, location={}",
+                session_id,
+                location
+            );
+            self.send_request(
+                RtspMethod::Redirect,
+                session_uri,
+                vec![
+                    (
+                        RtspHeader::Session,
+                        SessionHeader::new(session_id).to_string(),
+                    ),
+                    (RtspHeader::Location, location.to_string()),
+                    (
+                        RtspHeader::TerminateReason,
+                        format!("Server-Admin;time={}", closed_at.format("%Y%m%dT%H%M%SZ")),
+                    ),
+                ],
+            )
+            .await?;
+            self.drain_deadline = Some(tokio::time::Instant::now() + drain.grace);
+            return Ok(());
+        }
+        tracing::info!(
+            "tear down rtsp session on drain, the client does not follow redirects, session_id={}",
+            session_id
+        );
+        self.send_request(
+            RtspMethod::TearDown,
+            session_uri,
+            vec![(
+                RtspHeader::Session,
+                SessionHeader::new(session_id).to_string(),
+            )],
+        )
+        .await?;
+        Err(RtspServerError::GracefulExit)
+    }
+
+    /// a request of the server to the client, the responses to them are just logged
+    async fn send_request(
+        &mut self,
+        method: RtspMethod,
+        uri: Url,
+        headers: Vec<(RtspHeader, String)>,
+    ) -> RtspServerResult<()> {
+        self.server_cseq += 1;
+        let request = RtspRequest::builder()
+            .method(method)
+            .uri(uri)
+            .header(RtspHeader::CSeq, self.server_cseq.to_string())
+            .headers(headers)
+            .build()?;
+        tracing::debug!("sending rtsp request: {}", request);
+        self.io.send(RtspMessage::Request(request)).await?;
+        Ok(())
+    }
+
+    /// records the role taken on the stream in the session registry
+    fn register_role(&self, role: SessionRole) {
+        if let Some(registry_handle) = &self.registry_handle {
+            registry_handle.set_role(role, &self.stream_key());
+        }
+    }
+
     pub async fn read_rtsp_message(&mut self) -> RtspServerResult<()> {
-        match self.io.next().await {
+        let message = tokio::select! {
+            message = self.io.next() => message,
+            requested = Self::close_or_drain_requested(&mut self.registry_handle) => {
+                let Some(drain) = requested else {
+                    // rtsp 1.0 has no way to tell the client, the connection is just closed
+                    tracing::info!(
+                        "rtsp session is closed by admin, session_id={:?}",
+                        self.session_id
+                    );
+                    return Err(RtspServerError::GracefulExit);
+                };
+                return self.on_drain(drain).await;
+            }
+            Some(reason) = self.stream_ended_rx.recv() => {
+                self.on_stream_ended(&reason).await;
+                return Ok(());
+            }
+            _ = Self::drain_deadline(self.drain_deadline) => {
+                tracing::info!(
+                    "redirected rtsp session is still here after the drain grace, session_id={:?}",
+                    self.session_id
+                );
+                return Err(RtspServerError::GracefulExit);
+            }
+        };
+        match message {
             Some(Ok(message)) => {
                 tracing::debug!("received rtsp message: {:?}", message);
                 match message {
-                    RtspMessage::Request(request) => {
+                    RtspMessage::Request(mut request) => {
+                        let received_at = Instant::now();
                         tracing::debug!("handle rtsp request: {}", request);
                         let request_span = tracing::debug_span!(
                             "handle_request",
                             method = request.method().to_string(),
                             uri = request.uri().to_string(),
-                            session_id = request.headers().get_unique(RtspHeader::Session),
+                            session_id = request.headers().session().map(|session| session.id),
                             cseq = request.headers().cseq(),
                         );
-                        let request = request_span.in_scope(|| self.pre_request(request))?;
-
-                        let response = if self.session_id.as_ref()
-                            != request.headers().get_unique(RtspHeader::Session)
+                        let context = self.middleware_context(&request, received_at);
+                        let response = if let ControlFlow::Break(response) = self
+                            .middlewares
+                            .on_request(&mut request, &context)
+                            .instrument(request_span.clone())
+                            .await
                         {
+                            Ok(response)
+                        } else if !self.is_of_this_session(&request) {
                             Ok(rtsp_server_simple_response(RtspStatus::SessionNotFound))
+                        } else if let Some(response) =
+                            request_span.in_scope(|| self.check_require(&request))
+                        {
+                            Ok(response)
                         } else {
                             self.handle_request(&request).instrument(request_span).await
                         };
                         match response {
                             Ok(response) => {
                                 tracing::info!("response: {}", response);
-                                self.send_response(&request, response).await?
+                                self.send_response(&request, response, received_at).await?
                             }
                             Err(RtspServerError::ParseStreamProperitiesFailed(err)) => {
                                 tracing::error!(
@@ -237,6 +737,7 @@ impl RtspSession {
                                 self.send_response(
                                     &request,
                                     rtsp_server_simple_response(RtspStatus::BadRequest),
+                                    received_at,
                                 )
                                 .await?
                             }
@@ -247,6 +748,7 @@ impl RtspSession {
                                 self.send_response(
                                     &request,
                                     rtsp_server_simple_response(RtspStatus::NotFound),
+                                    received_at,
                                 )
                                 .await?
                             }
@@ -257,6 +759,7 @@ impl RtspSession {
                                 self.send_response(
                                     &request,
                                     rtsp_server_simple_response(RtspStatus::BadRequest),
+                                    received_at,
                                 )
                                 .await?
                             }
@@ -265,6 +768,7 @@ impl RtspSession {
                                 self.send_response(
                                     &request,
                                     rtsp_server_simple_response(RtspStatus::InternalServerError),
+                                    received_at,
                                 )
                                 .await?
                             }
@@ -286,6 +790,25 @@ impl RtspSession {
                 tracing::info!("connection reset by peer");
                 return Ok(());
             }
+            Some(Err(RtspMessageError::BodyTooLarge {
+                length,
+                max_size,
+                cseq,
+            })) => {
+                // the body is not read, the next message can not be found after it
+                tracing::warn!(
+                    "request body of {} bytes is over the maximum of {} bytes, closing",
+                    length,
+                    max_size
+                );
+                let mut response =
+                    rtsp_server_simple_response(RtspStatus::RequestMessageBodyTooLarge);
+                response
+                    .headers_mut()
+                    .set(RtspHeader::CSeq, cseq.unwrap_or(0).to_string());
+                self.io.send(RtspMessage::Response(response)).await?;
+                return Err(RtspServerError::GracefulExit);
+            }
             Some(Err(e)) => {
                 tracing::error!("error receiving rtsp message: {:?}", e);
                 return Err(RtspServerError::RtspMessageError(e));
@@ -313,6 +836,15 @@ impl RtspSession {
         Ok(())
     }
 
+    /// the requests in a session carry its id, but for a SETUP retried by a client that missed
+    /// the answer to the first one, the media set up again in the session of the connection
+    fn is_of_this_session(&self, request: &RtspRequest) -> bool {
+        match request.headers().session() {
+            Some(session) => self.session_id.as_ref() == Some(&session.id),
+            None => self.session_id.is_none() || matches!(request.method(), RtspMethod::Setup),
+        }
+    }
+
     fn session_pre_setup(
         &mut self,
         stream_properities: StreamProperties,
@@ -335,8 +867,7 @@ impl RtspSession {
         }
 
         if let Some(stream_prop) = self.stream_properities.as_ref()
-            && (stream_prop.stream_name != stream_properities.stream_name
-                || stream_prop.app != stream_properities.app)
+            && stream_prop.stream_id != stream_properities.stream_id
         {
             tracing::error!(
                 "trying to {} a different stream in {} session, old stream={:?}, new stream={:?}",
@@ -351,10 +882,225 @@ impl RtspSession {
             );
             return Some(rtsp_server_simple_response(RtspStatus::SessionNotFound));
         }
+        self.parameters
+            .set_stream_name(stream_properities.stream_id.name.as_str());
         self.stream_properities = Some(stream_properities);
         None
     }
 
+    /// Ok(None) for a request without body,
+    /// Err(response) if the body is not text/parameters
+    fn parse_text_parameters(
+        &self,
+        request: &RtspRequest,
+    ) -> RtspServerResult<Result<Option<TextParameters>, RtspResponse>> {
+        let body = request.body().filter(|body| !body.trim().is_empty());
+        if body.is_none() {
+            return Ok(Ok(None));
+        }
+        let content_type = request.headers().content_type();
+        if !content_type.is_some_and(|content_type| {
+            content_type
+                .trim()
+                .eq_ignore_ascii_case(TEXT_PARAMETERS_CONTENT_TYPE)
+        }) {
+            tracing::warn!(
+                "unsupported content type for {}: {:?}",
+                request.method(),
+                content_type
+            );
+            return Ok(Err(rtsp_server_simple_response(
+                RtspStatus::UnsupportedMediaType,
+            )));
+        }
+        Ok(Ok(Some(body.unwrap().parse()?)))
+    }
+
+    /// 451 with the unsupported parameters in the body
+    /// @see: RFC 7826 Section 17.4.15
+    fn parameter_not_understood(unknown: TextParameters) -> RtspServerResult<RtspResponse> {
+        Ok(RtspResponse::builder()
+            .status(RtspStatus::ParameterNotUnderstood)
+            .content_type(TEXT_PARAMETERS_CONTENT_TYPE.to_owned())
+            .body(unknown.to_string())
+            .build()?)
+    }
+
+    fn state(&self) -> RtspSessionState {
+        if self.session_id.is_none() {
+            RtspSessionState::Init
+        } else if self.runtime_handle.is_publish() {
+            RtspSessionState::Record
+        } else if (self.runtime_handle.is_play() || self.multicast_lease.is_some())
+            && !self.parameters.is_paused()
+        {
+            RtspSessionState::Play
+        } else {
+            RtspSessionState::Ready
+        }
+    }
+
+    /// 455 with the methods valid in the current state, RFC 7826 13.4.8
+    fn method_not_valid_in_this_state(&self) -> RtspServerResult<RtspResponse> {
+        let state = self.state();
+        tracing::warn!("method not valid in state {:?}", state);
+        let allow = state
+            .allowed_methods()
+            .iter()
+            .map(<&str>::from)
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(RtspResponse::builder()
+            .status(RtspStatus::MethodNotValidInThisState)
+            .header(RtspHeader::Allow, allow)
+            .build()?)
+    }
+
+    /// the current play position, live streams are open ended
+    fn npt_range(&self) -> String {
+        format!("npt={:.3}-", self.parameters.position_at(Instant::now()))
+    }
+
+    /// the scale is the requested one and the one the stream plays at, if the client asked for one
+    fn play_response(
+        &self,
+        rtp_info: &RtpInfoHeader,
+        scale: Option<(ScaleHeader, ScaleHeader)>,
+    ) -> RtspServerResult<RtspResponse> {
+        let mut response_builder = RtspResponse::builder()
+            .ok()
+            .session(&SessionHeader::new(self.session_id.as_ref().unwrap()))
+            .header(RtspHeader::Range, self.npt_range());
+        if !rtp_info.is_empty() {
+            response_builder = response_builder.rtp_info(rtp_info);
+        }
+        if let Some((requested, effective)) = scale {
+            if effective != requested {
+                response_builder = response_builder.header(
+                    RtspHeader::Warning,
+                    format!(
+                        "199 {} \"scale is clamped to {} for live stream\"",
+                        SERVER_AGENT, effective
+                    ),
+                );
+            }
+            response_builder = response_builder.scale(effective);
+        }
+        Ok(response_builder.build()?)
+    }
+
+    /// recordings play at the requested scale, live streams always play at 1.0
+    async fn apply_scale(
+        &mut self,
+        scale: ScaleHeader,
+    ) -> RtspServerResult<(ScaleHeader, ScaleHeader)> {
+        let stream_prop = self.stream_properities.as_ref().unwrap();
+        let stream_id = stream_prop.stream_id.clone();
+        let effective = ScaleHeader(
+            StreamCenter::set_scale(&self.stream_center_event_sender, &stream_id, scale.0).await?,
+        );
+        if effective != scale {
+            tracing::warn!(
+                "scale {} is clamped to {} for live stream, session id: {:?}",
+                scale,
+                effective,
+                self.session_id
+            );
+        }
+        Ok((scale, effective))
+    }
+
+    /// the requested and effective scales, a multicast group always plays at 1.0 for all its receivers
+    async fn effective_scale(
+        &mut self,
+        scale: Option<ScaleHeader>,
+    ) -> RtspServerResult<Option<(ScaleHeader, ScaleHeader)>> {
+        match scale {
+            None => Ok(None),
+            Some(scale) if self.is_multicast() => Ok(Some((scale, ScaleHeader(1.0)))),
+            Some(scale) => Ok(Some(self.apply_scale(scale).await?)),
+        }
+    }
+
+    /// the medias are set up to be played from a multicast group
+    fn is_multicast(&self) -> bool {
+        self.transport
+            .as_ref()
+            .is_some_and(|transport| transport.cast == Some(TransportCast::Multicast))
+    }
+
+    /// RTP/SAVP and a new master key in a=crypto if the medias are played protected with srtp
+    fn with_srtp_key(&self, media: SdpMediaBuilder) -> SdpMediaBuilder {
+        #[cfg(feature = "srtp")]
+        if self.srtp {
+            return media
+                .protocol(SDPMediaProtocol::RtpSAvp)
+                .attribute(SDPAttribute::Crypto(Crypto {
+                    tag: 1,
+                    suite: AES_CM_128_HMAC_SHA1_80.to_owned(),
+                    key_params: SrtpMasterKey::random().to_key_params(),
+                    session_params: vec![],
+                }));
+        }
+        media
+    }
+
+    /// the rtp streams go on with their sequence numbers and rtp times,
+    /// without a dvr window the live stream resumes from its live edge
+    async fn resume_play(&mut self, scale: Option<ScaleHeader>) -> RtspServerResult<RtspResponse> {
+        let paused_for = self.parameters.on_resume(Instant::now());
+        self.play_paused.store(false, Ordering::Release);
+        tracing::info!(
+            "play resumed after {:?}, session_id={:?}",
+            paused_for,
+            self.session_id
+        );
+        let rtp_info = RtpInfoHeader::new(
+            in_sdp_order(&*self.media_sessions.read().await)
+                .into_iter()
+                .map(|value| {
+                    let rtp_info = RtpInfo::new(value.uri.as_str());
+                    match value
+                        .play_position
+                        .as_ref()
+                        .and_then(|position| position.resume_point(paused_for))
+                    {
+                        Some((seq, rtptime)) => rtp_info.with_seq(seq).with_rtptime(rtptime),
+                        None => rtp_info,
+                    }
+                })
+                .collect(),
+        );
+        let scale = self.effective_scale(scale).await?;
+        self.play_response(&rtp_info, scale)
+    }
+
+    /// what the uri of a request controls in the session described
+    fn resolve_control(&self, uri: &Url) -> ControlTarget {
+        match (&self.sdp, &self.content_base) {
+            (Some(sdp), Some(content_base)) => resolve_control(sdp, content_base, uri),
+            _ => ControlTarget::Unknown,
+        }
+    }
+
+    /// 460 for a request on a media of a session controlled as a whole, RFC 7826 13.4.15
+    fn require_aggregate(&self, request: &RtspRequest) -> Option<RtspResponse> {
+        let aggregate = self.sdp.as_ref().is_some_and(is_aggregate_controlled);
+        if aggregate
+            && let ControlTarget::Media { control, .. } = self.resolve_control(request.uri())
+        {
+            tracing::warn!(
+                "{} of media {} in a session under aggregate control",
+                request.method(),
+                control
+            );
+            return Some(rtsp_server_simple_response(
+                RtspStatus::OnlyAggregateOperationAllowed,
+            ));
+        }
+        None
+    }
+
     fn require_headers(
         &self,
         request: &RtspRequest,
@@ -380,6 +1126,44 @@ impl RtspSession {
         None
     }
 
+    fn supported_feature_tags(&self) -> Vec<&'static str> {
+        if self.onvif_backchannel {
+            vec![METHOD_REDIRECT, ONVIF_BACKCHANNEL]
+        } else {
+            vec![METHOD_REDIRECT]
+        }
+    }
+
+    /// 551 with the tags not supported in the Unsupported header,
+    /// the features the client supports are noted too
+    /// @see: RFC 7826 Section 13.5
+    fn check_require(&mut self, request: &RtspRequest) -> Option<RtspResponse> {
+        let required = request.headers().require();
+        if required
+            .iter()
+            .chain(request.headers().supported().iter())
+            .any(|tag| tag == METHOD_REDIRECT)
+        {
+            self.redirect_supported = true;
+        }
+        let unsupported =
+            feature_tag::unsupported_feature_tags(&required, &self.supported_feature_tags());
+        if !unsupported.is_empty() {
+            tracing::warn!("unsupported option tags required: {:?}", unsupported);
+            return Some(
+                RtspResponse::builder()
+                    .status(RtspStatus::OptionNotSupported)
+                    .header(RtspHeader::Unsupported, unsupported.join(", "))
+                    .build()
+                    .unwrap(),
+            );
+        }
+        if required.iter().any(|tag| tag == ONVIF_BACKCHANNEL) {
+            self.backchannel_required = true;
+        }
+        None
+    }
+
     async fn publish_stream(
         &mut self,
         request: &RtspRequest,
@@ -391,15 +1175,7 @@ impl RtspSession {
         let media_sender = StreamCenter::publish(
             &self.stream_center_event_sender,
             PublishProtocol::RTSP,
-            &StreamIdentifier {
-                stream_name: self
-                    .stream_properities
-                    .as_ref()
-                    .unwrap()
-                    .stream_name
-                    .clone(),
-                app: self.stream_properities.as_ref().unwrap().app.clone(),
-            },
+            &self.stream_properities.as_ref().unwrap().stream_id,
             &self.stream_properities.as_ref().unwrap().stream_context,
         )
         .await;
@@ -411,29 +1187,51 @@ impl RtspSession {
             stream_data_producer: media_sender.unwrap(),
             no_data_since: None,
         })));
+        let stream_prop = self.stream_properities.as_ref().unwrap();
+        let stream_id = stream_prop.stream_id.clone();
+        self.session_sdes()
+            .attach(self.stream_center_event_sender.clone(), stream_id, None);
+        self.stream_span = Some(stream_span(
+            &self.session_span,
+            &self.stream_key(),
+            StreamRole::Publish,
+        ));
+        self.register_role(SessionRole::Publisher);
         tracing::info!("rtsp stream publish to stream center succeed");
         Ok(None)
     }
 
     async fn unpublish_stream(&mut self) -> RtspServerResult<()> {
         if let Some(stream_prop) = self.stream_properities.as_ref() {
-            let unpublish_response = StreamCenter::unpublish(
-                &self.stream_center_event_sender,
-                &StreamIdentifier {
-                    stream_name: stream_prop.stream_name.clone(),
-                    app: stream_prop.app.clone(),
-                },
-            )
-            .await;
+            let unpublish_response =
+                StreamCenter::unpublish(&self.stream_center_event_sender, &stream_prop.stream_id)
+                    .await;
             if let Err(err) = unpublish_response {
                 tracing::error!("rtsp stream unpublish from stream center failed: {}", err);
                 return Err(err.into());
             }
             tracing::info!("rtsp stream unpublish from stream center succeed");
+            self.ingest_limiter.release(&self.stream_key());
         }
         Ok(())
     }
 
+    /// the cname is derived from the stream, so the stream must be known before the first media session
+    fn session_sdes(&mut self) -> SessionSdes {
+        let stream_key = self.stream_key();
+        let sdes = &self.sdes;
+        self.session_sdes
+            .get_or_insert_with(|| SessionSdes::new(sdes.describe(&stream_key)))
+            .clone()
+    }
+
+    fn stream_key(&self) -> String {
+        self.stream_properities
+            .as_ref()
+            .map(|stream_prop| stream_prop.stream_id.to_string())
+            .unwrap_or_default()
+    }
+
     async fn unsubscribe_stream(&mut self) -> RtspServerResult<()> {
         let play_handle = self.runtime_handle.get_play_handle();
         if play_handle.is_none() {
@@ -444,10 +1242,7 @@ impl RtspSession {
             let unsubscribe_response = StreamCenter::unsubscribe(
                 &self.stream_center_event_sender,
                 play_id,
-                &StreamIdentifier {
-                    stream_name: stream_prop.stream_name.clone(),
-                    app: stream_prop.app.clone(),
-                },
+                &stream_prop.stream_id,
             )
             .await;
             if let Err(err) = unsubscribe_response {
@@ -467,19 +1262,28 @@ impl RtspSession {
             return Ok(Some(res));
         }
 
+        // only the medias set up by the client are subscribed
+        let media_selection = {
+            let sessions = self.media_sessions.read().await;
+            let has_media = |media_type: SDPMediaType| {
+                sessions
+                    .values()
+                    .any(|value| value.media_sdp.media_line.media_type == media_type)
+            };
+            MediaSelection {
+                audio: has_media(SDPMediaType::Audio),
+                video: has_media(SDPMediaType::Video),
+                data: false,
+                ..Default::default()
+            }
+        };
+
         let subscribe_response = StreamCenter::subscribe(
             &self.stream_center_event_sender,
             PlayProtocol::RTSP,
-            &StreamIdentifier {
-                stream_name: self
-                    .stream_properities
-                    .as_ref()
-                    .unwrap()
-                    .stream_name
-                    .clone(),
-                app: self.stream_properities.as_ref().unwrap().app.clone(),
-            },
+            &self.stream_properities.as_ref().unwrap().stream_id,
             &self.stream_properities.as_ref().unwrap().stream_context,
+            media_selection,
         )
         .await;
 
@@ -489,31 +1293,129 @@ impl RtspSession {
         }
 
         let subscribe_response = subscribe_response.unwrap();
+        self.parameters.set_live(!subscribe_response.recording);
+        let stream_prop = self.stream_properities.as_ref().unwrap();
+        let stream_id = stream_prop.stream_id.clone();
+        self.session_sdes().attach(
+            self.stream_center_event_sender.clone(),
+            stream_id,
+            Some(subscribe_response.subscribe_id),
+        );
         self.runtime_handle = SessionRuntime::Play(Arc::new(RwLock::new(PlayHandle {
             stream_data_consumer: subscribe_response.media_receiver,
             play_id: subscribe_response.subscribe_id,
-            receive_audio: true,
-            receive_video: true,
+            receive_audio: media_selection.audio,
+            receive_video: media_selection.video,
+            audio_track: media_selection.audio_track,
             buffer_length: None,
+            latency_probe: subscribe_response.latency_probe,
         })));
+        self.stream_span = Some(stream_span(
+            &self.session_span,
+            &self.stream_key(),
+            StreamRole::Subscribe,
+        ));
+        self.register_role(SessionRole::Subscriber);
 
         Ok(None)
     }
 
-    async fn new_play_session(
+    /// joins the session to the delivery of the stream to its group, nothing is sent to the client itself
+    async fn play_multicast(
         &mut self,
         request: &RtspRequest,
-        transport: &TransportHeader,
+        stream_prop: StreamProperties,
+        scale: Option<ScaleHeader>,
     ) -> RtspServerResult<RtspResponse> {
-        tracing::debug!(
-            "creating new rtsp play session with request: {}, transport: {}",
-            request,
-            transport
-        );
-
-        let sdp = self.sdp.as_ref().unwrap();
-        let mut server_transport = transport.clone();
-        let generated_session_id = Uuid::now_v7().to_string();
+        if let Some(res) = self.session_pre_setup(stream_prop, false) {
+            return Ok(res);
+        }
+        let lease = self
+            .multicast
+            .join(
+                &self.stream_center_event_sender,
+                self.stream_properities.as_ref().unwrap(),
+                request.uri(),
+                self.sdp.as_ref().unwrap(),
+                self.retransmission,
+                self.fec_config,
+                self.sdes.describe(&self.stream_key()),
+            )
+            .await?;
+        self.multicast_lease = Some(lease);
+        self.register_role(SessionRole::Subscriber);
+        let rtp_info = RtpInfoHeader::new(
+            in_sdp_order(&*self.media_sessions.read().await)
+                .into_iter()
+                .map(|value| RtpInfo::new(value.uri.as_str()))
+                .collect(),
+        );
+        let scale = self.effective_scale(scale).await?;
+        self.parameters.on_play(Instant::now());
+        self.play_response(&rtp_info, scale)
+    }
+
+    async fn new_play_session(
+        &mut self,
+        request: &RtspRequest,
+        transport: &TransportHeader,
+        media_index: usize,
+        control: RtspSDPControl,
+    ) -> RtspServerResult<RtspResponse> {
+        tracing::debug!(
+            "creating new rtsp play session with request: {}, transport: {}",
+            request,
+            transport
+        );
+        let multicast = transport.cast == Some(TransportCast::Multicast);
+        if self.transport.is_some() && self.is_multicast() != multicast {
+            tracing::warn!("the medias of a session are either all multicast or all unicast");
+            return Ok(rtsp_server_simple_response(
+                RtspStatus::UnsupportedTransport,
+            ));
+        }
+        // the medias of a session are either all protected or none, as they are described
+        let secure = transport
+            .profile
+            .as_ref()
+            .is_some_and(TransportProtocol::is_secure);
+        if secure != self.srtp || (secure && multicast) {
+            tracing::warn!(
+                "transport {} does not match the medias described, srtp: {}",
+                transport,
+                self.srtp
+            );
+            return Ok(rtsp_server_simple_response(
+                RtspStatus::UnsupportedTransport,
+            ));
+        }
+        if multicast {
+            return self
+                .new_multicast_play_session(request, transport, media_index, control)
+                .await;
+        }
+
+        // the client may be behind a path with a small mtu, the limit holds for the later medias too
+        let blocksize = match request
+            .headers()
+            .get_unique(RtspHeader::Blocksize)
+            .map(|blocksize| blocksize.trim().parse::<u64>())
+            .transpose()
+        {
+            Ok(blocksize) => blocksize.map(effective_blocksize),
+            Err(err) => {
+                tracing::warn!("invalid blocksize header: {}", err);
+                return Ok(rtsp_server_simple_response(RtspStatus::BadRequest));
+            }
+        };
+        if let Some(blocksize) = blocksize {
+            self.max_rtp_packet_size = blocksize;
+        }
+
+        let sdes = self.session_sdes();
+        let sdp = self.sdp.as_ref().unwrap();
+        let mut server_transport = transport.clone();
+        let generated_session_id = Uuid::now_v7().to_string();
         let this_session_id = self
             .session_id
             .as_ref()
@@ -521,69 +1423,108 @@ impl RtspSession {
             .clone();
         let mut response_builder = RtspResponse::builder();
 
-        for media in &sdp.media_description {
-            let control = media.attributes.iter().find_map(|attr| {
-                if let SDPAttribute::Trivial(attr) = attr
-                    && attr.name == "control"
-                {
-                    RtspSDPControl::try_from(attr).ok()
-                } else {
-                    None
-                }
-            });
-            if control.is_none() {
-                tracing::warn!("media control attribute not found");
-                continue;
-            }
-            let rtpmap = media.get_rtp_map();
-            if rtpmap.is_none() {
-                tracing::warn!("rtpmap attribute not found");
-                continue;
-            }
-            let control = control.unwrap();
-            let control_str = control.url_to_str();
-            if !request.uri().path().contains(control_str.as_str()) {
-                continue;
-            }
-
-            if let Some(session) = self.media_sessions.read().await.get(control_str.as_str()) {
-                tracing::warn!("media session already exists: {:?}", session);
+        let media = &sdp.media_description[media_index];
+        let Some(rtpmap) = PayloadTypeMap::from_media(media)
+            .preferred(&media.media_line.media_type)
+            .map(|format| format.rtpmap.clone())
+        else {
+            tracing::warn!("rtpmap attribute not found");
+            return Ok(rtsp_server_simple_response(
+                RtspStatus::UnsupportedMediaType,
+            ));
+        };
+        match media.media_line.media_type {
+            SDPMediaType::Video => {}
+            SDPMediaType::Audio => {}
+            _ => {
+                tracing::warn!("unsupported media type: {:?}", media.media_line.media_type);
+                return Ok(rtsp_server_simple_response(RtspStatus::BadRequest));
             }
-
+        }
+        let control_str = control.url_to_str();
+        // a media set up again gets the new transport, the sockets of the old one are closed
+        if let Some(replaced) = self.media_sessions.write().await.remove(&control_str) {
             tracing::info!(
-                "new rtsp media play session, session id: {}, uri: {}, control: {}, media_description: {:?}, transport: {}",
-                this_session_id,
-                request.uri(),
-                control,
-                media,
-                transport,
+                "media of control {} is set up again, replacing transport {}",
+                control_str,
+                replaced.transport
             );
+            replaced.abort();
+        }
+
+        tracing::info!(
+            "new rtsp media play session, session id: {}, uri: {}, control: {}, media_description: {:?}, transport: {}",
+            this_session_id,
+            request.uri(),
+            control,
+            media,
+            transport,
+        );
+        let media_session = if is_onvif_backchannel(media) {
+            // the audio of the client has no stream to be fed to
+            self.media_sessions.write().await.insert(
+                control_str.clone(),
+                RtspMediaSessionHandler {
+                    uri: request.uri().clone(),
+                    media_sdp: media.clone(),
+                    transport: transport.clone(),
+                    media_frame_sender: None,
+                    play_position: None,
+                    media_index,
+                    task: None,
+                },
+            );
+            RtspMediaSession::new_backchannel_session(
+                self.peer_addr,
+                request.uri().clone(),
+                this_session_id.clone(),
+                media,
+                transport.clone(),
+                self.rtsp_command_tx.subscribe(),
+                &sdes,
+                &self.capture_tap,
+                self.rtp_io_factory.as_ref(),
+            )
+            .await
+        } else {
             let (media_frame_distributor_tx, media_frame_distributor_rx) =
                 tokio::sync::mpsc::channel::<MediaFrame>(1000);
             self.media_sessions.write().await.insert(
                 control_str.clone(),
                 RtspMediaSessionHandler {
-                    peer_addr: self.peer_addr,
                     uri: request.uri().clone(),
-                    session_id: this_session_id.clone(),
                     media_sdp: media.clone(),
                     transport: transport.clone(),
                     media_frame_sender: Some(media_frame_distributor_tx),
+                    play_position: None,
+                    media_index,
+                    task: None,
                 },
             );
-            let media_session = RtspMediaSession::new_play_session(
+            RtspMediaSession::new_play_session(
                 self.peer_addr,
                 request.uri().clone(),
                 &control,
                 media,
-                &rtpmap.unwrap(),
+                &rtpmap,
                 this_session_id.clone(),
                 transport.clone(),
                 self.rtsp_command_tx.subscribe(),
                 media_frame_distributor_rx,
+                self.retransmission,
+                self.fec_config,
+                self.pacing,
+                self.max_rtp_packet_size,
+                &sdes,
+                &self.capture_tap,
+                self.rtp_io_factory.as_ref(),
             )
-            .await;
-            if let Err(err) = media_session {
+            .await
+        };
+        let mut media_session = match media_session {
+            Ok(media_session) => media_session,
+            Err(err) => {
+                self.media_sessions.write().await.remove(&control_str);
                 if let RtspServerError::InvalidTransport(err) = err {
                     tracing::error!("transport: {} is invalid", err);
                     return Ok(rtsp_server_simple_response(
@@ -594,45 +1535,53 @@ impl RtspSession {
                     return Err(err);
                 }
             }
-            let mut media_session = media_session.unwrap();
-            tracing::info!(
-                "media session created for session id: {}, control: {}",
-                this_session_id,
-                control_str
-            );
+        };
+        tracing::info!(
+            "media session created for session id: {}, control: {}",
+            this_session_id,
+            control_str
+        );
 
-            server_transport
-                .server_port
-                .replace((media_session.local_rtp_port, media_session.local_rtcp_port));
-            response_builder =
-                response_builder.header(RtspHeader::Transport, format!("{}", server_transport));
-            media_session.transport = server_transport.clone();
-            tokio::task::spawn(async move {
+        server_transport
+            .server_port
+            .replace((media_session.local_rtp_port, media_session.local_rtcp_port));
+        response_builder = response_builder.transport(&server_transport);
+        media_session.transport = server_transport.clone();
+        self.transport = Some(server_transport.clone());
+        self.parameters
+            .add_packets_counter(media_session.packets_sent());
+        self.parameters
+            .add_retransmission_metrics(media_session.retransmission_metrics());
+        self.parameters.add_fec_metrics(media_session.fec_metrics());
+        let play_position = media_session.play_position();
+        let task = tokio::task::spawn(
+            async move {
                 if let Err(err) = media_session.run().await {
                     tracing::error!("media session error: {:?}", err);
                 } else {
                     tracing::info!("media session exited gracefully");
                 }
-            });
-            match media.media_line.media_type {
-                SDPMediaType::Video => {}
-                SDPMediaType::Audio => {}
-                _ => {
-                    tracing::warn!("unsupported media type: {:?}", media.media_line.media_type);
-                    return Ok(rtsp_server_simple_response(RtspStatus::BadRequest));
-                }
             }
+            .instrument(self.span()),
+        );
+        if let Some(handler) = self.media_sessions.write().await.get_mut(&control_str) {
+            handler.play_position = play_position;
+            handler.task = Some(task.abort_handle());
         }
 
         if self.session_id.is_none() {
             tracing::trace!("new publish session, session_id={}", this_session_id);
             self.session_id = Some(this_session_id);
+            self.session_uri = Some(request.uri().clone());
+        }
+        if blocksize.is_some() {
+            response_builder = response_builder
+                .header(RtspHeader::Blocksize, self.max_rtp_packet_size.to_string());
         }
         let response = response_builder
-            .header(RtspHeader::Session, format!(
-                "{};timeout={}",
-                self.session_id.as_ref().unwrap(),
-                self.timeout_ms.checked_div(1000).unwrap())
+            .session(
+                &SessionHeader::new(self.session_id.as_ref().unwrap())
+                    .with_timeout(self.timeout_ms / 1000),
             )
             .header(RtspHeader::AcceptRanges, "npt")
             .header(RtspHeader::MediaProperties, "Random Access: No-Seeking, Content Modifications: TimeProgressing, Retention: Time-Duration=0.0")
@@ -640,85 +1589,178 @@ impl RtspSession {
             .build()?;
         Ok(response)
     }
+    /// answers the group and ports the media is multicast to, the delivery starts with PLAY
+    async fn new_multicast_play_session(
+        &mut self,
+        request: &RtspRequest,
+        transport: &TransportHeader,
+        media_index: usize,
+        control: RtspSDPControl,
+    ) -> RtspServerResult<RtspResponse> {
+        let stream_prop: StreamProperties = request.uri().try_into()?;
+        let stream_id = stream_prop.stream_id;
+        let Some(group) = self.multicast.group(&stream_id) else {
+            tracing::warn!("no multicast group is configured for stream {}", stream_id);
+            return Ok(rtsp_server_simple_response(
+                RtspStatus::UnsupportedTransport,
+            ));
+        };
+
+        let sdp = self.sdp.as_ref().unwrap();
+        let this_session_id = self
+            .session_id
+            .clone()
+            .unwrap_or_else(|| Uuid::now_v7().to_string());
+        let mut response_builder = RtspResponse::builder();
+        let media = &sdp.media_description[media_index];
+        // the audio of the client has nowhere to go in a group
+        let server_transport = match group.transport(transport, media_index) {
+            Some(server_transport) if !is_onvif_backchannel(media) => server_transport,
+            _ => {
+                return Ok(rtsp_server_simple_response(
+                    RtspStatus::UnsupportedTransport,
+                ));
+            }
+        };
+        tracing::info!(
+            "new rtsp media multicast play session, session id: {}, uri: {}, control: {}, transport: {}",
+            this_session_id,
+            request.uri(),
+            control,
+            server_transport,
+        );
+        self.media_sessions.write().await.insert(
+            control.url_to_str(),
+            RtspMediaSessionHandler {
+                uri: request.uri().clone(),
+                media_sdp: media.clone(),
+                transport: server_transport.clone(),
+                media_frame_sender: None,
+                play_position: None,
+                media_index,
+                task: None,
+            },
+        );
+        response_builder = response_builder.transport(&server_transport);
+        self.transport = Some(server_transport);
+
+        if self.session_id.is_none() {
+            self.session_id = Some(this_session_id);
+            self.session_uri = Some(request.uri().clone());
+        }
+        Ok(response_builder
+            .session(
+                &SessionHeader::new(self.session_id.as_ref().unwrap())
+                    .with_timeout(self.timeout_ms / 1000),
+            )
+            .header(RtspHeader::AcceptRanges, "npt")
+            .ok()
+            .build()?)
+    }
+
     async fn new_publish_session(
         &mut self,
         request: &RtspRequest,
         transport: &TransportHeader,
+        media_index: usize,
+        control: RtspSDPControl,
     ) -> RtspServerResult<RtspResponse> {
+        if transport
+            .profile
+            .as_ref()
+            .is_some_and(TransportProtocol::is_secure)
+        {
+            tracing::warn!(
+                "publishing with srtp is not supported, transport: {}",
+                transport
+            );
+            return Ok(rtsp_server_simple_response(
+                RtspStatus::UnsupportedTransport,
+            ));
+        }
         if self.session_id.is_none()
             && let Some(response) = self.publish_stream(request).await?
         {
             return Ok(response);
         }
+        let sdes = self.session_sdes();
         let sdp = self.sdp.as_ref().unwrap();
         let mut server_transport = transport.clone();
 
         let generated_session_id = Uuid::now_v7().to_string();
         let this_session_id = self.session_id.as_ref().unwrap_or(&generated_session_id);
         let mut response_builder = RtspResponse::builder();
-        for media in &sdp.media_description {
-            let control = media.attributes.iter().find_map(|attr| {
-                if let SDPAttribute::Trivial(attr) = attr
-                    && attr.name == "control"
-                {
-                    RtspSDPControl::try_from(attr).ok()
-                } else {
-                    None
-                }
-            });
-            if control.is_none() {
-                tracing::warn!("media control attribute not found");
-                continue;
+        let media = &sdp.media_description[media_index];
+        match media.media_line.media_type {
+            SDPMediaType::Video => {}
+            SDPMediaType::Audio => {}
+            _ => {
+                tracing::warn!("unsupported media type: {:?}", media.media_line.media_type);
+                return Ok(rtsp_server_simple_response(RtspStatus::BadRequest));
             }
-            let control = control.unwrap();
-            let control_str = control.url_to_str();
-            if !request.uri().path().contains(control_str.as_str()) {
-                continue;
-            }
-
-            if let Some(session) = self.media_sessions.read().await.get(control_str.as_str()) {
-                tracing::warn!("media session already exists: {:?}", session);
-            }
-
+        }
+        let control_str = control.url_to_str();
+        // a media set up again gets the new transport, the sockets of the old one are closed
+        if let Some(replaced) = self.media_sessions.write().await.remove(&control_str) {
             tracing::info!(
-                "new rtsp media publish session, session id: {}, uri: {}, control: {}, media_description: {:?}, transport: {}",
-                this_session_id,
-                request.uri(),
-                control,
-                media,
-                transport,
+                "media of control {} is set up again, replacing transport {}",
+                control_str,
+                replaced.transport
             );
+            replaced.abort();
+        }
 
-            self.media_sessions.write().await.insert(
-                control_str.clone(),
-                RtspMediaSessionHandler {
-                    peer_addr: self.peer_addr,
-                    uri: request.uri().clone(),
-                    session_id: this_session_id.clone(),
-                    media_sdp: media.clone(),
-                    transport: transport.clone(),
-                    media_frame_sender: None,
-                },
-            );
+        tracing::info!(
+            "new rtsp media publish session, session id: {}, uri: {}, control: {}, media_description: {:?}, transport: {}",
+            this_session_id,
+            request.uri(),
+            control,
+            media,
+            transport,
+        );
 
-            let media_session = RtspMediaSession::new_publish_session(
-                self.peer_addr,
-                request.uri().clone(),
-                this_session_id.clone(),
-                media.clone(),
-                transport.clone(),
-                self.rtsp_command_tx.subscribe(),
-                self.runtime_handle
-                    .get_publish_handle()
-                    .unwrap()
-                    .clone()
-                    .read()
-                    .await
-                    .stream_data_producer
-                    .clone(),
-            )
-            .await;
-            if let Err(err) = media_session {
+        self.media_sessions.write().await.insert(
+            control_str.clone(),
+            RtspMediaSessionHandler {
+                uri: request.uri().clone(),
+                media_sdp: media.clone(),
+                transport: transport.clone(),
+                media_frame_sender: None,
+                play_position: None,
+                media_index,
+                task: None,
+            },
+        );
+
+        let media_session = RtspMediaSession::new_publish_session(
+            self.peer_addr,
+            request.uri().clone(),
+            this_session_id.clone(),
+            media.clone(),
+            transport.clone(),
+            self.rtsp_command_tx.subscribe(),
+            self.runtime_handle
+                .get_publish_handle()
+                .unwrap()
+                .clone()
+                .read()
+                .await
+                .stream_data_producer
+                .clone(),
+            self.ingest_limiter.clone(),
+            self.stream_key(),
+            self.h264_buffer,
+            self.h264_access_unit_delimiters,
+            self.h264_reorder_frames,
+            &sdes,
+            &self.capture_tap,
+            self.rtp_io_factory.as_ref(),
+        )
+        .await;
+        let mut media_session = match media_session {
+            Ok(media_session) => media_session,
+            Err(err) => {
+                self.media_sessions.write().await.remove(&control_str);
                 if let RtspServerError::InvalidTransport(err) = err {
                     tracing::error!("transport: {} is invalid", err);
                     return Ok(rtsp_server_simple_response(
@@ -729,47 +1771,47 @@ impl RtspSession {
                     return Err(err);
                 }
             }
-            let mut media_session = media_session.unwrap();
-            tracing::info!(
-                "media session created for session id: {}, control: {}",
-                this_session_id,
-                control_str
-            );
-
-            server_transport
-                .server_port
-                .replace((media_session.local_rtp_port, media_session.local_rtcp_port));
-            response_builder =
-                response_builder.header(RtspHeader::Transport, format!("{}", server_transport));
+        };
+        tracing::info!(
+            "media session created for session id: {}, control: {}",
+            this_session_id,
+            control_str
+        );
 
-            media_session.transport = server_transport.clone();
-            tokio::task::spawn(async move {
+        server_transport
+            .server_port
+            .replace((media_session.local_rtp_port, media_session.local_rtcp_port));
+        response_builder = response_builder.transport(&server_transport);
+
+        media_session.transport = server_transport.clone();
+        self.transport = Some(server_transport.clone());
+        self.parameters
+            .add_buffer_metrics(media_session.buffer_metrics());
+        self.parameters
+            .add_unknown_payload_type_counter(media_session.unknown_payload_type_packets());
+        let task = tokio::task::spawn(
+            async move {
                 if let Err(err) = media_session.run().await {
                     tracing::error!("media session error: {:?}", err);
                 } else {
                     tracing::info!("media session exited gracefully");
                 }
-            });
-
-            match media.media_line.media_type {
-                SDPMediaType::Video => {}
-                SDPMediaType::Audio => {}
-                _ => {
-                    tracing::warn!("unsupported media type: {:?}", media.media_line.media_type);
-                    return Ok(rtsp_server_simple_response(RtspStatus::BadRequest));
-                }
             }
+            .instrument(self.span()),
+        );
+        if let Some(handler) = self.media_sessions.write().await.get_mut(&control_str) {
+            handler.task = Some(task.abort_handle());
         }
 
         if self.session_id.is_none() {
             tracing::trace!("new publish session, session_id={}", this_session_id);
             self.session_id = Some(this_session_id.clone());
+            self.session_uri = Some(request.uri().clone());
         }
         let response = response_builder
-            .header(RtspHeader::Session, format!(
-                "{};timeout={}",
-                self.session_id.as_ref().unwrap(),
-                self.timeout_ms.checked_div(1000).unwrap())
+            .session(
+                &SessionHeader::new(self.session_id.as_ref().unwrap())
+                    .with_timeout(self.timeout_ms / 1000),
             )
             .header(RtspHeader::AcceptRanges, "npt")
             .header(RtspHeader::MediaProperties, "Random Access: No-Seeking, Content Modifications: TimeProgressing, Retention: Time-Duration=0.0")
@@ -824,6 +1866,10 @@ impl RtspRequestHandler for RtspSession {
         let response = RtspResponse::builder()
             .status(RtspStatus::OK)
             .header(RtspHeader::Public, RTSP_METHODS.join(","))
+            .header(
+                RtspHeader::Supported,
+                self.supported_feature_tags().join(", "),
+            )
             .build()?;
         Ok(response)
     }
@@ -831,12 +1877,41 @@ impl RtspRequestHandler for RtspSession {
     async fn handle_describe(&mut self, request: &RtspRequest) -> RtspServerResult<RtspResponse> {
         // Handle DESCRIBE request
         let stream_properities: StreamProperties = request.uri().try_into()?;
-        let stream_id = StreamIdentifier {
-            stream_name: stream_properities.stream_name,
-            app: stream_properities.app,
+        let stream_id = stream_properities.stream_id;
+        let described = tokio::time::timeout(
+            self.describe.wait,
+            StreamCenter::describe_configured(&self.stream_center_event_sender, &stream_id),
+        )
+        .await;
+        let (media_description, medias) = match described {
+            Ok(media_description) => {
+                let media_description = media_description?;
+                let medias = self.describe_cache.get_or_describe(&media_description)?;
+                (media_description, medias)
+            }
+            // not cached, the sequence headers may still come
+            Err(_) if self.describe.minimal_sdp_on_timeout => {
+                tracing::warn!(
+                    "the sequence headers of stream {} did not come in {:?}, describe the medias known",
+                    stream_id,
+                    self.describe.wait
+                );
+                let media_description =
+                    StreamCenter::describe(&self.stream_center_event_sender, &stream_id).await?;
+                let medias = DescribedMedias::new(&media_description)?;
+                (media_description, medias)
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "the sequence headers of stream {} did not come in {:?}",
+                    stream_id,
+                    self.describe.wait
+                );
+                return Ok(rtsp_server_simple_response(RtspStatus::NotEnoughBandwidth));
+            }
         };
-        let media_description =
-            StreamCenter::describe(&self.stream_center_event_sender, &stream_id).await?;
+        // players can tell the network delay from the send time if the latency is measured
+        let offer_abs_send_time = media_description.latency.is_some();
 
         tracing::info!("media description: {:#?}", media_description);
         let mut sdp_builder = SdpBuilder::new()
@@ -851,74 +1926,36 @@ impl RtspRequestHandler for RtspSession {
             .attribute(SDPAttribute::Trivial((&RtspSDPControl::Asterisk).into()))
             .time_info(0, 0, vec![]);
 
-        if media_description.has_audio
-            && let Some(audio_config) = media_description.audio_conifg
-        {
-            let codec_id = (&audio_config).into();
-            let payload_type = get_audio_rtp_payload_type(codec_id).unwrap();
-            let mut audio_sdp = SdpMediaBuilder::new()
-                .media_type(SDPMediaType::Audio)
-                .port(0.into())
-                .protocol(sdp_formats::session::SDPMediaProtocol::RtpAvp)
-                .media_format(payload_type.to_string())
-                .attribute(SDPAttribute::Trivial(
-                    (&RtspSDPControl::Relative("control=audio".to_owned())).into(),
-                ))
-                .rtpmap(RtpMap {
-                    payload_type,
-                    encoding_name: audio_get_rtp_encoding_name(codec_id).unwrap().to_string(),
-                    clock_rate: audio_get_rtp_clockrate(codec_id).unwrap().to_u64().unwrap(),
-                    encoding_params: None,
-                });
-            match audio_config {
-                AudioConfig::AAC(aac_config) => {
-                    let audio_fmtp: RtpMpeg4Fmtp = (&aac_config).try_into()?;
-                    let fmtp = FormatParameters {
-                        fmt: payload_type,
-                        params: format!("{}", audio_fmtp),
-                    };
-                    audio_sdp = audio_sdp.fmtp(fmtp);
-                }
+        self.fec_config = self.fec.get(&stream_id).copied().unwrap_or_default();
+        for described in [medias.audio, medias.video].into_iter().flatten() {
+            let media_type = described.media.media_line.media_type.clone();
+            let mut media = SdpMediaBuilder::from(described.media);
+            if self.offer_rtx {
+                media = with_rtx(media, described.payload_type, described.clock_rate);
             }
-            sdp_builder = sdp_builder.media_description(audio_sdp.build());
-        }
-        if media_description.has_video
-            && let Some(video_config) = media_description.video_config
-        {
-            let codec_id = (&video_config).into();
-            let payload_type = get_video_rtp_payload_type(codec_id).unwrap();
-            let mut video_sdp = SdpMediaBuilder::new()
-                .media_type(SDPMediaType::Video)
-                .port(0.into())
-                .protocol(sdp_formats::session::SDPMediaProtocol::RtpAvp)
-                .media_format(payload_type.to_string())
-                .attribute(SDPAttribute::Trivial(
-                    (&RtspSDPControl::Relative("control=video".to_owned())).into(),
-                ))
-                .rtpmap(RtpMap {
-                    payload_type,
-                    encoding_name: video_get_rtp_encoding_name(codec_id).unwrap().to_string(),
-                    clock_rate: video_get_rtp_clockrate(codec_id).unwrap().to_u64().unwrap(),
-                    encoding_params: None,
-                });
-            match video_config {
-                codec_common::video::VideoConfig::H264(h264_config) => {
-                    let video_fmtp: RtpH264Fmtp = RtpH264FmtpBuilder::from(&h264_config)
-                        .packetization_mode(PacketizationMode::NonInterleaved)
-                        .build();
-                    let fmtp = FormatParameters {
-                        fmt: payload_type,
-                        params: format!("{}", video_fmtp),
-                    };
-                    video_sdp = video_sdp.fmtp(fmtp);
+            match media_type {
+                SDPMediaType::Audio if self.fec_config.red => {
+                    media = with_red(media, described.payload_type, described.clock_rate);
+                }
+                SDPMediaType::Video if self.fec_config.ulpfec_group_size.is_some() => {
+                    media = with_ulpfec(media);
                 }
+                _ => {}
+            }
+            if offer_abs_send_time {
+                media = with_abs_send_time(media);
             }
-            sdp_builder = sdp_builder.media_description(video_sdp.build());
+            sdp_builder = sdp_builder.media_description(self.with_srtp_key(media).build());
+        }
+        if self.backchannel_required {
+            sdp_builder = sdp_builder
+                .media_description(self.with_srtp_key(onvif_backchannel_media()).build());
         }
 
         let sdp = sdp_builder.build();
         let sdp_str = sdp.to_string();
         self.sdp = Some(sdp);
+        self.content_base = Some(request.uri().clone());
         let response = RtspResponseBuilder::new()
             .header(RtspHeader::ContentType, "application/sdp")
             .header(RtspHeader::ContentBase, request.uri().as_str())
@@ -954,6 +1991,29 @@ impl RtspRequestHandler for RtspSession {
             tracing::error!("sdp is not set by now, unable to handle SETUP request");
             return Ok(rtsp_server_simple_response(RtspStatus::NotAcceptable));
         }
+        let (media_index, control) = match self.resolve_control(request.uri()) {
+            ControlTarget::Media { index, control } => (index, control),
+            ControlTarget::Aggregate => {
+                tracing::warn!("SETUP of the aggregate control uri {}", request.uri());
+                return Ok(rtsp_server_simple_response(
+                    RtspStatus::AggregateOperationNotAllowed,
+                ));
+            }
+            ControlTarget::Unknown => {
+                tracing::warn!("no media is controlled by uri {}", request.uri());
+                return Ok(rtsp_server_simple_response(RtspStatus::NotFound));
+            }
+        };
+        // the frames are distributed to the media sessions there are when the play starts
+        if self.runtime_handle.is_play()
+            && self
+                .media_sessions
+                .read()
+                .await
+                .contains_key(&control.url_to_str())
+        {
+            return self.method_not_valid_in_this_state();
+        }
 
         let transport_mode = if transport.mode.is_empty() {
             &TransportMode::Play
@@ -962,8 +2022,14 @@ impl RtspRequestHandler for RtspSession {
         };
 
         match transport_mode {
-            TransportMode::Play => self.new_play_session(request, &transport).await,
-            TransportMode::Record => self.new_publish_session(request, &transport).await,
+            TransportMode::Play => {
+                self.new_play_session(request, &transport, media_index, control)
+                    .await
+            }
+            TransportMode::Record => {
+                self.new_publish_session(request, &transport, media_index, control)
+                    .await
+            }
             TransportMode::Other(mode) => {
                 tracing::error!("unknow transport mode in SETUP method: {}", mode);
                 Ok(rtsp_server_simple_response(
@@ -977,27 +2043,73 @@ impl RtspRequestHandler for RtspSession {
         if let Some(res) = self.require_headers(request, &[RtspHeader::Session]) {
             return Ok(res);
         }
-        if self.session_id.as_ref() != request.headers().get_unique(RtspHeader::Session) {
+        if self.session_id != request.headers().session().map(|session| session.id) {
             return Ok(rtsp_server_simple_response(RtspStatus::SessionNotFound));
         }
+        if let Some(response) = self.require_aggregate(request) {
+            return Ok(response);
+        }
+        let scale = match request
+            .headers()
+            .get_unique(RtspHeader::Scale)
+            .map(|scale| scale.parse::<ScaleHeader>())
+            .transpose()
+        {
+            Ok(scale) => scale,
+            Err(err) => {
+                tracing::warn!("invalid scale header: {}", err);
+                return Ok(rtsp_server_simple_response(RtspStatus::BadRequest));
+            }
+        };
 
-        if self.runtime_handle.is_play() || self.runtime_handle.is_publish() {
-            return Ok(rtsp_server_simple_response(
-                RtspStatus::MethodNotValidInThisState,
-            ));
+        match self.state() {
+            RtspSessionState::Play | RtspSessionState::Record => {
+                return self.method_not_valid_in_this_state();
+            }
+            RtspSessionState::Ready if self.parameters.is_paused() => {
+                return self.resume_play(scale).await;
+            }
+            _ => {}
         }
-        let stream_prop: StreamProperties = request.uri().try_into()?;
+        let mut stream_prop: StreamProperties = request.uri().try_into()?;
+        if self.is_multicast() {
+            return self.play_multicast(request, stream_prop, scale).await;
+        }
+        match play_range_start(request) {
+            // npt is the stream time, a start after 0 plays from the dvr window of the stream
+            Ok(Some(start)) => {
+                stream_prop
+                    .stream_context
+                    .insert(DVR_START_KEY.to_owned(), start.to_string());
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!("invalid range header: {}", err);
+                return Ok(rtsp_server_simple_response(RtspStatus::InvalidRange));
+            }
+        }
+        let shaper = self.egress_shaper.subscriber(
+            &stream_prop.stream_id.to_string(),
+            &stream_prop.stream_context,
+        );
         if let Some(response) = self.subscribe_stream(stream_prop).await? {
             return Ok(response);
         }
+        let scale = self.effective_scale(scale).await?;
         let play_handle = self.runtime_handle.get_play_handle().unwrap().clone();
-        let mut rtsp_command_receiver = self.rtsp_command_tx.subscribe();
-        let rtsp_command_sender = self.rtsp_command_tx.clone();
 
+        let rtp_info = RtpInfoHeader::new(
+            in_sdp_order(&*self.media_sessions.read().await)
+                .into_iter()
+                .map(|value| RtpInfo::new(value.uri.as_str()))
+                .collect(),
+        );
         let frame_distributors: Vec<_> = {
             let sessions = self.media_sessions.read().await;
-            sessions
-                .values()
+            in_sdp_order(&sessions)
+                .into_iter()
+                // the backchannel carries audio from the client
+                .filter(|value| !is_onvif_backchannel(&value.media_sdp))
                 .map(|value| {
                     value.media_frame_sender.as_ref()?;
                     Some((
@@ -1016,72 +2128,64 @@ impl RtspRequestHandler for RtspSession {
         }
         let frame_distributors: Vec<_> =
             frame_distributors.into_iter().map(|v| v.unwrap()).collect();
-        tokio::spawn(async move {
-            defer!(let _ = rtsp_command_sender.send(RtspSessionCommand::Stop););
-            let mut first_frame_sent = false;
-            loop {
-                let mut play_handle = play_handle.write().await;
-                match play_handle.stream_data_consumer.recv().await {
-                    Some(frame) => {
-                        if !first_frame_sent
-                            && !frame.is_sequence_header()
-                            && !frame.is_video_key_frame()
-                        {
-                            continue;
-                        }
-
-                        if !first_frame_sent && frame.is_video_key_frame() {
-                            first_frame_sent = true;
-                        }
-                        for (is_video, distributor) in &frame_distributors {
-                            if (*is_video && frame.is_video() || !*is_video && frame.is_audio())
-                                && let Err(err) = distributor.send(frame.clone()).await
-                            {
-                                tracing::error!("failed to distribute media frame: {}", err);
-                                return;
-                            }
-                        }
-                    }
-                    None => {
-                        tracing::info!("no more media frames, exiting");
-                        return;
-                    }
-                }
-                match rtsp_command_receiver.try_recv() {
-                    Ok(RtspSessionCommand::Stop) => {
-                        tracing::info!("play session received teardown command, exiting");
-                        return;
-                    }
-                    Ok(_) => {}
-                    Err(tokio::sync::broadcast::error::TryRecvError::Closed) => {
-                        tracing::info!("play session command channel closed, exiting");
-                        return;
-                    }
-                    Err(tokio::sync::broadcast::error::TryRecvError::Lagged(skipped)) => {
-                        tracing::warn!(
-                            "play session command channel lagged, skipped {} messages",
-                            skipped
-                        );
-                    }
-                    Err(tokio::sync::broadcast::error::TryRecvError::Empty) => {}
-                }
-            }
+        // set before the stream was known to be live or not, the media sessions pace by it from the first frame
+        let _ = self
+            .rtsp_command_tx
+            .send(RtspSessionCommand::Speed(self.parameters.speed()));
+        self.span().in_scope(|| {
+            spawn_frame_distribution(
+                play_handle,
+                frame_distributors,
+                self.play_paused.clone(),
+                self.rtsp_command_tx.clone(),
+                Some(shaper),
+                Some(self.stream_ended_tx.clone()),
+            )
         });
 
-        Ok(rtsp_server_simple_response(RtspStatus::OK))
+        self.parameters.on_play(Instant::now());
+        self.play_response(&rtp_info, scale)
     }
 
-    async fn handle_pause(&mut self, _request: &RtspRequest) -> RtspServerResult<RtspResponse> {
-        Ok(rtsp_server_simple_response(RtspStatus::OK))
+    async fn handle_pause(&mut self, request: &RtspRequest) -> RtspServerResult<RtspResponse> {
+        if self.state() == RtspSessionState::Init {
+            return self.method_not_valid_in_this_state();
+        }
+        if let Some(res) = self.require_headers(request, &[RtspHeader::Session]) {
+            return Ok(res);
+        }
+        if self.session_id != request.headers().session().map(|session| session.id) {
+            return Ok(rtsp_server_simple_response(RtspStatus::SessionNotFound));
+        }
+        if let Some(response) = self.require_aggregate(request) {
+            return Ok(response);
+        }
+        match self.state() {
+            RtspSessionState::Init | RtspSessionState::Record => {
+                return self.method_not_valid_in_this_state();
+            }
+            RtspSessionState::Play => {
+                self.parameters.on_pause(Instant::now());
+                self.play_paused.store(true, Ordering::Release);
+                tracing::info!("play paused, session_id={:?}", self.session_id);
+            }
+            // pausing a paused session changes nothing
+            RtspSessionState::Ready => {}
+        }
+        Ok(RtspResponse::builder()
+            .ok()
+            .session(&SessionHeader::new(self.session_id.as_ref().unwrap()))
+            .header(RtspHeader::Range, self.npt_range())
+            .build()?)
     }
 
     async fn handle_teardown(&mut self, request: &RtspRequest) -> RtspServerResult<RtspResponse> {
-        let session_id = request.headers().get_unique(RtspHeader::Session);
-        if session_id.is_none() {
+        let session = request.headers().session();
+        if session.is_none() {
             return Ok(rtsp_server_simple_response(RtspStatus::BadRequest));
         }
-        let session_id = session_id.unwrap();
-        if self.session_id.is_none() || self.session_id.as_ref().unwrap().ne(session_id) {
+        let session_id = session.unwrap().id;
+        if self.session_id.is_none() || self.session_id.as_ref().unwrap().ne(&session_id) {
             return Ok(rtsp_server_simple_response(RtspStatus::NotFound));
         }
 
@@ -1089,10 +2193,7 @@ impl RtspRequestHandler for RtspSession {
             tracing::info!("got teardown request, about to close session");
             self.on_session_pre_exit().await;
         }
-        self.media_sessions.write().await.clear();
-        self.session_id = None;
-        self.sdp = None;
-        self.range = None;
+        self.reset_session().await;
 
         Ok(rtsp_server_simple_response(RtspStatus::OK))
     }
@@ -1102,14 +2203,101 @@ impl RtspRequestHandler for RtspSession {
         request: &RtspRequest,
     ) -> RtspServerResult<RtspResponse> {
         tracing::debug!("get prarameter request: {}", request);
-        Ok(rtsp_server_simple_response(RtspStatus::OK))
+        let requested = match self.parse_text_parameters(request)? {
+            Ok(Some(requested)) => requested,
+            // an empty GET_PARAMETER is a keepalive
+            Ok(None) => return Ok(rtsp_server_simple_response(RtspStatus::OK)),
+            Err(response) => return Ok(response),
+        };
+
+        let mut known = TextParameters::new();
+        let mut unknown = TextParameters::new();
+        for name in requested.names() {
+            match name.parse::<RtspParameter>() {
+                Ok(parameter) => known.push(name, Some(self.parameters.get(parameter))),
+                Err(_) => unknown.push(name, None),
+            }
+        }
+        if !unknown.is_empty() {
+            return Self::parameter_not_understood(unknown);
+        }
+
+        Ok(RtspResponse::builder()
+            .ok()
+            .content_type(TEXT_PARAMETERS_CONTENT_TYPE.to_owned())
+            .body(known.to_string())
+            .build()?)
     }
 
     async fn handle_set_parameter(
         &mut self,
-        _request: &RtspRequest,
+        request: &RtspRequest,
     ) -> RtspServerResult<RtspResponse> {
-        Ok(rtsp_server_simple_response(RtspStatus::OK))
+        tracing::debug!("set prarameter request: {}", request);
+        let requested = match self.parse_text_parameters(request)? {
+            Ok(Some(requested)) => requested,
+            Ok(None) => return Ok(rtsp_server_simple_response(RtspStatus::BadRequest)),
+            Err(response) => return Ok(response),
+        };
+
+        let mut unknown = TextParameters::new();
+        let mut speed = None;
+        for (name, value) in requested.entries() {
+            match name.parse::<RtspParameter>() {
+                Ok(RtspParameter::Speed) => {
+                    match value.as_ref().and_then(|v| v.parse::<f64>().ok()) {
+                        Some(value) => speed = Some(value),
+                        None => return Ok(rtsp_server_simple_response(RtspStatus::BadRequest)),
+                    }
+                }
+                Ok(parameter) => {
+                    tracing::warn!("parameter {} is read-only", parameter);
+                    return Ok(rtsp_server_simple_response(RtspStatus::ParameterIsReadOnly));
+                }
+                Err(_) => unknown.push(name, value.clone()),
+            }
+        }
+        if !unknown.is_empty() {
+            return Self::parameter_not_understood(unknown);
+        }
+
+        let mut applied = TextParameters::new();
+        let mut response_builder = RtspResponse::builder().ok();
+        if let Some(speed) = speed {
+            let effective = match self.parameters.set_speed(speed) {
+                Ok(effective) => effective,
+                Err(err) => {
+                    tracing::warn!("set speed failed: {}", err);
+                    return Ok(rtsp_server_simple_response(RtspStatus::BadRequest));
+                }
+            };
+            if effective != speed {
+                tracing::warn!(
+                    "speed {} is clamped to {} for live stream, session id: {:?}",
+                    speed,
+                    effective,
+                    self.session_id
+                );
+                response_builder = response_builder.header(
+                    RtspHeader::Warning,
+                    format!(
+                        "199 {} \"speed is clamped to {:.1} for live stream\"",
+                        SERVER_AGENT, effective
+                    ),
+                );
+            }
+            // none to pace by it before SETUP, the PLAY tells the media sessions set up later
+            let _ = self.rtsp_command_tx.send(RtspSessionCommand::Speed(effective));
+            applied.push(
+                RtspParameter::Speed.name(),
+                Some(self.parameters.get(RtspParameter::Speed)),
+            );
+        }
+
+        Ok(response_builder
+            .content_type(TEXT_PARAMETERS_CONTENT_TYPE.to_owned())
+            .body(applied.to_string())
+            .build()?)
     }
 
     async fn handle_play_notify(
@@ -1140,6 +2328,7 @@ impl RtspRequestHandler for RtspSession {
         if let Some(Ok(sdp)) = body {
             tracing::debug!("received SDP: {:?}", &sdp);
             self.sdp.replace(sdp);
+            self.content_base = Some(request.uri().clone());
         }
 
         Ok(rtsp_server_simple_response(RtspStatus::OK))
@@ -1157,3 +2346,223 @@ impl RtspRequestHandler for RtspSession {
         Ok(response.build()?)
     }
 }
+
+/// the rtp packet size for a Blocksize of a client, RFC 2326 12.7,
+/// no larger than the default and large enough for the headers
+pub(crate) fn effective_blocksize(blocksize: u64) -> usize {
+    let effective = blocksize.clamp(MIN_RTP_PACKET_SIZE as u64, DEFAULT_RTP_PACKET_SIZE as u64);
+    if effective != blocksize {
+        tracing::warn!(
+            "blocksize {} is out of range, {} is used",
+            blocksize,
+            effective
+        );
+    }
+    effective.to_usize().unwrap()
+}
+
+/// the npt start of the Range of a PLAY in seconds, None if the client asked for live,
+/// which is no range, a start of 0 or now, or a range in another format
+pub(crate) fn play_range_start(request: &RtspRequest) -> Result<Option<f64>, RtspMessageError> {
+    let Some(range) = request.headers().get_unique(RtspHeader::Range) else {
+        return Ok(None);
+    };
+    let range: TimeRange = range.trim().parse()?;
+    Ok(match range.start_time {
+        Some(MediaTimeFormat::NPT(start)) => start.seconds().filter(|seconds| *seconds > 0.0),
+        _ => None,
+    })
+}
+
+/// offers a rtx stream for the payload type, RFC 4588 8.6
+/// sends the frames of a subscription to the media sessions playing them, video or audio,
+/// starting from a sequence header or a key frame. stops all the media sessions on exit,
+/// the task runs in the current span. the frames over the bitrate cap of the shaper
+/// are dropped here, before they are packetized.
+/// the final end of stream goes to all the media sessions, which send the rtcp bye and stop,
+/// and the reason to stream_ended
+pub(crate) fn spawn_frame_distribution(
+    play_handle: Arc<RwLock<PlayHandle>>,
+    frame_distributors: Vec<(bool, tokio::sync::mpsc::Sender<MediaFrame>)>,
+    play_paused: Arc<AtomicBool>,
+    rtsp_command_sender: tokio::sync::broadcast::Sender<RtspSessionCommand>,
+    mut shaper: Option<SubscriberShaper>,
+    stream_ended: Option<UnboundedSender<String>>,
+) -> tokio::task::JoinHandle<()> {
+    let mut rtsp_command_receiver = rtsp_command_sender.subscribe();
+    tokio::spawn(
+        async move {
+            // the stop would race the frames still queued for the media sessions at the end of stream
+            let stop_guard = scopeguard::guard(rtsp_command_sender, |sender| {
+                let _ = sender.send(RtspSessionCommand::Stop);
+            });
+            let latency_probe = play_handle.read().await.latency_probe.clone();
+            let mut first_frame_sent = false;
+            loop {
+                let mut play_handle = play_handle.write().await;
+                // the lock is held while waiting for a frame, so the stop command is waited for
+                // as well, the session can not unsubscribe before the lock is released
+                let frame = tokio::select! {
+                    frame = play_handle.stream_data_consumer.recv() => frame,
+                    command = rtsp_command_receiver.recv() => match command {
+                        Ok(RtspSessionCommand::Stop) => {
+                            tracing::info!("play session received teardown command, exiting");
+                            return;
+                        }
+                        Ok(_) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                            tracing::info!("play session command channel closed, exiting");
+                            return;
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                            tracing::warn!(
+                                "play session command channel lagged, skipped {} messages",
+                                skipped
+                            );
+                            continue;
+                        }
+                    },
+                };
+                let ended = match &frame {
+                    Some(MediaFrame::EndOfStream { reason, .. }) if reason.is_final() => {
+                        Some(reason.to_string())
+                    }
+                    Some(MediaFrame::SourceError { code, detail, .. }) => {
+                        Some(format!("{}, {}", code, detail))
+                    }
+                    _ => None,
+                };
+                if let (Some(reason), Some(frame)) = (ended, &frame) {
+                    tracing::info!("stream ended, reason: {}", reason);
+                    for (_, distributor) in &frame_distributors {
+                        if let Err(err) = distributor.send(frame.clone()).await {
+                            tracing::error!("failed to distribute end of stream: {}", err);
+                        }
+                    }
+                    ScopeGuard::into_inner(stop_guard);
+                    if let Some(stream_ended) = stream_ended {
+                        let _ = stream_ended.send(reason);
+                    }
+                    return;
+                }
+                match frame {
+                    Some(frame) => {
+                        // nothing piles up during the pause, the play resumes from the next key frame
+                        if play_paused.load(Ordering::Acquire) {
+                            first_frame_sent = false;
+                            continue;
+                        }
+                        if !first_frame_sent
+                            && !frame.is_sequence_header()
+                            && !frame.is_video_key_frame()
+                        {
+                            continue;
+                        }
+
+                        if let Some(shaper) = shaper.as_mut()
+                            && !shaper.admit(&frame)
+                        {
+                            continue;
+                        }
+                        if !first_frame_sent && frame.is_video_key_frame() {
+                            first_frame_sent = true;
+                        }
+                        for (is_video, distributor) in &frame_distributors {
+                            if (*is_video && frame.is_video() || !*is_video && frame.is_audio())
+                                && let Err(err) = distributor.send(frame.clone()).await
+                            {
+                                tracing::error!("failed to distribute media frame: {}", err);
+                                return;
+                            }
+                        }
+                        // the media sessions packetize and send the frame right away
+                        if let Some(probe) = &latency_probe {
+                            probe.on_frame_sent(frame.get_ingest_time());
+                        }
+                    }
+                    None => {
+                        tracing::info!("no more media frames, exiting");
+                        return;
+                    }
+                }
+            }
+        }
+        .instrument(Span::current()),
+    )
+}
+
+fn with_rtx(media: SdpMediaBuilder, payload_type: u8, clock_rate: u64) -> SdpMediaBuilder {
+    let Some(rtx_payload_type) = get_rtx_payload_type(payload_type) else {
+        return media;
+    };
+    media
+        .media_format(rtx_payload_type.to_string())
+        .rtpmap(RtpMap {
+            payload_type: rtx_payload_type,
+            encoding_name: RTX_ENCODING_NAME.to_string(),
+            clock_rate,
+            encoding_params: None,
+        })
+        .fmtp(FormatParameters {
+            fmt: rtx_payload_type,
+            params: format!("apt={}", payload_type),
+        })
+}
+
+/// the audio is sent as red with the redundancy of its own payload type, RFC 2198 5
+fn with_red(media: SdpMediaBuilder, payload_type: u8, clock_rate: u64) -> SdpMediaBuilder {
+    media
+        .media_format(RED_AUDIO.to_string())
+        .rtpmap(RtpMap {
+            payload_type: RED_AUDIO,
+            encoding_name: RED_ENCODING_NAME.to_string(),
+            clock_rate,
+            encoding_params: None,
+        })
+        .fmtp(FormatParameters {
+            fmt: RED_AUDIO,
+            params: format!("{}/{}", payload_type, payload_type),
+        })
+}
+
+/// parity packets follow the video on a payload type of their own, RFC 5109 14.1
+fn with_ulpfec(media: SdpMediaBuilder) -> SdpMediaBuilder {
+    media.media_format(ULPFEC_VIDEO.to_string()).rtpmap(RtpMap {
+        payload_type: ULPFEC_VIDEO,
+        encoding_name: ULPFEC_ENCODING_NAME.to_string(),
+        clock_rate: 90000,
+        encoding_params: None,
+    })
+}
+
+/// the extmap id the abs-send-time extension is offered with
+const ABS_SEND_TIME_EXTMAP_ID: u8 = 1;
+
+fn with_abs_send_time(media: SdpMediaBuilder) -> SdpMediaBuilder {
+    media.attribute(SDPAttribute::Trivial(SDPTrivialAttribute {
+        name: "extmap".to_owned(),
+        value: Some(format!("{} {}", ABS_SEND_TIME_EXTMAP_ID, ABS_SEND_TIME_URI)),
+    }))
+}
+
+const BACKCHANNEL_CONTROL: &str = "control=backchannel";
+
+/// pcmu audio sent by the client, sendonly from the view of the client
+/// @see: ONVIF Streaming Specification Section 5.3.1
+fn onvif_backchannel_media() -> SdpMediaBuilder {
+    SdpMediaBuilder::new()
+        .media_type(SDPMediaType::Audio)
+        .port(0.into())
+        .protocol(sdp_formats::session::SDPMediaProtocol::RtpAvp)
+        .media_format("0".to_owned())
+        .attribute(SDPAttribute::Trivial(
+            (&RtspSDPControl::Relative(BACKCHANNEL_CONTROL.to_owned())).into(),
+        ))
+        .rtpmap(RtpMap {
+            payload_type: 0,
+            encoding_name: "PCMU".to_owned(),
+            clock_rate: 8000,
+            encoding_params: None,
+        })
+        .attribute(SDPAttribute::MediaDirection(MediaDirection::SendOnly))
+}
@@ -0,0 +1,11 @@
+use std::{net::IpAddr, time::Duration};
+
+#[derive(Debug)]
+pub struct SrtServerConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    /// receiver latency negotiated with the callers
+    pub latency: Duration,
+    /// callers must use the same passphrase if set, the stream is not encrypted otherwise
+    pub passphrase: Option<String>,
+}
@@ -0,0 +1,26 @@
+use codec_aac::errors::AACCodecError;
+use codec_h264::errors::H264CodecError;
+use server_utils::ingest_limit::errors::IngestLimitError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SrtServerError {
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("stream center error: {0}")]
+    StreamCenterError(#[from] stream_center::errors::StreamCenterError),
+    #[error("invalid stream id: {0}")]
+    InvalidStreamId(String),
+    #[error("h264 codec error: {0}")]
+    H264CodecError(#[from] H264CodecError),
+    #[error("aac codec error: {0}")]
+    AACCodecError(#[from] AACCodecError),
+    #[error("codec parameters error: {0}")]
+    CodecParametersError(String),
+    #[error("codec error: {0}")]
+    CodecCommonError(#[from] codec_common::errors::CodecCommonError),
+    #[error("ingest limit exceeded: {0}")]
+    IngestLimitExceeded(#[from] IngestLimitError),
+}
+
+pub type SrtServerResult<T> = Result<T, SrtServerError>;
@@ -0,0 +1,240 @@
+use codec_aac::adts::split_adts_frames;
+use codec_common::{
+    FrameType, MediaFrameTimestamp,
+    audio::{AudioCodecCommon, AudioFrameInfo, SoundInfoCommon},
+    video::{VideoCodecCommon, VideoFrameInfo, VideoFrameUnit},
+};
+use codec_h264::{
+    annexb::split_annexb,
+    avc_decoder_configuration_record::AvcDecoderConfigurationRecord,
+    nalu::NalUnit,
+    nalu_type::NALUType,
+    pps::Pps,
+    sps::{Sps, chroma_format_idc::ChromaFormatIdc},
+};
+use mpegts_formats::demuxer::{PesPacket, stream_type};
+use stream_center::gop::MediaFrame;
+use tokio_util::bytes::Bytes;
+use utils::traits::reader::ReadFrom;
+
+use crate::errors::{SrtServerError, SrtServerResult};
+
+/// pts, dts and pcr base are 33 bits counters in 90kHz
+const TIMESTAMP_WRAP: u64 = 1 << 33;
+const TIMESTAMP_CLOCK_RATE: u128 = 90_000;
+const AAC_SAMPLES_PER_FRAME: u64 = 1024;
+
+#[derive(Debug)]
+struct AudioState {
+    audio_specific_config: [u8; 2],
+    sound_info: SoundInfoCommon,
+    sample_rate: u32,
+}
+
+/// converts demuxed PES packets into stream center media frames.
+/// sequence headers are generated from the in-band parameters
+/// whenever they show up for the first time or change
+#[derive(Debug, Default)]
+pub struct TsFrameConverter {
+    timestamp_base: Option<u64>,
+    last_timestamp: Option<u64>,
+    sps: Option<NalUnit>,
+    pps: Option<NalUnit>,
+    video_config_sent: bool,
+    audio: Option<AudioState>,
+}
+
+impl TsFrameConverter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn convert(&mut self, pes: PesPacket) -> SrtServerResult<Vec<MediaFrame>> {
+        match pes.stream_type {
+            stream_type::H264 => self.convert_h264(pes),
+            stream_type::AAC_ADTS => self.convert_aac(pes),
+            stream_type => {
+                tracing::trace!(
+                    "unsupported stream type {:#04x} on pid {}, ignored",
+                    stream_type,
+                    pes.pid
+                );
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// extends the 33 bits timestamp across wrap arounds, picking the candidate closest to the last one
+    fn unwrap_timestamp(&mut self, timestamp: u64) -> u64 {
+        let unwrapped = match self.last_timestamp {
+            None => timestamp,
+            Some(last) => {
+                let candidate = last - last % TIMESTAMP_WRAP + timestamp;
+                [
+                    candidate.checked_sub(TIMESTAMP_WRAP),
+                    Some(candidate),
+                    Some(candidate + TIMESTAMP_WRAP),
+                ]
+                .into_iter()
+                .flatten()
+                .min_by_key(|value| value.abs_diff(last))
+                .unwrap()
+            }
+        };
+        self.last_timestamp = Some(unwrapped);
+        unwrapped
+    }
+
+    /// maps a 90kHz timestamp to nanoseconds since the first pcr of the stream,
+    /// or the first timestamp seen if the stream carries no pcr
+    fn timestamp_nano(&mut self, timestamp: u64, pcr: Option<u64>) -> u64 {
+        if self.timestamp_base.is_none() {
+            let base = self.unwrap_timestamp(pcr.unwrap_or(timestamp));
+            self.timestamp_base = Some(base);
+        }
+        let timestamp = self.unwrap_timestamp(timestamp);
+        let ticks = timestamp.saturating_sub(self.timestamp_base.unwrap());
+        (ticks as u128 * 1_000_000_000 / TIMESTAMP_CLOCK_RATE) as u64
+    }
+
+    fn make_video_config(&self, timestamp_nano: u64) -> SrtServerResult<Option<MediaFrame>> {
+        let (Some(sps), Some(pps)) = (&self.sps, &self.pps) else {
+            return Ok(None);
+        };
+        let sps = Sps::try_from(sps)?;
+        let pps = Pps::try_from((
+            sps.get_chroma_format_idc()
+                .unwrap_or(ChromaFormatIdc::Chroma420),
+            pps,
+        ))?;
+        let record = AvcDecoderConfigurationRecord::from((&sps, &pps));
+        Ok(Some(MediaFrame::VideoConfig {
+            timestamp_nano,
+            config: Box::new(record.into()),
+        }))
+    }
+
+    fn convert_h264(&mut self, pes: PesPacket) -> SrtServerResult<Vec<MediaFrame>> {
+        let Some(pts) = pes.pts else {
+            tracing::warn!("h264 pes without pts on pid {}, dropped", pes.pid);
+            return Ok(vec![]);
+        };
+        let dts_nano = self.timestamp_nano(pes.dts.unwrap_or(pts), pes.pcr);
+        let pts_nano = self.timestamp_nano(pts, pes.pcr).max(dts_nano);
+
+        let mut frames = vec![];
+        let mut nal_units = vec![];
+        let mut key_frame = false;
+        for mut nalu_bytes in split_annexb(&pes.payload) {
+            let nalu = NalUnit::read_from(&mut nalu_bytes)?;
+            match nalu.header.nal_unit_type {
+                NALUType::AccessUnitDelimiter => continue,
+                NALUType::SPS => {
+                    if self.sps.as_ref().is_none_or(|sps| sps.body != nalu.body) {
+                        self.sps = Some(nalu.clone());
+                        self.video_config_sent = false;
+                    }
+                }
+                NALUType::PPS => {
+                    if self.pps.as_ref().is_none_or(|pps| pps.body != nalu.body) {
+                        self.pps = Some(nalu.clone());
+                        self.video_config_sent = false;
+                    }
+                }
+                NALUType::IDRSlice => key_frame = true,
+                _ => {}
+            }
+            nal_units.push(nalu);
+        }
+
+        if !self.video_config_sent
+            && let Some(config) = self.make_video_config(dts_nano)?
+        {
+            frames.push(config);
+            self.video_config_sent = true;
+        }
+        if !self.video_config_sent {
+            tracing::debug!("no sps/pps received yet, h264 frame dropped");
+            return Ok(frames);
+        }
+        if nal_units.is_empty() {
+            return Ok(frames);
+        }
+
+        frames.push(MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                if key_frame || pes.random_access {
+                    FrameType::KeyFrame
+                } else {
+                    FrameType::CodedFrames
+                },
+                MediaFrameTimestamp::new(pts_nano, dts_nano),
+            ),
+            payload: VideoFrameUnit::H264 { nal_units },
+        });
+        Ok(frames)
+    }
+
+    fn convert_aac(&mut self, pes: PesPacket) -> SrtServerResult<Vec<MediaFrame>> {
+        let Some(pts) = pes.pts else {
+            tracing::warn!("aac pes without pts on pid {}, dropped", pes.pid);
+            return Ok(vec![]);
+        };
+        let pts_nano = self.timestamp_nano(pts, pes.pcr);
+
+        let mut frames = vec![];
+        let mut samples = 0;
+        for (header, raw) in split_adts_frames(&pes.payload)? {
+            let audio_specific_config = header.audio_specific_config_bytes();
+            let config_changed = self
+                .audio
+                .as_ref()
+                .is_none_or(|audio| audio.audio_specific_config != audio_specific_config);
+            let sample_rate = match &self.audio {
+                Some(audio) if !config_changed => audio.sample_rate,
+                _ => {
+                    let config = header.audio_specific_config()?;
+                    let sample_rate = config
+                        .sampling_frequency_index
+                        .get_sampling_frequency()
+                        .ok_or_else(|| {
+                            SrtServerError::CodecParametersError(format!(
+                                "invalid sampling frequency index: {}",
+                                header.sampling_frequency_index
+                            ))
+                        })?;
+                    let sound_info: SoundInfoCommon = (&config).try_into()?;
+                    frames.push(MediaFrame::AudioConfig {
+                        timestamp_nano: pts_nano,
+                        sound_info,
+                        config: Box::new(config.into()),
+                        track_id: 0,
+                    });
+                    self.audio = Some(AudioState {
+                        audio_specific_config,
+                        sound_info,
+                        sample_rate,
+                    });
+                    sample_rate
+                }
+            };
+
+            let timestamp_nano = pts_nano + samples * 1_000_000_000 / sample_rate as u64;
+            samples += AAC_SAMPLES_PER_FRAME;
+            let sound_info = self.audio.as_ref().unwrap().sound_info;
+            frames.push(MediaFrame::Audio {
+                frame_info: AudioFrameInfo::new(
+                    AudioCodecCommon::AAC,
+                    FrameType::CodedFrames,
+                    sound_info.sound_rate,
+                    sound_info.sound_size,
+                    sound_info.sound_type,
+                    timestamp_nano,
+                ),
+                payload: Bytes::copy_from_slice(raw),
+            });
+        }
+        Ok(frames)
+    }
+}
@@ -0,0 +1,8 @@
+pub mod config;
+pub mod errors;
+pub mod frame_converter;
+pub mod server;
+pub mod session;
+pub mod stream_id;
+#[cfg(test)]
+mod test;
@@ -0,0 +1,145 @@
+use std::net::SocketAddr;
+
+use futures::StreamExt;
+use server_utils::{
+    ingest_limit::IngestRateLimiter,
+    log_context::{StreamRole, session_span, stream_span},
+    metrics::ConnectionMetricsGuard,
+};
+use srt_tokio::{
+    SrtListener,
+    access::{RejectReason, ServerRejectReason},
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::Instrument;
+use utils::error_chain::ErrorChainExt;
+
+use crate::{
+    config::SrtServerConfig,
+    errors::SrtServerResult,
+    session::SrtSession,
+    stream_id::{SrtStreamId, SrtStreamMode},
+};
+
+#[derive(Debug)]
+pub struct SrtServer {
+    stream_center_event_sender: UnboundedSender<stream_center::events::StreamCenterEvent>,
+    config: SrtServerConfig,
+    ingest_limiter: IngestRateLimiter,
+}
+
+impl SrtServer {
+    pub fn new(
+        stream_center_event_sender: UnboundedSender<stream_center::events::StreamCenterEvent>,
+        config: SrtServerConfig,
+        ingest_limiter: IngestRateLimiter,
+    ) -> Self {
+        Self {
+            stream_center_event_sender,
+            config,
+            ingest_limiter,
+        }
+    }
+
+    pub async fn run(&self) -> SrtServerResult<()> {
+        tracing::info!(
+            "srt server is starting with config: address: {}, port: {}, latency: {:?}, encryption: {}",
+            self.config.address,
+            self.config.port,
+            self.config.latency,
+            self.config.passphrase.is_some()
+        );
+        let mut builder = SrtListener::builder().latency(self.config.latency);
+        if let Some(passphrase) = &self.config.passphrase {
+            builder = builder.encryption(0, passphrase.as_str());
+        }
+        let (_listener, mut incoming) = builder
+            .bind(SocketAddr::new(self.config.address, self.config.port))
+            .await?;
+
+        while let Some(request) = incoming.incoming().next().await {
+            let peer_addr = request.remote();
+            let stream_id = request
+                .stream_id()
+                .map(|stream_id| stream_id.to_string())
+                .unwrap_or_default();
+            tracing::info!(
+                "got new srt connection request, peer addr: {}, stream id: {}",
+                peer_addr,
+                stream_id
+            );
+
+            let stream_id = match stream_id.parse::<SrtStreamId>() {
+                Ok(stream_id) if stream_id.mode == SrtStreamMode::Publish => stream_id,
+                Ok(stream_id) => {
+                    tracing::warn!(
+                        "srt request mode is not supported yet, peer addr: {}, stream: {}",
+                        peer_addr,
+                        stream_id.stream_key()
+                    );
+                    let _ = request
+                        .reject(RejectReason::Server(ServerRejectReason::BadMode))
+                        .await;
+                    continue;
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "reject srt connection with invalid stream id, peer addr: {}, err: {}",
+                        peer_addr,
+                        err
+                    );
+                    let _ = request
+                        .reject(RejectReason::Server(ServerRejectReason::BadRequest))
+                        .await;
+                    continue;
+                }
+            };
+
+            let socket = match request.accept(None).await {
+                Ok(socket) => socket,
+                Err(err) => {
+                    tracing::warn!(
+                        "accept srt connection failed, peer addr: {}, err: {}",
+                        peer_addr,
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            let stream_center_event_sender = self.stream_center_event_sender.clone();
+            let ingest_limiter = self.ingest_limiter.clone();
+            // only publishing is supported, the stream is known from the start
+            let span = stream_span(
+                &session_span("srt", peer_addr),
+                &stream_id.stream_key(),
+                StreamRole::Publish,
+            );
+            tokio::task::spawn(
+                async move {
+                    let _connection = ConnectionMetricsGuard::accepted("srt");
+                    let mut session = SrtSession::new(
+                        stream_center_event_sender,
+                        socket,
+                        peer_addr,
+                        stream_id,
+                        ingest_limiter,
+                    );
+                    match session.run().await {
+                        Ok(()) => {
+                            tracing::info!(
+                                "srt session gracefully closed, peer addr: {}",
+                                peer_addr
+                            );
+                        }
+                        Err(err) => {
+                            tracing::error!("srt session exit with error: {}", err.chain());
+                        }
+                    }
+                }
+                .instrument(span),
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use std::{io, net::SocketAddr, time::Instant};
+
+use futures::{Stream, StreamExt};
+use mpegts_formats::demuxer::{PesPacket, TsDemuxer};
+use server_utils::ingest_limit::IngestRateLimiter;
+use stream_center::{
+    events::StreamCenterEvent, gop::MediaFrame, stream_center::StreamCenter,
+    stream_source::PublishProtocol,
+};
+use tokio::sync::mpsc::{Sender, UnboundedSender};
+use tokio_util::bytes::Bytes;
+
+use crate::{
+    errors::{SrtServerError, SrtServerResult},
+    frame_converter::TsFrameConverter,
+    stream_id::SrtStreamId,
+};
+
+/// a publishing srt connection, carrying a single program transport stream
+pub struct SrtSession<S> {
+    stream_center_event_sender: UnboundedSender<StreamCenterEvent>,
+    socket: S,
+    peer_addr: SocketAddr,
+    stream_id: SrtStreamId,
+    ingest_limiter: IngestRateLimiter,
+    demuxer: TsDemuxer,
+    converter: TsFrameConverter,
+}
+
+impl<S> SrtSession<S>
+where
+    S: Stream<Item = io::Result<(Instant, Bytes)>> + Unpin,
+{
+    pub fn new(
+        stream_center_event_sender: UnboundedSender<StreamCenterEvent>,
+        socket: S,
+        peer_addr: SocketAddr,
+        stream_id: SrtStreamId,
+        ingest_limiter: IngestRateLimiter,
+    ) -> Self {
+        Self {
+            stream_center_event_sender,
+            socket,
+            peer_addr,
+            stream_id,
+            ingest_limiter,
+            demuxer: TsDemuxer::new(),
+            converter: TsFrameConverter::new(),
+        }
+    }
+
+    pub async fn run(&mut self) -> SrtServerResult<()> {
+        let media_sender = StreamCenter::publish(
+            &self.stream_center_event_sender,
+            PublishProtocol::SRT,
+            &self.stream_id.properties.stream_id,
+            &self.stream_id.properties.stream_context,
+        )
+        .await
+        .inspect_err(|err| {
+            tracing::error!("srt stream publish to stream center failed: {}", err);
+        })?;
+        tracing::info!(
+            "srt stream publish to stream center succeed, peer addr: {}, stream: {}",
+            self.peer_addr,
+            self.stream_id.stream_key()
+        );
+
+        let result = self.receive(&media_sender).await;
+
+        self.ingest_limiter.release(&self.stream_id.stream_key());
+        if let Err(err) = StreamCenter::unpublish(
+            &self.stream_center_event_sender,
+            &self.stream_id.properties.stream_id,
+        )
+        .await
+        {
+            tracing::error!("srt stream unpublish from stream center failed: {}", err);
+        }
+        result
+    }
+
+    async fn receive(&mut self, media_sender: &Sender<MediaFrame>) -> SrtServerResult<()> {
+        let stream_key = self.stream_id.stream_key();
+        while let Some(data) = self.socket.next().await {
+            let (_, data) = data?;
+            self.ingest_limiter
+                .check(&stream_key, data.len())
+                .inspect_err(|err| {
+                    tracing::error!("ingest limit exceeded, disconnecting publisher: {}", err);
+                })?;
+            for pes in self.demuxer.push(&data) {
+                self.forward(pes, media_sender).await?;
+            }
+        }
+        tracing::info!("srt connection closed by peer: {}", self.peer_addr);
+        // unbounded video pes packets are only complete once the next one starts
+        for pes in self.demuxer.flush() {
+            self.forward(pes, media_sender).await?;
+        }
+        Ok(())
+    }
+
+    async fn forward(
+        &mut self,
+        pes: PesPacket,
+        media_sender: &Sender<MediaFrame>,
+    ) -> SrtServerResult<()> {
+        let frames = match self.converter.convert(pes) {
+            Ok(frames) => frames,
+            Err(err) => {
+                tracing::warn!("convert pes packet to media frames failed: {}", err);
+                return Ok(());
+            }
+        };
+        for frame in frames {
+            media_sender.send(frame).await.map_err(|err| {
+                tracing::error!("send media frame to stream center failed: {}", err);
+                SrtServerError::IoError(io::Error::other(format!(
+                    "send media frame to stream center failed: {}",
+                    err
+                )))
+            })?;
+        }
+        Ok(())
+    }
+}
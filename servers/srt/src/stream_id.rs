@@ -0,0 +1,86 @@
+use std::str::FromStr;
+
+use server_utils::stream_properities::StreamProperties;
+use url::Url;
+
+use crate::errors::{SrtServerError, SrtServerResult};
+
+/// prefix of the structured stream id, see the SRT Access Control Guidelines
+const STRUCTURED_PREFIX: &str = "#!::";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtStreamMode {
+    Publish,
+    Request,
+}
+
+/// routing info carried by the srt streamid, in one of the forms:
+/// - `app/stream?key=value`
+/// - `#!::r=app/stream,m=publish,key=value`
+///
+/// `m` defaults to publish since the server only ingests for now
+#[derive(Debug)]
+pub struct SrtStreamId {
+    pub mode: SrtStreamMode,
+    pub properties: StreamProperties,
+}
+
+impl SrtStreamId {
+    pub fn stream_key(&self) -> String {
+        self.properties.stream_id.to_string()
+    }
+}
+
+fn parse_resource(resource: &str) -> SrtServerResult<StreamProperties> {
+    let url = Url::parse(&format!(
+        "srt://localhost/{}",
+        resource.trim_start_matches('/')
+    ))
+    .map_err(|err| SrtServerError::InvalidStreamId(format!("{}: {}", resource, err)))?;
+    (&url)
+        .try_into()
+        .map_err(|err| SrtServerError::InvalidStreamId(format!("{}: {}", resource, err)))
+}
+
+impl FromStr for SrtStreamId {
+    type Err = SrtServerError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(structured) = s.strip_prefix(STRUCTURED_PREFIX) else {
+            return Ok(Self {
+                mode: SrtStreamMode::Publish,
+                properties: parse_resource(s)?,
+            });
+        };
+
+        let mut resource = None;
+        let mut mode = SrtStreamMode::Publish;
+        let mut context = vec![];
+        for pair in structured.split(',').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| {
+                SrtServerError::InvalidStreamId(format!("invalid key value pair: {}", pair))
+            })?;
+            match key {
+                "r" => resource = Some(value),
+                "m" => {
+                    mode = match value {
+                        "publish" => SrtStreamMode::Publish,
+                        "request" => SrtStreamMode::Request,
+                        _ => {
+                            return Err(SrtServerError::InvalidStreamId(format!(
+                                "unsupported mode: {}",
+                                value
+                            )));
+                        }
+                    }
+                }
+                _ => context.push((key.to_owned(), value.to_owned())),
+            }
+        }
+        let resource = resource.ok_or_else(|| {
+            SrtServerError::InvalidStreamId(format!("no resource name in: {}", s))
+        })?;
+        let mut properties = parse_resource(resource)?;
+        properties.stream_context.extend(context);
+        Ok(Self { mode, properties })
+    }
+}
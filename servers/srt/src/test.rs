@@ -0,0 +1,230 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        net::Ipv4Addr,
+        time::{Duration, Instant},
+    };
+
+    use codec_h264::annexb::split_annexb;
+    use futures::SinkExt;
+    use mpegts_formats::demuxer::TsDemuxer;
+    use server_utils::ingest_limit::IngestRateLimiter;
+    use srt_tokio::SrtSocket;
+    use stream_center::{
+        gop::MediaFrame,
+        identity::StreamIdentity,
+        stream_center::StreamCenter,
+        stream_source::{MediaSelection, PlayProtocol},
+    };
+    use tokio_util::bytes::Bytes;
+
+    use crate::{
+        config::SrtServerConfig,
+        frame_converter::TsFrameConverter,
+        server::SrtServer,
+        stream_id::{SrtStreamId, SrtStreamMode},
+    };
+
+    /// 11 h264 frames at 25fps with pcr 100ms ahead of the first dts,
+    /// each followed by an audio pes of 2 adts frames 10ms later
+    const H264_AAC_TS: &[u8] = include_bytes!("../../../formats/mpegts/test_data/h264_aac.ts");
+    /// 7 ts packets, the usual srt live mode payload
+    const SRT_PAYLOAD_SIZE: usize = 1316;
+
+    fn convert_all() -> Vec<MediaFrame> {
+        let mut demuxer = TsDemuxer::new();
+        let mut converter = TsFrameConverter::new();
+        let mut packets = demuxer.push(H264_AAC_TS);
+        packets.extend(demuxer.flush());
+        packets
+            .into_iter()
+            .flat_map(|pes| converter.convert(pes).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_annexb() {
+        let bytes = [
+            0, 0, 0, 1, 0x09, 0xF0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xCE,
+        ];
+        let nalus = split_annexb(&bytes);
+        assert_eq!(nalus, vec![&[0x09, 0xF0][..], &[0x67, 0x42], &[0x68, 0xCE]]);
+    }
+
+    #[test]
+    fn test_parse_stream_id() {
+        let stream_id: SrtStreamId = "live/test?token=abc".parse().unwrap();
+        assert_eq!(stream_id.mode, SrtStreamMode::Publish);
+        assert_eq!(stream_id.stream_key(), "live/test");
+        assert_eq!(
+            stream_id.properties.stream_context.get("token"),
+            Some(&"abc".to_owned())
+        );
+
+        let stream_id: SrtStreamId = "#!::r=live/test,m=publish,u=alice".parse().unwrap();
+        assert_eq!(stream_id.mode, SrtStreamMode::Publish);
+        assert_eq!(stream_id.stream_key(), "live/test");
+        assert_eq!(
+            stream_id.properties.stream_context.get("u"),
+            Some(&"alice".to_owned())
+        );
+
+        let stream_id: SrtStreamId = "#!::m=request,r=live/test".parse().unwrap();
+        assert_eq!(stream_id.mode, SrtStreamMode::Request);
+
+        assert!("live".parse::<SrtStreamId>().is_err());
+        assert!("#!::m=publish".parse::<SrtStreamId>().is_err());
+        assert!(
+            "#!::r=live/test,m=bidirectional"
+                .parse::<SrtStreamId>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_convert_ts_to_media_frames() {
+        let frames = convert_all();
+        // the bounded audio pes completes first, the video one waits for the next payload unit start
+        assert!(matches!(frames[0], MediaFrame::AudioConfig { .. }));
+        assert!(matches!(
+            frames.iter().find(|frame| frame.is_video()),
+            Some(MediaFrame::VideoConfig { .. })
+        ));
+        assert_eq!(
+            frames
+                .iter()
+                .filter(|frame| matches!(frame, MediaFrame::VideoConfig { .. }))
+                .count(),
+            1
+        );
+        assert_eq!(
+            frames
+                .iter()
+                .filter(|frame| matches!(frame, MediaFrame::AudioConfig { .. }))
+                .count(),
+            1
+        );
+
+        let video: Vec<_> = frames
+            .iter()
+            .filter(|frame| frame.is_video() && !frame.is_sequence_header())
+            .collect();
+        assert_eq!(video.len(), 11);
+        assert!(video[0].is_video_key_frame());
+        assert!(video[1..].iter().all(|frame| !frame.is_video_key_frame()));
+        // timestamps are relative to the first pcr, which is 9000 ticks ahead of the first dts
+        for (i, frame) in video.iter().enumerate() {
+            assert_eq!(
+                frame.get_decode_timestamp_ns(),
+                100_000_000 + i as u64 * 40_000_000
+            );
+        }
+        match video[0] {
+            MediaFrame::Video {
+                payload: codec_common::video::VideoFrameUnit::H264 { nal_units },
+                ..
+            } => {
+                // aud is dropped, sps and pps are kept in band
+                assert_eq!(nal_units.len(), 3);
+            }
+            _ => unreachable!(),
+        }
+
+        let audio: Vec<_> = frames
+            .iter()
+            .filter(|frame| frame.is_audio() && !frame.is_sequence_header())
+            .collect();
+        assert_eq!(audio.len(), 22);
+        assert_eq!(audio[0].get_decode_timestamp_ns(), 110_000_000);
+        match audio[0] {
+            MediaFrame::Audio { payload, .. } => assert_eq!(payload.len(), 50),
+            _ => unreachable!(),
+        }
+        // the second adts frame of a pes is 1024 samples later
+        assert_eq!(
+            audio[1].get_decode_timestamp_ns() - audio[0].get_decode_timestamp_ns(),
+            1024 * 1_000_000_000 / 44100
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn srt_loopback_publishes_video_frames() {
+        let port = 19350;
+        let mut stream_center = StreamCenter::new();
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let server = SrtServer::new(
+            event_sender.clone(),
+            SrtServerConfig {
+                address: Ipv4Addr::LOCALHOST.into(),
+                port,
+                latency: Duration::from_millis(20),
+                passphrase: None,
+            },
+            IngestRateLimiter::default(),
+        );
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut socket = SrtSocket::builder()
+            .latency(Duration::from_millis(20))
+            .call(
+                format!("127.0.0.1:{}", port).as_str(),
+                Some("live/loopback"),
+            )
+            .await
+            .unwrap();
+
+        let stream_id = StreamIdentity::new("live", "loopback").unwrap();
+        let mut response = None;
+        for _ in 0..50 {
+            if let Ok(subscribed) = StreamCenter::subscribe(
+                &event_sender,
+                PlayProtocol::RTMP,
+                &stream_id,
+                &HashMap::new(),
+                MediaSelection::default(),
+            )
+            .await
+            {
+                response = Some(subscribed);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let mut response = response.expect("srt stream is not published");
+
+        for chunk in H264_AAC_TS.chunks(SRT_PAYLOAD_SIZE) {
+            socket
+                .send((Instant::now(), Bytes::copy_from_slice(chunk)))
+                .await
+                .unwrap();
+        }
+
+        let mut frames = vec![];
+        while let Ok(Some(frame)) =
+            tokio::time::timeout(Duration::from_secs(1), response.media_receiver.recv()).await
+        {
+            frames.push(frame);
+        }
+        let video: Vec<_> = frames
+            .iter()
+            .filter(|frame| frame.is_video() && !frame.is_sequence_header())
+            .collect();
+        // the last access unit is only complete once the next one starts
+        assert_eq!(video.len(), 10);
+        assert!(video[0].is_video_key_frame());
+        assert!(
+            frames
+                .iter()
+                .any(|frame| frame.is_video() && frame.is_sequence_header())
+        );
+
+        let _ = socket.close().await;
+    }
+}
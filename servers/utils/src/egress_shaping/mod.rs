@@ -0,0 +1,354 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex, MutexGuard, Weak},
+    time::Instant,
+};
+
+use stream_center::gop::MediaFrame;
+use utils::metrics::{self, Counter, Family};
+
+use crate::ingest_limit::TokenBucket;
+
+pub const DEFAULT_EGRESS_BURST_MS: u64 = 1000;
+/// the play param a subscriber lowers its own cap with, in kbps
+pub const MAX_BITRATE_KEY: &str = "max_bitrate_kbps";
+/// video frames leave this much of each bucket to the audio
+const AUDIO_HEADROOM_MS: u64 = 200;
+
+static SHAPED_BYTES: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_egress_shaped_bytes_total",
+        "payload bytes not delivered to the subscribers of a stream as a bitrate cap is hit",
+        &["stream", "media"],
+    )
+});
+
+#[derive(Debug, Clone)]
+pub struct EgressShapingConfig {
+    /// None means unlimited, a subscriber may ask for less with the max_bitrate_kbps param
+    pub per_subscriber_kbps: Option<u64>,
+    /// all the subscribers of a stream together, None means unlimited
+    pub per_stream_kbps: Option<u64>,
+    /// all the subscribers of the server together, None means unlimited
+    pub global_kbps: Option<u64>,
+    /// how long a subscriber is allowed to burst above the bitrate, a key frame must fit in it
+    pub burst_ms: u64,
+}
+
+impl Default for EgressShapingConfig {
+    fn default() -> Self {
+        Self {
+            per_subscriber_kbps: None,
+            per_stream_kbps: None,
+            global_kbps: None,
+            burst_ms: DEFAULT_EGRESS_BURST_MS,
+        }
+    }
+}
+
+/// Shared by the sessions of all protocols,
+/// hands out the shapers of the subscribers, which share the buckets of their stream and of the server
+#[derive(Debug, Clone, Default)]
+pub struct EgressShaper {
+    config: EgressShapingConfig,
+    global_bucket: Option<Arc<Mutex<TokenBucket>>>,
+    /// a bucket is gone with the last subscriber of its stream
+    stream_buckets: Arc<Mutex<HashMap<String, Weak<Mutex<TokenBucket>>>>>,
+}
+
+impl EgressShaper {
+    pub fn new(config: EgressShapingConfig) -> Self {
+        Self {
+            global_bucket: config
+                .global_kbps
+                .map(|kbps| Arc::new(Mutex::new(TokenBucket::from_kbps(kbps, config.burst_ms)))),
+            stream_buckets: Default::default(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &EgressShapingConfig {
+        &self.config
+    }
+
+    /// the shaper of a new subscriber of the stream, the max_bitrate_kbps param in the stream context
+    /// may only lower the configured cap
+    pub fn subscriber(
+        &self,
+        stream_key: &str,
+        stream_context: &HashMap<String, String>,
+    ) -> SubscriberShaper {
+        let requested_kbps = stream_context
+            .get(MAX_BITRATE_KEY)
+            .and_then(|kbps| kbps.trim().parse::<u64>().ok())
+            .filter(|kbps| *kbps > 0);
+        let subscriber_kbps = match (self.config.per_subscriber_kbps, requested_kbps) {
+            (Some(configured), Some(requested)) => Some(configured.min(requested)),
+            (configured, requested) => configured.or(requested),
+        };
+        let stream_bucket = self.config.per_stream_kbps.map(|kbps| {
+            let mut buckets = self.stream_buckets.lock().unwrap();
+            buckets.retain(|_, bucket| bucket.strong_count() > 0);
+            if let Some(bucket) = buckets.get(stream_key).and_then(Weak::upgrade) {
+                return bucket;
+            }
+            let bucket = Arc::new(Mutex::new(TokenBucket::from_kbps(
+                kbps,
+                self.config.burst_ms,
+            )));
+            buckets.insert(stream_key.to_owned(), Arc::downgrade(&bucket));
+            bucket
+        });
+        SubscriberShaper {
+            subscriber_bucket: subscriber_kbps
+                .map(|kbps| TokenBucket::from_kbps(kbps, self.config.burst_ms)),
+            stream_bucket,
+            global_bucket: self.global_bucket.clone(),
+            dropping_video: false,
+            shaped_bytes: 0,
+            shaped_video: SHAPED_BYTES.with_labels(&[stream_key, "video"]),
+            shaped_audio: SHAPED_BYTES.with_labels(&[stream_key, "audio"]),
+        }
+    }
+}
+
+/// decides frame by frame what a subscriber gets, the buckets are taken once per frame.
+/// once a video frame is dropped the video is dropped up to the next key frame,
+/// so the player never gets a frame referencing one it did not get.
+/// the audio goes on as long as the buckets have room for it
+#[derive(Debug)]
+pub struct SubscriberShaper {
+    subscriber_bucket: Option<TokenBucket>,
+    stream_bucket: Option<Arc<Mutex<TokenBucket>>>,
+    global_bucket: Option<Arc<Mutex<TokenBucket>>>,
+    dropping_video: bool,
+    shaped_bytes: u64,
+    shaped_video: Counter,
+    shaped_audio: Counter,
+}
+
+impl SubscriberShaper {
+    pub fn is_unlimited(&self) -> bool {
+        self.subscriber_bucket.is_none()
+            && self.stream_bucket.is_none()
+            && self.global_bucket.is_none()
+    }
+
+    /// payload bytes dropped so far
+    pub fn shaped_bytes(&self) -> u64 {
+        self.shaped_bytes
+    }
+
+    /// whether the frame is to be sent, the ones sent are accounted
+    pub fn admit(&mut self, frame: &MediaFrame) -> bool {
+        self.admit_at(frame, Instant::now())
+    }
+
+    pub fn admit_at(&mut self, frame: &MediaFrame, now: Instant) -> bool {
+        if self.is_unlimited() {
+            return true;
+        }
+        let bytes = frame.payload_bytes() as u64;
+        match frame {
+            MediaFrame::Video { .. } => {
+                if !self.dropping_video || frame.is_video_key_frame() {
+                    self.dropping_video = !self.try_consume(bytes, AUDIO_HEADROOM_MS, now);
+                }
+                if self.dropping_video {
+                    self.shaped_bytes += bytes;
+                    self.shaped_video.inc_by(bytes);
+                }
+                !self.dropping_video
+            }
+            MediaFrame::Audio { .. } => {
+                let admitted = self.try_consume(bytes, 0, now);
+                if !admitted {
+                    self.shaped_bytes += bytes;
+                    self.shaped_audio.inc_by(bytes);
+                }
+                admitted
+            }
+            // sequence headers and scripts are small and needed to play anything, they always go
+            _ => {
+                self.force_consume(bytes, now);
+                true
+            }
+        }
+    }
+
+    /// the bytes are taken from all the buckets if each of them keeps the headroom after it
+    fn try_consume(&mut self, bytes: u64, headroom_ms: u64, now: Instant) -> bool {
+        // always stream before global, so the subscribers never wait for each other in a circle
+        let mut stream_bucket = self.stream_bucket.as_ref().map(lock);
+        let mut global_bucket = self.global_bucket.as_ref().map(lock);
+        let fits = |bucket: &mut TokenBucket| {
+            let headroom = bucket.bytes_per_second() * headroom_ms / 1000;
+            bucket.tokens_at(now) >= bytes + headroom
+        };
+        let all_fit = self.subscriber_bucket.as_mut().is_none_or(fits)
+            && stream_bucket.as_deref_mut().is_none_or(fits)
+            && global_bucket.as_deref_mut().is_none_or(fits);
+        if !all_fit {
+            return false;
+        }
+        for bucket in [
+            self.subscriber_bucket.as_mut(),
+            stream_bucket.as_deref_mut(),
+            global_bucket.as_deref_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            bucket.consume(bytes);
+        }
+        true
+    }
+
+    fn force_consume(&mut self, bytes: u64, now: Instant) {
+        let mut stream_bucket = self.stream_bucket.as_ref().map(lock);
+        let mut global_bucket = self.global_bucket.as_ref().map(lock);
+        for bucket in [
+            self.subscriber_bucket.as_mut(),
+            stream_bucket.as_deref_mut(),
+            global_bucket.as_deref_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            bucket.tokens_at(now);
+            bucket.consume(bytes);
+        }
+    }
+}
+
+fn lock(bucket: &Arc<Mutex<TokenBucket>>) -> MutexGuard<'_, TokenBucket> {
+    bucket.lock().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    };
+
+    use stream_center::gop::MediaFrame;
+    use test_support::{flv::FRAME_INTERVAL_MS, frames};
+
+    use super::{EgressShaper, EgressShapingConfig, MAX_BITRATE_KEY};
+
+    /// the frames are FRAME_INTERVAL_MS apart
+    const FPS: u64 = 25;
+    const KEY_FRAME_BYTES: usize = 60_000;
+    /// 5mbps with the key frame of each one second gop
+    const FRAME_BYTES: usize = 23_500;
+    /// 128kbps with two of them for each video frame
+    const AUDIO_FRAME_BYTES: usize = 320;
+
+    fn video_frame(index: u64, bytes: usize) -> MediaFrame {
+        frames::video_frame(index, FPS, bytes)
+    }
+
+    fn audio_frame(index: u64) -> MediaFrame {
+        frames::audio_frame(index, AUDIO_FRAME_BYTES)
+    }
+
+    #[test]
+    fn capped_subscriber_drops_video_by_gop_and_converges_to_the_cap() {
+        let shaper = EgressShaper::new(EgressShapingConfig {
+            per_subscriber_kbps: Some(1000),
+            ..Default::default()
+        });
+        let mut subscriber = shaper.subscriber("live/shaped", &HashMap::new());
+        let start = Instant::now();
+        let seconds = 30;
+        let mut sent_bytes_per_second = vec![0; seconds as usize];
+        let mut total_bytes = 0;
+        for second in 0..seconds {
+            let mut dropping = false;
+            for index in second * FPS..(second + 1) * FPS {
+                let bytes = if index.is_multiple_of(FPS) {
+                    KEY_FRAME_BYTES
+                } else {
+                    FRAME_BYTES
+                };
+                let at = start + Duration::from_millis(index * FRAME_INTERVAL_MS);
+                total_bytes += bytes;
+                if subscriber.admit_at(&video_frame(index, bytes), at) {
+                    assert!(!dropping, "frame {} is sent after a dropped one", index);
+                    sent_bytes_per_second[second as usize] += bytes;
+                } else {
+                    dropping = true;
+                }
+                // two audio frames for each video frame, none of them is dropped
+                for at in [at, at + Duration::from_millis(FRAME_INTERVAL_MS / 2)] {
+                    total_bytes += AUDIO_FRAME_BYTES;
+                    assert!(subscriber.admit_at(&audio_frame(index), at));
+                    sent_bytes_per_second[second as usize] += AUDIO_FRAME_BYTES;
+                }
+            }
+            assert!(dropping, "nothing is dropped in second {}", second);
+        }
+
+        let sent_bytes: usize = sent_bytes_per_second.iter().sum();
+        assert_eq!(subscriber.shaped_bytes() as usize, total_bytes - sent_bytes);
+        // 1mbps is 125000 bytes per second, the burst is spent in the first seconds
+        let steady: usize = sent_bytes_per_second[10..].iter().sum();
+        let rate = steady as f64 / (seconds - 10) as f64;
+        assert!(
+            (rate - 125_000.0).abs() < 125_000.0 * 0.1,
+            "{} bytes per second",
+            rate
+        );
+    }
+
+    #[test]
+    fn stream_and_global_buckets_are_shared() {
+        let shaper = EgressShaper::new(EgressShapingConfig {
+            per_stream_kbps: Some(8),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        // 1000 bytes per second, the same for the burst, 200 bytes are left to the audio
+        let mut a = shaper.subscriber("live/a", &HashMap::new());
+        let mut b = shaper.subscriber("live/a", &HashMap::new());
+        let mut c = shaper.subscriber("live/c", &HashMap::new());
+        assert!(a.admit_at(&video_frame(0, 600), now));
+        assert!(!b.admit_at(&video_frame(0, 600), now));
+        assert!(c.admit_at(&video_frame(0, 600), now));
+        // the audio takes the headroom the video left
+        assert!(b.admit_at(&audio_frame(0), now));
+
+        let shaper = EgressShaper::new(EgressShapingConfig {
+            global_kbps: Some(8),
+            ..Default::default()
+        });
+        let mut a = shaper.subscriber("live/a", &HashMap::new());
+        let mut c = shaper.subscriber("live/c", &HashMap::new());
+        assert!(a.admit_at(&video_frame(0, 600), now));
+        assert!(!c.admit_at(&video_frame(0, 600), now));
+        assert_eq!(c.shaped_bytes(), 600);
+    }
+
+    #[test]
+    fn subscriber_param_only_lowers_the_cap() {
+        let now = Instant::now();
+        let unlimited = EgressShaper::default();
+        let mut subscriber = unlimited.subscriber("live/a", &HashMap::new());
+        assert!(subscriber.is_unlimited());
+        assert!(subscriber.admit_at(&video_frame(0, 10_000_000), now));
+
+        let context = HashMap::from([(MAX_BITRATE_KEY.to_owned(), "8".to_owned())]);
+        let mut subscriber = unlimited.subscriber("live/a", &context);
+        assert!(!subscriber.admit_at(&video_frame(0, 900), now));
+        assert!(subscriber.admit_at(&video_frame(0, 800), now));
+
+        let capped = EgressShaper::new(EgressShapingConfig {
+            per_subscriber_kbps: Some(8),
+            ..Default::default()
+        });
+        let context = HashMap::from([(MAX_BITRATE_KEY.to_owned(), "8000".to_owned())]);
+        let mut subscriber = capped.subscriber("live/a", &context);
+        assert!(!subscriber.admit_at(&video_frame(0, 900), now));
+    }
+}
@@ -0,0 +1,17 @@
+use std::io;
+
+use flv_formats::errors::FLVError;
+use stream_center::errors::StreamCenterError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FlvRecorderError {
+    #[error("write recording failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("write flv header failed: {0}")]
+    Header(#[from] FLVError),
+    #[error("remux frame to flv tag failed: {0}")]
+    Remux(#[from] StreamCenterError),
+}
+
+pub type FlvRecorderResult<T> = Result<T, FlvRecorderError>;
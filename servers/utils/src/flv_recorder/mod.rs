@@ -0,0 +1,457 @@
+use std::{collections::HashMap, io::SeekFrom};
+
+use codec_common::video::VideoCodecCommon;
+use errors::FlvRecorderResult;
+use flv_formats::{
+    header::FLVHeader,
+    tag::{flv_tag_body::FLVTagBody, on_meta_data::OnMetaData, video_tag_header::VideoTagHeader},
+};
+use stream_center::{
+    end_of_stream::{EndOfStreamReason, SourceErrorCode},
+    gop::{FlvVideoHeader, MediaFrame, flv_tag_to_bytes},
+};
+use tokio::{
+    io::{AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+use tokio_util::bytes::Bytes;
+use utils::traits::writer::WriteTo;
+
+pub mod errors;
+
+/// the nal units of the recorded h264 frames are prefixed with 4 bytes of length
+const NALU_SIZE_LENGTH: u8 = 4;
+/// the marker of an amf0 number, the 8 bytes of the f64 follow it
+const AMF0_NUMBER_MARKER: u8 = 0x00;
+
+/// why the recording ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingEnd {
+    /// a final end of stream
+    Ended(EndOfStreamReason),
+    Failed {
+        code: SourceErrorCode,
+        detail: String,
+    },
+    /// the channel closed without telling why, e.g., the subscription is cancelled
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingSummary {
+    pub end: RecordingEnd,
+    /// the tags of the frames, the onMetaData of the recorder excluded
+    pub tags: u64,
+    pub duration_ms: u64,
+    pub file_size: u64,
+}
+
+/// where the numbers of the onMetaData patched at the end are in the file
+#[derive(Debug, Clone, Copy)]
+struct TrailerOffsets {
+    duration: u64,
+    file_size: u64,
+}
+
+/// Writes the frames of a subscription to a new flv file.
+/// The onMetaData written ahead of the frames has placeholders for the duration and the filesize,
+/// which are patched once the stream ends, after the frames queued before the end are written
+pub struct FlvRecorder<W> {
+    writer: W,
+    has_audio: bool,
+    has_video: bool,
+    video_header: FlvVideoHeader,
+    /// the codec and the timestamp of the last enhanced video tag,
+    /// the sequence of it is ended when the file is finalized
+    enhanced_video_sequence: Option<(VideoCodecCommon, u32)>,
+    /// None before the header is written
+    trailer: Option<TrailerOffsets>,
+    written_bytes: u64,
+    tags: u64,
+    first_dts_ms: Option<u64>,
+    last_dts_ms: u64,
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> FlvRecorder<W> {
+    /// the writer is at the start of an empty file
+    pub fn new(writer: W, has_audio: bool, has_video: bool) -> Self {
+        Self {
+            writer,
+            has_audio,
+            has_video,
+            video_header: FlvVideoHeader::Legacy,
+            enhanced_video_sequence: None,
+            trailer: None,
+            written_bytes: 0,
+            tags: 0,
+            first_dts_ms: None,
+            last_dts_ms: 0,
+        }
+    }
+
+    /// the video is recorded with the legacy header by default, which every player reads.
+    /// with the enhanced one, hevc and the other codecs of a FourCC go in the ExVideoTagHeader
+    pub fn with_video_header(mut self, video_header: FlvVideoHeader) -> Self {
+        self.video_header = video_header;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// records the frames until the stream ends, then finalizes the file.
+    /// a stalled publisher does not end the recording
+    pub async fn record(
+        &mut self,
+        receiver: &mut mpsc::Receiver<MediaFrame>,
+    ) -> FlvRecorderResult<RecordingSummary> {
+        let end = loop {
+            match receiver.recv().await {
+                None => break RecordingEnd::Closed,
+                Some(MediaFrame::EndOfStream { reason, .. }) if reason.is_final() => {
+                    break RecordingEnd::Ended(reason);
+                }
+                Some(MediaFrame::EndOfStream { reason, .. }) => {
+                    tracing::info!("{}, the recording goes on", reason);
+                }
+                Some(MediaFrame::SourceError { code, detail, .. }) => {
+                    break RecordingEnd::Failed { code, detail };
+                }
+                Some(frame) => self.write_frame(&frame).await?,
+            }
+        };
+        tracing::info!("recording ended: {:?}", end);
+        self.finalize().await?;
+        Ok(RecordingSummary {
+            end,
+            tags: self.tags,
+            duration_ms: self.duration_ms(),
+            file_size: self.written_bytes,
+        })
+    }
+
+    /// the control frames are not written, nor the metadata of the publisher but the first one,
+    /// which goes into the onMetaData of the recorder
+    pub async fn write_frame(&mut self, frame: &MediaFrame) -> FlvRecorderResult<()> {
+        if frame.is_control() {
+            return Ok(());
+        }
+        if self.trailer.is_none() {
+            let on_meta_data = match frame {
+                MediaFrame::Script { on_meta_data, .. } => (**on_meta_data).clone(),
+                _ => None,
+            };
+            self.write_header(on_meta_data).await?;
+        }
+        if frame.is_script() {
+            return Ok(());
+        }
+        let tag = frame.to_flv_tag_with_video_header(NALU_SIZE_LENGTH, self.video_header)?;
+        if let FLVTagBody::Video {
+            header: VideoTagHeader::Enhanced(_),
+            ..
+        } = &tag.body_with_filter.body
+        {
+            let codec_id = match frame {
+                MediaFrame::VideoConfig { config, .. } => config.as_ref().into(),
+                _ => frame.video_codec_id().unwrap(),
+            };
+            self.enhanced_video_sequence = Some((codec_id, tag.tag_header.timestamp));
+        }
+        self.write(&flv_tag_to_bytes(&tag)?).await?;
+        self.tags += 1;
+        if frame.is_video() || frame.is_audio() {
+            let dts_ms = frame.get_decode_timestamp_ms();
+            self.first_dts_ms.get_or_insert(dts_ms);
+            self.last_dts_ms = dts_ms;
+        }
+        Ok(())
+    }
+
+    /// ends the enhanced video sequence and patches the duration and the filesize into the onMetaData,
+    /// the file is complete after it
+    pub async fn finalize(&mut self) -> FlvRecorderResult<()> {
+        let trailer = match self.trailer {
+            Some(trailer) => trailer,
+            None => self.write_header(None).await?,
+        };
+        if let Some((codec_id, timestamp)) = self.enhanced_video_sequence.take() {
+            let tag = MediaFrame::video_sequence_end_flv_tag(
+                codec_id,
+                timestamp,
+                FlvVideoHeader::Enhanced,
+            )?;
+            self.write(&flv_tag_to_bytes(&tag)?).await?;
+        }
+        let duration_seconds = self.duration_ms() as f64 / 1000.0;
+        self.writer.seek(SeekFrom::Start(trailer.duration)).await?;
+        self.writer
+            .write_all(&duration_seconds.to_be_bytes())
+            .await?;
+        self.writer.seek(SeekFrom::Start(trailer.file_size)).await?;
+        self.writer
+            .write_all(&(self.written_bytes as f64).to_be_bytes())
+            .await?;
+        self.writer.seek(SeekFrom::End(0)).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    fn duration_ms(&self) -> u64 {
+        self.first_dts_ms
+            .map_or(0, |first| self.last_dts_ms.saturating_sub(first))
+    }
+
+    async fn write_header(
+        &mut self,
+        on_meta_data: Option<OnMetaData>,
+    ) -> FlvRecorderResult<TrailerOffsets> {
+        let mut bytes = Vec::with_capacity(9 + 4);
+        FLVHeader::new(self.has_audio, self.has_video).write_to(&mut bytes)?;
+        bytes.extend_from_slice(&0_u32.to_be_bytes());
+
+        let mut on_meta_data = on_meta_data.unwrap_or_else(|| OnMetaData::from(HashMap::new()));
+        on_meta_data.duration = Some(0.0);
+        on_meta_data.file_size = Some(0.0);
+        let script = MediaFrame::Script {
+            timestamp_nano: 0,
+            on_meta_data: Box::new(Some(on_meta_data)),
+            payload: Bytes::new(),
+        }
+        .to_flv_tag_bytes(NALU_SIZE_LENGTH)?;
+        let trailer = TrailerOffsets {
+            duration: bytes.len() as u64 + number_offset(&script, "duration"),
+            file_size: bytes.len() as u64 + number_offset(&script, "filesize"),
+        };
+        bytes.extend_from_slice(&script);
+        self.write(&bytes).await?;
+        self.trailer = Some(trailer);
+        Ok(trailer)
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> FlvRecorderResult<()> {
+        self.writer.write_all(bytes).await?;
+        self.written_bytes += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+/// where the f64 of the number of the key is in a tag of an ecma array having the key
+fn number_offset(tag: &[u8], key: &str) -> u64 {
+    let mut pattern = (key.len() as u16).to_be_bytes().to_vec();
+    pattern.extend_from_slice(key.as_bytes());
+    pattern.push(AMF0_NUMBER_MARKER);
+    tag.windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|position| (position + pattern.len()) as u64)
+        .expect("the onMetaData of the recorder has the key")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use flv_formats::{
+        stream::{FlvReadMode, FlvTagStream},
+        tag::{FLVTag, flv_tag_body::FLVTagBody},
+    };
+    use stream_center::{
+        end_of_stream::{EndOfStreamReason, SourceErrorCode},
+        gop::{FlvVideoHeader, MediaFrame},
+    };
+    use test_support::{flv::FRAME_INTERVAL_MS, frames::audio_frame};
+    use tokio::sync::mpsc;
+    use utils::traits::reader::ReadFrom;
+
+    use super::{FlvRecorder, RecordingEnd, number_offset};
+
+    /// an avc frame of one nal unit, as a legacy flv tag of it parses
+    fn avc_frame(index: u64, composition_time: u8) -> MediaFrame {
+        let dts_ms = (100 + index * FRAME_INTERVAL_MS) as u32;
+        let mut tag = vec![9, 0, 0, 11];
+        tag.extend_from_slice(&dts_ms.to_be_bytes()[1..]);
+        tag.extend_from_slice(&[0, 0, 0, 0]);
+        // key frame of avc, nalu, the composition time, then a 2 bytes idr slice
+        tag.extend_from_slice(&[0x17, 1, 0, 0, composition_time]);
+        tag.extend_from_slice(&[0, 0, 0, 2, 0x65, index as u8]);
+        MediaFrame::from_flv_tag(FLVTag::read_from(&mut Cursor::new(&tag)).unwrap(), 4).unwrap()
+    }
+
+    fn end_of_stream(reason: EndOfStreamReason) -> MediaFrame {
+        MediaFrame::EndOfStream {
+            timestamp_nano: 0,
+            reason,
+        }
+    }
+
+    /// the tag types of the file, after the header
+    /// read back by a strict tag stream, so a recording with a damaged tag fails
+    async fn tag_types(file: &[u8]) -> Vec<u8> {
+        let mut stream = FlvTagStream::new(Cursor::new(file.to_vec()))
+            .await
+            .unwrap()
+            .with_read_mode(FlvReadMode::Strict);
+        let mut tags = vec![];
+        while let Some((_, tag)) = stream.next_tag().await.unwrap() {
+            tags.push(u8::from(tag.tag_header.tag_type));
+        }
+        assert_eq!(stream.truncated_at(), None);
+        tags
+    }
+
+    fn number(file: &[u8], key: &str) -> f64 {
+        let offset = number_offset(file, key) as usize;
+        f64::from_be_bytes(file[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[tokio::test]
+    async fn trailer_is_written_after_the_queued_frames() {
+        let (sender, mut receiver) = mpsc::channel(64);
+        for index in 0..10 {
+            sender.send(audio_frame(index, 32)).await.unwrap();
+        }
+        sender
+            .send(end_of_stream(EndOfStreamReason::Unpublished))
+            .await
+            .unwrap();
+        // nothing after the end of stream is recorded
+        sender.send(audio_frame(10, 32)).await.unwrap();
+
+        let mut recorder = FlvRecorder::new(Cursor::new(Vec::new()), true, false);
+        let summary = recorder.record(&mut receiver).await.unwrap();
+        assert_eq!(
+            summary.end,
+            RecordingEnd::Ended(EndOfStreamReason::Unpublished)
+        );
+        assert_eq!(summary.tags, 10);
+        assert_eq!(summary.duration_ms, 9 * FRAME_INTERVAL_MS);
+
+        let file = recorder.into_inner().into_inner();
+        assert_eq!(&file[..3], b"FLV");
+        assert_eq!(summary.file_size, file.len() as u64);
+        // the onMetaData of the recorder, then all the frames queued before the end of stream
+        let mut expected = vec![18];
+        expected.extend([8; 10]);
+        assert_eq!(tag_types(&file).await, expected);
+        assert_eq!(
+            number(&file, "duration"),
+            (9 * FRAME_INTERVAL_MS) as f64 / 1000.0
+        );
+        assert_eq!(number(&file, "filesize"), file.len() as f64);
+        // the last frame ends right before the size of its tag
+        let last_tag_size = u32::from_be_bytes(file[file.len() - 4..].try_into().unwrap());
+        assert_eq!(file[file.len() - 4 - last_tag_size as usize], 8);
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn stalled_publisher_does_not_end_the_recording() {
+        let (sender, mut receiver) = mpsc::channel(64);
+        sender.send(audio_frame(0, 32)).await.unwrap();
+        sender
+            .send(end_of_stream(EndOfStreamReason::Stalled))
+            .await
+            .unwrap();
+        sender.send(audio_frame(1, 32)).await.unwrap();
+        sender
+            .send(MediaFrame::SourceError {
+                timestamp_nano: 0,
+                code: SourceErrorCode::BadFrame,
+                detail: "bad tag".to_owned(),
+            })
+            .await
+            .unwrap();
+
+        let mut recorder = FlvRecorder::new(Cursor::new(Vec::new()), true, false);
+        let summary = recorder.record(&mut receiver).await.unwrap();
+        assert_eq!(
+            summary.end,
+            RecordingEnd::Failed {
+                code: SourceErrorCode::BadFrame,
+                detail: "bad tag".to_owned(),
+            }
+        );
+        assert_eq!(summary.tags, 2);
+        let file = recorder.into_inner().into_inner();
+        assert_eq!(tag_types(&file).await, [18, 8, 8]);
+        assert_eq!(number(&file, "duration"), FRAME_INTERVAL_MS as f64 / 1000.0);
+    }
+
+    /// the video tags of the file, after the header
+    fn video_tags(file: &[u8]) -> Vec<FLVTag> {
+        let mut cursor = Cursor::new(&file[9 + 4..]);
+        let mut tags = vec![];
+        while (cursor.position() as usize) < file.len() - 9 - 4 {
+            let tag = FLVTag::read_from(&mut cursor).unwrap();
+            cursor.set_position(cursor.position() + 4);
+            if matches!(tag.body_with_filter.body, FLVTagBody::Video { .. }) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    #[tokio::test]
+    async fn enhanced_video_is_recorded_with_ex_video_headers() {
+        let (sender, mut receiver) = mpsc::channel(64);
+        let frames = [avc_frame(0, 0), avc_frame(1, 40)];
+        for frame in &frames {
+            sender.send(frame.clone()).await.unwrap();
+        }
+        sender
+            .send(end_of_stream(EndOfStreamReason::Unpublished))
+            .await
+            .unwrap();
+
+        let mut recorder = FlvRecorder::new(Cursor::new(Vec::new()), false, true)
+            .with_video_header(FlvVideoHeader::Enhanced);
+        let summary = recorder.record(&mut receiver).await.unwrap();
+        assert_eq!(summary.tags, 2);
+        let file = recorder.into_inner().into_inner();
+        assert_eq!(summary.file_size, file.len() as u64);
+        // the frames, then the end of the sequence
+        assert_eq!(tag_types(&file).await, [18, 9, 9, 9]);
+
+        let mut tags = video_tags(&file);
+        let sequence_end = tags.pop().unwrap();
+        let FLVTagBody::Video { body, .. } = &sequence_end.body_with_filter.body else {
+            unreachable!()
+        };
+        assert!(body.is_empty());
+        assert_eq!(
+            sequence_end.tag_header.timestamp,
+            tags[1].tag_header.timestamp
+        );
+        for (frame, tag) in frames.iter().zip(tags) {
+            let parsed = MediaFrame::from_flv_tag(tag, 4).unwrap();
+            assert_eq!(
+                parsed.get_presentation_timestamp_ns(),
+                frame.get_presentation_timestamp_ns()
+            );
+            assert_eq!(
+                parsed.to_flv_tag_bytes(4).unwrap(),
+                frame.to_flv_tag_bytes(4).unwrap()
+            );
+        }
+        // IsExHeader | inter frame | SequenceEnd, then the FourCC
+        let offset = file.len() - 4 - 5;
+        assert_eq!(
+            file[offset..offset + 5],
+            [0x80 | (2 << 4) | 2, b'a', b'v', b'c', b'1']
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_recording_is_a_valid_file() {
+        let (sender, mut receiver) = mpsc::channel::<MediaFrame>(1);
+        drop(sender);
+        let mut recorder = FlvRecorder::new(Cursor::new(Vec::new()), true, true);
+        let summary = recorder.record(&mut receiver).await.unwrap();
+        assert_eq!(summary.end, RecordingEnd::Closed);
+        let file = recorder.into_inner().into_inner();
+        assert_eq!(tag_types(&file).await, [18]);
+        assert_eq!(number(&file, "duration"), 0.0);
+        assert_eq!(number(&file, "filesize"), file.len() as f64);
+    }
+}
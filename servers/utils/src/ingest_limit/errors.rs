@@ -0,0 +1,11 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum IngestLimitError {
+    #[error("stream bitrate exceeded, stream: {stream_key}, limit: {limit_kbps}kbps")]
+    StreamBitrateExceeded { stream_key: String, limit_kbps: u64 },
+    #[error("global bitrate exceeded, stream: {stream_key}, limit: {limit_kbps}kbps")]
+    GlobalBitrateExceeded { stream_key: String, limit_kbps: u64 },
+}
+
+pub type IngestLimitResult<T> = Result<T, IngestLimitError>;
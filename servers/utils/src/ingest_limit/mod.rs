@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use errors::{IngestLimitError, IngestLimitResult};
+
+pub mod errors;
+
+/// well above the keyframes of common encoders, the 24 bits message length allows up to 16MiB
+pub const DEFAULT_MAX_RTMP_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+pub const DEFAULT_MAX_RTP_ACCESS_UNIT_SIZE: usize = 4 * 1024 * 1024;
+pub const DEFAULT_PER_STREAM_BITRATE_KBPS: u64 = 50_000;
+pub const DEFAULT_BURST_MS: u64 = 2000;
+
+#[derive(Debug, Clone)]
+pub struct IngestLimitConfig {
+    /// rtmp messages declaring a length above this are rejected
+    pub max_rtmp_message_size: usize,
+    /// max size of a single nal unit reassembled from rtp fragments
+    pub max_rtp_access_unit_size: usize,
+    /// None means unlimited
+    pub per_stream_bitrate_kbps: Option<u64>,
+    /// None means unlimited
+    pub global_bitrate_kbps: Option<u64>,
+    /// how long a publisher is allowed to burst above the bitrate
+    pub burst_ms: u64,
+}
+
+impl Default for IngestLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_rtmp_message_size: DEFAULT_MAX_RTMP_MESSAGE_SIZE,
+            max_rtp_access_unit_size: DEFAULT_MAX_RTP_ACCESS_UNIT_SIZE,
+            per_stream_bitrate_kbps: Some(DEFAULT_PER_STREAM_BITRATE_KBPS),
+            global_bitrate_kbps: None,
+            burst_ms: DEFAULT_BURST_MS,
+        }
+    }
+}
+
+/// A classic token bucket, tokens are bytes
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: u64,
+    tokens: u64,
+    bytes_per_second: u64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(bytes_per_second: u64, capacity: u64) -> Self {
+        Self::new_at(bytes_per_second, capacity, Instant::now())
+    }
+
+    pub fn new_at(bytes_per_second: u64, capacity: u64, now: Instant) -> Self {
+        Self {
+            capacity,
+            tokens: capacity,
+            bytes_per_second,
+            last_refill: now,
+        }
+    }
+
+    pub fn from_kbps(kbps: u64, burst_ms: u64) -> Self {
+        let bytes_per_second = kbps * 1000 / 8;
+        Self::new(
+            bytes_per_second,
+            (bytes_per_second * burst_ms / 1000).max(1),
+        )
+    }
+
+    pub fn tokens(&self) -> u64 {
+        self.tokens
+    }
+
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+
+    /// the tokens after refilling up to now
+    pub fn tokens_at(&mut self, now: Instant) -> u64 {
+        self.refill(now);
+        self.tokens
+    }
+
+    /// takes the bytes without checking, the tokens never go below zero
+    pub fn consume(&mut self, bytes: u64) {
+        self.tokens = self.tokens.saturating_sub(bytes);
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        let refilled = (self.bytes_per_second as u128 * elapsed.as_micros() / 1_000_000) as u64;
+        if refilled == 0 {
+            return;
+        }
+        self.tokens = self.tokens.saturating_add(refilled).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    pub fn try_consume_at(&mut self, bytes: u64, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens < bytes {
+            return false;
+        }
+        self.tokens -= bytes;
+        true
+    }
+
+    pub fn try_consume(&mut self, bytes: u64) -> bool {
+        self.try_consume_at(bytes, Instant::now())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IngestLimitMetrics {
+    pub oversize_rejected: AtomicU64,
+    pub rate_limited: AtomicU64,
+    pub publishers_disconnected: AtomicU64,
+}
+
+impl IngestLimitMetrics {
+    pub fn on_oversize_rejected(&self) {
+        self.oversize_rejected.fetch_add(1, Ordering::Relaxed);
+        self.publishers_disconnected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+        self.publishers_disconnected.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Shared by all the sessions of all protocols,
+/// checks the bitrate of each publishing stream and of the whole server
+#[derive(Debug, Clone, Default)]
+pub struct IngestRateLimiter {
+    config: IngestLimitConfig,
+    global_bucket: Option<Arc<Mutex<TokenBucket>>>,
+    stream_buckets: Arc<Mutex<HashMap<String, TokenBucket>>>,
+    metrics: Arc<IngestLimitMetrics>,
+}
+
+impl IngestRateLimiter {
+    pub fn new(config: IngestLimitConfig) -> Self {
+        Self {
+            global_bucket: config
+                .global_bitrate_kbps
+                .map(|kbps| Arc::new(Mutex::new(TokenBucket::from_kbps(kbps, config.burst_ms)))),
+            stream_buckets: Default::default(),
+            metrics: Default::default(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &IngestLimitConfig {
+        &self.config
+    }
+
+    pub fn metrics(&self) -> &Arc<IngestLimitMetrics> {
+        &self.metrics
+    }
+
+    /// account bytes received for the stream, fails if any of the limits is exceeded
+    pub fn check(&self, stream_key: &str, bytes: usize) -> IngestLimitResult<()> {
+        if let Some(limit_kbps) = self.config.per_stream_bitrate_kbps {
+            let mut buckets = self.stream_buckets.lock().unwrap();
+            let bucket = buckets
+                .entry(stream_key.to_owned())
+                .or_insert_with(|| TokenBucket::from_kbps(limit_kbps, self.config.burst_ms));
+            if !bucket.try_consume(bytes as u64) {
+                self.metrics.on_rate_limited();
+                return Err(IngestLimitError::StreamBitrateExceeded {
+                    stream_key: stream_key.to_owned(),
+                    limit_kbps,
+                });
+            }
+        }
+
+        if let Some(global_bucket) = &self.global_bucket
+            && !global_bucket.lock().unwrap().try_consume(bytes as u64)
+        {
+            self.metrics.on_rate_limited();
+            return Err(IngestLimitError::GlobalBitrateExceeded {
+                stream_key: stream_key.to_owned(),
+                limit_kbps: self.config.global_bitrate_kbps.unwrap_or_default(),
+            });
+        }
+        Ok(())
+    }
+
+    /// drop the bucket of a stream once the publisher is gone
+    pub fn release(&self, stream_key: &str) {
+        self.stream_buckets.lock().unwrap().remove(stream_key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        time::{Duration, Instant},
+    };
+
+    use rtmp_formats::chunk::{errors::ChunkMessageError, reader::Reader};
+    use tokio_util::bytes::BytesMut;
+
+    use super::{
+        DEFAULT_MAX_RTMP_MESSAGE_SIZE, IngestLimitConfig, IngestRateLimiter, TokenBucket,
+        errors::IngestLimitError,
+    };
+
+    #[test]
+    fn token_bucket_consumes_and_refills() {
+        let start = Instant::now();
+        // 1000 bytes per second, burst of 500 bytes
+        let mut bucket = TokenBucket::new_at(1000, 500, start);
+        assert!(bucket.try_consume_at(400, start));
+        assert_eq!(bucket.tokens(), 100);
+        assert!(!bucket.try_consume_at(200, start));
+        assert_eq!(bucket.tokens(), 100);
+
+        // 100ms refills 100 bytes
+        assert!(bucket.try_consume_at(200, start + Duration::from_millis(100)));
+        assert_eq!(bucket.tokens(), 0);
+
+        // refill never exceeds the capacity
+        assert!(!bucket.try_consume_at(501, start + Duration::from_secs(10)));
+        assert_eq!(bucket.tokens(), 500);
+        assert!(bucket.try_consume_at(500, start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn token_bucket_from_kbps() {
+        let bucket = TokenBucket::from_kbps(8, 2000);
+        // 8kbps is 1000 bytes per second, 2 seconds of burst
+        assert_eq!(bucket.tokens(), 2000);
+    }
+
+    #[test]
+    fn limiter_rejects_stream_over_bitrate() {
+        let limiter = IngestRateLimiter::new(IngestLimitConfig {
+            per_stream_bitrate_kbps: Some(8),
+            global_bitrate_kbps: None,
+            burst_ms: 1000,
+            ..Default::default()
+        });
+        assert!(limiter.check("live/a", 1000).is_ok());
+        assert!(matches!(
+            limiter.check("live/a", 1000),
+            Err(IngestLimitError::StreamBitrateExceeded { .. })
+        ));
+        // other streams have their own bucket
+        assert!(limiter.check("live/b", 1000).is_ok());
+        assert_eq!(
+            limiter
+                .metrics()
+                .rate_limited
+                .load(std::sync::atomic::Ordering::Relaxed),
+            1
+        );
+
+        limiter.release("live/a");
+        assert!(limiter.check("live/a", 1000).is_ok());
+    }
+
+    #[test]
+    fn limiter_rejects_over_global_bitrate() {
+        let limiter = IngestRateLimiter::new(IngestLimitConfig {
+            per_stream_bitrate_kbps: None,
+            global_bitrate_kbps: Some(8),
+            burst_ms: 1000,
+            ..Default::default()
+        });
+        assert!(limiter.check("live/a", 600).is_ok());
+        assert!(matches!(
+            limiter.check("live/b", 600),
+            Err(IngestLimitError::GlobalBitrateExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn default_config_rejects_oversize_rtmp_messages() {
+        let config = IngestLimitConfig::default();
+        // a type 0 video message header on chunk stream 6, 8MiB fits in the 24 bits length
+        let message_length: u32 = 8 * 1024 * 1024;
+        let mut chunk = vec![0x06, 0, 0, 0];
+        chunk.extend_from_slice(&message_length.to_be_bytes()[1..]);
+        chunk.extend_from_slice(&[9, 1, 0, 0, 0]);
+        let chunk = BytesMut::from(chunk.as_slice());
+
+        let mut reader = Reader::new();
+        reader.set_max_message_size(Some(config.max_rtmp_message_size));
+        match reader.read(&mut Cursor::new(&chunk), true) {
+            Err(ChunkMessageError::MessageTooLarge { size, limit, .. }) => {
+                assert_eq!(size, message_length as usize);
+                assert_eq!(limit, DEFAULT_MAX_RTMP_MESSAGE_SIZE);
+            }
+            other => panic!("expect MessageTooLarge, got: {:?}", other),
+        }
+    }
+}
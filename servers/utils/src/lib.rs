@@ -1,2 +1,10 @@
+pub mod egress_shaping;
+pub mod flv_recorder;
+pub mod ingest_limit;
+pub mod log_context;
+pub mod metrics;
+pub mod relay;
 pub mod runtime_handle;
+pub mod session_registry;
 pub mod stream_properities;
+pub mod supervisor;
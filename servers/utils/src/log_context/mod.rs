@@ -0,0 +1,96 @@
+use std::{fmt, net::SocketAddr};
+
+use tracing::Span;
+use uuid::Uuid;
+
+/// what a session does with a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamRole {
+    Publish,
+    Subscribe,
+}
+
+impl fmt::Display for StreamRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamRole::Publish => f.write_str("publish"),
+            StreamRole::Subscribe => f.write_str("subscribe"),
+        }
+    }
+}
+
+/// created when a connection is accepted, the task serving the connection is instrumented with it.
+/// error level so that the fields stay on the log lines of any log level
+pub fn session_span(protocol: &str, peer_addr: SocketAddr) -> Span {
+    tracing::error_span!(
+        "session",
+        protocol = %protocol,
+        session_id = %Uuid::now_v7(),
+        peer_addr = %peer_addr,
+    )
+}
+
+/// entered around the processing of a stream once a session publishes or plays it,
+/// the parent is the session span
+pub fn stream_span(session_span: &Span, stream_key: &str, role: StreamRole) -> Span {
+    tracing::error_span!(parent: session_span, "stream", stream_key = %stream_key, role = %role)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Cursor},
+        net::{Ipv4Addr, SocketAddr},
+        sync::{Arc, Mutex},
+    };
+
+    use rtmp_formats::chunk::reader::Reader;
+    use tokio_util::bytes::BytesMut;
+
+    use super::{StreamRole, session_span, stream_span};
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn chunk_reader_logs_carry_the_session_and_stream() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let peer_addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 50000));
+        tracing::subscriber::with_default(subscriber, || {
+            let session = session_span("rtmp", peer_addr);
+            let _stream = stream_span(&session, "live/test", StreamRole::Publish).entered();
+            // a type 1 message header on a chunk stream never seen before
+            let chunk = BytesMut::from(&[0x43, 0, 0, 0, 0, 0, 1, 9, 0][..]);
+            let _ = Reader::new().read(&mut Cursor::new(&chunk), true);
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let line = logs
+            .lines()
+            .find(|line| line.contains("new chunk must start with a type 0 message header"))
+            .unwrap();
+        assert!(line.contains("protocol=rtmp"), "{}", line);
+        assert!(line.contains("session_id="), "{}", line);
+        assert!(line.contains("peer_addr=127.0.0.1:50000"), "{}", line);
+        assert!(line.contains("stream_key=live/test"), "{}", line);
+        assert!(line.contains("role=publish"), "{}", line);
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::LazyLock;
+
+use utils::metrics::{self, Counter, Family, Gauge, GaugeGuard};
+
+static CONNECTIONS_ACCEPTED: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_connections_accepted_total",
+        "connections accepted by each server",
+        &["server"],
+    )
+});
+
+static CONNECTIONS_CLOSED: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_connections_closed_total",
+        "connections closed by each server",
+        &["server"],
+    )
+});
+
+static CONNECTIONS_ACTIVE: LazyLock<Family<Gauge>> = LazyLock::new(|| {
+    metrics::global().gauge_family(
+        "media_server_connections_active",
+        "connections being served by each server",
+        &["server"],
+    )
+});
+
+/// held by the task serving a connection, the connection is counted as closed once it is dropped
+#[derive(Debug)]
+pub struct ConnectionMetricsGuard {
+    closed: Counter,
+    _active: GaugeGuard,
+}
+
+impl ConnectionMetricsGuard {
+    /// `server` is the label of the server, e.g. rtmp
+    pub fn accepted(server: &str) -> Self {
+        CONNECTIONS_ACCEPTED.with_labels(&[server]).inc();
+        Self {
+            closed: CONNECTIONS_CLOSED.with_labels(&[server]),
+            _active: CONNECTIONS_ACTIVE.with_labels(&[server]).guard(),
+        }
+    }
+}
+
+impl Drop for ConnectionMetricsGuard {
+    fn drop(&mut self) {
+        self.closed.inc();
+    }
+}
@@ -0,0 +1,24 @@
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum RelayError {
+    #[error("invalid stream pattern: {0}")]
+    InvalidPattern(String),
+    #[error("invalid relay url: {0}")]
+    InvalidUrl(String),
+    #[error("relay host does not resolve: {0}")]
+    UnresolvedHost(String),
+    #[error("relay url points back to this server: {0}")]
+    Loop(String),
+    #[error("relay not found: {0}")]
+    NotFound(Uuid),
+    #[error("unsupported relays file version: {0}")]
+    UnsupportedVersion(u64),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type RelayResult<T> = Result<T, RelayError>;
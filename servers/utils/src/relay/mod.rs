@@ -0,0 +1,587 @@
+//! the relays of streams between this server and others, added and removed by the admin api at runtime.
+//! a pull relay plays a stream from an upstream server once it is subscribed to here and not published,
+//! a push relay publishes the streams live here to a downstream server.
+//! the relays and the log of their changes are kept in the data dir across restarts
+
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use errors::{RelayError, RelayResult};
+use serde::{Deserialize, Serialize};
+use stream_center::identity::StreamIdentity;
+use tokio::sync::watch;
+use utils::media_url::{MediaUrl, percent_encode};
+use uuid::Uuid;
+
+pub mod errors;
+
+/// in the data dir
+pub const RELAYS_FILE_NAME: &str = "relays.json";
+/// bumped when a field changes its meaning, fields added later default when missing
+pub const RELAYS_VERSION: u64 = 1;
+/// the latest changes kept in the audit log
+pub const MAX_AUDIT_ENTRIES: usize = 256;
+/// of the rtmp urls without a port
+pub const DEFAULT_RTMP_PORT: u16 = 1935;
+const RTMP_SCHEME: &str = "rtmp";
+const WILDCARD: char = '*';
+
+/// the streams a relay is for, `app/stream` in text and in json.
+/// a part is taken as is, or is `*` for any, or ends with `*` for the ones starting with the rest of it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct StreamPattern {
+    app: String,
+    stream: String,
+}
+
+impl StreamPattern {
+    /// apps are told without case as the stream identities are
+    pub fn matches(&self, stream_id: &StreamIdentity) -> bool {
+        part_matches(&self.app, stream_id.app.as_str())
+            && part_matches(&self.stream, stream_id.name.as_str())
+    }
+}
+
+fn part_matches(pattern: &str, value: &str) -> bool {
+    match pattern.strip_suffix(WILDCARD) {
+        Some(prefix) => value.starts_with(prefix),
+        None => pattern == value,
+    }
+}
+
+impl fmt::Display for StreamPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.app, self.stream)
+    }
+}
+
+impl FromStr for StreamPattern {
+    type Err = RelayError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RelayError::InvalidPattern(s.to_owned());
+        let (app, stream) = s.trim().split_once('/').ok_or_else(invalid)?;
+        let valid = |part: &str| {
+            !part.is_empty()
+                && !part.contains('/')
+                && !part.chars().any(char::is_control)
+                && part.find(WILDCARD).is_none_or(|at| at == part.len() - 1)
+        };
+        if !valid(app) || !valid(stream) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            app: app.to_ascii_lowercase(),
+            stream: stream.to_owned(),
+        })
+    }
+}
+
+impl TryFrom<String> for StreamPattern {
+    type Error = RelayError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<StreamPattern> for String {
+    fn from(value: StreamPattern) -> Self {
+        value.to_string()
+    }
+}
+
+/// what a relay does for the matching streams. the urls are `rtmp://host[:port][/app[/stream]][?query]`,
+/// the app and the stream left out are the ones of the stream relayed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RelayRule {
+    /// the stream is played from the upstream server once it is subscribed to here and not published
+    Pull {
+        pattern: StreamPattern,
+        upstream: String,
+    },
+    /// the stream is published to the target server while it is live here
+    Push {
+        pattern: StreamPattern,
+        target: String,
+    },
+}
+
+impl RelayRule {
+    pub fn pattern(&self) -> &StreamPattern {
+        match self {
+            RelayRule::Pull { pattern, .. } | RelayRule::Push { pattern, .. } => pattern,
+        }
+    }
+
+    /// the upstream of a pull, the target of a push
+    pub fn url(&self) -> &str {
+        match self {
+            RelayRule::Pull { upstream, .. } => upstream,
+            RelayRule::Push { target, .. } => target,
+        }
+    }
+
+    fn parsed_url(&self) -> RelayResult<MediaUrl> {
+        let url = MediaUrl::parse(self.url())
+            .map_err(|err| RelayError::InvalidUrl(format!("{}: {}", self.url(), err)))?;
+        if url.scheme() != RTMP_SCHEME {
+            return Err(RelayError::InvalidUrl(format!(
+                "{}: only rtmp is relayed",
+                self.url()
+            )));
+        }
+        if !url.rest().is_empty() {
+            return Err(RelayError::InvalidUrl(format!(
+                "{}: more than an app and a stream in the path",
+                self.url()
+            )));
+        }
+        Ok(url)
+    }
+
+    /// where the stream is relayed from or to
+    pub fn endpoint_for(&self, stream_id: &StreamIdentity) -> RelayResult<RelayEndpoint> {
+        let url = self.parsed_url()?;
+        let app = url.app().unwrap_or(stream_id.app.as_str());
+        let stream = url.stream().unwrap_or(stream_id.name.as_str());
+        let host = url.host().to_owned();
+        let port = url.port().unwrap_or(DEFAULT_RTMP_PORT);
+        let mut stream_name = percent_encode(stream);
+        for (i, (key, value)) in url.query().iter().enumerate() {
+            let separator = if i == 0 { '?' } else { '&' };
+            stream_name.push(separator);
+            stream_name.push_str(&percent_encode(key));
+            stream_name.push('=');
+            stream_name.push_str(&percent_encode(value));
+        }
+        Ok(RelayEndpoint {
+            tc_url: format!("{}://{}:{}/{}", RTMP_SCHEME, host, port, percent_encode(app)),
+            app: app.to_owned(),
+            stream_name,
+            host,
+            port,
+        })
+    }
+}
+
+/// a rtmp server and what a relay publishes or plays there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayEndpoint {
+    /// ipv6 hosts are in brackets
+    pub host: String,
+    pub port: u16,
+    pub app: String,
+    pub tc_url: String,
+    /// encoded, with the query of the url
+    pub stream_name: String,
+}
+
+impl RelayEndpoint {
+    /// `host:port`, to connect to
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relay {
+    pub id: Uuid,
+    /// unix millis
+    pub created_at: u64,
+    #[serde(flatten)]
+    pub rule: RelayRule,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayAction {
+    Added,
+    Removed,
+}
+
+/// a change of the relays
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayAuditEntry {
+    /// unix millis
+    pub at: u64,
+    pub action: RelayAction,
+    pub relay: Relay,
+}
+
+/// what is kept in the data dir
+#[derive(Debug, Serialize, Deserialize)]
+struct RelaysFile {
+    version: u64,
+    #[serde(default)]
+    relays: Vec<Relay>,
+    #[serde(default)]
+    audit: VecDeque<RelayAuditEntry>,
+}
+
+#[derive(Debug, Default)]
+struct RelayState {
+    /// in the order they were added
+    relays: Vec<Relay>,
+    audit: VecDeque<RelayAuditEntry>,
+}
+
+#[derive(Debug, Default)]
+struct RelayRegistryInner {
+    state: Mutex<RelayState>,
+    relays_sender: watch::Sender<Arc<Vec<Relay>>>,
+    data_dir: Option<PathBuf>,
+    /// where the rtmp server listens, a relay to any of them would loop back
+    local_addresses: Vec<SocketAddr>,
+}
+
+/// the relays in effect, changed by the admin api and acted on by the relay runners
+#[derive(Debug, Clone, Default)]
+pub struct RelayRegistry {
+    inner: Arc<RelayRegistryInner>,
+}
+
+impl RelayRegistry {
+    /// with the relays kept in the data dir, a file which can not be read is logged
+    /// and replaced by the next change
+    pub fn open(data_dir: Option<PathBuf>, local_addresses: Vec<SocketAddr>) -> Self {
+        let state = match data_dir.as_deref().map(Self::load).transpose() {
+            Ok(state) => state.flatten().unwrap_or_default(),
+            Err(err) => {
+                tracing::error!("load relays failed, starting with none: {}", err);
+                RelayState::default()
+            }
+        };
+        let (relays_sender, _) = watch::channel(Arc::new(state.relays.clone()));
+        Self {
+            inner: Arc::new(RelayRegistryInner {
+                state: Mutex::new(state),
+                relays_sender,
+                data_dir,
+                local_addresses,
+            }),
+        }
+    }
+
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(RELAYS_FILE_NAME)
+    }
+
+    fn load(data_dir: &Path) -> RelayResult<Option<RelayState>> {
+        let json = match fs::read_to_string(Self::path(data_dir)) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let file: RelaysFile = serde_json::from_str(&json)?;
+        if !(1..=RELAYS_VERSION).contains(&file.version) {
+            return Err(RelayError::UnsupportedVersion(file.version));
+        }
+        Ok(Some(RelayState {
+            relays: file.relays,
+            audit: file.audit,
+        }))
+    }
+
+    /// replaces the file in the data dir, a crash halfway leaves the previous one as is
+    fn save(&self, state: &RelayState) -> RelayResult<()> {
+        let Some(data_dir) = &self.inner.data_dir else {
+            return Ok(());
+        };
+        let file = RelaysFile {
+            version: RELAYS_VERSION,
+            relays: state.relays.clone(),
+            audit: state.audit.clone(),
+        };
+        fs::create_dir_all(data_dir)?;
+        let path = Self::path(data_dir);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(&file)?)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// in the order they were added
+    pub fn relays(&self) -> Vec<Relay> {
+        self.inner.state.lock().unwrap().relays.clone()
+    }
+
+    /// the latest changes, the oldest first
+    pub fn audit(&self) -> Vec<RelayAuditEntry> {
+        self.inner
+            .state
+            .lock()
+            .unwrap()
+            .audit
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// the relays now and after each change
+    pub fn watch(&self) -> watch::Receiver<Arc<Vec<Relay>>> {
+        self.inner.relays_sender.subscribe()
+    }
+
+    /// the pull relay added first of the stream, if any
+    pub fn pull_relay_of(&self, stream_id: &StreamIdentity) -> Option<Relay> {
+        self.inner
+            .state
+            .lock()
+            .unwrap()
+            .relays
+            .iter()
+            .find(|relay| {
+                matches!(relay.rule, RelayRule::Pull { .. }) && relay.rule.pattern().matches(stream_id)
+            })
+            .cloned()
+    }
+
+    /// takes effect at once and is kept once saved.
+    /// a relay to this server is refused, whatever name or address of it the url has
+    pub async fn add(&self, rule: RelayRule) -> RelayResult<RelayAuditEntry> {
+        let url = rule.parsed_url()?;
+        self.check_loop(&url).await?;
+        let now = unix_millis(SystemTime::now());
+        let relay = Relay {
+            id: Uuid::now_v7(),
+            created_at: now,
+            rule,
+        };
+        self.change(RelayAction::Added, relay, now)
+    }
+
+    pub fn remove(&self, id: Uuid) -> RelayResult<RelayAuditEntry> {
+        let relay = self
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .relays
+            .iter()
+            .find(|relay| relay.id == id)
+            .cloned()
+            .ok_or(RelayError::NotFound(id))?;
+        self.change(
+            RelayAction::Removed,
+            relay,
+            unix_millis(SystemTime::now()),
+        )
+    }
+
+    /// the change is saved before it is in effect, a change failing to be saved is not made
+    fn change(&self, action: RelayAction, relay: Relay, at: u64) -> RelayResult<RelayAuditEntry> {
+        let mut state = self.inner.state.lock().unwrap();
+        let mut relays = state.relays.clone();
+        match action {
+            RelayAction::Added => relays.push(relay.clone()),
+            RelayAction::Removed => {
+                let Some(index) = relays.iter().position(|kept| kept.id == relay.id) else {
+                    return Err(RelayError::NotFound(relay.id));
+                };
+                relays.remove(index);
+            }
+        }
+        let entry = RelayAuditEntry { at, action, relay };
+        let mut audit = state.audit.clone();
+        audit.push_back(entry.clone());
+        while audit.len() > MAX_AUDIT_ENTRIES {
+            audit.pop_front();
+        }
+        let changed = RelayState { relays, audit };
+        self.save(&changed)?;
+        *state = changed;
+        self.inner
+            .relays_sender
+            .send_replace(Arc::new(state.relays.clone()));
+        tracing::info!(
+            "relay {} {:?}: {} {} {}",
+            entry.relay.id,
+            entry.action,
+            match entry.relay.rule {
+                RelayRule::Pull { .. } => "pull",
+                RelayRule::Push { .. } => "push",
+            },
+            entry.relay.rule.pattern(),
+            entry.relay.rule.url()
+        );
+        Ok(entry)
+    }
+
+    /// whether any address the host resolves to is one the rtmp server listens on,
+    /// a host which does not resolve can not be told and is refused
+    async fn check_loop(&self, url: &MediaUrl) -> RelayResult<()> {
+        let host = url.host().trim_start_matches('[').trim_end_matches(']');
+        let port = url.port().unwrap_or(DEFAULT_RTMP_PORT);
+        let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|err| RelayError::UnresolvedHost(format!("{}: {}", url.host(), err)))?
+            .collect();
+        if addresses.is_empty() {
+            return Err(RelayError::UnresolvedHost(url.host().to_owned()));
+        }
+        if addresses.iter().any(|address| self.is_local(address)) {
+            return Err(RelayError::Loop(url.to_string()));
+        }
+        Ok(())
+    }
+
+    fn is_local(&self, address: &SocketAddr) -> bool {
+        self.inner.local_addresses.iter().any(|local| {
+            local.port() == address.port()
+                && (local.ip() == address.ip()
+                    || (local.ip().is_unspecified() && is_own_ip(address.ip())))
+        })
+    }
+}
+
+/// an address of a local interface, which a socket can be bound to
+fn is_own_ip(ip: IpAddr) -> bool {
+    ip.is_loopback() || ip.is_unspecified() || UdpSocket::bind((ip, 0)).is_ok()
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use stream_center::identity::StreamIdentity;
+
+    use super::{
+        RelayAction, RelayError, RelayRegistry, RelayRule, StreamPattern,
+        errors::RelayResult,
+    };
+
+    fn pattern(pattern: &str) -> StreamPattern {
+        pattern.parse().unwrap()
+    }
+
+    fn stream(key: &str) -> StreamIdentity {
+        key.parse().unwrap()
+    }
+
+    fn push(pattern_text: &str, target: &str) -> RelayRule {
+        RelayRule::Push {
+            pattern: pattern(pattern_text),
+            target: target.to_owned(),
+        }
+    }
+
+    #[test]
+    fn patterns_match_literally_or_by_prefix() {
+        assert!(pattern("live/test").matches(&stream("live/test")));
+        assert!(!pattern("live/test").matches(&stream("live/test2")));
+        assert!(pattern("Live/*").matches(&stream("live/anything")));
+        assert!(pattern("*/cam*").matches(&stream("studio/cam1")));
+        assert!(!pattern("*/cam*").matches(&stream("studio/main")));
+
+        for invalid in ["live", "/test", "live/", "li*ve/test", "live/a/b"] {
+            assert!(
+                matches!(
+                    invalid.parse::<StreamPattern>(),
+                    Err(RelayError::InvalidPattern(_))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn endpoints_default_to_the_stream_relayed() {
+        let endpoint = push("live/*", "rtmp://origin.example/")
+            .endpoint_for(&stream("live/test"))
+            .unwrap();
+        assert_eq!(endpoint.address(), "origin.example:1935");
+        assert_eq!(endpoint.tc_url, "rtmp://origin.example:1935/live");
+        assert_eq!(endpoint.stream_name, "test");
+
+        let endpoint = push("live/*", "rtmp://origin.example:1936/ingest/main?token=abc")
+            .endpoint_for(&stream("live/test"))
+            .unwrap();
+        assert_eq!(endpoint.tc_url, "rtmp://origin.example:1936/ingest");
+        assert_eq!(endpoint.stream_name, "main?token=abc");
+    }
+
+    #[tokio::test]
+    async fn relays_to_this_server_are_refused() -> RelayResult<()> {
+        let local: SocketAddr = "0.0.0.0:19350".parse().unwrap();
+        let registry = RelayRegistry::open(None, vec![local]);
+        for url in [
+            "rtmp://127.0.0.1:19350/live",
+            "rtmp://localhost:19350",
+            "rtmp://[::1]:19350/live",
+        ] {
+            assert!(
+                matches!(
+                    registry.add(push("live/*", url)).await,
+                    Err(RelayError::Loop(_))
+                ),
+                "{}",
+                url
+            );
+        }
+        assert!(matches!(
+            registry.add(push("live/*", "http://127.0.0.1/live")).await,
+            Err(RelayError::InvalidUrl(_))
+        ));
+        assert!(registry.relays().is_empty());
+
+        // another port of this host is another server
+        registry
+            .add(push("live/*", "rtmp://127.0.0.1:19351/live"))
+            .await?;
+        assert_eq!(registry.relays().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn relays_and_their_changes_are_kept_across_restarts() -> RelayResult<()> {
+        let data_dir = std::env::temp_dir().join(format!("relays-{}", uuid::Uuid::now_v7()));
+        let registry = RelayRegistry::open(Some(data_dir.clone()), Vec::new());
+        let mut changes = registry.watch();
+        let pull = registry
+            .add(RelayRule::Pull {
+                pattern: pattern("edge/*"),
+                upstream: "rtmp://127.0.0.1:1940/live".to_owned(),
+            })
+            .await?;
+        let pushed = registry
+            .add(push("live/*", "rtmp://127.0.0.1:1941"))
+            .await?;
+        registry.remove(pushed.relay.id)?;
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(changes.borrow_and_update().len(), 1);
+        assert!(matches!(
+            registry.remove(pushed.relay.id),
+            Err(RelayError::NotFound(_))
+        ));
+
+        let reopened = RelayRegistry::open(Some(data_dir.clone()), Vec::new());
+        assert_eq!(reopened.relays(), vec![pull.relay.clone()]);
+        let actions: Vec<_> = reopened.audit().iter().map(|entry| entry.action).collect();
+        assert_eq!(
+            actions,
+            [RelayAction::Added, RelayAction::Added, RelayAction::Removed]
+        );
+        assert_eq!(
+            reopened.pull_relay_of(&stream("edge/cam1")),
+            Some(pull.relay)
+        );
+        assert_eq!(reopened.pull_relay_of(&stream("live/cam1")), None);
+        std::fs::remove_dir_all(data_dir)?;
+        Ok(())
+    }
+}
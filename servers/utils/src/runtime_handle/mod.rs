@@ -1,6 +1,6 @@
 use std::{sync::Arc, time::SystemTime};
 
-use stream_center::gop::MediaFrame;
+use stream_center::{gop::MediaFrame, latency::LatencyProbe, stream_source::MediaSelection};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
@@ -10,7 +10,21 @@ pub struct PlayHandle {
     pub play_id: Uuid,
     pub receive_audio: bool,
     pub receive_video: bool,
+    pub audio_track: u8,
     pub buffer_length: Option<u32>,
+    /// None if latency measurement is disabled
+    pub latency_probe: Option<LatencyProbe>,
+}
+
+impl PlayHandle {
+    pub fn media_selection(&self) -> MediaSelection {
+        MediaSelection {
+            audio: self.receive_audio,
+            video: self.receive_video,
+            data: true,
+            audio_track: self.audio_track,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
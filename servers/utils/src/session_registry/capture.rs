@@ -0,0 +1,253 @@
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rtp_session::{
+    capture::{CaptureFormat, CaptureInfo, CaptureLimits},
+    errors::RtpSessionError,
+};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::{SessionEntry, SessionProtocol, SessionRegistry};
+
+#[derive(Debug, Error)]
+pub enum SessionCaptureError {
+    #[error("session not found: {0}")]
+    SessionNotFound(Uuid),
+    #[error("no session carrying rtp publishes or plays {0}")]
+    NoSessionOfStream(String),
+    #[error("{0} sessions do not carry rtp")]
+    NotRtp(SessionProtocol),
+    #[error("session {0} is not captured")]
+    NotCaptured(Uuid),
+    #[error("create capture dir failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("capture error: {0}")]
+    Capture(#[from] RtpSessionError),
+}
+
+pub type SessionCaptureResult<T> = Result<T, SessionCaptureError>;
+
+/// a capture of the packets of a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionCapture {
+    pub session_id: Uuid,
+    pub info: CaptureInfo,
+}
+
+impl SessionRegistry {
+    fn entry(&self, id: &Uuid) -> SessionCaptureResult<Arc<SessionEntry>> {
+        self.sessions
+            .get(id)
+            .map(|entry| entry.clone())
+            .ok_or(SessionCaptureError::SessionNotFound(*id))
+    }
+
+    /// the sessions carrying rtp the stream is published or played by
+    fn rtp_entries_of(&self, stream_key: &str) -> Vec<Arc<SessionEntry>> {
+        self.sessions
+            .iter()
+            .filter(|entry| {
+                entry.protocol.carries_rtp()
+                    && entry
+                        .state
+                        .lock()
+                        .unwrap()
+                        .stream_keys
+                        .iter()
+                        .any(|key| key == stream_key)
+            })
+            .map(|entry| entry.clone())
+            .collect()
+    }
+
+    /// captures the rtp and rtcp packets of the session to a new file in the dir,
+    /// named after the session and the time it starts
+    pub async fn start_capture(
+        &self,
+        id: &Uuid,
+        dir: &Path,
+        format: CaptureFormat,
+        limits: CaptureLimits,
+    ) -> SessionCaptureResult<SessionCapture> {
+        Self::start_entry_capture(self.entry(id)?, dir, format, limits).await
+    }
+
+    async fn start_entry_capture(
+        entry: Arc<SessionEntry>,
+        dir: &Path,
+        format: CaptureFormat,
+        limits: CaptureLimits,
+    ) -> SessionCaptureResult<SessionCapture> {
+        if !entry.protocol.carries_rtp() {
+            return Err(SessionCaptureError::NotRtp(entry.protocol));
+        }
+        tokio::fs::create_dir_all(dir).await?;
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!(
+            "{}-{}.{}",
+            entry.id,
+            started_at,
+            format.extension()
+        ));
+        let capture = entry.capture.start(path, format, limits).await?;
+        Ok(SessionCapture {
+            session_id: entry.id,
+            info: capture.info(),
+        })
+    }
+
+    /// the packets recorded so far are still written to the file
+    pub fn stop_capture(&self, id: &Uuid) -> SessionCaptureResult<SessionCapture> {
+        let entry = self.entry(id)?;
+        let capture = entry
+            .capture
+            .stop()
+            .ok_or(SessionCaptureError::NotCaptured(*id))?;
+        Ok(SessionCapture {
+            session_id: entry.id,
+            info: capture.info(),
+        })
+    }
+
+    /// captures each session carrying rtp that publishes or plays the stream, to a file of its own
+    pub async fn start_stream_capture(
+        &self,
+        stream_key: &str,
+        dir: &Path,
+        format: CaptureFormat,
+        limits: CaptureLimits,
+    ) -> SessionCaptureResult<Vec<SessionCapture>> {
+        let entries = self.rtp_entries_of(stream_key);
+        if entries.is_empty() {
+            return Err(SessionCaptureError::NoSessionOfStream(
+                stream_key.to_owned(),
+            ));
+        }
+        let mut captures = Vec::with_capacity(entries.len());
+        for entry in entries {
+            captures.push(Self::start_entry_capture(entry, dir, format, limits).await?);
+        }
+        Ok(captures)
+    }
+
+    /// the captures of the stream that were running
+    pub fn stop_stream_capture(&self, stream_key: &str) -> Vec<SessionCapture> {
+        self.rtp_entries_of(stream_key)
+            .into_iter()
+            .filter_map(|entry| {
+                entry.capture.stop().map(|capture| SessionCapture {
+                    session_id: entry.id,
+                    info: capture.info(),
+                })
+            })
+            .collect()
+    }
+
+    /// the capture running of each session, or its latest one
+    pub fn captures(&self) -> Vec<SessionCapture> {
+        self.sessions
+            .iter()
+            .filter_map(|entry| {
+                entry.capture.capture().map(|capture| SessionCapture {
+                    session_id: entry.id,
+                    info: capture.info(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use rtp_session::capture::{
+        CaptureFormat, CaptureLimits, CaptureStop, PacketDirection, PacketKind,
+    };
+    use tokio_util::bytes::Bytes;
+
+    use super::SessionCaptureError;
+    use crate::session_registry::{SessionProtocol, SessionRegistry, SessionRole};
+
+    fn peer(port: u16) -> SocketAddr {
+        (Ipv4Addr::LOCALHOST, port).into()
+    }
+
+    #[tokio::test]
+    async fn the_rtp_sessions_of_a_stream_are_captured_to_files_of_their_own() {
+        let dir = std::env::temp_dir().join(format!("session-captures-{}", std::process::id()));
+        let registry = SessionRegistry::default();
+        let publisher = registry.register(SessionProtocol::Rtsp, peer(1000));
+        publisher.set_role(SessionRole::Publisher, "live/cam");
+        let player = registry.register(SessionProtocol::Rtsp, peer(1001));
+        player.set_role(SessionRole::Subscriber, "live/cam");
+        let rtmp = registry.register(SessionProtocol::Rtmp, peer(1002));
+        rtmp.set_role(SessionRole::Subscriber, "live/cam");
+
+        assert!(matches!(
+            registry
+                .start_capture(
+                    &rtmp.id(),
+                    &dir,
+                    CaptureFormat::Pcap,
+                    CaptureLimits::default()
+                )
+                .await,
+            Err(SessionCaptureError::NotRtp(SessionProtocol::Rtmp))
+        ));
+        assert!(matches!(
+            registry
+                .start_stream_capture("live/other", &dir, CaptureFormat::Pcap, Default::default())
+                .await,
+            Err(SessionCaptureError::NoSessionOfStream(_))
+        ));
+        let captures = registry
+            .start_stream_capture("live/cam", &dir, CaptureFormat::RtpDump, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(captures.len(), 2);
+        assert!(
+            captures
+                .iter()
+                .all(|capture| capture.info.path.starts_with(&dir))
+        );
+        assert!(matches!(
+            registry
+                .start_capture(
+                    &player.id(),
+                    &dir,
+                    CaptureFormat::Pcap,
+                    CaptureLimits::default()
+                )
+                .await,
+            Err(SessionCaptureError::Capture(_))
+        ));
+
+        let packet = Bytes::from_static(&[0x80, 96, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        publisher.capture_tap().record(
+            PacketKind::Rtp,
+            PacketDirection::Received,
+            None,
+            Some(peer(1000)),
+            &packet,
+        );
+        let stopped = registry.stop_stream_capture("live/cam");
+        assert_eq!(stopped.len(), 2);
+        let publisher_capture = publisher.capture_tap().capture().unwrap();
+        assert_eq!(publisher_capture.stopped().await, CaptureStop::Requested);
+        assert_eq!(publisher_capture.info().packets, 1);
+        assert!(matches!(
+            registry.stop_capture(&player.id()),
+            Err(SessionCaptureError::NotCaptured(_))
+        ));
+        assert_eq!(registry.captures().len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
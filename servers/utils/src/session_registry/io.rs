@@ -0,0 +1,155 @@
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_util::bytes::Bytes;
+use unified_io::{UnderlyingIO, UnifiedIO};
+
+use super::SessionCounters;
+
+/// counts the bytes read from and written to a byte stream
+#[derive(Debug)]
+pub struct CountedStream<T> {
+    inner: T,
+    counters: SessionCounters,
+}
+
+impl<T> CountedStream<T> {
+    pub fn new(inner: T, counters: SessionCounters) -> Self {
+        Self { inner, counters }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for CountedStream<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = res {
+            this.counters.on_received(buf.filled().len() - filled);
+        }
+        res
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for CountedStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = res {
+            this.counters.on_sent(written);
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// counts the bytes received from and sent to an io
+pub struct CountedIO {
+    inner: Pin<Box<dyn UnifiedIO + Send>>,
+    counters: SessionCounters,
+}
+
+impl CountedIO {
+    pub fn new(inner: Pin<Box<dyn UnifiedIO + Send>>, counters: SessionCounters) -> Self {
+        Self { inner, counters }
+    }
+}
+
+impl fmt::Debug for CountedIO {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CountedIO")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl Stream for CountedIO {
+    type Item = Result<Bytes, std::io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let res = this.inner.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(Ok(bytes))) = &res {
+            this.counters.on_received(bytes.len());
+        }
+        res
+    }
+}
+
+impl Sink<Bytes> for CountedIO {
+    type Error = std::io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().inner.as_mut().poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let len = item.len();
+        this.inner.as_mut().start_send(item)?;
+        this.counters.on_sent(len);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().inner.as_mut().poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().inner.as_mut().poll_close(cx)
+    }
+}
+
+impl UnifiedIO for CountedIO {
+    fn get_underlying_io_type(&self) -> UnderlyingIO {
+        self.inner.get_underlying_io_type()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::CountedStream;
+    use crate::session_registry::{SessionProtocol, SessionRegistry};
+
+    #[tokio::test]
+    async fn counts_the_bytes_read_and_written() {
+        let registry = SessionRegistry::default();
+        let handle = registry.register(SessionProtocol::Rtmp, (Ipv4Addr::LOCALHOST, 1935).into());
+        let (client, server) = tokio::io::duplex(64);
+        let mut server = CountedStream::new(server, handle.counters());
+        let mut client = client;
+
+        client.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        server.read_exact(&mut buf).await.unwrap();
+        server.write_all(b"hello world").await.unwrap();
+        drop(server);
+
+        let info = registry.get(&handle.id()).unwrap();
+        assert_eq!((info.bytes_in, info.bytes_out), (5, 11));
+    }
+}
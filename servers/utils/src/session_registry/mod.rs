@@ -0,0 +1,469 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::DashMap;
+use rtp_session::capture::CaptureTap;
+use stream_center::drain::DrainRequest;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+pub mod capture;
+pub mod io;
+
+/// how often the bytes counted by a session are added to its entry
+pub const COUNTER_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionProtocol {
+    Rtmp,
+    Rtsp,
+    HttpFlv,
+    Srt,
+}
+
+impl SessionProtocol {
+    /// the sessions of the protocol send and receive rtp, their packets can be captured
+    pub fn carries_rtp(&self) -> bool {
+        matches!(self, SessionProtocol::Rtsp)
+    }
+}
+
+impl fmt::Display for SessionProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionProtocol::Rtmp => write!(f, "rtmp"),
+            SessionProtocol::Rtsp => write!(f, "rtsp"),
+            SessionProtocol::HttpFlv => write!(f, "http-flv"),
+            SessionProtocol::Srt => write!(f, "srt"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SessionRole {
+    /// neither publishing nor playing yet
+    #[default]
+    Pending,
+    Publisher,
+    Subscriber,
+}
+
+impl fmt::Display for SessionRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionRole::Pending => write!(f, "pending"),
+            SessionRole::Publisher => write!(f, "publisher"),
+            SessionRole::Subscriber => write!(f, "subscriber"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SessionState {
+    role: SessionRole,
+    /// `app/stream` of the streams published or played, in the order they started
+    stream_keys: Vec<String>,
+}
+
+#[derive(Debug)]
+struct SessionEntry {
+    id: Uuid,
+    protocol: SessionProtocol,
+    peer_addr: SocketAddr,
+    created_at: SystemTime,
+    state: Mutex<SessionState>,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    /// unix millis of the latest flush with any bytes, the creation before
+    last_active_ms: AtomicU64,
+    close_sender: watch::Sender<bool>,
+    /// the captures of the rtp and rtcp packets of the session
+    capture: CaptureTap,
+}
+
+impl SessionEntry {
+    fn info(&self) -> SessionInfo {
+        let state = self.state.lock().unwrap();
+        SessionInfo {
+            id: self.id,
+            protocol: self.protocol,
+            peer_addr: self.peer_addr,
+            role: state.role,
+            stream_keys: state.stream_keys.clone(),
+            created_at: self.created_at,
+            last_active: UNIX_EPOCH
+                + Duration::from_millis(self.last_active_ms.load(Ordering::Relaxed)),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            close_requested: *self.close_sender.borrow(),
+        }
+    }
+}
+
+/// a snapshot of a registered session, the counters lag behind by up to the flush interval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    pub protocol: SessionProtocol,
+    pub peer_addr: SocketAddr,
+    pub role: SessionRole,
+    pub stream_keys: Vec<String>,
+    pub created_at: SystemTime,
+    pub last_active: SystemTime,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub close_requested: bool,
+}
+
+/// the sessions of all the servers, shared by them and the admin api.
+/// a session touches the map only when it registers and when its handle is dropped
+#[derive(Debug, Clone, Default)]
+pub struct SessionRegistry {
+    sessions: Arc<DashMap<Uuid, Arc<SessionEntry>>>,
+    /// the latest drain of the server, seen by the sessions registered before it
+    drain_sender: Arc<watch::Sender<Option<DrainRequest>>>,
+}
+
+impl SessionRegistry {
+    /// the session stays in the registry until the handle is dropped
+    pub fn register(&self, protocol: SessionProtocol, peer_addr: SocketAddr) -> SessionHandle {
+        let created_at = SystemTime::now();
+        let (close_sender, close_receiver) = watch::channel(false);
+        let entry = Arc::new(SessionEntry {
+            id: Uuid::now_v7(),
+            protocol,
+            peer_addr,
+            created_at,
+            state: Mutex::new(SessionState::default()),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            last_active_ms: AtomicU64::new(unix_millis(created_at)),
+            close_sender,
+            capture: CaptureTap::default(),
+        });
+        self.sessions.insert(entry.id, entry.clone());
+        tracing::debug!(
+            "session registered, id: {}, protocol: {}, peer addr: {}",
+            entry.id,
+            protocol,
+            peer_addr
+        );
+        SessionHandle {
+            registry: self.clone(),
+            entry,
+            close_receiver,
+            drain_receiver: self.drain_sender.subscribe(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// the sessions of a protocol, e.g., the load of its server
+    pub fn count(&self, protocol: SessionProtocol) -> usize {
+        self.sessions
+            .iter()
+            .filter(|entry| entry.protocol == protocol)
+            .count()
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<SessionInfo> {
+        self.sessions.get(id).map(|entry| entry.info())
+    }
+
+    /// the most recently active first, the least recently active ones are cut by `limit`
+    pub fn list(&self, limit: Option<usize>) -> Vec<SessionInfo> {
+        let mut sessions: Vec<_> = self.sessions.iter().map(|entry| entry.info()).collect();
+        sessions.sort_by(|a, b| {
+            b.last_active
+                .cmp(&a.last_active)
+                .then_with(|| b.created_at.cmp(&a.created_at))
+        });
+        if let Some(limit) = limit {
+            sessions.truncate(limit);
+        }
+        sessions
+    }
+
+    /// asks the session to close the way its protocol does, false if it is not registered
+    pub fn close(&self, id: &Uuid) -> bool {
+        match self.sessions.get(id) {
+            Some(entry) => {
+                tracing::info!(
+                    "closing session, id: {}, protocol: {}, peer addr: {}",
+                    entry.id,
+                    entry.protocol,
+                    entry.peer_addr
+                );
+                entry.close_sender.send_replace(true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// asks the sessions registered by now to move to the target of the request,
+    /// the ways of the protocols that can tell their clients so, e.g., rtsp REDIRECT
+    pub fn drain(&self, request: DrainRequest) {
+        tracing::info!(
+            "draining {} sessions to {}",
+            self.sessions.len(),
+            request.target
+        );
+        self.drain_sender.send_replace(Some(request));
+    }
+}
+
+/// held by a session while it is served, removes it from the registry once dropped
+#[derive(Debug)]
+pub struct SessionHandle {
+    registry: SessionRegistry,
+    entry: Arc<SessionEntry>,
+    close_receiver: watch::Receiver<bool>,
+    drain_receiver: watch::Receiver<Option<DrainRequest>>,
+}
+
+impl SessionHandle {
+    pub fn id(&self) -> Uuid {
+        self.entry.id
+    }
+
+    /// records the role the session took and the stream it took it on
+    pub fn set_role(&self, role: SessionRole, stream_key: &str) {
+        let mut state = self.entry.state.lock().unwrap();
+        state.role = role;
+        if !state.stream_keys.iter().any(|key| key == stream_key) {
+            state.stream_keys.push(stream_key.to_owned());
+        }
+    }
+
+    /// counts the bytes of the session, e.g. wrapped around its io
+    pub fn counters(&self) -> SessionCounters {
+        SessionCounters {
+            entry: self.entry.clone(),
+            bytes_in: 0,
+            bytes_out: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// records the packets of the rtp sessions of the session while the admin api captures them
+    pub fn capture_tap(&self) -> CaptureTap {
+        self.entry.capture.clone()
+    }
+
+    pub fn is_close_requested(&self) -> bool {
+        *self.close_receiver.borrow()
+    }
+
+    /// resolves once the session is asked to close, cancel safe
+    pub async fn close_requested(&mut self) {
+        // the sender is kept by the entry, so waiting never fails
+        let _ = self.close_receiver.wait_for(|close| *close).await;
+    }
+
+    /// resolves with each drain requested after the session registered, cancel safe
+    pub async fn drain_requested(&mut self) -> DrainRequest {
+        Self::next_drain(&mut self.drain_receiver).await
+    }
+
+    async fn next_drain(
+        drain_receiver: &mut watch::Receiver<Option<DrainRequest>>,
+    ) -> DrainRequest {
+        loop {
+            // the sender is kept by the registry of the handle, so waiting never fails
+            let _ = drain_receiver.changed().await;
+            if let Some(request) = drain_receiver.borrow_and_update().clone() {
+                return request;
+            }
+        }
+    }
+
+    /// resolves once the session is asked to close or to drain, whichever comes first, cancel safe
+    pub async fn close_or_drain_requested(&mut self) -> Option<DrainRequest> {
+        tokio::select! {
+            _ = self.close_receiver.wait_for(|close| *close) => None,
+            request = Self::next_drain(&mut self.drain_receiver) => Some(request),
+        }
+    }
+}
+
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        self.registry.sessions.remove(&self.entry.id);
+        tracing::debug!("session unregistered, id: {}", self.entry.id);
+    }
+}
+
+/// counts bytes locally and adds them to the entry of the session at most once per flush interval,
+/// the rest is added when dropped
+#[derive(Debug)]
+pub struct SessionCounters {
+    entry: Arc<SessionEntry>,
+    bytes_in: u64,
+    bytes_out: u64,
+    last_flush: Instant,
+}
+
+impl SessionCounters {
+    pub fn on_received(&mut self, bytes: usize) {
+        self.bytes_in += bytes as u64;
+        self.flush_if_due();
+    }
+
+    pub fn on_sent(&mut self, bytes: usize) {
+        self.bytes_out += bytes as u64;
+        self.flush_if_due();
+    }
+
+    fn flush_if_due(&mut self) {
+        if self.last_flush.elapsed() >= COUNTER_FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.bytes_in == 0 && self.bytes_out == 0 {
+            return;
+        }
+        self.entry
+            .bytes_in
+            .fetch_add(std::mem::take(&mut self.bytes_in), Ordering::Relaxed);
+        self.entry
+            .bytes_out
+            .fetch_add(std::mem::take(&mut self.bytes_out), Ordering::Relaxed);
+        self.entry
+            .last_active_ms
+            .fetch_max(unix_millis(SystemTime::now()), Ordering::Relaxed);
+    }
+}
+
+impl Drop for SessionCounters {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        time::Duration,
+    };
+
+    use stream_center::drain::DrainRequest;
+
+    use super::{SessionProtocol, SessionRegistry, SessionRole};
+
+    fn peer(port: u16) -> SocketAddr {
+        (Ipv4Addr::LOCALHOST, port).into()
+    }
+
+    #[test]
+    fn sessions_are_removed_once_their_handles_drop() {
+        let registry = SessionRegistry::default();
+        let rtmp = registry.register(SessionProtocol::Rtmp, peer(1000));
+        let rtsp = registry.register(SessionProtocol::Rtsp, peer(1001));
+        assert_eq!(registry.len(), 2);
+
+        rtmp.set_role(SessionRole::Publisher, "live/test");
+        rtmp.set_role(SessionRole::Publisher, "live/test");
+        let info = registry.get(&rtmp.id()).unwrap();
+        assert_eq!(info.protocol, SessionProtocol::Rtmp);
+        assert_eq!(info.peer_addr, peer(1000));
+        assert_eq!(info.role, SessionRole::Publisher);
+        assert_eq!(info.stream_keys, vec!["live/test".to_owned()]);
+
+        let rtsp_id = rtsp.id();
+        drop(rtsp);
+        assert!(registry.get(&rtsp_id).is_none());
+        assert!(!registry.close(&rtsp_id));
+        drop(rtmp);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn counters_are_flushed_on_drop_and_active_sessions_are_listed_first() {
+        let registry = SessionRegistry::default();
+        let idle = registry.register(SessionProtocol::Rtmp, peer(1000));
+        let busy = registry.register(SessionProtocol::HttpFlv, peer(1001));
+
+        let mut counters = busy.counters();
+        counters.on_received(100);
+        counters.on_sent(1000);
+        counters.on_sent(24);
+        // less than the flush interval passed
+        assert_eq!(registry.get(&busy.id()).unwrap().bytes_out, 0);
+        drop(counters);
+        let info = registry.get(&busy.id()).unwrap();
+        assert_eq!((info.bytes_in, info.bytes_out), (100, 1024));
+
+        let listed: Vec<_> = registry.list(None).iter().map(|info| info.id).collect();
+        assert_eq!(listed, vec![busy.id(), idle.id()]);
+        let listed: Vec<_> = registry.list(Some(1)).iter().map(|info| info.id).collect();
+        assert_eq!(listed, vec![busy.id()]);
+    }
+
+    #[tokio::test]
+    async fn close_wakes_the_session() {
+        let registry = SessionRegistry::default();
+        let mut handle = registry.register(SessionProtocol::Rtsp, peer(1000));
+        assert!(!handle.is_close_requested());
+
+        let id = handle.id();
+        let waiting = tokio::spawn(async move {
+            handle.close_requested().await;
+            handle
+        });
+        assert!(registry.close(&id));
+        let handle = waiting.await.unwrap();
+        assert!(handle.is_close_requested());
+        assert!(registry.get(&id).unwrap().close_requested);
+    }
+
+    #[tokio::test]
+    async fn drain_wakes_the_sessions_registered_before_it() {
+        let registry = SessionRegistry::default();
+        let mut rtsp = registry.register(SessionProtocol::Rtsp, peer(1000));
+        let _rtmp = registry.register(SessionProtocol::Rtmp, peer(1001));
+        assert_eq!(registry.count(SessionProtocol::Rtsp), 1);
+        assert_eq!(registry.count(SessionProtocol::HttpFlv), 0);
+
+        let request = DrainRequest {
+            target: "sibling:554".to_owned(),
+            description: None,
+            grace: Duration::from_secs(10),
+        };
+        registry.drain(request.clone());
+        let drained = tokio::time::timeout(Duration::from_secs(1), rtsp.drain_requested())
+            .await
+            .unwrap();
+        assert_eq!(drained, request);
+
+        let mut late = registry.register(SessionProtocol::Rtsp, peer(1002));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), late.drain_requested())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,14 +1,14 @@
 use std::{collections::HashMap, fmt::Debug};
 
 use errors::StreamPropertiesError;
+use stream_center::identity::StreamIdentity;
 use url::Url;
+use utils::media_url::MediaUrl;
 
 pub mod errors;
 
-#[derive(Default)]
 pub struct StreamProperties {
-    pub stream_name: String,
-    pub app: String,
+    pub stream_id: StreamIdentity,
     pub stream_context: HashMap<String, String>,
 }
 
@@ -16,41 +16,35 @@ impl Debug for StreamProperties {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "app={}, stream_name={}, stream_context={:?}",
-            self.app, self.stream_name, self.stream_context
+            "stream={}, stream_context={:?}",
+            self.stream_id, self.stream_context
         )
     }
 }
 
-impl TryFrom<&Url> for StreamProperties {
+/// the app and the stream are percent-decoded and validated, the query goes to the context,
+/// the same for the urls of all protocols
+impl TryFrom<&MediaUrl> for StreamProperties {
     type Error = StreamPropertiesError;
-    fn try_from(value: &Url) -> Result<Self, Self::Error> {
-        let path_segs = value.path_segments();
-        if path_segs.is_none() {
-            return Err(StreamPropertiesError::ParseFromUrlFailed(format!(
-                "invalid url: {}",
-                value
-            )));
-        }
-
-        let path_segs = path_segs.unwrap();
-        let path_seg_vec: Vec<_> = path_segs.collect();
-        if path_seg_vec.len() < 2 {
-            return Err(StreamPropertiesError::ParseFromUrlFailed(format!(
-                "invalid url: {}",
-                value
-            )));
-        }
-        let app = path_seg_vec[0].to_owned();
-        let stream_name = path_seg_vec[1].to_owned();
-        let mut context = HashMap::new();
-        for (k, v) in value.query_pairs() {
-            context.insert(k.to_string(), v.to_string());
-        }
+    fn try_from(value: &MediaUrl) -> Result<Self, Self::Error> {
+        let stream_id = StreamIdentity::from_media_url(value)
+            .map_err(|err| StreamPropertiesError::ParseFromUrlFailed(err.to_string()))?;
         Ok(Self {
-            stream_name,
-            app,
-            stream_context: context,
+            stream_id,
+            stream_context: value
+                .query()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
         })
     }
 }
+
+impl TryFrom<&Url> for StreamProperties {
+    type Error = StreamPropertiesError;
+    fn try_from(value: &Url) -> Result<Self, Self::Error> {
+        let url = MediaUrl::parse(value.as_str())
+            .map_err(|err| StreamPropertiesError::ParseFromUrlFailed(err.to_string()))?;
+        Self::try_from(&url)
+    }
+}
@@ -0,0 +1,332 @@
+//! runs the long lived tasks of the servers and the stream center under watch,
+//! a task panicking or failing is logged and run again after a backoff.
+//! each run is a tokio task of its own, so a crash takes nothing else down with it
+
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use tokio::task::JoinHandle;
+
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+pub const DEFAULT_STABLE_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// the wait before the first restart, doubled for each crash in a row
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// a run lasting this long is not a crash in a row, the backoff starts over after it
+    pub stable_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            stable_after: DEFAULT_STABLE_AFTER,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// the wait after `crashes_in_a_row` crashes, 1 for the first one
+    pub fn backoff(&self, crashes_in_a_row: u32) -> Duration {
+        let doublings = crashes_in_a_row.saturating_sub(1).min(31);
+        self.initial_backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff)
+    }
+}
+
+/// how a supervised task has fared so far
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskHealth {
+    pub name: String,
+    /// false while it waits to be restarted, or once it exits for good
+    pub running: bool,
+    /// the times it was run again after a crash
+    pub restarts: u64,
+    pub last_crash: Option<SystemTime>,
+    /// the panic message or the error of the last crash
+    pub last_crash_reason: Option<String>,
+}
+
+impl TaskHealth {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            running: false,
+            restarts: 0,
+            last_crash: None,
+            last_crash_reason: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SupervisedTask {
+    health: TaskHealth,
+    #[cfg(any(test, feature = "fault-injection"))]
+    crash: Arc<tokio::sync::Notify>,
+}
+
+/// the tasks run under watch, shared with the stats api
+#[derive(Debug, Clone, Default)]
+pub struct SupervisedTasks {
+    tasks: Arc<Mutex<BTreeMap<String, SupervisedTask>>>,
+}
+
+/// aborts the task once dropped, a run is aborted along with the supervisor awaiting it, e.g., on shutdown,
+/// and the helpers a run spawns go with the run
+pub struct AbortOnDrop<T>(pub JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl SupervisedTasks {
+    /// the tasks ordered by name
+    pub fn list(&self) -> Vec<TaskHealth> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .values()
+            .map(|task| task.health.clone())
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<TaskHealth> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|task| task.health.clone())
+    }
+
+    fn update(&self, name: &str, update: impl FnOnce(&mut TaskHealth)) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let task = tasks
+            .entry(name.to_owned())
+            .or_insert_with(|| SupervisedTask {
+                health: TaskHealth::new(name),
+                #[cfg(any(test, feature = "fault-injection"))]
+                crash: Default::default(),
+            });
+        update(&mut task.health);
+    }
+
+    /// runs what `make` makes until a run returns Ok or the supervisor is dropped.
+    /// a run panicking or returning an error is made and run again after the backoff of the policy,
+    /// whatever it shares with the other tasks, the stream center for one, is left as it is
+    pub async fn supervise<F, Fut, E>(self, name: &str, policy: RestartPolicy, mut make: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Debug + Send + 'static,
+    {
+        let mut crashes_in_a_row = 0;
+        loop {
+            self.update(name, |health| health.running = true);
+            let started = Instant::now();
+            let mut run = AbortOnDrop(tokio::spawn(self.crashable(name, make())));
+            let outcome = (&mut run.0).await;
+            self.update(name, |health| health.running = false);
+            let reason = match outcome {
+                Ok(Ok(())) => {
+                    tracing::info!("supervised task {} exits", name);
+                    return;
+                }
+                Ok(Err(err)) => format!("failed: {:?}", err),
+                Err(err) if err.is_panic() => {
+                    format!("panicked: {}", panic_message(err.into_panic()))
+                }
+                // aborted from elsewhere, not a crash
+                Err(_) => return,
+            };
+            if started.elapsed() >= policy.stable_after {
+                crashes_in_a_row = 0;
+            }
+            crashes_in_a_row += 1;
+            let backoff = policy.backoff(crashes_in_a_row);
+            tracing::error!(
+                "supervised task {} {}, run again in {:?}",
+                name,
+                reason,
+                backoff
+            );
+            self.update(name, |health| {
+                health.last_crash = Some(SystemTime::now());
+                health.last_crash_reason = Some(reason);
+            });
+            tokio::time::sleep(backoff).await;
+            self.update(name, |health| health.restarts += 1);
+        }
+    }
+
+    #[cfg(not(any(test, feature = "fault-injection")))]
+    fn crashable<Fut>(&self, _name: &str, run: Fut) -> Fut {
+        run
+    }
+
+    /// the run panics once a crash is injected
+    #[cfg(any(test, feature = "fault-injection"))]
+    fn crashable<Fut, E>(
+        &self,
+        name: &str,
+        run: Fut,
+    ) -> impl Future<Output = Result<(), E>> + use<Fut, E>
+    where
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Send + 'static,
+    {
+        self.update(name, |_| {});
+        let crash = self.tasks.lock().unwrap()[name].crash.clone();
+        let name = name.to_owned();
+        async move {
+            tokio::select! {
+                result = run => result,
+                _ = crash.notified() => panic!("crash injected into {}", name),
+            }
+        }
+    }
+
+    /// makes the current run of the task panic, false if there is no such task
+    #[cfg(any(test, feature = "fault-injection"))]
+    pub fn inject_panic(&self, name: &str) -> bool {
+        match self.tasks.lock().unwrap().get(name) {
+            Some(task) => {
+                task.crash.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic payload".to_owned(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicU32, Ordering},
+        },
+        time::Duration,
+    };
+
+    use super::{RestartPolicy, SupervisedTasks, TaskHealth};
+
+    const QUICK: RestartPolicy = RestartPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(40),
+        stable_after: Duration::from_secs(60),
+    };
+
+    /// the health of the task once it is as expected, polled as the supervisor runs along
+    async fn wait_for(
+        tasks: &SupervisedTasks,
+        name: &str,
+        expected: impl Fn(&TaskHealth) -> bool,
+    ) -> TaskHealth {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match tasks.get(name) {
+                    Some(health) if expected(&health) => return health,
+                    _ => tokio::time::sleep(Duration::from_millis(1)).await,
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("task {} never got as expected: {:?}", name, tasks.get(name)))
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let backoffs: Vec<_> = (1..=5)
+            .map(|crashes| QUICK.backoff(crashes).as_millis())
+            .collect();
+        assert_eq!(backoffs, vec![10, 20, 40, 40, 40]);
+        assert_eq!(
+            RestartPolicy::default().backoff(u32::MAX),
+            RestartPolicy::default().max_backoff
+        );
+    }
+
+    #[tokio::test]
+    async fn panicking_and_failing_runs_are_run_again() {
+        let tasks = SupervisedTasks::default();
+        let runs = Arc::new(AtomicU32::new(0));
+        let counted = runs.clone();
+        tasks
+            .clone()
+            .supervise("flaky", QUICK, move || {
+                let run = counted.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match run {
+                        0 => panic!("malformed handshake"),
+                        1 => Err("listener gone"),
+                        _ => Ok(()),
+                    }
+                }
+            })
+            .await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let health = tasks.get("flaky").unwrap();
+        assert_eq!(health.restarts, 2);
+        assert!(!health.running);
+        assert!(health.last_crash.is_some());
+        assert_eq!(
+            health.last_crash_reason.as_deref(),
+            Some("failed: \"listener gone\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn injected_crashes_restart_the_task_only() {
+        let tasks = SupervisedTasks::default();
+        let runs = Arc::new(AtomicU32::new(0));
+        let counted = runs.clone();
+        let supervisor = tokio::spawn(tasks.clone().supervise("server", QUICK, move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            std::future::pending::<Result<(), ()>>()
+        }));
+        wait_for(&tasks, "server", |health| health.running).await;
+        assert!(tasks.inject_panic("server"));
+        assert!(!tasks.inject_panic("elsewhere"));
+
+        let health = wait_for(&tasks, "server", |health| {
+            health.running && health.restarts == 1
+        })
+        .await;
+        assert!(health.running);
+        assert_eq!(health.restarts, 1);
+        assert_eq!(
+            health.last_crash_reason.as_deref(),
+            Some("panicked: crash injected into server")
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        // the run goes with its supervisor
+        supervisor.abort();
+        let _ = supervisor.await;
+        assert_eq!(tasks.list().len(), 1);
+    }
+}
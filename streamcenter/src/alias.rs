@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use crate::{errors::StreamCenterError, identity::StreamIdentity};
+
+/// other names a stream plays under, e.g., `live/court-a` for `live/event123`.
+/// a subscription to an alias plays the canonical stream it points to at the time,
+/// publishing to an alias is refused. aliases do not chain
+#[derive(Debug, Default, Clone)]
+pub struct StreamAliases(HashMap<StreamIdentity, StreamIdentity>);
+
+impl StreamAliases {
+    /// the canonical stream if `stream_id` is an alias
+    pub fn resolve(&self, stream_id: &StreamIdentity) -> Option<&StreamIdentity> {
+        self.0.get(stream_id)
+    }
+
+    /// points the alias to the canonical stream, returns the one it pointed to before
+    pub fn set(
+        &mut self,
+        alias: StreamIdentity,
+        canonical: StreamIdentity,
+    ) -> Result<Option<StreamIdentity>, StreamCenterError> {
+        if alias == canonical {
+            return Err(StreamCenterError::InvalidAlias(format!(
+                "{} points to itself",
+                alias
+            )));
+        }
+        if let Some(target) = self.0.get(&canonical) {
+            return Err(StreamCenterError::InvalidAlias(format!(
+                "{} is an alias of {} itself",
+                canonical, target
+            )));
+        }
+        if let Some((other, _)) = self.0.iter().find(|(_, target)| **target == alias) {
+            return Err(StreamCenterError::InvalidAlias(format!(
+                "{} is the stream of alias {}",
+                alias, other
+            )));
+        }
+        Ok(self.0.insert(alias, canonical))
+    }
+
+    /// returns the canonical stream the alias pointed to
+    pub fn remove(&mut self, alias: &StreamIdentity) -> Option<StreamIdentity> {
+        self.0.remove(alias)
+    }
+
+    /// pairs of alias and canonical stream, ordered by alias
+    pub fn to_vec(&self) -> Vec<(StreamIdentity, StreamIdentity)> {
+        let mut aliases: Vec<_> = self
+            .0
+            .iter()
+            .map(|(alias, canonical)| (alias.clone(), canonical.clone()))
+            .collect();
+        aliases.sort_by_key(|(alias, _)| alias.to_string());
+        aliases
+    }
+}
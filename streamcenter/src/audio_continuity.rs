@@ -0,0 +1,195 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use codec_common::audio::AudioConfig;
+use serde::Serialize;
+use tokio_util::bytes::Bytes;
+
+use crate::{errors::StreamCenterError, gop::MediaFrame};
+
+/// a hole longer than this is a pause of the publisher or a jump of its clock, it is left as it is
+pub const MAX_CONCEALED_GAP: Duration = Duration::from_secs(5);
+
+/// a stretched frame lasts up to this part of a frame longer, until the timeline caught up
+const STRETCH_DIVISOR: u64 = 4;
+
+/// how the holes the publisher leaves in the audio of a stream are concealed.
+/// a hole is a frame coming more than 1.5 frame durations after the one before, on the same track,
+/// the frame duration is taken from the aac config of the track. frames are never reordered
+/// and the video is left as it is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioGapConcealment {
+    /// the audio is sent with its holes
+    #[default]
+    Off,
+    /// the hole is filled with silent frames of the config of the track
+    Silence,
+    /// the frames after the hole are pulled back to follow on, and then spaced up to a quarter of
+    /// a frame wider until they are back on the timestamps of the publisher
+    Stretch,
+}
+
+/// parses `off`, `silence` or `stretch`
+impl FromStr for AudioGapConcealment {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(Self::Off),
+            "silence" => Ok(Self::Silence),
+            "stretch" => Ok(Self::Stretch),
+            other => Err(StreamCenterError::InvalidAudioGapConcealment(
+                other.to_owned(),
+            )),
+        }
+    }
+}
+
+/// the holes concealed in the audio of a stream so far, of all the tracks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct AudioConcealmentStats {
+    pub gaps: u64,
+    /// the silent frames put in the holes
+    pub silence_frames: u64,
+    /// the total duration of the holes
+    pub concealed_nano: u64,
+}
+
+#[derive(Debug, Default)]
+struct TrackContinuity {
+    /// from the aac config of the track, None if it tells no frame length
+    frame_duration_nano: Option<u64>,
+    /// made once per config, None if the config has no silence
+    silence: Option<Bytes>,
+    /// the dts the publisher sent the last frame with
+    last_dts_nano: Option<u64>,
+    /// how far the stretched timestamps are behind the ones of the publisher
+    lag_nano: u64,
+}
+
+impl TrackContinuity {
+    fn configure(&mut self, config: &AudioConfig) {
+        let AudioConfig::AAC(config) = config;
+        self.frame_duration_nano = config.frame_duration_nano();
+        self.silence = match codec_aac::silence::silent_raw_data_block(config) {
+            Ok(silence) => Some(Bytes::from(silence)),
+            Err(err) => {
+                tracing::warn!("audio holes are not filled with silence: {}", err);
+                None
+            }
+        };
+    }
+}
+
+/// conceals the holes in the audio of a stream as the policy says
+#[derive(Debug, Default)]
+pub(crate) struct AudioContinuity {
+    concealment: AudioGapConcealment,
+    tracks: HashMap<u8, TrackContinuity>,
+    stats: AudioConcealmentStats,
+}
+
+impl AudioContinuity {
+    pub(crate) fn new(concealment: AudioGapConcealment) -> Self {
+        Self {
+            concealment,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn stats(&self) -> AudioConcealmentStats {
+        self.stats
+    }
+
+    /// notes the audio configs, and stretches the timestamp of the frame if it follows a hole.
+    /// the silent frames filling the hole before the frame are returned, in dts order
+    pub(crate) fn conceal(&mut self, frame: &mut MediaFrame) -> Vec<MediaFrame> {
+        if self.concealment == AudioGapConcealment::Off {
+            return Vec::new();
+        }
+        let (frame_info, payload) = match frame {
+            MediaFrame::AudioConfig {
+                config, track_id, ..
+            } => {
+                self.tracks.entry(*track_id).or_default().configure(config);
+                return Vec::new();
+            }
+            MediaFrame::Audio {
+                frame_info,
+                payload,
+            } => (frame_info, payload),
+            _ => return Vec::new(),
+        };
+        let track = self.tracks.entry(frame_info.track_id).or_default();
+        let dts_nano = frame_info.timestamp_nano;
+        let last_dts_nano = track.last_dts_nano.replace(dts_nano);
+        let (Some(frame_duration_nano), Some(last_dts_nano)) =
+            (track.frame_duration_nano, last_dts_nano)
+        else {
+            return Vec::new();
+        };
+        if payload.is_empty() || dts_nano <= last_dts_nano {
+            // a clock going back starts the timeline over
+            track.lag_nano = 0;
+            return Vec::new();
+        }
+
+        let expected_nano = last_dts_nano + frame_duration_nano;
+        let gap_nano = dts_nano.saturating_sub(expected_nano);
+        let is_hole =
+            gap_nano * 2 > frame_duration_nano && gap_nano <= MAX_CONCEALED_GAP.as_nanos() as u64;
+        if gap_nano > MAX_CONCEALED_GAP.as_nanos() as u64 {
+            tracing::info!(
+                "audio of track {} jumps {}ms, not concealed",
+                frame_info.track_id,
+                gap_nano / 1_000_000
+            );
+        }
+
+        let mut silence_frames = Vec::new();
+        match self.concealment {
+            AudioGapConcealment::Silence if is_hole => {
+                let Some(silence) = &track.silence else {
+                    return Vec::new();
+                };
+                // the hole is filled to the frame, the rest of it is jitter
+                let count = (gap_nano + frame_duration_nano / 2) / frame_duration_nano;
+                for index in 0..count {
+                    let mut silence_info = frame_info.clone();
+                    silence_info.timestamp_nano = expected_nano + index * frame_duration_nano;
+                    silence_info.ingest_time = None;
+                    #[cfg(feature = "frame-crc")]
+                    {
+                        silence_info.payload_crc = None;
+                    }
+                    silence_frames.push(MediaFrame::Audio {
+                        frame_info: silence_info,
+                        payload: silence.clone(),
+                    });
+                }
+                self.stats.silence_frames += count;
+                self.stats.concealed_nano += count * frame_duration_nano;
+            }
+            AudioGapConcealment::Stretch => {
+                if is_hole {
+                    track.lag_nano += gap_nano;
+                    self.stats.concealed_nano += gap_nano;
+                } else {
+                    track.lag_nano = track
+                        .lag_nano
+                        .saturating_sub(frame_duration_nano / STRETCH_DIVISOR);
+                }
+                frame_info.timestamp_nano = dts_nano - track.lag_nano.min(dts_nano);
+            }
+            _ => {}
+        }
+        if is_hole {
+            self.stats.gaps += 1;
+            tracing::debug!(
+                "audio hole of {}ms on track {} concealed, {}ms concealed in total",
+                gap_nano / 1_000_000,
+                frame_info.track_id,
+                self.stats.concealed_nano / 1_000_000
+            );
+        }
+        silence_frames
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::{BTreeMap, HashMap};
+
+use codec_common::audio::AudioCodecCommon;
+use flv_formats::tag::on_meta_data::OnMetaData;
+use serde::Serialize;
+
+use crate::gop::MediaFrame;
+
+/// the audio track a subscriber gets if it does not pick one
+pub const DEFAULT_AUDIO_TRACK: u8 = 0;
+
+/// the fields of an audioTrackIdInfoMap entry tried for the label of a track, in order
+const LABEL_FIELDS: [&str; 3] = ["name", "title", "language"];
+
+/// an audio track the publisher sent, e.g., one language of a multitrack enhanced rtmp stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AudioTrack {
+    pub id: u8,
+    pub codec: AudioCodecCommon,
+    /// from the audioTrackIdInfoMap of onMetaData, if the publisher labeled the track
+    pub label: Option<String>,
+    /// from the multichannel config of the track, if the publisher sent one
+    pub channels: Option<u8>,
+}
+
+/// the audio tracks seen in a stream so far
+#[derive(Debug, Clone, Default)]
+pub struct AudioTracks {
+    codecs: BTreeMap<u8, AudioCodecCommon>,
+    labels: HashMap<u8, String>,
+    channels: HashMap<u8, u8>,
+}
+
+impl AudioTracks {
+    pub fn on_frame(&mut self, frame: &MediaFrame) {
+        match frame {
+            MediaFrame::Audio { frame_info, .. } => {
+                self.codecs.insert(frame_info.track_id, frame_info.codec_id);
+            }
+            MediaFrame::AudioConfig {
+                config, track_id, ..
+            } => {
+                self.codecs.insert(*track_id, config.as_ref().into());
+            }
+            MediaFrame::AudioChannelConfig {
+                codec_id,
+                config,
+                track_id,
+                ..
+            } => {
+                self.codecs.insert(*track_id, *codec_id);
+                self.channels.insert(*track_id, config.channel_count);
+            }
+            MediaFrame::Script { on_meta_data, .. } => {
+                if let Some(on_meta_data) = on_meta_data.as_ref() {
+                    self.label_with(on_meta_data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// the keys of audioTrackIdInfoMap are the track ids, the entries without a usable label are skipped
+    fn label_with(&mut self, on_meta_data: &OnMetaData) {
+        let Some(info_map) = &on_meta_data.audio_track_id_info_map else {
+            return;
+        };
+        for (key, value) in info_map {
+            let Ok(track_id) = key.parse::<u8>() else {
+                continue;
+            };
+            let Ok(fields) = value.clone().try_into_pairs() else {
+                continue;
+            };
+            let fields: HashMap<String, amf_formats::Value> = fields.collect();
+            let label = LABEL_FIELDS.iter().find_map(|field| {
+                fields
+                    .get(*field)
+                    .and_then(|value| value.try_as_str())
+                    .filter(|label| !label.is_empty())
+            });
+            if let Some(label) = label {
+                self.labels.insert(track_id, label.to_owned());
+            }
+        }
+    }
+
+    /// ordered by track id, only the tracks that carried audio are listed
+    pub fn to_vec(&self) -> Vec<AudioTrack> {
+        self.codecs
+            .iter()
+            .map(|(id, codec)| AudioTrack {
+                id: *id,
+                codec: *codec,
+                label: self.labels.get(id).cloned(),
+                channels: self.channels.get(id).copied(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,246 @@
+use std::{str::FromStr, time::Duration};
+
+use tokio::{sync::mpsc, time::Instant};
+
+use crate::errors::StreamCenterError;
+
+/// the congestion of a stream is scored over windows of this long
+pub const BACKPRESSURE_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// an advisory suggests at least this share of the ingest bitrate
+const MIN_SUGGESTED_SHARE: f64 = 0.5;
+
+/// when the publishers of an app are told the server can not keep up with their streams
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BackpressurePolicy {
+    /// the frames the server can not keep up with are dropped silently
+    #[default]
+    Off,
+    /// the publisher is advised to lower its bitrate once the congestion score reaches `raise`,
+    /// and told the congestion is over once it falls to `clear`,
+    /// with `min_interval` at least between two advisories
+    On {
+        raise: f64,
+        clear: f64,
+        min_interval: Duration,
+    },
+}
+
+/// parses `off` or `<raise>:<clear>:<min interval seconds>`, e.g., `0.5:0.2:10`,
+/// the scores are between 0 and 1 and clear is below raise
+impl FromStr for BackpressurePolicy {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let policy = s.trim();
+        if policy == "off" {
+            return Ok(Self::Off);
+        }
+        let invalid = || StreamCenterError::InvalidBackpressurePolicy(policy.to_string());
+        let mut parts = policy.split(':').map(str::trim);
+        let (Some(raise), Some(clear), Some(min_interval), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let (Ok(raise), Ok(clear), Ok(min_interval)) = (
+            raise.parse::<f64>(),
+            clear.parse::<f64>(),
+            min_interval.parse::<u64>(),
+        ) else {
+            return Err(invalid());
+        };
+        if !(0.0 < clear && clear < raise && raise <= 1.0) {
+            return Err(invalid());
+        }
+        Ok(Self::On {
+            raise,
+            clear,
+            min_interval: Duration::from_secs(min_interval),
+        })
+    }
+}
+
+/// what the publisher of a congested stream is told
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackpressureAdvisory {
+    Congested {
+        /// 0 to 1
+        score: f64,
+        /// what the publisher sent over the last sample
+        ingest_kbps: u64,
+        suggested_kbps: u64,
+    },
+    /// the score fell back, the publisher may raise its bitrate again
+    Relieved { score: f64 },
+}
+
+/// what a stream went through over a sample, the score is the worst of the drop rate of the frames
+/// and the fill of the queues
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CongestionSample {
+    /// frames handed to the subscribers
+    pub sent: u64,
+    /// frames a subscriber or the mix queue had no room for
+    pub dropped: u64,
+    /// 0 to 1, of the fullest subscriber queue
+    pub subscriber_queue_fill: f64,
+    /// 0 to 1
+    pub mix_queue_fill: f64,
+    pub ingest_bytes: u64,
+    pub duration: Duration,
+}
+
+impl CongestionSample {
+    pub fn score(&self) -> f64 {
+        let drop_rate = match self.sent + self.dropped {
+            0 => 0.0,
+            total => self.dropped as f64 / total as f64,
+        };
+        drop_rate
+            .max(self.subscriber_queue_fill)
+            .max(self.mix_queue_fill)
+            .clamp(0.0, 1.0)
+    }
+
+    pub fn ingest_kbps(&self) -> u64 {
+        let secs = self.duration.as_secs_f64();
+        if secs == 0.0 {
+            return 0;
+        }
+        (self.ingest_bytes as f64 * 8.0 / 1000.0 / secs) as u64
+    }
+}
+
+/// turns the samples of a stream into advisories, with hysteresis between the raise and clear scores
+#[derive(Debug)]
+pub struct BackpressureAdvisor {
+    raise: f64,
+    clear: f64,
+    min_interval: Duration,
+    congested: bool,
+    last_advisory: Option<Instant>,
+}
+
+impl BackpressureAdvisor {
+    /// None if the policy is off
+    pub fn new(policy: BackpressurePolicy) -> Option<Self> {
+        let BackpressurePolicy::On {
+            raise,
+            clear,
+            min_interval,
+        } = policy
+        else {
+            return None;
+        };
+        Some(Self {
+            raise,
+            clear,
+            min_interval,
+            congested: false,
+            last_advisory: None,
+        })
+    }
+
+    pub fn is_congested(&self) -> bool {
+        self.congested
+    }
+
+    /// a congested stream is advised again every min_interval while the score stays at raise,
+    /// the bitrate suggested is cut by half the score
+    pub fn observe(
+        &mut self,
+        now: Instant,
+        sample: &CongestionSample,
+    ) -> Option<BackpressureAdvisory> {
+        if self
+            .last_advisory
+            .is_some_and(|last| now.duration_since(last) < self.min_interval)
+        {
+            return None;
+        }
+        let score = sample.score();
+        let advisory = if score >= self.raise {
+            self.congested = true;
+            let ingest_kbps = sample.ingest_kbps();
+            BackpressureAdvisory::Congested {
+                score,
+                ingest_kbps,
+                suggested_kbps: (ingest_kbps as f64 * (1.0 - score / 2.0).max(MIN_SUGGESTED_SHARE))
+                    as u64,
+            }
+        } else if self.congested && score <= self.clear {
+            self.congested = false;
+            BackpressureAdvisory::Relieved { score }
+        } else {
+            return None;
+        };
+        self.last_advisory = Some(now);
+        Some(advisory)
+    }
+}
+
+/// counts what a stream went through since the last sample and hands the advisories to the publisher
+#[derive(Debug)]
+pub(crate) struct BackpressureMonitor {
+    advisor: BackpressureAdvisor,
+    sender: mpsc::UnboundedSender<BackpressureAdvisory>,
+    sample_start: Instant,
+    sent: u64,
+    dropped: u64,
+    ingest_bytes: u64,
+}
+
+impl BackpressureMonitor {
+    /// None if the policy is off or the publisher listens to no advisories
+    pub(crate) fn new(
+        policy: BackpressurePolicy,
+        sender: Option<mpsc::UnboundedSender<BackpressureAdvisory>>,
+    ) -> Option<Self> {
+        Some(Self {
+            advisor: BackpressureAdvisor::new(policy)?,
+            sender: sender?,
+            sample_start: Instant::now(),
+            sent: 0,
+            dropped: 0,
+            ingest_bytes: 0,
+        })
+    }
+
+    pub(crate) fn count_in(&mut self, bytes: usize) {
+        self.ingest_bytes += bytes as u64;
+    }
+
+    pub(crate) fn count_sent(&mut self, dropped: bool) {
+        if dropped {
+            self.dropped += 1;
+        } else {
+            self.sent += 1;
+        }
+    }
+
+    /// the sample is taken once it is long enough, with the fills of the queues as they are now
+    pub(crate) fn sample(
+        &mut self,
+        subscriber_queue_fill: f64,
+        mix_queue_fill: f64,
+    ) -> Option<BackpressureAdvisory> {
+        let now = Instant::now();
+        let duration = now.duration_since(self.sample_start);
+        if duration < BACKPRESSURE_SAMPLE_INTERVAL {
+            return None;
+        }
+        let sample = CongestionSample {
+            sent: std::mem::take(&mut self.sent),
+            dropped: std::mem::take(&mut self.dropped),
+            subscriber_queue_fill,
+            mix_queue_fill,
+            ingest_bytes: std::mem::take(&mut self.ingest_bytes),
+            duration,
+        };
+        self.sample_start = now;
+        let advisory = self.advisor.observe(now, &sample)?;
+        if self.sender.send(advisory).is_err() {
+            tracing::debug!("the publisher is gone, drop the advisory {:?}", advisory);
+        }
+        Some(advisory)
+    }
+}
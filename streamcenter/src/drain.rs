@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::identity::StreamIdentity;
+
+/// asks the publishers of the server to move to another host, e.g., before it is stopped for a deploy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrainRequest {
+    /// `host[:port]` the publishers are asked to reconnect to
+    pub target: String,
+    /// told to the publishers along with the target
+    pub description: Option<String>,
+    /// the publishers still here after it are disconnected
+    pub grace: Duration,
+}
+
+/// the streams whose publishers are drained by a request,
+/// the publishers already drained by an earlier one are not counted
+#[derive(Debug, Clone)]
+pub struct DrainSummary {
+    pub drain_id: Uuid,
+    pub streams: Vec<StreamIdentity>,
+}
+
+/// how a drained publisher left the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrainOutcome {
+    /// the stream is published here again by a new session, which took it over
+    RepublishedLocally,
+    /// the publisher unpublished within the grace period, presumably to publish to the target
+    Left,
+    /// the publisher was still here when the grace period ended and got disconnected
+    Disconnected,
+}
+
+/// kept by the stream center for a drained publisher until it leaves
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DrainedPublisher {
+    pub(crate) drain_id: Uuid,
+    pub(crate) publisher_id: Uuid,
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::{errors::StreamCenterError, gop::MediaFrame};
+
+pub const DEFAULT_DVR_MAX_BYTES: u64 = 256 << 20;
+
+/// the subscribe context key of the seconds a timeshift subscriber plays behind live, e.g., `?delay=120`
+pub const DVR_DELAY_KEY: &str = "delay";
+/// the subscribe context key of the stream time in seconds a timeshift subscriber starts at,
+/// the rtsp server puts the npt start of PLAY there
+pub const DVR_START_KEY: &str = "dvr_start";
+
+/// the window may grow over its byte cap by this factor for the readers pinning it,
+/// the reader furthest behind is dropped beyond
+const PINNED_BYTES_FACTOR: u64 = 2;
+
+/// how much of a stream is kept for the players to rewind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DvrWindow {
+    /// the players only get the gop cache
+    #[default]
+    Off,
+    /// the frames of the last duration, of max_bytes payload bytes at most,
+    /// the window is trimmed a gop at a time from its oldest key frame
+    On { duration: Duration, max_bytes: u64 },
+}
+
+/// parses `off`, `<seconds>` or `<seconds>:<max bytes>`
+impl FromStr for DvrWindow {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let window = s.trim();
+        if window == "off" {
+            return Ok(Self::Off);
+        }
+        let invalid = || StreamCenterError::InvalidDvrWindow(window.to_string());
+        let (secs, max_bytes) = match window.split_once(':') {
+            Some((secs, max_bytes)) => (secs, max_bytes.trim().parse::<u64>().ok()),
+            None => (window, Some(DEFAULT_DVR_MAX_BYTES)),
+        };
+        let secs = secs.trim().parse::<u64>().ok().filter(|secs| *secs > 0);
+        match (secs, max_bytes.filter(|bytes| *bytes > 0)) {
+            (Some(secs), Some(max_bytes)) => Ok(Self::On {
+                duration: Duration::from_secs(secs),
+                max_bytes,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// where a timeshift subscriber starts in the dvr window of a stream,
+/// it is moved back to the key frame before, or to the oldest one if the window does not reach that far
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timeshift {
+    /// behind the latest frame of the stream
+    Delay(Duration),
+    /// at a decode timestamp of the stream
+    At(Duration),
+}
+
+impl Timeshift {
+    /// from the delay or the start in the subscribe context, None for live,
+    /// as are the seconds no duration holds
+    pub fn from_context(context: &HashMap<String, String>) -> Option<Self> {
+        let secs = |key: &str| {
+            context
+                .get(key)
+                .and_then(|secs| secs.trim().parse::<f64>().ok())
+                .filter(|secs| *secs > 0.0)
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        };
+        secs(DVR_DELAY_KEY)
+            .map(Self::Delay)
+            .or_else(|| secs(DVR_START_KEY).map(Self::At))
+    }
+}
+
+/// the frames of a stream over its dvr window, sequence headers included, in the order they went out.
+/// the frames share their payloads with the gop cache and the subscribers, the latest gop is not held twice.
+/// frames are told by a sequence number counting up from the first one the stream sent
+#[derive(Debug)]
+pub(crate) struct DvrBuffer {
+    duration: Duration,
+    max_bytes: u64,
+    frames: VecDeque<MediaFrame>,
+    /// the sequence number of the front frame
+    first_seq: u64,
+    /// the sequence numbers of the video key frames in the window
+    key_frames: VecDeque<u64>,
+    has_video: bool,
+    /// the latest sequence header of each kind and track trimmed off the window,
+    /// they are still in effect for the front frames
+    base_headers: BTreeMap<(u8, u8), MediaFrame>,
+    /// the payload bytes of the frames
+    bytes: u64,
+    latest_dts_nano: u64,
+}
+
+/// the order sequence headers go out to a new reader, the channel layout of a track after its config
+fn header_slot(frame: &MediaFrame) -> Option<(u8, u8)> {
+    match frame {
+        MediaFrame::Script { .. } => Some((0, 0)),
+        MediaFrame::VideoConfig { .. } => Some((1, 0)),
+        MediaFrame::AudioConfig { track_id, .. } => Some((2, *track_id)),
+        MediaFrame::AudioChannelConfig { track_id, .. } => Some((3, *track_id)),
+        _ => None,
+    }
+}
+
+impl DvrBuffer {
+    /// None if the window is off
+    pub(crate) fn new(window: DvrWindow) -> Option<Self> {
+        let DvrWindow::On {
+            duration,
+            max_bytes,
+        } = window
+        else {
+            return None;
+        };
+        Some(Self {
+            duration,
+            max_bytes,
+            frames: VecDeque::new(),
+            first_seq: 0,
+            key_frames: VecDeque::new(),
+            has_video: false,
+            base_headers: BTreeMap::new(),
+            bytes: 0,
+            latest_dts_nano: 0,
+        })
+    }
+
+    /// the sequence number the next frame gets
+    pub(crate) fn end_seq(&self) -> u64 {
+        self.first_seq + self.frames.len() as u64
+    }
+
+    pub(crate) fn get(&self, seq: u64) -> Option<&MediaFrame> {
+        self.frames.get(seq.checked_sub(self.first_seq)? as usize)
+    }
+
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub(crate) fn append(&mut self, frame: MediaFrame) {
+        if frame.is_control() {
+            return;
+        }
+        if frame.is_video_key_frame() {
+            self.key_frames.push_back(self.end_seq());
+        }
+        if (frame.is_video() || frame.is_audio()) && !frame.is_sequence_header() {
+            self.has_video |= frame.is_video();
+            self.latest_dts_nano = self.latest_dts_nano.max(frame.get_decode_timestamp_ns());
+        }
+        self.bytes += frame.payload_bytes() as u64;
+        self.frames.push_back(frame);
+    }
+
+    /// where a reader starts for the timeshift, None if it plays live.
+    /// a delay is cut to the duration of the window, a start past the latest frame plays live
+    pub(crate) fn start_of(&self, timeshift: Timeshift) -> Option<u64> {
+        let target_nano = match timeshift {
+            Timeshift::Delay(delay) => self
+                .latest_dts_nano
+                .saturating_sub(delay.min(self.duration).as_nanos() as u64),
+            Timeshift::At(at) => u64::try_from(at.as_nanos()).unwrap_or(u64::MAX),
+        };
+        if target_nano >= self.latest_dts_nano {
+            return None;
+        }
+        if !self.has_video {
+            // every audio frame is a place to start at
+            return self
+                .frames
+                .iter()
+                .position(|frame| {
+                    frame.is_audio()
+                        && !frame.is_sequence_header()
+                        && frame.get_decode_timestamp_ns() >= target_nano
+                })
+                .map(|index| self.first_seq + index as u64);
+        }
+        let dts_of = |seq: &u64| {
+            self.get(*seq)
+                .map_or(0, MediaFrame::get_decode_timestamp_ns)
+        };
+        self.key_frames
+            .iter()
+            .rev()
+            .find(|seq| dts_of(seq) <= target_nano)
+            .or(self.key_frames.front())
+            .copied()
+    }
+
+    /// the sequence headers in effect at the frame, the latest of each kind and track before it
+    pub(crate) fn headers_at(&self, seq: u64) -> Vec<MediaFrame> {
+        let mut headers = self.base_headers.clone();
+        let count = seq.saturating_sub(self.first_seq) as usize;
+        for frame in self.frames.iter().take(count) {
+            if let Some(slot) = header_slot(frame) {
+                headers.insert(slot, frame.clone());
+            }
+        }
+        headers.into_values().collect()
+    }
+
+    fn front_dts_nano(&self) -> Option<u64> {
+        self.frames
+            .iter()
+            .find(|frame| (frame.is_video() || frame.is_audio()) && !frame.is_sequence_header())
+            .map(MediaFrame::get_decode_timestamp_ns)
+    }
+
+    fn is_over(&self) -> bool {
+        self.bytes > self.max_bytes
+            || self.front_dts_nano().is_some_and(|front| {
+                self.latest_dts_nano.saturating_sub(front) > self.duration.as_nanos() as u64
+            })
+    }
+
+    /// where the oldest gop ends, None if the window holds the latest gop only
+    fn front_boundary(&self) -> Option<u64> {
+        if !self.has_video {
+            return (!self.frames.is_empty()).then_some(self.first_seq + 1);
+        }
+        self.key_frames
+            .iter()
+            .find(|seq| **seq > self.first_seq)
+            .copied()
+    }
+
+    /// trims the oldest gops off the window while it is over its duration or byte cap,
+    /// the frames from the pinned one on are kept for the reader at it
+    pub(crate) fn trim(&mut self, pinned: Option<u64>) {
+        while self.is_over() {
+            let Some(boundary) = self.front_boundary() else {
+                return;
+            };
+            if pinned.is_some_and(|pinned| pinned < boundary) {
+                return;
+            }
+            while self.first_seq < boundary {
+                let Some(frame) = self.frames.pop_front() else {
+                    return;
+                };
+                self.first_seq += 1;
+                self.bytes -= frame.payload_bytes() as u64;
+                if let Some(slot) = header_slot(&frame) {
+                    self.base_headers.insert(slot, frame);
+                }
+            }
+            while self
+                .key_frames
+                .front()
+                .is_some_and(|seq| *seq < self.first_seq)
+            {
+                self.key_frames.pop_front();
+            }
+        }
+    }
+
+    /// a reader pins the window far over its byte cap, it has to go
+    pub(crate) fn is_pinned_over(&self) -> bool {
+        self.bytes > self.max_bytes.saturating_mul(PINNED_BYTES_FACTOR)
+            && self.front_boundary().is_some()
+    }
+}
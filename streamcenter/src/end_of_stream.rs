@@ -0,0 +1,71 @@
+use std::fmt;
+
+use crate::{errors::StreamCenterError, takeover::KickReason};
+
+/// why the frames of a stream stop, told to the subscribers in-band after the last media frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndOfStreamReason {
+    /// the publisher unpublished or disconnected
+    Unpublished,
+    /// the stream center disconnected the publisher, the idle watchdog reaping it included
+    Kicked(KickReason),
+    /// the idle watchdog found the publisher silent and there is no backup to stand in,
+    /// the frames go on if the publisher resumes
+    Stalled,
+    /// the stream center is gone
+    Shutdown,
+}
+
+impl EndOfStreamReason {
+    /// nothing follows a final end of stream, the channel of the subscriber is closed after it
+    pub fn is_final(&self) -> bool {
+        !matches!(self, Self::Stalled)
+    }
+}
+
+impl fmt::Display for EndOfStreamReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unpublished => f.write_str("unpublished"),
+            Self::Kicked(KickReason::Takeover(_)) => f.write_str("taken over"),
+            Self::Kicked(KickReason::IdleTimeout) => f.write_str("idle timeout"),
+            Self::Kicked(KickReason::Drain) => f.write_str("drained"),
+            Self::Stalled => f.write_str("publisher stalled"),
+            Self::Shutdown => f.write_str("shutdown"),
+        }
+    }
+}
+
+/// what failed the stream, the channel of the subscriber is closed after the error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceErrorCode {
+    /// a frame of the publisher could not be parsed or remuxed
+    BadFrame,
+    /// the stream failed on its own
+    Internal,
+}
+
+impl From<&StreamCenterError> for SourceErrorCode {
+    fn from(value: &StreamCenterError) -> Self {
+        match value {
+            StreamCenterError::ParseFLVTagFailed(_)
+            | StreamCenterError::H264CodecError(_)
+            | StreamCenterError::AACCodecError(_)
+            | StreamCenterError::AV1CodecError(_)
+            | StreamCenterError::InvalidStreamType(_)
+            | StreamCenterError::RemuxFailed(_)
+            | StreamCenterError::RemuxAmfFailed { .. }
+            | StreamCenterError::RemuxCodecFailed { .. } => Self::BadFrame,
+            _ => Self::Internal,
+        }
+    }
+}
+
+impl fmt::Display for SourceErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadFrame => f.write_str("bad frame"),
+            Self::Internal => f.write_str("internal error"),
+        }
+    }
+}
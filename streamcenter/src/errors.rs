@@ -2,15 +2,15 @@ use std::{backtrace::Backtrace, io};
 
 use thiserror::Error;
 
-use crate::stream_source::StreamIdentifier;
+use crate::identity::StreamIdentity;
 #[derive(Debug, Error)]
 pub enum StreamCenterError {
     #[error("io error: {0}")]
     Io(#[from] io::Error),
     #[error("stream is already publishing {0:?}")]
-    DuplicateStream(StreamIdentifier),
+    DuplicateStream(StreamIdentity),
     #[error("stream not found: {0:?}")]
-    StreamNotFound(StreamIdentifier),
+    StreamNotFound(StreamIdentity),
     #[error("channel send failed, {backtrace}")]
     ChannelSendFailed { backtrace: Backtrace },
     #[error("invalid stream type: {0}")]
@@ -21,10 +21,59 @@ pub enum StreamCenterError {
     H264CodecError(#[from] codec_h264::errors::H264CodecError),
     #[error("parse aac codec elements failed: {0}")]
     AACCodecError(#[from] codec_aac::errors::AACCodecError),
+    #[error("parse av1 codec elements failed: {0}")]
+    AV1CodecError(#[from] codec_av1::errors::Av1CodecError),
     #[error("remux failed: {0}")]
     RemuxFailed(String),
+    #[error("remux {context} failed: {source}")]
+    RemuxAmfFailed {
+        context: &'static str,
+        source: amf_formats::errors::AmfError,
+    },
+    #[error("remux {context} failed: {source}")]
+    RemuxCodecFailed {
+        context: &'static str,
+        source: codec_common::errors::CodecCommonError,
+    },
     #[error("mix queue full: {0} {1}")]
     MixQueueFull(String, usize),
+    #[error("pipeline tracing is disabled")]
+    TracingDisabled,
+    #[error("invalid takeover policy: {0}")]
+    InvalidTakeoverPolicy(String),
+    #[error("invalid idle watchdog: {0}")]
+    InvalidIdleWatchdog(String),
+    #[error("invalid recovery point join: {0}")]
+    InvalidRecoveryPointJoin(String),
+    #[error("invalid audio gap concealment: {0}")]
+    InvalidAudioGapConcealment(String),
+    #[error("invalid slice integrity: {0}")]
+    InvalidSliceIntegrity(String),
+    #[error("invalid timestamp normalization: {0}")]
+    InvalidTimestampNormalization(String),
+    #[error("invalid dvr window: {0}")]
+    InvalidDvrWindow(String),
+    #[error("invalid backpressure policy: {0}")]
+    InvalidBackpressurePolicy(String),
+    #[error("invalid playback scale: {0}")]
+    InvalidScale(f64),
+    #[error("serialize or parse stream center snapshot failed: {0}")]
+    SnapshotJson(#[from] serde_json::Error),
+    #[error("unsupported stream center snapshot version: {0}")]
+    UnsupportedSnapshotVersion(u64),
+    #[error("invalid stream center snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("invalid stream key: {0}, expect app/stream")]
+    InvalidStreamKey(String),
+    #[error("invalid stream identity: {0}")]
+    InvalidStreamIdentity(String),
+    #[error("invalid stream alias: {0}")]
+    InvalidAlias(String),
+    #[error("{alias} is an alias of {canonical}, publish to {canonical} instead")]
+    PublishToAlias {
+        alias: StreamIdentity,
+        canonical: StreamIdentity,
+    },
 }
 
 pub type StreamCenterResult<T> = Result<T, StreamCenterError>;
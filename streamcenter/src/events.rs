@@ -1,12 +1,34 @@
 use crate::{
+    audio_continuity::AudioConcealmentStats,
+    audio_track::AudioTrack,
+    backpressure::BackpressureAdvisory,
+    drain::{DrainRequest, DrainSummary},
     errors::StreamCenterResult,
     gop::MediaFrame,
+    identity::StreamIdentity,
+    keyframe::KeyframeSnapshot,
+    latency::{LatencyProbe, LatencySummary},
+    playback::PlaybackControl,
+    rate_estimate::RateEstimateStats,
+    rtcp_peer::RtcpPeer,
+    rtmp_control::RtmpControl,
+    rtp_receive::RtpReceiveStats,
+    serialized::SerializedFrameCache,
+    snapshot::StreamCenterSnapshot,
     stream_source::{
-        ParsedContext, PlayProtocol, PlayStat, PublishProtocol, StreamIdentifier, SubscribeHandler,
+        MediaSelection, ParsedContext, PlayProtocol, PlayStat, PublishProtocol, SubscribeHandler,
     },
+    takeover::{PublisherHandle, PublisherKicked},
+    timestamp_normalization::TimestampNormalizationStats,
+    trace::TraceRecord,
 };
 use codec_common::{audio::AudioConfig, video::VideoConfig};
-use std::{collections::HashMap, time::SystemTime};
+use flv_formats::tag::on_meta_data::OnMetaData;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
@@ -14,29 +36,149 @@ use uuid::Uuid;
 pub enum StreamCenterEvent {
     Publish {
         protocol: PublishProtocol,
-        stream_id: StreamIdentifier,
+        stream_id: StreamIdentity,
         context: HashMap<String, String>,
+        /// the stream can only be taken over by another publisher if this is set
+        publisher: Option<PublisherHandle>,
+        /// set if the publisher replays a recording, which can be played at other scales
+        playback: Option<PlaybackControl>,
         result_sender: oneshot::Sender<StreamCenterResult<mpsc::Sender<MediaFrame>>>, // success or not
     },
     Unpublish {
-        stream_id: StreamIdentifier,
+        stream_id: StreamIdentity,
+        /// only unpublish if the stream is still owned by this publisher
+        publisher_id: Option<Uuid>,
         result_sender: oneshot::Sender<StreamCenterResult<()>>,
     },
     Subscribe {
-        stream_id: StreamIdentifier,
+        stream_id: StreamIdentity,
         protocol: PlayProtocol,
         context: HashMap<String, String>,
+        media_selection: MediaSelection,
         result_sender: oneshot::Sender<StreamCenterResult<SubscribeResponse>>,
     },
+    UpdateMediaSelection {
+        stream_id: StreamIdentity,
+        uuid: Uuid,
+        media_selection: MediaSelection,
+        result_sender: oneshot::Sender<StreamCenterResult<()>>,
+    },
     Unsubscribe {
-        stream_id: StreamIdentifier,
+        stream_id: StreamIdentity,
         uuid: Uuid,
         result_sender: oneshot::Sender<StreamCenterResult<()>>,
     },
     Describe {
-        stream_id: StreamIdentifier,
+        stream_id: StreamIdentity,
         result_sender: oneshot::Sender<StreamCenterResult<StreamDescription>>,
     },
+    /// answered with the description once the sequence headers of the stream are known,
+    /// a stream not published yet is waited for. the caller bounds the wait
+    DescribeConfigured {
+        stream_id: StreamIdentity,
+        result_sender: oneshot::Sender<StreamCenterResult<StreamDescription>>,
+    },
+    /// recent pipeline events of the stream, oldest first
+    Trace {
+        stream_id: StreamIdentity,
+        result_sender: oneshot::Sender<StreamCenterResult<Vec<TraceRecord>>>,
+    },
+    /// asks the publisher of a recording to play at the scale, answered with the effective scale,
+    /// live streams always play at 1.0
+    SetScale {
+        stream_id: StreamIdentity,
+        scale: f64,
+        result_sender: oneshot::Sender<StreamCenterResult<f64>>,
+    },
+    /// the latest IDR access unit of the stream, None before the first one
+    Keyframe {
+        stream_id: StreamIdentity,
+        result_sender: oneshot::Sender<StreamCenterResult<Option<KeyframeSnapshot>>>,
+    },
+    /// sent by the stream source when the publisher changes its codec parameters mid-stream
+    ConfigChanged {
+        stream_id: StreamIdentity,
+        change: StreamConfigChange,
+    },
+    /// sent by the stream source when its publisher sent no audio or video for the stall threshold
+    PublisherStalled {
+        stream_id: StreamIdentity,
+        idle: Duration,
+    },
+    /// sent by the stream source when a stalled publisher sends audio or video again
+    PublisherResumed {
+        stream_id: StreamIdentity,
+        stalled_for: Duration,
+    },
+    /// sent by the stream source when the timestamps of its publisher jumped and were anchored again,
+    /// the jump is how far they went off the clock of the server, negative if backwards
+    TimestampDiscontinuity {
+        stream_id: StreamIdentity,
+        jump_nano: i64,
+    },
+    /// sent by the stream source when its publisher stayed silent for the reap threshold,
+    /// the stream is unpublished and the publisher disconnected, if the source still owns the stream
+    ReapIdleStream {
+        stream_id: StreamIdentity,
+        source_id: Uuid,
+        idle: Duration,
+    },
+    /// sent by rtp based sessions when a remote participant describes itself in rtcp,
+    /// the subscriber id is None for the peers of the publisher
+    RtcpPeerDescribed {
+        stream_id: StreamIdentity,
+        subscriber_id: Option<Uuid>,
+        peer: RtcpPeer,
+    },
+    /// sent by rtmp sessions once they publish or play, the subscriber id is None for the publisher
+    RtmpControlNegotiated {
+        stream_id: StreamIdentity,
+        subscriber_id: Option<Uuid>,
+        control: RtmpControl,
+    },
+    /// sent by rtp based publish sessions each time they report the reception of a track in rtcp
+    RtpReceiveReported {
+        stream_id: StreamIdentity,
+        stats: RtpReceiveStats,
+    },
+    /// asks the kickable publishers to move to the target host,
+    /// the ones still here after the grace period are kicked
+    Drain {
+        request: DrainRequest,
+        result_sender: oneshot::Sender<StreamCenterResult<DrainSummary>>,
+    },
+    /// sent by the timer of a drain when its grace period is over
+    DrainGraceExpired { drain_id: Uuid },
+    /// the takeover policies and the restorable sources in effect
+    Snapshot {
+        result_sender: oneshot::Sender<StreamCenterSnapshot>,
+    },
+    /// points the alias to the canonical stream, or removes it if there is none,
+    /// answered with the stream it pointed to before. the subscribers so far stay where they are
+    SetAlias {
+        alias: StreamIdentity,
+        canonical: Option<StreamIdentity>,
+        result_sender: oneshot::Sender<StreamCenterResult<Option<StreamIdentity>>>,
+    },
+    /// pairs of alias and canonical stream, ordered by alias
+    Aliases {
+        result_sender: oneshot::Sender<Vec<(StreamIdentity, StreamIdentity)>>,
+    },
+    /// the streams published, ordered
+    Streams {
+        result_sender: oneshot::Sender<Vec<StreamIdentity>>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamConfigChange {
+    /// counts config changes since publish, starts from 0
+    pub config_generation: u64,
+    pub video_changed: bool,
+    pub audio_changed: bool,
+    /// parsed from the new sps, if any
+    pub width: Option<u64>,
+    pub height: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -45,7 +187,14 @@ pub struct SubscriberInfo {
     pub play_protocol: PlayProtocol,
     pub context: HashMap<String, String>,
     pub parsed_context: ParsedContext,
+    pub media_selection: MediaSelection,
     pub play_stat: PlayStat,
+    /// the rtp participants on the side of the subscriber, ordered by ssrc
+    pub rtcp_peers: Vec<RtcpPeer>,
+    /// the protocol control settings sent to the subscriber, if it plays rtmp
+    pub rtmp_control: Option<RtmpControl>,
+    /// the alias the subscriber asked for, if it did not ask for the stream itself
+    pub alias: Option<StreamIdentity>,
 }
 
 impl From<&SubscribeHandler> for SubscriberInfo {
@@ -55,7 +204,11 @@ impl From<&SubscribeHandler> for SubscriberInfo {
             play_protocol: value.play_protocol,
             context: value.context.clone(),
             parsed_context: value.parsed_context.clone(),
+            media_selection: value.media_selection,
             play_stat: value.stat.clone(),
+            rtcp_peers: value.rtcp_peers.to_vec(),
+            rtmp_control: value.rtmp_control,
+            alias: value.alias.clone(),
         }
     }
 }
@@ -63,13 +216,58 @@ impl From<&SubscribeHandler> for SubscriberInfo {
 #[derive(Debug)]
 pub struct StreamDescription {
     pub publish_protocol: PublishProtocol,
-    pub stream_id: StreamIdentifier,
+    pub stream_id: StreamIdentity,
     pub video_config: Option<VideoConfig>,
     pub has_video: bool,
     pub audio_conifg: Option<AudioConfig>,
+    /// of the default audio track, from its multichannel config
+    pub audio_channels: Option<u8>,
     pub has_audio: bool,
+    pub config_generation: u64,
     pub publish_start_time: SystemTime,
+    /// the idle watchdog found the publisher silent, cleared once it sends audio or video again
+    pub stalled: bool,
     pub subscribers: HashMap<Uuid, SubscriberInfo>,
+    /// the rtp participants on the side of the publisher, ordered by ssrc
+    pub publisher_rtcp_peers: Vec<RtcpPeer>,
+    /// the protocol control settings sent to the publisher, if it publishes rtmp
+    pub publisher_rtmp_control: Option<RtmpControl>,
+    /// the reception of the tracks of an rtp based publisher, ordered by track
+    pub publisher_rtp_receive: Vec<RtpReceiveStats>,
+    /// 0 to 100, scored from the loss and jitter of the worst track, None if no track was reported
+    pub health: Option<u8>,
+    /// the audio tracks the publisher sent, ordered by track id
+    pub audio_tracks: Vec<AudioTrack>,
+    /// the holes in the audio of the publisher concealed so far
+    pub audio_concealment: AudioConcealmentStats,
+    /// how far the timestamps of the publisher were moved, all zero if they are not normalized
+    pub timestamp_normalization: TimestampNormalizationStats,
+    /// ingest to sink latency over the recent window, None if measurement is disabled
+    pub latency: Option<LatencySummary>,
+    /// the frame rate and bitrates measured from the frames of the publisher
+    pub rate_estimate: RateEstimateStats,
+    /// the onMetaData of the stream with the updates of the publisher merged in
+    pub meta_data: Option<OnMetaData>,
+}
+
+#[derive(Debug)]
+pub struct PublishResponse {
+    pub publisher_id: Uuid,
+    pub media_sender: mpsc::Sender<MediaFrame>,
+    /// fires if another publisher takes over the stream, or the idle watchdog reaps it
+    pub kicked_receiver: oneshot::Receiver<PublisherKicked>,
+    /// fires if the server is drained, the publisher is kicked after the grace period if it stays
+    pub drain_receiver: oneshot::Receiver<DrainRequest>,
+    /// the server can not keep up with the stream, or caught up again,
+    /// nothing comes unless the backpressure policy of the app is on
+    pub backpressure_receiver: mpsc::UnboundedReceiver<BackpressureAdvisory>,
+}
+
+#[derive(Debug)]
+pub struct RecordingPublishResponse {
+    pub media_sender: mpsc::Sender<MediaFrame>,
+    /// the scales players asked for, the latest one wins
+    pub scale_receiver: mpsc::UnboundedReceiver<f64>,
 }
 
 #[derive(Debug)]
@@ -77,5 +275,14 @@ pub struct SubscribeResponse {
     pub subscribe_id: Uuid,
     pub has_video: bool,
     pub has_audio: bool,
+    /// the stream ends with an EndOfStream or a SourceError after the last frame queued for the subscriber,
+    /// the channel stays open until that frame is consumed and is closed right after a final one.
+    /// a stalled EndOfStream is not final, the frames go on if the publisher resumes
     pub media_receiver: mpsc::Receiver<MediaFrame>,
+    /// sinks report the latency of the frames they write out, None if measurement is disabled
+    pub latency_probe: Option<LatencyProbe>,
+    /// frames serialized by one sink of the stream, reused by the others
+    pub serialized_frames: SerializedFrameCache,
+    /// the stream replays a recording, live streams can only be played at speed 1.0
+    pub recording: bool,
 }
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use crate::{gop::MediaFrame, identity::StreamIdentity};
+
+/// the publish context key naming the stream of the same app whose frames the subscribers get
+/// while the publisher is stalled, e.g., `stream_a?backup=stream_a_backup`
+pub const BACKUP_STREAM_KEY: &str = "backup";
+/// frames of the backup queued for the stream it stands in for
+pub const FAILOVER_CHANNEL_CAPACITY: usize = 100_000;
+/// the gap put between the last frame of a source and the first one of the next,
+/// if no frame interval is seen yet
+const DEFAULT_SWITCH_GAP_NANO: u64 = 20_000_000;
+/// a larger step of the decode timestamps is a jump, not the frame interval
+const MAX_SWITCH_GAP_NANO: u64 = 100_000_000;
+
+/// the backup declared in the publish context of a stream, a stream is not its own backup
+pub fn backup_stream(
+    stream_id: &StreamIdentity,
+    context: &HashMap<String, String>,
+) -> Option<StreamIdentity> {
+    context
+        .get(BACKUP_STREAM_KEY)
+        .map(|name| name.trim())
+        .filter(|name| *name != stream_id.name.as_str())
+        .and_then(|name| stream_id.with_name(name).ok())
+}
+
+/// keeps the timestamps the subscribers see going forward across source switches,
+/// the frames of a new source are shifted to continue right after the last frame sent
+#[derive(Debug, Default)]
+pub struct TimestampRewriter {
+    offset_nano: i64,
+    /// the latest decode timestamp of the current source, before it is shifted
+    last_input_dts_nano: Option<u64>,
+    /// the largest decode timestamp handed out so far
+    last_output_dts_nano: Option<u64>,
+    /// the latest step of the decode timestamps of the current source
+    gap_nano: Option<u64>,
+    switch_pending: bool,
+}
+
+impl TimestampRewriter {
+    /// the next frame comes from another source, it is put right after the last frame sent
+    pub fn switch_source(&mut self) {
+        self.switch_pending = true;
+        self.last_input_dts_nano = None;
+    }
+
+    /// shifts the frame by the offset of the current source, the composition time offset is kept
+    pub fn rewrite(&mut self, frame: &mut MediaFrame) {
+        let dts_nano = frame.get_decode_timestamp_ns();
+        if std::mem::take(&mut self.switch_pending)
+            && let Some(last_output_dts_nano) = self.last_output_dts_nano
+        {
+            let gap_nano = self.gap_nano.unwrap_or(DEFAULT_SWITCH_GAP_NANO);
+            self.offset_nano = (last_output_dts_nano + gap_nano) as i64 - dts_nano as i64;
+            tracing::info!(
+                "source switched, timestamps are shifted by {}ns",
+                self.offset_nano
+            );
+        }
+        if frame.is_video() || frame.is_audio() {
+            if let Some(last_input_dts_nano) = self.last_input_dts_nano
+                && dts_nano > last_input_dts_nano
+                && dts_nano - last_input_dts_nano <= MAX_SWITCH_GAP_NANO
+            {
+                self.gap_nano = Some(dts_nano - last_input_dts_nano);
+            }
+            self.last_input_dts_nano = Some(dts_nano);
+        }
+        if self.offset_nano != 0 {
+            frame.shift_timestamps_ns(self.offset_nano);
+        }
+        let output_dts_nano = frame.get_decode_timestamp_ns();
+        self.last_output_dts_nano = Some(
+            self.last_output_dts_nano
+                .map_or(output_dts_nano, |last| last.max(output_dts_nano)),
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! audio and video frames are stamped with the crc of their payload when they enter the stream center,
+//! the stages claiming to keep the payload as is, e.g., the fan-out and the flv muxing of the sinks,
+//! check it again. a mismatch is logged with the stage and counted.
+//! for catching corruption between stages in development, only built with the frame-crc feature
+
+use std::io::Cursor;
+
+use codec_common::frame_crc::{audio_payload_crc, video_payload_crc};
+use flv_formats::tag::FLVTag;
+use utils::traits::{reader::ReadFrom, writer::WriteTo};
+
+use crate::{
+    gop::MediaFrame, identity::StreamIdentity, metrics::StreamMetrics, trace::TraceFrameKind,
+};
+
+/// the crc of the payload, None for frames other than audio and video
+pub fn payload_crc(frame: &MediaFrame) -> Option<u32> {
+    match frame {
+        MediaFrame::Video { payload, .. } => Some(video_payload_crc(payload)),
+        MediaFrame::Audio { payload, .. } => Some(audio_payload_crc(payload)),
+        _ => None,
+    }
+}
+
+pub(crate) fn stamp(frame: &mut MediaFrame) {
+    if let Some(crc) = payload_crc(frame) {
+        frame.set_payload_crc(crc);
+    }
+}
+
+/// checks `output`, what the stage made of `input`, against the crc `input` was stamped with.
+/// false on a mismatch, frames never stamped pass
+pub fn verify(
+    stage: &str,
+    stream: &StreamIdentity,
+    input: &MediaFrame,
+    output: &MediaFrame,
+) -> bool {
+    let Some(expected) = input.get_payload_crc() else {
+        return true;
+    };
+    match payload_crc(output) {
+        Some(actual) if actual == expected => true,
+        actual => {
+            on_mismatch(
+                stage,
+                stream,
+                input,
+                expected,
+                actual.map(|crc| format!("{:08x}", crc)),
+            );
+            false
+        }
+    }
+}
+
+/// checks the flv tag a sink muxed the frame into, written out and parsed back with the same nalu length size
+pub fn verify_flv_tag(
+    stage: &str,
+    stream: &StreamIdentity,
+    frame: &MediaFrame,
+    tag: &FLVTag,
+    nalu_size_length: u8,
+) -> bool {
+    if frame.get_payload_crc().is_none() {
+        return true;
+    }
+    let mut bytes = Vec::new();
+    if let Err(err) = tag.write_to(&mut bytes) {
+        return verify_parsed(stage, stream, frame, Err(err.to_string()));
+    }
+    verify_flv_tag_bytes(stage, stream, frame, &bytes, nalu_size_length)
+}
+
+/// like `verify_flv_tag`, for a tag written out already, the previous tag size may follow it
+pub fn verify_flv_tag_bytes(
+    stage: &str,
+    stream: &StreamIdentity,
+    frame: &MediaFrame,
+    tag: &[u8],
+    nalu_size_length: u8,
+) -> bool {
+    if frame.get_payload_crc().is_none() {
+        return true;
+    }
+    let parsed = FLVTag::read_from(&mut Cursor::new(tag))
+        .map_err(|err| err.to_string())
+        .and_then(|tag| {
+            MediaFrame::from_flv_tag(tag, nalu_size_length).map_err(|err| err.to_string())
+        });
+    verify_parsed(stage, stream, frame, parsed)
+}
+
+fn verify_parsed(
+    stage: &str,
+    stream: &StreamIdentity,
+    frame: &MediaFrame,
+    parsed: Result<MediaFrame, String>,
+) -> bool {
+    let Some(expected) = frame.get_payload_crc() else {
+        return true;
+    };
+    match parsed {
+        Ok(parsed) => verify(stage, stream, frame, &parsed),
+        Err(err) => {
+            on_mismatch(
+                stage,
+                stream,
+                frame,
+                expected,
+                Some(format!("unparsable tag, {}", err)),
+            );
+            false
+        }
+    }
+}
+
+fn on_mismatch(
+    stage: &str,
+    stream: &StreamIdentity,
+    frame: &MediaFrame,
+    expected: u32,
+    actual: Option<String>,
+) {
+    StreamMetrics::frame_crc_mismatches(stage).inc();
+    tracing::error!(
+        "frame payload changed in stage {}, stream: {}, kind: {:?}, dts ms: {}, pts ms: {}, payload bytes: {}, crc expected: {:08x}, actual: {}",
+        stage,
+        stream,
+        TraceFrameKind::from(frame),
+        frame.get_decode_timestamp_ms(),
+        frame.get_presentation_timestamp_ms(),
+        frame.payload_bytes(),
+        expected,
+        actual.as_deref().unwrap_or("none"),
+    );
+}
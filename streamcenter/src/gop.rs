@@ -1,37 +1,80 @@
-use crate::errors::{StreamCenterError, StreamCenterResult};
+use crate::{
+    end_of_stream::{EndOfStreamReason, SourceErrorCode},
+    errors::{StreamCenterError, StreamCenterResult},
+    gop_budget::GopCacheAccount,
+    parameter_sets::without_parameter_sets,
+};
 use bitstream_io::{BitRead, BitWrite};
 use codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecificConfig;
+use codec_av1::av1_codec_configuration_record::Av1CodecConfigurationRecord;
 use codec_common::{
     FrameType, MediaFrameTimestamp,
     audio::{AudioCodecCommon, AudioConfig, AudioFrameInfo, SoundInfoCommon},
-    video::{H264VideoConfig, VideoCodecCommon, VideoConfig, VideoFrameInfo, VideoFrameUnit},
+    video::{
+        Av1VideoConfig, H264VideoConfig, VideoCodecCommon, VideoConfig, VideoFrameInfo,
+        VideoFrameUnit,
+    },
 };
 use codec_h264::avc_decoder_configuration_record::AvcDecoderConfigurationRecord;
 use flv_formats::tag::{
     FLVTag,
-    audio_tag_header::LegacyAudioTagHeader,
+    audio_tag_header::{AudioTagHeader, LegacyAudioTagHeader},
     audio_tag_header_info::AudioTagHeaderWithoutMultiTrack,
-    enhanced::ex_video::ex_video_header::VideoPacketType,
+    enhanced::{
+        ex_audio::{
+            ex_audio_body::AudioMultichannelConfig,
+            ex_audio_header::{AudioModEx, AudioPacketType, AudioTrackInfo, ExAudioTagHeader},
+        },
+        ex_video::ex_video_header::{ExVideoTagHeader, VideoPacketType},
+    },
     flv_tag_body::FLVTagBody,
-    flv_tag_header::FLVTagType,
+    flv_tag_header::{FLVTagHeader, FLVTagType},
     on_meta_data::OnMetaData,
-    video_tag_header::{FrameTypeFLV, LegacyVideoTagHeader},
+    video_tag_header::{FrameTypeFLV, VideoTagHeader},
     video_tag_header_info::VideoTagHeaderWithoutMultiTrack,
 };
 use num::ToPrimitive;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
     io,
+    time::Instant,
 };
-use tokio_util::bytes::{Buf, Bytes};
+use tokio_util::bytes::{Buf, BufMut, Bytes};
 use tracing::debug_span;
+use utils::bytes::pool;
 use utils::traits::reader::ReadFrom;
 use utils::traits::writer::{BitwiseWriteTo, WriteTo};
 use utils::traits::{
     dynamic_sized_packet::{DynamicSizedBitsPacket, DynamicSizedPacket},
+    fixed_packet::FixedPacket,
     reader::BitwiseReadFrom,
 };
 
+/// data frames larger than this are dropped by the stream center
+pub const MAX_DATA_FRAME_BYTES: usize = 64 * 1024;
+
+const SET_DATA_FRAME: &str = "@setDataFrame";
+const ON_META_DATA: &str = "onMetaData";
+
+/// the header the video tags of a flv output go with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlvVideoHeader {
+    /// the legacy header for the codecs with a CodecID, which every player reads
+    #[default]
+    Legacy,
+    /// the ExVideoTagHeader of enhanced rtmp for the codecs with a FourCC
+    Enhanced,
+}
+
+impl FlvVideoHeader {
+    fn header_of(&self, frame_info: &VideoFrameInfo) -> StreamCenterResult<VideoTagHeader> {
+        match self {
+            Self::Legacy => Ok(frame_info.try_into()?),
+            Self::Enhanced => Ok(VideoTagHeader::enhanced(frame_info)?),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum MediaFrame {
     VideoConfig {
@@ -46,6 +89,16 @@ pub enum MediaFrame {
         timestamp_nano: u64,
         sound_info: SoundInfoCommon,
         config: Box<AudioConfig>,
+        /// the audio track of a multitrack stream, 0 is the default track
+        track_id: u8,
+    },
+    /// the channel layout of an audio track, from a MultichannelConfig packet of enhanced rtmp,
+    /// kept along with the audio config of the track
+    AudioChannelConfig {
+        timestamp_nano: u64,
+        codec_id: AudioCodecCommon,
+        config: Box<AudioMultichannelConfig>,
+        track_id: u8,
     },
     Audio {
         // NOTE - this tag_header is also included in the frame payload
@@ -59,9 +112,49 @@ pub enum MediaFrame {
         on_meta_data: Box<Option<OnMetaData>>,
         payload: Bytes,
     },
+    /// script data other than onMetaData, e.g., onCuePoint, onTextData,
+    /// they are delivered in timestamp order with audio and video
+    Data {
+        timestamp_nano: u64,
+        /// the name of the handler, like onCuePoint
+        name: String,
+        /// amf0 encoded values, the name included
+        payload: Bytes,
+    },
+    /// sent to the subscribers after the last media frame of the stream,
+    /// nothing follows a final one, nor a stall unless the publisher resumes
+    EndOfStream {
+        timestamp_nano: u64,
+        reason: EndOfStreamReason,
+    },
+    /// the stream failed, sent to the subscribers after the last media frame, nothing follows it
+    SourceError {
+        timestamp_nano: u64,
+        code: SourceErrorCode,
+        detail: String,
+    },
 }
 
 impl MediaFrame {
+    /// the end of stream and the source error, which tell the subscribers the frames stop
+    #[inline]
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            MediaFrame::EndOfStream { .. } | MediaFrame::SourceError { .. }
+        )
+    }
+
+    /// nothing follows the frame, the channel of the subscriber is closed after it
+    #[inline]
+    pub fn is_final(&self) -> bool {
+        match self {
+            MediaFrame::EndOfStream { reason, .. } => reason.is_final(),
+            MediaFrame::SourceError { .. } => true,
+            _ => false,
+        }
+    }
+
     #[inline]
     pub fn is_video(&self) -> bool {
         matches!(
@@ -98,6 +191,7 @@ impl MediaFrame {
                 frame_info: _,
                 payload: _,
             } | MediaFrame::AudioConfig { .. }
+                | MediaFrame::AudioChannelConfig { .. }
         )
     }
 
@@ -113,6 +207,11 @@ impl MediaFrame {
         )
     }
 
+    #[inline]
+    pub fn is_data(&self) -> bool {
+        matches!(self, MediaFrame::Data { .. })
+    }
+
     pub fn get_presentation_timestamp_ns(&self) -> u64 {
         match self {
             Self::Audio {
@@ -120,8 +219,12 @@ impl MediaFrame {
                 ..
             }
             | Self::AudioConfig { timestamp_nano, .. }
+            | Self::AudioChannelConfig { timestamp_nano, .. }
             | Self::VideoConfig { timestamp_nano, .. }
-            | Self::Script { timestamp_nano, .. } => *timestamp_nano,
+            | Self::Script { timestamp_nano, .. }
+            | Self::Data { timestamp_nano, .. }
+            | Self::EndOfStream { timestamp_nano, .. }
+            | Self::SourceError { timestamp_nano, .. } => *timestamp_nano,
             Self::Video {
                 frame_info: VideoFrameInfo { timestamp, .. },
                 ..
@@ -142,8 +245,12 @@ impl MediaFrame {
                 ..
             }
             | Self::AudioConfig { timestamp_nano, .. }
+            | Self::AudioChannelConfig { timestamp_nano, .. }
             | Self::VideoConfig { timestamp_nano, .. }
-            | Self::Script { timestamp_nano, .. } => *timestamp_nano,
+            | Self::Script { timestamp_nano, .. }
+            | Self::Data { timestamp_nano, .. }
+            | Self::EndOfStream { timestamp_nano, .. }
+            | Self::SourceError { timestamp_nano, .. } => *timestamp_nano,
             Self::Video {
                 frame_info: VideoFrameInfo { timestamp, .. },
                 ..
@@ -164,8 +271,12 @@ impl MediaFrame {
                 ..
             }
             | Self::AudioConfig { timestamp_nano, .. }
+            | Self::AudioChannelConfig { timestamp_nano, .. }
             | Self::VideoConfig { timestamp_nano, .. }
-            | Self::Script { timestamp_nano, .. } => *timestamp_nano = pts_nano,
+            | Self::Script { timestamp_nano, .. }
+            | Self::Data { timestamp_nano, .. }
+            | Self::EndOfStream { timestamp_nano, .. }
+            | Self::SourceError { timestamp_nano, .. } => *timestamp_nano = pts_nano,
             Self::Video {
                 frame_info: VideoFrameInfo { timestamp, .. },
                 ..
@@ -187,8 +298,12 @@ impl MediaFrame {
                 ..
             }
             | Self::AudioConfig { timestamp_nano, .. }
+            | Self::AudioChannelConfig { timestamp_nano, .. }
             | Self::VideoConfig { timestamp_nano, .. }
-            | Self::Script { timestamp_nano, .. } => *timestamp_nano = dts_nano,
+            | Self::Script { timestamp_nano, .. }
+            | Self::Data { timestamp_nano, .. }
+            | Self::EndOfStream { timestamp_nano, .. }
+            | Self::SourceError { timestamp_nano, .. } => *timestamp_nano = dts_nano,
             Self::Video {
                 frame_info: VideoFrameInfo { timestamp, .. },
                 ..
@@ -203,11 +318,127 @@ impl MediaFrame {
         self.set_decode_timestamp_ns(ts);
     }
 
+    /// moves all timestamps of the frame by the offset, clamped at 0
+    pub fn shift_timestamps_ns(&mut self, offset_nano: i64) {
+        match self {
+            Self::Video {
+                frame_info: VideoFrameInfo { timestamp, .. },
+                ..
+            } => {
+                timestamp.shift_nano(offset_nano);
+            }
+            _ => {
+                let dts_nano = self.get_decode_timestamp_ns();
+                self.set_decode_timestamp_ns(dts_nano.saturating_add_signed(offset_nano));
+            }
+        }
+    }
+
+    /// keeps the timestamps the publisher sent the frame with in its info, before they are rewritten,
+    /// only audio and video frames carry them
+    pub fn keep_publisher_timestamps(&mut self) {
+        match self {
+            Self::Audio { frame_info, .. } => {
+                frame_info.publisher_timestamp_nano = Some(frame_info.timestamp_nano)
+            }
+            Self::Video { frame_info, .. } => {
+                frame_info.publisher_timestamp = Some(frame_info.timestamp)
+            }
+            _ => {}
+        }
+    }
+
+    /// only audio and video frames are marked
+    pub fn set_discontinuity(&mut self) {
+        match self {
+            Self::Audio { frame_info, .. } => frame_info.discontinuity = true,
+            Self::Video { frame_info, .. } => frame_info.discontinuity = true,
+            _ => {}
+        }
+    }
+
+    #[inline]
+    pub fn is_discontinuity(&self) -> bool {
+        match self {
+            Self::Audio { frame_info, .. } => frame_info.discontinuity,
+            Self::Video { frame_info, .. } => frame_info.discontinuity,
+            _ => false,
+        }
+    }
+
+    /// only audio and video frames carry the ingest time
+    #[inline]
+    pub fn get_ingest_time(&self) -> Option<Instant> {
+        match self {
+            Self::Audio { frame_info, .. } => frame_info.ingest_time,
+            Self::Video { frame_info, .. } => frame_info.ingest_time,
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn set_ingest_time(&mut self, time: Instant) {
+        match self {
+            Self::Audio { frame_info, .. } => frame_info.ingest_time = Some(time),
+            Self::Video { frame_info, .. } => frame_info.ingest_time = Some(time),
+            _ => {}
+        }
+    }
+
+    /// only audio and video frames carry the payload crc, see [`crate::frame_crc`]
+    #[cfg(feature = "frame-crc")]
+    #[inline]
+    pub fn get_payload_crc(&self) -> Option<u32> {
+        match self {
+            Self::Audio { frame_info, .. } => frame_info.payload_crc,
+            Self::Video { frame_info, .. } => frame_info.payload_crc,
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "frame-crc")]
+    #[inline]
+    pub fn set_payload_crc(&mut self, crc: u32) {
+        match self {
+            Self::Audio { frame_info, .. } => frame_info.payload_crc = Some(crc),
+            Self::Video { frame_info, .. } => frame_info.payload_crc = Some(crc),
+            _ => {}
+        }
+    }
+
+    /// bytes of the payload, nal unit headers included and start codes excluded,
+    /// obus as they are, 0 for sequence headers
+    pub fn payload_bytes(&self) -> usize {
+        match self {
+            Self::Video {
+                payload: VideoFrameUnit::H264 { nal_units },
+                ..
+            } => nal_units
+                .iter()
+                .map(|nal_unit| 1 + nal_unit.body.len())
+                .sum(),
+            Self::Video {
+                payload: VideoFrameUnit::AV1 { obus },
+                ..
+            } => obus.len(),
+            Self::Audio { payload, .. }
+            | Self::Script { payload, .. }
+            | Self::Data { payload, .. } => payload.len(),
+            Self::VideoConfig { .. }
+            | Self::AudioConfig { .. }
+            | Self::AudioChannelConfig { .. }
+            | Self::EndOfStream { .. }
+            | Self::SourceError { .. } => 0,
+        }
+    }
+
     #[inline]
     pub fn is_sequence_header(&self) -> bool {
         matches!(
             self,
-            MediaFrame::AudioConfig { .. } | MediaFrame::VideoConfig { .. }
+            MediaFrame::AudioConfig { .. }
+                | MediaFrame::AudioChannelConfig { .. }
+                | MediaFrame::VideoConfig { .. }
         )
     }
 
@@ -242,6 +473,17 @@ impl MediaFrame {
         }
     }
 
+    /// the audio track the frame belongs to, none for other frames
+    pub fn audio_track_id(&self) -> Option<u8> {
+        match self {
+            Self::Audio { frame_info, .. } => Some(frame_info.track_id),
+            Self::AudioConfig { track_id, .. } | Self::AudioChannelConfig { track_id, .. } => {
+                Some(*track_id)
+            }
+            _ => None,
+        }
+    }
+
     pub fn same_codec_video(&self, other: &Self) -> bool {
         if !self.is_video() || !other.is_video() {
             return false;
@@ -250,8 +492,17 @@ impl MediaFrame {
     }
 
     pub fn to_flv_tag(&self, nalu_size_length: u8) -> StreamCenterResult<flv_formats::tag::FLVTag> {
+        self.to_flv_tag_with_video_header(nalu_size_length, FlvVideoHeader::Legacy)
+    }
+
+    /// the video frames and configs go with the header asked for, other frames are the same
+    pub fn to_flv_tag_with_video_header(
+        &self,
+        nalu_size_length: u8,
+        video_header: FlvVideoHeader,
+    ) -> StreamCenterResult<flv_formats::tag::FLVTag> {
         assert!(nalu_size_length == 1 || nalu_size_length == 2 || nalu_size_length == 4);
-        let span = debug_span!("media frame to flv tag", nalu_size_length);
+        let span = debug_span!("media frame to flv tag", nalu_size_length, ?video_header);
 
         let flv_dts_ms = self.get_decode_timestamp_ms().to_u32().unwrap();
         let _enter = span.enter();
@@ -311,27 +562,89 @@ impl MediaFrame {
                     },
                 })
             }
+            Self::Data { payload, .. } => {
+                let value = amf_formats::amf0::Value::read_all(payload.clone().reader()).map_err(
+                    |err| StreamCenterError::RemuxAmfFailed {
+                        context: "from data frame to flv script tag",
+                        source: err,
+                    },
+                )?;
+                Ok(flv_formats::tag::FLVTag {
+                    tag_header: flv_formats::tag::flv_tag_header::FLVTagHeader {
+                        tag_type: FLVTagType::Script,
+                        data_size: payload.len().to_u32().unwrap(),
+                        timestamp: flv_dts_ms,
+                        filter_enabled: false,
+                    },
+                    body_with_filter: flv_formats::tag::flv_tag_body::FLVTagBodyWithFilter {
+                        filter: None,
+                        body: flv_formats::tag::flv_tag_body::FLVTagBody::Script { value },
+                    },
+                })
+            }
+            Self::EndOfStream { .. } | Self::SourceError { .. } => Err(
+                StreamCenterError::RemuxFailed("control frames have no flv tag".to_string()),
+            ),
+            Self::AudioChannelConfig {
+                codec_id, config, ..
+            } => {
+                // subscribers get the track they picked as the only one of the stream
+                let header = ExAudioTagHeader {
+                    packet_type: AudioPacketType::MultichannelConfig,
+                    packet_mod_ex: AudioModEx::default(),
+                    track_type: None,
+                    tracks: HashMap::from([(
+                        0,
+                        AudioTrackInfo {
+                            codec: (*codec_id).try_into()?,
+                        },
+                    )]),
+                };
+                let mut bytes = Vec::new();
+                config.write_to(&mut bytes)?;
+                Ok(flv_formats::tag::FLVTag {
+                    tag_header: flv_formats::tag::flv_tag_header::FLVTagHeader {
+                        tag_type: FLVTagType::Audio,
+                        data_size: header
+                            .get_packet_bytes_count()
+                            .checked_add(bytes.len())
+                            .and_then(|v| v.to_u32())
+                            .unwrap(),
+                        timestamp: flv_dts_ms,
+                        filter_enabled: false,
+                    },
+                    body_with_filter: flv_formats::tag::flv_tag_body::FLVTagBodyWithFilter {
+                        filter: None,
+                        body: flv_formats::tag::flv_tag_body::FLVTagBody::Audio {
+                            header: AudioTagHeader::Enhanced(header),
+                            body: Bytes::from(bytes),
+                        },
+                    },
+                })
+            }
             Self::Video {
                 frame_info,
                 payload,
             } => {
                 let span = debug_span!("video", ?frame_info);
                 let _enter = span.enter();
-                let legacy_header: LegacyVideoTagHeader = frame_info.try_into()?;
-                let mut bytes =
-                    Vec::with_capacity(payload.bytes_cnt(nalu_size_length.to_usize().unwrap()));
-                let mut writer = io::Cursor::new(&mut bytes);
+                let header = video_header.header_of(frame_info)?;
+                // flv has the parameter sets in the sequence header
+                let stripped = without_parameter_sets(payload);
+                let payload = stripped.as_ref().unwrap_or(payload);
+                // mostly copied into the tag bytes right away, so the body goes back to the pool
+                let mut buffer =
+                    pool::take(payload.bytes_cnt(nalu_size_length.to_usize().unwrap()));
                 codec_common::video::writer::VideoFrameUnitAvccWriter(payload, nalu_size_length)
-                    .write_to(&mut writer)
-                    .map_err(|err| {
-                        StreamCenterError::RemuxFailed(format!(
-                            "remux from video frame to flv video tag failed: {}",
-                            err
-                        ))
+                    .write_to(&mut (&mut *buffer).writer())
+                    .map_err(|err| StreamCenterError::RemuxCodecFailed {
+                        context: "from video frame to flv video tag",
+                        source: err,
                     })?;
+                let bytes = buffer.split_freeze();
                 let tag_header = flv_formats::tag::flv_tag_header::FLVTagHeader {
                     tag_type: FLVTagType::Video,
-                    data_size: legacy_header
+                    data_size: header
                         .get_packet_bytes_count()
                         .checked_add(bytes.len())
                         .and_then(|v| v.to_u32())
@@ -345,10 +658,8 @@ impl MediaFrame {
                     body_with_filter: flv_formats::tag::flv_tag_body::FLVTagBodyWithFilter {
                         filter: None,
                         body: flv_formats::tag::flv_tag_body::FLVTagBody::Video {
-                            header: flv_formats::tag::video_tag_header::VideoTagHeader::Legacy(
-                                legacy_header,
-                            ),
-                            body: Bytes::from_owner(bytes),
+                            header,
+                            body: bytes,
                         },
                     },
                 })
@@ -358,64 +669,64 @@ impl MediaFrame {
                 config,
                 ..
             } => {
-                let frame_info = VideoFrameInfo {
-                    codec_id: config.as_ref().into(),
-                    frame_type: FrameType::SequenceStart,
-                    timestamp: MediaFrameTimestamp::with_timestamp_nano(*timestamp_nano),
-                };
+                let frame_info = VideoFrameInfo::new(
+                    config.as_ref().into(),
+                    FrameType::SequenceStart,
+                    MediaFrameTimestamp::with_timestamp_nano(*timestamp_nano),
+                );
                 let span = debug_span!("video_config", ?frame_info);
                 let _enter = span.enter();
-                let legacy_header: LegacyVideoTagHeader = (&frame_info).try_into()?;
+                let header = video_header.header_of(&frame_info)?;
+                let mut bytes = Vec::new();
+                let mut writer = io::Cursor::new(&mut bytes);
                 match config.as_ref() {
                     VideoConfig::H264(H264VideoConfig {
-                        sps: _,
-                        pps: _,
-                        sps_ext: _,
-                        avc_decoder_configuration_record,
-                    }) => {
-                        if let Some(record) = avc_decoder_configuration_record {
-                            let mut bytes = Vec::with_capacity(record.get_packet_bytes_count());
-                            let mut writer = io::Cursor::new(&mut bytes);
-                            record.write_to(&mut writer)?;
-                            let tag_header = flv_formats::tag::flv_tag_header::FLVTagHeader {
-                                tag_type: FLVTagType::Video,
-                                data_size: legacy_header
-                                    .get_packet_bytes_count()
-                                    .checked_add(bytes.len())
-                                    .and_then(|v| v.to_u32())
-                                    .unwrap(),
-                                timestamp: flv_dts_ms,
-                                filter_enabled: false,
-                            };
-                            tracing::debug!("video sequence header tag header: {:?}", tag_header);
-                            Ok(flv_formats::tag::FLVTag {
-                                tag_header,
-                                body_with_filter:
-                                    flv_formats::tag::flv_tag_body::FLVTagBodyWithFilter {
-                                        filter: None,
-                                        body: flv_formats::tag::flv_tag_body::FLVTagBody::Video {
-                                            header: flv_formats::tag::video_tag_header::VideoTagHeader::Legacy(legacy_header),
-                                            body: Bytes::from_owner(bytes),
-                                        },
-                                    },
-                            })
-                        } else {
-                            unimplemented!()
-                        }
-                    }
+                        avc_decoder_configuration_record: Some(record),
+                        ..
+                    }) => record.write_to(&mut writer)?,
+                    VideoConfig::AV1(Av1VideoConfig {
+                        av1_codec_configuration_record: Some(record),
+                        ..
+                    }) => record.write_to(&mut writer)?,
+                    _ => unimplemented!(),
                 }
+                let tag_header = flv_formats::tag::flv_tag_header::FLVTagHeader {
+                    tag_type: FLVTagType::Video,
+                    data_size: header
+                        .get_packet_bytes_count()
+                        .checked_add(bytes.len())
+                        .and_then(|v| v.to_u32())
+                        .unwrap(),
+                    timestamp: flv_dts_ms,
+                    filter_enabled: false,
+                };
+                tracing::debug!("video sequence header tag header: {:?}", tag_header);
+                Ok(flv_formats::tag::FLVTag {
+                    tag_header,
+                    body_with_filter: flv_formats::tag::flv_tag_body::FLVTagBodyWithFilter {
+                        filter: None,
+                        body: flv_formats::tag::flv_tag_body::FLVTagBody::Video {
+                            header,
+                            body: Bytes::from_owner(bytes),
+                        },
+                    },
+                })
             }
             Self::AudioConfig {
                 timestamp_nano,
                 sound_info,
                 config,
+                track_id,
             } => {
-                let frame_info = AudioFrameInfo {
-                    codec_id: config.as_ref().into(),
-                    frame_type: FrameType::SequenceStart,
-                    timestamp_nano: *timestamp_nano,
-                    sound_info: *sound_info,
-                };
+                let mut frame_info = AudioFrameInfo::new(
+                    config.as_ref().into(),
+                    FrameType::SequenceStart,
+                    sound_info.sound_rate,
+                    sound_info.sound_size,
+                    sound_info.sound_type,
+                    *timestamp_nano,
+                );
+                frame_info.track_id = *track_id;
                 let span = debug_span!("audio_config", ?frame_info);
                 let _enter = span.enter();
                 let legacy_header: LegacyAudioTagHeader = (&frame_info).try_into()?;
@@ -458,6 +769,140 @@ impl MediaFrame {
         }
     }
 
+    /// the flv tag followed by its previous tag size, as they go into a flv body
+    pub fn to_flv_tag_bytes(&self, nalu_size_length: u8) -> StreamCenterResult<Bytes> {
+        self.to_flv_tag_bytes_with_video_header(nalu_size_length, FlvVideoHeader::Legacy)
+    }
+
+    pub fn to_flv_tag_bytes_with_video_header(
+        &self,
+        nalu_size_length: u8,
+        video_header: FlvVideoHeader,
+    ) -> StreamCenterResult<Bytes> {
+        let tag = self.to_flv_tag_with_video_header(nalu_size_length, video_header)?;
+        flv_tag_to_bytes(&tag)
+    }
+
+    /// the tag that ends the video sequence of a codec, after which the player flushes its decoder,
+    /// it has no body
+    pub fn video_sequence_end_flv_tag(
+        codec_id: VideoCodecCommon,
+        timestamp_ms: u32,
+        video_header: FlvVideoHeader,
+    ) -> StreamCenterResult<FLVTag> {
+        let frame_info = VideoFrameInfo::new(
+            codec_id,
+            FrameType::SequenceEnd,
+            MediaFrameTimestamp::with_timestamp_ms(timestamp_ms.into()),
+        );
+        let header = video_header.header_of(&frame_info)?;
+        Ok(FLVTag {
+            tag_header: FLVTagHeader {
+                tag_type: FLVTagType::Video,
+                data_size: header.get_packet_bytes_count().to_u32().unwrap(),
+                timestamp: timestamp_ms,
+                filter_enabled: false,
+            },
+            body_with_filter: flv_formats::tag::flv_tag_body::FLVTagBodyWithFilter {
+                filter: None,
+                body: FLVTagBody::Video {
+                    header,
+                    body: Bytes::new(),
+                },
+            },
+        })
+    }
+
+    /// like `to_flv_tag`, but the sub-millisecond part of the timestamp is kept
+    /// in a TimestampOffsetNano ModEx, which takes an enhanced header,
+    /// for subscribers that negotiated ModEx and nano timestamps
+    pub fn to_flv_tag_with_timestamp_nano(
+        &self,
+        nalu_size_length: u8,
+        video_header: FlvVideoHeader,
+    ) -> StreamCenterResult<flv_formats::tag::FLVTag> {
+        let tag = self.to_flv_tag_with_video_header(nalu_size_length, video_header)?;
+        let timestamp_nano = (self.get_presentation_timestamp_ns() % 1_000_000)
+            .to_u32()
+            .unwrap();
+        if timestamp_nano == 0 {
+            return Ok(tag);
+        }
+        let (legacy_bytes_count, enhanced_bytes_count, body) = match &tag.body_with_filter.body {
+            FLVTagBody::Audio {
+                header: AudioTagHeader::Legacy(legacy_header),
+                body,
+            } if matches!(self, Self::Audio { .. }) => {
+                let mut header_info = AudioTagHeaderWithoutMultiTrack::from(legacy_header);
+                header_info.timestamp_nano = Some(timestamp_nano);
+                // codecs without a FourCC can only go with the legacy header
+                let Ok(ex_header): Result<ExAudioTagHeader, _> = (&header_info).try_into() else {
+                    return Ok(tag);
+                };
+                (
+                    legacy_header.get_packet_bytes_count(),
+                    ex_header.get_packet_bytes_count(),
+                    FLVTagBody::Audio {
+                        header: AudioTagHeader::Enhanced(ex_header),
+                        body: body.clone(),
+                    },
+                )
+            }
+            FLVTagBody::Video {
+                header: VideoTagHeader::Legacy(legacy_header),
+                body,
+            } if matches!(self, Self::Video { .. }) => {
+                let mut header_info = VideoTagHeaderWithoutMultiTrack::from(legacy_header);
+                header_info.timestamp_nano = Some(timestamp_nano);
+                let Ok(ex_header): Result<ExVideoTagHeader, _> = (&header_info).try_into() else {
+                    return Ok(tag);
+                };
+                (
+                    legacy_header.get_packet_bytes_count(),
+                    ex_header.get_packet_bytes_count(),
+                    FLVTagBody::Video {
+                        header: VideoTagHeader::Enhanced(ex_header),
+                        body: body.clone(),
+                    },
+                )
+            }
+            FLVTagBody::Video {
+                header: VideoTagHeader::Enhanced(ex_header),
+                body,
+            } if matches!(self, Self::Video { .. }) => {
+                let mut with_timestamp_nano = ex_header.clone();
+                with_timestamp_nano.packet_mod_ex.timestamp_nano = Some(timestamp_nano);
+                (
+                    ex_header.get_packet_bytes_count(),
+                    with_timestamp_nano.get_packet_bytes_count(),
+                    FLVTagBody::Video {
+                        header: VideoTagHeader::Enhanced(with_timestamp_nano),
+                        body: body.clone(),
+                    },
+                )
+            }
+            _ => return Ok(tag),
+        };
+        let data_size = tag
+            .tag_header
+            .data_size
+            .to_usize()
+            .and_then(|v| v.checked_sub(legacy_bytes_count))
+            .and_then(|v| v.checked_add(enhanced_bytes_count))
+            .and_then(|v| v.to_u32())
+            .unwrap();
+        Ok(flv_formats::tag::FLVTag {
+            tag_header: flv_formats::tag::flv_tag_header::FLVTagHeader {
+                data_size,
+                ..tag.tag_header
+            },
+            body_with_filter: flv_formats::tag::flv_tag_body::FLVTagBodyWithFilter {
+                filter: None,
+                body,
+            },
+        })
+    }
+
     pub fn from_flv_tag(tag: FLVTag, nalu_size_length: u8) -> StreamCenterResult<Self> {
         let span = tracing::debug_span!(
             "flv tag to media frame",
@@ -476,7 +921,17 @@ impl MediaFrame {
                     timestamp_nano=tag_header_info.timestamp_nano.unwrap_or(0),
                 );
                 let _ = span.enter();
-                let frame_info = AudioFrameInfo::new(
+                if tag_header_info.packet_type == AudioPacketType::MultichannelConfig {
+                    let config = AudioMultichannelConfig::read_from(&mut body.as_ref())?;
+                    tracing::debug!("got audio multichannel config: {:?}", config);
+                    return Ok(Self::AudioChannelConfig {
+                        timestamp_nano: 0,
+                        codec_id: tag_header_info.codec_id,
+                        config: Box::new(config),
+                        track_id: tag_header_info.track_id,
+                    });
+                }
+                let mut frame_info = AudioFrameInfo::new(
                     tag_header_info.codec_id,
                     tag_header_info.packet_type.try_into()?,
                     tag_header_info
@@ -509,6 +964,7 @@ impl MediaFrame {
                         })
                         .unwrap(),
                 );
+                frame_info.track_id = tag_header_info.track_id;
                 if frame_info.frame_type == FrameType::SequenceStart {
                     let audio_config = match frame_info.codec_id {
                         codec_common::audio::AudioCodecCommon::AAC => {
@@ -524,6 +980,7 @@ impl MediaFrame {
                         timestamp_nano: 0,
                         sound_info: frame_info.sound_info,
                         config: Box::new(audio_config),
+                        track_id: frame_info.track_id,
                     });
                 }
                 Ok(Self::Audio {
@@ -532,6 +989,36 @@ impl MediaFrame {
                 })
             }
             FLVTagBody::Script { ref value } => {
+                let timestamp_nano = tag
+                    .tag_header
+                    .timestamp
+                    .to_u64()
+                    .and_then(|v| v.checked_mul(1_000_000))
+                    .unwrap();
+                // rtmp publishers prefix the data with @setDataFrame
+                let value = match value.first().and_then(|v| v.try_as_str()) {
+                    Some(SET_DATA_FRAME) => &value[1..],
+                    _ => &value[..],
+                };
+                if let Some(name) = value.first().and_then(|v| v.try_as_str())
+                    && name != ON_META_DATA
+                {
+                    let mut bytes = Vec::new();
+                    for v in value {
+                        v.write_to(&mut bytes).map_err(|err| {
+                            StreamCenterError::RemuxAmfFailed {
+                                context: "from flv script tag to data frame",
+                                source: err,
+                            }
+                        })?;
+                    }
+                    return Ok(Self::Data {
+                        timestamp_nano,
+                        name: name.to_owned(),
+                        payload: bytes.into(),
+                    });
+                }
+
                 let mut bytes = Vec::new();
                 tag.body_with_filter.write_to(&mut bytes)?;
 
@@ -546,12 +1033,7 @@ impl MediaFrame {
                 }
 
                 Ok(Self::Script {
-                    timestamp_nano: tag
-                        .tag_header
-                        .timestamp
-                        .to_u64()
-                        .and_then(|v| v.checked_mul(1_000_000))
-                        .unwrap(),
+                    timestamp_nano,
                     on_meta_data: Box::new(Some(OnMetaData::from(map))),
                     payload: bytes.into(),
                 })
@@ -569,13 +1051,18 @@ impl MediaFrame {
                 let _enter = span.enter();
                 match tag_header_info.packet_type {
                     VideoPacketType::SequenceStart => {
-                        // avc decoder configuration record
+                        // avc decoder configuration record or av1 codec configuration record
                         let video_config = match tag_header_info.codec_id {
                             VideoCodecCommon::AVC => {
                                 let config =
                                     AvcDecoderConfigurationRecord::read_from(&mut body.reader())?;
                                 VideoConfig::from(config)
                             }
+                            VideoCodecCommon::AV1 => {
+                                let config =
+                                    Av1CodecConfigurationRecord::read_from(&mut body.reader())?;
+                                VideoConfig::from(config)
+                            }
                             _ => {
                                 todo!()
                             }
@@ -601,10 +1088,15 @@ impl MediaFrame {
                             Some(nalu_size_length),
                         )
                         .map_err(|err| {
-                            StreamCenterError::RemuxFailed(format!(
+                            tracing::error!(
                                 "demux video nalus for codec id: {:?} failed: {}",
-                                tag_header_info.codec_id, err
-                            ))
+                                tag_header_info.codec_id,
+                                err
+                            );
+                            StreamCenterError::RemuxCodecFailed {
+                                context: "from flv video tag to video nalus",
+                                source: err,
+                            }
                         })?;
                         let frame_type =
                             if tag_header_info.packet_type == VideoPacketType::SequenceEnd {
@@ -616,15 +1108,12 @@ impl MediaFrame {
                             } else {
                                 FrameType::CodedFrames
                             };
+                        // the tag timestamp is the dts, the composition time takes it to the pts
                         let timestamp = *MediaFrameTimestamp::with_timestamp_ms(
                             tag.tag_header.timestamp.to_u64().unwrap(),
                         )
-                        .apply_offset_ms(
-                            tag_header_info
-                                .composition_time
-                                .unwrap_or(0)
-                                .to_u64()
-                                .unwrap(),
+                        .apply_signed_offset_ms(
+                            tag_header_info.composition_time.unwrap_or(0).into(),
                         )
                         .apply_offset_nano(
                             tag_header_info
@@ -648,6 +1137,21 @@ impl MediaFrame {
     }
 }
 
+/// the bytes of a flv tag, the previous tag size after them.
+/// they are split off of a pooled buffer, which takes its allocation back once the serialized
+/// frames and the sinks drop all the tags in it
+pub fn flv_tag_to_bytes(tag: &FLVTag) -> StreamCenterResult<Bytes> {
+    let tag_size = tag
+        .tag_header
+        .data_size
+        .checked_add(FLVTagHeader::bytes_count().to_u32().unwrap())
+        .unwrap();
+    let mut buffer = pool::take(tag_size.to_usize().unwrap() + 4);
+    tag.write_to(&mut (&mut *buffer).writer())?;
+    buffer.put_u32(tag_size);
+    Ok(buffer.split_freeze())
+}
+
 #[derive(Debug)]
 pub struct Gop {
     pub media_frames: VecDeque<MediaFrame>,
@@ -656,6 +1160,8 @@ pub struct Gop {
     meta_tag_cnt: usize,
     first_video_dts_nano: u64,
     last_video_dts_nano: u64,
+    /// the payload bytes of the frames
+    bytes: u64,
 }
 
 impl Gop {
@@ -667,6 +1173,7 @@ impl Gop {
             meta_tag_cnt: 0,
             first_video_dts_nano: 0,
             last_video_dts_nano: 0,
+            bytes: 0,
         }
     }
 
@@ -674,6 +1181,7 @@ impl Gop {
     pub fn pop_front(&mut self) -> Option<MediaFrame> {
         let dropped = self.media_frames.pop_front();
         if let Some(frame) = dropped.as_ref() {
+            self.bytes -= frame.payload_bytes().to_u64().unwrap();
             if frame.is_audio() {
                 self.audio_tag_cnt -= 1;
             } else if frame.is_video() {
@@ -719,6 +1227,11 @@ impl Gop {
         self.last_video_dts_nano
     }
 
+    #[inline]
+    pub fn get_bytes(&self) -> u64 {
+        self.bytes
+    }
+
     #[inline]
     pub fn get_last_video_frame_mut(&mut self) -> Option<&mut MediaFrame> {
         self.media_frames
@@ -740,12 +1253,14 @@ impl Gop {
                 self.last_video_dts_nano = frame.get_decode_timestamp_ns();
             }
             MediaFrame::Audio { .. } => self.audio_tag_cnt += 1,
-            MediaFrame::AudioConfig { .. } => {
+            MediaFrame::AudioConfig { .. } | MediaFrame::AudioChannelConfig { .. } => {
                 self.audio_tag_cnt += 1;
             }
-            MediaFrame::Script { .. } => self.meta_tag_cnt += 1,
+            MediaFrame::Script { .. } | MediaFrame::Data { .. } => self.meta_tag_cnt += 1,
+            MediaFrame::EndOfStream { .. } | MediaFrame::SourceError { .. } => {}
         }
 
+        self.bytes += frame.payload_bytes().to_u64().unwrap();
         self.media_frames.push_back(frame);
     }
 }
@@ -759,7 +1274,10 @@ impl Default for Gop {
 #[derive(Debug)]
 pub struct GopQueue {
     pub video_config: Option<VideoConfig>, // video config
-    pub audio_config: Option<(AudioConfig, SoundInfoCommon)>, // audio config, sound info
+    /// audio config and sound info of each audio track
+    pub audio_configs: BTreeMap<u8, (AudioConfig, SoundInfoCommon)>,
+    /// channel layout and codec of the audio tracks the publisher sent one for
+    pub audio_channel_configs: BTreeMap<u8, (AudioMultichannelConfig, AudioCodecCommon)>,
     pub script_frame: Option<MediaFrame>,
     pub gops: VecDeque<Gop>,
     total_frame_cnt: u64,
@@ -768,13 +1286,20 @@ pub struct GopQueue {
     dropped_gops_cnt: u64,
     dropped_video_cnt: u64,
     dropped_audio_cnt: u64,
+    /// the payload bytes of the cached frames
+    bytes: u64,
+    /// the share of the cache in the memory budget of all the streams, no limit but the ones above if None
+    budget: Option<GopCacheAccount>,
+    /// the gops dropped for the budget
+    evicted_gops_cnt: u64,
 }
 
 impl GopQueue {
     pub fn new(max_duration_ms: u64, max_frame_cnt: u64) -> Self {
         Self {
             video_config: None,
-            audio_config: None,
+            audio_configs: BTreeMap::new(),
+            audio_channel_configs: BTreeMap::new(),
             script_frame: None,
             gops: VecDeque::new(),
             max_duration_ms,
@@ -783,6 +1308,55 @@ impl GopQueue {
             dropped_gops_cnt: 0,
             dropped_video_cnt: 0,
             dropped_audio_cnt: 0,
+            bytes: 0,
+            budget: None,
+            evicted_gops_cnt: 0,
+        }
+    }
+
+    pub fn set_budget(&mut self, account: GopCacheAccount) {
+        self.budget = Some(account);
+    }
+
+    #[inline]
+    pub fn audio_config(&self, track_id: u8) -> Option<&(AudioConfig, SoundInfoCommon)> {
+        self.audio_configs.get(&track_id)
+    }
+
+    /// the channel layout of the track as a frame, it goes out right after the audio config
+    pub fn audio_channel_config_frame(
+        &self,
+        track_id: u8,
+        timestamp_nano: u64,
+    ) -> Option<MediaFrame> {
+        self.audio_channel_configs
+            .get(&track_id)
+            .map(|(config, codec_id)| MediaFrame::AudioChannelConfig {
+                timestamp_nano,
+                codec_id: *codec_id,
+                config: Box::new(config.clone()),
+                track_id,
+            })
+    }
+
+    /// the channel count of the track, from its channel layout
+    pub fn audio_channels(&self, track_id: u8) -> Option<u8> {
+        self.audio_channel_configs
+            .get(&track_id)
+            .map(|(config, _)| config.channel_count)
+    }
+
+    /// drops all cached gops, e.g., they are encoded with outdated sequence headers
+    pub fn clear_gops(&mut self) {
+        while let Some(gop) = self.gops.pop_front() {
+            self.dropped_gops_cnt += 1;
+            self.dropped_video_cnt += gop.get_video_frame_cnt().to_u64().unwrap();
+            self.dropped_audio_cnt += gop.get_audio_frame_cnt().to_u64().unwrap();
+        }
+        self.total_frame_cnt = 0;
+        self.bytes = 0;
+        if let Some(account) = &self.budget {
+            account.charge(0, 0);
         }
     }
 
@@ -806,6 +1380,16 @@ impl GopQueue {
         self.dropped_audio_cnt
     }
 
+    #[inline]
+    pub fn get_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    #[inline]
+    pub fn get_evicted_gops_cnt(&self) -> u64 {
+        self.evicted_gops_cnt
+    }
+
     #[inline]
     fn accumulate_gops<'a, F>(&'a self, f: F) -> usize
     where
@@ -833,7 +1417,8 @@ impl GopQueue {
         self.accumulate_gops(|gop| gop.get_meta_frame_cnt())
     }
 
-    pub fn append_frame(&mut self, mut frame: MediaFrame) -> StreamCenterResult<()> {
+    /// whether the frame is kept in a gop, a consumer dumping the gops afterwards gets it
+    pub fn append_frame(&mut self, mut frame: MediaFrame) -> StreamCenterResult<bool> {
         let span = tracing::trace_span!("gop cache append frame");
         let _enter = span.enter();
 
@@ -849,7 +1434,7 @@ impl GopQueue {
                 frame.video_codec_id(),
                 frame.get_decode_timestamp_ns()
             );
-            return Ok(());
+            return Ok(false);
         }
         let first_dts = self
             .gops
@@ -877,10 +1462,7 @@ impl GopQueue {
                 dropped.as_ref().map_or(0, |v| v.get_meta_frame_cnt())
             );
             if let Some(gop) = dropped {
-                self.dropped_gops_cnt += 1;
-                self.dropped_video_cnt += gop.get_video_frame_cnt().to_u64().unwrap();
-                self.dropped_audio_cnt += gop.get_audio_frame_cnt().to_u64().unwrap();
-                self.total_frame_cnt -= gop.media_frames.len().to_u64().unwrap();
+                self.on_gop_dropped(&gop);
             }
         }
 
@@ -903,8 +1485,20 @@ impl GopQueue {
                 timestamp_nano: _,
                 sound_info,
                 config,
+                track_id,
             } => {
-                self.audio_config = Some((*config.clone(), *sound_info));
+                self.audio_configs
+                    .insert(*track_id, (*config.clone(), *sound_info));
+                is_sequence_header = true;
+            }
+            MediaFrame::AudioChannelConfig {
+                codec_id,
+                config,
+                track_id,
+                ..
+            } => {
+                self.audio_channel_configs
+                    .insert(*track_id, (*config.clone(), *codec_id));
                 is_sequence_header = true;
             }
             MediaFrame::Video {
@@ -929,28 +1523,76 @@ impl GopQueue {
                 is_sequence_header = true;
                 tracing::info!("meta, pts: {}, data: {:?}", pts, on_meta_data);
             }
+            MediaFrame::Data { .. } => {}
+            // not part of any gop, a new subscriber should not see it
+            MediaFrame::EndOfStream { .. } | MediaFrame::SourceError { .. } => return Ok(false),
         }
 
         if is_sequence_header {
             tracing::trace!("skip sequence header");
-            return Ok(());
+            return Ok(false);
         }
 
         if self.gops.is_empty() && is_video {
             self.dropped_video_cnt += 1;
-            return Ok(());
+            return Ok(false);
         }
 
         if self.gops.is_empty() {
             self.gops.push_back(Gop::new());
         }
 
+        self.bytes += frame.payload_bytes().to_u64().unwrap();
         self.gops
             .back_mut()
             .expect("this cannot be empty")
             .append_media_frame(frame);
         self.total_frame_cnt += 1;
+        self.charge_budget();
+
+        Ok(true)
+    }
+
+    fn on_gop_dropped(&mut self, gop: &Gop) {
+        self.dropped_gops_cnt += 1;
+        self.dropped_video_cnt += gop.get_video_frame_cnt().to_u64().unwrap();
+        self.dropped_audio_cnt += gop.get_audio_frame_cnt().to_u64().unwrap();
+        self.total_frame_cnt -= gop.media_frames.len().to_u64().unwrap();
+        self.bytes -= gop.get_bytes();
+    }
+
+    /// the bytes of all the gops but the latest ones kept whatever the budget
+    fn trimmable_bytes(&self, min_gops: usize) -> u64 {
+        self.gops
+            .iter()
+            .rev()
+            .skip(min_gops)
+            .map(|gop| gop.get_bytes())
+            .sum()
+    }
 
-        Ok(())
+    /// drops the oldest gops while the cache owes the budget,
+    /// the latest gop starting from the latest key frame is never dropped
+    fn charge_budget(&mut self) {
+        let Some(account) = self.budget.take() else {
+            return;
+        };
+        let min_gops = account.min_gops();
+        let mut owed = account.charge(self.bytes, self.trimmable_bytes(min_gops));
+        if owed > 0 {
+            while owed > 0 && self.gops.len() > min_gops {
+                let gop = self.gops.pop_front().expect("this cannot be empty");
+                tracing::debug!(
+                    "evicting a gop of {} bytes for the gop cache budget, {} bytes owed",
+                    gop.get_bytes(),
+                    owed
+                );
+                owed = owed.saturating_sub(gop.get_bytes());
+                self.on_gop_dropped(&gop);
+                self.evicted_gops_cnt += 1;
+            }
+            account.charge(self.bytes, self.trimmable_bytes(min_gops));
+        }
+        self.budget = Some(account);
     }
 }
@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+pub const DEFAULT_GOP_CACHE_BUDGET_BYTES: u64 = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GopCacheBudgetConfig {
+    /// the payload bytes the gop caches of all the streams take together, 0 for no limit
+    pub max_bytes: u64,
+    /// the latest gops a stream keeps whatever the budget, one at least,
+    /// so a late joiner starts from the latest key frame with the sequence headers before it
+    pub min_gops: usize,
+}
+
+impl Default for GopCacheBudgetConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_GOP_CACHE_BUDGET_BYTES,
+            min_gops: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct CacheUsage {
+    bytes: u64,
+    /// the bytes of the gops the cache may drop, all but the latest ones it keeps
+    trimmable_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct BudgetLedger {
+    next_account_id: u64,
+    caches: HashMap<u64, CacheUsage>,
+}
+
+/// the memory budget the gop caches of the streams share.
+/// once they are over it, the largest caches give up their oldest gops first,
+/// each cache drops what it owes the next time it appends a frame, on the task of its stream
+#[derive(Debug, Clone, Default)]
+pub struct GopCacheBudget {
+    config: GopCacheBudgetConfig,
+    ledger: Arc<Mutex<BudgetLedger>>,
+}
+
+impl GopCacheBudget {
+    pub fn new(config: GopCacheBudgetConfig) -> Self {
+        Self {
+            config,
+            ledger: Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> GopCacheBudgetConfig {
+        self.config
+    }
+
+    /// the account of a gop cache, the cache leaves the budget once it is dropped
+    pub fn open_account(&self) -> GopCacheAccount {
+        let mut ledger = self.ledger.lock().unwrap();
+        let id = ledger.next_account_id;
+        ledger.next_account_id += 1;
+        ledger.caches.insert(id, CacheUsage::default());
+        GopCacheAccount {
+            id,
+            budget: self.clone(),
+        }
+    }
+
+    /// the bytes of all the caches
+    pub fn used_bytes(&self) -> u64 {
+        self.ledger
+            .lock()
+            .unwrap()
+            .caches
+            .values()
+            .map(|usage| usage.bytes)
+            .sum()
+    }
+}
+
+#[derive(Debug)]
+pub struct GopCacheAccount {
+    id: u64,
+    budget: GopCacheBudget,
+}
+
+impl GopCacheAccount {
+    pub fn min_gops(&self) -> usize {
+        self.budget.config.min_gops.max(1)
+    }
+
+    /// reports the bytes of the cache and the ones it may drop,
+    /// gets the bytes it has to drop for all the caches to be within the budget
+    pub fn charge(&self, bytes: u64, trimmable_bytes: u64) -> u64 {
+        let mut ledger = self.budget.ledger.lock().unwrap();
+        ledger.caches.insert(
+            self.id,
+            CacheUsage {
+                bytes,
+                trimmable_bytes,
+            },
+        );
+        if self.budget.config.max_bytes == 0 {
+            return 0;
+        }
+        let used_bytes: u64 = ledger.caches.values().map(|usage| usage.bytes).sum();
+        let mut excess = used_bytes.saturating_sub(self.budget.config.max_bytes);
+        if excess == 0 {
+            return 0;
+        }
+        let mut caches: Vec<_> = ledger.caches.iter().collect();
+        // the larger first, the one opened earlier of the same size
+        caches.sort_by(|(id, usage), (other_id, other)| {
+            other.bytes.cmp(&usage.bytes).then(id.cmp(other_id))
+        });
+        for (id, usage) in caches {
+            let owed = excess.min(usage.trimmable_bytes);
+            if *id == self.id {
+                return owed;
+            }
+            excess -= owed;
+            if excess == 0 {
+                break;
+            }
+        }
+        0
+    }
+}
+
+impl Drop for GopCacheAccount {
+    fn drop(&mut self) {
+        self.budget.ledger.lock().unwrap().caches.remove(&self.id);
+    }
+}
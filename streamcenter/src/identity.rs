@@ -0,0 +1,227 @@
+//! the app and the name a stream is told by, validated and normalized once where they come in,
+//! so a stream published over one protocol is found by the players of any other
+
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use utils::media_url::MediaUrl;
+
+use crate::errors::{StreamCenterError, StreamCenterResult};
+
+pub const MAX_APP_BYTES: usize = 64;
+pub const MAX_STREAM_NAME_BYTES: usize = 255;
+
+/// what an app and a name have in common, a path segment of the urls of all protocols:
+/// not empty once trimmed, no slash, no control character, not `.` or `..`
+fn validate<'a>(part: &str, value: &'a str, max_bytes: usize) -> StreamCenterResult<&'a str> {
+    let invalid = |reason: &str| {
+        StreamCenterError::InvalidStreamIdentity(format!("{} {:?} {}", part, value, reason))
+    };
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(invalid("is empty"));
+    }
+    if trimmed.len() > max_bytes {
+        return Err(invalid(&format!("is longer than {} bytes", max_bytes)));
+    }
+    if trimmed == "." || trimmed == ".." {
+        return Err(invalid("is a relative path"));
+    }
+    if trimmed
+        .chars()
+        .any(|c| c == '/' || c == '\\' || c.is_control())
+    {
+        return Err(invalid("has a slash or a control character"));
+    }
+    Ok(trimmed)
+}
+
+/// implements what the parts of a stream identity share, they are strings once validated
+macro_rules! identity_part {
+    ($part:ident) => {
+        impl $part {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Deref for $part {
+            type Target = str;
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $part {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        /// so the maps by a part are looked up by a str
+        impl Borrow<str> for $part {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $part {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl FromStr for $part {
+            type Err = StreamCenterError;
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::new(s)
+            }
+        }
+
+        impl TryFrom<String> for $part {
+            type Error = StreamCenterError;
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                Self::new(&value)
+            }
+        }
+
+        impl From<$part> for String {
+            fn from(value: $part) -> Self {
+                value.0
+            }
+        }
+
+        impl PartialEq<str> for $part {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $part {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+/// the app of a stream. apps are names of the server rather than of the publishers,
+/// they are told without case, e.g., `Live` is `live`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct StreamApp(String);
+
+impl StreamApp {
+    pub fn new(app: &str) -> StreamCenterResult<Self> {
+        Ok(Self(
+            validate("app", app, MAX_APP_BYTES)?.to_ascii_lowercase(),
+        ))
+    }
+}
+
+identity_part!(StreamApp);
+
+/// the name of a stream in its app, kept as it is but trimmed, as it is often a key of the publisher
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct StreamName(String);
+
+impl StreamName {
+    pub fn new(name: &str) -> StreamCenterResult<Self> {
+        Ok(Self(
+            validate("stream name", name, MAX_STREAM_NAME_BYTES)?.to_owned(),
+        ))
+    }
+}
+
+identity_part!(StreamName);
+
+/// a stream, `app/name` in text and in json
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct StreamIdentity {
+    pub app: StreamApp,
+    pub name: StreamName,
+}
+
+impl StreamIdentity {
+    /// the app and the name are decoded already
+    pub fn new(app: &str, name: &str) -> StreamCenterResult<Self> {
+        Ok(Self {
+            app: StreamApp::new(app)?,
+            name: StreamName::new(name)?,
+        })
+    }
+
+    /// another stream of the same app
+    pub fn with_name(&self, name: &str) -> StreamCenterResult<Self> {
+        Ok(Self {
+            app: self.app.clone(),
+            name: StreamName::new(name)?,
+        })
+    }
+
+    /// the first two path segments of a url, decoded, the rest and the query are not the stream
+    pub fn from_media_url(url: &MediaUrl) -> StreamCenterResult<Self> {
+        let (app, name) = url
+            .app_and_stream()
+            .map_err(|err| StreamCenterError::InvalidStreamIdentity(err.to_string()))?;
+        Self::new(app, name)
+    }
+
+    /// a rtsp uri or a http url, e.g., `rtsp://host/live/test/trackID=1`
+    pub fn from_url(url: &str) -> StreamCenterResult<Self> {
+        let url = MediaUrl::parse(url)
+            .map_err(|err| StreamCenterError::InvalidStreamIdentity(err.to_string()))?;
+        Self::from_media_url(&url)
+    }
+
+    /// the stream a rtmp client publishes or plays, the name is relative to the app of the tcUrl
+    /// and may come with a query, e.g., `rtmp://host/live` and `test?token=abc`
+    pub fn from_rtmp(tc_url: &str, stream_name: &str) -> StreamCenterResult<Self> {
+        let url = MediaUrl::parse(tc_url)
+            .and_then(|url| url.with_stream(stream_name))
+            .map_err(|err| StreamCenterError::InvalidStreamIdentity(err.to_string()))?;
+        Self::from_media_url(&url)
+    }
+
+    /// the path of a request, e.g., `/live/test.flv?token=abc` of a http route
+    pub fn from_path(path_and_query: &str) -> StreamCenterResult<Self> {
+        let url = MediaUrl::new("http", "localhost", None)
+            .with_path(path_and_query)
+            .map_err(|err| StreamCenterError::InvalidStreamIdentity(err.to_string()))?;
+        Self::from_media_url(&url)
+    }
+}
+
+impl fmt::Display for StreamIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.app, self.name)
+    }
+}
+
+/// parses `app/name`, the form of Display
+impl FromStr for StreamIdentity {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once('/') {
+            Some((app, name)) if !app.trim().is_empty() && !name.trim().is_empty() => {
+                Self::new(app, name)
+            }
+            _ => Err(StreamCenterError::InvalidStreamKey(s.to_owned())),
+        }
+    }
+}
+
+impl TryFrom<String> for StreamIdentity {
+    type Error = StreamCenterError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<StreamIdentity> for String {
+    fn from(value: StreamIdentity) -> Self {
+        value.to_string()
+    }
+}
@@ -0,0 +1,82 @@
+use codec_common::video::{H264VideoConfig, VideoCodecCommon, VideoConfig, VideoFrameUnit};
+use codec_h264::{annexb, nalu::NalUnit, nalu_type::NALUType};
+use utils::traits::writer::WriteTo;
+
+use crate::{errors::StreamCenterResult, gop::MediaFrame};
+
+/// the latest IDR access unit of a stream,
+/// previews are made of it without decoding the stream
+#[derive(Debug, Clone)]
+pub struct KeyframeSnapshot {
+    pub codec_id: VideoCodecCommon,
+    pub timestamp_nano: u64,
+    /// the active sps and pps go ahead of the nalus of the access unit
+    pub nal_units: Vec<NalUnit>,
+}
+
+impl KeyframeSnapshot {
+    /// None if the frame is not an IDR access unit,
+    /// or no sps and pps are known for it, in band or from the sequence header
+    pub fn from_frame(frame: &MediaFrame, video_config: Option<&VideoConfig>) -> Option<Self> {
+        let MediaFrame::Video {
+            frame_info,
+            payload: VideoFrameUnit::H264 { nal_units },
+        } = frame
+        else {
+            return None;
+        };
+        let has = |nalu_type: NALUType| {
+            nal_units
+                .iter()
+                .any(|nalu| nalu.header.nal_unit_type == nalu_type)
+        };
+        if !frame.is_video_key_frame() || !has(NALUType::IDRSlice) {
+            return None;
+        }
+
+        let mut snapshot_nalus = Vec::with_capacity(nal_units.len() + 2);
+        if !has(NALUType::SPS) || !has(NALUType::PPS) {
+            let Some(VideoConfig::H264(H264VideoConfig {
+                sps: Some(sps),
+                pps: Some(pps),
+                ..
+            })) = video_config
+            else {
+                return None;
+            };
+            snapshot_nalus.push(sps.into());
+            snapshot_nalus.push(pps.into());
+        }
+        snapshot_nalus.extend(nal_units.iter().cloned());
+        Some(Self {
+            codec_id: frame_info.codec_id,
+            timestamp_nano: frame_info.timestamp.pts(),
+            nal_units: snapshot_nalus,
+        })
+    }
+
+    fn nalu_bytes(&self) -> StreamCenterResult<Vec<Vec<u8>>> {
+        self.nal_units
+            .iter()
+            .map(|nalu| {
+                let mut bytes = Vec::new();
+                nalu.write_to(&mut bytes)?;
+                Ok(bytes)
+            })
+            .collect()
+    }
+
+    /// start codes ahead of each nalu, as in a .h264 elementary stream
+    pub fn to_annexb(&self) -> StreamCenterResult<Vec<u8>> {
+        let mut result = Vec::new();
+        annexb::write_annexb(self.nalu_bytes()?.iter().map(Vec::as_slice), &mut result)?;
+        Ok(result)
+    }
+
+    /// each nalu prefixed by its length in 4 bytes, as in flv and mp4
+    pub fn to_avcc(&self) -> StreamCenterResult<Vec<u8>> {
+        let mut result = Vec::new();
+        annexb::write_avcc(self.nalu_bytes()?.iter().map(Vec::as_slice), 3, &mut result)?;
+        Ok(result)
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+pub const DEFAULT_LATENCY_WINDOW: Duration = Duration::from_secs(10);
+
+/// upper bounds of the buckets in milliseconds, anything above the last one goes to the overflow bucket
+pub const LATENCY_BUCKET_BOUNDS_MS: [u64; 13] =
+    [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000, 10000];
+
+const BUCKET_COUNT: usize = LATENCY_BUCKET_BOUNDS_MS.len() + 1;
+
+/// the window slides slot by slot
+const WINDOW_SLOTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyConfig {
+    /// percentiles are computed over the latencies recorded within the window
+    pub window: Duration,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_LATENCY_WINDOW,
+        }
+    }
+}
+
+/// ingest to sink latency of a stream, in milliseconds.
+/// the percentiles are the upper bound of the bucket they fall in, capped by the max
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LatencySlot {
+    /// which slot duration since the origin the counts belong to
+    epoch: u64,
+    counts: [u64; BUCKET_COUNT],
+    max_us: u64,
+}
+
+/// fixed buckets over a ring of time slots, recording never allocates
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    origin: Instant,
+    slot_duration: Duration,
+    slots: [LatencySlot; WINDOW_SLOTS],
+}
+
+impl LatencyHistogram {
+    pub fn new(origin: Instant, window: Duration) -> Self {
+        assert!(!window.is_zero());
+        Self {
+            origin,
+            slot_duration: (window / WINDOW_SLOTS as u32).max(Duration::from_millis(1)),
+            slots: [LatencySlot::default(); WINDOW_SLOTS],
+        }
+    }
+
+    #[inline]
+    fn epoch_of(&self, now: Instant) -> u64 {
+        (now.saturating_duration_since(self.origin).as_nanos() / self.slot_duration.as_nanos())
+            as u64
+    }
+
+    #[inline]
+    pub fn bucket_of(latency: Duration) -> usize {
+        let latency_us = latency.as_micros();
+        LATENCY_BUCKET_BOUNDS_MS.partition_point(|bound| (*bound as u128) * 1000 < latency_us)
+    }
+
+    pub fn record(&mut self, now: Instant, latency: Duration) {
+        let epoch = self.epoch_of(now);
+        let slot = &mut self.slots[epoch as usize % WINDOW_SLOTS];
+        if slot.epoch != epoch {
+            *slot = LatencySlot {
+                epoch,
+                ..Default::default()
+            };
+        }
+        slot.counts[Self::bucket_of(latency)] += 1;
+        slot.max_us = slot.max_us.max(latency.as_micros() as u64);
+    }
+
+    pub fn summary(&self, now: Instant) -> LatencySummary {
+        let epoch = self.epoch_of(now);
+        let mut counts = [0u64; BUCKET_COUNT];
+        let mut max_us = 0;
+        for slot in self
+            .slots
+            .iter()
+            .filter(|slot| slot.epoch <= epoch && epoch - slot.epoch < WINDOW_SLOTS as u64)
+        {
+            for (total, count) in counts.iter_mut().zip(slot.counts.iter()) {
+                *total += count;
+            }
+            max_us = max_us.max(slot.max_us);
+        }
+        let count: u64 = counts.iter().sum();
+        let max_ms = max_us.div_ceil(1000);
+        let percentile = |quantile: u64| {
+            if count == 0 {
+                return 0;
+            }
+            let rank = (count * quantile).div_ceil(100);
+            let mut seen = 0;
+            for (bucket, bucket_count) in counts.iter().enumerate() {
+                seen += bucket_count;
+                if seen >= rank {
+                    return LATENCY_BUCKET_BOUNDS_MS
+                        .get(bucket)
+                        .map_or(max_ms, |bound| (*bound).min(max_ms));
+                }
+            }
+            max_ms
+        };
+        LatencySummary {
+            count,
+            p50_ms: percentile(50),
+            p95_ms: percentile(95),
+            max_ms,
+        }
+    }
+}
+
+/// shared by the stream source and the sinks of a stream,
+/// the sinks record when they write a frame out
+#[derive(Debug, Clone)]
+pub struct LatencyProbe {
+    histogram: Arc<Mutex<LatencyHistogram>>,
+}
+
+impl LatencyProbe {
+    pub fn new(config: LatencyConfig) -> Self {
+        Self {
+            histogram: Arc::new(Mutex::new(LatencyHistogram::new(
+                Self::now(),
+                config.window,
+            ))),
+        }
+    }
+
+    /// follows the tokio clock, so it can be paused in tests
+    #[inline]
+    pub fn now() -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    /// takes the ingest time of the frame, see [`crate::gop::MediaFrame::get_ingest_time`],
+    /// frames without one are skipped
+    pub fn on_frame_sent(&self, ingest_time: Option<Instant>) {
+        let Some(ingest_time) = ingest_time else {
+            return;
+        };
+        let now = Self::now();
+        if let Ok(mut histogram) = self.histogram.lock() {
+            histogram.record(now, now.saturating_duration_since(ingest_time));
+        }
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        self.histogram
+            .lock()
+            .map(|histogram| histogram.summary(Self::now()))
+            .unwrap_or_default()
+    }
+}
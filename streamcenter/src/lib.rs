@@ -1,15 +1,50 @@
 #![feature(error_generic_member_access)]
 
+use std::collections::HashMap;
+
 use codec_common::{audio::AudioCodecCommon, video::VideoCodecCommon};
 use flv_formats::tag::on_meta_data::OnMetaData;
+pub mod alias;
+pub mod audio_continuity;
+pub mod audio_track;
+pub mod backpressure;
+pub mod drain;
+pub mod dvr;
+pub mod end_of_stream;
 pub mod errors;
 pub mod events;
+pub mod failover;
+#[cfg(feature = "frame-crc")]
+pub mod frame_crc;
 pub mod frame_info;
 pub mod gop;
+pub mod gop_budget;
+pub mod identity;
+pub mod keyframe;
+pub mod latency;
+mod metrics;
 pub mod mix_queue;
+pub mod notification;
+pub mod parameter_sets;
+pub mod playback;
+pub mod rate_estimate;
+pub mod recovery_point;
+pub mod rtcp_peer;
+pub mod rtmp_control;
+pub mod rtp_receive;
+pub mod serialized;
 pub mod signal;
+pub mod slice_integrity;
+pub mod snapshot;
 pub mod stream_center;
 pub mod stream_source;
+pub mod takeover;
+pub mod timestamp_normalization;
+pub mod trace;
+pub mod watchdog;
+
+#[cfg(test)]
+mod test;
 
 pub fn make_fake_on_meta_data(
     audio_codec: AudioCodecCommon,
@@ -18,6 +53,7 @@ pub fn make_fake_on_meta_data(
     width: f64,
 ) -> OnMetaData {
     OnMetaData {
+        audio_channels: None,
         audio_codec_id: Some(audio_codec),
         audio_data_rate: None,
         audio_delay: None,
@@ -36,5 +72,6 @@ pub fn make_fake_on_meta_data(
         audio_track_id_info_map: None,
         video_track_id_info_map: None,
         keyframes: None,
+        extra_fields: HashMap::new(),
     }
 }
@@ -0,0 +1,221 @@
+use std::sync::LazyLock;
+
+use utils::metrics::{self, Counter, Family, Gauge, GaugeGuard};
+
+use crate::{
+    identity::StreamIdentity,
+    stream_source::{PlayProtocol, PublishProtocol},
+};
+
+static PUBLISHERS_ACTIVE: LazyLock<Family<Gauge>> = LazyLock::new(|| {
+    metrics::global().gauge_family(
+        "media_server_publishers_active",
+        "streams being published",
+        &["protocol"],
+    )
+});
+
+static SUBSCRIBERS_ACTIVE: LazyLock<Family<Gauge>> = LazyLock::new(|| {
+    metrics::global().gauge_family(
+        "media_server_subscribers_active",
+        "subscribers of all the streams",
+        &["protocol"],
+    )
+});
+
+static FRAMES_IN: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_stream_frames_in_total",
+        "frames received from the publisher of a stream",
+        &["stream"],
+    )
+});
+
+static BYTES_IN: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_stream_bytes_in_total",
+        "payload bytes received from the publisher of a stream",
+        &["stream"],
+    )
+});
+
+static FRAMES_OUT: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_stream_frames_out_total",
+        "frames fanned out to the subscribers of a stream",
+        &["stream"],
+    )
+});
+
+static BYTES_OUT: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_stream_bytes_out_total",
+        "payload bytes fanned out to the subscribers of a stream",
+        &["stream"],
+    )
+});
+
+static MIX_QUEUE_CORRECTIONS: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_mix_queue_corrections_total",
+        "frames the mix queue put back in dts order",
+        &["stream"],
+    )
+});
+
+static MIX_QUEUE_DROPPED: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_mix_queue_dropped_total",
+        "frames dropped as the mix queue is full",
+        &["stream"],
+    )
+});
+
+static CORRUPT_FRAMES: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_stream_corrupt_frames_total",
+        "h264 access units of the publisher of a stream whose slices do not cover their pictures",
+        &["stream"],
+    )
+});
+
+static CORRUPT_FRAMES_DROPPED: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_stream_corrupt_frames_dropped_total",
+        "video frames of a stream dropped as corrupt or referring to a corrupt one",
+        &["stream"],
+    )
+});
+
+static GOP_CACHE_BYTES: LazyLock<Family<Gauge>> = LazyLock::new(|| {
+    metrics::global().gauge_family(
+        "media_server_gop_cache_bytes",
+        "payload bytes of the frames in the gop cache of a stream",
+        &["stream"],
+    )
+});
+
+static GOP_CACHE_EVICTIONS: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_gop_cache_evictions_total",
+        "gops dropped from the gop cache of a stream for the memory budget of all the streams",
+        &["stream"],
+    )
+});
+
+static GOP_CACHE_KEYFRAME_WAITS: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_gop_cache_keyframe_waits_total",
+        "subscribers of the video of a stream that found no key frame cached and wait for the next one",
+        &["stream"],
+    )
+});
+
+static RTP_BYTES_BUFFERED: LazyLock<Family<Gauge>> = LazyLock::new(|| {
+    metrics::global().gauge_family(
+        "media_server_stream_rtp_bytes_buffered",
+        "bytes held by the buffers reassembling the h264 of an rtp based publisher, as of its latest reports",
+        &["stream"],
+    )
+});
+
+static RTP_BUFFER_DISCONTINUITIES: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_stream_rtp_buffer_discontinuities_total",
+        "times the h264 reassembly buffers of an rtp based publisher dropped data to fit in their budgets",
+        &["stream"],
+    )
+});
+
+#[cfg(feature = "frame-crc")]
+static FRAME_CRC_MISMATCHES: LazyLock<Family<Counter>> = LazyLock::new(|| {
+    metrics::global().counter_family(
+        "media_server_frame_crc_mismatches_total",
+        "frames whose payload a stage changed while it should have kept it",
+        &["stage"],
+    )
+});
+
+fn publish_protocol_label(protocol: PublishProtocol) -> &'static str {
+    match protocol {
+        PublishProtocol::RTMP => "rtmp",
+        PublishProtocol::RTSP => "rtsp",
+        PublishProtocol::VOD => "vod",
+        PublishProtocol::SRT => "srt",
+        PublishProtocol::Embedded => "embedded",
+        PublishProtocol::Relay => "relay",
+    }
+}
+
+fn play_protocol_label(protocol: PlayProtocol) -> &'static str {
+    match protocol {
+        PlayProtocol::RTMP => "rtmp",
+        PlayProtocol::HTTPFLV => "http_flv",
+        PlayProtocol::RTSP => "rtsp",
+        PlayProtocol::RTSPMulticast => "rtsp_multicast",
+        PlayProtocol::Embedded => "embedded",
+        PlayProtocol::Relay => "relay",
+    }
+}
+
+/// the series of a stream, looked up once when the stream source is created,
+/// the stream key label is bounded by the series limit of the families
+#[derive(Debug)]
+pub(crate) struct StreamMetrics {
+    pub frames_in: Counter,
+    pub bytes_in: Counter,
+    pub frames_out: Counter,
+    pub bytes_out: Counter,
+    pub mix_queue_corrections: Counter,
+    pub mix_queue_dropped: Counter,
+    pub corrupt_frames: Counter,
+    pub corrupt_frames_dropped: Counter,
+    pub gop_cache_bytes: Gauge,
+    pub gop_cache_evictions: Counter,
+    pub gop_cache_keyframe_waits: Counter,
+    pub rtp_bytes_buffered: Gauge,
+    pub rtp_buffer_discontinuities: Counter,
+    _publishing: GaugeGuard,
+}
+
+impl StreamMetrics {
+    pub fn new(identifier: &StreamIdentity, publish_protocol: PublishProtocol) -> Self {
+        let stream = identifier.to_string();
+        let labels = [stream.as_str()];
+        Self {
+            frames_in: FRAMES_IN.with_labels(&labels),
+            bytes_in: BYTES_IN.with_labels(&labels),
+            frames_out: FRAMES_OUT.with_labels(&labels),
+            bytes_out: BYTES_OUT.with_labels(&labels),
+            mix_queue_corrections: MIX_QUEUE_CORRECTIONS.with_labels(&labels),
+            mix_queue_dropped: MIX_QUEUE_DROPPED.with_labels(&labels),
+            corrupt_frames: CORRUPT_FRAMES.with_labels(&labels),
+            corrupt_frames_dropped: CORRUPT_FRAMES_DROPPED.with_labels(&labels),
+            gop_cache_bytes: GOP_CACHE_BYTES.with_labels(&labels),
+            gop_cache_evictions: GOP_CACHE_EVICTIONS.with_labels(&labels),
+            gop_cache_keyframe_waits: GOP_CACHE_KEYFRAME_WAITS.with_labels(&labels),
+            rtp_bytes_buffered: RTP_BYTES_BUFFERED.with_labels(&labels),
+            rtp_buffer_discontinuities: RTP_BUFFER_DISCONTINUITIES.with_labels(&labels),
+            _publishing: PUBLISHERS_ACTIVE
+                .with_labels(&[publish_protocol_label(publish_protocol)])
+                .guard(),
+        }
+    }
+
+    pub fn subscribers(protocol: PlayProtocol) -> Gauge {
+        SUBSCRIBERS_ACTIVE.with_labels(&[play_protocol_label(protocol)])
+    }
+
+    #[cfg(feature = "frame-crc")]
+    pub fn frame_crc_mismatches(stage: &str) -> Counter {
+        FRAME_CRC_MISMATCHES.with_labels(&[stage])
+    }
+}
+
+/// the cache and the buffers of a stream gone hold nothing
+impl Drop for StreamMetrics {
+    fn drop(&mut self) {
+        self.gop_cache_bytes.set(0);
+        self.rtp_bytes_buffered.set(0);
+    }
+}
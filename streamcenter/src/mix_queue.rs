@@ -4,9 +4,13 @@ use utils::traits::buffer::GenericSequencer;
 
 #[derive(Debug)]
 pub struct MixQueue {
-    pub media_frames: BTreeMap<u64, MediaFrame>,
+    /// keyed by dts and arrival order, so frames sharing a dts are all kept
+    pub media_frames: BTreeMap<(u64, u64), MediaFrame>,
     video_cnt: usize,
     audio_cnt: usize,
+    enqueued_cnt: u64,
+    /// frames that arrived behind a later queued one
+    reordered_cnt: u64,
     pure_av_max_frame_count: usize,
     capacity: usize,
 }
@@ -21,9 +25,29 @@ impl MixQueue {
             capacity,
             video_cnt: 0,
             audio_cnt: 0,
+            enqueued_cnt: 0,
+            reordered_cnt: 0,
         }
     }
 
+    /// drains every queued frame in dts order, regardless of the av balance
+    pub fn dump_all(&mut self) -> Vec<MediaFrame> {
+        self.video_cnt = 0;
+        self.audio_cnt = 0;
+        std::mem::take(&mut self.media_frames)
+            .into_values()
+            .collect()
+    }
+
+    /// 0 to 1, how much of the capacity is taken
+    pub fn fill(&self) -> f64 {
+        (self.media_frames.len() as f64 / self.capacity as f64).min(1.0)
+    }
+
+    pub fn reordered_count(&self) -> u64 {
+        self.reordered_cnt
+    }
+
     fn try_dump_one(&mut self) -> Option<MediaFrame> {
         if self.audio_cnt == 0 && self.video_cnt == 0 {
             return None;
@@ -37,7 +61,7 @@ impl MixQueue {
         if let Some((_, frame)) = self.media_frames.pop_first() {
             if frame.is_video() {
                 self.video_cnt -= 1;
-            } else {
+            } else if frame.is_audio() {
                 self.audio_cnt -= 1;
             }
             return Some(frame);
@@ -66,11 +90,19 @@ impl GenericSequencer for MixQueue {
             self.video_cnt += 1;
         } else if packet.is_audio() {
             self.audio_cnt += 1;
-        } else {
-            unreachable!("MixQueue only supports audio and video packets");
+        } else if !packet.is_data() {
+            unreachable!("MixQueue only supports audio, video and data packets");
+        }
+        let dts = packet.get_decode_timestamp_ns();
+        if self
+            .media_frames
+            .last_key_value()
+            .is_some_and(|((last_dts, _), _)| *last_dts > dts)
+        {
+            self.reordered_cnt += 1;
         }
-        self.media_frames
-            .insert(packet.get_decode_timestamp_ns(), packet);
+        self.media_frames.insert((dts, self.enqueued_cnt), packet);
+        self.enqueued_cnt += 1;
         Ok(())
     }
 
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use crate::{
+    drain::DrainOutcome,
+    events::StreamConfigChange,
+    identity::StreamIdentity,
+    stream_source::{PlayProtocol, PublishProtocol},
+    takeover::KickReason,
+};
+use uuid::Uuid;
+
+/// notifications kept for a slow receiver, it misses the older ones beyond this
+pub const DEFAULT_NOTIFICATION_CAPACITY: usize = 256;
+
+/// what happened to the streams of a stream center, broadcast to whoever watches it,
+/// e.g., a host process embedding the servers
+#[derive(Debug, Clone)]
+pub enum StreamNotification {
+    Published {
+        stream_id: StreamIdentity,
+        protocol: PublishProtocol,
+    },
+    /// the source of a stream in a restored snapshot published it again
+    Restored {
+        stream_id: StreamIdentity,
+        protocol: PublishProtocol,
+    },
+    /// the publisher unpublished the stream
+    Unpublished { stream_id: StreamIdentity },
+    /// the stream center took the stream away from its publisher
+    Kicked {
+        stream_id: StreamIdentity,
+        reason: KickReason,
+    },
+    ConfigChanged {
+        stream_id: StreamIdentity,
+        change: StreamConfigChange,
+    },
+    PublisherStalled {
+        stream_id: StreamIdentity,
+        idle: Duration,
+    },
+    PublisherResumed {
+        stream_id: StreamIdentity,
+        stalled_for: Duration,
+    },
+    /// the timestamps of the publisher jumped, the sinks get a frame marked as a discontinuity
+    TimestampDiscontinuity {
+        stream_id: StreamIdentity,
+        jump_nano: i64,
+    },
+    /// a publisher drained from the server is gone
+    Drained {
+        stream_id: StreamIdentity,
+        outcome: DrainOutcome,
+    },
+    /// the alias points to another stream, or to none if it was removed
+    AliasChanged {
+        alias: StreamIdentity,
+        canonical: Option<StreamIdentity>,
+        previous: Option<StreamIdentity>,
+    },
+    /// a subscriber asked for a stream which is not published, e.g., for a relay to pull it
+    SubscribeMissed {
+        stream_id: StreamIdentity,
+        protocol: PlayProtocol,
+    },
+    /// a subscriber asked for the stream by its own name, see SubscribedViaAlias for the others
+    Subscribed {
+        stream_id: StreamIdentity,
+        subscriber_id: Uuid,
+        protocol: PlayProtocol,
+    },
+    Unsubscribed {
+        stream_id: StreamIdentity,
+        subscriber_id: Uuid,
+    },
+    /// a subscriber asked for the alias and plays the stream it points to
+    SubscribedViaAlias {
+        stream_id: StreamIdentity,
+        alias: StreamIdentity,
+        subscriber_id: Uuid,
+        protocol: PlayProtocol,
+    },
+}
+
+impl StreamNotification {
+    pub fn stream_id(&self) -> &StreamIdentity {
+        match self {
+            Self::Published { stream_id, .. }
+            | Self::Restored { stream_id, .. }
+            | Self::Unpublished { stream_id }
+            | Self::Kicked { stream_id, .. }
+            | Self::ConfigChanged { stream_id, .. }
+            | Self::PublisherStalled { stream_id, .. }
+            | Self::PublisherResumed { stream_id, .. }
+            | Self::TimestampDiscontinuity { stream_id, .. }
+            | Self::Drained { stream_id, .. }
+            | Self::SubscribeMissed { stream_id, .. }
+            | Self::Subscribed { stream_id, .. }
+            | Self::Unsubscribed { stream_id, .. }
+            | Self::SubscribedViaAlias { stream_id, .. } => stream_id,
+            Self::AliasChanged { alias, .. } => alias,
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! the h264 parameter sets of the streams, checked once as they come in and placed for each sink
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use codec_common::video::{H264VideoConfig, VideoConfig, VideoFrameUnit};
+use codec_h264::{
+    avc_decoder_configuration_record::AvcDecoderConfigurationRecord, nalu::NalUnit,
+    nalu_type::NALUType, pps::Pps, sps::Sps, sps::chroma_format_idc::ChromaFormatIdc,
+};
+
+use crate::{gop::MediaFrame, stream_source::PlayProtocol};
+
+#[inline]
+fn is_parameter_set(nal: &NalUnit) -> bool {
+    matches!(nal.header.nal_unit_type, NALUType::SPS | NALUType::PPS)
+}
+
+/// the parameter sets are told apart by the hash of their content written out again,
+/// so the same one in a sequence header and in band hashes the same however it was encoded
+fn content_hash(nal: &NalUnit) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    nal.body.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// the parameter sets a stream has in effect, by id, from its sequence headers and the ones in band.
+/// exact duplicates in an access unit are dropped, and a parameter set changing under an id it had
+/// makes a new sequence header
+#[derive(Debug, Default)]
+pub(crate) struct ParameterSetTracker {
+    sps: HashMap<u8, (u64, Sps)>,
+    pps: HashMap<u8, (u64, Pps)>,
+    /// the ids of the latest ones, they make the sequence header
+    latest: Option<(u8, u8)>,
+    has_config: bool,
+}
+
+impl ParameterSetTracker {
+    /// the parameter sets of a sequence header of the publisher
+    pub(crate) fn on_video_config(&mut self, config: &VideoConfig) {
+        let VideoConfig::H264(H264VideoConfig { sps, pps, .. }) = config else {
+            return;
+        };
+        self.has_config = true;
+        if let Some(sps) = sps {
+            self.sps.insert(
+                sps.seq_parameter_set_id,
+                (content_hash(&sps.into()), sps.clone()),
+            );
+        }
+        if let Some(pps) = pps {
+            self.pps.insert(
+                pps.pic_parameter_set_id,
+                (content_hash(&pps.into()), pps.clone()),
+            );
+        }
+        if let (Some(sps), Some(pps)) = (sps, pps) {
+            self.latest = Some((sps.seq_parameter_set_id, pps.pic_parameter_set_id));
+        }
+    }
+
+    /// notes an in band parameter set, true if it changed the content under its id
+    fn on_parameter_set(&mut self, nal: &NalUnit) -> bool {
+        match nal.header.nal_unit_type {
+            NALUType::SPS => {
+                let Ok(sps) = Sps::try_from(nal) else {
+                    tracing::warn!("in band sps can not be parsed, it is left as it is");
+                    return false;
+                };
+                let id = sps.seq_parameter_set_id;
+                let hash = content_hash(&(&sps).into());
+                let previous = self.sps.insert(id, (hash, sps));
+                self.latest = self.latest.map(|(_, pps)| (id, pps));
+                previous.is_some_and(|(previous, _)| previous != hash)
+            }
+            NALUType::PPS => {
+                // the pps is parsed with the chroma format of the latest sps
+                let chroma_format_idc = self
+                    .latest
+                    .and_then(|(sps, _)| self.sps.get(&sps))
+                    .and_then(|(_, sps)| sps.get_chroma_format_idc())
+                    .unwrap_or(ChromaFormatIdc::Chroma420);
+                let Ok(pps) = Pps::try_from((chroma_format_idc, nal)) else {
+                    tracing::warn!("in band pps can not be parsed, it is left as it is");
+                    return false;
+                };
+                let (id, sps_id) = (pps.pic_parameter_set_id, pps.seq_parameter_set_id);
+                let hash = content_hash(&(&pps).into());
+                let previous = self.pps.insert(id, (hash, pps));
+                self.latest = Some((sps_id, id));
+                previous.is_some_and(|(previous, _)| previous != hash)
+            }
+            _ => false,
+        }
+    }
+
+    /// the pps of the id and the sps it refers to
+    pub(crate) fn get(&self, pps_id: u64) -> Option<(&Sps, &Pps)> {
+        let (_, pps) = self.pps.get(&u8::try_from(pps_id).ok()?)?;
+        let (_, sps) = self.sps.get(&pps.seq_parameter_set_id)?;
+        Some((sps, pps))
+    }
+
+    /// drops the exact duplicates of the parameter sets in the access unit and notes the rest.
+    /// a sequence header of the latest parameter sets is returned if one changed under its id,
+    /// or if the stream has none yet
+    pub(crate) fn normalize(&mut self, frame: &mut MediaFrame) -> Option<MediaFrame> {
+        let dts_nano = frame.get_decode_timestamp_ns();
+        let MediaFrame::Video {
+            payload: VideoFrameUnit::H264 { nal_units },
+            ..
+        } = frame
+        else {
+            return None;
+        };
+        if !nal_units.iter().any(is_parameter_set) {
+            return None;
+        }
+        let mut seen: Vec<NalUnit> = Vec::new();
+        let before = nal_units.len();
+        nal_units.retain(|nal| {
+            if !is_parameter_set(nal) {
+                return true;
+            }
+            if seen.iter().any(|seen| {
+                seen.header.nal_unit_type == nal.header.nal_unit_type && seen.body == nal.body
+            }) {
+                return false;
+            }
+            seen.push(nal.clone());
+            true
+        });
+        if nal_units.len() < before {
+            tracing::debug!(
+                "drop {} duplicated parameter sets of the access unit at {}ms",
+                before - nal_units.len(),
+                dts_nano / 1_000_000
+            );
+        }
+
+        let mut changed = false;
+        for nal in &seen {
+            if self.on_parameter_set(nal) {
+                tracing::warn!(
+                    "{:?} changed under the same id in band, the sequence header is made again",
+                    nal.header.nal_unit_type
+                );
+                changed = true;
+            }
+        }
+        if !changed && self.has_config {
+            return None;
+        }
+        let (sps, pps) = self.latest?;
+        let ((_, sps), (_, pps)) = (self.sps.get(&sps)?, self.pps.get(&pps)?);
+        self.has_config = true;
+        Some(MediaFrame::VideoConfig {
+            timestamp_nano: dts_nano,
+            config: Box::new(AvcDecoderConfigurationRecord::from((sps, pps)).into()),
+        })
+    }
+}
+
+/// where a sink wants the h264 parameter sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterSetCarriage {
+    /// in the access units of the IDRs, ahead of the slices, as rtp players expect them
+    InBand,
+    /// only in the sequence header, as the avc decoder configuration record of flv has them
+    OutOfBand,
+}
+
+impl From<PlayProtocol> for ParameterSetCarriage {
+    fn from(value: PlayProtocol) -> Self {
+        match value {
+            PlayProtocol::RTSP | PlayProtocol::RTSPMulticast => Self::InBand,
+            PlayProtocol::RTMP
+            | PlayProtocol::HTTPFLV
+            | PlayProtocol::Embedded
+            | PlayProtocol::Relay => Self::OutOfBand,
+        }
+    }
+}
+
+/// the nal units of the frame without its parameter sets, None if it has none
+pub fn without_parameter_sets(payload: &VideoFrameUnit) -> Option<VideoFrameUnit> {
+    let VideoFrameUnit::H264 { nal_units } = payload else {
+        return None;
+    };
+    if !nal_units.iter().any(is_parameter_set) {
+        return None;
+    }
+    Some(VideoFrameUnit::H264 {
+        nal_units: nal_units
+            .iter()
+            .filter(|nal| !is_parameter_set(nal))
+            .cloned()
+            .collect(),
+    })
+}
+
+/// puts the parameter sets of the frames of a subscription where its sink wants them,
+/// the ones of the latest sequence header go into the IDRs missing them for in band
+#[derive(Debug)]
+pub struct ParameterSetPlacement {
+    carriage: ParameterSetCarriage,
+    sps: Option<NalUnit>,
+    pps: Option<NalUnit>,
+}
+
+impl ParameterSetPlacement {
+    pub fn new(carriage: ParameterSetCarriage) -> Self {
+        Self {
+            carriage,
+            sps: None,
+            pps: None,
+        }
+    }
+
+    pub fn place(&mut self, mut frame: MediaFrame) -> MediaFrame {
+        match (&mut frame, self.carriage) {
+            (MediaFrame::VideoConfig { config, .. }, ParameterSetCarriage::InBand) => {
+                if let VideoConfig::H264(H264VideoConfig { sps, pps, .. }) = config.as_ref() {
+                    self.sps = sps.as_ref().map(NalUnit::from);
+                    self.pps = pps.as_ref().map(NalUnit::from);
+                }
+            }
+            (MediaFrame::Video { payload, .. }, ParameterSetCarriage::OutOfBand) => {
+                if let Some(stripped) = without_parameter_sets(payload) {
+                    *payload = stripped;
+                }
+            }
+            (
+                MediaFrame::Video {
+                    payload: VideoFrameUnit::H264 { nal_units },
+                    ..
+                },
+                ParameterSetCarriage::InBand,
+            ) => {
+                let has = |nal_unit_type| {
+                    nal_units
+                        .iter()
+                        .any(|nal| nal.header.nal_unit_type == nal_unit_type)
+                };
+                if !has(NALUType::IDRSlice) {
+                    return frame;
+                }
+                let (has_sps, has_pps) = (has(NALUType::SPS), has(NALUType::PPS));
+                // after the access unit delimiter, if any
+                let at = nal_units
+                    .iter()
+                    .position(|nal| nal.header.nal_unit_type != NALUType::AccessUnitDelimiter)
+                    .unwrap_or(nal_units.len());
+                if !has_pps && let Some(pps) = &self.pps {
+                    nal_units.insert(at, pps.clone());
+                }
+                if !has_sps && let Some(sps) = &self.sps {
+                    nal_units.insert(at, sps.clone());
+                }
+            }
+            _ => {}
+        }
+        frame
+    }
+}
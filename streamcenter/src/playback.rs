@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+use crate::snapshot::SourceSnapshotter;
+
+/// the slowest and the fastest playback rates, backwards or not
+pub const MIN_SCALE: f64 = 1.0 / 64.0;
+pub const MAX_SCALE: f64 = 64.0;
+
+/// playback rate relative to realtime, negative ones play backwards
+#[inline]
+pub fn is_valid_scale(scale: f64) -> bool {
+    (MIN_SCALE..=MAX_SCALE).contains(&scale.abs())
+}
+
+/// held by the stream center for a publisher replaying a recording, live publishers have none.
+/// players asking for trick play reach the publisher through it
+#[derive(Debug)]
+pub struct PlaybackControl {
+    pub scale_sender: mpsc::UnboundedSender<f64>,
+    /// set if the recording is played again after a restart
+    pub source: Option<Arc<dyn SourceSnapshotter>>,
+}
@@ -0,0 +1,258 @@
+//! the frame rate and bitrates of a stream estimated from the frames of the publisher,
+//! filled into the onMetaData of the publishers telling none
+
+use std::{collections::VecDeque, time::Duration};
+
+use flv_formats::tag::on_meta_data::OnMetaData;
+use serde::Serialize;
+
+use crate::gop::MediaFrame;
+
+/// the frames of the last this long are measured, by their dts
+pub const RATE_ESTIMATE_WINDOW: Duration = Duration::from_secs(5);
+/// the estimate is filled into the metadata once the frames of this long are measured,
+/// from the start of the stream or the last config change
+pub const RATE_ESTIMATE_SETTLE: Duration = Duration::from_secs(3);
+/// the frame rate takes this many video frames at least
+const MIN_VIDEO_FRAMES: usize = 10;
+
+pub const FRAME_RATE_FIELD: &str = "framerate";
+pub const VIDEO_DATA_RATE_FIELD: &str = "videodatarate";
+pub const AUDIO_DATA_RATE_FIELD: &str = "audiodatarate";
+
+/// what the stats tell of the estimate
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RateEstimateStats {
+    /// frames per second, None until enough video is measured
+    pub frame_rate: Option<f64>,
+    /// kilobits per second
+    pub video_data_rate: Option<f64>,
+    /// kilobits per second
+    pub audio_data_rate: Option<f64>,
+    /// the onMetaData fields filled with the estimate, as the publisher told none of them
+    pub estimated_fields: Vec<&'static str>,
+}
+
+/// the dts and the bytes of the frames of a kind over the window
+#[derive(Debug, Default)]
+struct FrameWindow {
+    frames: VecDeque<(u64, usize)>,
+}
+
+impl FrameWindow {
+    fn push(&mut self, dts_nano: u64, bytes: usize) {
+        if self.frames.back().is_some_and(|(last, _)| *last > dts_nano) {
+            // the clock of the publisher jumped back, what was measured is of another timeline
+            self.frames.clear();
+        }
+        self.frames.push_back((dts_nano, bytes));
+        let window = RATE_ESTIMATE_WINDOW.as_nanos() as u64;
+        while self
+            .frames
+            .front()
+            .is_some_and(|(first, _)| first + window < dts_nano)
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    fn span_nano(&self) -> u64 {
+        match (self.frames.front(), self.frames.back()) {
+            (Some((first, _)), Some((last, _))) => last - first,
+            _ => 0,
+        }
+    }
+
+    /// kilobits per second, the bytes of the first frame are before the span
+    fn data_rate(&self) -> Option<f64> {
+        let span = self.span_nano();
+        if span == 0 {
+            return None;
+        }
+        let bytes: usize = self.frames.iter().skip(1).map(|(_, bytes)| bytes).sum();
+        Some(bytes as f64 * 8.0 / 1000.0 / (span as f64 / 1e9))
+    }
+
+    /// the slope of the dts over the frame count, fitted by least squares so the jitter of the
+    /// timestamps averages out. an interval of n times the median counts n frames, e.g., a dropped
+    /// frame, and the ones under half the median are left out, e.g., a burst
+    fn frame_rate(&self) -> Option<f64> {
+        if self.frames.len() < MIN_VIDEO_FRAMES {
+            return None;
+        }
+        let intervals: Vec<u64> = self
+            .frames
+            .iter()
+            .zip(self.frames.iter().skip(1))
+            .map(|((previous, _), (dts, _))| dts - previous)
+            .collect();
+        let mut sorted = intervals.clone();
+        sorted.sort_unstable();
+        let median = sorted[sorted.len() / 2] as f64;
+        if median == 0.0 {
+            return None;
+        }
+        let mut points = vec![(0.0, self.frames[0].0 as f64)];
+        let mut count = 0.0;
+        for (interval, (dts, _)) in intervals.iter().zip(self.frames.iter().skip(1)) {
+            let steps = (*interval as f64 / median).round();
+            if steps < 1.0 {
+                continue;
+            }
+            count += steps;
+            points.push((count, *dts as f64));
+        }
+        let n = points.len() as f64;
+        let (mean_x, mean_y) = points
+            .iter()
+            .fold((0.0, 0.0), |(x, y), (px, py)| (x + px / n, y + py / n));
+        let (covariance, variance) = points.iter().fold((0.0, 0.0), |(c, v), (x, y)| {
+            (c + (x - mean_x) * (y - mean_y), v + (x - mean_x).powi(2))
+        });
+        if variance == 0.0 || covariance <= 0.0 {
+            return None;
+        }
+        Some(1e9 / (covariance / variance))
+    }
+}
+
+/// the fields the publisher told in its onMetaData, they are never overridden
+#[derive(Debug, Default, Clone, Copy)]
+struct ProvidedFields {
+    frame_rate: bool,
+    video_data_rate: bool,
+    audio_data_rate: bool,
+}
+
+/// measures the frames of a stream and fills the estimate into its metadata,
+/// once per config generation once settled
+#[derive(Debug, Default)]
+pub(crate) struct RateEstimator {
+    video: FrameWindow,
+    audio: FrameWindow,
+    /// the dts the measuring started at, since the start or the last config change
+    start_dts_nano: Option<u64>,
+    /// the fixed frame rate the sequence header declares, trusted over the measured one
+    /// as the timestamps jitter on network ingest
+    nominal_frame_rate: Option<f64>,
+    provided: ProvidedFields,
+    estimated_fields: Vec<&'static str>,
+    filled: bool,
+}
+
+/// 0 tells nothing, as some encoders send it for the rates they do not know
+#[inline]
+fn is_told(value: Option<f64>) -> bool {
+    value.is_some_and(|value| value > 0.0)
+}
+
+impl RateEstimator {
+    /// an onMetaData of the publisher, the fields it tells are its own from now on
+    pub(crate) fn on_publisher_meta_data(&mut self, meta_data: &OnMetaData) {
+        let provided = &mut self.provided;
+        provided.frame_rate |= is_told(meta_data.frame_rate);
+        provided.video_data_rate |= is_told(meta_data.video_data_rate);
+        provided.audio_data_rate |= is_told(meta_data.audio_data_rate);
+        let provided = *provided;
+        self.estimated_fields.retain(|field| match *field {
+            FRAME_RATE_FIELD => !provided.frame_rate,
+            VIDEO_DATA_RATE_FIELD => !provided.video_data_rate,
+            _ => !provided.audio_data_rate,
+        });
+    }
+
+    pub(crate) fn on_frame(&mut self, frame: &MediaFrame) {
+        let window = match frame {
+            MediaFrame::VideoConfig { config, .. } => {
+                self.nominal_frame_rate = config.nominal_frame_rate();
+                return;
+            }
+            MediaFrame::Video { .. } => &mut self.video,
+            MediaFrame::Audio { .. } => &mut self.audio,
+            _ => return,
+        };
+        let dts_nano = frame.get_decode_timestamp_ns();
+        window.push(dts_nano, frame.payload_bytes());
+        if self.start_dts_nano.is_none_or(|start| start > dts_nano) {
+            self.start_dts_nano = Some(dts_nano);
+        }
+    }
+
+    /// the config changed, the estimate is measured and filled in again,
+    /// the nominal frame rate is of the sequence header changing it
+    pub(crate) fn restart(&mut self) {
+        self.video = FrameWindow::default();
+        self.audio = FrameWindow::default();
+        self.start_dts_nano = None;
+        self.filled = false;
+    }
+
+    fn frame_rate(&self) -> Option<f64> {
+        self.nominal_frame_rate.or_else(|| self.video.frame_rate())
+    }
+
+    fn is_settled(&self) -> bool {
+        let Some(start) = self.start_dts_nano else {
+            return false;
+        };
+        let last = [self.video.frames.back(), self.audio.frames.back()]
+            .into_iter()
+            .flatten()
+            .map(|(dts, _)| *dts)
+            .max()
+            .unwrap_or(start);
+        last - start >= RATE_ESTIMATE_SETTLE.as_nanos() as u64
+    }
+
+    /// the metadata with the fields the publisher did not tell filled in, once settled,
+    /// None if it is filled already for this config or there is nothing to fill
+    pub(crate) fn fill(&mut self, meta_data: &OnMetaData) -> Option<OnMetaData> {
+        if self.filled || !self.is_settled() {
+            return None;
+        }
+        self.filled = true;
+        let frame_rate = self.frame_rate();
+        let mut filled = meta_data.clone();
+        let mut fill = |field, provided: bool, value: Option<f64>, target: &mut Option<f64>| {
+            let Some(value) = value.filter(|_| !provided) else {
+                return false;
+            };
+            // two decimals, 29.97 rather than 29.970029
+            *target = Some((value * 100.0).round() / 100.0);
+            if !self.estimated_fields.contains(&field) {
+                self.estimated_fields.push(field);
+            }
+            true
+        };
+        let changed = [
+            fill(
+                FRAME_RATE_FIELD,
+                self.provided.frame_rate,
+                frame_rate,
+                &mut filled.frame_rate,
+            ),
+            fill(
+                VIDEO_DATA_RATE_FIELD,
+                self.provided.video_data_rate,
+                self.video.data_rate(),
+                &mut filled.video_data_rate,
+            ),
+            fill(
+                AUDIO_DATA_RATE_FIELD,
+                self.provided.audio_data_rate,
+                self.audio.data_rate(),
+                &mut filled.audio_data_rate,
+            ),
+        ];
+        changed.contains(&true).then_some(filled)
+    }
+
+    pub(crate) fn stats(&self) -> RateEstimateStats {
+        RateEstimateStats {
+            frame_rate: self.frame_rate(),
+            video_data_rate: self.video.data_rate(),
+            audio_data_rate: self.audio.data_rate(),
+            estimated_fields: self.estimated_fields.clone(),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+use codec_common::FrameType;
+
+use crate::{errors::StreamCenterError, gop::MediaFrame};
+
+pub const DEFAULT_RECOVERY_GROUP_FRAMES: u32 = 30;
+
+/// whether players may join a stream at the frames carrying a recovery point sei,
+/// intra refresh encoders send those instead of IDR frames, apart from the first one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryPointJoin {
+    /// recovery points are only noted in the frame info, players join at key frames
+    Off,
+    /// a recovery point is promoted to a key frame, it and the frames after it make up
+    /// a synthetic key frame group of group_frames frames, recovery points inside are left as they are
+    On { group_frames: u32 },
+}
+
+impl Default for RecoveryPointJoin {
+    fn default() -> Self {
+        Self::On {
+            group_frames: DEFAULT_RECOVERY_GROUP_FRAMES,
+        }
+    }
+}
+
+/// parses `off` or `<group frames>`
+impl FromStr for RecoveryPointJoin {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let join = s.trim();
+        if join == "off" {
+            return Ok(Self::Off);
+        }
+        let group_frames = join
+            .parse::<u32>()
+            .ok()
+            .filter(|frames| *frames > 0)
+            .ok_or_else(|| StreamCenterError::InvalidRecoveryPointJoin(join.to_string()))?;
+        Ok(Self::On { group_frames })
+    }
+}
+
+/// notes the recovery points of a stream in the frame info and promotes them as the join policy says
+#[derive(Debug)]
+pub(crate) struct RecoveryPointMarker {
+    join: RecoveryPointJoin,
+    /// the frames left in the group of the last promoted recovery point
+    group_frames_left: u32,
+}
+
+impl RecoveryPointMarker {
+    pub(crate) fn new(join: RecoveryPointJoin) -> Self {
+        Self {
+            join,
+            group_frames_left: 0,
+        }
+    }
+
+    pub(crate) fn mark(&mut self, frame: &mut MediaFrame) {
+        let MediaFrame::Video {
+            frame_info,
+            payload,
+        } = frame
+        else {
+            return;
+        };
+        match frame_info.frame_type {
+            FrameType::KeyFrame => {
+                // a real key frame starts a group of its own
+                self.group_frames_left = 0;
+                return;
+            }
+            FrameType::CodedFrames => {}
+            FrameType::SequenceStart | FrameType::SequenceEnd => return,
+        }
+
+        let in_group = self.group_frames_left > 0;
+        self.group_frames_left = self.group_frames_left.saturating_sub(1);
+        let Some(recovery_point) = payload.recovery_point() else {
+            return;
+        };
+        frame_info.recovery_frame_cnt = Some(recovery_point.recovery_frame_cnt);
+        let RecoveryPointJoin::On { group_frames } = self.join else {
+            return;
+        };
+        if in_group {
+            return;
+        }
+        frame_info.frame_type = FrameType::KeyFrame;
+        self.group_frames_left = group_frames - 1;
+    }
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+/// the peers kept per publisher or subscriber, the later ones are dropped
+pub const MAX_RTCP_PEERS: usize = 16;
+
+/// the sdes items a remote participant of an rtp session sent in rtcp, RFC 3550 6.5
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RtcpPeer {
+    pub ssrc: u32,
+    pub cname: Option<String>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub tool: Option<String>,
+}
+
+/// the latest description of each ssrc
+#[derive(Debug, Clone, Default)]
+pub struct RtcpPeers(HashMap<u32, RtcpPeer>);
+
+impl RtcpPeers {
+    /// false if the peer is new and there are too many already
+    pub fn describe(&mut self, peer: RtcpPeer) -> bool {
+        if self.0.len() >= MAX_RTCP_PEERS && !self.0.contains_key(&peer.ssrc) {
+            return false;
+        }
+        self.0.insert(peer.ssrc, peer);
+        true
+    }
+
+    /// ordered by ssrc
+    pub fn to_vec(&self) -> Vec<RtcpPeer> {
+        let mut peers: Vec<_> = self.0.values().cloned().collect();
+        peers.sort_by_key(|peer| peer.ssrc);
+        peers
+    }
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// the protocol control settings the server sent to an rtmp client, @see: RTMP spec 5.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RtmpControl {
+    /// of the chunks the server sends
+    pub chunk_size: u32,
+    /// the client acknowledges each time it received this many bytes
+    pub window_ack_size: u32,
+    /// the client limits its output bandwidth to this many unacknowledged bytes
+    pub peer_bandwidth: u32,
+    pub peer_bandwidth_limit_type: PeerBandwidthLimitType,
+}
+
+/// @see: RTMP spec 5.4.5
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerBandwidthLimitType {
+    Hard,
+    Soft,
+    Dynamic,
+}
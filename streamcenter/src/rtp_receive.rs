@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+/// the tracks kept per publisher, the later ones are dropped
+pub const MAX_RTP_RECEIVE_TRACKS: usize = 16;
+
+/// loss up to this costs no health, from it to the bad one the loss part of the score drains linearly
+pub const GOOD_LOSS: f64 = 0.01;
+pub const BAD_LOSS: f64 = 0.10;
+/// the same for the interarrival jitter
+pub const GOOD_JITTER_MS: f64 = 30.0;
+pub const BAD_JITTER_MS: f64 = 200.0;
+/// what loss and jitter weigh in the score of 100
+const LOSS_WEIGHT: f64 = 70.0;
+const JITTER_WEIGHT: f64 = 30.0;
+
+/// how the rtp packets of a track of an rtp based publisher arrive, as in the latest receiver report, RFC 3550 6.4.1
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RtpReceiveStats {
+    /// the control of the track
+    pub track: String,
+    pub ssrc: u32,
+    pub packets_received: u64,
+    /// since the start, negative if duplicates arrived
+    pub packets_lost: i64,
+    /// of the packets expected since the previous report, 0 to 1
+    pub fraction_lost: f64,
+    pub jitter_ms: f64,
+    /// bits per second over the last second and the last 10 seconds
+    pub bitrate_1s: u64,
+    pub bitrate_10s: u64,
+    /// to the publisher, None until it reports on a sender report of the server
+    pub rtt_ms: Option<f64>,
+    /// bytes held by the buffers reassembling the h264 of the track when it was reported
+    pub bytes_buffered: u64,
+    /// times the reassembly buffers dropped data to fit in their budgets
+    pub buffer_discontinuities: u64,
+}
+
+impl RtpReceiveStats {
+    /// 100 for a track without loss and jitter, 0 for one at the bad thresholds of both
+    pub fn health(&self) -> u8 {
+        let drain =
+            |value: f64, good: f64, bad: f64| ((value - good) / (bad - good)).clamp(0.0, 1.0);
+        let score = 100.0
+            - LOSS_WEIGHT * drain(self.fraction_lost, GOOD_LOSS, BAD_LOSS)
+            - JITTER_WEIGHT * drain(self.jitter_ms, GOOD_JITTER_MS, BAD_JITTER_MS);
+        score.round() as u8
+    }
+}
+
+/// the latest stats of each track
+#[derive(Debug, Clone, Default)]
+pub struct RtpReceiveTracks(HashMap<String, RtpReceiveStats>);
+
+impl RtpReceiveTracks {
+    /// false if the track is new and there are too many already
+    pub fn update(&mut self, stats: RtpReceiveStats) -> bool {
+        if self.0.len() >= MAX_RTP_RECEIVE_TRACKS && !self.0.contains_key(&stats.track) {
+            return false;
+        }
+        self.0.insert(stats.track.clone(), stats);
+        true
+    }
+
+    /// ordered by track
+    pub fn to_vec(&self) -> Vec<RtpReceiveStats> {
+        let mut tracks: Vec<_> = self.0.values().cloned().collect();
+        tracks.sort_by(|a, b| a.track.cmp(&b.track));
+        tracks
+    }
+
+    /// of all the tracks
+    pub fn bytes_buffered(&self) -> u64 {
+        self.0.values().map(|stats| stats.bytes_buffered).sum()
+    }
+
+    pub fn buffer_discontinuities(&self) -> u64 {
+        self.0
+            .values()
+            .map(|stats| stats.buffer_discontinuities)
+            .sum()
+    }
+
+    /// the health of the worst track, None before the first report
+    pub fn health(&self) -> Option<u8> {
+        self.0.values().map(RtpReceiveStats::health).min()
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use flv_formats::tag::flv_tag_header::FLVTagHeader;
+use tokio_util::bytes::{Bytes, BytesMut};
+use utils::traits::fixed_packet::FixedPacket;
+
+use crate::{gop::MediaFrame, trace::TraceFrameKind};
+
+/// the latest serialized frames kept, a sink lagging further behind serializes on its own
+pub const DEFAULT_SERIALIZED_FRAMES: usize = 256;
+
+/// the output formats sinks serialize frames to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerializedFlavor {
+    /// the flv tag followed by its previous tag size,
+    /// avc nal units are prefixed by lengths of the given size
+    FlvTag { nalu_length_size: u8 },
+}
+
+/// tells a frame from the others by the payload buffer its clones share,
+/// the buffer is held so no other frame can reuse it while the entry lives
+#[derive(Debug)]
+struct FrameIdentity {
+    kind: TraceFrameKind,
+    dts_nano: u64,
+    pts_nano: u64,
+    payload: Bytes,
+}
+
+impl FrameIdentity {
+    /// sequence headers and the end of stream are rebuilt for each subscriber, they are never cached
+    fn of(frame: &MediaFrame) -> Option<Self> {
+        let payload = match frame {
+            MediaFrame::Video {
+                payload: codec_common::video::VideoFrameUnit::H264 { nal_units },
+                ..
+            } => &nal_units.first()?.body,
+            MediaFrame::Video {
+                payload: codec_common::video::VideoFrameUnit::AV1 { obus },
+                ..
+            } => obus,
+            MediaFrame::Audio { payload, .. }
+            | MediaFrame::Script { payload, .. }
+            | MediaFrame::Data { payload, .. } => payload,
+            MediaFrame::VideoConfig { .. }
+            | MediaFrame::AudioConfig { .. }
+            | MediaFrame::AudioChannelConfig { .. }
+            | MediaFrame::EndOfStream { .. }
+            | MediaFrame::SourceError { .. } => return None,
+        };
+        // empty buffers all look the same
+        if payload.is_empty() {
+            return None;
+        }
+        Some(Self {
+            kind: frame.into(),
+            dts_nano: frame.get_decode_timestamp_ns(),
+            pts_nano: frame.get_presentation_timestamp_ns(),
+            payload: payload.clone(),
+        })
+    }
+
+    fn is_same(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.dts_nano == other.dts_nano
+            && self.pts_nano == other.pts_nano
+            && self.payload.as_ptr() == other.payload.as_ptr()
+            && self.payload.len() == other.payload.len()
+    }
+}
+
+#[derive(Debug)]
+struct SerializedEntry {
+    identity: FrameIdentity,
+    flavor: SerializedFlavor,
+    /// None if the serialization failed
+    bytes: Arc<OnceLock<Option<Bytes>>>,
+}
+
+/// frames of a stream serialized by its sinks, shared by all the subscribers of the stream.
+/// the first sink asking for a frame in some flavor serializes it, the others get clones of the bytes.
+/// each new entry evicts the oldest one beyond the capacity
+#[derive(Debug, Clone)]
+pub struct SerializedFrameCache {
+    entries: Arc<Mutex<VecDeque<SerializedEntry>>>,
+    capacity: usize,
+}
+
+impl Default for SerializedFrameCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SERIALIZED_FRAMES)
+    }
+}
+
+impl SerializedFrameCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the frame in the flavor, serialized at most once while it stays in the cache.
+    /// sinks asking for the same frame at the same time wait for the one serializing it
+    pub fn get_or_serialize<E, F>(
+        &self,
+        frame: &MediaFrame,
+        flavor: SerializedFlavor,
+        serialize: F,
+    ) -> Result<Bytes, E>
+    where
+        F: Fn(&MediaFrame) -> Result<Bytes, E>,
+    {
+        if self.capacity == 0 {
+            return serialize(frame);
+        }
+        let Some(identity) = FrameIdentity::of(frame) else {
+            return serialize(frame);
+        };
+        let bytes = {
+            let mut entries = self.entries.lock().unwrap();
+            // sinks are mostly at the latest frames
+            match entries
+                .iter()
+                .rev()
+                .find(|entry| entry.flavor == flavor && entry.identity.is_same(&identity))
+            {
+                Some(entry) => entry.bytes.clone(),
+                None => {
+                    if entries.len() >= self.capacity {
+                        entries.pop_front();
+                    }
+                    let bytes = Arc::new(OnceLock::new());
+                    entries.push_back(SerializedEntry {
+                        identity,
+                        flavor,
+                        bytes: bytes.clone(),
+                    });
+                    bytes
+                }
+            }
+        };
+        match bytes.get_or_init(|| serialize(frame).ok()) {
+            Some(bytes) => Ok(bytes.clone()),
+            // every sink gets the error on its own
+            None => serialize(frame),
+        }
+    }
+}
+
+/// zero bases the timestamps of the flv tags a viewer gets.
+/// the first audio or video tag sets the base, sequence headers and metadata
+/// dumped to a new viewer before it are at 0 already.
+/// tags are shared with the other viewers, only the header of a rebased tag is copied
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FlvTimestampRebase {
+    base_ms: Option<u32>,
+}
+
+impl FlvTimestampRebase {
+    pub fn base_ms(&self) -> Option<u32> {
+        self.base_ms
+    }
+
+    /// the tag as the viewer gets it, the patched header and the rest of the tag if rebased
+    pub fn rebase(&mut self, frame: &MediaFrame, tag: Bytes) -> (Bytes, Option<Bytes>) {
+        let header_size = FLVTagHeader::bytes_count();
+        if tag.len() < header_size {
+            return (tag, None);
+        }
+        let timestamp = u32::from_be_bytes([tag[7], tag[4], tag[5], tag[6]]);
+        if self.base_ms.is_none()
+            && matches!(frame, MediaFrame::Video { .. } | MediaFrame::Audio { .. })
+        {
+            self.base_ms = Some(timestamp);
+        }
+        let rebased = timestamp.saturating_sub(self.base_ms.unwrap_or(0));
+        if rebased == timestamp {
+            return (tag, None);
+        }
+        let mut header = BytesMut::from(&tag[..header_size]);
+        let [extended, high, middle, low] = rebased.to_be_bytes();
+        header[4..8].copy_from_slice(&[high, middle, low, extended]);
+        (header.freeze(), Some(tag.slice(header_size..)))
+    }
+}
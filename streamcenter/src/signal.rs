@@ -1,4 +1,72 @@
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::{
+    end_of_stream::EndOfStreamReason,
+    errors::StreamCenterResult,
+    events::{StreamDescription, SubscribeResponse},
+    gop::MediaFrame,
+    keyframe::KeyframeSnapshot,
+    rtcp_peer::RtcpPeer,
+    rtmp_control::RtmpControl,
+    rtp_receive::RtpReceiveStats,
+    stream_source::{MediaSelection, SubscribeHandler},
+};
+
+/// sent by the stream center to the task of a stream source,
+/// requests with a result_sender are answered by the stream source directly
 #[derive(Debug)]
 pub enum StreamSignal {
-    Stop,
+    /// the subscribers are told the reason in-band
+    Stop {
+        reason: EndOfStreamReason,
+    },
+    Subscribe {
+        handler: SubscribeHandler,
+        media_receiver: mpsc::Receiver<MediaFrame>,
+        result_sender: oneshot::Sender<StreamCenterResult<SubscribeResponse>>,
+    },
+    Unsubscribe {
+        subscriber_id: Uuid,
+        result_sender: oneshot::Sender<StreamCenterResult<()>>,
+    },
+    UpdateMediaSelection {
+        subscriber_id: Uuid,
+        media_selection: MediaSelection,
+    },
+    /// a snapshot of the stream and its subscribers
+    Describe {
+        result_sender: oneshot::Sender<StreamCenterResult<StreamDescription>>,
+    },
+    /// the snapshot once the sequence headers the stream has are known
+    DescribeConfigured {
+        result_sender: oneshot::Sender<StreamCenterResult<StreamDescription>>,
+    },
+    /// the latest IDR access unit, None before the first one
+    Keyframe {
+        result_sender: oneshot::Sender<StreamCenterResult<Option<KeyframeSnapshot>>>,
+    },
+    /// None for the peers of the publisher
+    RtcpPeerDescribed {
+        subscriber_id: Option<Uuid>,
+        peer: RtcpPeer,
+    },
+    /// None for the publisher
+    RtmpControlNegotiated {
+        subscriber_id: Option<Uuid>,
+        control: RtmpControl,
+    },
+    RtpReceiveReported {
+        stats: RtpReceiveStats,
+    },
+    /// the frames of the stream are copied to a stream failing over to it,
+    /// starting with the sequence headers and the latest gop
+    Mirror {
+        sender: mpsc::Sender<MediaFrame>,
+    },
+    /// the answer to a stalled publisher, the subscribers get the frames of the backup
+    /// until the publisher resumes, None if the stream has no backup to fail over to
+    FailOver {
+        backup_frames: Option<mpsc::Receiver<MediaFrame>>,
+    },
 }
@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+use codec_common::{FrameType, video::VideoFrameUnit};
+use codec_h264::slice_coverage::{PictureSlices, SliceCoverage};
+
+use crate::{errors::StreamCenterError, gop::MediaFrame, parameter_sets::ParameterSetTracker};
+
+/// what is done with the h264 access units whose slices do not cover their pictures,
+/// e.g., the ones a depacketizer flushed on its timeout with slices lost on the way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SliceIntegrity {
+    /// the access units are not checked
+    #[default]
+    Off,
+    /// the broken access units are sent marked as corrupt in their frame info, the sinks decide
+    Tag,
+    /// the broken access units are dropped, and the frames after them until the next key frame
+    /// or recovery point, as they refer to the broken picture
+    Drop,
+}
+
+/// parses `off`, `tag` or `drop`
+impl FromStr for SliceIntegrity {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(Self::Off),
+            "tag" => Ok(Self::Tag),
+            "drop" => Ok(Self::Drop),
+            other => Err(StreamCenterError::InvalidSliceIntegrity(other.to_owned())),
+        }
+    }
+}
+
+/// what the check found of a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct IntegrityVerdict {
+    /// the slices of the access unit do not cover its pictures
+    pub corrupt: bool,
+    /// the frame is not to be sent
+    pub drop: bool,
+}
+
+/// checks the slices of the h264 access units of a stream as the policy says
+#[derive(Debug, Default)]
+pub(crate) struct SliceIntegrityCheck {
+    policy: SliceIntegrity,
+    coverage: SliceCoverage,
+    /// a broken access unit was dropped, the video waits for a picture to decode from
+    dropping: bool,
+}
+
+impl SliceIntegrityCheck {
+    pub(crate) fn new(policy: SliceIntegrity) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// the access units that can not be parsed are let through as they are
+    pub(crate) fn check(
+        &mut self,
+        frame: &mut MediaFrame,
+        parameter_sets: &ParameterSetTracker,
+    ) -> IntegrityVerdict {
+        if self.policy == SliceIntegrity::Off {
+            return IntegrityVerdict::default();
+        }
+        let dts_nano = frame.get_decode_timestamp_ns();
+        let MediaFrame::Video {
+            frame_info,
+            payload: VideoFrameUnit::H264 { nal_units },
+        } = frame
+        else {
+            return IntegrityVerdict::default();
+        };
+        if matches!(
+            frame_info.frame_type,
+            FrameType::SequenceStart | FrameType::SequenceEnd
+        ) {
+            return IntegrityVerdict::default();
+        }
+        let pictures = match PictureSlices::of_access_unit(nal_units.iter(), |pps_id| {
+            parameter_sets.get(pps_id)
+        }) {
+            Ok(pictures) => pictures,
+            Err(err) => {
+                tracing::debug!(
+                    "slices of the access unit at {}ms not checked: {}",
+                    dts_nano / 1_000_000,
+                    err
+                );
+                Vec::new()
+            }
+        };
+        let mut corrupt = false;
+        for picture in &pictures {
+            if let Err(gap) = self.coverage.check(picture) {
+                tracing::warn!(
+                    "access unit at {}ms is corrupt: {:?}",
+                    dts_nano / 1_000_000,
+                    gap
+                );
+                corrupt = true;
+            }
+        }
+        if self.policy == SliceIntegrity::Tag {
+            frame_info.corrupt = corrupt;
+            return IntegrityVerdict {
+                corrupt,
+                drop: false,
+            };
+        }
+        let decodable =
+            frame_info.frame_type == FrameType::KeyFrame || frame_info.recovery_frame_cnt.is_some();
+        if corrupt {
+            self.dropping = true;
+        } else if self.dropping && decodable {
+            tracing::info!(
+                "video resumes at {}ms after corrupt access units",
+                dts_nano / 1_000_000
+            );
+            self.dropping = false;
+        }
+        IntegrityVerdict {
+            corrupt,
+            drop: self.dropping,
+        }
+    }
+}
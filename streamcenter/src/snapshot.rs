@@ -0,0 +1,224 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    errors::{StreamCenterError, StreamCenterResult},
+    identity::{StreamApp, StreamIdentity, StreamName},
+    playback::is_valid_scale,
+    takeover::TakeoverPolicy,
+};
+
+/// bumped when a field changes its meaning, fields added later default when missing
+pub const SNAPSHOT_VERSION: u64 = 1;
+/// in the data dir
+pub const SNAPSHOT_FILE_NAME: &str = "stream_center.snapshot.json";
+
+/// what the stream center needs to pick up where it was after a restart,
+/// taken on graceful shutdown and restored once at startup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamCenterSnapshot {
+    pub version: u64,
+    /// unix millis
+    pub taken_at: u64,
+    pub default_takeover_policy: TakeoverPolicy,
+    /// by app
+    #[serde(default)]
+    pub takeover_policies: BTreeMap<String, TakeoverPolicy>,
+    /// `app/stream` of an alias to the one of its canonical stream
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// the streams whose sources are run by the servers and have to be started again
+    #[serde(default)]
+    pub sources: Vec<SourceSnapshot>,
+}
+
+/// a stream and what played into it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceSnapshot {
+    pub app: StreamApp,
+    pub stream: StreamName,
+    #[serde(flatten)]
+    pub definition: SourceDefinition,
+}
+
+impl SourceSnapshot {
+    pub fn stream_id(&self) -> StreamIdentity {
+        StreamIdentity {
+            app: self.app.clone(),
+            name: self.stream.clone(),
+        }
+    }
+
+    /// the app and the stream are validated as they are parsed
+    fn validate(&self) -> StreamCenterResult<()> {
+        match &self.definition {
+            SourceDefinition::Vod(vod) => vod.validate(),
+        }
+    }
+}
+
+/// how to start a source again, by the kind of it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SourceDefinition {
+    Vod(VodSourceDefinition),
+}
+
+impl SourceDefinition {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Vod(_) => "vod",
+        }
+    }
+}
+
+/// an flv file played by the vod api
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VodSourceDefinition {
+    /// relative to the vod directory
+    pub path: String,
+    pub speed: f64,
+    /// the one in effect, players may have changed it
+    pub scale: f64,
+    #[serde(default)]
+    pub loop_playback: bool,
+    pub idle_timeout_ms: u64,
+    /// file time of the latest frame delivered, the playback goes on from the key frame before it
+    #[serde(default)]
+    pub position_ms: u64,
+}
+
+impl VodSourceDefinition {
+    fn validate(&self) -> StreamCenterResult<()> {
+        if self.path.is_empty() {
+            return Err(StreamCenterError::InvalidSnapshot(
+                "vod source without a path".to_owned(),
+            ));
+        }
+        if !self.speed.is_finite() || self.speed <= 0.0 || !is_valid_scale(self.scale) {
+            return Err(StreamCenterError::InvalidSnapshot(format!(
+                "bad vod speed or scale, speed: {}, scale: {}",
+                self.speed, self.scale
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// held by the stream center for a source it does not run itself, e.g., a vod file played by the http server,
+/// asked for the definition of the source whenever the stream center is snapshotted
+pub trait SourceSnapshotter: fmt::Debug + Send + Sync {
+    fn definition(&self) -> SourceDefinition;
+}
+
+impl StreamCenterSnapshot {
+    pub(crate) fn new(
+        default_takeover_policy: TakeoverPolicy,
+        takeover_policies: BTreeMap<String, TakeoverPolicy>,
+        aliases: BTreeMap<String, String>,
+        mut sources: Vec<SourceSnapshot>,
+    ) -> Self {
+        sources.sort_by(|a, b| (&a.app, &a.stream).cmp(&(&b.app, &b.stream)));
+        Self {
+            version: SNAPSHOT_VERSION,
+            taken_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or(0),
+            default_takeover_policy,
+            takeover_policies,
+            aliases,
+            sources,
+        }
+    }
+
+    pub fn to_json(&self) -> StreamCenterResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// snapshots of older versions are upgraded, newer ones are refused.
+    /// the sources are validated one by one, the invalid ones and the ones of kinds unknown here,
+    /// e.g., written by a newer build, are skipped
+    pub fn from_json(json: &str) -> StreamCenterResult<Self> {
+        let mut value: Value = serde_json::from_str(json)?;
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| StreamCenterError::InvalidSnapshot("no version".to_owned()))?;
+        Self::upgrade(&mut value, version)?;
+        let sources = match value.as_object_mut().and_then(|map| map.remove("sources")) {
+            Some(Value::Array(sources)) => sources,
+            None | Some(Value::Null) => vec![],
+            Some(sources) => {
+                return Err(StreamCenterError::InvalidSnapshot(format!(
+                    "sources is not a list: {}",
+                    sources
+                )));
+            }
+        };
+        let mut snapshot: Self = serde_json::from_value(value)?;
+        let mut stream_ids = HashSet::new();
+        for source in sources {
+            let source = serde_json::from_value::<SourceSnapshot>(source)
+                .map_err(StreamCenterError::from)
+                .and_then(|parsed| parsed.validate().map(|_| parsed));
+            match source {
+                Ok(source) if stream_ids.insert(source.stream_id()) => {
+                    snapshot.sources.push(source)
+                }
+                Ok(source) => {
+                    tracing::warn!(
+                        "skip source of stream {} in snapshot, the stream has one already",
+                        source.stream_id()
+                    );
+                }
+                Err(err) => tracing::warn!("skip source in snapshot: {}", err),
+            }
+        }
+        snapshot.version = SNAPSHOT_VERSION;
+        Ok(snapshot)
+    }
+
+    /// brings a snapshot of an older version to the current one
+    fn upgrade(_value: &mut Value, version: u64) -> StreamCenterResult<()> {
+        match version {
+            // nothing changed its meaning so far
+            1..=SNAPSHOT_VERSION => Ok(()),
+            _ => Err(StreamCenterError::UnsupportedSnapshotVersion(version)),
+        }
+    }
+
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(SNAPSHOT_FILE_NAME)
+    }
+
+    /// replaces the snapshot in the data dir, a crash halfway leaves the previous one as is
+    pub fn save(&self, data_dir: &Path) -> StreamCenterResult<()> {
+        fs::create_dir_all(data_dir)?;
+        let path = Self::path(data_dir);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, self.to_json()?)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// reads and removes the snapshot in the data dir, so that it is restored once,
+    /// None if there is none
+    pub fn take(data_dir: &Path) -> StreamCenterResult<Option<Self>> {
+        let path = Self::path(data_dir);
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        fs::remove_file(&path)?;
+        Self::from_json(&json).map(Some)
+    }
+}
@@ -1,20 +1,52 @@
 use crate::{
+    alias::StreamAliases,
+    audio_continuity::AudioGapConcealment,
+    backpressure::BackpressurePolicy,
+    drain::{DrainOutcome, DrainRequest, DrainSummary, DrainedPublisher},
+    dvr::DvrWindow,
+    end_of_stream::EndOfStreamReason,
     errors::{StreamCenterError, StreamCenterResult},
-    events::{StreamCenterEvent, StreamDescription, SubscribeResponse, SubscriberInfo},
+    events::{
+        PublishResponse, RecordingPublishResponse, StreamCenterEvent, StreamConfigChange,
+        StreamDescription, SubscribeResponse,
+    },
+    failover::{FAILOVER_CHANNEL_CAPACITY, backup_stream},
     gop::MediaFrame,
+    gop_budget::{GopCacheBudget, GopCacheBudgetConfig},
+    identity::StreamIdentity,
+    keyframe::KeyframeSnapshot,
+    latency::{LatencyConfig, LatencyProbe},
+    notification::{DEFAULT_NOTIFICATION_CAPACITY, StreamNotification},
+    playback::{PlaybackControl, is_valid_scale},
+    recovery_point::RecoveryPointJoin,
+    rtcp_peer::RtcpPeer,
+    rtmp_control::RtmpControl,
+    rtp_receive::RtpReceiveStats,
     signal::StreamSignal,
+    slice_integrity::SliceIntegrity,
+    snapshot::{SourceSnapshot, SourceSnapshotter, StreamCenterSnapshot},
     stream_source::{
-        ParsedContext, PlayProtocol, PublishProtocol, StreamIdentifier, StreamSource,
+        MediaSelection, ParsedContext, PlayProtocol, PublishProtocol, StreamSource,
         SubscribeHandler,
     },
+    takeover::{KickReason, PublishActivity, PublisherHandle, PublisherKicked, TakeoverPolicy},
+    timestamp_normalization::TimestampNormalization,
+    trace::{PipelineTracer, TraceEvent, TraceHandle, TraceRecord},
+    watchdog::IdleWatchdog,
 };
 use codec_common::{audio::AudioConfig, video::VideoConfig};
-use std::{backtrace::Backtrace, collections::HashMap, sync::Arc, time::SystemTime};
+use std::{
+    backtrace::Backtrace,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 use tokio::sync::{
-    RwLock,
+    broadcast,
     mpsc::{self, Sender, UnboundedSender},
     oneshot,
 };
+use tracing::Instrument;
 use uuid::Uuid;
 
 #[derive(Debug)]
@@ -23,26 +55,73 @@ pub struct StreamSourceDynamicInfo {
     pub has_audio: bool,
     pub video_config: Option<VideoConfig>,
     pub audio_config: Option<AudioConfig>,
+    /// bumped every time the publisher changes its sequence headers
+    pub config_generation: u64,
 }
 
+/// the stream source runs on a task of its own, which owns the subscribers of the stream,
+/// the stream center only talks to it through the signals
 #[derive(Debug)]
 struct StreamSourceHandles {
-    signal_sender: mpsc::Sender<StreamSignal>,
+    signal_sender: mpsc::UnboundedSender<StreamSignal>,
     _source_sender: mpsc::Sender<MediaFrame>,
-
-    _stream_identifier: StreamIdentifier,
-
-    data_distributer: Arc<RwLock<HashMap<Uuid, SubscribeHandler>>>,
-    stream_dynamic_info: Arc<RwLock<StreamSourceDynamicInfo>>,
-    publish_protocol: PublishProtocol,
-    publish_start_time: SystemTime,
+    activity: Arc<PublishActivity>,
+    /// the source a reap request comes from may have been replaced already
+    source_id: Uuid,
+    /// None if the publisher can not be kicked
+    publisher: Option<PublisherHandle>,
+    /// None for live publishers
+    playback: Option<PlaybackControl>,
+    /// the stream the subscribers are failed over to while the publisher is stalled
+    backup: Option<StreamIdentity>,
 }
 
 #[derive(Debug)]
 pub struct StreamCenter {
-    streams: HashMap<StreamIdentifier, StreamSourceHandles>,
+    streams: HashMap<StreamIdentity, StreamSourceHandles>,
     event_receiver: mpsc::UnboundedReceiver<StreamCenterEvent>,
     event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    tracer: TraceHandle,
+    /// by app
+    takeover_policies: HashMap<String, TakeoverPolicy>,
+    default_takeover_policy: TakeoverPolicy,
+    /// by app
+    idle_watchdogs: HashMap<String, IdleWatchdog>,
+    default_idle_watchdog: IdleWatchdog,
+    /// by app
+    recovery_point_joins: HashMap<String, RecoveryPointJoin>,
+    default_recovery_point_join: RecoveryPointJoin,
+    /// by app
+    audio_gap_concealments: HashMap<String, AudioGapConcealment>,
+    default_audio_gap_concealment: AudioGapConcealment,
+    /// by app
+    timestamp_normalizations: HashMap<String, TimestampNormalization>,
+    default_timestamp_normalization: TimestampNormalization,
+    slice_integrities: HashMap<String, SliceIntegrity>,
+    default_slice_integrity: SliceIntegrity,
+    /// by app
+    dvr_windows: HashMap<String, DvrWindow>,
+    default_dvr_window: DvrWindow,
+    /// by app
+    backpressure_policies: HashMap<String, BackpressurePolicy>,
+    default_backpressure_policy: BackpressurePolicy,
+    /// None if latency measurement is disabled
+    latency: Option<LatencyConfig>,
+    /// shared by the gop caches of all the streams
+    gop_cache_budget: GopCacheBudget,
+    #[cfg(feature = "frame-crc")]
+    frame_crc: bool,
+    notification_sender: broadcast::Sender<StreamNotification>,
+    /// the publishers asked to move to another host, until they are gone
+    draining: HashMap<StreamIdentity, DrainedPublisher>,
+    /// the streams of a restored snapshot whose sources are not published again yet
+    restoring: HashSet<StreamIdentity>,
+    aliases: StreamAliases,
+    /// the stream each subscriber that asked for an alias plays, the alias may point elsewhere since
+    alias_subscribers: HashMap<Uuid, StreamIdentity>,
+    /// the describes of streams not published yet, handed to the source once published
+    describe_waiters:
+        HashMap<StreamIdentity, Vec<oneshot::Sender<StreamCenterResult<StreamDescription>>>>,
 }
 
 impl StreamCenter {
@@ -52,6 +131,279 @@ impl StreamCenter {
             streams: HashMap::new(),
             event_receiver: rx,
             event_sender: tx,
+            tracer: TraceHandle::disabled(),
+            takeover_policies: HashMap::new(),
+            default_takeover_policy: TakeoverPolicy::default(),
+            idle_watchdogs: HashMap::new(),
+            default_idle_watchdog: IdleWatchdog::default(),
+            recovery_point_joins: HashMap::new(),
+            default_recovery_point_join: RecoveryPointJoin::default(),
+            audio_gap_concealments: HashMap::new(),
+            default_audio_gap_concealment: AudioGapConcealment::default(),
+            timestamp_normalizations: HashMap::new(),
+            default_timestamp_normalization: TimestampNormalization::default(),
+            slice_integrities: HashMap::new(),
+            default_slice_integrity: SliceIntegrity::default(),
+            dvr_windows: HashMap::new(),
+            default_dvr_window: DvrWindow::default(),
+            backpressure_policies: HashMap::new(),
+            default_backpressure_policy: BackpressurePolicy::default(),
+            latency: None,
+            gop_cache_budget: GopCacheBudget::default(),
+            #[cfg(feature = "frame-crc")]
+            frame_crc: false,
+            notification_sender: broadcast::channel(DEFAULT_NOTIFICATION_CAPACITY).0,
+            draining: HashMap::new(),
+            restoring: HashSet::new(),
+            aliases: StreamAliases::default(),
+            alias_subscribers: HashMap::new(),
+            describe_waiters: HashMap::new(),
+        }
+    }
+
+    /// takes the takeover policies and the aliases of the snapshot, the ones in effect when it was taken,
+    /// and returns the sources to start again. once published again they are notified
+    /// with StreamNotification::Restored instead of Published
+    pub fn restore(&mut self, snapshot: StreamCenterSnapshot) -> Vec<SourceSnapshot> {
+        tracing::info!(
+            "restore stream center snapshot taken at {}, {} takeover policies, {} aliases, {} sources",
+            snapshot.taken_at,
+            snapshot.takeover_policies.len(),
+            snapshot.aliases.len(),
+            snapshot.sources.len()
+        );
+        self.default_takeover_policy = snapshot.default_takeover_policy;
+        self.takeover_policies.extend(snapshot.takeover_policies);
+        for (alias, canonical) in snapshot.aliases {
+            let restored = alias.parse().and_then(|alias| {
+                canonical
+                    .parse()
+                    .and_then(|canonical| self.set_alias(alias, Some(canonical)))
+            });
+            if let Err(err) = restored {
+                tracing::warn!("skip alias {} in snapshot: {}", alias, err);
+            }
+        }
+        self.restoring
+            .extend(snapshot.sources.iter().map(SourceSnapshot::stream_id));
+        snapshot.sources
+    }
+
+    /// what happens if a stream of the app is published twice
+    pub fn set_takeover_policy(&mut self, app: &str, policy: TakeoverPolicy) {
+        self.takeover_policies.insert(app.to_owned(), policy);
+    }
+
+    /// for apps without a policy of their own
+    pub fn set_default_takeover_policy(&mut self, policy: TakeoverPolicy) {
+        self.default_takeover_policy = policy;
+    }
+
+    /// how long the streams of the app published afterwards may go without audio or video
+    pub fn set_idle_watchdog(&mut self, app: &str, watchdog: IdleWatchdog) {
+        self.idle_watchdogs.insert(app.to_owned(), watchdog);
+    }
+
+    /// for apps without a watchdog of their own
+    pub fn set_default_idle_watchdog(&mut self, watchdog: IdleWatchdog) {
+        self.default_idle_watchdog = watchdog;
+    }
+
+    /// whether players join the streams of the app published afterwards at recovery points
+    pub fn set_recovery_point_join(&mut self, app: &str, join: RecoveryPointJoin) {
+        self.recovery_point_joins.insert(app.to_owned(), join);
+    }
+
+    /// for apps without a recovery point join of their own
+    pub fn set_default_recovery_point_join(&mut self, join: RecoveryPointJoin) {
+        self.default_recovery_point_join = join;
+    }
+
+    /// how the holes in the audio of the streams of the app published afterwards are concealed
+    pub fn set_audio_gap_concealment(&mut self, app: &str, concealment: AudioGapConcealment) {
+        self.audio_gap_concealments
+            .insert(app.to_owned(), concealment);
+    }
+
+    /// for apps without an audio gap concealment of their own
+    pub fn set_default_audio_gap_concealment(&mut self, concealment: AudioGapConcealment) {
+        self.default_audio_gap_concealment = concealment;
+    }
+
+    /// how the timestamps of the publishers of the streams of the app published afterwards are mapped
+    pub fn set_timestamp_normalization(
+        &mut self,
+        app: &str,
+        normalization: TimestampNormalization,
+    ) {
+        self.timestamp_normalizations
+            .insert(app.to_owned(), normalization);
+    }
+
+    /// for apps without a timestamp normalization of their own
+    pub fn set_default_timestamp_normalization(&mut self, normalization: TimestampNormalization) {
+        self.default_timestamp_normalization = normalization;
+    }
+
+    /// what is done with the broken h264 access units of the streams of the app published afterwards
+    pub fn set_slice_integrity(&mut self, app: &str, integrity: SliceIntegrity) {
+        self.slice_integrities.insert(app.to_owned(), integrity);
+    }
+
+    /// for apps without a slice integrity of their own
+    pub fn set_default_slice_integrity(&mut self, integrity: SliceIntegrity) {
+        self.default_slice_integrity = integrity;
+    }
+
+    /// how much of the streams of the app published afterwards is kept for timeshift playback
+    pub fn set_dvr_window(&mut self, app: &str, window: DvrWindow) {
+        self.dvr_windows.insert(app.to_owned(), window);
+    }
+
+    /// for apps without a dvr window of their own
+    pub fn set_default_dvr_window(&mut self, window: DvrWindow) {
+        self.default_dvr_window = window;
+    }
+
+    /// when the publishers of the app published afterwards are told the server can not keep up
+    pub fn set_backpressure_policy(&mut self, app: &str, policy: BackpressurePolicy) {
+        self.backpressure_policies.insert(app.to_owned(), policy);
+    }
+
+    /// for apps without a backpressure policy of their own
+    pub fn set_default_backpressure_policy(&mut self, policy: BackpressurePolicy) {
+        self.default_backpressure_policy = policy;
+    }
+
+    /// points the alias to the canonical stream, or removes it if there is none,
+    /// returns the stream it pointed to before. only the subscriptions afterwards follow it,
+    /// a stream published under the alias is refused
+    pub fn set_alias(
+        &mut self,
+        alias: StreamIdentity,
+        canonical: Option<StreamIdentity>,
+    ) -> StreamCenterResult<Option<StreamIdentity>> {
+        let previous = match canonical.clone() {
+            None => self.aliases.remove(&alias),
+            Some(_) if self.streams.contains_key(&alias) => {
+                return Err(StreamCenterError::InvalidAlias(format!(
+                    "{} is published",
+                    alias
+                )));
+            }
+            Some(canonical) => self.aliases.set(alias.clone(), canonical)?,
+        };
+        if previous != canonical {
+            tracing::info!(
+                "alias {} points to {:?}, was {:?}",
+                alias,
+                canonical,
+                previous
+            );
+            self.notify(StreamNotification::AliasChanged {
+                alias,
+                canonical,
+                previous: previous.clone(),
+            });
+        }
+        Ok(previous)
+    }
+
+    /// the stream asked for, or the one it points to if it is an alias
+    fn canonical(&self, stream_id: StreamIdentity) -> StreamIdentity {
+        self.aliases
+            .resolve(&stream_id)
+            .cloned()
+            .unwrap_or(stream_id)
+    }
+
+    /// the stream the subscriber plays, which the alias it asked for may not point to anymore
+    fn subscribed_stream(&self, stream_id: StreamIdentity, subscriber_id: Uuid) -> StreamIdentity {
+        match self.alias_subscribers.get(&subscriber_id) {
+            Some(subscribed) => subscribed.clone(),
+            None => self.canonical(stream_id),
+        }
+    }
+
+    /// stamps frames of the streams published afterwards with their ingest time,
+    /// the sinks report how long it took them to write the frames out
+    pub fn set_latency_measurement(&mut self, config: LatencyConfig) {
+        self.latency = Some(config);
+    }
+
+    /// the memory budget the gop caches of the streams published afterwards share
+    pub fn set_gop_cache_budget(&mut self, config: GopCacheBudgetConfig) {
+        self.gop_cache_budget = GopCacheBudget::new(config);
+    }
+
+    /// stamps frames of the streams published afterwards with the crc of their payload,
+    /// see [`crate::frame_crc`]
+    #[cfg(feature = "frame-crc")]
+    pub fn set_frame_crc_validation(&mut self, enabled: bool) {
+        self.frame_crc = enabled;
+    }
+
+    fn get_takeover_policy(&self, app: &str) -> TakeoverPolicy {
+        self.takeover_policies
+            .get(app)
+            .copied()
+            .unwrap_or(self.default_takeover_policy)
+    }
+
+    fn get_idle_watchdog(&self, app: &str) -> IdleWatchdog {
+        self.idle_watchdogs
+            .get(app)
+            .copied()
+            .unwrap_or(self.default_idle_watchdog)
+    }
+
+    fn get_recovery_point_join(&self, app: &str) -> RecoveryPointJoin {
+        self.recovery_point_joins
+            .get(app)
+            .copied()
+            .unwrap_or(self.default_recovery_point_join)
+    }
+
+    fn get_audio_gap_concealment(&self, app: &str) -> AudioGapConcealment {
+        self.audio_gap_concealments
+            .get(app)
+            .copied()
+            .unwrap_or(self.default_audio_gap_concealment)
+    }
+
+    fn get_timestamp_normalization(&self, app: &str) -> TimestampNormalization {
+        self.timestamp_normalizations
+            .get(app)
+            .copied()
+            .unwrap_or(self.default_timestamp_normalization)
+    }
+
+    fn get_slice_integrity(&self, app: &str) -> SliceIntegrity {
+        self.slice_integrities
+            .get(app)
+            .copied()
+            .unwrap_or(self.default_slice_integrity)
+    }
+
+    fn get_dvr_window(&self, app: &str) -> DvrWindow {
+        self.dvr_windows
+            .get(app)
+            .copied()
+            .unwrap_or(self.default_dvr_window)
+    }
+
+    fn get_backpressure_policy(&self, app: &str) -> BackpressurePolicy {
+        self.backpressure_policies
+            .get(app)
+            .copied()
+            .unwrap_or(self.default_backpressure_policy)
+    }
+
+    /// records pipeline events of every stream into the tracer
+    pub fn with_tracer(tracer: Arc<dyn PipelineTracer>) -> Self {
+        Self {
+            tracer: TraceHandle::new(tracer),
+            ..Self::new()
         }
     }
 
@@ -59,13 +411,23 @@ impl StreamCenter {
         self.event_sender.clone()
     }
 
+    /// notifications of the streams published or gone from now on
+    pub fn subscribe_notifications(&self) -> broadcast::Receiver<StreamNotification> {
+        self.notification_sender.subscribe()
+    }
+
+    fn notify(&self, notification: StreamNotification) {
+        // nobody watching is fine
+        let _ = self.notification_sender.send(notification);
+    }
+
     pub async fn run(&mut self) -> StreamCenterResult<()> {
         tracing::info!("stream center is running");
         loop {
             match self.event_receiver.recv().await {
                 None => {}
                 Some(event) => {
-                    if let Err(err) = self.process_event(event).await {
+                    if let Err(err) = self.process_event(event) {
                         tracing::error!("process stream center event failed, {:?}", err);
                     }
                 }
@@ -73,104 +435,667 @@ impl StreamCenter {
         }
     }
 
-    async fn process_event(&mut self, event: StreamCenterEvent) -> StreamCenterResult<()> {
+    /// only touches the registry, everything about a single stream is done by its own task
+    fn process_event(&mut self, event: StreamCenterEvent) -> StreamCenterResult<()> {
         tracing::info!("process event: {:?}", event);
         match event {
             StreamCenterEvent::Publish {
                 protocol,
                 stream_id,
                 context,
+                publisher,
+                playback,
+                result_sender,
+            } => self.process_publish_event(
+                protocol,
+                stream_id,
+                context,
+                publisher,
+                playback,
                 result_sender,
-            } => self.process_publish_event(protocol, stream_id, context, result_sender)?,
+            )?,
             StreamCenterEvent::Unpublish {
                 stream_id,
+                publisher_id,
                 result_sender,
-            } => {
-                self.process_unpublish_event(stream_id, result_sender)
-                    .await?
-            }
+            } => self.process_unpublish_event(stream_id, publisher_id, result_sender)?,
             StreamCenterEvent::Subscribe {
                 stream_id,
                 protocol,
                 result_sender,
                 context,
-            } => {
-                self.process_subscribe_event(stream_id, protocol, result_sender, context)
-                    .await?
-            }
+                media_selection,
+            } => self.process_subscribe_event(
+                stream_id,
+                protocol,
+                result_sender,
+                context,
+                media_selection,
+            ),
+            StreamCenterEvent::UpdateMediaSelection {
+                stream_id,
+                uuid,
+                media_selection,
+                result_sender,
+            } => self.process_update_media_selection_event(
+                self.subscribed_stream(stream_id, uuid),
+                uuid,
+                media_selection,
+                result_sender,
+            )?,
             StreamCenterEvent::Unsubscribe {
                 stream_id,
                 uuid,
                 result_sender,
-            } => {
-                self.process_unsubscribe_event(uuid, stream_id, result_sender)
-                    .await?
-            }
+            } => self.process_unsubscribe_event(uuid, stream_id, result_sender),
             StreamCenterEvent::Describe {
                 stream_id,
                 result_sender,
+            } => self.process_describe_event(&self.canonical(stream_id), result_sender),
+            StreamCenterEvent::DescribeConfigured {
+                stream_id,
+                result_sender,
+            } => self.process_describe_configured_event(self.canonical(stream_id), result_sender),
+            StreamCenterEvent::Trace {
+                stream_id,
+                result_sender,
+            } => self.process_trace_event(&self.canonical(stream_id), result_sender)?,
+            StreamCenterEvent::SetScale {
+                stream_id,
+                scale,
+                result_sender,
+            } => self.process_set_scale_event(&self.canonical(stream_id), scale, result_sender),
+            StreamCenterEvent::Keyframe {
+                stream_id,
+                result_sender,
+            } => self.send_signal(
+                &self.canonical(stream_id),
+                StreamSignal::Keyframe { result_sender },
+            ),
+            StreamCenterEvent::ConfigChanged { stream_id, change } => {
+                self.process_config_changed_event(&stream_id, change)
+            }
+            StreamCenterEvent::PublisherStalled { stream_id, idle } => {
+                tracing::warn!(
+                    "publisher of stream {} stalled, no audio or video for {:?}",
+                    stream_id,
+                    idle
+                );
+                self.fail_over(&stream_id);
+                self.notify(StreamNotification::PublisherStalled { stream_id, idle });
+            }
+            StreamCenterEvent::PublisherResumed {
+                stream_id,
+                stalled_for,
             } => {
-                self.process_describe_event(&stream_id, result_sender)
-                    .await?;
+                tracing::info!(
+                    "publisher of stream {} resumed, stalled for {:?}",
+                    stream_id,
+                    stalled_for
+                );
+                self.notify(StreamNotification::PublisherResumed {
+                    stream_id,
+                    stalled_for,
+                });
             }
-        }
-        Ok(())
-    }
-
-    async fn process_describe_event(
-        &self,
-        stream_id: &StreamIdentifier,
-        result_sender: oneshot::Sender<StreamCenterResult<StreamDescription>>,
-    ) -> StreamCenterResult<()> {
-        if !self.streams.contains_key(stream_id) {
-            return result_sender
-                .send(Err(StreamCenterError::StreamNotFound(stream_id.clone())))
+            StreamCenterEvent::TimestampDiscontinuity {
+                stream_id,
+                jump_nano,
+            } => {
+                tracing::warn!(
+                    "timestamps of stream {} jumped by {}ms, anchored again",
+                    stream_id,
+                    jump_nano / 1_000_000
+                );
+                self.notify(StreamNotification::TimestampDiscontinuity {
+                    stream_id,
+                    jump_nano,
+                });
+            }
+            StreamCenterEvent::ReapIdleStream {
+                stream_id,
+                source_id,
+                idle,
+            } => self.process_reap_idle_stream_event(&stream_id, source_id, idle),
+            StreamCenterEvent::RtcpPeerDescribed {
+                stream_id,
+                subscriber_id,
+                peer,
+            } => self.send_signal(
+                &match subscriber_id {
+                    Some(subscriber_id) => self.subscribed_stream(stream_id, subscriber_id),
+                    None => stream_id,
+                },
+                StreamSignal::RtcpPeerDescribed {
+                    subscriber_id,
+                    peer,
+                },
+            ),
+            StreamCenterEvent::RtmpControlNegotiated {
+                stream_id,
+                subscriber_id,
+                control,
+            } => self.send_signal(
+                &match subscriber_id {
+                    Some(subscriber_id) => self.subscribed_stream(stream_id, subscriber_id),
+                    None => stream_id,
+                },
+                StreamSignal::RtmpControlNegotiated {
+                    subscriber_id,
+                    control,
+                },
+            ),
+            StreamCenterEvent::RtpReceiveReported { stream_id, stats } => {
+                self.send_signal(&stream_id, StreamSignal::RtpReceiveReported { stats })
+            }
+            StreamCenterEvent::Drain {
+                request,
+                result_sender,
+            } => self.process_drain_event(request, result_sender)?,
+            StreamCenterEvent::DrainGraceExpired { drain_id } => {
+                self.process_drain_grace_expired_event(drain_id)
+            }
+            StreamCenterEvent::Snapshot { result_sender } => {
+                self.process_snapshot_event(result_sender)?
+            }
+            StreamCenterEvent::SetAlias {
+                alias,
+                canonical,
+                result_sender,
+            } => result_sender
+                .send(self.set_alias(alias, canonical))
                 .map_err(|err| {
-                    tracing::error!("deliver describe fail result to caller failed, {:?}", err);
+                    tracing::error!("deliver set alias result to caller failed, {:?}", err);
                     StreamCenterError::ChannelSendFailed {
                         backtrace: Backtrace::capture(),
                     }
-                });
+                })?,
+            StreamCenterEvent::Aliases { result_sender } => {
+                result_sender.send(self.aliases.to_vec()).map_err(|err| {
+                    tracing::error!("deliver aliases to caller failed, {:?}", err);
+                    StreamCenterError::ChannelSendFailed {
+                        backtrace: Backtrace::capture(),
+                    }
+                })?
+            }
+            StreamCenterEvent::Streams { result_sender } => {
+                let mut streams: Vec<_> = self.streams.keys().cloned().collect();
+                streams.sort();
+                result_sender.send(streams).map_err(|err| {
+                    tracing::error!("deliver streams to caller failed, {:?}", err);
+                    StreamCenterError::ChannelSendFailed {
+                        backtrace: Backtrace::capture(),
+                    }
+                })?
+            }
         }
+        Ok(())
+    }
 
-        let stream = self.streams.get(stream_id).unwrap();
-        let subscribers: HashMap<Uuid, SubscriberInfo> = stream
-            .data_distributer
-            .read()
-            .await
+    fn process_snapshot_event(
+        &self,
+        result_sender: oneshot::Sender<StreamCenterSnapshot>,
+    ) -> StreamCenterResult<()> {
+        let sources = self
+            .streams
             .iter()
-            .map(|(id, v)| (*id, v.into()))
+            .filter_map(|(stream_id, handles)| {
+                let source = handles.playback.as_ref()?.source.as_ref()?;
+                Some(SourceSnapshot {
+                    app: stream_id.app.clone(),
+                    stream: stream_id.name.clone(),
+                    definition: source.definition(),
+                })
+            })
             .collect();
-        let dynamic_info = stream.stream_dynamic_info.read().await;
-        let description = StreamDescription {
-            publish_protocol: stream.publish_protocol,
-            stream_id: stream._stream_identifier.clone(),
-            video_config: dynamic_info.video_config.clone(),
-            has_video: dynamic_info.has_video,
-            audio_conifg: dynamic_info.audio_config.clone(),
-            has_audio: dynamic_info.has_audio,
-            publish_start_time: stream.publish_start_time,
-            subscribers,
+        let snapshot = StreamCenterSnapshot::new(
+            self.default_takeover_policy,
+            self.takeover_policies
+                .iter()
+                .map(|(app, policy)| (app.clone(), *policy))
+                .collect(),
+            self.aliases
+                .to_vec()
+                .into_iter()
+                .map(|(alias, canonical)| (alias.to_string(), canonical.to_string()))
+                .collect(),
+            sources,
+        );
+        tracing::info!(
+            "stream center snapshot taken, {} sources",
+            snapshot.sources.len()
+        );
+        result_sender.send(snapshot).map_err(|err| {
+            tracing::error!("deliver snapshot to caller failed, {:?}", err);
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })
+    }
+
+    fn process_set_scale_event(
+        &self,
+        stream_id: &StreamIdentity,
+        scale: f64,
+        result_sender: oneshot::Sender<StreamCenterResult<f64>>,
+    ) {
+        let result = match self.streams.get(stream_id) {
+            _ if !is_valid_scale(scale) => Err(StreamCenterError::InvalidScale(scale)),
+            None => Err(StreamCenterError::StreamNotFound(stream_id.clone())),
+            Some(StreamSourceHandles {
+                playback: Some(playback),
+                ..
+            }) if playback.scale_sender.send(scale).is_ok() => Ok(scale),
+            // live streams, or a recording that is over
+            Some(_) => Ok(1.0),
         };
-        result_sender.send(Ok(description)).map_err(|err| {
+        if result_sender.send(result).is_err() {
             tracing::error!(
-                "deliver describe success result to caller failed, {:?}",
-                err
+                "deliver set scale result to caller failed, stream: {}",
+                stream_id
             );
+        }
+    }
+
+    fn process_trace_event(
+        &self,
+        stream_id: &StreamIdentity,
+        result_sender: oneshot::Sender<StreamCenterResult<Vec<TraceRecord>>>,
+    ) -> StreamCenterResult<()> {
+        let res = if !self.tracer.is_enabled() {
+            Err(StreamCenterError::TracingDisabled)
+        } else if !self.streams.contains_key(stream_id) {
+            Err(StreamCenterError::StreamNotFound(stream_id.clone()))
+        } else {
+            Ok(self.tracer.snapshot(stream_id).unwrap_or_default())
+        };
+        result_sender.send(res).map_err(|err| {
+            tracing::error!("deliver trace result to caller failed, {:?}", err);
             StreamCenterError::ChannelSendFailed {
                 backtrace: Backtrace::capture(),
             }
-        })?;
-        Ok(())
+        })
+    }
+
+    fn process_config_changed_event(&self, stream_id: &StreamIdentity, change: StreamConfigChange) {
+        if !self.streams.contains_key(stream_id) {
+            tracing::warn!(
+                "got config change of a stream already gone: {}, change: {:?}",
+                stream_id,
+                change
+            );
+            return;
+        }
+        tracing::info!(
+            "publisher of stream {} changed its parameters, generation: {}, video changed: {}, audio changed: {}, resolution: {:?}x{:?}",
+            stream_id,
+            change.config_generation,
+            change.video_changed,
+            change.audio_changed,
+            change.width,
+            change.height
+        );
+        self.notify(StreamNotification::ConfigChanged {
+            stream_id: stream_id.clone(),
+            change,
+        });
+    }
+
+    fn process_describe_event(
+        &self,
+        stream_id: &StreamIdentity,
+        result_sender: oneshot::Sender<StreamCenterResult<StreamDescription>>,
+    ) {
+        self.send_signal(stream_id, StreamSignal::Describe { result_sender });
+    }
+
+    fn process_describe_configured_event(
+        &mut self,
+        stream_id: StreamIdentity,
+        result_sender: oneshot::Sender<StreamCenterResult<StreamDescription>>,
+    ) {
+        if self.streams.contains_key(&stream_id) {
+            return self.send_signal(
+                &stream_id,
+                StreamSignal::DescribeConfigured { result_sender },
+            );
+        }
+        tracing::info!(
+            "stream {} is not published yet, describe waits for it",
+            stream_id
+        );
+        let waiters = self.describe_waiters.entry(stream_id).or_default();
+        // the callers gave up on the ones closed
+        waiters.retain(|waiter| !waiter.is_closed());
+        waiters.push(result_sender);
+    }
+
+    /// hands the signal to the task of the stream, which answers the caller itself,
+    /// so the stream center never waits for a busy stream.
+    /// the caller gets StreamNotFound if the stream or its task is gone
+    fn send_signal(&self, stream_id: &StreamIdentity, signal: StreamSignal) {
+        let signal = match self.streams.get(stream_id) {
+            None => signal,
+            Some(handles) => match handles.signal_sender.send(signal) {
+                Ok(()) => return,
+                Err(err) => {
+                    tracing::error!("the task of stream {} is gone", stream_id);
+                    err.0
+                }
+            },
+        };
+        let err = StreamCenterError::StreamNotFound(stream_id.clone());
+        let delivered = match signal {
+            StreamSignal::Stop { .. }
+            | StreamSignal::UpdateMediaSelection { .. }
+            | StreamSignal::RtcpPeerDescribed { .. }
+            | StreamSignal::RtmpControlNegotiated { .. }
+            | StreamSignal::RtpReceiveReported { .. }
+            | StreamSignal::Mirror { .. }
+            | StreamSignal::FailOver { .. } => true,
+            StreamSignal::Subscribe { result_sender, .. } => result_sender.send(Err(err)).is_ok(),
+            StreamSignal::Unsubscribe { result_sender, .. } => result_sender.send(Err(err)).is_ok(),
+            StreamSignal::Describe { result_sender }
+            | StreamSignal::DescribeConfigured { result_sender } => {
+                result_sender.send(Err(err)).is_ok()
+            }
+            StreamSignal::Keyframe { result_sender } => result_sender.send(Err(err)).is_ok(),
+        };
+        if !delivered {
+            tracing::error!(
+                "deliver stream not found result of stream {} to caller failed",
+                stream_id
+            );
+        }
+    }
+
+    /// a stalled stream with a backup gets the frames of the backup until its publisher resumes,
+    /// the stalled stream ends its subscribers' stream itself if there is no backup to fail over to
+    fn fail_over(&self, stream_id: &StreamIdentity) {
+        let Some(backup_id) = self
+            .streams
+            .get(stream_id)
+            .and_then(|handles| handles.backup.as_ref())
+        else {
+            return;
+        };
+        let backup_frames = self.streams.get(backup_id).and_then(|backup| {
+            let (sender, receiver) = mpsc::channel(FAILOVER_CHANNEL_CAPACITY);
+            backup
+                .signal_sender
+                .send(StreamSignal::Mirror { sender })
+                .ok()
+                .map(|_| receiver)
+        });
+        if backup_frames.is_some() {
+            tracing::info!("stream {} fails over to {}", stream_id, backup_id);
+        } else {
+            tracing::warn!(
+                "backup {} of stream {} is not published, can not fail over",
+                backup_id,
+                stream_id
+            );
+        }
+        self.send_signal(stream_id, StreamSignal::FailOver { backup_frames });
+    }
+
+    /// whether the current publisher of the stream can be kicked by a new one
+    fn can_takeover(&self, stream_id: &StreamIdentity) -> bool {
+        let Some(handles) = self.streams.get(stream_id) else {
+            return false;
+        };
+        if handles.publisher.is_none() {
+            return false;
+        }
+        match self.get_takeover_policy(&stream_id.app) {
+            TakeoverPolicy::Reject => false,
+            TakeoverPolicy::KickOld => true,
+            TakeoverPolicy::TakeoverIfIdle(idle) => handles.activity.idle_duration() >= idle,
+        }
+    }
+
+    fn kick_publisher(
+        &mut self,
+        stream_id: &StreamIdentity,
+        protocol: PublishProtocol,
+        reason: KickReason,
+    ) {
+        let Some(handles) = self.streams.remove(stream_id) else {
+            return;
+        };
+        let idle = handles.activity.idle_duration();
+        tracing::info!(
+            "publisher of stream {:?} is taken over by a new {:?} publisher, reason: {:?}, idle for {:?}",
+            stream_id,
+            protocol,
+            reason,
+            idle
+        );
+        self.tracer
+            .record(stream_id, || TraceEvent::PublisherTakeover {
+                protocol,
+                previous_idle_ms: idle.as_millis() as u64,
+            });
+
+        Self::stop_source(stream_id, handles, PublisherKicked { reason, idle });
+        self.notify(StreamNotification::Kicked {
+            stream_id: stream_id.clone(),
+            reason,
+        });
+    }
+
+    /// the idle watchdog of the source gave up on the publisher
+    fn process_reap_idle_stream_event(
+        &mut self,
+        stream_id: &StreamIdentity,
+        source_id: Uuid,
+        idle: Duration,
+    ) {
+        if self
+            .streams
+            .get(stream_id)
+            .is_none_or(|handles| handles.source_id != source_id)
+        {
+            tracing::info!(
+                "ignore reap request of stream {} from source {}, which no longer owns it",
+                stream_id,
+                source_id
+            );
+            return;
+        }
+        let Some(handles) = self.streams.remove(stream_id) else {
+            return;
+        };
+        tracing::warn!(
+            "reap stream {}, no audio or video from its publisher for {:?}. total stream count: {}",
+            stream_id,
+            idle,
+            self.streams.len()
+        );
+        self.tracer.remove(stream_id);
+        Self::stop_source(
+            stream_id,
+            handles,
+            PublisherKicked {
+                reason: KickReason::IdleTimeout,
+                idle,
+            },
+        );
+        self.notify(StreamNotification::Kicked {
+            stream_id: stream_id.clone(),
+            reason: KickReason::IdleTimeout,
+        });
+        self.finish_drain(stream_id, DrainOutcome::Disconnected);
+    }
+
+    /// the stream is removed from the registry already
+    fn stop_source(
+        stream_id: &StreamIdentity,
+        handles: StreamSourceHandles,
+        kicked: PublisherKicked,
+    ) {
+        if let Err(err) = handles.signal_sender.send(StreamSignal::Stop {
+            reason: EndOfStreamReason::Kicked(kicked.reason),
+        }) {
+            tracing::error!("send stop signal to stream source failed, {:?}", err);
+        }
+        if let Some(publisher) = handles.publisher
+            && publisher.kick_sender.send(kicked).is_err()
+        {
+            tracing::warn!(
+                "the kicked publisher {} of stream {:?} is already gone",
+                publisher.id,
+                stream_id
+            );
+        }
+    }
+
+    /// the kickable publishers not drained yet are asked to move, non kickable ones are left alone
+    fn process_drain_event(
+        &mut self,
+        request: DrainRequest,
+        result_sender: oneshot::Sender<StreamCenterResult<DrainSummary>>,
+    ) -> StreamCenterResult<()> {
+        let drain_id = Uuid::now_v7();
+        let mut streams = Vec::new();
+        for (stream_id, handles) in self.streams.iter_mut() {
+            let Some(publisher) = handles.publisher.as_mut() else {
+                continue;
+            };
+            if self.draining.contains_key(stream_id) {
+                continue;
+            }
+            if publisher
+                .drain_sender
+                .take()
+                .is_none_or(|drain_sender| drain_sender.send(request.clone()).is_err())
+            {
+                tracing::info!(
+                    "publisher {} of stream {} can not be asked to move, it is kicked after {:?}",
+                    publisher.id,
+                    stream_id,
+                    request.grace
+                );
+            }
+            self.draining.insert(
+                stream_id.clone(),
+                DrainedPublisher {
+                    drain_id,
+                    publisher_id: publisher.id,
+                },
+            );
+            streams.push(stream_id.clone());
+        }
+        tracing::info!(
+            "drain {} to {}, grace period: {:?}, streams: {:?}",
+            drain_id,
+            request.target,
+            request.grace,
+            streams
+        );
+        let event_sender = self.event_sender.clone();
+        let grace = request.grace;
+        tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+            let _ = event_sender.send(StreamCenterEvent::DrainGraceExpired { drain_id });
+        });
+        result_sender
+            .send(Ok(DrainSummary { drain_id, streams }))
+            .map_err(|err| {
+                tracing::error!("deliver drain result to caller failed, {:?}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })
+    }
+
+    /// the publishers of the drain still here are kicked
+    fn process_drain_grace_expired_event(&mut self, drain_id: Uuid) {
+        let expired: Vec<_> = self
+            .draining
+            .iter()
+            .filter(|(_, drained)| drained.drain_id == drain_id)
+            .map(|(stream_id, drained)| (stream_id.clone(), drained.publisher_id))
+            .collect();
+        for (stream_id, publisher_id) in expired {
+            let owned = self.streams.get(&stream_id).is_some_and(|handles| {
+                handles
+                    .publisher
+                    .as_ref()
+                    .is_some_and(|publisher| publisher.id == publisher_id)
+            });
+            if owned && let Some(handles) = self.streams.remove(&stream_id) {
+                let idle = handles.activity.idle_duration();
+                tracing::info!(
+                    "drained publisher {} of stream {} is still here after the grace period, kicking it",
+                    publisher_id,
+                    stream_id
+                );
+                self.tracer.remove(&stream_id);
+                Self::stop_source(
+                    &stream_id,
+                    handles,
+                    PublisherKicked {
+                        reason: KickReason::Drain,
+                        idle,
+                    },
+                );
+                self.notify(StreamNotification::Kicked {
+                    stream_id: stream_id.clone(),
+                    reason: KickReason::Drain,
+                });
+            }
+            self.finish_drain(&stream_id, DrainOutcome::Disconnected);
+        }
+    }
+
+    /// the drained publisher of the stream is gone, if there is one
+    fn finish_drain(&mut self, stream_id: &StreamIdentity, outcome: DrainOutcome) {
+        let Some(drained) = self.draining.remove(stream_id) else {
+            return;
+        };
+        tracing::info!(
+            "drained publisher {} of stream {} is gone, outcome: {:?}",
+            drained.publisher_id,
+            stream_id,
+            outcome
+        );
+        self.notify(StreamNotification::Drained {
+            stream_id: stream_id.clone(),
+            outcome,
+        });
     }
 
     fn process_publish_event(
         &mut self,
         protocol: PublishProtocol,
-        stream_id: StreamIdentifier,
+        stream_id: StreamIdentity,
         context: HashMap<String, String>,
+        publisher: Option<PublisherHandle>,
+        playback: Option<PlaybackControl>,
         result_sender: oneshot::Sender<StreamCenterResult<mpsc::Sender<MediaFrame>>>,
     ) -> StreamCenterResult<()> {
+        if let Some(canonical) = self.aliases.resolve(&stream_id) {
+            let err = StreamCenterError::PublishToAlias {
+                alias: stream_id.clone(),
+                canonical: canonical.clone(),
+            };
+            tracing::warn!("refuse to publish, {}", err);
+            return result_sender.send(Err(err)).map_err(|err| {
+                tracing::error!("deliver publish fail result to caller failed, {:?}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            });
+        }
+        if self.draining.contains_key(&stream_id) {
+            // most likely the drained publisher reconnecting, whatever the takeover policy
+            self.kick_publisher(&stream_id, protocol, KickReason::Drain);
+            self.finish_drain(&stream_id, DrainOutcome::RepublishedLocally);
+        } else if self.can_takeover(&stream_id) {
+            let policy = self.get_takeover_policy(&stream_id.app);
+            self.kick_publisher(&stream_id, protocol, KickReason::Takeover(policy));
+        }
         if self.streams.contains_key(&stream_id) {
             return result_sender
                 .send(Err(StreamCenterError::DuplicateStream(stream_id.clone())))
@@ -183,38 +1108,58 @@ impl StreamCenter {
         }
 
         let (frame_sender, frame_receiver) = mpsc::channel(128);
-        let (signal_sender, signal_receiver) = mpsc::channel(1);
-        let data_distributer = Arc::new(RwLock::new(HashMap::new()));
-        let stream_source_dynamic_info = Arc::new(RwLock::new(StreamSourceDynamicInfo {
-            has_video: true,
-            has_audio: true,
-            video_config: None,
-            audio_config: None,
-        }));
+        let (signal_sender, signal_receiver) = mpsc::unbounded_channel();
+        let backup = backup_stream(&stream_id, &context);
 
         let mut source = StreamSource::new(
-            &stream_id.stream_name,
-            &stream_id.app,
+            stream_id.clone(),
             protocol,
             frame_receiver,
             signal_receiver,
-            Arc::clone(&data_distributer),
-            Arc::clone(&stream_source_dynamic_info),
-        );
+            self.event_sender.clone(),
+            self.tracer.clone(),
+            self.latency.map(LatencyProbe::new),
+        )
+        .with_idle_watchdog(self.get_idle_watchdog(&stream_id.app))
+        .with_gop_cache_budget(&self.gop_cache_budget)
+        .with_recovery_point_join(self.get_recovery_point_join(&stream_id.app))
+        .with_audio_gap_concealment(self.get_audio_gap_concealment(&stream_id.app))
+        .with_timestamp_normalization(self.get_timestamp_normalization(&stream_id.app))
+        .with_slice_integrity(self.get_slice_integrity(&stream_id.app))
+        .with_dvr_window(self.get_dvr_window(&stream_id.app))
+        .with_backpressure(
+            self.get_backpressure_policy(&stream_id.app),
+            publisher
+                .as_ref()
+                .map(|publisher| publisher.backpressure_sender.clone()),
+        )
+        .with_backup(backup.clone())
+        .with_recording(playback.is_some());
+        #[cfg(feature = "frame-crc")]
+        source.set_frame_crc(self.frame_crc);
 
         self.streams.insert(
             stream_id.clone(),
             StreamSourceHandles {
                 signal_sender,
                 _source_sender: frame_sender.clone(),
-                _stream_identifier: stream_id.clone(),
-                data_distributer,
-                stream_dynamic_info: stream_source_dynamic_info,
-                publish_protocol: protocol,
-                publish_start_time: source.publish_start_time,
+                activity: Arc::clone(&source.activity),
+                source_id: source.source_id,
+                publisher,
+                playback,
+                backup,
             },
         );
-        tokio::spawn(async move { source.run().await });
+        // the fan-out of the stream logs with its key, like the sessions publishing and playing it
+        let span =
+            tracing::error_span!(parent: None, "stream", stream_key = %stream_id, role = "fanout");
+        tokio::spawn(async move { source.run().await }.instrument(span));
+        for result_sender in self.describe_waiters.remove(&stream_id).unwrap_or_default() {
+            self.send_signal(
+                &stream_id,
+                StreamSignal::DescribeConfigured { result_sender },
+            );
+        }
 
         result_sender.send(Ok(frame_sender)).map_err(|err| {
             tracing::error!("deliver publish success result to caller failed, {:?}", err);
@@ -225,20 +1170,55 @@ impl StreamCenter {
 
         tracing::info!(
             "publish new stream success, stream_name: {}, app: {}, context: {:?}. total stream count: {}",
-            &stream_id.stream_name,
+            &stream_id.name,
             &stream_id.app,
             context,
             self.streams.len()
         );
+        if self.restoring.remove(&stream_id) {
+            self.notify(StreamNotification::Restored {
+                stream_id,
+                protocol,
+            });
+        } else {
+            self.notify(StreamNotification::Published {
+                stream_id,
+                protocol,
+            });
+        }
 
         Ok(())
     }
 
-    async fn process_unpublish_event(
+    fn process_unpublish_event(
         &mut self,
-        stream_id: StreamIdentifier,
+        stream_id: StreamIdentity,
+        publisher_id: Option<Uuid>,
         result_sender: oneshot::Sender<StreamCenterResult<()>>,
     ) -> StreamCenterResult<()> {
+        if let Some(publisher_id) = publisher_id
+            && let Some(handles) = self.streams.get(&stream_id)
+            && handles
+                .publisher
+                .as_ref()
+                .is_none_or(|publisher| publisher.id != publisher_id)
+        {
+            // the publisher has been taken over, the stream belongs to someone else now
+            tracing::info!(
+                "ignore unpublish of stream {:?} from publisher {}, which no longer owns it",
+                stream_id,
+                publisher_id
+            );
+            return result_sender.send(Ok(())).map_err(|err| {
+                tracing::error!(
+                    "deliver unpublish success result to caller failed, {:?}",
+                    err
+                );
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            });
+        }
         let removed = self.streams.remove(&stream_id);
         match removed {
             None => result_sender
@@ -253,16 +1233,12 @@ impl StreamCenter {
                     }
                 }),
             Some(handles) => {
-                let _ = handles
-                    .signal_sender
-                    .send(StreamSignal::Stop)
-                    .await
-                    .map_err(|err| {
-                        tracing::error!("send stop signal to stream source failed, {:?}", err);
-                        StreamCenterError::ChannelSendFailed {
-                            backtrace: Backtrace::capture(),
-                        }
-                    });
+                self.tracer.remove(&stream_id);
+                if let Err(err) = handles.signal_sender.send(StreamSignal::Stop {
+                    reason: EndOfStreamReason::Unpublished,
+                }) {
+                    tracing::error!("send stop signal to stream source failed, {:?}", err);
+                }
 
                 result_sender.send(Ok(())).map_err(|err| {
                     tracing::error!(
@@ -275,148 +1251,314 @@ impl StreamCenter {
                 })?;
                 tracing::info!(
                     "ubpublish stream success, stream_name: {}, app: {} total stream count: {}",
-                    &stream_id.stream_name,
+                    &stream_id.name,
                     &stream_id.app,
                     self.streams.len()
                 );
+                self.finish_drain(&stream_id, DrainOutcome::Left);
+                self.notify(StreamNotification::Unpublished { stream_id });
                 Ok(())
             }
         }
     }
 
-    async fn process_subscribe_event(
+    fn process_subscribe_event(
         &mut self,
-        stream_id: StreamIdentifier,
+        stream_id: StreamIdentity,
         protocol: PlayProtocol,
         result_sender: oneshot::Sender<StreamCenterResult<SubscribeResponse>>,
         context: HashMap<String, String>,
-    ) -> StreamCenterResult<()> {
+        media_selection: MediaSelection,
+    ) {
+        let (tx, rx) = mpsc::channel(100_000);
+        let uuid = Uuid::now_v7();
+        let parsed_context: ParsedContext = (&context).into();
+        let (stream_id, alias) = match self.aliases.resolve(&stream_id) {
+            Some(canonical) => (canonical.clone(), Some(stream_id)),
+            None => (stream_id, None),
+        };
         if !self.streams.contains_key(&stream_id) {
-            return result_sender
-                .send(Err(StreamCenterError::StreamNotFound(stream_id.clone())))
-                .map_err(|err| {
+            // the canonical stream of an alias, which is the one to be published
+            self.notify(StreamNotification::SubscribeMissed {
+                stream_id: stream_id.clone(),
+                protocol,
+            });
+        }
+        if let Some(alias) = &alias {
+            if !self.streams.contains_key(&stream_id) {
+                // the subscriber knows the stream by the alias only
+                if result_sender
+                    .send(Err(StreamCenterError::StreamNotFound(alias.clone())))
+                    .is_err()
+                {
                     tracing::error!(
-                        "deliver subscribe failed result to caller failed, {:?}",
-                        err
+                        "deliver stream not found result of alias {} to caller failed",
+                        alias
                     );
-                    StreamCenterError::StreamNotFound(stream_id.clone())
-                });
+                }
+                return;
+            }
+            self.alias_subscribers
+                .retain(|_, subscribed| self.streams.contains_key(subscribed));
+            self.alias_subscribers.insert(uuid, stream_id.clone());
+            self.notify(StreamNotification::SubscribedViaAlias {
+                stream_id: stream_id.clone(),
+                alias: alias.clone(),
+                subscriber_id: uuid,
+                protocol,
+            });
+        } else if alias.is_none() {
+            self.notify(StreamNotification::Subscribed {
+                stream_id: stream_id.clone(),
+                subscriber_id: uuid,
+                protocol,
+            });
         }
-
-        let (tx, rx) = mpsc::channel(100_000);
-        let uuid = Uuid::now_v7();
-        let source_has_video;
-        let source_has_audio;
-        {
-            let parsed_context: ParsedContext = (&context).into();
-            let stream = self.streams.get_mut(&stream_id).expect("this must exist");
-            stream.data_distributer.write().await.insert(
-                uuid,
-                SubscribeHandler {
+        self.send_signal(
+            &stream_id,
+            StreamSignal::Subscribe {
+                handler: SubscribeHandler {
                     id: uuid,
                     context,
                     play_protocol: protocol,
                     parsed_context,
+                    media_selection,
                     data_sender: tx,
                     stat: Default::default(),
+                    rtcp_peers: Default::default(),
+                    rtmp_control: None,
+                    alias: alias.clone(),
+                    wait_video_key_frame: false,
+                    timeshift_cursor: None,
                 },
-            );
-            let info = stream.stream_dynamic_info.read().await;
-            source_has_video = info.has_video;
-            source_has_audio = info.has_audio;
-        }
-
-        result_sender
-            .send(Ok(SubscribeResponse {
-                subscribe_id: uuid,
-                has_video: source_has_video,
-                has_audio: source_has_audio,
                 media_receiver: rx,
-            }))
-            .map_err(|err| {
-                tracing::error!(
-                    "deliver subscribe success result to caller failed, {:?}",
-                    err
-                );
-                StreamCenterError::ChannelSendFailed {
-                    backtrace: Backtrace::capture(),
-                }
-            })?;
+                result_sender,
+            },
+        );
         tracing::info!(
-            "subscribe stream success, stream_name: {}, app: {}, uuid: {}",
-            &stream_id.stream_name,
+            "subscribe stream, stream_name: {}, app: {}, alias: {:?}, uuid: {}, media selection: {:?}",
+            &stream_id.name,
             &stream_id.app,
+            alias.map(|alias| alias.to_string()),
             uuid,
+            media_selection,
         );
-        Ok(())
     }
 
-    async fn process_unsubscribe_event(
+    fn process_update_media_selection_event(
         &mut self,
+        stream_id: StreamIdentity,
         uuid: Uuid,
-        stream_id: StreamIdentifier,
+        media_selection: MediaSelection,
         result_sender: oneshot::Sender<StreamCenterResult<()>>,
     ) -> StreamCenterResult<()> {
-        if !self.streams.contains_key(&stream_id) {
+        let Some(stream) = self.streams.get(&stream_id) else {
             return result_sender
                 .send(Err(StreamCenterError::StreamNotFound(stream_id.clone())))
                 .map_err(|err| {
                     tracing::error!(
-                        "deliver unsubscribe fail result to caller failed, {:?}",
+                        "deliver update media selection fail result to caller failed, {:?}",
                         err
                     );
                     StreamCenterError::StreamNotFound(stream_id.clone())
                 });
-        }
-        {
-            let removed = self
-                .streams
-                .get_mut(&stream_id)
-                .expect("this must exist")
-                .data_distributer
-                .write()
-                .await
-                .remove(&uuid);
-            if let Some(handler) = removed {
-                tracing::info!("unsubscribe done, stat: {:?}", handler.stat);
+        };
+        // the fan-out task owns the subscribers, let it apply the change in between frames
+        let res = stream
+            .signal_sender
+            .send(StreamSignal::UpdateMediaSelection {
+                subscriber_id: uuid,
+                media_selection,
+            })
+            .map_err(|err| {
+                tracing::error!(
+                    "send update media selection signal to stream source failed, {:?}",
+                    err
+                );
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            });
+        result_sender.send(res).map_err(|err| {
+            tracing::error!(
+                "deliver update media selection result to caller failed, {:?}",
+                err
+            );
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
             }
+        })?;
+        Ok(())
+    }
+
+    fn process_unsubscribe_event(
+        &mut self,
+        uuid: Uuid,
+        stream_id: StreamIdentity,
+        result_sender: oneshot::Sender<StreamCenterResult<()>>,
+    ) {
+        let stream_id = match self.alias_subscribers.remove(&uuid) {
+            Some(subscribed) => subscribed,
+            None => self.canonical(stream_id),
+        };
+        if self.streams.contains_key(&stream_id) {
+            self.notify(StreamNotification::Unsubscribed {
+                stream_id: stream_id.clone(),
+                subscriber_id: uuid,
+            });
         }
+        self.send_signal(
+            &stream_id,
+            StreamSignal::Unsubscribe {
+                subscriber_id: uuid,
+                result_sender,
+            },
+        );
+        tracing::info!(
+            "unsubscribe stream, stream_name: {}, app: {}, uuid: {}",
+            &stream_id.name,
+            &stream_id.app,
+            uuid,
+        );
+    }
+
+    pub async fn publish(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        protocol: PublishProtocol,
+        stream_id: &StreamIdentity,
+        context: &HashMap<String, String>,
+    ) -> StreamCenterResult<Sender<MediaFrame>> {
+        Self::send_publish(
+            stream_center_event_sender,
+            protocol,
+            stream_id,
+            context,
+            None,
+            None,
+        )
+        .await
+    }
+
+    /// like publish, for publishers replaying a recording,
+    /// the scales players ask for through set_scale arrive at the scale_receiver of the response
+    pub async fn publish_recording(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        protocol: PublishProtocol,
+        stream_id: &StreamIdentity,
+        context: &HashMap<String, String>,
+    ) -> StreamCenterResult<RecordingPublishResponse> {
+        Self::send_publish_recording(
+            stream_center_event_sender,
+            protocol,
+            stream_id,
+            context,
+            None,
+        )
+        .await
+    }
+
+    /// like publish_recording, the source is snapshotted along with the stream center,
+    /// so that it can be started again after a restart
+    pub async fn publish_restorable_recording(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        protocol: PublishProtocol,
+        stream_id: &StreamIdentity,
+        context: &HashMap<String, String>,
+        source: Arc<dyn SourceSnapshotter>,
+    ) -> StreamCenterResult<RecordingPublishResponse> {
+        Self::send_publish_recording(
+            stream_center_event_sender,
+            protocol,
+            stream_id,
+            context,
+            Some(source),
+        )
+        .await
+    }
 
-        result_sender.send(Ok(())).map_err(|err| {
-            tracing::error!(
-                "deliver unsubscribe success result to caller failed, {:?}",
-                err
-            );
-            StreamCenterError::ChannelSendFailed {
-                backtrace: Backtrace::capture(),
-            }
-        })?;
-        tracing::info!(
-            "unsubscribe stream success, stream_name: {}, app: {}, uuid: {}",
-            &stream_id.stream_name,
-            &stream_id.app,
-            uuid,
-        );
-        Ok(())
+    async fn send_publish_recording(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        protocol: PublishProtocol,
+        stream_id: &StreamIdentity,
+        context: &HashMap<String, String>,
+        source: Option<Arc<dyn SourceSnapshotter>>,
+    ) -> StreamCenterResult<RecordingPublishResponse> {
+        let (scale_sender, scale_receiver) = mpsc::unbounded_channel();
+        let media_sender = Self::send_publish(
+            stream_center_event_sender,
+            protocol,
+            stream_id,
+            context,
+            None,
+            Some(PlaybackControl {
+                scale_sender,
+                source,
+            }),
+        )
+        .await?;
+        Ok(RecordingPublishResponse {
+            media_sender,
+            scale_receiver,
+        })
     }
 
-    pub async fn publish(
+    /// like publish, but the publisher can be kicked by a later one if the takeover policy of the app allows,
+    /// the kicked_receiver of the response fires then.
+    /// the publisher is also told through the backpressure_receiver if the server can not keep up
+    pub async fn publish_kickable(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        protocol: PublishProtocol,
+        stream_id: &StreamIdentity,
+        context: &HashMap<String, String>,
+    ) -> StreamCenterResult<PublishResponse> {
+        let publisher_id = Uuid::now_v7();
+        let (kick_sender, kicked_receiver) = oneshot::channel();
+        let (drain_sender, drain_receiver) = oneshot::channel();
+        let (backpressure_sender, backpressure_receiver) = mpsc::unbounded_channel();
+        let media_sender = Self::send_publish(
+            stream_center_event_sender,
+            protocol,
+            stream_id,
+            context,
+            Some(PublisherHandle {
+                id: publisher_id,
+                kick_sender,
+                drain_sender: Some(drain_sender),
+                backpressure_sender,
+            }),
+            None,
+        )
+        .await?;
+        Ok(PublishResponse {
+            publisher_id,
+            media_sender,
+            kicked_receiver,
+            drain_receiver,
+            backpressure_receiver,
+        })
+    }
+
+    async fn send_publish(
         stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
         protocol: PublishProtocol,
-        stream_id: &StreamIdentifier,
+        stream_id: &StreamIdentity,
         context: &HashMap<String, String>,
+        publisher: Option<PublisherHandle>,
+        playback: Option<PlaybackControl>,
     ) -> StreamCenterResult<Sender<MediaFrame>> {
         let (tx, rx) = oneshot::channel();
         let span = tracing::trace_span!(
             "publish",
-            app = stream_id.app,
-            stream_name = stream_id.stream_name
+            app = %stream_id.app,
+            stream_name = %stream_id.name
         );
         stream_center_event_sender
             .send(StreamCenterEvent::Publish {
                 protocol,
                 stream_id: stream_id.clone(),
                 context: context.clone(),
+                publisher,
+                playback,
                 result_sender: tx,
             })
             .map_err(|err| {
@@ -424,7 +1566,7 @@ impl StreamCenter {
                 tracing::error!(
                     "send publish event to stream center failed, {:?}. stream_name: {}, app: {}",
                     err,
-                    stream_id.stream_name,
+                    stream_id.name,
                     stream_id.app,
                 );
                 StreamCenterError::ChannelSendFailed {
@@ -455,18 +1597,36 @@ impl StreamCenter {
 
     pub async fn unpublish(
         stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
-        stream_id: &StreamIdentifier,
+        stream_id: &StreamIdentity,
+    ) -> StreamCenterResult<()> {
+        Self::send_unpublish(stream_center_event_sender, stream_id, None).await
+    }
+
+    /// unpublish only if the publisher still owns the stream, which is not the case after it is kicked
+    pub async fn unpublish_publisher(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+        publisher_id: Uuid,
+    ) -> StreamCenterResult<()> {
+        Self::send_unpublish(stream_center_event_sender, stream_id, Some(publisher_id)).await
+    }
+
+    async fn send_unpublish(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+        publisher_id: Option<Uuid>,
     ) -> StreamCenterResult<()> {
         let (tx, rx) = oneshot::channel();
         let span = tracing::trace_span!(
             "unpublish",
-            app = stream_id.app,
-            stream_name = stream_id.stream_name
+            app = %stream_id.app,
+            stream_name = %stream_id.name
         );
 
         stream_center_event_sender
             .send(StreamCenterEvent::Unpublish {
                 stream_id: stream_id.clone(),
+                publisher_id,
                 result_sender: tx,
             })
             .map_err(|err| {
@@ -501,14 +1661,15 @@ impl StreamCenter {
     pub async fn subscribe(
         stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
         protocol: PlayProtocol,
-        stream_id: &StreamIdentifier,
+        stream_id: &StreamIdentity,
         context: &HashMap<String, String>,
+        media_selection: MediaSelection,
     ) -> StreamCenterResult<SubscribeResponse> {
         let (tx, rx) = oneshot::channel();
         let span = tracing::trace_span!(
             "subscribe",
-            app = stream_id.app,
-            stream_name = stream_id.stream_name
+            app = %stream_id.app,
+            stream_name = %stream_id.name
         );
 
         stream_center_event_sender
@@ -516,6 +1677,7 @@ impl StreamCenter {
                 stream_id: stream_id.clone(),
                 protocol,
                 context: context.clone(),
+                media_selection,
                 result_sender: tx,
             })
             .map_err(|err| {
@@ -552,14 +1714,14 @@ impl StreamCenter {
     pub async fn unsubscribe(
         stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
         uuid: Uuid,
-        stream_id: &StreamIdentifier,
+        stream_id: &StreamIdentity,
     ) -> StreamCenterResult<()> {
         let (tx, rx) = oneshot::channel();
         let span = tracing::trace_span!(
             "unsubscribe",
             uuid = %uuid,
-            app = stream_id.app,
-            stream_name = stream_id.stream_name
+            app = %stream_id.app,
+            stream_name = %stream_id.name
         );
 
         stream_center_event_sender
@@ -596,15 +1758,141 @@ impl StreamCenter {
         }
     }
 
+    pub async fn update_media_selection(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        uuid: Uuid,
+        stream_id: &StreamIdentity,
+        media_selection: MediaSelection,
+    ) -> StreamCenterResult<()> {
+        let (tx, rx) = oneshot::channel();
+        let span = tracing::trace_span!(
+            "update media selection",
+            uuid = %uuid,
+            app = %stream_id.app,
+            stream_name = %stream_id.name
+        );
+
+        stream_center_event_sender
+            .send(StreamCenterEvent::UpdateMediaSelection {
+                stream_id: stream_id.clone(),
+                uuid,
+                media_selection,
+                result_sender: tx,
+            })
+            .map_err(|err| {
+                let _ = span.enter();
+                tracing::error!(
+                    "send update media selection event to stream center failed: {}",
+                    err
+                );
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        match rx.await {
+            Err(_err) => {
+                let _ = span.enter();
+                tracing::error!(
+                    "channel closed while trying to receive update media selection result"
+                );
+                Err(StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                })
+            }
+            Ok(Err(err)) => {
+                let _ = span.enter();
+                tracing::error!("update media selection failed: {}", err);
+                Err(err)
+            }
+            Ok(Ok(())) => {
+                let _ = span.enter();
+                tracing::info!("update media selection success: {:?}", media_selection);
+                Ok(())
+            }
+        }
+    }
+
+    /// hands the protocol control settings sent to an rtmp client to the stream,
+    /// they show up in its description. the subscriber id is None for the publisher
+    pub fn negotiate_rtmp_control(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+        subscriber_id: Option<Uuid>,
+        control: RtmpControl,
+    ) -> StreamCenterResult<()> {
+        stream_center_event_sender
+            .send(StreamCenterEvent::RtmpControlNegotiated {
+                stream_id: stream_id.clone(),
+                subscriber_id,
+                control,
+            })
+            .map_err(|err| {
+                tracing::error!(
+                    "send rtmp control negotiated event to stream center failed: {}",
+                    err
+                );
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })
+    }
+
+    /// hands the sdes items a remote rtp participant sent to the stream, they show up in its description.
+    /// the subscriber id is None for the peers of the publisher
+    pub fn describe_rtcp_peer(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+        subscriber_id: Option<Uuid>,
+        peer: RtcpPeer,
+    ) -> StreamCenterResult<()> {
+        stream_center_event_sender
+            .send(StreamCenterEvent::RtcpPeerDescribed {
+                stream_id: stream_id.clone(),
+                subscriber_id,
+                peer,
+            })
+            .map_err(|err| {
+                tracing::error!(
+                    "send rtcp peer described event to stream center failed: {}",
+                    err
+                );
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })
+    }
+
+    /// hands the reception of a track of an rtp based publisher to the stream, it shows up in its description
+    pub fn report_rtp_receive(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+        stats: RtpReceiveStats,
+    ) -> StreamCenterResult<()> {
+        stream_center_event_sender
+            .send(StreamCenterEvent::RtpReceiveReported {
+                stream_id: stream_id.clone(),
+                stats,
+            })
+            .map_err(|err| {
+                tracing::error!(
+                    "send rtp receive reported event to stream center failed: {}",
+                    err
+                );
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })
+    }
+
     pub async fn describe(
         stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
-        stream_id: &StreamIdentifier,
+        stream_id: &StreamIdentity,
     ) -> StreamCenterResult<StreamDescription> {
         let (tx, rx) = oneshot::channel();
         let span = tracing::trace_span!(
             "describe",
-            app = stream_id.app,
-            stream_name = stream_id.stream_name
+            app = %stream_id.app,
+            stream_name = %stream_id.name
         );
 
         stream_center_event_sender
@@ -639,6 +1927,225 @@ impl StreamCenter {
             }
         }
     }
+
+    /// waits for the stream to be published and its sequence headers to be known,
+    /// wrap it in a timeout, it waits for ever otherwise
+    pub async fn describe_configured(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+    ) -> StreamCenterResult<StreamDescription> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::DescribeConfigured {
+                stream_id: stream_id.clone(),
+                result_sender: tx,
+            })
+            .map_err(|err| {
+                tracing::error!(
+                    "send describe configured event to stream center failed: {}",
+                    err
+                );
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        // the stream stopped before its sequence headers came
+        rx.await
+            .map_err(|_| StreamCenterError::StreamNotFound(stream_id.clone()))?
+    }
+
+    pub async fn trace(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+    ) -> StreamCenterResult<Vec<TraceRecord>> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::Trace {
+                stream_id: stream_id.clone(),
+                result_sender: tx,
+            })
+            .map_err(|err| {
+                tracing::error!("send trace event to stream center failed: {}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        rx.await.map_err(|_err| {
+            tracing::error!("channel closed while trying to receive trace result");
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })?
+    }
+
+    /// asks the stream to play at the scale, returns the scale it plays at,
+    /// which is 1.0 unless the stream replays a recording
+    pub async fn set_scale(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+        scale: f64,
+    ) -> StreamCenterResult<f64> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::SetScale {
+                stream_id: stream_id.clone(),
+                scale,
+                result_sender: tx,
+            })
+            .map_err(|err| {
+                tracing::error!("send set scale event to stream center failed: {}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        rx.await.map_err(|_err| {
+            tracing::error!("channel closed while trying to receive set scale result");
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })?
+    }
+
+    /// asks the publishers to move to the target host, see DrainRequest.
+    /// how each of them leaves is notified with StreamNotification::Drained
+    pub async fn drain(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        request: DrainRequest,
+    ) -> StreamCenterResult<DrainSummary> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::Drain {
+                request,
+                result_sender: tx,
+            })
+            .map_err(|err| {
+                tracing::error!("send drain event to stream center failed: {}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        rx.await.map_err(|_err| {
+            tracing::error!("channel closed while trying to receive drain result");
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })?
+    }
+
+    /// the takeover policies and the restorable sources in effect, saved on graceful shutdown
+    pub async fn snapshot(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+    ) -> StreamCenterResult<StreamCenterSnapshot> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::Snapshot { result_sender: tx })
+            .map_err(|err| {
+                tracing::error!("send snapshot event to stream center failed: {}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        rx.await.map_err(|_err| {
+            tracing::error!("channel closed while trying to receive snapshot");
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })
+    }
+
+    /// points the alias to the canonical stream, or removes it if there is none, see `set_alias`
+    pub async fn point_alias(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        alias: &StreamIdentity,
+        canonical: Option<&StreamIdentity>,
+    ) -> StreamCenterResult<Option<StreamIdentity>> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::SetAlias {
+                alias: alias.clone(),
+                canonical: canonical.cloned(),
+                result_sender: tx,
+            })
+            .map_err(|err| {
+                tracing::error!("send set alias event to stream center failed: {}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        rx.await.map_err(|_err| {
+            tracing::error!("channel closed while trying to receive set alias result");
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })?
+    }
+
+    /// pairs of alias and canonical stream, ordered by alias
+    pub async fn aliases(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+    ) -> StreamCenterResult<Vec<(StreamIdentity, StreamIdentity)>> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::Aliases { result_sender: tx })
+            .map_err(|err| {
+                tracing::error!("send aliases event to stream center failed: {}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        rx.await.map_err(|_err| {
+            tracing::error!("channel closed while trying to receive aliases");
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })
+    }
+
+    /// the streams published, ordered
+    pub async fn streams(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+    ) -> StreamCenterResult<Vec<StreamIdentity>> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::Streams { result_sender: tx })
+            .map_err(|err| {
+                tracing::error!("send streams event to stream center failed: {}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        rx.await.map_err(|_err| {
+            tracing::error!("channel closed while trying to receive streams");
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })
+    }
+
+    /// the latest IDR access unit of the stream, None until the stream has one
+    pub async fn keyframe(
+        stream_center_event_sender: &UnboundedSender<StreamCenterEvent>,
+        stream_id: &StreamIdentity,
+    ) -> StreamCenterResult<Option<KeyframeSnapshot>> {
+        let (tx, rx) = oneshot::channel();
+        stream_center_event_sender
+            .send(StreamCenterEvent::Keyframe {
+                stream_id: stream_id.clone(),
+                result_sender: tx,
+            })
+            .map_err(|err| {
+                tracing::error!("send keyframe event to stream center failed: {}", err);
+                StreamCenterError::ChannelSendFailed {
+                    backtrace: Backtrace::capture(),
+                }
+            })?;
+        rx.await.map_err(|_err| {
+            tracing::error!("channel closed while trying to receive keyframe result");
+            StreamCenterError::ChannelSendFailed {
+                backtrace: Backtrace::capture(),
+            }
+        })?
+    }
 }
 
 impl Default for StreamCenter {
@@ -1,27 +1,62 @@
 use crate::{
-    errors::StreamCenterResult,
-    gop::{GopQueue, MediaFrame},
+    audio_continuity::{AudioContinuity, AudioGapConcealment},
+    audio_track::{AudioTracks, DEFAULT_AUDIO_TRACK},
+    backpressure::{BackpressureAdvisory, BackpressureMonitor, BackpressurePolicy},
+    dvr::{DvrBuffer, DvrWindow, Timeshift},
+    end_of_stream::EndOfStreamReason,
+    errors::{StreamCenterError, StreamCenterResult},
+    events::{
+        StreamCenterEvent, StreamConfigChange, StreamDescription, SubscribeResponse, SubscriberInfo,
+    },
+    failover::TimestampRewriter,
+    gop::{GopQueue, MAX_DATA_FRAME_BYTES, MediaFrame},
+    gop_budget::GopCacheBudget,
+    identity::StreamIdentity,
+    keyframe::KeyframeSnapshot,
+    latency::LatencyProbe,
     make_fake_on_meta_data,
+    metrics::StreamMetrics,
     mix_queue::MixQueue,
+    parameter_sets::ParameterSetTracker,
+    rate_estimate::RateEstimator,
+    recovery_point::{RecoveryPointJoin, RecoveryPointMarker},
+    rtcp_peer::{RtcpPeer, RtcpPeers},
+    rtmp_control::RtmpControl,
+    rtp_receive::{RtpReceiveStats, RtpReceiveTracks},
+    serialized::SerializedFrameCache,
     signal::StreamSignal,
+    slice_integrity::{SliceIntegrity, SliceIntegrityCheck},
     stream_center::StreamSourceDynamicInfo,
+    takeover::PublishActivity,
+    timestamp_normalization::{TimestampNormalization, TimestampNormalizer},
+    trace::{TraceEvent, TraceFrameKind, TraceHandle},
+    watchdog::IdleWatchdog,
 };
+use bitstream_io::{BigEndian, BitWrite, BitWriter};
 use codec_common::{
-    audio::{AudioCodecCommon, AudioConfig},
-    video::{H264VideoConfig, VideoCodecCommon},
+    audio::{AudioCodecCommon, AudioConfig, SoundInfoCommon},
+    video::{Av1VideoConfig, H264VideoConfig, VideoCodecCommon, VideoConfig},
 };
+use codec_h264::sps::Sps;
+use flv_formats::tag::enhanced::ex_audio::ex_audio_body::AudioMultichannelConfig;
 use num::ToPrimitive;
+use serde::Serialize;
 use std::{
     cmp::{max, min},
-    collections::HashMap,
-    fmt,
+    collections::{BTreeMap, HashMap},
     sync::Arc,
-    time::SystemTime,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
 };
-use tokio::sync::{RwLock, mpsc};
 use tokio_util::bytes::Bytes;
 use tracing::trace_span;
-use utils::traits::buffer::GenericSequencer;
+use utils::traits::{
+    buffer::GenericSequencer,
+    writer::{BitwiseWriteTo, WriteTo},
+};
 use uuid::Uuid;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -31,29 +66,31 @@ enum StreamStatus {
     Stopped,
 }
 
-#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum PublishProtocol {
     RTMP,
     RTSP,
+    /// playback of a recorded file
+    VOD,
+    /// mpeg-ts over srt
+    SRT,
+    /// frames handed over by the process embedding the servers
+    Embedded,
+    /// played from another server by a relay
+    Relay,
 }
 
-#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum PlayProtocol {
     RTMP,
     HTTPFLV,
     RTSP,
-}
-
-#[derive(Debug, Hash, Clone, PartialEq, Eq)]
-pub struct StreamIdentifier {
-    pub stream_name: String,
-    pub app: String,
-}
-
-impl fmt::Display for StreamIdentifier {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}/{}", self.app, self.stream_name)
-    }
+    /// rtp sent to a multicast group, a single subscriber however many receivers joined the group
+    RTSPMulticast,
+    /// frames taken by the process embedding the servers
+    Embedded,
+    /// published to another server by a relay
+    Relay,
 }
 
 #[derive(Debug, Clone)]
@@ -77,189 +114,1570 @@ pub struct PlayStat {
     script_frame_send_fail_cnt: u64,
 }
 
+impl PlayStat {
+    fn count_sent(&mut self, frame: &MediaFrame, fail: bool) {
+        if frame.is_video() {
+            self.video_frame_send_fail_cnt += <bool as Into<u64>>::into(fail);
+            self.video_frames_sent += <bool as Into<u64>>::into(!fail);
+        } else if frame.is_audio() {
+            self.audio_frame_send_fail_cnt += <bool as Into<u64>>::into(fail);
+            self.audio_frames_sent += <bool as Into<u64>>::into(!fail);
+        } else {
+            self.script_frame_send_fail_cnt += <bool as Into<u64>>::into(fail);
+            self.script_frames_sent += <bool as Into<u64>>::into(!fail);
+        }
+    }
+}
+
+/// the subscribe context key picking the audio track of a multitrack stream
+pub const AUDIO_TRACK_KEY: &str = "audio_track";
+
+/// which kinds of frames a subscriber receives,
+/// sequence headers follow the selection of their media
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaSelection {
+    pub audio: bool,
+    pub video: bool,
+    /// script frames, e.g., onMetaData
+    pub data: bool,
+    /// the audio frames of other tracks are filtered out
+    pub audio_track: u8,
+}
+
+impl Default for MediaSelection {
+    fn default() -> Self {
+        Self {
+            audio: true,
+            video: true,
+            data: true,
+            audio_track: DEFAULT_AUDIO_TRACK,
+        }
+    }
+}
+
+impl From<&HashMap<String, String>> for MediaSelection {
+    fn from(value: &HashMap<String, String>) -> Self {
+        let selection = if value.contains_key("audioOnly") {
+            Self::audio_only()
+        } else if value.contains_key("videoOnly") {
+            Self::video_only()
+        } else {
+            Self::default()
+        };
+        Self {
+            audio_track: value
+                .get(AUDIO_TRACK_KEY)
+                .and_then(|track| track.parse().ok())
+                .unwrap_or(DEFAULT_AUDIO_TRACK),
+            ..selection
+        }
+    }
+}
+
+impl MediaSelection {
+    pub fn audio_only() -> Self {
+        Self {
+            video: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn video_only() -> Self {
+        Self {
+            audio: false,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn accepts(&self, frame: &MediaFrame) -> bool {
+        if frame.is_video() {
+            self.video
+        } else if frame.is_audio() {
+            self.audio && frame.audio_track_id() == Some(self.audio_track)
+        } else {
+            self.data
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SubscribeHandler {
     pub id: Uuid,
     pub context: HashMap<String, String>,
     pub parsed_context: ParsedContext,
+    pub media_selection: MediaSelection,
     pub data_sender: mpsc::Sender<MediaFrame>,
     pub stat: PlayStat,
     pub play_protocol: PlayProtocol,
+    pub rtcp_peers: RtcpPeers,
+    pub rtmp_control: Option<RtmpControl>,
+    /// the alias the subscriber asked for, if it did not ask for the stream itself
+    pub alias: Option<StreamIdentity>,
+    /// video got enabled mid-stream, frames are held back until the next key frame
+    pub(crate) wait_video_key_frame: bool,
+    /// the next frame of the dvr window a timeshift subscriber gets, None once it plays live
+    pub(crate) timeshift_cursor: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ParsedContext {
-    // videoOnly
-    pub video_only: bool,
-    // audioOnly
-    pub audio_only: bool,
     // backtrackGopCnt
     pub backtrack_gop_cnt: ConsumeGopCache,
+    /// the subscriber plays from the dvr window of the stream, if the stream has one
+    pub timeshift: Option<Timeshift>,
 }
 
 impl From<&HashMap<String, String>> for ParsedContext {
     fn from(value: &HashMap<String, String>) -> Self {
         Self {
-            video_only: value.contains_key("videoOnly"),
-            audio_only: value.contains_key("audioOnly"),
             backtrack_gop_cnt: value.get("backtraceGopCnt").map_or_else(
                 || ConsumeGopCache::GopCount(1),
                 |s| ConsumeGopCache::GopCount(s.parse().unwrap_or(0)),
             ),
+            timeshift: Timeshift::from_context(value),
         }
     }
 }
 
+/// the backup a stalled stream is failed over to
+#[derive(Debug)]
+struct Failover {
+    /// None once the backup is gone
+    backup_frames: Option<mpsc::Receiver<MediaFrame>>,
+    /// the sequence headers of the publisher, distributed again when it resumes
+    video_config: Option<VideoConfig>,
+    audio_configs: BTreeMap<u8, (AudioConfig, SoundInfoCommon)>,
+    audio_channel_configs: BTreeMap<u8, (AudioMultichannelConfig, AudioCodecCommon)>,
+}
+
 #[derive(Debug)]
 pub struct StreamSource {
-    pub(crate) identifier: StreamIdentifier,
+    pub(crate) identifier: StreamIdentity,
     pub(crate) publish_protocol: PublishProtocol,
     pub(crate) publish_start_time: SystemTime,
+    pub(crate) activity: Arc<PublishActivity>,
+    /// tells this source apart from a later one of the same stream
+    pub(crate) source_id: Uuid,
 
     data_receiver: mpsc::Receiver<MediaFrame>,
-    data_distributer: Arc<RwLock<HashMap<Uuid, SubscribeHandler>>>,
-    stream_dynamic_info: Arc<RwLock<StreamSourceDynamicInfo>>,
-    // data_consumer: broadcast::Receiver<FrameData>,
+    /// owned by the task of the stream, so the fan-out of a stream never waits for another one
+    subscribers: HashMap<Uuid, SubscribeHandler>,
+    publisher_rtcp_peers: RtcpPeers,
+    publisher_rtmp_control: Option<RtmpControl>,
+    publisher_rtp_receive: RtpReceiveTracks,
+    audio_tracks: AudioTracks,
+    stream_dynamic_info: StreamSourceDynamicInfo,
     status: StreamStatus,
-    signal_receiver: mpsc::Receiver<StreamSignal>,
+    signal_receiver: mpsc::UnboundedReceiver<StreamSignal>,
     gop_cache: GopQueue,
+    /// the latest IDR access unit, kept apart from the gop cache which may drop it any time
+    keyframe: Option<KeyframeSnapshot>,
     mix_queue: MixQueue,
+    event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    tracer: TraceHandle,
+    /// None if latency measurement is disabled, frames are not stamped then
+    latency: Option<LatencyProbe>,
+    metrics: StreamMetrics,
+    /// handed to every subscriber, so a frame is serialized once per output format
+    serialized_frames: SerializedFrameCache,
+    idle_watchdog: IdleWatchdog,
+    recovery_points: RecoveryPointMarker,
+    audio_continuity: AudioContinuity,
+    /// maps the timestamps of the publisher, ahead of everything else
+    timestamp_normalizer: TimestampNormalizer,
+    parameter_sets: ParameterSetTracker,
+    slice_integrity: SliceIntegrityCheck,
+    /// None if the stream keeps no dvr window
+    dvr: Option<DvrBuffer>,
+    /// None if the publisher is not told of the congestion of the stream
+    backpressure: Option<BackpressureMonitor>,
+    /// fills the frame rate and bitrates into onMetaData if the publisher tells none of them
+    rate_estimate: RateEstimator,
+    /// when the publisher last sent audio or video, on the tokio clock the watchdog sleeps on
+    last_media_at: Instant,
+    last_media_dts_nano: u64,
+    /// set by the idle watchdog, cleared by the next audio or video frame
+    stalled_since: Option<Instant>,
+    /// the stream center is asked to reap the stream, the watchdog has nothing left to do
+    reap_requested: bool,
+    /// the stream standing in while the publisher is stalled
+    backup: Option<StreamIdentity>,
+    failover: Option<Failover>,
+    /// a stream failed over to this one, it gets a copy of the frames
+    mirror: Option<mpsc::Sender<MediaFrame>>,
+    /// the sequence headers the stream has are known, they come ahead of the frames,
+    /// so all of them are by the first audio or video frame if not both before
+    configured: bool,
+    /// the describes waiting for the sequence headers
+    configured_waiters: Vec<oneshot::Sender<StreamCenterResult<StreamDescription>>>,
+    /// keeps the timestamps going forward when the subscribers are switched to another source
+    timestamps: TimestampRewriter,
+    /// replays a recording, whose subscribers may change the speed and the scale
+    recording: bool,
+    /// stamp frames with the crc of their payload and check it before the fan-out
+    #[cfg(feature = "frame-crc")]
+    frame_crc: bool,
+}
+
+impl Drop for StreamSource {
+    /// a stream failing on a frame is dropped without being stopped
+    fn drop(&mut self) {
+        self.drop_subscribers();
+    }
 }
 
 impl StreamSource {
-    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        stream_name: &str,
-        app: &str,
+        identifier: StreamIdentity,
         publish_protocol: PublishProtocol,
         data_receiver: mpsc::Receiver<MediaFrame>,
-        signal_receiver: mpsc::Receiver<StreamSignal>,
-        data_distributer: Arc<RwLock<HashMap<Uuid, SubscribeHandler>>>,
-        stream_dynamic_info: Arc<RwLock<StreamSourceDynamicInfo>>,
+        signal_receiver: mpsc::UnboundedReceiver<StreamSignal>,
+        event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+        tracer: TraceHandle,
+        latency: Option<LatencyProbe>,
     ) -> Self {
         Self {
-            identifier: StreamIdentifier {
-                stream_name: stream_name.to_string(),
-                app: app.to_string(),
-            },
+            metrics: StreamMetrics::new(&identifier, publish_protocol),
+            identifier,
             publish_protocol,
             publish_start_time: SystemTime::now(),
+            activity: Arc::new(PublishActivity::new()),
+            source_id: Uuid::now_v7(),
             data_receiver,
-            data_distributer,
-            stream_dynamic_info,
-            // data_consumer: rx,
+            subscribers: HashMap::new(),
+            publisher_rtcp_peers: RtcpPeers::default(),
+            publisher_rtmp_control: None,
+            publisher_rtp_receive: RtpReceiveTracks::default(),
+            audio_tracks: AudioTracks::default(),
+            stream_dynamic_info: StreamSourceDynamicInfo {
+                has_video: true,
+                has_audio: true,
+                video_config: None,
+                audio_config: None,
+                config_generation: 0,
+            },
             gop_cache: GopQueue::new(6_0000, 8000),
+            keyframe: None,
             status: StreamStatus::NotStarted,
             signal_receiver,
             mix_queue: MixQueue::new(100, 100),
+            event_sender,
+            tracer,
+            latency,
+            serialized_frames: SerializedFrameCache::default(),
+            idle_watchdog: IdleWatchdog::default(),
+            recovery_points: RecoveryPointMarker::new(RecoveryPointJoin::default()),
+            audio_continuity: AudioContinuity::default(),
+            timestamp_normalizer: TimestampNormalizer::default(),
+            parameter_sets: ParameterSetTracker::default(),
+            slice_integrity: SliceIntegrityCheck::default(),
+            dvr: None,
+            backpressure: None,
+            rate_estimate: RateEstimator::default(),
+            last_media_at: Instant::now(),
+            last_media_dts_nano: 0,
+            stalled_since: None,
+            reap_requested: false,
+            backup: None,
+            failover: None,
+            mirror: None,
+            configured: false,
+            configured_waiters: Vec::new(),
+            timestamps: TimestampRewriter::default(),
+            recording: false,
+            #[cfg(feature = "frame-crc")]
+            frame_crc: false,
         }
     }
 
+    pub fn with_idle_watchdog(mut self, idle_watchdog: IdleWatchdog) -> Self {
+        self.idle_watchdog = idle_watchdog;
+        self
+    }
+
+    /// the gop cache of the stream shares the budget with the ones of the other streams
+    pub fn with_gop_cache_budget(mut self, budget: &GopCacheBudget) -> Self {
+        self.gop_cache.set_budget(budget.open_account());
+        self
+    }
+
+    pub fn with_recovery_point_join(mut self, join: RecoveryPointJoin) -> Self {
+        self.recovery_points = RecoveryPointMarker::new(join);
+        self
+    }
+
+    pub fn with_audio_gap_concealment(mut self, concealment: AudioGapConcealment) -> Self {
+        self.audio_continuity = AudioContinuity::new(concealment);
+        self
+    }
+
+    pub fn with_timestamp_normalization(mut self, normalization: TimestampNormalization) -> Self {
+        self.timestamp_normalizer = TimestampNormalizer::new(normalization);
+        self
+    }
+
+    pub fn with_slice_integrity(mut self, integrity: SliceIntegrity) -> Self {
+        self.slice_integrity = SliceIntegrityCheck::new(integrity);
+        self
+    }
+
+    pub fn with_dvr_window(mut self, window: DvrWindow) -> Self {
+        self.dvr = DvrBuffer::new(window);
+        self
+    }
+
+    /// the advisories go to the sender, if any, while the policy is on
+    pub fn with_backpressure(
+        mut self,
+        policy: BackpressurePolicy,
+        sender: Option<mpsc::UnboundedSender<BackpressureAdvisory>>,
+    ) -> Self {
+        self.backpressure = BackpressureMonitor::new(policy, sender);
+        self
+    }
+
+    /// a stalled stream with a backup keeps its subscribers,
+    /// the stream center fails them over to the backup
+    pub fn with_backup(mut self, backup: Option<StreamIdentity>) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    pub fn with_recording(mut self, recording: bool) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    #[cfg(feature = "frame-crc")]
+    pub fn set_frame_crc(&mut self, enabled: bool) {
+        self.frame_crc = enabled;
+    }
+
     pub async fn run(&mut self) -> StreamCenterResult<()> {
         if self.status == StreamStatus::Running {
             return Ok(());
         }
         self.status = StreamStatus::Running;
         tracing::info!("stream is running, stream id: {:?}", self.identifier);
-        loop {
-            match tokio::time::timeout(
-                tokio::time::Duration::from_millis(10),
-                self.data_receiver.recv(),
-            )
-            .await
-            {
-                Err(_) => {}
-                Ok(None) => {}
-                Ok(Some(frame)) => {
-                    if (frame.is_video() || frame.is_audio()) && !frame.is_sequence_header() {
-                        let _ = self.mix_queue.enqueue(frame).inspect_err(|err| {
-                            tracing::error!("enqueue frame to mix queue failed: {:?}", err);
-                        });
-
-                        for frame in self.mix_queue.try_dump() {
-                            if let Err(err) = self.on_media_frame(frame).await {
-                                tracing::error!("on media frame failed: {:?}", err);
-                                return Err(err);
-                            }
-                        }
-                    } else {
-                        // sequence header or script frame
-                        if let Err(err) = self.on_media_frame(frame).await {
-                            tracing::error!("on media frame failed: {:?}", err);
-                            return Err(err);
+        while self.status == StreamStatus::Running {
+            let watchdog_deadline = self.watchdog_deadline();
+            tokio::select! {
+                // registry changes go before the frames queued after them
+                biased;
+                signal = self.signal_receiver.recv() => match signal {
+                    Some(signal) => self.on_signal(signal),
+                    // the stream center is gone, so is the stream
+                    None => self.stop(EndOfStreamReason::Shutdown),
+                },
+                frame = self.data_receiver.recv() => match frame {
+                    Some(frame) => self.on_frame(frame).inspect_err(|err| self.fail(err))?,
+                    None => self.stop(EndOfStreamReason::Unpublished),
+                },
+                frame = Self::recv_backup_frame(&mut self.failover) => match frame {
+                    Some(frame) => self.on_backup_frame(frame).inspect_err(|err| self.fail(err))?,
+                    None => self.on_backup_gone(),
+                },
+                _ = tokio::time::sleep_until(watchdog_deadline.unwrap_or_else(Instant::now)),
+                    if watchdog_deadline.is_some() => self.on_watchdog().inspect_err(|err| self.fail(err))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// the stream fails on the error, the subscribers are told why after the frames queued for them
+    fn fail(&mut self, err: &StreamCenterError) {
+        tracing::error!("stream {} failed: {}", self.identifier, err);
+        self.send_final_frame(MediaFrame::SourceError {
+            timestamp_nano: self.last_media_dts_nano,
+            code: err.into(),
+            detail: err.to_string(),
+        });
+    }
+
+    /// pending unless failed over to a backup
+    async fn recv_backup_frame(failover: &mut Option<Failover>) -> Option<MediaFrame> {
+        match failover
+            .as_mut()
+            .and_then(|failover| failover.backup_frames.as_mut())
+        {
+            Some(backup_frames) => backup_frames.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// when the idle watchdog fires next, None if it has nothing left to do
+    fn watchdog_deadline(&self) -> Option<Instant> {
+        // the stream is not reaped while the backup stands in
+        if self
+            .failover
+            .as_ref()
+            .is_some_and(|failover| failover.backup_frames.is_some())
+        {
+            return None;
+        }
+        let IdleWatchdog::On {
+            stall_after,
+            reap_after,
+        } = self.idle_watchdog
+        else {
+            return None;
+        };
+        match (self.stalled_since, self.reap_requested) {
+            (_, true) => None,
+            (None, false) => Some(self.last_media_at + stall_after),
+            (Some(_), false) => Some(self.last_media_at + reap_after),
+        }
+    }
+
+    /// the first time the publisher is found silent the stream stalls,
+    /// the second time the stream center is asked to reap it
+    fn on_watchdog(&mut self) -> StreamCenterResult<()> {
+        let idle = self.last_media_at.elapsed();
+        if self.stalled_since.is_none() {
+            return self.on_stall(idle);
+        }
+        self.reap_requested = true;
+        tracing::warn!(
+            "no audio or video from the publisher of stream {} for {:?}, reap it",
+            self.identifier,
+            idle
+        );
+        let _ = self
+            .event_sender
+            .send(StreamCenterEvent::ReapIdleStream {
+                stream_id: self.identifier.clone(),
+                source_id: self.source_id,
+                idle,
+            })
+            .inspect_err(|err| {
+                tracing::error!("ask stream center to reap idle stream failed: {:?}", err);
+            });
+        Ok(())
+    }
+
+    fn on_stall(&mut self, idle: Duration) -> StreamCenterResult<()> {
+        self.stalled_since = Some(Instant::now());
+        tracing::warn!(
+            "no audio or video from the publisher of stream {} for {:?}, stalled",
+            self.identifier,
+            idle
+        );
+        self.tracer
+            .record(&self.identifier, || TraceEvent::PublisherStalled {
+                idle_ms: idle.as_millis() as u64,
+            });
+        // no more frames come to push them out of the mix queue
+        for pending in self.mix_queue.dump_all() {
+            self.on_media_frame(pending)?;
+        }
+        // the stream center answers with the backup to fail over to, or tells there is none
+        if self.backup.is_none() {
+            self.send_end_of_stream(EndOfStreamReason::Stalled);
+        }
+        let _ = self
+            .event_sender
+            .send(StreamCenterEvent::PublisherStalled {
+                stream_id: self.identifier.clone(),
+                idle,
+            })
+            .inspect_err(|err| {
+                tracing::error!(
+                    "report stalled publisher to stream center failed: {:?}",
+                    err
+                );
+            });
+        Ok(())
+    }
+
+    fn on_timestamp_discontinuity(&mut self, jump_nano: i64) {
+        tracing::warn!(
+            "timestamps of the publisher of stream {} jumped by {}ms off the clock, anchored again",
+            self.identifier,
+            jump_nano / 1_000_000
+        );
+        self.tracer
+            .record(&self.identifier, || TraceEvent::TimestampDiscontinuity {
+                jump_ms: jump_nano / 1_000_000,
+            });
+        let _ = self
+            .event_sender
+            .send(StreamCenterEvent::TimestampDiscontinuity {
+                stream_id: self.identifier.clone(),
+                jump_nano,
+            })
+            .inspect_err(|err| {
+                tracing::error!(
+                    "report timestamp discontinuity to stream center failed: {:?}",
+                    err
+                );
+            });
+    }
+
+    fn send_end_of_stream(&mut self, reason: EndOfStreamReason) {
+        let end_of_stream = MediaFrame::EndOfStream {
+            timestamp_nano: self.last_media_dts_nano,
+            reason,
+        };
+        for (id, handler) in self.subscribers.iter_mut() {
+            if let Err(err) = handler.data_sender.try_send(end_of_stream.clone()) {
+                tracing::error!("send end of stream to {} failed: {:?}", id, err);
+            }
+        }
+    }
+
+    /// the subscribers keep their sessions, the frames of the backup continue the timestamps of the publisher
+    fn on_fail_over(&mut self, backup_frames: Option<mpsc::Receiver<MediaFrame>>) {
+        // the publisher resumed before the answer came
+        if self.stalled_since.is_none() {
+            return;
+        }
+        let Some(backup_frames) = backup_frames else {
+            self.send_end_of_stream(EndOfStreamReason::Stalled);
+            return;
+        };
+        tracing::info!("stream {} fails over to {:?}", self.identifier, self.backup);
+        self.failover = Some(Failover {
+            backup_frames: Some(backup_frames),
+            video_config: self.gop_cache.video_config.clone(),
+            audio_configs: self.gop_cache.audio_configs.clone(),
+            audio_channel_configs: self.gop_cache.audio_channel_configs.clone(),
+        });
+        self.start_another_source();
+    }
+
+    /// the frames cached so far can not be decoded along with the ones of the other source
+    fn start_another_source(&mut self) {
+        self.timestamps.switch_source();
+        self.gop_cache.clear_gops();
+        for handler in self.subscribers.values_mut() {
+            handler.wait_video_key_frame = true;
+        }
+    }
+
+    /// a frame the backup sent while standing in, it went through the mix queue of the backup already
+    fn on_backup_frame(&mut self, mut frame: MediaFrame) -> StreamCenterResult<()> {
+        self.timestamps.rewrite(&mut frame);
+        self.audio_tracks.on_frame(&frame);
+        if frame.is_video() || frame.is_audio() {
+            self.last_media_dts_nano = frame.get_decode_timestamp_ns();
+        }
+        if let Some(change) = self.detect_config_change(&frame) {
+            self.on_config_change(change, &frame)?;
+        }
+        self.on_media_frame(frame)
+    }
+
+    /// the subscribers see the end of the stream as if there was no backup,
+    /// the stream is still switched back if the publisher resumes
+    fn on_backup_gone(&mut self) {
+        tracing::warn!(
+            "backup {:?} of stream {} is gone",
+            self.backup,
+            self.identifier
+        );
+        if let Some(failover) = self.failover.as_mut() {
+            failover.backup_frames = None;
+        }
+        self.send_end_of_stream(EndOfStreamReason::Stalled);
+    }
+
+    /// the publisher resumed, its sequence headers go out again ahead of its frames
+    fn switch_back(&mut self, dts_nano: u64) -> StreamCenterResult<()> {
+        let Some(failover) = self.failover.take() else {
+            return Ok(());
+        };
+        tracing::info!(
+            "publisher of stream {} resumed, switch back from {:?}",
+            self.identifier,
+            self.backup
+        );
+        self.start_another_source();
+        let video_config = failover.video_config.map(|config| MediaFrame::VideoConfig {
+            timestamp_nano: dts_nano,
+            config: Box::new(config),
+        });
+        let audio_configs =
+            failover
+                .audio_configs
+                .into_iter()
+                .map(|(track_id, (config, sound_info))| MediaFrame::AudioConfig {
+                    timestamp_nano: dts_nano,
+                    sound_info,
+                    config: Box::new(config),
+                    track_id,
+                });
+        let audio_channel_configs =
+            failover
+                .audio_channel_configs
+                .into_iter()
+                .map(
+                    |(track_id, (config, codec_id))| MediaFrame::AudioChannelConfig {
+                        timestamp_nano: dts_nano,
+                        codec_id,
+                        config: Box::new(config),
+                        track_id,
+                    },
+                );
+        for mut frame in video_config
+            .into_iter()
+            .chain(audio_configs)
+            .chain(audio_channel_configs)
+        {
+            self.timestamps.rewrite(&mut frame);
+            if let Some(change) = self.detect_config_change(&frame) {
+                self.on_config_change(change, &frame)?;
+            }
+            self.on_media_frame(frame)?;
+        }
+        Ok(())
+    }
+
+    /// the copy starts with the sequence headers and the latest gop,
+    /// so the subscribers of the other stream can decode it right away
+    fn on_mirror(&mut self, sender: mpsc::Sender<MediaFrame>) {
+        let gop = self.gop_cache.gops.back();
+        let timestamp_nano = gop.and_then(|gop| gop.media_frames.front()).map_or(
+            self.last_media_dts_nano,
+            MediaFrame::get_decode_timestamp_ns,
+        );
+        let video_config =
+            self.gop_cache
+                .video_config
+                .as_ref()
+                .map(|config| MediaFrame::VideoConfig {
+                    timestamp_nano,
+                    config: Box::new(config.clone()),
+                });
+        let audio_configs =
+            self.gop_cache
+                .audio_configs
+                .iter()
+                .map(|(track_id, (config, sound_info))| MediaFrame::AudioConfig {
+                    timestamp_nano,
+                    sound_info: *sound_info,
+                    config: Box::new(config.clone()),
+                    track_id: *track_id,
+                });
+        let audio_channel_configs =
+            self.gop_cache
+                .audio_channel_configs
+                .keys()
+                .filter_map(|track_id| {
+                    self.gop_cache
+                        .audio_channel_config_frame(*track_id, timestamp_nano)
+                });
+        let frames = video_config
+            .into_iter()
+            .chain(audio_configs)
+            .chain(audio_channel_configs)
+            .chain(
+                gop.into_iter()
+                    .flat_map(|gop| gop.media_frames.iter().cloned()),
+            );
+        for frame in frames {
+            if let Err(err) = sender.try_send(frame) {
+                tracing::error!("mirror stream {} failed: {:?}", self.identifier, err);
+                return;
+            }
+        }
+        tracing::info!(
+            "stream {} is mirrored to a stream failed over to it",
+            self.identifier
+        );
+        self.mirror = Some(sender);
+    }
+
+    fn on_media_activity(&mut self, dts_nano: u64) {
+        self.last_media_at = Instant::now();
+        self.last_media_dts_nano = dts_nano;
+        let Some(stalled_since) = self.stalled_since.take() else {
+            return;
+        };
+        let stalled_for = stalled_since.elapsed();
+        tracing::info!(
+            "publisher of stream {} resumed after being stalled for {:?}",
+            self.identifier,
+            stalled_for
+        );
+        self.tracer
+            .record(&self.identifier, || TraceEvent::PublisherResumed {
+                stalled_ms: stalled_for.as_millis() as u64,
+            });
+        let _ = self
+            .event_sender
+            .send(StreamCenterEvent::PublisherResumed {
+                stream_id: self.identifier.clone(),
+                stalled_for,
+            })
+            .inspect_err(|err| {
+                tracing::error!(
+                    "report resumed publisher to stream center failed: {:?}",
+                    err
+                );
+            });
+    }
+
+    /// the subscribers get the end of the stream after the frames queued for them,
+    /// their receivers are closed once it is consumed
+    fn stop(&mut self, reason: EndOfStreamReason) {
+        self.status = StreamStatus::Stopped;
+        tracing::info!(
+            "stream stopped, stream id: {:?}, reason: {}, dropping {} subscribers",
+            self.identifier,
+            reason,
+            self.subscribers.len()
+        );
+        // the frames the publisher sent before it left go out before the end of stream,
+        // so do the ones held back by the mix queue
+        while let Ok(frame) = self.data_receiver.try_recv() {
+            if let Err(err) = self.on_frame(frame) {
+                self.fail(&err);
+                self.drop_subscribers();
+                return;
+            }
+        }
+        let concealment = self.audio_continuity.stats();
+        if concealment.gaps > 0 {
+            tracing::info!(
+                "{} audio holes of stream {} concealed, {}ms in total, {} silent frames",
+                concealment.gaps,
+                self.identifier,
+                concealment.concealed_nano / 1_000_000,
+                concealment.silence_frames
+            );
+        }
+        for pending in self.mix_queue.dump_all() {
+            if let Err(err) = self.on_media_frame(pending) {
+                tracing::error!(
+                    "flush mix queue of stream {} failed: {}",
+                    self.identifier,
+                    err
+                );
+                break;
+            }
+        }
+        self.send_final_frame(MediaFrame::EndOfStream {
+            timestamp_nano: self.last_media_dts_nano,
+            reason,
+        });
+        self.drop_subscribers();
+    }
+
+    /// nothing is sent after the final frame, so a subscriber with a full queue gets it
+    /// once there is room, the sender is kept open until then
+    fn send_final_frame(&mut self, frame: MediaFrame) {
+        for (id, handler) in self.subscribers.iter() {
+            match handler.data_sender.try_send(frame.clone()) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(frame)) => {
+                    let id = *id;
+                    let sender = handler.data_sender.clone();
+                    tokio::spawn(async move {
+                        if sender.send(frame).await.is_err() {
+                            tracing::warn!("subscriber {} is gone before the end of stream", id);
                         }
-                    }
+                    });
                 }
+                // the subscriber is gone
+                Err(mpsc::error::TrySendError::Closed(_)) => {}
             }
+        }
+    }
 
-            match self.signal_receiver.try_recv() {
-                Err(_) => {}
-                Ok(signal) => match signal {
-                    StreamSignal::Stop => {
-                        self.status = StreamStatus::Stopped;
-                        return Ok(());
+    fn drop_subscribers(&mut self) {
+        for (_, handler) in self.subscribers.drain() {
+            StreamMetrics::subscribers(handler.play_protocol).dec();
+        }
+    }
+
+    fn on_signal(&mut self, signal: StreamSignal) {
+        match signal {
+            StreamSignal::Stop { reason } => self.stop(reason),
+            StreamSignal::Subscribe {
+                handler,
+                media_receiver,
+                result_sender,
+            } => self.on_subscribe(handler, media_receiver, result_sender),
+            StreamSignal::Unsubscribe {
+                subscriber_id,
+                result_sender,
+            } => {
+                if let Some(handler) = self.subscribers.remove(&subscriber_id) {
+                    StreamMetrics::subscribers(handler.play_protocol).dec();
+                    tracing::info!("unsubscribe done, stat: {:?}", handler.stat);
+                }
+                if result_sender.send(Ok(())).is_err() {
+                    tracing::error!("deliver unsubscribe success result to caller failed");
+                }
+            }
+            StreamSignal::UpdateMediaSelection {
+                subscriber_id,
+                media_selection,
+            } => self.update_media_selection(subscriber_id, media_selection),
+            StreamSignal::Describe { result_sender } => {
+                if result_sender.send(Ok(self.describe())).is_err() {
+                    tracing::error!("deliver describe success result to caller failed");
+                }
+            }
+            StreamSignal::DescribeConfigured { result_sender } => {
+                if self.configured {
+                    if result_sender.send(Ok(self.describe())).is_err() {
+                        tracing::error!("deliver describe success result to caller failed");
                     }
-                },
+                    return;
+                }
+                // the callers gave up on the ones closed
+                self.configured_waiters.retain(|waiter| !waiter.is_closed());
+                self.configured_waiters.push(result_sender);
             }
+            StreamSignal::Keyframe { result_sender } => {
+                if result_sender.send(Ok(self.keyframe.clone())).is_err() {
+                    tracing::error!("deliver keyframe success result to caller failed");
+                }
+            }
+            StreamSignal::RtcpPeerDescribed {
+                subscriber_id,
+                peer,
+            } => self.on_rtcp_peer_described(subscriber_id, peer),
+            StreamSignal::RtmpControlNegotiated {
+                subscriber_id,
+                control,
+            } => self.on_rtmp_control_negotiated(subscriber_id, control),
+            StreamSignal::RtpReceiveReported { stats } => self.on_rtp_receive_reported(stats),
+            StreamSignal::Mirror { sender } => self.on_mirror(sender),
+            StreamSignal::FailOver { backup_frames } => self.on_fail_over(backup_frames),
         }
     }
 
-    async fn on_media_frame(&mut self, frame: MediaFrame) -> StreamCenterResult<()> {
-        match &frame {
-            MediaFrame::AudioConfig { config, .. } => {
-                self.stream_dynamic_info.write().await.audio_config = Some(*config.clone())
+    fn on_rtcp_peer_described(&mut self, subscriber_id: Option<Uuid>, peer: RtcpPeer) {
+        let peers = match subscriber_id {
+            None => &mut self.publisher_rtcp_peers,
+            Some(id) => match self.subscribers.get_mut(&id) {
+                Some(handler) => &mut handler.rtcp_peers,
+                None => return,
+            },
+        };
+        let ssrc = peer.ssrc;
+        if !peers.describe(peer) {
+            tracing::warn!(
+                "drop the description of rtcp peer {} of {:?}, too many peers, stream id: {:?}",
+                ssrc,
+                subscriber_id,
+                self.identifier
+            );
+        }
+    }
+
+    fn on_rtmp_control_negotiated(&mut self, subscriber_id: Option<Uuid>, control: RtmpControl) {
+        match subscriber_id {
+            None => self.publisher_rtmp_control = Some(control),
+            Some(id) => {
+                if let Some(handler) = self.subscribers.get_mut(&id) {
+                    handler.rtmp_control = Some(control);
+                }
             }
-            MediaFrame::VideoConfig { config, .. } => {
-                self.stream_dynamic_info.write().await.video_config = Some(*config.clone())
+        }
+    }
+
+    fn on_rtp_receive_reported(&mut self, stats: RtpReceiveStats) {
+        let track = stats.track.clone();
+        let discontinuities = self.publisher_rtp_receive.buffer_discontinuities();
+        if !self.publisher_rtp_receive.update(stats) {
+            tracing::warn!(
+                "drop the rtp receive stats of track {}, too many tracks, stream id: {:?}",
+                track,
+                self.identifier
+            );
+        }
+        self.metrics
+            .rtp_bytes_buffered
+            .set(self.publisher_rtp_receive.bytes_buffered() as i64);
+        // the reports count since the publisher started, the counter takes what is new
+        self.metrics.rtp_buffer_discontinuities.inc_by(
+            self.publisher_rtp_receive
+                .buffer_discontinuities()
+                .saturating_sub(discontinuities),
+        );
+    }
+
+    /// the gop cache is dumped to the subscriber along with the next frame,
+    /// no frame of the stream can get in between since both happen on this task
+    fn on_subscribe(
+        &mut self,
+        handler: SubscribeHandler,
+        media_receiver: mpsc::Receiver<MediaFrame>,
+        result_sender: oneshot::Sender<StreamCenterResult<SubscribeResponse>>,
+    ) {
+        let subscribe_id = handler.id;
+        let subscribers = StreamMetrics::subscribers(handler.play_protocol);
+        subscribers.inc();
+        let mut handler = handler;
+        self.start_timeshift(&mut handler);
+        self.subscribers.insert(subscribe_id, handler);
+        let response = SubscribeResponse {
+            subscribe_id,
+            has_video: self.stream_dynamic_info.has_video,
+            has_audio: self.stream_dynamic_info.has_audio,
+            media_receiver,
+            latency_probe: self.latency.clone(),
+            serialized_frames: self.serialized_frames.clone(),
+            recording: self.recording,
+        };
+        if result_sender.send(Ok(response)).is_err() {
+            tracing::error!("deliver subscribe success result to caller failed");
+            self.subscribers.remove(&subscribe_id);
+            subscribers.dec();
+        }
+    }
+
+    fn describe(&self) -> StreamDescription {
+        StreamDescription {
+            publish_protocol: self.publish_protocol,
+            stream_id: self.identifier.clone(),
+            video_config: self.stream_dynamic_info.video_config.clone(),
+            has_video: self.stream_dynamic_info.has_video,
+            audio_conifg: self.stream_dynamic_info.audio_config.clone(),
+            audio_channels: self.gop_cache.audio_channels(DEFAULT_AUDIO_TRACK),
+            has_audio: self.stream_dynamic_info.has_audio,
+            config_generation: self.stream_dynamic_info.config_generation,
+            publish_start_time: self.publish_start_time,
+            stalled: self.stalled_since.is_some(),
+            subscribers: self
+                .subscribers
+                .iter()
+                .map(|(id, v)| (*id, SubscriberInfo::from(v)))
+                .collect(),
+            publisher_rtcp_peers: self.publisher_rtcp_peers.to_vec(),
+            publisher_rtmp_control: self.publisher_rtmp_control,
+            publisher_rtp_receive: self.publisher_rtp_receive.to_vec(),
+            health: self.publisher_rtp_receive.health(),
+            audio_tracks: self.audio_tracks.to_vec(),
+            audio_concealment: self.audio_continuity.stats(),
+            timestamp_normalization: self.timestamp_normalizer.stats(),
+            latency: self.latency.as_ref().map(LatencyProbe::summary),
+            rate_estimate: self.rate_estimate.stats(),
+            meta_data: match &self.gop_cache.script_frame {
+                Some(MediaFrame::Script { on_meta_data, .. }) => on_meta_data.as_ref().clone(),
+                _ => None,
+            },
+        }
+    }
+
+    fn on_frame(&mut self, mut frame: MediaFrame) -> StreamCenterResult<()> {
+        self.activity.on_frame();
+        #[cfg(feature = "frame-crc")]
+        if self.frame_crc {
+            crate::frame_crc::stamp(&mut frame);
+        }
+        if let Some(jump_nano) = self.timestamp_normalizer.normalize(&mut frame) {
+            self.on_timestamp_discontinuity(jump_nano);
+        }
+        if matches!(frame, MediaFrame::Video { .. } | MediaFrame::Audio { .. }) {
+            self.switch_back(frame.get_decode_timestamp_ns())?;
+        }
+        self.timestamps.rewrite(&mut frame);
+        self.audio_tracks.on_frame(&frame);
+        self.recovery_points.mark(&mut frame);
+        let silence_frames = self.audio_continuity.conceal(&mut frame);
+        if let Some(config) = self.parameter_sets.normalize(&mut frame) {
+            self.on_in_band_config(config)?;
+        }
+        if matches!(frame, MediaFrame::Video { .. } | MediaFrame::Audio { .. }) {
+            self.on_media_activity(frame.get_decode_timestamp_ns());
+        }
+        self.metrics.frames_in.inc();
+        self.metrics.bytes_in.inc_by(frame.payload_bytes() as u64);
+        let integrity = self.slice_integrity.check(&mut frame, &self.parameter_sets);
+        if integrity.corrupt {
+            self.metrics.corrupt_frames.inc();
+        }
+        if integrity.drop {
+            self.metrics.corrupt_frames_dropped.inc();
+            return Ok(());
+        }
+        if let Some(backpressure) = &mut self.backpressure {
+            backpressure.count_in(frame.payload_bytes());
+        }
+        self.rate_estimate.on_frame(&frame);
+        if self.latency.is_some() {
+            frame.set_ingest_time(LatencyProbe::now());
+        }
+        self.tracer.record(&self.identifier, || {
+            TraceEvent::frame_received(self.publish_protocol, &frame)
+        });
+        // the sequence headers skip the mix queue, the ones ahead of the frame are known already
+        if !self.configured && matches!(frame, MediaFrame::Video { .. } | MediaFrame::Audio { .. })
+        {
+            self.on_configured_frame(&frame);
+        }
+        if let MediaFrame::Data { name, payload, .. } = &frame
+            && payload.len() > MAX_DATA_FRAME_BYTES
+        {
+            tracing::warn!(
+                "drop data frame {} of {} bytes, stream id: {:?}",
+                name,
+                payload.len(),
+                self.identifier
+            );
+        } else if (frame.is_video() || frame.is_audio() || frame.is_data())
+            && !frame.is_sequence_header()
+        {
+            for mut silence in silence_frames {
+                #[cfg(feature = "frame-crc")]
+                if self.frame_crc {
+                    crate::frame_crc::stamp(&mut silence);
+                }
+                if let Some(ingest_time) = frame.get_ingest_time() {
+                    silence.set_ingest_time(ingest_time);
+                }
+                self.enqueue_mixed(silence);
             }
+            self.enqueue_mixed(frame);
+
+            for frame in self.mix_queue.try_dump() {
+                if let Err(err) = self.on_media_frame(frame) {
+                    tracing::error!("on media frame failed: {:?}", err);
+                    return Err(err);
+                }
+            }
+            self.sample_backpressure();
+            self.fill_estimated_rates()?;
+        } else {
+            // sequence header or script frame
+            let channel_config = self.derived_channel_config(&frame);
+            self.on_sequence_header(frame)?;
+            if let Some(channel_config) = channel_config {
+                self.audio_tracks.on_frame(&channel_config);
+                self.on_sequence_header(channel_config)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_sequence_header(&mut self, frame: MediaFrame) -> StreamCenterResult<()> {
+        if let MediaFrame::Script { on_meta_data, .. } = &frame
+            && let Some(on_meta_data) = on_meta_data.as_ref()
+        {
+            self.rate_estimate.on_publisher_meta_data(on_meta_data);
+        }
+        let frame = self.merge_meta_data(frame);
+        let frame = self.with_audio_channels(&frame).unwrap_or(frame);
+        let default_audio_channels = matches!(
+            frame,
+            MediaFrame::AudioChannelConfig {
+                track_id: DEFAULT_AUDIO_TRACK,
+                ..
+            }
+        );
+        if let MediaFrame::VideoConfig { config, .. } = &frame {
+            self.parameter_sets.on_video_config(config);
+        }
+        if let Some(change) = self.detect_config_change(&frame)
+            && let Err(err) = self.on_config_change(change, &frame)
+        {
+            tracing::error!("handle config change failed: {:?}", err);
+            return Err(err);
+        }
+        if let Err(err) = self.on_media_frame(frame) {
+            tracing::error!("on media frame failed: {:?}", err);
+            return Err(err);
+        }
+        // the channel layout came after onMetaData, the subscribers get the metadata again
+        if default_audio_channels
+            && let Some(script) = self
+                .gop_cache
+                .script_frame
+                .as_ref()
+                .and_then(|script| self.with_audio_channels(script))
+        {
+            self.on_media_frame(script)?;
+        }
+        Ok(())
+    }
+
+    /// the channel layout of an aac config of more than 2 channels, e.g., of its program config element,
+    /// as if the publisher sent it. one the publisher sent of the same channels is kept
+    fn derived_channel_config(&self, frame: &MediaFrame) -> Option<MediaFrame> {
+        let MediaFrame::AudioConfig {
+            timestamp_nano,
+            config,
+            track_id,
+            ..
+        } = frame
+        else {
+            return None;
+        };
+        let layout = match config.channel_layout() {
+            Ok(layout) => layout?,
+            Err(err) => {
+                tracing::warn!(
+                    "no channel layout of the audio config of stream {}: {}",
+                    self.identifier,
+                    err
+                );
+                return None;
+            }
+        };
+        // a stereo config after a multichannel one takes the stale layout back
+        match self.gop_cache.audio_channels(*track_id) {
+            Some(channels) if channels == layout.channel_count() => return None,
+            None if !layout.is_multichannel() => return None,
             _ => {}
         }
-        if let Err(err) = self.gop_cache.append_frame(frame.clone()) {
-            tracing::error!("append frame to gop cache failed: {:?}", err);
+        Some(MediaFrame::AudioChannelConfig {
+            timestamp_nano: *timestamp_nano,
+            codec_id: config.as_ref().into(),
+            config: Box::new(layout.into()),
+            track_id: *track_id,
+        })
+    }
+
+    /// the fill of the live subscriber queues, the timeshift readers are fed as their queues take
+    fn sample_backpressure(&mut self) {
+        let Some(backpressure) = &mut self.backpressure else {
+            return;
+        };
+        let subscriber_queue_fill = self
+            .subscribers
+            .values()
+            .filter(|handler| handler.timeshift_cursor.is_none())
+            .map(|handler| {
+                let sender = &handler.data_sender;
+                1.0 - sender.capacity() as f64 / sender.max_capacity() as f64
+            })
+            .fold(0.0, f64::max);
+        if let Some(advisory) = backpressure.sample(subscriber_queue_fill, self.mix_queue.fill()) {
+            tracing::warn!(
+                "backpressure advisory to the publisher of stream {}: {:?}",
+                self.identifier,
+                advisory
+            );
+        }
+    }
+
+    fn enqueue_mixed(&mut self, frame: MediaFrame) {
+        let kind = TraceFrameKind::from(&frame);
+        let dts_ms = frame.get_decode_timestamp_ms();
+        let reordered = self.mix_queue.reordered_count();
+        match self.mix_queue.enqueue(frame) {
+            Ok(()) => {
+                if self.mix_queue.reordered_count() > reordered {
+                    self.metrics.mix_queue_corrections.inc();
+                }
+                self.tracer
+                    .record(&self.identifier, || TraceEvent::MixQueueEnqueued {
+                        kind,
+                        dts_ms,
+                        queued: self.mix_queue.media_frames.len(),
+                    })
+            }
+            Err(err) => {
+                tracing::error!("enqueue frame to mix queue failed: {:?}", err);
+                self.metrics.mix_queue_dropped.inc();
+                if let Some(backpressure) = &mut self.backpressure {
+                    backpressure.count_sent(true);
+                }
+                self.tracer
+                    .record(&self.identifier, || TraceEvent::MixQueueDropped {
+                        kind,
+                        dts_ms,
+                        reason: err.to_string(),
+                    });
+            }
         }
+    }
 
-        if self.data_distributer.read().await.is_empty() {
+    /// the subscribers get the metadata again once the estimated rates settle,
+    /// with the fields the publisher did not tell filled in
+    fn fill_estimated_rates(&mut self) -> StreamCenterResult<()> {
+        let Some(MediaFrame::Script { on_meta_data, .. }) = &self.gop_cache.script_frame else {
             return Ok(());
+        };
+        let Some(filled) = on_meta_data
+            .as_ref()
+            .as_ref()
+            .and_then(|on_meta_data| self.rate_estimate.fill(on_meta_data))
+        else {
+            return Ok(());
+        };
+        tracing::info!(
+            "stream {} rates estimated: {:?}",
+            self.identifier,
+            self.rate_estimate.stats()
+        );
+        self.on_media_frame(MediaFrame::Script {
+            timestamp_nano: self.last_media_dts_nano,
+            on_meta_data: Box::new(Some(filled)),
+            payload: Bytes::new(),
+        })
+    }
+
+    /// publishers re-send onMetaData if their settings change, maybe with only some of the fields,
+    /// the update is merged into the cached metadata, and sent at the current time of the stream
+    fn merge_meta_data(&self, frame: MediaFrame) -> MediaFrame {
+        let MediaFrame::Script {
+            timestamp_nano,
+            on_meta_data,
+            ..
+        } = &frame
+        else {
+            return frame;
+        };
+        let (
+            Some(update),
+            Some(MediaFrame::Script {
+                on_meta_data: cached,
+                ..
+            }),
+        ) = (on_meta_data.as_ref(), &self.gop_cache.script_frame)
+        else {
+            return frame;
+        };
+        let Some(mut merged) = cached.as_ref().clone() else {
+            return frame;
+        };
+        merged.merge(update.clone());
+        MediaFrame::Script {
+            timestamp_nano: (*timestamp_nano).max(self.last_media_dts_nano),
+            on_meta_data: Box::new(Some(merged)),
+            payload: Bytes::new(),
         }
+    }
 
-        let update_stat = |stat: &mut PlayStat, frame: &MediaFrame, fail: bool| {
-            if frame.is_video() {
-                stat.video_frame_send_fail_cnt += <bool as Into<u64>>::into(fail);
-                stat.video_frames_sent += <bool as Into<u64>>::into(!fail);
-            } else if frame.is_audio() {
-                stat.audio_frame_send_fail_cnt += <bool as Into<u64>>::into(fail);
-                stat.audio_frames_sent += <bool as Into<u64>>::into(!fail);
-            } else {
-                stat.script_frame_send_fail_cnt += <bool as Into<u64>>::into(fail);
-                stat.script_frames_sent += <bool as Into<u64>>::into(!fail);
-            }
+    /// onMetaData tells the channel count of the default audio track if the publisher sent its channel layout,
+    /// None if the frame is no onMetaData or it tells the count already
+    fn with_audio_channels(&self, frame: &MediaFrame) -> Option<MediaFrame> {
+        let channels = self.gop_cache.audio_channels(DEFAULT_AUDIO_TRACK)?;
+        let MediaFrame::Script {
+            timestamp_nano,
+            on_meta_data,
+            ..
+        } = frame
+        else {
+            return None;
+        };
+        let mut on_meta_data = on_meta_data.as_ref().clone()?;
+        if on_meta_data.audio_channels == Some(channels.into()) {
+            return None;
+        }
+        on_meta_data.audio_channels = Some(channels.into());
+        Some(MediaFrame::Script {
+            timestamp_nano: (*timestamp_nano).max(self.last_media_dts_nano),
+            on_meta_data: Box::new(Some(on_meta_data)),
+            payload: Bytes::new(),
+        })
+    }
+
+    /// compares an incoming sequence header against the cached one,
+    /// the first sequence header of a stream is not a change
+    fn detect_config_change(&self, frame: &MediaFrame) -> Option<StreamConfigChange> {
+        let (video_changed, audio_changed) = match frame {
+            MediaFrame::VideoConfig { config, .. } => (
+                self.gop_cache
+                    .video_config
+                    .as_ref()
+                    .is_some_and(|previous| video_config_changed(previous, config)),
+                false,
+            ),
+            MediaFrame::AudioConfig {
+                config, track_id, ..
+            } => (
+                false,
+                self.gop_cache
+                    .audio_config(*track_id)
+                    .is_some_and(|(previous, _)| audio_config_changed(previous, config)),
+            ),
+            _ => (false, false),
         };
+        if !video_changed && !audio_changed {
+            return None;
+        }
+        let dimensions = match frame {
+            MediaFrame::VideoConfig { config, .. } => config.dimensions(),
+            _ => None,
+        };
+        Some(StreamConfigChange {
+            config_generation: 0,
+            video_changed,
+            audio_changed,
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+        })
+    }
 
-        if !self.data_distributer.read().await.is_empty() && self.gop_cache.script_frame.is_none() {
-            let audio_codec = self.gop_cache.audio_config.as_ref().map(|(v, _)| match v {
-                AudioConfig::AAC(_) => AudioCodecCommon::AAC,
+    /// the parameter sets in band changed under an id they had, or the stream had no sequence header,
+    /// the one made of them goes out ahead of the frame with them
+    fn on_in_band_config(&mut self, config: MediaFrame) -> StreamCenterResult<()> {
+        if self.gop_cache.video_config.is_some() {
+            let dimensions = match &config {
+                MediaFrame::VideoConfig { config, .. } => config.dimensions(),
+                _ => None,
+            };
+            let change = StreamConfigChange {
+                config_generation: 0,
+                video_changed: true,
+                audio_changed: false,
+                width: dimensions.map(|(width, _)| width),
+                height: dimensions.map(|(_, height)| height),
+            };
+            self.on_config_change(change, &config)?;
+        }
+        self.on_media_frame(config)
+    }
+
+    /// the new sequence header is about to be distributed by the caller,
+    /// everything encoded with the old parameters goes out before it
+    fn on_config_change(
+        &mut self,
+        mut change: StreamConfigChange,
+        frame: &MediaFrame,
+    ) -> StreamCenterResult<()> {
+        for pending in self.mix_queue.dump_all() {
+            self.on_media_frame(pending)?;
+        }
+        // the cached gops can not be decoded with the new sequence header
+        self.gop_cache.clear_gops();
+
+        self.stream_dynamic_info.config_generation += 1;
+        // what was measured is of the old encoder settings
+        self.rate_estimate.restart();
+        change.config_generation = self.stream_dynamic_info.config_generation;
+        tracing::info!(
+            "stream {} config changed: {:?}, sequence header: {:?}",
+            self.identifier,
+            change,
+            frame
+        );
+
+        if change.video_changed {
+            let mut on_meta_data = self
+                .gop_cache
+                .script_frame
+                .as_ref()
+                .and_then(|script| match script {
+                    MediaFrame::Script { on_meta_data, .. } => on_meta_data.as_ref().clone(),
+                    _ => None,
+                })
+                .unwrap_or_else(|| {
+                    let video_codec = match frame {
+                        MediaFrame::VideoConfig { config, .. } => config.as_ref().into(),
+                        _ => VideoCodecCommon::AVC,
+                    };
+                    make_fake_on_meta_data(AudioCodecCommon::AAC, video_codec, 0.0, 0.0)
+                });
+            if let (Some(width), Some(height)) = (change.width, change.height) {
+                on_meta_data.width = width.to_f64();
+                on_meta_data.height = height.to_f64();
+            }
+            self.on_media_frame(MediaFrame::Script {
+                timestamp_nano: frame.get_decode_timestamp_ns(),
+                on_meta_data: Box::new(Some(on_meta_data)),
+                payload: Bytes::new(),
+            })?;
+        }
+
+        let _ = self
+            .event_sender
+            .send(StreamCenterEvent::ConfigChanged {
+                stream_id: self.identifier.clone(),
+                change,
+            })
+            .inspect_err(|err| {
+                tracing::error!("report config change to stream center failed: {:?}", err);
             });
+        Ok(())
+    }
+
+    /// applies a new selection to a subscriber without resubscribing,
+    /// a newly selected media or audio track starts with its sequence header, and for video, a key frame
+    fn update_media_selection(&mut self, subscriber_id: Uuid, media_selection: MediaSelection) {
+        let Some(handler) = self.subscribers.get_mut(&subscriber_id) else {
+            tracing::warn!(
+                "update media selection for unknown subscriber: {}, ignore",
+                subscriber_id
+            );
+            return;
+        };
+        let previous = handler.media_selection;
+        handler.media_selection = media_selection;
+        tracing::info!(
+            "media selection of subscriber {} updated: {:?} -> {:?}",
+            subscriber_id,
+            previous,
+            media_selection
+        );
+
+        if media_selection.video && !previous.video {
+            if let Some(video_sh) = &self.gop_cache.video_config {
+                let res = handler.data_sender.try_send(MediaFrame::VideoConfig {
+                    timestamp_nano: 0,
+                    config: Box::new(video_sh.clone()),
+                });
+                if let Err(err) = res {
+                    tracing::error!(
+                        "distribute video sh frame data to {} failed: {:?}",
+                        subscriber_id,
+                        err
+                    );
+                    handler.stat.video_frame_send_fail_cnt += 1;
+                } else {
+                    handler.stat.video_frames_sent += 1;
+                }
+            }
+            // a later video sh goes along with the frames, no need to dump the gop cache again
+            handler.stat.video_sh_sent = true;
+            handler.wait_video_key_frame = true;
+        }
+
+        if media_selection.audio
+            && (!previous.audio || media_selection.audio_track != previous.audio_track)
+        {
+            if let Some(audio_sh) = self.gop_cache.audio_config(media_selection.audio_track) {
+                let res = handler.data_sender.try_send(MediaFrame::AudioConfig {
+                    timestamp_nano: 0,
+                    sound_info: audio_sh.1,
+                    config: Box::new(audio_sh.0.clone()),
+                    track_id: media_selection.audio_track,
+                });
+                if let Err(err) = res {
+                    tracing::error!(
+                        "distribute audio sh frame data to {} failed: {:?}",
+                        subscriber_id,
+                        err
+                    );
+                    handler.stat.audio_frame_send_fail_cnt += 1;
+                } else {
+                    handler.stat.audio_frames_sent += 1;
+                    if let Some(channel_config) = self
+                        .gop_cache
+                        .audio_channel_config_frame(media_selection.audio_track, 0)
+                    {
+                        match handler.data_sender.try_send(channel_config) {
+                            Ok(()) => handler.stat.audio_frames_sent += 1,
+                            Err(_) => handler.stat.audio_frame_send_fail_cnt += 1,
+                        }
+                    }
+                }
+            }
+            handler.stat.audio_sh_sent = true;
+        }
+    }
+
+    /// answers the describes waiting once the sequence headers are known
+    fn on_configured_frame(&mut self, frame: &MediaFrame) {
+        self.configured = !frame.is_sequence_header()
+            || (self.stream_dynamic_info.video_config.is_some()
+                && self.stream_dynamic_info.audio_config.is_some());
+        if !self.configured || self.configured_waiters.is_empty() {
+            return;
+        }
+        tracing::info!(
+            "stream {} is configured, answer {} describes waiting",
+            self.identifier,
+            self.configured_waiters.len()
+        );
+        for waiter in std::mem::take(&mut self.configured_waiters) {
+            if waiter.send(Ok(self.describe())).is_err() {
+                tracing::warn!("the caller gave up on the describe waiting");
+            }
+        }
+    }
+
+    fn on_media_frame(&mut self, frame: MediaFrame) -> StreamCenterResult<()> {
+        let config_generation = match &frame {
+            MediaFrame::AudioConfig {
+                config, track_id, ..
+            } => {
+                // the description only tells the default track
+                if *track_id == DEFAULT_AUDIO_TRACK {
+                    self.stream_dynamic_info.audio_config = Some(*config.clone());
+                }
+                Some(self.stream_dynamic_info.config_generation)
+            }
+            MediaFrame::VideoConfig { config, .. } => {
+                self.stream_dynamic_info.video_config = Some(*config.clone());
+                Some(self.stream_dynamic_info.config_generation)
+            }
+            _ => None,
+        };
+        if !self.configured && frame.is_sequence_header() {
+            self.on_configured_frame(&frame);
+        }
+        if let Some(keyframe) =
+            KeyframeSnapshot::from_frame(&frame, self.stream_dynamic_info.video_config.as_ref())
+        {
+            self.keyframe = Some(keyframe);
+        }
+        if let Some(config_generation) = config_generation {
+            self.tracer
+                .record(&self.identifier, || TraceEvent::SequenceHeaderRefresh {
+                    kind: (&frame).into(),
+                    config_generation,
+                });
+        }
+        let evicted_gops_cnt = self.gop_cache.get_evicted_gops_cnt();
+        let cached = match self.gop_cache.append_frame(frame.clone()) {
+            Ok(cached) => cached,
+            Err(err) => {
+                tracing::error!("append frame to gop cache failed: {:?}", err);
+                false
+            }
+        };
+        self.metrics
+            .gop_cache_evictions
+            .inc_by(self.gop_cache.get_evicted_gops_cnt() - evicted_gops_cnt);
+        self.metrics
+            .gop_cache_bytes
+            .set(self.gop_cache.get_bytes().to_i64().unwrap_or(i64::MAX));
+        if let Some(mirror) = &self.mirror
+            && mirror.try_send(frame.clone()).is_err()
+        {
+            tracing::info!("stop mirroring stream {}", self.identifier);
+            self.mirror = None;
+        }
+        if let Some(dvr) = &mut self.dvr {
+            dvr.append(frame.clone());
+            self.trim_dvr();
+        }
+
+        if self.subscribers.is_empty() {
+            return Ok(());
+        }
+        // the mix queue and the gop cache keep the payload as is
+        #[cfg(feature = "frame-crc")]
+        crate::frame_crc::verify("fanout", &self.identifier, &frame, &frame);
+
+        let update_stat =
+            |stat: &mut PlayStat, frame: &MediaFrame, fail: bool| stat.count_sent(frame, fail);
+
+        if self.gop_cache.script_frame.is_none() {
+            let audio_codec = self
+                .gop_cache
+                .audio_configs
+                .values()
+                .next()
+                .map(|(v, _)| match v {
+                    // the rate the decoder outputs, twice the core rate of he-aac
+                    AudioConfig::AAC(config) => {
+                        (AudioCodecCommon::AAC, config.effective_sample_rate())
+                    }
+                });
             if let Some((video_codec, video_height, video_width)) =
-                self.gop_cache.video_config.as_ref().map(|v| match v {
-                    codec_common::video::VideoConfig::H264(H264VideoConfig {
-                        sps,
-                        pps: _,
-                        sps_ext: _,
-                        avc_decoder_configuration_record: _,
-                    }) => (
-                        VideoCodecCommon::AVC,
-                        sps.as_ref().map_or(0, |v| v.get_video_height()),
-                        sps.as_ref().map_or(0, |v| v.get_video_width()),
-                    ),
+                self.gop_cache.video_config.as_ref().map(|v| {
+                    let (width, height) = v.dimensions().unwrap_or((0, 0));
+                    (VideoCodecCommon::from(v), height, width)
                 })
-                && let Some(audio_codec) = audio_codec
+                && let Some((audio_codec, audio_sample_rate)) = audio_codec
             {
-                let fake_meta = Some(make_fake_on_meta_data(
+                let mut fake_meta = make_fake_on_meta_data(
                     audio_codec,
                     video_codec,
                     video_height.to_f64().unwrap(),
                     video_width.to_f64().unwrap(),
-                ));
+                );
+                fake_meta.audio_sample_rate = audio_sample_rate.map(f64::from);
+                let fake_meta = Some(fake_meta);
                 tracing::info!("make fake meta: {:?}", fake_meta);
                 self.gop_cache.script_frame = Some(MediaFrame::Script {
                     timestamp_nano: 0,
@@ -269,67 +1687,213 @@ impl StreamSource {
             }
         }
 
+        let frame_bytes = frame.payload_bytes() as u64;
         let mut invalid_ids = vec![];
-        for (key, handler) in &mut self.data_distributer.write().await.iter_mut() {
-            if (!handler.stat.audio_sh_sent && !handler.parsed_context.video_only)
-                || (!handler.stat.video_sh_sent && !handler.parsed_context.audio_only)
+        for (key, handler) in self.subscribers.iter_mut() {
+            if (!handler.stat.audio_sh_sent && handler.media_selection.audio)
+                || (!handler.stat.video_sh_sent && handler.media_selection.video)
             {
-                self.on_new_consumer(key, handler, update_stat).await?;
+                Self::on_new_consumer(
+                    &self.gop_cache,
+                    &mut self.stream_dynamic_info,
+                    &self.metrics,
+                    key,
+                    handler,
+                    update_stat,
+                );
+                // the dumped gops end with the frame already
+                if cached {
+                    continue;
+                }
             }
-            if handler.parsed_context.audio_only && frame.is_video() {
+            if !handler.media_selection.accepts(&frame)
+                || (handler.timeshift_cursor.is_some() && !frame.is_control())
+            {
                 continue;
             }
-            if handler.parsed_context.video_only && frame.is_audio() {
-                continue;
+            if handler.wait_video_key_frame && frame.is_video() && !frame.is_sequence_header() {
+                if !frame.is_video_key_frame() {
+                    continue;
+                }
+                handler.wait_video_key_frame = false;
             }
             let res = handler.data_sender.try_send(frame.clone());
             if res.is_err() {
                 tracing::error!("distribute frame data to {} failed: {:?}", key, res);
                 invalid_ids.push(*key);
+            } else {
+                self.metrics.frames_out.inc();
+                self.metrics.bytes_out.inc_by(frame_bytes);
+            }
+            if let Some(backpressure) = &mut self.backpressure {
+                backpressure.count_sent(res.is_err());
             }
+            self.tracer
+                .record(&self.identifier, || TraceEvent::SentToSubscriber {
+                    subscriber: *key,
+                    kind: (&frame).into(),
+                    dts_ms: frame.get_decode_timestamp_ms(),
+                    success: res.is_ok(),
+                });
             if frame.is_video_key_frame() {
                 handler.stat.first_key_frame_sent = true;
             }
             update_stat(&mut handler.stat, &frame, res.is_err());
         }
 
-        self.data_distributer.write().await.retain(|key, value| {
+        self.subscribers.retain(|key, value| {
             if invalid_ids.contains(key) {
                 tracing::info!("remove invalid subscriber: {}, {:?}", key, value);
+                StreamMetrics::subscribers(value.play_protocol).dec();
                 return false;
             }
             true
         });
+        self.feed_timeshift_readers();
 
         Ok(())
     }
 
-    async fn on_new_consumer<F>(
-        &self,
+    /// a timeshift subscriber gets the sequence headers in effect at its start in the dvr window,
+    /// the frames from there on are fed to it as its channel takes them
+    fn start_timeshift(&mut self, handler: &mut SubscribeHandler) {
+        let (Some(dvr), Some(timeshift)) = (&self.dvr, handler.parsed_context.timeshift) else {
+            return;
+        };
+        let Some(start) = dvr.start_of(timeshift) else {
+            tracing::info!(
+                "timeshift {:?} of subscriber {} is not in the dvr window, it plays live",
+                timeshift,
+                handler.id
+            );
+            return;
+        };
+        for header in dvr.headers_at(start) {
+            if !handler.media_selection.accepts(&header) {
+                continue;
+            }
+            let res = handler.data_sender.try_send(header.clone());
+            if let Err(err) = &res {
+                tracing::error!(
+                    "distribute sequence header to timeshift subscriber {} failed: {:?}",
+                    handler.id,
+                    err
+                );
+            }
+            handler.stat.count_sent(&header, res.is_err());
+        }
+        // the gop cache is not dumped to it, the window has the frames before live
+        handler.stat.video_sh_sent = true;
+        handler.stat.audio_sh_sent = true;
+        handler.stat.first_key_frame_sent = true;
+        handler.timeshift_cursor = Some(start);
+        tracing::info!(
+            "subscriber {} starts {:?} in the dvr window of stream {}, {} frames behind live",
+            handler.id,
+            timeshift,
+            self.identifier,
+            dvr.end_seq() - start
+        );
+    }
+
+    /// sends the timeshift subscribers on from their cursors, as much as their channels take,
+    /// one that reached the end of the window gets the frames after live
+    fn feed_timeshift_readers(&mut self) {
+        let Some(dvr) = &self.dvr else {
+            return;
+        };
+        let mut invalid_ids = vec![];
+        for (key, handler) in self.subscribers.iter_mut() {
+            let Some(mut cursor) = handler.timeshift_cursor else {
+                continue;
+            };
+            while let Some(frame) = dvr.get(cursor) {
+                if handler.data_sender.capacity() == 0 {
+                    break;
+                }
+                cursor += 1;
+                if !handler.media_selection.accepts(frame) {
+                    continue;
+                }
+                let res = handler.data_sender.try_send(frame.clone());
+                handler.stat.count_sent(frame, res.is_err());
+                if res.is_err() {
+                    tracing::error!("distribute dvr frame to {} failed: {:?}", key, res);
+                    invalid_ids.push(*key);
+                    break;
+                }
+                self.metrics.frames_out.inc();
+                self.metrics.bytes_out.inc_by(frame.payload_bytes() as u64);
+            }
+            if cursor >= dvr.end_seq() {
+                tracing::info!("timeshift subscriber {} caught up with live", key);
+                handler.timeshift_cursor = None;
+            } else {
+                handler.timeshift_cursor = Some(cursor);
+            }
+        }
+        for key in invalid_ids {
+            if let Some(handler) = self.subscribers.remove(&key) {
+                StreamMetrics::subscribers(handler.play_protocol).dec();
+            }
+        }
+    }
+
+    /// the window is trimmed up to the timeshift subscriber furthest behind,
+    /// which is dropped if it holds the window at twice its byte cap
+    fn trim_dvr(&mut self) {
+        let Some(dvr) = &mut self.dvr else {
+            return;
+        };
+        loop {
+            let pinned = self
+                .subscribers
+                .iter()
+                .filter_map(|(key, handler)| handler.timeshift_cursor.map(|cursor| (cursor, *key)))
+                .min();
+            dvr.trim(pinned.map(|(cursor, _)| cursor));
+            let Some((_, key)) = pinned.filter(|_| dvr.is_pinned_over()) else {
+                return;
+            };
+            tracing::warn!(
+                "drop timeshift subscriber {} of stream {}, it holds {} bytes of the dvr window",
+                key,
+                self.identifier,
+                dvr.bytes()
+            );
+            if let Some(handler) = self.subscribers.remove(&key) {
+                StreamMetrics::subscribers(handler.play_protocol).dec();
+            }
+        }
+    }
+
+    fn on_new_consumer<F>(
+        gop_cache: &GopQueue,
+        stream_dynamic_info: &mut StreamSourceDynamicInfo,
+        metrics: &StreamMetrics,
         key: &Uuid,
         handler: &mut SubscribeHandler,
         update_stat: F,
-    ) -> StreamCenterResult<()>
-    where
+    ) where
         F: Fn(&mut PlayStat, &MediaFrame, bool),
     {
         let span = trace_span!(
             "new comsumer dump gop cache",
             play_id = ?key,
-            gops_cnt=self.gop_cache.gops.len(),
-            video_cnt=self.gop_cache.get_video_frame_cnt(),
-            audio_cnt=self.gop_cache.get_audio_frame_cut()
+            gops_cnt=gop_cache.gops.len(),
+            video_cnt=gop_cache.get_video_frame_cnt(),
+            audio_cnt=gop_cache.get_audio_frame_cut()
         );
         let _enter = span.enter();
         // we trust the gop stats after 3 gops (but why?)
-        if self.gop_cache.gops.len() > 2 {
-            self.stream_dynamic_info.write().await.has_audio =
-                self.gop_cache.get_audio_frame_cut() > 0;
-            self.stream_dynamic_info.write().await.has_video =
-                self.gop_cache.get_video_frame_cnt() > 0;
+        if gop_cache.gops.len() > 2 {
+            stream_dynamic_info.has_audio = gop_cache.get_audio_frame_cut() > 0;
+            stream_dynamic_info.has_video = gop_cache.get_video_frame_cnt() > 0;
         }
 
-        if let Some(script) = &self.gop_cache.script_frame {
+        if let Some(script) = &gop_cache.script_frame
+            && handler.media_selection.data
+        {
             let res = handler.data_sender.try_send(script.clone());
             if res.is_err() {
                 tracing::error!("distribute script frame data to {} failed: {:?}", key, res);
@@ -340,8 +1904,8 @@ impl StreamSource {
             }
         }
 
-        if let Some(video_sh) = &self.gop_cache.video_config {
-            if !handler.parsed_context.audio_only {
+        if let Some(video_sh) = &gop_cache.video_config {
+            if handler.media_selection.video {
                 let res = handler.data_sender.try_send(MediaFrame::VideoConfig {
                     timestamp_nano: 0,
                     config: Box::new(video_sh.clone()),
@@ -363,12 +1927,14 @@ impl StreamSource {
             handler.stat.video_sh_sent = true;
         }
 
-        if let Some(audio_sh) = &self.gop_cache.audio_config {
-            if !handler.parsed_context.video_only {
+        let audio_track = handler.media_selection.audio_track;
+        if let Some(audio_sh) = gop_cache.audio_config(audio_track) {
+            if handler.media_selection.audio {
                 let res = handler.data_sender.try_send(MediaFrame::AudioConfig {
                     timestamp_nano: 0,
                     sound_info: audio_sh.1,
                     config: Box::new(audio_sh.0.clone()),
+                    track_id: audio_track,
                 });
                 if res.is_err() {
                     tracing::error!(
@@ -381,17 +1947,37 @@ impl StreamSource {
                     handler.stat.audio_sh_sent = true;
                     handler.stat.audio_frames_sent += 1;
                     tracing::info!("distribute audio sh frame to {} succeed", key);
+                    if let Some(channel_config) =
+                        gop_cache.audio_channel_config_frame(audio_track, 0)
+                    {
+                        match handler.data_sender.try_send(channel_config) {
+                            Ok(()) => handler.stat.audio_frames_sent += 1,
+                            Err(_) => handler.stat.audio_frame_send_fail_cnt += 1,
+                        }
+                    }
                 }
             }
         } else {
             handler.stat.audio_sh_sent = true;
         }
 
-        let total_gop_cnt = self.gop_cache.get_gops_cnt();
+        let total_gop_cnt = gop_cache.get_gops_cnt();
+
+        // the gops start from key frames, an empty cache has none
+        if handler.media_selection.video
+            && gop_cache.video_config.is_some()
+            && gop_cache.get_video_frame_cnt() == 0
+        {
+            tracing::info!(
+                "no key frame cached for new consumer {}, it waits for the next",
+                key
+            );
+            metrics.gop_cache_keyframe_waits.inc();
+        }
 
         if total_gop_cnt == 0 {
             tracing::info!("got new consumer {} but no gop cached", key);
-            return Ok(());
+            return;
         }
 
         let gop_consumer_cnt = min(
@@ -409,7 +1995,7 @@ impl StreamSource {
         tracing::info!("dump {} gops", gop_consumer_cnt);
 
         for index in (total_gop_cnt - gop_consumer_cnt)..total_gop_cnt {
-            let gop = self.gop_cache.gops.get(index).expect("this cannot be none");
+            let gop = gop_cache.gops.get(index).expect("this cannot be none");
 
             let span = tracing::trace_span!(
                 "dump gop",
@@ -419,10 +2005,7 @@ impl StreamSource {
             let _enter = span.enter();
             tracing::info!("start dump");
             for frame in &gop.media_frames {
-                if handler.parsed_context.audio_only && frame.is_video() {
-                    continue;
-                }
-                if handler.parsed_context.video_only && frame.is_audio() {
+                if !handler.media_selection.accepts(frame) {
                     continue;
                 }
                 let res = handler.data_sender.try_send(frame.clone());
@@ -439,6 +2022,73 @@ impl StreamSource {
             // there must be some key frames
             handler.stat.first_key_frame_sent = true;
         }
-        Ok(())
+    }
+}
+
+fn video_config_changed(previous: &VideoConfig, current: &VideoConfig) -> bool {
+    match (previous, current) {
+        (
+            VideoConfig::H264(H264VideoConfig {
+                sps: previous_sps,
+                avc_decoder_configuration_record: previous_record,
+                ..
+            }),
+            VideoConfig::H264(H264VideoConfig {
+                sps: current_sps,
+                avc_decoder_configuration_record: current_record,
+                ..
+            }),
+        ) => {
+            if let (Some(previous), Some(current)) = (previous_record, current_record) {
+                let mut previous_bytes = Vec::new();
+                let mut current_bytes = Vec::new();
+                return previous.write_to(&mut previous_bytes).is_err()
+                    || current.write_to(&mut current_bytes).is_err()
+                    || previous_bytes != current_bytes;
+            }
+            let sps_summary = |sps: &Option<Sps>| {
+                sps.as_ref().map(|sps| {
+                    (
+                        sps.profile_idc,
+                        sps.level_idc,
+                        sps.get_video_width(),
+                        sps.get_video_height(),
+                    )
+                })
+            };
+            sps_summary(previous_sps) != sps_summary(current_sps)
+        }
+        (
+            VideoConfig::AV1(Av1VideoConfig {
+                sequence_header: previous_header,
+                av1_codec_configuration_record: previous_record,
+            }),
+            VideoConfig::AV1(Av1VideoConfig {
+                sequence_header: current_header,
+                av1_codec_configuration_record: current_record,
+            }),
+        ) => {
+            if let (Some(previous), Some(current)) = (previous_record, current_record) {
+                return previous != current;
+            }
+            previous_header != current_header
+        }
+        // switching codecs always takes a new decoder
+        _ => true,
+    }
+}
+
+fn audio_config_changed(previous: &AudioConfig, current: &AudioConfig) -> bool {
+    let to_bytes = |config: &AudioConfig| match config {
+        AudioConfig::AAC(config) => {
+            let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+            config.write_to(&mut writer).ok()?;
+            writer.byte_align().ok()?;
+            Some(writer.into_writer())
+        }
+    };
+    match (to_bytes(previous), to_bytes(current)) {
+        (Some(previous), Some(current)) => previous != current,
+        _ => true,
     }
 }
@@ -0,0 +1,137 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::{backpressure::BackpressureAdvisory, drain::DrainRequest, errors::StreamCenterError};
+
+/// what to do when a stream key is published while the previous publisher is still registered,
+/// e.g., a flaky publisher reconnects before its old tcp session times out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TakeoverPolicy {
+    /// the new publish fails
+    #[default]
+    Reject,
+    /// the previous publisher is disconnected and the new one gets the stream
+    KickOld,
+    /// same as KickOld, but only if no frames arrived from the previous publisher for the duration
+    TakeoverIfIdle(Duration),
+}
+
+/// parses `reject`, `kick_old` or `takeover_if_idle:<seconds>`
+impl FromStr for TakeoverPolicy {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "reject" => Ok(Self::Reject),
+            "kick_old" => Ok(Self::KickOld),
+            policy => {
+                let idle_secs = policy
+                    .strip_prefix("takeover_if_idle:")
+                    .and_then(|secs| secs.trim().parse::<u64>().ok())
+                    .ok_or_else(|| StreamCenterError::InvalidTakeoverPolicy(policy.to_string()))?;
+                Ok(Self::TakeoverIfIdle(Duration::from_secs(idle_secs)))
+            }
+        }
+    }
+}
+
+/// the form parsed by from_str
+impl fmt::Display for TakeoverPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reject => write!(f, "reject"),
+            Self::KickOld => write!(f, "kick_old"),
+            Self::TakeoverIfIdle(idle) => write!(f, "takeover_if_idle:{}", idle.as_secs()),
+        }
+    }
+}
+
+impl Serialize for TakeoverPolicy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TakeoverPolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// why the stream center disconnects a publisher
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KickReason {
+    /// another publisher took over the stream under the policy
+    Takeover(TakeoverPolicy),
+    /// the publisher sent nothing for the reap threshold of the idle watchdog
+    IdleTimeout,
+    /// the server is drained and the publisher did not leave within the grace period,
+    /// or a new publisher of the stream showed up here
+    Drain,
+}
+
+/// delivered to a publish session when the stream center takes its stream away
+#[derive(Debug, Clone)]
+pub struct PublisherKicked {
+    pub reason: KickReason,
+    /// how long the kicked publisher had been sending nothing
+    pub idle: Duration,
+}
+
+/// held by the stream center to stop the session owning a publish
+#[derive(Debug)]
+pub struct PublisherHandle {
+    pub id: Uuid,
+    pub kick_sender: oneshot::Sender<PublisherKicked>,
+    /// taken when the server is drained, the publisher is asked to move to another host
+    pub drain_sender: Option<oneshot::Sender<DrainRequest>>,
+    /// the advisories of the stream if the backpressure policy of the app is on
+    pub backpressure_sender: mpsc::UnboundedSender<BackpressureAdvisory>,
+}
+
+/// when the publisher last sent a frame, updated by the stream source for every frame
+#[derive(Debug)]
+pub struct PublishActivity {
+    start_time: Instant,
+    /// since start_time
+    last_frame_ms: AtomicU64,
+}
+
+impl PublishActivity {
+    pub fn new() -> Self {
+        Self {
+            start_time: Instant::now(),
+            last_frame_ms: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn on_frame(&self) {
+        self.last_frame_ms.store(
+            self.start_time.elapsed().as_millis() as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// since the last frame, or since publish if no frame arrived yet
+    pub fn idle_duration(&self) -> Duration {
+        let last_frame = Duration::from_millis(self.last_frame_ms.load(Ordering::Relaxed));
+        self.start_time.elapsed().saturating_sub(last_frame)
+    }
+}
+
+impl Default for PublishActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,5285 @@
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Cursor, sync::Arc, time::Duration};
+
+    use amf_formats::amf0;
+
+    use codec_aac::mpeg4_configuration::audio_specific_config::AudioSpecificConfig;
+    use codec_common::{
+        FrameType, MediaFrameTimestamp,
+        audio::{
+            AudioCodecCommon, AudioConfig, AudioFrameInfo, ChannelLayout, SoundInfoCommon,
+            SoundRateCommon, SoundSizeCommon, SoundTypeCommon,
+        },
+        video::{H264VideoConfig, VideoCodecCommon, VideoConfig, VideoFrameInfo, VideoFrameUnit},
+    };
+    use codec_h264::{
+        avc_decoder_configuration_record::AvcDecoderConfigurationRecord, nalu::NalUnit,
+        nalu_header::NaluHeader, nalu_type::NALUType, pps::Pps, sps::Sps,
+        sps::chroma_format_idc::ChromaFormatIdc, vui::TimingInfo,
+    };
+    use flv_formats::{
+        header::FLVHeader,
+        tag::{
+            FLVTag,
+            enhanced::{
+                ex_audio::ex_audio_body::{
+                    AudioChannel, AudioChannelOrder, AudioMultichannelConfig,
+                },
+                ex_video::ex_video_header::VideoPacketType,
+            },
+            flv_tag_body::{FLVTagBody, FLVTagBodyWithFilter},
+            flv_tag_header::{FLVTagHeader, FLVTagType},
+            on_meta_data::OnMetaData,
+            video_tag_header_info::VideoTagHeaderWithoutMultiTrack,
+        },
+    };
+    use tokio::sync::mpsc;
+    use tokio_util::bytes::{Buf, Bytes};
+    use utils::traits::{
+        reader::{BitwiseReadFrom, ReadFrom},
+        writer::WriteTo,
+    };
+
+    use crate::{
+        alias::StreamAliases,
+        audio_continuity::{AudioConcealmentStats, AudioGapConcealment},
+        audio_track::{AudioTrack, DEFAULT_AUDIO_TRACK},
+        backpressure::{
+            BACKPRESSURE_SAMPLE_INTERVAL, BackpressureAdvisor, BackpressureAdvisory,
+            BackpressurePolicy, CongestionSample,
+        },
+        drain::{DrainOutcome, DrainRequest},
+        dvr::{DVR_DELAY_KEY, DvrBuffer, DvrWindow, Timeshift},
+        end_of_stream::EndOfStreamReason,
+        errors::StreamCenterError,
+        events::StreamCenterEvent,
+        failover::{BACKUP_STREAM_KEY, backup_stream},
+        gop::{FlvVideoHeader, GopQueue, MAX_DATA_FRAME_BYTES, MediaFrame},
+        gop_budget::{GopCacheBudget, GopCacheBudgetConfig},
+        identity::StreamIdentity,
+        latency::{LatencyConfig, LatencyHistogram, LatencySummary},
+        make_fake_on_meta_data,
+        notification::StreamNotification,
+        parameter_sets::{ParameterSetCarriage, ParameterSetPlacement, ParameterSetTracker},
+        rate_estimate::{
+            AUDIO_DATA_RATE_FIELD, FRAME_RATE_FIELD, RateEstimator, VIDEO_DATA_RATE_FIELD,
+        },
+        recovery_point::RecoveryPointJoin,
+        rtcp_peer::{MAX_RTCP_PEERS, RtcpPeer},
+        rtmp_control::{PeerBandwidthLimitType, RtmpControl},
+        rtp_receive::RtpReceiveStats,
+        serialized::{FlvTimestampRebase, SerializedFlavor, SerializedFrameCache},
+        signal::StreamSignal,
+        slice_integrity::SliceIntegrity,
+        snapshot::{
+            SNAPSHOT_VERSION, SourceDefinition, SourceSnapshot, SourceSnapshotter,
+            StreamCenterSnapshot, VodSourceDefinition,
+        },
+        stream_center::StreamCenter,
+        stream_source::StreamSource,
+        stream_source::{MediaSelection, PlayProtocol, PublishProtocol},
+        takeover::{KickReason, TakeoverPolicy},
+        timestamp_normalization::{TimestampNormalization, TimestampNormalizationStats},
+        trace::{
+            PipelineTracer, RingBufferTracer, TraceEvent, TraceFrameKind, TraceHandle, TraceRing,
+        },
+        watchdog::IdleWatchdog,
+    };
+
+    /// baseline profile, 1280x720
+    const SPS_720P: [u8; 8] = [0x42, 0x00, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe4];
+    /// baseline profile, 1920x1088 cropped to 1920x1080
+    const SPS_1080P: [u8; 9] = [0x42, 0x00, 0x1f, 0xda, 0x01, 0xe0, 0x08, 0x9f, 0x95];
+
+    const FRAME_INTERVAL_MS: u64 = 40;
+    const GOP_SIZE: u64 = 10;
+
+    fn stream_id() -> StreamIdentity {
+        StreamIdentity::new("live", "selection").unwrap()
+    }
+
+    fn start_stream_center() -> mpsc::UnboundedSender<StreamCenterEvent> {
+        let mut stream_center = StreamCenter::new();
+        let sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        sender
+    }
+
+    fn video_config() -> MediaFrame {
+        MediaFrame::VideoConfig {
+            timestamp_nano: 0,
+            config: Box::new(VideoConfig::H264(H264VideoConfig {
+                sps: None,
+                pps: None,
+                sps_ext: None,
+                avc_decoder_configuration_record: None,
+            })),
+        }
+    }
+
+    fn video_config_with_sps(sps_bytes: &[u8], timestamp_nano: u64) -> MediaFrame {
+        let mut reader = bitstream_io::BitReader::endian(sps_bytes, bitstream_io::BigEndian);
+        let sps = Sps::read_from(&mut reader).unwrap();
+        MediaFrame::VideoConfig {
+            timestamp_nano,
+            config: Box::new(VideoConfig::H264(H264VideoConfig {
+                sps: Some(sps),
+                pps: None,
+                sps_ext: None,
+                avc_decoder_configuration_record: None,
+            })),
+        }
+    }
+
+    fn video_config_height(frame: &MediaFrame) -> Option<u64> {
+        match frame {
+            MediaFrame::VideoConfig { config, .. } => config.dimensions().map(|(_, height)| height),
+            _ => None,
+        }
+    }
+
+    fn video_frame(index: u64) -> MediaFrame {
+        let (nal_header, frame_type) = if index.is_multiple_of(GOP_SIZE) {
+            (0x65, FrameType::KeyFrame)
+        } else {
+            (0x41, FrameType::CodedFrames)
+        };
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                frame_type,
+                MediaFrameTimestamp::with_timestamp_ms(index * FRAME_INTERVAL_MS),
+            ),
+            payload: VideoFrameUnit::H264 {
+                nal_units: vec![NalUnit {
+                    header: NaluHeader::try_from(nal_header).unwrap(),
+                    body: Bytes::from_static(&[0; 4]),
+                }],
+            },
+        }
+    }
+
+    /// audio frames sit right between two video frames, so no two frames share a dts
+    fn audio_frame(index: u64) -> MediaFrame {
+        MediaFrame::Audio {
+            frame_info: AudioFrameInfo::new(
+                AudioCodecCommon::AAC,
+                FrameType::CodedFrames,
+                SoundRateCommon::KHZ44,
+                SoundSizeCommon::Bit16,
+                SoundTypeCommon::Stereo,
+                (index * FRAME_INTERVAL_MS + FRAME_INTERVAL_MS / 2) * 1_000_000,
+            ),
+            payload: Bytes::from_static(&[0; 4]),
+        }
+    }
+
+    fn script_frame() -> MediaFrame {
+        MediaFrame::Script {
+            timestamp_nano: 0,
+            on_meta_data: Box::new(Some(make_fake_on_meta_data(
+                AudioCodecCommon::AAC,
+                VideoCodecCommon::AVC,
+                720.0,
+                1280.0,
+            ))),
+            payload: Bytes::new(),
+        }
+    }
+
+    async fn send_av_frames(sender: &mpsc::Sender<MediaFrame>, indexes: std::ops::Range<u64>) {
+        for index in indexes {
+            sender.send(video_frame(index)).await.unwrap();
+            sender.send(audio_frame(index)).await.unwrap();
+        }
+    }
+
+    /// collects delivered frames until nothing arrives for a while
+    async fn drain(receiver: &mut mpsc::Receiver<MediaFrame>) -> Vec<MediaFrame> {
+        let mut frames = vec![];
+        while let Ok(Some(frame)) =
+            tokio::time::timeout(Duration::from_millis(200), receiver.recv()).await
+        {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn video_only_subscriber_gets_sequence_header_after_metadata_refresh() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::video_only(),
+        )
+        .await
+        .unwrap();
+
+        send_av_frames(&media_sender, GOP_SIZE..GOP_SIZE * 2).await;
+        media_sender.send(script_frame()).await.unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, GOP_SIZE * 2..GOP_SIZE * 4).await;
+
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(frames.iter().any(|frame| frame.is_video()));
+        assert!(
+            frames.iter().all(|frame| !frame.is_audio()),
+            "video only subscriber got audio frames"
+        );
+        let script_position = frames
+            .iter()
+            .rposition(|frame| matches!(frame, MediaFrame::Script { .. }))
+            .expect("metadata refresh is not delivered");
+        assert!(
+            frames[script_position..]
+                .iter()
+                .any(|frame| frame.is_video() && frame.is_sequence_header()),
+            "no video sequence header after metadata refresh"
+        );
+    }
+
+    #[tokio::test]
+    async fn new_subscriber_gets_the_frame_it_joins_on_once() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        // the gop cache is dumped along with the first frame after the subscribe, which it ends with
+        send_av_frames(&media_sender, GOP_SIZE..GOP_SIZE * 2).await;
+
+        let frames = drain(&mut response.media_receiver).await;
+        for is_video in [true, false] {
+            let dts: Vec<_> = frames
+                .iter()
+                .filter(|frame| !frame.is_sequence_header())
+                .filter(|frame| {
+                    if is_video {
+                        frame.is_video()
+                    } else {
+                        frame.is_audio()
+                    }
+                })
+                .map(MediaFrame::get_decode_timestamp_ns)
+                .collect();
+            assert!(!dts.is_empty());
+            assert!(dts.windows(2).all(|w| w[0] < w[1]), "{:?}", dts);
+        }
+    }
+
+    #[tokio::test]
+    async fn describe_configured_waits_for_the_publish_and_its_sequence_headers() {
+        let event_sender = start_stream_center();
+        let describe = tokio::spawn({
+            let event_sender = event_sender.clone();
+            async move { StreamCenter::describe_configured(&event_sender, &stream_id()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        // the audio sequence header may still come
+        media_sender.send(video_config()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!describe.is_finished());
+
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+        let description = tokio::time::timeout(Duration::from_secs(1), describe)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(description.video_config.is_some());
+        assert!(description.audio_conifg.is_none());
+
+        // answered right away from now on
+        let description = tokio::time::timeout(
+            Duration::from_secs(1),
+            StreamCenter::describe_configured(&event_sender, &stream_id()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(description.video_config.is_some());
+    }
+
+    #[tokio::test]
+    async fn describe_configured_fails_if_the_stream_stops_before_its_sequence_headers() {
+        let event_sender = start_stream_center();
+        let _media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let describe = tokio::spawn({
+            let event_sender = event_sender.clone();
+            async move { StreamCenter::describe_configured(&event_sender, &stream_id()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(1), describe)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(result, Err(StreamCenterError::StreamNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn media_selection_updates_without_resubscribe() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::audio_only(),
+        )
+        .await
+        .unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE / 2).await;
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(frames.iter().all(|frame| !frame.is_video()));
+
+        StreamCenter::update_media_selection(
+            &event_sender,
+            response.subscribe_id,
+            &stream_id(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        send_av_frames(&media_sender, GOP_SIZE / 2..GOP_SIZE * 3).await;
+
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(frames.iter().any(|frame| frame.is_audio()));
+        let mut video_frames = frames.iter().filter(|frame| frame.is_video());
+        assert!(
+            video_frames
+                .next()
+                .is_some_and(|frame| frame.is_sequence_header()),
+            "video sequence header should come first once video is enabled"
+        );
+        assert!(
+            video_frames
+                .next()
+                .is_some_and(|frame| frame.is_video_key_frame()),
+            "video should resume from a key frame"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolution_change_resends_sequence_header_before_next_key_frame() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender
+            .send(video_config_with_sps(&SPS_720P, 0))
+            .await
+            .unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+
+        let mut receivers = vec![];
+        for _ in 0..2 {
+            let response = StreamCenter::subscribe(
+                &event_sender,
+                PlayProtocol::HTTPFLV,
+                &stream_id(),
+                &HashMap::new(),
+                MediaSelection::default(),
+            )
+            .await
+            .unwrap();
+            receivers.push(response.media_receiver);
+        }
+
+        send_av_frames(&media_sender, GOP_SIZE..GOP_SIZE * 2).await;
+        let switch_time_nano = GOP_SIZE * 2 * FRAME_INTERVAL_MS * 1_000_000;
+        media_sender
+            .send(video_config_with_sps(&SPS_1080P, switch_time_nano))
+            .await
+            .unwrap();
+        send_av_frames(&media_sender, GOP_SIZE * 2..GOP_SIZE * 4).await;
+
+        for receiver in &mut receivers {
+            let frames = drain(receiver).await;
+            let new_key_frame = frames
+                .iter()
+                .position(|frame| {
+                    frame.is_video_key_frame()
+                        && frame.get_decode_timestamp_ns() >= switch_time_nano
+                })
+                .expect("no key frame after the switch");
+            let last_old_frame = frames
+                .iter()
+                .rposition(|frame| {
+                    frame.is_video()
+                        && !frame.is_sequence_header()
+                        && frame.get_decode_timestamp_ns() < switch_time_nano
+                })
+                .expect("no frame before the switch");
+            assert!(last_old_frame < new_key_frame);
+
+            let new_headers: Vec<_> = frames[last_old_frame..new_key_frame]
+                .iter()
+                .filter_map(video_config_height)
+                .collect();
+            assert_eq!(new_headers, vec![1080]);
+            assert!(
+                frames[new_key_frame..]
+                    .iter()
+                    .all(|frame| video_config_height(frame).is_none()),
+                "sequence header is sent again after the key frame"
+            );
+            assert!(frames[..new_key_frame].iter().any(|frame| matches!(
+                frame,
+                MediaFrame::Script { on_meta_data, .. }
+                    if on_meta_data.as_ref().as_ref().and_then(|v| v.height) == Some(1080.0)
+            )));
+        }
+
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(description.config_generation, 1);
+    }
+
+    #[test]
+    fn trace_ring_keeps_the_latest_records() {
+        let mut ring = TraceRing::new(3);
+        for index in 0..5 {
+            ring.push(
+                index,
+                TraceEvent::MixQueueEnqueued {
+                    kind: TraceFrameKind::Video,
+                    dts_ms: index,
+                    queued: 1,
+                },
+            );
+        }
+        assert_eq!(ring.len(), 3);
+        let records = ring.records();
+        assert_eq!(
+            records.iter().map(|record| record.seq).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.elapsed_us)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+
+        let tracer = RingBufferTracer::new(2);
+        let other_stream = StreamIdentity::new("live", "other").unwrap();
+        for index in 0..3 {
+            tracer.record(
+                &stream_id(),
+                TraceEvent::frame_received(PublishProtocol::RTMP, &video_frame(index)),
+            );
+        }
+        tracer.record(
+            &other_stream,
+            TraceEvent::frame_received(PublishProtocol::SRT, &audio_frame(0)),
+        );
+        assert_eq!(tracer.snapshot(&stream_id()).unwrap().len(), 2);
+        assert_eq!(tracer.snapshot(&stream_id()).unwrap()[1].seq, 2);
+
+        let json = serde_json::to_value(tracer.snapshot(&other_stream).unwrap()).unwrap();
+        assert_eq!(json[0]["seq"], 0);
+        assert_eq!(json[0]["event"], "frame_received");
+        assert_eq!(json[0]["protocol"], "SRT");
+        assert_eq!(json[0]["kind"], "audio");
+        assert_eq!(json[0]["dts_ms"], FRAME_INTERVAL_MS / 2);
+        assert_eq!(json[0]["size"], 4);
+        tracer.remove(&other_stream);
+        assert!(tracer.snapshot(&other_stream).is_none());
+    }
+
+    #[test]
+    fn disabled_trace_handle_builds_no_events() {
+        let handle = TraceHandle::disabled();
+        assert!(!handle.is_enabled());
+        handle.record(&stream_id(), || {
+            unreachable!("events must not be built when tracing is off")
+        });
+        assert!(handle.snapshot(&stream_id()).is_none());
+    }
+
+    #[tokio::test]
+    async fn trace_records_pipeline_events() {
+        let mut stream_center = StreamCenter::with_tracer(Arc::new(RingBufferTracer::new(16)));
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+        drain(&mut response.media_receiver).await;
+
+        let records = StreamCenter::trace(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        // the ring wrapped, only the latest 16 events are kept
+        assert_eq!(records.len(), 16);
+        assert!(
+            records
+                .windows(2)
+                .all(|pair| pair[0].seq + 1 == pair[1].seq)
+        );
+        assert!(
+            records
+                .windows(2)
+                .all(|pair| pair[0].elapsed_us <= pair[1].elapsed_us)
+        );
+        assert!(records.iter().any(|record| matches!(
+            record.event,
+            TraceEvent::SentToSubscriber { subscriber, success: true, .. }
+                if subscriber == response.subscribe_id
+        )));
+        assert!(
+            records
+                .iter()
+                .any(|record| matches!(record.event, TraceEvent::MixQueueEnqueued { .. }))
+        );
+
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert!(matches!(
+            StreamCenter::trace(&event_sender, &stream_id()).await,
+            Err(StreamCenterError::StreamNotFound(_))
+        ));
+
+        let event_sender = start_stream_center();
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            StreamCenter::trace(&event_sender, &stream_id()).await,
+            Err(StreamCenterError::TracingDisabled)
+        ));
+    }
+
+    fn start_stream_center_with_takeover(
+        policy: TakeoverPolicy,
+    ) -> mpsc::UnboundedSender<StreamCenterEvent> {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_takeover_policy(&stream_id().app, policy);
+        let sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        sender
+    }
+
+    async fn stream_exists(event_sender: &mpsc::UnboundedSender<StreamCenterEvent>) -> bool {
+        StreamCenter::describe(event_sender, &stream_id())
+            .await
+            .is_ok()
+    }
+
+    #[test]
+    fn parse_takeover_policy() {
+        assert_eq!(
+            "reject".parse::<TakeoverPolicy>().unwrap(),
+            TakeoverPolicy::Reject
+        );
+        assert_eq!(
+            "kick_old".parse::<TakeoverPolicy>().unwrap(),
+            TakeoverPolicy::KickOld
+        );
+        assert_eq!(
+            "takeover_if_idle:5".parse::<TakeoverPolicy>().unwrap(),
+            TakeoverPolicy::TakeoverIfIdle(Duration::from_secs(5))
+        );
+        assert!(matches!(
+            "takeover_if_idle:soon".parse::<TakeoverPolicy>(),
+            Err(StreamCenterError::InvalidTakeoverPolicy(_))
+        ));
+        assert!("kick".parse::<TakeoverPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn scale_reaches_recordings_and_live_streams_stay_at_normal() {
+        let event_sender = start_stream_center_with_takeover(TakeoverPolicy::Reject);
+        assert!(matches!(
+            StreamCenter::set_scale(&event_sender, &stream_id(), 2.0).await,
+            Err(StreamCenterError::StreamNotFound(_))
+        ));
+
+        let live = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            StreamCenter::set_scale(&event_sender, &stream_id(), 2.0)
+                .await
+                .unwrap(),
+            1.0
+        );
+        drop(live);
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+
+        let mut recording = StreamCenter::publish_recording(
+            &event_sender,
+            PublishProtocol::VOD,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            StreamCenter::set_scale(&event_sender, &stream_id(), -2.0)
+                .await
+                .unwrap(),
+            -2.0
+        );
+        assert_eq!(recording.scale_receiver.try_recv().unwrap(), -2.0);
+        for invalid in [0.0, 1e-300, -128.0] {
+            assert!(matches!(
+                StreamCenter::set_scale(&event_sender, &stream_id(), invalid).await,
+                Err(StreamCenterError::InvalidScale(_))
+            ));
+        }
+        assert!(recording.scale_receiver.try_recv().is_err());
+
+        // the recording stopped listening
+        drop(recording.scale_receiver);
+        assert_eq!(
+            StreamCenter::set_scale(&event_sender, &stream_id(), 2.0)
+                .await
+                .unwrap(),
+            1.0
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_policy_keeps_the_first_publisher() {
+        let event_sender = start_stream_center_with_takeover(TakeoverPolicy::Reject);
+        let mut first = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let second = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await;
+        assert!(matches!(second, Err(StreamCenterError::DuplicateStream(_))));
+        assert!(first.kicked_receiver.try_recv().is_err());
+        assert!(!first.media_sender.is_closed());
+    }
+
+    #[tokio::test]
+    async fn kick_old_policy_hands_the_stream_to_the_new_publisher() {
+        let event_sender = start_stream_center_with_takeover(TakeoverPolicy::KickOld);
+        let first = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let second = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+
+        let kicked = first.kicked_receiver.await.unwrap();
+        assert_eq!(kicked.reason, KickReason::Takeover(TakeoverPolicy::KickOld));
+        // the old stream source is stopped
+        tokio::time::timeout(Duration::from_secs(1), first.media_sender.closed())
+            .await
+            .unwrap();
+
+        // the late unpublish of the kicked publisher must not remove the new stream
+        StreamCenter::unpublish_publisher(&event_sender, &stream_id(), first.publisher_id)
+            .await
+            .unwrap();
+        assert!(stream_exists(&event_sender).await);
+
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        second.media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&second.media_sender, 0..GOP_SIZE).await;
+        assert!(!drain(&mut response.media_receiver).await.is_empty());
+
+        StreamCenter::unpublish_publisher(&event_sender, &stream_id(), second.publisher_id)
+            .await
+            .unwrap();
+        assert!(!stream_exists(&event_sender).await);
+    }
+
+    #[tokio::test]
+    async fn notifications_follow_the_lifecycle_of_streams() {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_takeover_policy(&stream_id().app, TakeoverPolicy::KickOld);
+        let mut notifications = stream_center.subscribe_notifications();
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+
+        let _first = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::Embedded,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let _second = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            notifications.recv().await.unwrap(),
+            StreamNotification::Published {
+                protocol: PublishProtocol::Embedded,
+                ..
+            }
+        ));
+        assert!(matches!(
+            notifications.recv().await.unwrap(),
+            StreamNotification::Kicked {
+                reason: KickReason::Takeover(TakeoverPolicy::KickOld),
+                ..
+            }
+        ));
+        assert!(matches!(
+            notifications.recv().await.unwrap(),
+            StreamNotification::Published {
+                protocol: PublishProtocol::RTMP,
+                ..
+            }
+        ));
+        let unpublished = notifications.recv().await.unwrap();
+        assert!(matches!(
+            unpublished,
+            StreamNotification::Unpublished { .. }
+        ));
+        assert_eq!(unpublished.stream_id(), &stream_id());
+    }
+
+    #[tokio::test]
+    async fn kick_old_policy_rejects_if_the_old_publisher_is_not_kickable() {
+        let event_sender = start_stream_center_with_takeover(TakeoverPolicy::KickOld);
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTSP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let second = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await;
+        assert!(matches!(second, Err(StreamCenterError::DuplicateStream(_))));
+    }
+
+    #[tokio::test]
+    async fn takeover_if_idle_policy_waits_for_the_old_publisher_to_go_quiet() {
+        let idle = Duration::from_millis(200);
+        let event_sender = start_stream_center_with_takeover(TakeoverPolicy::TakeoverIfIdle(idle));
+        let mut first = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        first.media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&first.media_sender, 0..2).await;
+
+        // the first publisher is still active
+        let second = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await;
+        assert!(matches!(second, Err(StreamCenterError::DuplicateStream(_))));
+        assert!(first.kicked_receiver.try_recv().is_err());
+
+        tokio::time::sleep(idle + Duration::from_millis(100)).await;
+        let second = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let kicked = first.kicked_receiver.await.unwrap();
+        assert!(kicked.idle >= idle);
+        assert!(matches!(
+            kicked.reason,
+            KickReason::Takeover(TakeoverPolicy::TakeoverIfIdle(_))
+        ));
+
+        StreamCenter::unpublish_publisher(&event_sender, &stream_id(), first.publisher_id)
+            .await
+            .unwrap();
+        assert!(stream_exists(&event_sender).await);
+        StreamCenter::unpublish_publisher(&event_sender, &stream_id(), second.publisher_id)
+            .await
+            .unwrap();
+        assert!(!stream_exists(&event_sender).await);
+    }
+
+    #[test]
+    fn parse_idle_watchdog() {
+        assert_eq!("off".parse::<IdleWatchdog>().unwrap(), IdleWatchdog::Off);
+        assert_eq!(
+            "10:30".parse::<IdleWatchdog>().unwrap(),
+            IdleWatchdog::On {
+                stall_after: Duration::from_secs(10),
+                reap_after: Duration::from_secs(30)
+            }
+        );
+        assert!(matches!(
+            "30:10".parse::<IdleWatchdog>(),
+            Err(StreamCenterError::InvalidIdleWatchdog(_))
+        ));
+        assert!("0:10".parse::<IdleWatchdog>().is_err());
+        assert!("10".parse::<IdleWatchdog>().is_err());
+    }
+
+    const STALL_AFTER: Duration = Duration::from_secs(10);
+    const REAP_AFTER: Duration = Duration::from_secs(30);
+
+    fn start_stream_center_with_watchdog() -> mpsc::UnboundedSender<StreamCenterEvent> {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_idle_watchdog(
+            &stream_id().app,
+            IdleWatchdog::On {
+                stall_after: STALL_AFTER,
+                reap_after: REAP_AFTER,
+            },
+        );
+        let sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        sender
+    }
+
+    async fn is_stalled(event_sender: &mpsc::UnboundedSender<StreamCenterEvent>) -> bool {
+        StreamCenter::describe(event_sender, &stream_id())
+            .await
+            .unwrap()
+            .stalled
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_watchdog_stalls_then_reaps_a_silent_publisher() {
+        let event_sender = start_stream_center_with_watchdog();
+        let mut publisher = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        publisher.media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&publisher.media_sender, 0..GOP_SIZE).await;
+        drain(&mut response.media_receiver).await;
+        assert!(!is_stalled(&event_sender).await);
+
+        tokio::time::sleep(STALL_AFTER).await;
+        assert!(is_stalled(&event_sender).await);
+        // the frames held back by the mix queue go out before the end of stream
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(frames.len() > 1);
+        assert!(matches!(
+            frames.last(),
+            Some(MediaFrame::EndOfStream { timestamp_nano, reason: EndOfStreamReason::Stalled })
+                if *timestamp_nano == audio_frame(GOP_SIZE - 1).get_decode_timestamp_ns()
+        ));
+
+        // data resuming between the two thresholds clears the stall and restarts the clock
+        send_av_frames(&publisher.media_sender, GOP_SIZE..2 * GOP_SIZE).await;
+        assert!(!is_stalled(&event_sender).await);
+        assert!(!drain(&mut response.media_receiver).await.is_empty());
+        tokio::time::sleep(REAP_AFTER - Duration::from_secs(5)).await;
+        assert!(stream_exists(&event_sender).await);
+        assert!(is_stalled(&event_sender).await);
+        assert!(publisher.kicked_receiver.try_recv().is_err());
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let kicked = publisher.kicked_receiver.await.unwrap();
+        assert_eq!(kicked.reason, KickReason::IdleTimeout);
+        assert!(kicked.idle >= REAP_AFTER);
+        assert!(!stream_exists(&event_sender).await);
+        tokio::time::timeout(Duration::from_secs(1), publisher.media_sender.closed())
+            .await
+            .unwrap();
+        // the subscriber is told the stream is reaped, then the stream is gone
+        let mut last_frame = None;
+        while let Some(frame) = response.media_receiver.recv().await {
+            last_frame = Some(frame);
+        }
+        assert!(matches!(
+            last_frame,
+            Some(MediaFrame::EndOfStream {
+                reason: EndOfStreamReason::Kicked(KickReason::IdleTimeout),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_watchdog_reports_stall_resume_and_reap_of_the_source() {
+        let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+        let (frame_sender, frame_receiver) = mpsc::channel(128);
+        let (signal_sender, signal_receiver) = mpsc::unbounded_channel();
+        let mut source = StreamSource::new(
+            stream_id(),
+            PublishProtocol::RTMP,
+            frame_receiver,
+            signal_receiver,
+            event_sender,
+            TraceHandle::disabled(),
+            None,
+        )
+        .with_idle_watchdog(IdleWatchdog::On {
+            stall_after: STALL_AFTER,
+            reap_after: REAP_AFTER,
+        });
+        let source_id = source.source_id;
+        tokio::spawn(async move { source.run().await });
+
+        frame_sender.send(video_config()).await.unwrap();
+        send_av_frames(&frame_sender, 0..GOP_SIZE).await;
+        assert!(matches!(
+            event_receiver.recv().await,
+            Some(StreamCenterEvent::PublisherStalled { idle, .. }) if idle >= STALL_AFTER
+        ));
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        send_av_frames(&frame_sender, GOP_SIZE..GOP_SIZE + 1).await;
+        assert!(matches!(
+            event_receiver.recv().await,
+            Some(StreamCenterEvent::PublisherResumed { stalled_for, .. })
+                if stalled_for >= Duration::from_secs(5)
+        ));
+
+        // a sequence header alone does not count as data
+        frame_sender.send(video_config()).await.unwrap();
+        assert!(matches!(
+            event_receiver.recv().await,
+            Some(StreamCenterEvent::PublisherStalled { .. })
+        ));
+        assert!(matches!(
+            event_receiver.recv().await,
+            Some(StreamCenterEvent::ReapIdleStream { source_id: id, idle, .. })
+                if id == source_id && idle >= REAP_AFTER
+        ));
+        // nothing more until the stream center reaps the source
+        tokio::time::sleep(REAP_AFTER).await;
+        assert!(event_receiver.try_recv().is_err());
+        signal_sender
+            .send(StreamSignal::Stop {
+                reason: EndOfStreamReason::Kicked(KickReason::IdleTimeout),
+            })
+            .unwrap();
+        frame_sender.closed().await;
+    }
+
+    #[test]
+    fn parse_recovery_point_join() {
+        assert_eq!(
+            "off".parse::<RecoveryPointJoin>().unwrap(),
+            RecoveryPointJoin::Off
+        );
+        assert_eq!(
+            "30".parse::<RecoveryPointJoin>().unwrap(),
+            RecoveryPointJoin::On { group_frames: 30 }
+        );
+        assert!(matches!(
+            "0".parse::<RecoveryPointJoin>(),
+            Err(StreamCenterError::InvalidRecoveryPointJoin(_))
+        ));
+        assert!("on".parse::<RecoveryPointJoin>().is_err());
+    }
+
+    /// a recovery point sei, recovery_frame_cnt = 9, exact_match_flag = 1
+    const RECOVERY_POINT_SEI: [u8; 5] = [6, 2, 0b0001_0101, 0b0001_0000, 0x80];
+
+    /// an intra refresh stream, no IDR at all, every recovery_point_interval-th frame carries a recovery point
+    fn intra_refresh_frame(index: u64, recovery_point_interval: u64) -> MediaFrame {
+        let mut nal_units = vec![];
+        if index.is_multiple_of(recovery_point_interval) {
+            nal_units.push(NalUnit {
+                header: NaluHeader::try_from(0x06).unwrap(),
+                body: Bytes::from_static(&RECOVERY_POINT_SEI),
+            });
+        }
+        nal_units.push(NalUnit {
+            header: NaluHeader::try_from(0x41).unwrap(),
+            body: Bytes::from_static(&[0; 4]),
+        });
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                FrameType::CodedFrames,
+                MediaFrameTimestamp::with_timestamp_ms(index * FRAME_INTERVAL_MS),
+            ),
+            payload: VideoFrameUnit::H264 { nal_units },
+        }
+    }
+
+    async fn send_intra_refresh_frames(
+        sender: &mpsc::Sender<MediaFrame>,
+        indexes: std::ops::Range<u64>,
+        recovery_point_interval: u64,
+    ) {
+        for index in indexes {
+            sender
+                .send(intra_refresh_frame(index, recovery_point_interval))
+                .await
+                .unwrap();
+            sender.send(audio_frame(index)).await.unwrap();
+        }
+    }
+
+    /// publishes an intra refresh stream and subscribes to it after `published_frames` frames,
+    /// the video frames the subscriber gets are returned
+    async fn late_subscriber_of_intra_refresh_stream(
+        join: RecoveryPointJoin,
+        recovery_point_interval: u64,
+        published_frames: u64,
+    ) -> Vec<MediaFrame> {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_recovery_point_join(&stream_id().app, join);
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_intra_refresh_frames(&media_sender, 0..published_frames, recovery_point_interval)
+            .await;
+        // the subscription would go before the frames still queued
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::video_only(),
+        )
+        .await
+        .unwrap();
+        send_intra_refresh_frames(
+            &media_sender,
+            published_frames..published_frames * 2,
+            recovery_point_interval,
+        )
+        .await;
+        drain(&mut response.media_receiver)
+            .await
+            .into_iter()
+            .filter(|frame| frame.is_video() && !frame.is_sequence_header())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn late_subscriber_of_intra_refresh_stream_starts_at_recovery_point() {
+        let frames = late_subscriber_of_intra_refresh_stream(
+            RecoveryPointJoin::On {
+                group_frames: GOP_SIZE as u32,
+            },
+            GOP_SIZE,
+            25,
+        )
+        .await;
+        // the gop cache starts at the last recovery point before the subscription
+        let first = frames.first().expect("no video frame delivered");
+        assert!(first.is_video_key_frame());
+        assert_eq!(first.get_decode_timestamp_ms(), 20 * FRAME_INTERVAL_MS);
+        assert!(matches!(
+            first,
+            MediaFrame::Video { frame_info, .. } if frame_info.recovery_frame_cnt == Some(9)
+        ));
+
+        let key_frames: Vec<_> = frames
+            .iter()
+            .filter(|frame| frame.is_video_key_frame())
+            .map(|frame| frame.get_decode_timestamp_ms() / FRAME_INTERVAL_MS)
+            .collect();
+        assert_eq!(key_frames[..3], [20, 30, 40]);
+
+        // players begin decoding at flv key frames, the frame type follows the 11 bytes of tag header
+        let mut bytes = Vec::new();
+        first.to_flv_tag(4).unwrap().write_to(&mut bytes).unwrap();
+        assert_eq!(bytes[11] >> 4, 1);
+    }
+
+    #[tokio::test]
+    async fn recovery_points_inside_a_group_are_not_promoted() {
+        // a recovery point on every frame, as some encoders send
+        let frames = late_subscriber_of_intra_refresh_stream(
+            RecoveryPointJoin::On { group_frames: 8 },
+            1,
+            20,
+        )
+        .await;
+        assert_eq!(
+            frames.first().map(|frame| frame.get_decode_timestamp_ms()),
+            Some(16 * FRAME_INTERVAL_MS)
+        );
+        let key_frames: Vec<_> = frames
+            .iter()
+            .filter(|frame| frame.is_video_key_frame())
+            .map(|frame| frame.get_decode_timestamp_ms() / FRAME_INTERVAL_MS)
+            .collect();
+        assert_eq!(key_frames[..3], [16, 24, 32]);
+    }
+
+    #[tokio::test]
+    async fn recovery_points_are_only_noted_if_join_is_off() {
+        let frames =
+            late_subscriber_of_intra_refresh_stream(RecoveryPointJoin::Off, GOP_SIZE, 25).await;
+        assert!(!frames.is_empty());
+        assert!(frames.iter().all(|frame| !frame.is_video_key_frame()));
+        let recovery_points: Vec<_> = frames
+            .iter()
+            .filter(|frame| {
+                matches!(
+                    frame,
+                    MediaFrame::Video { frame_info, .. } if frame_info.recovery_frame_cnt.is_some()
+                )
+            })
+            .map(|frame| frame.get_decode_timestamp_ms() / FRAME_INTERVAL_MS)
+            .collect();
+        assert_eq!(recovery_points[..2], [30, 40]);
+    }
+
+    fn cue_point_tag(timestamp_ms: u32) -> FLVTag {
+        let value = vec![
+            amf0::string("@setDataFrame"),
+            amf0::string("onCuePoint"),
+            amf0::object(
+                [
+                    ("name", amf0::string("ad-break")),
+                    ("time", amf0::number(timestamp_ms as f64 / 1000.0)),
+                ]
+                .into_iter(),
+            ),
+        ];
+        let mut bytes = Vec::new();
+        value.iter().for_each(|v| v.write_to(&mut bytes).unwrap());
+        FLVTag {
+            tag_header: FLVTagHeader {
+                tag_type: FLVTagType::Script,
+                data_size: bytes.len() as u32,
+                timestamp: timestamp_ms,
+                filter_enabled: false,
+            },
+            body_with_filter: FLVTagBodyWithFilter {
+                filter: None,
+                body: FLVTagBody::Script { value },
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn published_cue_point_is_recorded_in_timestamp_order() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+
+        // right between the video frame and the audio frame of index GOP_SIZE + 2
+        let cue_timestamp_ms = ((GOP_SIZE + 2) * FRAME_INTERVAL_MS + 10) as u32;
+        let cue_point = MediaFrame::from_flv_tag(cue_point_tag(cue_timestamp_ms), 4).unwrap();
+        match &cue_point {
+            MediaFrame::Data { name, .. } => assert_eq!(name, "onCuePoint"),
+            _ => panic!("cue point is not a data frame: {:?}", cue_point),
+        }
+        send_av_frames(&media_sender, GOP_SIZE..GOP_SIZE + 2).await;
+        media_sender.send(video_frame(GOP_SIZE + 2)).await.unwrap();
+        media_sender.send(cue_point).await.unwrap();
+        media_sender.send(audio_frame(GOP_SIZE + 2)).await.unwrap();
+        send_av_frames(&media_sender, GOP_SIZE + 3..GOP_SIZE * 2).await;
+
+        // the fake video config has no decoder configuration record to remux
+        let mut recorded = Vec::new();
+        FLVHeader::new(true, true).write_to(&mut recorded).unwrap();
+        recorded.extend_from_slice(&0_u32.to_be_bytes());
+        for frame in drain(&mut response.media_receiver)
+            .await
+            .into_iter()
+            .filter(|frame| !frame.is_sequence_header() && !frame.is_script())
+        {
+            let tag = frame.to_flv_tag(4).unwrap();
+            tag.write_to(&mut recorded).unwrap();
+            recorded.extend_from_slice(&(tag.tag_header.data_size + 11).to_be_bytes());
+        }
+
+        let mut reader = Cursor::new(&recorded);
+        FLVHeader::read_from(&mut reader).unwrap();
+        reader.get_u32();
+        let mut tags = vec![];
+        while reader.has_remaining() {
+            tags.push(FLVTag::read_from(&mut reader).unwrap());
+            reader.get_u32();
+        }
+        assert!(
+            tags.windows(2)
+                .all(|pair| pair[0].tag_header.timestamp <= pair[1].tag_header.timestamp),
+            "recorded tags are out of order"
+        );
+        let cue_tags: Vec<_> = tags
+            .iter()
+            .filter(|tag| tag.tag_header.tag_type == FLVTagType::Script)
+            .collect();
+        assert_eq!(cue_tags.len(), 1);
+        assert_eq!(cue_tags[0].tag_header.timestamp, cue_timestamp_ms);
+        match &cue_tags[0].body_with_filter.body {
+            FLVTagBody::Script { value } => match cue_point_tag(cue_timestamp_ms)
+                .body_with_filter
+                .body
+            {
+                // the @setDataFrame prefix is for rtmp only
+                FLVTagBody::Script { value: published } => assert_eq!(value[..], published[1..]),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// onMetaData the way rtmp publishers send it, wrapped in @setDataFrame
+    fn set_data_frame_meta(timestamp_ms: u32, fields: Vec<(&str, amf0::Value)>) -> MediaFrame {
+        let value = vec![
+            amf0::string("@setDataFrame"),
+            amf0::string("onMetaData"),
+            amf0::Value::ECMAArray(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key.to_owned(), value))
+                    .collect(),
+            ),
+        ];
+        let mut bytes = Vec::new();
+        value.iter().for_each(|v| v.write_to(&mut bytes).unwrap());
+        let tag = FLVTag {
+            tag_header: FLVTagHeader {
+                tag_type: FLVTagType::Script,
+                data_size: bytes.len() as u32,
+                timestamp: timestamp_ms,
+                filter_enabled: false,
+            },
+            body_with_filter: FLVTagBodyWithFilter {
+                filter: None,
+                body: FLVTagBody::Script { value },
+            },
+        };
+        MediaFrame::from_flv_tag(tag, 4).unwrap()
+    }
+
+    fn meta_data_of(frame: &MediaFrame) -> &OnMetaData {
+        match frame {
+            MediaFrame::Script { on_meta_data, .. } => on_meta_data.as_ref().as_ref().unwrap(),
+            _ => panic!("not a script frame: {:?}", frame),
+        }
+    }
+
+    #[tokio::test]
+    async fn metadata_updates_of_the_publisher_are_merged() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender
+            .send(set_data_frame_meta(
+                0,
+                vec![
+                    ("width", amf0::number(1280)),
+                    ("height", amf0::number(720)),
+                    (
+                        "encoder",
+                        amf0::string("obs-output module (libobs version 29.1.0)"),
+                    ),
+                    ("fileName", amf0::string("live.flv")),
+                ],
+            ))
+            .await
+            .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+
+        let mut early = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        // obs sends the updates at timestamp 0
+        send_av_frames(&media_sender, GOP_SIZE..GOP_SIZE * 2).await;
+        media_sender
+            .send(set_data_frame_meta(
+                0,
+                vec![
+                    ("height", amf0::number(1080)),
+                    ("videodatarate", amf0::number(6000)),
+                    (
+                        "encoder",
+                        amf0::string("obs-output module (libobs version 30.0.0)"),
+                    ),
+                ],
+            ))
+            .await
+            .unwrap();
+        send_av_frames(&media_sender, GOP_SIZE * 2..GOP_SIZE * 3).await;
+
+        let frames = drain(&mut early.media_receiver).await;
+        assert_eq!(meta_data_of(&frames[0]).height, Some(720.0));
+        let update_position = frames
+            .iter()
+            .rposition(|frame| matches!(frame, MediaFrame::Script { .. }))
+            .filter(|position| *position > 0)
+            .expect("metadata update is not delivered");
+        let update = &frames[update_position];
+        assert_eq!(meta_data_of(update).height, Some(1080.0));
+        assert!(
+            frames[..update_position]
+                .iter()
+                .filter(|frame| frame.is_video() || frame.is_audio())
+                .all(|frame| frame.get_decode_timestamp_ns() <= update.get_decode_timestamp_ns()),
+            "metadata update goes back in time"
+        );
+        assert!(update.get_decode_timestamp_ns() > 0);
+
+        let mut late = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        send_av_frames(&media_sender, GOP_SIZE * 3..GOP_SIZE * 4).await;
+        let frames = drain(&mut late.media_receiver).await;
+        let merged = meta_data_of(&frames[0]);
+        assert_eq!(merged.width, Some(1280.0));
+        assert_eq!(merged.height, Some(1080.0));
+        assert_eq!(merged.video_data_rate, Some(6000.0));
+        assert_eq!(
+            merged.extra_fields["encoder"].try_as_str(),
+            Some("obs-output module (libobs version 30.0.0)")
+        );
+        assert_eq!(
+            merged.extra_fields["fileName"].try_as_str(),
+            Some("live.flv")
+        );
+
+        // the tag sent to flv players carries the nonstandard fields too
+        let tag = frames[0].to_flv_tag(4).unwrap();
+        match tag.body_with_filter.body {
+            FLVTagBody::Script { value } => {
+                match &value[1] {
+                    amf0::Value::ECMAArray(entries) => {
+                        assert!(entries.iter().any(|(key, value)| key == "fileName"
+                            && value.try_as_str() == Some("live.flv")));
+                    }
+                    value => panic!("unexpected metadata: {:?}", value),
+                }
+            }
+            body => panic!("unexpected tag body: {:?}", body),
+        }
+
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        let meta_data = description.meta_data.unwrap();
+        assert_eq!(meta_data.width, Some(1280.0));
+        assert_eq!(meta_data.height, Some(1080.0));
+    }
+
+    #[tokio::test]
+    async fn oversized_data_frame_is_dropped() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+
+        send_av_frames(&media_sender, 0..2).await;
+        media_sender
+            .send(MediaFrame::Data {
+                timestamp_nano: FRAME_INTERVAL_MS * 2 * 1_000_000,
+                name: "onTextData".to_owned(),
+                payload: Bytes::from(vec![0; MAX_DATA_FRAME_BYTES + 1]),
+            })
+            .await
+            .unwrap();
+        send_av_frames(&media_sender, 2..GOP_SIZE).await;
+
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(frames.iter().any(|frame| frame.is_video()));
+        assert!(frames.iter().all(|frame| !frame.is_data()));
+    }
+
+    /// the slow stream keeps replaying a large gop cache to subscribers which never read,
+    /// none of it should hold back the frames of the other stream
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn slow_stream_does_not_delay_other_streams() {
+        const SLOW_GOPS: u64 = 50;
+        let event_sender = start_stream_center();
+        let slow_stream = StreamIdentity::new("live", "slow").unwrap();
+        let fast_stream = StreamIdentity::new("live", "fast").unwrap();
+
+        let slow_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &slow_stream,
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        slow_sender.send(video_config()).await.unwrap();
+        send_av_frames(&slow_sender, 0..GOP_SIZE * SLOW_GOPS).await;
+        let slow_subscribers = tokio::spawn({
+            let event_sender = event_sender.clone();
+            async move {
+                let context = HashMap::from([("backtraceGopCnt".to_owned(), "1000".to_owned())]);
+                let mut subscribers = vec![];
+                for index in SLOW_GOPS..SLOW_GOPS * 2 {
+                    subscribers.push(
+                        StreamCenter::subscribe(
+                            &event_sender,
+                            PlayProtocol::RTMP,
+                            &slow_stream,
+                            &context,
+                            MediaSelection::default(),
+                        )
+                        .await
+                        .unwrap(),
+                    );
+                    send_av_frames(&slow_sender, GOP_SIZE * index..GOP_SIZE * (index + 1)).await;
+                }
+                subscribers
+            }
+        });
+
+        let fast_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &fast_stream,
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        fast_sender.send(video_config()).await.unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &fast_stream,
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut max_latency = Duration::ZERO;
+        for index in 0..GOP_SIZE * 10 {
+            let start = tokio::time::Instant::now();
+            // the video frame leaves the mix queue once the audio frame after it arrives
+            send_av_frames(&fast_sender, index..index + 1).await;
+            loop {
+                let frame =
+                    tokio::time::timeout(Duration::from_secs(1), response.media_receiver.recv())
+                        .await
+                        .expect("the fast stream stalled")
+                        .unwrap();
+                if frame.is_video()
+                    && !frame.is_sequence_header()
+                    && frame.get_decode_timestamp_ms() == index * FRAME_INTERVAL_MS
+                {
+                    break;
+                }
+            }
+            max_latency = max_latency.max(start.elapsed());
+        }
+
+        let slow_subscribers = slow_subscribers.await.unwrap();
+        assert_eq!(slow_subscribers.len() as u64, SLOW_GOPS);
+        assert!(
+            max_latency < Duration::from_millis(100),
+            "frame latency of the fast stream: {:?}",
+            max_latency
+        );
+    }
+
+    #[test]
+    fn latency_histogram_bucket_math() {
+        let ms = Duration::from_millis;
+        assert_eq!(LatencyHistogram::bucket_of(Duration::ZERO), 0);
+        assert_eq!(LatencyHistogram::bucket_of(ms(1)), 0);
+        assert_eq!(LatencyHistogram::bucket_of(Duration::from_micros(1001)), 1);
+        assert_eq!(LatencyHistogram::bucket_of(ms(150)), 7);
+        assert_eq!(LatencyHistogram::bucket_of(ms(10_001)), 13);
+
+        let origin = std::time::Instant::now();
+        let mut histogram = LatencyHistogram::new(origin, Duration::from_secs(10));
+        assert_eq!(histogram.summary(origin), LatencySummary::default());
+        for _ in 0..90 {
+            histogram.record(origin, ms(3));
+        }
+        for _ in 0..10 {
+            histogram.record(origin, ms(180));
+        }
+        // percentiles report the upper bound of their bucket, capped by the max
+        assert_eq!(
+            histogram.summary(origin),
+            LatencySummary {
+                count: 100,
+                p50_ms: 5,
+                p95_ms: 180,
+                max_ms: 180,
+            }
+        );
+
+        let later = origin + Duration::from_secs(5);
+        histogram.record(later, ms(12_000));
+        let summary = histogram.summary(later);
+        assert_eq!(summary.count, 101);
+        assert_eq!(summary.max_ms, 12_000);
+
+        // the first slot slides out of the window, the later one stays
+        let summary = histogram.summary(origin + Duration::from_secs(12));
+        assert_eq!(
+            summary,
+            LatencySummary {
+                count: 1,
+                p50_ms: 12_000,
+                p95_ms: 12_000,
+                max_ms: 12_000,
+            }
+        );
+        assert_eq!(
+            histogram.summary(origin + Duration::from_secs(30)),
+            LatencySummary::default()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn latency_is_measured_from_ingest_to_sink() {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_latency_measurement(LatencyConfig::default());
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+        let frames = drain(&mut response.media_receiver).await;
+        let stamped: Vec<_> = frames
+            .iter()
+            .filter(|frame| !frame.is_sequence_header())
+            .map(|frame| frame.get_ingest_time())
+            .collect();
+        assert!(!stamped.is_empty());
+        assert!(stamped.iter().all(|time| time.is_some()));
+
+        tokio::time::advance(Duration::from_millis(30)).await;
+        let probe = response.latency_probe.as_ref().unwrap();
+        for frame in &frames {
+            probe.on_frame_sent(frame.get_ingest_time());
+        }
+
+        let latency = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap()
+            .latency
+            .unwrap();
+        assert_eq!(latency.count, stamped.len() as u64);
+        assert!(latency.max_ms >= 30);
+        assert!(latency.p50_ms <= latency.p95_ms && latency.p95_ms <= latency.max_ms);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn disabled_latency_measurement_stamps_nothing() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        assert!(response.latency_probe.is_none());
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(!frames.is_empty());
+        assert!(frames.iter().all(|frame| frame.get_ingest_time().is_none()));
+        assert!(
+            StreamCenter::describe(&event_sender, &stream_id())
+                .await
+                .unwrap()
+                .latency
+                .is_none()
+        );
+    }
+
+    /// a muxer with a bug in it, flips the last payload byte of the flv tag
+    #[cfg(feature = "frame-crc")]
+    fn buggy_flv_mux(frame: &MediaFrame) -> Vec<u8> {
+        let mut bytes = frame.to_flv_tag_bytes(4).unwrap().to_vec();
+        // followed by the previous tag size
+        let at = bytes.len() - 5;
+        bytes[at] ^= 0xFF;
+        bytes
+    }
+
+    #[cfg(feature = "frame-crc")]
+    #[tokio::test(start_paused = true)]
+    async fn frame_crc_mismatch_is_attributed_to_the_stage_changing_the_payload() {
+        use crate::{frame_crc, metrics::StreamMetrics};
+
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_frame_crc_validation(true);
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+        let frames: Vec<_> = drain(&mut response.media_receiver)
+            .await
+            .into_iter()
+            .filter(|frame| (frame.is_video() || frame.is_audio()) && !frame.is_sequence_header())
+            .collect();
+        assert!(!frames.is_empty());
+        assert!(frames.iter().all(|frame| frame.get_payload_crc().is_some()));
+
+        for frame in &frames {
+            let tag = frame.to_flv_tag(4).unwrap();
+            assert!(frame_crc::verify_flv_tag(
+                "test_flv_mux",
+                &stream_id(),
+                frame,
+                &tag,
+                4
+            ));
+            assert!(!frame_crc::verify_flv_tag_bytes(
+                "test_buggy_flv_mux",
+                &stream_id(),
+                frame,
+                &buggy_flv_mux(frame),
+                4
+            ));
+        }
+        assert_eq!(StreamMetrics::frame_crc_mismatches("fanout").get(), 0);
+        assert_eq!(StreamMetrics::frame_crc_mismatches("test_flv_mux").get(), 0);
+        assert_eq!(
+            StreamMetrics::frame_crc_mismatches("test_buggy_flv_mux").get(),
+            frames.len() as u64
+        );
+    }
+
+    /// (dts, pts) in milliseconds of IBBP gops in decode order,
+    /// the P-frame is decoded ahead of the two B-frames displayed before it
+    const IBBP: [(u64, u64); 4] = [(0, 40), (40, 160), (80, 80), (120, 120)];
+    /// the same gop presented 40ms earlier, the B-frames have negative composition times
+    const IBBP_NEGATIVE_CTS: [(u64, u64); 4] = [(0, 0), (40, 120), (80, 40), (120, 80)];
+
+    fn video_frame_at(index: u64, dts_ms: u64, pts_ms: u64) -> MediaFrame {
+        let MediaFrame::Video {
+            frame_info,
+            payload,
+        } = video_frame(index)
+        else {
+            unreachable!()
+        };
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo {
+                timestamp: MediaFrameTimestamp::new(pts_ms * 1_000_000, dts_ms * 1_000_000),
+                ..frame_info
+            },
+            payload,
+        }
+    }
+
+    #[test]
+    fn composition_times_survive_flv_round_trip() {
+        for gop in [IBBP, IBBP_NEGATIVE_CTS] {
+            for (index, (dts_ms, pts_ms)) in gop.into_iter().enumerate() {
+                let frame = video_frame_at(index as u64, dts_ms, pts_ms);
+
+                let mut bytes = Vec::new();
+                frame.to_flv_tag(4).unwrap().write_to(&mut bytes).unwrap();
+                // the tag header, frame type and codec id, avc packet type, SI24 composition time
+                let cts = i32::from_be_bytes([bytes[13], bytes[14], bytes[15], 0]) >> 8;
+                assert_eq!(cts as i64, pts_ms as i64 - dts_ms as i64);
+
+                let tag = FLVTag::read_from(&mut Cursor::new(&bytes)).unwrap();
+                assert_eq!(tag.tag_header.timestamp as u64, dts_ms);
+                let parsed = MediaFrame::from_flv_tag(tag, 4).unwrap();
+                assert_eq!(parsed.get_decode_timestamp_ms(), dts_ms);
+                assert_eq!(parsed.get_presentation_timestamp_ms(), pts_ms);
+
+                let mut remuxed = Vec::new();
+                parsed
+                    .to_flv_tag(4)
+                    .unwrap()
+                    .write_to(&mut remuxed)
+                    .unwrap();
+                assert_eq!(remuxed, bytes);
+            }
+        }
+    }
+
+    fn write_enhanced(frame: &MediaFrame) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        frame
+            .to_flv_tag_with_video_header(4, FlvVideoHeader::Enhanced)
+            .unwrap()
+            .write_to(&mut bytes)
+            .unwrap();
+        // the tag header, then IsExHeader, the frame type and the packet type, then the FourCC
+        assert_eq!(bytes[11] & 0x80, 0x80);
+        bytes
+    }
+
+    #[test]
+    fn avc_frames_survive_enhanced_flv_round_trip() {
+        for gop in [IBBP, IBBP_NEGATIVE_CTS] {
+            for (index, (dts_ms, pts_ms)) in gop.into_iter().enumerate() {
+                let frame = video_frame_at(index as u64, dts_ms, pts_ms);
+                let bytes = write_enhanced(&frame);
+                assert_eq!(&bytes[12..16], b"avc1");
+                // the zero composition time is left out by CodedFramesX
+                let packet_type = bytes[11] & 0x0f;
+                if pts_ms == dts_ms {
+                    assert_eq!(packet_type, VideoPacketType::CodedFramesX as u8);
+                } else {
+                    assert_eq!(packet_type, VideoPacketType::CodedFrames as u8);
+                    let cts = i32::from_be_bytes([bytes[16], bytes[17], bytes[18], 0]) >> 8;
+                    assert_eq!(cts as i64, pts_ms as i64 - dts_ms as i64);
+                }
+
+                let tag = FLVTag::read_from(&mut Cursor::new(&bytes)).unwrap();
+                assert_eq!(tag.tag_header.timestamp as u64, dts_ms);
+                let parsed = MediaFrame::from_flv_tag(tag, 4).unwrap();
+                assert_eq!(parsed.get_decode_timestamp_ms(), dts_ms);
+                assert_eq!(parsed.get_presentation_timestamp_ms(), pts_ms);
+                assert_eq!(parsed.is_video_key_frame(), frame.is_video_key_frame());
+                // the same nal units as the legacy tag of the frame carries
+                assert_eq!(
+                    parsed.to_flv_tag_bytes(4).unwrap(),
+                    frame.to_flv_tag_bytes(4).unwrap()
+                );
+                assert_eq!(write_enhanced(&parsed), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn avc_sequence_start_survives_enhanced_flv_round_trip() {
+        let frame = video_config_with_record();
+        let bytes = write_enhanced(&frame);
+        assert_eq!(
+            bytes[11],
+            0x80 | (1 << 4) | VideoPacketType::SequenceStart as u8
+        );
+        assert_eq!(&bytes[12..16], b"avc1");
+
+        let tag = FLVTag::read_from(&mut Cursor::new(&bytes)).unwrap();
+        let parsed = MediaFrame::from_flv_tag(tag, 4).unwrap();
+        assert!(parsed.is_sequence_header());
+        assert_eq!(
+            parsed.to_flv_tag_bytes(4).unwrap(),
+            frame.to_flv_tag_bytes(4).unwrap()
+        );
+    }
+
+    #[test]
+    fn video_sequence_end_tags_have_no_body() {
+        for (codec_id, video_header) in [
+            (VideoCodecCommon::AVC, FlvVideoHeader::Legacy),
+            (VideoCodecCommon::AVC, FlvVideoHeader::Enhanced),
+            (VideoCodecCommon::AV1, FlvVideoHeader::Enhanced),
+        ] {
+            let tag = MediaFrame::video_sequence_end_flv_tag(codec_id, 120, video_header).unwrap();
+            let mut bytes = Vec::new();
+            tag.write_to(&mut bytes).unwrap();
+            // the tag header, then the video header of 5 bytes either way
+            assert_eq!(bytes.len(), 11 + 5);
+
+            let tag = FLVTag::read_from(&mut Cursor::new(&bytes)).unwrap();
+            let FLVTagBody::Video { header, body } = &tag.body_with_filter.body else {
+                panic!("expect a video tag, got: {:?}", tag);
+            };
+            assert!(body.is_empty());
+            let info = VideoTagHeaderWithoutMultiTrack::try_from(header).unwrap();
+            assert_eq!(info.packet_type, VideoPacketType::SequenceEnd);
+            assert_eq!(info.codec_id, codec_id);
+            assert_eq!(
+                info.is_enhanced_rtmp,
+                video_header == FlvVideoHeader::Enhanced
+            );
+            assert_eq!(tag.tag_header.timestamp, 120);
+        }
+    }
+
+    /// main profile, level 4.0, 1920x1080, 8 bits 4:2:0
+    const AV1_SEQUENCE_HEADER_1080P: [u8; 13] = [
+        0x0a, 0x0b, 0x00, 0x00, 0x00, 0x42, 0xab, 0xbf, 0xc3, 0x73, 0xff, 0xe6, 0x01,
+    ];
+
+    /// an enhanced rtmp video tag of the av01 FourCC
+    fn av1_tag_bytes(frame_and_packet_type: u8, timestamp_ms: u32, body: &[u8]) -> Vec<u8> {
+        let data_size = (5 + body.len()) as u32;
+        let mut bytes = vec![9];
+        bytes.extend_from_slice(&data_size.to_be_bytes()[1..]);
+        bytes.extend_from_slice(&timestamp_ms.to_be_bytes()[1..]);
+        bytes.extend_from_slice(&[(timestamp_ms >> 24) as u8, 0, 0, 0]);
+        bytes.push(0b1000_0000 | frame_and_packet_type);
+        bytes.extend_from_slice(b"av01");
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[test]
+    fn av1_sequence_start_reports_the_resolution() {
+        let mut record = vec![0x81, 0x08, 0x0c, 0x00];
+        record.extend_from_slice(&AV1_SEQUENCE_HEADER_1080P);
+        // key frame, sequence start
+        let bytes = av1_tag_bytes(0x10, 0, &record);
+
+        let tag = FLVTag::read_from(&mut Cursor::new(&bytes)).unwrap();
+        let frame = MediaFrame::from_flv_tag(tag, 4).unwrap();
+        let MediaFrame::VideoConfig { config, .. } = &frame else {
+            panic!("expect a video config, got: {:?}", frame);
+        };
+        assert_eq!(
+            VideoCodecCommon::from(config.as_ref()),
+            VideoCodecCommon::AV1
+        );
+        assert_eq!(config.dimensions(), Some((1920, 1080)));
+
+        // the record goes out unchanged
+        let mut remuxed = Vec::new();
+        frame.to_flv_tag(4).unwrap().write_to(&mut remuxed).unwrap();
+        assert_eq!(remuxed, bytes);
+    }
+
+    #[test]
+    fn av1_obus_survive_flv_round_trip() {
+        // temporal delimiter, sequence header, then a frame obu without a size field
+        let mut obus = vec![0x12, 0x00];
+        obus.extend_from_slice(&AV1_SEQUENCE_HEADER_1080P);
+        obus.extend_from_slice(&[0x30, 0xaa, 0xbb, 0xcc]);
+        // key frame, coded frames
+        let bytes = av1_tag_bytes(0x11, 40, &obus);
+
+        let tag = FLVTag::read_from(&mut Cursor::new(&bytes)).unwrap();
+        let frame = MediaFrame::from_flv_tag(tag, 4).unwrap();
+        assert!(frame.is_video_key_frame());
+        assert_eq!(frame.get_decode_timestamp_ms(), 40);
+        assert_eq!(frame.payload_bytes(), obus.len());
+        assert!(matches!(
+            &frame,
+            MediaFrame::Video {
+                payload: VideoFrameUnit::AV1 { obus: payload },
+                ..
+            } if payload.as_ref() == obus.as_slice()
+        ));
+
+        let mut remuxed = Vec::new();
+        frame.to_flv_tag(4).unwrap().write_to(&mut remuxed).unwrap();
+        assert_eq!(remuxed, bytes);
+    }
+
+    const FLV_TAG: SerializedFlavor = SerializedFlavor::FlvTag {
+        nalu_length_size: 4,
+    };
+
+    /// a video config that makes a flv sequence header, without any parameter set
+    fn video_config_with_record() -> MediaFrame {
+        let record = [0x01, 0x42, 0x00, 0x1f, 0xff, 0xe0, 0x00];
+        MediaFrame::VideoConfig {
+            timestamp_nano: 0,
+            config: Box::new(VideoConfig::H264(H264VideoConfig {
+                sps: None,
+                pps: None,
+                sps_ext: None,
+                avc_decoder_configuration_record: Some(
+                    AvcDecoderConfigurationRecord::read_from(&mut &record[..]).unwrap(),
+                ),
+            })),
+        }
+    }
+
+    /// what a flv viewer writes out for the frames, the tag timestamps it got along
+    fn serve_flv(
+        frames: &[MediaFrame],
+        serialized_frames: &SerializedFrameCache,
+        serialize_cnt: &std::sync::atomic::AtomicUsize,
+    ) -> (Vec<Bytes>, Vec<u32>) {
+        let mut timestamp_rebase = FlvTimestampRebase::default();
+        let mut pieces = vec![];
+        let mut timestamps = vec![];
+        for frame in frames {
+            let tag = serialized_frames
+                .get_or_serialize(frame, FLV_TAG, |frame| {
+                    serialize_cnt.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    frame.to_flv_tag_bytes(4)
+                })
+                .unwrap();
+            let (header, rest) = timestamp_rebase.rebase(frame, tag);
+            timestamps.push(u32::from_be_bytes([
+                header[7], header[4], header[5], header[6],
+            ]));
+            pieces.push(header);
+            pieces.extend(rest);
+        }
+        (pieces, timestamps)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn late_flv_viewer_gets_zero_based_timestamps_from_shared_tags() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let (stream_id, context) = (stream_id(), HashMap::new());
+        let subscribe = || {
+            StreamCenter::subscribe(
+                &event_sender,
+                PlayProtocol::HTTPFLV,
+                &stream_id,
+                &context,
+                MediaSelection::default(),
+            )
+        };
+        let mut early = subscribe().await.unwrap();
+        media_sender.send(video_config_with_record()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE * 2).await;
+        let early_frames = drain(&mut early.media_receiver).await;
+
+        let mut late = subscribe().await.unwrap();
+        send_av_frames(&media_sender, GOP_SIZE * 2..GOP_SIZE * 3).await;
+        let early_frames = [early_frames, drain(&mut early.media_receiver).await].concat();
+        let late_frames = drain(&mut late.media_receiver).await;
+
+        let serialize_cnt = std::sync::atomic::AtomicUsize::new(0);
+        let (_, early_timestamps) =
+            serve_flv(&early_frames, &early.serialized_frames, &serialize_cnt);
+        let early_serialize_cnt = serialize_cnt.swap(0, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(early_serialize_cnt, early_frames.len());
+        let (late_pieces, late_timestamps) =
+            serve_flv(&late_frames, &late.serialized_frames, &serialize_cnt);
+        // only the sequence header sent to the new viewer is serialized again
+        assert_eq!(
+            serialize_cnt.load(std::sync::atomic::Ordering::Relaxed),
+            late_frames
+                .iter()
+                .filter(|frame| frame.is_sequence_header())
+                .count()
+        );
+
+        // the early viewer joined at the start of the stream, nothing to rebase
+        assert!(
+            early_frames
+                .iter()
+                .zip(&early_timestamps)
+                .all(|(frame, timestamp)| frame.get_decode_timestamp_ms() == *timestamp as u64)
+        );
+        // the late viewer starts from the latest cached gop
+        let first_media = late_frames
+            .iter()
+            .position(|frame| !frame.is_sequence_header())
+            .unwrap();
+        assert!(late_frames[first_media].get_decode_timestamp_ms() > 0);
+        assert!(
+            late_timestamps
+                .iter()
+                .all(|timestamp| *timestamp <= ((GOP_SIZE * 2) * FRAME_INTERVAL_MS) as u32)
+        );
+        assert_eq!(late_timestamps[first_media], 0);
+        assert!(late_timestamps[first_media..].is_sorted());
+        for (frame, timestamp) in late_frames.iter().zip(&late_timestamps).skip(first_media) {
+            assert_eq!(
+                frame.get_decode_timestamp_ms()
+                    - late_frames[first_media].get_decode_timestamp_ms(),
+                *timestamp as u64
+            );
+        }
+        // rebased tags are a patched header and the bytes shared with the early viewer
+        assert!(late_pieces.len() > late_frames.len());
+    }
+
+    #[test]
+    fn serialized_frame_cache_is_bounded() {
+        let serialized_frames = SerializedFrameCache::new(4);
+        let frames: Vec<_> = (0..10).map(video_frame).collect();
+        let serialize_cnt = std::sync::atomic::AtomicUsize::new(0);
+        serve_flv(&frames, &serialized_frames, &serialize_cnt);
+        assert_eq!(serialized_frames.len(), 4);
+        // the latest frames are still there, the older ones are serialized again
+        serve_flv(&frames[6..], &serialized_frames, &serialize_cnt);
+        assert_eq!(serialize_cnt.load(std::sync::atomic::Ordering::Relaxed), 10);
+        serve_flv(&frames[..1], &serialized_frames, &serialize_cnt);
+        assert_eq!(serialize_cnt.load(std::sync::atomic::Ordering::Relaxed), 11);
+        // neither sequence headers nor empty payloads are cached
+        serve_flv(
+            &[video_config_with_record(), script_frame()],
+            &serialized_frames,
+            &serialize_cnt,
+        );
+        assert_eq!(serialized_frames.len(), 4);
+    }
+
+    /// serializing for N in-memory viewers with and without the shared cache,
+    /// each frame is serialized once for all of them with it
+    #[test]
+    fn serialized_frame_cache_serializes_once_for_all_viewers() {
+        const VIEWERS: usize = 100;
+        const FRAMES: u64 = 25;
+        let frames: Vec<_> = (0..FRAMES)
+            .map(|index| {
+                let mut frame = video_frame(index);
+                if let MediaFrame::Video {
+                    payload: VideoFrameUnit::H264 { nal_units },
+                    ..
+                } = &mut frame
+                {
+                    nal_units[0].body = Bytes::from(vec![index as u8; 4 * 1024]);
+                }
+                frame
+            })
+            .collect();
+
+        let serialize_cnt = std::sync::atomic::AtomicUsize::new(0);
+        let mut uncached = vec![];
+        for _ in 0..VIEWERS {
+            // each viewer owns a cache, nothing is shared
+            uncached.push(serve_flv(&frames, &SerializedFrameCache::new(0), &serialize_cnt).0);
+        }
+        assert_eq!(
+            serialize_cnt.swap(0, std::sync::atomic::Ordering::Relaxed),
+            VIEWERS * FRAMES as usize
+        );
+
+        let serialized_frames = SerializedFrameCache::default();
+        let mut cached = vec![];
+        for _ in 0..VIEWERS {
+            cached.push(serve_flv(&frames, &serialized_frames, &serialize_cnt).0);
+        }
+        assert_eq!(
+            serialize_cnt.load(std::sync::atomic::Ordering::Relaxed),
+            FRAMES as usize
+        );
+
+        assert_eq!(uncached, cached);
+        // the viewers got the very same bytes
+        for pieces in &cached[1..] {
+            assert!(
+                pieces
+                    .iter()
+                    .zip(&cached[0])
+                    .all(|(piece, first)| piece.as_ptr() == first.as_ptr())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rtcp_peers_show_up_in_the_description() {
+        let event_sender = start_stream_center();
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTSP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTSP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::video_only(),
+        )
+        .await
+        .unwrap();
+
+        let peer = |ssrc: u32, tool: &str| RtcpPeer {
+            ssrc,
+            cname: Some(format!("peer-{}", ssrc)),
+            tool: Some(tool.to_owned()),
+            ..Default::default()
+        };
+        let describe = |subscriber_id, peer| {
+            StreamCenter::describe_rtcp_peer(&event_sender, &stream_id(), subscriber_id, peer)
+                .unwrap()
+        };
+        describe(None, peer(2, "camera"));
+        describe(None, peer(1, "camera"));
+        describe(Some(response.subscribe_id), peer(3, "VLC"));
+        // a newer description replaces the former one
+        describe(Some(response.subscribe_id), peer(3, "ffplay"));
+        // the subscriber is gone
+        describe(Some(uuid::Uuid::now_v7()), peer(4, "VLC"));
+        for ssrc in 100..100 + MAX_RTCP_PEERS as u32 {
+            describe(None, peer(ssrc, "camera"));
+        }
+
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(description.publisher_rtcp_peers.len(), MAX_RTCP_PEERS);
+        assert_eq!(
+            description.publisher_rtcp_peers[..2],
+            [peer(1, "camera"), peer(2, "camera")]
+        );
+        assert_eq!(
+            description.subscribers[&response.subscribe_id].rtcp_peers,
+            [peer(3, "ffplay")]
+        );
+    }
+
+    #[tokio::test]
+    async fn rtmp_controls_show_up_in_the_description() {
+        let event_sender = start_stream_center();
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+
+        let control = |chunk_size| RtmpControl {
+            chunk_size,
+            window_ack_size: 2_500_000,
+            peer_bandwidth: 2_500_000,
+            peer_bandwidth_limit_type: PeerBandwidthLimitType::Dynamic,
+        };
+        StreamCenter::negotiate_rtmp_control(&event_sender, &stream_id(), None, control(60000))
+            .unwrap();
+        StreamCenter::negotiate_rtmp_control(
+            &event_sender,
+            &stream_id(),
+            Some(response.subscribe_id),
+            control(128),
+        )
+        .unwrap();
+
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(description.publisher_rtmp_control, Some(control(60000)));
+        assert_eq!(
+            description.subscribers[&response.subscribe_id].rtmp_control,
+            Some(control(128))
+        );
+    }
+
+    #[test]
+    fn health_drains_from_the_good_to_the_bad_thresholds() {
+        let stats = |fraction_lost, jitter_ms| RtpReceiveStats {
+            fraction_lost,
+            jitter_ms,
+            ..Default::default()
+        };
+        assert_eq!(stats(0.0, 0.0).health(), 100);
+        assert_eq!(stats(0.01, 30.0).health(), 100);
+        // half way to the bad loss costs half of its 70
+        assert_eq!(stats(0.055, 0.0).health(), 65);
+        // half way to the bad jitter costs half of its 30
+        assert_eq!(stats(0.0, 115.0).health(), 85);
+        assert_eq!(stats(0.5, 1000.0).health(), 0);
+    }
+
+    #[tokio::test]
+    async fn rtp_receive_stats_score_the_health_of_the_stream() {
+        let event_sender = start_stream_center();
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTSP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(description.health, None);
+
+        let stats = |track: &str, fraction_lost| RtpReceiveStats {
+            track: track.to_owned(),
+            ssrc: 1,
+            packets_received: 1000,
+            fraction_lost,
+            jitter_ms: 5.0,
+            ..Default::default()
+        };
+        let report =
+            |stats| StreamCenter::report_rtp_receive(&event_sender, &stream_id(), stats).unwrap();
+        report(stats("trackID=1", 0.0));
+        report(stats("trackID=0", 0.2));
+        // a newer report of the track replaces the former one
+        report(stats("trackID=0", 0.032));
+
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(
+            description.publisher_rtp_receive,
+            [stats("trackID=0", 0.032), stats("trackID=1", 0.0)]
+        );
+        // the worst track, (0.032 - 0.01) / 0.09 of 70
+        assert_eq!(description.health, Some(83));
+    }
+
+    #[tokio::test]
+    async fn rtp_receive_stats_set_the_buffer_gauges_of_the_stream() {
+        let event_sender = start_stream_center();
+        let stream_id = StreamIdentity::new("live", "rtp_buffers").unwrap();
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTSP,
+            &stream_id,
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let stats = |track: &str, bytes_buffered, buffer_discontinuities| RtpReceiveStats {
+            track: track.to_owned(),
+            bytes_buffered,
+            buffer_discontinuities,
+            ..Default::default()
+        };
+        for stats in [
+            stats("trackID=0", 1000, 1),
+            stats("trackID=1", 200, 0),
+            stats("trackID=0", 3000, 2),
+        ] {
+            StreamCenter::report_rtp_receive(&event_sender, &stream_id, stats).unwrap();
+        }
+        // the reports are handled in order before the describe
+        StreamCenter::describe(&event_sender, &stream_id)
+            .await
+            .unwrap();
+
+        let encoded = utils::metrics::global().encode();
+        assert!(encoded.contains(
+            "media_server_stream_rtp_bytes_buffered{stream=\"live/rtp_buffers\"} 3200\n"
+        ));
+        assert!(encoded.contains(
+            "media_server_stream_rtp_buffer_discontinuities_total{stream=\"live/rtp_buffers\"} 2\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn drained_publishers_are_asked_to_move_and_kicked_after_the_grace_period() {
+        let mut stream_center = StreamCenter::new();
+        let mut notifications = stream_center.subscribe_notifications();
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let stream = |name: &str| StreamIdentity::new("live", name).unwrap();
+        let publish = |name: &'static str| {
+            let event_sender = event_sender.clone();
+            async move {
+                StreamCenter::publish_kickable(
+                    &event_sender,
+                    PublishProtocol::RTMP,
+                    &stream(name),
+                    &HashMap::new(),
+                )
+                .await
+                .unwrap()
+            }
+        };
+        let republished = publish("republished").await;
+        let left = publish("left").await;
+        let stayed = publish("stayed").await;
+        // not kickable, so not drained either
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTSP,
+            &stream("embedded"),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+
+        let grace = Duration::from_millis(300);
+        let request = DrainRequest {
+            target: "edge-2.example.com:1935".to_owned(),
+            description: Some("deploying".to_owned()),
+            grace,
+        };
+        let mut summary = StreamCenter::drain(&event_sender, request.clone())
+            .await
+            .unwrap();
+        summary
+            .streams
+            .sort_by_key(|stream_id| stream_id.to_string());
+        assert_eq!(
+            summary.streams,
+            [stream("left"), stream("republished"), stream("stayed")]
+        );
+        // a later drain leaves the publishers drained already alone
+        let again = StreamCenter::drain(&event_sender, request.clone())
+            .await
+            .unwrap();
+        assert!(again.streams.is_empty());
+
+        // a capable publisher reconnects here, the new session takes the stream over
+        assert_eq!(republished.drain_receiver.await.unwrap(), request);
+        let new_publisher = publish("republished").await;
+        assert_eq!(
+            republished.kicked_receiver.await.unwrap().reason,
+            KickReason::Drain
+        );
+        // a capable publisher reconnects elsewhere and leaves
+        assert_eq!(left.drain_receiver.await.unwrap(), request);
+        StreamCenter::unpublish_publisher(&event_sender, &stream("left"), left.publisher_id)
+            .await
+            .unwrap();
+        // an incapable publisher never hears of the drain
+        drop(stayed.drain_receiver);
+        let kicked = tokio::time::timeout(grace * 3, stayed.kicked_receiver)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(kicked.reason, KickReason::Drain);
+
+        let mut outcomes = Vec::new();
+        while outcomes.len() < 3 {
+            if let StreamNotification::Drained { stream_id, outcome } =
+                notifications.recv().await.unwrap()
+            {
+                outcomes.push((stream_id.name.to_string(), outcome));
+            }
+        }
+        assert_eq!(
+            outcomes,
+            [
+                ("republished".to_owned(), DrainOutcome::RepublishedLocally),
+                ("left".to_owned(), DrainOutcome::Left),
+                ("stayed".to_owned(), DrainOutcome::Disconnected),
+            ]
+        );
+        // the new publisher is not drained
+        assert!(!new_publisher.media_sender.is_closed());
+        assert!(
+            StreamCenter::describe(&event_sender, &stream("republished"))
+                .await
+                .is_ok()
+        );
+    }
+
+    /// aac lc, 44.1khz stereo
+    fn audio_config_of_track(track_id: u8) -> MediaFrame {
+        let mut reader = codec_bitstream::reader::BitstreamReader::new(&[0x12, 0x10]);
+        MediaFrame::AudioConfig {
+            timestamp_nano: 0,
+            sound_info: SoundInfoCommon {
+                sound_rate: SoundRateCommon::KHZ44,
+                sound_size: SoundSizeCommon::Bit16,
+                sound_type: SoundTypeCommon::Stereo,
+            },
+            config: Box::new(AudioConfig::AAC(
+                AudioSpecificConfig::read_from(&mut reader).unwrap(),
+            )),
+            track_id,
+        }
+    }
+
+    /// the frames of each track are 1ms apart, so no two frames share a dts
+    fn audio_frame_of_track(index: u64, track_id: u8) -> MediaFrame {
+        let mut frame = audio_frame(index);
+        let dts_nano = frame.get_decode_timestamp_ns() + track_id as u64 * 1_000_000;
+        frame.set_decode_timestamp_ns(dts_nano);
+        if let MediaFrame::Audio { frame_info, .. } = &mut frame {
+            frame_info.track_id = track_id;
+        }
+        frame
+    }
+
+    fn script_frame_with_track_labels() -> MediaFrame {
+        let mut on_meta_data =
+            make_fake_on_meta_data(AudioCodecCommon::AAC, VideoCodecCommon::AVC, 720.0, 1280.0);
+        on_meta_data.audio_track_id_info_map = Some(HashMap::from([(
+            "1".to_owned(),
+            amf0::object([("name", amf0::string("commentary"))].into_iter()).into(),
+        )]));
+        MediaFrame::Script {
+            timestamp_nano: 0,
+            on_meta_data: Box::new(Some(on_meta_data)),
+            payload: Bytes::new(),
+        }
+    }
+
+    async fn publish_two_tracks() -> (
+        mpsc::UnboundedSender<StreamCenterEvent>,
+        mpsc::Sender<MediaFrame>,
+    ) {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender
+            .send(script_frame_with_track_labels())
+            .await
+            .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        media_sender.send(audio_config_of_track(0)).await.unwrap();
+        media_sender.send(audio_config_of_track(1)).await.unwrap();
+        (event_sender, media_sender)
+    }
+
+    async fn send_two_track_frames(
+        sender: &mpsc::Sender<MediaFrame>,
+        indexes: std::ops::Range<u64>,
+    ) {
+        for index in indexes {
+            sender.send(video_frame(index)).await.unwrap();
+            sender.send(audio_frame_of_track(index, 0)).await.unwrap();
+            sender.send(audio_frame_of_track(index, 1)).await.unwrap();
+        }
+    }
+
+    fn audio_track_ids(frames: &[MediaFrame]) -> Vec<u8> {
+        frames
+            .iter()
+            .filter_map(|frame| frame.audio_track_id())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn subscribers_of_a_two_track_stream_get_the_track_they_picked() {
+        let (event_sender, media_sender) = publish_two_tracks().await;
+        let subscribe = |context: HashMap<String, String>| {
+            let event_sender = event_sender.clone();
+            async move {
+                StreamCenter::subscribe(
+                    &event_sender,
+                    PlayProtocol::HTTPFLV,
+                    &stream_id(),
+                    &context,
+                    MediaSelection::from(&context),
+                )
+                .await
+                .unwrap()
+            }
+        };
+        let mut default_track = subscribe(HashMap::new()).await;
+        let mut commentary =
+            subscribe(HashMap::from([("audio_track".to_owned(), "1".to_owned())])).await;
+        send_two_track_frames(&media_sender, 0..GOP_SIZE * 2).await;
+
+        let mut video_frames = vec![];
+        for (response, track_id) in [(&mut default_track, 0), (&mut commentary, 1)] {
+            let frames = drain(&mut response.media_receiver).await;
+            let tracks = audio_track_ids(&frames);
+            assert!(tracks.len() > 1);
+            assert!(
+                tracks.iter().all(|id| *id == track_id),
+                "subscriber of track {} got tracks {:?}",
+                track_id,
+                tracks
+            );
+            assert!(
+                frames
+                    .iter()
+                    .find(|frame| frame.is_audio())
+                    .is_some_and(|frame| frame.is_sequence_header()),
+                "audio of track {} does not start with its sequence header",
+                track_id
+            );
+            video_frames.push(frames.iter().filter(|frame| frame.is_video()).count());
+        }
+        // the track only filters audio
+        assert!(video_frames[0] > 0);
+        assert_eq!(video_frames[0], video_frames[1]);
+
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(
+            description.audio_tracks,
+            [
+                AudioTrack {
+                    id: 0,
+                    codec: AudioCodecCommon::AAC,
+                    label: None,
+                    channels: None,
+                },
+                AudioTrack {
+                    id: 1,
+                    codec: AudioCodecCommon::AAC,
+                    label: Some("commentary".to_owned()),
+                    channels: None,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn switching_audio_track_resends_the_sequence_header_of_the_new_track() {
+        let (event_sender, media_sender) = publish_two_tracks().await;
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        send_two_track_frames(&media_sender, 0..GOP_SIZE).await;
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(audio_track_ids(&frames).iter().all(|id| *id == 0));
+
+        StreamCenter::update_media_selection(
+            &event_sender,
+            response.subscribe_id,
+            &stream_id(),
+            MediaSelection {
+                audio_track: 1,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        send_two_track_frames(&media_sender, GOP_SIZE..GOP_SIZE * 3).await;
+
+        let frames = drain(&mut response.media_receiver).await;
+        let mut audio_frames = frames.iter().filter(|frame| frame.is_audio());
+        assert!(
+            audio_frames
+                .next()
+                .is_some_and(|frame| matches!(frame, MediaFrame::AudioConfig { track_id: 1, .. })),
+            "the new track should start with its sequence header"
+        );
+        assert!(audio_frames.all(|frame| frame.audio_track_id() == Some(1)));
+        assert!(
+            frames
+                .iter()
+                .any(|frame| frame.is_video() && !frame.is_sequence_header()),
+            "video stops with the switch"
+        );
+    }
+
+    fn backup_stream_id() -> StreamIdentity {
+        StreamIdentity::new(&stream_id().app, "selection_backup").unwrap()
+    }
+
+    /// audio and video frames, configs are stamped by whoever sends them
+    fn media_dts_ns(frames: &[MediaFrame]) -> Vec<u64> {
+        frames
+            .iter()
+            .filter(|frame| matches!(frame, MediaFrame::Video { .. } | MediaFrame::Audio { .. }))
+            .map(MediaFrame::get_decode_timestamp_ns)
+            .collect()
+    }
+
+    #[test]
+    fn parse_backup_stream() {
+        let context =
+            |backup: &str| HashMap::from([(BACKUP_STREAM_KEY.to_owned(), backup.to_owned())]);
+        assert_eq!(
+            backup_stream(&stream_id(), &context("selection_backup")),
+            Some(backup_stream_id())
+        );
+        assert_eq!(backup_stream(&stream_id(), &context("selection")), None);
+        assert_eq!(backup_stream(&stream_id(), &context(" ")), None);
+        assert_eq!(backup_stream(&stream_id(), &HashMap::new()), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stalled_stream_fails_over_to_its_backup_and_back() {
+        let event_sender = start_stream_center_with_watchdog();
+        let primary = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::from([(
+                BACKUP_STREAM_KEY.to_owned(),
+                backup_stream_id().name.to_string(),
+            )]),
+        )
+        .await
+        .unwrap();
+        let backup = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &backup_stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        primary.send(video_config()).await.unwrap();
+        send_av_frames(&primary, 0..GOP_SIZE).await;
+        // the backup encoder runs on a clock of its own
+        backup.send(video_config()).await.unwrap();
+        send_av_frames(&backup, 500..500 + GOP_SIZE).await;
+        let mut frames = drain(&mut response.media_receiver).await;
+
+        tokio::time::sleep(STALL_AFTER).await;
+        frames.extend(drain(&mut response.media_receiver).await);
+        send_av_frames(&backup, 500 + GOP_SIZE..500 + 2 * GOP_SIZE).await;
+        let from_backup = drain(&mut response.media_receiver).await;
+        assert!(
+            from_backup
+                .iter()
+                .any(|frame| frame.is_video() && !frame.is_sequence_header())
+        );
+        frames.extend(from_backup);
+        assert!(
+            StreamCenter::describe(&event_sender, &stream_id())
+                .await
+                .unwrap()
+                .stalled
+        );
+
+        // the publisher resumes, its frames go on where the ones of the backup stopped
+        send_av_frames(&primary, GOP_SIZE..3 * GOP_SIZE).await;
+        let resumed = drain(&mut response.media_receiver).await;
+        assert!(matches!(
+            resumed.first(),
+            Some(MediaFrame::VideoConfig { .. })
+        ));
+        frames.extend(resumed);
+        assert!(
+            !StreamCenter::describe(&event_sender, &stream_id())
+                .await
+                .unwrap()
+                .stalled
+        );
+
+        assert!(
+            !frames
+                .iter()
+                .any(|frame| matches!(frame, MediaFrame::EndOfStream { .. })),
+            "the subscriber keeps its session"
+        );
+        let dts = media_dts_ns(&frames);
+        assert!(dts.len() > 4 * GOP_SIZE as usize);
+        for pair in dts.windows(2) {
+            assert!(
+                pair[0] <= pair[1],
+                "timestamp regression {} -> {}",
+                pair[0],
+                pair[1]
+            );
+            assert!(
+                pair[1] - pair[0] <= FRAME_INTERVAL_MS * 1_000_000,
+                "timestamp jump {} -> {}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stalled_stream_without_published_backup_ends_as_before() {
+        let event_sender = start_stream_center_with_watchdog();
+        let primary = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::from([(
+                BACKUP_STREAM_KEY.to_owned(),
+                backup_stream_id().name.to_string(),
+            )]),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        primary.send(video_config()).await.unwrap();
+        send_av_frames(&primary, 0..GOP_SIZE).await;
+        drain(&mut response.media_receiver).await;
+
+        tokio::time::sleep(STALL_AFTER).await;
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(matches!(
+            frames.last(),
+            Some(MediaFrame::EndOfStream { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn subscribers_get_the_end_of_stream_after_the_queued_frames() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+        // the publisher leaves before the stream gets to its frames
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+
+        let mut frames = vec![];
+        while let Some(frame) = response.media_receiver.recv().await {
+            frames.push(frame);
+        }
+        assert!(matches!(
+            frames.last(),
+            Some(MediaFrame::EndOfStream {
+                reason: EndOfStreamReason::Unpublished,
+                timestamp_nano,
+            }) if *timestamp_nano == audio_frame(GOP_SIZE - 1).get_decode_timestamp_ns()
+        ));
+        assert_eq!(frames.iter().filter(|frame| frame.is_control()).count(), 1);
+        // the frames held back by the mix queue are not lost
+        assert_eq!(
+            frames
+                .iter()
+                .filter(
+                    |frame| (frame.is_video() || frame.is_audio()) && !frame.is_sequence_header()
+                )
+                .count(),
+            2 * GOP_SIZE as usize
+        );
+    }
+
+    /// an enhanced rtmp MultichannelConfig tag of the mp4a FourCC, 5.1 with the surround channels on the sides
+    const MULTICHANNEL_CONFIG_TAG: [u8; 24] = [
+        8, 0, 0, 13, 0, 0, 0, 0, 0, 0, 0, 0x94, b'm', b'p', b'4', b'a', 2, 6, 0, 1, 2, 3, 9, 10,
+    ];
+
+    #[tokio::test]
+    async fn multichannel_config_goes_with_the_audio_config_and_into_the_metadata() {
+        let (event_sender, media_sender) = publish_two_tracks().await;
+        let tag = FLVTag::read_from(&mut Cursor::new(&MULTICHANNEL_CONFIG_TAG)).unwrap();
+        let channel_config = MediaFrame::from_flv_tag(tag, 4).unwrap();
+        assert!(channel_config.is_sequence_header());
+        media_sender.send(channel_config).await.unwrap();
+
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        send_two_track_frames(&media_sender, 0..GOP_SIZE).await;
+        let frames = drain(&mut response.media_receiver).await;
+        let audio_config = frames
+            .iter()
+            .position(|frame| matches!(frame, MediaFrame::AudioConfig { .. }))
+            .unwrap();
+        let channel_config = &frames[audio_config + 1];
+        let MediaFrame::AudioChannelConfig {
+            codec_id, config, ..
+        } = channel_config
+        else {
+            panic!(
+                "expect the channel config after the audio config, got {:?}",
+                channel_config
+            );
+        };
+        assert_eq!(*codec_id, AudioCodecCommon::AAC);
+        assert_eq!(config.channel_count, 6);
+        // it goes out to flv and rtmp subscribers as it came in
+        let mut remuxed = Vec::new();
+        channel_config
+            .to_flv_tag(4)
+            .unwrap()
+            .write_to(&mut remuxed)
+            .unwrap();
+        assert_eq!(remuxed, MULTICHANNEL_CONFIG_TAG);
+
+        let on_meta_data = frames
+            .iter()
+            .find_map(|frame| match frame {
+                MediaFrame::Script { on_meta_data, .. } => on_meta_data.as_ref().clone(),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(on_meta_data.audio_channels, Some(6.0));
+
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(description.audio_channels, Some(6));
+        assert_eq!(description.audio_tracks[0].channels, Some(6));
+        assert_eq!(description.audio_tracks[1].channels, None);
+        assert_eq!(description.meta_data.unwrap().audio_channels, Some(6.0));
+    }
+
+    /// aac lc 48khz of channel configuration 0, its program config element lays out 5.1
+    fn audio_config_5_1() -> MediaFrame {
+        let bytes = utils::bytes::hex_to_bytes("118004C8050001088000").unwrap();
+        let mut reader = codec_bitstream::reader::BitstreamReader::new(&bytes);
+        MediaFrame::AudioConfig {
+            timestamp_nano: 0,
+            sound_info: SoundInfoCommon {
+                sound_rate: SoundRateCommon::KHZ44,
+                sound_size: SoundSizeCommon::Bit16,
+                sound_type: SoundTypeCommon::Stereo,
+            },
+            config: Box::new(AudioConfig::AAC(
+                AudioSpecificConfig::read_from(&mut reader).unwrap(),
+            )),
+            track_id: DEFAULT_AUDIO_TRACK,
+        }
+    }
+
+    async fn subscribe_channel_config(
+        event_sender: &mpsc::UnboundedSender<StreamCenterEvent>,
+        media_sender: &mpsc::Sender<MediaFrame>,
+    ) -> (AudioMultichannelConfig, Vec<MediaFrame>) {
+        let mut response = StreamCenter::subscribe(
+            event_sender,
+            PlayProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        send_av_frames(media_sender, 0..GOP_SIZE).await;
+        let frames = drain(&mut response.media_receiver).await;
+        let audio_config = frames
+            .iter()
+            .position(|frame| matches!(frame, MediaFrame::AudioConfig { .. }))
+            .unwrap();
+        let MediaFrame::AudioChannelConfig { config, .. } = &frames[audio_config + 1] else {
+            panic!(
+                "expect the channel config after the audio config, got {:?}",
+                frames[audio_config + 1]
+            );
+        };
+        (*config.clone(), frames)
+    }
+
+    #[tokio::test]
+    async fn the_program_config_element_layout_goes_with_the_audio_config() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(script_frame()).await.unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        media_sender.send(audio_config_5_1()).await.unwrap();
+
+        let (config, frames) = subscribe_channel_config(&event_sender, &media_sender).await;
+        assert_eq!(
+            config,
+            AudioMultichannelConfig::from(ChannelLayout::Surround5_1)
+        );
+        assert_eq!(config.channel_count, 6);
+        assert_eq!(config.channel_mapping[3], AudioChannel::LowFrequency1);
+        let on_meta_data = frames
+            .iter()
+            .find_map(|frame| match frame {
+                MediaFrame::Script { on_meta_data, .. } => on_meta_data.as_ref().clone(),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(on_meta_data.audio_channels, Some(6.0));
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(description.audio_channels, Some(6));
+        assert_eq!(description.audio_tracks[0].channels, Some(6));
+
+        // the layout the publisher sends is kept over the one of the element
+        let tag = FLVTag::read_from(&mut Cursor::new(&MULTICHANNEL_CONFIG_TAG)).unwrap();
+        media_sender
+            .send(MediaFrame::from_flv_tag(tag, 4).unwrap())
+            .await
+            .unwrap();
+        media_sender.send(audio_config_5_1()).await.unwrap();
+        let (config, _) = subscribe_channel_config(&event_sender, &media_sender).await;
+        assert_eq!(config.channel_order, AudioChannelOrder::Custom);
+        assert_eq!(config.channel_mapping[4], AudioChannel::SideLeft);
+    }
+
+    #[derive(Debug)]
+    struct FixedSource(VodSourceDefinition);
+
+    impl SourceSnapshotter for FixedSource {
+        fn definition(&self) -> SourceDefinition {
+            SourceDefinition::Vod(self.0.clone())
+        }
+    }
+
+    fn vod_definition() -> VodSourceDefinition {
+        VodSourceDefinition {
+            path: "live/a.flv".to_owned(),
+            speed: 1.0,
+            scale: 2.0,
+            loop_playback: true,
+            idle_timeout_ms: 30_000,
+            position_ms: 12_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn restored_sources_are_notified_as_restored() {
+        let event_sender = start_stream_center_with_takeover(TakeoverPolicy::KickOld);
+        let _recording = StreamCenter::publish_restorable_recording(
+            &event_sender,
+            PublishProtocol::VOD,
+            &stream_id(),
+            &HashMap::new(),
+            Arc::new(FixedSource(vod_definition())),
+        )
+        .await
+        .unwrap();
+        let other = StreamIdentity::new("live", "live").unwrap();
+        // live publishers and plain recordings can not be started again
+        let _live = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &other,
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        StreamCenter::point_alias(&event_sender, &alias_id("court-a"), Some(&other))
+            .await
+            .unwrap();
+        let snapshot = StreamCenter::snapshot(&event_sender).await.unwrap();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert_eq!(
+            snapshot.takeover_policies.get(stream_id().app.as_str()),
+            Some(&TakeoverPolicy::KickOld)
+        );
+        assert_eq!(
+            snapshot.sources,
+            vec![SourceSnapshot {
+                app: stream_id().app,
+                stream: stream_id().name,
+                definition: SourceDefinition::Vod(vod_definition()),
+            }]
+        );
+
+        let restored = StreamCenterSnapshot::from_json(&snapshot.to_json().unwrap()).unwrap();
+        assert_eq!(restored, snapshot);
+        let mut stream_center = StreamCenter::new();
+        let sources = stream_center.restore(restored);
+        assert_eq!(sources, snapshot.sources);
+        let mut notifications = stream_center.subscribe_notifications();
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let _recording = StreamCenter::publish_restorable_recording(
+            &event_sender,
+            PublishProtocol::VOD,
+            &sources[0].stream_id(),
+            &HashMap::new(),
+            Arc::new(FixedSource(vod_definition())),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            notifications.recv().await.unwrap(),
+            StreamNotification::Restored { stream_id: restored, protocol: PublishProtocol::VOD }
+                if restored == stream_id()
+        ));
+        // the takeover policy of the app and the aliases came along
+        let snapshot = StreamCenter::snapshot(&event_sender).await.unwrap();
+        assert_eq!(
+            snapshot.takeover_policies.get(stream_id().app.as_str()),
+            Some(&TakeoverPolicy::KickOld)
+        );
+        assert_eq!(
+            StreamCenter::aliases(&event_sender).await.unwrap(),
+            vec![(alias_id("court-a"), other)]
+        );
+    }
+
+    #[test]
+    fn snapshot_sources_are_validated_one_by_one() {
+        let json = r#"{
+            "version": 1,
+            "taken_at": 1700000000000,
+            "default_takeover_policy": "takeover_if_idle:5",
+            "sources": [
+                {"app": "live", "stream": "a", "kind": "vod", "path": "a.flv", "speed": 1.0, "scale": 1.0, "idle_timeout_ms": 1000},
+                {"app": "live", "stream": "b", "kind": "relay", "url": "rtmp://origin/live/b"},
+                {"app": "live", "stream": "c", "kind": "vod", "path": "c.flv", "speed": 0.0, "scale": 1.0, "idle_timeout_ms": 1000},
+                {"app": "live", "stream": "a", "kind": "vod", "path": "d.flv", "speed": 1.0, "scale": 1.0, "idle_timeout_ms": 1000}
+            ]
+        }"#;
+        let snapshot = StreamCenterSnapshot::from_json(json).unwrap();
+        assert_eq!(
+            snapshot.default_takeover_policy,
+            TakeoverPolicy::TakeoverIfIdle(Duration::from_secs(5))
+        );
+        assert!(snapshot.takeover_policies.is_empty());
+        // the fields missing default, the unknown kind, the bad speed and the duplicate are skipped
+        assert_eq!(
+            snapshot.sources,
+            vec![SourceSnapshot {
+                app: "live".parse().unwrap(),
+                stream: "a".parse().unwrap(),
+                definition: SourceDefinition::Vod(VodSourceDefinition {
+                    path: "a.flv".to_owned(),
+                    speed: 1.0,
+                    scale: 1.0,
+                    loop_playback: false,
+                    idle_timeout_ms: 1000,
+                    position_ms: 0,
+                }),
+            }]
+        );
+
+        assert!(matches!(
+            StreamCenterSnapshot::from_json(
+                r#"{"version": 2, "taken_at": 0, "default_takeover_policy": "reject"}"#
+            ),
+            Err(StreamCenterError::UnsupportedSnapshotVersion(2))
+        ));
+        assert!(matches!(
+            StreamCenterSnapshot::from_json(
+                r#"{"taken_at": 0, "default_takeover_policy": "reject"}"#
+            ),
+            Err(StreamCenterError::InvalidSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn snapshot_is_taken_from_data_dir_once() {
+        let data_dir =
+            std::env::temp_dir().join(format!("stream-center-snapshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        assert!(StreamCenterSnapshot::take(&data_dir).unwrap().is_none());
+        let snapshot: StreamCenterSnapshot = StreamCenterSnapshot::from_json(
+            r#"{"version": 1, "taken_at": 0, "default_takeover_policy": "kick_old"}"#,
+        )
+        .unwrap();
+        snapshot.save(&data_dir).unwrap();
+        assert_eq!(
+            StreamCenterSnapshot::take(&data_dir).unwrap(),
+            Some(snapshot)
+        );
+        assert!(StreamCenterSnapshot::take(&data_dir).unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+
+    fn alias_id(stream_name: &str) -> StreamIdentity {
+        StreamIdentity::new("live", stream_name).unwrap()
+    }
+
+    fn start_stream_center_with_notifications() -> (
+        mpsc::UnboundedSender<StreamCenterEvent>,
+        tokio::sync::broadcast::Receiver<StreamNotification>,
+    ) {
+        let mut stream_center = StreamCenter::new();
+        let notifications = stream_center.subscribe_notifications();
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        (event_sender, notifications)
+    }
+
+    #[test]
+    fn aliases_do_not_chain() {
+        let mut aliases = StreamAliases::default();
+        assert_eq!(aliases.set(alias_id("court-a"), stream_id()).unwrap(), None);
+        assert!(matches!(
+            aliases.set(alias_id("court-b"), alias_id("court-a")),
+            Err(StreamCenterError::InvalidAlias(_))
+        ));
+        assert!(matches!(
+            aliases.set(stream_id(), alias_id("court-b")),
+            Err(StreamCenterError::InvalidAlias(_))
+        ));
+        assert!(matches!(
+            aliases.set(alias_id("court-b"), alias_id("court-b")),
+            Err(StreamCenterError::InvalidAlias(_))
+        ));
+        assert_eq!(
+            aliases.set(alias_id("court-a"), alias_id("other")).unwrap(),
+            Some(stream_id())
+        );
+        assert_eq!(
+            aliases.resolve(&alias_id("court-a")),
+            Some(&alias_id("other"))
+        );
+        assert_eq!(
+            aliases.remove(&alias_id("court-a")),
+            Some(alias_id("other"))
+        );
+        assert!(aliases.to_vec().is_empty());
+    }
+
+    #[test]
+    fn parse_stream_key() {
+        assert_eq!(
+            "live/selection".parse::<StreamIdentity>().unwrap(),
+            stream_id()
+        );
+        for key in ["live", "live/", "/selection"] {
+            assert!(matches!(
+                key.parse::<StreamIdentity>(),
+                Err(StreamCenterError::InvalidStreamKey(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn stream_identities_of_all_protocols_are_the_same() {
+        let identity = StreamIdentity::new(" Live ", "test").unwrap();
+        assert_eq!(identity.to_string(), "live/test");
+        assert_eq!(
+            StreamIdentity::from_rtmp("rtmp://host/Live", "test?token=abc").unwrap(),
+            identity
+        );
+        assert_eq!(
+            StreamIdentity::from_url("rtsp://host:8554/live/test/trackID=1").unwrap(),
+            identity
+        );
+        assert_eq!(
+            StreamIdentity::from_path("/live/te%73t?token=abc").unwrap(),
+            identity
+        );
+        assert_eq!(" live/test ".parse::<StreamIdentity>().unwrap(), identity);
+        // names keep their case
+        assert_ne!(StreamIdentity::new("live", "Test").unwrap(), identity);
+
+        let json = serde_json::to_string(&identity).unwrap();
+        assert_eq!(json, "\"live/test\"");
+        assert_eq!(
+            serde_json::from_str::<StreamIdentity>(&json).unwrap(),
+            identity
+        );
+    }
+
+    #[test]
+    fn invalid_stream_identities_are_rejected() {
+        let too_long = "a".repeat(256);
+        for (app, name) in [
+            ("live", ""),
+            ("  ", "test"),
+            ("live", ".."),
+            ("live", "te\nst"),
+            ("live", "te\\st"),
+            ("live", too_long.as_str()),
+        ] {
+            assert!(
+                matches!(
+                    StreamIdentity::new(app, name),
+                    Err(StreamCenterError::InvalidStreamIdentity(_))
+                ),
+                "{:?}/{:?}",
+                app,
+                name
+            );
+        }
+        assert!(StreamIdentity::new("live", &"a".repeat(255)).is_ok());
+        assert!(StreamIdentity::from_path("/live/te%2Fst").is_err());
+        assert!(StreamIdentity::from_path("/live").is_err());
+        assert!(serde_json::from_str::<StreamIdentity>("\"live/..\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn alias_plays_the_canonical_stream_once_it_is_live() {
+        let (event_sender, mut notifications) = start_stream_center_with_notifications();
+        StreamCenter::point_alias(&event_sender, &alias_id("court-a"), Some(&stream_id()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            notifications.recv().await.unwrap(),
+            StreamNotification::AliasChanged { alias, canonical: Some(canonical), previous: None }
+                if alias == alias_id("court-a") && canonical == stream_id()
+        ));
+
+        // nothing is published yet, the subscriber hears of the name it asked for
+        assert!(matches!(
+            StreamCenter::subscribe(
+                &event_sender,
+                PlayProtocol::RTMP,
+                &alias_id("court-a"),
+                &HashMap::new(),
+                MediaSelection::default(),
+            )
+            .await,
+            Err(StreamCenterError::StreamNotFound(stream)) if stream == alias_id("court-a")
+        ));
+
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTMP,
+            &alias_id("court-a"),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        send_av_frames(&media_sender, 0..GOP_SIZE).await;
+        let frames = drain(&mut response.media_receiver).await;
+        assert!(frames.iter().any(|frame| frame.is_video_key_frame()));
+
+        // described under both names, with the alias the subscriber asked for
+        for name in [stream_id(), alias_id("court-a")] {
+            let description = StreamCenter::describe(&event_sender, &name).await.unwrap();
+            assert_eq!(
+                description.subscribers[&response.subscribe_id].alias,
+                Some(alias_id("court-a"))
+            );
+        }
+        let subscribed = loop {
+            match notifications.recv().await.unwrap() {
+                notification @ StreamNotification::SubscribedViaAlias { .. } => break notification,
+                _ => continue,
+            }
+        };
+        assert!(matches!(
+            subscribed,
+            StreamNotification::SubscribedViaAlias { stream_id: canonical, alias, subscriber_id, protocol: PlayProtocol::RTMP }
+                if canonical == stream_id() && alias == alias_id("court-a") && subscriber_id == response.subscribe_id
+        ));
+    }
+
+    #[tokio::test]
+    async fn subscribers_missing_a_stream_are_told_of_for_the_stream_to_be_pulled() {
+        let (event_sender, mut notifications) = start_stream_center_with_notifications();
+        StreamCenter::point_alias(&event_sender, &alias_id("court-a"), Some(&stream_id()))
+            .await
+            .unwrap();
+        for stream in [alias_id("other"), alias_id("court-a")] {
+            assert!(
+                StreamCenter::subscribe(
+                    &event_sender,
+                    PlayProtocol::HTTPFLV,
+                    &stream,
+                    &HashMap::new(),
+                    MediaSelection::default(),
+                )
+                .await
+                .is_err()
+            );
+        }
+        let mut missed = vec![];
+        while let Ok(notification) = notifications.try_recv() {
+            if let StreamNotification::SubscribeMissed {
+                stream_id,
+                protocol: PlayProtocol::HTTPFLV,
+            } = notification
+            {
+                missed.push(stream_id);
+            }
+        }
+        // the alias misses the stream it points to
+        assert_eq!(missed, vec![alias_id("other"), stream_id()]);
+        assert!(
+            StreamCenter::streams(&event_sender)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let _media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let _response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &alias_id("court-a"),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        while let Ok(notification) = notifications.try_recv() {
+            assert!(!matches!(
+                notification,
+                StreamNotification::SubscribeMissed { .. }
+            ));
+        }
+        assert_eq!(
+            StreamCenter::streams(&event_sender).await.unwrap(),
+            vec![stream_id()]
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribers_joining_and_leaving_are_told_of() {
+        let (event_sender, mut notifications) = start_stream_center_with_notifications();
+        let _media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTSP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        StreamCenter::unsubscribe(&event_sender, response.subscribe_id, &stream_id())
+            .await
+            .unwrap();
+        let mut subscribers = vec![];
+        while let Ok(notification) = notifications.try_recv() {
+            match notification {
+                StreamNotification::Subscribed {
+                    stream_id,
+                    subscriber_id,
+                    protocol: PlayProtocol::RTSP,
+                } => subscribers.push((stream_id, subscriber_id, true)),
+                StreamNotification::Unsubscribed {
+                    stream_id,
+                    subscriber_id,
+                } => subscribers.push((stream_id, subscriber_id, false)),
+                _ => {}
+            }
+        }
+        assert_eq!(
+            subscribers,
+            vec![
+                (stream_id(), response.subscribe_id, true),
+                (stream_id(), response.subscribe_id, false)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn repointed_alias_leaves_the_subscribers_so_far_on_the_old_stream() {
+        let event_sender = start_stream_center();
+        let other = alias_id("other");
+        let old_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let new_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &other,
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        StreamCenter::point_alias(&event_sender, &alias_id("court-a"), Some(&stream_id()))
+            .await
+            .unwrap();
+        let alias = alias_id("court-a");
+        let context = HashMap::new();
+        let subscribe = || {
+            StreamCenter::subscribe(
+                &event_sender,
+                PlayProtocol::RTMP,
+                &alias,
+                &context,
+                MediaSelection::video_only(),
+            )
+        };
+        let mut old_response = subscribe().await.unwrap();
+
+        assert_eq!(
+            StreamCenter::point_alias(&event_sender, &alias_id("court-a"), Some(&other))
+                .await
+                .unwrap(),
+            Some(stream_id())
+        );
+        let mut new_response = subscribe().await.unwrap();
+
+        // the streams are told apart by their frame indexes
+        send_av_frames(&old_sender, 0..GOP_SIZE).await;
+        send_av_frames(&new_sender, GOP_SIZE..GOP_SIZE * 2).await;
+        let first_video = |frames: Vec<MediaFrame>| {
+            frames
+                .into_iter()
+                .find(|frame| frame.is_video())
+                .map(|frame| frame.get_decode_timestamp_ms())
+        };
+        assert_eq!(
+            first_video(drain(&mut old_response.media_receiver).await),
+            Some(0)
+        );
+        assert_eq!(
+            first_video(drain(&mut new_response.media_receiver).await),
+            Some(GOP_SIZE * FRAME_INTERVAL_MS)
+        );
+
+        // the alias still leads to the stream each subscriber plays
+        StreamCenter::unsubscribe(
+            &event_sender,
+            old_response.subscribe_id,
+            &alias_id("court-a"),
+        )
+        .await
+        .unwrap();
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert!(description.subscribers.is_empty());
+        let description = StreamCenter::describe(&event_sender, &alias_id("court-a"))
+            .await
+            .unwrap();
+        assert!(
+            description
+                .subscribers
+                .contains_key(&new_response.subscribe_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn publishing_to_an_alias_is_refused() {
+        let event_sender = start_stream_center();
+        StreamCenter::point_alias(&event_sender, &alias_id("court-a"), Some(&stream_id()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            StreamCenter::publish(
+                &event_sender,
+                PublishProtocol::RTMP,
+                &alias_id("court-a"),
+                &HashMap::new(),
+            )
+            .await,
+            Err(StreamCenterError::PublishToAlias { alias, canonical })
+                if alias == alias_id("court-a") && canonical == stream_id()
+        ));
+
+        // a published stream can not become an alias
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &alias_id("other"),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            StreamCenter::point_alias(&event_sender, &alias_id("other"), Some(&stream_id())).await,
+            Err(StreamCenterError::InvalidAlias(_))
+        ));
+        // removed, the name can be published again
+        StreamCenter::point_alias(&event_sender, &alias_id("court-a"), None)
+            .await
+            .unwrap();
+        StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &alias_id("court-a"),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+    }
+
+    /// a h264 frame of a stream with gops of `gop_size` frames, `frame_bytes` payload bytes each
+    fn sized_video_frame(index: u64, gop_size: u64, frame_bytes: usize) -> MediaFrame {
+        let (nal_header, frame_type) = if index.is_multiple_of(gop_size) {
+            (0x65, FrameType::KeyFrame)
+        } else {
+            (0x41, FrameType::CodedFrames)
+        };
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                frame_type,
+                MediaFrameTimestamp::with_timestamp_ms(index * FRAME_INTERVAL_MS),
+            ),
+            payload: VideoFrameUnit::H264 {
+                nal_units: vec![NalUnit {
+                    header: NaluHeader::try_from(nal_header).unwrap(),
+                    // the nalu header takes a byte
+                    body: Bytes::from(vec![0; frame_bytes - 1]),
+                }],
+            },
+        }
+    }
+
+    #[test]
+    fn gop_caches_over_the_budget_evict_the_largest_first() {
+        const MAX_BYTES: u64 = 2000;
+        let budget = GopCacheBudget::new(GopCacheBudgetConfig {
+            max_bytes: MAX_BYTES,
+            min_gops: 1,
+        });
+        // (gop size, frame bytes): 1000, 250 and 50 bytes a gop
+        let streams = [(10, 100), (5, 50), (2, 25)];
+        let mut caches: Vec<GopQueue> = streams
+            .iter()
+            .map(|_| {
+                let mut cache = GopQueue::new(60_000, 8000);
+                cache.set_budget(budget.open_account());
+                cache.append_frame(video_config()).unwrap();
+                cache
+            })
+            .collect();
+
+        let mut evictions = Vec::new();
+        for index in 0..40 {
+            for (stream, (gop_size, frame_bytes)) in streams.iter().enumerate() {
+                // the most any other cache could have given up
+                let largest_other_trimmable = caches
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, cache)| *other != stream && cache.get_gops_cnt() > 1)
+                    .map(|(_, cache)| cache.get_bytes())
+                    .max()
+                    .unwrap_or(0);
+                let cache = &mut caches[stream];
+                let evicted = cache.get_evicted_gops_cnt();
+                let bytes = cache.get_bytes() + *frame_bytes as u64;
+                cache
+                    .append_frame(sized_video_frame(index, *gop_size, *frame_bytes))
+                    .unwrap();
+                if cache.get_evicted_gops_cnt() > evicted {
+                    // no larger cache was left to give up its gops first
+                    assert!(bytes >= largest_other_trimmable);
+                    evictions.push(stream);
+                }
+
+                // the latest gop and the sequence headers are kept whatever the budget
+                assert!(cache.video_config.is_some());
+                let latest_key_frame = index - index % gop_size;
+                let latest_gop = cache.gops.back().unwrap();
+                let first_frame = latest_gop.media_frames.front().unwrap();
+                assert!(first_frame.is_video_key_frame());
+                assert_eq!(
+                    first_frame.get_decode_timestamp_ns(),
+                    latest_key_frame * FRAME_INTERVAL_MS * 1_000_000
+                );
+                assert_eq!(
+                    cache.get_bytes(),
+                    cache.gops.iter().map(|gop| gop.get_bytes()).sum::<u64>()
+                );
+            }
+            // the caches drop what they owe on their next append, a round later at most
+            assert!(budget.used_bytes() <= MAX_BYTES + 100);
+        }
+
+        // the largest cache is the first to give up its oldest gop, the smallest is the last
+        assert_eq!(evictions.first(), Some(&0));
+        let first_eviction_of_smallest = evictions.iter().position(|stream| *stream == 2).unwrap();
+        assert!(evictions[..first_eviction_of_smallest].contains(&1));
+        assert!(budget.used_bytes() <= MAX_BYTES);
+        assert_eq!(
+            budget.used_bytes(),
+            caches.iter().map(|cache| cache.get_bytes()).sum::<u64>()
+        );
+
+        // a cache leaves the budget with its stream
+        let remaining_bytes: u64 = caches[1..].iter().map(|cache| cache.get_bytes()).sum();
+        caches.remove(0);
+        assert_eq!(budget.used_bytes(), remaining_bytes);
+    }
+
+    #[test]
+    fn parse_audio_gap_concealment() {
+        assert_eq!(
+            "silence".parse::<AudioGapConcealment>().unwrap(),
+            AudioGapConcealment::Silence
+        );
+        assert_eq!(
+            " stretch ".parse::<AudioGapConcealment>().unwrap(),
+            AudioGapConcealment::Stretch
+        );
+        assert_eq!(
+            "off".parse::<AudioGapConcealment>().unwrap(),
+            AudioGapConcealment::Off
+        );
+        assert!(matches!(
+            "mute".parse::<AudioGapConcealment>(),
+            Err(StreamCenterError::InvalidAudioGapConcealment(_))
+        ));
+    }
+
+    fn aac_config_from_hex(hex: &str) -> AudioSpecificConfig {
+        let bytes = utils::bytes::hex_to_bytes(hex).unwrap();
+        let mut reader = codec_bitstream::reader::BitstreamReader::new(&bytes);
+        AudioSpecificConfig::read_from(&mut reader).unwrap()
+    }
+
+    #[test]
+    fn he_aac_configs_tell_the_rate_and_frames_of_the_sbr() {
+        // (config, has ps, core rate, effective rate, samples per frame)
+        let cases = [
+            // aac lc, 44.1khz stereo
+            ("1210", false, 44100, 44100, 1024),
+            // he-aac v1, explicit: sbr 48khz over a 24khz lc core, stereo
+            ("2B118800", false, 24000, 48000, 2048),
+            // he-aac v2, explicit: ps and sbr 48khz over a 24khz lc core, mono
+            ("EB098800", true, 24000, 48000, 2048),
+            // he-aac v1, implicit: lc 24khz stereo with the sync extension of sbr 48khz
+            ("131056E598", false, 24000, 48000, 2048),
+            // he-aac v2, implicit: the sync extension of ps follows the one of sbr
+            ("131056E59D4880", true, 24000, 48000, 2048),
+        ];
+        for (hex, has_ps, core_rate, effective_rate, samples) in cases {
+            let config = aac_config_from_hex(hex);
+            // the sequence headers sent on carry the signaling as published
+            let mut written = vec![];
+            AudioConfig::AAC(config.clone())
+                .write_to(&mut written)
+                .unwrap();
+            assert_eq!(written, utils::bytes::hex_to_bytes(hex).unwrap(), "{}", hex);
+            assert_eq!(config.has_sbr(), effective_rate != core_rate, "{}", hex);
+            assert_eq!(config.has_ps(), has_ps, "{}", hex);
+            assert_eq!(config.core_sample_rate(), Some(core_rate), "{}", hex);
+            assert_eq!(
+                config.effective_sample_rate(),
+                Some(effective_rate),
+                "{}",
+                hex
+            );
+            assert_eq!(config.samples_per_frame(), Some(samples), "{}", hex);
+            assert_eq!(
+                config.frame_duration_nano(),
+                Some(samples as u64 * 1_000_000_000 / effective_rate as u64),
+                "{}",
+                hex
+            );
+            assert_eq!(
+                SoundInfoCommon::try_from(&config).unwrap().sound_rate,
+                SoundRateCommon::KHZ44,
+                "{}",
+                hex
+            );
+        }
+        // a frame of he-aac lasts as long as the frame of its core
+        assert_eq!(
+            aac_config_from_hex("2B118800").frame_duration_nano(),
+            Some(42_666_666)
+        );
+    }
+
+    /// 1024 samples at 44.1khz, the frames of audio_config_of_track
+    const AAC_FRAME_NANO: u64 = 1024 * 1_000_000_000 / 44100;
+    const AUDIO_HOLE_NANO: u64 = 400_000_000;
+    /// the frames sent before the hole
+    const FRAMES_BEFORE_HOLE: u64 = 20;
+
+    /// the frames after the hole come AUDIO_HOLE_NANO later than they would without it
+    fn aac_frame_around_hole(index: u64) -> MediaFrame {
+        let mut frame = audio_frame(0);
+        let hole_nano = if index < FRAMES_BEFORE_HOLE {
+            0
+        } else {
+            AUDIO_HOLE_NANO
+        };
+        frame.set_decode_timestamp_ns(index * AAC_FRAME_NANO + hole_nano);
+        frame
+    }
+
+    /// publishes audio with a hole of AUDIO_HOLE_NANO, the audio frames an audio only subscriber gets
+    /// and the concealment the stream reported before it stopped are returned
+    async fn publish_audio_with_hole(
+        concealment: AudioGapConcealment,
+        frames: u64,
+    ) -> (Vec<MediaFrame>, AudioConcealmentStats) {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_audio_gap_concealment(&stream_id().app, concealment);
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::audio_only(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(audio_config_of_track(0)).await.unwrap();
+        for index in 0..frames {
+            media_sender
+                .send(aac_frame_around_hole(index))
+                .await
+                .unwrap();
+        }
+        // the describe would go before the frames still queued
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        // the stream stops and flushes what the mix queue holds
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        let frames = drain(&mut response.media_receiver)
+            .await
+            .into_iter()
+            .filter(|frame| frame.is_audio() && !frame.is_sequence_header())
+            .collect();
+        (frames, description.audio_concealment)
+    }
+
+    #[tokio::test]
+    async fn audio_hole_is_filled_with_silence() {
+        let (frames, stats) =
+            publish_audio_with_hole(AudioGapConcealment::Silence, FRAMES_BEFORE_HOLE * 2).await;
+        // the hole is 17.2 frames, it is filled to the frame
+        const SILENCE_FRAMES: u64 = 17;
+        assert_eq!(frames.len() as u64, FRAMES_BEFORE_HOLE * 2 + SILENCE_FRAMES);
+
+        // lc stereo, a cpe of two silent ics and the end element
+        let MediaFrame::AudioConfig { config, .. } = audio_config_of_track(0) else {
+            unreachable!()
+        };
+        let AudioConfig::AAC(config) = *config;
+        let silence = codec_aac::silence::silent_raw_data_block(&config).unwrap();
+        assert_eq!(silence.len(), 7);
+        let silent_dts: Vec<_> = frames
+            .iter()
+            .filter(|frame| {
+                matches!(frame, MediaFrame::Audio { payload, .. } if payload[..] == silence[..])
+            })
+            .map(|frame| frame.get_decode_timestamp_ns())
+            .collect();
+        let expected: Vec<_> = (FRAMES_BEFORE_HOLE..FRAMES_BEFORE_HOLE + SILENCE_FRAMES)
+            .map(|index| index * AAC_FRAME_NANO)
+            .collect();
+        assert_eq!(silent_dts, expected);
+
+        // no hole is left, and the frames of the publisher keep their timestamps
+        let dts = media_dts_ns(&frames);
+        assert!(
+            dts.windows(2)
+                .all(|pair| { pair[1] > pair[0] && pair[1] - pair[0] < AAC_FRAME_NANO * 3 / 2 })
+        );
+        assert_eq!(
+            dts.last().copied(),
+            Some((FRAMES_BEFORE_HOLE * 2 - 1) * AAC_FRAME_NANO + AUDIO_HOLE_NANO)
+        );
+        assert_eq!(
+            stats,
+            AudioConcealmentStats {
+                gaps: 1,
+                silence_frames: SILENCE_FRAMES,
+                concealed_nano: SILENCE_FRAMES * AAC_FRAME_NANO,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn audio_hole_is_stretched_over_the_frames_after_it() {
+        let (frames, stats) =
+            publish_audio_with_hole(AudioGapConcealment::Stretch, FRAMES_BEFORE_HOLE * 5).await;
+        assert_eq!(frames.len() as u64, FRAMES_BEFORE_HOLE * 5);
+        let dts = media_dts_ns(&frames);
+        // the frame after the hole follows on, the ones after it are a quarter of a frame wider
+        assert_eq!(
+            dts[FRAMES_BEFORE_HOLE as usize],
+            FRAMES_BEFORE_HOLE * AAC_FRAME_NANO
+        );
+        assert!(
+            dts.windows(2).all(|pair| {
+                pair[1] > pair[0] && pair[1] - pair[0] <= AAC_FRAME_NANO * 5 / 4 + 1
+            })
+        );
+        // until they are back on the timestamps of the publisher
+        assert_eq!(
+            dts.last().copied(),
+            Some((FRAMES_BEFORE_HOLE * 5 - 1) * AAC_FRAME_NANO + AUDIO_HOLE_NANO)
+        );
+        assert_eq!(stats.gaps, 1);
+        assert_eq!(stats.silence_frames, 0);
+        assert_eq!(stats.concealed_nano, AUDIO_HOLE_NANO);
+    }
+
+    #[tokio::test]
+    async fn audio_holes_are_left_if_concealment_is_off() {
+        let (frames, stats) =
+            publish_audio_with_hole(AudioGapConcealment::Off, FRAMES_BEFORE_HOLE * 2).await;
+        assert_eq!(frames.len() as u64, FRAMES_BEFORE_HOLE * 2);
+        assert_eq!(stats, AudioConcealmentStats::default());
+    }
+
+    #[test]
+    fn parse_timestamp_normalization() {
+        assert_eq!(
+            "off".parse::<TimestampNormalization>().unwrap(),
+            TimestampNormalization::Off
+        );
+        assert_eq!(
+            " wallclock ".parse::<TimestampNormalization>().unwrap(),
+            TimestampNormalization::wallclock()
+        );
+        assert_eq!(
+            "wallclock:2:500".parse::<TimestampNormalization>().unwrap(),
+            TimestampNormalization::Wallclock {
+                max_slew_per_sec: Duration::from_millis(2),
+                discontinuity_threshold: Duration::from_millis(500),
+            }
+        );
+        for invalid in ["on", "wallclock:5", "wallclock:1000:500", "wallclock:5:0"] {
+            assert!(matches!(
+                invalid.parse::<TimestampNormalization>(),
+                Err(StreamCenterError::InvalidTimestampNormalization(_))
+            ));
+        }
+    }
+
+    /// publishes audio frames arriving a step apart on the clock of the server, with the decode
+    /// timestamps of the publisher. the audio frames an audio only subscriber gets, the discontinuities
+    /// notified and the normalization the stream reported before it stopped are returned
+    async fn publish_audio_on_clock(
+        normalization: TimestampNormalization,
+        arrival_step: Duration,
+        publisher_dts_nano: impl IntoIterator<Item = u64>,
+    ) -> (Vec<MediaFrame>, Vec<i64>, TimestampNormalizationStats) {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_timestamp_normalization(&stream_id().app, normalization);
+        let mut notifications = stream_center.subscribe_notifications();
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::audio_only(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(audio_config_of_track(0)).await.unwrap();
+        let start = std::time::Instant::now();
+        for (index, dts_nano) in publisher_dts_nano.into_iter().enumerate() {
+            let mut frame = audio_frame(0);
+            frame.set_decode_timestamp_ns(dts_nano);
+            frame.set_ingest_time(start + arrival_step * index as u32);
+            media_sender.send(frame).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        let frames = drain(&mut response.media_receiver)
+            .await
+            .into_iter()
+            .filter(|frame| frame.is_audio() && !frame.is_sequence_header())
+            .collect();
+        let mut jumps = vec![];
+        while let Ok(notification) = notifications.try_recv() {
+            if let StreamNotification::TimestampDiscontinuity { jump_nano, .. } = notification {
+                jumps.push(jump_nano);
+            }
+        }
+        (frames, jumps, description.timestamp_normalization)
+    }
+
+    fn publisher_timestamps_ns(frames: &[MediaFrame]) -> Vec<Option<u64>> {
+        frames
+            .iter()
+            .map(|frame| match frame {
+                MediaFrame::Audio { frame_info, .. } => frame_info.publisher_timestamp_nano,
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn a_drifting_publisher_clock_is_slewed_to_the_server_clock() {
+        const SECOND_NANO: u64 = 1_000_000_000;
+        const FRAMES: u64 = 601;
+        // 50ppm fast, 30ms ahead after 10 minutes, well within the slew
+        let publisher_dts: Vec<_> = (0..FRAMES).map(|index| index * 1_000_050_000).collect();
+        let (frames, jumps, stats) = publish_audio_on_clock(
+            TimestampNormalization::wallclock(),
+            Duration::from_secs(1),
+            publisher_dts.clone(),
+        )
+        .await;
+        assert!(jumps.is_empty());
+        let expected: Vec<_> = (0..FRAMES).map(|index| index * SECOND_NANO).collect();
+        assert_eq!(media_dts_ns(&frames), expected);
+        assert!(frames.iter().all(|frame| !frame.is_discontinuity()));
+        // the timestamps of the publisher are kept
+        assert_eq!(
+            publisher_timestamps_ns(&frames),
+            publisher_dts.into_iter().map(Some).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            stats,
+            TimestampNormalizationStats {
+                discontinuities: 0,
+                correction_nano: -30_000_000,
+                total_correction_nano: 30_000_000,
+            }
+        );
+
+        // 1% fast, the correction is held to 5ms a second of media
+        let (frames, _, stats) = publish_audio_on_clock(
+            TimestampNormalization::wallclock(),
+            Duration::from_secs(1),
+            (0..FRAMES).map(|index| index * 1_010_000_000),
+        )
+        .await;
+        let dts = media_dts_ns(&frames);
+        assert!(
+            dts.windows(2)
+                .all(|pair| pair[1] - pair[0] == 1_004_950_000)
+        );
+        assert_eq!(stats.correction_nano, -((FRAMES - 1) as i64) * 5_050_000);
+        assert_eq!(stats.total_correction_nano, (FRAMES - 1) * 5_050_000);
+    }
+
+    #[tokio::test]
+    async fn a_publisher_clock_jumping_back_an_hour_is_anchored_again_once() {
+        const STEP_NANO: u64 = 40_000_000;
+        const HOUR_NANO: u64 = 3_600_000_000_000;
+        const FRAMES_BEFORE_JUMP: u64 = 50;
+        let publisher_dts: Vec<_> = (0..FRAMES_BEFORE_JUMP * 2)
+            .map(|index| match index < FRAMES_BEFORE_JUMP {
+                true => HOUR_NANO + index * STEP_NANO,
+                false => (index - FRAMES_BEFORE_JUMP) * STEP_NANO,
+            })
+            .collect();
+        let (frames, jumps, stats) = publish_audio_on_clock(
+            TimestampNormalization::wallclock(),
+            Duration::from_nanos(STEP_NANO),
+            publisher_dts.clone(),
+        )
+        .await;
+        // the frames after the jump follow on the ones before
+        let expected: Vec<_> = (0..FRAMES_BEFORE_JUMP * 2)
+            .map(|index| HOUR_NANO + index * STEP_NANO)
+            .collect();
+        assert_eq!(media_dts_ns(&frames), expected);
+        let discontinuities: Vec<_> = frames
+            .iter()
+            .enumerate()
+            .filter(|(_, frame)| frame.is_discontinuity())
+            .map(|(index, _)| index as u64)
+            .collect();
+        assert_eq!(discontinuities, vec![FRAMES_BEFORE_JUMP]);
+        assert_eq!(
+            publisher_timestamps_ns(&frames)[FRAMES_BEFORE_JUMP as usize],
+            Some(0)
+        );
+        assert_eq!(
+            jumps,
+            vec![-((HOUR_NANO + FRAMES_BEFORE_JUMP * STEP_NANO) as i64)]
+        );
+        assert_eq!(stats.discontinuities, 1);
+        assert_eq!(stats.total_correction_nano, 0);
+
+        // left as they are if the app does not normalize them
+        let (frames, jumps, stats) = publish_audio_on_clock(
+            TimestampNormalization::Off,
+            Duration::from_nanos(STEP_NANO),
+            publisher_dts.clone(),
+        )
+        .await;
+        // the mix queue puts them back in decode order
+        let mut sorted = publisher_dts;
+        sorted.sort();
+        assert_eq!(media_dts_ns(&frames), sorted);
+        assert!(publisher_timestamps_ns(&frames).iter().all(Option::is_none));
+        assert!(jumps.is_empty());
+        assert_eq!(stats, TimestampNormalizationStats::default());
+    }
+
+    #[test]
+    fn parse_dvr_window() {
+        assert_eq!("off".parse::<DvrWindow>().unwrap(), DvrWindow::Off);
+        assert_eq!(
+            " 120:1048576 ".parse::<DvrWindow>().unwrap(),
+            DvrWindow::On {
+                duration: Duration::from_secs(120),
+                max_bytes: 1 << 20,
+            }
+        );
+        assert!(matches!(
+            "120".parse::<DvrWindow>().unwrap(),
+            DvrWindow::On { duration, .. } if duration == Duration::from_secs(120)
+        ));
+        for invalid in ["0", "-1", "120:", "120:0", "forever"] {
+            assert!(matches!(
+                invalid.parse::<DvrWindow>(),
+                Err(StreamCenterError::InvalidDvrWindow(_))
+            ));
+        }
+        let context = HashMap::from([(DVR_DELAY_KEY.to_owned(), "1.5".to_owned())]);
+        assert_eq!(
+            Timeshift::from_context(&context),
+            Some(Timeshift::Delay(Duration::from_millis(1500)))
+        );
+        assert_eq!(Timeshift::from_context(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn out_of_range_timeshifts_play_live_or_from_the_window_front() {
+        for huge in ["1e300", "inf", "NaN", "-5", "0"] {
+            let context = HashMap::from([(DVR_DELAY_KEY.to_owned(), huge.to_owned())]);
+            assert_eq!(Timeshift::from_context(&context), None, "{}", huge);
+        }
+        let context = HashMap::from([(DVR_DELAY_KEY.to_owned(), "1e15".to_owned())]);
+        let Some(delay) = Timeshift::from_context(&context) else {
+            panic!("a delay a duration holds is a timeshift");
+        };
+        let dvr = dvr_buffer_of(50, u64::MAX);
+        assert_eq!(
+            dvr.start_of(delay),
+            dvr.start_of(Timeshift::Delay(Duration::from_secs(1)))
+        );
+        assert_eq!(dvr.start_of(Timeshift::At(Duration::MAX)), None);
+    }
+
+    /// a window of a second, of 10 frames of 100 bytes a gop
+    fn dvr_buffer_of(frames: u64, max_bytes: u64) -> DvrBuffer {
+        let mut dvr = DvrBuffer::new(DvrWindow::On {
+            duration: Duration::from_secs(1),
+            max_bytes,
+        })
+        .unwrap();
+        dvr.append(video_config());
+        for index in 0..frames {
+            dvr.append(sized_video_frame(index, GOP_SIZE, 100));
+        }
+        dvr
+    }
+
+    #[test]
+    fn dvr_window_is_trimmed_by_gops_up_to_the_pinned_frame() {
+        // 2 seconds, the sequence header goes first
+        let mut dvr = dvr_buffer_of(50, u64::MAX);
+        let first_key_frame = 1;
+        dvr.trim(Some(first_key_frame));
+        assert_eq!(
+            dvr.get(first_key_frame).map(MediaFrame::is_video_key_frame),
+            Some(true)
+        );
+
+        let key_frame_at = |dvr: &DvrBuffer, seconds: f64| {
+            dvr.start_of(Timeshift::At(Duration::from_secs_f64(seconds)))
+                .and_then(|seq| dvr.get(seq))
+                .map(MediaFrame::get_decode_timestamp_ms)
+        };
+        assert_eq!(key_frame_at(&dvr, 0.5), Some(400));
+        // the last second is from the key frame at 1.2s on without the pin
+        dvr.trim(None);
+        assert!(dvr.get(first_key_frame).is_none());
+        assert_eq!(key_frame_at(&dvr, 0.5), Some(1200));
+        assert_eq!(key_frame_at(&dvr, 1.7), Some(1600));
+        // the headers trimmed off still go to a reader at the front
+        let front = dvr
+            .start_of(Timeshift::Delay(Duration::from_secs(10)))
+            .unwrap();
+        assert!(matches!(
+            dvr.headers_at(front).as_slice(),
+            [MediaFrame::VideoConfig { .. }]
+        ));
+        assert_eq!(dvr.start_of(Timeshift::Delay(Duration::ZERO)), None);
+
+        // a reader holding twice the byte cap has to go
+        let mut dvr = dvr_buffer_of(30, 1000);
+        dvr.trim(Some(first_key_frame));
+        assert!(dvr.is_pinned_over());
+        dvr.trim(None);
+        assert!(!dvr.is_pinned_over());
+        assert_eq!(dvr.bytes(), 1000);
+    }
+
+    #[tokio::test]
+    async fn timeshift_subscriber_crosses_from_the_dvr_window_into_live() {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_dvr_window(&stream_id().app, "60".parse().unwrap());
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        // 2 seconds of frames
+        send_av_frames(&media_sender, 0..50).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut timeshift = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id(),
+            &HashMap::from([(DVR_DELAY_KEY.to_owned(), "1".to_owned())]),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        let mut live = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        send_av_frames(&media_sender, 50..80).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+
+        let timeshift_frames = drain(&mut timeshift.media_receiver).await;
+        assert!(matches!(
+            timeshift_frames.first(),
+            Some(MediaFrame::VideoConfig { .. })
+        ));
+        // a second behind the last frame at 1.98s, the gop of the key frame at 0.8s on
+        let dts = media_dts_ns(&timeshift_frames);
+        let expected: Vec<_> = (20..80)
+            .flat_map(|index| {
+                [
+                    video_frame(index).get_decode_timestamp_ns(),
+                    audio_frame(index).get_decode_timestamp_ns(),
+                ]
+            })
+            .collect();
+        assert_eq!(dts, expected);
+        assert!(timeshift_frames[1].is_video_key_frame());
+
+        // the live subscriber ends on the same frames
+        let live_dts = media_dts_ns(&drain(&mut live.media_receiver).await);
+        assert_eq!(live_dts.last(), dts.last());
+        assert!(live_dts.first() > dts.first());
+    }
+
+    /// baseline, the ids and the qps at 0, cavlc
+    const PPS: [u8; 3] = [0xce, 0x3c, 0x80];
+
+    fn nal_unit(header: u8, body: &[u8]) -> NalUnit {
+        NalUnit {
+            header: NaluHeader::try_from(header).unwrap(),
+            body: Bytes::copy_from_slice(body),
+        }
+    }
+
+    fn sps_nal(sps_bytes: &[u8]) -> NalUnit {
+        nal_unit(0x67, sps_bytes)
+    }
+
+    fn pps_nal() -> NalUnit {
+        nal_unit(0x68, &PPS)
+    }
+
+    fn key_frame_with(index: u64, nal_units: Vec<NalUnit>) -> MediaFrame {
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                FrameType::KeyFrame,
+                MediaFrameTimestamp::with_timestamp_ms(index * FRAME_INTERVAL_MS),
+            ),
+            payload: VideoFrameUnit::H264 { nal_units },
+        }
+    }
+
+    fn nal_unit_types(frame: &MediaFrame) -> Vec<NALUType> {
+        match frame {
+            MediaFrame::Video {
+                payload: VideoFrameUnit::H264 { nal_units },
+                ..
+            } => nal_units
+                .iter()
+                .map(|nal| nal.header.nal_unit_type)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn repeated_parameter_sets_of_an_access_unit_are_dropped() {
+        let mut tracker = ParameterSetTracker::default();
+        let idr = nal_unit(0x65, &[0; 4]);
+        let mut frame = key_frame_with(
+            0,
+            vec![
+                sps_nal(&SPS_720P),
+                pps_nal(),
+                sps_nal(&SPS_720P),
+                pps_nal(),
+                idr.clone(),
+            ],
+        );
+        // the stream had no sequence header, the in band ones make it
+        let config = tracker.normalize(&mut frame);
+        assert_eq!(config.as_ref().and_then(video_config_height), Some(720));
+        assert_eq!(
+            nal_unit_types(&frame),
+            vec![NALUType::SPS, NALUType::PPS, NALUType::IDRSlice]
+        );
+
+        // the same ones again change nothing
+        let mut frame = key_frame_with(1, vec![sps_nal(&SPS_720P), pps_nal(), idr.clone()]);
+        assert!(tracker.normalize(&mut frame).is_none());
+        assert_eq!(nal_unit_types(&frame).len(), 3);
+
+        // another sps under the same id
+        let mut frame = key_frame_with(2, vec![sps_nal(&SPS_1080P), pps_nal(), idr]);
+        let config = tracker.normalize(&mut frame);
+        assert_eq!(config.as_ref().and_then(video_config_height), Some(1080));
+        assert_eq!(
+            config.map(|config| config.get_decode_timestamp_ns()),
+            Some(2 * FRAME_INTERVAL_MS * 1_000_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn parameter_set_changed_under_its_id_in_band_bumps_the_config_generation() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTSP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender
+            .send(video_config_with_sps(&SPS_720P, 0))
+            .await
+            .unwrap();
+        let mut subscriber = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::RTSP,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        let idr = || nal_unit(0x65, &[0; 4]);
+        for index in 0..GOP_SIZE * 2 {
+            let sps = if index < GOP_SIZE {
+                &SPS_720P[..]
+            } else {
+                &SPS_1080P[..]
+            };
+            let frame = if index.is_multiple_of(GOP_SIZE) {
+                key_frame_with(index, vec![sps_nal(sps), pps_nal(), idr()])
+            } else {
+                video_frame(index)
+            };
+            media_sender.send(frame).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        assert_eq!(description.config_generation, 1);
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+
+        let frames = drain(&mut subscriber.media_receiver).await;
+        let heights: Vec<_> = frames.iter().filter_map(video_config_height).collect();
+        assert_eq!(heights, vec![720, 1080]);
+        let new_header = frames
+            .iter()
+            .position(|frame| video_config_height(frame) == Some(1080))
+            .unwrap();
+        assert!(frames[new_header + 1].is_video_key_frame());
+        assert_eq!(
+            frames[new_header + 1].get_decode_timestamp_ms(),
+            GOP_SIZE * FRAME_INTERVAL_MS
+        );
+    }
+
+    #[test]
+    fn parameter_sets_are_placed_where_the_sink_wants_them() {
+        let chroma = ChromaFormatIdc::Chroma420;
+        let sps = Sps::try_from(&sps_nal(&SPS_720P)).unwrap();
+        let pps = Pps::try_from((chroma, &pps_nal())).unwrap();
+        let config = MediaFrame::VideoConfig {
+            timestamp_nano: 0,
+            config: Box::new(AvcDecoderConfigurationRecord::from((&sps, &pps)).into()),
+        };
+        let idr = || nal_unit(0x65, &[0; 4]);
+        let aud = nal_unit(0x09, &[0xf0]);
+
+        // rtp players get them in band before the IDRs
+        let mut in_band = ParameterSetPlacement::new(PlayProtocol::RTSP.into());
+        in_band.place(config.clone());
+        let frame = in_band.place(key_frame_with(0, vec![aud.clone(), idr()]));
+        assert_eq!(
+            nal_unit_types(&frame),
+            vec![
+                NALUType::AccessUnitDelimiter,
+                NALUType::SPS,
+                NALUType::PPS,
+                NALUType::IDRSlice
+            ]
+        );
+        let frame = in_band.place(key_frame_with(
+            1,
+            vec![sps_nal(&SPS_720P), pps_nal(), idr()],
+        ));
+        assert_eq!(nal_unit_types(&frame).len(), 3);
+        assert_eq!(nal_unit_types(&in_band.place(video_frame(1))).len(), 1);
+
+        // flv has them in the sequence header only
+        let with_parameter_sets = key_frame_with(0, vec![sps_nal(&SPS_720P), pps_nal(), idr()]);
+        let mut out_of_band = ParameterSetPlacement::new(PlayProtocol::HTTPFLV.into());
+        assert_eq!(
+            nal_unit_types(&out_of_band.place(with_parameter_sets.clone())),
+            vec![NALUType::IDRSlice]
+        );
+        assert_eq!(
+            ParameterSetCarriage::from(PlayProtocol::RTMP),
+            ParameterSetCarriage::OutOfBand
+        );
+        assert_eq!(
+            with_parameter_sets.to_flv_tag_bytes(4).unwrap(),
+            key_frame_with(0, vec![idr()]).to_flv_tag_bytes(4).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_backpressure_policy() {
+        assert_eq!(BackpressurePolicy::default(), BackpressurePolicy::Off);
+        assert_eq!(
+            "off".parse::<BackpressurePolicy>().unwrap(),
+            BackpressurePolicy::Off
+        );
+        assert_eq!(
+            " 0.5 : 0.2 : 10 ".parse::<BackpressurePolicy>().unwrap(),
+            BackpressurePolicy::On {
+                raise: 0.5,
+                clear: 0.2,
+                min_interval: Duration::from_secs(10),
+            }
+        );
+        for invalid in [
+            "0.5:0.2",
+            "0.2:0.5:10",
+            "1.5:0.2:10",
+            "0.5:0:10",
+            "0.5:0.2:-1",
+            "on",
+        ] {
+            assert!(matches!(
+                invalid.parse::<BackpressurePolicy>(),
+                Err(StreamCenterError::InvalidBackpressurePolicy(_))
+            ));
+        }
+    }
+
+    /// a second of 250 kB, 2000 kbps, with the drops of the frames
+    fn congestion_sample(sent: u64, dropped: u64) -> CongestionSample {
+        CongestionSample {
+            sent,
+            dropped,
+            ingest_bytes: 250_000,
+            duration: Duration::from_secs(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn backpressure_advisories_have_hysteresis() {
+        let mut advisor = BackpressureAdvisor::new(BackpressurePolicy::On {
+            raise: 0.5,
+            clear: 0.2,
+            min_interval: Duration::from_secs(10),
+        })
+        .unwrap();
+        let start = tokio::time::Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(advisor.observe(at(0), &congestion_sample(90, 10)), None);
+        assert_eq!(
+            advisor.observe(at(1), &congestion_sample(40, 60)),
+            Some(BackpressureAdvisory::Congested {
+                score: 0.6,
+                ingest_kbps: 2000,
+                suggested_kbps: 1400,
+            })
+        );
+        // not within the min interval however bad
+        assert_eq!(advisor.observe(at(2), &congestion_sample(0, 100)), None);
+        // between the scores, the stream stays congested and is not advised again
+        assert_eq!(advisor.observe(at(11), &congestion_sample(70, 30)), None);
+        assert!(advisor.is_congested());
+        // still congested after the interval, the suggestion is cut by half at most
+        assert_eq!(
+            advisor.observe(at(12), &congestion_sample(0, 100)),
+            Some(BackpressureAdvisory::Congested {
+                score: 1.0,
+                ingest_kbps: 2000,
+                suggested_kbps: 1000,
+            })
+        );
+        assert_eq!(advisor.observe(at(13), &congestion_sample(100, 0)), None);
+        assert_eq!(
+            advisor.observe(at(22), &congestion_sample(90, 10)),
+            Some(BackpressureAdvisory::Relieved { score: 0.1 })
+        );
+        assert!(!advisor.is_congested());
+        // below raise, a stream not congested is not advised
+        assert_eq!(advisor.observe(at(40), &congestion_sample(70, 30)), None);
+
+        // the fill of the queues counts without drops
+        let queued = CongestionSample {
+            mix_queue_fill: 0.8,
+            ..congestion_sample(100, 0)
+        };
+        assert!(matches!(
+            advisor.observe(at(50), &queued),
+            Some(BackpressureAdvisory::Congested { score: 0.8, .. })
+        ));
+        assert!(BackpressureAdvisor::new(BackpressurePolicy::Off).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn publishers_are_advised_of_a_filling_mix_queue() {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_idle_watchdog(&stream_id().app, IdleWatchdog::Off);
+        stream_center.set_backpressure_policy(&stream_id().app, "0.5:0.2:10".parse().unwrap());
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let mut publisher = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let media_sender = publisher.media_sender.clone();
+        media_sender.send(video_config()).await.unwrap();
+        // video only, the mix queue holds the frames waiting for audio
+        for index in 0..79 {
+            media_sender
+                .send(sized_video_frame(index, GOP_SIZE, 1000))
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(BACKPRESSURE_SAMPLE_INTERVAL).await;
+        assert!(publisher.backpressure_receiver.try_recv().is_err());
+        media_sender
+            .send(sized_video_frame(79, GOP_SIZE, 1000))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let Ok(BackpressureAdvisory::Congested {
+            score,
+            ingest_kbps,
+            suggested_kbps,
+        }) = publisher.backpressure_receiver.try_recv()
+        else {
+            panic!("the publisher is not advised");
+        };
+        assert_eq!(score, 0.8);
+        // 80 frames of 1000 bytes in the second
+        assert_eq!(ingest_kbps, 640);
+        assert_eq!(suggested_kbps, 384);
+
+        // the audio comes, the queue is mixed out
+        for index in 0..80 {
+            media_sender.send(audio_frame(index)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        send_av_frames(&media_sender, 80..81).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(matches!(
+            publisher.backpressure_receiver.try_recv(),
+            Ok(BackpressureAdvisory::Relieved { .. })
+        ));
+
+        // off by default
+        let mut other = StreamCenter::publish_kickable(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &StreamIdentity::new("vod", "other").unwrap(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        other.media_sender.send(video_config()).await.unwrap();
+        for index in 0..80 {
+            other
+                .media_sender
+                .send(sized_video_frame(index, GOP_SIZE, 1000))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(40)).await;
+        }
+        assert!(other.backpressure_receiver.try_recv().is_err());
+    }
+
+    /// 30000/1001 frames per second
+    const NTSC_FRAME_NANO: f64 = 1e9 * 1001.0 / 30000.0;
+
+    /// the dts of a video frame, off by up to 5ms either way as the clocks of encoders are
+    fn jittered_dts_nano(start_nano: u64, index: u64, frame_nano: f64) -> u64 {
+        let jitter_ms = (index * 7919 % 11) as i64 - 5;
+        (start_nano as f64 + index as f64 * frame_nano + jitter_ms as f64 * 1e6) as u64
+    }
+
+    fn video_frame_at_nano(index: u64, dts_nano: u64) -> MediaFrame {
+        let mut frame = video_frame(index);
+        frame.set_decode_timestamp_ns(dts_nano);
+        frame.set_presentation_timestamp_ns(dts_nano);
+        frame
+    }
+
+    fn audio_frame_at_nano(dts_nano: u64) -> MediaFrame {
+        let mut frame = audio_frame(0);
+        frame.set_decode_timestamp_ns(dts_nano);
+        frame
+    }
+
+    #[test]
+    fn frame_rate_of_jittered_frames_converges() {
+        let mut estimator = RateEstimator::default();
+        // 10 seconds, with a frame dropped now and then
+        for index in (0..300).filter(|index| index % 97 != 96) {
+            estimator.on_frame(&video_frame_at_nano(
+                index,
+                jittered_dts_nano(0, index, NTSC_FRAME_NANO),
+            ));
+        }
+        let frame_rate = estimator.stats().frame_rate.unwrap();
+        assert!(
+            (frame_rate - 29.97).abs() < 0.05,
+            "frame rate: {}",
+            frame_rate
+        );
+        assert!(estimator.stats().video_data_rate.unwrap() > 0.0);
+        assert_eq!(estimator.stats().audio_data_rate, None);
+    }
+
+    #[test]
+    fn rates_are_filled_once_settled_and_once_per_config() {
+        let mut estimator = RateEstimator::default();
+        let meta_data =
+            make_fake_on_meta_data(AudioCodecCommon::AAC, VideoCodecCommon::AVC, 0.0, 0.0);
+        let mut index = 0;
+        while (index as f64) * NTSC_FRAME_NANO < 2.9e9 {
+            estimator.on_frame(&video_frame_at_nano(
+                index,
+                jittered_dts_nano(0, index, NTSC_FRAME_NANO),
+            ));
+            index += 1;
+        }
+        assert!(estimator.fill(&meta_data).is_none());
+        for _ in 0..10 {
+            estimator.on_frame(&video_frame_at_nano(
+                index,
+                jittered_dts_nano(0, index, NTSC_FRAME_NANO),
+            ));
+            index += 1;
+        }
+        let filled = estimator.fill(&meta_data).unwrap();
+        assert!((filled.frame_rate.unwrap() - 29.97).abs() < 0.05);
+        assert!(filled.video_data_rate.is_some());
+        assert!(estimator.fill(&meta_data).is_none());
+        assert_eq!(
+            estimator.stats().estimated_fields,
+            vec![FRAME_RATE_FIELD, VIDEO_DATA_RATE_FIELD]
+        );
+
+        estimator.restart();
+        assert!(estimator.fill(&filled).is_none());
+        let start_nano = jittered_dts_nano(0, index, NTSC_FRAME_NANO);
+        for frame in 0..100 {
+            estimator.on_frame(&video_frame_at_nano(frame, start_nano + frame * 40_000_000));
+        }
+        let refilled = estimator.fill(&filled).unwrap();
+        assert!((refilled.frame_rate.unwrap() - 25.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn rates_told_by_the_publisher_are_not_estimated() {
+        let mut estimator = RateEstimator::default();
+        let mut meta_data =
+            make_fake_on_meta_data(AudioCodecCommon::AAC, VideoCodecCommon::AVC, 0.0, 0.0);
+        meta_data.frame_rate = Some(25.0);
+        // 0 tells nothing
+        meta_data.video_data_rate = Some(0.0);
+        estimator.on_publisher_meta_data(&meta_data);
+        for index in 0..150 {
+            let dts_nano = jittered_dts_nano(0, index, NTSC_FRAME_NANO);
+            estimator.on_frame(&video_frame_at_nano(index, dts_nano));
+            estimator.on_frame(&audio_frame_at_nano(dts_nano + 1_000_000));
+        }
+        let filled = estimator.fill(&meta_data).unwrap();
+        assert_eq!(filled.frame_rate, Some(25.0));
+        assert!(filled.video_data_rate.unwrap() > 0.0);
+        assert!(filled.audio_data_rate.unwrap() > 0.0);
+        assert_eq!(
+            estimator.stats().estimated_fields,
+            vec![VIDEO_DATA_RATE_FIELD, AUDIO_DATA_RATE_FIELD]
+        );
+
+        // the publisher tells the video bitrate later, the estimate is no longer used for it
+        meta_data.video_data_rate = Some(2500.0);
+        estimator.on_publisher_meta_data(&meta_data);
+        assert_eq!(
+            estimator.stats().estimated_fields,
+            vec![AUDIO_DATA_RATE_FIELD]
+        );
+    }
+
+    #[test]
+    fn nominal_frame_rate_of_the_sps_is_preferred_to_the_measured_one() {
+        // high 4.4 1080p by x264 with a vui, emulation prevention removed
+        let sps_bytes = [
+            0x64, 0x00, 0x2c, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0xc0, 0x44, 0x00, 0x00,
+            0x00, 0x04, 0x00, 0x00, 0x00, 0xc8, 0x3c, 0x60, 0xc6, 0x58,
+        ];
+        let mut config = video_config_with_sps(&sps_bytes, 0);
+        let MediaFrame::VideoConfig { config: video, .. } = &mut config else {
+            unreachable!()
+        };
+        let VideoConfig::H264(H264VideoConfig { sps: Some(sps), .. }) = video.as_mut() else {
+            unreachable!()
+        };
+        sps.vui_parameters.as_mut().unwrap().timing_info = Some(TimingInfo {
+            num_units_in_tick: 1001,
+            time_scale: 60000,
+            fixed_frame_rate_flag: true,
+        });
+
+        let mut estimator = RateEstimator::default();
+        estimator.on_frame(&config);
+        let meta_data =
+            make_fake_on_meta_data(AudioCodecCommon::AAC, VideoCodecCommon::AVC, 0.0, 0.0);
+        // ms quantized timestamps, measured they are off the nominal rate a little
+        for index in 0..120 {
+            estimator.on_frame(&video_frame_at_nano(
+                index,
+                (index as f64 * NTSC_FRAME_NANO / 1e6).round() as u64 * 1_000_000,
+            ));
+        }
+        let filled = estimator.fill(&meta_data).unwrap();
+        assert_eq!(filled.frame_rate, Some(29.97));
+        let frame_rate = estimator.stats().frame_rate.unwrap();
+        assert!(
+            (frame_rate - 30000.0 / 1001.0).abs() < 1e-9,
+            "{}",
+            frame_rate
+        );
+
+        // the nominal rate is of the sequence header, it stays over a restart
+        estimator.restart();
+        assert_eq!(
+            estimator
+                .stats()
+                .frame_rate
+                .map(|rate| (rate * 100.0).round()),
+            Some(2997.0)
+        );
+        // a sequence header without timing info leaves the rate to be measured
+        estimator.on_frame(&video_config());
+        assert_eq!(estimator.stats().frame_rate, None);
+    }
+
+    #[tokio::test]
+    async fn estimated_rates_are_sent_in_the_metadata_once() {
+        let event_sender = start_stream_center();
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(script_frame()).await.unwrap();
+        media_sender.send(video_config()).await.unwrap();
+        media_sender.send(audio_config_of_track(0)).await.unwrap();
+        // 6 seconds
+        for index in 0..180 {
+            let dts_nano = jittered_dts_nano(0, index, NTSC_FRAME_NANO);
+            media_sender
+                .send(video_frame_at_nano(index, dts_nano))
+                .await
+                .unwrap();
+            media_sender
+                .send(audio_frame_at_nano(dts_nano + 1_000_000))
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let description = StreamCenter::describe(&event_sender, &stream_id())
+            .await
+            .unwrap();
+        StreamCenter::unpublish(&event_sender, &stream_id())
+            .await
+            .unwrap();
+
+        let estimate = description.rate_estimate;
+        let frame_rate = estimate.frame_rate.unwrap();
+        assert!(
+            (frame_rate - 29.97).abs() < 0.05,
+            "frame rate: {}",
+            frame_rate
+        );
+        assert_eq!(
+            estimate.estimated_fields,
+            vec![
+                FRAME_RATE_FIELD,
+                VIDEO_DATA_RATE_FIELD,
+                AUDIO_DATA_RATE_FIELD
+            ]
+        );
+        let meta_data = description.meta_data.unwrap();
+        assert!((meta_data.frame_rate.unwrap() - 29.97).abs() < 0.05);
+        assert!(meta_data.video_data_rate.is_some());
+        assert!(meta_data.audio_data_rate.is_some());
+
+        let filled: Vec<_> = drain(&mut response.media_receiver)
+            .await
+            .into_iter()
+            .filter(|frame| frame.is_script() && meta_data_of(frame).frame_rate.is_some())
+            .collect();
+        assert_eq!(filled.len(), 1, "{:?}", filled);
+        // sent once the rates settled, 3 seconds into the stream
+        let sent_at = filled[0].get_decode_timestamp_ns();
+        assert!(
+            (3_000_000_000..3_100_000_000).contains(&sent_at),
+            "{}",
+            sent_at
+        );
+    }
+
+    #[test]
+    fn parse_slice_integrity() {
+        assert_eq!(
+            "tag".parse::<SliceIntegrity>().unwrap(),
+            SliceIntegrity::Tag
+        );
+        assert_eq!(
+            " drop ".parse::<SliceIntegrity>().unwrap(),
+            SliceIntegrity::Drop
+        );
+        assert_eq!(
+            "off".parse::<SliceIntegrity>().unwrap(),
+            SliceIntegrity::Off
+        );
+        assert!(matches!(
+            "conceal".parse::<SliceIntegrity>(),
+            Err(StreamCenterError::InvalidSliceIntegrity(_))
+        ));
+    }
+
+    /// SPS_720P is 80x45 macroblocks, sliced in three
+    const SLICE_STARTS: [u64; 3] = [0, 1200, 2400];
+
+    fn write_ue(
+        writer: &mut bitstream_io::BitWriter<&mut Vec<u8>, bitstream_io::BigEndian>,
+        value: u64,
+    ) {
+        use bitstream_io::BitWrite;
+        let bits = 64 - (value + 1).leading_zeros();
+        writer.write_var(bits - 1, 0_u64).unwrap();
+        writer.write_var(bits, value + 1).unwrap();
+    }
+
+    /// the pps 0 of the sps 0, cavlc without weighted prediction
+    fn video_config_with_slices() -> MediaFrame {
+        let mut reader = bitstream_io::BitReader::endian(&SPS_720P[..], bitstream_io::BigEndian);
+        let sps = Sps::read_from(&mut reader).unwrap();
+        let pps = Pps::try_from((
+            ChromaFormatIdc::Chroma420,
+            &NalUnit {
+                header: NaluHeader::try_from(0x68).unwrap(),
+                body: Bytes::from_static(&[0xce, 0x3c, 0x80]),
+            },
+        ))
+        .unwrap();
+        MediaFrame::VideoConfig {
+            timestamp_nano: 0,
+            config: Box::new(VideoConfig::H264(H264VideoConfig {
+                sps: Some(sps),
+                pps: Some(pps),
+                sps_ext: None,
+                avc_decoder_configuration_record: None,
+            })),
+        }
+    }
+
+    /// an idr every GOP_SIZE frames and p frames between, of the slices starting at `starts`
+    fn sliced_video_frame(index: u64, starts: &[u64]) -> MediaFrame {
+        use bitstream_io::BitWrite;
+        let idr = index.is_multiple_of(GOP_SIZE);
+        let (nal_header, slice_type, frame_type) = if idr {
+            (0x65, 7, FrameType::KeyFrame)
+        } else {
+            (0x41, 5, FrameType::CodedFrames)
+        };
+        let nal_units = starts
+            .iter()
+            .map(|first_mb| {
+                let mut body = Vec::new();
+                let mut writer =
+                    bitstream_io::BitWriter::endian(&mut body, bitstream_io::BigEndian);
+                write_ue(&mut writer, *first_mb);
+                write_ue(&mut writer, slice_type);
+                write_ue(&mut writer, 0);
+                // frame_num, log2_max_frame_num is 4
+                writer.write::<4, u64>(index % GOP_SIZE).unwrap();
+                if idr {
+                    write_ue(&mut writer, 0);
+                }
+                // the rest of the slice
+                writer.write::<16, u16>(0xABCD).unwrap();
+                writer.write_bit(true).unwrap();
+                writer.byte_align().unwrap();
+                NalUnit {
+                    header: NaluHeader::try_from(nal_header).unwrap(),
+                    body: Bytes::from(body),
+                }
+            })
+            .collect();
+        MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                frame_type,
+                MediaFrameTimestamp::with_timestamp_ms(index * FRAME_INTERVAL_MS),
+            ),
+            payload: VideoFrameUnit::H264 { nal_units },
+        }
+    }
+
+    /// the frame 4 misses its middle slice
+    async fn send_sliced_frames(sender: &mpsc::Sender<MediaFrame>, indexes: std::ops::Range<u64>) {
+        for index in indexes {
+            let starts: &[u64] = if index == 4 {
+                &[SLICE_STARTS[0], SLICE_STARTS[2]]
+            } else {
+                &SLICE_STARTS
+            };
+            sender
+                .send(sliced_video_frame(index, starts))
+                .await
+                .unwrap();
+            sender.send(audio_frame(index)).await.unwrap();
+        }
+    }
+
+    /// publishes a stream of sliced frames under the policy, the video frames a subscriber gets are returned
+    async fn publish_with_corrupt_frame(integrity: SliceIntegrity) -> Vec<MediaFrame> {
+        let mut stream_center = StreamCenter::new();
+        stream_center.set_slice_integrity(&stream_id().app, integrity);
+        let event_sender = stream_center.get_event_sender();
+        tokio::spawn(async move {
+            let _ = stream_center.run().await;
+        });
+        let media_sender = StreamCenter::publish(
+            &event_sender,
+            PublishProtocol::RTMP,
+            &stream_id(),
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        media_sender.send(video_config_with_slices()).await.unwrap();
+        send_sliced_frames(&media_sender, 0..2).await;
+        // the subscription would go before the frames still queued
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut response = StreamCenter::subscribe(
+            &event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id(),
+            &HashMap::new(),
+            MediaSelection::video_only(),
+        )
+        .await
+        .unwrap();
+        send_sliced_frames(&media_sender, 2..GOP_SIZE + 3).await;
+        drain(&mut response.media_receiver)
+            .await
+            .into_iter()
+            .filter(|frame| frame.is_video() && !frame.is_sequence_header())
+            .collect()
+    }
+
+    fn frame_indexes(frames: &[MediaFrame]) -> Vec<u64> {
+        frames
+            .iter()
+            .map(|frame| frame.get_decode_timestamp_ms() / FRAME_INTERVAL_MS)
+            .collect()
+    }
+
+    fn corrupt_frame_indexes(frames: &[MediaFrame]) -> Vec<u64> {
+        frames
+            .iter()
+            .filter(
+                |frame| matches!(frame, MediaFrame::Video { frame_info, .. } if frame_info.corrupt),
+            )
+            .map(|frame| frame.get_decode_timestamp_ms() / FRAME_INTERVAL_MS)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn access_units_missing_slices_are_tagged() {
+        let frames = publish_with_corrupt_frame(SliceIntegrity::Tag).await;
+        assert_eq!(
+            frame_indexes(&frames),
+            (0..GOP_SIZE + 3).collect::<Vec<_>>()
+        );
+        assert_eq!(corrupt_frame_indexes(&frames), [4]);
+    }
+
+    #[tokio::test]
+    async fn access_units_missing_slices_are_dropped_until_the_next_key_frame() {
+        let frames = publish_with_corrupt_frame(SliceIntegrity::Drop).await;
+        let expected: Vec<_> = (0..4).chain(GOP_SIZE..GOP_SIZE + 3).collect();
+        assert_eq!(frame_indexes(&frames), expected);
+        assert!(corrupt_frame_indexes(&frames).is_empty());
+    }
+
+    #[tokio::test]
+    async fn access_units_are_not_checked_if_integrity_is_off() {
+        let frames = publish_with_corrupt_frame(SliceIntegrity::Off).await;
+        assert_eq!(
+            frame_indexes(&frames),
+            (0..GOP_SIZE + 3).collect::<Vec<_>>()
+        );
+        assert!(corrupt_frame_indexes(&frames).is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{errors::StreamCenterError, gop::MediaFrame};
+
+pub const DEFAULT_MAX_SLEW_PER_SEC: Duration = Duration::from_millis(5);
+pub const DEFAULT_DISCONTINUITY_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// how the timestamps of the publisher are mapped to the timeline of the stream.
+/// the audio and video frames of the stream are compared with the one before, of either kind,
+/// so the threshold must be above how far apart the publisher interleaves them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampNormalization {
+    /// the timestamps of the publisher are sent as they are
+    #[default]
+    Off,
+    /// the first frame anchors the timestamps of the publisher to the monotonic clock of the server,
+    /// the drift between the two is corrected by moving the timestamps up to max_slew_per_sec per second of media.
+    /// a jump of the timestamps beyond the threshold, backwards or ahead of the clock, anchors them again
+    /// right after the frame before, the frame is marked as a discontinuity
+    Wallclock {
+        max_slew_per_sec: Duration,
+        discontinuity_threshold: Duration,
+    },
+}
+
+impl TimestampNormalization {
+    pub fn wallclock() -> Self {
+        Self::Wallclock {
+            max_slew_per_sec: DEFAULT_MAX_SLEW_PER_SEC,
+            discontinuity_threshold: DEFAULT_DISCONTINUITY_THRESHOLD,
+        }
+    }
+}
+
+/// parses `off`, `wallclock` or `wallclock:<max slew ms per second>:<discontinuity threshold ms>`
+impl FromStr for TimestampNormalization {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalization = s.trim();
+        let invalid =
+            || StreamCenterError::InvalidTimestampNormalization(normalization.to_string());
+        match normalization.split(':').collect::<Vec<_>>()[..] {
+            ["off"] => Ok(Self::Off),
+            ["wallclock"] => Ok(Self::wallclock()),
+            ["wallclock", slew_ms, threshold_ms] => {
+                let slew_ms = slew_ms.trim().parse::<u64>().map_err(|_| invalid())?;
+                let threshold_ms = threshold_ms
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|ms| *ms > 0)
+                    .ok_or_else(invalid)?;
+                // a second may not be slewed by more than it lasts
+                if slew_ms >= 1000 {
+                    return Err(invalid());
+                }
+                Ok(Self::Wallclock {
+                    max_slew_per_sec: Duration::from_millis(slew_ms),
+                    discontinuity_threshold: Duration::from_millis(threshold_ms),
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// how far the timestamps of a stream were moved off the ones of its publisher
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TimestampNormalizationStats {
+    /// the times the timestamps were anchored again after a jump
+    pub discontinuities: u64,
+    /// the drift correction the frames get now, positive if the clock of the publisher is slow
+    pub correction_nano: i64,
+    /// the correction slewed in either direction so far
+    pub total_correction_nano: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    publisher_dts_nano: u64,
+    arrival: Instant,
+    /// from the timestamps of the publisher to the timeline of the stream, before the drift correction
+    offset_nano: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LastFrame {
+    publisher_dts_nano: u64,
+    arrival: Instant,
+    dts_nano: u64,
+}
+
+/// maps the timestamps of the publisher of a stream as the normalization says
+#[derive(Debug, Default)]
+pub(crate) struct TimestampNormalizer {
+    normalization: TimestampNormalization,
+    anchor: Option<Anchor>,
+    last: Option<LastFrame>,
+    correction_nano: i64,
+    stats: TimestampNormalizationStats,
+}
+
+impl TimestampNormalizer {
+    pub(crate) fn new(normalization: TimestampNormalization) -> Self {
+        Self {
+            normalization,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn stats(&self) -> TimestampNormalizationStats {
+        TimestampNormalizationStats {
+            correction_nano: self.correction_nano,
+            ..self.stats
+        }
+    }
+
+    /// rewrites the timestamps of the frame, the ones of the publisher are kept in its info.
+    /// the frame arrived at its ingest time, or now if it has none.
+    /// returns how far the timestamps jumped off the clock if the frame is a discontinuity
+    pub(crate) fn normalize(&mut self, frame: &mut MediaFrame) -> Option<i64> {
+        let TimestampNormalization::Wallclock {
+            max_slew_per_sec,
+            discontinuity_threshold,
+        } = self.normalization
+        else {
+            return None;
+        };
+        if !matches!(frame, MediaFrame::Video { .. } | MediaFrame::Audio { .. }) {
+            // the sequence headers and the like ahead of the first frame are left as they are
+            if let Some(anchor) = self.anchor {
+                frame.shift_timestamps_ns(anchor.offset_nano + self.correction_nano);
+            }
+            return None;
+        }
+        let publisher_dts_nano = frame.get_decode_timestamp_ns();
+        let arrival = frame.get_ingest_time().unwrap_or_else(Instant::now);
+        frame.keep_publisher_timestamps();
+        let mut jump_nano = None;
+        match (self.anchor, self.last) {
+            (Some(anchor), Some(last)) => {
+                let publisher_step = publisher_dts_nano as i64 - last.publisher_dts_nano as i64;
+                let arrival_step = arrival.duration_since(last.arrival).as_nanos() as i64;
+                let threshold = discontinuity_threshold.as_nanos() as i64;
+                if publisher_step < -threshold || publisher_step - arrival_step > threshold {
+                    let dts_nano = last.dts_nano.saturating_add(arrival_step as u64);
+                    self.anchor = Some(Anchor {
+                        publisher_dts_nano,
+                        arrival,
+                        offset_nano: dts_nano as i64 - publisher_dts_nano as i64,
+                    });
+                    self.correction_nano = 0;
+                    self.stats.discontinuities += 1;
+                    frame.set_discontinuity();
+                    jump_nano = Some(publisher_step - arrival_step);
+                } else if publisher_step > 0 {
+                    self.slew(
+                        anchor,
+                        publisher_dts_nano,
+                        arrival,
+                        publisher_step,
+                        max_slew_per_sec,
+                    );
+                }
+            }
+            _ => {
+                self.anchor = Some(Anchor {
+                    publisher_dts_nano,
+                    arrival,
+                    offset_nano: 0,
+                });
+            }
+        }
+        let offset_nano = self.anchor.map_or(0, |anchor| anchor.offset_nano) + self.correction_nano;
+        frame.shift_timestamps_ns(offset_nano);
+        self.last = Some(LastFrame {
+            publisher_dts_nano,
+            arrival,
+            dts_nano: frame.get_decode_timestamp_ns(),
+        });
+        jump_nano
+    }
+
+    /// moves the correction towards how far the clock of the publisher drifted since the anchor,
+    /// by the max slew for the media the step lasts at most, so the timestamps never go backwards
+    fn slew(
+        &mut self,
+        anchor: Anchor,
+        publisher_dts_nano: u64,
+        arrival: Instant,
+        publisher_step_nano: i64,
+        max_slew_per_sec: Duration,
+    ) {
+        let drift_nano = arrival.duration_since(anchor.arrival).as_nanos() as i64
+            - (publisher_dts_nano as i64 - anchor.publisher_dts_nano as i64);
+        let max_step_nano = (publisher_step_nano as i128 * max_slew_per_sec.as_nanos() as i128
+            / Duration::from_secs(1).as_nanos() as i128) as i64;
+        let step_nano = (drift_nano - self.correction_nano).clamp(-max_step_nano, max_step_nano);
+        self.correction_nano += step_nano;
+        self.stats.total_correction_nano += step_nano.unsigned_abs();
+    }
+}
@@ -0,0 +1,270 @@
+use std::{collections::VecDeque, fmt, sync::Arc, time::Instant};
+
+use codec_common::video::VideoFrameUnit;
+use dashmap::DashMap;
+use serde::Serialize;
+use utils::traits::dynamic_sized_packet::DynamicSizedPacket;
+use uuid::Uuid;
+
+use crate::{gop::MediaFrame, identity::StreamIdentity, stream_source::PublishProtocol};
+
+pub const DEFAULT_TRACE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceFrameKind {
+    Video,
+    Audio,
+    VideoConfig,
+    AudioConfig,
+    AudioChannelConfig,
+    Script,
+    Data,
+    EndOfStream,
+    SourceError,
+}
+
+impl From<&MediaFrame> for TraceFrameKind {
+    fn from(value: &MediaFrame) -> Self {
+        match value {
+            MediaFrame::Video { .. } => Self::Video,
+            MediaFrame::Audio { .. } => Self::Audio,
+            MediaFrame::VideoConfig { .. } => Self::VideoConfig,
+            MediaFrame::AudioConfig { .. } => Self::AudioConfig,
+            MediaFrame::AudioChannelConfig { .. } => Self::AudioChannelConfig,
+            MediaFrame::Script { .. } => Self::Script,
+            MediaFrame::Data { .. } => Self::Data,
+            MediaFrame::EndOfStream { .. } => Self::EndOfStream,
+            MediaFrame::SourceError { .. } => Self::SourceError,
+        }
+    }
+}
+
+/// what happened to a frame at one of the pipeline points, timestamps are in milliseconds
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    FrameReceived {
+        protocol: PublishProtocol,
+        kind: TraceFrameKind,
+        key_frame: bool,
+        size: usize,
+        pts_ms: u64,
+        dts_ms: u64,
+    },
+    MixQueueEnqueued {
+        kind: TraceFrameKind,
+        dts_ms: u64,
+        queued: usize,
+    },
+    MixQueueDropped {
+        kind: TraceFrameKind,
+        dts_ms: u64,
+        reason: String,
+    },
+    SentToSubscriber {
+        subscriber: Uuid,
+        kind: TraceFrameKind,
+        dts_ms: u64,
+        success: bool,
+    },
+    SequenceHeaderRefresh {
+        kind: TraceFrameKind,
+        config_generation: u64,
+    },
+    /// a new publisher kicked the previous one
+    PublisherTakeover {
+        protocol: PublishProtocol,
+        previous_idle_ms: u64,
+    },
+    /// the idle watchdog found the publisher sending nothing
+    PublisherStalled { idle_ms: u64 },
+    /// a stalled publisher sent audio or video again
+    PublisherResumed { stalled_ms: u64 },
+    /// the timestamps of the publisher jumped and were anchored again
+    TimestampDiscontinuity { jump_ms: i64 },
+}
+
+impl TraceEvent {
+    pub fn frame_received(protocol: PublishProtocol, frame: &MediaFrame) -> Self {
+        let size = match frame {
+            MediaFrame::Video {
+                payload: VideoFrameUnit::H264 { nal_units },
+                ..
+            } => nal_units
+                .iter()
+                .map(|nalu| nalu.get_packet_bytes_count())
+                .sum(),
+            MediaFrame::Video {
+                payload: VideoFrameUnit::AV1 { obus },
+                ..
+            } => obus.len(),
+            MediaFrame::Audio { payload, .. }
+            | MediaFrame::Script { payload, .. }
+            | MediaFrame::Data { payload, .. } => payload.len(),
+            MediaFrame::VideoConfig { .. }
+            | MediaFrame::AudioConfig { .. }
+            | MediaFrame::AudioChannelConfig { .. }
+            | MediaFrame::EndOfStream { .. }
+            | MediaFrame::SourceError { .. } => 0,
+        };
+        Self::FrameReceived {
+            protocol,
+            kind: frame.into(),
+            key_frame: frame.is_video_key_frame(),
+            size,
+            pts_ms: frame.get_presentation_timestamp_ms(),
+            dts_ms: frame.get_decode_timestamp_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceRecord {
+    /// counts every event of the stream, gaps at the front mean older events are overwritten
+    pub seq: u64,
+    /// monotonic, since the tracer is created
+    pub elapsed_us: u64,
+    #[serde(flatten)]
+    pub event: TraceEvent,
+}
+
+/// sink of the pipeline events, injected into the stream center
+pub trait PipelineTracer: fmt::Debug + Send + Sync {
+    fn record(&self, stream_id: &StreamIdentity, event: TraceEvent);
+    fn snapshot(&self, stream_id: &StreamIdentity) -> Option<Vec<TraceRecord>>;
+    fn remove(&self, stream_id: &StreamIdentity);
+}
+
+/// keeps the latest `capacity` records, the oldest one is overwritten when full
+#[derive(Debug)]
+pub struct TraceRing {
+    capacity: usize,
+    next_seq: u64,
+    records: VecDeque<TraceRecord>,
+}
+
+impl TraceRing {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            capacity,
+            next_seq: 0,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, elapsed_us: u64, event: TraceEvent) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(TraceRecord {
+            seq: self.next_seq,
+            elapsed_us,
+            event,
+        });
+        self.next_seq += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// oldest first
+    pub fn records(&self) -> Vec<TraceRecord> {
+        self.records.iter().cloned().collect()
+    }
+}
+
+/// one ring buffer per stream
+#[derive(Debug)]
+pub struct RingBufferTracer {
+    capacity: usize,
+    start_time: Instant,
+    rings: DashMap<StreamIdentity, TraceRing>,
+}
+
+impl RingBufferTracer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            start_time: Instant::now(),
+            rings: DashMap::new(),
+        }
+    }
+}
+
+impl Default for RingBufferTracer {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRACE_CAPACITY)
+    }
+}
+
+impl PipelineTracer for RingBufferTracer {
+    fn record(&self, stream_id: &StreamIdentity, event: TraceEvent) {
+        let elapsed_us = self.start_time.elapsed().as_micros() as u64;
+        if let Some(mut ring) = self.rings.get_mut(stream_id) {
+            ring.push(elapsed_us, event);
+            return;
+        }
+        self.rings
+            .entry(stream_id.clone())
+            .or_insert_with(|| TraceRing::new(self.capacity))
+            .push(elapsed_us, event);
+    }
+
+    fn snapshot(&self, stream_id: &StreamIdentity) -> Option<Vec<TraceRecord>> {
+        self.rings.get(stream_id).map(|ring| ring.records())
+    }
+
+    fn remove(&self, stream_id: &StreamIdentity) {
+        self.rings.remove(stream_id);
+    }
+}
+
+/// what the pipeline holds, tracing is off if no tracer is set.
+/// events are built lazily so nothing is allocated or formatted when off
+#[derive(Debug, Clone, Default)]
+pub struct TraceHandle {
+    tracer: Option<Arc<dyn PipelineTracer>>,
+}
+
+impl TraceHandle {
+    pub fn new(tracer: Arc<dyn PipelineTracer>) -> Self {
+        Self {
+            tracer: Some(tracer),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self { tracer: None }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    #[inline(always)]
+    pub fn record<F: FnOnce() -> TraceEvent>(&self, stream_id: &StreamIdentity, event: F) {
+        if let Some(tracer) = &self.tracer {
+            tracer.record(stream_id, event());
+        }
+    }
+
+    pub fn snapshot(&self, stream_id: &StreamIdentity) -> Option<Vec<TraceRecord>> {
+        self.tracer
+            .as_ref()
+            .and_then(|tracer| tracer.snapshot(stream_id))
+    }
+
+    pub fn remove(&self, stream_id: &StreamIdentity) {
+        if let Some(tracer) = &self.tracer {
+            tracer.remove(stream_id);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::{str::FromStr, time::Duration};
+
+use crate::errors::StreamCenterError;
+
+pub const DEFAULT_STALL_AFTER: Duration = Duration::from_secs(10);
+pub const DEFAULT_REAP_AFTER: Duration = Duration::from_secs(30);
+
+/// what to do with a stream whose publisher stays connected but sends no audio or video,
+/// e.g., a mobile publisher on a dead link whose tcp session has not timed out yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleWatchdog {
+    /// the stream stays registered however long its publisher is silent
+    Off,
+    /// silent for stall_after, the subscribers get an end of stream, or the frames of the backup of the stream,
+    /// and the publisher is reported stalled,
+    /// silent for reap_after, the stream is unpublished and its publisher disconnected
+    On {
+        stall_after: Duration,
+        reap_after: Duration,
+    },
+}
+
+impl Default for IdleWatchdog {
+    fn default() -> Self {
+        Self::On {
+            stall_after: DEFAULT_STALL_AFTER,
+            reap_after: DEFAULT_REAP_AFTER,
+        }
+    }
+}
+
+/// parses `off` or `<stall seconds>:<reap seconds>`, the stream must stall before it is reaped
+impl FromStr for IdleWatchdog {
+    type Err = StreamCenterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let watchdog = s.trim();
+        if watchdog == "off" {
+            return Ok(Self::Off);
+        }
+        let (stall_secs, reap_secs) = watchdog
+            .split_once(':')
+            .and_then(|(stall, reap)| {
+                Some((
+                    stall.trim().parse::<u64>().ok()?,
+                    reap.trim().parse::<u64>().ok()?,
+                ))
+            })
+            .filter(|(stall, reap)| *stall > 0 && reap > stall)
+            .ok_or_else(|| StreamCenterError::InvalidIdleWatchdog(watchdog.to_string()))?;
+        Ok(Self::On {
+            stall_after: Duration::from_secs(stall_secs),
+            reap_after: Duration::from_secs(reap_secs),
+        })
+    }
+}
@@ -0,0 +1,33 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TestSupportError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("rtmp chunk message error: {0}")]
+    ChunkMessage(#[from] rtmp_formats::chunk::errors::ChunkMessageError),
+    #[error("flv error: {0}")]
+    Flv(#[from] flv_formats::errors::FLVError),
+    #[error("url error: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("rtsp message error: {0}")]
+    RtspMessage(#[from] rtsp_formats::errors::RtspMessageError),
+    #[error("sdp error: {0}")]
+    Sdp(#[from] sdp_formats::errors::SDPError),
+    #[error("h264 sdp error: {0}")]
+    H264Sdp(#[from] rtp_formats::codec::h264::paramters::errors::H264SDPError),
+    #[error("rtp error: {0}")]
+    Rtp(#[from] rtp_formats::errors::RtpError),
+    #[error("h264 codec error: {0}")]
+    H264Codec(#[from] codec_h264::errors::H264CodecError),
+    #[error("stream center error: {0}")]
+    StreamCenter(#[from] stream_center::errors::StreamCenterError),
+    #[error("http error: {0}")]
+    Http(String),
+    #[error("unexpected response: {0}")]
+    UnexpectedResponse(String),
+    #[error("timed out: {0}")]
+    Timeout(String),
+}
+
+pub type TestSupportResult<T> = Result<T, TestSupportError>;
@@ -0,0 +1,227 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use unified_io::channel::{ChannelIo, ChannelReceiver, ChannelSender, channel};
+
+/// how long a packet is held before being delivered
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Delay {
+    #[default]
+    None,
+    Fixed(Duration),
+    /// drawn for each packet, packets are reordered if the draws differ by more than their interval
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+}
+
+/// faults applied to the packets of one direction of a channel,
+/// the same config applied to the same packets gives the same result
+#[derive(Debug, Clone, Copy)]
+pub struct FaultConfig {
+    /// drops the nth, 2nth, ... packets
+    pub drop_every: Option<usize>,
+    /// sends the nth, 2nth, ... packets twice, dropped packets are not counted
+    pub duplicate_every: Option<usize>,
+    pub delay: Delay,
+    /// seeds the draws of `delay`
+    pub seed: u64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            drop_every: None,
+            duplicate_every: None,
+            delay: Delay::None,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+}
+
+impl FaultConfig {
+    pub fn is_faultless(&self) -> bool {
+        self.drop_every.is_none()
+            && self.duplicate_every.is_none()
+            && matches!(self.delay, Delay::None)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FaultStats {
+    relayed: AtomicUsize,
+    dropped: AtomicUsize,
+    duplicated: AtomicUsize,
+}
+
+impl FaultStats {
+    /// packets taken by the relay, including the dropped ones
+    pub fn relayed(&self) -> usize {
+        self.relayed.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn duplicated(&self) -> usize {
+        self.duplicated.load(Ordering::Relaxed)
+    }
+}
+
+/// xorshift64*, good enough to spread delays and reproducible across platforms
+#[derive(Debug)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        Self(if seed == 0 { 1 } else { seed })
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn delay(&mut self, delay: Delay) -> Duration {
+        match delay {
+            Delay::None => Duration::ZERO,
+            Delay::Fixed(duration) => duration,
+            Delay::Uniform { min, max } => {
+                let span = max.saturating_sub(min).as_micros() as u64;
+                if span == 0 {
+                    return min;
+                }
+                min + Duration::from_micros(self.next_u64() % (span + 1))
+            }
+        }
+    }
+}
+
+/// two ends of an in-memory connection like `ChannelIo::pair`,
+/// what the first end sends goes through `fault` before reaching the second end,
+/// the other direction is faultless
+pub fn faulty_pair(buffer: usize, fault: FaultConfig) -> (ChannelIo, ChannelIo, Arc<FaultStats>) {
+    let (a_tx, relay_rx) = channel(buffer);
+    let (relay_tx, b_rx) = channel(buffer);
+    let (b_tx, a_rx) = channel(buffer);
+    let stats: Arc<FaultStats> = Default::default();
+    tokio::spawn(relay(relay_rx, relay_tx, fault, stats.clone()));
+    (
+        ChannelIo::new(a_rx, a_tx),
+        ChannelIo::new(b_rx, b_tx),
+        stats,
+    )
+}
+
+async fn relay(
+    mut rx: ChannelReceiver,
+    mut tx: ChannelSender,
+    fault: FaultConfig,
+    stats: Arc<FaultStats>,
+) {
+    let mut rng = XorShift::new(fault.seed);
+    let mut delivered: usize = 0;
+    while let Some(packet) = rx.next().await {
+        // the reason the first end closed with reaches the second end
+        let packet = match packet {
+            Ok(packet) => packet,
+            Err(reason) => {
+                tx.close_with_reason(reason);
+                return;
+            }
+        };
+        let index = stats.relayed.fetch_add(1, Ordering::Relaxed) + 1;
+        if fault
+            .drop_every
+            .is_some_and(|n| n > 0 && index.is_multiple_of(n))
+        {
+            stats.dropped.fetch_add(1, Ordering::Relaxed);
+            tracing::trace!("fault injection dropped packet {}", index);
+            continue;
+        }
+        delivered += 1;
+        let copies = if fault
+            .duplicate_every
+            .is_some_and(|n| n > 0 && delivered.is_multiple_of(n))
+        {
+            stats.duplicated.fetch_add(1, Ordering::Relaxed);
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let delay = rng.delay(fault.delay);
+            if delay.is_zero() {
+                if tx.send(packet.clone()).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            let mut tx = tx.clone();
+            let packet = packet.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = tx.send(packet).await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::bytes::Bytes;
+
+    use super::{Delay, FaultConfig, XorShift, faulty_pair};
+
+    #[tokio::test]
+    async fn drops_and_duplicates_by_count() {
+        let (mut sender, mut receiver, stats) = faulty_pair(
+            64,
+            FaultConfig {
+                drop_every: Some(3),
+                duplicate_every: Some(4),
+                ..Default::default()
+            },
+        );
+        for i in 1..=12u8 {
+            sender.send(Bytes::from(vec![i])).await.unwrap();
+        }
+        drop(sender);
+        let mut received = vec![];
+        while let Some(Ok(packet)) = receiver.next().await {
+            received.push(packet[0]);
+        }
+        assert_eq!(received, vec![1, 2, 4, 5, 5, 7, 8, 10, 11, 11]);
+        assert_eq!(stats.relayed(), 12);
+        assert_eq!(stats.dropped(), 4);
+        assert_eq!(stats.duplicated(), 2);
+    }
+
+    #[test]
+    fn uniform_delays_are_reproducible_and_bounded() {
+        let delay = Delay::Uniform {
+            min: Duration::from_millis(1),
+            max: Duration::from_millis(5),
+        };
+        let seed = FaultConfig::default().seed;
+        let (mut a, mut b) = (XorShift::new(seed), XorShift::new(seed));
+        for _ in 0..100 {
+            let drawn = a.delay(delay);
+            assert_eq!(drawn, b.delay(delay));
+            assert!(drawn >= Duration::from_millis(1) && drawn <= Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use codec_common::{
+    FrameType, MediaFrameTimestamp,
+    video::{H264VideoConfig, VideoCodecCommon, VideoConfig, VideoFrameInfo, VideoFrameUnit},
+};
+use codec_h264::{
+    avc_decoder_configuration_record::AvcDecoderConfigurationRecord,
+    nalu::NalUnit,
+    nalu_header::NaluHeader,
+    nalu_type::NALUType,
+    pps::Pps,
+    sps::{Sps, chroma_format_idc::ChromaFormatIdc},
+};
+use flv_formats::{
+    header::FLVHeader,
+    tag::flv_tag_header::{FLVTagHeader, FLVTagType},
+};
+use stream_center::gop::MediaFrame;
+use tokio_util::bytes::{Buf, BufMut, Bytes};
+use utils::traits::{fixed_packet::FixedPacket, reader::ReadFrom, writer::WriteTo};
+
+use crate::errors::TestSupportResult;
+
+/// high profile, level 3.0
+pub const SPS: [u8; 26] = [
+    0x67, 0x64, 0x00, 0x1e, 0xac, 0xd9, 0x40, 0xd8, 0x3d, 0xe6, 0xf0, 0x11, 0x00, 0x00, 0x03, 0x00,
+    0x01, 0x00, 0x00, 0x03, 0x00, 0x30, 0x0f, 0x16, 0x2d, 0x96,
+];
+pub const PPS: [u8; 4] = [0x68, 0xef, 0x8f, 0xcb];
+
+pub const FRAME_INTERVAL_MS: u64 = 40;
+
+/// the first byte of a slice body, first_mb_in_slice is 0 so every slice starts an access unit
+pub(crate) const SLICE_HEADER_PREFIX: u8 = 0x88;
+/// the prefix and the frame index
+const FRAME_NAL_UNIT_MIN_BODY: usize = 5;
+
+/// aac, 44.1khz 16 bits stereo, then the aac packet type, a sequence header of aac lc
+const AAC_SEQUENCE_HEADER_TAG_BODY: [u8; 4] = [0xaf, 0x00, 0x12, 0x10];
+/// a raw aac frame of silence
+const AAC_FRAME_TAG_BODY: [u8; 11] = [
+    0xaf, 0x01, 0x21, 0x00, 0x49, 0x90, 0x02, 0x19, 0x00, 0x23, 0x80,
+];
+
+/// the video and the audio sequence headers
+pub const SEQUENCE_HEADER_TAGS: usize = 2;
+/// a video and an audio tag for each frame, the stream center holds the frames of a stream
+/// with no audio until many are queued, so the audio lets the video through as it is published
+pub const TAGS_PER_FRAME: usize = 2;
+
+/// a canned stream of single slice frames,
+/// the slice of each frame carries its index so a receiver can tell which frames arrived intact
+#[derive(Debug, Clone, Copy)]
+pub struct CannedVideo {
+    pub frame_count: u32,
+    /// frames between key frames, including the key frame
+    pub gop_size: u32,
+    /// bytes of the body of each slice nal unit
+    pub frame_size: usize,
+}
+
+impl Default for CannedVideo {
+    fn default() -> Self {
+        Self {
+            frame_count: 50,
+            gop_size: 10,
+            frame_size: 600,
+        }
+    }
+}
+
+impl CannedVideo {
+    pub fn is_key_frame(&self, index: u32) -> bool {
+        index.is_multiple_of(self.gop_size)
+    }
+
+    pub fn nal_unit(&self, index: u32) -> TestSupportResult<NalUnit> {
+        let header = if self.is_key_frame(index) { 0x65 } else { 0x41 };
+        let size = self.frame_size.max(FRAME_NAL_UNIT_MIN_BODY);
+        let mut body = Vec::with_capacity(size);
+        body.put_u8(SLICE_HEADER_PREFIX);
+        body.put_u32(index);
+        body.extend((0..size - FRAME_NAL_UNIT_MIN_BODY).map(|i| filler(index, i)));
+        Ok(NalUnit {
+            header: NaluHeader::try_from(header)?,
+            body: Bytes::from(body),
+        })
+    }
+
+    /// the index of the frame `nal_unit` is the slice of, None if it is not one of them or corrupted
+    pub fn frame_index(&self, nal_unit: &NalUnit) -> Option<u32> {
+        if !matches!(
+            nal_unit.header.nal_unit_type,
+            NALUType::IDRSlice | NALUType::NonIDRSlice
+        ) {
+            return None;
+        }
+        let body = nal_unit.body.as_ref();
+        if body.len() != self.frame_size.max(FRAME_NAL_UNIT_MIN_BODY)
+            || body[0] != SLICE_HEADER_PREFIX
+        {
+            return None;
+        }
+        let index = u32::from_be_bytes(body[1..FRAME_NAL_UNIT_MIN_BODY].try_into().ok()?);
+        let intact = index < self.frame_count
+            && self.is_key_frame(index) == (nal_unit.header.nal_unit_type == NALUType::IDRSlice)
+            && body[FRAME_NAL_UNIT_MIN_BODY..]
+                .iter()
+                .enumerate()
+                .all(|(i, byte)| *byte == filler(index, i));
+        intact.then_some(index)
+    }
+
+    pub fn frame(&self, index: u32) -> TestSupportResult<MediaFrame> {
+        let frame_type = if self.is_key_frame(index) {
+            FrameType::KeyFrame
+        } else {
+            FrameType::CodedFrames
+        };
+        Ok(MediaFrame::Video {
+            frame_info: VideoFrameInfo::new(
+                VideoCodecCommon::AVC,
+                frame_type,
+                MediaFrameTimestamp::with_timestamp_ms(index as u64 * FRAME_INTERVAL_MS),
+            ),
+            payload: VideoFrameUnit::H264 {
+                nal_units: vec![self.nal_unit(index)?],
+            },
+        })
+    }
+
+    /// the index of the first tag of frame `index` in the tags of `to_flv`
+    pub fn tag_index(&self, index: u32) -> usize {
+        SEQUENCE_HEADER_TAGS + index as usize * TAGS_PER_FRAME
+    }
+
+    /// a flv file with the sequence headers and all the frames,
+    /// each video frame is followed by an audio frame half a frame interval later
+    pub fn to_flv(&self) -> TestSupportResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        FLVHeader::new(true, true).write_to(&mut bytes)?;
+        bytes.put_u32(0);
+        write_flv_tag(&mut bytes, &video_config()?)?;
+        write_audio_tag(&mut bytes, 0, &AAC_SEQUENCE_HEADER_TAG_BODY)?;
+        for index in 0..self.frame_count {
+            write_flv_tag(&mut bytes, &self.frame(index)?)?;
+            let timestamp = index as u64 * FRAME_INTERVAL_MS + FRAME_INTERVAL_MS / 2;
+            write_audio_tag(&mut bytes, timestamp as u32, &AAC_FRAME_TAG_BODY)?;
+        }
+        Ok(bytes)
+    }
+}
+
+fn filler(index: u32, i: usize) -> u8 {
+    (index as usize).wrapping_mul(31).wrapping_add(i) as u8
+}
+
+fn write_audio_tag(bytes: &mut Vec<u8>, timestamp: u32, body: &[u8]) -> TestSupportResult<()> {
+    FLVTagHeader {
+        tag_type: FLVTagType::Audio,
+        data_size: body.len() as u32,
+        timestamp,
+        filter_enabled: false,
+    }
+    .write_to(bytes)?;
+    bytes.extend_from_slice(body);
+    bytes.put_u32((FLVTagHeader::bytes_count() + body.len()) as u32);
+    Ok(())
+}
+
+fn write_flv_tag(bytes: &mut Vec<u8>, frame: &MediaFrame) -> TestSupportResult<()> {
+    let tag = frame.to_flv_tag(4)?;
+    let start = bytes.len();
+    tag.write_to(bytes)?;
+    let tag_size = (bytes.len() - start) as u32;
+    bytes.put_u32(tag_size);
+    Ok(())
+}
+
+pub fn sps() -> TestSupportResult<Sps> {
+    let nal_unit = NalUnit::read_from(&mut SPS.as_slice())?;
+    Ok(Sps::try_from(&nal_unit)?)
+}
+
+pub fn pps(sps: &Sps) -> TestSupportResult<Pps> {
+    let nal_unit = NalUnit::read_from(&mut PPS.as_slice())?;
+    let chroma_format_idc = sps
+        .get_chroma_format_idc()
+        .unwrap_or(ChromaFormatIdc::Chroma420);
+    Ok(Pps::try_from((chroma_format_idc, &nal_unit))?)
+}
+
+pub fn video_config() -> TestSupportResult<MediaFrame> {
+    let sps = sps()?;
+    let pps = pps(&sps)?;
+    let record = AvcDecoderConfigurationRecord::from((&sps, &pps));
+    Ok(MediaFrame::VideoConfig {
+        timestamp_nano: 0,
+        config: Box::new(VideoConfig::H264(H264VideoConfig {
+            sps: Some(sps),
+            pps: Some(pps),
+            sps_ext: None,
+            avc_decoder_configuration_record: Some(record),
+        })),
+    })
+}
+
+/// a tag of a flv file, `body` is the raw tag body
+#[derive(Debug, Clone)]
+pub struct FlvTagData {
+    pub tag_type: FLVTagType,
+    pub timestamp: u32,
+    pub body: Bytes,
+}
+
+impl FlvTagData {
+    /// the nal units of an avc video tag carrying frames, 4 bytes nal unit lengths are assumed
+    pub fn avc_nal_units(&self) -> TestSupportResult<Vec<NalUnit>> {
+        // frame type and codec id, avc packet type, composition time
+        const AVC_VIDEO_TAG_HEADER_SIZE: usize = 5;
+        if self.tag_type != FLVTagType::Video
+            || self.body.len() < AVC_VIDEO_TAG_HEADER_SIZE
+            || self.body[1] != 1
+        {
+            return Ok(vec![]);
+        }
+        let mut avcc = &self.body[AVC_VIDEO_TAG_HEADER_SIZE..];
+        let mut result = vec![];
+        while avcc.remaining() >= 4 {
+            let length = avcc.get_u32() as usize;
+            if avcc.remaining() < length {
+                break;
+            }
+            result.push(NalUnit::read_from(&mut &avcc[..length])?);
+            avcc.advance(length);
+        }
+        Ok(result)
+    }
+}
+
+/// parses the tags of a flv file, a truncated trailing tag is ignored
+/// as `bytes` might be read from a live stream
+pub fn read_flv_tags(bytes: &[u8]) -> TestSupportResult<Vec<FlvTagData>> {
+    // the header and the previous tag size following it
+    const FLV_HEADER_SIZE: usize = 9;
+    if bytes.len() < FLV_HEADER_SIZE + 4 {
+        return Ok(vec![]);
+    }
+    let mut reader = bytes;
+    FLVHeader::read_from(&mut reader)?;
+    reader.advance(4);
+    let mut result = vec![];
+    while reader.remaining() >= FLVTagHeader::bytes_count() {
+        let mut peek = reader;
+        let tag_header = FLVTagHeader::read_from(&mut peek)?;
+        let data_size = tag_header.data_size as usize;
+        if peek.remaining() < data_size + 4 {
+            break;
+        }
+        result.push(FlvTagData {
+            tag_type: tag_header.tag_type,
+            timestamp: tag_header.timestamp,
+            body: Bytes::copy_from_slice(&peek[..data_size]),
+        });
+        peek.advance(data_size + 4);
+        reader = peek;
+    }
+    Ok(result)
+}
@@ -0,0 +1,50 @@
+use codec_common::{
+    FrameType, MediaFrameTimestamp,
+    audio::{AudioCodecCommon, AudioFrameInfo, SoundRateCommon, SoundSizeCommon, SoundTypeCommon},
+    video::{VideoCodecCommon, VideoFrameInfo, VideoFrameUnit},
+};
+use codec_h264::{nalu::NalUnit, nalu_header::NaluHeader};
+use stream_center::gop::MediaFrame;
+use tokio_util::bytes::Bytes;
+
+use crate::flv::{FRAME_INTERVAL_MS, SLICE_HEADER_PREFIX};
+
+/// a frame of a single h264 slice, a key frame every `gop_size` frames,
+/// `payload_bytes` is what the stream center counts of it
+pub fn video_frame(index: u64, gop_size: u64, payload_bytes: usize) -> MediaFrame {
+    let (nal_header, frame_type) = if index.is_multiple_of(gop_size) {
+        (0x65, FrameType::KeyFrame)
+    } else {
+        (0x41, FrameType::CodedFrames)
+    };
+    MediaFrame::Video {
+        frame_info: VideoFrameInfo::new(
+            VideoCodecCommon::AVC,
+            frame_type,
+            MediaFrameTimestamp::with_timestamp_ms(index * FRAME_INTERVAL_MS),
+        ),
+        payload: VideoFrameUnit::H264 {
+            nal_units: vec![NalUnit {
+                header: NaluHeader::try_from(nal_header).expect("valid nal unit header"),
+                // the header byte is counted as well
+                body: Bytes::from(vec![SLICE_HEADER_PREFIX; payload_bytes.saturating_sub(1)]),
+            }],
+        },
+    }
+}
+
+/// an aac frame right between the video frames `index` and `index + 1`,
+/// so it keeps the mix queue of a stream flowing and shares no dts with them
+pub fn audio_frame(index: u64, payload_bytes: usize) -> MediaFrame {
+    MediaFrame::Audio {
+        frame_info: AudioFrameInfo::new(
+            AudioCodecCommon::AAC,
+            FrameType::CodedFrames,
+            SoundRateCommon::KHZ44,
+            SoundSizeCommon::Bit16,
+            SoundTypeCommon::Stereo,
+            (index * FRAME_INTERVAL_MS + FRAME_INTERVAL_MS / 2) * 1_000_000,
+        ),
+        payload: Bytes::from(vec![0; payload_bytes]),
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use amf_formats::limits::{
+    DEFAULT_MAX_DECODED_BYTES, DEFAULT_MAX_DEPTH, DEFAULT_MAX_ELEMENTS, DEFAULT_MAX_STRING_LENGTH,
+};
+use http_server::{
+    config::HttpServerConfig,
+    events::{DEFAULT_EVENT_LOG_CAPACITY, EventLog},
+    server::HttpServer,
+};
+use rocket::local::asynchronous::Client;
+use rtmp_server::{
+    config::{
+        DEFAULT_FORWARD_AUDIO_FOUR_CC, DEFAULT_FORWARD_VIDEO_FOUR_CC, DEFAULT_PEER_BANDWIDTH,
+        DEFAULT_WINDOW_ACK_SIZE, RtmpServerConfig,
+    },
+    relay::RelayRunner,
+    server::RtmpServer,
+};
+use rtp_formats::codec::h264::dts::DEFAULT_REORDER_FRAMES;
+use rtp_session::retransmission::RetransmissionConfig;
+use rtsp_formats::consts::common::DEFAULT_MAX_BODY_SIZE;
+use rtsp_server::{config::RtspServerConfig, sdes::RtspSdes, server::RtspServer};
+use server_utils::{
+    egress_shaping::EgressShaper, ingest_limit::IngestRateLimiter, relay::RelayRegistry,
+    session_registry::SessionRegistry,
+};
+use stream_center::{
+    events::StreamCenterEvent, identity::StreamIdentity, rtmp_control::PeerBandwidthLimitType,
+    stream_center::StreamCenter,
+};
+use tokio::{io::DuplexStream, sync::mpsc};
+use unified_io::channel::{ChannelConnector, ChannelIo, channel_listener};
+
+use crate::{
+    errors::{TestSupportError, TestSupportResult},
+    fault::FaultConfig,
+    relay::ChannelRelayConnector,
+    rtsp::ChannelRtpIoFactory,
+};
+
+const LISTENER_BACKLOG: usize = 16;
+const WAIT_FOR_STREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// a stream center with the rtmp, rtsp and http servers attached to it, all in process,
+/// connections are made through the connectors and never touch a socket
+pub struct TestServers {
+    pub stream_center_event_sender: mpsc::UnboundedSender<StreamCenterEvent>,
+    pub rtmp: ChannelConnector<DuplexStream>,
+    pub rtsp: ChannelConnector<ChannelIo>,
+    /// the rtp ios of rtsp play sessions, the rtp packets sent by the server go through `fault`
+    pub rtp_io_factory: Arc<ChannelRtpIoFactory>,
+    pub http: Client,
+    /// the sessions of all three servers
+    pub session_registry: SessionRegistry,
+    /// the relays run by the servers, not kept anywhere
+    pub relays: RelayRegistry,
+    /// where the relays connect to, the rtmp servers of other test servers are added to it
+    pub relay_connector: Arc<ChannelRelayConnector>,
+}
+
+impl TestServers {
+    pub async fn start(fault: FaultConfig) -> TestSupportResult<Self> {
+        let mut stream_center = StreamCenter::new();
+        let stream_center_event_sender = stream_center.get_event_sender();
+        let notifications = stream_center.subscribe_notifications();
+        let event_log = EventLog::new(DEFAULT_EVENT_LOG_CAPACITY);
+        tokio::spawn(
+            event_log
+                .clone()
+                .run(stream_center.subscribe_notifications()),
+        );
+        tokio::spawn(async move { stream_center.run().await });
+        let session_registry = SessionRegistry::default();
+
+        let (rtmp, rtmp_listener) = channel_listener(LISTENER_BACKLOG);
+        let mut rtmp_server = RtmpServer::new(
+            RtmpServerConfig {
+                address: Ipv4Addr::LOCALHOST.into(),
+                port: 1935,
+                chunk_size: 4096,
+                window_ack_size: DEFAULT_WINDOW_ACK_SIZE,
+                peer_bandwidth: DEFAULT_PEER_BANDWIDTH,
+                peer_bandwidth_limit_type: PeerBandwidthLimitType::Dynamic,
+                apps: HashMap::new(),
+                write_timeout_ms: 10000,
+                read_timeout_ms: 10000,
+                amf_max_depth: DEFAULT_MAX_DEPTH,
+                amf_max_decoded_bytes: DEFAULT_MAX_DECODED_BYTES,
+                amf_max_string_length: DEFAULT_MAX_STRING_LENGTH,
+                amf_max_elements: DEFAULT_MAX_ELEMENTS,
+                rtmps: None,
+                forward_video_four_cc: DEFAULT_FORWARD_VIDEO_FOUR_CC
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect(),
+                forward_audio_four_cc: DEFAULT_FORWARD_AUDIO_FOUR_CC
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect(),
+                trusted_proxies: Default::default(),
+                send_on_bw_done: true,
+            },
+            IngestRateLimiter::default(),
+            EgressShaper::default(),
+            session_registry.clone(),
+            stream_center_event_sender.clone(),
+        );
+        tokio::spawn(async move { rtmp_server.serve(rtmp_listener).await });
+
+        let relays = RelayRegistry::open(
+            None,
+            vec![SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1935)],
+        );
+        let relay_connector = Arc::new(ChannelRelayConnector::default());
+        let relay_runner = RelayRunner::new(
+            relays.clone(),
+            stream_center_event_sender.clone(),
+            notifications,
+        )
+        .with_connector(relay_connector.clone());
+        tokio::spawn(relay_runner.run());
+
+        let rtp_io_factory = Arc::new(ChannelRtpIoFactory::new(fault));
+        let (rtsp, rtsp_listener) = channel_listener(LISTENER_BACKLOG);
+        let rtsp_server = RtspServer::new(
+            stream_center_event_sender.clone(),
+            RtspServerConfig {
+                address: Ipv4Addr::LOCALHOST.into(),
+                port: 554,
+                rtsps: None,
+                retransmission: RetransmissionConfig::default(),
+                offer_rtx: false,
+                // the scenarios publish faster than real time, pacing would hold the plays back
+                pacing: None,
+                h264_buffer: Default::default(),
+                h264_access_unit_delimiters: true,
+                h264_reorder_frames: DEFAULT_REORDER_FRAMES,
+                onvif_backchannel: false,
+                srtp: false,
+                multicast: Default::default(),
+                fec: Default::default(),
+                sdes: RtspSdes::default().config,
+                data_dir: None,
+                describe: Default::default(),
+                trusted_proxies: Default::default(),
+                max_body_size: DEFAULT_MAX_BODY_SIZE,
+            },
+            IngestRateLimiter::default(),
+            EgressShaper::default(),
+            session_registry.clone(),
+        )
+        .with_rtp_io_factory(rtp_io_factory.clone());
+        tokio::spawn(async move { rtsp_server.serve(rtsp_listener).await });
+
+        let http_server = HttpServer::new(
+            HttpServerConfig {
+                address: Ipv4Addr::LOCALHOST.into(),
+                port: 8080,
+                workers: 1,
+                vod_dir: None,
+                capture_dir: None,
+                trusted_proxies: Default::default(),
+            },
+            EgressShaper::default(),
+            session_registry.clone(),
+            stream_center_event_sender.clone(),
+        )
+        .with_relays(relays.clone())
+        .with_event_log(event_log);
+        let http = Client::tracked(http_server.build())
+            .await
+            .map_err(|err| TestSupportError::Http(err.to_string()))?;
+
+        Ok(Self {
+            stream_center_event_sender,
+            rtmp,
+            rtsp,
+            rtp_io_factory,
+            http,
+            session_registry,
+            relays,
+            relay_connector,
+        })
+    }
+
+    /// waits until the stream is published and its video config is known
+    pub async fn wait_for_stream(&self, app: &str, stream: &str) -> TestSupportResult<()> {
+        let stream_id = StreamIdentity::new(app, stream)?;
+        let poll = async {
+            loop {
+                if let Ok(description) =
+                    StreamCenter::describe(&self.stream_center_event_sender, &stream_id).await
+                    && description.video_config.is_some()
+                {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(WAIT_FOR_STREAM_TIMEOUT, poll)
+            .await
+            .map_err(|_| {
+                TestSupportError::Timeout(format!("stream {}/{} is not published", app, stream))
+            })
+    }
+}
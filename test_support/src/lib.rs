@@ -0,0 +1,11 @@
+pub mod errors;
+pub mod fault;
+pub mod flv;
+pub mod frames;
+pub mod harness;
+pub mod relay;
+pub mod rtmp;
+pub mod rtsp;
+
+#[cfg(test)]
+mod test;
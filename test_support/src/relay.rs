@@ -0,0 +1,48 @@
+use std::{collections::HashMap, io, net::SocketAddr, sync::Mutex};
+
+use futures::future::BoxFuture;
+use rtmp_server::{
+    errors::{RtmpServerError, RtmpServerResult},
+    relay::RelayConnector,
+};
+use server_utils::relay::RelayEndpoint;
+use tokio::io::DuplexStream;
+use unified_io::{channel::ChannelConnector, tcp::BoxedStream};
+
+use crate::rtmp::DUPLEX_BUFFER;
+
+/// connects the relays to the rtmp servers of other test servers, found by the `host:port`
+/// of the relay endpoints
+#[derive(Debug, Default)]
+pub struct ChannelRelayConnector {
+    servers: Mutex<HashMap<String, ChannelConnector<DuplexStream>>>,
+}
+
+impl ChannelRelayConnector {
+    pub fn add_server(&self, address: &str, rtmp: ChannelConnector<DuplexStream>) {
+        self.servers
+            .lock()
+            .unwrap()
+            .insert(address.to_owned(), rtmp);
+    }
+}
+
+impl RelayConnector for ChannelRelayConnector {
+    fn connect<'a>(
+        &'a self,
+        endpoint: &'a RelayEndpoint,
+    ) -> BoxFuture<'a, RtmpServerResult<BoxedStream>> {
+        Box::pin(async move {
+            let address = endpoint.address();
+            let Some(rtmp) = self.servers.lock().unwrap().get(&address).cloned() else {
+                return Err(RtmpServerError::Io(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("no server at {}", address),
+                )));
+            };
+            let peer_addr: SocketAddr = "127.0.0.1:50100".parse().unwrap();
+            let io = rtmp.connect(peer_addr, DUPLEX_BUFFER).await?;
+            Ok(Box::new(io) as BoxedStream)
+        })
+    }
+}
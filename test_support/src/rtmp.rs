@@ -0,0 +1,235 @@
+use std::{net::SocketAddr, time::Duration};
+
+use flv_formats::tag::flv_tag_header::FLVTagType;
+use rtmp_formats::{
+    chunk::writer::Writer,
+    commands::{
+        CallCommandRequest, CapsExInfo, ConnectCommandRequest, ConnectCommandRequestObject,
+        CreateStreamCommandRequest, PublishCommand, consts::s2c_command_names,
+    },
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream, WriteHalf},
+    sync::mpsc,
+};
+use tokio_util::either::Either;
+use unified_io::channel::ChannelConnector;
+
+use crate::{
+    errors::{TestSupportError, TestSupportResult},
+    flv::FlvTagData,
+};
+
+const HANDSHAKE_PACKET_SIZE: usize = 1536;
+const RTMP_VERSION: u8 = 3;
+const CHUNK_SIZE: u32 = 4096;
+pub(crate) const DUPLEX_BUFFER: usize = 64 * 1024;
+/// what createStream gives the first stream of a connection
+const MESSAGE_STREAM_ID: u32 = 1;
+/// how long a legacy encoder waits for onBWDone
+const ON_BW_DONE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// a minimal rtmp client publishing flv tags over an in-memory connection
+pub struct RtmpPublisher {
+    writer: Writer,
+    io: WriteHalf<DuplexStream>,
+    /// what the server sent, closed with the connection
+    response_receiver: mpsc::UnboundedReceiver<Vec<u8>>,
+    responses: Vec<u8>,
+}
+
+impl RtmpPublisher {
+    /// handshakes, connects to `app` and publishes `stream`
+    pub async fn connect(
+        connector: &ChannelConnector<DuplexStream>,
+        app: &str,
+        stream: &str,
+    ) -> TestSupportResult<Self> {
+        Self::connect_with_caps_ex(connector, app, stream, None).await
+    }
+
+    /// like connect, advertising the enhanced rtmp capabilities in the connect command
+    pub async fn connect_with_caps_ex(
+        connector: &ChannelConnector<DuplexStream>,
+        app: &str,
+        stream: &str,
+        caps_ex_info: Option<CapsExInfo>,
+    ) -> TestSupportResult<Self> {
+        let mut publisher = Self::handshake(connector).await?;
+        publisher.writer.write_set_chunk_size(CHUNK_SIZE)?;
+        publisher
+            .writer
+            .write_connect_request(ConnectCommandRequest {
+                command_name: "connect".to_owned(),
+                transaction_id: 1,
+                command_object: ConnectCommandRequestObject {
+                    app: app.to_owned(),
+                    tc_url: format!("rtmp://127.0.0.1/{}", app),
+                    caps_ex_info,
+                    ..Default::default()
+                },
+                optional_user_arguments: None,
+            })?;
+        publisher
+            .writer
+            .write_create_stream_request(CreateStreamCommandRequest {
+                command_name: "createStream".to_owned(),
+                transaction_id: 2.0,
+                command_object: None,
+            })?;
+        publisher
+            .writer
+            .write_publish_request(PublishCommand::new(stream.to_owned(), "live".to_owned()))?;
+        publisher.writer.write_to(&mut publisher.io).await?;
+        Ok(publisher)
+    }
+
+    /// replays what a legacy hardware encoder sends: it connects, waits for onBWDone,
+    /// checks the bandwidth, answers onBWDone and only then publishes `stream`
+    pub async fn connect_as_legacy_encoder(
+        connector: &ChannelConnector<DuplexStream>,
+        app: &str,
+        stream: &str,
+    ) -> TestSupportResult<Self> {
+        let mut publisher = Self::handshake(connector).await?;
+        publisher.writer.write_set_chunk_size(CHUNK_SIZE)?;
+        publisher
+            .writer
+            .write_connect_request(ConnectCommandRequest {
+                command_name: "connect".to_owned(),
+                transaction_id: 1,
+                command_object: ConnectCommandRequestObject {
+                    app: app.to_owned(),
+                    flash_version: "FMLE/3.0 (compatible; FMSc/1.0)".to_owned(),
+                    tc_url: format!("rtmp://127.0.0.1/{}", app),
+                    ..Default::default()
+                },
+                optional_user_arguments: None,
+            })?;
+        publisher.writer.write_to(&mut publisher.io).await?;
+        if !publisher
+            .wait_for_response(s2c_command_names::ON_BW_DONE, ON_BW_DONE_TIMEOUT)
+            .await
+        {
+            return Err(TestSupportError::Timeout(
+                "the server called no onBWDone".to_owned(),
+            ));
+        }
+
+        let call =
+            |procedure_name: &str, transaction_id: f64, stream: Option<&str>| CallCommandRequest {
+                procedure_name: procedure_name.to_owned(),
+                transaction_id,
+                command_object: None,
+                optional_arguments: stream
+                    .map(|stream| Either::Left(amf_formats::amf0::string(stream).into())),
+            };
+        publisher
+            .writer
+            .write_call_request(call("_checkbw", 2.0, None))?;
+        publisher
+            .writer
+            .write_call_request(call("_result", 0.0, None))?;
+        publisher
+            .writer
+            .write_call_request(call("releaseStream", 3.0, Some(stream)))?;
+        publisher
+            .writer
+            .write_call_request(call("FCPublish", 4.0, Some(stream)))?;
+        publisher
+            .writer
+            .write_create_stream_request(CreateStreamCommandRequest {
+                command_name: "createStream".to_owned(),
+                transaction_id: 5.0,
+                command_object: None,
+            })?;
+        publisher
+            .writer
+            .write_publish_request(PublishCommand::new(stream.to_owned(), "live".to_owned()))?;
+        publisher.writer.write_to(&mut publisher.io).await?;
+        Ok(publisher)
+    }
+
+    /// the simple handshake, c1 is all zeros
+    async fn handshake(connector: &ChannelConnector<DuplexStream>) -> TestSupportResult<Self> {
+        let peer_addr: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        let mut io = connector.connect(peer_addr, DUPLEX_BUFFER).await?;
+
+        let mut c0c1 = vec![0; 1 + HANDSHAKE_PACKET_SIZE];
+        c0c1[0] = RTMP_VERSION;
+        io.write_all(&c0c1).await?;
+        let mut s0s1s2 = vec![0; 1 + 2 * HANDSHAKE_PACKET_SIZE];
+        io.read_exact(&mut s0s1s2).await?;
+        io.write_all(&s0s1s2[1..1 + HANDSHAKE_PACKET_SIZE]).await?;
+
+        let (mut reader, writer_half) = tokio::io::split(io);
+        let (response_sender, response_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 4096];
+            while let Ok(read) = reader.read(&mut buffer).await {
+                if read == 0 || response_sender.send(buffer[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            writer: Writer::new(),
+            io: writer_half,
+            response_receiver,
+            responses: Vec::new(),
+        })
+    }
+
+    /// whether the server sent the text, e.g. a status code, within the timeout.
+    /// the responses are not parsed, the amf strings in them are searched as is
+    pub async fn wait_for_response(&mut self, text: &str, timeout: Duration) -> bool {
+        let wait = async {
+            loop {
+                if self
+                    .responses
+                    .windows(text.len())
+                    .any(|window| window == text.as_bytes())
+                {
+                    return true;
+                }
+                match self.response_receiver.recv().await {
+                    Some(bytes) => self.responses.extend(bytes),
+                    None => return false,
+                }
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.unwrap_or(false)
+    }
+
+    /// whether the server closed the connection within the timeout
+    pub async fn wait_for_close(&mut self, timeout: Duration) -> bool {
+        let wait = async {
+            while let Some(bytes) = self.response_receiver.recv().await {
+                self.responses.extend(bytes);
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.is_ok()
+    }
+
+    pub async fn send_tags(&mut self, tags: &[FlvTagData]) -> TestSupportResult<()> {
+        for tag in tags {
+            match tag.tag_type {
+                FLVTagType::Video => {
+                    self.writer
+                        .write_video(tag.body.clone(), tag.timestamp, MESSAGE_STREAM_ID)?
+                }
+                FLVTagType::Audio => {
+                    self.writer
+                        .write_audio(tag.body.clone(), tag.timestamp, MESSAGE_STREAM_ID)?
+                }
+                FLVTagType::Script => {
+                    self.writer
+                        .write_meta(tag.body.clone(), tag.timestamp, MESSAGE_STREAM_ID)?
+                }
+            }
+            self.writer.write_to(&mut self.io).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU16, Ordering},
+    },
+    time::Duration,
+};
+
+use codec_h264::nalu::NalUnit;
+use futures::{SinkExt, StreamExt, future::BoxFuture};
+use rtp_formats::{
+    codec::h264::{
+        packet::sequencer::{RtpH264Sequencer, budget::RtpH264BufferConfig},
+        paramters::RtpH264Fmtp,
+    },
+    packet::{
+        RtpTrivialPacket, framed::RtpTrivialPacketFramed, sequencer::RtpBufferedSequencer,
+        sequencer::RtpTrivialSequencer,
+    },
+};
+use rtsp_formats::{
+    RtspMessage, RtspMessageFramed,
+    consts::{methods::RtspMethod, status::RtspStatus, version::RtspVersion},
+    header::RtspHeader,
+    request::RtspRequest,
+    response::RtspResponse,
+    sdp_extension::attribute::RtspSDPControl,
+};
+use rtsp_server::{
+    errors::RtspServerResult,
+    rtp_io::{RtpIo, RtpIoFactory},
+};
+use sdp_formats::{
+    attributes::SDPAttribute,
+    session::{SDPMediaDescription, SDPMediaType, Sdp},
+};
+use tokio::sync::mpsc;
+use unified_io::{UnifiedIO, UnifiyStreamed, channel::ChannelConnector, channel::ChannelIo};
+use url::Url;
+use utils::traits::buffer::GenericSequencer;
+
+use crate::{
+    errors::{TestSupportError, TestSupportResult},
+    fault::{FaultConfig, FaultStats, faulty_pair},
+};
+
+const RTP_CHANNEL_BUFFER: usize = 1024;
+const RTCP_CHANNEL_BUFFER: usize = 64;
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the client ends of the rtp and rtcp ios of one media session
+#[derive(Debug)]
+pub struct ClientRtpIo {
+    pub rtp: ChannelIo,
+    pub rtcp: ChannelIo,
+    /// faults applied to the rtp packets sent by the server
+    pub fault_stats: Arc<FaultStats>,
+}
+
+impl ClientRtpIo {
+    /// whether the server lets go of its ends of the rtp and rtcp ios within `wait`,
+    /// the packets still sent meanwhile are skipped
+    pub async fn wait_for_close(&mut self, wait: Duration) -> bool {
+        let closed = async {
+            while self.rtp.next().await.is_some() {}
+            while self.rtcp.next().await.is_some() {}
+        };
+        tokio::time::timeout(wait, closed).await.is_ok()
+    }
+}
+
+/// hands the rtsp server in-memory rtp ios instead of udp sockets,
+/// the client ends are kept by the rtp port the client announced in its Transport header
+#[derive(Debug)]
+pub struct ChannelRtpIoFactory {
+    fault: FaultConfig,
+    next_port: AtomicU16,
+    clients: Mutex<HashMap<u16, ClientRtpIo>>,
+}
+
+impl ChannelRtpIoFactory {
+    pub fn new(fault: FaultConfig) -> Self {
+        Self {
+            fault,
+            next_port: AtomicU16::new(20000),
+            clients: Default::default(),
+        }
+    }
+
+    pub fn take(&self, client_rtp_port: u16) -> Option<ClientRtpIo> {
+        self.clients.lock().unwrap().remove(&client_rtp_port)
+    }
+}
+
+impl RtpIoFactory for ChannelRtpIoFactory {
+    fn create_pair(
+        &self,
+        _peer_addr: SocketAddr,
+        peer_rtp_port: u16,
+        _peer_rtcp_port: u16,
+    ) -> BoxFuture<'_, RtspServerResult<(RtpIo, RtpIo)>> {
+        Box::pin(async move {
+            let (server_rtp, client_rtp, fault_stats) = faulty_pair(RTP_CHANNEL_BUFFER, self.fault);
+            let (server_rtcp, client_rtcp) = ChannelIo::pair(RTCP_CHANNEL_BUFFER);
+            let port = self.next_port.fetch_add(2, Ordering::Relaxed);
+            self.clients.lock().unwrap().insert(
+                peer_rtp_port,
+                ClientRtpIo {
+                    rtp: client_rtp,
+                    rtcp: client_rtcp,
+                    fault_stats,
+                },
+            );
+            let server_rtp: Pin<Box<dyn UnifiedIO>> = Box::pin(server_rtp);
+            let server_rtcp: Pin<Box<dyn UnifiedIO>> = Box::pin(server_rtcp);
+            Ok(((server_rtp, port), (server_rtcp, port + 1)))
+        })
+    }
+}
+
+static NEXT_CLIENT_PORT: AtomicU16 = AtomicU16::new(40000);
+
+/// a minimal rtsp client playing the h264 video of a stream over the channel rtp ios
+pub struct RtspPlayer {
+    io: UnifiyStreamed<RtspMessageFramed>,
+    base_uri: Url,
+    cseq: u32,
+    session_id: Option<String>,
+}
+
+impl RtspPlayer {
+    pub async fn connect(
+        connector: &ChannelConnector<ChannelIo>,
+        app: &str,
+        stream: &str,
+    ) -> TestSupportResult<Self> {
+        let io = connector
+            .connect("127.0.0.1:50000".parse().unwrap(), RTCP_CHANNEL_BUFFER)
+            .await?;
+        Ok(Self {
+            io: UnifiyStreamed::new_byte_stream(Box::pin(io), RtspMessageFramed::default()),
+            base_uri: format!("rtsp://127.0.0.1/{}/{}", app, stream).parse()?,
+            cseq: 0,
+            session_id: None,
+        })
+    }
+
+    async fn request(
+        &mut self,
+        method: RtspMethod,
+        uri: Url,
+        headers: Vec<(RtspHeader, String)>,
+    ) -> TestSupportResult<RtspResponse> {
+        self.cseq += 1;
+        let mut builder = RtspRequest::builder()
+            .method(method)
+            .uri(uri)
+            .version(RtspVersion::V2)
+            .header(RtspHeader::CSeq, self.cseq.to_string())
+            .headers(headers);
+        if let Some(session_id) = &self.session_id {
+            builder = builder.header(RtspHeader::Session, session_id.clone());
+        }
+        self.io.send(RtspMessage::Request(builder.build()?)).await?;
+        loop {
+            match tokio::time::timeout(RESPONSE_TIMEOUT, self.io.next()).await {
+                Err(_) => return Err(TestSupportError::Timeout(format!("{:?}", method))),
+                Ok(None) => {
+                    return Err(TestSupportError::UnexpectedResponse(
+                        "connection closed".to_owned(),
+                    ));
+                }
+                Ok(Some(message)) => {
+                    if let RtspMessage::Response(response) = message? {
+                        return Ok(response);
+                    }
+                }
+            }
+        }
+    }
+
+    fn expect_ok(response: RtspResponse) -> TestSupportResult<RtspResponse> {
+        if response.status() != RtspStatus::OK {
+            return Err(TestSupportError::UnexpectedResponse(format!(
+                "{}",
+                response
+            )));
+        }
+        Ok(response)
+    }
+
+    pub async fn options(&mut self) -> TestSupportResult<()> {
+        let response = self
+            .request(RtspMethod::Options, self.base_uri.clone(), vec![])
+            .await?;
+        Self::expect_ok(response).map(|_| ())
+    }
+
+    pub async fn describe(&mut self) -> TestSupportResult<Sdp> {
+        let response = self
+            .request(
+                RtspMethod::Describe,
+                self.base_uri.clone(),
+                vec![(RtspHeader::Accept, "application/sdp".to_owned())],
+            )
+            .await?;
+        let response = Self::expect_ok(response)?;
+        let body = response
+            .body()
+            .as_ref()
+            .ok_or_else(|| TestSupportError::UnexpectedResponse("sdp is missing".to_owned()))?;
+        Ok(body.parse()?)
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    /// sets up the video media of `sdp` for udp transport, the rtp ios are taken from `factory`
+    pub async fn setup_video(
+        &mut self,
+        sdp: &Sdp,
+        factory: &ChannelRtpIoFactory,
+    ) -> TestSupportResult<VideoReceiver> {
+        let client_io = self.setup_video_io(sdp, factory).await?;
+        VideoReceiver::new(video_media(sdp)?, client_io)
+    }
+
+    /// sets up the video media of `sdp`, the client ends of the rtp ios are left to the caller.
+    /// a video set up before in the session has its transport replaced
+    pub async fn setup_video_io(
+        &mut self,
+        sdp: &Sdp,
+        factory: &ChannelRtpIoFactory,
+    ) -> TestSupportResult<ClientRtpIo> {
+        let media = video_media(sdp)?;
+        let control = media_control(media)
+            .ok_or_else(|| TestSupportError::UnexpectedResponse("no media control".to_owned()))?;
+        let client_rtp_port = NEXT_CLIENT_PORT.fetch_add(2, Ordering::Relaxed);
+        let response = self
+            .request(
+                RtspMethod::Setup,
+                format!("{}/{}", self.base_uri, control).parse()?,
+                vec![(
+                    RtspHeader::Transport,
+                    format!(
+                        "RTP/AVP/UDP;unicast;client_port={}-{}",
+                        client_rtp_port,
+                        client_rtp_port + 1
+                    ),
+                )],
+            )
+            .await?;
+        let response = Self::expect_ok(response)?;
+        self.session_id = response.headers().session().map(|session| session.id);
+        factory.take(client_rtp_port).ok_or_else(|| {
+            TestSupportError::UnexpectedResponse("no rtp io is created for the media".to_owned())
+        })
+    }
+
+    /// plays or resumes the session, the response carries the Range and RTP-Info of the play
+    pub async fn play(&mut self) -> TestSupportResult<RtspResponse> {
+        let response = self
+            .request(RtspMethod::Play, self.base_uri.clone(), vec![])
+            .await?;
+        Self::expect_ok(response)
+    }
+
+    pub async fn pause(&mut self) -> TestSupportResult<RtspResponse> {
+        let response = self
+            .request(RtspMethod::Pause, self.base_uri.clone(), vec![])
+            .await?;
+        Self::expect_ok(response)
+    }
+}
+
+fn video_media(sdp: &Sdp) -> TestSupportResult<&SDPMediaDescription> {
+    sdp.media_description
+        .iter()
+        .find(|media| matches!(media.media_line.media_type, SDPMediaType::Video))
+        .ok_or_else(|| TestSupportError::UnexpectedResponse("no video media".to_owned()))
+}
+
+fn media_control(media: &SDPMediaDescription) -> Option<String> {
+    media.attributes.iter().find_map(|attr| {
+        if let SDPAttribute::Trivial(attr) = attr
+            && attr.name == "control"
+        {
+            RtspSDPControl::try_from(attr)
+                .ok()
+                .map(|control| control.url_to_str())
+        } else {
+            None
+        }
+    })
+}
+
+/// reassembles the h264 access units carried by the rtp packets of a play session
+pub struct VideoReceiver {
+    access_units: mpsc::UnboundedReceiver<Vec<NalUnit>>,
+    packets: mpsc::UnboundedReceiver<RtpPacketPosition>,
+    fault_stats: Arc<FaultStats>,
+}
+
+/// the sequence number and the rtp timestamp of a received rtp packet, as sent by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpPacketPosition {
+    pub sequence_number: u16,
+    pub timestamp: u32,
+}
+
+impl VideoReceiver {
+    fn new(media: &SDPMediaDescription, client_io: ClientRtpIo) -> TestSupportResult<Self> {
+        let fmtp: RtpH264Fmtp = media
+            .get_fmtp()
+            .ok_or_else(|| TestSupportError::UnexpectedResponse("no fmtp".to_owned()))?
+            .params
+            .parse()?;
+        let sequencer = RtpH264Sequencer::new(
+            fmtp.packetization_mode.unwrap_or_default(),
+            (&fmtp).into(),
+            fmtp.sprop_parameter_sets
+                .as_ref()
+                .and_then(|sets| sets.sps.clone()),
+            fmtp.sprop_parameter_sets
+                .as_ref()
+                .and_then(|sets| sets.pps.clone()),
+            RtpH264BufferConfig::default(),
+        );
+        let ClientRtpIo {
+            rtp,
+            mut rtcp,
+            fault_stats,
+        } = client_io;
+        tokio::spawn(async move { while let Some(Ok(_)) = rtcp.next().await {} });
+        let (tx, access_units) = mpsc::unbounded_channel();
+        let (packets_tx, packets) = mpsc::unbounded_channel();
+        tokio::spawn(receive_video(rtp, sequencer, tx, packets_tx));
+        Ok(Self {
+            access_units,
+            packets,
+            fault_stats,
+        })
+    }
+
+    /// the nal units of the next access unit, None if none arrives within `timeout`
+    pub async fn next_access_unit(&mut self, timeout: Duration) -> Option<Vec<NalUnit>> {
+        tokio::time::timeout(timeout, self.access_units.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// the next rtp packet received, None if none arrives within `timeout`
+    pub async fn next_packet(&mut self, timeout: Duration) -> Option<RtpPacketPosition> {
+        tokio::time::timeout(timeout, self.packets.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// the last rtp packet received so far, the ones before it are discarded
+    pub fn last_packet(&mut self) -> Option<RtpPacketPosition> {
+        let mut last = None;
+        while let Ok(packet) = self.packets.try_recv() {
+            last = Some(packet);
+        }
+        last
+    }
+
+    pub fn fault_stats(&self) -> &FaultStats {
+        &self.fault_stats
+    }
+}
+
+async fn receive_video(
+    rtp: ChannelIo,
+    mut h264_sequencer: RtpH264Sequencer,
+    tx: mpsc::UnboundedSender<Vec<NalUnit>>,
+    packets_tx: mpsc::UnboundedSender<RtpPacketPosition>,
+) {
+    let mut packets = UnifiyStreamed::new(Box::pin(rtp), RtpTrivialPacketFramed);
+    let mut trivial_sequencer = RtpTrivialSequencer::new(40, 4);
+    let mut first_sequence_number = None;
+    while let Some(packet) = packets.next().await {
+        let mut packet: RtpTrivialPacket = match packet {
+            Ok(packet) => packet,
+            Err(err) => {
+                tracing::warn!("invalid rtp packet: {}", err);
+                continue;
+            }
+        };
+        let _ = packets_tx.send(RtpPacketPosition {
+            sequence_number: packet.header.sequence_number,
+            timestamp: packet.header.timestamp,
+        });
+        // starts far from the wrap point so a late packet is never taken as a wrap
+        let first = *first_sequence_number.get_or_insert(packet.header.sequence_number);
+        packet.header.sequence_number = packet
+            .header
+            .sequence_number
+            .wrapping_sub(first)
+            .wrapping_add(1000);
+        if trivial_sequencer.enqueue(packet).is_err() {
+            continue;
+        }
+        for packet in trivial_sequencer.try_dump() {
+            if let Err(err) = h264_sequencer.enqueue(packet) {
+                tracing::debug!("h264 sequencer dropped a packet: {}", err);
+            }
+        }
+        for item in h264_sequencer.try_dump_packets() {
+            if tx.send(item.nal_units).is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,761 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        time::{Duration, Instant},
+    };
+
+    use codec_h264::{
+        annexb::{split_annexb, split_avcc},
+        nalu::NalUnit,
+        nalu_type::NALUType,
+    };
+    use rocket::http::{ContentType, Status};
+    use rtmp_formats::commands::CapsExInfo;
+    use rtsp_formats::header::RtspHeader;
+    use server_utils::session_registry::{SessionProtocol, SessionRole};
+    use stream_center::{
+        identity::StreamIdentity,
+        stream_center::StreamCenter,
+        stream_source::{MediaSelection, PlayProtocol},
+    };
+    use tokio::io::AsyncReadExt;
+    use utils::traits::reader::ReadFrom;
+
+    use crate::{
+        errors::TestSupportResult,
+        fault::{Delay, FaultConfig},
+        flv::{CannedVideo, FRAME_INTERVAL_MS, TAGS_PER_FRAME, read_flv_tags},
+        harness::TestServers,
+        rtmp::RtmpPublisher,
+        rtsp::{RtspPlayer, VideoReceiver},
+    };
+
+    const APP: &str = "live";
+    const STREAM: &str = "test";
+
+    fn is_slice(nal_unit: &NalUnit) -> bool {
+        matches!(
+            nal_unit.header.nal_unit_type,
+            NALUType::IDRSlice | NALUType::NonIDRSlice
+        )
+    }
+
+    /// publishes the config and the first gop of `video`, then plays the stream over rtsp
+    /// and publishes the rest
+    async fn publish_and_play(
+        servers: &TestServers,
+        video: &CannedVideo,
+    ) -> TestSupportResult<(RtmpPublisher, RtspPlayer, VideoReceiver)> {
+        let tags = read_flv_tags(&video.to_flv()?)?;
+        let (first_gop, rest) = tags.split_at(video.tag_index(video.gop_size));
+        let mut publisher = RtmpPublisher::connect(&servers.rtmp, APP, STREAM).await?;
+        publisher.send_tags(first_gop).await?;
+        servers.wait_for_stream(APP, STREAM).await?;
+
+        let mut player = RtspPlayer::connect(&servers.rtsp, APP, STREAM).await?;
+        player.options().await?;
+        let sdp = player.describe().await?;
+        let receiver = player.setup_video(&sdp, &servers.rtp_io_factory).await?;
+        player.play().await?;
+        publisher.send_tags(rest).await?;
+        // the sessions end with the connections, they are kept until the test is done
+        Ok((publisher, player, receiver))
+    }
+
+    #[tokio::test]
+    async fn rtmp_publish_rtsp_play() {
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let video = CannedVideo::default();
+        let (_publisher, _player, mut receiver) = publish_and_play(&servers, &video).await.unwrap();
+
+        let mut frames = vec![];
+        while let Some(nal_units) = receiver.next_access_unit(Duration::from_secs(2)).await {
+            for nal_unit in nal_units.iter().filter(|nal_unit| is_slice(nal_unit)) {
+                frames.push(video.frame_index(nal_unit).expect("corrupted slice"));
+            }
+            if frames.last() == Some(&(video.frame_count - 1)) {
+                break;
+            }
+        }
+        assert!(!frames.is_empty());
+        assert!(video.is_key_frame(frames[0]));
+        assert!(frames.windows(2).all(|w| w[1] == w[0] + 1), "{:?}", frames);
+        assert_eq!(frames.last(), Some(&(video.frame_count - 1)));
+        assert_eq!(receiver.fault_stats().dropped(), 0);
+    }
+
+    /// the slices of the access units received until `last_frame` arrives
+    async fn receive_frames_until(
+        receiver: &mut VideoReceiver,
+        video: &CannedVideo,
+        last_frame: u32,
+    ) -> Vec<u32> {
+        let mut frames = vec![];
+        while let Some(nal_units) = receiver.next_access_unit(Duration::from_secs(2)).await {
+            for nal_unit in nal_units.iter().filter(|nal_unit| is_slice(nal_unit)) {
+                frames.push(video.frame_index(nal_unit).expect("corrupted slice"));
+            }
+            if frames.last() == Some(&last_frame) {
+                break;
+            }
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn setup_again_closes_the_rtp_ios_it_replaces() {
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let video = CannedVideo::default();
+        let tags = read_flv_tags(&video.to_flv().unwrap()).unwrap();
+        let mut publisher = RtmpPublisher::connect(&servers.rtmp, APP, STREAM)
+            .await
+            .unwrap();
+        publisher
+            .send_tags(&tags[..video.tag_index(video.gop_size)])
+            .await
+            .unwrap();
+        servers.wait_for_stream(APP, STREAM).await.unwrap();
+
+        let mut player = RtspPlayer::connect(&servers.rtsp, APP, STREAM)
+            .await
+            .unwrap();
+        let sdp = player.describe().await.unwrap();
+        let mut first = player
+            .setup_video_io(&sdp, &servers.rtp_io_factory)
+            .await
+            .unwrap();
+        let session_id = player.session_id().unwrap().to_owned();
+        let mut second = player
+            .setup_video_io(&sdp, &servers.rtp_io_factory)
+            .await
+            .unwrap();
+        assert_eq!(player.session_id(), Some(session_id.as_str()));
+
+        // the server ends of the replaced ios are dropped, the new ones are kept
+        assert!(first.wait_for_close(Duration::from_secs(2)).await);
+        assert!(!second.wait_for_close(Duration::from_millis(300)).await);
+    }
+
+    #[tokio::test]
+    async fn rtsp_pause_and_resume_keep_rtp_continuous() {
+        const VIDEO_CLOCKRATE: u64 = 90000;
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let video = CannedVideo {
+            frame_count: 40,
+            ..Default::default()
+        };
+        let tags = read_flv_tags(&video.to_flv().unwrap()).unwrap();
+        // the sequence headers and one gop each
+        let (first_gop, rest) = tags.split_at(video.tag_index(video.gop_size));
+        let gops: Vec<_> = rest
+            .chunks(video.gop_size as usize * TAGS_PER_FRAME)
+            .collect();
+
+        let mut publisher = RtmpPublisher::connect(&servers.rtmp, APP, STREAM)
+            .await
+            .unwrap();
+        publisher.send_tags(first_gop).await.unwrap();
+        servers.wait_for_stream(APP, STREAM).await.unwrap();
+        let mut player = RtspPlayer::connect(&servers.rtsp, APP, STREAM)
+            .await
+            .unwrap();
+        let sdp = player.describe().await.unwrap();
+        let mut receiver = player
+            .setup_video(&sdp, &servers.rtp_io_factory)
+            .await
+            .unwrap();
+        let response = player.play().await.unwrap();
+        assert!(response.headers().get_unique(RtspHeader::Range).is_some());
+        publisher.send_tags(gops[0]).await.unwrap();
+        let frames = receive_frames_until(&mut receiver, &video, 19).await;
+        assert_eq!(frames.last(), Some(&19));
+
+        let pause_requested_at = Instant::now();
+        let response = player.pause().await.unwrap();
+        let paused_at = Instant::now();
+        assert!(
+            response
+                .headers()
+                .get_unique(RtspHeader::Range)
+                .is_some_and(|range| range.starts_with("npt="))
+        );
+        // the frames published during the pause are not sent
+        publisher.send_tags(gops[1]).await.unwrap();
+        assert!(
+            receiver
+                .next_access_unit(Duration::from_millis(300))
+                .await
+                .is_none()
+        );
+        let last = receiver.last_packet().unwrap();
+
+        let resume_requested_at = Instant::now();
+        let response = player.play().await.unwrap();
+        let resumed_at = Instant::now();
+        let rtp_info = response.headers().rtp_info().unwrap();
+        assert_eq!(rtp_info.0.len(), 1);
+        assert_eq!(
+            rtp_info.0[0].seq,
+            Some(last.sequence_number.wrapping_add(1))
+        );
+        // the rtptime moves on by the pause duration
+        let rtptime_advance = rtp_info.0[0].rtptime.unwrap().wrapping_sub(last.timestamp) as u64;
+        let bounds = |paused_for: Duration| paused_for.as_millis() as u64 * VIDEO_CLOCKRATE / 1000;
+        assert!(
+            rtptime_advance >= bounds(resume_requested_at - paused_at)
+                && rtptime_advance <= bounds(resumed_at - pause_requested_at) + 1,
+            "rtptime advanced by {}",
+            rtptime_advance
+        );
+
+        // the live stream resumes from its next key frame
+        publisher.send_tags(gops[2]).await.unwrap();
+        let first_after_resume = receiver.next_packet(Duration::from_secs(2)).await.unwrap();
+        assert_eq!(
+            first_after_resume.sequence_number,
+            last.sequence_number.wrapping_add(1)
+        );
+        assert_eq!(
+            first_after_resume.timestamp.wrapping_sub(last.timestamp) as u64,
+            (30 - 19) * FRAME_INTERVAL_MS * VIDEO_CLOCKRATE / 1000
+        );
+        let frames = receive_frames_until(&mut receiver, &video, 39).await;
+        assert_eq!(frames.first(), Some(&30));
+        assert!(frames.windows(2).all(|w| w[1] == w[0] + 1), "{:?}", frames);
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint_exposes_traffic() {
+        // the registry is shared by the tests, this one has a stream of its own
+        const METRICS_STREAM: &str = "metrics";
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let video = CannedVideo::default();
+        let tags = read_flv_tags(&video.to_flv().unwrap()).unwrap();
+        let (first_gop, rest) = tags.split_at(video.tag_index(video.gop_size));
+        let mut publisher = RtmpPublisher::connect(&servers.rtmp, APP, METRICS_STREAM)
+            .await
+            .unwrap();
+        publisher.send_tags(first_gop).await.unwrap();
+        servers.wait_for_stream(APP, METRICS_STREAM).await.unwrap();
+        let mut player = RtspPlayer::connect(&servers.rtsp, APP, METRICS_STREAM)
+            .await
+            .unwrap();
+        let sdp = player.describe().await.unwrap();
+        let mut receiver = player
+            .setup_video(&sdp, &servers.rtp_io_factory)
+            .await
+            .unwrap();
+        player.play().await.unwrap();
+        publisher.send_tags(rest).await.unwrap();
+        let frames = receive_frames_until(&mut receiver, &video, video.frame_count - 1).await;
+        assert_eq!(frames.last(), Some(&(video.frame_count - 1)));
+
+        let response = servers.http.get("/metrics").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.content_type().is_some_and(
+            |content_type| content_type.top() == "text" && content_type.sub() == "plain"
+        ));
+        let body = response.into_string().await.unwrap();
+        let value = |series: &str| -> f64 {
+            body.lines()
+                .find_map(|line| line.strip_prefix(series)?.strip_prefix(' ')?.parse().ok())
+                .unwrap_or_else(|| panic!("no series {} in:\n{}", series, body))
+        };
+        let stream = format!("{{stream=\"{}/{}\"}}", APP, METRICS_STREAM);
+
+        // the sequence headers and all the frames
+        let frames_in = value(&format!("media_server_stream_frames_in_total{}", stream));
+        assert!(frames_in >= video.tag_index(video.frame_count) as f64);
+        let bytes_in = value(&format!("media_server_stream_bytes_in_total{}", stream));
+        assert!(bytes_in >= (video.frame_count as usize * video.frame_size) as f64);
+        // the gop cache dumped on subscribe is not counted
+        let frames_out = value(&format!("media_server_stream_frames_out_total{}", stream));
+        assert!(frames_out >= (video.frame_count - video.gop_size) as f64);
+        assert!(frames_out <= frames_in);
+        assert!(value(&format!("media_server_stream_bytes_out_total{}", stream)) > 0.0);
+        assert_eq!(
+            value(&format!("media_server_mix_queue_dropped_total{}", stream)),
+            0.0
+        );
+
+        for server in ["rtmp", "rtsp"] {
+            let accepted = value(&format!(
+                "media_server_connections_accepted_total{{server=\"{}\"}}",
+                server
+            ));
+            let active = value(&format!(
+                "media_server_connections_active{{server=\"{}\"}}",
+                server
+            ));
+            assert!(accepted >= 1.0 && active >= 1.0 && active <= accepted);
+        }
+        assert!(value("media_server_publishers_active{protocol=\"rtmp\"}") >= 1.0);
+        assert!(value("media_server_subscribers_active{protocol=\"rtsp\"}") >= 1.0);
+        // the player neither publishes rtp nor sends receiver reports
+        assert!(value("media_server_rtp_packets_lost_total") >= 0.0);
+        assert!(value("media_server_rtcp_rtt_seconds_count") >= 0.0);
+    }
+
+    #[tokio::test]
+    async fn rtmp_publish_http_flv_play() {
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let video = CannedVideo::default();
+        let tags = read_flv_tags(&video.to_flv().unwrap()).unwrap();
+        // the subscriber gets the gop cache along with the first frame after it joins
+        let (published, rest) = tags.split_at(video.tag_index(video.frame_count - video.gop_size));
+        let mut publisher = RtmpPublisher::connect(&servers.rtmp, APP, STREAM)
+            .await
+            .unwrap();
+        publisher.send_tags(published).await.unwrap();
+        servers.wait_for_stream(APP, STREAM).await.unwrap();
+
+        let mut response = servers
+            .http
+            .get(format!("/live_stream/v1/{}/{}.flv", APP, STREAM))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        publisher.send_tags(rest).await.unwrap();
+
+        let mut bytes = vec![];
+        let mut buffer = vec![0; 64 * 1024];
+        let frames = loop {
+            let read = tokio::time::timeout(Duration::from_secs(5), response.read(&mut buffer))
+                .await
+                .expect("flv stream stalled")
+                .unwrap();
+            assert_ne!(read, 0, "flv stream ended early");
+            bytes.extend_from_slice(&buffer[..read]);
+
+            let mut frames = vec![];
+            for tag in read_flv_tags(&bytes).unwrap() {
+                for nal_unit in tag.avc_nal_units().unwrap().iter().filter(|v| is_slice(v)) {
+                    frames.push(video.frame_index(nal_unit).expect("corrupted slice"));
+                }
+            }
+            if frames.last() == Some(&(video.frame_count - 1)) {
+                break frames;
+            }
+        };
+        // a late subscriber starts from the last gop
+        assert!(video.is_key_frame(frames[0]));
+        assert!(frames.windows(2).all(|w| w[1] == w[0] + 1), "{:?}", frames);
+    }
+
+    #[tokio::test]
+    async fn keyframe_snapshot_follows_the_latest_idr() {
+        const KEYFRAME_STREAM: &str = "keyframe";
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let video = CannedVideo::default();
+        let tags = read_flv_tags(&video.to_flv().unwrap()).unwrap();
+        let url = format!("/api/streams/{}/{}/keyframe.h264", APP, KEYFRAME_STREAM);
+
+        let response = servers.http.get(url.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        let mut publisher = RtmpPublisher::connect(&servers.rtmp, APP, KEYFRAME_STREAM)
+            .await
+            .unwrap();
+        let keyframe_of = |bytes: &[u8], nalus: Vec<&[u8]>| {
+            assert!(!bytes.is_empty());
+            let nal_units: Vec<_> = nalus
+                .into_iter()
+                .map(|mut nalu| NalUnit::read_from(&mut nalu).unwrap())
+                .collect();
+            let types: Vec<_> = nal_units
+                .iter()
+                .map(|nal_unit| nal_unit.header.nal_unit_type)
+                .collect();
+            assert_eq!(types, [NALUType::SPS, NALUType::PPS, NALUType::IDRSlice]);
+            video.frame_index(&nal_units[2]).expect("corrupted slice")
+        };
+        let wait_for_keyframe = async |index: u32| {
+            let poll = async {
+                loop {
+                    let response = servers.http.get(url.clone()).dispatch().await;
+                    if response.status() == Status::Ok {
+                        assert_eq!(
+                            response.content_type(),
+                            Some(ContentType::new("video", "h264"))
+                        );
+                        let bytes = response.into_bytes().await.unwrap();
+                        if keyframe_of(&bytes, split_annexb(&bytes)) == index {
+                            return;
+                        }
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), poll)
+                .await
+                .unwrap_or_else(|_| panic!("keyframe {} is not served", index));
+        };
+
+        // the sequence headers and the first frame
+        publisher
+            .send_tags(&tags[..video.tag_index(1)])
+            .await
+            .unwrap();
+        wait_for_keyframe(0).await;
+        // the non IDR frames leave the snapshot alone, the next IDR replaces it
+        publisher
+            .send_tags(&tags[video.tag_index(1)..video.tag_index(video.gop_size + 1)])
+            .await
+            .unwrap();
+        wait_for_keyframe(video.gop_size).await;
+
+        let response = servers
+            .http
+            .get(format!("{}?format=avcc", url))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::Binary));
+        let bytes = response.into_bytes().await.unwrap();
+        assert_eq!(
+            keyframe_of(&bytes, split_avcc(&bytes, 3).unwrap()),
+            video.gop_size
+        );
+
+        let response = servers
+            .http
+            .get(format!("{}?format=hevc", url))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn lossy_rtp_is_reassembled_without_corruption() {
+        let fault = FaultConfig {
+            drop_every: Some(7),
+            duplicate_every: Some(5),
+            delay: Delay::Uniform {
+                min: Duration::ZERO,
+                max: Duration::from_millis(2),
+            },
+            ..Default::default()
+        };
+        let servers = TestServers::start(fault).await.unwrap();
+        // larger than the mtu of the packetizer, so each slice is fragmented
+        let video = CannedVideo {
+            frame_count: 100,
+            gop_size: 10,
+            frame_size: 3000,
+        };
+        let (_publisher, _player, mut receiver) = publish_and_play(&servers, &video).await.unwrap();
+
+        let mut frames = BTreeSet::new();
+        while let Some(nal_units) = receiver.next_access_unit(Duration::from_secs(1)).await {
+            for nal_unit in nal_units.iter().filter(|nal_unit| is_slice(nal_unit)) {
+                let index = video.frame_index(nal_unit);
+                assert!(index.is_some(), "corrupted slice: {:?}", nal_unit.header);
+                frames.extend(index);
+            }
+        }
+        assert!(receiver.fault_stats().dropped() > 0);
+        assert!(
+            frames.len() >= video.frame_count as usize / 4,
+            "only {} frames arrived",
+            frames.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn drain_asks_only_capable_rtmp_publishers_to_reconnect() {
+        const PUBLISH_START: &str = "NetStream.Publish.Start";
+        const RECONNECT_REQUEST: &str = "NetConnection.Connect.ReconnectRequest";
+        const TIMEOUT: Duration = Duration::from_secs(2);
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let capable_caps = CapsExInfo {
+            support_reconnect: true,
+            ..Default::default()
+        };
+        let mut capable =
+            RtmpPublisher::connect_with_caps_ex(&servers.rtmp, APP, "capable", Some(capable_caps))
+                .await
+                .unwrap();
+        let mut incapable = RtmpPublisher::connect(&servers.rtmp, APP, "incapable")
+            .await
+            .unwrap();
+        assert!(capable.wait_for_response(PUBLISH_START, TIMEOUT).await);
+        assert!(incapable.wait_for_response(PUBLISH_START, TIMEOUT).await);
+
+        let response = servers
+            .http
+            .post("/api/admin/drain")
+            .header(ContentType::JSON)
+            .body(r#"{"target": "edge-2.example.com:1936", "grace_ms": 500}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        assert!(
+            body.contains(r#""streams":["live/capable","live/incapable"]"#),
+            "{}",
+            body
+        );
+
+        // the capable publisher is told where to go, with the app of its connection
+        assert!(capable.wait_for_response(RECONNECT_REQUEST, TIMEOUT).await);
+        assert!(
+            capable
+                .wait_for_response("rtmp://edge-2.example.com:1936/live", TIMEOUT)
+                .await
+        );
+        // and comes back through another connection, which takes the stream over
+        let mut reconnected =
+            RtmpPublisher::connect_with_caps_ex(&servers.rtmp, APP, "capable", Some(capable_caps))
+                .await
+                .unwrap();
+        assert!(reconnected.wait_for_response(PUBLISH_START, TIMEOUT).await);
+        assert!(capable.wait_for_close(TIMEOUT).await);
+
+        // the incapable publisher hears nothing until it is disconnected after the grace period
+        assert!(
+            !incapable
+                .wait_for_response(RECONNECT_REQUEST, Duration::from_millis(300))
+                .await
+        );
+        assert!(incapable.wait_for_close(TIMEOUT).await);
+        assert!(
+            !incapable
+                .wait_for_response(RECONNECT_REQUEST, Duration::ZERO)
+                .await
+        );
+        // the publisher that reconnected is not drained
+        assert!(!reconnected.wait_for_close(Duration::from_millis(500)).await);
+    }
+
+    #[tokio::test]
+    async fn legacy_encoders_waiting_for_on_bw_done_publish() {
+        const TIMEOUT: Duration = Duration::from_secs(2);
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let mut publisher = RtmpPublisher::connect_as_legacy_encoder(&servers.rtmp, APP, STREAM)
+            .await
+            .unwrap();
+        assert!(
+            publisher
+                .wait_for_response("NetStream.Publish.Start", TIMEOUT)
+                .await
+        );
+        // the replies to onBWDone left the session as it was
+        let video = CannedVideo::default();
+        let tags = read_flv_tags(&video.to_flv().unwrap()).unwrap();
+        publisher.send_tags(&tags).await.unwrap();
+        servers.wait_for_stream(APP, STREAM).await.unwrap();
+        assert!(!publisher.wait_for_close(Duration::from_millis(300)).await);
+    }
+
+    #[tokio::test]
+    async fn force_closed_sessions_leave_their_streams() {
+        const TIMEOUT: Duration = Duration::from_secs(2);
+        let servers = TestServers::start(FaultConfig::default()).await.unwrap();
+        let video = CannedVideo::default();
+        let (mut publisher, _player, _receiver) = publish_and_play(&servers, &video).await.unwrap();
+        let stream_id = StreamIdentity::new(APP, STREAM).unwrap();
+        let subscribers = || async {
+            StreamCenter::describe(&servers.stream_center_event_sender, &stream_id)
+                .await
+                .map(|description| description.subscribers.len())
+        };
+        assert_eq!(subscribers().await.unwrap(), 1);
+
+        let response = servers.http.get("/api/sessions").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        for field in [
+            r#""protocol":"rtmp""#,
+            r#""role":"publisher""#,
+            r#""protocol":"rtsp""#,
+            r#""role":"subscriber""#,
+            r#""stream_keys":["live/test"]"#,
+        ] {
+            assert!(body.contains(field), "{}", body);
+        }
+        let sessions = servers.session_registry.list(None);
+        assert_eq!(sessions.len(), 2);
+        let find = |protocol| {
+            sessions
+                .iter()
+                .find(|session| session.protocol == protocol)
+                .unwrap()
+                .clone()
+        };
+        let (rtmp, rtsp) = (find(SessionProtocol::Rtmp), find(SessionProtocol::Rtsp));
+        assert_eq!(rtmp.role, SessionRole::Publisher);
+        assert_eq!(rtsp.role, SessionRole::Subscriber);
+
+        let response = servers
+            .http
+            .delete(format!("/api/sessions/{}", rtsp.id))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        assert!(body.contains(r#""close_requested":true"#), "{}", body);
+
+        // the play is unsubscribed and the session unregistered, the publisher is left alone
+        let closed = async {
+            while subscribers().await.unwrap() != 0
+                || servers.session_registry.get(&rtsp.id).is_some()
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(TIMEOUT, closed).await.unwrap();
+        assert!(!publisher.wait_for_close(Duration::from_millis(300)).await);
+        let response = servers
+            .http
+            .delete(format!("/api/sessions/{}", rtsp.id))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        // the publisher is told before the connection is closed, and the stream goes with it
+        let response = servers
+            .http
+            .delete(format!("/api/sessions/{}", rtmp.id))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(
+            publisher
+                .wait_for_response("NetConnection.Connect.Closed", TIMEOUT)
+                .await
+        );
+        assert!(publisher.wait_for_close(TIMEOUT).await);
+        let unpublished = async {
+            while subscribers().await.is_ok() || !servers.session_registry.is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(TIMEOUT, unpublished).await.unwrap();
+    }
+
+    /// waits until the stream is, or is no longer, in the streams of the servers
+    async fn wait_for_listed(servers: &TestServers, listed: bool) {
+        let stream_id = StreamIdentity::new(APP, STREAM).unwrap();
+        let poll = async {
+            while StreamCenter::streams(&servers.stream_center_event_sender)
+                .await
+                .unwrap()
+                .contains(&stream_id)
+                != listed
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), poll)
+            .await
+            .unwrap_or_else(|_| panic!("stream listed is not {}", listed));
+    }
+
+    #[tokio::test]
+    async fn push_relay_added_while_live_publishes_until_removed() {
+        let origin = TestServers::start(FaultConfig::default()).await.unwrap();
+        let downstream = TestServers::start(FaultConfig::default()).await.unwrap();
+        origin
+            .relay_connector
+            .add_server("192.0.2.10:1935", downstream.rtmp.clone());
+        let video = CannedVideo::default();
+        let tags = read_flv_tags(&video.to_flv().unwrap()).unwrap();
+        let mut publisher = RtmpPublisher::connect(&origin.rtmp, APP, STREAM)
+            .await
+            .unwrap();
+        publisher.send_tags(&tags).await.unwrap();
+        origin.wait_for_stream(APP, STREAM).await.unwrap();
+
+        // a target resolving to the server itself would relay the stream back to it
+        let response = origin
+            .http
+            .post("/api/relays")
+            .header(ContentType::JSON)
+            .body(r#"{"kind":"push","pattern":"live/*","target":"rtmp://127.0.0.1/live"}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        assert!(origin.relays.relays().is_empty());
+
+        let response = origin
+            .http
+            .post("/api/relays")
+            .header(ContentType::JSON)
+            .body(r#"{"kind":"push","pattern":"live/*","target":"rtmp://192.0.2.10:1935/live"}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        assert!(body.contains(r#""action":"added""#), "{}", body);
+        wait_for_listed(&downstream, true).await;
+
+        let relay = origin.relays.relays().remove(0);
+        let response = origin
+            .http
+            .delete(format!("/api/relays/{}", relay.id))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        assert!(body.contains(r#""action":"removed""#), "{}", body);
+        wait_for_listed(&downstream, false).await;
+        assert_eq!(origin.relays.audit().len(), 2);
+        // the stream published here is left alone
+        origin.wait_for_stream(APP, STREAM).await.unwrap();
+
+        let response = origin
+            .http
+            .delete(format!("/api/relays/{}", relay.id))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[tokio::test]
+    async fn pull_relay_plays_a_missed_stream_from_upstream() {
+        let origin = TestServers::start(FaultConfig::default()).await.unwrap();
+        let edge = TestServers::start(FaultConfig::default()).await.unwrap();
+        edge.relay_connector
+            .add_server("192.0.2.20:1935", origin.rtmp.clone());
+        let response = edge
+            .http
+            .post("/api/relays")
+            .header(ContentType::JSON)
+            .body(r#"{"kind":"pull","pattern":"live/*","upstream":"rtmp://192.0.2.20:1935/live"}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let video = CannedVideo::default();
+        let tags = read_flv_tags(&video.to_flv().unwrap()).unwrap();
+        let mut publisher = RtmpPublisher::connect(&origin.rtmp, APP, STREAM)
+            .await
+            .unwrap();
+        publisher.send_tags(&tags).await.unwrap();
+        origin.wait_for_stream(APP, STREAM).await.unwrap();
+
+        // the play missing the stream on the edge has it pulled, for the next one to find
+        let stream_id = StreamIdentity::new(APP, STREAM).unwrap();
+        let missed = StreamCenter::subscribe(
+            &edge.stream_center_event_sender,
+            PlayProtocol::HTTPFLV,
+            &stream_id,
+            &HashMap::new(),
+            MediaSelection::default(),
+        )
+        .await;
+        assert!(missed.is_err());
+        wait_for_listed(&edge, true).await;
+
+        let relay = edge.relays.relays().remove(0);
+        let response = edge
+            .http
+            .delete(format!("/api/relays/{}", relay.id))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        wait_for_listed(&edge, false).await;
+        origin.wait_for_stream(APP, STREAM).await.unwrap();
+    }
+}
@@ -1,28 +1,245 @@
 use std::{
+    io,
+    net::SocketAddr,
     pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
     task::{Context, Poll},
 };
 
-use futures::{Sink, SinkExt, Stream, ready};
+use futures::{Sink, SinkExt, Stream, StreamExt, ready};
+use tokio::{io::DuplexStream, sync::mpsc};
 use tokio_util::{bytes::Bytes, sync::PollSender};
 
 use crate::UnifiedIO;
 
+/// what passed one direction of a channel, the same seen from its sender and its receiver
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// received by the receiver
+    pub messages: u64,
+    pub bytes: u64,
+    /// the most messages queued at once
+    pub high_water_mark: usize,
+}
+
+/// shared by the sender and the receiver of a direction
+#[derive(Debug, Default)]
+struct ChannelState {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+    queued: AtomicUsize,
+    high_water_mark: AtomicUsize,
+    /// the kind and the message of the error the channel was closed with, io errors do not clone
+    close_reason: Mutex<Option<(io::ErrorKind, String)>>,
+}
+
+impl ChannelState {
+    fn on_queued(&self) {
+        let queued = self.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        self.high_water_mark.fetch_max(queued, Ordering::Relaxed);
+    }
+
+    fn on_received(&self, bytes: &Bytes) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> ChannelStats {
+        ChannelStats {
+            messages: self.messages.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            high_water_mark: self.high_water_mark.load(Ordering::Relaxed),
+        }
+    }
+
+    /// the first reason is kept
+    fn set_close_reason(&self, reason: &io::Error) {
+        self.close_reason
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| (reason.kind(), reason.to_string()));
+    }
+
+    fn close_reason(&self) -> Option<io::Error> {
+        self.close_reason
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(kind, message)| io::Error::new(*kind, message.clone()))
+    }
+
+    /// what a send into the closed channel fails with
+    fn closed_error(&self) -> io::Error {
+        self.close_reason().unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the receiver of the channel is gone",
+            )
+        })
+    }
+}
+
+/// a bounded channel of messages, the sender pends while `capacity` messages are queued
+pub fn channel(capacity: usize) -> (ChannelSender, ChannelReceiver) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    let state: Arc<ChannelState> = Default::default();
+    (
+        ChannelSender {
+            sender: PollSender::new(sender),
+            state: state.clone(),
+        },
+        ChannelReceiver {
+            receiver,
+            state,
+            reason_told: false,
+        },
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelSender {
+    sender: PollSender<Bytes>,
+    state: Arc<ChannelState>,
+}
+
+impl ChannelSender {
+    /// fails with WouldBlock if the channel is full
+    pub fn try_send(&self, bytes: Bytes) -> io::Result<()> {
+        let sender = self
+            .sender
+            .get_ref()
+            .ok_or_else(|| self.state.closed_error())?;
+        match sender.try_send(bytes) {
+            Ok(()) => {
+                self.state.on_queued();
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "the channel is full",
+            )),
+            Err(mpsc::error::TrySendError::Closed(_)) => Err(self.state.closed_error()),
+        }
+    }
+
+    /// the receiver reads the messages queued before, then `reason` in place of the end of the channel.
+    /// the other clones of the sender may still send
+    pub fn close_with_reason(&mut self, reason: io::Error) {
+        self.state.set_close_reason(&reason);
+        self.sender.close();
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        self.state.stats()
+    }
+}
+
+impl Sink<Bytes> for ChannelSender {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let state = self.state.clone();
+        self.sender
+            .poll_ready_unpin(cx)
+            .map_err(|_| state.closed_error())
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        self.sender
+            .start_send_unpin(item)
+            .map_err(|_| self.state.closed_error())?;
+        self.state.on_queued();
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let state = self.state.clone();
+        self.sender
+            .poll_close_unpin(cx)
+            .map_err(|_| state.closed_error())
+    }
+}
+
+/// ends once all the senders are gone, with the reason of the one closed with one
+#[derive(Debug)]
+pub struct ChannelReceiver {
+    receiver: mpsc::Receiver<Bytes>,
+    state: Arc<ChannelState>,
+    reason_told: bool,
+}
+
+impl ChannelReceiver {
+    /// the senders fail with `reason` from now on, the messages queued before are still read
+    pub fn close_with_reason(&mut self, reason: io::Error) {
+        self.state.set_close_reason(&reason);
+        self.receiver.close();
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        self.state.stats()
+    }
+}
+
+impl Stream for ChannelReceiver {
+    type Item = io::Result<Bytes>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match ready!(self.receiver.poll_recv(cx)) {
+            Some(bytes) => {
+                self.state.on_received(&bytes);
+                Poll::Ready(Some(Ok(bytes)))
+            }
+            None if self.reason_told => Poll::Ready(None),
+            None => {
+                self.reason_told = true;
+                Poll::Ready(self.state.close_reason().map(Err))
+            }
+        }
+    }
+}
+
+/// one end of an in-memory connection made of a channel each way
 #[derive(Debug)]
 pub struct ChannelIo {
-    pub(crate) source: tokio::sync::mpsc::Receiver<Bytes>,
-    pub(crate) sink: PollSender<Bytes>,
+    pub(crate) source: ChannelReceiver,
+    pub(crate) sink: ChannelSender,
 }
 
 impl ChannelIo {
-    pub fn new(
-        source: tokio::sync::mpsc::Receiver<Bytes>,
-        sink: tokio::sync::mpsc::Sender<Bytes>,
-    ) -> Self {
-        Self {
-            source,
-            sink: PollSender::new(sink),
-        }
+    pub fn new(source: ChannelReceiver, sink: ChannelSender) -> Self {
+        Self { source, sink }
+    }
+
+    /// two ends of an in-memory connection, what is sent on one is received on the other,
+    /// a send pends while `capacity` messages are not received yet
+    pub fn pair(capacity: usize) -> (Self, Self) {
+        let (a_tx, b_rx) = channel(capacity);
+        let (b_tx, a_rx) = channel(capacity);
+        (Self::new(a_rx, a_tx), Self::new(b_rx, b_tx))
+    }
+
+    /// the peer reads `reason` after the messages sent before, and its sends fail with it
+    pub fn close_with_reason(&mut self, reason: io::Error) {
+        self.source
+            .close_with_reason(io::Error::new(reason.kind(), reason.to_string()));
+        self.sink.close_with_reason(reason);
+    }
+
+    /// what this end sent, the peer received
+    pub fn sent_stats(&self) -> ChannelStats {
+        self.sink.stats()
+    }
+
+    /// what the peer sent, this end received
+    pub fn received_stats(&self) -> ChannelStats {
+        self.source.stats()
     }
 }
 
@@ -33,44 +250,162 @@ impl UnifiedIO for ChannelIo {
 }
 
 impl Sink<Bytes> for ChannelIo {
-    type Error = std::io::Error;
-    fn start_send(mut self: std::pin::Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
-        self.sink
-            .start_send_unpin(item)
-            .map_err(|err| std::io::Error::other(err.to_string()))
+    type Error = io::Error;
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        self.sink.start_send_unpin(item)
     }
-    fn poll_ready(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), Self::Error>> {
-        self.sink
-            .poll_ready_unpin(cx)
-            .map_err(|err| std::io::Error::other(err.to_string()))
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_ready_unpin(cx)
     }
 
-    fn poll_close(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), Self::Error>> {
-        self.sink
-            .poll_close_unpin(cx)
-            .map_err(|err| std::io::Error::other(err.to_string()))
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_close_unpin(cx)
     }
 
-    fn poll_flush(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), std::io::Error>> {
-        std::task::Poll::Ready(Ok(()))
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.sink.poll_flush_unpin(cx)
     }
 }
 
 impl Stream for ChannelIo {
-    type Item = Result<Bytes, std::io::Error>;
+    type Item = io::Result<Bytes>;
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match ready!(self.source.poll_recv(cx)) {
-            Some(bytes) => Poll::Ready(Some(Ok(bytes))),
-            None => Poll::Ready(None),
+        self.source.poll_next_unpin(cx)
+    }
+}
+
+/// hands connections made in process to a server in place of a tcp listener
+#[derive(Debug)]
+pub struct ChannelListener<T> {
+    incoming: mpsc::Receiver<(T, SocketAddr)>,
+}
+
+impl<T> ChannelListener<T> {
+    /// the server end of the next connection and the address its peer pretends to have,
+    /// None if all the connectors are dropped
+    pub async fn accept(&mut self) -> Option<(T, SocketAddr)> {
+        self.incoming.recv().await
+    }
+}
+
+#[derive(Debug)]
+pub struct ChannelConnector<T> {
+    outgoing: mpsc::Sender<(T, SocketAddr)>,
+}
+
+impl<T> Clone for ChannelConnector<T> {
+    fn clone(&self) -> Self {
+        Self {
+            outgoing: self.outgoing.clone(),
         }
     }
 }
+
+pub fn channel_listener<T>(backlog: usize) -> (ChannelConnector<T>, ChannelListener<T>) {
+    let (outgoing, incoming) = mpsc::channel(backlog);
+    (ChannelConnector { outgoing }, ChannelListener { incoming })
+}
+
+impl<T> ChannelConnector<T> {
+    /// queues `server_end` to be accepted by the listener
+    pub async fn connect_with(&self, server_end: T, peer_addr: SocketAddr) -> io::Result<()> {
+        self.outgoing
+            .send((server_end, peer_addr))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, "listener is closed"))
+    }
+}
+
+impl ChannelConnector<ChannelIo> {
+    /// connects to a server taking message oriented io, e.g. rtsp
+    pub async fn connect(&self, peer_addr: SocketAddr, buffer: usize) -> io::Result<ChannelIo> {
+        let (client_end, server_end) = ChannelIo::pair(buffer);
+        self.connect_with(server_end, peer_addr).await?;
+        Ok(client_end)
+    }
+}
+
+impl ChannelConnector<DuplexStream> {
+    /// connects to a server taking byte streams, e.g. rtmp
+    pub async fn connect(
+        &self,
+        peer_addr: SocketAddr,
+        max_buf_size: usize,
+    ) -> io::Result<DuplexStream> {
+        let (client_end, server_end) = tokio::io::duplex(max_buf_size);
+        self.connect_with(server_end, peer_addr).await?;
+        Ok(client_end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::bytes::Bytes;
+
+    use super::{ChannelIo, ChannelStats};
+
+    #[tokio::test]
+    async fn senders_pend_while_the_channel_is_full() {
+        let (mut a, mut b) = ChannelIo::pair(2);
+        a.send(Bytes::from_static(b"1")).await.unwrap();
+        a.send(Bytes::from_static(b"22")).await.unwrap();
+        assert!(
+            tokio::time::timeout(
+                Duration::from_millis(50),
+                a.send(Bytes::from_static(b"333"))
+            )
+            .await
+            .is_err()
+        );
+
+        assert_eq!(b.next().await.unwrap().unwrap(), Bytes::from_static(b"1"));
+        a.send(Bytes::from_static(b"333")).await.unwrap();
+        assert_eq!(b.next().await.unwrap().unwrap(), Bytes::from_static(b"22"));
+        assert_eq!(b.next().await.unwrap().unwrap(), Bytes::from_static(b"333"));
+        let stats = ChannelStats {
+            messages: 3,
+            bytes: 6,
+            high_water_mark: 2,
+        };
+        assert_eq!(a.sent_stats(), stats);
+        assert_eq!(b.received_stats(), stats);
+        assert_eq!(b.sent_stats(), ChannelStats::default());
+    }
+
+    #[tokio::test]
+    async fn the_peer_reads_the_close_reason_after_the_messages() {
+        let (mut a, mut b) = ChannelIo::pair(8);
+        a.send(Bytes::from_static(b"last")).await.unwrap();
+        a.close_with_reason(io::Error::new(io::ErrorKind::TimedOut, "no keepalive"));
+
+        assert_eq!(
+            b.next().await.unwrap().unwrap(),
+            Bytes::from_static(b"last")
+        );
+        let err = b.next().await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(err.to_string(), "no keepalive");
+        assert!(b.next().await.is_none());
+
+        let err = b.send(Bytes::from_static(b"late")).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn dropping_an_end_wakes_the_pending_reader() {
+        let (a, mut b) = ChannelIo::pair(8);
+        let reading = tokio::spawn(async move { b.next().await.is_none() });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(a);
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), reading)
+                .await
+                .unwrap()
+                .unwrap()
+        );
+    }
+}
@@ -1,9 +1,23 @@
+use std::net::SocketAddr;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum UnifiedIOError {
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("tls error: {0}")]
+    Tls(#[from] tokio_rustls::rustls::Error),
+    #[error("invalid tls certificate or key: {0}")]
+    InvalidCertificate(String),
+    #[error("invalid ip network: {0}")]
+    InvalidCidr(String),
+    #[error("invalid proxy protocol header: {0}")]
+    InvalidProxyHeader(String),
+    #[error("proxy protocol header from untrusted peer {0}")]
+    UntrustedProxyHeader(SocketAddr),
+    #[error("no proxy protocol header from {0} in time")]
+    ProxyHeaderTimeout(SocketAddr),
 }
 
 pub type UnifiedIOResult<T> = Result<T, UnifiedIOError>;
@@ -9,9 +9,12 @@ use tokio_util::{
     bytes::{Bytes, BytesMut},
     codec::{Decoder, Encoder},
 };
+use utils::bytes::pool;
 pub mod channel;
-mod errors;
+pub mod errors;
+pub mod proxy_protocol;
 pub mod tcp;
+pub mod tls;
 pub mod udp;
 
 pub enum UnderlyingIO {
@@ -47,22 +50,40 @@ pub trait UnifiedIO:
 }
 
 const INITIAL_RD_CAPACITY: usize = 64 * 1024;
+/// room for an encoded packet of the mtu, larger items grow the buffer
+const ENCODE_BUFFER_SIZE: usize = 1500;
 pub struct UnifiyStreamed<C> {
     io: Pin<Box<dyn UnifiedIO>>,
     read_buffer: BytesMut,
     is_readable: bool,
+    /// the bytes not decoded yet are kept for the next read, instead of dropped with the datagram
+    byte_stream: bool,
     codec: C,
 }
 
 impl<C> UnifiyStreamed<C> {
+    /// each read is a datagram decoded on its own, e.g., rtp packets
     pub fn new(io: Pin<Box<dyn UnifiedIO>>, codec: C) -> Self {
         Self {
             io,
             read_buffer: BytesMut::new(),
             is_readable: false,
+            byte_stream: false,
             codec,
         }
     }
+
+    /// the reads are a stream of bytes, a frame may arrive split over many of them, e.g., rtsp over tcp
+    pub fn new_byte_stream(io: Pin<Box<dyn UnifiedIO>>, codec: C) -> Self {
+        Self {
+            byte_stream: true,
+            ..Self::new(io, codec)
+        }
+    }
+
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
 }
 
 impl<C> Unpin for UnifiyStreamed<C> {}
@@ -79,16 +100,27 @@ where
         let pin = self.get_mut();
         pin.read_buffer.reserve(INITIAL_RD_CAPACITY);
         loop {
-            if pin.is_readable
-                && let Some(frame) = pin.codec.decode_eof(&mut pin.read_buffer)?
-            {
-                return Poll::Ready(Some(Ok(frame)));
+            if pin.is_readable {
+                let frame = if pin.byte_stream {
+                    pin.codec.decode(&mut pin.read_buffer)?
+                } else {
+                    pin.codec.decode_eof(&mut pin.read_buffer)?
+                };
+                if let Some(frame) = frame {
+                    return Poll::Ready(Some(Ok(frame)));
+                }
             }
             pin.is_readable = false;
-            pin.read_buffer.clear();
+            if !pin.byte_stream {
+                pin.read_buffer.clear();
+            }
 
             let res = ready!(pin.io.poll_next_unpin(cx));
             if res.is_none() {
+                if pin.byte_stream {
+                    // the frame cut short by the end of the stream is an error of the codec
+                    return Poll::Ready(pin.codec.decode_eof(&mut pin.read_buffer).transpose());
+                }
                 return Poll::Ready(None);
             }
             pin.read_buffer.extend(res.unwrap()?);
@@ -125,10 +157,34 @@ where
 
     fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
         let pin = self.get_mut();
-        let mut bytes = BytesMut::new();
-        pin.codec.encode(item, &mut bytes)?;
+        let mut buffer = pool::take(ENCODE_BUFFER_SIZE);
+        pin.codec.encode(item, &mut buffer)?;
         pin.io
-            .start_send_unpin(bytes.freeze())
+            .start_send_unpin(buffer.split_freeze())
             .map_err(|err| err.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::{bytes::Bytes, codec::LinesCodec};
+
+    use super::UnifiyStreamed;
+    use crate::channel::ChannelIo;
+
+    #[tokio::test]
+    async fn frames_split_over_reads_of_a_byte_stream_are_kept() {
+        let (mut peer, io) = ChannelIo::pair(8);
+        let mut streamed = UnifiyStreamed::new_byte_stream(Box::pin(io), LinesCodec::new());
+        for chunk in ["hel", "lo\nwor", "l", "d\nta", "il"] {
+            peer.send(Bytes::from(chunk)).await.unwrap();
+        }
+        drop(peer);
+        let mut lines = Vec::new();
+        while let Some(line) = streamed.next().await {
+            lines.push(line.unwrap());
+        }
+        assert_eq!(lines, vec!["hello", "world", "tail"]);
+    }
+}
@@ -0,0 +1,698 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    net::TcpStream,
+};
+
+use crate::errors::{UnifiedIOError, UnifiedIOResult};
+
+/// how long a trusted proxy has to send the header, and an untrusted peer its first bytes
+pub const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the longest v1 header, crlf included
+const V1_MAX_LENGTH: usize = 107;
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V2_SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+/// the addresses and the tlvs after the fixed 16 bytes, more than any proxy sends
+const V2_MAX_PAYLOAD_LENGTH: usize = 2048;
+
+/// an ip network, e.g., 10.0.0.0/8 or fd00::/8, a bare address is a network of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> UnifiedIOResult<Self> {
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_prefix_len {
+            return Err(UnifiedIOError::InvalidCidr(format!(
+                "prefix length {} of {} is over {}",
+                prefix_len, addr, max_prefix_len
+            )));
+        }
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// ipv4 mapped ipv6 addresses count as the ipv4 ones
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = UnifiedIOError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|err| UnifiedIOError::InvalidCidr(format!("{}: {}", s, err)))?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .map_err(|err| UnifiedIOError::InvalidCidr(format!("{}: {}", s, err)))?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(addr, prefix_len)
+    }
+}
+
+impl TryFrom<String> for IpCidr {
+    type Error = UnifiedIOError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<IpCidr> for String {
+    fn from(value: IpCidr) -> Self {
+        value.to_string()
+    }
+}
+
+/// the proxies and load balancers the address of the client is taken from,
+/// a connection from any other peer is the client itself
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct TrustedProxies(Vec<IpCidr>);
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<IpCidr>) -> Self {
+        Self(networks)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn networks(&self) -> &[IpCidr] {
+        &self.0
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|network| network.contains(ip))
+    }
+}
+
+impl FromStr for TrustedProxies {
+    type Err = UnifiedIOError;
+    /// comma separated networks
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|network| !network.trim().is_empty())
+            .map(|network| network.parse())
+            .collect::<UnifiedIOResult<_>>()
+            .map(Self)
+    }
+}
+
+/// the header a proxy sends ahead of the bytes of the client, the PROXY protocol v1 or v2
+/// @see: https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyHeader {
+    /// the proxy relays the connection of the client
+    Proxied {
+        source: SocketAddr,
+        destination: SocketAddr,
+    },
+    /// the proxy connects on its own, e.g., a health check, or does not tell the addresses
+    Local,
+}
+
+impl ProxyHeader {
+    /// the address the connection comes from, `peer_addr` if the proxy does not tell
+    pub fn source_or(&self, peer_addr: SocketAddr) -> SocketAddr {
+        match self {
+            Self::Proxied { source, .. } => *source,
+            Self::Local => peer_addr,
+        }
+    }
+
+    /// the text line of v1, crlf included
+    pub fn parse_v1(line: &[u8]) -> UnifiedIOResult<Self> {
+        if line.len() > V1_MAX_LENGTH {
+            return Err(invalid_header(format!(
+                "v1 header of {} bytes is over {}",
+                line.len(),
+                V1_MAX_LENGTH
+            )));
+        }
+        let line = line
+            .strip_suffix(b"\r\n")
+            .ok_or_else(|| invalid_header("v1 header does not end with crlf"))?;
+        let line =
+            std::str::from_utf8(line).map_err(|_| invalid_header("v1 header is not ascii"))?;
+        let mut fields = line.split(' ');
+        if fields.next() != Some("PROXY") {
+            return Err(invalid_header(format!("not a v1 header: {}", line)));
+        }
+        let is_ipv4 = match fields.next() {
+            Some("TCP4") => true,
+            Some("TCP6") => false,
+            // the rest of the line is to be ignored
+            Some("UNKNOWN") => return Ok(Self::Local),
+            protocol => {
+                return Err(invalid_header(format!(
+                    "unknown v1 protocol: {:?}",
+                    protocol
+                )));
+            }
+        };
+        let [
+            Some(source_ip),
+            Some(destination_ip),
+            Some(source_port),
+            Some(destination_port),
+        ] = [fields.next(), fields.next(), fields.next(), fields.next()]
+        else {
+            return Err(invalid_header(format!("v1 header misses fields: {}", line)));
+        };
+        if fields.next().is_some() {
+            return Err(invalid_header(format!(
+                "v1 header has extra fields: {}",
+                line
+            )));
+        }
+        let parse_ip = |ip: &str| -> UnifiedIOResult<IpAddr> {
+            let parsed = if is_ipv4 {
+                ip.parse::<Ipv4Addr>().map(IpAddr::V4)
+            } else {
+                ip.parse::<Ipv6Addr>().map(IpAddr::V6)
+            };
+            parsed.map_err(|err| invalid_header(format!("bad v1 address {}: {}", ip, err)))
+        };
+        let parse_port = |port: &str| -> UnifiedIOResult<u16> {
+            // no sign and no leading zeros
+            if port.is_empty()
+                || !port.bytes().all(|b| b.is_ascii_digit())
+                || (port.len() > 1 && port.starts_with('0'))
+            {
+                return Err(invalid_header(format!("bad v1 port: {}", port)));
+            }
+            port.parse()
+                .map_err(|err| invalid_header(format!("bad v1 port {}: {}", port, err)))
+        };
+        Ok(Self::Proxied {
+            source: SocketAddr::new(parse_ip(source_ip)?, parse_port(source_port)?),
+            destination: SocketAddr::new(parse_ip(destination_ip)?, parse_port(destination_port)?),
+        })
+    }
+
+    /// the 16 fixed bytes of v2 and the `len` bytes after them
+    pub fn parse_v2(header: &[u8]) -> UnifiedIOResult<Self> {
+        if header.len() < 16 || header[..12] != V2_SIGNATURE {
+            return Err(invalid_header("not a v2 header"));
+        }
+        let version = header[12] >> 4;
+        let command = header[12] & 0x0F;
+        if version != 2 {
+            return Err(invalid_header(format!("unknown v2 version: {}", version)));
+        }
+        let length = u16::from_be_bytes([header[14], header[15]]) as usize;
+        let payload = &header[16..];
+        if payload.len() != length {
+            return Err(invalid_header(format!(
+                "v2 header tells {} bytes of addresses, got {}",
+                length,
+                payload.len()
+            )));
+        }
+        match command {
+            // the addresses, if any, are to be ignored
+            0x0 => return Ok(Self::Local),
+            0x1 => {}
+            _ => return Err(invalid_header(format!("unknown v2 command: {}", command))),
+        }
+        let family = header[13] >> 4;
+        let transport = header[13] & 0x0F;
+        match (family, transport) {
+            // tcp over ipv4
+            (0x1, 0x1) => {
+                if payload.len() < 12 {
+                    return Err(invalid_header("v2 ipv4 addresses are cut short"));
+                }
+                let source_ip = Ipv4Addr::from(<[u8; 4]>::try_from(&payload[0..4]).unwrap());
+                let destination_ip = Ipv4Addr::from(<[u8; 4]>::try_from(&payload[4..8]).unwrap());
+                Ok(Self::Proxied {
+                    source: SocketAddr::new(
+                        source_ip.into(),
+                        u16::from_be_bytes([payload[8], payload[9]]),
+                    ),
+                    destination: SocketAddr::new(
+                        destination_ip.into(),
+                        u16::from_be_bytes([payload[10], payload[11]]),
+                    ),
+                })
+            }
+            // tcp over ipv6
+            (0x2, 0x1) => {
+                if payload.len() < 36 {
+                    return Err(invalid_header("v2 ipv6 addresses are cut short"));
+                }
+                let source_ip = Ipv6Addr::from(<[u8; 16]>::try_from(&payload[0..16]).unwrap());
+                let destination_ip =
+                    Ipv6Addr::from(<[u8; 16]>::try_from(&payload[16..32]).unwrap());
+                Ok(Self::Proxied {
+                    source: SocketAddr::new(
+                        source_ip.into(),
+                        u16::from_be_bytes([payload[32], payload[33]]),
+                    ),
+                    destination: SocketAddr::new(
+                        destination_ip.into(),
+                        u16::from_be_bytes([payload[34], payload[35]]),
+                    ),
+                })
+            }
+            // unspecified, unix sockets and datagrams tell no tcp client
+            (0x0..=0x3, _) => Ok(Self::Local),
+            _ => Err(invalid_header(format!(
+                "unknown v2 address family: {}",
+                family
+            ))),
+        }
+    }
+
+    pub fn to_v1_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Proxied {
+                source,
+                destination,
+            } if source.is_ipv4() == destination.is_ipv4() => format!(
+                "PROXY {} {} {} {} {}\r\n",
+                if source.is_ipv4() { "TCP4" } else { "TCP6" },
+                source.ip(),
+                destination.ip(),
+                source.port(),
+                destination.port()
+            )
+            .into_bytes(),
+            _ => b"PROXY UNKNOWN\r\n".to_vec(),
+        }
+    }
+
+    pub fn to_v2_bytes(&self) -> Vec<u8> {
+        let mut bytes = V2_SIGNATURE.to_vec();
+        let addresses = match self {
+            Self::Proxied {
+                source: SocketAddr::V4(source),
+                destination: SocketAddr::V4(destination),
+            } => {
+                let mut addresses = Vec::with_capacity(12);
+                addresses.extend_from_slice(&source.ip().octets());
+                addresses.extend_from_slice(&destination.ip().octets());
+                addresses.extend_from_slice(&source.port().to_be_bytes());
+                addresses.extend_from_slice(&destination.port().to_be_bytes());
+                Some((0x11, addresses))
+            }
+            Self::Proxied {
+                source: SocketAddr::V6(source),
+                destination: SocketAddr::V6(destination),
+            } => {
+                let mut addresses = Vec::with_capacity(36);
+                addresses.extend_from_slice(&source.ip().octets());
+                addresses.extend_from_slice(&destination.ip().octets());
+                addresses.extend_from_slice(&source.port().to_be_bytes());
+                addresses.extend_from_slice(&destination.port().to_be_bytes());
+                Some((0x21, addresses))
+            }
+            _ => None,
+        };
+        match addresses {
+            Some((family, addresses)) => {
+                bytes.push(0x21);
+                bytes.push(family);
+                bytes.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+                bytes.extend_from_slice(&addresses);
+            }
+            None => bytes.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]),
+        }
+        bytes
+    }
+}
+
+fn invalid_header<S: Into<String>>(message: S) -> UnifiedIOError {
+    UnifiedIOError::InvalidProxyHeader(message.into())
+}
+
+/// reads the header and nothing after it, so the bytes of the client are left to the protocol
+pub async fn read_proxy_header<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> UnifiedIOResult<ProxyHeader> {
+    // both versions are longer than the v2 signature
+    let mut header = vec![0u8; V2_SIGNATURE.len()];
+    reader.read_exact(&mut header).await?;
+    if header == V2_SIGNATURE {
+        let mut fixed = [0u8; 4];
+        reader.read_exact(&mut fixed).await?;
+        header.extend_from_slice(&fixed);
+        let length = u16::from_be_bytes([fixed[2], fixed[3]]) as usize;
+        if length > V2_MAX_PAYLOAD_LENGTH {
+            return Err(invalid_header(format!(
+                "v2 header of {} bytes is over {}",
+                length, V2_MAX_PAYLOAD_LENGTH
+            )));
+        }
+        let mut payload = vec![0u8; length];
+        reader.read_exact(&mut payload).await?;
+        header.extend_from_slice(&payload);
+        return ProxyHeader::parse_v2(&header);
+    }
+    if !header.starts_with(V1_PREFIX) {
+        return Err(invalid_header("no proxy protocol signature"));
+    }
+    // a byte at a time, the line ends where the bytes of the client start
+    while !header.ends_with(b"\r\n") {
+        if header.len() >= V1_MAX_LENGTH {
+            return Err(invalid_header(format!(
+                "v1 header is over {} bytes",
+                V1_MAX_LENGTH
+            )));
+        }
+        header.push(reader.read_u8().await?);
+    }
+    ProxyHeader::parse_v1(&header)
+}
+
+/// whether the first bytes of a connection are a proxy protocol header, as far as they tell
+fn looks_like_proxy_header(first_bytes: &[u8]) -> bool {
+    first_bytes.starts_with(V1_PREFIX)
+        || (first_bytes.len() >= 5
+            && V2_SIGNATURE.starts_with(&first_bytes[..first_bytes.len().min(V2_SIGNATURE.len())]))
+}
+
+/// the address of the client a tcp connection comes from, before any byte of the protocol is read.
+/// a trusted proxy has to send a header first, the address of the client is taken from it.
+/// a header from any other peer is a spoofing attempt and the connection is refused,
+/// the first bytes of the peer are only peeked at, so the protocol still reads them
+pub async fn resolve_peer_addr(
+    stream: &mut TcpStream,
+    peer_addr: SocketAddr,
+    trusted_proxies: &TrustedProxies,
+) -> UnifiedIOResult<SocketAddr> {
+    if trusted_proxies.is_empty() {
+        return Ok(peer_addr);
+    }
+    if trusted_proxies.contains(peer_addr.ip()) {
+        let header = tokio::time::timeout(PROXY_HEADER_TIMEOUT, read_proxy_header(stream))
+            .await
+            .map_err(|_| UnifiedIOError::ProxyHeaderTimeout(peer_addr))??;
+        let client_addr = header.source_or(peer_addr);
+        tracing::debug!(
+            "proxy protocol header from {}, client addr: {}",
+            peer_addr,
+            client_addr
+        );
+        return Ok(client_addr);
+    }
+    let mut first_bytes = [0u8; V2_SIGNATURE.len()];
+    // a silent peer is left to the timeouts of its protocol
+    let peeked =
+        match tokio::time::timeout(PROXY_HEADER_TIMEOUT, stream.peek(&mut first_bytes)).await {
+            Ok(peeked) => peeked?,
+            Err(_) => return Ok(peer_addr),
+        };
+    if looks_like_proxy_header(&first_bytes[..peeked]) {
+        return Err(UnifiedIOError::UntrustedProxyHeader(peer_addr));
+    }
+    Ok(peer_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, SocketAddr};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{IpCidr, ProxyHeader, TrustedProxies, read_proxy_header, resolve_peer_addr};
+    use crate::errors::UnifiedIOError;
+
+    fn proxied(source: &str, destination: &str) -> ProxyHeader {
+        ProxyHeader::Proxied {
+            source: source.parse().unwrap(),
+            destination: destination.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn cidr_contains() {
+        let network: IpCidr = "10.1.0.0/16".parse().unwrap();
+        assert!(network.contains("10.1.200.3".parse().unwrap()));
+        assert!(network.contains("::ffff:10.1.0.1".parse().unwrap()));
+        assert!(!network.contains("10.2.0.1".parse().unwrap()));
+        assert!(!network.contains("fd00::1".parse().unwrap()));
+        let network: IpCidr = "fd00::/8".parse().unwrap();
+        assert!(network.contains("fd12::1".parse().unwrap()));
+        assert!(!network.contains("fe80::1".parse().unwrap()));
+        let host: IpCidr = "192.168.1.1".parse().unwrap();
+        assert_eq!(host.to_string(), "192.168.1.1/32");
+        assert!(!host.contains("192.168.1.2".parse().unwrap()));
+        let any: IpCidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("8.8.8.8".parse().unwrap()));
+        for text in ["10.0.0.0/33", "fd00::/129", "10.0.0/8", "10.0.0.0/x", ""] {
+            assert!(text.parse::<IpCidr>().is_err(), "{}", text);
+        }
+        let trusted: TrustedProxies = "10.0.0.0/8, 127.0.0.1".parse().unwrap();
+        assert_eq!(trusted.networks().len(), 2);
+        assert!(trusted.contains(IpAddr::from([127, 0, 0, 1])));
+        assert!(!trusted.contains(IpAddr::from([127, 0, 0, 2])));
+    }
+
+    #[test]
+    fn v1_headers() {
+        let header =
+            ProxyHeader::parse_v1(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n").unwrap();
+        assert_eq!(header, proxied("192.168.0.1:56324", "192.168.0.11:443"));
+        assert_eq!(
+            header.to_v1_bytes(),
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n"
+        );
+        let header = proxied("[2001:db8::1]:1935", "[2001:db8::2]:554");
+        assert_eq!(
+            ProxyHeader::parse_v1(&header.to_v1_bytes()).unwrap(),
+            header
+        );
+        assert_eq!(
+            ProxyHeader::parse_v1(b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\n").unwrap(),
+            ProxyHeader::Local
+        );
+        assert_eq!(ProxyHeader::Local.to_v1_bytes(), b"PROXY UNKNOWN\r\n");
+
+        for malformed in [
+            &b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443"[..],
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 56324\r\n",
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443 1\r\n",
+            b"PROXY TCP4 2001:db8::1 192.168.0.11 56324 443\r\n",
+            b"PROXY TCP6 192.168.0.1 192.168.0.11 56324 443\r\n",
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 65536 443\r\n",
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 +1 443\r\n",
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 0443 443\r\n",
+            b"PROXY UDP4 192.168.0.1 192.168.0.11 56324 443\r\n",
+            b"PROXY  TCP4 192.168.0.1 192.168.0.11 56324 443\r\n",
+            b"GET / HTTP/1.1\r\n",
+        ] {
+            assert!(
+                ProxyHeader::parse_v1(malformed).is_err(),
+                "{}",
+                String::from_utf8_lossy(malformed)
+            );
+        }
+        let too_long = format!("PROXY UNKNOWN {}\r\n", "a".repeat(100));
+        assert!(ProxyHeader::parse_v1(too_long.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn v2_headers() {
+        for header in [
+            proxied("192.168.0.1:56324", "192.168.0.11:443"),
+            proxied("[2001:db8::1]:1935", "[2001:db8::2]:554"),
+            ProxyHeader::Local,
+        ] {
+            assert_eq!(
+                ProxyHeader::parse_v2(&header.to_v2_bytes()).unwrap(),
+                header
+            );
+        }
+        let bytes = proxied("192.168.0.1:56324", "192.168.0.11:443").to_v2_bytes();
+        assert_eq!(bytes.len(), 28);
+        assert_eq!(&bytes[12..16], &[0x21, 0x11, 0x00, 0x0C]);
+
+        // tlvs after the addresses are skipped
+        let mut with_tlv = bytes.clone();
+        with_tlv[15] += 4;
+        with_tlv.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]);
+        assert_eq!(
+            ProxyHeader::parse_v2(&with_tlv).unwrap(),
+            proxied("192.168.0.1:56324", "192.168.0.11:443")
+        );
+
+        let mut bad_version = bytes.clone();
+        bad_version[12] = 0x11;
+        let mut bad_command = bytes.clone();
+        bad_command[12] = 0x2F;
+        let mut bad_family = bytes.clone();
+        bad_family[13] = 0x41;
+        let mut cut_short = bytes.clone();
+        cut_short[15] = 8;
+        cut_short.truncate(24);
+        let mut bad_length = bytes.clone();
+        bad_length.pop();
+        let mut bad_signature = bytes.clone();
+        bad_signature[11] = 0x0B;
+        for malformed in [
+            bad_version,
+            bad_command,
+            bad_family,
+            cut_short,
+            bad_length,
+            bad_signature,
+        ] {
+            assert!(
+                ProxyHeader::parse_v2(&malformed).is_err(),
+                "{:?}",
+                malformed
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn header_is_read_up_to_the_bytes_of_the_client() {
+        for header in [
+            proxied("203.0.113.7:40000", "10.0.0.1:1935").to_v1_bytes(),
+            proxied("203.0.113.7:40000", "10.0.0.1:1935").to_v2_bytes(),
+        ] {
+            let mut bytes = header.clone();
+            bytes.extend_from_slice(b"\x03rtmp");
+            let mut reader = bytes.as_slice();
+            assert_eq!(
+                read_proxy_header(&mut reader).await.unwrap(),
+                proxied("203.0.113.7:40000", "10.0.0.1:1935")
+            );
+            assert_eq!(reader, b"\x03rtmp");
+        }
+        let endless = [b"PROXY TCP4 ".as_slice(), &[b'1'; 200]].concat();
+        assert!(matches!(
+            read_proxy_header(&mut endless.as_slice()).await,
+            Err(UnifiedIOError::InvalidProxyHeader(_))
+        ));
+    }
+
+    /// connects to a listener on the loopback interface, the address of the client is 127.0.0.1
+    async fn connect(
+        client_bytes: Vec<u8>,
+        trusted_proxies: &TrustedProxies,
+    ) -> (Result<SocketAddr, UnifiedIOError>, Vec<u8>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream.write_all(&client_bytes).await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+        let (mut stream, peer_addr) = listener.accept().await.unwrap();
+        let resolved = resolve_peer_addr(&mut stream, peer_addr, trusted_proxies).await;
+        client.await.unwrap();
+        let mut rest = Vec::new();
+        if resolved.is_ok() {
+            stream.read_to_end(&mut rest).await.unwrap();
+        }
+        (resolved, rest)
+    }
+
+    #[tokio::test]
+    async fn trusted_proxy_tells_the_client_addr() {
+        let trusted: TrustedProxies = "127.0.0.0/8".parse().unwrap();
+        let header = proxied("203.0.113.7:40000", "10.0.0.1:554");
+        for header_bytes in [header.to_v1_bytes(), header.to_v2_bytes()] {
+            let (resolved, rest) = connect(
+                [header_bytes, b"OPTIONS * RTSP/1.0\r\n".to_vec()].concat(),
+                &trusted,
+            )
+            .await;
+            assert_eq!(resolved.unwrap(), "203.0.113.7:40000".parse().unwrap());
+            assert_eq!(rest, b"OPTIONS * RTSP/1.0\r\n");
+        }
+
+        // a health check of the proxy is the proxy itself
+        let (resolved, _) = connect(ProxyHeader::Local.to_v2_bytes(), &trusted).await;
+        assert_eq!(resolved.unwrap().ip(), IpAddr::from([127, 0, 0, 1]));
+
+        // the header is required from a trusted proxy
+        let (resolved, _) = connect(b"OPTIONS * RTSP/1.0\r\n".to_vec(), &trusted).await;
+        assert!(matches!(
+            resolved,
+            Err(UnifiedIOError::InvalidProxyHeader(_))
+        ));
+        let (resolved, _) = connect(
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 40000\r\nOPTIONS".to_vec(),
+            &trusted,
+        )
+        .await;
+        assert!(matches!(
+            resolved,
+            Err(UnifiedIOError::InvalidProxyHeader(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn untrusted_peer_may_not_send_a_header() {
+        let trusted: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+        let header = proxied("203.0.113.7:40000", "10.0.0.1:554");
+        for header_bytes in [header.to_v1_bytes(), header.to_v2_bytes()] {
+            let (resolved, _) = connect(
+                [header_bytes, b"OPTIONS * RTSP/1.0\r\n".to_vec()].concat(),
+                &trusted,
+            )
+            .await;
+            assert!(matches!(
+                resolved,
+                Err(UnifiedIOError::UntrustedProxyHeader(addr)) if addr.ip() == IpAddr::from([127, 0, 0, 1])
+            ));
+        }
+
+        // the bytes of a client connecting directly are left to the protocol
+        let (resolved, rest) =
+            connect(b"PLAY rtsp://host/live RTSP/1.0\r\n".to_vec(), &trusted).await;
+        assert_eq!(resolved.unwrap().ip(), IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(rest, b"PLAY rtsp://host/live RTSP/1.0\r\n");
+
+        // no trusted proxy, no header is looked for
+        let (resolved, rest) = connect(header.to_v1_bytes(), &TrustedProxies::default()).await;
+        assert_eq!(resolved.unwrap().ip(), IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(rest, header.to_v1_bytes());
+    }
+}
@@ -1,8 +1,11 @@
-use std::{net::SocketAddr, task::Poll};
+use std::{fmt::Debug, net::SocketAddr, task::Poll};
 
 use futures::{Sink, SinkExt, Stream, StreamExt, ready};
 use std::pin::Pin;
-use tokio::net::TcpStream;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
 use tokio_util::{
     bytes::Bytes,
     codec::{BytesCodec, Framed},
@@ -10,9 +13,17 @@ use tokio_util::{
 
 use crate::UnifiedIO;
 
+/// a byte stream over tcp, either plain or wrapped by tls.
+/// sync so that a session owning one can be borrowed across awaits of a spawned task
+pub trait AsyncReadWrite: AsyncRead + AsyncWrite + Debug + Send + Sync + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Debug + Send + Sync + Unpin> AsyncReadWrite for T {}
+
+pub type BoxedStream = Box<dyn AsyncReadWrite>;
+
 #[derive(Debug)]
 pub struct TcpIO {
-    inner: Framed<TcpStream, BytesCodec>,
+    inner: Framed<BoxedStream, BytesCodec>,
     local_addr: SocketAddr,
     peer_addr: SocketAddr,
 }
@@ -22,6 +33,15 @@ impl TcpIO {
         Self {
             local_addr: inner.local_addr().unwrap(),
             peer_addr: inner.peer_addr().unwrap(),
+            inner: Framed::new(Box::new(inner), BytesCodec::new()),
+        }
+    }
+
+    /// wraps a stream running on top of a tcp connection, e.g., a tls stream
+    pub fn with_stream(inner: BoxedStream, local_addr: SocketAddr, peer_addr: SocketAddr) -> Self {
+        Self {
+            local_addr,
+            peer_addr,
             inner: Framed::new(inner, BytesCodec::new()),
         }
     }
@@ -45,19 +65,28 @@ impl Sink<Bytes> for TcpIO {
         mut self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Result<(), Self::Error>> {
-        <tokio_util::codec::Framed<tokio::net::TcpStream, tokio_util::codec::BytesCodec> as futures::SinkExt<Bytes>>::poll_ready_unpin(&mut self.inner, cx)
+        <Framed<BoxedStream, BytesCodec> as futures::SinkExt<Bytes>>::poll_ready_unpin(
+            &mut self.inner,
+            cx,
+        )
     }
     fn poll_close(
         mut self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Result<(), Self::Error>> {
-        <tokio_util::codec::Framed<tokio::net::TcpStream, tokio_util::codec::BytesCodec> as futures::SinkExt<Bytes>>::poll_close_unpin(&mut self.inner, cx)
+        <Framed<BoxedStream, BytesCodec> as futures::SinkExt<Bytes>>::poll_close_unpin(
+            &mut self.inner,
+            cx,
+        )
     }
     fn poll_flush(
         mut self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Result<(), Self::Error>> {
-        <tokio_util::codec::Framed<tokio::net::TcpStream, tokio_util::codec::BytesCodec> as futures::SinkExt<Bytes>>::poll_flush_unpin(&mut self.inner, cx)
+        <Framed<BoxedStream, BytesCodec> as futures::SinkExt<Bytes>>::poll_flush_unpin(
+            &mut self.inner,
+            cx,
+        )
     }
 }
 
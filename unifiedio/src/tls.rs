@@ -0,0 +1,283 @@
+use std::{
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    rustls::{
+        ServerConfig,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    },
+    server::TlsStream,
+};
+
+use crate::{
+    errors::{UnifiedIOError, UnifiedIOResult},
+    tcp::TcpIO,
+};
+
+/// how often the certificate files are checked for changes
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TlsServerConfig {
+    /// PEM encoded certificate chain, leaf certificate first
+    pub cert_chain_path: PathBuf,
+    /// PEM encoded private key, PKCS#1, PKCS#8 or SEC1
+    pub private_key_path: PathBuf,
+}
+
+/// a tls listener of a server, e.g., rtmps or rtsps
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TlsListenerConfig {
+    pub port: u16,
+    pub tls: TlsServerConfig,
+}
+
+fn load_server_config(config: &TlsServerConfig) -> UnifiedIOResult<ServerConfig> {
+    let cert_chain = CertificateDer::pem_file_iter(&config.cert_chain_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| {
+            UnifiedIOError::InvalidCertificate(format!(
+                "load certificate chain from {:?} failed: {}",
+                config.cert_chain_path, err
+            ))
+        })?;
+    if cert_chain.is_empty() {
+        return Err(UnifiedIOError::InvalidCertificate(format!(
+            "no certificate found in {:?}",
+            config.cert_chain_path
+        )));
+    }
+    let private_key = PrivateKeyDer::from_pem_file(&config.private_key_path).map_err(|err| {
+        UnifiedIOError::InvalidCertificate(format!(
+            "load private key from {:?} failed: {}",
+            config.private_key_path, err
+        ))
+    })?;
+
+    let server_config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, private_key)?;
+    Ok(server_config)
+}
+
+/// Performs tls handshakes for accepted tcp connections,
+/// the certificate can be reloaded without restarting the listener
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    config: TlsServerConfig,
+    inner: Arc<RwLock<tokio_rustls::TlsAcceptor>>,
+}
+
+impl Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsAcceptor")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl TlsAcceptor {
+    pub fn new(config: TlsServerConfig) -> UnifiedIOResult<Self> {
+        let server_config = load_server_config(&config)?;
+        Ok(Self {
+            config,
+            inner: Arc::new(RwLock::new(tokio_rustls::TlsAcceptor::from(Arc::new(
+                server_config,
+            )))),
+        })
+    }
+
+    pub fn config(&self) -> &TlsServerConfig {
+        &self.config
+    }
+
+    /// reloads the certificate chain and the private key,
+    /// connections accepted before keep using the old ones
+    pub fn reload(&self) -> UnifiedIOResult<()> {
+        let server_config = load_server_config(&self.config)?;
+        let mut inner = self.inner.write().unwrap();
+        *inner = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+        tracing::info!("tls certificate reloaded: {:?}", self.config);
+        Ok(())
+    }
+
+    pub async fn accept_stream(&self, stream: TcpStream) -> UnifiedIOResult<TlsStream<TcpStream>> {
+        let acceptor = self.inner.read().unwrap().clone();
+        Ok(acceptor.accept(stream).await?)
+    }
+
+    /// performs the handshake and wraps the tls stream as the same TcpIO the plain path yields
+    pub async fn accept(&self, stream: TcpStream) -> UnifiedIOResult<TcpIO> {
+        let local_addr = stream.local_addr()?;
+        let peer_addr = stream.peer_addr()?;
+        let tls_stream = self.accept_stream(stream).await?;
+        Ok(TcpIO::with_stream(
+            Box::new(tls_stream),
+            local_addr,
+            peer_addr,
+        ))
+    }
+
+    fn files_modified_at(&self) -> Option<(SystemTime, SystemTime)> {
+        let cert = std::fs::metadata(&self.config.cert_chain_path)
+            .and_then(|meta| meta.modified())
+            .ok()?;
+        let key = std::fs::metadata(&self.config.private_key_path)
+            .and_then(|meta| meta.modified())
+            .ok()?;
+        Some((cert, key))
+    }
+
+    /// reloads the certificate on SIGHUP or when the files change,
+    /// a failed reload is logged and the previous certificate stays in use
+    pub fn spawn_reloader(&self) -> tokio::task::JoinHandle<()> {
+        let acceptor = self.clone();
+        tokio::spawn(async move {
+            let mut hangup = HangupSignal::new();
+            let mut interval = tokio::time::interval(RELOAD_POLL_INTERVAL);
+            let mut modified_at = acceptor.files_modified_at();
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let current = acceptor.files_modified_at();
+                        if current.is_none() || current == modified_at {
+                            continue;
+                        }
+                        modified_at = current;
+                    }
+                    _ = hangup.recv() => {
+                        tracing::info!("got SIGHUP, reloading tls certificate");
+                        modified_at = acceptor.files_modified_at();
+                    }
+                }
+                if let Err(err) = acceptor.reload() {
+                    tracing::error!(
+                        "reload tls certificate failed, keep using the previous one: {}",
+                        err
+                    );
+                }
+            }
+        })
+    }
+}
+
+struct HangupSignal {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl HangupSignal {
+    fn new() -> Self {
+        Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .inspect_err(|err| tracing::warn!("listen to SIGHUP failed: {}", err))
+                .ok(),
+        }
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.signal.as_mut() {
+            signal.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::{
+        TlsConnector,
+        rustls::{ClientConfig, RootCertStore, pki_types::ServerName},
+    };
+    use tokio_util::bytes::Bytes;
+
+    use super::{TlsAcceptor, TlsServerConfig};
+    use crate::UnifiedIO;
+
+    fn write_self_signed_cert(name: &str) -> (TlsServerConfig, Vec<u8>) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("unified-io-tls-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = TlsServerConfig {
+            cert_chain_path: dir.join("cert.pem"),
+            private_key_path: dir.join("key.pem"),
+        };
+        std::fs::write(&config.cert_chain_path, certified.cert.pem()).unwrap();
+        std::fs::write(&config.private_key_path, certified.key_pair.serialize_pem()).unwrap();
+        (config, certified.cert.der().to_vec())
+    }
+
+    #[tokio::test]
+    async fn bytes_flow_through_tls_io() {
+        let (config, cert_der) = write_self_signed_cert("flow");
+        let acceptor = TlsAcceptor::new(config).unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, peer_addr) = listener.accept().await.unwrap();
+            let mut io = acceptor.accept(stream).await.unwrap();
+            assert_eq!(io.get_peer_addr(), Some(peer_addr));
+            let mut received = Vec::new();
+            while received.len() < 5 {
+                received.extend_from_slice(&io.next().await.unwrap().unwrap());
+            }
+            assert_eq!(received, b"hello");
+            io.send(Bytes::from_static(b"world")).await.unwrap();
+        });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert_der.into()).unwrap();
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut tls = connector
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+        tls.write_all(b"hello").await.unwrap();
+        tls.flush().await.unwrap();
+        let mut buf = [0u8; 5];
+        tls.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"world");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_handshake_does_not_stop_acceptor() {
+        let (config, _) = write_self_signed_cert("handshake");
+        let acceptor = TlsAcceptor::new(config).unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        client.write_all(b"not a tls client hello").await.unwrap();
+        assert!(acceptor.accept(stream).await.is_err());
+
+        // the acceptor is still usable, and the certificate can be reloaded
+        acceptor.reload().unwrap();
+    }
+
+    #[test]
+    fn invalid_certificate_is_rejected() {
+        let (mut config, _) = write_self_signed_cert("invalid");
+        config.private_key_path = config.cert_chain_path.clone();
+        assert!(TlsAcceptor::new(config).is_err());
+    }
+}
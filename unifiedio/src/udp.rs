@@ -3,19 +3,43 @@ use crate::{
     errors::{UnifiedIOError, UnifiedIOResult},
 };
 use futures::{Sink, Stream, ready};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
 use std::{
     io::{self},
-    net::SocketAddr,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     task::Poll,
 };
 use tokio::net::UdpSocket;
 use tokio_util::bytes::Bytes;
 
+/// how a socket sends to and receives from a multicast group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastOptions {
+    /// hops the packets sent to the group live for, 1 keeps them in the local network
+    pub ttl: u32,
+    /// the local interface the group is sent to and joined on, the routing table decides if unspecified
+    pub interface: Ipv4Addr,
+    /// whether the sockets of this host joined to the group receive what is sent to it
+    pub loopback: bool,
+}
+
+impl Default for MulticastOptions {
+    fn default() -> Self {
+        Self {
+            ttl: 1,
+            interface: Ipv4Addr::UNSPECIFIED,
+            loopback: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct UdpIO {
     inner: UdpSocket,
     local_addr: SocketAddr,
     peer_addr: SocketAddr,
+    /// a socket joined to a multicast group is not connected, it sends to the group by address
+    connected: bool,
     pending_send: Option<Bytes>,
 }
 
@@ -27,6 +51,7 @@ impl UdpIO {
                     local_addr: socket.local_addr().unwrap(),
                     peer_addr: socket.peer_addr().unwrap(),
                     inner: socket,
+                    connected: true,
                     pending_send: None,
                 }),
                 Err(err) => Err(UnifiedIOError::Io(err)),
@@ -35,6 +60,18 @@ impl UdpIO {
         }
     }
 
+    /// a socket bound already, e.g., to a port announced before the peer was known
+    pub async fn with_socket(socket: UdpSocket, remote_addr: SocketAddr) -> UnifiedIOResult<Self> {
+        socket.connect(remote_addr).await?;
+        Ok(Self {
+            local_addr: socket.local_addr()?,
+            peer_addr: socket.peer_addr()?,
+            inner: socket,
+            connected: true,
+            pending_send: None,
+        })
+    }
+
     pub async fn new_with_remote_addr(
         mut local_port_start_from: u16,
         remote_addr: SocketAddr,
@@ -56,6 +93,67 @@ impl UdpIO {
             "Failed to bind to any port",
         )))
     }
+
+    /// sends to a multicast group from `local_addr`,
+    /// only packets from the group address itself could be received on it
+    pub async fn new_multicast_sender(
+        local_addr: SocketAddr,
+        group: SocketAddrV4,
+        options: &MulticastOptions,
+    ) -> UnifiedIOResult<Self> {
+        check_multicast(&group)?;
+        let socket = UdpSocket::bind(local_addr).await?;
+        socket.set_multicast_ttl_v4(options.ttl)?;
+        socket.set_multicast_loop_v4(options.loopback)?;
+        if !options.interface.is_unspecified() {
+            SockRef::from(&socket).set_multicast_if_v4(&options.interface)?;
+        }
+        socket.connect(group).await?;
+        Ok(Self {
+            local_addr: socket.local_addr()?,
+            peer_addr: socket.peer_addr()?,
+            inner: socket,
+            connected: true,
+            pending_send: None,
+        })
+    }
+
+    /// receives what is sent to the port of a multicast group, other sockets may join the same group and port.
+    /// what is sent on it goes to the group
+    pub fn join_multicast(
+        group: SocketAddrV4,
+        options: &MulticastOptions,
+    ) -> UnifiedIOResult<Self> {
+        check_multicast(&group)?;
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, group.port())).into())?;
+        socket.join_multicast_v4(group.ip(), &options.interface)?;
+        socket.set_multicast_ttl_v4(options.ttl)?;
+        socket.set_multicast_loop_v4(options.loopback)?;
+        if !options.interface.is_unspecified() {
+            socket.set_multicast_if_v4(&options.interface)?;
+        }
+        socket.set_nonblocking(true)?;
+        let socket = UdpSocket::from_std(socket.into())?;
+        Ok(Self {
+            local_addr: socket.local_addr()?,
+            peer_addr: SocketAddr::V4(group),
+            inner: socket,
+            connected: false,
+            pending_send: None,
+        })
+    }
+}
+
+fn check_multicast(group: &SocketAddrV4) -> UnifiedIOResult<()> {
+    if group.ip().is_multicast() {
+        return Ok(());
+    }
+    Err(UnifiedIOError::Io(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{} is not a multicast address", group),
+    )))
 }
 
 impl UnifiedIO for UdpIO {
@@ -81,7 +179,12 @@ impl Sink<Bytes> for UdpIO {
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Result<(), Self::Error>> {
         if let Some(bytes) = self.pending_send.take() {
-            match self.inner.poll_send(cx, &bytes) {
+            let sent = if self.connected {
+                self.inner.poll_send(cx, &bytes)
+            } else {
+                self.inner.poll_send_to(cx, &bytes, self.peer_addr)
+            };
+            match sent {
                 Poll::Ready(Ok(_len)) => Poll::Ready(Ok(())),
                 Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
                 Poll::Pending => {
@@ -136,3 +239,73 @@ impl Stream for UdpIO {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+        time::Duration,
+    };
+
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::bytes::Bytes;
+
+    use super::{MulticastOptions, UdpIO};
+
+    fn loopback_options() -> MulticastOptions {
+        MulticastOptions {
+            ttl: 1,
+            interface: Ipv4Addr::LOCALHOST,
+            loopback: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn packets_sent_to_a_group_reach_the_joined_sockets() {
+        let group = SocketAddrV4::new(Ipv4Addr::new(239, 255, 42, 1), 46004);
+        let options = loopback_options();
+        let mut first = UdpIO::join_multicast(group, &options).unwrap();
+        let mut second = UdpIO::join_multicast(group, &options).unwrap();
+        let mut sender = UdpIO::new_multicast_sender(
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            group,
+            &options,
+        )
+        .await
+        .unwrap();
+
+        sender.send(Bytes::from_static(b"rtp")).await.unwrap();
+        for receiver in [&mut first, &mut second] {
+            let received = tokio::time::timeout(Duration::from_secs(2), receiver.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert_eq!(received, Bytes::from_static(b"rtp"));
+        }
+
+        // a joined socket sends to the group too
+        first.send(Bytes::from_static(b"rtcp")).await.unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(2), second.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(received, Bytes::from_static(b"rtcp"));
+    }
+
+    #[tokio::test]
+    async fn unicast_addresses_are_not_groups() {
+        let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 46006);
+        assert!(UdpIO::join_multicast(addr, &MulticastOptions::default()).is_err());
+        assert!(
+            UdpIO::new_multicast_sender(
+                SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+                addr,
+                &MulticastOptions::default()
+            )
+            .await
+            .is_err()
+        );
+    }
+}
@@ -1,3 +1,5 @@
+pub mod pool;
+
 use crate::traits::writer::WriteTo;
 use std::fmt::Write;
 
@@ -0,0 +1,266 @@
+//! the buffers the packets and tags are serialized into, pooled per thread by size class.
+//! what is written into a buffer is split off and frozen while the rest of it stays pooled,
+//! and the pool takes the whole allocation back once all the bytes frozen from it are dropped
+
+use std::{
+    cell::RefCell,
+    ops::{Deref, DerefMut},
+};
+
+use tokio_util::bytes::{Bytes, BytesMut};
+
+/// the capacities of the pooled buffers, a buffer larger than all of them is not pooled
+const SIZE_CLASSES: [usize; 4] = [2 * 1024, 16 * 1024, 128 * 1024, 1024 * 1024];
+/// how many free buffers a size class keeps
+const MAX_FREE_BUFFERS: usize = 8;
+
+thread_local! {
+    static POOL: RefCell<[Vec<BytesMut>; SIZE_CLASSES.len()]> = RefCell::new(Default::default());
+}
+
+/// a buffer with room for at least `size` bytes, from the pool of this thread if it has one
+pub fn take(size: usize) -> PooledBuffer {
+    let Some(class) = SIZE_CLASSES.iter().position(|capacity| size <= *capacity) else {
+        return PooledBuffer {
+            buffer: BytesMut::with_capacity(size),
+            class: None,
+        };
+    };
+    let pooled = POOL
+        .try_with(|pool| {
+            let free = &mut pool.borrow_mut()[class];
+            // a buffer the frozen bytes still share is skipped, it is free again once they drop
+            let index = free
+                .iter_mut()
+                .rposition(|buffer| buffer.try_reclaim(size))?;
+            Some(free.remove(index))
+        })
+        .ok()
+        .flatten();
+    PooledBuffer {
+        buffer: pooled.unwrap_or_else(|| BytesMut::with_capacity(SIZE_CLASSES[class])),
+        class: Some(class),
+    }
+}
+
+/// a buffer taken from the pool, it goes back cleared when dropped
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buffer: BytesMut,
+    /// the size class it goes back to, None if it is not pooled
+    class: Option<usize>,
+}
+
+impl PooledBuffer {
+    /// the written bytes, the rest of the buffer goes back to the pool
+    pub fn split_freeze(&mut self) -> Bytes {
+        self.buffer.split().freeze()
+    }
+
+    /// the whole buffer, it is owned by the bytes and does not go back to the pool
+    pub fn freeze(mut self) -> Bytes {
+        self.class = None;
+        std::mem::take(&mut self.buffer).freeze()
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = BytesMut;
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let Some(class) = self.class else {
+            return;
+        };
+        let mut buffer = std::mem::take(&mut self.buffer);
+        // grown past its class by the writes, it would pin more than the class is for
+        if buffer.capacity() > SIZE_CLASSES[class] {
+            return;
+        }
+        buffer.clear();
+        let _ = POOL.try_with(|pool| {
+            let free = &mut pool.borrow_mut()[class];
+            free.push(buffer);
+            // the oldest is the most likely to be still shared by the frozen bytes
+            if free.len() > MAX_FREE_BUFFERS {
+                free.remove(0);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    use tokio_util::bytes::{BufMut, BytesMut};
+
+    use super::{POOL, take};
+
+    /// counts the allocations of each thread, so that the tests running along do not count
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations_of<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = ALLOCATIONS.with(Cell::get);
+        let result = f();
+        (result, ALLOCATIONS.with(Cell::get) - before)
+    }
+
+    #[test]
+    fn buffers_are_cleared_between_uses() {
+        let mut buffer = take(100);
+        buffer.put_slice(b"the previous packet");
+        let allocation = buffer.as_ptr();
+        drop(buffer);
+
+        let buffer = take(100);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.as_ptr(), allocation);
+        assert!(buffer.capacity() >= 100);
+    }
+
+    #[test]
+    fn frozen_bytes_are_kept_while_the_buffer_is_used_again() {
+        let mut buffer = take(100);
+        buffer.put_slice(b"first");
+        let first = buffer.split_freeze();
+        assert!(buffer.is_empty());
+        buffer.put_slice(b"second");
+        let second = buffer.split_freeze();
+        drop(buffer);
+
+        let mut buffer = take(100);
+        assert!(buffer.is_empty());
+        buffer.put_slice(b"third");
+        assert_eq!(&first[..], b"first");
+        assert_eq!(&second[..], b"second");
+        assert_eq!(&buffer[..], b"third");
+    }
+
+    #[test]
+    fn the_allocation_is_reclaimed_once_its_bytes_are_dropped() {
+        let mut buffer = take(1500);
+        let allocation = buffer.as_ptr();
+        buffer.put_bytes(0xab, 1500);
+        let packet = buffer.split_freeze();
+        drop(buffer);
+        drop(packet);
+
+        let buffer = take(1500);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.as_ptr(), allocation);
+    }
+
+    #[test]
+    fn the_allocation_kept_by_the_bytes_is_taken_again_once_they_drop() {
+        let mut buffer = take(1500);
+        let kept = buffer.as_ptr();
+        buffer.put_bytes(0xab, 1500);
+        let packet = buffer.split_freeze();
+        drop(buffer);
+        let mut buffer = take(1500);
+        buffer.put_bytes(0xcd, 1500);
+        let other_packet = buffer.split_freeze();
+        drop(buffer);
+
+        drop(packet);
+        let buffer = take(1500);
+        assert_eq!(buffer.as_ptr(), kept);
+        drop(other_packet);
+    }
+
+    #[test]
+    fn the_allocation_is_left_to_the_bytes_still_kept() {
+        let mut buffer = take(1500);
+        let allocation = buffer.as_ptr();
+        buffer.put_bytes(0xab, 1500);
+        let packet = buffer.split_freeze();
+        drop(buffer);
+
+        // the rest of the buffer has no room for the next packet while the first one is kept
+        let mut buffer = take(1500);
+        assert_ne!(buffer.as_ptr(), allocation);
+        buffer.put_bytes(0xcd, 1500);
+        assert!(packet.iter().all(|byte| *byte == 0xab));
+    }
+
+    #[test]
+    fn frozen_buffers_do_not_go_back_to_the_pool() {
+        let mut buffer = take(100);
+        buffer.put_slice(b"owned");
+        let allocation = buffer.as_ptr();
+        let owned = buffer.freeze();
+
+        let buffer = take(100);
+        assert_ne!(buffer.as_ptr(), allocation);
+        assert_eq!(&owned[..], b"owned");
+    }
+
+    #[test]
+    fn buffers_larger_than_the_size_classes_are_not_pooled() {
+        drop(take(4 * 1024 * 1024));
+        drop(take(100));
+        let free_buffers = POOL.with(|pool| pool.borrow().iter().map(Vec::len).sum::<usize>());
+        assert_eq!(free_buffers, 1);
+    }
+
+    /// serializing packets into pooled buffers and into new ones, the packets dropped after
+    /// being sent as the sinks do
+    #[test]
+    fn pooled_serialization_benchmark() {
+        const PACKETS: usize = 10_000;
+        let payload = [0x5a; 1200];
+
+        let (_, unpooled_allocations) = allocations_of(|| {
+            for _ in 0..PACKETS {
+                let mut buffer = BytesMut::new();
+                buffer.put_slice(&payload);
+                std::hint::black_box(buffer.freeze());
+            }
+        });
+        let (_, pooled_allocations) = allocations_of(|| {
+            for _ in 0..PACKETS {
+                let mut buffer = take(payload.len());
+                buffer.put_slice(&payload);
+                std::hint::black_box(buffer.split_freeze());
+            }
+        });
+        assert!(
+            pooled_allocations * 10 < unpooled_allocations,
+            "{} allocations pooled, {} unpooled",
+            pooled_allocations,
+            unpooled_allocations
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::{error::Error, fmt};
+
+/// the error followed by its sources, the outermost first
+pub fn sources<'a>(
+    err: &'a (dyn Error + 'static),
+) -> impl Iterator<Item = &'a (dyn Error + 'static)> {
+    std::iter::successors(Some(err), |err: &&'a (dyn Error + 'static)| (*err).source())
+}
+
+/// the first error of the chain of type T, the error itself included.
+/// a boxed T is found too, errors holding each other as sources box one of them
+pub fn find_source<'a, T: Error + 'static>(err: &'a (dyn Error + 'static)) -> Option<&'a T> {
+    sources(err).find_map(|err| {
+        err.downcast_ref::<T>()
+            .or_else(|| err.downcast_ref::<Box<T>>().map(|err| err.as_ref()))
+    })
+}
+
+/// displays the whole chain of an error in one line, separated by ": ".
+/// most errors print their source in their own message already, such a source is not repeated
+#[derive(Clone, Copy)]
+pub struct ErrorChain<'a>(pub &'a (dyn Error + 'static));
+
+impl fmt::Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printed = self.0.to_string();
+        f.write_str(&printed)?;
+        for source in sources(self.0).skip(1) {
+            let message = source.to_string();
+            if printed.contains(&message) {
+                continue;
+            }
+            write!(f, ": {}", message)?;
+            printed.push_str(": ");
+            printed.push_str(&message);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+pub trait ErrorChainExt {
+    fn chain(&self) -> ErrorChain<'_>;
+}
+
+impl<E: Error + 'static> ErrorChainExt for E {
+    fn chain(&self) -> ErrorChain<'_> {
+        ErrorChain(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, fmt, io};
+
+    use super::{ErrorChainExt, find_source, sources};
+
+    #[derive(Debug)]
+    struct Outer {
+        inner: io::Error,
+        repeat_source: bool,
+    }
+
+    impl fmt::Display for Outer {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            if self.repeat_source {
+                write!(f, "read failed: {}", self.inner)
+            } else {
+                write!(f, "read failed")
+            }
+        }
+    }
+
+    impl Error for Outer {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.inner)
+        }
+    }
+
+    #[derive(Debug)]
+    struct Boxed(Box<io::Error>);
+
+    impl fmt::Display for Boxed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "boxed")
+        }
+    }
+
+    impl Error for Boxed {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn chain_is_printed_in_one_line() {
+        let err = Outer {
+            inner: io::Error::other("connection reset"),
+            repeat_source: false,
+        };
+        assert_eq!(err.chain().to_string(), "read failed: connection reset");
+        let err = Outer {
+            inner: io::Error::other("connection reset"),
+            repeat_source: true,
+        };
+        assert_eq!(err.chain().to_string(), "read failed: connection reset");
+        assert_eq!(sources(&err).count(), 2);
+    }
+
+    #[test]
+    fn sources_are_found_by_type() {
+        let err = Outer {
+            inner: io::Error::new(io::ErrorKind::UnexpectedEof, "eof"),
+            repeat_source: false,
+        };
+        assert_eq!(
+            find_source::<io::Error>(&err).unwrap().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert!(find_source::<Outer>(&err).is_some());
+        assert!(find_source::<fmt::Error>(&err).is_none());
+
+        let err = Boxed(Box::new(io::Error::other("connection reset")));
+        assert!(find_source::<io::Error>(&err).is_some());
+        assert_eq!(err.chain().to_string(), "boxed: connection reset");
+    }
+}
@@ -0,0 +1,128 @@
+/// puts the decode timestamps of the frames of a constant frame rate stream on the grid of its
+/// nominal frame rate, so the frames are paced evenly though their timestamps are missing or
+/// quantized coarsely, e.g., to milliseconds beating against 29.97
+#[derive(Debug, Clone)]
+pub struct FrameGrid {
+    frame_rate: f64,
+    interval_nano: f64,
+    /// the dts the grid starts at, in nanoseconds
+    origin_nano: Option<u64>,
+    /// the grid index of the latest placed frame
+    index: u64,
+}
+
+impl FrameGrid {
+    /// None if the frame rate is not a positive number
+    pub fn new(frame_rate: f64) -> Option<Self> {
+        if !frame_rate.is_finite() || frame_rate <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            frame_rate,
+            interval_nano: 1e9 / frame_rate,
+            origin_nano: None,
+            index: 0,
+        })
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        self.frame_rate
+    }
+
+    pub fn interval_nano(&self) -> f64 {
+        self.interval_nano
+    }
+
+    fn at(&self, origin_nano: u64, index: u64) -> u64 {
+        origin_nano + (index as f64 * self.interval_nano).round() as u64
+    }
+
+    /// the dts of the next frame on the grid, in nanoseconds, a frame without a dts is a frame
+    /// interval after the previous one. the frames keep their order, a dts going back or more
+    /// than a frame interval behind the grid is a discontinuity and the grid starts over at it
+    pub fn place(&mut self, dts_nano: Option<u64>) -> u64 {
+        let Some(origin_nano) = self.origin_nano else {
+            let dts_nano = dts_nano.unwrap_or_default();
+            self.origin_nano = Some(dts_nano);
+            self.index = 0;
+            return dts_nano;
+        };
+        let Some(dts_nano) = dts_nano else {
+            self.index += 1;
+            return self.at(origin_nano, self.index);
+        };
+        let index = match dts_nano.checked_sub(origin_nano) {
+            Some(offset) => {
+                ((offset as f64 / self.interval_nano).round() as u64).max(self.index + 1)
+            }
+            None => {
+                return self.restart(dts_nano);
+            }
+        };
+        let placed = self.at(origin_nano, index);
+        if placed.abs_diff(dts_nano) as f64 > self.interval_nano {
+            return self.restart(dts_nano);
+        }
+        self.index = index;
+        placed
+    }
+
+    fn restart(&mut self, dts_nano: u64) -> u64 {
+        self.origin_nano = Some(dts_nano);
+        self.index = 0;
+        dts_nano
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameGrid;
+
+    const NTSC_RATE: f64 = 30000.0 / 1001.0;
+
+    /// the ms quantized dts of the frame at the index
+    fn quantized_ms(index: u64, frame_rate: f64) -> u64 {
+        (index as f64 * 1000.0 / frame_rate).round() as u64 * 1_000_000
+    }
+
+    #[test]
+    fn quantized_timestamps_are_put_on_the_grid() {
+        let mut grid = FrameGrid::new(NTSC_RATE).unwrap();
+        let start = 5_000_000_000;
+        let placed: Vec<_> = (0..300)
+            .map(|index| grid.place(Some(start + quantized_ms(index, NTSC_RATE))))
+            .collect();
+        for (index, dts) in placed.iter().enumerate() {
+            let expected = start as f64 + index as f64 * 1e9 / NTSC_RATE;
+            assert!((*dts as f64 - expected).abs() <= 1.0, "{index}: {dts}");
+        }
+    }
+
+    #[test]
+    fn frames_without_timestamps_follow_the_previous_one() {
+        let mut grid = FrameGrid::new(25.0).unwrap();
+        assert_eq!(grid.place(None), 0);
+        assert_eq!(grid.place(None), 40_000_000);
+        // a dropped frame is skipped on the grid
+        assert_eq!(grid.place(Some(120_000_000)), 120_000_000);
+        assert_eq!(grid.place(None), 160_000_000);
+        // frames with the same timestamp keep their order
+        assert_eq!(grid.place(Some(160_000_000)), 200_000_000);
+    }
+
+    #[test]
+    fn discontinuities_start_the_grid_over() {
+        let mut grid = FrameGrid::new(25.0).unwrap();
+        assert_eq!(grid.place(Some(1_000_000_000)), 1_000_000_000);
+        assert_eq!(grid.place(Some(1_041_000_000)), 1_040_000_000);
+        // a gap is frames missing, the grid goes on
+        assert_eq!(grid.place(Some(10_003_000_000)), 10_000_000_000);
+        assert_eq!(grid.place(Some(0)), 0);
+        assert_eq!(grid.place(Some(40_000_000)), 40_000_000);
+        // a timestamp repeated for too long is not pushed along the grid
+        assert_eq!(grid.place(Some(40_000_000)), 80_000_000);
+        assert_eq!(grid.place(Some(40_000_000)), 40_000_000);
+        assert!(FrameGrid::new(0.0).is_none());
+        assert!(FrameGrid::new(f64::NAN).is_none());
+    }
+}
@@ -1,4 +1,8 @@
 pub mod bytes;
+pub mod error_chain;
+pub mod frame_grid;
+pub mod media_url;
+pub mod metrics;
 pub mod random;
 pub mod system;
 pub mod traits;
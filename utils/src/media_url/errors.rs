@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MediaUrlError {
+    #[error("invalid url: {0}")]
+    InvalidUrl(String),
+    #[error("invalid host: {0}")]
+    InvalidHost(String),
+    #[error("invalid port: {0}")]
+    InvalidPort(String),
+    #[error("invalid percent escape: {0}")]
+    InvalidEscape(String),
+    #[error("not utf-8 once decoded: {0}")]
+    InvalidUtf8(String),
+    #[error("control character in: {0}")]
+    InvalidCharacter(String),
+    #[error("invalid path segment: {0}")]
+    InvalidSegment(String),
+    #[error("no app in url: {0}")]
+    MissingApp(String),
+    #[error("no stream in url: {0}")]
+    MissingStream(String),
+}
+
+pub type MediaUrlResult<T> = Result<T, MediaUrlError>;
@@ -0,0 +1,289 @@
+use std::{collections::BTreeMap, fmt};
+
+use errors::{MediaUrlError, MediaUrlResult};
+
+pub mod errors;
+#[cfg(test)]
+mod test;
+
+/// the query param the clients carry the token of the auth in, of all protocols
+pub const TOKEN_KEY: &str = "token";
+
+/// A rtmp, rtsp or http url of a stream, `scheme://host[:port]/app/stream[/rest..][?query]`.
+/// The path segments and the query are percent-decoded once, a double-encoded key keeps one level.
+/// Empty segments are dropped, so trailing and doubled slashes do not change the stream.
+/// Raw spaces and unicode are taken as they are, clients send the keys typed by the users
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaUrl {
+    scheme: String,
+    host: String,
+    port: Option<u16>,
+    app: Option<String>,
+    stream: Option<String>,
+    /// the segments after the stream, e.g., the control of a rtsp track
+    rest: Vec<String>,
+    query: BTreeMap<String, String>,
+}
+
+impl MediaUrl {
+    /// a url with no path, to put the paths of the requests under
+    pub fn new(scheme: &str, host: &str, port: Option<u16>) -> Self {
+        Self {
+            scheme: scheme.to_ascii_lowercase(),
+            host: host.to_owned(),
+            port,
+            app: None,
+            stream: None,
+            rest: Vec::new(),
+            query: BTreeMap::new(),
+        }
+    }
+
+    pub fn parse(url: &str) -> MediaUrlResult<Self> {
+        let (scheme, remaining) = url
+            .split_once("://")
+            .ok_or_else(|| MediaUrlError::InvalidUrl(format!("no scheme: {}", url)))?;
+        if scheme.is_empty()
+            || !scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        {
+            return Err(MediaUrlError::InvalidUrl(format!("bad scheme: {}", url)));
+        }
+        let authority_end = remaining.find(['/', '?', '#']).unwrap_or(remaining.len());
+        let (authority, path_and_query) = remaining.split_at(authority_end);
+        // the user info is never part of the stream
+        let authority = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        let (host, port) = parse_authority(authority)?;
+        Self::new(scheme, host, port).with_path(path_and_query)
+    }
+
+    /// the url with the path and the query of a request, the query params are added to the ones of self
+    pub fn with_path(&self, path_and_query: &str) -> MediaUrlResult<Self> {
+        let (segments, query) = parse_path_and_query(path_and_query)?;
+        let mut segments = segments.into_iter();
+        let mut result = self.clone();
+        result.app = segments.next();
+        result.stream = segments.next();
+        result.rest = segments.collect();
+        result.query.extend(query);
+        Ok(result)
+    }
+
+    /// the url with the stream name a rtmp client publishes or plays in the app of the tcUrl,
+    /// the name is a path relative to the app and comes with a query of its own
+    pub fn with_stream(&self, stream_name: &str) -> MediaUrlResult<Self> {
+        let (segments, query) = parse_path_and_query(stream_name)?;
+        let mut segments = segments.into_iter();
+        let mut result = self.clone();
+        result.stream = segments.next();
+        result.rest = segments.collect();
+        result.query.extend(query);
+        Ok(result)
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// ipv6 hosts are in brackets
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn app(&self) -> Option<&str> {
+        self.app.as_deref()
+    }
+
+    pub fn stream(&self) -> Option<&str> {
+        self.stream.as_deref()
+    }
+
+    pub fn rest(&self) -> &[String] {
+        &self.rest
+    }
+
+    pub fn query(&self) -> &BTreeMap<String, String> {
+        &self.query
+    }
+
+    pub fn query_value(&self, key: &str) -> Option<&str> {
+        self.query.get(key).map(String::as_str)
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.query_value(TOKEN_KEY)
+    }
+
+    /// the decoded app and stream, both of which are needed to tell the stream
+    pub fn app_and_stream(&self) -> MediaUrlResult<(&str, &str)> {
+        match (self.app(), self.stream()) {
+            (Some(app), Some(stream)) => Ok((app, stream)),
+            (None, _) => Err(MediaUrlError::MissingApp(self.to_string())),
+            (Some(_), None) => Err(MediaUrlError::MissingStream(self.to_string())),
+        }
+    }
+
+    /// the encoded path, `/app/stream/rest..`
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in self.app.iter().chain(self.stream.iter()).chain(&self.rest) {
+            path.push('/');
+            path.push_str(&percent_encode(segment));
+        }
+        path
+    }
+}
+
+/// the canonical form, in which the segments and the query are encoded the same way
+/// whatever the client sent, the query params are sorted by the key
+impl fmt::Display for MediaUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        f.write_str(&self.path())?;
+        for (i, (key, value)) in self.query.iter().enumerate() {
+            let separator = if i == 0 { '?' } else { '&' };
+            write!(
+                f,
+                "{}{}={}",
+                separator,
+                percent_encode(key),
+                percent_encode(value)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_authority(authority: &str) -> MediaUrlResult<(&str, Option<u16>)> {
+    let (host, port) = if let Some(ipv6) = authority.strip_prefix('[') {
+        let end = ipv6
+            .find(']')
+            .ok_or_else(|| MediaUrlError::InvalidHost(authority.to_owned()))?;
+        let (address, after) = ipv6.split_at(end);
+        if address.is_empty()
+            || !address
+                .chars()
+                .all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.')
+        {
+            return Err(MediaUrlError::InvalidHost(authority.to_owned()));
+        }
+        let port = match &after[1..] {
+            "" => None,
+            port => Some(
+                port.strip_prefix(':')
+                    .ok_or_else(|| MediaUrlError::InvalidHost(authority.to_owned()))?,
+            ),
+        };
+        (&authority[..end + 2], port)
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty()
+        || (!host.starts_with('[')
+            && !host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_')))
+    {
+        return Err(MediaUrlError::InvalidHost(authority.to_owned()));
+    }
+    let port = match port {
+        // `host:` has the default port
+        None | Some("") => None,
+        Some(port) => Some(
+            port.parse::<u16>()
+                .map_err(|_| MediaUrlError::InvalidPort(authority.to_owned()))?,
+        ),
+    };
+    Ok((host, port))
+}
+
+type PathAndQuery = (Vec<String>, Vec<(String, String)>);
+
+fn parse_path_and_query(path_and_query: &str) -> MediaUrlResult<PathAndQuery> {
+    // the fragment never reaches the server
+    let path_and_query = path_and_query
+        .split_once('#')
+        .map_or(path_and_query, |(before, _)| before);
+    let (path, query) = path_and_query
+        .split_once('?')
+        .unwrap_or((path_and_query, ""));
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            let decoded = percent_decode(segment, false)?;
+            // the decoded segment is one segment still
+            if decoded.contains('/') || decoded == "." || decoded == ".." {
+                return Err(MediaUrlError::InvalidSegment(segment.to_owned()));
+            }
+            Ok(decoded)
+        })
+        .collect::<MediaUrlResult<Vec<_>>>()?;
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(key, true)?, percent_decode(value, true)?))
+        })
+        .filter(|pair| !matches!(pair, Ok((key, _)) if key.is_empty()))
+        .collect::<MediaUrlResult<Vec<_>>>()?;
+    Ok((segments, query))
+}
+
+/// decodes the `%XX` escapes once, `+` is a space in the query only.
+/// the result is utf-8 and has no control characters
+pub fn percent_decode(encoded: &str, plus_as_space: bool) -> MediaUrlResult<String> {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let escape = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| MediaUrlError::InvalidEscape(encoded.to_owned()))?;
+                decoded.push(escape);
+                i += 3;
+                continue;
+            }
+            b'+' if plus_as_space => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    let decoded =
+        String::from_utf8(decoded).map_err(|_| MediaUrlError::InvalidUtf8(encoded.to_owned()))?;
+    if decoded.chars().any(char::is_control) {
+        return Err(MediaUrlError::InvalidCharacter(encoded.to_owned()));
+    }
+    Ok(decoded)
+}
+
+/// encodes all but the unreserved characters of RFC 3986, @see: 2.3
+pub fn percent_encode(decoded: &str) -> String {
+    let mut encoded = String::with_capacity(decoded.len());
+    for byte in decoded.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
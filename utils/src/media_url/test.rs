@@ -0,0 +1,177 @@
+#[cfg(test)]
+mod tests {
+    use crate::media_url::{MediaUrl, errors::MediaUrlError, percent_decode, percent_encode};
+
+    #[test]
+    fn test_parse_stream_url() {
+        let url = MediaUrl::parse("rtmp://example.com:1935/live/my%20key?token=abc&x=1").unwrap();
+        assert_eq!(url.scheme(), "rtmp");
+        assert_eq!(url.host(), "example.com");
+        assert_eq!(url.port(), Some(1935));
+        assert_eq!(url.app_and_stream().unwrap(), ("live", "my key"));
+        assert_eq!(url.token(), Some("abc"));
+        assert_eq!(url.query_value("x"), Some("1"));
+        assert_eq!(
+            url.to_string(),
+            "rtmp://example.com:1935/live/my%20key?token=abc&x=1"
+        );
+
+        // the rtsp track control follows the stream
+        let url = MediaUrl::parse("RTSP://10.0.0.1/live/test/trackID=1").unwrap();
+        assert_eq!(url.scheme(), "rtsp");
+        assert_eq!(url.port(), None);
+        assert_eq!(url.app_and_stream().unwrap(), ("live", "test"));
+        assert_eq!(url.rest(), ["trackID=1"]);
+        assert_eq!(url.path(), "/live/test/trackID%3D1");
+    }
+
+    #[test]
+    fn test_ipv6_host() {
+        let url = MediaUrl::parse("rtsp://[::1]:8554/live/test").unwrap();
+        assert_eq!(url.host(), "[::1]");
+        assert_eq!(url.port(), Some(8554));
+        assert_eq!(url.app_and_stream().unwrap(), ("live", "test"));
+        assert_eq!(url.to_string(), "rtsp://[::1]:8554/live/test");
+
+        let url = MediaUrl::parse("http://[2001:db8::7]/live/test.flv").unwrap();
+        assert_eq!(url.host(), "[2001:db8::7]");
+        assert_eq!(url.port(), None);
+
+        for bad in [
+            "rtsp://[::1/live/test",
+            "rtsp://[::1]8554/live/test",
+            "rtsp://[]/live/test",
+            "rtsp://[::1]:port/live/test",
+        ] {
+            assert!(MediaUrl::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_missing_app_and_stream() {
+        let url = MediaUrl::parse("rtmp://example.com").unwrap();
+        assert_eq!(url.app(), None);
+        assert!(matches!(
+            url.app_and_stream(),
+            Err(MediaUrlError::MissingApp(_))
+        ));
+
+        // the tcUrl has the app only
+        let url = MediaUrl::parse("rtmp://example.com/live").unwrap();
+        assert_eq!(url.app(), Some("live"));
+        assert!(matches!(
+            url.app_and_stream(),
+            Err(MediaUrlError::MissingStream(_))
+        ));
+
+        assert!(MediaUrl::parse("example.com/live/test").is_err());
+        assert!(MediaUrl::parse("rtmp:///live/test").is_err());
+    }
+
+    #[test]
+    fn test_trailing_and_doubled_slashes() {
+        let canonical = MediaUrl::parse("rtsp://host/live/test").unwrap();
+        for url in [
+            "rtsp://host/live/test/",
+            "rtsp://host//live//test",
+            "rtsp://host/live/test/?",
+        ] {
+            assert_eq!(MediaUrl::parse(url).unwrap(), canonical, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_decoded_once() {
+        // the client encoded "my%20key", the key keeps the escape
+        let url = MediaUrl::parse("rtmp://host/live/my%2520key").unwrap();
+        assert_eq!(url.stream(), Some("my%20key"));
+        assert_eq!(url.to_string(), "rtmp://host/live/my%2520key");
+        assert_eq!(MediaUrl::parse(&url.to_string()).unwrap(), url);
+
+        // raw spaces and unicode, as typed by the users
+        let url = MediaUrl::parse("rtmp://host/live/my key 直播").unwrap();
+        assert_eq!(url.stream(), Some("my key 直播"));
+        assert_eq!(
+            url.to_string(),
+            "rtmp://host/live/my%20key%20%E7%9B%B4%E6%92%AD"
+        );
+        assert_eq!(MediaUrl::parse(&url.to_string()).unwrap(), url);
+
+        // a plus is a space in the query only
+        let url = MediaUrl::parse("rtmp://host/live/a+b?name=a+b%2Bc").unwrap();
+        assert_eq!(url.stream(), Some("a+b"));
+        assert_eq!(url.query_value("name"), Some("a b+c"));
+    }
+
+    #[test]
+    fn test_invalid_characters() {
+        for bad in [
+            // an encoded slash would make another segment
+            "rtmp://host/live/a%2Fb",
+            "rtmp://host/live/..",
+            "rtmp://host/live/%2e%2e",
+            "rtmp://host/live/a%0Ab",
+            "rtmp://host/live/a%zzb",
+            "rtmp://host/live/a%2",
+            "rtmp://host/live/%FF",
+            "rtmp://host/live/test?token=%00",
+            "rtmp://ho st/live/test",
+        ] {
+            assert!(MediaUrl::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_empty_query_values() {
+        let url = MediaUrl::parse("rtsp://host/live/test?token=&flag&=orphan&&x=1").unwrap();
+        assert_eq!(url.token(), Some(""));
+        assert_eq!(url.query_value("flag"), Some(""));
+        assert_eq!(url.query().len(), 3);
+        assert_eq!(url.to_string(), "rtsp://host/live/test?flag=&token=&x=1");
+    }
+
+    #[test]
+    fn test_with_stream() {
+        // the tcUrl, then the name of the publish
+        let tc_url = MediaUrl::parse("rtmp://host/live?vhost=a").unwrap();
+        let url = tc_url.with_stream("my key?token=abc&x=1").unwrap();
+        assert_eq!(url.app_and_stream().unwrap(), ("live", "my key"));
+        assert_eq!(url.token(), Some("abc"));
+        assert_eq!(url.query_value("vhost"), Some("a"));
+        assert_eq!(
+            url.to_string(),
+            "rtmp://host/live/my%20key?token=abc&vhost=a&x=1"
+        );
+
+        // the same stream is told by all protocols
+        let rtsp = MediaUrl::parse("rtsp://host:8554/live/my%20key?token=abc").unwrap();
+        let http = MediaUrl::new("http", "host", Some(8080))
+            .with_path("/live/my%20key?token=abc")
+            .unwrap();
+        assert_eq!(
+            rtsp.app_and_stream().unwrap(),
+            url.app_and_stream().unwrap()
+        );
+        assert_eq!(
+            http.app_and_stream().unwrap(),
+            url.app_and_stream().unwrap()
+        );
+        assert_eq!(http.token(), url.token());
+
+        assert!(tc_url.with_stream("").unwrap().stream().is_none());
+        assert!(tc_url.with_stream("?token=abc").unwrap().stream().is_none());
+    }
+
+    #[test]
+    fn test_percent_encode_round_trip() {
+        for decoded in ["plain-key_1.0~", "a b/c?d&e=f%g", "直播", ""] {
+            let encoded = percent_encode(decoded);
+            assert!(
+                encoded
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"-._~%".contains(&b))
+            );
+            assert_eq!(percent_decode(&encoded, false).unwrap(), decoded);
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! a minimal prometheus registry, encoded in the text exposition format.
+//! the handles of a series are updated with atomics only,
+//! the lock of a family is taken when a series is looked up, so callers keep the handles they use
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    sync::{
+        Arc, LazyLock, Mutex, RwLock,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+    },
+};
+
+/// a family keeps at most this many series, the label values of the others become `OVERFLOW_LABEL_VALUE`
+pub const MAX_SERIES_PER_FAMILY: usize = 1000;
+pub const OVERFLOW_LABEL_VALUE: &str = "_other";
+
+/// upper bounds in seconds, for durations from a millisecond to ten seconds
+pub const DEFAULT_SECONDS_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static GLOBAL_REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::default);
+
+/// the registry served by the metrics endpoint
+pub fn global() -> &'static Registry {
+    &GLOBAL_REGISTRY
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    #[inline]
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    #[inline]
+    pub fn inc_by(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicI64>);
+
+impl Gauge {
+    #[inline]
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    #[inline]
+    pub fn dec(&self) {
+        self.add(-1);
+    }
+
+    #[inline]
+    pub fn add(&self, value: i64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// the gauge is increased until the guard is dropped, e.g. for the live connections
+    pub fn guard(&self) -> GaugeGuard {
+        self.inc();
+        GaugeGuard(self.clone())
+    }
+}
+
+#[derive(Debug)]
+pub struct GaugeGuard(Gauge);
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+#[derive(Debug)]
+struct HistogramCore {
+    bounds: &'static [f64],
+    /// not cumulative, the last one is for the values above all the bounds
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    /// bits of the f64 sum
+    sum: AtomicU64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Histogram(Arc<HistogramCore>);
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        debug_assert!(bounds.windows(2).all(|w| w[0] < w[1]));
+        Self(Arc::new(HistogramCore {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0f64.to_bits()),
+        }))
+    }
+
+    pub fn observe(&self, value: f64) {
+        let bucket = self.0.bounds.partition_point(|bound| *bound < value);
+        self.0.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.0.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .0
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+                Some((f64::from_bits(sum) + value).to_bits())
+            });
+    }
+
+    pub fn count(&self) -> u64 {
+        self.0.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.0.sum.load(Ordering::Relaxed))
+    }
+}
+
+pub trait Metric: Clone + Send + Sync + 'static {
+    const TYPE: &'static str;
+    fn encode(&self, name: &str, labels: &str, out: &mut String) -> fmt::Result;
+}
+
+impl Metric for Counter {
+    const TYPE: &'static str = "counter";
+    fn encode(&self, name: &str, labels: &str, out: &mut String) -> fmt::Result {
+        writeln!(out, "{}{} {}", name, braced(labels), self.get())
+    }
+}
+
+impl Metric for Gauge {
+    const TYPE: &'static str = "gauge";
+    fn encode(&self, name: &str, labels: &str, out: &mut String) -> fmt::Result {
+        writeln!(out, "{}{} {}", name, braced(labels), self.get())
+    }
+}
+
+impl Metric for Histogram {
+    const TYPE: &'static str = "histogram";
+    fn encode(&self, name: &str, labels: &str, out: &mut String) -> fmt::Result {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (index, bucket) in self.0.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = self
+                .0
+                .bounds
+                .get(index)
+                .map_or_else(|| "+Inf".to_owned(), |bound| bound.to_string());
+            writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, le, cumulative
+            )?;
+        }
+        writeln!(out, "{}_sum{} {}", name, braced(labels), self.sum())?;
+        writeln!(out, "{}_count{} {}", name, braced(labels), self.count())
+    }
+}
+
+fn braced(labels: &str) -> String {
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels)
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+struct FamilyCore<M> {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    new_metric: Box<dyn Fn() -> M + Send + Sync>,
+    /// ordered by the label values, so the encoded series are stable
+    series: RwLock<BTreeMap<Vec<String>, M>>,
+}
+
+/// the series of a metric, one for each combination of label values
+pub struct Family<M>(Arc<FamilyCore<M>>);
+
+impl<M> Clone for Family<M> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<M: Metric> Family<M> {
+    /// the series of `label_values`, in the order of the label names of the family
+    pub fn with_labels(&self, label_values: &[&str]) -> M {
+        debug_assert_eq!(label_values.len(), self.0.label_names.len());
+        let key: Vec<String> = label_values.iter().map(|value| value.to_string()).collect();
+        if let Some(metric) = self.0.series.read().unwrap().get(&key) {
+            return metric.clone();
+        }
+        let mut series = self.0.series.write().unwrap();
+        let key = if series.len() < MAX_SERIES_PER_FAMILY || series.contains_key(&key) {
+            key
+        } else {
+            vec![OVERFLOW_LABEL_VALUE.to_owned(); key.len()]
+        };
+        series
+            .entry(key)
+            .or_insert_with(|| (self.0.new_metric)())
+            .clone()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name
+    }
+}
+
+trait EncodeFamily: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn encode(&self, out: &mut String) -> fmt::Result;
+}
+
+impl<M: Metric> EncodeFamily for FamilyCore<M> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn encode(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "# HELP {} {}", self.name, self.help)?;
+        writeln!(out, "# TYPE {} {}", self.name, M::TYPE)?;
+        for (values, metric) in self.series.read().unwrap().iter() {
+            let labels = self
+                .label_names
+                .iter()
+                .zip(values)
+                .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            metric.encode(self.name, &labels, out)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Registry {
+    families: Mutex<Vec<Arc<dyn EncodeFamily>>>,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("families", &self.families.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl Registry {
+    fn register<M: Metric>(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+        new_metric: Box<dyn Fn() -> M + Send + Sync>,
+    ) -> Family<M> {
+        let core = Arc::new(FamilyCore {
+            name,
+            help,
+            label_names,
+            new_metric,
+            series: Default::default(),
+        });
+        let mut families = self.families.lock().unwrap();
+        debug_assert!(
+            families.iter().all(|family| family.name() != name),
+            "metric {} is registered twice",
+            name
+        );
+        families.push(core.clone());
+        Family(core)
+    }
+
+    pub fn counter_family(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Family<Counter> {
+        self.register(name, help, label_names, Box::new(Counter::default))
+    }
+
+    pub fn gauge_family(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Family<Gauge> {
+        self.register(name, help, label_names, Box::new(Gauge::default))
+    }
+
+    pub fn histogram_family(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+        bounds: &'static [f64],
+    ) -> Family<Histogram> {
+        self.register(
+            name,
+            help,
+            label_names,
+            Box::new(move || Histogram::new(bounds)),
+        )
+    }
+
+    pub fn counter(&self, name: &'static str, help: &'static str) -> Counter {
+        self.counter_family(name, help, &[]).with_labels(&[])
+    }
+
+    pub fn gauge(&self, name: &'static str, help: &'static str) -> Gauge {
+        self.gauge_family(name, help, &[]).with_labels(&[])
+    }
+
+    pub fn histogram(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        bounds: &'static [f64],
+    ) -> Histogram {
+        self.histogram_family(name, help, &[], bounds)
+            .with_labels(&[])
+    }
+
+    /// all the families in the text exposition format, ordered by name
+    pub fn encode(&self) -> String {
+        let mut families = self.families.lock().unwrap().clone();
+        families.sort_by_key(|family| family.name());
+        let mut out = String::new();
+        for family in families {
+            // writing to a string never fails
+            let _ = family.encode(&mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_SECONDS_BUCKETS, MAX_SERIES_PER_FAMILY, OVERFLOW_LABEL_VALUE, Registry};
+
+    #[test]
+    fn encode_text_exposition() {
+        let registry = Registry::default();
+        let connections = registry.counter_family(
+            "test_connections_total",
+            "accepted connections",
+            &["server"],
+        );
+        connections.with_labels(&["rtmp"]).inc_by(3);
+        connections.with_labels(&["rtsp"]).inc();
+        // the same series is handed out again
+        connections.with_labels(&["rtmp"]).inc();
+        let active = registry.gauge("test_active", "active things");
+        {
+            let _guard = active.guard();
+            assert_eq!(active.get(), 1);
+        }
+        active.add(2);
+        let rtt = registry.histogram("test_rtt_seconds", "round trip time", &[0.1, 1.0]);
+        rtt.observe(0.0625);
+        rtt.observe(0.5);
+        rtt.observe(3.0);
+
+        assert_eq!(
+            registry.encode(),
+            "# HELP test_active active things\n\
+# TYPE test_active gauge\n\
+test_active 2\n\
+# HELP test_connections_total accepted connections\n\
+# TYPE test_connections_total counter\n\
+test_connections_total{server=\"rtmp\"} 4\n\
+test_connections_total{server=\"rtsp\"} 1\n\
+# HELP test_rtt_seconds round trip time\n\
+# TYPE test_rtt_seconds histogram\n\
+test_rtt_seconds_bucket{le=\"0.1\"} 1\n\
+test_rtt_seconds_bucket{le=\"1\"} 2\n\
+test_rtt_seconds_bucket{le=\"+Inf\"} 3\n\
+test_rtt_seconds_sum 3.5625\n\
+test_rtt_seconds_count 3\n"
+        );
+    }
+
+    #[test]
+    fn label_values_are_escaped_and_bounded() {
+        let registry = Registry::default();
+        let frames = registry.counter_family("test_frames_total", "frames", &["stream"]);
+        frames.with_labels(&["live/\"a\"\\b"]).inc();
+        assert!(
+            registry
+                .encode()
+                .contains("test_frames_total{stream=\"live/\\\"a\\\"\\\\b\"} 1\n")
+        );
+
+        for index in 0..MAX_SERIES_PER_FAMILY + 10 {
+            frames.with_labels(&[&format!("live/{}", index)]).inc();
+        }
+        let encoded = registry.encode();
+        assert_eq!(
+            encoded
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .count(),
+            MAX_SERIES_PER_FAMILY + 1
+        );
+        assert!(encoded.contains(&format!(
+            "test_frames_total{{stream=\"{}\"}} 11\n",
+            OVERFLOW_LABEL_VALUE
+        )));
+    }
+
+    #[test]
+    fn histogram_labels_come_before_le() {
+        let registry = Registry::default();
+        let latency = registry.histogram_family(
+            "test_latency_seconds",
+            "latency",
+            &["server"],
+            DEFAULT_SECONDS_BUCKETS,
+        );
+        latency.with_labels(&["rtsp"]).observe(0.002);
+        let encoded = registry.encode();
+        assert!(encoded.contains("test_latency_seconds_bucket{server=\"rtsp\",le=\"0.001\"} 0\n"));
+        assert!(encoded.contains("test_latency_seconds_bucket{server=\"rtsp\",le=\"0.0025\"} 1\n"));
+        assert!(encoded.contains("test_latency_seconds_count{server=\"rtsp\"} 1\n"));
+    }
+}